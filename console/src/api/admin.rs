@@ -0,0 +1,28 @@
+use axum::extract::State;
+
+use crate::{
+    application::{
+        HttpState,
+        collaborator::ConsoleAccountAuth,
+        response::{ApiResult, PaginatedResponse},
+        validation::ValidatedQuery,
+    },
+    core::{
+        dto::query::deployment::GlobalSearchQueryParams,
+        models::GlobalSearchResult,
+        queries::{GlobalSearchQuery, Query},
+    },
+};
+
+/// Projects and deployments across the platform matching a name, hostname,
+/// publishable key or custom hostname id, deleted ones included. Only
+/// platform operators get this far.
+pub async fn global_search(
+    State(app_state): State<HttpState>,
+    _operator: ConsoleAccountAuth,
+    ValidatedQuery(params): ValidatedQuery<GlobalSearchQueryParams>,
+) -> ApiResult<PaginatedResponse<GlobalSearchResult>> {
+    let results = GlobalSearchQuery::new(params.q).execute(&app_state).await?;
+
+    Ok(PaginatedResponse::from(results).into())
+}
@@ -0,0 +1,52 @@
+//! Inbound calls to workflow webhook triggers. The secret in the URL is the
+//! only credential, so the callers are whatever systems the customer gave
+//! the URL to.
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, State},
+    routing::post,
+};
+use serde_json::{Value, json};
+
+use crate::{
+    application::{HttpState, response::ApiResult},
+    core::{
+        commands::{Command, FireWebhookTriggerCommand},
+        error::AppError,
+        models::WorkflowExecution,
+    },
+};
+
+pub fn ai_trigger_routes() -> Router<HttpState> {
+    Router::new().route("/ai/triggers/{token}", post(fire_webhook_trigger))
+}
+
+/// Starts a run of the trigger's workflow. The body, if there is one, has
+/// to be a JSON object; it becomes the run's trigger data.
+pub async fn fire_webhook_trigger(
+    State(app_state): State<HttpState>,
+    Path(token): Path<String>,
+    body: Bytes,
+) -> ApiResult<WorkflowExecution> {
+    let payload = if body.iter().all(u8::is_ascii_whitespace) {
+        json!({})
+    } else {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(payload @ Value::Object(_)) => payload,
+            _ => {
+                return Err(AppError::BadRequest(
+                    "The request body must be a JSON object".to_string(),
+                )
+                .into());
+            }
+        }
+    };
+
+    FireWebhookTriggerCommand::new(token, payload)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
@@ -0,0 +1,65 @@
+//! Endpoints for frontend SDKs. They are called from browsers with nothing
+//! but the deployment's publishable key, so they only ever return what is
+//! safe to publish.
+
+use axum::{
+    Router,
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+
+use crate::{
+    application::{
+        HttpState,
+        response::{ApiErrorResponse, ApiSuccess},
+    },
+    core::{
+        dto::query::ClientConfigQueryParams,
+        queries::{GetClientConfigQuery, Query as _},
+    },
+};
+
+pub fn client_routes() -> Router<HttpState> {
+    Router::new().route("/v1/client/config", get(get_client_config))
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        })
+}
+
+/// The deployment's public configuration. The ETag changes whenever any of
+/// the settings in it do, so SDKs can revalidate with `If-None-Match` and
+/// get a 304 until then.
+pub async fn get_client_config(
+    State(app_state): State<HttpState>,
+    Query(params): Query<ClientConfigQueryParams>,
+    headers: HeaderMap,
+) -> Result<Response, ApiErrorResponse> {
+    let config = GetClientConfigQuery::new(params.publishable_key)
+        .execute(&app_state)
+        .await?;
+
+    let etag = format!("\"{:x}\"", config.updated_at.timestamp_micros());
+    let cache_headers = [
+        (
+            header::ETAG,
+            HeaderValue::from_str(&etag).expect("hex digits in quotes are a valid header value"),
+        ),
+        (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+    ];
+
+    if if_none_match(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    Ok((cache_headers, ApiSuccess::from(config)).into_response())
+}
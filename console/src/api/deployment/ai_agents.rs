@@ -1,53 +1,61 @@
-use axum::extract::{Json, Path, Query, State};
+use axum::extract::{Path, Query, State};
+use chrono::Utc;
 
 use crate::{
     application::{
         HttpState,
         response::{ApiResult, PaginatedResponse},
+        validation::{Validated, ValidatedQuery},
     },
     core::{
-        commands::{Command, CreateAiAgentCommand, DeleteAiAgentCommand, UpdateAiAgentCommand},
+        commands::{
+            AppendAgentSessionMessageCommand, AttachAgentKnowledgeBaseCommand,
+            CloseAgentSessionCommand, Command, CreateAgentSessionCommand, CreateAiAgentCommand,
+            DeleteAiAgentCommand, DetachAgentKnowledgeBaseCommand, UpdateAiAgentBudgetCommand,
+            UpdateAiAgentCommand, UpdateDeploymentAiBudgetCommand,
+        },
         dto::{
-            json::deployment::{CreateAgentRequest, UpdateAgentRequest},
-            query::deployment::GetAgentsQuery,
+            json::deployment::{
+                AppendAgentSessionMessageRequest, AttachAgentKnowledgeBaseRequest,
+                CreateAgentRequest, CreateAgentSessionRequest, RetrieveAgentContextRequest,
+                UpdateAgentRequest, UpdateAiBudgetRequest,
+            },
+            query::{DateRangeParams, Pagination, UsageQueryParams, deployment::GetAgentsQuery},
+        },
+        error::AppError,
+        models::{
+            AgentContext, AiAgent, AiAgentKnowledgeBase, AiAgentSession, AiAgentSessionMessage,
+            AiAgentSessionTranscript, AiAgentSessionWithStats, AiAgentWithDetails, AiBudget,
+            AiUsageReport, BillingPeriod,
+        },
+        queries::{
+            GetAgentSessionTranscriptQuery, GetAgentUsageQuery, GetAiAgentByIdQuery,
+            GetAiAgentsQuery, ListAgentKnowledgeBasesQuery, ListAgentSessionsQuery,
+            Query as QueryTrait, RetrieveAgentContextQuery,
         },
-        models::{AiAgent, AiAgentWithDetails},
-        queries::{GetAiAgentByIdQuery, GetAiAgentsQuery, Query as QueryTrait},
     },
 };
 
 pub async fn get_ai_agents(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
     Query(query): Query<GetAgentsQuery>,
 ) -> ApiResult<PaginatedResponse<AiAgentWithDetails>> {
-    let limit = query.limit.unwrap_or(50) as u32;
-
     let agents = GetAiAgentsQuery::new(deployment_id)
-        .with_limit(Some(limit + 1))
-        .with_offset(query.offset.map(|o| o as u32))
+        .with_limit(Some(pagination.fetch_limit() as u32))
+        .with_offset(Some(pagination.offset() as u32))
         .with_search(query.search)
         .execute(&app_state)
         .await?;
 
-    let has_more = agents.len() > limit as usize;
-    let agents = if has_more {
-        agents[..limit as usize].to_vec()
-    } else {
-        agents
-    };
-
-    Ok(PaginatedResponse {
-        data: agents,
-        has_more,
-    }
-    .into())
+    Ok(PaginatedResponse::page(agents, &pagination).into())
 }
 
 pub async fn create_ai_agent(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(request): Json<CreateAgentRequest>,
+    Validated(request): Validated<CreateAgentRequest>,
 ) -> ApiResult<AiAgent> {
     let configuration = request.configuration.unwrap_or(serde_json::json!({}));
 
@@ -77,7 +85,7 @@ pub async fn get_ai_agent_by_id(
 pub async fn update_ai_agent(
     State(app_state): State<HttpState>,
     Path((deployment_id, agent_id)): Path<(i64, i64)>,
-    Json(request): Json<UpdateAgentRequest>,
+    Validated(request): Validated<UpdateAgentRequest>,
 ) -> ApiResult<AiAgent> {
     let mut command = UpdateAiAgentCommand::new(deployment_id, agent_id);
 
@@ -108,3 +116,207 @@ pub async fn delete_ai_agent(
         .map(Into::into)
         .map_err(Into::into)
 }
+
+pub async fn get_agent_knowledge_bases(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id)): Path<(i64, i64)>,
+) -> ApiResult<Vec<AiAgentKnowledgeBase>> {
+    ListAgentKnowledgeBasesQuery::new(deployment_id, agent_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn attach_agent_knowledge_base(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id)): Path<(i64, i64)>,
+    Validated(request): Validated<AttachAgentKnowledgeBaseRequest>,
+) -> ApiResult<AiAgentKnowledgeBase> {
+    let mut command =
+        AttachAgentKnowledgeBaseCommand::new(deployment_id, agent_id, request.knowledge_base_id);
+
+    if let Some(top_k) = request.top_k {
+        command = command.with_top_k(top_k);
+    }
+    if let Some(score_threshold) = request.score_threshold {
+        command = command.with_score_threshold(score_threshold);
+    }
+    if let Some(include_citations) = request.include_citations {
+        command = command.with_include_citations(include_citations);
+    }
+
+    command
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn detach_agent_knowledge_base(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id, knowledge_base_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<()> {
+    DetachAgentKnowledgeBaseCommand::new(deployment_id, agent_id, knowledge_base_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn retrieve_agent_context(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id)): Path<(i64, i64)>,
+    Validated(request): Validated<RetrieveAgentContextRequest>,
+) -> ApiResult<AgentContext> {
+    let mut query = RetrieveAgentContextQuery::new(deployment_id, agent_id, request.query);
+
+    if let Some(limit) = request.limit {
+        query = query.with_limit(limit as usize);
+    }
+
+    query
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_agent_sessions(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    ValidatedQuery(range): ValidatedQuery<DateRangeParams>,
+) -> ApiResult<PaginatedResponse<AiAgentSessionWithStats>> {
+    let sessions = ListAgentSessionsQuery::new(deployment_id, agent_id)
+        .with_limit(Some(pagination.fetch_limit() as u32))
+        .with_offset(Some(pagination.offset() as u32))
+        .with_started_after(range.from)
+        .with_started_before(range.to)
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(sessions, &pagination).into())
+}
+
+pub async fn create_agent_session(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id)): Path<(i64, i64)>,
+    Validated(request): Validated<CreateAgentSessionRequest>,
+) -> ApiResult<AiAgentSession> {
+    let mut command = CreateAgentSessionCommand::new(deployment_id, agent_id);
+
+    if let Some(metadata) = request.metadata {
+        command = command.with_metadata(metadata);
+    }
+
+    command
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_agent_session_transcript(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id, session_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<AiAgentSessionTranscript> {
+    GetAgentSessionTranscriptQuery::new(deployment_id, agent_id, session_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn append_agent_session_message(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id, session_id)): Path<(i64, i64, i64)>,
+    Validated(request): Validated<AppendAgentSessionMessageRequest>,
+) -> ApiResult<AiAgentSessionMessage> {
+    let mut command = AppendAgentSessionMessageCommand::new(
+        deployment_id,
+        agent_id,
+        session_id,
+        request.role,
+        request.content,
+    )
+    .with_token_counts(
+        request.prompt_tokens.unwrap_or(0),
+        request.completion_tokens.unwrap_or(0),
+    )
+    .with_spend_micros(request.spend_micros.unwrap_or(0));
+
+    if let Some(tool_calls) = request.tool_calls {
+        command = command.with_tool_calls(tool_calls);
+    }
+
+    command
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn close_agent_session(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id, session_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<AiAgentSession> {
+    CloseAgentSessionCommand::new(deployment_id, agent_id, session_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_ai_usage(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Query(query_params): Query<UsageQueryParams>,
+) -> ApiResult<AiUsageReport> {
+    let period = match query_params.month.as_deref() {
+        Some(month) => BillingPeriod::parse(month).ok_or_else(|| {
+            AppError::BadRequest("month must be formatted as YYYY-MM".to_string())
+        })?,
+        None => BillingPeriod::containing(Utc::now()),
+    };
+
+    GetAgentUsageQuery::new(deployment_id, period)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_deployment_ai_budget(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<UpdateAiBudgetRequest>,
+) -> ApiResult<AiBudget> {
+    let budget = AiBudget {
+        monthly_token_limit: request.monthly_token_limit,
+        monthly_spend_limit_micros: request.monthly_spend_limit_micros,
+    };
+
+    UpdateDeploymentAiBudgetCommand::new(deployment_id, budget)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_ai_agent_budget(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, agent_id)): Path<(i64, i64)>,
+    Validated(request): Validated<UpdateAiBudgetRequest>,
+) -> ApiResult<AiBudget> {
+    let budget = AiBudget {
+        monthly_token_limit: request.monthly_token_limit,
+        monthly_spend_limit_micros: request.monthly_spend_limit_micros,
+    };
+
+    UpdateAiAgentBudgetCommand::new(deployment_id, agent_id, budget)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
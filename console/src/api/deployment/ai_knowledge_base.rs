@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Json, Multipart, Path, Query, State},
+    extract::{Multipart, Path, Query, State},
     http::StatusCode,
 };
 
@@ -7,24 +7,38 @@ use crate::{
     application::{
         AppError, HttpState,
         response::{ApiResult, PaginatedResponse},
+        validation::{Validated, ValidatedQuery},
     },
     core::{
         commands::{
             Command, CreateAiKnowledgeBaseCommand, DeleteAiKnowledgeBaseCommand,
-            DeleteKnowledgeBaseDocumentCommand, UpdateAiKnowledgeBaseCommand,
+            DeleteKnowledgeBaseDocumentCommand, IngestUrlIntoKnowledgeBaseCommand,
+            RechunkDocumentCommand, ReconcileQdrantCollectionsCommand, ReembedKnowledgeBaseCommand,
+            UpdateAiKnowledgeBaseCommand, UpdateDeploymentEmbeddingProviderCommand,
             UploadKnowledgeBaseDocumentCommand, UploadKnowledgeBaseUrlCommand,
         },
         dto::{
             json::ai_knowledge_base::{
-                CreateKnowledgeBaseRequest, GetDocumentsQuery, KnowledgeBaseResponse,
-                UpdateKnowledgeBaseRequest, UploadUrlRequest,
+                CreateKnowledgeBaseRequest, DryRunChunkingRequest, IngestUrlRequest,
+                RechunkDocumentRequest, UpdateEmbeddingProviderRequest, UpdateKnowledgeBaseRequest,
+                UploadUrlRequest,
             },
-            query::deployment::GetKnowledgeBasesQuery,
+            query::{
+                Pagination,
+                deployment::{GetKnowledgeBasesQuery, ReconcileQdrantCollectionsQueryParams},
+            },
+        },
+        models::{
+            AiKnowledgeBase, AiKnowledgeBaseCrawl, AiKnowledgeBaseDocument,
+            AiKnowledgeBaseReembedJob, AiKnowledgeBaseWithDetails, ChunkingConfig, ChunkingDryRun,
+            DeploymentEmbeddingProviders, KnowledgeBaseDocumentRechunk,
+            KnowledgeBaseDocumentSummary, QdrantCollectionReconciliation,
         },
-        models::{AiKnowledgeBase, AiKnowledgeBaseDocument, AiKnowledgeBaseWithDetails},
         queries::{
-            GetAiKnowledgeBaseByIdQuery, GetAiKnowledgeBasesQuery as GetKnowledgeBasesQueryCore,
-            GetKnowledgeBaseDocumentsQuery, Query as QueryTrait,
+            DryRunChunkingQuery, GetAiKnowledgeBaseByIdQuery,
+            GetAiKnowledgeBasesQuery as GetKnowledgeBasesQueryCore,
+            GetDeploymentEmbeddingProvidersQuery, GetKnowledgeBaseCrawlQuery,
+            GetReembedProgressQuery, ListKnowledgeBaseDocumentsQuery, Query as QueryTrait,
         },
     },
 };
@@ -32,51 +46,54 @@ use crate::{
 pub async fn get_ai_knowledge_bases(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
     Query(query): Query<GetKnowledgeBasesQuery>,
-) -> ApiResult<KnowledgeBaseResponse> {
-    let limit = query.limit.unwrap_or(20);
-    let offset = query.offset.unwrap_or(0);
-
-    let mut query_builder = GetKnowledgeBasesQueryCore::new(deployment_id, limit + 1, offset);
+) -> ApiResult<PaginatedResponse<AiKnowledgeBaseWithDetails>> {
+    let mut query_builder = GetKnowledgeBasesQueryCore::new(
+        deployment_id,
+        pagination.fetch_limit() as usize,
+        pagination.offset() as usize,
+    );
 
     if let Some(search) = query.search {
         query_builder = query_builder.with_search(search);
     }
 
-    let mut knowledge_bases = query_builder
+    let knowledge_bases = query_builder
         .execute(&app_state)
         .await
         .map_err(|e| AppError::from(e))?;
 
-    let has_more = knowledge_bases.len() > limit;
-    if has_more {
-        knowledge_bases.pop();
-    }
-
-    Ok(KnowledgeBaseResponse {
-        data: knowledge_bases,
-        has_more,
-    }
-    .into())
+    Ok(PaginatedResponse::page(knowledge_bases, &pagination).into())
 }
 
 pub async fn create_ai_knowledge_base(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(request): Json<CreateKnowledgeBaseRequest>,
+    Validated(request): Validated<CreateKnowledgeBaseRequest>,
 ) -> ApiResult<AiKnowledgeBase> {
     let configuration = request.configuration.unwrap_or(serde_json::json!({}));
 
-    CreateAiKnowledgeBaseCommand::new(
+    let mut command = CreateAiKnowledgeBaseCommand::new(
         deployment_id,
         request.name,
         request.description,
         configuration,
-    )
-    .execute(&app_state)
-    .await
-    .map(Into::into)
-    .map_err(Into::into)
+    );
+
+    if let Some(embedding_provider) = request.embedding_provider {
+        command = command.with_embedding_provider(embedding_provider);
+    }
+
+    if let Some(chunking) = request.chunking {
+        command = command.with_chunking(chunking);
+    }
+
+    command
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
 }
 
 pub async fn get_ai_knowledge_base_by_id(
@@ -93,7 +110,7 @@ pub async fn get_ai_knowledge_base_by_id(
 pub async fn update_ai_knowledge_base(
     State(app_state): State<HttpState>,
     Path((deployment_id, kb_id)): Path<(i64, i64)>,
-    Json(request): Json<UpdateKnowledgeBaseRequest>,
+    Validated(request): Validated<UpdateKnowledgeBaseRequest>,
 ) -> ApiResult<AiKnowledgeBase> {
     let mut command = UpdateAiKnowledgeBaseCommand::new(deployment_id, kb_id);
 
@@ -109,6 +126,14 @@ pub async fn update_ai_knowledge_base(
         command = command.with_configuration(configuration);
     }
 
+    if let Some(embedding_provider) = request.embedding_provider {
+        command = command.with_embedding_provider(embedding_provider);
+    }
+
+    if let Some(chunking) = request.chunking {
+        command = command.with_chunking(chunking);
+    }
+
     command
         .execute(&app_state)
         .await
@@ -219,7 +244,7 @@ pub async fn upload_knowledge_base_document(
 pub async fn upload_knowledge_base_url(
     State(app_state): State<HttpState>,
     Path((deployment_id, kb_id)): Path<(i64, i64)>,
-    Json(request): Json<UploadUrlRequest>,
+    Validated(request): Validated<UploadUrlRequest>,
 ) -> ApiResult<AiKnowledgeBaseDocument> {
     // Verify the knowledge base exists and belongs to the deployment
     GetAiKnowledgeBaseByIdQuery::new(deployment_id, kb_id)
@@ -239,11 +264,11 @@ pub async fn upload_knowledge_base_url(
         .map_err(Into::into)
 }
 
-pub async fn get_knowledge_base_documents(
+pub async fn ingest_knowledge_base_url(
     State(app_state): State<HttpState>,
     Path((deployment_id, kb_id)): Path<(i64, i64)>,
-    Query(query): Query<GetDocumentsQuery>,
-) -> ApiResult<PaginatedResponse<AiKnowledgeBaseDocument>> {
+    Validated(request): Validated<IngestUrlRequest>,
+) -> ApiResult<AiKnowledgeBaseCrawl> {
     // Verify the knowledge base exists and belongs to the deployment
     GetAiKnowledgeBaseByIdQuery::new(deployment_id, kb_id)
         .execute(&app_state)
@@ -255,24 +280,110 @@ pub async fn get_knowledge_base_documents(
             )
         })?;
 
-    let limit = query.limit.unwrap_or(20);
-    let offset = query.offset.unwrap_or(0);
+    let mut command = IngestUrlIntoKnowledgeBaseCommand::new(kb_id, request.url);
+
+    if request.crawl {
+        command = command.with_crawl(request.max_pages, request.max_depth);
+    }
 
-    let mut documents = GetKnowledgeBaseDocumentsQuery::new(kb_id, limit + 1, offset)
+    command
         .execute(&app_state)
         .await
-        .map_err(|e| AppError::from(e))?;
+        .map(Into::into)
+        .map_err(Into::into)
+}
 
-    let has_more = documents.len() > limit;
-    if has_more {
-        documents.pop();
-    }
+pub async fn get_knowledge_base_crawl(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, kb_id, crawl_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<AiKnowledgeBaseCrawl> {
+    // Verify the knowledge base exists and belongs to the deployment
+    GetAiKnowledgeBaseByIdQuery::new(deployment_id, kb_id)
+        .execute(&app_state)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                "Knowledge base not found".to_string(),
+            )
+        })?;
 
-    Ok(PaginatedResponse {
-        data: documents,
-        has_more,
-    }
-    .into())
+    GetKnowledgeBaseCrawlQuery::new(kb_id, crawl_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn reembed_knowledge_base(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, kb_id)): Path<(i64, i64)>,
+) -> ApiResult<AiKnowledgeBaseReembedJob> {
+    // Verify the knowledge base exists and belongs to the deployment
+    GetAiKnowledgeBaseByIdQuery::new(deployment_id, kb_id)
+        .execute(&app_state)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                "Knowledge base not found".to_string(),
+            )
+        })?;
+
+    ReembedKnowledgeBaseCommand::new(kb_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_reembed_progress(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, kb_id, job_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<AiKnowledgeBaseReembedJob> {
+    // Verify the knowledge base exists and belongs to the deployment
+    GetAiKnowledgeBaseByIdQuery::new(deployment_id, kb_id)
+        .execute(&app_state)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                "Knowledge base not found".to_string(),
+            )
+        })?;
+
+    GetReembedProgressQuery::new(kb_id, job_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_knowledge_base_documents(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, kb_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+) -> ApiResult<PaginatedResponse<KnowledgeBaseDocumentSummary>> {
+    // Verify the knowledge base exists and belongs to the deployment
+    GetAiKnowledgeBaseByIdQuery::new(deployment_id, kb_id)
+        .execute(&app_state)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                "Knowledge base not found".to_string(),
+            )
+        })?;
+
+    let documents = ListKnowledgeBaseDocumentsQuery::new(
+        kb_id,
+        pagination.fetch_limit() as usize,
+        pagination.offset() as usize,
+    )
+    .execute(&app_state)
+    .await?;
+
+    Ok(PaginatedResponse::page(documents, &pagination).into())
 }
 
 pub async fn delete_knowledge_base_document(
@@ -285,3 +396,80 @@ pub async fn delete_knowledge_base_document(
         .map(|_| ().into())
         .map_err(Into::into)
 }
+
+pub async fn rechunk_knowledge_base_document(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, kb_id, document_id)): Path<(i64, i64, i64)>,
+    Validated(request): Validated<RechunkDocumentRequest>,
+) -> ApiResult<KnowledgeBaseDocumentRechunk> {
+    RechunkDocumentCommand::new(deployment_id, kb_id, document_id)
+        .with_chunking(ChunkingConfig {
+            strategy: request.strategy,
+            chunk_size: request.chunk_size as usize,
+            chunk_overlap: request.chunk_overlap as usize,
+            boundary: request.boundary,
+        })
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn dry_run_knowledge_base_chunking(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, kb_id)): Path<(i64, i64)>,
+    Validated(request): Validated<DryRunChunkingRequest>,
+) -> ApiResult<ChunkingDryRun> {
+    let mut query = DryRunChunkingQuery::new(deployment_id, kb_id, request.content);
+
+    if let Some(file_type) = request.file_type {
+        query = query.with_file_type(file_type);
+    }
+
+    if let Some(chunking) = request.chunking {
+        query = query.with_chunking(chunking);
+    }
+
+    query
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_embedding_providers(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentEmbeddingProviders> {
+    GetDeploymentEmbeddingProvidersQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_embedding_provider(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<UpdateEmbeddingProviderRequest>,
+) -> ApiResult<DeploymentEmbeddingProviders> {
+    UpdateDeploymentEmbeddingProviderCommand::new(deployment_id, request.provider)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Knowledge base collections in Qdrant that no live knowledge base uses.
+/// Pass `delete=true` to delete them as well.
+pub async fn reconcile_qdrant_collections(
+    State(app_state): State<HttpState>,
+    Query(params): Query<ReconcileQdrantCollectionsQueryParams>,
+) -> ApiResult<QdrantCollectionReconciliation> {
+    ReconcileQdrantCollectionsCommand::new()
+        .with_delete_orphans(params.delete)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
@@ -1,12 +1,21 @@
 use axum::extract::{Path, Query, State};
 
 use crate::{
-    application::{AppError, HttpState, response::ApiResult},
+    application::{AppError, HttpState, response::ApiResult, validation::Validated},
     core::{
         dto::json::ai_knowledge_base::{
-            KnowledgeBaseSearchResult, SearchKnowledgeBaseQuery, SearchKnowledgeBaseResponse,
+            KnowledgeBaseSearchResult, SearchKnowledgeBaseQuery, SearchKnowledgeBaseRequest,
+            SearchKnowledgeBaseResponse,
+        },
+        models::KnowledgeBaseSearchResults,
+        queries::{
+            Query as QueryTrait,
+            ai_knowledge_base::{
+                GetAiKnowledgeBaseByIdQuery, GetKnowledgeBaseCollectionsQuery,
+                GetKnowledgeBaseEmbedderQuery,
+                SearchKnowledgeBaseQuery as SearchKnowledgeBaseQueryCore,
+            },
         },
-        queries::{Query as QueryTrait, ai_knowledge_base::GetAiKnowledgeBaseByIdQuery},
         services::qdrant::QdrantService,
     },
 };
@@ -19,15 +28,6 @@ pub async fn search_knowledge_base(
 ) -> ApiResult<SearchKnowledgeBaseResponse> {
     let limit = params.limit.unwrap_or(10).min(100); // Cap at 100 results
 
-    // Generate embedding for the search query
-    let query_embedding = app_state
-        .embedding_service
-        .generate_embeddings(vec![params.query.clone()])
-        .await?
-        .into_iter()
-        .next()
-        .ok_or_else(|| AppError::Internal("Failed to generate query embedding".to_string()))?;
-
     let results = if let Some(kb_id) = params.knowledge_base_id {
         // Search specific knowledge base
         let _kb = GetAiKnowledgeBaseByIdQuery::new(deployment_id, kb_id)
@@ -35,7 +35,18 @@ pub async fn search_knowledge_base(
             .await
             .map_err(|_| AppError::NotFound("Knowledge base not found".to_string()))?;
 
-        QdrantService::search_similar(query_embedding, limit, kb_id).await?
+        // Generate embedding for the search query
+        let query_embedding = GetKnowledgeBaseEmbedderQuery::new(kb_id)
+            .execute(&app_state)
+            .await?
+            .embed_one(params.query.clone())
+            .await?;
+
+        let collections = GetKnowledgeBaseCollectionsQuery::new(kb_id)
+            .execute(&app_state)
+            .await?;
+
+        QdrantService::search_similar(&collections.active, query_embedding, limit, kb_id).await?
     } else {
         // For searching across all knowledge bases in a deployment, we need to get all KB IDs
         // and search each one, then combine results. For now, return an error.
@@ -75,15 +86,23 @@ pub async fn search_specific_knowledge_base(
     let limit = params.limit.unwrap_or(10).min(100); // Cap at 100 results
 
     // Generate embedding for the search query
-    let query_embedding = app_state
-        .embedding_service
-        .generate_embeddings(vec![params.query.clone()])
+    let query_embedding = GetKnowledgeBaseEmbedderQuery::new(knowledge_base_id)
+        .execute(&app_state)
         .await?
-        .into_iter()
-        .next()
-        .ok_or_else(|| AppError::Internal("Failed to generate query embedding".to_string()))?;
+        .embed_one(params.query.clone())
+        .await?;
+
+    let collections = GetKnowledgeBaseCollectionsQuery::new(knowledge_base_id)
+        .execute(&app_state)
+        .await?;
 
-    let results = QdrantService::search_similar(query_embedding, limit, knowledge_base_id).await?;
+    let results = QdrantService::search_similar(
+        &collections.active,
+        query_embedding,
+        limit,
+        knowledge_base_id,
+    )
+    .await?;
 
     let search_results: Vec<KnowledgeBaseSearchResult> = results
         .into_iter()
@@ -99,3 +118,40 @@ pub async fn search_specific_knowledge_base(
     }
     .into())
 }
+
+/// Retrieval test against a knowledge base with tunable top_k, score threshold and filters
+pub async fn query_knowledge_base(
+    Path((deployment_id, knowledge_base_id)): Path<(i64, i64)>,
+    State(app_state): State<HttpState>,
+    Validated(request): Validated<SearchKnowledgeBaseRequest>,
+) -> ApiResult<KnowledgeBaseSearchResults> {
+    // Verify the knowledge base exists and belongs to the deployment
+    let _kb = GetAiKnowledgeBaseByIdQuery::new(deployment_id, knowledge_base_id)
+        .execute(&app_state)
+        .await
+        .map_err(|_| AppError::NotFound("Knowledge base not found".to_string()))?;
+
+    let mut query = SearchKnowledgeBaseQueryCore::new(knowledge_base_id, request.query);
+
+    if let Some(top_k) = request.top_k {
+        query = query.with_top_k(top_k);
+    }
+
+    if let Some(score_threshold) = request.score_threshold {
+        query = query.with_score_threshold(score_threshold);
+    }
+
+    if let Some(document_id) = request.filters.document_id {
+        query = query.with_document_id(document_id);
+    }
+
+    if let Some(source_url) = request.filters.source_url {
+        query = query.with_source_url(source_url);
+    }
+
+    query
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
@@ -1,17 +1,23 @@
-use axum::extract::{Json, Path, Query, State};
+use axum::extract::{Path, Query, State};
 
 use crate::{
     application::{
         HttpState,
         response::{ApiResult, PaginatedResponse},
+        validation::{Validated, ValidatedQuery},
     },
     core::{
-        commands::{Command, CreateAiToolCommand, DeleteAiToolCommand, UpdateAiToolCommand},
+        commands::{
+            Command, CreateAiToolCommand, DeleteAiToolCommand, ExecuteAiToolCommand,
+            ImportToolsFromOpenApiCommand, OpenApiSource, UpdateAiToolCommand,
+        },
         dto::{
-            json::deployment::{CreateToolRequest, UpdateToolRequest},
-            query::deployment::GetToolsQuery,
+            json::deployment::{
+                CreateToolRequest, ExecuteToolRequest, ImportOpenApiToolsRequest, UpdateToolRequest,
+            },
+            query::{Pagination, deployment::GetToolsQuery},
         },
-        models::{AiTool, AiToolType, AiToolWithDetails},
+        models::{AiTool, AiToolInvocation, AiToolType, AiToolWithDetails, OpenApiImportReport},
         queries::{GetAiToolByIdQuery, GetAiToolsQuery, Query as QueryTrait},
     },
 };
@@ -19,35 +25,23 @@ use crate::{
 pub async fn get_ai_tools(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
     Query(query): Query<GetToolsQuery>,
 ) -> ApiResult<PaginatedResponse<AiToolWithDetails>> {
-    let limit = query.limit.unwrap_or(50) as u32;
-
     let tools = GetAiToolsQuery::new(deployment_id)
-        .with_limit(Some(limit + 1))
-        .with_offset(query.offset.map(|o| o as u32))
+        .with_limit(Some(pagination.fetch_limit() as u32))
+        .with_offset(Some(pagination.offset() as u32))
         .with_search(query.search)
         .execute(&app_state)
         .await?;
 
-    let has_more = tools.len() > limit as usize;
-    let tools = if has_more {
-        tools[..limit as usize].to_vec()
-    } else {
-        tools
-    };
-
-    Ok(PaginatedResponse {
-        data: tools,
-        has_more,
-    }
-    .into())
+    Ok(PaginatedResponse::page(tools, &pagination).into())
 }
 
 pub async fn create_ai_tool(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(request): Json<CreateToolRequest>,
+    Validated(request): Validated<CreateToolRequest>,
 ) -> ApiResult<AiTool> {
     let tool_type = AiToolType::from(request.tool_type);
 
@@ -78,7 +72,7 @@ pub async fn get_ai_tool_by_id(
 pub async fn update_ai_tool(
     State(app_state): State<HttpState>,
     Path((deployment_id, tool_id)): Path<(i64, i64)>,
-    Json(request): Json<UpdateToolRequest>,
+    Validated(request): Validated<UpdateToolRequest>,
 ) -> ApiResult<AiTool> {
     let mut command = UpdateAiToolCommand::new(deployment_id, tool_id);
 
@@ -112,3 +106,47 @@ pub async fn delete_ai_tool(
         .map(Into::into)
         .map_err(Into::into)
 }
+
+pub async fn execute_ai_tool(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, tool_id)): Path<(i64, i64)>,
+    Validated(request): Validated<ExecuteToolRequest>,
+) -> ApiResult<AiToolInvocation> {
+    ExecuteAiToolCommand::new(deployment_id, tool_id, request.inputs)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn import_openapi_tools(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<ImportOpenApiToolsRequest>,
+) -> ApiResult<OpenApiImportReport> {
+    let source = match (request.spec_url, request.spec) {
+        (Some(spec_url), _) => OpenApiSource::Url(spec_url),
+        (None, spec) => OpenApiSource::Document(spec.unwrap_or_default()),
+    };
+    let mut command =
+        ImportToolsFromOpenApiCommand::new(deployment_id, source).with_headers(request.headers);
+
+    if let Some(operation_ids) = request.operation_ids {
+        command = command.with_operation_ids(operation_ids);
+    }
+    if let Some(base_url) = request.base_url {
+        command = command.with_base_url(base_url);
+    }
+    if let Some(authorization) = request.authorization {
+        command = command.with_authorization(authorization);
+    }
+    if let Some(execution_policy) = request.execution_policy {
+        command = command.with_execution_policy(execution_policy);
+    }
+
+    command
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
@@ -1,55 +1,60 @@
-use axum::extract::{Json, Path, Query, State};
+use axum::extract::{Path, Query, State};
 
 use crate::{
     application::{
         HttpState,
         response::{ApiResult, PaginatedResponse},
+        validation::{Validated, ValidatedQuery},
     },
     core::{
         commands::{
-            Command, CreateAiWorkflowCommand, DeleteAiWorkflowCommand, UpdateAiWorkflowCommand,
+            CancelWorkflowRunCommand, Command, CreateAiWorkflowCommand,
+            CreateWorkflowTriggerCommand, DeleteAiWorkflowCommand, DeleteWorkflowTriggerCommand,
+            ExecuteAiWorkflowCommand, PublishAiWorkflowCommand, RollbackAiWorkflowCommand,
+            UpdateAiWorkflowCommand,
         },
         dto::{
-            json::deployment::{CreateWorkflowRequest, UpdateWorkflowRequest},
-            query::deployment::GetWorkflowsQuery,
+            json::deployment::{
+                CreateWorkflowRequest, CreateWorkflowTriggerRequest, ExecuteWorkflowRequest,
+                PublishWorkflowRequest, UpdateWorkflowRequest,
+            },
+            query::{
+                DateRangeParams, Pagination,
+                deployment::{GetWorkflowRunsQuery, GetWorkflowsQuery},
+            },
+        },
+        models::{
+            AiWorkflow, AiWorkflowTrigger, AiWorkflowVersion, AiWorkflowWithDetails,
+            CreatedAiWorkflowTrigger, ExecutionStatus, WorkflowExecution, WorkflowTriggerType,
+        },
+        queries::{
+            GetAiWorkflowByIdQuery, GetAiWorkflowsQuery, GetWorkflowRunQuery,
+            ListWorkflowRunsQuery, ListWorkflowTriggersQuery, ListWorkflowVersionsQuery,
+            Query as QueryTrait,
         },
-        models::{AiWorkflow, AiWorkflowWithDetails},
-        queries::{GetAiWorkflowByIdQuery, GetAiWorkflowsQuery, Query as QueryTrait},
     },
 };
 
 pub async fn get_ai_workflows(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
     Query(query): Query<GetWorkflowsQuery>,
 ) -> ApiResult<PaginatedResponse<AiWorkflowWithDetails>> {
-    let limit = query.limit.unwrap_or(50) as u32;
-
     let workflows = GetAiWorkflowsQuery::new(deployment_id)
-        .with_limit(Some(limit + 1))
-        .with_offset(query.offset.map(|o| o as u32))
+        .with_limit(Some(pagination.fetch_limit() as u32))
+        .with_offset(Some(pagination.offset() as u32))
         .with_search(query.search)
         .execute(&app_state)
         .await?;
 
-    let has_more = workflows.len() > limit as usize;
-    let workflows = if has_more {
-        workflows[..limit as usize].to_vec()
-    } else {
-        workflows
-    };
-
-    Ok(PaginatedResponse {
-        data: workflows,
-        has_more,
-    }
-    .into())
+    Ok(PaginatedResponse::page(workflows, &pagination).into())
 }
 
 pub async fn create_ai_workflow(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(request): Json<CreateWorkflowRequest>,
+    Validated(request): Validated<CreateWorkflowRequest>,
 ) -> ApiResult<AiWorkflow> {
     CreateAiWorkflowCommand::new(
         deployment_id,
@@ -78,7 +83,7 @@ pub async fn get_ai_workflow_by_id(
 pub async fn update_ai_workflow(
     State(app_state): State<HttpState>,
     Path((deployment_id, workflow_id)): Path<(i64, i64)>,
-    Json(request): Json<UpdateWorkflowRequest>,
+    Validated(request): Validated<UpdateWorkflowRequest>,
 ) -> ApiResult<AiWorkflow> {
     let mut command = UpdateAiWorkflowCommand::new(deployment_id, workflow_id);
 
@@ -112,3 +117,150 @@ pub async fn delete_ai_workflow(
         .map(Into::into)
         .map_err(Into::into)
 }
+
+pub async fn execute_ai_workflow(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id)): Path<(i64, i64)>,
+    Validated(request): Validated<ExecuteWorkflowRequest>,
+) -> ApiResult<WorkflowExecution> {
+    let mut command = ExecuteAiWorkflowCommand::new(deployment_id, workflow_id);
+
+    if let Some(trigger_data) = request.trigger_data {
+        command = command.with_trigger_data(trigger_data);
+    }
+    if let Some(variables) = request.variables {
+        command = command.with_variables(variables);
+    }
+
+    command
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_workflow_runs(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    ValidatedQuery(range): ValidatedQuery<DateRangeParams>,
+    Query(query): Query<GetWorkflowRunsQuery>,
+) -> ApiResult<PaginatedResponse<WorkflowExecution>> {
+    let runs = ListWorkflowRunsQuery::new(deployment_id, workflow_id)
+        .with_limit(Some(pagination.fetch_limit() as u32))
+        .with_offset(Some(pagination.offset() as u32))
+        .with_status(query.status.map(ExecutionStatus::from))
+        .with_started_after(range.from)
+        .with_started_before(range.to)
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(runs, &pagination).into())
+}
+
+pub async fn get_workflow_run(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id, run_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<WorkflowExecution> {
+    GetWorkflowRunQuery::new(deployment_id, workflow_id, run_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn cancel_workflow_run(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id, run_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<WorkflowExecution> {
+    CancelWorkflowRunCommand::new(deployment_id, workflow_id, run_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_workflow_versions(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+) -> ApiResult<PaginatedResponse<AiWorkflowVersion>> {
+    let versions = ListWorkflowVersionsQuery::new(deployment_id, workflow_id)
+        .with_limit(Some(pagination.fetch_limit() as u32))
+        .with_offset(Some(pagination.offset() as u32))
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(versions, &pagination).into())
+}
+
+pub async fn publish_ai_workflow(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id)): Path<(i64, i64)>,
+    Validated(request): Validated<PublishWorkflowRequest>,
+) -> ApiResult<AiWorkflowVersion> {
+    PublishAiWorkflowCommand::new(deployment_id, workflow_id)
+        .with_published_by(request.published_by)
+        .with_change_note(request.change_note)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn rollback_ai_workflow(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id, version_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<AiWorkflowVersion> {
+    RollbackAiWorkflowCommand::new(deployment_id, workflow_id, version_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_workflow_triggers(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id)): Path<(i64, i64)>,
+) -> ApiResult<Vec<AiWorkflowTrigger>> {
+    ListWorkflowTriggersQuery::new(deployment_id, workflow_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn create_workflow_trigger(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id)): Path<(i64, i64)>,
+    Validated(request): Validated<CreateWorkflowTriggerRequest>,
+) -> ApiResult<CreatedAiWorkflowTrigger> {
+    let command = match WorkflowTriggerType::from(request.trigger_type) {
+        WorkflowTriggerType::Schedule => CreateWorkflowTriggerCommand::schedule(
+            deployment_id,
+            workflow_id,
+            request.cron_expression.unwrap_or_default(),
+            request.timezone.unwrap_or_else(|| "UTC".to_string()),
+        ),
+        WorkflowTriggerType::Webhook => {
+            CreateWorkflowTriggerCommand::webhook(deployment_id, workflow_id)
+        }
+    };
+
+    command
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn delete_workflow_trigger(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workflow_id, trigger_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<()> {
+    DeleteWorkflowTriggerCommand::new(deployment_id, workflow_id, trigger_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
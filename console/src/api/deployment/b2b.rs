@@ -1,45 +1,69 @@
-use axum::Json;
 use axum::extract::{Path, Query as QueryParams, State};
 
 use crate::core::commands::{
-    AddOrganizationMemberCommand, Command, CreateOrganizationCommand,
-    CreateOrganizationRoleCommand, CreateWorkspaceCommand, DeleteOrganizationCommand,
-    DeleteOrganizationRoleCommand, RemoveOrganizationMemberCommand,
-    UpdateDeploymentB2bSettingsCommand, UpdateOrganizationCommand, UpdateOrganizationMemberCommand,
-    UpdateOrganizationRoleCommand,
+    AcceptOrganizationInvitationCommand, AddOrganizationMemberCommand, AddWorkspaceMemberCommand,
+    Command, CreateOrganizationCommand, CreateOrganizationInvitationCommand,
+    CreateOrganizationRoleCommand, CreateWorkspaceCommand, CreateWorkspaceRoleCommand,
+    DeleteOrganizationCommand, DeleteOrganizationRoleCommand, DeleteWorkspaceCommand,
+    DeleteWorkspaceRoleCommand, RemoveOrganizationMemberCommand, RemoveWorkspaceMemberCommand,
+    ResendOrganizationInvitationCommand, RevokeOrganizationInvitationCommand,
+    UpdateDeploymentB2bSettingsCommand, UpdateOrganizationCommand,
+    UpdateOrganizationMemberRoleCommand, UpdateOrganizationMetadataCommand,
+    UpdateOrganizationRoleCommand, UpdateWorkspaceCommand, UpdateWorkspaceMemberRoleCommand,
+    UpdateWorkspaceRoleCommand,
 };
 use crate::core::dto::{
     json::{
         b2b::{
-            AddOrganizationMemberRequest, CreateOrganizationRequest, CreateOrganizationRoleRequest,
-            CreateWorkspaceRequest, UpdateOrganizationMemberRequest, UpdateOrganizationRequest,
-            UpdateOrganizationRoleRequest,
+            AcceptOrganizationInvitationRequest, AddOrganizationMemberRequest,
+            AddWorkspaceMemberRequest, CreateOrganizationInvitationRequest,
+            CreateOrganizationRequest, CreateOrganizationRoleRequest, CreateWorkspaceRequest,
+            CreateWorkspaceRoleRequest, ResendOrganizationInvitationRequest,
+            SimulatePermissionChangeRequest, UpdateOrganizationMemberRequest,
+            UpdateOrganizationMetadataRequest, UpdateOrganizationRequest,
+            UpdateOrganizationRoleRequest, UpdateWorkspaceMemberRequest, UpdateWorkspaceRequest,
+            UpdateWorkspaceRoleRequest,
         },
         deployment_settings::DeploymentB2bSettingsUpdates,
     },
-    query::OrganizationListQueryParams,
+    query::{
+        DeleteOrganizationQueryParams, DeleteRoleQueryParams, DeleteWorkspaceQueryParams,
+        OrganizationAuditEventListQueryParams, OrganizationInvitationListQueryParams,
+        OrganizationMemberListQueryParams, OrganizationSortColumns, Pagination,
+        RemoveMemberQueryParams, SortParams, WorkspaceMemberListQueryParams,
+    },
 };
 use crate::core::models::{
-    Organization, OrganizationDetails, OrganizationMemberDetails, OrganizationRole, Workspace,
-    WorkspaceDetails, WorkspaceWithOrganizationName,
+    Organization, OrganizationAuditEvent, OrganizationDeletion, OrganizationDetails,
+    OrganizationInvitation, OrganizationMemberDetails, OrganizationRole, OrganizationRoleUpdate,
+    OrganizationRoleUsage, RolePermissionChangeReport, Workspace, WorkspaceDetails,
+    WorkspaceMemberDetails, WorkspaceRole, WorkspaceRoleUsage, WorkspaceWithOrganizationName,
 };
 use crate::core::queries::{
     DeploymentOrganizationListQuery, DeploymentWorkspaceListQuery, GetOrganizationDetailsQuery,
-    GetWorkspaceDetailsQuery,
+    GetWorkspaceDetailsQuery, ListOrganizationAuditEventsQuery, ListOrganizationInvitationsQuery,
+    ListOrganizationMembersQuery, ListOrganizationRolesQuery, ListOrganizationWorkspacesQuery,
+    ListWorkspaceMembersQuery, ListWorkspaceRolesQuery, SimulatePermissionChangeQuery,
 };
 use crate::{
-    application::{HttpState, response::ApiResult, response::PaginatedResponse},
+    application::{
+        HttpState,
+        api_key::{ApiKeyAuth, initiated_by, require_scope},
+        response::ApiResult,
+        response::PaginatedResponse,
+        validation::{Validated, ValidatedQuery},
+    },
     core::{
         models::{DeploymentOrganizationRole, DeploymentWorkspaceRole},
-        queries::{GetDeploymentOrganizationRolesQuery, GetDeploymentWorkspaceRolesQuery, Query},
+        queries::Query,
     },
 };
 
 pub async fn get_deployment_workspace_roles(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-) -> ApiResult<PaginatedResponse<DeploymentWorkspaceRole>> {
-    GetDeploymentWorkspaceRolesQuery::new(deployment_id)
+) -> ApiResult<PaginatedResponse<WorkspaceRoleUsage>> {
+    ListWorkspaceRolesQuery::new(deployment_id)
         .execute(&app_state)
         .await
         .map(PaginatedResponse::from)
@@ -47,11 +71,48 @@ pub async fn get_deployment_workspace_roles(
         .map_err(Into::into)
 }
 
+pub async fn create_deployment_workspace_role(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<CreateWorkspaceRoleRequest>,
+) -> ApiResult<DeploymentWorkspaceRole> {
+    CreateWorkspaceRoleCommand::new(deployment_id, request.name, request.permissions)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_deployment_workspace_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, role_id)): Path<(i64, i64)>,
+    Validated(request): Validated<UpdateWorkspaceRoleRequest>,
+) -> ApiResult<DeploymentWorkspaceRole> {
+    UpdateWorkspaceRoleCommand::new(deployment_id, role_id, request.name, request.permissions)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn delete_deployment_workspace_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, role_id)): Path<(i64, i64)>,
+    QueryParams(query_params): QueryParams<DeleteRoleQueryParams>,
+) -> ApiResult<()> {
+    DeleteWorkspaceRoleCommand::new(deployment_id, role_id)
+        .with_reassign_to(query_params.reassign_to_role_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
 pub async fn get_deployment_org_roles(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-) -> ApiResult<PaginatedResponse<DeploymentOrganizationRole>> {
-    GetDeploymentOrganizationRolesQuery::new(deployment_id)
+) -> ApiResult<PaginatedResponse<OrganizationRoleUsage>> {
+    ListOrganizationRolesQuery::new(deployment_id)
         .execute(&app_state)
         .await
         .map(PaginatedResponse::from)
@@ -59,10 +120,60 @@ pub async fn get_deployment_org_roles(
         .map_err(Into::into)
 }
 
+pub async fn create_deployment_org_role(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<CreateOrganizationRoleRequest>,
+) -> ApiResult<DeploymentOrganizationRole> {
+    CreateOrganizationRoleCommand::new(deployment_id, request.name, request.permissions)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_deployment_org_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, role_id)): Path<(i64, i64)>,
+    Validated(request): Validated<UpdateOrganizationRoleRequest>,
+) -> ApiResult<OrganizationRoleUpdate> {
+    UpdateOrganizationRoleCommand::new(deployment_id, role_id, request.name, request.permissions)
+        .with_notify(request.notify.unwrap_or(false))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn simulate_deployment_org_role_change(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, role_id)): Path<(i64, i64)>,
+    Validated(request): Validated<SimulatePermissionChangeRequest>,
+) -> ApiResult<RolePermissionChangeReport> {
+    SimulatePermissionChangeQuery::new(deployment_id, role_id, request.permissions)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn delete_deployment_org_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, role_id)): Path<(i64, i64)>,
+    QueryParams(query_params): QueryParams<DeleteRoleQueryParams>,
+) -> ApiResult<()> {
+    DeleteOrganizationRoleCommand::new(deployment_id, role_id)
+        .with_reassign_to(query_params.reassign_to_role_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
 pub async fn update_deployment_b2b_settings(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(settings): Json<DeploymentB2bSettingsUpdates>,
+    Validated(settings): Validated<DeploymentB2bSettingsUpdates>,
 ) -> ApiResult<()> {
     UpdateDeploymentB2bSettingsCommand::new(deployment_id, settings)
         .execute(&app_state)
@@ -74,51 +185,35 @@ pub async fn update_deployment_b2b_settings(
 pub async fn get_organization_list(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    QueryParams(query_params): QueryParams<OrganizationListQueryParams>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    ValidatedQuery(sort): ValidatedQuery<SortParams<OrganizationSortColumns>>,
 ) -> ApiResult<PaginatedResponse<Organization>> {
-    let limit = query_params.limit.unwrap_or(10);
-
     let organizations = DeploymentOrganizationListQuery::new(deployment_id)
-        .limit(limit + 1)
-        .offset(query_params.offset.unwrap_or(0))
-        .sort_key(query_params.sort_key)
-        .sort_order(query_params.sort_order)
+        .limit(pagination.fetch_limit() as i32)
+        .offset(pagination.offset())
+        .sort_key(Some(sort.key().to_string()))
+        .sort_order(Some(sort.order().to_string()))
         .execute(&app_state)
         .await?;
 
-    let has_more = organizations.len() > limit as usize;
-    let organizations = if has_more {
-        organizations[..limit as usize].to_vec()
-    } else {
-        organizations
-    };
-
-    Ok(PaginatedResponse::from(organizations).into())
+    Ok(PaginatedResponse::page(organizations, &pagination).into())
 }
 
 pub async fn get_workspace_list(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    QueryParams(query_params): QueryParams<OrganizationListQueryParams>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    ValidatedQuery(sort): ValidatedQuery<SortParams<OrganizationSortColumns>>,
 ) -> ApiResult<PaginatedResponse<WorkspaceWithOrganizationName>> {
-    let limit = query_params.limit.unwrap_or(10);
-
     let workspaces = DeploymentWorkspaceListQuery::new(deployment_id)
-        .limit(limit + 1)
-        .offset(query_params.offset.unwrap_or(0))
-        .sort_key(query_params.sort_key)
-        .sort_order(query_params.sort_order)
+        .limit(pagination.fetch_limit() as i32)
+        .offset(pagination.offset())
+        .sort_key(Some(sort.key().to_string()))
+        .sort_order(Some(sort.order().to_string()))
         .execute(&app_state)
         .await?;
 
-    let has_more = workspaces.len() > limit as usize;
-    let workspaces = if has_more {
-        workspaces[..limit as usize].to_vec()
-    } else {
-        workspaces
-    };
-
-    Ok(PaginatedResponse::from(workspaces).into())
+    Ok(PaginatedResponse::page(workspaces, &pagination).into())
 }
 
 pub async fn get_organization_details(
@@ -146,7 +241,7 @@ pub async fn get_workspace_details(
 pub async fn create_organization(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(request): Json<CreateOrganizationRequest>,
+    Validated(request): Validated<CreateOrganizationRequest>,
 ) -> ApiResult<Organization> {
     CreateOrganizationCommand::new(
         deployment_id,
@@ -164,8 +259,9 @@ pub async fn create_organization(
 
 pub async fn create_workspace_for_organization(
     State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
     Path((deployment_id, organization_id)): Path<(i64, i64)>,
-    Json(request): Json<CreateWorkspaceRequest>,
+    Validated(request): Validated<CreateWorkspaceRequest>,
 ) -> ApiResult<Workspace> {
     CreateWorkspaceCommand::new(
         deployment_id,
@@ -176,16 +272,71 @@ pub async fn create_workspace_for_organization(
         request.public_metadata,
         request.private_metadata,
     )
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
+    .execute(&app_state)
+    .await
+    .map(Into::into)
+    .map_err(Into::into)
+}
+
+pub async fn get_organization_workspaces(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, organization_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    ValidatedQuery(sort): ValidatedQuery<SortParams<OrganizationSortColumns>>,
+) -> ApiResult<PaginatedResponse<Workspace>> {
+    let workspaces = ListOrganizationWorkspacesQuery::new(deployment_id, organization_id)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .sort_key(Some(sort.key().to_string()))
+        .sort_order(Some(sort.order().to_string()))
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(workspaces, &pagination).into())
+}
+
+pub async fn update_workspace(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, workspace_id)): Path<(i64, i64)>,
+    Validated(request): Validated<UpdateWorkspaceRequest>,
+) -> ApiResult<Workspace> {
+    UpdateWorkspaceCommand::new(
+        deployment_id,
+        workspace_id,
+        request.name,
+        request.description,
+        request.image_url,
+        request.public_metadata,
+        request.private_metadata,
+    )
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
     .execute(&app_state)
     .await
     .map(Into::into)
     .map_err(Into::into)
 }
 
+pub async fn delete_workspace(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, workspace_id)): Path<(i64, i64)>,
+    QueryParams(query_params): QueryParams<DeleteWorkspaceQueryParams>,
+) -> ApiResult<()> {
+    DeleteWorkspaceCommand::new(deployment_id, workspace_id)
+        .with_initiated_by(initiated_by(api_key, query_params.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
 pub async fn update_organization(
     State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
     Path((deployment_id, organization_id)): Path<(i64, i64)>,
-    Json(request): Json<UpdateOrganizationRequest>,
+    Validated(request): Validated<UpdateOrganizationRequest>,
 ) -> ApiResult<Organization> {
     UpdateOrganizationCommand::new(
         deployment_id,
@@ -196,17 +347,45 @@ pub async fn update_organization(
         request.public_metadata,
         request.private_metadata,
     )
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
     .execute(&app_state)
     .await
     .map(Into::into)
     .map_err(Into::into)
 }
 
+pub async fn update_organization_metadata(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, organization_id)): Path<(i64, i64)>,
+    Validated(request): Validated<UpdateOrganizationMetadataRequest>,
+) -> ApiResult<Organization> {
+    UpdateOrganizationMetadataCommand::new(deployment_id, organization_id)
+        .with_public_metadata(request.public_metadata)
+        .with_private_metadata(request.private_metadata)
+        .with_mode(request.mode)
+        .with_initiated_by(initiated_by(api_key, request.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// With `dry_run=true` nothing is deleted and the counts show what would be.
 pub async fn delete_organization(
     State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
     Path((deployment_id, organization_id)): Path<(i64, i64)>,
-) -> ApiResult<()> {
+    QueryParams(query_params): QueryParams<DeleteOrganizationQueryParams>,
+) -> ApiResult<OrganizationDeletion> {
+    if query_params.admin_override {
+        require_scope(api_key.as_ref(), "settings:write")?;
+    }
+
     DeleteOrganizationCommand::new(deployment_id, organization_id)
+        .with_admin_override(query_params.admin_override)
+        .with_dry_run(query_params.dry_run)
+        .with_initiated_by(initiated_by(api_key, query_params.initiated_by))
         .execute(&app_state)
         .await
         .map(Into::into)
@@ -215,10 +394,28 @@ pub async fn delete_organization(
 
 // Organization Member Management
 
+pub async fn get_organization_members(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, organization_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    QueryParams(query_params): QueryParams<OrganizationMemberListQueryParams>,
+) -> ApiResult<PaginatedResponse<OrganizationMemberDetails>> {
+    let members = ListOrganizationMembersQuery::new(deployment_id, organization_id)
+        .role_id(query_params.role_id)
+        .search(query_params.search)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(members, &pagination).into())
+}
+
 pub async fn add_organization_member(
     State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
     Path((deployment_id, organization_id)): Path<(i64, i64)>,
-    Json(request): Json<AddOrganizationMemberRequest>,
+    Validated(request): Validated<AddOrganizationMemberRequest>,
 ) -> ApiResult<OrganizationMemberDetails> {
     AddOrganizationMemberCommand::new(
         deployment_id,
@@ -226,6 +423,7 @@ pub async fn add_organization_member(
         request.user_id,
         request.role_ids,
     )
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
     .execute(&app_state)
     .await
     .map(Into::into)
@@ -234,15 +432,17 @@ pub async fn add_organization_member(
 
 pub async fn update_organization_member(
     State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
     Path((deployment_id, organization_id, membership_id)): Path<(i64, i64, i64)>,
-    Json(request): Json<UpdateOrganizationMemberRequest>,
-) -> ApiResult<()> {
-    UpdateOrganizationMemberCommand::new(
+    Validated(request): Validated<UpdateOrganizationMemberRequest>,
+) -> ApiResult<OrganizationMemberDetails> {
+    UpdateOrganizationMemberRoleCommand::new(
         deployment_id,
         organization_id,
         membership_id,
         request.role_ids,
     )
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
     .execute(&app_state)
     .await
     .map(Into::into)
@@ -251,57 +451,287 @@ pub async fn update_organization_member(
 
 pub async fn remove_organization_member(
     State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
     Path((deployment_id, organization_id, membership_id)): Path<(i64, i64, i64)>,
-) -> ApiResult<()> {
+    QueryParams(query_params): QueryParams<RemoveMemberQueryParams>,
+) -> ApiResult<OrganizationMemberDetails> {
     RemoveOrganizationMemberCommand::new(deployment_id, organization_id, membership_id)
+        .with_initiated_by(initiated_by(api_key, query_params.initiated_by))
         .execute(&app_state)
         .await
         .map(Into::into)
         .map_err(Into::into)
 }
 
-// Organization Role Management
+/// Only the organization's admins see its audit events, so `user_id` must be
+/// one of them.
+pub async fn get_organization_audit_events(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, organization_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    QueryParams(query_params): QueryParams<OrganizationAuditEventListQueryParams>,
+) -> ApiResult<PaginatedResponse<OrganizationAuditEvent>> {
+    let events =
+        ListOrganizationAuditEventsQuery::new(deployment_id, organization_id, query_params.user_id)
+            .actor(query_params.actor)
+            .event_type(query_params.event_type)
+            .from(query_params.from)
+            .to(query_params.to)
+            .limit(pagination.fetch_limit())
+            .offset(pagination.offset())
+            .execute(&app_state)
+            .await?;
 
-pub async fn create_organization_role(
+    Ok(PaginatedResponse::page(events, &pagination).into())
+}
+
+// Organization Invitation Management
+
+pub async fn get_organization_invitations(
     State(app_state): State<HttpState>,
     Path((deployment_id, organization_id)): Path<(i64, i64)>,
-    Json(request): Json<CreateOrganizationRoleRequest>,
-) -> ApiResult<OrganizationRole> {
-    CreateOrganizationRoleCommand::new(
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    QueryParams(query_params): QueryParams<OrganizationInvitationListQueryParams>,
+) -> ApiResult<PaginatedResponse<OrganizationInvitation>> {
+    let invitations = ListOrganizationInvitationsQuery::new(deployment_id, organization_id)
+        .pending(query_params.pending.unwrap_or(false))
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(invitations, &pagination).into())
+}
+
+pub async fn create_organization_invitation(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, organization_id)): Path<(i64, i64)>,
+    Validated(request): Validated<CreateOrganizationInvitationRequest>,
+) -> ApiResult<OrganizationInvitation> {
+    CreateOrganizationInvitationCommand::new(
         deployment_id,
         organization_id,
-        request.name,
-        request.permissions,
+        request.email_address,
+        request.role_id,
     )
+    .with_inviter_id(request.inviter_id)
+    .with_expiry_days(request.expiry_days)
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
     .execute(&app_state)
     .await
     .map(Into::into)
     .map_err(Into::into)
 }
 
-pub async fn update_organization_role(
+pub async fn resend_organization_invitation(
     State(app_state): State<HttpState>,
-    Path((deployment_id, organization_id, role_id)): Path<(i64, i64, i64)>,
-    Json(request): Json<UpdateOrganizationRoleRequest>,
-) -> ApiResult<OrganizationRole> {
-    UpdateOrganizationRoleCommand::new(
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, organization_id, invitation_id)): Path<(i64, i64, i64)>,
+    Validated(request): Validated<ResendOrganizationInvitationRequest>,
+) -> ApiResult<OrganizationInvitation> {
+    ResendOrganizationInvitationCommand::new(deployment_id, organization_id, invitation_id)
+        .with_expiry_days(request.expiry_days)
+        .with_initiated_by(initiated_by(api_key, request.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn revoke_organization_invitation(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, organization_id, invitation_id)): Path<(i64, i64, i64)>,
+    QueryParams(query_params): QueryParams<RemoveMemberQueryParams>,
+) -> ApiResult<OrganizationInvitation> {
+    RevokeOrganizationInvitationCommand::new(deployment_id, organization_id, invitation_id)
+        .with_initiated_by(initiated_by(api_key, query_params.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn accept_organization_invitation(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<AcceptOrganizationInvitationRequest>,
+) -> ApiResult<OrganizationMemberDetails> {
+    AcceptOrganizationInvitationCommand::new(deployment_id, request)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+// Workspace Member Management
+
+pub async fn get_workspace_members(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workspace_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    QueryParams(query_params): QueryParams<WorkspaceMemberListQueryParams>,
+) -> ApiResult<PaginatedResponse<WorkspaceMemberDetails>> {
+    let members = ListWorkspaceMembersQuery::new(deployment_id, workspace_id)
+        .role_id(query_params.role_id)
+        .search(query_params.search)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(members, &pagination).into())
+}
+
+pub async fn add_workspace_member(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, workspace_id)): Path<(i64, i64)>,
+    Validated(request): Validated<AddWorkspaceMemberRequest>,
+) -> ApiResult<WorkspaceMemberDetails> {
+    AddWorkspaceMemberCommand::new(
         deployment_id,
-        organization_id,
-        role_id,
-        request.name,
-        request.permissions,
+        workspace_id,
+        request.user_id,
+        request.role_ids,
     )
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
     .execute(&app_state)
     .await
     .map(Into::into)
     .map_err(Into::into)
 }
 
+pub async fn update_workspace_member(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, workspace_id, membership_id)): Path<(i64, i64, i64)>,
+    Validated(request): Validated<UpdateWorkspaceMemberRequest>,
+) -> ApiResult<WorkspaceMemberDetails> {
+    UpdateWorkspaceMemberRoleCommand::new(
+        deployment_id,
+        workspace_id,
+        membership_id,
+        request.role_ids,
+    )
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
+    .execute(&app_state)
+    .await
+    .map(Into::into)
+    .map_err(Into::into)
+}
+
+pub async fn remove_workspace_member(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, workspace_id, membership_id)): Path<(i64, i64, i64)>,
+    QueryParams(query_params): QueryParams<RemoveMemberQueryParams>,
+) -> ApiResult<WorkspaceMemberDetails> {
+    RemoveWorkspaceMemberCommand::new(deployment_id, workspace_id, membership_id)
+        .with_initiated_by(initiated_by(api_key, query_params.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+// Organization Role Management
+
+pub async fn create_organization_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, organization_id)): Path<(i64, i64)>,
+    Validated(request): Validated<CreateOrganizationRoleRequest>,
+) -> ApiResult<OrganizationRole> {
+    CreateOrganizationRoleCommand::new(deployment_id, request.name, request.permissions)
+        .with_organization_id(organization_id)
+        .execute(&app_state)
+        .await
+        .map(OrganizationRole::from)
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_organization_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, organization_id, role_id)): Path<(i64, i64, i64)>,
+    Validated(request): Validated<UpdateOrganizationRoleRequest>,
+) -> ApiResult<OrganizationRole> {
+    UpdateOrganizationRoleCommand::new(deployment_id, role_id, request.name, request.permissions)
+        .with_organization_id(organization_id)
+        .with_notify(request.notify.unwrap_or(false))
+        .execute(&app_state)
+        .await
+        .map(|update| OrganizationRole::from(update.role))
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn simulate_organization_role_change(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, organization_id, role_id)): Path<(i64, i64, i64)>,
+    Validated(request): Validated<SimulatePermissionChangeRequest>,
+) -> ApiResult<RolePermissionChangeReport> {
+    SimulatePermissionChangeQuery::new(deployment_id, role_id, request.permissions)
+        .with_organization_id(organization_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
 pub async fn delete_organization_role(
     State(app_state): State<HttpState>,
     Path((deployment_id, organization_id, role_id)): Path<(i64, i64, i64)>,
+    QueryParams(query_params): QueryParams<DeleteRoleQueryParams>,
+) -> ApiResult<()> {
+    DeleteOrganizationRoleCommand::new(deployment_id, role_id)
+        .with_organization_id(organization_id)
+        .with_reassign_to(query_params.reassign_to_role_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+// Workspace Role Management
+
+pub async fn create_workspace_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workspace_id)): Path<(i64, i64)>,
+    Validated(request): Validated<CreateWorkspaceRoleRequest>,
+) -> ApiResult<WorkspaceRole> {
+    CreateWorkspaceRoleCommand::new(deployment_id, request.name, request.permissions)
+        .with_workspace_id(workspace_id)
+        .execute(&app_state)
+        .await
+        .map(WorkspaceRole::from)
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_workspace_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workspace_id, role_id)): Path<(i64, i64, i64)>,
+    Validated(request): Validated<UpdateWorkspaceRoleRequest>,
+) -> ApiResult<WorkspaceRole> {
+    UpdateWorkspaceRoleCommand::new(deployment_id, role_id, request.name, request.permissions)
+        .with_workspace_id(workspace_id)
+        .execute(&app_state)
+        .await
+        .map(WorkspaceRole::from)
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn delete_workspace_role(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, workspace_id, role_id)): Path<(i64, i64, i64)>,
+    QueryParams(query_params): QueryParams<DeleteRoleQueryParams>,
 ) -> ApiResult<()> {
-    DeleteOrganizationRoleCommand::new(deployment_id, organization_id, role_id)
+    DeleteWorkspaceRoleCommand::new(deployment_id, role_id)
+        .with_workspace_id(workspace_id)
+        .with_reassign_to(query_params.reassign_to_role_id)
         .execute(&app_state)
         .await
         .map(Into::into)
@@ -1,25 +1,59 @@
 use crate::{
     application::{
         HttpState,
-        response::{ApiResult, ApiSuccess, PaginatedResponse},
+        api_key::{ApiKeyAuth, initiated_by, require_scope},
+        response::{ApiErrorResponse, ApiResult, ApiSuccess, PaginatedResponse},
+        validation::Validated,
     },
     core::{
-        commands::{Command, UpsertDeploymentSocialConnectionCommand},
-        dto::json::DeploymentSocialConnectionUpsert,
-        models::DeploymentSocialConnection,
-        queries::{Query, deployment::GetDeploymentSocialConnectionsQuery},
+        commands::{
+            Command, CreateSsoConnectionCommand, DeleteDeploymentSocialConnectionCommand,
+            DeleteSsoConnectionCommand, RecordSecretsRevealedCommand, UpdateSsoConnectionCommand,
+            UpsertDeploymentSocialConnectionCommand,
+        },
+        dto::{
+            json::{
+                CreateSsoConnectionRequest, DeploymentSocialConnectionUpsert,
+                UpdateSsoConnectionRequest,
+            },
+            query::deployment::RevealSecretsQueryParams,
+        },
+        error::AppError,
+        models::{
+            DeploymentSocialConnection, DeploymentSsoConnection, SECRETS_READ_SCOPE,
+            SocialConnectionProvider,
+        },
+        queries::{
+            GenerateSpMetadataQuery, ListSsoConnectionsQuery, Query as QueryTrait,
+            deployment::GetDeploymentSocialConnectionsQuery,
+        },
     },
 };
 use axum::{
-    Json,
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
 };
 
+/// Client secrets come back masked. `?reveal=true` returns them in full,
+/// which API keys need the `secrets:read` scope for, and is audited.
 pub async fn get_deployment_social_connections(
     State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
     Path(deployment_id): Path<i64>,
+    Query(params): Query<RevealSecretsQueryParams>,
 ) -> ApiResult<PaginatedResponse<DeploymentSocialConnection>> {
+    let reveal = params.reveal.unwrap_or(false);
+    if reveal {
+        require_scope(api_key.as_ref(), SECRETS_READ_SCOPE)?;
+        RecordSecretsRevealedCommand::new(deployment_id, "social_connections")
+            .with_initiated_by(initiated_by(api_key, params.initiated_by))
+            .execute(&app_state)
+            .await?;
+    }
+
     GetDeploymentSocialConnectionsQuery::new(deployment_id)
+        .with_reveal_secrets(reveal)
         .execute(&app_state)
         .await
         .map(Into::<PaginatedResponse<_>>::into)
@@ -30,7 +64,7 @@ pub async fn get_deployment_social_connections(
 pub async fn upsert_deployment_social_connection(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(payload): Json<DeploymentSocialConnectionUpsert>,
+    Validated(payload): Validated<DeploymentSocialConnectionUpsert>,
 ) -> ApiResult<DeploymentSocialConnection> {
     UpsertDeploymentSocialConnectionCommand::new(deployment_id, payload)
         .execute(&app_state)
@@ -39,3 +73,84 @@ pub async fn upsert_deployment_social_connection(
         .map(ApiSuccess::from)
         .map_err(Into::into)
 }
+
+/// Blocked when the provider is the last way left to sign in to the
+/// deployment.
+pub async fn delete_deployment_social_connection(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, provider)): Path<(i64, String)>,
+) -> ApiResult<()> {
+    let provider = provider
+        .parse::<SocialConnectionProvider>()
+        .map_err(AppError::BadRequest)?;
+
+    DeleteDeploymentSocialConnectionCommand::new(deployment_id, provider)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_sso_connections(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<PaginatedResponse<DeploymentSsoConnection>> {
+    ListSsoConnectionsQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(PaginatedResponse::from)
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn create_sso_connection(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<CreateSsoConnectionRequest>,
+) -> ApiResult<DeploymentSsoConnection> {
+    CreateSsoConnectionCommand::new(deployment_id, request)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_sso_connection(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, connection_id)): Path<(i64, i64)>,
+    Validated(request): Validated<UpdateSsoConnectionRequest>,
+) -> ApiResult<DeploymentSsoConnection> {
+    UpdateSsoConnectionCommand::new(deployment_id, connection_id, request)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn delete_sso_connection(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, connection_id)): Path<(i64, i64)>,
+) -> ApiResult<()> {
+    DeleteSsoConnectionCommand::new(deployment_id, connection_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Served as a raw XML document so it can be downloaded and uploaded to the
+/// identity provider as-is.
+pub async fn get_sso_sp_metadata(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, connection_id)): Path<(i64, i64)>,
+) -> Result<Response, ApiErrorResponse> {
+    let metadata = GenerateSpMetadataQuery::new(deployment_id, connection_id)
+        .execute(&app_state)
+        .await?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/samlmetadata+xml")],
+        metadata,
+    )
+        .into_response())
+}
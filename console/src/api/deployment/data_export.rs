@@ -0,0 +1,55 @@
+use axum::extract::{Path, Query as QueryParams, State};
+
+use crate::{
+    application::{
+        HttpState,
+        api_key::{ApiKeyAuth, initiated_by},
+        response::{ApiResult, PaginatedResponse},
+        validation::ValidatedQuery,
+    },
+    core::{
+        commands::{Command, ExportDeploymentDataCommand},
+        dto::query::{DataExportQueryParams, Pagination},
+        models::DeploymentDataExport,
+        queries::{GetExportStatusQuery, ListExportsQuery, Query},
+    },
+};
+
+pub async fn export_deployment_data(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path(deployment_id): Path<i64>,
+    QueryParams(query_params): QueryParams<DataExportQueryParams>,
+) -> ApiResult<DeploymentDataExport> {
+    ExportDeploymentDataCommand::new(deployment_id)
+        .with_requested_by(initiated_by(api_key, query_params.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_data_exports(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+) -> ApiResult<PaginatedResponse<DeploymentDataExport>> {
+    let exports = ListExportsQuery::new(deployment_id)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(exports, &pagination).into())
+}
+
+pub async fn get_data_export(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, export_id)): Path<(i64, i64)>,
+) -> ApiResult<DeploymentDataExport> {
+    GetExportStatusQuery::new(deployment_id, export_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
@@ -5,6 +5,9 @@ pub mod ai_tools;
 pub mod ai_workflows;
 pub mod b2b;
 pub mod connection;
+pub mod data_export;
+pub mod request_logs;
 pub mod settings;
 pub mod upload;
+pub mod usage;
 pub mod user;
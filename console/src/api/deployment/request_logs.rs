@@ -0,0 +1,38 @@
+use axum::extract::{Path, Query as QueryParams, State};
+
+use crate::{
+    application::{HttpState, response::ApiResult},
+    core::{
+        dto::query::RequestLogQueryParams,
+        models::RequestLogPage,
+        queries::{ListRequestLogsQuery, Query, parse_status_class},
+    },
+};
+
+pub async fn get_request_logs(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    QueryParams(query_params): QueryParams<RequestLogQueryParams>,
+) -> ApiResult<RequestLogPage> {
+    let status_class = query_params
+        .status
+        .as_deref()
+        .map(parse_status_class)
+        .transpose()?;
+
+    let mut query = ListRequestLogsQuery::new(deployment_id)
+        .status_class(status_class)
+        .path(query_params.path)
+        .from(query_params.from)
+        .to(query_params.to)
+        .cursor(query_params.cursor);
+    if let Some(limit) = query_params.limit {
+        query = query.limit(limit);
+    }
+
+    query
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
@@ -1,34 +1,77 @@
 use crate::{
     application::{
-        HttpState,
+        AppError, HttpState,
+        api_key::{ApiKeyAuth, initiated_by, require_scope},
         response::{ApiResult, PaginatedResponse},
+        validation::{Validated, ValidatedQuery},
     },
     core::{
         commands::{
-            Command, CreateDeploymentJwtTemplateCommand, DeleteDeploymentJwtTemplateCommand,
-            UpdateDeploymentAuthSettingsCommand, UpdateDeploymentDisplaySettingsCommand,
-            UpdateDeploymentEmailTemplateCommand, UpdateDeploymentJwtTemplateCommand,
-            UpdateDeploymentRestrictionsCommand,
+            AddDeploymentDisposableDomainCommand, Command, CreateDeploymentApiKeyCommand,
+            CreateDeploymentJwtTemplateCommand, CreateScimTokenCommand,
+            DeleteDeploymentEmailProviderCommand, DeleteDeploymentJwtTemplateCommand,
+            DeleteDeploymentLogExportCommand, GetLogExportExternalIdCommand,
+            ImportDeploymentConfigCommand, ReconcileCustomHostnamesCommand,
+            RecordSecretsRevealedCommand, RefreshDisposableDomainsCommand,
+            RemoveDeploymentDisposableDomainCommand, ResetDeploymentEmailTemplateCommand,
+            RetryFailedEmailsCommand, RetryJobCommand, RevokeDeploymentApiKeyCommand,
+            RevokeDeploymentTokensCommand, RevokeScimTokenCommand, RevokeTokenCommand,
+            SendTestEmailCommand, SetDeploymentEmailProviderCommand, SetDeploymentLogExportCommand,
+            TestLogExportDestinationCommand, UpdateDeploymentAuthSettingsCommand,
+            UpdateDeploymentDisplaySettingsCommand, UpdateDeploymentEmailTemplateCommand,
+            UpdateDeploymentFeatureFlagsCommand, UpdateDeploymentJwtTemplateCommand,
+            UpdateDeploymentMaintenanceCommand, UpdateDeploymentRestrictionsCommand,
         },
         dto::{
             json::{
+                CreateDeploymentApiKeyRequest, CreateScimTokenRequest,
                 DeploymentAuthSettingsUpdates, DeploymentDisplaySettingsUpdates,
-                DeploymentRestrictionsUpdates, NewDeploymentJwtTemplate,
-                PartialDeploymentJwtTemplate,
+                DeploymentRestrictionsUpdates, DisposableDomainRequest,
+                ImportDeploymentConfigRequest, NewDeploymentJwtTemplate,
+                PartialDeploymentJwtTemplate, RenderJwtTemplateRequest,
+                ResetAllEmailTemplatesRequest, ResetEmailTemplateRequest,
+                RevokeDeploymentTokensRequest, RevokeTokenRequest, SendTestEmailRequest,
+                SetEmailProviderRequest, SetLogExportRequest, UpdateDeploymentFeatureFlagsRequest,
+                UpdateDeploymentMaintenanceRequest,
             },
             params::deployment::DeploymentNameParams,
+            query::{
+                Pagination,
+                deployment::{
+                    BackgroundJobQueryParams, EmailOutboxQueryParams,
+                    ExportDeploymentConfigQueryParams, FeatureFlagDeploymentsQueryParams,
+                    ReconcileCustomHostnamesQueryParams, RevealSecretsQueryParams,
+                    TokenRevocationStatusQueryParams, UpdateEmailTemplateQueryParams,
+                },
+            },
+        },
+        models::{
+            BackgroundJob, CreatedDeploymentApiKey, CreatedScimToken, CustomHostnameReconciliation,
+            DeploymentApiKey, DeploymentConfigBundle, DeploymentConfigImportResult,
+            DeploymentDisposableDomain, DeploymentEmailProvider, DeploymentFeatureFlagSettings,
+            DeploymentFlagValue, DeploymentJwtTemplate, DeploymentLogExport, DeploymentMaintenance,
+            DeploymentWithSettings, DisposableDomainDataset, DisposableDomainSummary,
+            EmailOutboxEntry, EmailRetryResult, EmailTemplate, FEATURE_FLAGS,
+            FeatureFlagDefinition, FlaggedJwtTemplate, GeoIpDatabaseInfo, LogExportDestinationTest,
+            LogExportExternalId, PhoneIntelligenceMetrics, RenderedJwtTemplate,
+            RestrictionCandidate, RestrictionDecision, RevokedToken, RevokedTokensBefore,
+            SECRETS_READ_SCOPE, ScimToken, TestEmailResult, TokenRevocationStatus, feature_flag,
         },
-        models::{DeploymentJwtTemplate, DeploymentWithSettings, EmailTemplate},
         queries::{
-            GetDeploymentEmailTemplateQuery, Query,
+            CheckTokenRevokedQuery, EvaluateRestrictionsQuery, ExportDeploymentConfigQuery,
+            GetDeploymentEmailProviderQuery, GetDeploymentEmailTemplateQuery,
+            GetDeploymentFeatureFlagsQuery, GetDeploymentLogExportQuery,
+            GetDeploymentMaintenanceQuery, GetDisposableDomainSummaryQuery,
+            ListBackgroundJobsQuery, ListDeploymentApiKeysQuery, ListDeploymentsWithFlagQuery,
+            ListEmailOutboxQuery, ListScimTokensQuery, Query as QueryTrait, RenderJwtTemplateQuery,
+            ValidateExistingJwtTemplatesQuery, ValidateRedirectUrlsQuery,
             deployment::{GetDeploymentJwtTemplatesQuery, GetDeploymentWithSettingsQuery},
         },
+        validators::RedirectUrlValidation,
     },
 };
-use axum::{
-    Json,
-    extract::{Path, State},
-};
+use axum::extract::{Path, Query, State};
+use chrono::DateTime;
 
 pub async fn get_deployment_with_settings(
     State(app_state): State<HttpState>,
@@ -44,7 +87,7 @@ pub async fn get_deployment_with_settings(
 pub async fn update_deployment_authetication_settings(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(settings): Json<DeploymentAuthSettingsUpdates>,
+    Validated(settings): Validated<DeploymentAuthSettingsUpdates>,
 ) -> ApiResult<()> {
     UpdateDeploymentAuthSettingsCommand::new(deployment_id, settings)
         .execute(&app_state)
@@ -56,7 +99,7 @@ pub async fn update_deployment_authetication_settings(
 pub async fn update_deployment_restrictions(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(updates): Json<DeploymentRestrictionsUpdates>,
+    Validated(updates): Validated<DeploymentRestrictionsUpdates>,
 ) -> ApiResult<()> {
     UpdateDeploymentRestrictionsCommand::new(deployment_id, updates)
         .execute(&app_state)
@@ -65,11 +108,293 @@ pub async fn update_deployment_restrictions(
         .map_err(Into::into)
 }
 
+/// Lets the dashboard check how a sign-up would be treated by the current
+/// allowlist and blocklist.
+pub async fn evaluate_deployment_restrictions(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(candidate): Validated<RestrictionCandidate>,
+) -> ApiResult<RestrictionDecision> {
+    EvaluateRestrictionsQuery::new(deployment_id, candidate)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_disposable_domains(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DisposableDomainSummary> {
+    GetDisposableDomainSummaryQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn add_disposable_domain(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<DisposableDomainRequest>,
+) -> ApiResult<DeploymentDisposableDomain> {
+    AddDeploymentDisposableDomainCommand::new(deployment_id, request.domain, request.kind)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn remove_disposable_domain(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, domain)): Path<(i64, String)>,
+) -> ApiResult<()> {
+    RemoveDeploymentDisposableDomainCommand::new(deployment_id, domain)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn revoke_token(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<RevokeTokenRequest>,
+) -> ApiResult<RevokedToken> {
+    RevokeTokenCommand::new(deployment_id, request.jti, request.expires_at)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Revokes every session token the deployment has issued so far.
+pub async fn revoke_deployment_tokens(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<RevokeDeploymentTokensRequest>,
+) -> ApiResult<RevokedTokensBefore> {
+    RevokeDeploymentTokensCommand::new(deployment_id)
+        .with_initiated_by(initiated_by(api_key, request.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Called by services verifying session tokens, once the signature and
+/// expiry check out.
+pub async fn get_token_revocation_status(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Query(params): Query<TokenRevocationStatusQueryParams>,
+) -> ApiResult<TokenRevocationStatus> {
+    let issued_at = DateTime::from_timestamp(params.issued_at, 0)
+        .ok_or_else(|| AppError::BadRequest("issued_at is out of range".to_string()))?;
+
+    CheckTokenRevokedQuery::new(deployment_id, params.jti, params.user_id, issued_at)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_scim_tokens(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<PaginatedResponse<ScimToken>> {
+    ListScimTokensQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(PaginatedResponse::from)
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// The response carries the token's secret, which can't be read again.
+pub async fn create_scim_token(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<CreateScimTokenRequest>,
+) -> ApiResult<CreatedScimToken> {
+    CreateScimTokenCommand::new(deployment_id, request.name)
+        .with_created_by(request.initiated_by)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn revoke_scim_token(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, token_id)): Path<(i64, i64)>,
+) -> ApiResult<ScimToken> {
+    RevokeScimTokenCommand::new(deployment_id, token_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_api_keys(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<PaginatedResponse<DeploymentApiKey>> {
+    ListDeploymentApiKeysQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(PaginatedResponse::from)
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// The response carries the key's secret, which can't be read again.
+pub async fn create_api_key(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<CreateDeploymentApiKeyRequest>,
+) -> ApiResult<CreatedDeploymentApiKey> {
+    CreateDeploymentApiKeyCommand::new(deployment_id, request.name, request.scopes)
+        .with_expires_at(request.expires_at)
+        .with_created_by(request.initiated_by)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn revoke_api_key(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, key_id)): Path<(i64, i64)>,
+) -> ApiResult<DeploymentApiKey> {
+    RevokeDeploymentApiKeyCommand::new(deployment_id, key_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn refresh_disposable_domains(
+    State(app_state): State<HttpState>,
+) -> ApiResult<DisposableDomainDataset> {
+    RefreshDisposableDomainsCommand::new()
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Custom hostnames in the Cloudflare zone that no deployment uses. Pass
+/// `delete=true` to delete them as well.
+pub async fn reconcile_custom_hostnames(
+    State(app_state): State<HttpState>,
+    Query(params): Query<ReconcileCustomHostnamesQueryParams>,
+) -> ApiResult<CustomHostnameReconciliation> {
+    ReconcileCustomHostnamesCommand::new()
+        .with_delete_orphans(params.delete)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Background jobs across every deployment, newest first.
+pub async fn get_background_jobs(
+    State(app_state): State<HttpState>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    Query(params): Query<BackgroundJobQueryParams>,
+) -> ApiResult<PaginatedResponse<BackgroundJob>> {
+    let jobs = ListBackgroundJobsQuery::new()
+        .status(params.status)
+        .job_type(params.job_type)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(jobs, &pagination).into())
+}
+
+pub async fn retry_background_job(
+    State(app_state): State<HttpState>,
+    Path(job_id): Path<i64>,
+) -> ApiResult<BackgroundJob> {
+    RetryJobCommand::new(job_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Every feature flag deployments can be given, with its default.
+pub async fn get_feature_flag_definitions() -> ApiResult<PaginatedResponse<FeatureFlagDefinition>> {
+    Ok(PaginatedResponse::from(FEATURE_FLAGS.to_vec()).into())
+}
+
+/// Deployments a flag has the given value for, to follow a rollout.
+pub async fn list_deployments_with_flag(
+    State(app_state): State<HttpState>,
+    Path(flag): Path<String>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    Query(params): Query<FeatureFlagDeploymentsQueryParams>,
+) -> ApiResult<PaginatedResponse<DeploymentFlagValue>> {
+    let definition = feature_flag(&flag)
+        .ok_or_else(|| AppError::NotFound(format!("There is no feature flag named {}", flag)))?;
+    let value = definition.kind.parse(&params.value).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "{} takes {} values",
+            flag,
+            definition.kind.as_str()
+        ))
+    })?;
+    let deployments = ListDeploymentsWithFlagQuery::new(flag, value)
+        .with_limit(pagination.fetch_limit())
+        .with_offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(deployments, &pagination).into())
+}
+
+/// Counters since this instance started, for keeping an eye on lookup spend.
+pub async fn get_phone_intelligence_metrics(
+    State(app_state): State<HttpState>,
+) -> ApiResult<PhoneIntelligenceMetrics> {
+    Ok(app_state.phone_intelligence_service.metrics().into())
+}
+
+/// Re-reads the GeoIP database on this instance without waiting for the file
+/// watcher to notice a change.
+pub async fn reload_geoip_database(
+    State(app_state): State<HttpState>,
+) -> ApiResult<GeoIpDatabaseInfo> {
+    app_state
+        .geoip_service
+        .reload()
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Custom signing keys come back masked. `?reveal=true` returns them in
+/// full, which API keys need the `secrets:read` scope for, and is audited.
 pub async fn get_deployment_jwt_templates(
     State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
     Path(deployment_id): Path<i64>,
+    Query(params): Query<RevealSecretsQueryParams>,
 ) -> ApiResult<PaginatedResponse<DeploymentJwtTemplate>> {
+    let reveal = params.reveal.unwrap_or(false);
+    if reveal {
+        require_scope(api_key.as_ref(), SECRETS_READ_SCOPE)?;
+        RecordSecretsRevealedCommand::new(deployment_id, "jwt_templates")
+            .with_initiated_by(initiated_by(api_key, params.initiated_by))
+            .execute(&app_state)
+            .await?;
+    }
+
     GetDeploymentJwtTemplatesQuery::new(deployment_id)
+        .with_reveal_secrets(reveal)
         .execute(&app_state)
         .await
         .map(PaginatedResponse::from)
@@ -77,10 +402,35 @@ pub async fn get_deployment_jwt_templates(
         .map_err(Into::into)
 }
 
+/// The deployment's flags merged with the platform defaults, and the ones set
+/// for it.
+pub async fn get_deployment_feature_flags(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentFeatureFlagSettings> {
+    GetDeploymentFeatureFlagsQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_deployment_feature_flags(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<UpdateDeploymentFeatureFlagsRequest>,
+) -> ApiResult<DeploymentFeatureFlagSettings> {
+    UpdateDeploymentFeatureFlagsCommand::new(deployment_id, request.flags)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
 pub async fn create_deployment_jwt_template(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(template): Json<NewDeploymentJwtTemplate>,
+    Validated(template): Validated<NewDeploymentJwtTemplate>,
 ) -> ApiResult<DeploymentJwtTemplate> {
     CreateDeploymentJwtTemplateCommand::new(deployment_id, template)
         .execute(&app_state)
@@ -92,7 +442,7 @@ pub async fn create_deployment_jwt_template(
 pub async fn update_deployment_jwt_template(
     State(app_state): State<HttpState>,
     Path((_, id)): Path<(i64, i64)>,
-    Json(template): Json<PartialDeploymentJwtTemplate>,
+    Validated(template): Validated<PartialDeploymentJwtTemplate>,
 ) -> ApiResult<DeploymentJwtTemplate> {
     UpdateDeploymentJwtTemplateCommand::new(id, template)
         .execute(&app_state)
@@ -101,6 +451,33 @@ pub async fn update_deployment_jwt_template(
         .map_err(Into::into)
 }
 
+/// Templates of the deployment that wouldn't pass the checks they get on
+/// save.
+pub async fn validate_deployment_jwt_templates(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<PaginatedResponse<FlaggedJwtTemplate>> {
+    ValidateExistingJwtTemplatesQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(PaginatedResponse::from)
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Signs a test token from the template for an existing or made-up user.
+pub async fn render_deployment_jwt_template(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, id)): Path<(i64, i64)>,
+    Validated(request): Validated<RenderJwtTemplateRequest>,
+) -> ApiResult<RenderedJwtTemplate> {
+    RenderJwtTemplateQuery::new(deployment_id, id, request)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
 pub async fn delete_deployment_jwt_template(
     State(app_state): State<HttpState>,
     Path((_, id)): Path<(i64, i64)>,
@@ -115,7 +492,7 @@ pub async fn delete_deployment_jwt_template(
 pub async fn update_deployment_ui_settings(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(settings): Json<DeploymentDisplaySettingsUpdates>,
+    Validated(settings): Validated<DeploymentDisplaySettingsUpdates>,
 ) -> ApiResult<()> {
     UpdateDeploymentDisplaySettingsCommand::new(deployment_id, settings)
         .execute(&app_state)
@@ -124,6 +501,19 @@ pub async fn update_deployment_ui_settings(
         .map_err(Into::into)
 }
 
+/// Lists the stored redirect settings that the deployment's redirect policy
+/// would reject if they were saved now.
+pub async fn validate_deployment_redirect_urls(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<RedirectUrlValidation> {
+    ValidateRedirectUrlsQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
 pub async fn get_email_template(
     State(app_state): State<HttpState>,
     Path((deployment_id, template_name)): Path<(i64, DeploymentNameParams)>,
@@ -138,9 +528,244 @@ pub async fn get_email_template(
 pub async fn update_email_template(
     State(app_state): State<HttpState>,
     Path((deployment_id, template_name)): Path<(i64, DeploymentNameParams)>,
-    Json(template): Json<EmailTemplate>,
+    Query(params): Query<UpdateEmailTemplateQueryParams>,
+    Validated(template): Validated<EmailTemplate>,
 ) -> ApiResult<EmailTemplate> {
     UpdateDeploymentEmailTemplateCommand::new(deployment_id, template_name, template)
+        .with_allow_warnings(params.allow_warnings.unwrap_or(false))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn reset_email_template(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, template_name)): Path<(i64, DeploymentNameParams)>,
+    Validated(request): Validated<ResetEmailTemplateRequest>,
+) -> ApiResult<EmailTemplate> {
+    let template = ResetDeploymentEmailTemplateCommand::new(deployment_id, template_name)
+        .with_initiated_by(request.initiated_by)
+        .execute(&app_state)
+        .await?
+        .pop()
+        .ok_or_else(|| AppError::Internal("No email template was reset".to_string()))?;
+
+    Ok(template.into())
+}
+
+pub async fn reset_all_email_templates(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<ResetAllEmailTemplatesRequest>,
+) -> ApiResult<Vec<EmailTemplate>> {
+    ResetDeploymentEmailTemplateCommand::all(deployment_id)
+        .with_initiated_by(request.initiated_by)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_email_provider(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentEmailProvider> {
+    GetDeploymentEmailProviderQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Fails with `email_provider_verification_failed` when the provider turns
+/// the credentials down.
+pub async fn set_email_provider(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<SetEmailProviderRequest>,
+) -> ApiResult<DeploymentEmailProvider> {
+    SetDeploymentEmailProviderCommand::new(deployment_id, request.credentials)
+        .with_from_address(request.from_address)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn delete_email_provider(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentEmailProvider> {
+    DeleteDeploymentEmailProviderCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn send_test_email(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<SendTestEmailRequest>,
+) -> ApiResult<TestEmailResult> {
+    SendTestEmailCommand::new(deployment_id, request.to_address)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_maintenance(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentMaintenance> {
+    GetDeploymentMaintenanceQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// While maintenance is on, or inside the scheduled window, end-user routes
+/// answer 503 with the message and the estimated end.
+pub async fn update_maintenance(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<UpdateDeploymentMaintenanceRequest>,
+) -> ApiResult<DeploymentMaintenance> {
+    UpdateDeploymentMaintenanceCommand::new(
+        deployment_id,
+        DeploymentMaintenance {
+            maintenance_mode: request.maintenance_mode,
+            message: request.message,
+            starts_at: request.starts_at,
+            ends_at: request.ends_at,
+        },
+    )
+    .with_initiated_by(initiated_by(api_key, request.initiated_by))
+    .execute(&app_state)
+    .await
+    .map(Into::into)
+    .map_err(Into::into)
+}
+
+pub async fn get_log_export(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentLogExport> {
+    GetDeploymentLogExportQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// The external ID a role assumed for the log export has to require in its
+/// trust policy. Fetch it before setting up an `assume_role` export.
+pub async fn get_log_export_external_id(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<LogExportExternalId> {
+    GetLogExportExternalIdCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Fails with `log_export_destination_failed` when the bucket can't be
+/// written to with the credentials.
+pub async fn set_log_export(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<SetLogExportRequest>,
+) -> ApiResult<DeploymentLogExport> {
+    SetDeploymentLogExportCommand::new(
+        deployment_id,
+        request.bucket,
+        request.region,
+        request.credentials,
+    )
+    .with_prefix(request.prefix)
+    .with_format(request.format)
+    .with_schedule(request.schedule)
+    .execute(&app_state)
+    .await
+    .map(Into::into)
+    .map_err(Into::into)
+}
+
+pub async fn delete_log_export(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentLogExport> {
+    DeleteDeploymentLogExportCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn test_log_export_destination(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<LogExportDestinationTest> {
+    TestLogExportDestinationCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_email_outbox(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    Query(params): Query<EmailOutboxQueryParams>,
+) -> ApiResult<PaginatedResponse<EmailOutboxEntry>> {
+    let entries = ListEmailOutboxQuery::new(deployment_id)
+        .status(params.status)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(entries, &pagination).into())
+}
+
+pub async fn retry_failed_emails(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<EmailRetryResult> {
+    RetryFailedEmailsCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn export_deployment_config(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Query(params): Query<ExportDeploymentConfigQueryParams>,
+) -> ApiResult<DeploymentConfigBundle> {
+    ExportDeploymentConfigQuery::new(deployment_id)
+        .with_include_secrets(params.include_secrets.unwrap_or(false))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn import_deployment_config(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<ImportDeploymentConfigRequest>,
+) -> ApiResult<DeploymentConfigImportResult> {
+    ImportDeploymentConfigCommand::new(deployment_id, request.bundle)
+        .with_dry_run(request.dry_run)
         .execute(&app_state)
         .await
         .map(Into::into)
@@ -4,125 +4,71 @@ use axum::{
 };
 
 use crate::{
-    application::{HttpState, response::ApiResult},
+    application::{HttpState, response::ApiResult, validation::Validated},
     core::{
-        commands::{Command, UpdateDeploymentDisplaySettingsCommand, UploadToCdnCommand},
-        dto::json::{DeploymentDisplaySettingsUpdates, UploadResult},
+        commands::{
+            Command, ConfirmUploadCommand, CreateSignedUploadUrlCommand,
+            UploadDeploymentAssetCommand,
+        },
+        dto::json::{CreateSignedUploadRequest, UploadResult},
+        models::{DeploymentAssetKind, SignedUpload, StorageUpload},
     },
 };
 
 pub async fn upload_image(
     State(app_state): State<HttpState>,
-    Path((deployment_id, image_type)): Path<(i64, String)>,
+    Path((deployment_id, kind)): Path<(i64, String)>,
     mut multipart: Multipart,
 ) -> ApiResult<UploadResult> {
-    let mut image_buffer: Vec<u8> = Vec::new();
-    let mut file_extension = String::from("png");
+    let kind: DeploymentAssetKind = kind
+        .parse()
+        .map_err(|e: String| (StatusCode::BAD_REQUEST, e))?;
 
-    let mut updates = DeploymentDisplaySettingsUpdates::default();
-
-    while let Some(field) = multipart
+    let field = multipart
         .next_field()
         .await
         .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
-    {
-        let content_type = field.content_type().unwrap_or_default().to_string();
-
-        if !content_type.starts_with("image/") {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                "Invalid file type. Only images are allowed.".to_string(),
-            )
-                .into());
-        }
-
-        if content_type == "image/jpeg" || content_type == "image/jpg" {
-            file_extension = String::from("jpg");
-        } else if content_type == "image/png" {
-            file_extension = String::from("png");
-        } else if content_type == "image/gif" {
-            file_extension = String::from("gif");
-        } else if content_type == "image/webp" {
-            file_extension = String::from("webp");
-        } else if content_type == "image/x-icon" || content_type == "image/vnd.microsoft.icon" {
-            file_extension = String::from("ico");
-        } else {
-            return Err((
+        .ok_or_else(|| {
+            (
                 StatusCode::BAD_REQUEST,
-                "Unsupported image format. Supported formats: JPEG, PNG, GIF, WEBP, ICO"
-                    .to_string(),
+                "No image data provided".to_string(),
             )
-                .into());
-        }
-
-        image_buffer = field
-            .bytes()
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-            .to_vec();
-    }
+        })?;
+    let content_type = field.content_type().unwrap_or_default().to_string();
+    let body = field
+        .bytes()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+        .to_vec();
 
-    if image_buffer.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "No image data provided".to_string(),
-        )
-            .into());
-    }
+    let url = UploadDeploymentAssetCommand::new(deployment_id, kind, content_type, body)
+        .execute(&app_state)
+        .await?;
 
-    let file_path = match image_type.as_str() {
-        "logo" => {
-            updates.logo_image_url = Some(format!(
-                "https://cdn.wacht.services/deployments/{}/logo.{}",
-                deployment_id, file_extension
-            ));
-            format!("deployments/{}/logo.{}", deployment_id, file_extension)
-        }
-        "favicon" => {
-            updates.favicon_image_url = Some(format!(
-                "https://cdn.wacht.services/deployments/{}/favicon.{}",
-                deployment_id, file_extension
-            ));
-            format!("deployments/{}/favicon.{}", deployment_id, file_extension)
-        }
-        "user-profile" => {
-            updates.default_user_profile_image_url = Some(format!(
-                "https://cdn.wacht.services/deployments/{}/user-profile.{}",
-                deployment_id, file_extension
-            ));
-            format!(
-                "deployments/{}/user-profile.{}",
-                deployment_id, file_extension
-            )
-        }
-        "org-profile" => {
-            updates.default_organization_profile_image_url = Some(format!(
-                "https://cdn.wacht.services/deployments/{}/org-profile.{}",
-                deployment_id, file_extension
-            ));
-            format!(
-                "deployments/{}/org-profile.{}",
-                deployment_id, file_extension
-            )
-        }
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                "Invalid image type. Allowed types: logo, favicon, user-profile, org-profile"
-                    .to_string(),
-            )
-                .into());
-        }
-    };
+    Ok(UploadResult { url }.into())
+}
 
-    let url = UploadToCdnCommand::new(file_path, image_buffer)
+/// Upload the file with the returned method, URL and headers, then confirm
+/// it before using its URL anywhere.
+pub async fn create_signed_upload(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<CreateSignedUploadRequest>,
+) -> ApiResult<SignedUpload> {
+    CreateSignedUploadUrlCommand::new(deployment_id, request)
         .execute(&app_state)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map(Into::into)
+        .map_err(Into::into)
+}
 
-    UpdateDeploymentDisplaySettingsCommand::new(deployment_id, updates)
+pub async fn confirm_upload(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, upload_id)): Path<(i64, i64)>,
+) -> ApiResult<StorageUpload> {
+    ConfirmUploadCommand::new(deployment_id, upload_id)
         .execute(&app_state)
-        .await?;
-
-    Ok(UploadResult { url }.into())
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
 }
@@ -0,0 +1,31 @@
+use axum::extract::{Path, Query as QueryParams, State};
+use chrono::Utc;
+
+use crate::{
+    application::{HttpState, response::ApiResult},
+    core::{
+        dto::query::UsageQueryParams,
+        error::AppError,
+        models::{BillingPeriod, DeploymentUsage},
+        queries::{GetDeploymentUsageQuery, Query},
+    },
+};
+
+pub async fn get_deployment_usage(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    QueryParams(query_params): QueryParams<UsageQueryParams>,
+) -> ApiResult<DeploymentUsage> {
+    let period = match query_params.month.as_deref() {
+        Some(month) => BillingPeriod::parse(month).ok_or_else(|| {
+            AppError::BadRequest("month must be formatted as YYYY-MM".to_string())
+        })?,
+        None => BillingPeriod::containing(Utc::now()),
+    };
+
+    GetDeploymentUsageQuery::new(deployment_id, period)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
@@ -1,119 +1,139 @@
 use crate::{
     application::{
         HttpState,
+        api_key::{ApiKeyAuth, initiated_by},
         response::{ApiResult, PaginatedResponse},
+        validation::{Validated, ValidatedQuery},
     },
     core::{
         commands::{
-            AddUserEmailCommand, AddUserPhoneCommand, ApproveWaitlistUserCommand, Command,
-            CreateUserCommand, DeleteUserEmailCommand, DeleteUserPhoneCommand,
-            DeleteUserSocialConnectionCommand, InviteUserCommand, UpdateUserCommand,
-            UpdateUserEmailCommand, UpdateUserPhoneCommand,
+            AcceptInvitationCommand, AddUserEmailCommand, AddUserPhoneCommand,
+            AddUserWeb3WalletCommand, ApproveWaitlistEntryCommand,
+            BulkApproveWaitlistEntriesCommand, ChangeUserPasswordCommand, Command,
+            ConsumeMagicLinkCommand, CreateMagicLinkCommand, CreateUserCommand, DeleteUserCommand,
+            DeleteUserEmailCommand, DeleteUserPhoneCommand, DeleteUserSocialConnectionCommand,
+            DeleteUserWeb3WalletCommand, EraseUserCommand, InviteUserCommand,
+            IssueWeb3ChallengeCommand, JoinWaitlistCommand, RejectWaitlistEntryCommand,
+            ResetUserPasswordCommand, RestoreUserCommand, RevokeInvitationCommand,
+            RevokeUserSessionCommand, RevokeUserSessionsCommand, SetRequirePasswordChangeCommand,
+            UnlockUserCommand, UpdateUserCommand, UpdateUserEmailCommand, UpdateUserPhoneCommand,
+            VerifyWeb3SignatureCommand,
         },
         dto::{
             json::{
-                AddEmailRequest, AddPhoneRequest, CreateUserRequest, InviteUserRequest,
-                UpdateEmailRequest, UpdatePhoneRequest, UpdateUserRequest,
+                AcceptInvitationRequest, AddEmailRequest, AddPhoneRequest, AddWeb3WalletRequest,
+                BulkApproveWaitlistRequest, ConsumeMagicLinkRequest, CreateMagicLinkRequest,
+                CreateUserRequest, EraseUserRequest, InviteUserRequest, JoinWaitlistRequest,
+                ResetUserPasswordRequest, UpdateEmailRequest, UpdatePasswordRequirementRequest,
+                UpdatePhoneRequest, UpdateUserPasswordRequest, UpdateUserRequest,
+                VerifyWeb3SignatureRequest, Web3ChallengeRequest,
+            },
+            query::{
+                InvitationSortColumns, Pagination, SortParams, UserLookupQueryParams,
+                UserSessionsQueryParams, UserSortColumns, UsernameAvailabilityQueryParams,
+                WaitlistQueryParams,
             },
-            query::{ActiveUserListQueryParams, InvitationsWaitlistQueryParams},
         },
         models::{
-            DeploymentInvitation, DeploymentWaitlistUser, UserDetails, UserEmailAddress,
-            UserPhoneNumber, UserWithIdentifiers,
+            BulkWaitlistApproval, ConsumedMagicLink, DeploymentInvitation, DeploymentWaitlistUser,
+            DuplicateEmailAccounts, IssuedMagicLink, PasswordResetResult, RevokedUserSessions,
+            UserAuthorizationContext, UserDetails, UserEmailAddress, UserErasureReport, UserLookup,
+            UserPhoneNumber, UserSession, UserWeb3Wallet, UserWithIdentifiers,
+            UsernameAvailability, VerifiedWeb3Wallet, WaitlistApproval, Web3SignInChallenge,
         },
         queries::{
-            DeploymentActiveUserListQuery, DeploymentInvitationQuery, DeploymentWaitlistQuery,
-            GetUserDetailsQuery, Query,
+            CheckUsernameAvailabilityQuery, DeploymentActiveUserListQuery,
+            DeploymentInvitationQuery, FindDuplicateEmailAccountsQuery, FindUserByIdentifierQuery,
+            GetUserAuthorizationContextQuery, GetUserDetailsQuery, ListUserSessionsQuery,
+            ListWaitlistEntriesQuery, Query,
         },
     },
 };
-use axum::{
-    Json,
-    extract::{Path, Query as QueryParams, State},
-};
+use axum::extract::{Path, Query as QueryParams, State};
 
 pub async fn get_active_user_list(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    QueryParams(query_params): QueryParams<ActiveUserListQueryParams>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    ValidatedQuery(sort): ValidatedQuery<SortParams<UserSortColumns>>,
 ) -> ApiResult<PaginatedResponse<UserWithIdentifiers>> {
-    let limit = query_params.limit.unwrap_or(10) as i32;
-
     let users = DeploymentActiveUserListQuery::new(deployment_id)
-        .limit(limit + 1)
-        .offset(query_params.offset.unwrap_or(0))
-        .sort_key(query_params.sort_key.as_ref().map(ToString::to_string))
-        .sort_order(query_params.sort_order.as_ref().map(ToString::to_string))
+        .limit(pagination.fetch_limit() as i32)
+        .offset(pagination.offset())
+        .sort_key(Some(sort.key().to_string()))
+        .sort_order(Some(sort.order().to_string()))
         .execute(&app_state)
-        .await
-        .unwrap();
+        .await?;
 
-    let has_more = users.len() > limit as usize;
-    let users = if has_more {
-        users[..limit as usize].to_vec()
-    } else {
-        users
-    };
+    Ok(PaginatedResponse::page(users, &pagination).into())
+}
 
-    Ok(PaginatedResponse::from(users).into())
+pub async fn get_duplicate_email_accounts(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+) -> ApiResult<PaginatedResponse<DuplicateEmailAccounts>> {
+    let duplicates = FindDuplicateEmailAccountsQuery::new(deployment_id)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(duplicates, &pagination).into())
 }
 
 pub async fn get_invited_user_list(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    QueryParams(query_params): QueryParams<InvitationsWaitlistQueryParams>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    ValidatedQuery(sort): ValidatedQuery<SortParams<InvitationSortColumns>>,
 ) -> ApiResult<PaginatedResponse<DeploymentInvitation>> {
-    let limit = query_params.limit.unwrap_or(10) as i32;
-
-    let users = DeploymentInvitationQuery::new(deployment_id)
-        .limit(limit + 1)
-        .offset(query_params.offset.unwrap_or(0))
-        .sort_key(query_params.sort_key.as_ref().map(ToString::to_string))
-        .sort_order(query_params.sort_order.as_ref().map(ToString::to_string))
+    let invitations = DeploymentInvitationQuery::new(deployment_id)
+        .limit(pagination.fetch_limit() as i32)
+        .offset(pagination.offset())
+        .sort_key(Some(sort.key().to_string()))
+        .sort_order(Some(sort.order().to_string()))
         .execute(&app_state)
-        .await
-        .unwrap();
-
-    let has_more = users.len() > limit as usize;
-    let users = if has_more {
-        users[..limit as usize].to_vec()
-    } else {
-        users
-    };
+        .await?;
 
-    Ok(PaginatedResponse::from(users).into())
+    Ok(PaginatedResponse::page(invitations, &pagination).into())
 }
 
 pub async fn get_user_waitlist(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    QueryParams(query_params): QueryParams<InvitationsWaitlistQueryParams>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    ValidatedQuery(sort): ValidatedQuery<SortParams<InvitationSortColumns>>,
+    QueryParams(query_params): QueryParams<WaitlistQueryParams>,
 ) -> ApiResult<PaginatedResponse<DeploymentWaitlistUser>> {
-    let limit = query_params.limit.unwrap_or(10) as i32;
-
-    let users = DeploymentWaitlistQuery::new(deployment_id)
-        .limit(limit + 1)
-        .offset(query_params.offset.unwrap_or(0))
-        .sort_key(query_params.sort_key.as_ref().map(ToString::to_string))
-        .sort_order(query_params.sort_order.as_ref().map(ToString::to_string))
+    let entries = ListWaitlistEntriesQuery::new(deployment_id)
+        .status(query_params.status)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .sort_key(Some(sort.key().to_string()))
+        .sort_order(Some(sort.order().to_string()))
         .execute(&app_state)
-        .await
-        .unwrap();
+        .await?;
 
-    let has_more = users.len() > limit as usize;
-    let users = if has_more {
-        users[..limit as usize].to_vec()
-    } else {
-        users
-    };
+    Ok(PaginatedResponse::page(entries, &pagination).into())
+}
 
-    Ok(PaginatedResponse::from(users).into())
+pub async fn join_waitlist(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<JoinWaitlistRequest>,
+) -> ApiResult<DeploymentWaitlistUser> {
+    JoinWaitlistCommand::new(deployment_id, request)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
 }
 
 pub async fn create_user(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(request): Json<CreateUserRequest>,
+    Validated(request): Validated<CreateUserRequest>,
 ) -> ApiResult<UserWithIdentifiers> {
     let user = CreateUserCommand::new(deployment_id, request)
         .execute(&app_state)
@@ -133,10 +153,49 @@ pub async fn get_user_details(
     Ok(user_details.into())
 }
 
+/// What the user can do in their organizations and workspaces, for services
+/// making authorization decisions. Cached until a membership, role or b2b
+/// setting changes.
+pub async fn get_user_authorization(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+) -> ApiResult<UserAuthorizationContext> {
+    GetUserAuthorizationContextQuery::new(deployment_id, user_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn lookup_user(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    QueryParams(params): QueryParams<UserLookupQueryParams>,
+) -> ApiResult<UserLookup> {
+    let lookup = FindUserByIdentifierQuery::new(deployment_id, params.identifier)
+        .execute(&app_state)
+        .await?;
+
+    Ok(lookup.into())
+}
+
+/// Meant to be called as the user types, so it only reads.
+pub async fn check_username_availability(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    QueryParams(params): QueryParams<UsernameAvailabilityQueryParams>,
+) -> ApiResult<UsernameAvailability> {
+    let availability = CheckUsernameAvailabilityQuery::new(deployment_id, params.username)
+        .execute(&app_state)
+        .await?;
+
+    Ok(availability.into())
+}
+
 pub async fn invite_user(
     State(app_state): State<HttpState>,
     Path(deployment_id): Path<i64>,
-    Json(request): Json<InviteUserRequest>,
+    Validated(request): Validated<InviteUserRequest>,
 ) -> ApiResult<DeploymentInvitation> {
     let invitation = InviteUserCommand::new(deployment_id, request)
         .execute(&app_state)
@@ -145,21 +204,68 @@ pub async fn invite_user(
     Ok(invitation.into())
 }
 
+pub async fn accept_invitation(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<AcceptInvitationRequest>,
+) -> ApiResult<UserWithIdentifiers> {
+    let user = AcceptInvitationCommand::new(deployment_id, request)
+        .execute(&app_state)
+        .await?;
+
+    Ok(user.into())
+}
+
+pub async fn revoke_invitation(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, invitation_id)): Path<(i64, i64)>,
+) -> ApiResult<()> {
+    RevokeInvitationCommand::new(deployment_id, invitation_id)
+        .execute(&app_state)
+        .await?;
+
+    Ok(().into())
+}
+
 pub async fn approve_waitlist_user(
     State(app_state): State<HttpState>,
     Path((deployment_id, waitlist_user_id)): Path<(i64, i64)>,
-) -> ApiResult<DeploymentInvitation> {
-    let invitation = ApproveWaitlistUserCommand::new(deployment_id, waitlist_user_id)
+) -> ApiResult<WaitlistApproval> {
+    ApproveWaitlistEntryCommand::new(deployment_id, waitlist_user_id)
         .execute(&app_state)
-        .await?;
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
 
-    Ok(invitation.into())
+pub async fn bulk_approve_waitlist_users(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<BulkApproveWaitlistRequest>,
+) -> ApiResult<BulkWaitlistApproval> {
+    BulkApproveWaitlistEntriesCommand::new(deployment_id, request.ids)
+        .with_expiry_days(request.expiry_days)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn reject_waitlist_user(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, waitlist_user_id)): Path<(i64, i64)>,
+) -> ApiResult<DeploymentWaitlistUser> {
+    RejectWaitlistEntryCommand::new(deployment_id, waitlist_user_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
 }
 
 pub async fn update_user(
     State(app_state): State<HttpState>,
     Path((deployment_id, user_id)): Path<(i64, i64)>,
-    Json(request): Json<UpdateUserRequest>,
+    Validated(request): Validated<UpdateUserRequest>,
 ) -> ApiResult<UserDetails> {
     let user_details = UpdateUserCommand::new(deployment_id, user_id, request)
         .execute(&app_state)
@@ -171,7 +277,7 @@ pub async fn update_user(
 pub async fn add_user_email(
     State(app_state): State<HttpState>,
     Path((deployment_id, user_id)): Path<(i64, i64)>,
-    Json(request): Json<AddEmailRequest>,
+    Validated(request): Validated<AddEmailRequest>,
 ) -> ApiResult<UserEmailAddress> {
     let email = AddUserEmailCommand::new(deployment_id, user_id, request)
         .execute(&app_state)
@@ -183,7 +289,7 @@ pub async fn add_user_email(
 pub async fn update_user_email(
     State(app_state): State<HttpState>,
     Path((deployment_id, user_id, email_id)): Path<(i64, i64, i64)>,
-    Json(request): Json<UpdateEmailRequest>,
+    Validated(request): Validated<UpdateEmailRequest>,
 ) -> ApiResult<UserEmailAddress> {
     let email = UpdateUserEmailCommand::new(deployment_id, user_id, email_id, request)
         .execute(&app_state)
@@ -206,7 +312,7 @@ pub async fn delete_user_email(
 pub async fn add_user_phone(
     State(app_state): State<HttpState>,
     Path((deployment_id, user_id)): Path<(i64, i64)>,
-    Json(request): Json<AddPhoneRequest>,
+    Validated(request): Validated<AddPhoneRequest>,
 ) -> ApiResult<UserPhoneNumber> {
     let phone = AddUserPhoneCommand::new(deployment_id, user_id, request)
         .execute(&app_state)
@@ -219,7 +325,7 @@ pub async fn add_user_phone(
 pub async fn update_user_phone(
     State(app_state): State<HttpState>,
     Path((_, user_id, phone_id)): Path<(i64, i64, i64)>,
-    Json(request): Json<UpdatePhoneRequest>,
+    Validated(request): Validated<UpdatePhoneRequest>,
 ) -> ApiResult<UserPhoneNumber> {
     let phone = UpdateUserPhoneCommand::new(user_id, phone_id, request)
         .execute(&app_state)
@@ -239,6 +345,78 @@ pub async fn delete_user_phone(
     Ok(().into())
 }
 
+pub async fn add_user_web3_wallet(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+    Validated(request): Validated<AddWeb3WalletRequest>,
+) -> ApiResult<UserWeb3Wallet> {
+    let wallet = AddUserWeb3WalletCommand::new(deployment_id, user_id, request)
+        .execute(&app_state)
+        .await?;
+
+    Ok(wallet.into())
+}
+
+pub async fn delete_user_web3_wallet(
+    State(app_state): State<HttpState>,
+    Path((_, user_id, wallet_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<()> {
+    DeleteUserWeb3WalletCommand::new(user_id, wallet_id)
+        .execute(&app_state)
+        .await?;
+
+    Ok(().into())
+}
+
+pub async fn issue_web3_challenge(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<Web3ChallengeRequest>,
+) -> ApiResult<Web3SignInChallenge> {
+    let challenge = IssueWeb3ChallengeCommand::new(deployment_id, request)
+        .execute(&app_state)
+        .await?;
+
+    Ok(challenge.into())
+}
+
+pub async fn verify_web3_signature(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<VerifyWeb3SignatureRequest>,
+) -> ApiResult<VerifiedWeb3Wallet> {
+    let wallet = VerifyWeb3SignatureCommand::new(deployment_id, request)
+        .execute(&app_state)
+        .await?;
+
+    Ok(wallet.into())
+}
+
+pub async fn create_magic_link(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<CreateMagicLinkRequest>,
+) -> ApiResult<IssuedMagicLink> {
+    let link = CreateMagicLinkCommand::new(deployment_id, request.email_address)
+        .with_redirect_url(request.redirect_url)
+        .execute(&app_state)
+        .await?;
+
+    Ok(link.into())
+}
+
+pub async fn consume_magic_link(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<ConsumeMagicLinkRequest>,
+) -> ApiResult<ConsumedMagicLink> {
+    let link = ConsumeMagicLinkCommand::new(deployment_id, request.token)
+        .execute(&app_state)
+        .await?;
+
+    Ok(link.into())
+}
+
 pub async fn delete_user_social_connection(
     State(app_state): State<HttpState>,
     Path((_, user_id, connection_id)): Path<(i64, i64, i64)>,
@@ -249,3 +427,140 @@ pub async fn delete_user_social_connection(
 
     Ok(().into())
 }
+
+pub async fn get_user_sessions(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination>,
+    QueryParams(query_params): QueryParams<UserSessionsQueryParams>,
+) -> ApiResult<PaginatedResponse<UserSession>> {
+    let sessions = ListUserSessionsQuery::new(deployment_id, user_id)
+        .current_session_id(query_params.current_session_id)
+        .limit(pagination.fetch_limit())
+        .offset(pagination.offset())
+        .execute(&app_state)
+        .await?;
+
+    Ok(PaginatedResponse::page(sessions, &pagination).into())
+}
+
+pub async fn revoke_user_session(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, user_id, session_id)): Path<(i64, i64, i64)>,
+) -> ApiResult<()> {
+    RevokeUserSessionCommand::new(deployment_id, user_id, session_id)
+        .execute(&app_state)
+        .await?;
+
+    Ok(().into())
+}
+
+pub async fn revoke_user_sessions(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+    QueryParams(query_params): QueryParams<UserSessionsQueryParams>,
+) -> ApiResult<RevokedUserSessions> {
+    let mut command = RevokeUserSessionsCommand::new(deployment_id, user_id);
+    if let Some(current_session_id) = query_params.current_session_id {
+        command = command.with_current_session_id(current_session_id);
+    }
+
+    command
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn unlock_user(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+) -> ApiResult<()> {
+    UnlockUserCommand::new(deployment_id, user_id)
+        .execute(&app_state)
+        .await?;
+
+    Ok(().into())
+}
+
+pub async fn erase_user(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+    Validated(request): Validated<EraseUserRequest>,
+) -> ApiResult<UserErasureReport> {
+    EraseUserCommand::new(deployment_id, user_id, request.mode)
+        .with_initiated_by(initiated_by(api_key, request.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn delete_user(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+) -> ApiResult<UserDetails> {
+    DeleteUserCommand::new(deployment_id, user_id)
+        .with_initiated_by(initiated_by(api_key, None))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn restore_user(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+) -> ApiResult<UserDetails> {
+    RestoreUserCommand::new(deployment_id, user_id)
+        .with_initiated_by(initiated_by(api_key, None))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_user_password(
+    State(app_state): State<HttpState>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+    Validated(request): Validated<UpdateUserPasswordRequest>,
+) -> ApiResult<()> {
+    ChangeUserPasswordCommand::new(deployment_id, user_id, request.password)
+        .execute(&app_state)
+        .await?;
+
+    Ok(().into())
+}
+
+pub async fn reset_user_password(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+    Validated(request): Validated<ResetUserPasswordRequest>,
+) -> ApiResult<PasswordResetResult> {
+    ResetUserPasswordCommand::new(deployment_id, user_id)
+        .with_password(request.password)
+        .with_require_password_change(request.require_password_change)
+        .with_initiated_by(initiated_by(api_key, request.initiated_by))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_user_password_requirement(
+    State(app_state): State<HttpState>,
+    api_key: Option<ApiKeyAuth>,
+    Path((deployment_id, user_id)): Path<(i64, i64)>,
+    Validated(request): Validated<UpdatePasswordRequirementRequest>,
+) -> ApiResult<()> {
+    SetRequirePasswordChangeCommand::new(deployment_id, user_id, request.require_password_change)
+        .with_initiated_by(initiated_by(api_key, request.initiated_by))
+        .execute(&app_state)
+        .await?;
+
+    Ok(().into())
+}
@@ -1,7 +1,11 @@
+use axum::{extract::State, http::StatusCode};
 use serde_json::json;
 
-use crate::application::response::ApiResult;
+use crate::application::{HttpState, response::ApiResult};
+use crate::core::models::ReadinessReport;
 
+/// Liveness: the process is up and serving requests. Never touches a
+/// dependency, so a slow database can't get the pod restarted.
 pub async fn check() -> ApiResult<serde_json::Value> {
     Ok(json!({
         "status": "healthy",
@@ -9,3 +13,16 @@ pub async fn check() -> ApiResult<serde_json::Value> {
     })
     .into())
 }
+
+/// Readiness: 503 while a critical dependency is down, so the instance is
+/// taken out of rotation. A degraded instance stays ready.
+pub async fn ready(State(app_state): State<HttpState>) -> ApiResult<ReadinessReport> {
+    let report = app_state.health_service.readiness().await;
+    let status = if report.status.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    Ok((status, report).into())
+}
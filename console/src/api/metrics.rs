@@ -0,0 +1,16 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+use crate::application::{HttpState, response::ApiErrorResponse};
+use crate::core::metrics::METRICS;
+
+/// Prometheus scrape endpoint.
+pub async fn export(State(app_state): State<HttpState>) -> Result<Response, ApiErrorResponse> {
+    METRICS.observe_db_pool(&app_state.db_pool);
+
+    let body = METRICS.render()?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response())
+}
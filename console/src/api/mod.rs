@@ -1,4 +1,9 @@
+pub mod admin;
+pub mod ai_trigger;
 pub mod analytics;
+pub mod client;
 pub mod deployment;
 pub mod health;
+pub mod metrics;
 pub mod project;
+pub mod scim;
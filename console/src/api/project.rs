@@ -1,39 +1,106 @@
 use axum::{
-    extract::{Json, Multipart, Path, State},
-    http::StatusCode,
+    extract::{Multipart, Path, Query, State},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
 };
 
 use crate::{
     application::HttpState,
     core::{
         commands::{
-            Command, CreateProductionDeploymentCommand, CreateProjectWithStagingDeploymentCommand,
-            DeleteDeploymentCommand, DeleteProjectCommand, VerifyDeploymentDnsRecordsCommand,
+            AbortDeploymentDomainMigrationCommand, AddProjectCollaboratorCommand,
+            ChangeDeploymentDomainCommand, CloneDeploymentCommand, Command,
+            CreateProductionDeploymentCommand, CreateProjectWithStagingDeploymentCommand,
+            DeleteDeploymentCommand, DeleteProjectCommand, PromoteStagingToProductionCommand,
+            RemoveProjectCollaboratorCommand, RetryDeploymentProvisioningCommand,
+            RotateDkimKeyCommand, TransferProjectCommand, UpdateDeploymentMailSettingsCommand,
+            UpdateProjectCollaboratorRoleCommand, VerifyDeploymentDnsRecordsCommand,
+            VerifyDeploymentDomainMigrationCommand,
+        },
+        dto::{
+            json::project::{
+                AddProjectCollaboratorRequest, ChangeDeploymentDomainRequest,
+                CloneDeploymentRequest, CreateProductionDeploymentRequest,
+                PromoteDeploymentRequest, TransferProjectRequest,
+                UpdateDeploymentMailSettingsRequest, UpdateProjectCollaboratorRoleRequest,
+            },
+            query::deployment::ProjectListQueryParams,
+        },
+        models::{
+            AccountDashboard, Deployment, DeploymentDomainMigration, DeploymentMailSettings,
+            DeploymentPromotion, DeploymentProvisioning, DeploymentSummary, ProjectCollaborator,
+            ProjectTransfer, ProjectWithDeployments,
+        },
+        queries::{
+            GetAccountDashboardQuery, GetDeploymentMailSettingsQuery,
+            GetDeploymentProvisioningQuery, GetDomainMigrationStatusQuery,
+            GetProjectDeploymentsSummaryQuery, GetProjectsWithDeploymentQuery,
+            ListProjectCollaboratorsQuery, Query as QueryTrait,
         },
-        dto::json::project::CreateProductionDeploymentRequest,
-        models::{Deployment, ProjectWithDeployments},
-        queries::{GetProjectsWithDeploymentQuery, Query},
     },
 };
 
-use crate::application::response::{ApiResult, PaginatedResponse};
+use crate::application::{
+    collaborator::ConsoleAccountAuth,
+    response::{ApiErrorResponse, ApiResult, ApiSuccess, PaginatedResponse},
+    validation::Validated,
+};
 
+/// The projects the console account collaborates on. Filter with
+/// `verification_status` to see only deployments in that state.
 pub async fn get_projects(
     State(app_state): State<HttpState>,
+    account: Option<ConsoleAccountAuth>,
+    Query(params): Query<ProjectListQueryParams>,
 ) -> ApiResult<PaginatedResponse<ProjectWithDeployments>> {
-    let projects = GetProjectsWithDeploymentQuery::new(0)
+    let account_id = account.map_or(0, |account| account.account_id);
+    let projects = GetProjectsWithDeploymentQuery::new(account_id)
+        .with_verification_status(params.verification_status)
         .execute(&app_state)
         .await?;
 
-    Ok(PaginatedResponse {
-        data: projects,
-        has_more: false,
-    }
-    .into())
+    Ok(PaginatedResponse::from(projects).into())
+}
+
+/// Totals across every project the console account works on, for the
+/// console home page. The figures are cached for 30 seconds per account, and
+/// the browser may reuse the response for as long.
+pub async fn get_account_dashboard(
+    State(app_state): State<HttpState>,
+    account: ConsoleAccountAuth,
+) -> Result<Response, ApiErrorResponse> {
+    let dashboard: AccountDashboard = GetAccountDashboardQuery::new(account.account_id)
+        .execute(&app_state)
+        .await?;
+
+    Ok((
+        [(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, max-age=30"),
+        )],
+        ApiSuccess::from(dashboard),
+    )
+        .into_response())
+}
+
+/// User, organization and monthly active user counts for each deployment of
+/// the project. `monthly_active_users` is cached for a few minutes.
+pub async fn get_project_deployments_summary(
+    State(app_state): State<HttpState>,
+    Path(project_id): Path<i64>,
+) -> ApiResult<PaginatedResponse<DeploymentSummary>> {
+    GetProjectDeploymentsSummaryQuery::new(project_id)
+        .execute(&app_state)
+        .await
+        .map(PaginatedResponse::from)
+        .map(Into::into)
+        .map_err(Into::into)
 }
 
+/// The console account creating the project becomes its owner.
 pub async fn create_project(
     State(app_state): State<HttpState>,
+    account: Option<ConsoleAccountAuth>,
     mut multipart: Multipart,
 ) -> ApiResult<ProjectWithDeployments> {
     let mut name = String::new();
@@ -55,7 +122,7 @@ pub async fn create_project(
             name = String::from_utf8_lossy(&value).into();
         } else if field_name == "methods" {
             methods.push(val_str.into());
-        } else if field_name == "logo" && content_type == "image/png" {
+        } else if field_name == "logo" && content_type.starts_with("image/") {
             logo_buffer = value;
         }
     }
@@ -64,28 +131,31 @@ pub async fn create_project(
         return Err((StatusCode::BAD_REQUEST, "Name is required").into());
     }
 
-    CreateProjectWithStagingDeploymentCommand::new(name, logo_buffer, methods)
+    let mut command = CreateProjectWithStagingDeploymentCommand::new(name, logo_buffer, methods);
+    if let Some(account) = account {
+        command = command.with_owner(account.account_id);
+    }
+
+    command
         .execute(&app_state)
         .await
         .map(Into::into)
         .map_err(Into::into)
 }
 
+/// Returns as soon as the deployment is saved. Its hostnames and sending
+/// domain are provisioned in the background; poll the provisioning endpoint
+/// until `provisioning_status` is `ready`.
 pub async fn create_production_deployment(
     State(app_state): State<HttpState>,
     Path(project_id): Path<i64>,
-    Json(request): Json<CreateProductionDeploymentRequest>,
+    Validated(request): Validated<CreateProductionDeploymentRequest>,
 ) -> ApiResult<Deployment> {
-    let command = CreateProductionDeploymentCommand::new(
-        project_id,
-        request.custom_domain,
-        request.auth_methods,
-    )
-    .execute(&app_state)
-    .await
-    .unwrap();
-
-    Ok(command.into())
+    CreateProductionDeploymentCommand::new(project_id, request.custom_domain, request.auth_methods)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
 }
 
 pub async fn verify_deployment_dns_records(
@@ -99,6 +169,113 @@ pub async fn verify_deployment_dns_records(
         .map_err(Into::into)
 }
 
+pub async fn get_deployment_provisioning(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentProvisioning> {
+    GetDeploymentProvisioningQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn retry_deployment_provisioning(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentProvisioning> {
+    RetryDeploymentProvisioningCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_domain_migration(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentDomainMigration> {
+    GetDomainMigrationStatusQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Starts moving a production deployment to another custom domain. It keeps
+/// its current hosts until the new domain's records are verified through
+/// the verify endpoint.
+pub async fn change_deployment_domain(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<ChangeDeploymentDomainRequest>,
+) -> ApiResult<DeploymentDomainMigration> {
+    ChangeDeploymentDomainCommand::new(deployment_id, request.custom_domain)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn verify_domain_migration(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentDomainMigration> {
+    VerifyDeploymentDomainMigrationCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn abort_domain_migration(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentDomainMigration> {
+    AbortDeploymentDomainMigrationCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn get_deployment_mail_settings(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentMailSettings> {
+    GetDeploymentMailSettingsQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Moves the deployment's email to another subdomain of its custom domain.
+/// It keeps sending from the current host until the new one's records are
+/// verified through the verify-dns endpoint.
+pub async fn update_deployment_mail_settings(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<UpdateDeploymentMailSettingsRequest>,
+) -> ApiResult<DeploymentMailSettings> {
+    UpdateDeploymentMailSettingsCommand::new(deployment_id, request.mail_from_subdomain)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn rotate_deployment_dkim_key(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+) -> ApiResult<DeploymentMailSettings> {
+    RotateDkimKeyCommand::new(deployment_id)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
 pub async fn delete_project(
     State(app_state): State<HttpState>,
     Path(id): Path<i64>,
@@ -118,3 +295,101 @@ pub async fn delete_deployment(
 
     Ok(().into())
 }
+
+pub async fn transfer_project(
+    State(app_state): State<HttpState>,
+    Path(project_id): Path<i64>,
+    Validated(request): Validated<TransferProjectRequest>,
+) -> ApiResult<ProjectTransfer> {
+    TransferProjectCommand::new(project_id, request.target_owner_id)
+        .with_initiated_by(request.initiated_by)
+        .with_notify(request.notify)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Active collaborators and pending invitations, oldest first.
+pub async fn list_project_collaborators(
+    State(app_state): State<HttpState>,
+    Path(project_id): Path<i64>,
+) -> ApiResult<PaginatedResponse<ProjectCollaborator>> {
+    ListProjectCollaboratorsQuery::new(project_id)
+        .execute(&app_state)
+        .await
+        .map(PaginatedResponse::from)
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Emails an invitation when no console account uses the address yet.
+pub async fn add_project_collaborator(
+    State(app_state): State<HttpState>,
+    Path(project_id): Path<i64>,
+    account: Option<ConsoleAccountAuth>,
+    Validated(request): Validated<AddProjectCollaboratorRequest>,
+) -> ApiResult<ProjectCollaborator> {
+    AddProjectCollaboratorCommand::new(project_id, request.email, request.role)
+        .with_acting_account(account.map(|account| account.account_id))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn update_project_collaborator_role(
+    State(app_state): State<HttpState>,
+    Path((project_id, collaborator_id)): Path<(i64, i64)>,
+    account: Option<ConsoleAccountAuth>,
+    Validated(request): Validated<UpdateProjectCollaboratorRoleRequest>,
+) -> ApiResult<ProjectCollaborator> {
+    UpdateProjectCollaboratorRoleCommand::new(project_id, collaborator_id, request.role)
+        .with_acting_account(account.map(|account| account.account_id))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn remove_project_collaborator(
+    State(app_state): State<HttpState>,
+    Path((project_id, collaborator_id)): Path<(i64, i64)>,
+    account: Option<ConsoleAccountAuth>,
+) -> ApiResult<ProjectCollaborator> {
+    RemoveProjectCollaboratorCommand::new(project_id, collaborator_id)
+        .with_acting_account(account.map(|account| account.account_id))
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+pub async fn clone_deployment(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<CloneDeploymentRequest>,
+) -> ApiResult<Deployment> {
+    CloneDeploymentCommand::new(deployment_id)
+        .with_include_secrets(request.include_secrets)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Creates the project's production deployment with the staging deployment's
+/// configuration. Like a direct production create, its hostnames and sending
+/// domain are provisioned in the background.
+pub async fn promote_deployment(
+    State(app_state): State<HttpState>,
+    Path(deployment_id): Path<i64>,
+    Validated(request): Validated<PromoteDeploymentRequest>,
+) -> ApiResult<DeploymentPromotion> {
+    PromoteStagingToProductionCommand::new(deployment_id, request.custom_domain)
+        .with_copy_oauth_credentials(request.copy_oauth_credentials)
+        .execute(&app_state)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
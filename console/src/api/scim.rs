@@ -0,0 +1,356 @@
+//! SCIM 2.0 provisioning for identity providers such as Okta and Azure AD.
+//!
+//! These routes don't use the console's response shapes: bodies follow the
+//! SCIM schemas, errors use the SCIM error envelope, and every request needs
+//! a deployment SCIM token as its bearer token.
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Path, Query, Request, State},
+    http::{StatusCode, header, request::Parts},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    application::{HttpState, response::ApiErrorResponse},
+    core::{
+        commands::{
+            AuthenticateScimTokenCommand, Command, DeleteScimGroupCommand, DeleteScimUserCommand,
+            PatchScimGroupCommand, PatchScimUserCommand, ProvisionScimGroupCommand,
+            ProvisionScimUserCommand,
+        },
+        dto::{
+            json::{ScimGroupRequest, ScimPatchRequest, ScimUserRequest},
+            query::ScimListQueryParams,
+        },
+        error::{AppError, ErrorCode},
+        models::{SCIM_ERROR_SCHEMA, ScimFilter, ScimGroup, ScimListResponse, ScimUser},
+        queries::{
+            GetScimGroupQuery, GetScimUserQuery, ListScimGroupsQuery, ListScimUsersQuery,
+            Query as _,
+        },
+    },
+};
+
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+pub fn scim_routes() -> Router<HttpState> {
+    Router::new()
+        .route(
+            "/deployments/{deployment_id}/scim/v2/Users",
+            get(list_users).post(create_user),
+        )
+        .route(
+            "/deployments/{deployment_id}/scim/v2/Users/{user_id}",
+            get(get_user).patch(patch_user).delete(delete_user),
+        )
+        .route(
+            "/deployments/{deployment_id}/scim/v2/Groups",
+            get(list_groups).post(create_group),
+        )
+        .route(
+            "/deployments/{deployment_id}/scim/v2/Groups/{group_id}",
+            get(get_group).patch(patch_group).delete(delete_group),
+        )
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimError {
+    schemas: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scim_type: Option<&'static str>,
+    detail: String,
+    status: String,
+    #[serde(skip)]
+    status_code: StatusCode,
+}
+
+impl ScimError {
+    fn new(status_code: StatusCode, scim_type: Option<&'static str>, detail: String) -> Self {
+        Self {
+            schemas: vec![SCIM_ERROR_SCHEMA],
+            scim_type,
+            detail,
+            status: status_code.as_u16().to_string(),
+            status_code,
+        }
+    }
+}
+
+impl From<AppError> for ScimError {
+    fn from(error: AppError) -> Self {
+        let code = error.code();
+        let response = ApiErrorResponse::from(error);
+        let scim_type = match code {
+            ErrorCode::AlreadyExists => Some("uniqueness"),
+            ErrorCode::BadRequest | ErrorCode::ValidationFailed => Some("invalidValue"),
+            _ => None,
+        };
+        ScimError::new(response.staus_code, scim_type, response.error.message)
+    }
+}
+
+impl IntoResponse for ScimError {
+    fn into_response(self) -> Response {
+        (
+            self.status_code,
+            [(header::CONTENT_TYPE, SCIM_CONTENT_TYPE)],
+            Json(self),
+        )
+            .into_response()
+    }
+}
+
+/// A SCIM resource body. Defaults to 200; creates use 201.
+pub struct Scim<T>(StatusCode, T);
+
+impl<T: Serialize> IntoResponse for Scim<T> {
+    fn into_response(self) -> Response {
+        (
+            self.0,
+            [(header::CONTENT_TYPE, SCIM_CONTENT_TYPE)],
+            Json(self.1),
+        )
+            .into_response()
+    }
+}
+
+type ScimResult<T> = Result<Scim<T>, ScimError>;
+
+fn ok<T>(body: T) -> ScimResult<T> {
+    Ok(Scim(StatusCode::OK, body))
+}
+
+/// Rejects requests without a live SCIM token for the deployment in the path.
+pub struct ScimAuth {
+    pub deployment_id: i64,
+}
+
+impl FromRequestParts<HttpState> for ScimAuth {
+    type Rejection = ScimError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &HttpState,
+    ) -> Result<Self, Self::Rejection> {
+        let unauthorized = || {
+            ScimError::new(
+                StatusCode::UNAUTHORIZED,
+                None,
+                "A valid SCIM bearer token is required".to_string(),
+            )
+        };
+
+        let Path(params) =
+            Path::<std::collections::HashMap<String, String>>::from_request_parts(parts, state)
+                .await
+                .map_err(|_| unauthorized())?;
+        let deployment_id: i64 = params
+            .get("deployment_id")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(unauthorized)?;
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or_else(unauthorized)?;
+
+        AuthenticateScimTokenCommand::new(deployment_id, token)
+            .execute(state)
+            .await
+            .map_err(|e| match e {
+                AppError::Unauthorized => unauthorized(),
+                other => ScimError::from(other),
+            })?;
+
+        Ok(ScimAuth { deployment_id })
+    }
+}
+
+/// JSON body extractor with SCIM errors. Identity providers send
+/// `application/scim+json`, `application/json` or nothing, so the content
+/// type isn't checked.
+pub struct ScimJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ScimJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ScimError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| ScimError::new(e.status(), None, e.body_text()))?;
+
+        serde_json::from_slice(&bytes).map(ScimJson).map_err(|e| {
+            ScimError::new(
+                StatusCode::BAD_REQUEST,
+                Some("invalidSyntax"),
+                e.to_string(),
+            )
+        })
+    }
+}
+
+fn resource_id(id: &str, resource: &str) -> Result<i64, ScimError> {
+    id.parse().map_err(|_| {
+        ScimError::new(
+            StatusCode::NOT_FOUND,
+            None,
+            format!("{} {} not found", resource, id),
+        )
+    })
+}
+
+fn filter(query_params: &ScimListQueryParams) -> Result<Option<ScimFilter>, ScimError> {
+    query_params
+        .filter
+        .as_deref()
+        .map(ScimFilter::parse)
+        .transpose()
+        .map_err(|e| {
+            ScimError::new(
+                StatusCode::BAD_REQUEST,
+                Some("invalidFilter"),
+                ScimError::from(e).detail,
+            )
+        })
+}
+
+async fn list_users(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    Query(query_params): Query<ScimListQueryParams>,
+) -> ScimResult<ScimListResponse<ScimUser>> {
+    ListScimUsersQuery::new(auth.deployment_id)
+        .filter(filter(&query_params)?)
+        .start_index(query_params.start_index.unwrap_or(1))
+        .count(query_params.count.unwrap_or(100))
+        .execute(&app_state)
+        .await
+        .map_err(ScimError::from)
+        .and_then(ok)
+}
+
+async fn create_user(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    ScimJson(request): ScimJson<ScimUserRequest>,
+) -> ScimResult<ScimUser> {
+    let user = ProvisionScimUserCommand::new(auth.deployment_id, request)
+        .execute(&app_state)
+        .await?;
+    Ok(Scim(StatusCode::CREATED, user))
+}
+
+async fn get_user(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    Path((_, user_id)): Path<(i64, String)>,
+) -> ScimResult<ScimUser> {
+    GetScimUserQuery::new(auth.deployment_id, resource_id(&user_id, "User")?)
+        .execute(&app_state)
+        .await
+        .map_err(ScimError::from)
+        .and_then(ok)
+}
+
+async fn patch_user(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    Path((_, user_id)): Path<(i64, String)>,
+    ScimJson(request): ScimJson<ScimPatchRequest>,
+) -> ScimResult<ScimUser> {
+    PatchScimUserCommand::new(auth.deployment_id, resource_id(&user_id, "User")?, request)
+        .execute(&app_state)
+        .await
+        .map_err(ScimError::from)
+        .and_then(ok)
+}
+
+async fn delete_user(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    Path((_, user_id)): Path<(i64, String)>,
+) -> Result<StatusCode, ScimError> {
+    DeleteScimUserCommand::new(auth.deployment_id, resource_id(&user_id, "User")?)
+        .execute(&app_state)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_groups(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    Query(query_params): Query<ScimListQueryParams>,
+) -> ScimResult<ScimListResponse<ScimGroup>> {
+    ListScimGroupsQuery::new(auth.deployment_id)
+        .filter(filter(&query_params)?)
+        .start_index(query_params.start_index.unwrap_or(1))
+        .count(query_params.count.unwrap_or(100))
+        .execute(&app_state)
+        .await
+        .map_err(ScimError::from)
+        .and_then(ok)
+}
+
+async fn create_group(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    ScimJson(request): ScimJson<ScimGroupRequest>,
+) -> ScimResult<ScimGroup> {
+    let group = ProvisionScimGroupCommand::new(auth.deployment_id, request)
+        .execute(&app_state)
+        .await?;
+    Ok(Scim(StatusCode::CREATED, group))
+}
+
+async fn get_group(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    Path((_, group_id)): Path<(i64, String)>,
+) -> ScimResult<ScimGroup> {
+    GetScimGroupQuery::new(auth.deployment_id, resource_id(&group_id, "Group")?)
+        .execute(&app_state)
+        .await
+        .map_err(ScimError::from)
+        .and_then(ok)
+}
+
+async fn patch_group(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    Path((_, group_id)): Path<(i64, String)>,
+    ScimJson(request): ScimJson<ScimPatchRequest>,
+) -> ScimResult<ScimGroup> {
+    PatchScimGroupCommand::new(
+        auth.deployment_id,
+        resource_id(&group_id, "Group")?,
+        request,
+    )
+    .execute(&app_state)
+    .await
+    .map_err(ScimError::from)
+    .and_then(ok)
+}
+
+async fn delete_group(
+    State(app_state): State<HttpState>,
+    auth: ScimAuth,
+    Path((_, group_id)): Path<(i64, String)>,
+) -> Result<StatusCode, ScimError> {
+    DeleteScimGroupCommand::new(auth.deployment_id, resource_id(&group_id, "Group")?)
+        .execute(&app_state)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
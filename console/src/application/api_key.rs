@@ -0,0 +1,171 @@
+//! Deployment API keys.
+//!
+//! Requests without an `sk_live_`/`sk_test_` bearer token pass through
+//! untouched. Requests with one must use a live key of the deployment in the
+//! path, and the key must hold the scope the route needs. The key is then
+//! available to handlers through [`ApiKeyAuth`].
+
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts, Request, State},
+    http::{Method, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+use super::{HttpState, rate_limit::deployment_id_from_path, response::ApiErrorResponse};
+use crate::core::{
+    commands::{AuthenticateDeploymentApiKeyCommand, Command},
+    error::{AppError, ErrorCode},
+    models::DeploymentApiKey,
+};
+
+const API_KEY_PREFIXES: [&str; 2] = ["sk_live_", "sk_test_"];
+
+fn api_key_from_request(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| API_KEY_PREFIXES.iter().any(|p| token.starts_with(p)))
+}
+
+/// The scope a route needs, from the resource right after the deployment id.
+/// `None` means API keys can't call the route at all: keys can't mint other
+/// credentials.
+fn required_scope(method: &Method, path: &str) -> Option<String> {
+    let mut segments = path
+        .split('/')
+        .skip_while(|segment| !matches!(*segment, "deployment" | "deployments"));
+    let resource = segments.nth(2).unwrap_or_default();
+
+    let resource = match resource {
+        "api-keys" | "scim-tokens" | "scim" | "clone" => return None,
+        "users" | "invited-users" | "user-waitlist" | "exports" => "users",
+        "organizations"
+        | "organization-roles"
+        | "organization-invitations"
+        | "workspaces"
+        | "workspace-roles" => "orgs",
+        resource if resource.starts_with("ai-") => "ai",
+        _ => "settings",
+    };
+    let access = if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        "read"
+    } else {
+        "write"
+    };
+
+    Some(format!("{}:{}", resource, access))
+}
+
+fn missing_scope(scope: &str) -> AppError {
+    AppError::coded(
+        ErrorCode::InsufficientScope,
+        format!("This API key needs the {} scope", scope),
+    )
+    .with_details(json!({ "required_scope": scope }))
+}
+
+fn insufficient_scope(required_scope: Option<&str>) -> Response {
+    let error = match required_scope {
+        Some(scope) => missing_scope(scope),
+        None => AppError::coded(
+            ErrorCode::InsufficientScope,
+            "API keys can't call this endpoint",
+        )
+        .with_details(json!({ "required_scope": null })),
+    };
+    ApiErrorResponse::from(error).into_response()
+}
+
+pub async fn authenticate_api_key(
+    State(app_state): State<HttpState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(secret) = api_key_from_request(&request) else {
+        return next.run(request).await;
+    };
+
+    let key = match AuthenticateDeploymentApiKeyCommand::new(secret)
+        .execute(&app_state)
+        .await
+    {
+        Ok(key) => key,
+        Err(e) => return ApiErrorResponse::from(e).into_response(),
+    };
+
+    let path = request.uri().path();
+    if deployment_id_from_path(path) != Some(key.deployment_id) {
+        return ApiErrorResponse::from(AppError::Unauthorized).into_response();
+    }
+
+    // The key also rides on the response so the request log can attribute
+    // the call, rejected or not.
+    let scope = required_scope(request.method(), path);
+    if !scope.as_deref().is_some_and(|scope| key.has_scope(scope)) {
+        let mut response = insufficient_scope(scope.as_deref());
+        response.extensions_mut().insert(key);
+        return response;
+    }
+
+    request.extensions_mut().insert(key.clone());
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(key);
+    response
+}
+
+/// The API key a request was authenticated with. Routes that only make sense
+/// for server-side callers take this; everything else can take
+/// `Option<ApiKeyAuth>`.
+#[derive(Debug, Clone)]
+pub struct ApiKeyAuth(pub DeploymentApiKey);
+
+impl<S: Send + Sync> FromRequestParts<S> for ApiKeyAuth {
+    type Rejection = ApiErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<DeploymentApiKey>()
+            .cloned()
+            .map(ApiKeyAuth)
+            .ok_or_else(|| AppError::Unauthorized.into())
+    }
+}
+
+impl<S: Send + Sync> OptionalFromRequestParts<S> for ApiKeyAuth {
+    type Rejection = ApiErrorResponse;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<DeploymentApiKey>()
+            .cloned()
+            .map(ApiKeyAuth))
+    }
+}
+
+/// Who to record as `initiated_by`. Changes made with an API key are always
+/// attributed to the key, whatever the body claims.
+pub fn initiated_by(api_key: Option<ApiKeyAuth>, claimed: Option<String>) -> Option<String> {
+    match api_key {
+        Some(ApiKeyAuth(key)) => Some(format!("api_key:{}", key.id)),
+        None => claimed,
+    }
+}
+
+/// Checks a scope that only some requests to a route need, such as ones
+/// revealing secrets. Like the route scopes, it only limits API keys.
+pub fn require_scope(api_key: Option<&ApiKeyAuth>, scope: &str) -> Result<(), AppError> {
+    match api_key {
+        Some(ApiKeyAuth(key)) if !key.has_scope(scope) => Err(missing_scope(scope)),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,206 @@
+//! Project roles for console accounts.
+//!
+//! The console backend authenticates the person using the console and passes
+//! their account id in `X-Console-Account-Id`. Requests carrying it must come
+//! from an account with a role in the project the path points at, directly or
+//! through one of its deployments, and the role must cover the route. Requests
+//! authenticated with an API key are limited by the key's scopes instead, and
+//! requests with neither come from trusted internal callers and pass through.
+//! The `/admin` routes reach across every project or act on the whole
+//! platform, so they need a console account, and that account must be a
+//! platform operator.
+
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts, Request, State},
+    http::{HeaderName, Method, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::{HttpState, rate_limit::deployment_id_from_path, response::ApiErrorResponse};
+use crate::core::{
+    commands::insufficient_project_role,
+    error::{AppError, ErrorCode},
+    models::{DeploymentApiKey, ProjectRole},
+    queries::{GetProjectRoleQuery, IsPlatformOperatorQuery, Query},
+};
+
+const CONSOLE_ACCOUNT_HEADER: HeaderName = HeaderName::from_static("x-console-account-id");
+
+/// Finds the id in `/project/{id}/...` or `/projects/{id}/...`.
+fn project_id_from_path(path: &str) -> Option<i64> {
+    let mut segments = path.trim_start_matches('/').split('/');
+    match segments.next() {
+        Some("project" | "projects") => segments.next().and_then(|id| id.parse().ok()),
+        _ => None,
+    }
+}
+
+fn is_admin_route(path: &str) -> bool {
+    path.strip_prefix("/admin")
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn platform_operator_required() -> AppError {
+    AppError::coded(
+        ErrorCode::PlatformOperatorRequired,
+        "Only platform operators can call this endpoint",
+    )
+}
+
+/// Whether the query string asks for secrets in full with `reveal=true`.
+fn reveals_secrets(query: Option<&str>) -> bool {
+    query.is_some_and(|query| query.split('&').any(|pair| pair == "reveal=true"))
+}
+
+/// The least role that may call a route. Reads are open to every role,
+/// except revealing secrets, which is for admins; deleting or handing over
+/// the project is for owners, and collaborators and production deployments
+/// are managed by admins.
+fn required_role(method: &Method, path: &str, query: Option<&str>) -> ProjectRole {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        if reveals_secrets(query) {
+            return ProjectRole::Admin;
+        }
+        return ProjectRole::ReadOnly;
+    }
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["project", _] | ["projects", _, "transfer"] => ProjectRole::Owner,
+        ["projects", _, "collaborators", ..]
+        | ["project", _, "production-deployment"]
+        | ["project", _, "deployment", _] => ProjectRole::Admin,
+        _ => ProjectRole::Developer,
+    }
+}
+
+pub async fn authorize_project_role(
+    State(app_state): State<HttpState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    // Only operators may reach across projects, so the admin routes never
+    // pass through unauthenticated.
+    let admin = is_admin_route(request.uri().path());
+    if request.extensions().get::<DeploymentApiKey>().is_some() {
+        if admin {
+            return ApiErrorResponse::from(platform_operator_required()).into_response();
+        }
+        return next.run(request).await;
+    }
+    let Some(header) = request.headers().get(&CONSOLE_ACCOUNT_HEADER) else {
+        if admin {
+            return ApiErrorResponse::from(AppError::Unauthorized).into_response();
+        }
+        return next.run(request).await;
+    };
+    let Some(account_id) = header
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().parse::<i64>().ok())
+    else {
+        return ApiErrorResponse::from(AppError::Unauthorized).into_response();
+    };
+
+    let path = request.uri().path();
+    let query = if admin {
+        match IsPlatformOperatorQuery::new(account_id)
+            .execute(&app_state)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                return ApiErrorResponse::from(platform_operator_required()).into_response();
+            }
+            Err(e) => return ApiErrorResponse::from(e).into_response(),
+        }
+        request
+            .extensions_mut()
+            .insert(ConsoleAccountAuth { account_id });
+        return next.run(request).await;
+    } else if let Some(project_id) = project_id_from_path(path) {
+        GetProjectRoleQuery::for_project(project_id, account_id)
+    } else if let Some(deployment_id) = deployment_id_from_path(path) {
+        GetProjectRoleQuery::for_deployment(deployment_id, account_id)
+    } else {
+        request
+            .extensions_mut()
+            .insert(ConsoleAccountAuth { account_id });
+        return next.run(request).await;
+    };
+
+    let role = match query.execute(&app_state).await {
+        Ok(role) => role,
+        Err(e) => return ApiErrorResponse::from(e).into_response(),
+    };
+    let required = required_role(request.method(), path, request.uri().query());
+    if !role.is_some_and(|role| role.allows(required)) {
+        return ApiErrorResponse::from(insufficient_project_role(required, role)).into_response();
+    }
+
+    request
+        .extensions_mut()
+        .insert(ConsoleAccountAuth { account_id });
+    next.run(request).await
+}
+
+/// The console account a request was made for. On project and deployment
+/// routes its role has already been checked.
+#[derive(Debug, Clone)]
+pub struct ConsoleAccountAuth {
+    pub account_id: i64,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ConsoleAccountAuth {
+    type Rejection = ApiErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ConsoleAccountAuth>()
+            .cloned()
+            .ok_or_else(|| AppError::Unauthorized.into())
+    }
+}
+
+impl<S: Send + Sync> OptionalFromRequestParts<S> for ConsoleAccountAuth {
+    type Rejection = ApiErrorResponse;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<ConsoleAccountAuth>().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revealing_secrets_needs_an_admin() {
+        let path = "/deployments/1/social-connections";
+        assert_eq!(
+            required_role(&Method::GET, path, None),
+            ProjectRole::ReadOnly
+        );
+        assert_eq!(
+            required_role(&Method::GET, path, Some("reveal=false")),
+            ProjectRole::ReadOnly
+        );
+        assert_eq!(
+            required_role(&Method::GET, path, Some("initiated_by=a&reveal=true")),
+            ProjectRole::Admin
+        );
+    }
+
+    #[test]
+    fn platform_wide_routes_are_admin_routes() {
+        assert!(is_admin_route("/admin/geoip/reload"));
+        assert!(is_admin_route("/admin"));
+        assert!(!is_admin_route("/administrators"));
+        assert!(!is_admin_route("/deployments/1/feature-flags"));
+    }
+}
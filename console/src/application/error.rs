@@ -1,21 +1,101 @@
 use crate::application::response::ApiErrorResponse;
 use axum::http::StatusCode;
-use shared::error::AppError;
+use shared::error::{AppError, ErrorCode};
 
 impl From<AppError> for ApiErrorResponse {
     fn from(error: AppError) -> Self {
+        let code = error.code();
         match error {
-            AppError::Database(_) | AppError::Sonyflake(_) => {
+            AppError::Database(_) | AppError::IdGenerator(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong").into()
             }
             AppError::NotFound(message) => (StatusCode::NOT_FOUND, message).into(),
             AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized").into(),
             AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message).into(),
             AppError::Internal(message) => (StatusCode::INTERNAL_SERVER_ERROR, message).into(),
-            AppError::Validation(message) => (StatusCode::BAD_REQUEST, message).into(),
+            AppError::Validation(message) => {
+                ApiErrorResponse::new(StatusCode::BAD_REQUEST, code, message)
+            }
             AppError::Serialization(message) => (StatusCode::INTERNAL_SERVER_ERROR, message).into(),
             AppError::S3(message) => (StatusCode::INTERNAL_SERVER_ERROR, message).into(),
-            AppError::External(message) => (StatusCode::BAD_GATEWAY, message).into(),
+            AppError::External(error) => {
+                let response = ApiErrorResponse::new(status_for_code(code), code, error.message);
+                match error.provider {
+                    Some(provider) => response.with_details(serde_json::json!({
+                        "provider": provider,
+                        "status": error.status,
+                    })),
+                    None => response,
+                }
+            }
+            AppError::Coded {
+                code,
+                message,
+                details,
+            } => {
+                let response = ApiErrorResponse::new(status_for_code(code), code, message);
+                match details {
+                    Some(details) => response.with_details(details),
+                    None => response,
+                }
+            }
+        }
+    }
+}
+
+fn status_for_code(code: ErrorCode) -> StatusCode {
+    match code {
+        ErrorCode::NotFound => StatusCode::NOT_FOUND,
+        ErrorCode::AlreadyExists
+        | ErrorCode::DomainMigrationInProgress
+        | ErrorCode::InvitationAlreadyAccepted
+        | ErrorCode::ExportInProgress
+        | ErrorCode::EmbeddingModelMismatch => StatusCode::CONFLICT,
+        ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+        ErrorCode::InsufficientScope
+        | ErrorCode::InsufficientProjectRole
+        | ErrorCode::PlatformOperatorRequired
+        | ErrorCode::OrganizationAdminRequired => StatusCode::FORBIDDEN,
+        ErrorCode::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ErrorCode::InvalidEmailTemplate
+        | ErrorCode::EmailProviderVerificationFailed
+        | ErrorCode::InvalidJwtTemplate
+        | ErrorCode::RedirectUrlNotAllowed
+        | ErrorCode::LogExportDestinationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::RateLimited
+        | ErrorCode::LockedOut
+        | ErrorCode::BudgetExceeded
+        | ErrorCode::OtpResendCooldown => StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::ExternalServiceError => StatusCode::BAD_GATEWAY,
+        ErrorCode::DeploymentDeleted => StatusCode::GONE,
+        ErrorCode::DeploymentInMaintenance | ErrorCode::RedisUnavailable => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        ErrorCode::InternalError | ErrorCode::SecretDecryptionFailed => {
+            StatusCode::INTERNAL_SERVER_ERROR
         }
+        ErrorCode::BadRequest
+        | ErrorCode::ValidationFailed
+        | ErrorCode::InvalidJson
+        | ErrorCode::DomainInUse
+        | ErrorCode::ProductionDeploymentExists
+        | ErrorCode::LastDeploymentCannotBeDeleted
+        | ErrorCode::ProjectLimitReached
+        | ErrorCode::OrganizationMemberLimitReached
+        | ErrorCode::LastOrganizationAdmin
+        | ErrorCode::LastProjectOwner
+        | ErrorCode::LastSignInMethod
+        | ErrorCode::WorkspaceMemberLimitReached
+        | ErrorCode::LastWorkspaceAdmin
+        | ErrorCode::LastWorkspaceCannotBeDeleted
+        | ErrorCode::RoleInUse
+        | ErrorCode::VoipNumberNotAllowed
+        | ErrorCode::InvalidOtp
+        | ErrorCode::OtpExpired
+        | ErrorCode::Web3ChallengeExpired
+        | ErrorCode::InvalidWeb3Signature
+        | ErrorCode::InvalidMagicLink
+        | ErrorCode::MagicLinkExpired
+        | ErrorCode::MagicLinkAlreadyUsed => StatusCode::BAD_REQUEST,
     }
 }
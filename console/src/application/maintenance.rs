@@ -0,0 +1,133 @@
+//! Maintenance mode for end-user traffic.
+//!
+//! While a deployment is in maintenance, whether switched on or inside its
+//! scheduled window, the routes its end users reach through the customer's
+//! app answer 503 with the customer's message and the estimated end. The
+//! console and admin routes keep working so maintenance can be turned off,
+//! and the client config is still served with a banner for the SDKs.
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde_json::json;
+
+use super::{HttpState, rate_limit::deployment_id_from_path, response::ApiErrorResponse};
+use crate::core::{
+    error::{AppError, ErrorCode},
+    queries::{GetDeploymentMaintenanceQuery, Query},
+};
+
+/// Whether a route serves a deployment's end users rather than its admins.
+/// Listing the waitlist is an admin route; joining it is not.
+fn serves_end_users(method: &Method, path: &str) -> bool {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["deployments", _, "magic-links", ..]
+        | ["deployments", _, "web3-wallets", "challenges" | "verify"]
+        | [
+            "deployments",
+            _,
+            "invited-users" | "organization-invitations",
+            "accept",
+        ] => true,
+        ["deployments", _, "user-waitlist"] => *method == Method::POST,
+        _ => false,
+    }
+}
+
+pub async fn enforce_maintenance(
+    State(app_state): State<HttpState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if !serves_end_users(request.method(), path) {
+        return next.run(request).await;
+    }
+    let Some(deployment_id) = deployment_id_from_path(path) else {
+        return next.run(request).await;
+    };
+
+    let maintenance = match GetDeploymentMaintenanceQuery::new(deployment_id)
+        .execute(&app_state)
+        .await
+    {
+        Ok(maintenance) => maintenance,
+        // The handler reports a missing deployment the way it usually does.
+        Err(AppError::NotFound(_)) => return next.run(request).await,
+        Err(e) => return ApiErrorResponse::from(e).into_response(),
+    };
+
+    let now = Utc::now();
+    if !maintenance.is_active_at(now) {
+        return next.run(request).await;
+    }
+
+    let estimated_end_at = maintenance.estimated_end_at(now);
+    let mut response = ApiErrorResponse::from(
+        AppError::coded(
+            ErrorCode::DeploymentInMaintenance,
+            "This deployment is in maintenance mode",
+        )
+        .with_details(json!({
+            "message": maintenance.message,
+            "estimated_end_at": estimated_end_at,
+        })),
+    )
+    .into_response();
+    if let Some(estimated_end_at) = estimated_end_at {
+        let retry_after = (estimated_end_at - now).num_seconds().max(1);
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_end_user_routes_are_held_back() {
+        let post = Method::POST;
+        assert!(serves_end_users(&post, "/deployments/1/magic-links"));
+        assert!(serves_end_users(
+            &post,
+            "/deployments/1/magic-links/consume"
+        ));
+        assert!(serves_end_users(
+            &post,
+            "/deployments/1/web3-wallets/verify"
+        ));
+        assert!(serves_end_users(
+            &post,
+            "/deployments/1/invited-users/accept"
+        ));
+        assert!(serves_end_users(&post, "/deployments/1/user-waitlist"));
+        assert!(serves_end_users(
+            &post,
+            "/deployments/1/organization-invitations/accept"
+        ));
+
+        assert!(!serves_end_users(
+            &Method::GET,
+            "/deployments/1/user-waitlist"
+        ));
+        assert!(!serves_end_users(
+            &post,
+            "/deployments/1/user-waitlist/approve"
+        ));
+        assert!(!serves_end_users(&post, "/deployments/1/invited-users"));
+        assert!(!serves_end_users(&post, "/deployments/1/maintenance"));
+        assert!(!serves_end_users(
+            &post,
+            "/deployments/1/users/2/web3-wallets"
+        ));
+        assert!(!serves_end_users(&Method::GET, "/v1/client/config"));
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::core::metrics::METRICS;
+
+/// Decrements the in-flight gauge even when the request future is dropped
+/// before it finishes.
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        METRICS.http_requests_in_flight.inc();
+        Self
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        METRICS.http_requests_in_flight.dec();
+    }
+}
+
+/// Labels requests by route template so ids in the path don't blow up the
+/// number of series. Requests that match no route share one label.
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+
+    let _in_flight = InFlight::start();
+    let started_at = Instant::now();
+    let response = next.run(request).await;
+
+    METRICS.observe_http_request(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started_at.elapsed(),
+    );
+
+    response
+}
@@ -1,4 +1,11 @@
+pub mod api_key;
+pub mod collaborator;
 mod error;
+mod maintenance;
+mod metrics;
+mod rate_limit;
+mod request_log;
+pub mod validation;
 pub mod response;
 mod router;
 
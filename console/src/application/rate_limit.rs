@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::{HttpState, response::ApiErrorResponse};
+use crate::core::{
+    commands::{CheckRateLimitCommand, Command},
+    models::{DeploymentApiKey, EndpointClass, RateLimitDecision},
+};
+
+const EXEMPT_PREFIXES: [&str; 5] = ["/health", "/healthz", "/readyz", "/metrics", "/internal"];
+
+fn is_exempt(path: &str) -> bool {
+    EXEMPT_PREFIXES.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// Finds the id in `/deployment/{id}/...` or `/deployments/{id}/...`, wherever it
+/// appears in the path.
+pub(super) fn deployment_id_from_path(path: &str) -> Option<i64> {
+    let mut segments = path.split('/');
+    while let Some(segment) = segments.next() {
+        if (segment == "deployment" || segment == "deployments")
+            && let Some(id) = segments.next().and_then(|id| id.parse().ok())
+        {
+            return Some(id);
+        }
+    }
+    None
+}
+
+/// The deployment whose limits apply. Runs after `authenticate_api_key`, so
+/// a request made with an API key is charged to the key's deployment; other
+/// requests fall back to the deployment in the path.
+fn limited_deployment_id(request: &Request) -> Option<i64> {
+    request
+        .extensions()
+        .get::<DeploymentApiKey>()
+        .map(|key| key.deployment_id)
+        .or_else(|| deployment_id_from_path(request.uri().path()))
+}
+
+fn endpoint_class(method: &Method, path: &str) -> EndpointClass {
+    let expensive = path.split('/').any(|segment| {
+        segment.starts_with("ai-") || segment == "verify-dns" || segment == "upload"
+    });
+
+    if expensive {
+        EndpointClass::Expensive
+    } else if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        EndpointClass::Read
+    } else {
+        EndpointClass::Write
+    }
+}
+
+fn set_limit_headers(response: &mut Response, decision: &RateLimitDecision) {
+    let headers = response.headers_mut();
+    headers.insert("x-ratelimit-limit", HeaderValue::from(decision.limit));
+    headers.insert(
+        "x-ratelimit-remaining",
+        HeaderValue::from(decision.remaining),
+    );
+}
+
+pub async fn enforce_rate_limit(
+    State(app_state): State<HttpState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if is_exempt(path) {
+        return next.run(request).await;
+    }
+
+    let Some(deployment_id) = limited_deployment_id(&request) else {
+        return next.run(request).await;
+    };
+    let class = endpoint_class(request.method(), path);
+
+    let decision = match CheckRateLimitCommand::new(deployment_id, class)
+        .execute(&app_state)
+        .await
+    {
+        Ok(decision) => decision,
+        Err(e) => {
+            // Redis being down shouldn't take the whole API down with it.
+            tracing::warn!(
+                deployment_id,
+                "Rate limit check failed, allowing request: {}",
+                e
+            );
+            return next.run(request).await;
+        }
+    };
+
+    if !decision.allowed {
+        let mut response =
+            ApiErrorResponse::from((StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded"))
+                .into_response();
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(decision.retry_after_secs),
+        );
+        set_limit_headers(&mut response, &decision);
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    if decision.limit > 0 {
+        set_limit_headers(&mut response, &decision);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use chrono::Utc;
+
+    use super::*;
+
+    fn api_key(deployment_id: i64) -> DeploymentApiKey {
+        DeploymentApiKey {
+            id: 1,
+            created_at: Utc::now(),
+            deployment_id,
+            name: "ci".to_string(),
+            key_prefix: "sk_live_abc".to_string(),
+            scopes: vec!["users:read".to_string()],
+            created_by: None,
+            expires_at: None,
+            last_used_at: None,
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn api_key_deployment_takes_precedence_over_the_path() {
+        let mut request = Request::builder()
+            .uri("/deployments/7/users")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(limited_deployment_id(&request), Some(7));
+
+        request.extensions_mut().insert(api_key(42));
+        assert_eq!(limited_deployment_id(&request), Some(42));
+    }
+}
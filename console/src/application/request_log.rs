@@ -0,0 +1,105 @@
+//! Records every deployment-scoped API call for the request log.
+//!
+//! Only the route template, status and timing are kept. Headers and request
+//! bodies are never read; for non-2xx responses the error code and message
+//! from our own error body are stored, truncated.
+
+use std::time::Instant;
+
+use axum::{
+    body::{Body, HttpBody, to_bytes},
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+
+use super::{HttpState, rate_limit::deployment_id_from_path};
+use crate::core::{models::DeploymentApiKey, services::RequestLogRow};
+
+const MAX_ERROR_LENGTH: usize = 512;
+/// Error bodies are small JSON objects; anything bigger isn't one of ours.
+const MAX_ERROR_BODY_BYTES: usize = 16 * 1024;
+
+fn truncate(mut value: String) -> String {
+    if let Some((index, _)) = value.char_indices().nth(MAX_ERROR_LENGTH) {
+        value.truncate(index);
+    }
+    value
+}
+
+/// `code: message` from an error body, or `None` for anything that isn't one.
+fn error_summary(body: &[u8]) -> Option<String> {
+    let body: Value = serde_json::from_slice(body).ok()?;
+    let error = body.get("error")?;
+    let code = error.get("code").and_then(Value::as_str).unwrap_or("error");
+    let message = error.get("message").and_then(Value::as_str).unwrap_or("");
+
+    Some(truncate(format!("{}: {}", code, message)))
+}
+
+pub async fn record_request(
+    State(app_state): State<HttpState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(deployment_id) = deployment_id_from_path(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let Some(path) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+    else {
+        return next.run(request).await;
+    };
+    let method = request.method().to_string();
+
+    let started_at = Instant::now();
+    let timestamp = chrono::Utc::now().timestamp_millis();
+    let response = next.run(request).await;
+    let latency_ms = started_at.elapsed().as_millis().min(u32::MAX as u128) as u32;
+
+    let status = response.status();
+    let api_key_id = response
+        .extensions()
+        .get::<DeploymentApiKey>()
+        .map(|key| key.id);
+
+    let buffer_body = !status.is_success()
+        && response
+            .body()
+            .size_hint()
+            .upper()
+            .is_some_and(|size| size <= MAX_ERROR_BODY_BYTES as u64);
+
+    let (response, error) = if buffer_body {
+        let (parts, body) = response.into_parts();
+        match to_bytes(body, MAX_ERROR_BODY_BYTES).await {
+            Ok(bytes) => {
+                let error = error_summary(&bytes);
+                (Response::from_parts(parts, Body::from(bytes)), error)
+            }
+            Err(_) => (Response::from_parts(parts, Body::empty()), None),
+        }
+    } else {
+        (response, None)
+    };
+
+    let Ok(id) = app_state.sf.next_id() else {
+        return response;
+    };
+    app_state.request_log_buffer.push(RequestLogRow {
+        id: id as i64,
+        deployment_id,
+        api_key_id,
+        method,
+        path,
+        status: status.as_u16(),
+        latency_ms,
+        error,
+        timestamp,
+    });
+
+    response
+}
@@ -1,18 +1,99 @@
+//! Response bodies shared by every handler.
+//!
+//! Errors always have the shape
+//! `{"error": {"code": "...", "message": "...", "details": {...}}}`. `message` is
+//! for humans and may change; `code` is stable and is what clients should branch on:
+//!
+//! | code | status | meaning |
+//! |------|--------|---------|
+//! | `bad_request` | 400 | The request can't be processed as sent |
+//! | `invalid_json` | 400 | The body isn't valid JSON |
+//! | `validation_failed` | 400, 422 | One or more fields broke a rule; 422 responses list them in `details.fields` |
+//! | `unauthorized` | 401 | Missing or invalid credentials |
+//! | `insufficient_scope` | 403 | The API key lacks the scope the route needs; `details.required_scope` |
+//! | `insufficient_project_role` | 403 | The console account's role in the project doesn't allow this; `details.required_role` and `details.role` |
+//! | `platform_operator_required` | 403 | The `/admin` routes are for platform operators only |
+//! | `organization_admin_required` | 403 | The user isn't an admin of the organization whose audit events were requested |
+//! | `not_found` | 404 | The resource doesn't exist or was deleted |
+//! | `already_exists` | 409 | Another resource already uses a unique value; `details.field` |
+//! | `domain_migration_in_progress` | 409 | The deployment is already moving to another domain; `details.migration_id` |
+//! | `invitation_already_accepted` | 409 | The invitation was accepted already; `details.user_id` is who accepted it |
+//! | `export_in_progress` | 409 | The deployment is already exporting its data; `details.export_id` |
+//! | `deployment_deleted` | 410 | The deployment the publishable key belongs to was deleted |
+//! | `unsupported_media_type` | 415 | The body isn't `application/json` |
+//! | `invalid_email_template` | 422 | The email template has errors, or warnings that weren't allowed; `details.errors`, `details.warnings` |
+//! | `email_provider_verification_failed` | 422 | The email provider rejected the credentials or couldn't be reached; `details.provider` |
+//! | `invalid_jwt_template` | 422 | A JWT template is invalid; `details.errors` when saving it, `details.path` and `details.expression` when rendering it |
+//! | `redirect_url_not_allowed` | 422 | A redirect setting points outside the deployment's frontend host and allowed origins, or is a `javascript:`/`data:` URL; `details.errors` names each field |
+//! | `rate_limited` | 429 | Too many requests; see `Retry-After` |
+//! | `locked_out` | 429 | Too many failed sign-ins for this identifier and IP; `details.retry_after_seconds` |
+//! | `budget_exceeded` | 429 | The agent or deployment has used up a monthly AI budget; `details.scope`, `details.metric`, `details.used`, `details.limit` and `details.resets_at` |
+//! | `otp_resend_cooldown` | 429 | A code was sent to this identifier too recently; `details.retry_after_seconds` |
+//! | `domain_in_use` | 400 | The custom domain belongs to another deployment; `details.domain` |
+//! | `production_deployment_exists` | 400 | The project already has a production deployment |
+//! | `last_deployment_cannot_be_deleted` | 400 | Delete the project instead of its only deployment |
+//! | `project_limit_reached` | 400 | The target account can't own more projects; `details.max_projects` |
+//! | `voip_number_not_allowed` | 400 | The deployment blocks VOIP phone numbers; `details.phone_number` |
+//! | `organization_member_limit_reached` | 400 | The organization is at the deployment's member limit; `details.max_allowed_org_members` |
+//! | `last_organization_admin` | 400 | The member is the organization's only admin; `details.membership_id` |
+//! | `last_project_owner` | 400 | The collaborator is the project's only owner; `details.collaborator_id` |
+//! | `last_sign_in_method` | 400 | The change would leave the deployment with no enabled first factor or social connection |
+//! | `workspace_member_limit_reached` | 400 | The workspace is at the deployment's member limit; `details.max_allowed_workspace_members` |
+//! | `last_workspace_admin` | 400 | The member is the workspace's only admin; `details.membership_id` |
+//! | `last_workspace_cannot_be_deleted` | 400 | The deployment keeps each organization's last workspace; `details.organization_id` |
+//! | `role_in_use` | 400 | The role is a deployment default or still held by members; `details.member_count` |
+//! | `invalid_otp` | 400 | The code is wrong; `details.remaining_attempts`, and at 0 the code has stopped working |
+//! | `otp_expired` | 400 | No code is waiting for this identifier: it expired, ran out of attempts or was never sent. Send a new one |
+//! | `web3_challenge_expired` | 400 | No sign-in message is waiting for this wallet and nonce: it expired, was already used or was never issued. Ask for a new one |
+//! | `invalid_web3_signature` | 400 | The signature wasn't made by the wallet over the issued message |
+//! | `invalid_magic_link` | 400 | The magic link token wasn't issued by this deployment |
+//! | `magic_link_expired` | 400 | The magic link has expired or a newer one was sent; `details.reason` is `expired` or `superseded` |
+//! | `magic_link_already_used` | 400 | The magic link has already been used to sign in |
+//! | `log_export_destination_failed` | 422 | The log export bucket couldn't be written to with the given credentials; `details.bucket` |
+//! | `internal_error` | 500 | Something failed on our side |
+//! | `secret_decryption_failed` | 500 | A stored secret can't be read with the configured keys; `details.key_id` |
+//! | `external_service_error` | 502 | An upstream provider failed; `details.provider` and `details.status` when known |
+//! | `deployment_in_maintenance` | 503 | The deployment is in maintenance mode; SDKs should show a maintenance screen with `details.message`, and `details.estimated_end_at` when known |
+
 use axum::{Json, http::StatusCode, response::IntoResponse};
 use serde::Serialize;
+use serde_json::{Value, json};
+use shared::dto::query::Pagination;
+use shared::error::ErrorCode;
+use shared::validators::ValidationErrors;
 
+/// The `error` object in every error response body. `details` is always an object,
+/// empty when the error has nothing structured to add.
 #[derive(Debug, Clone, Serialize)]
 pub struct ApiError {
+    pub code: ErrorCode,
     pub message: String,
-    pub code: u16,
+    pub details: Value,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ApiErrorResponse {
     #[serde(skip_serializing)]
     pub staus_code: StatusCode,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub errors: Vec<ApiError>,
+    pub error: ApiError,
+}
+
+impl ApiErrorResponse {
+    pub fn new(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiErrorResponse {
+            staus_code: status,
+            error: ApiError {
+                code,
+                message: message.into(),
+                details: Value::Object(Default::default()),
+            },
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.error.details = details;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -53,50 +134,38 @@ impl<T> From<(StatusCode, T)> for ApiSuccess<T> {
     }
 }
 
-impl From<(StatusCode, Vec<ApiError>)> for ApiErrorResponse {
-    fn from(value: (StatusCode, Vec<ApiError>)) -> Self {
-        ApiErrorResponse {
-            staus_code: value.0,
-            errors: value.1,
-        }
-    }
-}
-
-impl From<(StatusCode, ApiError)> for ApiErrorResponse {
-    fn from(value: (StatusCode, ApiError)) -> Self {
-        ApiErrorResponse {
-            staus_code: value.0,
-            errors: vec![value.1],
-        }
+/// Picks the generic code for errors raised directly by handlers with only a
+/// status and a message.
+fn code_for_status(status: StatusCode) -> ErrorCode {
+    match status {
+        StatusCode::BAD_REQUEST => ErrorCode::BadRequest,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorCode::Unauthorized,
+        StatusCode::NOT_FOUND => ErrorCode::NotFound,
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+        StatusCode::UNPROCESSABLE_ENTITY => ErrorCode::ValidationFailed,
+        StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+        StatusCode::BAD_GATEWAY => ErrorCode::ExternalServiceError,
+        _ => ErrorCode::InternalError,
     }
 }
 
 impl From<(StatusCode, String)> for ApiErrorResponse {
     fn from(value: (StatusCode, String)) -> Self {
-        ApiErrorResponse {
-            staus_code: value.0,
-            errors: vec![ApiError {
-                message: value.1,
-                code: u16::from(value.0),
-            }],
-        }
+        ApiErrorResponse::new(value.0, code_for_status(value.0), value.1)
     }
 }
 
 impl From<(StatusCode, &str)> for ApiErrorResponse {
     fn from(value: (StatusCode, &str)) -> Self {
-        ApiErrorResponse {
-            staus_code: value.0,
-            errors: vec![ApiError {
-                message: value.1.to_string(),
-                code: u16::from(value.0),
-            }],
-        }
+        ApiErrorResponse::new(value.0, code_for_status(value.0), value.1)
     }
 }
 
 pub type ApiResult<T> = Result<ApiSuccess<T>, ApiErrorResponse>;
 
+/// The envelope of every list response. `limit` is the page size that was
+/// applied and `next_cursor` fetches the following page; both are null for
+/// lists returned whole.
 #[derive(Debug, Clone, Serialize)]
 pub struct PaginatedResponse<T>
 where
@@ -104,6 +173,28 @@ where
 {
     pub data: Vec<T>,
     pub has_more: bool,
+    pub limit: Option<i64>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> PaginatedResponse<T>
+where
+    T: Serialize,
+{
+    /// Builds a page from rows fetched with `pagination.fetch_limit()`; the
+    /// extra row only tells whether there is a next page.
+    pub fn page(mut data: Vec<T>, pagination: &Pagination) -> Self {
+        let limit = pagination.limit();
+        let has_more = data.len() > limit as usize;
+        data.truncate(limit as usize);
+
+        PaginatedResponse {
+            data,
+            has_more,
+            limit: Some(limit),
+            next_cursor: has_more.then(|| pagination.next_cursor()),
+        }
+    }
 }
 
 // Upload responses
@@ -120,6 +211,100 @@ where
         PaginatedResponse {
             data,
             has_more: false,
+            limit: None,
+            next_cursor: None,
         }
     }
 }
+
+impl From<ValidationErrors> for ApiErrorResponse {
+    fn from(value: ValidationErrors) -> Self {
+        ApiErrorResponse::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::ValidationFailed,
+            "The request body is invalid",
+        )
+        .with_details(json!({ "fields": value.errors }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::error::AppError;
+    use shared::validators::FieldViolation;
+
+    fn body(response: ApiErrorResponse) -> String {
+        serde_json::to_string(&response).unwrap()
+    }
+
+    #[test]
+    fn status_only_errors_get_a_generic_code() {
+        let response = ApiErrorResponse::from((StatusCode::NOT_FOUND, "Project not found"));
+
+        assert_eq!(response.staus_code, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body(response),
+            r#"{"error":{"code":"not_found","message":"Project not found","details":{}}}"#
+        );
+    }
+
+    #[test]
+    fn app_errors_map_to_their_generic_code() {
+        let response = ApiErrorResponse::from(AppError::Validation(
+            "Message role must be one of: system, user".to_string(),
+        ));
+
+        assert_eq!(response.staus_code, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body(response),
+            r#"{"error":{"code":"validation_failed","message":"Message role must be one of: system, user","details":{}}}"#
+        );
+    }
+
+    #[test]
+    fn coded_errors_keep_their_code_and_details() {
+        let error = AppError::coded(
+            ErrorCode::DomainInUse,
+            "Domain 'example.com' is already in use by another deployment",
+        )
+        .with_details(json!({ "domain": "example.com" }));
+        let response = ApiErrorResponse::from(error);
+
+        assert_eq!(response.staus_code, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body(response),
+            r#"{"error":{"code":"domain_in_use","message":"Domain 'example.com' is already in use by another deployment","details":{"domain":"example.com"}}}"#
+        );
+    }
+
+    #[test]
+    fn last_deployment_error_shape() {
+        let response = ApiErrorResponse::from(AppError::coded(
+            ErrorCode::LastDeploymentCannotBeDeleted,
+            "Cannot delete the last deployment in a project. Delete the project instead.",
+        ));
+
+        assert_eq!(
+            body(response),
+            r#"{"error":{"code":"last_deployment_cannot_be_deleted","message":"Cannot delete the last deployment in a project. Delete the project instead.","details":{}}}"#
+        );
+    }
+
+    #[test]
+    fn validation_errors_list_fields() {
+        let response = ApiErrorResponse::from(ValidationErrors {
+            errors: vec![FieldViolation::new(
+                "email",
+                "invalid_email",
+                "email is not a valid email address",
+            )],
+        });
+
+        assert_eq!(response.staus_code, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body(response),
+            r#"{"error":{"code":"validation_failed","message":"The request body is invalid","details":{"fields":[{"code":"invalid_email","field":"email","message":"email is not a valid email address"}]}}}"#
+        );
+    }
+}
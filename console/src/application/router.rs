@@ -1,5 +1,7 @@
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{delete, get, patch, post, put},
 };
 use tower_http::{
@@ -7,18 +9,86 @@ use tower_http::{
     trace::TraceLayer,
 };
 
-use super::HttpState;
-use crate::api;
+use super::{
+    HttpState, api_key::authenticate_api_key, collaborator::authorize_project_role,
+    maintenance::enforce_maintenance, metrics::track_http_metrics, rate_limit::enforce_rate_limit,
+    request_log::record_request,
+};
+use crate::{api, core::commands::MAX_DEPLOYMENT_ASSET_SIZE};
 
 fn health_routes() -> Router<HttpState> {
-    Router::new().route("/health", get(api::health::check))
+    Router::new()
+        .route("/health", get(api::health::check))
+        .route("/healthz", get(api::health::check))
+        .route("/readyz", get(api::health::ready))
+        .route("/metrics", get(api::metrics::export))
+}
+
+fn platform_routes() -> Router<HttpState> {
+    Router::new()
+        .route(
+            "/admin/disposable-domains/refresh",
+            post(api::deployment::settings::refresh_disposable_domains),
+        )
+        .route(
+            "/admin/custom-hostnames/reconcile",
+            post(api::deployment::settings::reconcile_custom_hostnames),
+        )
+        .route(
+            "/admin/qdrant-collections/reconcile",
+            post(api::deployment::ai_knowledge_base::reconcile_qdrant_collections),
+        )
+        .route(
+            "/admin/jobs",
+            get(api::deployment::settings::get_background_jobs),
+        )
+        .route(
+            "/admin/jobs/{job_id}/retry",
+            post(api::deployment::settings::retry_background_job),
+        )
+        .route("/admin/search", get(api::admin::global_search))
+        .route(
+            "/admin/phone-intelligence/metrics",
+            get(api::deployment::settings::get_phone_intelligence_metrics),
+        )
+        .route(
+            "/admin/geoip/reload",
+            post(api::deployment::settings::reload_geoip_database),
+        )
+        .route(
+            "/admin/feature-flags",
+            get(api::deployment::settings::get_feature_flag_definitions),
+        )
+        .route(
+            "/admin/feature-flags/{flag}/deployments",
+            get(api::deployment::settings::list_deployments_with_flag),
+        )
 }
 
 fn project_routes() -> Router<HttpState> {
     Router::new()
         .route("/projects", get(api::project::get_projects))
+        .route("/dashboard", get(api::project::get_account_dashboard))
         .route("/project", post(api::project::create_project))
         .route("/project/{id}", delete(api::project::delete_project))
+        .route(
+            "/projects/{project_id}/deployments/summary",
+            get(api::project::get_project_deployments_summary),
+        )
+        .route(
+            "/projects/{project_id}/transfer",
+            post(api::project::transfer_project),
+        )
+        .route(
+            "/projects/{project_id}/collaborators",
+            get(api::project::list_project_collaborators)
+                .post(api::project::add_project_collaborator),
+        )
+        .route(
+            "/projects/{project_id}/collaborators/{collaborator_id}",
+            patch(api::project::update_project_collaborator_role)
+                .delete(api::project::remove_project_collaborator),
+        )
         .route(
             "/project/{project_id}/production-deployment",
             post(api::project::create_production_deployment),
@@ -31,19 +101,67 @@ fn project_routes() -> Router<HttpState> {
             "/deployment/{deployment_id}/verify-dns",
             post(api::project::verify_deployment_dns_records),
         )
+        .route(
+            "/deployment/{deployment_id}/provisioning",
+            get(api::project::get_deployment_provisioning),
+        )
+        .route(
+            "/deployment/{deployment_id}/provisioning/retry",
+            post(api::project::retry_deployment_provisioning),
+        )
+        .route(
+            "/deployment/{deployment_id}/domain-migration",
+            get(api::project::get_domain_migration).post(api::project::change_deployment_domain),
+        )
+        .route(
+            "/deployment/{deployment_id}/domain-migration/verify",
+            post(api::project::verify_domain_migration),
+        )
+        .route(
+            "/deployment/{deployment_id}/domain-migration/abort",
+            post(api::project::abort_domain_migration),
+        )
+        .route(
+            "/deployment/{deployment_id}/mail-settings",
+            get(api::project::get_deployment_mail_settings)
+                .patch(api::project::update_deployment_mail_settings),
+        )
+        .route(
+            "/deployment/{deployment_id}/mail-settings/rotate-dkim",
+            post(api::project::rotate_deployment_dkim_key),
+        )
 }
 
 fn deployment_routes() -> Router<HttpState> {
     let routes = Router::new()
+        .route("/clone", post(api::project::clone_deployment))
+        .route("/promote", post(api::project::promote_deployment))
         .route("/users", get(api::deployment::user::get_active_user_list))
         .route("/users", post(api::deployment::user::create_user))
+        .route("/users/lookup", get(api::deployment::user::lookup_user))
+        .route(
+            "/users/duplicate-emails",
+            get(api::deployment::user::get_duplicate_email_accounts),
+        )
+        .route(
+            "/users/username-availability",
+            get(api::deployment::user::check_username_availability),
+        )
         .route(
             "/users/{user_id}/details",
             get(api::deployment::user::get_user_details),
         )
+        .route(
+            "/users/{user_id}/authorization",
+            get(api::deployment::user::get_user_authorization),
+        )
         .route(
             "/users/{user_id}",
-            patch(api::deployment::user::update_user),
+            patch(api::deployment::user::update_user).delete(api::deployment::user::delete_user),
+        )
+        .route(
+            "/users/{user_id}/restore",
+            post(api::deployment::user::restore_user),
         )
         .route(
             "/users/{user_id}/emails",
@@ -69,27 +187,99 @@ fn deployment_routes() -> Router<HttpState> {
             "/users/{user_id}/phones/{phone_id}",
             delete(api::deployment::user::delete_user_phone),
         )
+        .route(
+            "/users/{user_id}/web3-wallets",
+            post(api::deployment::user::add_user_web3_wallet),
+        )
+        .route(
+            "/users/{user_id}/web3-wallets/{wallet_id}",
+            delete(api::deployment::user::delete_user_web3_wallet),
+        )
+        .route(
+            "/web3-wallets/challenges",
+            post(api::deployment::user::issue_web3_challenge),
+        )
+        .route(
+            "/web3-wallets/verify",
+            post(api::deployment::user::verify_web3_signature),
+        )
+        .route(
+            "/magic-links",
+            post(api::deployment::user::create_magic_link),
+        )
+        .route(
+            "/magic-links/consume",
+            post(api::deployment::user::consume_magic_link),
+        )
         .route(
             "/users/{user_id}/social-connections/{connection_id}",
             delete(api::deployment::user::delete_user_social_connection),
         )
+        .route(
+            "/users/{user_id}/sessions",
+            get(api::deployment::user::get_user_sessions)
+                .delete(api::deployment::user::revoke_user_sessions),
+        )
+        .route(
+            "/users/{user_id}/sessions/{session_id}",
+            delete(api::deployment::user::revoke_user_session),
+        )
+        .route(
+            "/users/{user_id}/password",
+            put(api::deployment::user::update_user_password)
+                .patch(api::deployment::user::update_user_password_requirement),
+        )
+        .route(
+            "/users/{user_id}/password/reset",
+            post(api::deployment::user::reset_user_password),
+        )
+        .route(
+            "/users/{user_id}/unlock",
+            post(api::deployment::user::unlock_user),
+        )
+        .route(
+            "/users/{user_id}/erase",
+            post(api::deployment::user::erase_user),
+        )
         .route(
             "/invited-users",
             get(api::deployment::user::get_invited_user_list),
         )
         .route("/invited-users", post(api::deployment::user::invite_user))
+        .route(
+            "/invited-users/accept",
+            post(api::deployment::user::accept_invitation),
+        )
+        .route(
+            "/invited-users/{invitation_id}",
+            delete(api::deployment::user::revoke_invitation),
+        )
         .route(
             "/user-waitlist",
-            get(api::deployment::user::get_user_waitlist),
+            get(api::deployment::user::get_user_waitlist)
+                .post(api::deployment::user::join_waitlist),
+        )
+        .route(
+            "/user-waitlist/approve",
+            post(api::deployment::user::bulk_approve_waitlist_users),
         )
         .route(
             "/user-waitlist/{waitlist_user_id}/approve",
             post(api::deployment::user::approve_waitlist_user),
         )
+        .route(
+            "/user-waitlist/{waitlist_user_id}/reject",
+            post(api::deployment::user::reject_waitlist_user),
+        )
         .route(
             "/",
             get(api::deployment::settings::get_deployment_with_settings),
         )
+        .route(
+            "/feature-flags",
+            get(api::deployment::settings::get_deployment_feature_flags)
+                .patch(api::deployment::settings::update_deployment_feature_flags),
+        )
         .route(
             "/jwt-templates",
             get(api::deployment::settings::get_deployment_jwt_templates),
@@ -106,14 +296,49 @@ fn deployment_routes() -> Router<HttpState> {
             "/jwt-templates/{id}",
             delete(api::deployment::settings::delete_deployment_jwt_template),
         )
+        .route(
+            "/jwt-templates/validation",
+            get(api::deployment::settings::validate_deployment_jwt_templates),
+        )
+        .route(
+            "/jwt-templates/{id}/render",
+            post(api::deployment::settings::render_deployment_jwt_template),
+        )
         .route("/workspaces", get(api::deployment::b2b::get_workspace_list))
         .route(
             "/workspaces/{workspace_id}",
-            get(api::deployment::b2b::get_workspace_details),
+            get(api::deployment::b2b::get_workspace_details)
+                .patch(api::deployment::b2b::update_workspace)
+                .delete(api::deployment::b2b::delete_workspace),
+        )
+        .route(
+            "/workspaces/{workspace_id}/members",
+            get(api::deployment::b2b::get_workspace_members)
+                .post(api::deployment::b2b::add_workspace_member),
+        )
+        .route(
+            "/workspaces/{workspace_id}/members/{membership_id}",
+            patch(api::deployment::b2b::update_workspace_member)
+                .delete(api::deployment::b2b::remove_workspace_member),
+        )
+        .route(
+            "/workspaces/{workspace_id}/roles",
+            post(api::deployment::b2b::create_workspace_role),
+        )
+        .route(
+            "/workspaces/{workspace_id}/roles/{role_id}",
+            patch(api::deployment::b2b::update_workspace_role)
+                .delete(api::deployment::b2b::delete_workspace_role),
         )
         .route(
             "/workspace-roles",
-            get(api::deployment::b2b::get_deployment_workspace_roles),
+            get(api::deployment::b2b::get_deployment_workspace_roles)
+                .post(api::deployment::b2b::create_deployment_workspace_role),
+        )
+        .route(
+            "/workspace-roles/{role_id}",
+            patch(api::deployment::b2b::update_deployment_workspace_role)
+                .delete(api::deployment::b2b::delete_deployment_workspace_role),
         )
         .route(
             "/organizations",
@@ -126,19 +351,46 @@ fn deployment_routes() -> Router<HttpState> {
                 .patch(api::deployment::b2b::update_organization)
                 .delete(api::deployment::b2b::delete_organization),
         )
+        .route(
+            "/organizations/{organization_id}/metadata",
+            patch(api::deployment::b2b::update_organization_metadata),
+        )
         .route(
             "/organizations/{organization_id}/workspaces",
-            post(api::deployment::b2b::create_workspace_for_organization),
+            get(api::deployment::b2b::get_organization_workspaces)
+                .post(api::deployment::b2b::create_workspace_for_organization),
         )
         .route(
             "/organizations/{organization_id}/members",
-            post(api::deployment::b2b::add_organization_member),
+            get(api::deployment::b2b::get_organization_members)
+                .post(api::deployment::b2b::add_organization_member),
         )
         .route(
             "/organizations/{organization_id}/members/{membership_id}",
             patch(api::deployment::b2b::update_organization_member)
                 .delete(api::deployment::b2b::remove_organization_member),
         )
+        .route(
+            "/organizations/{organization_id}/audit-events",
+            get(api::deployment::b2b::get_organization_audit_events),
+        )
+        .route(
+            "/organizations/{organization_id}/invitations",
+            get(api::deployment::b2b::get_organization_invitations)
+                .post(api::deployment::b2b::create_organization_invitation),
+        )
+        .route(
+            "/organizations/{organization_id}/invitations/{invitation_id}",
+            delete(api::deployment::b2b::revoke_organization_invitation),
+        )
+        .route(
+            "/organizations/{organization_id}/invitations/{invitation_id}/resend",
+            post(api::deployment::b2b::resend_organization_invitation),
+        )
+        .route(
+            "/organization-invitations/accept",
+            post(api::deployment::b2b::accept_organization_invitation),
+        )
         .route(
             "/organizations/{organization_id}/roles",
             post(api::deployment::b2b::create_organization_role),
@@ -148,9 +400,23 @@ fn deployment_routes() -> Router<HttpState> {
             patch(api::deployment::b2b::update_organization_role)
                 .delete(api::deployment::b2b::delete_organization_role),
         )
+        .route(
+            "/organizations/{organization_id}/roles/{role_id}/simulate",
+            post(api::deployment::b2b::simulate_organization_role_change),
+        )
         .route(
             "/organization-roles",
-            get(api::deployment::b2b::get_deployment_org_roles),
+            get(api::deployment::b2b::get_deployment_org_roles)
+                .post(api::deployment::b2b::create_deployment_org_role),
+        )
+        .route(
+            "/organization-roles/{role_id}",
+            patch(api::deployment::b2b::update_deployment_org_role)
+                .delete(api::deployment::b2b::delete_deployment_org_role),
+        )
+        .route(
+            "/organization-roles/{role_id}/simulate",
+            post(api::deployment::b2b::simulate_deployment_org_role_change),
         )
         .route(
             "/settings/auth-settings",
@@ -160,10 +426,76 @@ fn deployment_routes() -> Router<HttpState> {
             "/settings/display-settings",
             patch(api::deployment::settings::update_deployment_ui_settings),
         )
+        .route(
+            "/settings/display-settings/validation",
+            get(api::deployment::settings::validate_deployment_redirect_urls),
+        )
         .route(
             "/restrictions",
             patch(api::deployment::settings::update_deployment_restrictions),
         )
+        .route(
+            "/restrictions/evaluate",
+            post(api::deployment::settings::evaluate_deployment_restrictions),
+        )
+        .route(
+            "/restrictions/disposable-domains",
+            get(api::deployment::settings::get_disposable_domains)
+                .post(api::deployment::settings::add_disposable_domain),
+        )
+        .route(
+            "/restrictions/disposable-domains/{domain}",
+            delete(api::deployment::settings::remove_disposable_domain),
+        )
+        .route(
+            "/api-keys",
+            get(api::deployment::settings::get_api_keys)
+                .post(api::deployment::settings::create_api_key),
+        )
+        .route(
+            "/api-keys/{key_id}",
+            delete(api::deployment::settings::revoke_api_key),
+        )
+        .route(
+            "/tokens/revoke",
+            post(api::deployment::settings::revoke_token),
+        )
+        .route(
+            "/tokens/revoke-all",
+            post(api::deployment::settings::revoke_deployment_tokens),
+        )
+        .route(
+            "/tokens/revocation-status",
+            get(api::deployment::settings::get_token_revocation_status),
+        )
+        .route(
+            "/request-logs",
+            get(api::deployment::request_logs::get_request_logs),
+        )
+        .route("/usage", get(api::deployment::usage::get_deployment_usage))
+        .route(
+            "/scim-tokens",
+            get(api::deployment::settings::get_scim_tokens)
+                .post(api::deployment::settings::create_scim_token),
+        )
+        .route(
+            "/scim-tokens/{token_id}",
+            delete(api::deployment::settings::revoke_scim_token),
+        )
+        .route(
+            "/sso-connections",
+            get(api::deployment::connection::get_sso_connections)
+                .post(api::deployment::connection::create_sso_connection),
+        )
+        .route(
+            "/sso-connections/{connection_id}",
+            patch(api::deployment::connection::update_sso_connection)
+                .delete(api::deployment::connection::delete_sso_connection),
+        )
+        .route(
+            "/sso-connections/{connection_id}/sp-metadata",
+            get(api::deployment::connection::get_sso_sp_metadata),
+        )
         .route(
             "/social-connections",
             get(api::deployment::connection::get_deployment_social_connections),
@@ -172,10 +504,22 @@ fn deployment_routes() -> Router<HttpState> {
             "/social-connections",
             put(api::deployment::connection::upsert_deployment_social_connection),
         )
+        .route(
+            "/social-connections/{provider}",
+            delete(api::deployment::connection::delete_deployment_social_connection),
+        )
         .route(
             "/settings/b2b-settings",
             patch(api::deployment::b2b::update_deployment_b2b_settings),
         )
+        .route(
+            "/config/export",
+            get(api::deployment::settings::export_deployment_config),
+        )
+        .route(
+            "/config/import",
+            post(api::deployment::settings::import_deployment_config),
+        )
         .route(
             "/email-templates/{template_name}",
             get(api::deployment::settings::get_email_template),
@@ -184,14 +528,85 @@ fn deployment_routes() -> Router<HttpState> {
             "/email-templates/{template_name}",
             patch(api::deployment::settings::update_email_template),
         )
+        .route(
+            "/email-provider",
+            get(api::deployment::settings::get_email_provider)
+                .put(api::deployment::settings::set_email_provider)
+                .delete(api::deployment::settings::delete_email_provider),
+        )
+        .route(
+            "/email-provider/test",
+            post(api::deployment::settings::send_test_email),
+        )
+        .route(
+            "/maintenance",
+            get(api::deployment::settings::get_maintenance)
+                .put(api::deployment::settings::update_maintenance),
+        )
+        .route(
+            "/log-export",
+            get(api::deployment::settings::get_log_export)
+                .put(api::deployment::settings::set_log_export)
+                .delete(api::deployment::settings::delete_log_export),
+        )
+        .route(
+            "/log-export/external-id",
+            get(api::deployment::settings::get_log_export_external_id),
+        )
+        .route(
+            "/log-export/test",
+            post(api::deployment::settings::test_log_export_destination),
+        )
+        .route(
+            "/email-outbox",
+            get(api::deployment::settings::get_email_outbox),
+        )
+        .route(
+            "/email-outbox/retry",
+            post(api::deployment::settings::retry_failed_emails),
+        )
+        .route(
+            "/email-templates/all/reset",
+            post(api::deployment::settings::reset_all_email_templates),
+        )
+        .route(
+            "/email-templates/{template_name}/reset",
+            post(api::deployment::settings::reset_email_template),
+        )
         .route(
             "/upload/{image_type}",
-            post(api::deployment::upload::upload_image),
+            post(api::deployment::upload::upload_image).layer(asset_body_limit()),
+        )
+        .route(
+            "/assets/{kind}",
+            post(api::deployment::upload::upload_image).layer(asset_body_limit()),
+        )
+        .route(
+            "/uploads",
+            post(api::deployment::upload::create_signed_upload),
+        )
+        .route(
+            "/uploads/{upload_id}/confirm",
+            post(api::deployment::upload::confirm_upload),
+        )
+        .route(
+            "/exports",
+            get(api::deployment::data_export::get_data_exports)
+                .post(api::deployment::data_export::export_deployment_data),
+        )
+        .route(
+            "/exports/{export_id}",
+            get(api::deployment::data_export::get_data_export),
         );
 
     Router::new().nest("/deployments/{deployment_id}", routes)
 }
 
+/// Room for a full-size asset plus the multipart framing around it.
+fn asset_body_limit() -> DefaultBodyLimit {
+    DefaultBodyLimit::max(MAX_DEPLOYMENT_ASSET_SIZE + 64 * 1024)
+}
+
 fn ai_routes() -> Router<HttpState> {
     Router::new()
         // AI Agents
@@ -206,6 +621,53 @@ fn ai_routes() -> Router<HttpState> {
                 .patch(api::deployment::ai_agents::update_ai_agent)
                 .delete(api::deployment::ai_agents::delete_ai_agent),
         )
+        .route(
+            "/deployment/{deployment_id}/ai-agents/{agent_id}/budget",
+            put(api::deployment::ai_agents::update_ai_agent_budget),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-usage",
+            get(api::deployment::ai_agents::get_ai_usage),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-budget",
+            put(api::deployment::ai_agents::update_deployment_ai_budget),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-embedding-provider",
+            get(api::deployment::ai_knowledge_base::get_embedding_providers)
+                .put(api::deployment::ai_knowledge_base::update_embedding_provider),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-agents/{agent_id}/knowledge-bases",
+            get(api::deployment::ai_agents::get_agent_knowledge_bases)
+                .post(api::deployment::ai_agents::attach_agent_knowledge_base),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-agents/{agent_id}/knowledge-bases/{knowledge_base_id}",
+            delete(api::deployment::ai_agents::detach_agent_knowledge_base),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-agents/{agent_id}/retrieve",
+            post(api::deployment::ai_agents::retrieve_agent_context),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-agents/{agent_id}/sessions",
+            get(api::deployment::ai_agents::get_agent_sessions)
+                .post(api::deployment::ai_agents::create_agent_session),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-agents/{agent_id}/sessions/{session_id}",
+            get(api::deployment::ai_agents::get_agent_session_transcript),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-agents/{agent_id}/sessions/{session_id}/messages",
+            post(api::deployment::ai_agents::append_agent_session_message),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-agents/{agent_id}/sessions/{session_id}/close",
+            post(api::deployment::ai_agents::close_agent_session),
+        )
         // AI Workflows
         .route(
             "/deployment/{deployment_id}/ai-workflows",
@@ -218,17 +680,59 @@ fn ai_routes() -> Router<HttpState> {
                 .patch(api::deployment::ai_workflows::update_ai_workflow)
                 .delete(api::deployment::ai_workflows::delete_ai_workflow),
         )
+        .route(
+            "/deployment/{deployment_id}/ai-workflows/{workflow_id}/publish",
+            post(api::deployment::ai_workflows::publish_ai_workflow),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-workflows/{workflow_id}/versions",
+            get(api::deployment::ai_workflows::get_workflow_versions),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-workflows/{workflow_id}/versions/{version_id}/rollback",
+            post(api::deployment::ai_workflows::rollback_ai_workflow),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-workflows/{workflow_id}/runs",
+            get(api::deployment::ai_workflows::get_workflow_runs)
+                .post(api::deployment::ai_workflows::execute_ai_workflow),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-workflows/{workflow_id}/runs/{run_id}",
+            get(api::deployment::ai_workflows::get_workflow_run),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-workflows/{workflow_id}/runs/{run_id}/cancel",
+            post(api::deployment::ai_workflows::cancel_workflow_run),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-workflows/{workflow_id}/triggers",
+            get(api::deployment::ai_workflows::get_workflow_triggers)
+                .post(api::deployment::ai_workflows::create_workflow_trigger),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-workflows/{workflow_id}/triggers/{trigger_id}",
+            delete(api::deployment::ai_workflows::delete_workflow_trigger),
+        )
         .route(
             "/deployment/{deployment_id}/ai-tools",
             get(api::deployment::ai_tools::get_ai_tools)
                 .post(api::deployment::ai_tools::create_ai_tool),
         )
+        .route(
+            "/deployment/{deployment_id}/ai-tools/openapi-import",
+            post(api::deployment::ai_tools::import_openapi_tools),
+        )
         .route(
             "/deployment/{deployment_id}/ai-tools/{tool_id}",
             get(api::deployment::ai_tools::get_ai_tool_by_id)
                 .patch(api::deployment::ai_tools::update_ai_tool)
                 .delete(api::deployment::ai_tools::delete_ai_tool),
         )
+        .route(
+            "/deployment/{deployment_id}/ai-tools/{tool_id}/execute",
+            post(api::deployment::ai_tools::execute_ai_tool),
+        )
         .route(
             "/deployment/{deployment_id}/ai-knowledge-bases",
             get(api::deployment::ai_knowledge_base::get_ai_knowledge_bases)
@@ -249,10 +753,34 @@ fn ai_routes() -> Router<HttpState> {
             "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/documents/url",
             post(api::deployment::ai_knowledge_base::upload_knowledge_base_url),
         )
+        .route(
+            "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/ingest-url",
+            post(api::deployment::ai_knowledge_base::ingest_knowledge_base_url),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/crawls/{crawl_id}",
+            get(api::deployment::ai_knowledge_base::get_knowledge_base_crawl),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/reembed",
+            post(api::deployment::ai_knowledge_base::reembed_knowledge_base),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/reembed/{job_id}",
+            get(api::deployment::ai_knowledge_base::get_reembed_progress),
+        )
         .route(
             "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/documents/{document_id}",
             delete(api::deployment::ai_knowledge_base::delete_knowledge_base_document),
         )
+        .route(
+            "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/documents/{document_id}/rechunk",
+            post(api::deployment::ai_knowledge_base::rechunk_knowledge_base_document),
+        )
+        .route(
+            "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/chunking/dry-run",
+            post(api::deployment::ai_knowledge_base::dry_run_knowledge_base_chunking),
+        )
         // AI Knowledge Base Search
         .route(
             "/deployment/{deployment_id}/ai-knowledge-bases/search",
@@ -260,7 +788,8 @@ fn ai_routes() -> Router<HttpState> {
         )
         .route(
             "/deployment/{deployment_id}/ai-knowledge-bases/{kb_id}/search",
-            get(api::deployment::ai_knowledge_base_search::search_specific_knowledge_base),
+            get(api::deployment::ai_knowledge_base_search::search_specific_knowledge_base)
+                .post(api::deployment::ai_knowledge_base_search::query_knowledge_base),
         )
 }
 
@@ -276,10 +805,37 @@ pub fn create_router(state: HttpState) -> Router {
 
     Router::new()
         .merge(health_routes())
+        .merge(platform_routes())
         .merge(project_routes())
         .merge(deployment_routes())
         .merge(ai_routes())
         .merge(api::analytics::analytics_routes())
+        .merge(api::scim::scim_routes())
+        .merge(api::client::client_routes())
+        .merge(api::ai_trigger::ai_trigger_routes())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            enforce_maintenance,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorize_project_role,
+        ))
+        // Inside `authenticate_api_key`, so API key calls are limited by the
+        // key's deployment.
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            enforce_rate_limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            authenticate_api_key,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            record_request,
+        ))
+        .layer(middleware::from_fn(track_http_metrics))
         .with_state(state)
         .layer(TraceLayer::new_for_http())
         .layer(cors)
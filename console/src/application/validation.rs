@@ -0,0 +1,161 @@
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{StatusCode, header, request::Parts},
+};
+use serde::de::DeserializeOwned;
+use serde_json::{error::Category, json};
+
+use super::response::ApiErrorResponse;
+use crate::core::{
+    error::ErrorCode,
+    validators::{FieldViolation, Validate, ValidationErrors},
+};
+
+/// JSON body extractor that deserializes and then runs the body's `Validate`
+/// rules, so handlers only ever see requests that passed both.
+///
+/// Malformed JSON is rejected with 400 `invalid_json`. Bodies that parse but have
+/// missing, mistyped or invalid fields are rejected with 422 `validation_failed`,
+/// with one entry per field in `details.fields`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Validated<T>(pub T);
+
+impl<T, S> FromRequest<S> for Validated<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiErrorResponse;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !has_json_content_type(&req) {
+            return Err(ApiErrorResponse::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorCode::UnsupportedMediaType,
+                "Expected request with `Content-Type: application/json`",
+            ));
+        }
+
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| ApiErrorResponse::from((e.status(), e.body_text())))?;
+
+        let value = deserialize::<T>(&bytes)?;
+        value.validate()?;
+
+        Ok(Validated(value))
+    }
+}
+
+/// Query string counterpart of [`Validated`]. Parameters that don't parse or
+/// break the type's `Validate` rules are rejected with 422
+/// `validation_failed`, one entry per parameter in `details.fields`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+        let deserializer =
+            serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+
+        let value: T = serde_path_to_error::deserialize(deserializer).map_err(|err| {
+            let path = match err.path().to_string() {
+                path if path == "." => String::new(),
+                path => path,
+            };
+            let message = err.into_inner().to_string();
+            invalid_query(ValidationErrors {
+                errors: vec![data_violation(path, message)],
+            })
+        })?;
+        value.validate().map_err(invalid_query)?;
+
+        Ok(ValidatedQuery(value))
+    }
+}
+
+fn invalid_query(errors: ValidationErrors) -> ApiErrorResponse {
+    ApiErrorResponse::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::ValidationFailed,
+        "The query parameters are invalid",
+    )
+    .with_details(json!({ "fields": errors.errors }))
+}
+
+fn has_json_content_type(req: &Request) -> bool {
+    req.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|mime| {
+            let mime = mime.trim();
+            mime.eq_ignore_ascii_case("application/json") || mime.ends_with("+json")
+        })
+        .unwrap_or(false)
+}
+
+fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ApiErrorResponse> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(bytes);
+
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let path = match err.path().to_string() {
+            path if path == "." => String::new(),
+            path => path,
+        };
+        let inner = err.into_inner();
+        let message = strip_position(&inner.to_string());
+
+        match inner.classify() {
+            Category::Data => ApiErrorResponse::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed,
+                "The request body is invalid",
+            )
+            .with_details(json!({ "fields": [data_violation(path, message)] })),
+            Category::Syntax | Category::Eof | Category::Io => {
+                ApiErrorResponse::new(StatusCode::BAD_REQUEST, ErrorCode::InvalidJson, message)
+            }
+        }
+    })
+}
+
+/// serde reports a missing field against its parent, so the field name is
+/// pulled out of the message to point the violation at the field itself.
+fn data_violation(path: String, message: String) -> FieldViolation {
+    if let Some(field) = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.strip_suffix('`'))
+    {
+        let field = if path.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", path, field)
+        };
+        let message = format!("{} is required", field);
+        return FieldViolation::new(field, "required", message);
+    }
+
+    let code = if message.starts_with("unknown variant") {
+        "invalid_choice"
+    } else {
+        "invalid_type"
+    };
+
+    FieldViolation::new(path, code, message)
+}
+
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
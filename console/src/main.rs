@@ -1,8 +1,15 @@
 mod api;
 mod application;
+mod shutdown;
 pub use shared as core;
 
+use std::future::IntoFuture;
+
 use anyhow::Result;
+use core::commands::{
+    Command, MigrateDeploymentVerificationStatusCommand, MigrateEncryptSecretsCommand,
+    ResumeInterruptedWorkflowRunsCommand,
+};
 use dotenvy::dotenv;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -24,10 +31,104 @@ async fn main() -> Result<()> {
 
     app_state.clickhouse_service.init_tables().await?;
 
-    let app = application::new(app_state);
+    match ResumeInterruptedWorkflowRunsCommand::new()
+        .execute(&app_state)
+        .await
+    {
+        Ok(0) => {}
+        Ok(resumed) => tracing::info!("Resumed {} interrupted workflow runs", resumed),
+        Err(e) => tracing::error!("Failed to resume interrupted workflow runs: {}", e),
+    }
+
+    // Reads fall back to plaintext until a row is sealed, so this doesn't have
+    // to finish before requests are served.
+    let migration_state = app_state.clone();
+    app_state.background_tasks.spawn(async move {
+        match MigrateEncryptSecretsCommand::new()
+            .execute(&migration_state)
+            .await
+        {
+            Ok(0) => {}
+            Ok(resealed) => tracing::info!("Encrypted {} stored secrets", resealed),
+            Err(e) => tracing::error!("Failed to encrypt stored secrets: {}", e),
+        }
+
+        match MigrateDeploymentVerificationStatusCommand::new()
+            .execute(&migration_state)
+            .await
+        {
+            Ok(0) => {}
+            Ok(updated) => tracing::info!(
+                "Stored the verification status of {} deployments",
+                updated
+            ),
+            Err(e) => tracing::error!("Failed to backfill deployment verification statuses: {}", e),
+        }
+    });
+
+    core::commands::spawn_job_worker(&app_state, core::commands::JobHandlers::builtin());
+    core::commands::spawn_provisioning_dispatcher(&app_state);
+    core::commands::spawn_upload_cleanup(&app_state);
+    core::commands::spawn_export_cleanup(&app_state);
+    core::commands::spawn_custom_hostname_sync(&app_state);
+    core::commands::spawn_billing_period_close(&app_state);
+    core::commands::spawn_workflow_trigger_scheduler(&app_state);
+    core::commands::spawn_log_export_scheduler(&app_state);
+
+    let app = application::new(app_state.clone());
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3001").await?;
-    axum::serve(listener, app).await?;
 
+    let (stop_tx, stop_rx) = tokio::sync::watch::channel(false);
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            let mut stop_rx = stop_rx;
+            let _ = stop_rx.wait_for(|stop| *stop).await;
+        })
+        .into_future();
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => return result.map_err(Into::into),
+        _ = shutdown::signal() => {}
+    }
+
+    // Stop accepting connections, then give in-flight requests and background
+    // tasks until the deadline to finish.
+    tracing::info!("Shutdown signal received, draining in-flight requests");
+    let deadline = tokio::time::Instant::now() + shutdown::grace_period();
+    let _ = stop_tx.send(true);
+    app_state.background_tasks.begin_shutdown();
+
+    match tokio::time::timeout_at(deadline, server).await {
+        Ok(result) => result?,
+        Err(_) => tracing::warn!("Grace period elapsed with requests still in flight"),
+    }
+    if !app_state.background_tasks.wait_until(deadline).await {
+        tracing::warn!("Grace period elapsed with background tasks still running");
+    }
+
+    // Requests are done, so nothing else will be buffered.
+    let flushed = tokio::time::timeout(shutdown::FLUSH_TIMEOUT, async {
+        tokio::join!(
+            app_state.auth_event_buffer.close(),
+            app_state.request_log_buffer.close(),
+            app_state.usage_event_buffer.close(),
+        )
+    })
+    .await;
+    if flushed.is_err() {
+        tracing::warn!("Timed out flushing buffered ClickHouse rows");
+    }
+
+    // Nothing is minting ids any more, so the worker id can go to the next
+    // instance.
+    app_state.sf.release().await;
+
+    app_state.db_pool.close().await;
+    // Dropping the state closes the Redis connections it holds.
+    drop(app_state);
+
+    tracing::info!("Shutdown complete");
     Ok(())
 }
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use tokio::signal;
+
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(25);
+/// Extra time after the grace period to write out buffered analytics rows.
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long in-flight requests and background tasks get to finish once a
+/// shutdown signal arrives, from `SHUTDOWN_GRACE_PERIOD_SECS`.
+pub fn grace_period() -> Duration {
+    std::env::var("SHUTDOWN_GRACE_PERIOD_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_GRACE_PERIOD)
+}
+
+/// Resolves on SIGINT or, on Unix, SIGTERM.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = signal::ctrl_c().await {
+            tracing::error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
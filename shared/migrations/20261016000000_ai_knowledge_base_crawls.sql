@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS ai_knowledge_base_crawls (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    knowledge_base_id BIGINT NOT NULL REFERENCES ai_knowledge_bases(id) ON DELETE CASCADE,
+    root_url TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    crawl BOOLEAN NOT NULL DEFAULT FALSE,
+    max_pages INTEGER NOT NULL,
+    max_depth INTEGER NOT NULL,
+    pages_ingested INTEGER NOT NULL DEFAULT 0,
+    pages_skipped INTEGER NOT NULL DEFAULT 0,
+    pages_failed INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_knowledge_base_crawls_knowledge_base_id
+    ON ai_knowledge_base_crawls (knowledge_base_id);
+
+CREATE INDEX IF NOT EXISTS idx_ai_knowledge_base_documents_source_url
+    ON ai_knowledge_base_documents (knowledge_base_id, (processing_metadata->>'source_url'));
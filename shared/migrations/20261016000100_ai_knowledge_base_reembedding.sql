@@ -0,0 +1,19 @@
+ALTER TABLE ai_knowledge_bases ADD COLUMN IF NOT EXISTS active_collection TEXT;
+
+CREATE TABLE IF NOT EXISTS ai_knowledge_base_reembed_jobs (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    knowledge_base_id BIGINT NOT NULL REFERENCES ai_knowledge_bases(id) ON DELETE CASCADE,
+    source_collection TEXT NOT NULL,
+    target_collection TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    cursor BIGINT,
+    processed_chunks BIGINT NOT NULL DEFAULT 0,
+    total_chunks BIGINT NOT NULL DEFAULT 0,
+    error TEXT,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_knowledge_base_reembed_jobs_knowledge_base_id
+    ON ai_knowledge_base_reembed_jobs (knowledge_base_id, created_at DESC);
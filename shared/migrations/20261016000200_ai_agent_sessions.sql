@@ -0,0 +1,37 @@
+CREATE TABLE IF NOT EXISTS ai_agent_sessions (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    agent_id BIGINT NOT NULL REFERENCES ai_agents(id) ON DELETE CASCADE,
+    status TEXT NOT NULL DEFAULT 'active',
+    metadata JSONB NOT NULL DEFAULT '{}'::jsonb,
+    closed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_agent_sessions_agent_id_created_at
+    ON ai_agent_sessions (agent_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS ai_agent_session_messages (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    session_id BIGINT NOT NULL REFERENCES ai_agent_sessions(id) ON DELETE CASCADE,
+    sequence INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    prompt_tokens INTEGER NOT NULL DEFAULT 0,
+    completion_tokens INTEGER NOT NULL DEFAULT 0,
+    tool_calls JSONB,
+    UNIQUE (session_id, sequence)
+);
+
+-- Messages are an audit and billing record, so rows can never be rewritten.
+CREATE OR REPLACE FUNCTION prevent_ai_agent_session_message_update() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'ai_agent_session_messages is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ai_agent_session_messages_append_only ON ai_agent_session_messages;
+CREATE TRIGGER ai_agent_session_messages_append_only
+    BEFORE UPDATE ON ai_agent_session_messages
+    FOR EACH ROW EXECUTE FUNCTION prevent_ai_agent_session_message_update();
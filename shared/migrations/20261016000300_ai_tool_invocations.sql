@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS ai_tool_invocations (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tool_id BIGINT NOT NULL REFERENCES ai_tools(id) ON DELETE CASCADE,
+    deployment_id BIGINT NOT NULL,
+    workflow_run_id BIGINT,
+    agent_session_id BIGINT REFERENCES ai_agent_sessions(id) ON DELETE SET NULL,
+    status TEXT NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    response_status INTEGER,
+    output JSONB,
+    error JSONB
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_tool_invocations_tool_id_created_at
+    ON ai_tool_invocations (tool_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_ai_tool_invocations_workflow_run_id
+    ON ai_tool_invocations (workflow_run_id)
+    WHERE workflow_run_id IS NOT NULL;
@@ -0,0 +1,51 @@
+-- The configuration and definition on ai_workflows are now the mutable draft.
+-- Runs only ever read from an immutable, published version.
+CREATE TABLE IF NOT EXISTS ai_workflow_versions (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    workflow_id BIGINT NOT NULL REFERENCES ai_workflows(id) ON DELETE CASCADE,
+    version_number INTEGER NOT NULL,
+    configuration JSONB NOT NULL,
+    workflow_definition JSONB NOT NULL,
+    published_by TEXT,
+    change_note TEXT,
+    deleted_at TIMESTAMPTZ,
+    UNIQUE (workflow_id, version_number)
+);
+
+CREATE OR REPLACE FUNCTION prevent_ai_workflow_version_update() RETURNS trigger AS $$
+BEGIN
+    IF NEW.workflow_id IS DISTINCT FROM OLD.workflow_id
+        OR NEW.version_number IS DISTINCT FROM OLD.version_number
+        OR NEW.configuration IS DISTINCT FROM OLD.configuration
+        OR NEW.workflow_definition IS DISTINCT FROM OLD.workflow_definition THEN
+        RAISE EXCEPTION 'ai_workflow_versions are immutable';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ai_workflow_versions_immutable ON ai_workflow_versions;
+CREATE TRIGGER ai_workflow_versions_immutable
+    BEFORE UPDATE ON ai_workflow_versions
+    FOR EACH ROW EXECUTE FUNCTION prevent_ai_workflow_version_update();
+
+ALTER TABLE ai_workflows
+    ADD COLUMN IF NOT EXISTS published_version_id BIGINT REFERENCES ai_workflow_versions(id),
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+ALTER TABLE ai_workflow_executions
+    ADD COLUMN IF NOT EXISTS workflow_version_id BIGINT REFERENCES ai_workflow_versions(id);
+
+-- Publish what is live today as version 1 so existing workflows keep running.
+-- The workflow id doubles as the version id since both are unique snowflakes.
+INSERT INTO ai_workflow_versions (id, created_at, workflow_id, version_number, configuration, workflow_definition, change_note)
+SELECT id, NOW(), id, 1, configuration, workflow_definition, 'Initial version'
+FROM ai_workflows
+WHERE published_version_id IS NULL
+ON CONFLICT DO NOTHING;
+
+UPDATE ai_workflows
+SET published_version_id = id
+WHERE published_version_id IS NULL
+    AND EXISTS (SELECT 1 FROM ai_workflow_versions v WHERE v.id = ai_workflows.id);
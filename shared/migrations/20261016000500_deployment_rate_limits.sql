@@ -0,0 +1,11 @@
+-- Per-deployment overrides for the default request quotas. Any class without a
+-- row here uses the defaults from the environment.
+CREATE TABLE IF NOT EXISTS deployment_rate_limits (
+    deployment_id BIGINT NOT NULL REFERENCES deployments(id) ON DELETE CASCADE,
+    endpoint_class TEXT NOT NULL CHECK (endpoint_class IN ('read', 'write', 'expensive')),
+    requests_per_minute INTEGER NOT NULL CHECK (requests_per_minute > 0),
+    burst INTEGER CHECK (burst > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (deployment_id, endpoint_class)
+);
@@ -0,0 +1,35 @@
+-- Accounts that own console projects. An organization account is shared by its
+-- members; an individual account belongs to one person.
+CREATE TABLE IF NOT EXISTS console_accounts (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMPTZ,
+    kind TEXT NOT NULL DEFAULT 'individual' CHECK (kind IN ('individual', 'organization')),
+    name TEXT NOT NULL,
+    email TEXT NOT NULL,
+    -- NULL means the account can own any number of projects.
+    max_projects INTEGER CHECK (max_projects >= 0)
+);
+
+ALTER TABLE projects
+    ADD COLUMN IF NOT EXISTS owner_id BIGINT REFERENCES console_accounts(id);
+
+CREATE INDEX IF NOT EXISTS idx_projects_owner_id
+    ON projects (owner_id)
+    WHERE deleted_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS console_audit_logs (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    project_id BIGINT NOT NULL,
+    deployment_id BIGINT,
+    actor TEXT,
+    action TEXT NOT NULL,
+    resource_type TEXT NOT NULL,
+    resource_id BIGINT NOT NULL,
+    metadata JSONB NOT NULL DEFAULT '{}'::jsonb
+);
+
+CREATE INDEX IF NOT EXISTS idx_console_audit_logs_project_id_created_at
+    ON console_audit_logs (project_id, created_at DESC);
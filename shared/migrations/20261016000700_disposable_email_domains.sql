@@ -0,0 +1,17 @@
+-- Disposable email providers pulled from the upstream list. The whole table is
+-- replaced on every refresh, and every row carries the time of that refresh.
+CREATE TABLE IF NOT EXISTS disposable_email_domains (
+    domain TEXT PRIMARY KEY,
+    refreshed_at TIMESTAMPTZ NOT NULL
+);
+
+-- Per-deployment additions to the upstream list ('blocked') and exceptions to
+-- it ('allowed').
+CREATE TABLE IF NOT EXISTS deployment_disposable_email_domains (
+    id BIGINT PRIMARY KEY,
+    deployment_id BIGINT NOT NULL REFERENCES deployments(id) ON DELETE CASCADE,
+    domain TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('blocked', 'allowed')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (deployment_id, domain)
+);
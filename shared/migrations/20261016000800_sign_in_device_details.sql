@@ -0,0 +1,9 @@
+ALTER TABLE signins ADD COLUMN IF NOT EXISTS ip_address TEXT;
+ALTER TABLE signins ADD COLUMN IF NOT EXISTS user_agent TEXT;
+ALTER TABLE signins ADD COLUMN IF NOT EXISTS browser TEXT;
+ALTER TABLE signins ADD COLUMN IF NOT EXISTS device TEXT;
+ALTER TABLE signins ADD COLUMN IF NOT EXISTS last_active_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_signins_user_id_active
+    ON signins (user_id)
+    WHERE NOT expired;
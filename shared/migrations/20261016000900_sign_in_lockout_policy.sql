@@ -0,0 +1,3 @@
+ALTER TABLE deployment_auth_settings
+    ADD COLUMN IF NOT EXISTS lockout_policy JSONB NOT NULL
+    DEFAULT '{"enabled": true, "max_failed_attempts": 5, "lockout_duration": 900, "counter_window": 900}'::jsonb;
@@ -0,0 +1,20 @@
+-- Set by admins to make the user pick a new password the next time they
+-- sign in. Cleared once the password is changed.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS require_password_change BOOLEAN NOT NULL DEFAULT false;
+
+-- One-time tokens for admin-initiated password resets. Only a SHA-256 hash of
+-- the token is stored; the token itself is only ever in the emailed link.
+CREATE TABLE IF NOT EXISTS user_password_reset_tokens (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_by TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_password_reset_tokens_user_id
+    ON user_password_reset_tokens (user_id)
+    WHERE used_at IS NULL;
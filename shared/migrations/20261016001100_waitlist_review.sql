@@ -0,0 +1,15 @@
+-- Waitlist entries used to be deleted on approval. They're kept now so
+-- admins can see who was approved or rejected and when.
+ALTER TABLE deployment_waitlist_users
+    ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'approved', 'rejected'));
+ALTER TABLE deployment_waitlist_users
+    ADD COLUMN IF NOT EXISTS metadata JSONB NOT NULL DEFAULT '{}'::jsonb;
+ALTER TABLE deployment_waitlist_users ADD COLUMN IF NOT EXISTS invitation_id BIGINT;
+ALTER TABLE deployment_waitlist_users ADD COLUMN IF NOT EXISTS reviewed_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_deployment_waitlist_users_deployment_status
+    ON deployment_waitlist_users (deployment_id, status, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_waitlist_users_deployment_email
+    ON deployment_waitlist_users (deployment_id, lower(email_address));
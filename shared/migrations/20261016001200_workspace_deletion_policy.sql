@@ -0,0 +1,2 @@
+ALTER TABLE deployment_b2b_settings
+    ADD COLUMN IF NOT EXISTS prevent_last_workspace_deletion BOOLEAN NOT NULL DEFAULT false;
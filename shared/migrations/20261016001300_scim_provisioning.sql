@@ -0,0 +1,24 @@
+-- Bearer tokens identity providers use to call a deployment's SCIM endpoints.
+-- Only a SHA-256 hash of the token is stored; the token is shown once.
+CREATE TABLE IF NOT EXISTS scim_tokens (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    token_prefix TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_by TEXT,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_scim_tokens_deployment_id
+    ON scim_tokens (deployment_id);
+
+-- The identity provider's own id for users and groups it provisioned.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS scim_external_id TEXT;
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS scim_external_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_users_scim_external_id
+    ON users (deployment_id, scim_external_id)
+    WHERE scim_external_id IS NOT NULL;
@@ -0,0 +1,23 @@
+-- Enterprise SAML identity providers configured for a deployment. The SP
+-- private key signs authentication requests and is never returned by the API.
+CREATE TABLE IF NOT EXISTS deployment_sso_connections (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    idp_metadata_url TEXT,
+    idp_metadata_xml TEXT,
+    idp_entity_id TEXT NOT NULL,
+    idp_sso_url TEXT,
+    idp_certificate TEXT NOT NULL,
+    certificate_expires_at TIMESTAMPTZ NOT NULL,
+    attribute_mapping JSONB NOT NULL DEFAULT '{}'::jsonb,
+    allowed_email_domains TEXT[] NOT NULL DEFAULT '{}',
+    sp_certificate TEXT NOT NULL,
+    sp_private_key TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_sso_connections_deployment_id
+    ON deployment_sso_connections (deployment_id);
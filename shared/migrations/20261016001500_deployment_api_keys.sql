@@ -0,0 +1,18 @@
+-- Server-side credentials for a deployment's API. Only a SHA-256 hash of the
+-- key is stored; the key itself is shown once.
+CREATE TABLE IF NOT EXISTS deployment_api_keys (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    created_by TEXT,
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_api_keys_deployment_id
+    ON deployment_api_keys (deployment_id);
@@ -0,0 +1,27 @@
+-- External resources of a production deployment (Cloudflare custom hostnames,
+-- the Postmark sending domain) are created after the deployment commits, from
+-- an outbox of actions written in the same transaction.
+ALTER TABLE deployments
+    ADD COLUMN IF NOT EXISTS provisioning_status TEXT NOT NULL DEFAULT 'ready';
+
+CREATE TABLE IF NOT EXISTS deployment_provisioning_actions (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    payload JSONB NOT NULL DEFAULT '{}',
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    result JSONB,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_provisioning_actions_deployment_id
+    ON deployment_provisioning_actions (deployment_id);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_provisioning_actions_due
+    ON deployment_provisioning_actions (next_attempt_at)
+    WHERE status = 'pending';
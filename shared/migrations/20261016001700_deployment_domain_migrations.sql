@@ -0,0 +1,37 @@
+-- Moving a production deployment to a new custom domain. The new hostnames
+-- and sending domain are provisioned through the same outbox as a new
+-- deployment, and the deployment keeps its old hosts until the new ones
+-- verify. The old hosts and records are kept here so an abort can put
+-- everything back.
+CREATE TABLE IF NOT EXISTS deployment_domain_migrations (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'provisioning',
+    old_frontend_host TEXT NOT NULL,
+    old_backend_host TEXT NOT NULL,
+    old_mail_from_host TEXT NOT NULL,
+    old_publishable_key TEXT NOT NULL,
+    old_domain_verification_records JSONB,
+    old_email_verification_records JSONB,
+    new_frontend_host TEXT NOT NULL,
+    new_backend_host TEXT NOT NULL,
+    new_mail_from_host TEXT NOT NULL,
+    new_domain_verification_records JSONB,
+    new_email_verification_records JSONB,
+    completed_at TIMESTAMPTZ
+);
+
+-- One migration in progress per deployment.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_deployment_domain_migrations_active
+    ON deployment_domain_migrations (deployment_id)
+    WHERE status IN ('provisioning', 'verifying', 'failed');
+
+-- Actions belonging to a migration report to it instead of the deployment.
+ALTER TABLE deployment_provisioning_actions
+    ADD COLUMN IF NOT EXISTS domain_migration_id BIGINT;
+
+CREATE INDEX IF NOT EXISTS idx_deployment_provisioning_actions_domain_migration_id
+    ON deployment_provisioning_actions (domain_migration_id)
+    WHERE domain_migration_id IS NOT NULL;
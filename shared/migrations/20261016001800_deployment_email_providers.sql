@@ -0,0 +1,14 @@
+-- A deployment's own email provider. Credentials are encrypted by the
+-- application before they get here; `settings` keeps the parts that are
+-- safe to show.
+CREATE TABLE IF NOT EXISTS deployment_email_providers (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL UNIQUE,
+    provider TEXT NOT NULL,
+    from_address TEXT,
+    settings JSONB NOT NULL DEFAULT '{}',
+    encrypted_credentials TEXT NOT NULL,
+    verified_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,13 @@
+-- A deployment's overrides of the platform's feature flags. Flags that aren't
+-- set here fall back to the platform defaults.
+CREATE TABLE IF NOT EXISTS deployment_feature_flags (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL UNIQUE,
+    flags JSONB NOT NULL DEFAULT '{}'
+);
+
+-- Rollout tracking looks deployments up by flag value.
+CREATE INDEX IF NOT EXISTS idx_deployment_feature_flags_flags
+    ON deployment_feature_flags USING GIN (flags jsonb_path_ops);
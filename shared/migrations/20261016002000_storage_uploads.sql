@@ -0,0 +1,25 @@
+-- Files clients were given a signed URL to upload straight to storage. A row
+-- stays unconfirmed until the object is checked against the size, type and
+-- checksum it was signed for; unconfirmed ones are deleted, object included,
+-- once they expire.
+CREATE TABLE IF NOT EXISTS storage_uploads (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    storage_class TEXT NOT NULL,
+    object_key TEXT NOT NULL UNIQUE,
+    content_type TEXT NOT NULL,
+    content_length BIGINT NOT NULL,
+    checksum_sha256 TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    confirmed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_storage_uploads_deployment_id
+    ON storage_uploads (deployment_id);
+
+-- The cleanup task looks for expired, unconfirmed uploads.
+CREATE INDEX IF NOT EXISTS idx_storage_uploads_unconfirmed
+    ON storage_uploads (expires_at)
+    WHERE confirmed_at IS NULL;
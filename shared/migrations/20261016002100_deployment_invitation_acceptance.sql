@@ -0,0 +1,7 @@
+-- Invitations are accepted once, through their signed link, and can be
+-- revoked until then. Both are kept on the row so a replayed link can be told
+-- apart from one that was never valid.
+ALTER TABLE deployment_invitations
+    ADD COLUMN IF NOT EXISTS accepted_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS accepted_user_id BIGINT,
+    ADD COLUMN IF NOT EXISTS revoked_at TIMESTAMPTZ;
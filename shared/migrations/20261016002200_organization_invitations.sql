@@ -0,0 +1,25 @@
+-- Invitations into one organization, with the role the invitee gets. Like
+-- deployment invitations they are accepted once through a signed link and can
+-- be revoked until then.
+CREATE TABLE IF NOT EXISTS organization_invitations (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    organization_id BIGINT NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    email_address TEXT NOT NULL,
+    organization_role_id BIGINT,
+    inviter_id BIGINT,
+    expiry TIMESTAMPTZ NOT NULL,
+    accepted_at TIMESTAMPTZ,
+    accepted_user_id BIGINT,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_organization_invitations_organization_id
+    ON organization_invitations (organization_id, created_at DESC);
+
+-- Looked up when an address is invited again, to find the pending one.
+CREATE INDEX IF NOT EXISTS idx_organization_invitations_pending_email
+    ON organization_invitations (organization_id, lower(email_address))
+    WHERE accepted_at IS NULL AND revoked_at IS NULL;
@@ -0,0 +1,25 @@
+-- Archives of a deployment's end-user data, built in the background for
+-- data-portability requests. The archive is kept in private storage until
+-- expires_at, after which it is deleted and the row marked expired.
+CREATE TABLE IF NOT EXISTS deployment_data_exports (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    requested_by TEXT,
+    object_key TEXT NOT NULL,
+    size_bytes BIGINT,
+    record_counts JSONB,
+    error TEXT,
+    completed_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_data_exports_deployment_id
+    ON deployment_data_exports (deployment_id, created_at DESC);
+
+-- A deployment runs one export at a time.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_deployment_data_exports_active
+    ON deployment_data_exports (deployment_id)
+    WHERE status IN ('pending', 'running');
@@ -0,0 +1,19 @@
+-- Whether a deployment's DNS records are verified, kept up to date by the
+-- verification and certificate sync commands so deployments can be listed and
+-- filtered without rerunning DNS checks. Existing production deployments are
+-- left NULL here and filled in from their stored records by
+-- MigrateDeploymentVerificationStatusCommand on startup.
+ALTER TABLE deployments
+    ADD COLUMN IF NOT EXISTS verification_status TEXT;
+
+ALTER TABLE deployments
+    ALTER COLUMN verification_status SET DEFAULT 'pending';
+
+-- Staging deployments have no records of their own to verify.
+UPDATE deployments
+SET verification_status = 'verified'
+WHERE mode = 'staging' AND verification_status IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_deployments_verification_status
+    ON deployments (verification_status)
+    WHERE deleted_at IS NULL;
@@ -0,0 +1,5 @@
+-- Origins besides the deployment's frontend host that its redirect settings
+-- may point at. Existing URLs outside the list are left in place and reported
+-- by ValidateRedirectUrlsQuery; the list is only enforced on updates.
+ALTER TABLE deployment_ui_settings
+    ADD COLUMN IF NOT EXISTS allowed_redirect_origins TEXT[] NOT NULL DEFAULT '{}';
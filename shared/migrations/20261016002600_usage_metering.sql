@@ -0,0 +1,43 @@
+-- Billing usage per deployment and calendar month. Live figures come from
+-- the ClickHouse usage_events table; a row here is written when the period
+-- is closed and never changes afterwards.
+CREATE TABLE IF NOT EXISTS deployment_usage_periods (
+    id BIGINT PRIMARY KEY,
+    deployment_id BIGINT NOT NULL REFERENCES deployments(id) ON DELETE CASCADE,
+    period_start DATE NOT NULL,
+    active_users BIGINT NOT NULL DEFAULT 0,
+    emails_sent BIGINT NOT NULL DEFAULT 0,
+    emails_by_template JSONB NOT NULL DEFAULT '{}'::jsonb,
+    closed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set once the billing event for the period has been published.
+    event_published_at TIMESTAMPTZ,
+    UNIQUE (deployment_id, period_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_usage_periods_unpublished
+    ON deployment_usage_periods (closed_at)
+    WHERE event_published_at IS NULL;
+
+-- Events that arrived for a period after it was closed. They are reported
+-- with the deployment's next billing event instead of changing the frozen
+-- period.
+CREATE TABLE IF NOT EXISTS deployment_usage_adjustments (
+    id BIGINT PRIMARY KEY,
+    deployment_id BIGINT NOT NULL REFERENCES deployments(id) ON DELETE CASCADE,
+    period_start DATE NOT NULL,
+    metric TEXT NOT NULL,
+    quantity BIGINT NOT NULL DEFAULT 1,
+    user_id BIGINT,
+    template_name TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    reported_at TIMESTAMPTZ
+);
+
+-- A user counts once per period, however late their sign-ins arrive.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_deployment_usage_adjustments_active_user
+    ON deployment_usage_adjustments (deployment_id, period_start, user_id)
+    WHERE metric = 'active_user';
+
+CREATE INDEX IF NOT EXISTS idx_deployment_usage_adjustments_unreported
+    ON deployment_usage_adjustments (deployment_id)
+    WHERE reported_at IS NULL;
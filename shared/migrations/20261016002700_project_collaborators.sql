@@ -0,0 +1,33 @@
+-- Console accounts that can work on a project, and the role each one has. A
+-- row without an account is an invitation sent to an email address that has
+-- no account yet; it turns into a membership when that account signs up.
+CREATE TABLE IF NOT EXISTS project_collaborators (
+    id BIGINT PRIMARY KEY,
+    project_id BIGINT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    account_id BIGINT REFERENCES console_accounts(id) ON DELETE CASCADE,
+    invited_email TEXT,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'admin', 'developer', 'read_only')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((account_id IS NULL) <> (invited_email IS NULL))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_project_collaborators_project_account
+    ON project_collaborators (project_id, account_id)
+    WHERE account_id IS NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_project_collaborators_project_invited_email
+    ON project_collaborators (project_id, LOWER(invited_email))
+    WHERE account_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_project_collaborators_account_id
+    ON project_collaborators (account_id)
+    WHERE account_id IS NOT NULL;
+
+-- Every existing project starts with its owning account as its owner. The
+-- project's id doubles as the row id; the id generator never hands it out again.
+INSERT INTO project_collaborators (id, project_id, account_id, role)
+SELECT p.id, p.id, p.owner_id, 'owner'
+FROM projects p
+WHERE p.owner_id IS NOT NULL AND p.deleted_at IS NULL
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,13 @@
+-- Deleting an organization now marks it and everything hanging off it as
+-- deleted instead of removing the rows. Workspaces already had the column.
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE organization_memberships ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE organization_roles ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE workspaces ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE workspace_memberships ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE workspace_roles ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+-- Organization lists only ever show the live ones.
+CREATE INDEX IF NOT EXISTS idx_organizations_deployment_live
+    ON organizations (deployment_id, created_at DESC)
+    WHERE deleted_at IS NULL;
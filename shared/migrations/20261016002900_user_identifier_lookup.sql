@@ -0,0 +1,10 @@
+-- Exact, case-insensitive lookups of users by email address or username,
+-- and by phone number as stored (E.164).
+CREATE INDEX IF NOT EXISTS idx_user_email_addresses_lower_email
+    ON user_email_addresses (LOWER(email_address));
+
+CREATE INDEX IF NOT EXISTS idx_user_phone_numbers_phone_number
+    ON user_phone_numbers (phone_number);
+
+CREATE INDEX IF NOT EXISTS idx_users_deployment_lower_username
+    ON users (deployment_id, LOWER(username));
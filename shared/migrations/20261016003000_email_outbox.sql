@@ -0,0 +1,28 @@
+-- Every email sent on a deployment's behalf. The rendered message is kept
+-- only while it may still need to be retried.
+CREATE TABLE IF NOT EXISTS email_outbox (
+    id BIGINT PRIMARY KEY,
+    deployment_id BIGINT NOT NULL REFERENCES deployments(id) ON DELETE CASCADE,
+    template_name TEXT NOT NULL,
+    to_email TEXT NOT NULL,
+    from_email TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    body_html TEXT,
+    body_text TEXT,
+    status TEXT NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'sent', 'failed', 'bounced')),
+    provider TEXT,
+    provider_message_id TEXT,
+    error TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    sent_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_outbox_deployment_created
+    ON email_outbox (deployment_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_email_outbox_failed
+    ON email_outbox (deployment_id, created_at)
+    WHERE status = 'failed';
@@ -0,0 +1,25 @@
+-- Monthly AI budgets. A NULL limit means no limit. Spend is in millionths
+-- of a US dollar, as reported with each model invocation.
+ALTER TABLE ai_agents
+    ADD COLUMN IF NOT EXISTS monthly_token_limit BIGINT,
+    ADD COLUMN IF NOT EXISTS monthly_spend_limit_micros BIGINT;
+
+ALTER TABLE deployments
+    ADD COLUMN IF NOT EXISTS ai_monthly_token_limit BIGINT,
+    ADD COLUMN IF NOT EXISTS ai_monthly_spend_limit_micros BIGINT;
+
+-- Model usage per agent and UTC hour. Rows outlive their agent so the
+-- deployment's spend for the month doesn't drop when an agent is deleted.
+CREATE TABLE IF NOT EXISTS ai_agent_usage_hourly (
+    deployment_id BIGINT NOT NULL REFERENCES deployments(id) ON DELETE CASCADE,
+    agent_id BIGINT NOT NULL,
+    hour TIMESTAMPTZ NOT NULL,
+    invocations BIGINT NOT NULL DEFAULT 0,
+    prompt_tokens BIGINT NOT NULL DEFAULT 0,
+    completion_tokens BIGINT NOT NULL DEFAULT 0,
+    spend_micros BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (agent_id, hour)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_agent_usage_hourly_deployment
+    ON ai_agent_usage_hourly (deployment_id, hour);
@@ -0,0 +1,11 @@
+-- How each knowledge base document was last split into chunks, and whether
+-- that worked. Documents from before this are assumed to have been ingested.
+ALTER TABLE ai_knowledge_base_documents
+    ADD COLUMN IF NOT EXISTS ingestion_status TEXT NOT NULL DEFAULT 'ready'
+        CHECK (ingestion_status IN ('pending', 'ready', 'failed')),
+    ADD COLUMN IF NOT EXISTS ingestion_error TEXT,
+    ADD COLUMN IF NOT EXISTS chunk_count INTEGER,
+    ADD COLUMN IF NOT EXISTS chunk_size INTEGER,
+    ADD COLUMN IF NOT EXISTS chunk_overlap INTEGER;
+
+ALTER TABLE ai_knowledge_base_documents ALTER COLUMN ingestion_status SET DEFAULT 'pending';
@@ -0,0 +1,9 @@
+-- Tools imported from an OpenAPI document remember which operation they
+-- came from, so importing the document again updates them in place.
+ALTER TABLE ai_tools
+    ADD COLUMN IF NOT EXISTS openapi_source TEXT,
+    ADD COLUMN IF NOT EXISTS openapi_operation_id TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_ai_tools_openapi_operation
+    ON ai_tools (deployment_id, openapi_source, openapi_operation_id)
+    WHERE openapi_operation_id IS NOT NULL;
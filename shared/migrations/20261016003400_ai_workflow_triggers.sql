@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS ai_workflow_triggers (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    workflow_id BIGINT NOT NULL REFERENCES ai_workflows(id) ON DELETE CASCADE,
+    trigger_type TEXT NOT NULL CHECK (trigger_type IN ('schedule', 'webhook')),
+    cron_expression TEXT,
+    timezone TEXT,
+    -- SHA-256 of the secret in a webhook trigger's URL
+    token_hash TEXT UNIQUE,
+    last_fired_at TIMESTAMPTZ,
+    next_fire_at TIMESTAMPTZ,
+    last_run_id BIGINT,
+    last_error TEXT,
+    CHECK (
+        (trigger_type = 'schedule' AND cron_expression IS NOT NULL AND timezone IS NOT NULL)
+        OR (trigger_type = 'webhook' AND token_hash IS NOT NULL)
+    )
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_workflow_triggers_workflow_id
+    ON ai_workflow_triggers (workflow_id);
+
+CREATE INDEX IF NOT EXISTS idx_ai_workflow_triggers_next_fire_at
+    ON ai_workflow_triggers (next_fire_at)
+    WHERE next_fire_at IS NOT NULL;
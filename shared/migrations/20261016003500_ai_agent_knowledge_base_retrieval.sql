@@ -0,0 +1,22 @@
+-- An agent can use several knowledge bases, each searched with its own
+-- settings. Detaching removes only the link, never the knowledge base.
+CREATE TABLE IF NOT EXISTS ai_agent_knowledge_bases (
+    agent_id BIGINT NOT NULL REFERENCES ai_agents(id) ON DELETE CASCADE,
+    knowledge_base_id BIGINT NOT NULL REFERENCES ai_knowledge_bases(id) ON DELETE CASCADE,
+    PRIMARY KEY (agent_id, knowledge_base_id)
+);
+
+ALTER TABLE ai_agent_knowledge_bases
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN IF NOT EXISTS top_k INTEGER NOT NULL DEFAULT 5,
+    ADD COLUMN IF NOT EXISTS score_threshold REAL,
+    ADD COLUMN IF NOT EXISTS include_citations BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Attaching again updates the settings, which needs a unique key even where
+-- the table predates this migration
+CREATE UNIQUE INDEX IF NOT EXISTS idx_ai_agent_knowledge_bases_agent_kb
+    ON ai_agent_knowledge_bases (agent_id, knowledge_base_id);
+
+CREATE INDEX IF NOT EXISTS idx_ai_agent_knowledge_bases_kb
+    ON ai_agent_knowledge_bases (knowledge_base_id);
@@ -0,0 +1,15 @@
+-- The embedding provider a deployment's knowledge bases use, by name, unless
+-- a knowledge base names its own. NULL means the platform default.
+ALTER TABLE deployments ADD COLUMN IF NOT EXISTS ai_embedding_provider TEXT;
+
+-- embedding_model is the model the knowledge base's vectors were made with.
+-- It is NULL for knowledge bases from before it was recorded, whose vectors
+-- came from the platform default, until their next write records it.
+ALTER TABLE ai_knowledge_bases
+    ADD COLUMN IF NOT EXISTS embedding_provider TEXT,
+    ADD COLUMN IF NOT EXISTS embedding_model TEXT;
+
+-- The provider a re-embedding job rebuilds the vectors with
+ALTER TABLE ai_knowledge_base_reembed_jobs
+    ADD COLUMN IF NOT EXISTS embedding_provider TEXT,
+    ADD COLUMN IF NOT EXISTS embedding_model TEXT;
@@ -0,0 +1,8 @@
+-- How a knowledge base splits its documents into chunks, as the
+-- text_processing ChunkingConfig. NULL for knowledge bases from before it was
+-- stored, which were all split into plain 1000 character windows.
+ALTER TABLE ai_knowledge_bases ADD COLUMN IF NOT EXISTS chunking_config JSONB;
+
+-- The strategy a document was last chunked with, next to its chunk size and
+-- overlap. NULL means characters.
+ALTER TABLE ai_knowledge_base_documents ADD COLUMN IF NOT EXISTS chunking_strategy TEXT;
@@ -0,0 +1,25 @@
+-- Work run later and retried until it succeeds, such as removing a deleted
+-- deployment's resources at Cloudflare and Postmark. A job whose attempts
+-- run out stays here as dead until someone retries it.
+CREATE TABLE IF NOT EXISTS background_jobs (
+    id BIGINT PRIMARY KEY,
+    job_type TEXT NOT NULL,
+    payload JSONB NOT NULL DEFAULT '{}'::jsonb,
+    status TEXT NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'running', 'succeeded', 'dead')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 8,
+    -- When a queued job is next due, or when a running job's claim runs out.
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    completed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_background_jobs_due
+    ON background_jobs (run_at)
+    WHERE status IN ('queued', 'running');
+
+CREATE INDEX IF NOT EXISTS idx_background_jobs_status_created
+    ON background_jobs (status, created_at DESC);
@@ -0,0 +1,5 @@
+-- The mail-from host a deployment is moving to, and the Postmark domain's
+-- records for it. The deployment keeps sending from mail_from_host until
+-- these verify, and both are NULL when no change is in progress.
+ALTER TABLE deployments ADD COLUMN IF NOT EXISTS pending_mail_from_host TEXT;
+ALTER TABLE deployments ADD COLUMN IF NOT EXISTS pending_email_verification_records JSONB;
@@ -0,0 +1,22 @@
+-- Usernames are unique within a deployment, ignoring case. Users that already
+-- share a name differing only in case keep it on the oldest account; the
+-- others get their id appended so the index can be built, and can pick a new
+-- name afterwards.
+UPDATE users u
+SET username = u.username || '-' || u.id
+FROM (
+    SELECT id, ROW_NUMBER() OVER (
+        PARTITION BY deployment_id, LOWER(username)
+        ORDER BY created_at, id
+    ) AS position
+    FROM users
+    WHERE username IS NOT NULL
+) ranked
+WHERE ranked.id = u.id AND ranked.position > 1;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_deployment_lower_username_unique
+    ON users (deployment_id, LOWER(username))
+    WHERE username IS NOT NULL;
+
+-- Lookups by username use the unique index now.
+DROP INDEX IF EXISTS idx_users_deployment_lower_username;
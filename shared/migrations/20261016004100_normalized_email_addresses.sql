@@ -0,0 +1,19 @@
+-- Each address as the mailbox it delivers to: lowercased, with the mail
+-- provider's aliases folded away and, on deployments with
+-- block_subaddresses, without its subaddress tag. Written by the API, which
+-- holds the provider rules, so it is NULL until the backfill below runs.
+ALTER TABLE user_email_addresses ADD COLUMN IF NOT EXISTS normalized_email_address TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_user_email_addresses_deployment_normalized
+    ON user_email_addresses (deployment_id, normalized_email_address);
+
+-- Backfills through the job queue, one job per deployment with addresses.
+-- Deployment ids and job ids come from the same id generator, so reusing the
+-- deployment's id as the job's can't collide.
+INSERT INTO background_jobs (id, job_type, payload)
+SELECT d.id, 'normalize_email_addresses', jsonb_build_object('deployment_id', d.id)
+FROM deployments d
+WHERE EXISTS (
+    SELECT 1 FROM user_email_addresses e WHERE e.deployment_id = d.id
+)
+ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,17 @@
+-- Deleted users are kept, hidden, until purge_after so the deletion can be
+-- undone, and then purged by a background job. identifiers_released is set
+-- when the deployment frees a deleted user's username and email addresses
+-- straight away rather than at the purge.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS purge_after TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS identifiers_released BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE deployment_auth_settings
+    ADD COLUMN IF NOT EXISTS user_deletion_policy JSONB NOT NULL
+    DEFAULT '{"retention_days": 30, "release_identifiers_immediately": false}'::jsonb;
+
+-- A released username can be taken by someone else.
+DROP INDEX IF EXISTS idx_users_deployment_lower_username_unique;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_deployment_lower_username_unique
+    ON users (deployment_id, LOWER(username))
+    WHERE username IS NOT NULL AND NOT identifiers_released;
@@ -0,0 +1,30 @@
+-- Platform operators may use the /admin routes, which reach across every
+-- project.
+ALTER TABLE console_accounts
+    ADD COLUMN IF NOT EXISTS platform_operator BOOLEAN NOT NULL DEFAULT false;
+
+-- Global search matches project names anywhere in the name, hostnames and
+-- publishable keys by prefix and custom hostname ids exactly. Deleted rows
+-- are searched too, so none of these indexes are partial.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_projects_lower_name_trgm
+    ON projects USING GIN (lower(name) gin_trgm_ops);
+
+CREATE INDEX IF NOT EXISTS idx_deployments_lower_frontend_host_prefix
+    ON deployments (lower(frontend_host) text_pattern_ops);
+
+CREATE INDEX IF NOT EXISTS idx_deployments_lower_backend_host_prefix
+    ON deployments (lower(backend_host) text_pattern_ops);
+
+CREATE INDEX IF NOT EXISTS idx_deployments_lower_mail_from_host_prefix
+    ON deployments (lower(mail_from_host) text_pattern_ops);
+
+CREATE INDEX IF NOT EXISTS idx_deployments_publishable_key_prefix
+    ON deployments (publishable_key text_pattern_ops);
+
+CREATE INDEX IF NOT EXISTS idx_deployments_frontend_hostname_id
+    ON deployments ((domain_verification_records::jsonb ->> 'frontend_hostname_id'));
+
+CREATE INDEX IF NOT EXISTS idx_deployments_backend_hostname_id
+    ON deployments ((domain_verification_records::jsonb ->> 'backend_hostname_id'));
@@ -0,0 +1,17 @@
+-- Events within one organization, shown to that organization's admins. The
+-- project audit log records the same changes for console users; this stream
+-- only holds what an organization admin may see.
+CREATE TABLE IF NOT EXISTS organization_audit_events (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    organization_id BIGINT NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    -- Workspaces are deleted outright, and their events outlive them.
+    workspace_id BIGINT,
+    actor TEXT,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL DEFAULT '{}'::jsonb
+);
+
+CREATE INDEX IF NOT EXISTS idx_organization_audit_events_organization_id
+    ON organization_audit_events (organization_id, created_at DESC, id DESC);
@@ -0,0 +1,24 @@
+-- The first factors the sign-in UI offers besides first_factor, in order.
+ALTER TABLE deployment_auth_settings
+    ADD COLUMN IF NOT EXISTS alternate_first_factors JSONB NOT NULL DEFAULT '[]'::jsonb;
+
+-- Existing deployments get what project creation would have given them:
+-- phone OTP, then username and password, for whichever of those is enabled
+-- and isn't already the primary factor.
+UPDATE deployment_auth_settings s
+SET alternate_first_factors = COALESCE(
+    (
+        SELECT jsonb_agg(f.factor ORDER BY f.position)
+        FROM (
+            VALUES
+                (1, 'phone_otp', COALESCE((s.phone_number->>'enabled')::BOOLEAN, FALSE)
+                    AND COALESCE((s.auth_factors_enabled->>'phone_otp')::BOOLEAN, FALSE)),
+                (2, 'username_password', COALESCE((s.username->>'enabled')::BOOLEAN, FALSE)
+                    AND COALESCE((s.auth_factors_enabled->>'username_password')::BOOLEAN, FALSE)
+                    AND COALESCE((s.password->>'enabled')::BOOLEAN, TRUE))
+        ) AS f(position, factor, available)
+        WHERE f.available AND f.factor <> s.first_factor::text
+    ),
+    '[]'::jsonb
+)
+WHERE s.alternate_first_factors = '[]'::jsonb;
@@ -0,0 +1,3 @@
+ALTER TABLE deployment_auth_settings
+    ADD COLUMN IF NOT EXISTS otp_settings JSONB NOT NULL
+    DEFAULT '{"code_length": 6, "expiry_minutes": 10, "max_attempts": 5, "resend_cooldown_seconds": 30}'::jsonb;
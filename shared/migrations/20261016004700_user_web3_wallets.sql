@@ -0,0 +1,27 @@
+-- Ethereum wallets users sign in with. Addresses are stored EIP-55
+-- checksummed, so one wallet is always one address. Like email addresses, a
+-- wallet belongs to at most one user per deployment, which is checked when
+-- it's added since a deleted user's released wallets no longer count.
+CREATE TABLE IF NOT EXISTS user_web3_wallets (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    chain_id BIGINT NOT NULL DEFAULT 1,
+    verified BOOLEAN NOT NULL DEFAULT FALSE,
+    verified_at TIMESTAMPTZ,
+    is_primary BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_web3_wallets_deployment_address
+    ON user_web3_wallets (deployment_id, address);
+
+CREATE INDEX IF NOT EXISTS idx_user_web3_wallets_user_id
+    ON user_web3_wallets (user_id);
+
+-- At most one primary wallet per user.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user_web3_wallets_primary
+    ON user_web3_wallets (user_id)
+    WHERE is_primary;
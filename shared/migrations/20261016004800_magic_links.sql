@@ -0,0 +1,28 @@
+-- Emailed sign-in links. Only a hash of each token is kept. A link works
+-- once, until it expires, and, when the deployment invalidates previous
+-- links, until a newer one is requested for the same address.
+CREATE TABLE IF NOT EXISTS magic_links (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL,
+    email_address TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    redirect_url TEXT,
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ,
+    invalidated_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_magic_links_token_hash
+    ON magic_links (token_hash);
+
+CREATE INDEX IF NOT EXISTS idx_magic_links_pending
+    ON magic_links (deployment_id, LOWER(email_address))
+    WHERE consumed_at IS NULL AND invalidated_at IS NULL;
+
+-- Existing settings get the defaults written out; anything already set wins.
+UPDATE deployment_auth_settings
+SET magic_link = '{"expiry_minutes": 15, "invalidate_previous": true}'::jsonb
+    || COALESCE(magic_link, '{}'::jsonb)
+WHERE magic_link IS NULL
+    OR NOT (magic_link ? 'expiry_minutes' AND magic_link ? 'invalidate_previous');
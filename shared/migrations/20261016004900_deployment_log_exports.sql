@@ -0,0 +1,29 @@
+-- Ships a deployment's audit and request logs to a bucket the customer
+-- owns. Credentials are encrypted by the application before they get here;
+-- `credentials` keeps the parts that are safe to show. Each kind of log has
+-- an exported-through watermark, the `(timestamp, id)` of the last entry
+-- written, so a run only exports what came after it.
+CREATE TABLE IF NOT EXISTS deployment_log_exports (
+    id BIGINT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    deployment_id BIGINT NOT NULL UNIQUE,
+    bucket TEXT NOT NULL,
+    region TEXT NOT NULL,
+    prefix TEXT NOT NULL DEFAULT '',
+    format TEXT NOT NULL DEFAULT 'ndjson',
+    schedule TEXT NOT NULL DEFAULT 'daily',
+    credentials JSONB NOT NULL DEFAULT '{}',
+    encrypted_credentials TEXT NOT NULL,
+    audit_logs_exported_through TIMESTAMPTZ,
+    audit_logs_exported_through_id BIGINT,
+    request_logs_exported_through TIMESTAMPTZ,
+    request_logs_exported_through_id BIGINT,
+    next_run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_success_at TIMESTAMPTZ,
+    last_error TEXT,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_log_exports_next_run_at
+    ON deployment_log_exports (next_run_at);
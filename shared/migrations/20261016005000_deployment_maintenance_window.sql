@@ -0,0 +1,8 @@
+-- What end users are told while a deployment is in maintenance, and an
+-- optional scheduled window. Maintenance is on while `maintenance_mode` is,
+-- or from `maintenance_starts_at` until `maintenance_ends_at`; the end is
+-- also the estimated end shown to users.
+ALTER TABLE deployments
+    ADD COLUMN IF NOT EXISTS maintenance_message TEXT,
+    ADD COLUMN IF NOT EXISTS maintenance_starts_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS maintenance_ends_at TIMESTAMPTZ;
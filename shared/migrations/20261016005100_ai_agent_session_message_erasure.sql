@@ -0,0 +1,18 @@
+-- Messages stay append-only, except that erasing a user may blank what they
+-- say. Erasure marks its own transaction with `wacht.erasing_user_data`, and
+-- even then only the content and tool calls may change.
+CREATE OR REPLACE FUNCTION prevent_ai_agent_session_message_update() RETURNS trigger AS $$
+BEGIN
+    IF current_setting('wacht.erasing_user_data', true) = 'on'
+        AND (NEW.id, NEW.created_at, NEW.session_id, NEW.sequence, NEW.role,
+             NEW.prompt_tokens, NEW.completion_tokens)
+            IS NOT DISTINCT FROM
+            (OLD.id, OLD.created_at, OLD.session_id, OLD.sequence, OLD.role,
+             OLD.prompt_tokens, OLD.completion_tokens)
+    THEN
+        RETURN NEW;
+    END IF;
+
+    RAISE EXCEPTION 'ai_agent_session_messages is append-only';
+END;
+$$ LANGUAGE plpgsql;
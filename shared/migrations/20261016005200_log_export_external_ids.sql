@@ -0,0 +1,6 @@
+-- The external ID the platform passes when it assumes a customer's role to
+-- export logs. It is generated for the deployment the first time it's asked
+-- for, never chosen by the customer, so one deployment can't have the
+-- platform assume a role that trusts another.
+ALTER TABLE deployments
+    ADD COLUMN IF NOT EXISTS log_export_external_id TEXT;
@@ -0,0 +1,17 @@
+-- One waitlist entry per address and deployment, so joining twice at once
+-- can't leave two entries to review. Duplicates already there are folded
+-- into the one that was reviewed, or else the earliest.
+DELETE FROM deployment_waitlist_users w
+USING (
+    SELECT id, ROW_NUMBER() OVER (
+        PARTITION BY deployment_id, lower(email_address)
+        ORDER BY (status <> 'pending') DESC, created_at, id
+    ) AS position
+    FROM deployment_waitlist_users
+) ranked
+WHERE w.id = ranked.id AND ranked.position > 1;
+
+DROP INDEX IF EXISTS idx_deployment_waitlist_users_deployment_email;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_deployment_waitlist_users_deployment_email
+    ON deployment_waitlist_users (deployment_id, lower(email_address));
@@ -0,0 +1,298 @@
+use chrono::Utc;
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{AiAgentSession, AiAgentSessionMessage},
+    state::AppState,
+};
+
+use super::{Command, RecordAgentUsageCommand, ensure_within_ai_budget};
+
+pub(crate) const AGENT_SESSION_MESSAGE_ROLES: [&str; 4] = ["system", "user", "assistant", "tool"];
+
+pub struct CreateAgentSessionCommand {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+    pub metadata: serde_json::Value,
+}
+
+impl CreateAgentSessionCommand {
+    pub fn new(deployment_id: i64, agent_id: i64) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            metadata: serde_json::json!({}),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+impl Command for CreateAgentSessionCommand {
+    type Output = AiAgentSession;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let agent = sqlx::query("SELECT id FROM ai_agents WHERE id = $1 AND deployment_id = $2")
+            .bind(self.agent_id)
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?;
+
+        if agent.is_none() {
+            return Err(AppError::NotFound("Agent not found".to_string()));
+        }
+
+        ensure_within_ai_budget(app_state, self.deployment_id, self.agent_id).await?;
+
+        let session_id = app_state.sf.next_id()? as i64;
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO ai_agent_sessions (id, created_at, updated_at, agent_id, status, metadata)
+            VALUES ($1, $2, $3, $4, 'active', $5)
+            "#,
+        )
+        .bind(session_id)
+        .bind(now)
+        .bind(now)
+        .bind(self.agent_id)
+        .bind(&self.metadata)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(AiAgentSession {
+            id: session_id,
+            created_at: now,
+            updated_at: now,
+            agent_id: self.agent_id,
+            status: "active".to_string(),
+            metadata: self.metadata,
+            closed_at: None,
+        })
+    }
+}
+
+pub struct AppendAgentSessionMessageCommand {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+    pub session_id: i64,
+    pub role: String,
+    pub content: String,
+    pub prompt_tokens: i32,
+    pub completion_tokens: i32,
+    pub spend_micros: i64,
+    pub tool_calls: Option<serde_json::Value>,
+}
+
+impl AppendAgentSessionMessageCommand {
+    pub fn new(
+        deployment_id: i64,
+        agent_id: i64,
+        session_id: i64,
+        role: String,
+        content: String,
+    ) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            session_id,
+            role,
+            content,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            spend_micros: 0,
+            tool_calls: None,
+        }
+    }
+
+    pub fn with_token_counts(mut self, prompt_tokens: i32, completion_tokens: i32) -> Self {
+        self.prompt_tokens = prompt_tokens;
+        self.completion_tokens = completion_tokens;
+        self
+    }
+
+    /// What the model invocation behind the message cost, in millionths of
+    /// a US dollar.
+    pub fn with_spend_micros(mut self, spend_micros: i64) -> Self {
+        self.spend_micros = spend_micros;
+        self
+    }
+
+    pub fn with_tool_calls(mut self, tool_calls: serde_json::Value) -> Self {
+        self.tool_calls = Some(tool_calls);
+        self
+    }
+}
+
+impl Command for AppendAgentSessionMessageCommand {
+    type Output = AiAgentSessionMessage;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if !AGENT_SESSION_MESSAGE_ROLES.contains(&self.role.as_str()) {
+            return Err(AppError::Validation(format!(
+                "Message role must be one of: {}",
+                AGENT_SESSION_MESSAGE_ROLES.join(", ")
+            )));
+        }
+
+        if self.prompt_tokens < 0 || self.completion_tokens < 0 {
+            return Err(AppError::Validation(
+                "Token counts cannot be negative".to_string(),
+            ));
+        }
+
+        // A user message is what starts the next model invocation, so that
+        // is where a used-up budget stops the agent. Whatever the model
+        // already returned is still recorded.
+        if self.role == "user" {
+            ensure_within_ai_budget(app_state, self.deployment_id, self.agent_id).await?;
+        }
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        // Locking the session row serialises appends so sequence numbers stay gapless
+        let session = sqlx::query(
+            r#"
+            SELECT s.status
+            FROM ai_agent_sessions s
+            JOIN ai_agents a ON a.id = s.agent_id
+            WHERE s.id = $1 AND s.agent_id = $2 AND a.deployment_id = $3
+            FOR UPDATE OF s
+            "#,
+        )
+        .bind(self.session_id)
+        .bind(self.agent_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+        let status: String = session.get("status");
+        if status != "active" {
+            return Err(AppError::BadRequest(
+                "Messages cannot be added to a closed session".to_string(),
+            ));
+        }
+
+        let sequence: i32 = sqlx::query(
+            "SELECT COALESCE(MAX(sequence), 0) + 1 AS sequence FROM ai_agent_session_messages WHERE session_id = $1",
+        )
+        .bind(self.session_id)
+        .fetch_one(&mut *tx)
+        .await?
+        .get("sequence");
+
+        let message_id = app_state.sf.next_id()? as i64;
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO ai_agent_session_messages
+            (id, created_at, session_id, sequence, role, content, prompt_tokens, completion_tokens, tool_calls)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(message_id)
+        .bind(now)
+        .bind(self.session_id)
+        .bind(sequence)
+        .bind(&self.role)
+        .bind(&self.content)
+        .bind(self.prompt_tokens)
+        .bind(self.completion_tokens)
+        .bind(&self.tool_calls)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE ai_agent_sessions SET updated_at = $2 WHERE id = $1")
+            .bind(self.session_id)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        if self.prompt_tokens > 0 || self.completion_tokens > 0 || self.spend_micros > 0 {
+            let usage = RecordAgentUsageCommand::new(
+                self.deployment_id,
+                self.agent_id,
+                self.prompt_tokens.into(),
+                self.completion_tokens.into(),
+            )
+            .with_spend_micros(self.spend_micros);
+            if let Err(e) = usage.execute(app_state).await {
+                tracing::warn!(
+                    deployment_id = self.deployment_id,
+                    agent_id = self.agent_id,
+                    "Failed to record agent usage: {}",
+                    e
+                );
+            }
+        }
+
+        Ok(AiAgentSessionMessage {
+            id: message_id,
+            created_at: now,
+            session_id: self.session_id,
+            sequence,
+            role: self.role,
+            content: self.content,
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            tool_calls: self.tool_calls,
+        })
+    }
+}
+
+pub struct CloseAgentSessionCommand {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+    pub session_id: i64,
+}
+
+impl CloseAgentSessionCommand {
+    pub fn new(deployment_id: i64, agent_id: i64, session_id: i64) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            session_id,
+        }
+    }
+}
+
+impl Command for CloseAgentSessionCommand {
+    type Output = AiAgentSession;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            UPDATE ai_agent_sessions s
+            SET status = 'closed', closed_at = COALESCE(s.closed_at, NOW()), updated_at = NOW()
+            FROM ai_agents a
+            WHERE s.id = $1 AND s.agent_id = $2 AND a.id = s.agent_id AND a.deployment_id = $3
+            RETURNING s.id, s.created_at, s.updated_at, s.agent_id, s.status, s.metadata, s.closed_at
+            "#,
+        )
+        .bind(self.session_id)
+        .bind(self.agent_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+        Ok(AiAgentSession {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            agent_id: row.get("agent_id"),
+            status: row.get("status"),
+            metadata: row.get("metadata"),
+            closed_at: row.get("closed_at"),
+        })
+    }
+}
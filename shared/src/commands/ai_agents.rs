@@ -1,4 +1,9 @@
-use crate::{commands::Command, error::AppError, models::AiAgent, state::AppState};
+use crate::{
+    commands::Command,
+    error::AppError,
+    models::{AiAgent, AiAgentKnowledgeBase},
+    state::AppState,
+};
 use chrono::Utc;
 use sqlx::Row;
 
@@ -28,7 +33,7 @@ impl CreateAiAgentCommand {
 impl Command for CreateAiAgentCommand {
     type Output = AiAgent;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let agent_id = app_state.sf.next_id()? as i64;
         let now = Utc::now();
 
@@ -100,7 +105,7 @@ impl UpdateAiAgentCommand {
 impl Command for UpdateAiAgentCommand {
     type Output = AiAgent;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let now = Utc::now();
 
         // Build dynamic query based on provided fields
@@ -181,7 +186,7 @@ impl DeleteAiAgentCommand {
 impl Command for DeleteAiAgentCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut tx = app_state
             .db_pool
             .begin()
@@ -228,3 +233,156 @@ impl Command for DeleteAiAgentCommand {
         Ok(())
     }
 }
+
+/// Attaches a knowledge base to an agent, or updates the settings it is
+/// searched with if it is attached already.
+pub struct AttachAgentKnowledgeBaseCommand {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+    pub knowledge_base_id: i64,
+    pub top_k: Option<i32>,
+    pub score_threshold: Option<f32>,
+    pub include_citations: Option<bool>,
+}
+
+impl AttachAgentKnowledgeBaseCommand {
+    pub fn new(deployment_id: i64, agent_id: i64, knowledge_base_id: i64) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            knowledge_base_id,
+            top_k: None,
+            score_threshold: None,
+            include_citations: None,
+        }
+    }
+
+    pub fn with_top_k(mut self, top_k: i32) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    pub fn with_score_threshold(mut self, score_threshold: f32) -> Self {
+        self.score_threshold = Some(score_threshold);
+        self
+    }
+
+    pub fn with_include_citations(mut self, include_citations: bool) -> Self {
+        self.include_citations = Some(include_citations);
+        self
+    }
+}
+
+impl Command for AttachAgentKnowledgeBaseCommand {
+    type Output = AiAgentKnowledgeBase;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let agent_exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM ai_agents WHERE id = $1 AND deployment_id = $2)",
+        )
+        .bind(self.agent_id)
+        .bind(self.deployment_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+        if !agent_exists {
+            return Err(AppError::NotFound("Agent not found".to_string()));
+        }
+
+        // A knowledge base of another deployment is refused the same way as
+        // one that doesn't exist
+        let knowledge_base = sqlx::query(
+            "SELECT name, description FROM ai_knowledge_bases WHERE id = $1 AND deployment_id = $2",
+        )
+        .bind(self.knowledge_base_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::BadRequest("The knowledge base does not exist in this deployment".to_string())
+        })?;
+
+        // Settings left out keep their current value, or the column default
+        // on a new attachment
+        let row = sqlx::query(
+            r#"
+            INSERT INTO ai_agent_knowledge_bases (
+                agent_id, knowledge_base_id, top_k, score_threshold, include_citations
+            )
+            VALUES ($1, $2, COALESCE($3, 5), $4, COALESCE($5, TRUE))
+            ON CONFLICT (agent_id, knowledge_base_id) DO UPDATE SET
+                top_k = COALESCE($3, ai_agent_knowledge_bases.top_k),
+                score_threshold = COALESCE($4, ai_agent_knowledge_bases.score_threshold),
+                include_citations = COALESCE($5, ai_agent_knowledge_bases.include_citations),
+                updated_at = NOW()
+            RETURNING top_k, score_threshold, include_citations, created_at, updated_at
+            "#,
+        )
+        .bind(self.agent_id)
+        .bind(self.knowledge_base_id)
+        .bind(self.top_k)
+        .bind(self.score_threshold)
+        .bind(self.include_citations)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        Ok(AiAgentKnowledgeBase {
+            agent_id: self.agent_id,
+            knowledge_base_id: self.knowledge_base_id,
+            name: knowledge_base.get("name"),
+            description: knowledge_base.get("description"),
+            top_k: row.get("top_k"),
+            score_threshold: row.get("score_threshold"),
+            include_citations: row.get("include_citations"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        })
+    }
+}
+
+/// Detaches a knowledge base from an agent. The knowledge base itself and
+/// its documents are left alone.
+pub struct DetachAgentKnowledgeBaseCommand {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+    pub knowledge_base_id: i64,
+}
+
+impl DetachAgentKnowledgeBaseCommand {
+    pub fn new(deployment_id: i64, agent_id: i64, knowledge_base_id: i64) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            knowledge_base_id,
+        }
+    }
+}
+
+impl Command for DetachAgentKnowledgeBaseCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM ai_agent_knowledge_bases akb
+            USING ai_agents a
+            WHERE akb.agent_id = a.id
+                AND a.id = $1
+                AND a.deployment_id = $2
+                AND akb.knowledge_base_id = $3
+            "#,
+        )
+        .bind(self.agent_id)
+        .bind(self.deployment_id)
+        .bind(self.knowledge_base_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(
+                "The knowledge base is not attached to this agent".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,445 @@
+//! Monthly budgets on AI model usage.
+//!
+//! Each model invocation is counted in Redis twice, once for the agent and
+//! once for its deployment, under keys named after the UTC month. A new
+//! month therefore starts from zero without anything resetting the
+//! counters, whatever time zone the customer is in. The same numbers are
+//! kept per hour in `ai_agent_usage_hourly`, which usage reports read.
+
+use chrono::{DurationRound, TimeDelta, Utc};
+use serde_json::json;
+use sqlx::Row;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{AiBudget, AiBudgetMetric, AiBudgetScope, AiBudgetWarningEvent, BillingPeriod},
+    state::AppState,
+};
+
+use super::Command;
+
+/// The Redis stream budget warnings are appended to, for whatever notifies
+/// the customer.
+pub const AI_BUDGET_EVENTS_STREAM: &str = "ai:budget_warnings";
+
+const AI_BUDGET_EVENTS_STREAM_LENGTH: usize = 10_000;
+
+/// How much of a limit can be used before a warning goes out.
+pub const AI_BUDGET_WARNING_PERCENT: i64 = 80;
+
+/// Long enough to outlast the month a counter belongs to.
+const USAGE_COUNTER_TTL_SECS: i64 = 40 * 24 * 60 * 60;
+
+fn usage_counter_key(scope: AiBudgetScope, id: i64, period: BillingPeriod) -> String {
+    let scope = match scope {
+        AiBudgetScope::Agent => "agent",
+        AiBudgetScope::Deployment => "deployment",
+    };
+    format!("ai_usage:{}:{}:{}", scope, id, period.start.format("%Y-%m"))
+}
+
+fn validate_budget(budget: &AiBudget) -> Result<(), AppError> {
+    for (field, limit) in [
+        ("monthly_token_limit", budget.monthly_token_limit),
+        (
+            "monthly_spend_limit_micros",
+            budget.monthly_spend_limit_micros,
+        ),
+    ] {
+        if limit.is_some_and(|limit| limit <= 0) {
+            return Err(AppError::Validation(format!(
+                "{} must be greater than zero",
+                field
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The agent's budget and its deployment's, in that order.
+async fn load_budgets(
+    app_state: &AppState,
+    deployment_id: i64,
+    agent_id: i64,
+) -> Result<(AiBudget, AiBudget), AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            a.monthly_token_limit,
+            a.monthly_spend_limit_micros,
+            d.ai_monthly_token_limit,
+            d.ai_monthly_spend_limit_micros
+        FROM ai_agents a
+        JOIN deployments d ON d.id = a.deployment_id
+        WHERE a.id = $1 AND a.deployment_id = $2
+        "#,
+    )
+    .bind(agent_id)
+    .bind(deployment_id)
+    .fetch_optional(&app_state.db_pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    Ok((
+        AiBudget {
+            monthly_token_limit: row.get("monthly_token_limit"),
+            monthly_spend_limit_micros: row.get("monthly_spend_limit_micros"),
+        },
+        AiBudget {
+            monthly_token_limit: row.get("ai_monthly_token_limit"),
+            monthly_spend_limit_micros: row.get("ai_monthly_spend_limit_micros"),
+        },
+    ))
+}
+
+fn limits(budget: &AiBudget) -> [(AiBudgetMetric, Option<i64>); 2] {
+    [
+        (AiBudgetMetric::Tokens, budget.monthly_token_limit),
+        (AiBudgetMetric::Spend, budget.monthly_spend_limit_micros),
+    ]
+}
+
+fn warning_threshold(limit: i64) -> i64 {
+    (limit as i128 * AI_BUDGET_WARNING_PERCENT as i128 / 100) as i64
+}
+
+/// The `tokens` and `spend` fields of a usage counter, unset until the
+/// first invocation of the month.
+type CounterFields = (Option<i64>, Option<i64>);
+
+/// Tokens and spend counted so far this month.
+#[derive(Debug, Clone, Copy, Default)]
+struct UsageTotals {
+    tokens: i64,
+    spend_micros: i64,
+}
+
+impl UsageTotals {
+    fn get(&self, metric: AiBudgetMetric) -> i64 {
+        match metric {
+            AiBudgetMetric::Tokens => self.tokens,
+            AiBudgetMetric::Spend => self.spend_micros,
+        }
+    }
+}
+
+/// Fails with `budget_exceeded` if the agent or its deployment has used up
+/// a monthly limit. Redis being unavailable lets the invocation through.
+pub(crate) async fn ensure_within_ai_budget(
+    app_state: &AppState,
+    deployment_id: i64,
+    agent_id: i64,
+) -> Result<(), AppError> {
+    let (agent_budget, deployment_budget) =
+        load_budgets(app_state, deployment_id, agent_id).await?;
+    if agent_budget == AiBudget::default() && deployment_budget == AiBudget::default() {
+        return Ok(());
+    }
+
+    let period = BillingPeriod::containing(Utc::now());
+    let counters = async {
+        let mut connection = app_state.redis.connection().await?;
+        let (agent, deployment): (CounterFields, CounterFields) = redis::pipe()
+            .hget(
+                usage_counter_key(AiBudgetScope::Agent, agent_id, period),
+                &["tokens", "spend"],
+            )
+            .hget(
+                usage_counter_key(AiBudgetScope::Deployment, deployment_id, period),
+                &["tokens", "spend"],
+            )
+            .query_async(&mut connection)
+            .await?;
+        Ok::<_, AppError>((agent, deployment))
+    }
+    .await;
+
+    let (agent, deployment) = match counters {
+        Ok(counters) => counters,
+        Err(e) => {
+            tracing::warn!(
+                deployment_id,
+                agent_id,
+                "AI budget check failed, allowing invocation: {}",
+                e
+            );
+            return Ok(());
+        }
+    };
+
+    let totals = |(tokens, spend): CounterFields| UsageTotals {
+        tokens: tokens.unwrap_or(0),
+        spend_micros: spend.unwrap_or(0),
+    };
+    for (scope, budget, used) in [
+        (AiBudgetScope::Agent, agent_budget, totals(agent)),
+        (
+            AiBudgetScope::Deployment,
+            deployment_budget,
+            totals(deployment),
+        ),
+    ] {
+        for (metric, limit) in limits(&budget) {
+            if let Some(limit) = limit
+                && used.get(metric) >= limit
+            {
+                let whose = match scope {
+                    AiBudgetScope::Agent => "agent's",
+                    AiBudgetScope::Deployment => "deployment's",
+                };
+                return Err(AppError::coded(
+                    ErrorCode::BudgetExceeded,
+                    format!("The {} monthly AI budget has been used up", whose),
+                )
+                .with_details(json!({
+                    "scope": scope,
+                    "metric": metric,
+                    "used": used.get(metric),
+                    "limit": limit,
+                    "resets_at": period.ends_at(),
+                })));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts one model invocation by an agent towards its budget and its
+/// deployment's. Publishes a warning to [`AI_BUDGET_EVENTS_STREAM`] when
+/// the invocation takes usage past [`AI_BUDGET_WARNING_PERCENT`] of a limit.
+pub struct RecordAgentUsageCommand {
+    deployment_id: i64,
+    agent_id: i64,
+    prompt_tokens: i64,
+    completion_tokens: i64,
+    spend_micros: i64,
+}
+
+impl RecordAgentUsageCommand {
+    pub fn new(
+        deployment_id: i64,
+        agent_id: i64,
+        prompt_tokens: i64,
+        completion_tokens: i64,
+    ) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            prompt_tokens,
+            completion_tokens,
+            spend_micros: 0,
+        }
+    }
+
+    /// What the invocation cost, in millionths of a US dollar.
+    pub fn with_spend_micros(mut self, spend_micros: i64) -> Self {
+        self.spend_micros = spend_micros;
+        self
+    }
+}
+
+impl Command for RecordAgentUsageCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self.prompt_tokens < 0 || self.completion_tokens < 0 || self.spend_micros < 0 {
+            return Err(AppError::Validation("Usage cannot be negative".to_string()));
+        }
+
+        let now = Utc::now();
+        let hour = now.duration_trunc(TimeDelta::hours(1)).unwrap_or(now);
+        let tokens = self.prompt_tokens + self.completion_tokens;
+
+        sqlx::query(
+            r#"
+            INSERT INTO ai_agent_usage_hourly (
+                deployment_id, agent_id, hour, invocations,
+                prompt_tokens, completion_tokens, spend_micros
+            )
+            VALUES ($1, $2, $3, 1, $4, $5, $6)
+            ON CONFLICT (agent_id, hour) DO UPDATE SET
+                invocations = ai_agent_usage_hourly.invocations + 1,
+                prompt_tokens = ai_agent_usage_hourly.prompt_tokens + EXCLUDED.prompt_tokens,
+                completion_tokens = ai_agent_usage_hourly.completion_tokens + EXCLUDED.completion_tokens,
+                spend_micros = ai_agent_usage_hourly.spend_micros + EXCLUDED.spend_micros
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.agent_id)
+        .bind(hour)
+        .bind(self.prompt_tokens)
+        .bind(self.completion_tokens)
+        .bind(self.spend_micros)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        let period = BillingPeriod::containing(now);
+        let agent_key = usage_counter_key(AiBudgetScope::Agent, self.agent_id, period);
+        let deployment_key =
+            usage_counter_key(AiBudgetScope::Deployment, self.deployment_id, period);
+
+        let mut connection = app_state.redis.connection().await?;
+        let (agent_tokens, agent_spend, deployment_tokens, deployment_spend): (i64, i64, i64, i64) =
+            redis::pipe()
+                .atomic()
+                .hincr(&agent_key, "tokens", tokens)
+                .hincr(&agent_key, "spend", self.spend_micros)
+                .expire(&agent_key, USAGE_COUNTER_TTL_SECS)
+                .ignore()
+                .hincr(&deployment_key, "tokens", tokens)
+                .hincr(&deployment_key, "spend", self.spend_micros)
+                .expire(&deployment_key, USAGE_COUNTER_TTL_SECS)
+                .ignore()
+                .query_async(&mut connection)
+                .await?;
+
+        // Each increment is atomic, so only one invocation sees usage go
+        // from below the threshold to at or above it.
+        let (agent_budget, deployment_budget) =
+            load_budgets(app_state, self.deployment_id, self.agent_id).await?;
+        let increments = UsageTotals {
+            tokens,
+            spend_micros: self.spend_micros,
+        };
+        for (scope, budget, used) in [
+            (
+                AiBudgetScope::Agent,
+                agent_budget,
+                UsageTotals {
+                    tokens: agent_tokens,
+                    spend_micros: agent_spend,
+                },
+            ),
+            (
+                AiBudgetScope::Deployment,
+                deployment_budget,
+                UsageTotals {
+                    tokens: deployment_tokens,
+                    spend_micros: deployment_spend,
+                },
+            ),
+        ] {
+            for (metric, limit) in limits(&budget) {
+                let Some(limit) = limit else {
+                    continue;
+                };
+                let threshold = warning_threshold(limit);
+                let after = used.get(metric);
+                let before = after - increments.get(metric);
+                if before >= threshold || after < threshold {
+                    continue;
+                }
+
+                let event = AiBudgetWarningEvent {
+                    deployment_id: self.deployment_id,
+                    agent_id: (scope == AiBudgetScope::Agent).then_some(self.agent_id),
+                    scope,
+                    metric,
+                    used: after,
+                    limit,
+                    period_start: period.start,
+                    occurred_at: now,
+                };
+                redis::cmd("XADD")
+                    .arg(AI_BUDGET_EVENTS_STREAM)
+                    .arg("MAXLEN")
+                    .arg("~")
+                    .arg(AI_BUDGET_EVENTS_STREAM_LENGTH)
+                    .arg("*")
+                    .arg("payload")
+                    .arg(serde_json::to_string(&event)?)
+                    .query_async::<String>(&mut connection)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct UpdateAiAgentBudgetCommand {
+    deployment_id: i64,
+    agent_id: i64,
+    budget: AiBudget,
+}
+
+impl UpdateAiAgentBudgetCommand {
+    pub fn new(deployment_id: i64, agent_id: i64, budget: AiBudget) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            budget,
+        }
+    }
+}
+
+impl Command for UpdateAiAgentBudgetCommand {
+    type Output = AiBudget;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        validate_budget(&self.budget)?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE ai_agents
+            SET monthly_token_limit = $3, monthly_spend_limit_micros = $4, updated_at = NOW()
+            WHERE id = $1 AND deployment_id = $2
+            "#,
+        )
+        .bind(self.agent_id)
+        .bind(self.deployment_id)
+        .bind(self.budget.monthly_token_limit)
+        .bind(self.budget.monthly_spend_limit_micros)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Agent not found".to_string()));
+        }
+
+        Ok(self.budget)
+    }
+}
+
+/// Sets the budget all of a deployment's agents share.
+pub struct UpdateDeploymentAiBudgetCommand {
+    deployment_id: i64,
+    budget: AiBudget,
+}
+
+impl UpdateDeploymentAiBudgetCommand {
+    pub fn new(deployment_id: i64, budget: AiBudget) -> Self {
+        Self {
+            deployment_id,
+            budget,
+        }
+    }
+}
+
+impl Command for UpdateDeploymentAiBudgetCommand {
+    type Output = AiBudget;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        validate_budget(&self.budget)?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE deployments
+            SET ai_monthly_token_limit = $2, ai_monthly_spend_limit_micros = $3, updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.budget.monthly_token_limit)
+        .bind(self.budget.monthly_spend_limit_micros)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Deployment not found".to_string()));
+        }
+
+        Ok(self.budget)
+    }
+}
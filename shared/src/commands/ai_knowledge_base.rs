@@ -1,25 +1,35 @@
 use crate::{
     commands::{Command, UploadToKnowledgeBaseBucketCommand},
     error::AppError,
-    models::{AiKnowledgeBase, AiKnowledgeBaseDocument},
-    queries::{GetAiKnowledgeBaseByIdQuery, Query},
+    models::{
+        AiKnowledgeBase, AiKnowledgeBaseCrawl, AiKnowledgeBaseDocument, AiKnowledgeBaseReembedJob,
+        ChunkingConfig, DeploymentEmbeddingProviders, HttpMethod, KnowledgeBaseDocumentRechunk,
+        KnowledgeBaseDocumentSource, StorageClass, ToolExecutionPolicy,
+    },
+    queries::{
+        GetAiKnowledgeBaseByIdQuery, GetKnowledgeBaseChunkingQuery,
+        GetKnowledgeBaseCollectionsQuery, GetKnowledgeBaseEmbedderQuery, GetReembedProgressQuery,
+        Query, ai_knowledge_base::deployment_embedding_providers,
+    },
     services::{
-        embedding::EmbeddingService,
+        Embedder, ToolExecutionService, ToolHttpRequest, ToolHttpResponse,
         qdrant::{DocumentChunk, QdrantService},
-        text_processing::TextProcessingService,
     },
     state::AppState,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use sqlx::Row;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 pub struct CreateAiKnowledgeBaseCommand {
     pub deployment_id: i64,
     pub name: String,
     pub description: Option<String>,
     pub configuration: serde_json::Value,
+    pub embedding_provider: Option<String>,
+    pub chunking: ChunkingConfig,
 }
 
 impl CreateAiKnowledgeBaseCommand {
@@ -34,17 +44,63 @@ impl CreateAiKnowledgeBaseCommand {
             name,
             description,
             configuration,
+            embedding_provider: None,
+            chunking: ChunkingConfig::default(),
         }
     }
+
+    /// Embeds with this provider instead of the deployment's.
+    pub fn with_embedding_provider(mut self, embedding_provider: String) -> Self {
+        self.embedding_provider = Some(embedding_provider);
+        self
+    }
+
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = chunking;
+        self
+    }
+}
+
+/// The provider a knowledge base's new vectors come from: its own, else its
+/// deployment's, else the platform default.
+async fn selected_embedder(
+    app_state: &AppState,
+    deployment_id: i64,
+    embedding_provider: Option<&str>,
+) -> Result<Embedder, AppError> {
+    let embedding_provider = match embedding_provider {
+        Some(embedding_provider) => Some(embedding_provider.to_string()),
+        None => sqlx::query_scalar("SELECT ai_embedding_provider FROM deployments WHERE id = $1")
+            .bind(deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?
+            .flatten(),
+    };
+
+    app_state
+        .embedding_service
+        .select(embedding_provider.as_deref())
+        .cloned()
 }
 
 impl Command for CreateAiKnowledgeBaseCommand {
     type Output = AiKnowledgeBase;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        self.chunking.validate()?;
+
+        let embedder = selected_embedder(
+            app_state,
+            self.deployment_id,
+            self.embedding_provider.as_deref(),
+        )
+        .await?;
+
         let knowledge_base_id = app_state.sf.next_id()? as i64;
         let now = Utc::now();
 
+        let mut tx = app_state.db_pool.begin().await?;
+
         let knowledge_base = sqlx::query!(
             r#"
             INSERT INTO ai_knowledge_bases (id, created_at, updated_at, name, description, deployment_id, configuration)
@@ -59,10 +115,32 @@ impl Command for CreateAiKnowledgeBaseCommand {
             self.deployment_id,
             self.configuration,
         )
-        .fetch_one(&app_state.db_pool)
+        .fetch_one(&mut *tx)
         .await
         .map_err(|e| AppError::Database(e))?;
 
+        // Created with the first chunks written to it
+        let collection =
+            QdrantService::knowledge_base_collection(self.deployment_id, knowledge_base_id);
+
+        sqlx::query(
+            r#"
+            UPDATE ai_knowledge_bases
+            SET embedding_provider = $2, embedding_model = $3, active_collection = $4,
+                chunking_config = $5
+            WHERE id = $1
+            "#,
+        )
+        .bind(knowledge_base_id)
+        .bind(&self.embedding_provider)
+        .bind(embedder.model())
+        .bind(collection)
+        .bind(json!(self.chunking))
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
         Ok(AiKnowledgeBase {
             id: knowledge_base.id,
             created_at: knowledge_base.created_at,
@@ -71,6 +149,9 @@ impl Command for CreateAiKnowledgeBaseCommand {
             description: knowledge_base.description,
             deployment_id: knowledge_base.deployment_id,
             configuration: knowledge_base.configuration,
+            embedding_provider: self.embedding_provider,
+            embedding_model: Some(embedder.model().to_string()),
+            chunking: self.chunking,
         })
     }
 }
@@ -81,6 +162,8 @@ pub struct UpdateAiKnowledgeBaseCommand {
     pub name: Option<String>,
     pub description: Option<String>,
     pub configuration: Option<serde_json::Value>,
+    pub embedding_provider: Option<String>,
+    pub chunking: Option<ChunkingConfig>,
 }
 
 impl UpdateAiKnowledgeBaseCommand {
@@ -91,9 +174,18 @@ impl UpdateAiKnowledgeBaseCommand {
             name: None,
             description: None,
             configuration: None,
+            embedding_provider: None,
+            chunking: None,
         }
     }
 
+    /// Changes the provider the next re-embedding uses. Existing vectors
+    /// stay as they are until then.
+    pub fn with_embedding_provider(mut self, embedding_provider: String) -> Self {
+        self.embedding_provider = Some(embedding_provider);
+        self
+    }
+
     pub fn with_name(mut self, name: String) -> Self {
         self.name = Some(name);
         self
@@ -108,12 +200,28 @@ impl UpdateAiKnowledgeBaseCommand {
         self.configuration = Some(configuration);
         self
     }
+
+    /// Changes how documents added from now on are chunked. Documents
+    /// already there keep their chunks until they are re-chunked.
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = Some(chunking);
+        self
+    }
 }
 
 impl Command for UpdateAiKnowledgeBaseCommand {
     type Output = AiKnowledgeBase;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if let Some(embedding_provider) = &self.embedding_provider {
+            app_state
+                .embedding_service
+                .select(Some(embedding_provider))?;
+        }
+        if let Some(chunking) = &self.chunking {
+            chunking.validate()?;
+        }
+
         let now = Utc::now();
 
         // Build dynamic query based on provided fields
@@ -133,13 +241,22 @@ impl Command for UpdateAiKnowledgeBaseCommand {
             query_parts.push(format!("configuration = ${}", param_count));
             param_count += 1;
         }
+        if self.embedding_provider.is_some() {
+            query_parts.push(format!("embedding_provider = ${}", param_count));
+            param_count += 1;
+        }
+        if self.chunking.is_some() {
+            query_parts.push(format!("chunking_config = ${}", param_count));
+            param_count += 1;
+        }
 
         let query = format!(
             r#"
             UPDATE ai_knowledge_bases 
             SET {}
             WHERE id = ${} AND deployment_id = ${}
-            RETURNING id, created_at, updated_at, name, description, deployment_id, configuration
+            RETURNING id, created_at, updated_at, name, description, deployment_id, configuration,
+                embedding_provider, embedding_model, chunking_config
             "#,
             query_parts.join(", "),
             param_count,
@@ -159,6 +276,12 @@ impl Command for UpdateAiKnowledgeBaseCommand {
         if let Some(configuration) = self.configuration {
             query_builder = query_builder.bind(configuration);
         }
+        if let Some(embedding_provider) = self.embedding_provider {
+            query_builder = query_builder.bind(embedding_provider);
+        }
+        if let Some(chunking) = self.chunking {
+            query_builder = query_builder.bind(json!(chunking));
+        }
 
         query_builder = query_builder
             .bind(self.knowledge_base_id)
@@ -177,6 +300,9 @@ impl Command for UpdateAiKnowledgeBaseCommand {
             description: knowledge_base.get("description"),
             deployment_id: knowledge_base.get("deployment_id"),
             configuration: knowledge_base.get("configuration"),
+            embedding_provider: knowledge_base.get("embedding_provider"),
+            embedding_model: knowledge_base.get("embedding_model"),
+            chunking: ChunkingConfig::from_stored(knowledge_base.get("chunking_config")),
         })
     }
 }
@@ -198,7 +324,37 @@ impl DeleteAiKnowledgeBaseCommand {
 impl Command for DeleteAiKnowledgeBaseCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        GetAiKnowledgeBaseByIdQuery::new(self.deployment_id, self.knowledge_base_id)
+            .execute(app_state)
+            .await
+            .map_err(|_| AppError::NotFound("Knowledge base not found".to_string()))?;
+
+        let collections = GetKnowledgeBaseCollectionsQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?;
+
+        // The vectors go first. If that fails the knowledge base is still
+        // there to delete again, instead of its vectors being left behind
+        // with nothing pointing at them.
+        for collection_name in collections.all() {
+            // Re-embedded knowledge bases own their collection outright
+            if collection_name != QdrantService::default_collection() {
+                QdrantService::delete_collection(&collection_name).await?;
+                continue;
+            }
+
+            QdrantService::delete_knowledge_base(&collection_name, self.knowledge_base_id).await?;
+            let remaining =
+                QdrantService::count_chunks(&collection_name, self.knowledge_base_id).await?;
+            if remaining > 0 {
+                return Err(AppError::Internal(format!(
+                    "{} chunks of knowledge base {} are still in collection '{}'",
+                    remaining, self.knowledge_base_id, collection_name
+                )));
+            }
+        }
+
         let mut tx = app_state
             .db_pool
             .begin()
@@ -214,6 +370,11 @@ impl Command for DeleteAiKnowledgeBaseCommand {
         .await
         .map_err(|e| AppError::Database(e))?;
 
+        sqlx::query("DELETE FROM ai_agent_knowledge_bases WHERE knowledge_base_id = $1")
+            .bind(self.knowledge_base_id)
+            .execute(&mut *tx)
+            .await?;
+
         // Delete the knowledge base
         sqlx::query!(
             "DELETE FROM ai_knowledge_bases WHERE id = $1 AND deployment_id = $2",
@@ -226,16 +387,6 @@ impl Command for DeleteAiKnowledgeBaseCommand {
 
         tx.commit().await.map_err(|e| AppError::Database(e))?;
 
-        // Delete Qdrant collection in background
-        let kb_id = self.knowledge_base_id;
-
-        if let Err(e) = QdrantService::delete_knowledge_base(kb_id).await {
-            eprintln!(
-                "Failed to delete Qdrant vectors for knowledge base {}: {}",
-                kb_id, e
-            );
-        }
-
         Ok(())
     }
 }
@@ -247,6 +398,7 @@ pub struct UploadKnowledgeBaseDocumentCommand {
     pub file_name: String,
     pub file_content: Vec<u8>,
     pub file_type: String,
+    pub source: KnowledgeBaseDocumentSource,
 }
 
 pub struct UploadKnowledgeBaseUrlCommand {
@@ -272,8 +424,15 @@ impl UploadKnowledgeBaseDocumentCommand {
             file_name,
             file_content,
             file_type,
+            source: KnowledgeBaseDocumentSource::Upload,
         }
     }
+
+    /// For documents fetched on the user's behalf rather than uploaded.
+    pub fn with_source(mut self, source: KnowledgeBaseDocumentSource) -> Self {
+        self.source = source;
+        self
+    }
 }
 
 impl UploadKnowledgeBaseUrlCommand {
@@ -295,7 +454,7 @@ impl UploadKnowledgeBaseUrlCommand {
 impl Command for UploadKnowledgeBaseDocumentCommand {
     type Output = AiKnowledgeBaseDocument;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let document_id = app_state.sf.next_id()? as i64;
         let now = Utc::now();
         let file_size = self.file_content.len() as i64;
@@ -330,30 +489,20 @@ impl Command for UploadKnowledgeBaseDocumentCommand {
         .await
         .map_err(|e| AppError::Database(e))?;
 
-        // Process document for embeddings in background
-        let doc_id = document.id;
-        let file_content_for_processing = self.file_content.clone();
-        let file_type_clone = self.file_type.clone();
-        let title_clone = self.title.clone();
-        let kb_id = self.knowledge_base_id;
-
-        // Clone services from app_state for background processing
-        let embedding_service = app_state.embedding_service.clone();
-        let text_processing_service = app_state.text_processing_service.clone();
-
-        if let Err(e) = Self::process_document_embeddings(
-            doc_id,
-            kb_id,
-            file_content_for_processing,
-            file_type_clone,
-            title_clone,
-            embedding_service,
-            text_processing_service,
-        )
-        .await
-        {
+        let collections = GetKnowledgeBaseCollectionsQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?;
+        let chunking = GetKnowledgeBaseChunkingQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?;
+
+        let ingestion = self
+            .process_document_embeddings(app_state, &collections.active, document.id, &chunking)
+            .await;
+        if let Err(e) = &ingestion {
             eprintln!("Failed to process document embeddings: {}", e);
         }
+        record_ingestion(app_state, document.id, &chunking, &ingestion).await?;
 
         Ok(AiKnowledgeBaseDocument {
             id: document.id,
@@ -372,63 +521,157 @@ impl Command for UploadKnowledgeBaseDocumentCommand {
 }
 
 impl UploadKnowledgeBaseDocumentCommand {
+    /// Returns how many chunks the document was split into.
     async fn process_document_embeddings(
+        &self,
+        app_state: &AppState,
+        collection_name: &str,
         document_id: i64,
-        knowledge_base_id: i64,
-        file_content: Vec<u8>,
-        file_type: String,
-        title: String,
-        embedding_service: EmbeddingService,
-        text_processing_service: TextProcessingService,
-    ) -> Result<(), AppError> {
-        // Create a snowflake generator for chunk IDs
-        let sf = sonyflake::Sonyflake::new().map_err(|e| AppError::Internal(e.to_string()))?;
-        // Extract text from file
-        let text = text_processing_service.extract_text_from_file(&file_content, &file_type)?;
-        let cleaned_text = text_processing_service.clean_text(&text);
+        chunking: &ChunkingConfig,
+    ) -> Result<usize, AppError> {
+        let text = app_state
+            .text_processing_service
+            .extract_text_for_chunking(&self.file_content, &self.file_type, chunking)?;
 
-        // Chunk the text
-        let chunks = text_processing_service.chunk_text(&cleaned_text, 1000, 200)?;
+        DocumentText {
+            document_id,
+            knowledge_base_id: self.knowledge_base_id,
+            title: self.title.clone(),
+            file_type: self.file_type.clone(),
+            source: self.source,
+            text,
+            metadata: HashMap::new(),
+        }
+        .write_chunks(
+            app_state,
+            collection_name,
+            chunking,
+            app_state.sf.next_id()? as i64,
+        )
+        .await
+    }
+}
+
+/// A document's cleaned text, ready to be split into chunks and embedded.
+struct DocumentText {
+    document_id: i64,
+    knowledge_base_id: i64,
+    title: String,
+    file_type: String,
+    source: KnowledgeBaseDocumentSource,
+    text: String,
+    /// Payload every chunk carries on top of the usual fields.
+    metadata: HashMap<String, serde_json::Value>,
+}
 
-        // Generate embeddings for chunks
+impl DocumentText {
+    /// Writes the chunks to the collection, each tagged with `revision` so
+    /// they can be told apart from chunks an earlier ingestion left. Returns
+    /// how many there were.
+    async fn write_chunks(
+        &self,
+        app_state: &AppState,
+        collection_name: &str,
+        chunking: &ChunkingConfig,
+        revision: i64,
+    ) -> Result<usize, AppError> {
+        let embedder = GetKnowledgeBaseEmbedderQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?;
+        let chunks = app_state.text_processing_service.chunk_text(
+            &self.text,
+            chunking,
+            embedder.tokenizer(),
+        )?;
         let chunk_texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
-        let embeddings = embedding_service.generate_embeddings(chunk_texts).await?;
-
-        // Create document chunks for Qdrant
-        let document_chunks: Vec<DocumentChunk> = chunks
-            .into_iter()
-            .zip(embeddings.into_iter())
-            .enumerate()
-            .map(|(_i, (chunk, embedding))| {
-                let mut metadata = HashMap::new();
-                metadata.insert("document_id".to_string(), json!(document_id.to_string()));
-                metadata.insert(
-                    "knowledge_base_id".to_string(),
-                    json!(knowledge_base_id.to_string()),
-                );
-                metadata.insert("chunk_index".to_string(), json!(chunk.chunk_index));
-                metadata.insert("title".to_string(), json!(title.clone()));
-                metadata.insert("file_type".to_string(), json!(file_type.clone()));
-
-                // Generate consistent chunk ID using snowflake
-                let chunk_id = sf.next_id().unwrap_or(0) as i64;
-
-                DocumentChunk {
-                    id: chunk_id, // Use snowflake ID as i64
-                    content: chunk.content,
-                    metadata,
-                    embedding,
-                }
-            })
-            .collect();
+        let embeddings = embedder.embed(chunk_texts).await?;
+        QdrantService::ensure_collection(
+            collection_name,
+            embedder.dimensions(),
+            embedder.distance(),
+        )
+        .await?;
+
+        // Pins the model of a knowledge base from before models were recorded
+        sqlx::query(
+            "UPDATE ai_knowledge_bases SET embedding_model = $2 WHERE id = $1 AND embedding_model IS NULL",
+        )
+        .bind(self.knowledge_base_id)
+        .bind(embedder.model())
+        .execute(&app_state.db_pool)
+        .await?;
+
+        let mut document_chunks = Vec::with_capacity(chunks.len());
+        for (chunk, embedding) in chunks.into_iter().zip(embeddings) {
+            let mut metadata = self.metadata.clone();
+            metadata.insert(
+                "document_id".to_string(),
+                json!(self.document_id.to_string()),
+            );
+            metadata.insert(
+                "knowledge_base_id".to_string(),
+                json!(self.knowledge_base_id.to_string()),
+            );
+            metadata.insert("chunk_index".to_string(), json!(chunk.chunk_index));
+            metadata.insert("char_start".to_string(), json!(chunk.start_offset));
+            metadata.insert("char_end".to_string(), json!(chunk.end_offset));
+            metadata.insert("title".to_string(), json!(self.title));
+            metadata.insert("file_type".to_string(), json!(self.file_type));
+            metadata.insert("source".to_string(), json!(self.source));
+            metadata.insert("revision".to_string(), json!(revision));
+            if !chunk.heading_path.is_empty() {
+                metadata.insert("heading_path".to_string(), json!(chunk.heading_path));
+            }
+
+            document_chunks.push(DocumentChunk {
+                id: app_state.sf.next_id()? as i64,
+                content: chunk.content,
+                metadata,
+                embedding,
+            });
+        }
 
-        // Store in Qdrant with knowledge base as tenant
-        QdrantService::upsert_documents(document_chunks, knowledge_base_id).await?;
+        let chunk_count = document_chunks.len();
+        QdrantService::upsert_documents(collection_name, document_chunks, self.knowledge_base_id)
+            .await?;
 
-        Ok(())
+        Ok(chunk_count)
     }
 }
 
+/// Marks a document ready with its chunk count, or failed with the error.
+async fn record_ingestion(
+    app_state: &AppState,
+    document_id: i64,
+    chunking: &ChunkingConfig,
+    ingestion: &Result<usize, AppError>,
+) -> Result<(), AppError> {
+    let (status, error, chunk_count) = match ingestion {
+        Ok(chunk_count) => ("ready", None, Some(*chunk_count as i32)),
+        Err(e) => ("failed", Some(e.to_string()), None),
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE ai_knowledge_base_documents
+        SET ingestion_status = $2, ingestion_error = $3, chunk_count = $4,
+            chunk_size = $5, chunk_overlap = $6, chunking_strategy = $7
+        WHERE id = $1
+        "#,
+    )
+    .bind(document_id)
+    .bind(status)
+    .bind(error)
+    .bind(chunk_count)
+    .bind(chunking.chunk_size as i32)
+    .bind(chunking.chunk_overlap as i32)
+    .bind(chunking.strategy.as_str())
+    .execute(&app_state.db_pool)
+    .await?;
+
+    Ok(())
+}
+
 pub struct DeleteKnowledgeBaseDocumentCommand {
     pub deployment_id: i64,
     pub knowledge_base_id: i64,
@@ -448,69 +691,228 @@ impl DeleteKnowledgeBaseDocumentCommand {
 impl Command for DeleteKnowledgeBaseDocumentCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         // Verify the knowledge base exists and belongs to the deployment
         let _kb = GetAiKnowledgeBaseByIdQuery::new(self.deployment_id, self.knowledge_base_id)
             .execute(app_state)
             .await
             .map_err(|_| AppError::NotFound("Knowledge base not found".to_string()))?;
 
-        // Delete the document
-        let result = sqlx::query!(
-            "DELETE FROM ai_knowledge_base_documents WHERE id = $1 AND knowledge_base_id = $2",
-            self.document_id,
-            self.knowledge_base_id
-        )
-        .execute(&app_state.db_pool)
-        .await
-        .map_err(|e| AppError::Database(e))?;
+        let mut tx = app_state.db_pool.begin().await?;
 
-        if result.rows_affected() == 0 {
-            return Err(AppError::NotFound("Document not found".to_string()));
+        // Held until the row is gone, so a re-chunk can't write new vectors
+        // for the document after its old ones were deleted
+        sqlx::query(
+            "SELECT id FROM ai_knowledge_base_documents WHERE id = $1 AND knowledge_base_id = $2 FOR UPDATE",
+        )
+        .bind(self.document_id)
+        .bind(self.knowledge_base_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+        // Vectors first, so a failure leaves the document in place to retry
+        let collections = GetKnowledgeBaseCollectionsQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?;
+        for collection_name in collections.all() {
+            QdrantService::delete_document_chunks(
+                &collection_name,
+                self.knowledge_base_id,
+                self.document_id,
+                None,
+            )
+            .await?;
         }
 
-        // Delete embeddings from Qdrant in background
-        let doc_id = self.document_id;
-        let kb_id = self.knowledge_base_id;
+        sqlx::query("DELETE FROM ai_knowledge_base_documents WHERE id = $1")
+            .bind(self.document_id)
+            .execute(&mut *tx)
+            .await?;
 
-        if let Err(e) = Self::delete_document_embeddings(doc_id, kb_id).await {
-            eprintln!("Failed to delete document embeddings: {}", e);
-        }
+        tx.commit().await?;
 
         Ok(())
     }
 }
 
-impl DeleteKnowledgeBaseDocumentCommand {
-    async fn delete_document_embeddings(
-        document_id: i64,
-        knowledge_base_id: i64,
-    ) -> Result<(), AppError> {
-        let mut metadata_filter = HashMap::new();
-        metadata_filter.insert("document_id".to_string(), json!(document_id.to_string()));
+/// Splits a document into chunks again, with the knowledge base's current
+/// settings unless others are given. The new chunks are written before the
+/// old ones are deleted, so searches keep finding the document throughout.
+pub struct RechunkDocumentCommand {
+    pub deployment_id: i64,
+    pub knowledge_base_id: i64,
+    pub document_id: i64,
+    pub chunking: Option<ChunkingConfig>,
+}
 
-        QdrantService::delete_by_metadata(knowledge_base_id, metadata_filter).await?;
+impl RechunkDocumentCommand {
+    pub fn new(deployment_id: i64, knowledge_base_id: i64, document_id: i64) -> Self {
+        Self {
+            deployment_id,
+            knowledge_base_id,
+            document_id,
+            chunking: None,
+        }
+    }
 
-        Ok(())
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = Some(chunking);
+        self
     }
 }
 
-impl Command for UploadKnowledgeBaseUrlCommand {
-    type Output = AiKnowledgeBaseDocument;
+impl Command for RechunkDocumentCommand {
+    type Output = KnowledgeBaseDocumentRechunk;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // Fetch content from URL using ureq
-        let mut response = ureq::get(&self.url)
-            .call()
-            .map_err(|e| AppError::Internal(format!("Failed to fetch URL: {}", e)))?;
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let knowledge_base =
+            GetAiKnowledgeBaseByIdQuery::new(self.deployment_id, self.knowledge_base_id)
+                .execute(app_state)
+                .await
+                .map_err(|_| AppError::NotFound("Knowledge base not found".to_string()))?;
+        let chunking = self.chunking.unwrap_or(knowledge_base.chunking);
+        chunking.validate()?;
 
-        if response.status() != 200 {
-            return Err(AppError::Internal(format!(
-                "Failed to fetch URL: HTTP {}",
-                response.status()
-            )));
+        let mut tx = app_state.db_pool.begin().await?;
+
+        // Locked for the whole run, so two re-chunks of one document don't
+        // each delete the other's chunks
+        let document = sqlx::query(
+            r#"
+            SELECT title, file_name, file_type, processing_metadata
+            FROM ai_knowledge_base_documents
+            WHERE id = $1 AND knowledge_base_id = $2
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.document_id)
+        .bind(self.knowledge_base_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+        let file_name: String = document.get("file_name");
+        let mut file_type: String = document.get("file_type");
+        let mut processing_metadata: Option<serde_json::Value> =
+            document.get("processing_metadata");
+        let crawled_from = processing_metadata
+            .as_ref()
+            .filter(|metadata| metadata.get("crawl_id").is_some())
+            .and_then(|metadata| metadata.get("source_url"))
+            .and_then(|url| url.as_str())
+            .map(str::to_string);
+        let source = if crawled_from.is_some() {
+            KnowledgeBaseDocumentSource::Crawl
+        } else if processing_metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.get("fetched_from").is_some())
+        {
+            KnowledgeBaseDocumentSource::Url
+        } else {
+            KnowledgeBaseDocumentSource::Upload
+        };
+
+        let text_processing_service = &app_state.text_processing_service;
+        let mut metadata = HashMap::new();
+
+        // Crawled pages aren't stored, so they are fetched again
+        let text = match &crawled_from {
+            Some(source_url) => {
+                let page_url = url::Url::parse(source_url)
+                    .map_err(|e| AppError::Internal(format!("Invalid source URL: {}", e)))?;
+                let html = IngestUrlIntoKnowledgeBaseCommand::fetch_page(page_url).await?;
+                let text =
+                    text_processing_service.extract_readable_text_for_chunking(&html, &chunking)?;
+
+                let content_hash = hex::encode(Sha256::digest(text.as_bytes()));
+                if let Some(serde_json::Value::Object(existing)) = processing_metadata.as_mut() {
+                    existing.insert("content_hash".to_string(), json!(content_hash));
+                }
+                metadata.insert("source_url".to_string(), json!(source_url));
+                metadata.insert("content_hash".to_string(), json!(content_hash));
+                file_type = "text/html".to_string();
+                text
+            }
+            None => {
+                let file_content = app_state
+                    .storage
+                    .bucket(StorageClass::KnowledgeBase)
+                    .get_object(&file_name)
+                    .await?;
+                text_processing_service.extract_text_for_chunking(
+                    &file_content,
+                    &file_type,
+                    &chunking,
+                )?
+            }
+        };
+
+        let collections = GetKnowledgeBaseCollectionsQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?;
+        let revision = app_state.sf.next_id()? as i64;
+
+        let chunk_count = DocumentText {
+            document_id: self.document_id,
+            knowledge_base_id: self.knowledge_base_id,
+            title: document.get("title"),
+            file_type,
+            source,
+            text,
+            metadata,
+        }
+        .write_chunks(app_state, &collections.active, &chunking, revision)
+        .await?;
+
+        let mut chunks_replaced = 0;
+        for collection_name in collections.all() {
+            chunks_replaced += QdrantService::delete_document_chunks(
+                &collection_name,
+                self.knowledge_base_id,
+                self.document_id,
+                Some(revision),
+            )
+            .await?;
         }
 
+        sqlx::query(
+            r#"
+            UPDATE ai_knowledge_base_documents
+            SET ingestion_status = 'ready', ingestion_error = NULL, chunk_count = $2,
+                chunk_size = $3, chunk_overlap = $4, processing_metadata = $5,
+                chunking_strategy = $6, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(self.document_id)
+        .bind(chunk_count as i32)
+        .bind(chunking.chunk_size as i32)
+        .bind(chunking.chunk_overlap as i32)
+        .bind(&processing_metadata)
+        .bind(chunking.strategy.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(KnowledgeBaseDocumentRechunk {
+            document_id: self.document_id,
+            chunk_count: chunk_count as i64,
+            chunk_size: chunking.chunk_size as i64,
+            chunk_overlap: chunking.chunk_overlap as i64,
+            chunking_strategy: chunking.strategy,
+            chunks_replaced,
+        })
+    }
+}
+
+impl Command for UploadKnowledgeBaseUrlCommand {
+    type Output = AiKnowledgeBaseDocument;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let response = fetch_url(&self.url).await?;
+
         // For now, we'll determine content type from URL extension or default to text/html
         let content_type = if self.url.ends_with(".pdf") {
             "application/pdf"
@@ -523,13 +925,7 @@ impl Command for UploadKnowledgeBaseUrlCommand {
         }
         .to_string();
 
-        // Read the response body as string and convert to bytes
-        let text = response
-            .body_mut()
-            .read_to_string()
-            .map_err(|e| AppError::Internal(format!("Failed to read URL content: {}", e)))?;
-
-        let content = text.into_bytes();
+        let content = response.body.into_bytes();
 
         // Extract filename from URL
         let file_name = self.url.split('/').last().unwrap_or("webpage").to_string();
@@ -542,8 +938,888 @@ impl Command for UploadKnowledgeBaseUrlCommand {
             file_name,
             content.to_vec(),
             content_type,
-        );
+        )
+        .with_source(KnowledgeBaseDocumentSource::Url);
+
+        let mut document = upload_command.execute(app_state).await?;
+
+        let processing_metadata = json!({ "fetched_from": self.url });
+        sqlx::query(
+            "UPDATE ai_knowledge_base_documents SET processing_metadata = $2 WHERE id = $1",
+        )
+        .bind(document.id)
+        .bind(&processing_metadata)
+        .execute(&app_state.db_pool)
+        .await?;
+        document.processing_metadata = Some(processing_metadata);
+
+        Ok(document)
+    }
+}
+
+const URL_FETCH_TIMEOUT_MS: u64 = 15_000;
+const MAX_URL_CONTENT_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Fetches a user-supplied URL under the default tool policy, so ingested and
+/// crawled URLs can't reach private networks or metadata endpoints. Redirects
+/// aren't followed, as their targets never went through the policy.
+async fn fetch_url(url: &str) -> Result<ToolHttpResponse, AppError> {
+    let policy = ToolExecutionPolicy {
+        timeout_ms: URL_FETCH_TIMEOUT_MS,
+        max_response_bytes: MAX_URL_CONTENT_BYTES,
+        ..ToolExecutionPolicy::default()
+    };
+    let request = ToolHttpRequest {
+        method: HttpMethod::GET,
+        url: url.to_string(),
+        headers: Vec::new(),
+        body: None,
+    };
+
+    let response = ToolExecutionService::send(&policy, request)
+        .await
+        .map_err(|e| AppError::External(format!("Failed to fetch URL: {}", e).into()))?;
+    if response.status != 200 {
+        return Err(AppError::External(
+            format!("Failed to fetch URL: HTTP {}", response.status).into(),
+        ));
+    }
+
+    Ok(response)
+}
+
+const DEFAULT_CRAWL_MAX_PAGES: i32 = 50;
+const MAX_CRAWL_MAX_PAGES: i32 = 500;
+const DEFAULT_CRAWL_MAX_DEPTH: i32 = 2;
+const MAX_CRAWL_MAX_DEPTH: i32 = 5;
+
+enum PageIngestOutcome {
+    Ingested,
+    Unchanged,
+}
+
+pub struct IngestUrlIntoKnowledgeBaseCommand {
+    pub knowledge_base_id: i64,
+    pub url: String,
+    pub crawl: bool,
+    pub max_pages: Option<i32>,
+    pub max_depth: Option<i32>,
+}
+
+impl IngestUrlIntoKnowledgeBaseCommand {
+    pub fn new(knowledge_base_id: i64, url: String) -> Self {
+        Self {
+            knowledge_base_id,
+            url,
+            crawl: false,
+            max_pages: None,
+            max_depth: None,
+        }
+    }
+
+    pub fn with_crawl(mut self, max_pages: Option<i32>, max_depth: Option<i32>) -> Self {
+        self.crawl = true;
+        self.max_pages = max_pages;
+        self.max_depth = max_depth;
+        self
+    }
+}
+
+impl Command for IngestUrlIntoKnowledgeBaseCommand {
+    type Output = AiKnowledgeBaseCrawl;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let root_url = url::Url::parse(&self.url)
+            .map_err(|e| AppError::Validation(format!("Invalid URL: {}", e)))?;
+
+        if !matches!(root_url.scheme(), "http" | "https") || root_url.host_str().is_none() {
+            return Err(AppError::Validation(
+                "Only http and https URLs can be ingested".to_string(),
+            ));
+        }
+
+        let (max_pages, max_depth) = if self.crawl {
+            (
+                self.max_pages
+                    .unwrap_or(DEFAULT_CRAWL_MAX_PAGES)
+                    .clamp(1, MAX_CRAWL_MAX_PAGES),
+                self.max_depth
+                    .unwrap_or(DEFAULT_CRAWL_MAX_DEPTH)
+                    .clamp(0, MAX_CRAWL_MAX_DEPTH),
+            )
+        } else {
+            (1, 0)
+        };
+
+        let crawl_id = app_state.sf.next_id()? as i64;
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO ai_knowledge_base_crawls
+            (id, created_at, updated_at, knowledge_base_id, root_url, status, crawl, max_pages, max_depth)
+            VALUES ($1, $2, $3, $4, $5, 'pending', $6, $7, $8)
+            "#,
+        )
+        .bind(crawl_id)
+        .bind(now)
+        .bind(now)
+        .bind(self.knowledge_base_id)
+        .bind(root_url.as_str())
+        .bind(self.crawl)
+        .bind(max_pages)
+        .bind(max_depth)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        let background_state = app_state.clone();
+        let knowledge_base_id = self.knowledge_base_id;
+        let crawl_root = root_url.clone();
+
+        app_state.background_tasks.spawn(async move {
+            if let Err(e) = Self::run_crawl(
+                &background_state,
+                crawl_id,
+                knowledge_base_id,
+                crawl_root,
+                max_pages,
+                max_depth,
+            )
+            .await
+            {
+                tracing::error!("Knowledge base crawl {} failed: {}", crawl_id, e);
+
+                let _ = sqlx::query(
+                    r#"
+                    UPDATE ai_knowledge_base_crawls
+                    SET status = 'failed', error = $2, updated_at = NOW(), completed_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(crawl_id)
+                .bind(e.to_string())
+                .execute(&background_state.db_pool)
+                .await;
+            }
+        });
+
+        Ok(AiKnowledgeBaseCrawl {
+            id: crawl_id,
+            created_at: now,
+            updated_at: now,
+            knowledge_base_id: self.knowledge_base_id,
+            root_url: root_url.to_string(),
+            status: "pending".to_string(),
+            crawl: self.crawl,
+            max_pages,
+            max_depth,
+            pages_ingested: 0,
+            pages_skipped: 0,
+            pages_failed: 0,
+            error: None,
+            completed_at: None,
+        })
+    }
+}
+
+impl IngestUrlIntoKnowledgeBaseCommand {
+    async fn run_crawl(
+        app_state: &AppState,
+        crawl_id: i64,
+        knowledge_base_id: i64,
+        root_url: url::Url,
+        max_pages: i32,
+        max_depth: i32,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE ai_knowledge_base_crawls SET status = 'running', updated_at = NOW() WHERE id = $1",
+        )
+        .bind(crawl_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        let mut queue = VecDeque::from([(root_url.clone(), 0)]);
+        let mut visited = HashSet::from([root_url.to_string()]);
+
+        let (mut ingested, mut skipped, mut failed) = (0i32, 0i32, 0i32);
+
+        while let Some((page_url, depth)) = queue.pop_front() {
+            if ingested + skipped + failed >= max_pages {
+                break;
+            }
+
+            // Crawls aren't resumable; keep what was ingested and stop.
+            if app_state.background_tasks.is_shutting_down() {
+                sqlx::query(
+                    r#"
+                    UPDATE ai_knowledge_base_crawls
+                    SET status = 'interrupted', updated_at = NOW(), completed_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(crawl_id)
+                .execute(&app_state.db_pool)
+                .await?;
+                return Ok(());
+            }
+
+            let html = match Self::fetch_page(page_url.clone()).await {
+                Ok(html) => html,
+                Err(e) => {
+                    tracing::warn!("Failed to fetch {}: {}", page_url, e);
+                    failed += 1;
+                    Self::update_progress(app_state, crawl_id, ingested, skipped, failed).await?;
+                    continue;
+                }
+            };
+
+            if depth < max_depth {
+                for link in Self::extract_same_domain_links(&html, &page_url, &root_url) {
+                    if visited.insert(link.to_string()) {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+
+            match Self::ingest_page(app_state, crawl_id, knowledge_base_id, &page_url, &html).await
+            {
+                Ok(PageIngestOutcome::Ingested) => ingested += 1,
+                Ok(PageIngestOutcome::Unchanged) => skipped += 1,
+                Err(e) => {
+                    tracing::warn!("Failed to ingest {}: {}", page_url, e);
+                    failed += 1;
+                }
+            }
+
+            Self::update_progress(app_state, crawl_id, ingested, skipped, failed).await?;
+        }
+
+        let status = if ingested + skipped == 0 && failed > 0 {
+            "failed"
+        } else {
+            "completed"
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE ai_knowledge_base_crawls
+            SET status = $2, updated_at = NOW(), completed_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(crawl_id)
+        .bind(status)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_progress(
+        app_state: &AppState,
+        crawl_id: i64,
+        ingested: i32,
+        skipped: i32,
+        failed: i32,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE ai_knowledge_base_crawls
+            SET pages_ingested = $2, pages_skipped = $3, pages_failed = $4, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(crawl_id)
+        .bind(ingested)
+        .bind(skipped)
+        .bind(failed)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fetch_page(page_url: url::Url) -> Result<String, AppError> {
+        let response = fetch_url(page_url.as_str()).await?;
+
+        let is_html = response
+            .content_type
+            .as_deref()
+            .map(|v| v.contains("text/html"))
+            .unwrap_or(true);
+
+        if !is_html {
+            return Err(AppError::External(
+                "URL did not return an HTML page".to_string().into(),
+            ));
+        }
+
+        Ok(response.body)
+    }
+
+    fn extract_same_domain_links(
+        html: &str,
+        page_url: &url::Url,
+        root_url: &url::Url,
+    ) -> Vec<url::Url> {
+        let href_regex = regex::Regex::new(r#"(?i)<a\s[^>]*href\s*=\s*["']([^"']+)["']"#).unwrap();
+
+        href_regex
+            .captures_iter(html)
+            .filter_map(|captures| page_url.join(captures[1].trim()).ok())
+            .filter(|link| {
+                matches!(link.scheme(), "http" | "https") && link.host_str() == root_url.host_str()
+            })
+            .map(|mut link| {
+                link.set_fragment(None);
+                link
+            })
+            .collect()
+    }
+
+    fn extract_title(html: &str, page_url: &url::Url) -> String {
+        regex::Regex::new(r"(?is)<title[^>]*>(.*?)</title>")
+            .unwrap()
+            .captures(html)
+            .map(|captures| captures[1].trim().to_string())
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| page_url.to_string())
+    }
+
+    async fn ingest_page(
+        app_state: &AppState,
+        crawl_id: i64,
+        knowledge_base_id: i64,
+        page_url: &url::Url,
+        html: &str,
+    ) -> Result<PageIngestOutcome, AppError> {
+        let chunking = GetKnowledgeBaseChunkingQuery::new(knowledge_base_id)
+            .execute(app_state)
+            .await?;
+        let cleaned_text = app_state
+            .text_processing_service
+            .extract_readable_text_for_chunking(html, &chunking)?;
+
+        if cleaned_text.is_empty() {
+            return Err(AppError::BadRequest(
+                "Page has no readable content".to_string(),
+            ));
+        }
+
+        let content_hash = hex::encode(Sha256::digest(cleaned_text.as_bytes()));
+        let source_url = page_url.to_string();
+
+        let existing = sqlx::query(
+            r#"
+            SELECT id, processing_metadata->>'content_hash' AS content_hash, ingestion_status
+            FROM ai_knowledge_base_documents
+            WHERE knowledge_base_id = $1 AND processing_metadata->>'source_url' = $2
+            "#,
+        )
+        .bind(knowledge_base_id)
+        .bind(&source_url)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        let existing_document_id = match existing {
+            Some(row) => {
+                // The hash is saved before the page is embedded, so it only
+                // says the page is unchanged once that has succeeded.
+                let existing_hash: Option<String> = row.get("content_hash");
+                let ingested = row.get::<String, _>("ingestion_status") == "ready";
+                if ingested && existing_hash.as_deref() == Some(content_hash.as_str()) {
+                    return Ok(PageIngestOutcome::Unchanged);
+                }
+                Some(row.get::<i64, _>("id"))
+            }
+            None => None,
+        };
+
+        let title = Self::extract_title(html, page_url);
+        let file_name = page_url
+            .path_segments()
+            .and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+            .unwrap_or("index")
+            .to_string();
+        let file_size = cleaned_text.len() as i64;
+        let processing_metadata = json!({
+            "source_url": source_url,
+            "content_hash": content_hash,
+            "crawl_id": crawl_id.to_string(),
+        });
+
+        let collections = GetKnowledgeBaseCollectionsQuery::new(knowledge_base_id)
+            .execute(app_state)
+            .await?;
+
+        let document_id = match existing_document_id {
+            Some(document_id) => {
+                let mut metadata_filter = HashMap::new();
+                metadata_filter.insert("document_id".to_string(), json!(document_id.to_string()));
+                for collection_name in collections.all() {
+                    QdrantService::delete_by_metadata(
+                        &collection_name,
+                        knowledge_base_id,
+                        metadata_filter.clone(),
+                    )
+                    .await?;
+                }
+
+                sqlx::query(
+                    r#"
+                    UPDATE ai_knowledge_base_documents
+                    SET updated_at = NOW(), title = $3, file_size = $4, processing_metadata = $5,
+                        ingestion_status = 'pending'
+                    WHERE id = $1 AND knowledge_base_id = $2
+                    "#,
+                )
+                .bind(document_id)
+                .bind(knowledge_base_id)
+                .bind(&title)
+                .bind(file_size)
+                .bind(&processing_metadata)
+                .execute(&app_state.db_pool)
+                .await?;
+
+                document_id
+            }
+            None => {
+                let document_id = app_state.sf.next_id()? as i64;
+                let now = Utc::now();
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO ai_knowledge_base_documents
+                    (id, created_at, updated_at, title, description, file_name, file_size, file_type, file_url, knowledge_base_id, processing_metadata)
+                    VALUES ($1, $2, $3, $4, NULL, $5, $6, 'text/html', $7, $8, $9)
+                    "#,
+                )
+                .bind(document_id)
+                .bind(now)
+                .bind(now)
+                .bind(&title)
+                .bind(&file_name)
+                .bind(file_size)
+                .bind(&source_url)
+                .bind(knowledge_base_id)
+                .bind(&processing_metadata)
+                .execute(&app_state.db_pool)
+                .await?;
+
+                document_id
+            }
+        };
+
+        let ingestion = DocumentText {
+            document_id,
+            knowledge_base_id,
+            title,
+            file_type: "text/html".to_string(),
+            source: KnowledgeBaseDocumentSource::Crawl,
+            text: cleaned_text,
+            metadata: HashMap::from([
+                ("source_url".to_string(), json!(source_url)),
+                ("content_hash".to_string(), json!(content_hash)),
+            ]),
+        }
+        .write_chunks(
+            app_state,
+            &collections.active,
+            &chunking,
+            app_state.sf.next_id()? as i64,
+        )
+        .await;
+        record_ingestion(app_state, document_id, &chunking, &ingestion).await?;
+        ingestion?;
+
+        Ok(PageIngestOutcome::Ingested)
+    }
+}
+
+const REEMBED_BATCH_SIZE: u32 = 64;
+const REEMBED_STALE_AFTER_SECS: i64 = 120;
+
+/// Rebuilds every vector of a knowledge base with its selected embedding provider in a
+/// fresh collection, then flips the knowledge base over to it in one update. Progress
+/// is checkpointed per batch, so re-running the command resumes an interrupted job.
+/// A job that fails drops its collection and starts over when the command is run
+/// again. While a job is running, the command returns its progress instead of
+/// starting another worker.
+pub struct ReembedKnowledgeBaseCommand {
+    pub knowledge_base_id: i64,
+}
+
+impl ReembedKnowledgeBaseCommand {
+    pub fn new(knowledge_base_id: i64) -> Self {
+        Self { knowledge_base_id }
+    }
+}
+
+impl Command for ReembedKnowledgeBaseCommand {
+    type Output = AiKnowledgeBaseReembedJob;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        // Serializes concurrent requests for the knowledge base, so only one of them
+        // claims the job and spawns a worker
+        sqlx::query("SELECT id FROM ai_knowledge_bases WHERE id = $1 FOR UPDATE")
+            .bind(self.knowledge_base_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let unfinished = sqlx::query(
+            r#"
+            SELECT id, status, updated_at
+            FROM ai_knowledge_base_reembed_jobs
+            WHERE knowledge_base_id = $1 AND status <> 'completed'
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(self.knowledge_base_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let job_id = match unfinished {
+            Some(row) => {
+                let job_id: i64 = row.get("id");
+                let status: String = row.get("status");
+                let updated_at: DateTime<Utc> = row.get("updated_at");
+
+                // A running job that checkpointed recently still has a live worker
+                if status == "running"
+                    && (Utc::now() - updated_at).num_seconds() < REEMBED_STALE_AFTER_SECS
+                {
+                    drop(tx);
+                    return GetReembedProgressQuery::new(self.knowledge_base_id, job_id)
+                        .execute(app_state)
+                        .await;
+                }
+
+                sqlx::query(
+                    r#"
+                    UPDATE ai_knowledge_base_reembed_jobs
+                    SET status = 'running', updated_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(job_id)
+                .execute(&mut *tx)
+                .await?;
+
+                job_id
+            }
+            None => {
+                let collections = GetKnowledgeBaseCollectionsQuery::new(self.knowledge_base_id)
+                    .execute(app_state)
+                    .await?;
+
+                let knowledge_base = sqlx::query(
+                    "SELECT deployment_id, embedding_provider FROM ai_knowledge_bases WHERE id = $1",
+                )
+                .bind(self.knowledge_base_id)
+                .fetch_one(&app_state.db_pool)
+                .await?;
+                let embedder = selected_embedder(
+                    app_state,
+                    knowledge_base.get("deployment_id"),
+                    knowledge_base
+                        .get::<Option<String>, _>("embedding_provider")
+                        .as_deref(),
+                )
+                .await?;
+
+                let job_id = app_state.sf.next_id()? as i64;
+                let target_collection = QdrantService::reembed_collection(
+                    knowledge_base.get("deployment_id"),
+                    self.knowledge_base_id,
+                    job_id,
+                );
+                let total_chunks =
+                    QdrantService::count_chunks(&collections.active, self.knowledge_base_id).await?
+                        as i64;
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO ai_knowledge_base_reembed_jobs
+                    (id, knowledge_base_id, source_collection, target_collection, status, total_chunks,
+                     embedding_provider, embedding_model)
+                    VALUES ($1, $2, $3, $4, 'running', $5, $6, $7)
+                    "#,
+                )
+                .bind(job_id)
+                .bind(self.knowledge_base_id)
+                .bind(&collections.active)
+                .bind(&target_collection)
+                .bind(total_chunks)
+                .bind(embedder.name())
+                .bind(embedder.model())
+                .execute(&mut *tx)
+                .await?;
+
+                job_id
+            }
+        };
+
+        tx.commit().await?;
+
+        let background_state = app_state.clone();
+        let knowledge_base_id = self.knowledge_base_id;
+
+        app_state.background_tasks.spawn(async move {
+            if let Err(e) = Self::run_job(&background_state, knowledge_base_id, job_id).await {
+                tracing::error!("Re-embedding job {} failed: {}", job_id, e);
+                Self::fail_job(&background_state, knowledge_base_id, job_id, &e).await;
+            }
+        });
+
+        GetReembedProgressQuery::new(self.knowledge_base_id, job_id)
+            .execute(app_state)
+            .await
+    }
+}
+
+impl ReembedKnowledgeBaseCommand {
+    /// Drops the job's half-built collection and rewinds it, so running the command
+    /// again starts the job over.
+    async fn fail_job(app_state: &AppState, knowledge_base_id: i64, job_id: i64, error: &AppError) {
+        match GetReembedProgressQuery::new(knowledge_base_id, job_id)
+            .execute(app_state)
+            .await
+        {
+            Ok(job) => {
+                if let Err(e) = QdrantService::delete_collection(&job.target_collection).await {
+                    tracing::warn!(
+                        "Failed to drop collection {} of failed re-embedding job {}: {}",
+                        job.target_collection,
+                        job_id,
+                        e
+                    );
+                }
+            }
+            Err(e) => tracing::warn!("Failed to load re-embedding job {}: {}", job_id, e),
+        }
+
+        let _ = sqlx::query(
+            r#"
+            UPDATE ai_knowledge_base_reembed_jobs
+            SET status = 'failed', error = $2, cursor = NULL, processed_chunks = 0,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(job_id)
+        .bind(error.to_string())
+        .execute(&app_state.db_pool)
+        .await;
+    }
+
+    async fn run_job(
+        app_state: &AppState,
+        knowledge_base_id: i64,
+        job_id: i64,
+    ) -> Result<(), AppError> {
+        let job = GetReembedProgressQuery::new(knowledge_base_id, job_id)
+            .execute(app_state)
+            .await?;
+        // Jobs from before providers were recorded used the default
+        let embedder = app_state
+            .embedding_service
+            .select(job.embedding_provider.as_deref())?
+            .clone();
+        if job
+            .embedding_model
+            .as_deref()
+            .is_some_and(|model| model != embedder.model())
+        {
+            return Err(AppError::Internal(format!(
+                "Embedding provider '{}' no longer serves the job's model",
+                embedder.name()
+            )));
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE ai_knowledge_base_reembed_jobs
+            SET status = 'running', error = NULL, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(job_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        QdrantService::ensure_collection(
+            &job.target_collection,
+            embedder.dimensions(),
+            embedder.distance(),
+        )
+        .await?;
+
+        let mut cursor = job.cursor;
+        let mut processed_chunks = job.processed_chunks;
+
+        loop {
+            let (chunks, next_cursor) = QdrantService::scroll_chunks(
+                &job.source_collection,
+                knowledge_base_id,
+                cursor,
+                REEMBED_BATCH_SIZE,
+            )
+            .await?;
+
+            if !chunks.is_empty() {
+                let chunk_texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
+                let embeddings = embedder.embed(chunk_texts).await?;
+
+                let document_chunks: Vec<DocumentChunk> = chunks
+                    .into_iter()
+                    .zip(embeddings)
+                    .map(|(chunk, embedding)| DocumentChunk {
+                        id: chunk.id,
+                        content: chunk.content,
+                        metadata: chunk.metadata,
+                        embedding,
+                    })
+                    .collect();
+
+                processed_chunks += document_chunks.len() as i64;
+
+                // Point ids are preserved, so replaying a batch after a crash is harmless
+                QdrantService::upsert_documents(
+                    &job.target_collection,
+                    document_chunks,
+                    knowledge_base_id,
+                )
+                .await?;
+            }
+
+            cursor = next_cursor;
+
+            sqlx::query(
+                r#"
+                UPDATE ai_knowledge_base_reembed_jobs
+                SET cursor = $2, processed_chunks = $3, updated_at = NOW()
+                WHERE id = $1
+                "#,
+            )
+            .bind(job_id)
+            .bind(cursor)
+            .bind(processed_chunks)
+            .execute(&app_state.db_pool)
+            .await?;
+
+            if cursor.is_none() {
+                break;
+            }
+
+            // The cursor is saved, so running the command again resumes here.
+            if app_state.background_tasks.is_shutting_down() {
+                sqlx::query(
+                    r#"
+                    UPDATE ai_knowledge_base_reembed_jobs
+                    SET status = 'interrupted', updated_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(job_id)
+                .execute(&app_state.db_pool)
+                .await?;
+                return Ok(());
+            }
+        }
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE ai_knowledge_bases
+            SET active_collection = $2, embedding_model = $3, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(knowledge_base_id)
+        .bind(&job.target_collection)
+        .bind(embedder.model())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE ai_knowledge_base_reembed_jobs
+            SET status = 'completed', updated_at = NOW(), completed_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(job_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        // The default collection is shared between knowledge bases, so only this
+        // knowledge base's points can be removed from it
+        let cleanup = if job.source_collection == QdrantService::default_collection() {
+            QdrantService::delete_knowledge_base(&job.source_collection, knowledge_base_id).await
+        } else {
+            QdrantService::delete_collection(&job.source_collection).await
+        };
+
+        if let Err(e) = cleanup {
+            tracing::warn!(
+                "Failed to clean up collection {} after re-embedding knowledge base {}: {}",
+                job.source_collection,
+                knowledge_base_id,
+                e
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Picks the embedding provider new knowledge bases in the deployment use
+/// when they don't name one. Existing knowledge bases keep the model their
+/// vectors were made with until they are re-embedded.
+pub struct UpdateDeploymentEmbeddingProviderCommand {
+    deployment_id: i64,
+    provider: Option<String>,
+}
+
+impl UpdateDeploymentEmbeddingProviderCommand {
+    pub fn new(deployment_id: i64, provider: Option<String>) -> Self {
+        Self {
+            deployment_id,
+            provider,
+        }
+    }
+}
+
+impl Command for UpdateDeploymentEmbeddingProviderCommand {
+    type Output = DeploymentEmbeddingProviders;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if let Some(provider) = &self.provider {
+            app_state.embedding_service.select(Some(provider))?;
+        }
+
+        let result = sqlx::query(
+            r#"
+            UPDATE deployments
+            SET ai_embedding_provider = $2, updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&self.provider)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Deployment not found".to_string()));
+        }
 
-        upload_command.execute(app_state).await
+        Ok(deployment_embedding_providers(app_state, self.provider))
     }
 }
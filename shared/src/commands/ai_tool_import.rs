@@ -0,0 +1,361 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{
+        AiToolConfiguration, ApiToolConfiguration, AuthorizationConfiguration, HttpMethod,
+        HttpParameter, OpenApiImportReport, OpenApiOperationOutcome, OpenApiOperationReport,
+        ToolExecutionPolicy,
+    },
+    services::{OpenApiDocument, ToolExecutionService, ToolHttpRequest},
+    state::AppState,
+};
+
+use super::Command;
+
+/// Larger documents are refused, whether uploaded or fetched.
+pub const MAX_OPENAPI_DOCUMENT_BYTES: usize = 5 * 1024 * 1024;
+
+const OPENAPI_FETCH_TIMEOUT_MS: u64 = 15_000;
+
+/// The longest tool name an operationId can become.
+const MAX_TOOL_NAME_LENGTH: usize = 100;
+
+pub enum OpenApiSource {
+    Url(String),
+    /// The document itself, as JSON or YAML.
+    Document(String),
+}
+
+/// Creates an API tool for each operation of an OpenAPI 3.x document, named
+/// after its operationId. Tools imported from the same document before are
+/// updated rather than created again. Operations that can't be expressed as
+/// a tool are skipped and reported with the reason.
+pub struct ImportToolsFromOpenApiCommand {
+    deployment_id: i64,
+    source: OpenApiSource,
+    operation_ids: Option<Vec<String>>,
+    base_url: Option<String>,
+    headers: Vec<HttpParameter>,
+    authorization: Option<AuthorizationConfiguration>,
+    execution_policy: ToolExecutionPolicy,
+}
+
+impl ImportToolsFromOpenApiCommand {
+    pub fn new(deployment_id: i64, source: OpenApiSource) -> Self {
+        Self {
+            deployment_id,
+            source,
+            operation_ids: None,
+            base_url: None,
+            headers: Vec::new(),
+            authorization: None,
+            execution_policy: ToolExecutionPolicy::default(),
+        }
+    }
+
+    /// Imports only these operations instead of all of them.
+    pub fn with_operation_ids(mut self, operation_ids: Vec<String>) -> Self {
+        self.operation_ids = Some(operation_ids);
+        self
+    }
+
+    /// Overrides the document's first server URL.
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    /// Headers every imported tool sends, before the operation's own.
+    pub fn with_headers(mut self, headers: Vec<HttpParameter>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_authorization(mut self, authorization: AuthorizationConfiguration) -> Self {
+        self.authorization = Some(authorization);
+        self
+    }
+
+    pub fn with_execution_policy(mut self, execution_policy: ToolExecutionPolicy) -> Self {
+        self.execution_policy = execution_policy;
+        self
+    }
+
+    /// Fetched under the default tool policy, so a document URL can't reach
+    /// private networks either.
+    async fn fetch_document(url: &str) -> Result<String, AppError> {
+        let policy = ToolExecutionPolicy {
+            timeout_ms: OPENAPI_FETCH_TIMEOUT_MS,
+            max_response_bytes: MAX_OPENAPI_DOCUMENT_BYTES as u64,
+            ..ToolExecutionPolicy::default()
+        };
+        let request = ToolHttpRequest {
+            method: HttpMethod::GET,
+            url: url.to_string(),
+            headers: vec![(
+                "Accept".to_string(),
+                "application/json, application/yaml".to_string(),
+            )],
+            body: None,
+        };
+
+        let response = ToolExecutionService::send(&policy, request)
+            .await
+            .map_err(|e| {
+                AppError::BadRequest(format!("Failed to fetch the OpenAPI document: {}", e))
+            })?;
+        if !(200..300).contains(&response.status) {
+            return Err(AppError::BadRequest(format!(
+                "Failed to fetch the OpenAPI document: HTTP {}",
+                response.status
+            )));
+        }
+
+        Ok(response.body)
+    }
+
+    /// The URL operation paths are appended to, without a trailing slash.
+    fn base_url(&self, document: &OpenApiDocument) -> Result<String, AppError> {
+        let base_url = match (&self.base_url, document.server_url(), &self.source) {
+            (Some(base_url), _, _) => url::Url::parse(base_url).ok(),
+            (None, Some(server_url), OpenApiSource::Url(document_url)) => {
+                url::Url::parse(document_url)
+                    .and_then(|document_url| document_url.join(server_url))
+                    .ok()
+            }
+            (None, Some(server_url), OpenApiSource::Document(_)) => {
+                url::Url::parse(server_url).ok()
+            }
+            (None, None, _) => None,
+        };
+
+        base_url
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .map(|url| url.as_str().trim_end_matches('/').to_string())
+            .ok_or_else(|| {
+                AppError::BadRequest(
+                    "The OpenAPI document has no absolute server URL, so base_url is required"
+                        .to_string(),
+                )
+            })
+    }
+}
+
+fn skip_operation(
+    report: &mut OpenApiImportReport,
+    operation_id: Option<String>,
+    method: &str,
+    path: &str,
+    reason: String,
+) {
+    report.skipped += 1;
+    report.operations.push(OpenApiOperationReport {
+        operation_id,
+        method: method.to_string(),
+        path: path.to_string(),
+        outcome: OpenApiOperationOutcome::Skipped,
+        tool_id: None,
+        reason: Some(reason),
+    });
+}
+
+impl Command for ImportToolsFromOpenApiCommand {
+    type Output = OpenApiImportReport;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let source = match &self.source {
+            OpenApiSource::Url(url) => Self::fetch_document(url).await?,
+            OpenApiSource::Document(document) => document.clone(),
+        };
+        if source.len() > MAX_OPENAPI_DOCUMENT_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "OpenAPI documents can be at most {} bytes",
+                MAX_OPENAPI_DOCUMENT_BYTES
+            )));
+        }
+
+        let document = OpenApiDocument::parse(&source)?;
+        let title = document
+            .title()
+            .ok_or_else(|| {
+                AppError::BadRequest("The OpenAPI document has no info.title".to_string())
+            })?
+            .to_string();
+        let base_url = self.base_url(&document)?;
+        let operations = document.operations();
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let existing: HashMap<String, i64> = sqlx::query(
+            r#"
+            SELECT id, openapi_operation_id FROM ai_tools
+            WHERE deployment_id = $1 AND openapi_source = $2 AND openapi_operation_id IS NOT NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&title)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|row| (row.get("openapi_operation_id"), row.get("id")))
+        .collect();
+
+        let mut operation_id_counts: HashMap<String, usize> = HashMap::new();
+        for operation in &operations {
+            if let Some(operation_id) = &operation.operation_id {
+                *operation_id_counts.entry(operation_id.clone()).or_default() += 1;
+            }
+        }
+
+        let selected: Option<HashSet<&str>> = self
+            .operation_ids
+            .as_ref()
+            .map(|ids| ids.iter().map(String::as_str).collect());
+
+        let mut report = OpenApiImportReport {
+            source: title.clone(),
+            created: 0,
+            updated: 0,
+            skipped: 0,
+            operations: Vec::new(),
+        };
+
+        for operation in operations {
+            if let Some(selected) = &selected
+                && !operation
+                    .operation_id
+                    .as_deref()
+                    .is_some_and(|operation_id| selected.contains(operation_id))
+            {
+                continue;
+            }
+
+            let Some(operation_id) = operation.operation_id.clone() else {
+                skip_operation(
+                    &mut report,
+                    None,
+                    &operation.method,
+                    &operation.path,
+                    "The operation has no operationId".to_string(),
+                );
+                continue;
+            };
+            let definition = if operation_id_counts[&operation_id] > 1 {
+                Err("The operationId is used by more than one operation".to_string())
+            } else if operation_id.len() > MAX_TOOL_NAME_LENGTH {
+                Err(format!(
+                    "The operationId is longer than {} characters",
+                    MAX_TOOL_NAME_LENGTH
+                ))
+            } else {
+                operation.tool
+            };
+            let definition = match definition {
+                Ok(definition) => definition,
+                Err(reason) => {
+                    skip_operation(
+                        &mut report,
+                        Some(operation_id),
+                        &operation.method,
+                        &operation.path,
+                        reason,
+                    );
+                    continue;
+                }
+            };
+
+            let mut headers = self.headers.clone();
+            headers.extend(definition.headers);
+            let configuration = AiToolConfiguration::Api(ApiToolConfiguration {
+                endpoint: format!("{}{}", base_url, operation.path),
+                method: definition.method,
+                path_parameters: definition.path_parameters,
+                headers,
+                query_parameters: definition.query_parameters,
+                body_parameters: definition.body_parameters,
+                authorization: self.authorization.clone(),
+                execution_policy: self.execution_policy.clone(),
+                input_schema: Some(definition.input_schema),
+            });
+            let configuration = serde_json::to_value(&configuration)
+                .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+            // A re-import keeps the tool's name, in case it was renamed
+            let (tool_id, outcome) = match existing.get(&operation_id) {
+                Some(&tool_id) => {
+                    sqlx::query(
+                        r#"
+                        UPDATE ai_tools
+                        SET description = $3, tool_type = 'api', configuration = $4, updated_at = NOW()
+                        WHERE id = $1 AND deployment_id = $2
+                        "#,
+                    )
+                    .bind(tool_id)
+                    .bind(self.deployment_id)
+                    .bind(&operation.summary)
+                    .bind(&configuration)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    report.updated += 1;
+                    (tool_id, OpenApiOperationOutcome::Updated)
+                }
+                None => {
+                    let tool_id = app_state.sf.next_id()? as i64;
+                    sqlx::query(
+                        r#"
+                        INSERT INTO ai_tools (
+                            id, created_at, updated_at, name, description, tool_type,
+                            deployment_id, configuration, openapi_source, openapi_operation_id
+                        )
+                        VALUES ($1, NOW(), NOW(), $2, $3, 'api', $4, $5, $6, $7)
+                        "#,
+                    )
+                    .bind(tool_id)
+                    .bind(&operation_id)
+                    .bind(&operation.summary)
+                    .bind(self.deployment_id)
+                    .bind(&configuration)
+                    .bind(&title)
+                    .bind(&operation_id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    report.created += 1;
+                    (tool_id, OpenApiOperationOutcome::Created)
+                }
+            };
+
+            report.operations.push(OpenApiOperationReport {
+                operation_id: Some(operation_id),
+                method: operation.method,
+                path: operation.path,
+                outcome,
+                tool_id: Some(tool_id),
+                reason: None,
+            });
+        }
+
+        if let Some(operation_ids) = &self.operation_ids {
+            for operation_id in operation_ids {
+                if !operation_id_counts.contains_key(operation_id) {
+                    skip_operation(
+                        &mut report,
+                        Some(operation_id.clone()),
+                        "",
+                        "",
+                        "The document has no operation with this operationId".to_string(),
+                    );
+                }
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(report)
+    }
+}
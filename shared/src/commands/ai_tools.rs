@@ -3,8 +3,19 @@ use crate::{
         commands::Command,
         models::{AiTool,  AiToolType, AiToolConfiguration},
 };
+use crate::{
+    models::{
+        AiToolInvocation, ApiToolConfiguration, HttpMethod, HttpParameter,
+        KnowledgeBaseToolConfiguration, ParameterValueType, ToolExecutionError,
+    },
+    queries::{GetAiKnowledgeBaseByIdQuery, GetAiToolByIdQuery, Query, SearchKnowledgeBaseQuery},
+    services::{ToolExecutionService, ToolHttpRequest},
+};
 use chrono::Utc;
+use serde_json::Value;
 use sqlx::Row;
+use std::collections::HashMap;
+use std::time::Instant;
 
 pub struct CreateAiToolCommand {
     pub deployment_id: i64,
@@ -35,7 +46,7 @@ impl CreateAiToolCommand {
 impl Command for CreateAiToolCommand {
     type Output = AiTool;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let tool_id = app_state.sf.next_id()? as i64;
         let now = Utc::now();
         let tool_type_str: String = self.tool_type.into();
@@ -123,7 +134,7 @@ impl UpdateAiToolCommand {
 impl Command for UpdateAiToolCommand {
     type Output = AiTool;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let now = Utc::now();
 
         // Build dynamic query based on provided fields
@@ -219,7 +230,7 @@ impl DeleteAiToolCommand {
 impl Command for DeleteAiToolCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut tx = app_state.db_pool.begin().await
             .map_err(|e| AppError::Database(e))?;
 
@@ -248,3 +259,289 @@ impl Command for DeleteAiToolCommand {
         Ok(())
     }
 }
+
+/// Percent-encodes everything but RFC 3986 unreserved characters, so a
+/// value can't add segments or a query to the path it is put in.
+fn encode_path_segment(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+pub struct ExecuteAiToolCommand {
+    pub deployment_id: i64,
+    pub tool_id: i64,
+    pub inputs: HashMap<String, Value>,
+    pub workflow_run_id: Option<i64>,
+    pub agent_session_id: Option<i64>,
+}
+
+struct ToolOutcome {
+    response_status: Option<i32>,
+    output: Option<Value>,
+    error: Option<ToolExecutionError>,
+}
+
+impl ExecuteAiToolCommand {
+    pub fn new(deployment_id: i64, tool_id: i64, inputs: HashMap<String, Value>) -> Self {
+        Self {
+            deployment_id,
+            tool_id,
+            inputs,
+            workflow_run_id: None,
+            agent_session_id: None,
+        }
+    }
+
+    pub fn with_workflow_run_id(mut self, workflow_run_id: i64) -> Self {
+        self.workflow_run_id = Some(workflow_run_id);
+        self
+    }
+
+    pub fn with_agent_session_id(mut self, agent_session_id: i64) -> Self {
+        self.agent_session_id = Some(agent_session_id);
+        self
+    }
+
+    fn resolve_parameters(
+        &self,
+        parameters: &[HttpParameter],
+    ) -> Result<Vec<(String, Value)>, ToolExecutionError> {
+        let mut resolved = Vec::with_capacity(parameters.len());
+
+        for parameter in parameters {
+            let value = match &parameter.value_type {
+                ParameterValueType::Hardcoded { value } => Some(Value::String(value.clone())),
+                ParameterValueType::FromChat { lookup_key } => self.inputs.get(lookup_key).cloned(),
+            };
+
+            match value {
+                Some(value) => resolved.push((parameter.name.clone(), value)),
+                None if parameter.required => {
+                    return Err(ToolExecutionError::MissingInput {
+                        name: parameter.name.clone(),
+                    });
+                }
+                None => {}
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    fn value_to_string(value: &Value) -> String {
+        match value {
+            Value::String(value) => value.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    fn build_api_request(
+        &self,
+        configuration: &ApiToolConfiguration,
+    ) -> Result<ToolHttpRequest, ToolExecutionError> {
+        let mut endpoint = configuration.endpoint.clone();
+        for (name, value) in self.resolve_parameters(&configuration.path_parameters)? {
+            let value = Self::value_to_string(&value);
+            // URL parsing resolves these even when percent-encoded
+            if value == "." || value == ".." {
+                return Err(ToolExecutionError::InvalidEndpoint {
+                    message: format!("Path parameter {} can't be '{}'", name, value),
+                });
+            }
+            endpoint = endpoint.replace(&format!("{{{}}}", name), &encode_path_segment(&value));
+        }
+
+        let mut url =
+            url::Url::parse(&endpoint).map_err(|e| ToolExecutionError::InvalidEndpoint {
+                message: e.to_string(),
+            })?;
+
+        let query_parameters = self.resolve_parameters(&configuration.query_parameters)?;
+        if !query_parameters.is_empty() {
+            let mut pairs = url.query_pairs_mut();
+            for (name, value) in &query_parameters {
+                pairs.append_pair(name, &Self::value_to_string(value));
+            }
+        }
+
+        let mut headers: Vec<(String, String)> = self
+            .resolve_parameters(&configuration.headers)?
+            .into_iter()
+            .map(|(name, value)| (name, Self::value_to_string(&value)))
+            .collect();
+
+        if let Some(authorization) = &configuration.authorization {
+            headers.extend(
+                self.resolve_parameters(&authorization.custom_headers)?
+                    .into_iter()
+                    .map(|(name, value)| (name, Self::value_to_string(&value))),
+            );
+        }
+
+        let body = if configuration.method == HttpMethod::GET {
+            None
+        } else {
+            let body_parameters = self.resolve_parameters(&configuration.body_parameters)?;
+            (!body_parameters.is_empty())
+                .then(|| Value::Object(body_parameters.into_iter().collect()))
+        };
+
+        Ok(ToolHttpRequest {
+            method: configuration.method.clone(),
+            url: url.to_string(),
+            headers,
+            body,
+        })
+    }
+
+    async fn run_api_tool(&self, configuration: &ApiToolConfiguration) -> ToolOutcome {
+        let request = match self.build_api_request(configuration) {
+            Ok(request) => request,
+            Err(error) => {
+                return ToolOutcome {
+                    response_status: None,
+                    output: None,
+                    error: Some(error),
+                };
+            }
+        };
+
+        match ToolExecutionService::send(&configuration.execution_policy, request).await {
+            Ok(response) => {
+                let output = serde_json::from_str::<Value>(&response.body)
+                    .unwrap_or(Value::String(response.body));
+                let error = (!(200..300).contains(&response.status)).then(|| {
+                    ToolExecutionError::RequestFailed {
+                        message: format!("Endpoint responded with status {}", response.status),
+                    }
+                });
+
+                ToolOutcome {
+                    response_status: Some(response.status as i32),
+                    output: Some(output),
+                    error,
+                }
+            }
+            Err(error) => ToolOutcome {
+                response_status: None,
+                output: None,
+                error: Some(error),
+            },
+        }
+    }
+
+    async fn run_knowledge_base_tool(
+        &self,
+        app_state: &AppState,
+        configuration: &KnowledgeBaseToolConfiguration,
+    ) -> Result<ToolOutcome, AppError> {
+        let Some(query) = self.inputs.get("query").map(Self::value_to_string) else {
+            return Ok(ToolOutcome {
+                response_status: None,
+                output: None,
+                error: Some(ToolExecutionError::MissingInput {
+                    name: "query".to_string(),
+                }),
+            });
+        };
+
+        GetAiKnowledgeBaseByIdQuery::new(self.deployment_id, configuration.knowledge_base_id)
+            .execute(app_state)
+            .await
+            .map_err(|_| AppError::NotFound("Knowledge base not found".to_string()))?;
+
+        let settings = &configuration.search_settings;
+        let mut search = SearchKnowledgeBaseQuery::new(configuration.knowledge_base_id, query)
+            .with_top_k(settings.max_results.unwrap_or(10) as u64);
+        if let Some(threshold) = settings.similarity_threshold {
+            search = search.with_score_threshold(threshold);
+        }
+
+        let results = search.execute(app_state).await?;
+        let output = serde_json::to_value(&results.results)
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        Ok(ToolOutcome {
+            response_status: None,
+            output: Some(output),
+            error: None,
+        })
+    }
+}
+
+impl Command for ExecuteAiToolCommand {
+    type Output = AiToolInvocation;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let tool = GetAiToolByIdQuery::new(self.deployment_id, self.tool_id)
+            .execute(app_state)
+            .await?;
+
+        let started = Instant::now();
+        let outcome = match &tool.configuration {
+            AiToolConfiguration::Api(configuration) => self.run_api_tool(configuration).await,
+            AiToolConfiguration::KnowledgeBase(configuration) => {
+                self.run_knowledge_base_tool(app_state, configuration)
+                    .await?
+            }
+        };
+        let duration_ms = started.elapsed().as_millis() as i64;
+
+        let status = if outcome.error.is_some() {
+            "failed"
+        } else {
+            "succeeded"
+        };
+        let error_json = outcome
+            .error
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        let invocation_id = app_state.sf.next_id()? as i64;
+        let row = sqlx::query(
+            r#"
+            INSERT INTO ai_tool_invocations (
+                id, tool_id, deployment_id, workflow_run_id, agent_session_id,
+                status, duration_ms, response_status, output, error
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING created_at
+            "#,
+        )
+        .bind(invocation_id)
+        .bind(self.tool_id)
+        .bind(self.deployment_id)
+        .bind(self.workflow_run_id)
+        .bind(self.agent_session_id)
+        .bind(status)
+        .bind(duration_ms)
+        .bind(outcome.response_status)
+        .bind(&outcome.output)
+        .bind(&error_json)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        Ok(AiToolInvocation {
+            id: invocation_id,
+            created_at: row.get("created_at"),
+            tool_id: self.tool_id,
+            deployment_id: self.deployment_id,
+            workflow_run_id: self.workflow_run_id,
+            agent_session_id: self.agent_session_id,
+            status: status.to_string(),
+            duration_ms,
+            response_status: outcome.response_status,
+            output: outcome.output,
+            error: outcome.error,
+        })
+    }
+}
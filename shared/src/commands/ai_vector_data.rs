@@ -0,0 +1,214 @@
+//! Knowledge base vectors outside of any one knowledge base's lifecycle.
+//!
+//! Each knowledge base has a Qdrant collection of its own, named after its
+//! deployment, plus one per unfinished re-embedding job. Knowledge bases
+//! from before that still share the default collection, where their points
+//! are told apart by `knowledge_base_id`.
+
+use std::collections::HashSet;
+
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{OrphanedQdrantCollection, QdrantCollectionReconciliation},
+    services::qdrant::QdrantService,
+    state::AppState,
+};
+
+use super::Command;
+
+/// Deletes every vector a deployment's knowledge bases have, for when the
+/// deployment itself goes. The knowledge base rows are left to whatever
+/// deletes the deployment's data.
+pub struct DeleteDeploymentVectorDataCommand {
+    deployment_id: i64,
+}
+
+impl DeleteDeploymentVectorDataCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for DeleteDeploymentVectorDataCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let default_collection = QdrantService::default_collection();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT kb.id AS knowledge_base_id, COALESCE(kb.active_collection, $2) AS collection
+            FROM ai_knowledge_bases kb
+            WHERE kb.deployment_id = $1
+            UNION
+            SELECT j.knowledge_base_id, c.collection
+            FROM ai_knowledge_base_reembed_jobs j
+            JOIN ai_knowledge_bases kb ON kb.id = j.knowledge_base_id
+            CROSS JOIN LATERAL (VALUES (j.source_collection), (j.target_collection)) AS c(collection)
+            WHERE kb.deployment_id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&default_collection)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        let mut shared_knowledge_bases = HashSet::new();
+        let mut collections = HashSet::new();
+        for row in rows {
+            let collection: String = row.get("collection");
+            if collection == default_collection {
+                shared_knowledge_bases.insert(row.get::<i64, _>("knowledge_base_id"));
+            } else {
+                collections.insert(collection);
+            }
+        }
+
+        // Also catches collections a crash left before they were recorded
+        let prefix = format!("kb_{}_", self.deployment_id);
+        collections.extend(
+            QdrantService::list_collections()
+                .await?
+                .into_iter()
+                .filter(|name| {
+                    name.starts_with(&prefix) && QdrantService::is_knowledge_base_collection(name)
+                }),
+        );
+
+        let mut failures = Vec::new();
+        for collection in &collections {
+            if let Err(e) = QdrantService::delete_collection(collection).await {
+                tracing::warn!("Failed to delete Qdrant collection {}: {}", collection, e);
+                failures.push(collection.clone());
+            }
+        }
+        for knowledge_base_id in shared_knowledge_bases {
+            if let Err(e) =
+                QdrantService::delete_knowledge_base(&default_collection, knowledge_base_id).await
+            {
+                tracing::warn!(
+                    "Failed to delete the vectors of knowledge base {}: {}",
+                    knowledge_base_id,
+                    e
+                );
+                failures.push(format!("{} ({})", default_collection, knowledge_base_id));
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(AppError::Internal(format!(
+                "Failed to delete the vectors of deployment {} in {}",
+                self.deployment_id,
+                failures.join(", ")
+            )));
+        }
+
+        tracing::info!(
+            "Deleted {} Qdrant collections of deployment {}",
+            collections.len(),
+            self.deployment_id
+        );
+        Ok(())
+    }
+}
+
+/// Lists the knowledge base collections in Qdrant that no knowledge base of
+/// a live deployment uses, such as ones left by a failed deletion. Only
+/// deletes them with [`Self::with_delete_orphans`]. The shared default
+/// collection is never one of them.
+pub struct ReconcileQdrantCollectionsCommand {
+    delete_orphans: bool,
+}
+
+impl ReconcileQdrantCollectionsCommand {
+    pub fn new() -> Self {
+        Self {
+            delete_orphans: false,
+        }
+    }
+
+    pub fn with_delete_orphans(mut self, delete_orphans: bool) -> Self {
+        self.delete_orphans = delete_orphans;
+        self
+    }
+}
+
+impl Default for ReconcileQdrantCollectionsCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for ReconcileQdrantCollectionsCommand {
+    type Output = QdrantCollectionReconciliation;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // Listed before the references are read: a knowledge base records its
+        // collection, and a re-embedding job its target, before the
+        // collection is created, so any collection in this list that's in
+        // use shows up in the query below.
+        let collections: Vec<String> = QdrantService::list_collections()
+            .await?
+            .into_iter()
+            .filter(|name| QdrantService::is_knowledge_base_collection(name))
+            .collect();
+
+        let referenced: HashSet<String> = sqlx::query_scalar(
+            r#"
+            SELECT kb.active_collection
+            FROM ai_knowledge_bases kb
+            JOIN deployments d ON d.id = kb.deployment_id
+            WHERE d.deleted_at IS NULL AND kb.active_collection IS NOT NULL
+            UNION
+            SELECT c.collection
+            FROM ai_knowledge_base_reembed_jobs j
+            JOIN ai_knowledge_bases kb ON kb.id = j.knowledge_base_id
+            JOIN deployments d ON d.id = kb.deployment_id
+            CROSS JOIN LATERAL (VALUES (j.source_collection), (j.target_collection)) AS c(collection)
+            WHERE d.deleted_at IS NULL AND j.status <> 'completed'
+            "#,
+        )
+        .fetch_all(&app_state.db_pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        let checked = collections.len();
+        let mut orphans = Vec::new();
+
+        for name in collections {
+            if referenced.contains(&name) {
+                continue;
+            }
+
+            let mut orphan = OrphanedQdrantCollection {
+                name,
+                deleted: false,
+                error: None,
+            };
+
+            if self.delete_orphans {
+                match QdrantService::delete_collection(&orphan.name).await {
+                    Ok(()) => {
+                        tracing::info!("Deleted orphaned Qdrant collection {}", orphan.name);
+                        orphan.deleted = true;
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to delete orphaned Qdrant collection {}: {}",
+                            orphan.name,
+                            e
+                        );
+                        orphan.error = Some(e.to_string());
+                    }
+                }
+            }
+
+            orphans.push(orphan);
+        }
+
+        Ok(QdrantCollectionReconciliation { checked, orphans })
+    }
+}
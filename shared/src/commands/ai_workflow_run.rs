@@ -0,0 +1,1053 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sqlx::Row;
+
+use crate::{
+    commands::{
+        AppendAgentSessionMessageCommand, CloseAgentSessionCommand, Command,
+        CreateAgentSessionCommand, ExecuteAiToolCommand,
+    },
+    error::AppError,
+    models::{
+        ActionNodeConfig, ActionType, AgentCallActionConfig, AiWorkflowVersion,
+        ConditionEvaluationType, ConditionNodeConfig, ConditionType, ExecutionContext,
+        ExecutionStatus, NodeExecution, TransformNodeConfig, TransformType, WorkflowEdge,
+        WorkflowExecution, WorkflowNode, WorkflowNodeType,
+    },
+    queries::{
+        GetAiAgentByIdQuery, GetAiKnowledgeBaseByIdQuery, GetAiWorkflowByIdQuery,
+        GetPublishedWorkflowVersionQuery, GetWorkflowRunQuery, Query, SearchKnowledgeBaseQuery,
+        WORKFLOW_RUN_COLUMNS, WORKFLOW_VERSION_COLUMNS, workflow_run_from_row,
+        workflow_version_from_row,
+    },
+    services::ChatMessage,
+    state::AppState,
+};
+
+const MAX_STEP_PAYLOAD_CHARS: usize = 4096;
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Per-node retry settings, read from `data.config.retry`. Anything left unset falls
+/// back to the workflow's `max_retries` and `retry_delay_seconds`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StepRetryPolicy {
+    max_retries: Option<u32>,
+    backoff_ms: Option<u64>,
+}
+
+pub struct ExecuteAiWorkflowCommand {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+    pub trigger_data: Option<Value>,
+    pub variables: HashMap<String, Value>,
+}
+
+impl ExecuteAiWorkflowCommand {
+    pub fn new(deployment_id: i64, workflow_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            trigger_data: None,
+            variables: HashMap::new(),
+        }
+    }
+
+    pub fn with_trigger_data(mut self, trigger_data: Value) -> Self {
+        self.trigger_data = Some(trigger_data);
+        self
+    }
+
+    pub fn with_variables(mut self, variables: HashMap<String, Value>) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    /// Explicit variables win over same-named trigger fields, which win over the
+    /// declared defaults.
+    fn resolve_variables(
+        &self,
+        version: &AiWorkflowVersion,
+    ) -> Result<HashMap<String, Value>, AppError> {
+        let mut variables = self.variables.clone();
+
+        for (key, variable) in &version.configuration.variables {
+            if variables.contains_key(key) {
+                continue;
+            }
+
+            let provided = self
+                .trigger_data
+                .as_ref()
+                .and_then(|data| data.get(key))
+                .cloned();
+            let default = variable.default_value.as_ref().map(|value| {
+                serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.clone()))
+            });
+
+            match provided.or(default) {
+                Some(value) => {
+                    variables.insert(key.clone(), value);
+                }
+                None if variable.required => {
+                    return Err(AppError::Validation(format!(
+                        "Workflow variable '{}' is required",
+                        key
+                    )));
+                }
+                None => {}
+            }
+        }
+
+        Ok(variables)
+    }
+}
+
+impl Command for ExecuteAiWorkflowCommand {
+    type Output = WorkflowExecution;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        GetAiWorkflowByIdQuery::new(self.deployment_id, self.workflow_id)
+            .execute(app_state)
+            .await?;
+
+        // Runs never see the draft, only what was last published.
+        let version = GetPublishedWorkflowVersionQuery::new(self.deployment_id, self.workflow_id)
+            .execute(app_state)
+            .await?;
+
+        if version.workflow_definition.nodes.is_empty() {
+            return Err(AppError::Validation(
+                "Workflow has no nodes to execute".to_string(),
+            ));
+        }
+
+        let context = ExecutionContext {
+            variables: self.resolve_variables(&version)?,
+            ..Default::default()
+        };
+        let context_json =
+            serde_json::to_value(&context).map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        let run_id = app_state.sf.next_id()? as i64;
+        let now = Utc::now();
+
+        let query = format!(
+            r#"
+            INSERT INTO ai_workflow_executions AS e (id, created_at, updated_at, workflow_id, workflow_version_id, status, trigger_data, execution_context)
+            VALUES ($1, $2, $2, $3, $4, 'pending', $5, $6)
+            RETURNING {}
+            "#,
+            WORKFLOW_RUN_COLUMNS
+        );
+
+        let row = sqlx::query(&query)
+            .bind(run_id)
+            .bind(now)
+            .bind(self.workflow_id)
+            .bind(version.id)
+            .bind(&self.trigger_data)
+            .bind(context_json)
+            .fetch_one(&app_state.db_pool)
+            .await?;
+        let run = workflow_run_from_row(&row)?;
+
+        let runner = WorkflowRunner {
+            deployment_id: self.deployment_id,
+            run_id,
+            trigger_data: self.trigger_data.unwrap_or(Value::Null),
+            version,
+            context,
+            outputs: serde_json::Map::new(),
+        };
+
+        runner.spawn(app_state);
+
+        Ok(run)
+    }
+}
+
+/// Restarts runs a previous shutdown interrupted. Call once at startup.
+pub struct ResumeInterruptedWorkflowRunsCommand;
+
+impl ResumeInterruptedWorkflowRunsCommand {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for ResumeInterruptedWorkflowRunsCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for ResumeInterruptedWorkflowRunsCommand {
+    type Output = usize;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
+            r#"
+            UPDATE ai_workflow_executions e
+            SET status = 'pending', updated_at = NOW()
+            FROM ai_workflows w
+            WHERE w.id = e.workflow_id AND e.status = 'interrupted'
+            RETURNING {}, w.deployment_id
+            "#,
+            WORKFLOW_RUN_COLUMNS
+        );
+
+        let rows = sqlx::query(&query).fetch_all(&app_state.db_pool).await?;
+
+        let version_query = format!(
+            r#"
+            SELECT {}
+            FROM ai_workflow_versions v
+            JOIN ai_workflows w ON w.id = v.workflow_id
+            WHERE v.id = $1
+            "#,
+            WORKFLOW_VERSION_COLUMNS
+        );
+
+        let mut resumed = 0;
+        for row in rows {
+            let run = workflow_run_from_row(&row)?;
+            let deployment_id: i64 = row.get("deployment_id");
+
+            let version = match run.workflow_version_id {
+                Some(version_id) => sqlx::query(&version_query)
+                    .bind(version_id)
+                    .fetch_optional(&app_state.db_pool)
+                    .await?
+                    .map(|row| workflow_version_from_row(&row))
+                    .transpose()?,
+                None => None,
+            };
+            let Some(version) = version else {
+                sqlx::query(
+                    r#"
+                    UPDATE ai_workflow_executions
+                    SET status = 'failed', error_message = 'Workflow version no longer exists',
+                        completed_at = NOW(), updated_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(run.id)
+                .execute(&app_state.db_pool)
+                .await?;
+                continue;
+            };
+
+            // Node outputs are only kept truncated, so later steps of a resumed
+            // run see the same payloads the run history shows.
+            let outputs = run
+                .execution_context
+                .node_executions
+                .iter()
+                .filter(|execution| execution.status == ExecutionStatus::Completed)
+                .filter_map(|execution| {
+                    execution
+                        .output_data
+                        .clone()
+                        .map(|output| (execution.node_id.clone(), output))
+                })
+                .collect();
+
+            WorkflowRunner {
+                deployment_id,
+                run_id: run.id,
+                trigger_data: run.trigger_data.unwrap_or(Value::Null),
+                version,
+                context: run.execution_context,
+                outputs,
+            }
+            .spawn(app_state);
+            resumed += 1;
+        }
+
+        Ok(resumed)
+    }
+}
+
+pub struct CancelWorkflowRunCommand {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+    pub run_id: i64,
+}
+
+impl CancelWorkflowRunCommand {
+    pub fn new(deployment_id: i64, workflow_id: i64, run_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            run_id,
+        }
+    }
+}
+
+impl Command for CancelWorkflowRunCommand {
+    type Output = WorkflowExecution;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // The runner checks the status before every step, so the step in flight
+        // finishes but nothing after it starts.
+        let query = format!(
+            r#"
+            UPDATE ai_workflow_executions e
+            SET status = 'cancelled', completed_at = NOW(), updated_at = NOW()
+            FROM ai_workflows w
+            WHERE w.id = e.workflow_id
+                AND e.id = $1 AND e.workflow_id = $2 AND w.deployment_id = $3
+                AND e.status IN ('pending', 'running')
+            RETURNING {}
+            "#,
+            WORKFLOW_RUN_COLUMNS
+        );
+
+        let row = sqlx::query(&query)
+            .bind(self.run_id)
+            .bind(self.workflow_id)
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?;
+
+        match row {
+            Some(row) => workflow_run_from_row(&row),
+            None => {
+                GetWorkflowRunQuery::new(self.deployment_id, self.workflow_id, self.run_id)
+                    .execute(app_state)
+                    .await?;
+
+                Err(AppError::BadRequest(
+                    "Workflow run has already finished".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+struct WorkflowRunner {
+    deployment_id: i64,
+    run_id: i64,
+    trigger_data: Value,
+    version: AiWorkflowVersion,
+    context: ExecutionContext,
+    outputs: serde_json::Map<String, Value>,
+}
+
+impl WorkflowRunner {
+    fn spawn(self, app_state: &AppState) {
+        let run_id = self.run_id;
+        let background_state = app_state.clone();
+
+        app_state.background_tasks.spawn(async move {
+            if let Err(e) = self.run(&background_state).await {
+                tracing::error!("Workflow run {} failed to execute: {}", run_id, e);
+                let _ = sqlx::query(
+                    r#"
+                    UPDATE ai_workflow_executions
+                    SET status = 'failed', error_message = $2, completed_at = NOW(), updated_at = NOW()
+                    WHERE id = $1 AND status IN ('pending', 'running')
+                    "#,
+                )
+                .bind(run_id)
+                .bind(e.to_string())
+                .execute(&background_state.db_pool)
+                .await;
+            }
+        });
+    }
+
+    async fn run(mut self, app_state: &AppState) -> Result<(), AppError> {
+        let started = sqlx::query(
+            r#"
+            UPDATE ai_workflow_executions
+            SET status = 'running', started_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND status = 'pending'
+            "#,
+        )
+        .bind(self.run_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if started.rows_affected() == 0 {
+            return Ok(());
+        }
+
+        let deadline = self
+            .version
+            .configuration
+            .timeout_seconds
+            .map(|seconds| Instant::now() + Duration::from_secs(seconds as u64));
+
+        // A resumed run carries on from the queue it saved when interrupted.
+        let mut queue: VecDeque<String> = if self.context.pending_nodes.is_empty() {
+            self.entry_nodes().into()
+        } else {
+            std::mem::take(&mut self.context.pending_nodes).into()
+        };
+        let mut visited: HashSet<String> = self
+            .context
+            .node_executions
+            .iter()
+            .map(|execution| execution.node_id.clone())
+            .collect();
+        let mut last_output = self
+            .context
+            .node_executions
+            .last()
+            .and_then(|execution| execution.output_data.clone())
+            .unwrap_or(Value::Null);
+
+        while let Some(node_id) = queue.pop_front() {
+            if app_state.background_tasks.is_shutting_down() {
+                queue.push_front(node_id);
+                return self.interrupt(app_state, queue).await;
+            }
+
+            if !visited.insert(node_id.clone()) {
+                continue;
+            }
+
+            if self.is_cancelled(app_state).await? {
+                return Ok(());
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return self
+                    .finish(
+                        app_state,
+                        ExecutionStatus::Timeout,
+                        None,
+                        Some("Workflow exceeded its timeout".to_string()),
+                    )
+                    .await;
+            }
+
+            let Some(node) = self
+                .version
+                .workflow_definition
+                .nodes
+                .iter()
+                .find(|node| node.id == node_id)
+                .cloned()
+            else {
+                continue;
+            };
+
+            if !node.data.enabled {
+                queue.extend(self.next_nodes(&node, true, None));
+                continue;
+            }
+
+            self.context.current_node = Some(node.id.clone());
+            self.persist_context(app_state).await?;
+
+            let Some(execution) = self.run_step(app_state, &node).await? else {
+                return Ok(());
+            };
+            let succeeded = execution.status == ExecutionStatus::Completed;
+            let error_message = execution.error_message.clone();
+            self.context.node_executions.push(execution);
+
+            if succeeded {
+                let output = self.outputs.get(&node.id).cloned().unwrap_or(Value::Null);
+                queue.extend(self.next_nodes(&node, true, Some(&output)));
+                last_output = output;
+            } else {
+                let error_paths = self.next_nodes(&node, false, None);
+                if error_paths.is_empty() {
+                    return self
+                        .finish(app_state, ExecutionStatus::Failed, None, error_message)
+                        .await;
+                }
+                queue.extend(error_paths);
+            }
+
+            self.persist_context(app_state).await?;
+        }
+
+        self.context.current_node = None;
+        self.finish(
+            app_state,
+            ExecutionStatus::Completed,
+            Some(last_output),
+            None,
+        )
+        .await
+    }
+
+    fn entry_nodes(&self) -> Vec<String> {
+        let definition = &self.version.workflow_definition;
+
+        let triggers: Vec<String> = definition
+            .nodes
+            .iter()
+            .filter(|node| matches!(node.node_type, WorkflowNodeType::Trigger(_)))
+            .map(|node| node.id.clone())
+            .collect();
+        if !triggers.is_empty() {
+            return triggers;
+        }
+
+        let targets: HashSet<&str> = definition
+            .edges
+            .iter()
+            .map(|edge| edge.target.as_str())
+            .collect();
+        definition
+            .nodes
+            .iter()
+            .filter(|node| !targets.contains(node.id.as_str()))
+            .map(|node| node.id.clone())
+            .collect()
+    }
+
+    fn next_nodes(
+        &self,
+        node: &WorkflowNode,
+        succeeded: bool,
+        output: Option<&Value>,
+    ) -> Vec<String> {
+        let branch = match (&node.node_type, output) {
+            (WorkflowNodeType::Condition(config), Some(output)) => {
+                let result = output
+                    .get("result")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                let path = if result {
+                    &config.true_path
+                } else {
+                    &config.false_path
+                };
+                if let Some(path) = path {
+                    return vec![path.clone()];
+                }
+                Some(if result { "true" } else { "false" })
+            }
+            _ => None,
+        };
+
+        let scope = self.scope();
+
+        self.version
+            .workflow_definition
+            .edges
+            .iter()
+            .filter(|edge| edge.source == node.id)
+            .filter(|edge| match (branch, edge.source_handle.as_deref()) {
+                (Some(branch), Some(handle)) => branch == handle,
+                _ => true,
+            })
+            .filter(|edge| Self::edge_applies(edge, succeeded, &scope))
+            .map(|edge| edge.target.clone())
+            .collect()
+    }
+
+    fn edge_applies(edge: &WorkflowEdge, succeeded: bool, scope: &Value) -> bool {
+        match &edge.condition {
+            None => succeeded,
+            Some(condition) => match condition.condition_type {
+                ConditionType::Always => true,
+                ConditionType::OnSuccess => succeeded,
+                ConditionType::OnError => !succeeded,
+                ConditionType::OnCondition => {
+                    succeeded && evaluate_expression(&condition.expression, scope).unwrap_or(false)
+                }
+            },
+        }
+    }
+
+    fn scope(&self) -> Value {
+        json!({
+            "trigger": self.trigger_data,
+            "variables": self.context.variables,
+            "nodes": self.outputs,
+        })
+    }
+
+    fn retry_policy(&self, node: &WorkflowNode) -> (u32, Duration) {
+        let policy: StepRetryPolicy = node
+            .data
+            .config
+            .get("retry")
+            .and_then(|retry| serde_json::from_value(retry.clone()).ok())
+            .unwrap_or_default();
+
+        let configuration = &self.version.configuration;
+        let max_retries = policy
+            .max_retries
+            .or(configuration.max_retries)
+            .unwrap_or(0);
+        let backoff = policy
+            .backoff_ms
+            .map(Duration::from_millis)
+            .or(configuration
+                .retry_delay_seconds
+                .map(|seconds| Duration::from_secs(seconds as u64)))
+            .unwrap_or(Duration::from_secs(1));
+
+        (max_retries, backoff)
+    }
+
+    /// Runs a node with retries. Returns `None` if the run was cancelled while
+    /// waiting to retry.
+    async fn run_step(
+        &mut self,
+        app_state: &AppState,
+        node: &WorkflowNode,
+    ) -> Result<Option<NodeExecution>, AppError> {
+        let started_at = Utc::now();
+        let scope = self.scope();
+        let input = resolve_templates(&node.data.config, &scope);
+
+        // Only actions talk to other systems; the rest are deterministic and would
+        // fail the same way again.
+        let (max_retries, backoff) = match node.node_type {
+            WorkflowNodeType::Action(_) => self.retry_policy(node),
+            _ => (0, Duration::ZERO),
+        };
+
+        let mut attempt = 0;
+        let result = loop {
+            let result = self.execute_node(app_state, node, &input, &scope).await;
+
+            if result.is_ok() || attempt >= max_retries {
+                break result;
+            }
+
+            let delay = backoff
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(MAX_RETRY_BACKOFF);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+
+            if self.is_cancelled(app_state).await? {
+                return Ok(None);
+            }
+        };
+
+        let (status, output, error_message) = match result {
+            Ok(output) => {
+                self.outputs.insert(node.id.clone(), output.clone());
+                (ExecutionStatus::Completed, Some(output), None)
+            }
+            Err(error) => (ExecutionStatus::Failed, None, Some(error)),
+        };
+
+        Ok(Some(NodeExecution {
+            node_id: node.id.clone(),
+            status,
+            started_at: Some(started_at),
+            completed_at: Some(Utc::now()),
+            input_data: Some(truncate_payload(&input)),
+            output_data: output.as_ref().map(truncate_payload),
+            error_message,
+            retry_count: attempt,
+        }))
+    }
+
+    async fn execute_node(
+        &self,
+        app_state: &AppState,
+        node: &WorkflowNode,
+        input: &Value,
+        scope: &Value,
+    ) -> Result<Value, String> {
+        match &node.node_type {
+            WorkflowNodeType::Trigger(_) => Ok(self.trigger_data.clone()),
+            WorkflowNodeType::Action(config) => self.execute_action(app_state, config, input).await,
+            WorkflowNodeType::Condition(config) => Self::execute_condition(config, scope),
+            WorkflowNodeType::Transform(config) => Self::execute_transform(config, scope),
+        }
+    }
+
+    async fn execute_action(
+        &self,
+        app_state: &AppState,
+        config: &ActionNodeConfig,
+        input: &Value,
+    ) -> Result<Value, String> {
+        if let Some(tool_id) = config.tool_id {
+            let inputs: HashMap<String, Value> = input
+                .get("inputs")
+                .and_then(Value::as_object)
+                .map(|inputs| inputs.clone().into_iter().collect())
+                .unwrap_or_default();
+
+            let invocation = ExecuteAiToolCommand::new(self.deployment_id, tool_id, inputs)
+                .with_workflow_run_id(self.run_id)
+                .execute(app_state)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            return match invocation.error {
+                Some(error) => Err(error.to_string()),
+                None => Ok(invocation.output.unwrap_or(Value::Null)),
+            };
+        }
+
+        match config.action_type {
+            ActionType::KnowledgeBaseSearch => {
+                let kb_config = config
+                    .knowledge_base_config
+                    .as_ref()
+                    .ok_or("Knowledge base search requires knowledge_base_config")?;
+                let query = input
+                    .get("query")
+                    .and_then(Value::as_str)
+                    .unwrap_or(&kb_config.query)
+                    .to_string();
+
+                GetAiKnowledgeBaseByIdQuery::new(self.deployment_id, kb_config.knowledge_base_id)
+                    .execute(app_state)
+                    .await
+                    .map_err(|_| "Knowledge base not found".to_string())?;
+
+                let mut search = SearchKnowledgeBaseQuery::new(kb_config.knowledge_base_id, query)
+                    .with_top_k(kb_config.max_results.unwrap_or(10) as u64);
+                if let Some(threshold) = kb_config.similarity_threshold {
+                    search = search.with_score_threshold(threshold);
+                }
+
+                let results = search.execute(app_state).await.map_err(|e| e.to_string())?;
+                serde_json::to_value(results.results).map_err(|e| e.to_string())
+            }
+            // Raw API calls would bypass the tool execution policy, so they have to
+            // go through a configured tool.
+            ActionType::ApiCall => Err("API call actions must reference a tool".to_string()),
+            ActionType::TriggerWorkflow => {
+                Err("Triggering workflows from a workflow run is not supported".to_string())
+            }
+            ActionType::AgentCall => {
+                let agent_config = config
+                    .agent_call_config
+                    .as_ref()
+                    .ok_or("Agent calls require agent_call_config")?;
+                let message = input
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or(&agent_config.message)
+                    .to_string();
+
+                self.execute_agent_call(app_state, agent_config, message)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Runs the call as an agent session, so budgets are checked before the
+    /// model is invoked and its usage is recorded against the agent.
+    async fn execute_agent_call(
+        &self,
+        app_state: &AppState,
+        config: &AgentCallActionConfig,
+        message: String,
+    ) -> Result<Value, AppError> {
+        let agent = GetAiAgentByIdQuery::new(self.deployment_id, config.agent_id)
+            .execute(app_state)
+            .await?;
+        let model = config
+            .model
+            .as_deref()
+            .or_else(|| agent.configuration.get("model").and_then(Value::as_str));
+        let system_prompt = config.system_prompt.as_deref().or_else(|| {
+            agent
+                .configuration
+                .get("system_prompt")
+                .and_then(Value::as_str)
+        });
+
+        let session = CreateAgentSessionCommand::new(self.deployment_id, config.agent_id)
+            .with_metadata(json!({ "workflow_run_id": self.run_id.to_string() }))
+            .execute(app_state)
+            .await?;
+
+        let result = async {
+            let mut messages = Vec::new();
+            if let Some(system_prompt) = system_prompt {
+                AppendAgentSessionMessageCommand::new(
+                    self.deployment_id,
+                    config.agent_id,
+                    session.id,
+                    "system".to_string(),
+                    system_prompt.to_string(),
+                )
+                .execute(app_state)
+                .await?;
+                messages.push(ChatMessage::new("system", system_prompt));
+            }
+
+            AppendAgentSessionMessageCommand::new(
+                self.deployment_id,
+                config.agent_id,
+                session.id,
+                "user".to_string(),
+                message.clone(),
+            )
+            .execute(app_state)
+            .await?;
+            messages.push(ChatMessage::new("user", message));
+
+            let completion = app_state
+                .agent_model_service
+                .complete(model, &messages)
+                .await?;
+
+            AppendAgentSessionMessageCommand::new(
+                self.deployment_id,
+                config.agent_id,
+                session.id,
+                "assistant".to_string(),
+                completion.content.clone(),
+            )
+            .with_token_counts(completion.prompt_tokens, completion.completion_tokens)
+            .execute(app_state)
+            .await?;
+
+            Ok::<_, AppError>(json!({
+                "session_id": session.id.to_string(),
+                "model": completion.model,
+                "content": completion.content,
+                "prompt_tokens": completion.prompt_tokens,
+                "completion_tokens": completion.completion_tokens,
+            }))
+        }
+        .await;
+
+        if let Err(e) =
+            CloseAgentSessionCommand::new(self.deployment_id, config.agent_id, session.id)
+                .execute(app_state)
+                .await
+        {
+            tracing::warn!(
+                session_id = session.id,
+                "Failed to close agent session: {}",
+                e
+            );
+        }
+
+        result
+    }
+
+    fn execute_condition(config: &ConditionNodeConfig, scope: &Value) -> Result<Value, String> {
+        match config.condition_type {
+            ConditionEvaluationType::Simple | ConditionEvaluationType::JsonPath => {
+                let result = evaluate_expression(&config.expression, scope)?;
+                Ok(json!({ "result": result }))
+            }
+            ConditionEvaluationType::JavaScript => {
+                Err("JavaScript conditions are not supported".to_string())
+            }
+        }
+    }
+
+    fn execute_transform(config: &TransformNodeConfig, scope: &Value) -> Result<Value, String> {
+        match config.transform_type {
+            TransformType::DataMapping => Ok(Value::Object(
+                config
+                    .output_mapping
+                    .iter()
+                    .map(|(key, path)| {
+                        (
+                            key.clone(),
+                            lookup_path(scope, path).cloned().unwrap_or(Value::Null),
+                        )
+                    })
+                    .collect(),
+            )),
+            _ => Err("Only data mapping transforms are supported".to_string()),
+        }
+    }
+
+    async fn is_cancelled(&self, app_state: &AppState) -> Result<bool, AppError> {
+        let status: Option<String> =
+            sqlx::query_scalar("SELECT status FROM ai_workflow_executions WHERE id = $1")
+                .bind(self.run_id)
+                .fetch_optional(&app_state.db_pool)
+                .await?;
+
+        Ok(status.is_none_or(|status| status == "cancelled"))
+    }
+
+    /// Saves the remaining queue so the run can carry on after a restart.
+    async fn interrupt(
+        &mut self,
+        app_state: &AppState,
+        queue: VecDeque<String>,
+    ) -> Result<(), AppError> {
+        self.context.current_node = None;
+        self.context.pending_nodes = queue.into();
+        let context_json = serde_json::to_value(&self.context)
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE ai_workflow_executions
+            SET status = 'interrupted', execution_context = $2, updated_at = NOW()
+            WHERE id = $1 AND status = 'running'
+            "#,
+        )
+        .bind(self.run_id)
+        .bind(context_json)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        tracing::info!("Workflow run {} interrupted by shutdown", self.run_id);
+        Ok(())
+    }
+
+    async fn persist_context(&self, app_state: &AppState) -> Result<(), AppError> {
+        let context_json = serde_json::to_value(&self.context)
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        sqlx::query(
+            "UPDATE ai_workflow_executions SET execution_context = $2, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(self.run_id)
+        .bind(context_json)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn finish(
+        &self,
+        app_state: &AppState,
+        status: ExecutionStatus,
+        output: Option<Value>,
+        error_message: Option<String>,
+    ) -> Result<(), AppError> {
+        let context_json = serde_json::to_value(&self.context)
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE ai_workflow_executions
+            SET status = $2, output_data = $3, error_message = $4, execution_context = $5,
+                completed_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND status = 'running'
+            "#,
+        )
+        .bind(self.run_id)
+        .bind(String::from(status))
+        .bind(output.as_ref().map(truncate_payload))
+        .bind(error_message)
+        .bind(context_json)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn truncate_payload(value: &Value) -> Value {
+    let serialized = value.to_string();
+    if serialized.len() <= MAX_STEP_PAYLOAD_CHARS {
+        return value.clone();
+    }
+
+    json!({
+        "truncated": true,
+        "preview": serialized.chars().take(MAX_STEP_PAYLOAD_CHARS).collect::<String>(),
+    })
+}
+
+/// Looks up a dotted path such as `nodes.search.0.content` or `$.trigger.user_id`.
+fn lookup_path<'a>(scope: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim();
+    let path = path.strip_prefix("$.").unwrap_or(path);
+
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(scope, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+/// Replaces any string of the form `{{ path }}` with the value at that path.
+fn resolve_templates(value: &Value, scope: &Value) -> Value {
+    match value {
+        Value::String(text) => text
+            .trim()
+            .strip_prefix("{{")
+            .and_then(|rest| rest.strip_suffix("}}"))
+            .map(|path| lookup_path(scope, path).cloned().unwrap_or(Value::Null))
+            .unwrap_or_else(|| value.clone()),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| resolve_templates(item, scope))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, item)| (key.clone(), resolve_templates(item, scope)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+/// Evaluates `<path> <op> <literal>` or a bare `<path>` for truthiness.
+fn evaluate_expression(expression: &str, scope: &Value) -> Result<bool, String> {
+    const OPERATORS: [&str; 6] = [">=", "<=", "==", "!=", ">", "<"];
+
+    let Some((operator, index)) = OPERATORS
+        .iter()
+        .filter_map(|operator| expression.find(operator).map(|index| (*operator, index)))
+        .min_by_key(|(_, index)| *index)
+    else {
+        return Ok(lookup_path(scope, expression).is_some_and(is_truthy));
+    };
+
+    let left = lookup_path(scope, &expression[..index])
+        .cloned()
+        .unwrap_or(Value::Null);
+    let right_text = expression[index + operator.len()..].trim();
+    let right: Value =
+        serde_json::from_str(right_text).unwrap_or_else(|_| Value::String(right_text.to_string()));
+
+    let ordering = match (left.as_f64(), right.as_f64()) {
+        (Some(left), Some(right)) => left.partial_cmp(&right),
+        _ => match (left.as_str(), right.as_str()) {
+            (Some(left), Some(right)) => Some(left.cmp(right)),
+            _ => None,
+        },
+    };
+
+    Ok(match operator {
+        "==" => left == right || ordering == Some(std::cmp::Ordering::Equal),
+        "!=" => left != right && ordering != Some(std::cmp::Ordering::Equal),
+        ">" => ordering == Some(std::cmp::Ordering::Greater),
+        "<" => ordering == Some(std::cmp::Ordering::Less),
+        ">=" => matches!(
+            ordering,
+            Some(std::cmp::Ordering::Greater | std::cmp::Ordering::Equal)
+        ),
+        "<=" => matches!(
+            ordering,
+            Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal)
+        ),
+        _ => return Err(format!("Unsupported operator '{}'", operator)),
+    })
+}
@@ -0,0 +1,452 @@
+//! Triggers start workflow runs on a cron schedule or when their webhook URL
+//! is called.
+//!
+//! Schedules are read in the trigger's own time zone. Postgres does the time
+//! zone arithmetic, since it already carries the IANA database: the cron
+//! expression is matched against local wall-clock time, and only the
+//! matching minute is converted back to UTC.
+
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{
+        AiWorkflowTrigger, CreatedAiWorkflowTrigger, EndpointClass, WorkflowExecution,
+        WorkflowTriggerType,
+    },
+    queries::{GetAiWorkflowByIdQuery, Query, WORKFLOW_TRIGGER_COLUMNS, workflow_trigger_from_row},
+    state::AppState,
+    utils::cron::CronSchedule,
+};
+
+use super::{CheckRateLimitCommand, Command, ExecuteAiWorkflowCommand};
+
+/// How many due schedules one scheduler tick fires.
+const FIRE_BATCH_SIZE: i64 = 100;
+
+const DEFAULT_SCHEDULER_INTERVAL: Duration = Duration::from_secs(30);
+
+fn hash_trigger_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// `{CONSOLE_PUBLIC_URL}/ai/triggers/{token}`, or just the path when the
+/// console's public URL isn't configured.
+fn webhook_url(token: &str) -> String {
+    let base_url = std::env::var("CONSOLE_PUBLIC_URL").unwrap_or_default();
+    format!("{}/ai/triggers/{}", base_url.trim_end_matches('/'), token)
+}
+
+async fn is_known_timezone(
+    connection: &mut PgConnection,
+    timezone: &str,
+) -> Result<bool, AppError> {
+    Ok(
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+            .bind(timezone)
+            .fetch_one(connection)
+            .await?,
+    )
+}
+
+/// The first time after `after` the schedule matches, or `None` if it never
+/// will again.
+async fn next_fire_at(
+    connection: &mut PgConnection,
+    schedule: &CronSchedule,
+    timezone: &str,
+    after: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let mut local: NaiveDateTime = sqlx::query_scalar("SELECT ($1::timestamptz AT TIME ZONE $2)")
+        .bind(after)
+        .bind(timezone)
+        .fetch_one(&mut *connection)
+        .await?;
+
+    // When clocks go back, a local time comes round twice. Skipping past
+    // the occurrence that has already been is enough to not fire twice.
+    for _ in 0..3 {
+        let Some(next_local) = schedule.next_after(local) else {
+            return Ok(None);
+        };
+        let next: DateTime<Utc> = sqlx::query_scalar("SELECT ($1::timestamp AT TIME ZONE $2)")
+            .bind(next_local)
+            .bind(timezone)
+            .fetch_one(&mut *connection)
+            .await?;
+        if next > after {
+            return Ok(Some(next));
+        }
+        local = next_local;
+    }
+
+    Ok(None)
+}
+
+pub struct CreateWorkflowTriggerCommand {
+    deployment_id: i64,
+    workflow_id: i64,
+    trigger_type: WorkflowTriggerType,
+    cron_expression: Option<String>,
+    timezone: Option<String>,
+}
+
+impl CreateWorkflowTriggerCommand {
+    pub fn schedule(
+        deployment_id: i64,
+        workflow_id: i64,
+        cron_expression: String,
+        timezone: String,
+    ) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            trigger_type: WorkflowTriggerType::Schedule,
+            cron_expression: Some(cron_expression),
+            timezone: Some(timezone),
+        }
+    }
+
+    pub fn webhook(deployment_id: i64, workflow_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            trigger_type: WorkflowTriggerType::Webhook,
+            cron_expression: None,
+            timezone: None,
+        }
+    }
+}
+
+impl Command for CreateWorkflowTriggerCommand {
+    type Output = CreatedAiWorkflowTrigger;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        GetAiWorkflowByIdQuery::new(self.deployment_id, self.workflow_id)
+            .execute(app_state)
+            .await?;
+
+        let mut connection = app_state.db_pool.acquire().await?;
+
+        let mut next = None;
+        if let (Some(cron_expression), Some(timezone)) = (&self.cron_expression, &self.timezone) {
+            let schedule = CronSchedule::parse(cron_expression).map_err(AppError::Validation)?;
+            if !is_known_timezone(&mut connection, timezone).await? {
+                return Err(AppError::Validation(format!(
+                    "'{}' is not a known time zone",
+                    timezone
+                )));
+            }
+
+            next = next_fire_at(&mut connection, &schedule, timezone, Utc::now()).await?;
+            if next.is_none() {
+                return Err(AppError::Validation(format!(
+                    "'{}' never matches a date",
+                    cron_expression
+                )));
+            }
+        }
+
+        let token = (self.trigger_type == WorkflowTriggerType::Webhook)
+            .then(|| format!("wft_{}", hex::encode(rand::random::<[u8; 32]>())));
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO ai_workflow_triggers AS t (
+                id, deployment_id, workflow_id, trigger_type, cron_expression, timezone,
+                token_hash, next_fire_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING {}
+            "#,
+            WORKFLOW_TRIGGER_COLUMNS
+        ))
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(self.workflow_id)
+        .bind(self.trigger_type.as_str())
+        .bind(&self.cron_expression)
+        .bind(&self.timezone)
+        .bind(token.as_deref().map(hash_trigger_token))
+        .bind(next)
+        .fetch_one(&mut *connection)
+        .await?;
+
+        Ok(CreatedAiWorkflowTrigger {
+            trigger: workflow_trigger_from_row(&row),
+            webhook_url: token.as_deref().map(webhook_url),
+        })
+    }
+}
+
+pub struct DeleteWorkflowTriggerCommand {
+    deployment_id: i64,
+    workflow_id: i64,
+    trigger_id: i64,
+}
+
+impl DeleteWorkflowTriggerCommand {
+    pub fn new(deployment_id: i64, workflow_id: i64, trigger_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            trigger_id,
+        }
+    }
+}
+
+impl Command for DeleteWorkflowTriggerCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let result = sqlx::query(
+            "DELETE FROM ai_workflow_triggers WHERE id = $1 AND workflow_id = $2 AND deployment_id = $3",
+        )
+        .bind(self.trigger_id)
+        .bind(self.workflow_id)
+        .bind(self.deployment_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Trigger not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Starts a run for a call to a webhook trigger's URL. The request body, a
+/// JSON object, is the run's trigger data, so its fields fill the
+/// workflow's variables of the same name. Calls count against the
+/// deployment's rate limit for expensive endpoints.
+pub struct FireWebhookTriggerCommand {
+    token: String,
+    payload: Value,
+}
+
+impl FireWebhookTriggerCommand {
+    pub fn new(token: String, payload: Value) -> Self {
+        Self { token, payload }
+    }
+}
+
+impl Command for FireWebhookTriggerCommand {
+    type Output = WorkflowExecution;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, deployment_id, workflow_id FROM ai_workflow_triggers
+            WHERE token_hash = $1 AND trigger_type = 'webhook'
+            "#,
+        )
+        .bind(hash_trigger_token(&self.token))
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Trigger not found".to_string()))?;
+        let trigger_id: i64 = row.get("id");
+        let deployment_id: i64 = row.get("deployment_id");
+        let workflow_id: i64 = row.get("workflow_id");
+
+        match CheckRateLimitCommand::new(deployment_id, EndpointClass::Expensive)
+            .execute(app_state)
+            .await
+        {
+            Ok(decision) if !decision.allowed => {
+                return Err(
+                    AppError::coded(ErrorCode::RateLimited, "Rate limit exceeded")
+                        .with_details(json!({ "retry_after_seconds": decision.retry_after_secs })),
+                );
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!(
+                    deployment_id,
+                    "Rate limit check failed, allowing webhook trigger: {}",
+                    e
+                );
+            }
+        }
+
+        let result = ExecuteAiWorkflowCommand::new(deployment_id, workflow_id)
+            .with_trigger_data(self.payload)
+            .execute(app_state)
+            .await;
+        let (run_id, error) = match &result {
+            Ok(run) => (Some(run.id), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        sqlx::query(
+            r#"
+            UPDATE ai_workflow_triggers
+            SET last_fired_at = NOW(), updated_at = NOW(),
+                last_run_id = COALESCE($2, last_run_id), last_error = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(trigger_id)
+        .bind(run_id)
+        .bind(error)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        result
+    }
+}
+
+/// Starts a run for every schedule that has come due, and works out when
+/// each fires next. A schedule that was due several times while nothing
+/// was checking fires once, not once per missed time.
+pub struct FireDueWorkflowTriggersCommand;
+
+impl FireDueWorkflowTriggersCommand {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for FireDueWorkflowTriggersCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for FireDueWorkflowTriggersCommand {
+    type Output = u64;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        // Held until next_fire_at moves on, so two instances never fire the
+        // same schedule.
+        let due = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM ai_workflow_triggers t
+            WHERE t.trigger_type = 'schedule' AND t.next_fire_at <= NOW()
+            ORDER BY t.next_fire_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            WORKFLOW_TRIGGER_COLUMNS
+        ))
+        .bind(FIRE_BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Each schedule is moved on and committed before its run starts, so
+        // a failure later in the batch can't make it fire again.
+        let mut claimed = Vec::with_capacity(due.len());
+        for row in &due {
+            let trigger: AiWorkflowTrigger = workflow_trigger_from_row(row);
+            let (Some(cron_expression), Some(timezone)) =
+                (&trigger.cron_expression, &trigger.timezone)
+            else {
+                continue;
+            };
+
+            let now = Utc::now();
+            let next = match CronSchedule::parse(cron_expression) {
+                Ok(schedule) => next_fire_at(&mut tx, &schedule, timezone, now).await?,
+                Err(_) => None,
+            };
+
+            sqlx::query(
+                r#"
+                UPDATE ai_workflow_triggers
+                SET last_fired_at = $2, updated_at = $2, next_fire_at = $3
+                WHERE id = $1
+                "#,
+            )
+            .bind(trigger.id)
+            .bind(now)
+            .bind(next)
+            .execute(&mut *tx)
+            .await?;
+
+            claimed.push(trigger);
+        }
+
+        tx.commit().await?;
+
+        for trigger in &claimed {
+            let result = ExecuteAiWorkflowCommand::new(trigger.deployment_id, trigger.workflow_id)
+                .with_trigger_data(json!({
+                    "trigger": "schedule",
+                    "trigger_id": trigger.id.to_string(),
+                    "scheduled_for": trigger.next_fire_at,
+                }))
+                .execute(app_state)
+                .await;
+            if let Err(e) = &result {
+                tracing::warn!(
+                    trigger_id = trigger.id,
+                    workflow_id = trigger.workflow_id,
+                    "Scheduled workflow run failed to start: {}",
+                    e
+                );
+            }
+
+            let (run_id, error) = match &result {
+                Ok(run) => (Some(run.id), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            if let Err(e) = sqlx::query(
+                r#"
+                UPDATE ai_workflow_triggers
+                SET last_run_id = COALESCE($2, last_run_id), last_error = $3
+                WHERE id = $1
+                "#,
+            )
+            .bind(trigger.id)
+            .bind(run_id)
+            .bind(error)
+            .execute(&app_state.db_pool)
+            .await
+            {
+                tracing::warn!(
+                    trigger_id = trigger.id,
+                    "Failed to record a scheduled workflow run: {}",
+                    e
+                );
+            }
+        }
+
+        Ok(claimed.len() as u64)
+    }
+}
+
+/// Fires due workflow schedules every `WORKFLOW_SCHEDULER_INTERVAL_SECS`
+/// (default thirty seconds) until shutdown.
+pub fn spawn_workflow_trigger_scheduler(app_state: &AppState) {
+    let interval = std::env::var("WORKFLOW_SCHEDULER_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SCHEDULER_INTERVAL);
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = background_state.background_tasks.shutdown_requested() => break,
+            }
+
+            if let Err(e) = FireDueWorkflowTriggersCommand::new()
+                .execute(&background_state)
+                .await
+            {
+                tracing::error!("Failed to fire due workflow schedules: {}", e);
+            }
+        }
+    });
+}
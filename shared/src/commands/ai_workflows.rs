@@ -1,7 +1,8 @@
 use crate::{
     commands::Command,
     error::AppError,
-    models::{AiWorkflow, WorkflowConfiguration, WorkflowDefinition},
+    models::{AiWorkflow, AiWorkflowVersion, WorkflowConfiguration, WorkflowDefinition},
+    queries::{WORKFLOW_VERSION_COLUMNS, workflow_version_from_row},
     state::AppState,
 };
 use chrono::Utc;
@@ -36,7 +37,7 @@ impl CreateAiWorkflowCommand {
 impl Command for CreateAiWorkflowCommand {
     type Output = AiWorkflow;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let workflow_id = app_state.sf.next_id()? as i64;
         let now = Utc::now();
 
@@ -78,6 +79,7 @@ impl Command for CreateAiWorkflowCommand {
             deployment_id: workflow.deployment_id,
             configuration,
             workflow_definition,
+            published_version_id: None,
         })
     }
 }
@@ -127,7 +129,9 @@ impl UpdateAiWorkflowCommand {
 impl Command for UpdateAiWorkflowCommand {
     type Output = AiWorkflow;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    /// Only the draft changes here; runs keep using the published version until
+    /// the draft is published.
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let now = Utc::now();
 
         // Build dynamic query based on provided fields
@@ -155,8 +159,8 @@ impl Command for UpdateAiWorkflowCommand {
             r#"
             UPDATE ai_workflows
             SET {}
-            WHERE id = ${} AND deployment_id = ${}
-            RETURNING id, created_at, updated_at, name, description, deployment_id, configuration, workflow_definition
+            WHERE id = ${} AND deployment_id = ${} AND deleted_at IS NULL
+            RETURNING id, created_at, updated_at, name, description, deployment_id, configuration, workflow_definition, published_version_id
             "#,
             query_parts.join(", "),
             param_count,
@@ -206,6 +210,7 @@ impl Command for UpdateAiWorkflowCommand {
             deployment_id: workflow.get("deployment_id"),
             configuration,
             workflow_definition,
+            published_version_id: workflow.get("published_version_id"),
         })
     }
 }
@@ -227,42 +232,213 @@ impl DeleteAiWorkflowCommand {
 impl Command for DeleteAiWorkflowCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut tx = app_state
             .db_pool
             .begin()
             .await
             .map_err(|e| AppError::Database(e))?;
 
-        // Delete all workflow relationships and executions first
-        sqlx::query!(
-            "DELETE FROM ai_agent_workflows WHERE workflow_id = $1",
-            self.workflow_id
+        // Versions and run history are kept so past runs stay explainable.
+        let deleted = sqlx::query(
+            r#"
+            UPDATE ai_workflows
+            SET deleted_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND deployment_id = $2 AND deleted_at IS NULL
+            "#,
         )
+        .bind(self.workflow_id)
+        .bind(self.deployment_id)
         .execute(&mut *tx)
-        .await
-        .map_err(|e| AppError::Database(e))?;
+        .await?;
 
-        sqlx::query!(
-            "DELETE FROM ai_workflow_executions WHERE workflow_id = $1",
-            self.workflow_id
-        )
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| AppError::Database(e))?;
+        if deleted.rows_affected() == 0 {
+            return Err(AppError::NotFound("Workflow not found".to_string()));
+        }
+
+        sqlx::query("DELETE FROM ai_agent_workflows WHERE workflow_id = $1")
+            .bind(self.workflow_id)
+            .execute(&mut *tx)
+            .await?;
 
-        // Delete the workflow
-        sqlx::query!(
-            "DELETE FROM ai_workflows WHERE id = $1 AND deployment_id = $2",
-            self.workflow_id,
-            self.deployment_id
+        sqlx::query(
+            "UPDATE ai_workflow_versions SET deleted_at = NOW() WHERE workflow_id = $1 AND deleted_at IS NULL",
         )
+        .bind(self.workflow_id)
         .execute(&mut *tx)
-        .await
-        .map_err(|e| AppError::Database(e))?;
+        .await?;
 
         tx.commit().await.map_err(|e| AppError::Database(e))?;
 
         Ok(())
     }
 }
+
+pub struct PublishAiWorkflowCommand {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+    pub published_by: Option<String>,
+    pub change_note: Option<String>,
+}
+
+impl PublishAiWorkflowCommand {
+    pub fn new(deployment_id: i64, workflow_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            published_by: None,
+            change_note: None,
+        }
+    }
+
+    pub fn with_published_by(mut self, published_by: Option<String>) -> Self {
+        self.published_by = published_by;
+        self
+    }
+
+    pub fn with_change_note(mut self, change_note: Option<String>) -> Self {
+        self.change_note = change_note;
+        self
+    }
+}
+
+impl Command for PublishAiWorkflowCommand {
+    type Output = AiWorkflowVersion;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        // Locking the workflow serializes concurrent publishes so version numbers
+        // can't collide.
+        let draft = sqlx::query(
+            r#"
+            SELECT configuration, workflow_definition
+            FROM ai_workflows
+            WHERE id = $1 AND deployment_id = $2 AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.workflow_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Workflow not found".to_string()))?;
+
+        let workflow_definition: WorkflowDefinition =
+            serde_json::from_value(draft.get("workflow_definition"))
+                .map_err(|e| AppError::Serialization(e.to_string()))?;
+        if workflow_definition.nodes.is_empty() {
+            return Err(AppError::Validation(
+                "Cannot publish a workflow without nodes".to_string(),
+            ));
+        }
+
+        let version_id = app_state.sf.next_id()? as i64;
+
+        sqlx::query(
+            r#"
+            INSERT INTO ai_workflow_versions (
+                id, created_at, workflow_id, version_number, configuration,
+                workflow_definition, published_by, change_note
+            )
+            SELECT $1, NOW(), $2, COALESCE(MAX(version_number), 0) + 1, $3, $4, $5, $6
+            FROM ai_workflow_versions
+            WHERE workflow_id = $2
+            "#,
+        )
+        .bind(version_id)
+        .bind(self.workflow_id)
+        .bind(draft.get::<serde_json::Value, _>("configuration"))
+        .bind(draft.get::<serde_json::Value, _>("workflow_definition"))
+        .bind(&self.published_by)
+        .bind(&self.change_note)
+        .execute(&mut *tx)
+        .await?;
+
+        let version = publish_version(&mut tx, self.workflow_id, version_id).await?;
+
+        tx.commit().await?;
+
+        Ok(version)
+    }
+}
+
+pub struct RollbackAiWorkflowCommand {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+    pub version_id: i64,
+}
+
+impl RollbackAiWorkflowCommand {
+    pub fn new(deployment_id: i64, workflow_id: i64, version_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            version_id,
+        }
+    }
+}
+
+impl Command for RollbackAiWorkflowCommand {
+    type Output = AiWorkflowVersion;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let exists = sqlx::query(
+            r#"
+            SELECT 1
+            FROM ai_workflow_versions v
+            JOIN ai_workflows w ON w.id = v.workflow_id
+            WHERE v.id = $1 AND v.workflow_id = $2 AND w.deployment_id = $3
+                AND v.deleted_at IS NULL AND w.deleted_at IS NULL
+            "#,
+        )
+        .bind(self.version_id)
+        .bind(self.workflow_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if exists.is_none() {
+            return Err(AppError::NotFound("Workflow version not found".to_string()));
+        }
+
+        let version = publish_version(&mut tx, self.workflow_id, self.version_id).await?;
+
+        tx.commit().await?;
+
+        Ok(version)
+    }
+}
+
+async fn publish_version(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    workflow_id: i64,
+    version_id: i64,
+) -> Result<AiWorkflowVersion, AppError> {
+    sqlx::query(
+        "UPDATE ai_workflows SET published_version_id = $2, updated_at = NOW() WHERE id = $1",
+    )
+    .bind(workflow_id)
+    .bind(version_id)
+    .execute(&mut **tx)
+    .await?;
+
+    let query = format!(
+        r#"
+        SELECT {}
+        FROM ai_workflow_versions v
+        JOIN ai_workflows w ON w.id = v.workflow_id
+        WHERE v.id = $1
+        "#,
+        WORKFLOW_VERSION_COLUMNS
+    );
+
+    let row = sqlx::query(&query)
+        .bind(version_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    workflow_version_from_row(&row)
+}
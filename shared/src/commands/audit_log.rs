@@ -0,0 +1,101 @@
+use chrono::Utc;
+use sqlx::PgConnection;
+
+use crate::{error::AppError, models::AuditLog, state::AppState};
+
+use super::Command;
+
+pub struct RecordAuditLogCommand {
+    pub project_id: i64,
+    pub deployment_id: Option<i64>,
+    pub actor: Option<String>,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: i64,
+    pub metadata: serde_json::Value,
+}
+
+impl RecordAuditLogCommand {
+    pub fn new(
+        project_id: i64,
+        action: impl Into<String>,
+        resource_type: impl Into<String>,
+        resource_id: i64,
+    ) -> Self {
+        Self {
+            project_id,
+            deployment_id: None,
+            actor: None,
+            action: action.into(),
+            resource_type: resource_type.into(),
+            resource_id,
+            metadata: serde_json::json!({}),
+        }
+    }
+
+    pub fn with_deployment_id(mut self, deployment_id: i64) -> Self {
+        self.deployment_id = Some(deployment_id);
+        self
+    }
+
+    pub fn with_actor(mut self, actor: Option<String>) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Writes the entry on an existing connection so it commits or rolls back
+    /// together with the change it describes.
+    pub(crate) async fn execute_with(
+        self,
+        id: i64,
+        conn: &mut PgConnection,
+    ) -> Result<AuditLog, AppError> {
+        let created_at = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO console_audit_logs
+                (id, created_at, project_id, deployment_id, actor, action, resource_type, resource_id, metadata)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(id)
+        .bind(created_at)
+        .bind(self.project_id)
+        .bind(self.deployment_id)
+        .bind(&self.actor)
+        .bind(&self.action)
+        .bind(&self.resource_type)
+        .bind(self.resource_id)
+        .bind(&self.metadata)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(AuditLog {
+            id,
+            created_at,
+            project_id: self.project_id,
+            deployment_id: self.deployment_id,
+            actor: self.actor,
+            action: self.action,
+            resource_type: self.resource_type,
+            resource_id: self.resource_id,
+            metadata: self.metadata,
+        })
+    }
+}
+
+impl Command for RecordAuditLogCommand {
+    type Output = AuditLog;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let id = app_state.sf.next_id()? as i64;
+        let mut conn = app_state.db_pool.acquire().await?;
+        self.execute_with(id, &mut conn).await
+    }
+}
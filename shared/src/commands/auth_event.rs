@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{error::AppError, models::AuthEventType, services::AuthEventRow, state::AppState};
+
+use super::{Command, RecordUsageEventCommand};
+
+const MAX_EVENT_ATTRIBUTES: usize = 32;
+const MAX_ATTRIBUTE_KEY_LENGTH: usize = 64;
+const MAX_ATTRIBUTE_VALUE_LENGTH: usize = 1024;
+
+pub struct RecordAuthEventCommand {
+    deployment_id: i64,
+    event_type: AuthEventType,
+    user_id: Option<i64>,
+    attributes: HashMap<String, String>,
+}
+
+impl RecordAuthEventCommand {
+    pub fn new(deployment_id: i64, event_type: AuthEventType) -> Self {
+        Self {
+            deployment_id,
+            event_type,
+            user_id: None,
+            attributes: HashMap::new(),
+        }
+    }
+
+    pub fn with_user_id(mut self, user_id: i64) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn with_attributes(mut self, attributes: HashMap<String, String>) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    fn validate_attributes(&self) -> Result<(), AppError> {
+        if self.attributes.len() > MAX_EVENT_ATTRIBUTES {
+            return Err(AppError::Validation(format!(
+                "Events can have at most {} attributes",
+                MAX_EVENT_ATTRIBUTES
+            )));
+        }
+
+        for (key, value) in &self.attributes {
+            if key.is_empty() || key.len() > MAX_ATTRIBUTE_KEY_LENGTH {
+                return Err(AppError::Validation(format!(
+                    "Attribute keys must be between 1 and {} characters",
+                    MAX_ATTRIBUTE_KEY_LENGTH
+                )));
+            }
+            if value.len() > MAX_ATTRIBUTE_VALUE_LENGTH {
+                return Err(AppError::Validation(format!(
+                    "Attribute '{}' exceeds {} characters",
+                    key, MAX_ATTRIBUTE_VALUE_LENGTH
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Command for RecordAuthEventCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        self.validate_attributes()?;
+
+        let mut attributes = self.attributes;
+        let active_user = self
+            .user_id
+            .filter(|_| matches!(self.event_type, AuthEventType::SignIn));
+
+        // These keys have dedicated columns that the analytics queries read from.
+        let user_name = attributes.remove("user_name");
+        let user_email = attributes.remove("user_email");
+        let auth_method = attributes.remove("auth_method");
+        let ip_address = attributes.remove("ip_address");
+
+        let event = AuthEventRow {
+            deployment_id: self.deployment_id,
+            user_id: self.user_id,
+            event_type: self.event_type.as_event_type().to_string(),
+            user_name,
+            user_email,
+            auth_method,
+            timestamp: Utc::now().timestamp_millis(),
+            ip_address,
+            attributes: attributes.into_iter().collect(),
+        };
+
+        app_state.auth_event_buffer.push(event);
+
+        // A usage hiccup must not fail the sign-in being recorded.
+        if let Some(user_id) = active_user
+            && let Err(e) = RecordUsageEventCommand::active_user(self.deployment_id, user_id)
+                .execute(app_state)
+                .await
+        {
+            tracing::warn!(
+                "Failed to record active user {} for deployment {}: {}",
+                user_id,
+                self.deployment_id,
+                e
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,501 @@
+//! Jobs run later, outside the request that asked for them, and retried
+//! until they succeed.
+//!
+//! A job is a row in `background_jobs` holding its type and a JSON payload.
+//! Each type has a [`Job`] registered for it in [`JobHandlers`], and the
+//! worker started by [`spawn_job_worker`] claims due jobs, runs them and
+//! records the outcome. Failures are retried with exponential backoff until
+//! the job's attempts run out, after which it is left as dead until an
+//! operator retries it. Since a job may run again after a crash, handlers
+//! have to be safe to run twice.
+
+use std::{collections::HashMap, future::Future, pin::Pin, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, de::DeserializeOwned};
+use sqlx::PgConnection;
+
+use crate::{
+    commands::{
+        Command, DeleteExternalResourceJob, NormalizeEmailAddressesJob, PurgeDeletedUserJob,
+    },
+    error::AppError,
+    models::{BackgroundJob, BackgroundJobStatus},
+    queries::{BACKGROUND_JOB_COLUMNS, background_job_from_row},
+    state::AppState,
+};
+
+/// Attempts a job gets unless it was enqueued with its own limit.
+pub const DEFAULT_JOB_MAX_ATTEMPTS: i32 = 8;
+const DISPATCH_BATCH_SIZE: i64 = 20;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a claimed job stays invisible to other workers. A process that
+/// dies mid-job leaves it to be picked up again once this runs out.
+const CLAIM_LEASE: Duration = Duration::from_secs(5 * 60);
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Succeeded jobs are kept this long for inspection, then removed.
+const SUCCEEDED_JOB_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    BASE_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(exponent))
+        .min(MAX_RETRY_DELAY)
+}
+
+pub type JobFuture<'a> = Pin<Box<dyn Future<Output = Result<(), AppError>> + Send + 'a>>;
+
+/// One type of job, stored as its JSON payload.
+pub trait Job: Serialize + DeserializeOwned + Send + 'static {
+    /// Stored with each job to find its handler, so it must not change
+    /// while jobs of the type may still be queued.
+    const JOB_TYPE: &'static str;
+
+    fn run(self, app_state: &AppState) -> impl Future<Output = Result<(), AppError>> + Send;
+}
+
+type RunJob = for<'a> fn(&'a AppState, serde_json::Value) -> JobFuture<'a>;
+
+fn run_job<J: Job>(app_state: &AppState, payload: serde_json::Value) -> JobFuture<'_> {
+    Box::pin(async move {
+        let job: J =
+            serde_json::from_value(payload).map_err(|e| AppError::Serialization(e.to_string()))?;
+        job.run(app_state).await
+    })
+}
+
+/// The job types a worker knows how to run.
+#[derive(Clone, Default)]
+pub struct JobHandlers {
+    handlers: HashMap<&'static str, RunJob>,
+}
+
+impl JobHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every job type defined in this crate.
+    pub fn builtin() -> Self {
+        Self::new()
+            .register::<DeleteExternalResourceJob>()
+            .register::<NormalizeEmailAddressesJob>()
+            .register::<PurgeDeletedUserJob>()
+    }
+
+    pub fn register<J: Job>(mut self) -> Self {
+        self.handlers.insert(J::JOB_TYPE, run_job::<J>);
+        self
+    }
+
+    pub fn job_types(&self) -> Vec<&'static str> {
+        let mut job_types: Vec<_> = self.handlers.keys().copied().collect();
+        job_types.sort_unstable();
+        job_types
+    }
+
+    async fn run(
+        &self,
+        app_state: &AppState,
+        job_type: &str,
+        payload: serde_json::Value,
+    ) -> Result<(), AppError> {
+        let run = self.handlers.get(job_type).ok_or_else(|| {
+            AppError::Internal(format!(
+                "No handler is registered for job type {}",
+                job_type
+            ))
+        })?;
+
+        run(app_state, payload).await
+    }
+}
+
+/// Queues a job to run once its `run_at` has passed, straight away unless
+/// given one. Returns the job's id.
+pub struct EnqueueJobCommand<J: Job> {
+    job: J,
+    run_at: Option<DateTime<Utc>>,
+    max_attempts: i32,
+}
+
+impl<J: Job> EnqueueJobCommand<J> {
+    pub fn new(job: J) -> Self {
+        Self {
+            job,
+            run_at: None,
+            max_attempts: DEFAULT_JOB_MAX_ATTEMPTS,
+        }
+    }
+
+    pub fn with_run_at(mut self, run_at: DateTime<Utc>) -> Self {
+        self.run_at = Some(run_at);
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: i32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Enqueues on the caller's connection, so a job can be committed with
+    /// the change that needs it.
+    pub(crate) async fn execute_with(
+        self,
+        app_state: &AppState,
+        conn: &mut PgConnection,
+    ) -> Result<i64, AppError> {
+        let job_id = app_state.sf.next_id()? as i64;
+        let payload =
+            serde_json::to_value(&self.job).map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO background_jobs (id, job_type, payload, max_attempts, run_at)
+            VALUES ($1, $2, $3, $4, COALESCE($5, NOW()))
+            "#,
+        )
+        .bind(job_id)
+        .bind(J::JOB_TYPE)
+        .bind(payload)
+        .bind(self.max_attempts)
+        .bind(self.run_at)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(job_id)
+    }
+}
+
+impl<J: Job> Command for EnqueueJobCommand<J> {
+    type Output = i64;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+        self.execute_with(app_state, &mut conn).await
+    }
+}
+
+/// Claims due jobs, runs them and records how each went. Returns the number
+/// of jobs run.
+pub struct DispatchJobsCommand {
+    handlers: JobHandlers,
+}
+
+impl DispatchJobsCommand {
+    pub fn new(handlers: JobHandlers) -> Self {
+        Self { handlers }
+    }
+
+    /// Marks due jobs as running and pushes their `run_at` out by the claim
+    /// lease. Running jobs whose lease has run out are claimed again.
+    async fn claim(app_state: &AppState) -> Result<Vec<BackgroundJob>, AppError> {
+        let rows = sqlx::query(&format!(
+            r#"
+            UPDATE background_jobs
+            SET status = 'running',
+                attempts = attempts + 1,
+                run_at = NOW() + $2 * INTERVAL '1 millisecond',
+                updated_at = NOW()
+            WHERE id IN (
+                SELECT id FROM background_jobs
+                WHERE status IN ('queued', 'running') AND run_at <= NOW()
+                ORDER BY run_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING {}
+            "#,
+            BACKGROUND_JOB_COLUMNS
+        ))
+        .bind(DISPATCH_BATCH_SIZE)
+        .bind(CLAIM_LEASE.as_millis() as i64)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        rows.iter().map(background_job_from_row).collect()
+    }
+
+    /// Hands claimed jobs that weren't run back straight away instead of
+    /// waiting out the lease.
+    async fn release(app_state: &AppState, job_ids: &[i64]) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE background_jobs
+            SET status = 'queued', attempts = attempts - 1, run_at = NOW(), updated_at = NOW()
+            WHERE id = ANY($1) AND status = 'running'
+            "#,
+        )
+        .bind(job_ids)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn record_success(app_state: &AppState, job: &BackgroundJob) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE background_jobs
+            SET status = 'succeeded', last_error = NULL, completed_at = NOW(), updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(job.id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn record_failure(
+        app_state: &AppState,
+        job: &BackgroundJob,
+        error: &AppError,
+    ) -> Result<(), AppError> {
+        let (status, delay) = if job.attempts >= job.max_attempts {
+            tracing::error!(
+                "Job {} ({}) failed for good after {} attempts: {}",
+                job.id,
+                job.job_type,
+                job.attempts,
+                error
+            );
+            (BackgroundJobStatus::Dead, Duration::ZERO)
+        } else {
+            let delay = retry_delay(job.attempts);
+            tracing::warn!(
+                "Job {} ({}) failed, retrying in {}s: {}",
+                job.id,
+                job.job_type,
+                delay.as_secs(),
+                error
+            );
+            (BackgroundJobStatus::Queued, delay)
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE background_jobs
+            SET status = $2, run_at = NOW() + $3 * INTERVAL '1 millisecond', last_error = $4,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(job.id)
+        .bind(status.as_str())
+        .bind(delay.as_millis() as i64)
+        .bind(error.to_string())
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl Command for DispatchJobsCommand {
+    type Output = usize;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let jobs = Self::claim(app_state).await?;
+        let mut attempted = 0;
+
+        for (index, job) in jobs.iter().enumerate() {
+            if app_state.background_tasks.is_shutting_down() {
+                let remaining: Vec<i64> = jobs[index..].iter().map(|j| j.id).collect();
+                Self::release(app_state, &remaining).await?;
+                break;
+            }
+
+            match self
+                .handlers
+                .run(app_state, &job.job_type, job.payload.clone())
+                .await
+            {
+                Ok(()) => Self::record_success(app_state, job).await?,
+                Err(e) => Self::record_failure(app_state, job, &e).await?,
+            }
+            attempted += 1;
+        }
+
+        Ok(attempted)
+    }
+}
+
+/// Puts a dead job back in the queue with its attempts reset. The worker
+/// picks it up at its next poll.
+pub struct RetryJobCommand {
+    job_id: i64,
+}
+
+impl RetryJobCommand {
+    pub fn new(job_id: i64) -> Self {
+        Self { job_id }
+    }
+}
+
+impl Command for RetryJobCommand {
+    type Output = BackgroundJob;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE background_jobs
+            SET status = 'queued', attempts = 0, run_at = NOW(), completed_at = NULL,
+                updated_at = NOW()
+            WHERE id = $1 AND status = 'dead'
+            RETURNING {}
+            "#,
+            BACKGROUND_JOB_COLUMNS
+        ))
+        .bind(self.job_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        if let Some(row) = row {
+            return background_job_from_row(&row);
+        }
+
+        let status: Option<String> =
+            sqlx::query_scalar("SELECT status FROM background_jobs WHERE id = $1")
+                .bind(self.job_id)
+                .fetch_optional(&app_state.db_pool)
+                .await?;
+
+        match status {
+            None => Err(AppError::NotFound("Job not found".to_string())),
+            Some(status) => Err(AppError::BadRequest(format!(
+                "Only dead jobs can be retried, this one is {}",
+                status
+            ))),
+        }
+    }
+}
+
+async fn prune_succeeded_jobs(app_state: &AppState) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM background_jobs
+        WHERE status = 'succeeded' AND completed_at < NOW() - $1 * INTERVAL '1 millisecond'
+        "#,
+    )
+    .bind(SUCCEEDED_JOB_RETENTION.as_millis() as i64)
+    .execute(&app_state.db_pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Polls for due jobs every `BACKGROUND_JOB_POLL_INTERVAL_SECS` (default 5)
+/// until shutdown, running them with `handlers`. Also removes succeeded jobs
+/// once they are a week old.
+pub fn spawn_job_worker(app_state: &AppState, handlers: JobHandlers) {
+    let interval = std::env::var("BACKGROUND_JOB_POLL_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_POLL_INTERVAL);
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        let mut prune_ticker = tokio::time::interval(PRUNE_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    // A full batch means more are likely due, so carry on
+                    // without waiting for the next tick.
+                    loop {
+                        match DispatchJobsCommand::new(handlers.clone())
+                            .execute(&background_state)
+                            .await
+                        {
+                            Ok(attempted) if attempted as i64 >= DISPATCH_BATCH_SIZE => {}
+                            Ok(_) => break,
+                            Err(e) => {
+                                tracing::error!("Failed to dispatch background jobs: {}", e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                _ = prune_ticker.tick() => {
+                    if let Err(e) = prune_succeeded_jobs(&background_state).await {
+                        tracing::error!("Failed to prune succeeded background jobs: {}", e);
+                    }
+                }
+                _ = background_state.background_tasks.shutdown_requested() => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::state::TestAppStateBuilder;
+
+    static RECEIVED: AtomicU32 = AtomicU32::new(0);
+
+    #[derive(Serialize, Deserialize)]
+    struct RecordJob {
+        value: u32,
+    }
+
+    impl Job for RecordJob {
+        const JOB_TYPE: &'static str = "record";
+
+        async fn run(self, _app_state: &AppState) -> Result<(), AppError> {
+            RECEIVED.store(self.value, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_cap() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(4), Duration::from_secs(240));
+        assert_eq!(retry_delay(20), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn handlers_run_jobs_by_type_from_their_payload() {
+        let app_state = TestAppStateBuilder::new().build();
+        let handlers = JobHandlers::new().register::<RecordJob>();
+
+        let payload = serde_json::to_value(RecordJob { value: 7 }).unwrap();
+        handlers.run(&app_state, "record", payload).await.unwrap();
+        assert_eq!(RECEIVED.load(Ordering::SeqCst), 7);
+
+        let malformed = handlers
+            .run(
+                &app_state,
+                "record",
+                serde_json::json!({ "value": "seven" }),
+            )
+            .await;
+        assert!(matches!(malformed, Err(AppError::Serialization(_))));
+
+        let unknown = handlers
+            .run(&app_state, "unknown", serde_json::json!({}))
+            .await;
+        assert!(matches!(unknown, Err(AppError::Internal(_))));
+    }
+
+    #[test]
+    fn builtin_handlers_cover_external_resource_cleanup() {
+        assert!(
+            JobHandlers::builtin()
+                .job_types()
+                .contains(&DeleteExternalResourceJob::JOB_TYPE)
+        );
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sqlx::PgConnection;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateOrganizationCommand {
@@ -33,12 +34,14 @@ impl CreateOrganizationCommand {
             private_metadata,
         }
     }
-}
 
-impl Command for CreateOrganizationCommand {
-    type Output = Organization;
-
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    /// Creates the organization on `conn`, so the caller can set it up in the
+    /// same transaction.
+    pub(crate) async fn execute_with(
+        self,
+        app_state: &AppState,
+        conn: &mut PgConnection,
+    ) -> Result<Organization, AppError> {
         let default_public_metadata = Value::Object(serde_json::Map::new());
         let default_private_metadata = Value::Object(serde_json::Map::new());
 
@@ -68,7 +71,7 @@ impl Command for CreateOrganizationCommand {
             chrono::Utc::now(),
             chrono::Utc::now(),
         )
-        .fetch_one(&app_state.db_pool)
+        .fetch_one(&mut *conn)
         .await?;
 
         Ok(Organization {
@@ -84,3 +87,12 @@ impl Command for CreateOrganizationCommand {
         })
     }
 }
+
+impl Command for CreateOrganizationCommand {
+    type Output = Organization;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+        self.execute_with(app_state, &mut conn).await
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{
     error::AppError, state::AppState,
-    commands::Command, models::Workspace,
+    commands::{Command, RecordOrganizationAuditEventCommand},
+    models::{OrganizationAuditEventType, Workspace},
 };
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Value, json};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateWorkspaceCommand {
@@ -14,6 +15,7 @@ pub struct CreateWorkspaceCommand {
     pub image_url: Option<String>,
     pub public_metadata: Option<Value>,
     pub private_metadata: Option<Value>,
+    pub initiated_by: Option<String>,
 }
 
 impl CreateWorkspaceCommand {
@@ -34,14 +36,34 @@ impl CreateWorkspaceCommand {
             image_url,
             public_metadata,
             private_metadata,
+            initiated_by: None,
         }
     }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
 }
 
 impl Command for CreateWorkspaceCommand {
     type Output = Workspace;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let organization_exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM organizations WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL)",
+        )
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+        if !organization_exists {
+            return Err(AppError::NotFound("Organization not found".to_string()));
+        }
+
+        let audit_event_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
         let default_public_metadata = Value::Object(serde_json::Map::new());
         let default_private_metadata = Value::Object(serde_json::Map::new());
 
@@ -72,9 +94,22 @@ impl Command for CreateWorkspaceCommand {
             chrono::Utc::now(),
             chrono::Utc::now(),
         )
-        .fetch_one(&app_state.db_pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        RecordOrganizationAuditEventCommand::new(
+            self.deployment_id,
+            self.organization_id,
+            OrganizationAuditEventType::WorkspaceCreated,
+        )
+        .with_workspace_id(workspace.id)
+        .with_actor(self.initiated_by)
+        .with_payload(json!({ "name": workspace.name }))
+        .execute_with(audit_event_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
         Ok(Workspace {
             id: workspace.id,
             created_at: workspace.created_at,
@@ -0,0 +1,146 @@
+//! Finding Cloudflare custom hostnames that nothing refers to anymore.
+//!
+//! Cleanups that ran before hostnames were deleted by id left some behind in
+//! the zone, still counting against the Cloudflare for SaaS quota. This
+//! compares the zone against the hostnames live deployments and domain
+//! migrations use, and reports or deletes the rest.
+
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{CustomHostnameReconciliation, OrphanedCustomHostname},
+    state::AppState,
+};
+
+use super::{Command, ExternalResource};
+
+/// Hostnames younger than this are left alone, in case the action that
+/// created one hasn't written its id down yet.
+const ORPHAN_GRACE_PERIOD: Duration = Duration::hours(1);
+
+/// Lists the custom hostnames in the zone that no live deployment or active
+/// domain migration refers to, by id or by name. Only deletes them with
+/// [`Self::with_delete_orphans`].
+pub struct ReconcileCustomHostnamesCommand {
+    delete_orphans: bool,
+}
+
+impl ReconcileCustomHostnamesCommand {
+    pub fn new() -> Self {
+        Self {
+            delete_orphans: false,
+        }
+    }
+
+    pub fn with_delete_orphans(mut self, delete_orphans: bool) -> Self {
+        self.delete_orphans = delete_orphans;
+        self
+    }
+}
+
+impl Default for ReconcileCustomHostnamesCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for ReconcileCustomHostnamesCommand {
+    type Output = CustomHostnameReconciliation;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // Listed before the references are read: a deployment's row exists
+        // before its hostnames are created, so any hostname in this list
+        // that's in use has its deployment in the query below.
+        let custom_hostnames = app_state.cloudflare_service.list_custom_hostnames().await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT frontend_host AS hostname,
+                domain_verification_records::jsonb ->> 'frontend_hostname_id' AS hostname_id
+            FROM deployments
+            WHERE deleted_at IS NULL
+            UNION ALL
+            SELECT backend_host,
+                domain_verification_records::jsonb ->> 'backend_hostname_id'
+            FROM deployments
+            WHERE deleted_at IS NULL
+            UNION ALL
+            SELECT new_frontend_host,
+                new_domain_verification_records ->> 'frontend_hostname_id'
+            FROM deployment_domain_migrations
+            WHERE status IN ('provisioning', 'verifying', 'failed')
+            UNION ALL
+            SELECT new_backend_host,
+                new_domain_verification_records ->> 'backend_hostname_id'
+            FROM deployment_domain_migrations
+            WHERE status IN ('provisioning', 'verifying', 'failed')
+            "#,
+        )
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        let mut hostnames = HashSet::new();
+        let mut hostname_ids = HashSet::new();
+        for row in rows {
+            hostnames.insert(row.get::<String, _>("hostname").to_lowercase());
+            hostname_ids.extend(row.get::<Option<String>, _>("hostname_id"));
+        }
+
+        let cutoff = Utc::now() - ORPHAN_GRACE_PERIOD;
+        let checked = custom_hostnames.len();
+        let mut orphans = Vec::new();
+
+        for custom_hostname in custom_hostnames {
+            let referenced = hostname_ids.contains(&custom_hostname.id)
+                || hostnames.contains(&custom_hostname.hostname.to_lowercase());
+            let recent = custom_hostname
+                .created_at
+                .is_some_and(|created_at| created_at > cutoff);
+            if referenced || recent {
+                continue;
+            }
+
+            let mut orphan = OrphanedCustomHostname {
+                id: custom_hostname.id,
+                hostname: custom_hostname.hostname,
+                status: custom_hostname.status,
+                created_at: custom_hostname.created_at,
+                deleted: false,
+                error: None,
+            };
+
+            if self.delete_orphans {
+                match ExternalResource::CustomHostname(orphan.id.clone())
+                    .delete(app_state)
+                    .await
+                {
+                    Ok(()) => {
+                        tracing::info!(
+                            "Deleted orphaned custom hostname {} ({})",
+                            orphan.hostname,
+                            orphan.id
+                        );
+                        orphan.deleted = true;
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to delete orphaned custom hostname {} ({}): {}",
+                            orphan.hostname,
+                            orphan.id,
+                            e
+                        );
+                        orphan.error = Some(e.to_string());
+                    }
+                }
+            }
+
+            orphans.push(orphan);
+        }
+
+        Ok(CustomHostnameReconciliation { checked, orphans })
+    }
+}
@@ -0,0 +1,281 @@
+//! Tracking of the TLS certificates Cloudflare issues for custom hostnames.
+//!
+//! DNS verification only shows that a hostname points at us; Cloudflare then
+//! validates it and issues the certificate on its own schedule. The status of
+//! each hostname is polled and kept next to its id in
+//! `domain_verification_records`, which the deployment detail returns.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{
+        DomainVerificationRecords, EmailVerificationRecords, HostnameSslStatus, SslStatus,
+        VerificationStatus,
+    },
+    services::CustomHostname,
+    state::AppState,
+};
+
+use super::Command;
+
+const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const SYNC_BATCH_SIZE: i64 = 100;
+
+fn parse_records(records: Option<serde_json::Value>) -> DomainVerificationRecords {
+    records
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn hostname_ssl_status(
+    custom_hostname: Option<CustomHostname>,
+    checked_at: DateTime<Utc>,
+) -> HostnameSslStatus {
+    let Some(custom_hostname) = custom_hostname else {
+        return HostnameSslStatus {
+            status: SslStatus::Failed,
+            cloudflare_status: "missing".to_string(),
+            error: Some("The custom hostname no longer exists in Cloudflare".to_string()),
+            checked_at,
+        };
+    };
+
+    // Cloudflare leaves `ssl` out until it starts on the certificate.
+    let (cloudflare_status, mut errors) = match custom_hostname.ssl {
+        Some(ssl) => (
+            ssl.status,
+            ssl.validation_errors
+                .into_iter()
+                .map(|error| error.message)
+                .collect::<Vec<_>>(),
+        ),
+        None => ("initializing".to_string(), Vec::new()),
+    };
+    errors.extend(custom_hostname.verification_errors.unwrap_or_default());
+
+    let status = SslStatus::from_cloudflare(&cloudflare_status);
+    let error = (status != SslStatus::Active && !errors.is_empty()).then(|| errors.join("; "));
+
+    HostnameSslStatus {
+        status,
+        cloudflare_status,
+        error,
+        checked_at,
+    }
+}
+
+/// Asks Cloudflare how the certificates of a deployment's custom hostnames
+/// are coming along and records the answer on the deployment.
+pub struct SyncCustomHostnameStatusCommand {
+    deployment_id: i64,
+}
+
+impl SyncCustomHostnameStatusCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for SyncCustomHostnameStatusCommand {
+    type Output = DomainVerificationRecords;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let records: Option<serde_json::Value> = sqlx::query_scalar(
+            r#"
+            SELECT domain_verification_records::jsonb
+            FROM deployments
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+        let records = parse_records(records);
+
+        let frontend_hostname_id = records.frontend_hostname_id.clone();
+        let backend_hostname_id = records.backend_hostname_id.clone();
+        if frontend_hostname_id.is_none() && backend_hostname_id.is_none() {
+            return Ok(records);
+        }
+
+        let cloudflare_service = &app_state.cloudflare_service;
+        let lookup = |hostname_id: Option<String>| async move {
+            match hostname_id {
+                Some(id) => cloudflare_service.get_custom_hostname(&id).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let (frontend, backend) = tokio::try_join!(
+            lookup(frontend_hostname_id.clone()),
+            lookup(backend_hostname_id.clone())
+        )?;
+        let checked_at = Utc::now();
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        // Provisioning or a domain migration may have changed the hostnames
+        // while Cloudflare was asked; only statuses of hostnames still in
+        // place are written.
+        let row = sqlx::query(
+            r#"
+            SELECT domain_verification_records::jsonb AS domain_verification_records,
+                email_verification_records::jsonb AS email_verification_records
+            FROM deployments
+            WHERE id = $1 AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+        let mut records = parse_records(row.get("domain_verification_records"));
+        let email_records: Option<EmailVerificationRecords> = row
+            .get::<Option<serde_json::Value>, _>("email_verification_records")
+            .and_then(|value| serde_json::from_value(value).ok());
+
+        if let Some(frontend) = frontend
+            && records.frontend_hostname_id == frontend_hostname_id
+        {
+            records.frontend_ssl = Some(hostname_ssl_status(frontend, checked_at));
+        }
+        if let Some(backend) = backend
+            && records.backend_hostname_id == backend_hostname_id
+        {
+            records.backend_ssl = Some(hostname_ssl_status(backend, checked_at));
+        }
+        records.update_ssl_status();
+        let verification_status =
+            VerificationStatus::from_records(Some(&records), email_records.as_ref());
+
+        sqlx::query(
+            r#"
+            UPDATE deployments
+            SET domain_verification_records = $2, verification_status = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(serde_json::to_value(&records).map_err(|e| AppError::Serialization(e.to_string()))?)
+        .bind(verification_status.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
+        Ok(records)
+    }
+}
+
+/// Runs [`SyncCustomHostnameStatusCommand`] for every deployment with a
+/// custom hostname whose certificate isn't active yet. Returns how many
+/// deployments were synced; one failing doesn't stop the rest.
+pub struct SyncPendingCustomHostnamesCommand;
+
+impl SyncPendingCustomHostnamesCommand {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for SyncPendingCustomHostnamesCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for SyncPendingCustomHostnamesCommand {
+    type Output = usize;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut synced = 0;
+        let mut after_id = 0i64;
+
+        loop {
+            let deployment_ids: Vec<i64> = sqlx::query_scalar(
+                r#"
+                SELECT id FROM deployments
+                WHERE deleted_at IS NULL AND id > $1
+                    AND (
+                        domain_verification_records::jsonb ->> 'frontend_hostname_id' IS NOT NULL
+                        OR domain_verification_records::jsonb ->> 'backend_hostname_id' IS NOT NULL
+                    )
+                    AND (domain_verification_records::jsonb ->> 'ssl_status')
+                        IS DISTINCT FROM 'active'
+                ORDER BY id
+                LIMIT $2
+                "#,
+            )
+            .bind(after_id)
+            .bind(SYNC_BATCH_SIZE)
+            .fetch_all(&app_state.db_pool)
+            .await?;
+
+            let Some(&last_id) = deployment_ids.last() else {
+                break;
+            };
+            after_id = last_id;
+
+            for deployment_id in deployment_ids {
+                if app_state.background_tasks.is_shutting_down() {
+                    return Ok(synced);
+                }
+
+                match SyncCustomHostnameStatusCommand::new(deployment_id)
+                    .execute(app_state)
+                    .await
+                {
+                    Ok(_) => synced += 1,
+                    Err(e) => tracing::warn!(
+                        "Failed to sync custom hostname status for deployment {}: {}",
+                        deployment_id,
+                        e
+                    ),
+                }
+            }
+        }
+
+        Ok(synced)
+    }
+}
+
+/// Runs [`SyncPendingCustomHostnamesCommand`] every
+/// `CUSTOM_HOSTNAME_SYNC_INTERVAL_SECS` (default five minutes) until
+/// shutdown.
+pub fn spawn_custom_hostname_sync(app_state: &AppState) {
+    let interval = std::env::var("CUSTOM_HOSTNAME_SYNC_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SYNC_INTERVAL);
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = background_state.background_tasks.shutdown_requested() => break,
+            }
+
+            if let Err(e) = SyncPendingCustomHostnamesCommand::new()
+                .execute(&background_state)
+                .await
+            {
+                tracing::error!("Failed to sync custom hostname statuses: {}", e);
+            }
+        }
+    });
+}
@@ -1,13 +1,31 @@
 use crate::{
-    error::AppError, state::AppState,
-    commands::Command,
+    commands::{Command, RecordAuditLogCommand},
+    error::AppError,
+    models::OrganizationDeletion,
+    queries::invalidate_deployment_authorization_contexts,
+    services::SessionRepository,
+    state::AppState,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, Row};
 
+/// Soft-deletes an organization with its workspaces, memberships, custom
+/// roles and pending invitations, in one transaction. The deployment's
+/// `allow_org_deletion` setting has to be on unless `admin_override` is set.
+/// Users whose active organization or workspace was in it have it cleared and
+/// their cached sessions dropped, so their next tokens no longer carry its
+/// claims. They stay signed in.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteOrganizationCommand {
     pub deployment_id: i64,
     pub organization_id: i64,
+    #[serde(default)]
+    admin_override: bool,
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    initiated_by: Option<String>,
 }
 
 impl DeleteOrganizationCommand {
@@ -15,36 +33,206 @@ impl DeleteOrganizationCommand {
         Self {
             deployment_id,
             organization_id,
+            admin_override: false,
+            dry_run: false,
+            initiated_by: None,
         }
     }
+
+    /// Deletes the organization even if the deployment doesn't allow it.
+    pub fn with_admin_override(mut self, admin_override: bool) -> Self {
+        self.admin_override = admin_override;
+        self
+    }
+
+    /// Counts what would be deleted without changing anything.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
 }
 
 impl Command for DeleteOrganizationCommand {
-    type Output = ();
-
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // First check if organization exists and belongs to deployment
-        let exists = sqlx::query!(
-            "SELECT id FROM organizations WHERE deployment_id = $1 AND id = $2",
-            self.deployment_id,
-            self.organization_id
+    type Output = OrganizationDeletion;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT
+                d.project_id,
+                o.name,
+                COALESCE(s.allow_org_deletion, false) AS allow_org_deletion
+            FROM organizations o
+            JOIN deployments d ON d.id = o.deployment_id
+            LEFT JOIN deployment_b2b_settings s
+                ON s.deployment_id = o.deployment_id AND s.deleted_at IS NULL
+            WHERE o.deployment_id = $1 AND o.id = $2 AND o.deleted_at IS NULL
+            FOR UPDATE OF o
+            "#,
         )
-        .fetch_optional(&app_state.db_pool)
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Organization not found".to_string()))?;
+
+        if !row.get::<bool, _>("allow_org_deletion") && !self.admin_override {
+            return Err(AppError::BadRequest(
+                "Organization deletion is disabled for this deployment".to_string(),
+            ));
+        }
+
+        let deletion = count_affected(&mut tx, self.organization_id, self.dry_run).await?;
+        if self.dry_run {
+            return Ok(deletion);
+        }
+
+        let detached_users: Vec<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE users SET
+                active_organization_membership_id = CASE
+                    WHEN active_organization_membership_id IN (
+                        SELECT id FROM organization_memberships WHERE organization_id = $1
+                    ) THEN NULL
+                    ELSE active_organization_membership_id
+                END,
+                active_workspace_membership_id = CASE
+                    WHEN active_workspace_membership_id IN (
+                        SELECT wm.id FROM workspace_memberships wm
+                        JOIN workspaces w ON w.id = wm.workspace_id
+                        WHERE w.organization_id = $1
+                    ) THEN NULL
+                    ELSE active_workspace_membership_id
+                END
+            WHERE active_organization_membership_id IN (
+                    SELECT id FROM organization_memberships WHERE organization_id = $1
+                )
+                OR active_workspace_membership_id IN (
+                    SELECT wm.id FROM workspace_memberships wm
+                    JOIN workspaces w ON w.id = wm.workspace_id
+                    WHERE w.organization_id = $1
+                )
+            RETURNING id
+            "#,
+        )
+        .bind(self.organization_id)
+        .fetch_all(&mut *tx)
         .await?;
 
-        if exists.is_none() {
-            return Err(AppError::NotFound("Organization not found".to_string()));
+        let statements = [
+            r#"
+            UPDATE workspace_memberships SET deleted_at = NOW()
+            WHERE deleted_at IS NULL
+                AND workspace_id IN (SELECT id FROM workspaces WHERE organization_id = $1)
+            "#,
+            "UPDATE workspaces SET deleted_at = NOW(), updated_at = NOW() WHERE organization_id = $1 AND deleted_at IS NULL",
+            "UPDATE organization_memberships SET deleted_at = NOW() WHERE organization_id = $1 AND deleted_at IS NULL",
+            "UPDATE workspace_roles SET deleted_at = NOW() WHERE organization_id = $1 AND deleted_at IS NULL",
+            "UPDATE organization_roles SET deleted_at = NOW() WHERE organization_id = $1 AND deleted_at IS NULL",
+            r#"
+            UPDATE organization_invitations SET revoked_at = NOW(), updated_at = NOW()
+            WHERE organization_id = $1 AND accepted_at IS NULL AND revoked_at IS NULL
+            "#,
+            "UPDATE organizations SET deleted_at = NOW(), updated_at = NOW() WHERE id = $1",
+        ];
+        for statement in statements {
+            sqlx::query(statement)
+                .bind(self.organization_id)
+                .execute(&mut *tx)
+                .await?;
         }
 
-        // Delete organization (this should cascade to related tables)
-        sqlx::query!(
-            "DELETE FROM organizations WHERE deployment_id = $1 AND id = $2",
-            self.deployment_id,
-            self.organization_id
+        RecordAuditLogCommand::new(
+            row.get("project_id"),
+            "organization.deleted",
+            "organization",
+            self.organization_id,
         )
-        .execute(&app_state.db_pool)
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({
+            "name": row.get::<String, _>("name"),
+            "admin_override": self.admin_override,
+            "affected": &deletion,
+        }))
+        .execute_with(app_state.sf.next_id()? as i64, &mut tx)
         .await?;
 
-        Ok(())
+        tx.commit().await?;
+
+        invalidate_deployment_authorization_contexts(app_state, self.deployment_id).await;
+
+        let sessions = SessionRepository::new(&app_state.db_pool, &app_state.redis);
+        for user_id in detached_users {
+            if let Err(e) = sessions.refresh(self.deployment_id, user_id).await {
+                tracing::warn!(
+                    user_id,
+                    organization_id = self.organization_id,
+                    "Failed to refresh sessions after deleting an organization: {}",
+                    e
+                );
+            }
+        }
+
+        Ok(deletion)
     }
 }
+
+async fn count_affected(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    dry_run: bool,
+) -> Result<OrganizationDeletion, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM workspaces
+                WHERE organization_id = $1 AND deleted_at IS NULL) AS workspaces,
+            (SELECT COUNT(*) FROM organization_memberships
+                WHERE organization_id = $1 AND deleted_at IS NULL) AS organization_memberships,
+            (SELECT COUNT(*) FROM workspace_memberships wm
+                JOIN workspaces w ON w.id = wm.workspace_id
+                WHERE w.organization_id = $1 AND wm.deleted_at IS NULL) AS workspace_memberships,
+            (SELECT COUNT(*) FROM organization_roles
+                WHERE organization_id = $1 AND deleted_at IS NULL)
+                + (SELECT COUNT(*) FROM workspace_roles
+                    WHERE organization_id = $1 AND deleted_at IS NULL) AS custom_roles,
+            (SELECT COUNT(*) FROM organization_invitations
+                WHERE organization_id = $1 AND accepted_at IS NULL AND revoked_at IS NULL)
+                AS pending_invitations,
+            (SELECT COUNT(DISTINCT s.session_id) FROM signins s
+                JOIN users u ON u.id = s.user_id
+                WHERE NOT s.expired AND (
+                    u.active_organization_membership_id IN (
+                        SELECT id FROM organization_memberships WHERE organization_id = $1
+                    )
+                    OR u.active_workspace_membership_id IN (
+                        SELECT wm.id FROM workspace_memberships wm
+                        JOIN workspaces w ON w.id = wm.workspace_id
+                        WHERE w.organization_id = $1
+                    )
+                )) AS sessions
+        "#,
+    )
+    .bind(organization_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(OrganizationDeletion {
+        organization_id,
+        dry_run,
+        workspaces: row.get("workspaces"),
+        organization_memberships: row.get("organization_memberships"),
+        workspace_memberships: row.get("workspace_memberships"),
+        custom_roles: row.get("custom_roles"),
+        pending_invitations: row.get("pending_invitations"),
+        sessions: row.get("sessions"),
+    })
+}
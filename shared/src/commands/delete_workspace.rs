@@ -0,0 +1,134 @@
+use crate::{
+    commands::{Command, RecordOrganizationAuditEventCommand},
+    error::{AppError, ErrorCode},
+    models::OrganizationAuditEventType,
+    queries::invalidate_deployment_authorization_contexts,
+    state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::Row;
+
+/// Deletes a workspace if the deployment's b2b settings allow it. With
+/// `prevent_last_workspace_deletion` on, an organization's only workspace
+/// stays.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteWorkspaceCommand {
+    pub deployment_id: i64,
+    pub workspace_id: i64,
+    pub initiated_by: Option<String>,
+}
+
+impl DeleteWorkspaceCommand {
+    pub fn new(deployment_id: i64, workspace_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workspace_id,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for DeleteWorkspaceCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_event_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT
+                w.organization_id,
+                w.name,
+                COALESCE(s.allow_workspace_deletion, false) AS allow_workspace_deletion,
+                COALESCE(s.prevent_last_workspace_deletion, false) AS prevent_last_workspace_deletion
+            FROM workspaces w
+            LEFT JOIN deployment_b2b_settings s
+                ON s.deployment_id = w.deployment_id AND s.deleted_at IS NULL
+            WHERE w.deployment_id = $1 AND w.id = $2 AND w.deleted_at IS NULL
+            FOR UPDATE OF w
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.workspace_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Workspace not found".to_string()))?;
+
+        if !row.get::<bool, _>("allow_workspace_deletion") {
+            return Err(AppError::BadRequest(
+                "Workspace deletion is disabled for this deployment".to_string(),
+            ));
+        }
+
+        let organization_id: i64 = row.get("organization_id");
+
+        if row.get::<bool, _>("prevent_last_workspace_deletion") {
+            // Locks the organization so its last two workspaces can't be
+            // deleted at the same time.
+            sqlx::query("SELECT id FROM organizations WHERE id = $1 FOR UPDATE")
+                .bind(organization_id)
+                .execute(&mut *tx)
+                .await?;
+
+            let workspace_count: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM workspaces WHERE organization_id = $1 AND deleted_at IS NULL",
+            )
+            .bind(organization_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if workspace_count <= 1 {
+                return Err(AppError::coded(
+                    ErrorCode::LastWorkspaceCannotBeDeleted,
+                    "Cannot delete an organization's only workspace.",
+                )
+                .with_details(serde_json::json!({
+                    "organization_id": organization_id.to_string(),
+                })));
+            }
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE users SET active_workspace_membership_id = NULL
+            WHERE active_workspace_membership_id IN (
+                SELECT id FROM workspace_memberships WHERE workspace_id = $1
+            )
+            "#,
+        )
+        .bind(self.workspace_id)
+        .execute(&mut *tx)
+        .await?;
+
+        // Memberships, their roles and the workspace's own roles cascade.
+        sqlx::query("DELETE FROM workspaces WHERE deployment_id = $1 AND id = $2")
+            .bind(self.deployment_id)
+            .bind(self.workspace_id)
+            .execute(&mut *tx)
+            .await?;
+
+        RecordOrganizationAuditEventCommand::new(
+            self.deployment_id,
+            organization_id,
+            OrganizationAuditEventType::WorkspaceDeleted,
+        )
+        .with_workspace_id(self.workspace_id)
+        .with_actor(self.initiated_by)
+        .with_payload(json!({ "name": row.get::<String, _>("name") }))
+        .execute_with(audit_event_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        invalidate_deployment_authorization_contexts(app_state, self.deployment_id).await;
+
+        Ok(())
+    }
+}
@@ -1,16 +1,26 @@
-use sqlx::Row;
+use sqlx::{PgConnection, Row};
 use std::str::FromStr;
 
-use super::Command;
+use super::{Command, EnqueueJobCommand, NormalizeEmailAddressesJob, RecordAuditLogCommand};
 use crate::{
-    error::AppError, state::AppState,
-        dto::json::{
-            DeploymentAuthSettingsUpdates, DeploymentB2bSettingsUpdates,
-            DeploymentDisplaySettingsUpdates, DeploymentRestrictionsUpdates,
-            DeploymentSocialConnectionUpsert, NewDeploymentJwtTemplate,
-            PartialDeploymentJwtTemplate,
-        },
-        models::{DeploymentJwtTemplate, DeploymentSocialConnection, SocialConnectionProvider},
+    dto::json::{
+        DeploymentAuthSettingsUpdates, DeploymentB2bSettingsUpdates,
+        DeploymentDisplaySettingsUpdates, DeploymentRestrictionsUpdates,
+        DeploymentSocialConnectionUpsert, NewDeploymentJwtTemplate, PartialDeploymentJwtTemplate,
+    },
+    error::{AppError, ErrorCode},
+    models::{
+        DeploymentAuthSettings, DeploymentJwtTemplate, DeploymentMaintenance,
+        DeploymentSocialConnection, FirstFactor, RestrictionEntry, SocialConnectionProvider,
+    },
+    queries::invalidate_deployment_authorization_contexts,
+    services::{JWT_TEMPLATE_SIGNING_KEY, SOCIAL_CONNECTION_CLIENT_SECRET},
+    state::AppState,
+    utils::validation::{validate_alternate_first_factors, validation_failed},
+    validators::{
+        JwtTemplateValidation, RedirectUrlPolicy, RedirectUrlValidation, normalize_redirect_origin,
+        validate_jwt_template,
+    },
 };
 use chrono::Utc;
 use serde_json::{Map, Value, json};
@@ -48,7 +58,7 @@ fn build_partial_json<T: serde::Serialize>(data: Option<&T>) -> Option<Value> {
 impl Command for UpdateDeploymentAuthSettingsCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut text_updates: Vec<(&str, String)> = Vec::new();
         let mut int_updates: Vec<(&str, i64)> = Vec::new();
         let mut jsonb_merges: Vec<(&str, Value)> = Vec::new();
@@ -163,6 +173,18 @@ impl Command for UpdateDeploymentAuthSettingsCommand {
             jsonb_merges.push(("multi_session_support", serde_json::to_value(session)?));
         }
 
+        if let Some(json_val) = build_partial_json(self.updates.lockout_policy.as_ref()) {
+            jsonb_merges.push(("lockout_policy", json_val));
+        }
+
+        if let Some(json_val) = build_partial_json(self.updates.user_deletion_policy.as_ref()) {
+            jsonb_merges.push(("user_deletion_policy", json_val));
+        }
+
+        if let Some(json_val) = build_partial_json(self.updates.otp.as_ref()) {
+            jsonb_merges.push(("otp_settings", json_val));
+        }
+
         if let Some(session_token_lifetime) = &self.updates.session_token_lifetime {
             int_updates.push(("session_token_lifetime", *session_token_lifetime));
         }
@@ -179,7 +201,11 @@ impl Command for UpdateDeploymentAuthSettingsCommand {
         let has_int_updates = !int_updates.is_empty();
         let has_jsonb_merges = !jsonb_merges.is_empty();
 
-        if !has_text_updates && !has_int_updates && !has_jsonb_merges {
+        if !has_text_updates
+            && !has_int_updates
+            && !has_jsonb_merges
+            && self.updates.alternate_first_factors.is_none()
+        {
             println!(
                 "No settings updates to apply for deployment_id: {}",
                 self.deployment_id
@@ -215,11 +241,23 @@ impl Command for UpdateDeploymentAuthSettingsCommand {
         query_builder.push(" WHERE deployment_id = ");
         query_builder.push_bind(self.deployment_id);
 
-        query_builder
-            .build()
-            .execute(&app_state.db_pool)
-            .await
-            .unwrap();
+        let mut tx = app_state.db_pool.begin().await?;
+        query_builder.build().execute(&mut *tx).await?;
+        if process_auth_factors || self.updates.password.is_some() {
+            ensure_sign_in_method(&mut tx, self.deployment_id).await?;
+        }
+        reconcile_alternate_first_factors(
+            &mut tx,
+            self.deployment_id,
+            self.updates.alternate_first_factors,
+        )
+        .await?;
+        tx.commit().await?;
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
 
         Ok(())
     }
@@ -241,38 +279,246 @@ impl UpsertDeploymentSocialConnectionCommand {
 impl Command for UpsertDeploymentSocialConnectionCommand {
     type Output = DeploymentSocialConnection;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let result = sqlx::query!(
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let cipher = &app_state.credential_cipher;
+        let provider = self.connection.provider.map(String::from);
+
+        // Leaving credentials out keeps the stored ones, so a provider can be
+        // switched off without sending its client secret again.
+        let credentials = match self.connection.credentials {
+            Some(credentials) => {
+                let mut credentials = serde_json::to_value(credentials)?;
+                if SOCIAL_CONNECTION_CLIENT_SECRET.holds_mask(&credentials) {
+                    let stored: Option<Value> = sqlx::query_scalar(
+                        "SELECT credentials FROM deployment_social_connections WHERE deployment_id = $1 AND provider = $2",
+                    )
+                    .bind(self.deployment_id)
+                    .bind(&provider)
+                    .fetch_optional(&app_state.db_pool)
+                    .await?
+                    .flatten();
+                    credentials = SOCIAL_CONNECTION_CLIENT_SECRET
+                        .keep_unchanged(credentials, stored.as_ref())?;
+                }
+                Some(SOCIAL_CONNECTION_CLIENT_SECRET.seal(cipher, credentials)?)
+            }
+            None => None,
+        };
+
+        let mut tx = app_state.db_pool.begin().await?;
+        let row = sqlx::query(
             r#"
             INSERT INTO deployment_social_connections (id, created_at, updated_at, deployment_id, provider, enabled, credentials)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
-            ON CONFLICT (deployment_id, provider) DO UPDATE SET updated_at = NOW(), enabled = EXCLUDED.enabled, credentials = EXCLUDED.credentials RETURNING *
+            VALUES ($1, NOW(), NOW(), $2, $3, COALESCE($4, TRUE), $5)
+            ON CONFLICT (deployment_id, provider) DO UPDATE SET
+                updated_at = NOW(),
+                enabled = COALESCE($4, deployment_social_connections.enabled),
+                credentials = COALESCE(EXCLUDED.credentials, deployment_social_connections.credentials),
+                deleted_at = NULL
+            RETURNING id, created_at, updated_at, deployment_id, provider, enabled, credentials
             "#,
-            app_state.sf.next_id()? as i64,
-            Utc::now(),
-            Utc::now(),
-            self.deployment_id,
-            self.connection.provider.map(|p| String::from(p)),
-            self.connection.enabled,
-            serde_json::to_value(self.connection.credentials).unwrap(),
         )
-        .fetch_one(&app_state.db_pool)
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(&provider)
+        .bind(self.connection.enabled)
+        .bind(credentials)
+        .fetch_one(&mut *tx)
         .await?;
 
+        ensure_sign_in_method(&mut tx, self.deployment_id).await?;
+        tx.commit().await?;
+
         let connection = DeploymentSocialConnection {
-            id: result.id,
-            created_at: result.created_at,
-            updated_at: result.updated_at,
-            deployment_id: result.deployment_id,
-            provider: SocialConnectionProvider::from_str(&result.provider.unwrap()).ok(),
-            enabled: result.enabled,
-            credentials: serde_json::from_value(result.credentials.unwrap()).unwrap_or(None),
-        };
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            deployment_id: row.get("deployment_id"),
+            provider: row
+                .get::<Option<String>, _>("provider")
+                .and_then(|provider| SocialConnectionProvider::from_str(&provider).ok()),
+            enabled: row.get("enabled"),
+            credentials: match row.get::<Option<Value>, _>("credentials") {
+                Some(credentials) => serde_json::from_value(
+                    SOCIAL_CONNECTION_CLIENT_SECRET.open(cipher, credentials)?,
+                )
+                .unwrap_or(None),
+                None => None,
+            },
+            has_secret: false,
+        }
+        .with_secret_flag()
+        .redact();
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
 
         Ok(connection)
     }
 }
 
+/// Soft-deletes a deployment's connection for a provider. The stored
+/// credentials are cleared with it; adding the provider back needs them again.
+pub struct DeleteDeploymentSocialConnectionCommand {
+    deployment_id: i64,
+    provider: SocialConnectionProvider,
+}
+
+impl DeleteDeploymentSocialConnectionCommand {
+    pub fn new(deployment_id: i64, provider: SocialConnectionProvider) -> Self {
+        Self {
+            deployment_id,
+            provider,
+        }
+    }
+}
+
+impl Command for DeleteDeploymentSocialConnectionCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+        let result = sqlx::query(
+            r#"
+            UPDATE deployment_social_connections
+            SET deleted_at = NOW(), updated_at = NOW(), enabled = FALSE, credentials = NULL
+            WHERE deployment_id = $1 AND provider = $2 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(String::from(self.provider))
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(
+                "Social connection not found".to_string(),
+            ));
+        }
+
+        ensure_sign_in_method(&mut tx, self.deployment_id).await?;
+        tx.commit().await?;
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
+        Ok(())
+    }
+}
+
+/// Refuses a change that leaves the deployment without a way to sign in.
+/// An enabled social connection counts, as does any enabled first factor;
+/// the password factors only while passwords themselves are on.
+/// Checks the alternate first factors against the auth settings as just
+/// updated. A list given in the update has to be valid as it stands;
+/// otherwise, factors the update made unusable are dropped from the stored
+/// list.
+async fn reconcile_alternate_first_factors(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    requested: Option<Vec<FirstFactor>>,
+) -> Result<(), AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            email_address, phone_number, username, password, auth_factors_enabled,
+            first_factor::text AS first_factor, alternate_first_factors
+        FROM deployment_auth_settings
+        WHERE deployment_id = $1
+        "#,
+    )
+    .bind(deployment_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let settings = DeploymentAuthSettings {
+        email_address: serde_json::from_value(row.get("email_address"))?,
+        phone_number: serde_json::from_value(row.get("phone_number"))?,
+        username: serde_json::from_value(row.get("username"))?,
+        password: serde_json::from_value(row.get("password"))?,
+        auth_factors_enabled: serde_json::from_value(row.get("auth_factors_enabled"))?,
+        first_factor: FirstFactor::from_str(row.get("first_factor")).map_err(AppError::Internal)?,
+        ..DeploymentAuthSettings::default()
+    };
+
+    let factors = match requested {
+        Some(factors) => {
+            let errors = validate_alternate_first_factors(&settings, &factors);
+            if !errors.is_empty() {
+                return Err(validation_failed(errors));
+            }
+            factors
+        }
+        None => {
+            let stored: Vec<FirstFactor> =
+                serde_json::from_value(row.get("alternate_first_factors")).unwrap_or_default();
+            let available: Vec<FirstFactor> = stored
+                .iter()
+                .filter(|factor| settings.first_factor_available(factor))
+                .cloned()
+                .collect();
+            if available.len() == stored.len() {
+                return Ok(());
+            }
+            available
+        }
+    };
+
+    sqlx::query(
+        "UPDATE deployment_auth_settings SET alternate_first_factors = $2 WHERE deployment_id = $1",
+    )
+    .bind(deployment_id)
+    .bind(serde_json::to_value(&factors)?)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn ensure_sign_in_method(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<(), AppError> {
+    let has_sign_in_method: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM deployment_social_connections
+            WHERE deployment_id = $1 AND enabled AND deleted_at IS NULL
+        ) OR EXISTS (
+            SELECT 1
+            FROM deployment_auth_settings s,
+                jsonb_each_text(s.auth_factors_enabled) AS factor(name, enabled)
+            WHERE s.deployment_id = $1
+                AND factor.enabled = 'true'
+                AND (
+                    factor.name IN ('email_otp', 'email_magic_link', 'phone_otp', 'passkey', 'sso', 'web3_wallet')
+                    OR (
+                        factor.name IN ('email_password', 'username_password')
+                        AND COALESCE((s.password->>'enabled')::BOOLEAN, TRUE)
+                    )
+                )
+        )
+        "#,
+    )
+    .bind(deployment_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if !has_sign_in_method {
+        return Err(AppError::coded(
+            ErrorCode::LastSignInMethod,
+            "This would leave the deployment with no way to sign in. Enable another sign-in method first.",
+        )
+        .with_details(json!({ "deployment_id": deployment_id.to_string() })));
+    }
+
+    Ok(())
+}
+
 pub struct UpdateDeploymentRestrictionsCommand {
     pub deployment_id: i64,
     pub updates: DeploymentRestrictionsUpdates,
@@ -290,7 +536,7 @@ impl UpdateDeploymentRestrictionsCommand {
 impl Command for UpdateDeploymentRestrictionsCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut query_builder =
             sqlx::QueryBuilder::new("UPDATE deployment_restrictions SET updated_at = NOW() ");
 
@@ -331,12 +577,12 @@ impl Command for UpdateDeploymentRestrictionsCommand {
 
         if let Some(allowlisted_resources) = self.updates.allowlisted_resources {
             query_builder.push(", allowlisted_resources = ");
-            query_builder.push_bind(allowlisted_resources);
+            query_builder.push_bind(RestrictionEntry::to_stored_list(&allowlisted_resources));
         }
 
         if let Some(blocklisted_resources) = self.updates.blocklisted_resources {
             query_builder.push(", blocklisted_resources = ");
-            query_builder.push_bind(blocklisted_resources);
+            query_builder.push_bind(RestrictionEntry::to_stored_list(&blocklisted_resources));
         }
 
         if let Some(sign_up_mode) = self.updates.sign_up_mode {
@@ -347,12 +593,40 @@ impl Command for UpdateDeploymentRestrictionsCommand {
         query_builder.push(" WHERE deployment_id = ");
         query_builder.push_bind(self.deployment_id);
 
-        query_builder.build().execute(&app_state.db_pool).await?;
+        let mut tx = app_state.db_pool.begin().await?;
+        query_builder.build().execute(&mut *tx).await?;
+
+        // Stored normalized addresses depend on whether tags are stripped.
+        if self.updates.block_subaddresses.is_some() {
+            EnqueueJobCommand::new(NormalizeEmailAddressesJob::new(self.deployment_id))
+                .execute_with(app_state, &mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        app_state
+            .disposable_domain_service
+            .invalidate_deployment(self.deployment_id);
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
 
         Ok(().into())
     }
 }
 
+/// Turns a failed validation into an error that lists every bad field.
+fn check_jwt_template(validation: JwtTemplateValidation) -> Result<(), AppError> {
+    if validation.is_valid() {
+        return Ok(());
+    }
+    Err(
+        AppError::coded(ErrorCode::InvalidJwtTemplate, "The JWT template has errors")
+            .with_details(serde_json::to_value(&validation)?),
+    )
+}
+
 pub struct CreateDeploymentJwtTemplateCommand {
     pub deployment_id: i64,
     pub template: NewDeploymentJwtTemplate,
@@ -370,7 +644,21 @@ impl CreateDeploymentJwtTemplateCommand {
 impl Command for CreateDeploymentJwtTemplateCommand {
     type Output = DeploymentJwtTemplate;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        check_jwt_template(validate_jwt_template(
+            Some(&self.template.template),
+            Some(self.template.token_lifetime),
+            Some(self.template.allowed_clock_skew),
+        ))?;
+
+        let cipher = &app_state.credential_cipher;
+        // A new template has no stored key, so a masked one is rejected here.
+        let custom_signing_key = JWT_TEMPLATE_SIGNING_KEY.keep_unchanged(
+            serde_json::to_value(self.template.custom_signing_key)?,
+            None,
+        )?;
+        let custom_signing_key = JWT_TEMPLATE_SIGNING_KEY.seal(cipher, custom_signing_key)?;
+
         let result = sqlx::query!(
             r#"
             INSERT INTO deployment_jwt_templates (id, created_at, updated_at, deployment_id, name, token_lifetime, allowed_clock_skew, custom_signing_key, template)
@@ -384,7 +672,7 @@ impl Command for CreateDeploymentJwtTemplateCommand {
             self.template.name,
             self.template.token_lifetime,
             self.template.allowed_clock_skew,
-            serde_json::to_value(self.template.custom_signing_key).unwrap(),
+            custom_signing_key,
             self.template.template,
         )
         .fetch_one(&app_state.db_pool)
@@ -398,11 +686,24 @@ impl Command for CreateDeploymentJwtTemplateCommand {
             name: result.name,
             token_lifetime: result.token_lifetime,
             allowed_clock_skew: result.allowed_clock_skew,
-            custom_signing_key: result
-                .custom_signing_key
-                .map(|v| serde_json::from_value(v).unwrap_or_default()),
+            custom_signing_key: match result.custom_signing_key {
+                Some(key) => Some(
+                    serde_json::from_value(JWT_TEMPLATE_SIGNING_KEY.open(cipher, key)?)
+                        .unwrap_or_default(),
+                ),
+                None => None,
+            },
             template: serde_json::from_value(result.template).unwrap_or_default(),
-        };
+            has_secret: false,
+        }
+        .with_secret_flag()
+        .redact();
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
         Ok(template)
     }
 }
@@ -421,7 +722,13 @@ impl UpdateDeploymentJwtTemplateCommand {
 impl Command for UpdateDeploymentJwtTemplateCommand {
     type Output = DeploymentJwtTemplate;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        check_jwt_template(validate_jwt_template(
+            self.template.template.as_ref(),
+            self.template.token_lifetime,
+            self.template.allowed_clock_skew,
+        ))?;
+
         let mut query_builder =
             sqlx::QueryBuilder::new("UPDATE deployment_jwt_templates SET updated_at = NOW() ");
 
@@ -441,8 +748,23 @@ impl Command for UpdateDeploymentJwtTemplateCommand {
         }
 
         if let Some(custom_signing_key) = &self.template.custom_signing_key {
+            let mut custom_signing_key = serde_json::to_value(custom_signing_key)?;
+            if JWT_TEMPLATE_SIGNING_KEY.holds_mask(&custom_signing_key) {
+                let stored: Option<Value> = sqlx::query_scalar(
+                    "SELECT custom_signing_key FROM deployment_jwt_templates WHERE id = $1",
+                )
+                .bind(self.id)
+                .fetch_optional(&app_state.db_pool)
+                .await?
+                .flatten();
+                custom_signing_key =
+                    JWT_TEMPLATE_SIGNING_KEY.keep_unchanged(custom_signing_key, stored.as_ref())?;
+            }
+
             query_builder.push(", custom_signing_key = ");
-            query_builder.push_bind(serde_json::to_value(custom_signing_key).unwrap());
+            query_builder.push_bind(
+                JWT_TEMPLATE_SIGNING_KEY.seal(&app_state.credential_cipher, custom_signing_key)?,
+            );
         }
 
         if let Some(template) = &self.template.template {
@@ -465,10 +787,21 @@ impl Command for UpdateDeploymentJwtTemplateCommand {
             name: result.get("name"),
             token_lifetime: result.get("token_lifetime"),
             allowed_clock_skew: result.get("allowed_clock_skew"),
-            custom_signing_key: serde_json::from_value(result.get("custom_signing_key"))
-                .unwrap_or_default(),
+            custom_signing_key: serde_json::from_value(JWT_TEMPLATE_SIGNING_KEY.open(
+                &app_state.credential_cipher,
+                result.get("custom_signing_key"),
+            )?)
+            .unwrap_or_default(),
             template: result.get("template"),
-        };
+            has_secret: false,
+        }
+        .with_secret_flag()
+        .redact();
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(template.deployment_id)
+            .await;
 
         Ok(template)
     }
@@ -487,16 +820,83 @@ impl DeleteDeploymentJwtTemplateCommand {
 impl Command for DeleteDeploymentJwtTemplateCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        sqlx::query("DELETE FROM deployment_jwt_templates WHERE id = $1")
-            .bind(self.id)
-            .execute(&app_state.db_pool)
-            .await?;
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let deployment_id: Option<i64> = sqlx::query_scalar(
+            "DELETE FROM deployment_jwt_templates WHERE id = $1 RETURNING deployment_id",
+        )
+        .bind(self.id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        if let Some(deployment_id) = deployment_id {
+            app_state
+                .cache_invalidator
+                .invalidate_deployment(deployment_id)
+                .await;
+        }
 
         Ok(().into())
     }
 }
 
+/// Records that a deployment's stored secrets were shown in full. Handlers
+/// run it before reading them, so a reveal that can't be audited doesn't
+/// happen.
+pub struct RecordSecretsRevealedCommand {
+    deployment_id: i64,
+    resource: &'static str,
+    initiated_by: Option<String>,
+}
+
+impl RecordSecretsRevealedCommand {
+    /// `resource` names what was revealed, such as `social_connections`.
+    pub fn new(deployment_id: i64, resource: &'static str) -> Self {
+        Self {
+            deployment_id,
+            resource,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for RecordSecretsRevealedCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let project_id: i64 = sqlx::query_scalar(
+            "SELECT project_id FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.deployment_id
+            ))
+        })?;
+
+        RecordAuditLogCommand::new(
+            project_id,
+            "deployment.secrets_revealed",
+            "deployment",
+            self.deployment_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({ "resource": self.resource }))
+        .execute(app_state)
+        .await?;
+
+        Ok(())
+    }
+}
+
 pub struct UpdateDeploymentB2bSettingsCommand {
     deployment_id: i64,
     settings: DeploymentB2bSettingsUpdates,
@@ -514,7 +914,7 @@ impl UpdateDeploymentB2bSettingsCommand {
 impl Command for UpdateDeploymentB2bSettingsCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut query_builder =
             sqlx::QueryBuilder::new("UPDATE deployment_b2b_settings SET updated_at = NOW() ");
 
@@ -553,6 +953,12 @@ impl Command for UpdateDeploymentB2bSettingsCommand {
             query_builder.push_bind(allow_workspace_deletion);
         }
 
+        if let Some(prevent_last_workspace_deletion) = self.settings.prevent_last_workspace_deletion
+        {
+            query_builder.push(", prevent_last_workspace_deletion = ");
+            query_builder.push_bind(prevent_last_workspace_deletion);
+        }
+
         if let Some(custom_org_role_enabled) = self.settings.custom_org_role_enabled {
             query_builder.push(", custom_org_role_enabled = ");
             query_builder.push_bind(custom_org_role_enabled);
@@ -630,10 +1036,69 @@ impl Command for UpdateDeploymentB2bSettingsCommand {
             )));
         }
 
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+        invalidate_deployment_authorization_contexts(app_state, self.deployment_id).await;
+
         Ok(())
     }
 }
 
+/// The redirect settings an update gives a non-empty value.
+fn redirect_url_updates(settings: &DeploymentDisplaySettingsUpdates) -> Vec<(&'static str, &str)> {
+    [
+        ("sign_in_page_url", &settings.sign_in_page_url),
+        ("sign_up_page_url", &settings.sign_up_page_url),
+        (
+            "after_sign_out_one_page_url",
+            &settings.after_sign_out_one_page_url,
+        ),
+        (
+            "after_sign_out_all_page_url",
+            &settings.after_sign_out_all_page_url,
+        ),
+        ("after_logo_click_url", &settings.after_logo_click_url),
+        (
+            "organization_profile_url",
+            &settings.organization_profile_url,
+        ),
+        ("create_organization_url", &settings.create_organization_url),
+        ("user_profile_url", &settings.user_profile_url),
+        (
+            "after_signup_redirect_url",
+            &settings.after_signup_redirect_url,
+        ),
+        (
+            "after_signin_redirect_url",
+            &settings.after_signin_redirect_url,
+        ),
+        (
+            "after_create_organization_redirect_url",
+            &settings.after_create_organization_redirect_url,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(field, value)| {
+        value
+            .as_deref()
+            .filter(|value| !value.is_empty())
+            .map(|value| (field, value))
+    })
+    .collect()
+}
+
+pub(crate) fn check_redirect_urls(validation: RedirectUrlValidation) -> Result<(), AppError> {
+    if validation.is_valid() {
+        return Ok(());
+    }
+    Err(
+        AppError::coded(ErrorCode::RedirectUrlNotAllowed, validation.summary())
+            .with_details(serde_json::to_value(&validation)?),
+    )
+}
+
 pub struct UpdateDeploymentDisplaySettingsCommand {
     deployment_id: i64,
     settings: DeploymentDisplaySettingsUpdates,
@@ -651,7 +1116,54 @@ impl UpdateDeploymentDisplaySettingsCommand {
 impl Command for UpdateDeploymentDisplaySettingsCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let allowed_redirect_origins = match &self.settings.allowed_redirect_origins {
+            Some(origins) => {
+                let mut normalized: Vec<String> = Vec::with_capacity(origins.len());
+                for origin in origins {
+                    let origin = normalize_redirect_origin(origin).ok_or_else(|| {
+                        AppError::BadRequest(format!("{} is not a valid redirect origin", origin))
+                    })?;
+                    if !normalized.contains(&origin) {
+                        normalized.push(origin);
+                    }
+                }
+                Some(normalized)
+            }
+            None => None,
+        };
+
+        // Only the URLs being set are checked; stored ones that no longer
+        // fit the policy are reported by `ValidateRedirectUrlsQuery`.
+        let redirect_urls = redirect_url_updates(&self.settings);
+        if !redirect_urls.is_empty() {
+            let row = sqlx::query(
+                r#"
+                SELECT deployments.frontend_host, deployment_ui_settings.allowed_redirect_origins
+                FROM deployments
+                JOIN deployment_ui_settings
+                    ON deployment_ui_settings.deployment_id = deployments.id
+                WHERE deployments.id = $1 AND deployments.deleted_at IS NULL
+                "#,
+            )
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "Display settings for deployment {} not found",
+                    self.deployment_id
+                ))
+            })?;
+            let frontend_host: String = row.get("frontend_host");
+            let stored_origins: Vec<String> = row.get("allowed_redirect_origins");
+            let origins = allowed_redirect_origins.as_ref().unwrap_or(&stored_origins);
+
+            check_redirect_urls(
+                RedirectUrlPolicy::new(&frontend_host, origins).validate(redirect_urls),
+            )?;
+        }
+
         let mut query_builder =
             sqlx::QueryBuilder::new("UPDATE deployment_ui_settings SET updated_at = NOW() ");
 
@@ -784,6 +1296,11 @@ impl Command for UpdateDeploymentDisplaySettingsCommand {
             query_builder.push_bind(after_create_organization_redirect_url);
         }
 
+        if let Some(allowed_redirect_origins) = allowed_redirect_origins {
+            query_builder.push(", allowed_redirect_origins = ");
+            query_builder.push_bind(allowed_redirect_origins);
+        }
+
         query_builder.push(" WHERE deployment_id = ");
         query_builder.push_bind(self.deployment_id);
 
@@ -796,6 +1313,98 @@ impl Command for UpdateDeploymentDisplaySettingsCommand {
             )));
         }
 
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
         Ok(().into())
     }
 }
+
+/// Replaces a deployment's maintenance settings: the switch, the message end
+/// users are shown and the scheduled window. Recorded in the audit log.
+pub struct UpdateDeploymentMaintenanceCommand {
+    deployment_id: i64,
+    maintenance: DeploymentMaintenance,
+    initiated_by: Option<String>,
+}
+
+impl UpdateDeploymentMaintenanceCommand {
+    pub fn new(deployment_id: i64, maintenance: DeploymentMaintenance) -> Self {
+        Self {
+            deployment_id,
+            maintenance,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for UpdateDeploymentMaintenanceCommand {
+    type Output = DeploymentMaintenance;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let message = self
+            .maintenance
+            .message
+            .as_deref()
+            .map(str::trim)
+            .filter(|message| !message.is_empty())
+            .map(str::to_string);
+
+        let project_id: i64 = sqlx::query_scalar(
+            r#"
+            UPDATE deployments
+            SET maintenance_mode = $2,
+                maintenance_message = $3,
+                maintenance_starts_at = $4,
+                maintenance_ends_at = $5,
+                updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING project_id
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.maintenance.maintenance_mode)
+        .bind(&message)
+        .bind(self.maintenance.starts_at)
+        .bind(self.maintenance.ends_at)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.deployment_id
+            ))
+        })?;
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
+        let maintenance = DeploymentMaintenance {
+            message,
+            ..self.maintenance
+        };
+
+        RecordAuditLogCommand::new(
+            project_id,
+            "deployment.maintenance_updated",
+            "deployment",
+            self.deployment_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(serde_json::to_value(&maintenance)?)
+        .execute(app_state)
+        .await?;
+
+        Ok(maintenance)
+    }
+}
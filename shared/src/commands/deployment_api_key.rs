@@ -0,0 +1,188 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    commands::Command,
+    error::AppError,
+    models::{CreatedDeploymentApiKey, DeploymentApiKey, DeploymentMode},
+    queries::{API_KEY_COLUMNS, deployment_api_key_from_row},
+    state::AppState,
+};
+
+/// Prefix plus eight characters of the random part.
+const KEY_PREFIX_LENGTH: usize = 16;
+
+fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+pub struct CreateDeploymentApiKeyCommand {
+    deployment_id: i64,
+    name: String,
+    scopes: Vec<String>,
+    expires_at: Option<DateTime<Utc>>,
+    created_by: Option<String>,
+}
+
+impl CreateDeploymentApiKeyCommand {
+    pub fn new(deployment_id: i64, name: impl Into<String>, scopes: Vec<String>) -> Self {
+        Self {
+            deployment_id,
+            name: name.into(),
+            scopes,
+            expires_at: None,
+            created_by: None,
+        }
+    }
+
+    pub fn with_expires_at(mut self, expires_at: Option<DateTime<Utc>>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    pub fn with_created_by(mut self, created_by: Option<String>) -> Self {
+        self.created_by = created_by;
+        self
+    }
+}
+
+impl Command for CreateDeploymentApiKeyCommand {
+    type Output = CreatedDeploymentApiKey;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Err(AppError::Validation(
+                "expires_at must be in the future".to_string(),
+            ));
+        }
+
+        let mode: String = sqlx::query_scalar("SELECT mode FROM deployments WHERE id = $1")
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+        let prefix = match DeploymentMode::from(mode) {
+            DeploymentMode::Production => "sk_live_",
+            DeploymentMode::Staging => "sk_test_",
+        };
+        let secret = format!("{}{}", prefix, hex::encode(rand::random::<[u8; 32]>()));
+
+        let mut scopes: Vec<String> = Vec::new();
+        for scope in self.scopes {
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO deployment_api_keys
+                (id, deployment_id, name, key_prefix, key_hash, scopes, created_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING {}
+            "#,
+            API_KEY_COLUMNS
+        ))
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(self.name.trim())
+        .bind(&secret[..KEY_PREFIX_LENGTH])
+        .bind(hash_api_key(&secret))
+        .bind(scopes)
+        .bind(self.created_by)
+        .bind(self.expires_at)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        Ok(CreatedDeploymentApiKey {
+            key: deployment_api_key_from_row(&row),
+            secret,
+        })
+    }
+}
+
+pub struct RevokeDeploymentApiKeyCommand {
+    deployment_id: i64,
+    key_id: i64,
+}
+
+impl RevokeDeploymentApiKeyCommand {
+    pub fn new(deployment_id: i64, key_id: i64) -> Self {
+        Self {
+            deployment_id,
+            key_id,
+        }
+    }
+}
+
+impl Command for RevokeDeploymentApiKeyCommand {
+    type Output = DeploymentApiKey;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE deployment_api_keys SET revoked_at = COALESCE(revoked_at, NOW())
+            WHERE deployment_id = $1 AND id = $2
+            RETURNING {}
+            "#,
+            API_KEY_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .bind(self.key_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("API key not found".to_string()))?;
+
+        Ok(deployment_api_key_from_row(&row))
+    }
+}
+
+/// Looks up a live key by its secret. `last_used_at` is only written when it
+/// is more than a minute old, so busy keys don't cost a write per request.
+pub struct AuthenticateDeploymentApiKeyCommand {
+    key: String,
+}
+
+impl AuthenticateDeploymentApiKeyCommand {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self { key: key.into() }
+    }
+}
+
+impl Command for AuthenticateDeploymentApiKeyCommand {
+    type Output = DeploymentApiKey;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM deployment_api_keys
+            WHERE key_hash = $1
+                AND revoked_at IS NULL
+                AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+            API_KEY_COLUMNS
+        ))
+        .bind(hash_api_key(&self.key))
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+        let key = deployment_api_key_from_row(&row);
+
+        sqlx::query(
+            r#"
+            UPDATE deployment_api_keys SET last_used_at = NOW()
+            WHERE id = $1
+                AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+            "#,
+        )
+        .bind(key.id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(key)
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    error::{AppError, ErrorCode},
+    models::DeploymentAssetKind,
+    services::{ImageOutputFormat, ProcessedImage, process_image},
+    state::AppState,
+};
+
+use super::{Command, PruneCdnPrefixCommand, UploadToCdnCommand};
+
+pub const MAX_DEPLOYMENT_ASSET_SIZE: usize = 2 * 1024 * 1024;
+
+/// Uploads one of a deployment's UI images to the CDN, with a thumbnail, and
+/// points its settings at it. Files are named after their content, so the
+/// CDN never serves the image it replaces, which is deleted once the
+/// settings no longer use it.
+pub struct UploadDeploymentAssetCommand {
+    deployment_id: i64,
+    kind: DeploymentAssetKind,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+impl UploadDeploymentAssetCommand {
+    pub fn new(
+        deployment_id: i64,
+        kind: DeploymentAssetKind,
+        content_type: impl Into<String>,
+        body: Vec<u8>,
+    ) -> Self {
+        Self {
+            deployment_id,
+            kind,
+            content_type: content_type.into(),
+            body,
+        }
+    }
+
+    fn check_image(&self) -> Result<ProcessedImage, AppError> {
+        if !self.content_type.starts_with("image/") {
+            return Err(AppError::coded(
+                ErrorCode::UnsupportedMediaType,
+                "Invalid file type. Only images are allowed.",
+            ));
+        }
+        if self.body.len() > MAX_DEPLOYMENT_ASSET_SIZE {
+            return Err(AppError::BadRequest(format!(
+                "The image is too large: {} bytes, more than the {} allowed",
+                self.body.len(),
+                MAX_DEPLOYMENT_ASSET_SIZE
+            )));
+        }
+
+        let image = process_image(&self.body, ImageOutputFormat::Png)?;
+        if let Some((max_width, max_height)) = self.kind.max_dimensions()
+            && (image.original_width > max_width || image.original_height > max_height)
+        {
+            return Err(AppError::BadRequest(format!(
+                "A {} can be at most {}x{} pixels, this image is {}x{}",
+                self.kind.as_str(),
+                max_width,
+                max_height,
+                image.original_width,
+                image.original_height
+            )));
+        }
+
+        Ok(image)
+    }
+}
+
+impl Command for UploadDeploymentAssetCommand {
+    type Output = String;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let image = self.check_image()?;
+
+        let directory = format!("deployments/{}", self.deployment_id);
+        let (image_path, thumbnail_path) = image.file_paths(&directory, self.kind.as_str());
+        UploadToCdnCommand::new(thumbnail_path.clone(), image.thumbnail.bytes)
+            .execute(app_state)
+            .await?;
+        let url = UploadToCdnCommand::new(image_path.clone(), image.image.bytes)
+            .execute(app_state)
+            .await?;
+
+        let result = sqlx::query(&format!(
+            "UPDATE deployment_ui_settings SET {} = $1, updated_at = NOW() WHERE deployment_id = $2",
+            self.kind.column()
+        ))
+        .bind(&url)
+        .bind(self.deployment_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "Display settings for deployment {} not found",
+                self.deployment_id
+            )));
+        }
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
+        // Images of this kind uploaded before, and their thumbnails. An
+        // external URL set for it is left alone.
+        let prefix = format!("{}/{}-", directory, self.kind.as_str());
+        if let Err(e) = PruneCdnPrefixCommand::new(prefix, vec![image_path, thumbnail_path])
+            .execute(app_state)
+            .await
+        {
+            tracing::warn!(
+                deployment_id = self.deployment_id,
+                "Failed to delete replaced {} images: {}",
+                self.kind.as_str(),
+                e
+            );
+        }
+
+        Ok(url)
+    }
+}
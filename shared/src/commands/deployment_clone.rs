@@ -0,0 +1,336 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::Utc;
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    error::AppError,
+    models::{
+        AUTH_SETTINGS_FIELDS, B2B_SETTINGS_FIELDS, Deployment, DeploymentMode,
+        DeploymentOrganizationRole, DeploymentWorkspaceRole, EMAIL_TEMPLATE_FIELDS,
+        OauthCredentials, ProvisioningStatus, RESTRICTIONS_FIELDS, SMS_TEMPLATE_FIELDS,
+        UI_SETTINGS_FIELDS, UI_SETTINGS_FRONTEND_URL_FIELDS,
+    },
+    services::KEY_PAIR_PRIVATE_KEY,
+    state::AppState,
+    utils::name::generate_random_name,
+};
+
+use super::Command;
+
+/// Creates a new staging deployment in the same project with the source
+/// deployment's configuration. Users, organizations and other end-user data
+/// are not copied, and the clone always gets its own signing key pair.
+pub struct CloneDeploymentCommand {
+    source_deployment_id: i64,
+    include_secrets: bool,
+}
+
+impl CloneDeploymentCommand {
+    pub fn new(source_deployment_id: i64) -> Self {
+        Self {
+            source_deployment_id,
+            include_secrets: false,
+        }
+    }
+
+    /// Copies social connection credentials as well. Without this the
+    /// connections are cloned with empty credentials.
+    pub fn with_include_secrets(mut self, include_secrets: bool) -> Self {
+        self.include_secrets = include_secrets;
+        self
+    }
+}
+
+impl Command for CloneDeploymentCommand {
+    type Output = Deployment;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let source = sqlx::query(
+            "SELECT project_id, frontend_host FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.source_deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.source_deployment_id
+            ))
+        })?;
+
+        let project_id: i64 = source.get("project_id");
+        let source_frontend_host: String = source.get("frontend_host");
+
+        let random_name = generate_random_name();
+        let count = app_state
+            .redis
+            .incr_counter(&format!("project_count:{}", random_name), 1, None)
+            .await?;
+
+        let hostname = format!("{}-{}", random_name, count);
+        let backend_host = app_state.domains.staging_backend_host(&hostname);
+        let frontend_host = app_state.domains.staging_frontend_host(&hostname);
+        let mail_from_host = app_state.domains.staging_mail_from_host.clone();
+        let publishable_key = format!(
+            "pk_test_{}",
+            BASE64_STANDARD.encode(format!("https://{}", backend_host))
+        );
+
+        let key_pair = rcgen::KeyPair::generate().map_err(|e| AppError::Internal(e.to_string()))?;
+
+        let deployment_id = app_state.sf.next_id()? as i64;
+        let now = Utc::now();
+        let mut tx = app_state.db_pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO deployments (
+                id, project_id, mode, backend_host, frontend_host, publishable_key,
+                maintenance_mode, mail_from_host, verification_status, created_at, updated_at
+            )
+            VALUES ($1, $2, 'staging', $3, $4, $5, false, $6, 'verified', $7, $7)
+            "#,
+        )
+        .bind(deployment_id)
+        .bind(project_id)
+        .bind(&backend_host)
+        .bind(&frontend_host)
+        .bind(&publishable_key)
+        .bind(&mail_from_host)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        for (table, fields) in [
+            ("deployment_auth_settings", AUTH_SETTINGS_FIELDS),
+            ("deployment_restrictions", RESTRICTIONS_FIELDS),
+            ("deployment_sms_templates", SMS_TEMPLATE_FIELDS),
+            ("deployment_email_templates", EMAIL_TEMPLATE_FIELDS),
+        ] {
+            copy_settings_row(
+                &mut tx,
+                table,
+                &fields.join(", "),
+                app_state.sf.next_id()? as i64,
+                self.source_deployment_id,
+                deployment_id,
+            )
+            .await?;
+        }
+
+        // The frontend URLs point at the source deployment's own host, so the
+        // clone gets them rewritten to its new one.
+        let ui_columns = UI_SETTINGS_FIELDS.join(", ");
+        let rewritten_urls = UI_SETTINGS_FRONTEND_URL_FIELDS
+            .iter()
+            .map(|column| format!("replace({column}, $4, $5)"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO deployment_ui_settings (
+                id, deployment_id, {ui_columns}, {}, created_at, updated_at
+            )
+            SELECT $1, $2, {ui_columns}, {rewritten_urls}, NOW(), NOW()
+            FROM deployment_ui_settings
+            WHERE deployment_id = $3
+            "#,
+            UI_SETTINGS_FRONTEND_URL_FIELDS.join(", "),
+        ))
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(deployment_id)
+        .bind(self.source_deployment_id)
+        .bind(&source_frontend_host)
+        .bind(&frontend_host)
+        .execute(&mut *tx)
+        .await?;
+
+        // Role ids are per deployment, so the clone gets fresh default roles
+        // instead of pointing at the source deployment's.
+        let workspace_admin = DeploymentWorkspaceRole::admin();
+        let workspace_member = DeploymentWorkspaceRole::member();
+        let org_admin = DeploymentOrganizationRole::admin();
+        let org_member = DeploymentOrganizationRole::member();
+        let roles = [
+            (
+                "workspace_roles",
+                workspace_admin.name,
+                workspace_admin.permissions,
+            ),
+            (
+                "workspace_roles",
+                workspace_member.name,
+                workspace_member.permissions,
+            ),
+            ("organization_roles", org_admin.name, org_admin.permissions),
+            (
+                "organization_roles",
+                org_member.name,
+                org_member.permissions,
+            ),
+        ];
+
+        let mut role_ids = Vec::with_capacity(roles.len());
+        for (table, name, permissions) in roles {
+            let role_id = app_state.sf.next_id()? as i64;
+            sqlx::query(&format!(
+                r#"
+                INSERT INTO {table} (id, deployment_id, name, permissions, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $5)
+                "#
+            ))
+            .bind(role_id)
+            .bind(deployment_id)
+            .bind(&name)
+            .bind(&permissions)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+            role_ids.push(role_id);
+        }
+
+        let b2b_columns = B2B_SETTINGS_FIELDS.join(", ");
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO deployment_b2b_settings (
+                id, deployment_id, {b2b_columns},
+                default_workspace_creator_role_id, default_workspace_member_role_id,
+                default_org_creator_role_id, default_org_member_role_id,
+                created_at, updated_at
+            )
+            SELECT $1, $2, {b2b_columns}, $4, $5, $6, $7, NOW(), NOW()
+            FROM deployment_b2b_settings
+            WHERE deployment_id = $3
+            "#
+        ))
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(deployment_id)
+        .bind(self.source_deployment_id)
+        .bind(role_ids[0])
+        .bind(role_ids[1])
+        .bind(role_ids[2])
+        .bind(role_ids[3])
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO deployment_key_pairs (id, deployment_id, public_key, private_key, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            "#,
+        )
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(deployment_id)
+        .bind(key_pair.public_key_pem())
+        .bind(
+            app_state
+                .credential_cipher
+                .seal(&key_pair.serialize_pem(), KEY_PAIR_PRIVATE_KEY)?,
+        )
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        let jwt_template_ids: Vec<i64> =
+            sqlx::query_scalar("SELECT id FROM deployment_jwt_templates WHERE deployment_id = $1")
+                .bind(self.source_deployment_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+        for source_id in jwt_template_ids {
+            sqlx::query(
+                r#"
+                INSERT INTO deployment_jwt_templates (
+                    id, created_at, updated_at, deployment_id, name, token_lifetime,
+                    allowed_clock_skew, custom_signing_key, template
+                )
+                SELECT $1, NOW(), NOW(), $2, name, token_lifetime, allowed_clock_skew,
+                    CASE WHEN $4 THEN custom_signing_key ELSE NULL END, template
+                FROM deployment_jwt_templates
+                WHERE id = $3
+                "#,
+            )
+            .bind(app_state.sf.next_id()? as i64)
+            .bind(deployment_id)
+            .bind(source_id)
+            .bind(self.include_secrets)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let empty_credentials = serde_json::to_value(OauthCredentials::default())?;
+        let social_connection_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT id FROM deployment_social_connections WHERE deployment_id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.source_deployment_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for source_id in social_connection_ids {
+            sqlx::query(
+                r#"
+                INSERT INTO deployment_social_connections (
+                    id, created_at, updated_at, deployment_id, provider, enabled, credentials
+                )
+                SELECT $1, NOW(), NOW(), $2, provider, enabled,
+                    CASE WHEN $4 THEN credentials ELSE $5 END
+                FROM deployment_social_connections
+                WHERE id = $3
+                "#,
+            )
+            .bind(app_state.sf.next_id()? as i64)
+            .bind(deployment_id)
+            .bind(source_id)
+            .bind(self.include_secrets)
+            .bind(&empty_credentials)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Deployment {
+            id: deployment_id,
+            created_at: now,
+            updated_at: now,
+            maintenance_mode: false,
+            backend_host,
+            frontend_host,
+            publishable_key,
+            project_id,
+            mode: DeploymentMode::Staging,
+            mail_from_host,
+            verification_status: Some(crate::models::VerificationStatus::Verified),
+            provisioning_status: ProvisioningStatus::Ready,
+            domain_verification_records: None,
+            email_verification_records: None,
+        })
+    }
+}
+
+/// Copies the single per-deployment row of a settings table to the new
+/// deployment. Missing source rows are skipped rather than treated as errors.
+async fn copy_settings_row(
+    conn: &mut PgConnection,
+    table: &str,
+    columns: &str,
+    id: i64,
+    source_deployment_id: i64,
+    deployment_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(&format!(
+        r#"
+        INSERT INTO {table} (id, deployment_id, {columns}, created_at, updated_at)
+        SELECT $1, $2, {columns}, NOW(), NOW()
+        FROM {table}
+        WHERE deployment_id = $3
+        "#
+    ))
+    .bind(id)
+    .bind(deployment_id)
+    .bind(source_deployment_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
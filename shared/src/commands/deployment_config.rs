@@ -0,0 +1,508 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde_json::{Map, Value};
+use sqlx::PgConnection;
+
+use crate::{
+    error::AppError,
+    models::{
+        CONFIG_SECTIONS, ConfigChange, ConfigChangeAction, DEPLOYMENT_CONFIG_SCHEMA_VERSION,
+        DeploymentConfigBundle, DeploymentConfigImportResult, JWT_TEMPLATE_FIELDS,
+        SOCIAL_CONNECTION_FIELDS,
+    },
+    queries::{JWT_TEMPLATE_SECRET_FIELD, SOCIAL_CONNECTION_SECRET_FIELD, read_deployment_config},
+    services::{JWT_TEMPLATE_SIGNING_KEY, SOCIAL_CONNECTION_CLIENT_SECRET},
+    state::AppState,
+};
+
+use super::Command;
+
+/// Applies a configuration bundle produced by `ExportDeploymentConfigQuery` to
+/// a deployment. The bundle describes the whole configuration: JWT templates
+/// and social connections missing from it are removed from the target.
+pub struct ImportDeploymentConfigCommand {
+    deployment_id: i64,
+    bundle: Value,
+    dry_run: bool,
+}
+
+impl ImportDeploymentConfigCommand {
+    /// Takes the bundle as raw JSON so its schema version can be checked before
+    /// the rest of it is parsed.
+    pub fn new(deployment_id: i64, bundle: Value) -> Self {
+        Self {
+            deployment_id,
+            bundle,
+            dry_run: false,
+        }
+    }
+
+    /// Computes the changes without applying them.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+impl Command for ImportDeploymentConfigCommand {
+    type Output = DeploymentConfigImportResult;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut bundle = parse_bundle(self.bundle)?;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        // Serializes imports into the same deployment so the diff stays valid
+        // until it has been applied.
+        sqlx::query("SELECT id FROM deployments WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+            .bind(self.deployment_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "Deployment with id {} not found",
+                    self.deployment_id
+                ))
+            })?;
+
+        let current = read_deployment_config(
+            &mut tx,
+            &app_state.credential_cipher,
+            self.deployment_id,
+            bundle.secrets_included,
+        )
+        .await?;
+
+        rebase_frontend_urls(&mut bundle, &current.source_frontend_host);
+        let changes = diff(&current, &bundle);
+
+        if self.dry_run || changes.is_empty() {
+            return Ok(DeploymentConfigImportResult {
+                dry_run: self.dry_run,
+                applied: false,
+                changes,
+            });
+        }
+
+        for section in &CONFIG_SECTIONS {
+            let Some(incoming) = bundle.section(section.name) else {
+                continue;
+            };
+            let fields: Vec<&str> = section
+                .all_fields()
+                .into_iter()
+                .filter(|field| incoming.contains_key(*field))
+                .collect();
+            if fields.is_empty() {
+                continue;
+            }
+
+            let values = Value::Object(incoming.clone());
+            if current.section(section.name).is_some() {
+                update_row(
+                    &mut tx,
+                    section.table,
+                    &fields,
+                    &values,
+                    self.deployment_id,
+                    None,
+                )
+                .await?;
+            } else {
+                insert_row(
+                    &mut tx,
+                    section.table,
+                    &fields,
+                    &values,
+                    app_state.sf.next_id()? as i64,
+                    self.deployment_id,
+                )
+                .await?;
+            }
+        }
+
+        let jwt_fields = importable_fields(JWT_TEMPLATE_FIELDS, JWT_TEMPLATE_SECRET_FIELD, &bundle);
+        let social_fields = importable_fields(
+            SOCIAL_CONNECTION_FIELDS,
+            SOCIAL_CONNECTION_SECRET_FIELD,
+            &bundle,
+        );
+
+        for change in &changes {
+            let (table, key_column, fields, secret) = match change.section.as_str() {
+                "jwt_templates" => (
+                    "deployment_jwt_templates",
+                    "name",
+                    &jwt_fields,
+                    &JWT_TEMPLATE_SIGNING_KEY,
+                ),
+                "social_connections" => (
+                    "deployment_social_connections",
+                    "provider",
+                    &social_fields,
+                    &SOCIAL_CONNECTION_CLIENT_SECRET,
+                ),
+                _ => continue,
+            };
+
+            // The diff compares plaintext, but only sealed secrets are stored.
+            let after = match &change.after {
+                Some(Value::Object(values)) => {
+                    let mut values = values.clone();
+                    secret.seal_in(&app_state.credential_cipher, &mut values)?;
+                    Some(Value::Object(values))
+                }
+                after => after.clone(),
+            };
+
+            match (change.action, &after) {
+                (ConfigChangeAction::Delete, _) => {
+                    sqlx::query(&format!(
+                        "DELETE FROM {table} WHERE deployment_id = $1 AND {key_column} = $2"
+                    ))
+                    .bind(self.deployment_id)
+                    .bind(&change.key)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                (ConfigChangeAction::Update, Some(values)) => {
+                    update_row(
+                        &mut tx,
+                        table,
+                        fields,
+                        values,
+                        self.deployment_id,
+                        Some((key_column, &change.key)),
+                    )
+                    .await?;
+                }
+                (ConfigChangeAction::Create, Some(values)) => {
+                    // A soft-deleted connection for the same provider still
+                    // holds the unique key, so it has to go first.
+                    if table == "deployment_social_connections" {
+                        sqlx::query(
+                            "DELETE FROM deployment_social_connections WHERE deployment_id = $1 AND provider = $2",
+                        )
+                        .bind(self.deployment_id)
+                        .bind(&change.key)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                    insert_row(
+                        &mut tx,
+                        table,
+                        fields,
+                        values,
+                        app_state.sf.next_id()? as i64,
+                        self.deployment_id,
+                    )
+                    .await?;
+                }
+                _ => {}
+            }
+        }
+
+        tx.commit().await?;
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
+        Ok(DeploymentConfigImportResult {
+            dry_run: false,
+            applied: true,
+            changes,
+        })
+    }
+}
+
+fn parse_bundle(raw: Value) -> Result<DeploymentConfigBundle, AppError> {
+    let version = raw
+        .get("schema_version")
+        .ok_or_else(|| AppError::Validation("The bundle has no schema_version".to_string()))?
+        .as_u64()
+        .ok_or_else(|| {
+            AppError::Validation("schema_version must be a positive integer".to_string())
+        })?;
+
+    if version > u64::from(DEPLOYMENT_CONFIG_SCHEMA_VERSION) {
+        return Err(AppError::Validation(format!(
+            "The bundle uses schema version {}, but this platform only supports up to version {}",
+            version, DEPLOYMENT_CONFIG_SCHEMA_VERSION
+        )));
+    }
+    if version == 0 {
+        return Err(AppError::Validation(
+            "schema_version 0 is not supported".to_string(),
+        ));
+    }
+
+    let bundle: DeploymentConfigBundle = serde_json::from_value(raw)
+        .map_err(|e| AppError::Validation(format!("Invalid configuration bundle: {}", e)))?;
+
+    for section in &CONFIG_SECTIONS {
+        if let Some(values) = bundle.section(section.name) {
+            check_fields(section.name, values, &section.all_fields())?;
+        }
+    }
+
+    check_entries(
+        "jwt_templates",
+        &bundle.jwt_templates,
+        JWT_TEMPLATE_FIELDS,
+        "name",
+    )?;
+    check_entries(
+        "social_connections",
+        &bundle.social_connections,
+        SOCIAL_CONNECTION_FIELDS,
+        "provider",
+    )?;
+
+    Ok(bundle)
+}
+
+fn check_fields(
+    section: &str,
+    values: &Map<String, Value>,
+    fields: &[&str],
+) -> Result<(), AppError> {
+    match values.keys().find(|key| !fields.contains(&key.as_str())) {
+        Some(key) => Err(AppError::Validation(format!(
+            "Unknown field `{}` in {}. The bundle may have been exported by a newer platform version",
+            key, section
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn check_entries(
+    section: &str,
+    entries: &[Map<String, Value>],
+    fields: &[&str],
+    key_field: &str,
+) -> Result<(), AppError> {
+    let mut seen = HashSet::new();
+    for entry in entries {
+        check_fields(section, entry, fields)?;
+
+        let key = entry
+            .get(key_field)
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                AppError::Validation(format!("Every entry in {} needs a {}", section, key_field))
+            })?;
+        if !seen.insert(key) {
+            return Err(AppError::Validation(format!(
+                "{} contains `{}` more than once",
+                section, key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Points URLs on the source deployment's frontend host at the target's.
+fn rebase_frontend_urls(bundle: &mut DeploymentConfigBundle, target_frontend_host: &str) {
+    let source_frontend_host = bundle.source_frontend_host.clone();
+    if source_frontend_host.is_empty() || source_frontend_host == target_frontend_host {
+        return;
+    }
+
+    for section in &CONFIG_SECTIONS {
+        let Some(Some(values)) = bundle.section_mut(section.name) else {
+            continue;
+        };
+        for field in section.frontend_url_fields {
+            if let Some(Value::String(url)) = values.get_mut(*field) {
+                *url = url.replace(&source_frontend_host, target_frontend_host);
+            }
+        }
+    }
+}
+
+fn importable_fields<'a>(
+    fields: &[&'a str],
+    secret_field: &str,
+    bundle: &DeploymentConfigBundle,
+) -> Vec<&'a str> {
+    fields
+        .iter()
+        .copied()
+        .filter(|field| bundle.secrets_included || *field != secret_field)
+        .collect()
+}
+
+fn diff(current: &DeploymentConfigBundle, bundle: &DeploymentConfigBundle) -> Vec<ConfigChange> {
+    let mut changes = Vec::new();
+
+    for section in &CONFIG_SECTIONS {
+        let Some(incoming) = bundle.section(section.name) else {
+            continue;
+        };
+        let existing = current.section(section.name);
+        let action = if existing.is_some() {
+            ConfigChangeAction::Update
+        } else {
+            ConfigChangeAction::Create
+        };
+
+        for (field, after) in incoming {
+            let before = existing.and_then(|values| values.get(field));
+            if before != Some(after) {
+                changes.push(ConfigChange {
+                    section: section.name.to_string(),
+                    key: field.clone(),
+                    action,
+                    before: before.cloned(),
+                    after: Some(after.clone()),
+                });
+            }
+        }
+    }
+
+    diff_entries(
+        &mut changes,
+        "jwt_templates",
+        &current.jwt_templates,
+        &bundle.jwt_templates,
+        "name",
+        JWT_TEMPLATE_SECRET_FIELD,
+        bundle.secrets_included,
+    );
+    diff_entries(
+        &mut changes,
+        "social_connections",
+        &current.social_connections,
+        &bundle.social_connections,
+        "provider",
+        SOCIAL_CONNECTION_SECRET_FIELD,
+        bundle.secrets_included,
+    );
+
+    changes
+}
+
+/// Diffs keyed collections entry by entry. When secrets aren't part of the
+/// bundle the secret field is left out, so redacted values never count as a
+/// change and the target keeps its own.
+fn diff_entries(
+    changes: &mut Vec<ConfigChange>,
+    section: &str,
+    current: &[Map<String, Value>],
+    incoming: &[Map<String, Value>],
+    key_field: &str,
+    secret_field: &str,
+    secrets_included: bool,
+) {
+    let by_key = |entries: &[Map<String, Value>]| -> BTreeMap<String, Value> {
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let key = entry.get(key_field)?.as_str()?.to_string();
+                let mut entry = entry.clone();
+                if !secrets_included {
+                    entry.remove(secret_field);
+                }
+                Some((key, Value::Object(entry)))
+            })
+            .collect()
+    };
+
+    let current = by_key(current);
+    let incoming = by_key(incoming);
+
+    for (key, after) in &incoming {
+        let before = current.get(key);
+        if before == Some(after) {
+            continue;
+        }
+        changes.push(ConfigChange {
+            section: section.to_string(),
+            key: key.clone(),
+            action: if before.is_some() {
+                ConfigChangeAction::Update
+            } else {
+                ConfigChangeAction::Create
+            },
+            before: before.cloned(),
+            after: Some(after.clone()),
+        });
+    }
+
+    for (key, before) in current {
+        if !incoming.contains_key(&key) {
+            changes.push(ConfigChange {
+                section: section.to_string(),
+                key,
+                action: ConfigChangeAction::Delete,
+                before: Some(before),
+                after: None,
+            });
+        }
+    }
+}
+
+/// Updates the given columns from a JSON object, letting Postgres convert each
+/// value to its column type. `key` narrows the update to one row of tables that
+/// hold several per deployment.
+async fn update_row(
+    conn: &mut PgConnection,
+    table: &str,
+    fields: &[&str],
+    values: &Value,
+    deployment_id: i64,
+    key: Option<(&str, &str)>,
+) -> Result<(), AppError> {
+    let assignments = fields
+        .iter()
+        .map(|field| format!("{field} = r.{field}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let key_filter = key
+        .map(|(column, _)| format!("AND t.{column} = $3"))
+        .unwrap_or_default();
+
+    let sql = format!(
+        r#"
+        UPDATE {table} t
+        SET {assignments}, updated_at = NOW()
+        FROM jsonb_populate_record(NULL::{table}, $1) r
+        WHERE t.deployment_id = $2 {key_filter}
+        "#
+    );
+
+    let mut query = sqlx::query(&sql).bind(values).bind(deployment_id);
+    if let Some((_, value)) = key {
+        query = query.bind(value);
+    }
+    query.execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+async fn insert_row(
+    conn: &mut PgConnection,
+    table: &str,
+    fields: &[&str],
+    values: &Value,
+    id: i64,
+    deployment_id: i64,
+) -> Result<(), AppError> {
+    let columns = fields.join(", ");
+
+    sqlx::query(&format!(
+        r#"
+        INSERT INTO {table} (id, deployment_id, {columns}, created_at, updated_at)
+        SELECT $1, $2, {columns}, NOW(), NOW()
+        FROM jsonb_populate_record(NULL::{table}, $3)
+        "#
+    ))
+    .bind(id)
+    .bind(deployment_id)
+    .bind(values)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,531 @@
+//! Data-portability exports of a deployment's end-user data.
+//!
+//! Requesting an export records a pending row and builds the archive in the
+//! background: every kind of record is written to its own newline-delimited
+//! JSON file, the files are zipped into a temporary file, and the archive is
+//! uploaded to the private exports bucket. Completed exports are deleted,
+//! archive and all, after [`DATA_EXPORT_RETENTION`].
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::Utc;
+use serde_json::json;
+use sqlx::Row;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    commands::RecordAuditLogCommand,
+    error::{AppError, ErrorCode},
+    models::DeploymentDataExport,
+    queries::{DATA_EXPORT_COLUMNS, data_export_from_row},
+    services::ObjectBody,
+    state::AppState,
+};
+
+use super::Command;
+
+/// How long a completed export can be downloaded.
+pub const DATA_EXPORT_RETENTION: chrono::Duration = chrono::Duration::days(7);
+
+/// An export that hasn't moved on to its next file for this long is taken to
+/// have died with its process.
+const STALLED_EXPORT_TIMEOUT: chrono::Duration = chrono::Duration::hours(1);
+
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+const DEFAULT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const CLEANUP_BATCH_SIZE: i64 = 100;
+
+/// The files of an archive and the records each holds. `$1` is the
+/// deployment. Every query selects a `cursor` to page by, which is left out
+/// of the file; ids are written as strings, as the API returns them.
+/// Passwords, second factor secrets and OAuth tokens are never exported.
+const EXPORT_DATASETS: &[(&str, &str)] = &[
+    (
+        "users.ndjson",
+        r#"
+        SELECT
+            u.id AS cursor, u.id::text AS id, u.created_at, u.updated_at,
+            u.first_name, u.last_name, u.username, u.disabled,
+            u.public_metadata, u.private_metadata
+        FROM users u
+        WHERE u.deployment_id = $1
+        "#,
+    ),
+    (
+        "email_addresses.ndjson",
+        r#"
+        SELECT
+            e.id AS cursor, e.id::text AS id, e.user_id::text AS user_id,
+            e.created_at, e.updated_at, e.email_address, e.is_primary,
+            e.verified, e.verified_at
+        FROM user_email_addresses e
+        WHERE e.deployment_id = $1
+        "#,
+    ),
+    (
+        "phone_numbers.ndjson",
+        r#"
+        SELECT
+            p.id AS cursor, p.id::text AS id, p.user_id::text AS user_id,
+            p.created_at, p.updated_at, p.phone_number, p.verified, p.verified_at
+        FROM user_phone_numbers p
+        JOIN users u ON u.id = p.user_id
+        WHERE u.deployment_id = $1
+        "#,
+    ),
+    (
+        "social_connections.ndjson",
+        r#"
+        SELECT
+            s.id AS cursor, s.id::text AS id, s.user_id::text AS user_id,
+            s.created_at, s.updated_at, s.provider, s.email_address
+        FROM social_connections s
+        JOIN users u ON u.id = s.user_id
+        WHERE u.deployment_id = $1
+        "#,
+    ),
+    (
+        "organizations.ndjson",
+        r#"
+        SELECT
+            o.id AS cursor, o.id::text AS id, o.created_at, o.updated_at,
+            o.name, o.description, o.image_url, o.member_count,
+            o.public_metadata, o.private_metadata
+        FROM organizations o
+        WHERE o.deployment_id = $1
+        "#,
+    ),
+    (
+        "workspaces.ndjson",
+        r#"
+        SELECT
+            w.id AS cursor, w.id::text AS id, w.organization_id::text AS organization_id,
+            w.created_at, w.updated_at, w.deleted_at, w.name, w.description,
+            w.image_url, w.member_count, w.public_metadata, w.private_metadata
+        FROM workspaces w
+        WHERE w.deployment_id = $1
+        "#,
+    ),
+    (
+        "organization_memberships.ndjson",
+        r#"
+        SELECT
+            om.id AS cursor, om.id::text AS id,
+            om.organization_id::text AS organization_id, om.user_id::text AS user_id,
+            om.created_at, om.updated_at,
+            ARRAY(
+                SELECT omr.organization_role_id::text
+                FROM organization_membership_roles omr
+                WHERE omr.organization_membership_id = om.id
+            ) AS role_ids
+        FROM organization_memberships om
+        JOIN organizations o ON o.id = om.organization_id
+        WHERE o.deployment_id = $1
+        "#,
+    ),
+    (
+        "workspace_memberships.ndjson",
+        r#"
+        SELECT
+            wm.id AS cursor, wm.id::text AS id,
+            wm.workspace_id::text AS workspace_id, wm.user_id::text AS user_id,
+            wm.created_at, wm.updated_at,
+            ARRAY(
+                SELECT wmr.workspace_role_id::text
+                FROM workspace_membership_roles wmr
+                WHERE wmr.workspace_membership_id = wm.id
+            ) AS role_ids
+        FROM workspace_memberships wm
+        JOIN workspaces w ON w.id = wm.workspace_id
+        WHERE w.deployment_id = $1
+        "#,
+    ),
+    (
+        "deployment_invitations.ndjson",
+        r#"
+        SELECT
+            i.id AS cursor, i.id::text AS id, i.created_at, i.updated_at,
+            i.first_name, i.last_name, i.email_address, i.expiry,
+            i.accepted_at, i.accepted_user_id::text AS accepted_user_id, i.revoked_at
+        FROM deployment_invitations i
+        WHERE i.deployment_id = $1
+        "#,
+    ),
+    (
+        "organization_invitations.ndjson",
+        r#"
+        SELECT
+            i.id AS cursor, i.id::text AS id, i.organization_id::text AS organization_id,
+            i.created_at, i.updated_at, i.email_address,
+            i.organization_role_id::text AS role_id, i.inviter_id::text AS inviter_id,
+            i.expiry, i.accepted_at, i.accepted_user_id::text AS accepted_user_id,
+            i.revoked_at
+        FROM organization_invitations i
+        WHERE i.deployment_id = $1
+        "#,
+    ),
+];
+
+fn export_object_key(deployment_id: i64, export_id: i64) -> String {
+    format!("exports/{}/{}.zip", deployment_id, export_id)
+}
+
+/// The archive being built, removed from disk however the export ends.
+struct TempArchive(PathBuf);
+
+impl Drop for TempArchive {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn archive_error(error: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Failed to write the export archive: {}", error))
+}
+
+/// Starts exporting the deployment's end-user data. Only one export runs per
+/// deployment at a time; requesting another meanwhile fails with
+/// `export_in_progress`.
+pub struct ExportDeploymentDataCommand {
+    deployment_id: i64,
+    requested_by: Option<String>,
+}
+
+impl ExportDeploymentDataCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            requested_by: None,
+        }
+    }
+
+    pub fn with_requested_by(mut self, requested_by: Option<String>) -> Self {
+        self.requested_by = requested_by;
+        self
+    }
+
+    async fn build_archive(
+        app_state: &AppState,
+        export_id: i64,
+        deployment_id: i64,
+        path: &Path,
+    ) -> Result<BTreeMap<String, i64>, AppError> {
+        let file = std::fs::File::create(path).map_err(archive_error)?;
+        let mut archive = ZipWriter::new(file);
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(true);
+
+        let mut record_counts = BTreeMap::new();
+
+        for (file_name, select) in EXPORT_DATASETS {
+            archive
+                .start_file(*file_name, options)
+                .map_err(archive_error)?;
+
+            let page_query = format!(
+                "SELECT t.cursor, (to_jsonb(t) - 'cursor')::text AS line FROM ({}) t \
+                 WHERE t.cursor > $2 ORDER BY t.cursor LIMIT $3",
+                select
+            );
+
+            let mut cursor = 0i64;
+            let mut count = 0i64;
+            loop {
+                // Archives aren't resumable; the export fails and can be
+                // requested again.
+                if app_state.background_tasks.is_shutting_down() {
+                    return Err(AppError::Internal(
+                        "The export was interrupted by a shutdown".to_string(),
+                    ));
+                }
+
+                let rows = sqlx::query(&page_query)
+                    .bind(deployment_id)
+                    .bind(cursor)
+                    .bind(EXPORT_PAGE_SIZE)
+                    .fetch_all(&app_state.db_pool)
+                    .await?;
+
+                for row in &rows {
+                    let line: String = row.get("line");
+                    archive.write_all(line.as_bytes()).map_err(archive_error)?;
+                    archive.write_all(b"\n").map_err(archive_error)?;
+                    cursor = row.get("cursor");
+                }
+                count += rows.len() as i64;
+
+                if (rows.len() as i64) < EXPORT_PAGE_SIZE {
+                    break;
+                }
+            }
+
+            record_counts.insert(file_name.to_string(), count);
+
+            sqlx::query(
+                "UPDATE deployment_data_exports SET updated_at = NOW(), record_counts = $2 WHERE id = $1",
+            )
+            .bind(export_id)
+            .bind(json!(record_counts))
+            .execute(&app_state.db_pool)
+            .await?;
+        }
+
+        archive.finish().map_err(archive_error)?;
+
+        Ok(record_counts)
+    }
+
+    async fn run_export(
+        app_state: &AppState,
+        export_id: i64,
+        deployment_id: i64,
+        object_key: &str,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE deployment_data_exports SET status = 'running', updated_at = NOW() WHERE id = $1",
+        )
+        .bind(export_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        let archive =
+            TempArchive(std::env::temp_dir().join(format!("deployment-export-{}.zip", export_id)));
+        let record_counts =
+            Self::build_archive(app_state, export_id, deployment_id, &archive.0).await?;
+
+        let size_bytes = std::fs::metadata(&archive.0).map_err(archive_error)?.len() as i64;
+
+        app_state
+            .storage
+            .exports()
+            .put_object(
+                object_key,
+                ObjectBody::File(archive.0.clone()),
+                Some("application/zip"),
+            )
+            .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE deployment_data_exports
+            SET status = 'completed', size_bytes = $2, record_counts = $3,
+                completed_at = NOW(), expires_at = $4, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(export_id)
+        .bind(size_bytes)
+        .bind(json!(record_counts))
+        .bind(Utc::now() + DATA_EXPORT_RETENTION)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl Command for ExportDeploymentDataCommand {
+    type Output = DeploymentDataExport;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let export_id = app_state.sf.next_id()? as i64;
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let object_key = export_object_key(self.deployment_id, export_id);
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let project_id: i64 = sqlx::query_scalar(
+            "SELECT project_id FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO deployment_data_exports (
+                id, created_at, updated_at, deployment_id, status, requested_by, object_key
+            )
+            VALUES ($1, NOW(), NOW(), $2, 'pending', $3, $4)
+            ON CONFLICT (deployment_id) WHERE status IN ('pending', 'running') DO NOTHING
+            RETURNING {}
+            "#,
+            DATA_EXPORT_COLUMNS
+        ))
+        .bind(export_id)
+        .bind(self.deployment_id)
+        .bind(&self.requested_by)
+        .bind(&object_key)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            let active: Option<i64> = sqlx::query_scalar(
+                "SELECT id FROM deployment_data_exports WHERE deployment_id = $1 AND status IN ('pending', 'running')",
+            )
+            .bind(self.deployment_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            return Err(AppError::coded(
+                ErrorCode::ExportInProgress,
+                "An export of this deployment is already running",
+            )
+            .with_details(json!({
+                "export_id": active.map(|id| id.to_string()),
+            })));
+        };
+        let export = data_export_from_row(&row)?;
+
+        RecordAuditLogCommand::new(
+            project_id,
+            "deployment.data_export_requested",
+            "deployment_data_export",
+            export_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.requested_by)
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let background_state = app_state.clone();
+        let deployment_id = self.deployment_id;
+
+        app_state.background_tasks.spawn(async move {
+            if let Err(e) =
+                Self::run_export(&background_state, export_id, deployment_id, &object_key).await
+            {
+                tracing::error!("Data export {} failed: {}", export_id, e);
+
+                let _ = sqlx::query(
+                    r#"
+                    UPDATE deployment_data_exports
+                    SET status = 'failed', error = $2, completed_at = NOW(), updated_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(export_id)
+                .bind(e.to_string())
+                .execute(&background_state.db_pool)
+                .await;
+            }
+        });
+
+        Ok(export)
+    }
+}
+
+/// Deletes the archives of exports past their retention and marks them
+/// expired, and fails exports whose process died while building them.
+/// Returns how many exports were expired.
+pub struct ExpireDataExportsCommand;
+
+impl ExpireDataExportsCommand {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for ExpireDataExportsCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for ExpireDataExportsCommand {
+    type Output = u64;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let stalled = sqlx::query(
+            r#"
+            UPDATE deployment_data_exports
+            SET status = 'failed', error = 'The export stopped without finishing',
+                completed_at = NOW(), updated_at = NOW()
+            WHERE status IN ('pending', 'running') AND updated_at < $1
+            "#,
+        )
+        .bind(Utc::now() - STALLED_EXPORT_TIMEOUT)
+        .execute(&app_state.db_pool)
+        .await?
+        .rows_affected();
+        if stalled > 0 {
+            tracing::warn!("Failed {} stalled data exports", stalled);
+        }
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, object_key FROM deployment_data_exports
+            WHERE status = 'completed' AND expires_at < NOW()
+            ORDER BY expires_at
+            LIMIT $1
+            "#,
+        )
+        .bind(CLEANUP_BATCH_SIZE)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        let bucket = app_state.storage.exports();
+        let mut expired = 0;
+        for row in rows {
+            let id: i64 = row.get("id");
+            let object_key: String = row.get("object_key");
+
+            if let Err(e) = bucket.delete_object(&object_key).await {
+                tracing::warn!(export_id = id, "Failed to delete expired export: {}", e);
+                continue;
+            }
+
+            expired += sqlx::query(
+                "UPDATE deployment_data_exports SET status = 'expired', updated_at = NOW() WHERE id = $1 AND status = 'completed'",
+            )
+            .bind(id)
+            .execute(&app_state.db_pool)
+            .await?
+            .rows_affected();
+        }
+
+        Ok(expired)
+    }
+}
+
+/// Runs [`ExpireDataExportsCommand`] every `EXPORT_CLEANUP_INTERVAL_SECS`
+/// (default an hour) until shutdown.
+pub fn spawn_export_cleanup(app_state: &AppState) {
+    let interval = std::env::var("EXPORT_CLEANUP_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CLEANUP_INTERVAL);
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = background_state.background_tasks.shutdown_requested() => break,
+            }
+
+            match ExpireDataExportsCommand::new()
+                .execute(&background_state)
+                .await
+            {
+                Ok(0) => {}
+                Ok(expired) => tracing::info!("Expired {} data exports", expired),
+                Err(e) => tracing::error!("Failed to expire data exports: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,524 @@
+//! Moving a production deployment to another custom domain.
+//!
+//! Starting a migration provisions the new hostnames and sending domain
+//! through the provisioning outbox while the deployment keeps serving from
+//! its current hosts. Once the customer's DNS for the new domain verifies,
+//! the hosts and publishable key are swapped in one transaction and the old
+//! resources are queued for deletion. Aborting leaves the deployment as it
+//! was and deletes whatever the migration had created.
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    commands::{
+        Command, CreateProductionDeploymentCommand, EnqueueDeploymentProvisioningCommand,
+        EnqueueResourceDeletionCommand, spawn_provisioning_dispatch,
+    },
+    error::{AppError, ErrorCode},
+    models::{
+        DeploymentDomainMigration, DomainMigrationStatus, EmailVerificationRecords,
+        UI_SETTINGS_FRONTEND_URL_FIELDS, VerificationStatus,
+    },
+    queries::{
+        DOMAIN_MIGRATION_COLUMNS, GetDomainMigrationStatusQuery, Query, domain_migration_from_row,
+    },
+    state::AppState,
+    validators::ProjectValidator,
+};
+
+/// Locks the deployment's migration in progress, if it has one.
+async fn lock_active_migration(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<Option<DeploymentDomainMigration>, AppError> {
+    let row = sqlx::query(&format!(
+        r#"
+        SELECT {} FROM deployment_domain_migrations
+        WHERE deployment_id = $1 AND status IN ('provisioning', 'verifying', 'failed')
+        FOR UPDATE
+        "#,
+        DOMAIN_MIGRATION_COLUMNS
+    ))
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row.as_ref().map(domain_migration_from_row))
+}
+
+/// Aborts the deployment's migration in progress: its pending creations are
+/// cancelled and whatever it already created is queued for deletion. The
+/// deployment itself was never changed, so there is nothing to put back.
+/// Returns the aborted migration's id.
+pub(crate) async fn abort_active_domain_migration(
+    app_state: &AppState,
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<Option<i64>, AppError> {
+    let Some(migration) = lock_active_migration(conn, deployment_id).await? else {
+        return Ok(None);
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE deployment_domain_migrations
+        SET status = $2, completed_at = NOW(), updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(migration.id)
+    .bind(DomainMigrationStatus::Aborted.as_str())
+    .execute(&mut *conn)
+    .await?;
+
+    // An action the dispatcher is running right now finishes as cancelled
+    // and removes what it created itself.
+    sqlx::query(
+        r#"
+        UPDATE deployment_provisioning_actions
+        SET status = 'cancelled', completed_at = NOW(), updated_at = NOW()
+        WHERE domain_migration_id = $1 AND action LIKE 'create_%'
+          AND status IN ('pending', 'failed')
+        "#,
+    )
+    .bind(migration.id)
+    .execute(&mut *conn)
+    .await?;
+
+    let results: Vec<Option<serde_json::Value>> = sqlx::query_scalar(
+        r#"
+        SELECT result FROM deployment_provisioning_actions
+        WHERE domain_migration_id = $1 AND action LIKE 'create_%' AND status = 'completed'
+        "#,
+    )
+    .bind(migration.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut deletion = EnqueueResourceDeletionCommand::new(deployment_id, migration.id);
+    for result in results.into_iter().flatten() {
+        deletion = deletion
+            .with_custom_hostname(
+                result
+                    .get("hostname_id")
+                    .and_then(|id| id.as_str())
+                    .map(str::to_string),
+            )
+            .with_postmark_domain(result.get("postmark_domain_id").and_then(|id| id.as_i64()));
+    }
+    deletion.execute_with(app_state, conn).await?;
+
+    Ok(Some(migration.id))
+}
+
+/// Starts moving a production deployment to `custom_domain`. The new
+/// hostnames get the same `accounts.`, `frontend.` and `wcmail.` prefixes a
+/// new production deployment would.
+pub struct ChangeDeploymentDomainCommand {
+    deployment_id: i64,
+    custom_domain: String,
+}
+
+impl ChangeDeploymentDomainCommand {
+    pub fn new(deployment_id: i64, custom_domain: String) -> Self {
+        Self {
+            deployment_id,
+            custom_domain: custom_domain.trim().to_lowercase(),
+        }
+    }
+}
+
+impl Command for ChangeDeploymentDomainCommand {
+    type Output = DeploymentDomainMigration;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        ProjectValidator::new().validate_domain_format(&self.custom_domain)?;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let deployment = sqlx::query(
+            r#"
+            SELECT mode, provisioning_status, frontend_host, backend_host, mail_from_host,
+                   pending_mail_from_host, publishable_key,
+                   domain_verification_records::jsonb AS domain_verification_records,
+                   email_verification_records::jsonb AS email_verification_records
+            FROM deployments
+            WHERE id = $1 AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        if deployment.get::<String, _>("mode") != "production" {
+            return Err(AppError::BadRequest(
+                "Only production deployments have a custom domain to change".to_string(),
+            ));
+        }
+        if deployment.get::<String, _>("provisioning_status") != "ready" {
+            return Err(AppError::BadRequest(
+                "The deployment's current domain has to finish provisioning first".to_string(),
+            ));
+        }
+
+        if deployment
+            .get::<Option<String>, _>("pending_mail_from_host")
+            .is_some()
+        {
+            return Err(AppError::BadRequest(
+                "The deployment's mail-from host change has to finish first".to_string(),
+            ));
+        }
+
+        if let Some(active) = lock_active_migration(&mut tx, self.deployment_id).await? {
+            return Err(AppError::coded(
+                ErrorCode::DomainMigrationInProgress,
+                "This deployment is already moving to another domain",
+            )
+            .with_details(serde_json::json!({ "migration_id": active.id.to_string() })));
+        }
+
+        let (frontend_host, backend_host, mail_from_host) =
+            CreateProductionDeploymentCommand::production_hosts(&self.custom_domain);
+
+        if deployment.get::<String, _>("frontend_host") == frontend_host {
+            return Err(AppError::BadRequest(
+                "The deployment already uses this domain".to_string(),
+            ));
+        }
+
+        // Taken either by a deployment or by another deployment's migration.
+        let existing: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM deployments
+            WHERE (frontend_host = $1 OR backend_host = $2 OR mail_from_host = $3)
+              AND deleted_at IS NULL
+            UNION ALL
+            SELECT deployment_id FROM deployment_domain_migrations
+            WHERE (new_frontend_host = $1 OR new_backend_host = $2 OR new_mail_from_host = $3)
+              AND status IN ('provisioning', 'verifying', 'failed')
+            LIMIT 1
+            "#,
+        )
+        .bind(&frontend_host)
+        .bind(&backend_host)
+        .bind(&mail_from_host)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(existing) = existing {
+            return Err(AppError::coded(
+                ErrorCode::DomainInUse,
+                format!(
+                    "Domain '{}' is already in use by another deployment",
+                    self.custom_domain
+                ),
+            )
+            .with_details(serde_json::json!({
+                "domain": self.custom_domain,
+                "deployment_id": existing.to_string(),
+            })));
+        }
+
+        let domain_verification_records = app_state
+            .cloudflare_service
+            .generate_domain_verification_records(
+                &frontend_host,
+                &backend_host,
+                &app_state.domains,
+            );
+
+        let migration_id = app_state.sf.next_id()? as i64;
+        sqlx::query(
+            r#"
+            INSERT INTO deployment_domain_migrations (
+                id, deployment_id, status,
+                old_frontend_host, old_backend_host, old_mail_from_host, old_publishable_key,
+                old_domain_verification_records, old_email_verification_records,
+                new_frontend_host, new_backend_host, new_mail_from_host,
+                new_domain_verification_records, new_email_verification_records
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            "#,
+        )
+        .bind(migration_id)
+        .bind(self.deployment_id)
+        .bind(DomainMigrationStatus::Provisioning.as_str())
+        .bind(deployment.get::<String, _>("frontend_host"))
+        .bind(deployment.get::<String, _>("backend_host"))
+        .bind(deployment.get::<String, _>("mail_from_host"))
+        .bind(deployment.get::<String, _>("publishable_key"))
+        .bind(deployment.get::<Option<serde_json::Value>, _>("domain_verification_records"))
+        .bind(deployment.get::<Option<serde_json::Value>, _>("email_verification_records"))
+        .bind(&frontend_host)
+        .bind(&backend_host)
+        .bind(&mail_from_host)
+        .bind(
+            serde_json::to_value(&domain_verification_records)
+                .map_err(|e| AppError::Serialization(e.to_string()))?,
+        )
+        .bind(
+            serde_json::to_value(EmailVerificationRecords::default())
+                .map_err(|e| AppError::Serialization(e.to_string()))?,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        EnqueueDeploymentProvisioningCommand::new(
+            self.deployment_id,
+            frontend_host,
+            backend_host,
+            mail_from_host,
+        )
+        .for_domain_migration(migration_id)
+        .execute_with(app_state, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        spawn_provisioning_dispatch(app_state, Some(self.deployment_id));
+
+        tracing::info!(
+            "Started moving deployment {} to domain {}",
+            self.deployment_id,
+            self.custom_domain
+        );
+
+        GetDomainMigrationStatusQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await
+    }
+}
+
+/// Checks the DNS records of the domain a deployment is moving to. Once they
+/// all verify, the deployment switches over: its hosts, verification records
+/// and publishable key become the new ones, its frontend URLs are rewritten,
+/// and the old hostnames and sending domain are queued for deletion.
+pub struct VerifyDeploymentDomainMigrationCommand {
+    deployment_id: i64,
+}
+
+impl VerifyDeploymentDomainMigrationCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for VerifyDeploymentDomainMigrationCommand {
+    type Output = DeploymentDomainMigration;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let migration = lock_active_migration(&mut tx, self.deployment_id)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(
+                    "No domain migration in progress for this deployment".to_string(),
+                )
+            })?;
+
+        if migration.status != DomainMigrationStatus::Verifying {
+            return Err(AppError::BadRequest(
+                "The new domain is still being provisioned".to_string(),
+            ));
+        }
+
+        let mut domain_verification_records = migration
+            .new_domain_verification_records
+            .unwrap_or_default();
+        let mut email_verification_records =
+            migration.new_email_verification_records.unwrap_or_default();
+
+        match app_state
+            .dns_verification_service
+            .verify_domain_records(
+                &mut domain_verification_records,
+                app_state.cloudflare_service.as_ref(),
+            )
+            .await
+        {
+            Ok(timing) => tracing::info!(
+                "Checked {} domain records for migration {} in {}ms ({} timed out)",
+                timing.records,
+                migration.id,
+                timing.total_ms,
+                timing.timed_out
+            ),
+            Err(e) => tracing::warn!("Failed to verify domain records: {}", e),
+        }
+        match app_state
+            .dns_verification_service
+            .verify_email_records(&mut email_verification_records)
+            .await
+        {
+            Ok(timing) => tracing::info!(
+                "Checked {} email records for migration {} in {}ms ({} timed out)",
+                timing.records,
+                migration.id,
+                timing.total_ms,
+                timing.timed_out
+            ),
+            Err(e) => tracing::warn!("Failed to verify email records: {}", e),
+        }
+
+        let verified = app_state
+            .dns_verification_service
+            .are_domain_records_verified(&domain_verification_records)
+            && app_state
+                .dns_verification_service
+                .are_email_records_verified(&email_verification_records);
+
+        let verification_status = VerificationStatus::from_records(
+            Some(&domain_verification_records),
+            Some(&email_verification_records),
+        );
+        let domain_verification_records = serde_json::to_value(&domain_verification_records)
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+        let email_verification_records = serde_json::to_value(&email_verification_records)
+            .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE deployment_domain_migrations
+            SET new_domain_verification_records = $2, new_email_verification_records = $3,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(migration.id)
+        .bind(&domain_verification_records)
+        .bind(&email_verification_records)
+        .execute(&mut *tx)
+        .await?;
+
+        if !verified {
+            tx.commit().await?;
+            return GetDomainMigrationStatusQuery::new(self.deployment_id)
+                .execute(app_state)
+                .await;
+        }
+
+        let publishable_key = format!(
+            "pk_live_{}",
+            BASE64_STANDARD.encode(format!("https://{}", migration.new_backend_host))
+        );
+
+        sqlx::query(
+            r#"
+            UPDATE deployments
+            SET frontend_host = $2, backend_host = $3, mail_from_host = $4, publishable_key = $5,
+                domain_verification_records = $6, email_verification_records = $7,
+                verification_status = $8, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&migration.new_frontend_host)
+        .bind(&migration.new_backend_host)
+        .bind(&migration.new_mail_from_host)
+        .bind(&publishable_key)
+        .bind(&domain_verification_records)
+        .bind(&email_verification_records)
+        .bind(verification_status.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        let rewritten_urls = UI_SETTINGS_FRONTEND_URL_FIELDS
+            .iter()
+            .map(|column| format!("{column} = replace({column}, $2, $3)"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        sqlx::query(&format!(
+            "UPDATE deployment_ui_settings SET {rewritten_urls}, updated_at = NOW() \
+             WHERE deployment_id = $1"
+        ))
+        .bind(self.deployment_id)
+        .bind(&migration.old_frontend_host)
+        .bind(&migration.new_frontend_host)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE deployment_domain_migrations
+            SET status = $2, completed_at = NOW(), updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(migration.id)
+        .bind(DomainMigrationStatus::Completed.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        let old_domain_records = migration
+            .old_domain_verification_records
+            .unwrap_or_default();
+        let old_email_records = migration.old_email_verification_records.unwrap_or_default();
+        EnqueueResourceDeletionCommand::new(self.deployment_id, migration.id)
+            .with_custom_hostname(old_domain_records.frontend_hostname_id)
+            .with_custom_hostname(old_domain_records.backend_hostname_id)
+            .with_postmark_domain(old_email_records.postmark_domain_id)
+            .execute_with(app_state, &mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+        spawn_provisioning_dispatch(app_state, Some(self.deployment_id));
+
+        tracing::info!(
+            "Deployment {} moved from {} to {}",
+            self.deployment_id,
+            migration.old_frontend_host,
+            migration.new_frontend_host
+        );
+
+        GetDomainMigrationStatusQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await
+    }
+}
+
+/// Abandons a deployment's domain migration. The deployment keeps serving
+/// from the domain it had, and the new hostnames and sending domain are
+/// deleted.
+pub struct AbortDeploymentDomainMigrationCommand {
+    deployment_id: i64,
+}
+
+impl AbortDeploymentDomainMigrationCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for AbortDeploymentDomainMigrationCommand {
+    type Output = DeploymentDomainMigration;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        abort_active_domain_migration(app_state, &mut tx, self.deployment_id)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(
+                    "No domain migration in progress for this deployment".to_string(),
+                )
+            })?;
+
+        tx.commit().await?;
+
+        spawn_provisioning_dispatch(app_state, Some(self.deployment_id));
+
+        GetDomainMigrationStatusQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await
+    }
+}
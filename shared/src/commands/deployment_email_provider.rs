@@ -0,0 +1,255 @@
+use chrono::Utc;
+use sqlx::Row;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{
+        DeploymentEmailProvider, EmailProviderCredentials, EmailProviderType, TestEmailResult,
+    },
+    queries::{EMAIL_PROVIDER_COLUMNS, email_provider_from_row},
+    services::EmailTransport,
+    state::AppState,
+};
+
+use super::Command;
+
+/// Binds stored credentials to their deployment.
+pub(crate) fn credentials_context(deployment_id: i64) -> String {
+    format!("deployment_email_provider:{}", deployment_id)
+}
+
+/// How a deployment's emails are sent.
+pub(crate) struct DeploymentEmailSender {
+    pub transport: EmailTransport,
+    /// `None` for the platform's provider.
+    pub provider: Option<EmailProviderType>,
+    pub from_address: Option<String>,
+}
+
+/// The deployment's own provider when it has one, otherwise the platform's.
+pub(crate) async fn deployment_email_sender(
+    app_state: &AppState,
+    deployment_id: i64,
+) -> Result<DeploymentEmailSender, AppError> {
+    let row = sqlx::query(
+        "SELECT from_address, encrypted_credentials FROM deployment_email_providers WHERE deployment_id = $1",
+    )
+    .bind(deployment_id)
+    .fetch_optional(&app_state.db_pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(DeploymentEmailSender {
+            transport: EmailTransport::Postmark(app_state.postmark_service.clone()),
+            provider: None,
+            from_address: None,
+        });
+    };
+
+    let credentials = app_state.credential_cipher.decrypt(
+        &row.get::<String, _>("encrypted_credentials"),
+        credentials_context(deployment_id).as_bytes(),
+    )?;
+    let credentials: EmailProviderCredentials = serde_json::from_slice(&credentials)?;
+
+    Ok(DeploymentEmailSender {
+        provider: Some(credentials.provider()),
+        transport: EmailTransport::from_credentials(credentials, &app_state.smtp_service),
+        from_address: row.get("from_address"),
+    })
+}
+
+async fn ensure_deployment_exists(
+    app_state: &AppState,
+    deployment_id: i64,
+) -> Result<(), AppError> {
+    let exists: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM deployments WHERE id = $1 AND deleted_at IS NULL")
+            .bind(deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?;
+
+    match exists {
+        Some(_) => Ok(()),
+        None => Err(AppError::NotFound(format!(
+            "Deployment with id {} not found",
+            deployment_id
+        ))),
+    }
+}
+
+/// Sends a deployment's emails through its own provider. The credentials are
+/// tried before they are saved, so a typo fails here and not on the next
+/// sign-up.
+pub struct SetDeploymentEmailProviderCommand {
+    deployment_id: i64,
+    credentials: EmailProviderCredentials,
+    from_address: Option<String>,
+}
+
+impl SetDeploymentEmailProviderCommand {
+    pub fn new(deployment_id: i64, credentials: EmailProviderCredentials) -> Self {
+        Self {
+            deployment_id,
+            credentials,
+            from_address: None,
+        }
+    }
+
+    pub fn with_from_address(mut self, from_address: Option<String>) -> Self {
+        self.from_address = from_address;
+        self
+    }
+}
+
+impl Command for SetDeploymentEmailProviderCommand {
+    type Output = DeploymentEmailProvider;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        ensure_deployment_exists(app_state, self.deployment_id).await?;
+
+        let transport =
+            EmailTransport::from_credentials(self.credentials.clone(), &app_state.smtp_service);
+        if let Err(e) = transport.verify().await {
+            return Err(AppError::coded(
+                ErrorCode::EmailProviderVerificationFailed,
+                format!("Couldn't sign in to {}: {}", transport.name(), e),
+            )
+            .with_details(serde_json::json!({
+                "provider": self.credentials.provider(),
+            })));
+        }
+
+        let encrypted = app_state.credential_cipher.encrypt(
+            &serde_json::to_vec(&self.credentials)?,
+            credentials_context(self.deployment_id).as_bytes(),
+        )?;
+        let id = app_state.sf.next_id()? as i64;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO deployment_email_providers
+                (id, deployment_id, provider, from_address, settings, encrypted_credentials, verified_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (deployment_id) DO UPDATE SET
+                provider = EXCLUDED.provider,
+                from_address = EXCLUDED.from_address,
+                settings = EXCLUDED.settings,
+                encrypted_credentials = EXCLUDED.encrypted_credentials,
+                verified_at = EXCLUDED.verified_at,
+                updated_at = NOW()
+            RETURNING {}
+            "#,
+            EMAIL_PROVIDER_COLUMNS
+        ))
+        .bind(id)
+        .bind(self.deployment_id)
+        .bind(self.credentials.provider().as_str())
+        .bind(&self.from_address)
+        .bind(self.credentials.settings())
+        .bind(encrypted)
+        .bind(Utc::now())
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        Ok(email_provider_from_row(&row))
+    }
+}
+
+/// Goes back to the platform's email provider.
+pub struct DeleteDeploymentEmailProviderCommand {
+    deployment_id: i64,
+}
+
+impl DeleteDeploymentEmailProviderCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for DeleteDeploymentEmailProviderCommand {
+    type Output = DeploymentEmailProvider;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            "DELETE FROM deployment_email_providers WHERE deployment_id = $1 RETURNING {}",
+            EMAIL_PROVIDER_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound("This deployment has no email provider of its own".to_string())
+        })?;
+
+        Ok(email_provider_from_row(&row))
+    }
+}
+
+/// Sends a short email through whichever provider the deployment's emails
+/// currently go through.
+pub struct SendTestEmailCommand {
+    deployment_id: i64,
+    to_address: String,
+}
+
+impl SendTestEmailCommand {
+    pub fn new(deployment_id: i64, to_address: String) -> Self {
+        Self {
+            deployment_id,
+            to_address,
+        }
+    }
+}
+
+impl Command for SendTestEmailCommand {
+    type Output = TestEmailResult;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mail_from_host: String = sqlx::query_scalar(
+            "SELECT mail_from_host FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.deployment_id
+            ))
+        })?;
+
+        let sender = deployment_email_sender(app_state, self.deployment_id).await?;
+        let from_address = sender
+            .from_address
+            .clone()
+            .unwrap_or_else(|| format!("notifications@{}", mail_from_host));
+
+        let html_body = format!(
+            "<p>This is a test email sent through {}.</p><p>If you can read it, the deployment's emails are being delivered.</p>",
+            sender.transport.name()
+        );
+        let text_body = format!(
+            "This is a test email sent through {}.\n\nIf you can read it, the deployment's emails are being delivered.",
+            sender.transport.name()
+        );
+
+        let message_id = sender
+            .transport
+            .send_email(
+                &from_address,
+                &self.to_address,
+                "Test email",
+                &html_body,
+                Some(&text_body),
+            )
+            .await?;
+
+        Ok(TestEmailResult {
+            provider: sender.provider,
+            from_address,
+            to_address: self.to_address,
+            message_id,
+        })
+    }
+}
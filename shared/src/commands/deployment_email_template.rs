@@ -1,14 +1,24 @@
+use serde_json::json;
+use sqlx::Row;
+
 use crate::{
-    error::AppError, state::AppState,
-    dto::params::deployment::DeploymentNameParams, models::EmailTemplate,
+    dto::params::deployment::DeploymentNameParams,
+    error::{AppError, ErrorCode},
+    models::{DeploymentEmailTemplate, EmailTemplate},
+    state::AppState,
+    validators::validate_email_template,
 };
 
-use super::Command;
+use super::{Command, RecordAuditLogCommand};
 
+/// Saves one of a deployment's email templates after validating it against
+/// the placeholders its type allows. Templates with warnings are only saved
+/// when `allow_warnings` is set.
 pub struct UpdateDeploymentEmailTemplateCommand {
     deployment_id: i64,
     template_name: DeploymentNameParams,
     template: EmailTemplate,
+    allow_warnings: bool,
 }
 
 impl UpdateDeploymentEmailTemplateCommand {
@@ -21,31 +31,35 @@ impl UpdateDeploymentEmailTemplateCommand {
             deployment_id,
             template_name,
             template,
+            allow_warnings: false,
         }
     }
+
+    pub fn with_allow_warnings(mut self, allow_warnings: bool) -> Self {
+        self.allow_warnings = allow_warnings;
+        self
+    }
 }
 
 impl Command for UpdateDeploymentEmailTemplateCommand {
     type Output = EmailTemplate;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let column_name = match self.template_name {
-            DeploymentNameParams::OrganizationInviteTemplate => "organization_invite_template",
-            DeploymentNameParams::VerificationCodeTemplate => "verification_code_template",
-            DeploymentNameParams::ResetPasswordCodeTemplate => "reset_password_code_template",
-            DeploymentNameParams::PrimaryEmailChangeTemplate => "primary_email_change_template",
-            DeploymentNameParams::PasswordChangeTemplate => "password_change_template",
-            DeploymentNameParams::PasswordRemoveTemplate => "password_remove_template",
-            DeploymentNameParams::SignInFromNewDeviceTemplate => "sign_in_from_new_device_template",
-            DeploymentNameParams::MagicLinkTemplate => "magic_link_template",
-            DeploymentNameParams::WaitlistSignupTemplate => "waitlist_signup_template",
-            DeploymentNameParams::WaitlistInviteTemplate => "waitlist_invite_template",
-            DeploymentNameParams::WorkspaceInviteTemplate => "workspace_invite_template",
-        };
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let validation = validate_email_template(self.template_name, &self.template);
+        let blocked = !validation.warnings.is_empty() && !self.allow_warnings;
+        if !validation.errors.is_empty() || blocked {
+            let message = if validation.errors.is_empty() {
+                "The email template has warnings. Save it with allow_warnings to keep them"
+            } else {
+                "The email template has errors"
+            };
+            return Err(AppError::coded(ErrorCode::InvalidEmailTemplate, message)
+                .with_details(serde_json::to_value(&validation)?));
+        }
 
         let query = format!(
             "UPDATE deployment_email_templates SET {} = $1, updated_at = NOW() WHERE deployment_id = $2 AND deleted_at IS NULL",
-            column_name
+            self.template_name.column_name()
         );
 
         let template_json = serde_json::to_value(&self.template)
@@ -60,3 +74,129 @@ impl Command for UpdateDeploymentEmailTemplateCommand {
         Ok(self.template)
     }
 }
+
+/// Puts one or all of a deployment's email templates back to the defaults a
+/// new deployment starts with.
+pub struct ResetDeploymentEmailTemplateCommand {
+    deployment_id: i64,
+    templates: Vec<DeploymentNameParams>,
+    initiated_by: Option<String>,
+}
+
+impl ResetDeploymentEmailTemplateCommand {
+    pub fn new(deployment_id: i64, template_name: DeploymentNameParams) -> Self {
+        Self {
+            deployment_id,
+            templates: vec![template_name],
+            initiated_by: None,
+        }
+    }
+
+    pub fn all(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            templates: DeploymentNameParams::ALL.to_vec(),
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for ResetDeploymentEmailTemplateCommand {
+    type Output = Vec<EmailTemplate>;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let defaults = DeploymentEmailTemplate::default();
+        let restored: Vec<EmailTemplate> = self
+            .templates
+            .iter()
+            .map(|name| default_template(&defaults, *name).clone())
+            .collect();
+
+        let assignments: Vec<String> = self
+            .templates
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("{} = ${}", name.column_name(), i + 2))
+            .collect();
+        let update = format!(
+            "UPDATE deployment_email_templates SET {}, updated_at = NOW() WHERE deployment_id = $1 AND deleted_at IS NULL",
+            assignments.join(", ")
+        );
+
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let project_id: i64 =
+            sqlx::query("SELECT project_id FROM deployments WHERE id = $1 AND deleted_at IS NULL")
+                .bind(self.deployment_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or_else(|| {
+                    AppError::NotFound(format!(
+                        "Deployment with id {} not found",
+                        self.deployment_id
+                    ))
+                })?
+                .get("project_id");
+
+        let mut query = sqlx::query(&update).bind(self.deployment_id);
+        for template in &restored {
+            query = query.bind(serde_json::to_value(template)?);
+        }
+        let result = query.execute(&mut *tx).await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "Email templates for deployment {} not found",
+                self.deployment_id
+            )));
+        }
+
+        let reset: Vec<&str> = self
+            .templates
+            .iter()
+            .map(|name| name.column_name())
+            .collect();
+        RecordAuditLogCommand::new(
+            project_id,
+            "deployment.email_templates_reset",
+            "deployment",
+            self.deployment_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({ "templates": reset }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(restored)
+    }
+}
+
+fn default_template(
+    defaults: &DeploymentEmailTemplate,
+    template_name: DeploymentNameParams,
+) -> &EmailTemplate {
+    match template_name {
+        DeploymentNameParams::OrganizationInviteTemplate => &defaults.organization_invite_template,
+        DeploymentNameParams::VerificationCodeTemplate => &defaults.verification_code_template,
+        DeploymentNameParams::ResetPasswordCodeTemplate => &defaults.reset_password_code_template,
+        DeploymentNameParams::PrimaryEmailChangeTemplate => &defaults.primary_email_change_template,
+        DeploymentNameParams::PasswordChangeTemplate => &defaults.password_change_template,
+        DeploymentNameParams::PasswordRemoveTemplate => &defaults.password_remove_template,
+        DeploymentNameParams::SignInFromNewDeviceTemplate => {
+            &defaults.sign_in_from_new_device_template
+        }
+        DeploymentNameParams::MagicLinkTemplate => &defaults.magic_link_template,
+        DeploymentNameParams::WaitlistSignupTemplate => &defaults.waitlist_signup_template,
+        DeploymentNameParams::WaitlistInviteTemplate => &defaults.waitlist_invite_template,
+        DeploymentNameParams::WorkspaceInviteTemplate => &defaults.workspace_invite_template,
+    }
+}
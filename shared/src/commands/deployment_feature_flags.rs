@@ -0,0 +1,75 @@
+use serde_json::{Map, Value};
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{DeploymentFeatureFlagSettings, DeploymentFeatureFlags},
+    state::AppState,
+};
+
+use super::Command;
+
+/// Sets some of a deployment's flags and leaves the others as they are. A
+/// null value clears the override, so the flag falls back to the platform
+/// default. Names and types are checked by the request's validation.
+pub struct UpdateDeploymentFeatureFlagsCommand {
+    deployment_id: i64,
+    flags: Map<String, Value>,
+}
+
+impl UpdateDeploymentFeatureFlagsCommand {
+    pub fn new(deployment_id: i64, flags: Map<String, Value>) -> Self {
+        Self {
+            deployment_id,
+            flags,
+        }
+    }
+}
+
+impl Command for UpdateDeploymentFeatureFlagsCommand {
+    type Output = DeploymentFeatureFlagSettings;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let exists: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM deployments WHERE id = $1 AND deleted_at IS NULL")
+                .bind(self.deployment_id)
+                .fetch_optional(&app_state.db_pool)
+                .await?;
+        if exists.is_none() {
+            return Err(AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.deployment_id
+            )));
+        }
+
+        // Merging in the database keeps concurrent updates of different flags
+        // from overwriting each other; nulls are dropped after the merge.
+        let row = sqlx::query(
+            r#"
+            INSERT INTO deployment_feature_flags (id, deployment_id, flags)
+            VALUES ($1, $2, jsonb_strip_nulls($3))
+            ON CONFLICT (deployment_id) DO UPDATE SET
+                flags = jsonb_strip_nulls(deployment_feature_flags.flags || $3),
+                updated_at = NOW()
+            RETURNING flags
+            "#,
+        )
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(Value::Object(self.flags))
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
+        let overrides = DeploymentFeatureFlags::from_stored(row.get("flags"));
+        Ok(DeploymentFeatureFlagSettings {
+            deployment_id: self.deployment_id,
+            flags: DeploymentFeatureFlags::effective(&overrides),
+            overrides,
+        })
+    }
+}
@@ -0,0 +1,243 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::Row;
+
+use crate::{
+    dto::json::{AcceptInvitationRequest, CreateUserRequest},
+    error::{AppError, ErrorCode},
+    models::UserWithIdentifiers,
+    state::AppState,
+};
+
+use super::{Command, CreateUserCommand};
+
+struct InvitationState {
+    first_name: String,
+    last_name: String,
+    email_address: String,
+    expiry: DateTime<Utc>,
+    accepted_at: Option<DateTime<Utc>>,
+    accepted_user_id: Option<i64>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+async fn fetch_invitation(
+    app_state: &AppState,
+    deployment_id: i64,
+    invitation_id: i64,
+) -> Result<InvitationState, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT first_name, last_name, email_address, expiry,
+               accepted_at, accepted_user_id, revoked_at
+        FROM deployment_invitations
+        WHERE id = $1 AND deployment_id = $2
+        "#,
+    )
+    .bind(invitation_id)
+    .bind(deployment_id)
+    .fetch_optional(&app_state.db_pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Invitation not found".to_string()))?;
+
+    Ok(InvitationState {
+        first_name: row.try_get("first_name")?,
+        last_name: row.try_get("last_name")?,
+        email_address: row.try_get("email_address")?,
+        expiry: row.try_get("expiry")?,
+        accepted_at: row.try_get("accepted_at")?,
+        accepted_user_id: row.try_get("accepted_user_id")?,
+        revoked_at: row.try_get("revoked_at")?,
+    })
+}
+
+impl InvitationState {
+    /// Why the invitation can't be accepted, if it can't.
+    fn unusable(&self) -> Option<AppError> {
+        if self.accepted_at.is_some() {
+            return Some(
+                AppError::coded(
+                    ErrorCode::InvitationAlreadyAccepted,
+                    "The invitation has already been accepted",
+                )
+                .with_details(json!({
+                    "user_id": self.accepted_user_id.map(|id| id.to_string()),
+                })),
+            );
+        }
+        if self.revoked_at.is_some() {
+            return Some(AppError::BadRequest(
+                "The invitation has been revoked".to_string(),
+            ));
+        }
+        if self.expiry <= Utc::now() {
+            return Some(AppError::BadRequest(
+                "The invitation has expired".to_string(),
+            ));
+        }
+        None
+    }
+}
+
+/// Creates the invited user from a signed invitation link. The invitation is
+/// claimed before the user is created, so a link used twice at once creates
+/// one user.
+pub struct AcceptInvitationCommand {
+    deployment_id: i64,
+    request: AcceptInvitationRequest,
+}
+
+impl AcceptInvitationCommand {
+    pub fn new(deployment_id: i64, request: AcceptInvitationRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+}
+
+impl Command for AcceptInvitationCommand {
+    type Output = UserWithIdentifiers;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let claims = app_state
+            .invitation_token_signer
+            .verify(&self.request.token)?;
+        if claims.deployment_id != self.deployment_id || claims.organization_id.is_some() {
+            return Err(AppError::BadRequest(
+                "The invitation is invalid".to_string(),
+            ));
+        }
+
+        let invitation =
+            fetch_invitation(app_state, self.deployment_id, claims.invitation_id).await?;
+        if let Some(error) = invitation.unusable() {
+            return Err(error);
+        }
+
+        let claimed = sqlx::query(
+            r#"
+            UPDATE deployment_invitations
+            SET accepted_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL
+            "#,
+        )
+        .bind(claims.invitation_id)
+        .execute(&app_state.db_pool)
+        .await?
+        .rows_affected();
+
+        if claimed == 0 {
+            let invitation =
+                fetch_invitation(app_state, self.deployment_id, claims.invitation_id).await?;
+            return Err(invitation.unusable().unwrap_or_else(|| {
+                AppError::Internal("The invitation could not be claimed".to_string())
+            }));
+        }
+
+        let request = self.request;
+        let created = CreateUserCommand::new(
+            self.deployment_id,
+            CreateUserRequest {
+                first_name: request.first_name.unwrap_or(invitation.first_name),
+                last_name: request.last_name.unwrap_or(invitation.last_name),
+                email_address: Some(invitation.email_address),
+                phone_number: request.phone_number,
+                username: request.username,
+                password: request.password,
+            },
+        )
+        .execute(app_state)
+        .await;
+
+        let user = match created {
+            Ok(user) => user,
+            Err(e) => {
+                // Let the invitee fix the request and try the link again.
+                sqlx::query(
+                    r#"
+                    UPDATE deployment_invitations
+                    SET accepted_at = NULL, updated_at = NOW()
+                    WHERE id = $1 AND accepted_user_id IS NULL
+                    "#,
+                )
+                .bind(claims.invitation_id)
+                .execute(&app_state.db_pool)
+                .await?;
+                return Err(e);
+            }
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE deployment_invitations
+            SET accepted_user_id = $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(claims.invitation_id)
+        .bind(user.id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(user)
+    }
+}
+
+/// Stops an invitation that hasn't been accepted from being used.
+pub struct RevokeInvitationCommand {
+    deployment_id: i64,
+    invitation_id: i64,
+}
+
+impl RevokeInvitationCommand {
+    pub fn new(deployment_id: i64, invitation_id: i64) -> Self {
+        Self {
+            deployment_id,
+            invitation_id,
+        }
+    }
+}
+
+impl Command for RevokeInvitationCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let invitation =
+            fetch_invitation(app_state, self.deployment_id, self.invitation_id).await?;
+        if invitation.accepted_at.is_some() {
+            return Err(invitation.unusable().unwrap_or_else(|| {
+                AppError::Internal("The invitation could not be revoked".to_string())
+            }));
+        }
+        if invitation.revoked_at.is_some() {
+            return Ok(());
+        }
+
+        let revoked = sqlx::query(
+            r#"
+            UPDATE deployment_invitations
+            SET revoked_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND deployment_id = $2 AND accepted_at IS NULL
+            "#,
+        )
+        .bind(self.invitation_id)
+        .bind(self.deployment_id)
+        .execute(&app_state.db_pool)
+        .await?
+        .rows_affected();
+
+        // It was accepted or revoked since it was read.
+        if revoked == 0 {
+            let invitation =
+                fetch_invitation(app_state, self.deployment_id, self.invitation_id).await?;
+            if invitation.accepted_at.is_some()
+                && let Some(error) = invitation.unusable()
+            {
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,777 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::Row;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{
+        AuditLog, DeploymentLogExport, LogExportCredentials, LogExportDestinationTest,
+        LogExportExternalId, LogExportFormat, LogExportRun, LogExportSchedule,
+    },
+    queries::{LOG_EXPORT_COLUMNS, log_export_from_row, request_log_from_row},
+    services::{
+        ExportedLog, ObjectBody, ObjectStorage, StorageBucket, connect_log_export_bucket,
+        encode_log_export, log_export_key, log_export_probe_key,
+    },
+    state::AppState,
+};
+
+use super::Command;
+
+/// How many entries go in one exported object.
+const EXPORT_BATCH_SIZE: i64 = 5000;
+
+/// Entries newer than this are left for the next run. Audit logs commit with
+/// their transaction and request logs are flushed in batches, so the newest
+/// entries can still be joined by older ones the export would skip past.
+const EXPORT_SETTLE_DELAY: Duration = Duration::minutes(5);
+
+/// The first retry after a failed run. Each further failure doubles it, up
+/// to the schedule's own interval.
+const BASE_RETRY_DELAY: Duration = Duration::minutes(5);
+
+/// How long a claimed export stays invisible to other schedulers. A process
+/// that dies mid-run leaves it to be picked up again once this runs out.
+const CLAIM_LEASE: Duration = Duration::minutes(30);
+
+/// How many due exports one scheduler tick claims.
+const DISPATCH_BATCH_SIZE: i64 = 10;
+
+const DEFAULT_SCHEDULER_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// Binds stored credentials to their deployment.
+pub(crate) fn log_export_credentials_context(deployment_id: i64) -> String {
+    format!("deployment_log_export:{}", deployment_id)
+}
+
+fn generate_external_id() -> String {
+    format!("wacht-{}", hex::encode(rand::random::<[u8; 16]>()))
+}
+
+/// The deployment's external ID for assuming its role, generated the first
+/// time it's needed.
+async fn log_export_external_id(
+    app_state: &AppState,
+    deployment_id: i64,
+) -> Result<String, AppError> {
+    sqlx::query_scalar(
+        r#"
+        UPDATE deployments
+        SET log_export_external_id = COALESCE(log_export_external_id, $2)
+        WHERE id = $1 AND deleted_at IS NULL
+        RETURNING log_export_external_id
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(generate_external_id())
+    .fetch_optional(&app_state.db_pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Deployment with id {} not found", deployment_id)))
+}
+
+fn retry_delay(consecutive_failures: i32, schedule: LogExportSchedule) -> Duration {
+    let exponent = consecutive_failures.saturating_sub(1).clamp(0, 16) as u32;
+    (BASE_RETRY_DELAY * 2i32.saturating_pow(exponent)).min(schedule.interval())
+}
+
+/// Writes a small object under the prefix and deletes it again, which needs
+/// the same permissions an export does.
+async fn probe_destination(bucket: &StorageBucket, prefix: &str) -> Result<String, AppError> {
+    let key = log_export_probe_key(prefix);
+
+    let result = async {
+        bucket
+            .put_object(
+                &key,
+                ObjectBody::Bytes(b"log export write test".to_vec()),
+                Some("text/plain"),
+            )
+            .await?;
+        bucket.delete_object(&key).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => Ok(key),
+        Err(e) => Err(AppError::coded(
+            ErrorCode::LogExportDestinationFailed,
+            format!("Couldn't write to the bucket {}: {}", bucket.name, e),
+        )
+        .with_details(json!({ "bucket": bucket.name }))),
+    }
+}
+
+/// A deployment's export settings with its credentials decrypted.
+struct LogExportTarget {
+    deployment_id: i64,
+    bucket: String,
+    region: String,
+    prefix: String,
+    format: LogExportFormat,
+    schedule: LogExportSchedule,
+    credentials: LogExportCredentials,
+    external_id: String,
+    audit_logs_after: Option<(DateTime<Utc>, i64)>,
+    request_logs_after: Option<(DateTime<Utc>, i64)>,
+    consecutive_failures: i32,
+}
+
+impl LogExportTarget {
+    async fn load(app_state: &AppState, deployment_id: i64) -> Result<Self, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT bucket, region, prefix, format, schedule, encrypted_credentials,
+                audit_logs_exported_through, audit_logs_exported_through_id,
+                request_logs_exported_through, request_logs_exported_through_id,
+                consecutive_failures
+            FROM deployment_log_exports
+            WHERE deployment_id = $1
+            "#,
+        )
+        .bind(deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("This deployment doesn't export its logs".to_string()))?;
+
+        let credentials = app_state.credential_cipher.decrypt(
+            &row.get::<String, _>("encrypted_credentials"),
+            log_export_credentials_context(deployment_id).as_bytes(),
+        )?;
+        let watermark = |timestamp: &str, id: &str| {
+            row.get::<Option<DateTime<Utc>>, _>(timestamp)
+                .zip(row.get::<Option<i64>, _>(id))
+        };
+
+        Ok(Self {
+            deployment_id,
+            bucket: row.get("bucket"),
+            region: row.get("region"),
+            prefix: row.get("prefix"),
+            format: LogExportFormat::from(row.get::<String, _>("format")),
+            schedule: LogExportSchedule::from(row.get::<String, _>("schedule")),
+            credentials: serde_json::from_slice(&credentials)?,
+            external_id: log_export_external_id(app_state, deployment_id).await?,
+            audit_logs_after: watermark(
+                "audit_logs_exported_through",
+                "audit_logs_exported_through_id",
+            ),
+            request_logs_after: watermark(
+                "request_logs_exported_through",
+                "request_logs_exported_through_id",
+            ),
+            consecutive_failures: row.get("consecutive_failures"),
+        })
+    }
+
+    async fn connect(&self) -> StorageBucket {
+        connect_log_export_bucket(
+            &self.bucket,
+            &self.region,
+            &self.credentials,
+            &self.external_id,
+        )
+        .await
+    }
+
+    async fn export_audit_logs(
+        &self,
+        app_state: &AppState,
+        bucket: &StorageBucket,
+        through: DateTime<Utc>,
+        run: &mut LogExportRun,
+    ) -> Result<(), AppError> {
+        let mut after = self
+            .audit_logs_after
+            .unwrap_or((DateTime::UNIX_EPOCH, i64::MIN));
+
+        loop {
+            let rows = sqlx::query(
+                r#"
+                SELECT id, created_at, project_id, deployment_id, actor, action,
+                    resource_type, resource_id, metadata
+                FROM console_audit_logs
+                WHERE deployment_id = $1
+                    AND (created_at, id) > ($2, $3)
+                    AND created_at <= $4
+                ORDER BY created_at, id
+                LIMIT $5
+                "#,
+            )
+            .bind(self.deployment_id)
+            .bind(after.0)
+            .bind(after.1)
+            .bind(through)
+            .bind(EXPORT_BATCH_SIZE)
+            .fetch_all(&app_state.db_pool)
+            .await?;
+
+            let logs: Vec<AuditLog> = rows
+                .iter()
+                .map(|row| AuditLog {
+                    id: row.get("id"),
+                    created_at: row.get("created_at"),
+                    project_id: row.get("project_id"),
+                    deployment_id: row.get("deployment_id"),
+                    actor: row.get("actor"),
+                    action: row.get("action"),
+                    resource_type: row.get("resource_type"),
+                    resource_id: row.get("resource_id"),
+                    metadata: row.get("metadata"),
+                })
+                .collect();
+            let (Some(first), Some(last)) = (logs.first(), logs.last()) else {
+                return Ok(());
+            };
+
+            let key = log_export_key(
+                &self.prefix,
+                ExportedLog::AuditLogs,
+                self.format,
+                first.created_at,
+                last.created_at,
+            );
+            bucket
+                .put_object(
+                    &key,
+                    ObjectBody::Bytes(encode_log_export(
+                        ExportedLog::AuditLogs,
+                        self.format,
+                        &logs,
+                    )?),
+                    Some(self.format.content_type()),
+                )
+                .await?;
+
+            after = (last.created_at, last.id);
+            sqlx::query(
+                r#"
+                UPDATE deployment_log_exports
+                SET audit_logs_exported_through = $2,
+                    audit_logs_exported_through_id = $3,
+                    updated_at = NOW()
+                WHERE deployment_id = $1
+                "#,
+            )
+            .bind(self.deployment_id)
+            .bind(after.0)
+            .bind(after.1)
+            .execute(&app_state.db_pool)
+            .await?;
+
+            run.audit_logs_exported += logs.len() as i64;
+            run.objects.push(key);
+
+            if (logs.len() as i64) < EXPORT_BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn export_request_logs(
+        &self,
+        app_state: &AppState,
+        bucket: &StorageBucket,
+        through: DateTime<Utc>,
+        run: &mut LogExportRun,
+    ) -> Result<(), AppError> {
+        let mut after = self
+            .request_logs_after
+            .map(|(timestamp, id)| (timestamp.timestamp_millis(), id));
+
+        loop {
+            let logs: Vec<_> = app_state
+                .clickhouse_service
+                .list_request_logs_after(
+                    self.deployment_id,
+                    after,
+                    through.timestamp_millis(),
+                    EXPORT_BATCH_SIZE as u32,
+                )
+                .await?
+                .into_iter()
+                .map(request_log_from_row)
+                .collect();
+            let (Some(first), Some(last)) = (logs.first(), logs.last()) else {
+                return Ok(());
+            };
+
+            let key = log_export_key(
+                &self.prefix,
+                ExportedLog::RequestLogs,
+                self.format,
+                first.timestamp,
+                last.timestamp,
+            );
+            bucket
+                .put_object(
+                    &key,
+                    ObjectBody::Bytes(encode_log_export(
+                        ExportedLog::RequestLogs,
+                        self.format,
+                        &logs,
+                    )?),
+                    Some(self.format.content_type()),
+                )
+                .await?;
+
+            after = Some((last.timestamp.timestamp_millis(), last.id));
+            sqlx::query(
+                r#"
+                UPDATE deployment_log_exports
+                SET request_logs_exported_through = $2,
+                    request_logs_exported_through_id = $3,
+                    updated_at = NOW()
+                WHERE deployment_id = $1
+                "#,
+            )
+            .bind(self.deployment_id)
+            .bind(last.timestamp)
+            .bind(last.id)
+            .execute(&app_state.db_pool)
+            .await?;
+
+            run.request_logs_exported += logs.len() as i64;
+            run.objects.push(key);
+
+            if (logs.len() as i64) < EXPORT_BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Ships a deployment's audit and request logs to a bucket of its own on a
+/// schedule. The bucket is written to before the settings are saved, so bad
+/// credentials fail here. Changing the destination carries on from where
+/// the last export stopped rather than exporting everything again. Roles
+/// are always assumed with the deployment's own external ID.
+pub struct SetDeploymentLogExportCommand {
+    deployment_id: i64,
+    bucket: String,
+    region: String,
+    credentials: LogExportCredentials,
+    prefix: Option<String>,
+    format: Option<LogExportFormat>,
+    schedule: Option<LogExportSchedule>,
+}
+
+impl SetDeploymentLogExportCommand {
+    pub fn new(
+        deployment_id: i64,
+        bucket: String,
+        region: String,
+        credentials: LogExportCredentials,
+    ) -> Self {
+        Self {
+            deployment_id,
+            bucket,
+            region,
+            credentials,
+            prefix: None,
+            format: None,
+            schedule: None,
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: Option<String>) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    pub fn with_format(mut self, format: Option<LogExportFormat>) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_schedule(mut self, schedule: Option<LogExportSchedule>) -> Self {
+        self.schedule = schedule;
+        self
+    }
+}
+
+impl Command for SetDeploymentLogExportCommand {
+    type Output = DeploymentLogExport;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let external_id = log_export_external_id(app_state, self.deployment_id).await?;
+
+        let prefix = self
+            .prefix
+            .as_deref()
+            .unwrap_or_default()
+            .trim_matches('/')
+            .to_string();
+        let bucket =
+            connect_log_export_bucket(&self.bucket, &self.region, &self.credentials, &external_id)
+                .await;
+        probe_destination(&bucket, &prefix).await?;
+
+        let encrypted = app_state.credential_cipher.encrypt(
+            &serde_json::to_vec(&self.credentials)?,
+            log_export_credentials_context(self.deployment_id).as_bytes(),
+        )?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO deployment_log_exports
+                (id, deployment_id, bucket, region, prefix, format, schedule, credentials,
+                 encrypted_credentials, next_run_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
+            ON CONFLICT (deployment_id) DO UPDATE SET
+                bucket = EXCLUDED.bucket,
+                region = EXCLUDED.region,
+                prefix = EXCLUDED.prefix,
+                format = EXCLUDED.format,
+                schedule = EXCLUDED.schedule,
+                credentials = EXCLUDED.credentials,
+                encrypted_credentials = EXCLUDED.encrypted_credentials,
+                next_run_at = EXCLUDED.next_run_at,
+                last_error = NULL,
+                consecutive_failures = 0,
+                updated_at = NOW()
+            RETURNING {}
+            "#,
+            LOG_EXPORT_COLUMNS
+        ))
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(&self.bucket)
+        .bind(&self.region)
+        .bind(prefix)
+        .bind(self.format.unwrap_or_default().as_str())
+        .bind(self.schedule.unwrap_or_default().as_str())
+        .bind(self.credentials.settings())
+        .bind(encrypted)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        Ok(log_export_from_row(&row))
+    }
+}
+
+/// The external ID to require in the trust policy of a role the platform
+/// assumes for the deployment's log export. Generated on the first call and
+/// the same afterwards.
+pub struct GetLogExportExternalIdCommand {
+    deployment_id: i64,
+}
+
+impl GetLogExportExternalIdCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for GetLogExportExternalIdCommand {
+    type Output = LogExportExternalId;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        Ok(LogExportExternalId {
+            deployment_id: self.deployment_id,
+            external_id: log_export_external_id(app_state, self.deployment_id).await?,
+        })
+    }
+}
+
+/// Stops exporting the deployment's logs.
+pub struct DeleteDeploymentLogExportCommand {
+    deployment_id: i64,
+}
+
+impl DeleteDeploymentLogExportCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for DeleteDeploymentLogExportCommand {
+    type Output = DeploymentLogExport;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            "DELETE FROM deployment_log_exports WHERE deployment_id = $1 RETURNING {}",
+            LOG_EXPORT_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("This deployment doesn't export its logs".to_string()))?;
+
+        Ok(log_export_from_row(&row))
+    }
+}
+
+/// Checks the saved credentials still work by writing a probe object to the
+/// bucket and deleting it. Fails with `log_export_destination_failed` when
+/// either step is refused.
+pub struct TestLogExportDestinationCommand {
+    deployment_id: i64,
+}
+
+impl TestLogExportDestinationCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for TestLogExportDestinationCommand {
+    type Output = LogExportDestinationTest;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let target = LogExportTarget::load(app_state, self.deployment_id).await?;
+        let bucket = target.connect().await;
+        let probe_key = probe_destination(&bucket, &target.prefix).await?;
+
+        Ok(LogExportDestinationTest {
+            bucket: target.bucket,
+            probe_key,
+            tested_at: Utc::now(),
+        })
+    }
+}
+
+/// Exports the audit and request logs added since the last run. Each
+/// object's watermark is saved once it's written, so a failed run is
+/// retried from where it stopped. Failures back off from five minutes up to
+/// the schedule's interval, and the project owner is emailed when a run
+/// fails after a successful one.
+pub struct RunLogExportCommand {
+    deployment_id: i64,
+}
+
+impl RunLogExportCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+
+    async fn export(
+        &self,
+        app_state: &AppState,
+        target: &LogExportTarget,
+    ) -> Result<LogExportRun, AppError> {
+        let bucket = target.connect().await;
+        let through = Utc::now() - EXPORT_SETTLE_DELAY;
+        let mut run = LogExportRun::default();
+
+        target
+            .export_audit_logs(app_state, &bucket, through, &mut run)
+            .await?;
+        target
+            .export_request_logs(app_state, &bucket, through, &mut run)
+            .await?;
+
+        Ok(run)
+    }
+}
+
+impl Command for RunLogExportCommand {
+    type Output = LogExportRun;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let target = LogExportTarget::load(app_state, self.deployment_id).await?;
+        let result = self.export(app_state, &target).await;
+
+        match &result {
+            Ok(_) => {
+                sqlx::query(
+                    r#"
+                    UPDATE deployment_log_exports
+                    SET last_success_at = NOW(),
+                        last_error = NULL,
+                        consecutive_failures = 0,
+                        next_run_at = $2,
+                        updated_at = NOW()
+                    WHERE deployment_id = $1
+                    "#,
+                )
+                .bind(self.deployment_id)
+                .bind(Utc::now() + target.schedule.interval())
+                .execute(&app_state.db_pool)
+                .await?;
+            }
+            Err(e) => {
+                let consecutive_failures = target.consecutive_failures + 1;
+                sqlx::query(
+                    r#"
+                    UPDATE deployment_log_exports
+                    SET last_error = $2,
+                        consecutive_failures = $3,
+                        next_run_at = $4,
+                        updated_at = NOW()
+                    WHERE deployment_id = $1
+                    "#,
+                )
+                .bind(self.deployment_id)
+                .bind(e.to_string())
+                .bind(consecutive_failures)
+                .bind(Utc::now() + retry_delay(consecutive_failures, target.schedule))
+                .execute(&app_state.db_pool)
+                .await?;
+
+                if consecutive_failures == 1 {
+                    notify_export_failure(app_state, &target, &e.to_string()).await;
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Emails the owner of the deployment's project that its log export has
+/// started failing. Delivery problems are only logged.
+async fn notify_export_failure(app_state: &AppState, target: &LogExportTarget, error: &str) {
+    let Ok(from) = std::env::var("CONSOLE_NOTIFICATION_FROM_EMAIL") else {
+        tracing::warn!("CONSOLE_NOTIFICATION_FROM_EMAIL is not set, skipping log export emails");
+        return;
+    };
+
+    let owner = sqlx::query(
+        r#"
+        SELECT a.email, p.name AS project_name
+        FROM deployments d
+        JOIN projects p ON p.id = d.project_id
+        JOIN console_accounts a ON a.id = p.owner_id
+        WHERE d.id = $1
+        "#,
+    )
+    .bind(target.deployment_id)
+    .fetch_optional(&app_state.db_pool)
+    .await;
+
+    let owner = match owner {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!(
+                deployment_id = target.deployment_id,
+                "Failed to look up who to tell about a failed log export: {}",
+                e
+            );
+            return;
+        }
+    };
+
+    let to: String = owner.get("email");
+    let project_name: String = owner.get("project_name");
+    let subject = format!("Log export for {} is failing", project_name);
+    let body = format!(
+        "<p>Exporting logs for <strong>{}</strong> to the bucket <strong>{}</strong> failed:</p><p>{}</p><p>The export will be retried automatically, and picks up from where it stopped once it succeeds.</p>",
+        handlebars::html_escape(&project_name),
+        handlebars::html_escape(&target.bucket),
+        handlebars::html_escape(error)
+    );
+
+    if let Err(e) = app_state
+        .postmark_service
+        .send_email(&from, &to, &subject, &body, None)
+        .await
+    {
+        tracing::error!("Failed to send log export failure email to {}: {}", to, e);
+    }
+}
+
+/// Claims the exports that are due and runs them. Returns how many ran.
+pub struct DispatchLogExportsCommand;
+
+impl DispatchLogExportsCommand {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for DispatchLogExportsCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for DispatchLogExportsCommand {
+    type Output = usize;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let deployment_ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE deployment_log_exports
+            SET next_run_at = $1
+            WHERE id IN (
+                SELECT e.id FROM deployment_log_exports e
+                JOIN deployments d ON d.id = e.deployment_id
+                WHERE e.next_run_at <= NOW() AND d.deleted_at IS NULL
+                ORDER BY e.next_run_at
+                LIMIT $2
+                FOR UPDATE OF e SKIP LOCKED
+            )
+            RETURNING deployment_id
+            "#,
+        )
+        .bind(Utc::now() + CLAIM_LEASE)
+        .bind(DISPATCH_BATCH_SIZE)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        for deployment_id in &deployment_ids {
+            if let Err(e) = RunLogExportCommand::new(*deployment_id)
+                .execute(app_state)
+                .await
+            {
+                tracing::warn!(deployment_id, "Log export failed: {}", e);
+            }
+        }
+
+        Ok(deployment_ids.len())
+    }
+}
+
+/// Runs [`DispatchLogExportsCommand`] every `LOG_EXPORT_SCHEDULER_INTERVAL_SECS`
+/// (default a minute) until shutdown.
+pub fn spawn_log_export_scheduler(app_state: &AppState) {
+    let interval = std::env::var("LOG_EXPORT_SCHEDULER_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(StdDuration::from_secs)
+        .unwrap_or(DEFAULT_SCHEDULER_INTERVAL);
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = background_state.background_tasks.shutdown_requested() => break,
+            }
+
+            if let Err(e) = DispatchLogExportsCommand::new()
+                .execute(&background_state)
+                .await
+            {
+                tracing::error!("Failed to dispatch log exports: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_up_to_the_schedule() {
+        assert_eq!(
+            retry_delay(1, LogExportSchedule::Daily),
+            Duration::minutes(5)
+        );
+        assert_eq!(
+            retry_delay(3, LogExportSchedule::Daily),
+            Duration::minutes(20)
+        );
+        assert_eq!(
+            retry_delay(10, LogExportSchedule::Hourly),
+            Duration::hours(1)
+        );
+        assert_eq!(retry_delay(40, LogExportSchedule::Daily), Duration::days(1));
+    }
+}
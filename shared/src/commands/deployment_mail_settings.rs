@@ -0,0 +1,512 @@
+//! Changing the host a production deployment sends email from, and rotating
+//! its DKIM key.
+//!
+//! A new mail-from host gets a Postmark domain of its own, held as the
+//! deployment's pending one while the customer adds its records. Emails keep
+//! going out from the current domain until the DNS check finds the new
+//! records, at which point the deployment switches over and the old domain is
+//! queued for deletion.
+
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    commands::{Command, DeleteExternalResourceJob, EnqueueJobCommand, ExternalResource},
+    error::{AppError, ErrorCode},
+    models::{
+        DeploymentMailSettings, DomainVerificationRecords, EmailVerificationRecords,
+        VerificationStatus,
+    },
+    queries::{
+        GetDeploymentMailSettingsQuery, MAIL_SETTINGS_COLUMNS, Query, mail_settings_from_row,
+    },
+    state::AppState,
+    validators::ProjectValidator,
+};
+
+/// Locks the deployment's row and reads its mail settings.
+async fn lock_mail_settings(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<(DeploymentMailSettings, sqlx::postgres::PgRow), AppError> {
+    let row = sqlx::query(&format!(
+        r#"
+        SELECT {}, mode, provisioning_status, frontend_host,
+               domain_verification_records::jsonb AS domain_verification_records
+        FROM deployments
+        WHERE id = $1 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+        MAIL_SETTINGS_COLUMNS
+    ))
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+    Ok((mail_settings_from_row(&row), row))
+}
+
+/// Queues the removal of a sending domain the deployment no longer uses.
+async fn enqueue_domain_removal(
+    app_state: &AppState,
+    conn: &mut PgConnection,
+    records: Option<&EmailVerificationRecords>,
+) -> Result<(), AppError> {
+    if let Some(postmark_domain_id) = records.and_then(|records| records.postmark_domain_id) {
+        EnqueueJobCommand::new(DeleteExternalResourceJob::new(
+            ExternalResource::PostmarkDomain(postmark_domain_id),
+        ))
+        .execute_with(app_state, conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Drops the deployment's pending mail-from host, if it has one, and queues
+/// its sending domain for deletion. Returns whether there was one.
+pub(crate) async fn cancel_pending_mail_from_host(
+    app_state: &AppState,
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<bool, AppError> {
+    let pending: Option<Option<serde_json::Value>> = sqlx::query_scalar(
+        r#"
+        SELECT pending_email_verification_records FROM deployments
+        WHERE id = $1 AND pending_mail_from_host IS NOT NULL
+        FOR UPDATE
+        "#,
+    )
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(records) = pending else {
+        return Ok(false);
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE deployments
+        SET pending_mail_from_host = NULL, pending_email_verification_records = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(deployment_id)
+    .execute(&mut *conn)
+    .await?;
+
+    let records: Option<EmailVerificationRecords> =
+        records.and_then(|v| serde_json::from_value(v).ok());
+    enqueue_domain_removal(app_state, conn, records.as_ref()).await?;
+
+    Ok(true)
+}
+
+/// Checks the records of the deployment's pending mail-from host, and once
+/// they all verify, makes it the host the deployment sends from. Its old
+/// sending domain is queued for deletion. Returns whether the deployment
+/// switched over.
+pub(crate) async fn verify_pending_mail_from_host(
+    app_state: &AppState,
+    deployment_id: i64,
+) -> Result<bool, AppError> {
+    let mut tx = app_state.db_pool.begin().await?;
+
+    let (settings, _) = lock_mail_settings(&mut tx, deployment_id).await?;
+    let Some(pending_mail_from_host) = settings.pending_mail_from_host else {
+        return Ok(false);
+    };
+
+    let mut pending_records = settings
+        .pending_email_verification_records
+        .unwrap_or_default();
+    match app_state
+        .dns_verification_service
+        .verify_email_records(&mut pending_records)
+        .await
+    {
+        Ok(timing) => tracing::info!(
+            "Checked {} records of pending mail-from host {} in {}ms ({} timed out)",
+            timing.records,
+            pending_mail_from_host,
+            timing.total_ms,
+            timing.timed_out
+        ),
+        Err(e) => tracing::warn!("Failed to verify pending email records: {}", e),
+    }
+
+    let verified = app_state
+        .dns_verification_service
+        .are_email_records_verified(&pending_records);
+    let pending_records_value = serde_json::to_value(&pending_records)
+        .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+    if !verified {
+        sqlx::query(
+            r#"
+            UPDATE deployments
+            SET pending_email_verification_records = $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(deployment_id)
+        .bind(&pending_records_value)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        return Ok(false);
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE deployments
+        SET mail_from_host = pending_mail_from_host, email_verification_records = $2,
+            pending_mail_from_host = NULL, pending_email_verification_records = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(&pending_records_value)
+    .execute(&mut *tx)
+    .await?;
+
+    enqueue_domain_removal(
+        app_state,
+        &mut tx,
+        settings.email_verification_records.as_ref(),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    app_state
+        .cache_invalidator
+        .invalidate_deployment(deployment_id)
+        .await;
+
+    tracing::info!(
+        "Deployment {} now sends email from {} instead of {}",
+        deployment_id,
+        pending_mail_from_host,
+        settings.mail_from_host
+    );
+
+    Ok(true)
+}
+
+/// Moves a production deployment's email to another subdomain of its custom
+/// domain, such as `mail` for `mail.example.com`. A Postmark domain is
+/// created for the new host straight away, and the deployment switches to
+/// it once its records verify through the DNS check. Asking for the host the
+/// deployment already sends from cancels a change in progress.
+pub struct UpdateDeploymentMailSettingsCommand {
+    deployment_id: i64,
+    mail_from_subdomain: String,
+}
+
+impl UpdateDeploymentMailSettingsCommand {
+    pub fn new(deployment_id: i64, mail_from_subdomain: String) -> Self {
+        Self {
+            deployment_id,
+            mail_from_subdomain: mail_from_subdomain.trim().to_lowercase(),
+        }
+    }
+
+    fn validate_subdomain(&self) -> Result<(), AppError> {
+        let subdomain = self.mail_from_subdomain.as_str();
+        let valid = !subdomain.is_empty()
+            && subdomain.len() <= 63
+            && !subdomain.starts_with('-')
+            && !subdomain.ends_with('-')
+            && subdomain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(AppError::BadRequest(
+                "The mail-from subdomain must be a single DNS label, such as 'mail'".to_string(),
+            ));
+        }
+
+        // The deployment's other hosts on the same domain.
+        if matches!(subdomain, "accounts" | "frontend") {
+            return Err(AppError::BadRequest(format!(
+                "'{}' is already used by the deployment's own hosts",
+                subdomain
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Command for UpdateDeploymentMailSettingsCommand {
+    type Output = DeploymentMailSettings;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        self.validate_subdomain()?;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let (settings, deployment) = lock_mail_settings(&mut tx, self.deployment_id).await?;
+
+        if deployment.get::<String, _>("mode") != "production" {
+            return Err(AppError::BadRequest(
+                "Only production deployments send email from their own domain".to_string(),
+            ));
+        }
+        if deployment.get::<String, _>("provisioning_status") != "ready" {
+            return Err(AppError::BadRequest(
+                "The deployment's domain has to finish provisioning first".to_string(),
+            ));
+        }
+
+        let migration_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM deployment_domain_migrations
+            WHERE deployment_id = $1 AND status IN ('provisioning', 'verifying', 'failed')
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(migration_id) = migration_id {
+            return Err(AppError::coded(
+                ErrorCode::DomainMigrationInProgress,
+                "The deployment is moving to another domain",
+            )
+            .with_details(serde_json::json!({ "migration_id": migration_id.to_string() })));
+        }
+
+        let frontend_host: String = deployment.get("frontend_host");
+        let custom_domain = frontend_host
+            .strip_prefix("accounts.")
+            .unwrap_or(&frontend_host);
+        let mail_from_host = format!("{}.{}", self.mail_from_subdomain, custom_domain);
+        ProjectValidator::new().validate_domain_format(&mail_from_host)?;
+
+        if mail_from_host == settings.mail_from_host {
+            if !cancel_pending_mail_from_host(app_state, &mut tx, self.deployment_id).await? {
+                return Err(AppError::BadRequest(
+                    "The deployment already sends email from this host".to_string(),
+                ));
+            }
+
+            tx.commit().await?;
+            tracing::info!(
+                "Cancelled the mail-from change of deployment {}",
+                self.deployment_id
+            );
+            return GetDeploymentMailSettingsQuery::new(self.deployment_id)
+                .execute(app_state)
+                .await;
+        }
+
+        if settings.pending_mail_from_host.as_deref() == Some(mail_from_host.as_str()) {
+            tx.commit().await?;
+            return Ok(settings);
+        }
+
+        let existing: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM deployments
+            WHERE (mail_from_host = $1 OR pending_mail_from_host = $1 OR frontend_host = $1
+                   OR backend_host = $1)
+              AND deleted_at IS NULL
+            LIMIT 1
+            "#,
+        )
+        .bind(&mail_from_host)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(existing) = existing {
+            return Err(AppError::coded(
+                ErrorCode::DomainInUse,
+                format!(
+                    "Host '{}' is already in use by another deployment",
+                    mail_from_host
+                ),
+            )
+            .with_details(serde_json::json!({
+                "domain": mail_from_host,
+                "deployment_id": existing.to_string(),
+            })));
+        }
+
+        // Looked up first, so a retry after a create that succeeded doesn't
+        // make a second domain.
+        let email_domain_service = &app_state.email_domain_service;
+        let postmark_domain = match email_domain_service.find_domain(&mail_from_host).await? {
+            Some(existing) => existing,
+            None => email_domain_service.create_domain(&mail_from_host).await?,
+        };
+        let pending_records =
+            email_domain_service.generate_email_verification_records(&postmark_domain);
+
+        // A change that was already in progress is replaced.
+        cancel_pending_mail_from_host(app_state, &mut tx, self.deployment_id).await?;
+
+        sqlx::query(
+            r#"
+            UPDATE deployments
+            SET pending_mail_from_host = $2, pending_email_verification_records = $3,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&mail_from_host)
+        .bind(
+            serde_json::to_value(&pending_records)
+                .map_err(|e| AppError::Serialization(e.to_string()))?,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        tracing::info!(
+            "Deployment {} is moving its email from {} to {}",
+            self.deployment_id,
+            settings.mail_from_host,
+            mail_from_host
+        );
+
+        GetDeploymentMailSettingsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await
+    }
+}
+
+/// Replaces the DKIM key of a production deployment's sending domain.
+/// Postmark keeps signing with the current key until the record of the new
+/// one verifies, so email keeps going out while the customer adds it.
+pub struct RotateDkimKeyCommand {
+    deployment_id: i64,
+}
+
+impl RotateDkimKeyCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for RotateDkimKeyCommand {
+    type Output = DeploymentMailSettings;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let (settings, deployment) = lock_mail_settings(&mut tx, self.deployment_id).await?;
+
+        if deployment.get::<String, _>("mode") != "production" {
+            return Err(AppError::BadRequest(
+                "Only production deployments have a DKIM key of their own".to_string(),
+            ));
+        }
+
+        let current_records = settings.email_verification_records.unwrap_or_default();
+        let postmark_domain_id = current_records.postmark_domain_id.ok_or_else(|| {
+            AppError::BadRequest("The deployment has no sending domain yet".to_string())
+        })?;
+
+        let postmark_domain = app_state
+            .email_domain_service
+            .rotate_dkim(postmark_domain_id)
+            .await?;
+        let email_verification_records = app_state
+            .email_domain_service
+            .generate_email_verification_records(&postmark_domain)
+            .with_checks_from(&current_records);
+
+        let domain_verification_records: Option<DomainVerificationRecords> = deployment
+            .get::<Option<serde_json::Value>, _>("domain_verification_records")
+            .and_then(|v| serde_json::from_value(v).ok());
+        let verification_status = VerificationStatus::from_records(
+            domain_verification_records.as_ref(),
+            Some(&email_verification_records),
+        );
+
+        sqlx::query(
+            r#"
+            UPDATE deployments
+            SET email_verification_records = $2, verification_status = $3, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(
+            serde_json::to_value(&email_verification_records)
+                .map_err(|e| AppError::Serialization(e.to_string()))?,
+        )
+        .bind(verification_status.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
+        tracing::info!(
+            "Started DKIM key rotation for deployment {} (Postmark domain {})",
+            self.deployment_id,
+            postmark_domain_id
+        );
+
+        GetDeploymentMailSettingsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::services::{EmailDomainApi, FakeEmailDomains};
+
+    const MAIL_FROM_HOST: &str = "mail.example.com";
+
+    #[tokio::test]
+    async fn rotated_records_keep_the_checks_of_unchanged_records() {
+        let email_domains = FakeEmailDomains::new();
+        let domain = email_domains.create_domain(MAIL_FROM_HOST).await.unwrap();
+
+        let checked_at = Utc::now();
+        let mut current = email_domains.generate_email_verification_records(&domain);
+        for record in current
+            .dkim_records
+            .iter_mut()
+            .chain(current.return_path_records.iter_mut())
+        {
+            record.verified = true;
+            record.verification_attempted_at = Some(checked_at);
+            record.last_verified_at = Some(checked_at);
+        }
+
+        let rotated = email_domains.rotate_dkim(domain.id).await.unwrap();
+        let records = email_domains
+            .generate_email_verification_records(&rotated)
+            .with_checks_from(&current);
+
+        assert_eq!(records.postmark_domain_id, Some(domain.id));
+        assert_eq!(records.dkim_records.len(), 1);
+        assert_ne!(records.dkim_records[0].name, current.dkim_records[0].name);
+        assert!(!records.dkim_records[0].verified);
+        assert_eq!(records.dkim_records[0].last_verified_at, None);
+
+        assert!(records.return_path_records[0].verified);
+        assert_eq!(
+            records.return_path_records[0].last_verified_at,
+            Some(checked_at)
+        );
+        assert!(!records.is_verified());
+    }
+}
@@ -0,0 +1,308 @@
+use sqlx::Row;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{
+        CONFIG_SECTIONS, DeploymentConfigImportResult, DeploymentPromotion, OauthCredentials,
+        PromotionSectionReport, PromotionSectionStatus,
+    },
+    queries::{ExportDeploymentConfigQuery, Query, SOCIAL_CONNECTION_SECRET_FIELD},
+    state::AppState,
+};
+
+use super::{Command, CreateProductionDeploymentCommand, ImportDeploymentConfigCommand};
+
+/// The role each of the b2b settings' default role columns points at.
+const DEFAULT_ROLE_COLUMNS: [(&str, &str); 4] = [
+    ("workspace_roles", "default_workspace_creator_role_id"),
+    ("workspace_roles", "default_workspace_member_role_id"),
+    ("organization_roles", "default_org_creator_role_id"),
+    ("organization_roles", "default_org_member_role_id"),
+];
+
+/// Creates the production deployment of a staging deployment's project and
+/// carries the staging configuration over to it.
+///
+/// The deployment is created exactly as `CreateProductionDeploymentCommand`
+/// would, then the staging configuration is applied to it the way a config
+/// bundle import is. Custom JWT signing keys are copied with their templates;
+/// OAuth credentials only when asked for, otherwise the connections come
+/// over with empty credentials. A section that fails to copy is reported
+/// rather than undoing the deployment, which already exists by then.
+pub struct PromoteStagingToProductionCommand {
+    staging_deployment_id: i64,
+    custom_domain: String,
+    copy_oauth_credentials: bool,
+}
+
+impl PromoteStagingToProductionCommand {
+    pub fn new(staging_deployment_id: i64, custom_domain: String) -> Self {
+        Self {
+            staging_deployment_id,
+            custom_domain,
+            copy_oauth_credentials: false,
+        }
+    }
+
+    pub fn with_copy_oauth_credentials(mut self, copy_oauth_credentials: bool) -> Self {
+        self.copy_oauth_credentials = copy_oauth_credentials;
+        self
+    }
+
+    /// Points the production deployment's default roles at roles with the
+    /// same names and permissions as the staging defaults, creating the ones
+    /// it doesn't have. Returns how many defaults were mapped.
+    async fn map_default_roles(
+        &self,
+        app_state: &AppState,
+        production_deployment_id: i64,
+    ) -> Result<usize, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+        let mut mapped = 0;
+
+        for (table, column) in DEFAULT_ROLE_COLUMNS {
+            let Some(source) = sqlx::query(&format!(
+                r#"
+                SELECT r.name, r.permissions
+                FROM deployment_b2b_settings b
+                JOIN {table} r ON r.id = b.{column}
+                WHERE b.deployment_id = $1
+                "#
+            ))
+            .bind(self.staging_deployment_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            else {
+                continue;
+            };
+
+            let name: String = source.get("name");
+            let permissions: Vec<String> = source.get("permissions");
+
+            let existing: Option<i64> = sqlx::query_scalar(&format!(
+                "SELECT id FROM {table} WHERE deployment_id = $1 AND name = $2"
+            ))
+            .bind(production_deployment_id)
+            .bind(&name)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let role_id = match existing {
+                Some(role_id) => {
+                    sqlx::query(&format!(
+                        "UPDATE {table} SET permissions = $2, updated_at = NOW() WHERE id = $1"
+                    ))
+                    .bind(role_id)
+                    .bind(&permissions)
+                    .execute(&mut *tx)
+                    .await?;
+                    role_id
+                }
+                None => {
+                    let role_id = app_state.sf.next_id()? as i64;
+                    sqlx::query(&format!(
+                        r#"
+                        INSERT INTO {table} (id, deployment_id, name, permissions, created_at, updated_at)
+                        VALUES ($1, $2, $3, $4, NOW(), NOW())
+                        "#
+                    ))
+                    .bind(role_id)
+                    .bind(production_deployment_id)
+                    .bind(&name)
+                    .bind(&permissions)
+                    .execute(&mut *tx)
+                    .await?;
+                    role_id
+                }
+            };
+
+            sqlx::query(&format!(
+                "UPDATE deployment_b2b_settings SET {column} = $2, updated_at = NOW() WHERE deployment_id = $1"
+            ))
+            .bind(production_deployment_id)
+            .bind(role_id)
+            .execute(&mut *tx)
+            .await?;
+            mapped += 1;
+        }
+
+        tx.commit().await?;
+        Ok(mapped)
+    }
+}
+
+impl Command for PromoteStagingToProductionCommand {
+    type Output = DeploymentPromotion;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let staging = sqlx::query(
+            "SELECT project_id, mode FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.staging_deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.staging_deployment_id
+            ))
+        })?;
+
+        if staging.get::<String, _>("mode") != "staging" {
+            return Err(AppError::BadRequest(
+                "Only staging deployments can be promoted".to_string(),
+            ));
+        }
+
+        let project_id: i64 = staging.get("project_id");
+
+        // The production path checks this too, but failing here keeps the
+        // staging configuration from being read for nothing.
+        let existing_production: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM deployments WHERE project_id = $1 AND mode = 'production' AND deleted_at IS NULL",
+        )
+        .bind(project_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        if existing_production.is_some() {
+            return Err(AppError::coded(
+                ErrorCode::ProductionDeploymentExists,
+                "A production deployment already exists for this project",
+            )
+            .with_details(serde_json::json!({ "project_id": project_id.to_string() })));
+        }
+
+        // Read before anything is created so a missing staging deployment
+        // can't leave a half-configured production one behind.
+        let mut bundle = ExportDeploymentConfigQuery::new(self.staging_deployment_id)
+            .with_include_secrets(true)
+            .execute(app_state)
+            .await?;
+
+        if !self.copy_oauth_credentials {
+            let empty_credentials = serde_json::to_value(OauthCredentials::default())?;
+            for connection in &mut bundle.social_connections {
+                connection.insert(
+                    SOCIAL_CONNECTION_SECRET_FIELD.to_string(),
+                    empty_credentials.clone(),
+                );
+            }
+        }
+
+        // Auth settings come over with the rest of the configuration, so the
+        // deployment only needs a valid starting point.
+        let deployment = CreateProductionDeploymentCommand::new(
+            project_id,
+            self.custom_domain.clone(),
+            vec!["email".to_string()],
+        )
+        .execute(app_state)
+        .await?;
+
+        let present: Vec<(&str, bool)> = CONFIG_SECTIONS
+            .iter()
+            .map(|section| (section.name, bundle.section(section.name).is_some()))
+            .chain([
+                ("jwt_templates", !bundle.jwt_templates.is_empty()),
+                ("social_connections", !bundle.social_connections.is_empty()),
+            ])
+            .collect();
+
+        let imported = match serde_json::to_value(&bundle) {
+            Ok(bundle) => {
+                ImportDeploymentConfigCommand::new(deployment.id, bundle)
+                    .execute(app_state)
+                    .await
+            }
+            Err(e) => Err(e.into()),
+        };
+
+        let mapped_roles = match &imported {
+            Ok(_) => Some(self.map_default_roles(app_state, deployment.id).await),
+            Err(_) => None,
+        };
+
+        let sections = present
+            .into_iter()
+            .map(|(section, present)| {
+                section_report(
+                    section,
+                    present,
+                    &imported,
+                    mapped_roles.as_ref(),
+                    self.copy_oauth_credentials,
+                )
+            })
+            .collect();
+
+        if let Err(e) = &imported {
+            tracing::error!(
+                "Promoted deployment {} to production deployment {} but its configuration failed to copy: {}",
+                self.staging_deployment_id,
+                deployment.id,
+                e
+            );
+        }
+
+        Ok(DeploymentPromotion {
+            deployment,
+            sections,
+        })
+    }
+}
+
+fn section_report(
+    section: &str,
+    present: bool,
+    imported: &Result<DeploymentConfigImportResult, AppError>,
+    mapped_roles: Option<&Result<usize, AppError>>,
+    copy_oauth_credentials: bool,
+) -> PromotionSectionReport {
+    let failed = |error: &AppError| PromotionSectionReport {
+        section: section.to_string(),
+        status: PromotionSectionStatus::Failed,
+        changes: 0,
+        note: Some(error.to_string()),
+    };
+
+    let result = match imported {
+        Ok(result) => result,
+        Err(e) => return failed(e),
+    };
+
+    let changes = result
+        .changes
+        .iter()
+        .filter(|change| change.section == section)
+        .count();
+    let status = if !present {
+        PromotionSectionStatus::Skipped
+    } else if changes > 0 {
+        PromotionSectionStatus::Copied
+    } else {
+        PromotionSectionStatus::Unchanged
+    };
+
+    let note = match section {
+        "b2b_settings" => match mapped_roles {
+            Some(Err(e)) => return failed(e),
+            Some(Ok(mapped)) if *mapped > 0 => Some(format!(
+                "{} default roles mapped to roles on the new deployment",
+                mapped
+            )),
+            _ => None,
+        },
+        "social_connections" if present && !copy_oauth_credentials => {
+            Some("OAuth credentials were left empty".to_string())
+        }
+        _ => None,
+    };
+
+    PromotionSectionReport {
+        section: section.to_string(),
+        status,
+        changes,
+        note,
+    }
+}
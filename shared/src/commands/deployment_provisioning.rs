@@ -0,0 +1,1190 @@
+//! The outbox behind a production deployment's external resources.
+//!
+//! Creating a production deployment only writes rows: the deployment itself
+//! plus one pending action per Cloudflare custom hostname and one for the
+//! Postmark sending domain, all in the same transaction. The dispatcher here
+//! claims due actions, makes the external calls, and writes what they created
+//! back onto the deployment. Every action looks its resource up before
+//! creating it, so running one twice after a crash doesn't make a duplicate.
+//!
+//! Domain migrations use the same queue. Their actions carry the migration's
+//! id and write onto the migration instead, and the migration enqueues
+//! deletions for whichever hostnames and sending domain it leaves behind.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    commands::{Command, EnqueueJobCommand, Job},
+    error::AppError,
+    models::{
+        DeploymentProvisioning, DeploymentProvisioningAction, DomainVerificationRecords,
+        EmailVerificationRecords, HostnameRole, ProvisioningActionKind, ProvisioningActionPayload,
+        ProvisioningActionStatus, ProvisioningStatus,
+    },
+    queries::{
+        GetDeploymentProvisioningQuery, PROVISIONING_ACTION_COLUMNS, Query,
+        provisioning_action_from_row,
+    },
+    services::{DomainsConfig, PostmarkDomain},
+    state::AppState,
+};
+
+/// After this many attempts an action is marked failed and waits for a retry
+/// from the console.
+const MAX_ATTEMPTS: i32 = 8;
+const DISPATCH_BATCH_SIZE: i64 = 20;
+const DEFAULT_DISPATCH_INTERVAL: Duration = Duration::from_secs(15);
+/// How long a claimed action stays invisible to other dispatchers. A process
+/// that dies mid-action leaves it to be picked up again once this runs out.
+const CLAIM_LEASE: Duration = Duration::from_secs(5 * 60);
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    BASE_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(exponent))
+        .min(MAX_RETRY_DELAY)
+}
+
+async fn insert_action(
+    app_state: &AppState,
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    domain_migration_id: Option<i64>,
+    action: ProvisioningActionKind,
+    payload: ProvisioningActionPayload,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO deployment_provisioning_actions (
+            id, deployment_id, domain_migration_id, action, payload
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(app_state.sf.next_id()? as i64)
+    .bind(deployment_id)
+    .bind(domain_migration_id)
+    .bind(action.as_str())
+    .bind(serde_json::to_value(&payload).map_err(|e| AppError::Serialization(e.to_string()))?)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Writes the pending actions for a new production deployment and marks it
+/// as provisioning. Runs on the transaction that creates the deployment.
+pub(crate) struct EnqueueDeploymentProvisioningCommand {
+    deployment_id: i64,
+    domain_migration_id: Option<i64>,
+    frontend_host: String,
+    backend_host: String,
+    mail_from_host: String,
+}
+
+impl EnqueueDeploymentProvisioningCommand {
+    pub(crate) fn new(
+        deployment_id: i64,
+        frontend_host: impl Into<String>,
+        backend_host: impl Into<String>,
+        mail_from_host: impl Into<String>,
+    ) -> Self {
+        Self {
+            deployment_id,
+            domain_migration_id: None,
+            frontend_host: frontend_host.into(),
+            backend_host: backend_host.into(),
+            mail_from_host: mail_from_host.into(),
+        }
+    }
+
+    /// Provisions the hosts a deployment is moving to. The deployment keeps
+    /// its own provisioning status, and what gets created is written onto
+    /// the migration.
+    pub(crate) fn for_domain_migration(mut self, domain_migration_id: i64) -> Self {
+        self.domain_migration_id = Some(domain_migration_id);
+        self
+    }
+
+    fn actions(
+        &self,
+        domains: &DomainsConfig,
+    ) -> [(ProvisioningActionKind, ProvisioningActionPayload); 3] {
+        [
+            (
+                ProvisioningActionKind::CreateCfHostname,
+                ProvisioningActionPayload::CustomHostname {
+                    hostname: self.frontend_host.clone(),
+                    origin: domains.frontend_origin.clone(),
+                    role: HostnameRole::Frontend,
+                },
+            ),
+            (
+                ProvisioningActionKind::CreateCfHostname,
+                ProvisioningActionPayload::CustomHostname {
+                    hostname: self.backend_host.clone(),
+                    origin: domains.backend_origin.clone(),
+                    role: HostnameRole::Backend,
+                },
+            ),
+            (
+                ProvisioningActionKind::CreatePostmarkDomain,
+                ProvisioningActionPayload::PostmarkDomain {
+                    domain: self.mail_from_host.clone(),
+                },
+            ),
+        ]
+    }
+
+    pub(crate) async fn execute_with(
+        self,
+        app_state: &AppState,
+        conn: &mut PgConnection,
+    ) -> Result<(), AppError> {
+        for (action, payload) in self.actions(&app_state.domains) {
+            insert_action(
+                app_state,
+                conn,
+                self.deployment_id,
+                self.domain_migration_id,
+                action,
+                payload,
+            )
+            .await?;
+        }
+
+        if self.domain_migration_id.is_some() {
+            return Ok(());
+        }
+
+        sqlx::query("UPDATE deployments SET provisioning_status = $2 WHERE id = $1")
+            .bind(self.deployment_id)
+            .bind(ProvisioningStatus::Provisioning.as_str())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Queues the removal of hostnames and sending domains a domain migration
+/// no longer needs. Runs on the transaction that finishes or aborts it.
+pub(crate) struct EnqueueResourceDeletionCommand {
+    deployment_id: i64,
+    domain_migration_id: i64,
+    hostname_ids: Vec<String>,
+    postmark_domain_ids: Vec<i64>,
+}
+
+impl EnqueueResourceDeletionCommand {
+    pub(crate) fn new(deployment_id: i64, domain_migration_id: i64) -> Self {
+        Self {
+            deployment_id,
+            domain_migration_id,
+            hostname_ids: Vec::new(),
+            postmark_domain_ids: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_custom_hostname(mut self, hostname_id: Option<String>) -> Self {
+        self.hostname_ids.extend(hostname_id);
+        self
+    }
+
+    pub(crate) fn with_postmark_domain(mut self, postmark_domain_id: Option<i64>) -> Self {
+        self.postmark_domain_ids.extend(postmark_domain_id);
+        self
+    }
+
+    pub(crate) async fn execute_with(
+        self,
+        app_state: &AppState,
+        conn: &mut PgConnection,
+    ) -> Result<(), AppError> {
+        for hostname_id in self.hostname_ids {
+            insert_action(
+                app_state,
+                conn,
+                self.deployment_id,
+                Some(self.domain_migration_id),
+                ProvisioningActionKind::DeleteCfHostname,
+                ProvisioningActionPayload::CustomHostnameId { hostname_id },
+            )
+            .await?;
+        }
+
+        for postmark_domain_id in self.postmark_domain_ids {
+            insert_action(
+                app_state,
+                conn,
+                self.deployment_id,
+                Some(self.domain_migration_id),
+                ProvisioningActionKind::DeletePostmarkDomain,
+                ProvisioningActionPayload::PostmarkDomainId { postmark_domain_id },
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A resource at a provider, by the id the provider gave it. Every cleanup
+/// path removes resources through [`ExternalResource::delete`], as
+/// Cloudflare only deletes a custom hostname by its id and not by name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub(crate) enum ExternalResource {
+    CustomHostname(String),
+    PostmarkDomain(i64),
+}
+
+impl ExternalResource {
+    /// The resources recorded on a deployment or a domain migration.
+    pub(crate) fn recorded(
+        domain_verification_records: Option<&DomainVerificationRecords>,
+        email_verification_records: Option<&EmailVerificationRecords>,
+    ) -> Vec<Self> {
+        let hostname_ids = domain_verification_records
+            .into_iter()
+            .flat_map(|records| {
+                [
+                    records.frontend_hostname_id.clone(),
+                    records.backend_hostname_id.clone(),
+                ]
+            })
+            .flatten()
+            .map(ExternalResource::CustomHostname);
+        let postmark_domain_ids = email_verification_records
+            .and_then(|records| records.postmark_domain_id)
+            .map(ExternalResource::PostmarkDomain);
+
+        hostname_ids.chain(postmark_domain_ids).collect()
+    }
+
+    /// Removes the resource. One the provider no longer has counts as
+    /// removed, so running a cleanup twice is harmless.
+    pub(crate) async fn delete(&self, app_state: &AppState) -> Result<(), AppError> {
+        match self {
+            ExternalResource::CustomHostname(id) => {
+                match app_state
+                    .cloudflare_service
+                    .delete_custom_hostname(id)
+                    .await
+                {
+                    Err(e) if e.status() == Some(404) => Ok(()),
+                    result => Ok(result?),
+                }
+            }
+            ExternalResource::PostmarkDomain(id) => {
+                match app_state.email_domain_service.delete_domain(*id).await {
+                    Err(e) if e.status() == Some(404) => Ok(()),
+                    result => Ok(result?),
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ExternalResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExternalResource::CustomHostname(id) => write!(f, "Cloudflare custom hostname {}", id),
+            ExternalResource::PostmarkDomain(id) => write!(f, "Postmark domain {}", id),
+        }
+    }
+}
+
+/// Removes a resource at its provider in the background, retrying until the
+/// provider is reached, so a deleted deployment doesn't leave it behind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DeleteExternalResourceJob {
+    resource: ExternalResource,
+}
+
+impl DeleteExternalResourceJob {
+    pub(crate) fn new(resource: ExternalResource) -> Self {
+        Self { resource }
+    }
+}
+
+impl Job for DeleteExternalResourceJob {
+    const JOB_TYPE: &'static str = "delete_external_resource";
+
+    async fn run(self, app_state: &AppState) -> Result<(), AppError> {
+        self.resource.delete(app_state).await?;
+        tracing::info!("Removed {}", self.resource);
+        Ok(())
+    }
+}
+
+/// What an action created, or `Deleted` for an action that removed something.
+enum ProvisionedResource {
+    CustomHostname { id: String, role: HostnameRole },
+    PostmarkDomain(Box<PostmarkDomain>),
+    Deleted,
+}
+
+impl ProvisionedResource {
+    fn result(&self) -> serde_json::Value {
+        match self {
+            ProvisionedResource::CustomHostname { id, .. } => json!({ "hostname_id": id }),
+            ProvisionedResource::PostmarkDomain(domain) => {
+                json!({ "postmark_domain_id": domain.id })
+            }
+            ProvisionedResource::Deleted => json!({ "deleted": true }),
+        }
+    }
+
+    /// What has to be removed to undo the action, for a deployment deleted
+    /// or a domain migration aborted while it was in flight.
+    fn into_external_resource(self) -> Option<ExternalResource> {
+        match self {
+            ProvisionedResource::CustomHostname { id, .. } => {
+                Some(ExternalResource::CustomHostname(id))
+            }
+            ProvisionedResource::PostmarkDomain(domain) => {
+                Some(ExternalResource::PostmarkDomain(domain.id))
+            }
+            ProvisionedResource::Deleted => None,
+        }
+    }
+}
+
+async fn perform_action(
+    app_state: &AppState,
+    payload: &ProvisioningActionPayload,
+) -> Result<ProvisionedResource, AppError> {
+    let cloudflare_service = &app_state.cloudflare_service;
+    let email_domain_service = &app_state.email_domain_service;
+
+    match payload.clone() {
+        ProvisioningActionPayload::CustomHostname {
+            hostname,
+            origin,
+            role,
+        } => {
+            let custom_hostname = match cloudflare_service.find_custom_hostname(&hostname).await? {
+                Some(existing) => existing,
+                None => {
+                    cloudflare_service
+                        .create_custom_hostname(&hostname, &origin)
+                        .await?
+                }
+            };
+            Ok(ProvisionedResource::CustomHostname {
+                id: custom_hostname.id,
+                role,
+            })
+        }
+        ProvisioningActionPayload::PostmarkDomain { domain } => {
+            let postmark_domain = match email_domain_service.find_domain(&domain).await? {
+                Some(existing) => existing,
+                None => email_domain_service.create_domain(&domain).await?,
+            };
+            Ok(ProvisionedResource::PostmarkDomain(Box::new(
+                postmark_domain,
+            )))
+        }
+        ProvisioningActionPayload::CustomHostnameId { hostname_id } => {
+            ExternalResource::CustomHostname(hostname_id)
+                .delete(app_state)
+                .await?;
+            Ok(ProvisionedResource::Deleted)
+        }
+        ProvisioningActionPayload::PostmarkDomainId { postmark_domain_id } => {
+            ExternalResource::PostmarkDomain(postmark_domain_id)
+                .delete(app_state)
+                .await?;
+            Ok(ProvisionedResource::Deleted)
+        }
+    }
+}
+
+/// The verification records column a created resource belongs in, and that
+/// column's value with the resource added.
+fn updated_records(
+    app_state: &AppState,
+    resource: &ProvisionedResource,
+    domain_verification_records: Option<serde_json::Value>,
+) -> Result<Option<(&'static str, serde_json::Value)>, AppError> {
+    let (column, records) = match resource {
+        ProvisionedResource::CustomHostname { id, role } => {
+            let mut records: DomainVerificationRecords = domain_verification_records
+                .and_then(|value| serde_json::from_value(value).ok())
+                .unwrap_or_default();
+            match role {
+                HostnameRole::Frontend => records.frontend_hostname_id = Some(id.clone()),
+                HostnameRole::Backend => records.backend_hostname_id = Some(id.clone()),
+            }
+            (
+                "domain_verification_records",
+                serde_json::to_value(&records),
+            )
+        }
+        ProvisionedResource::PostmarkDomain(domain) => {
+            let records = app_state
+                .email_domain_service
+                .generate_email_verification_records(domain);
+            ("email_verification_records", serde_json::to_value(&records))
+        }
+        ProvisionedResource::Deleted => return Ok(None),
+    };
+
+    let records = records.map_err(|e| AppError::Serialization(e.to_string()))?;
+    Ok(Some((column, records)))
+}
+
+/// Writes what an action created onto its deployment, or onto its domain
+/// migration. Returns the resource back if the deployment has been deleted
+/// or the migration aborted in the meantime, so the caller can remove it
+/// again.
+async fn record_success(
+    app_state: &AppState,
+    action: &DeploymentProvisioningAction,
+    resource: ProvisionedResource,
+) -> Result<Option<ProvisionedResource>, AppError> {
+    let mut tx = app_state.db_pool.begin().await?;
+
+    let target = match action.domain_migration_id {
+        Some(domain_migration_id) => sqlx::query(
+            r#"
+            SELECT status NOT IN ('provisioning', 'verifying', 'failed') AS deleted,
+                   new_domain_verification_records AS domain_verification_records
+            FROM deployment_domain_migrations
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(domain_migration_id),
+        None => sqlx::query(
+            r#"
+            SELECT deleted_at IS NOT NULL AS deleted,
+                   domain_verification_records::jsonb AS domain_verification_records
+            FROM deployments
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(action.deployment_id),
+    }
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let deleted = target
+        .as_ref()
+        .is_none_or(|row| row.get::<bool, _>("deleted"));
+
+    if deleted && !action.action.is_delete() {
+        mark_action(
+            &mut tx,
+            action.id,
+            ProvisioningActionStatus::Cancelled,
+            Some(resource.result()),
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        return Ok(Some(resource));
+    }
+
+    let domain_verification_records = target
+        .and_then(|row| row.get::<Option<serde_json::Value>, _>("domain_verification_records"));
+
+    if let Some((column, records)) =
+        updated_records(app_state, &resource, domain_verification_records)?
+    {
+        let (sql, id) = match action.domain_migration_id {
+            Some(domain_migration_id) => (
+                format!(
+                    "UPDATE deployment_domain_migrations SET new_{} = $2, updated_at = NOW() WHERE id = $1",
+                    column
+                ),
+                domain_migration_id,
+            ),
+            None => (
+                format!(
+                    "UPDATE deployments SET {} = $2, updated_at = NOW() WHERE id = $1",
+                    column
+                ),
+                action.deployment_id,
+            ),
+        };
+
+        sqlx::query(&sql)
+            .bind(id)
+            .bind(records)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    mark_action(
+        &mut tx,
+        action.id,
+        ProvisioningActionStatus::Completed,
+        Some(resource.result()),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(None)
+}
+
+async fn mark_action(
+    conn: &mut PgConnection,
+    action_id: i64,
+    status: ProvisioningActionStatus,
+    result: Option<serde_json::Value>,
+    last_error: Option<String>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE deployment_provisioning_actions
+        SET status = $2, result = COALESCE($3, result), last_error = COALESCE($4, last_error),
+            completed_at = NOW(), updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(action_id)
+    .bind(status.as_str())
+    .bind(result)
+    .bind(last_error)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn record_failure(
+    app_state: &AppState,
+    action: &DeploymentProvisioningAction,
+    error: &AppError,
+) -> Result<(), AppError> {
+    if action.attempts >= MAX_ATTEMPTS {
+        tracing::error!(
+            deployment_id = action.deployment_id,
+            "Provisioning action {} ({}) failed for good after {} attempts: {}",
+            action.id,
+            action.action.as_str(),
+            action.attempts,
+            error
+        );
+
+        let mut conn = app_state.db_pool.acquire().await?;
+        return mark_action(
+            &mut conn,
+            action.id,
+            ProvisioningActionStatus::Failed,
+            None,
+            Some(error.to_string()),
+        )
+        .await;
+    }
+
+    let delay = retry_delay(action.attempts);
+    tracing::warn!(
+        deployment_id = action.deployment_id,
+        "Provisioning action {} ({}) failed, retrying in {}s: {}",
+        action.id,
+        action.action.as_str(),
+        delay.as_secs(),
+        error
+    );
+
+    sqlx::query(
+        r#"
+        UPDATE deployment_provisioning_actions
+        SET next_attempt_at = NOW() + $2 * INTERVAL '1 millisecond', last_error = $3,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(action.id)
+    .bind(delay.as_millis() as i64)
+    .bind(error.to_string())
+    .execute(&app_state.db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Sets `provisioning_status` from the deployment's own actions: failed if
+/// any ran out of attempts, provisioning while any are pending, ready
+/// otherwise.
+async fn refresh_provisioning_status(
+    app_state: &AppState,
+    deployment_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE deployments
+        SET provisioning_status = CASE
+                WHEN EXISTS (
+                    SELECT 1 FROM deployment_provisioning_actions
+                    WHERE deployment_id = $1 AND domain_migration_id IS NULL
+                      AND status = 'failed'
+                ) THEN 'failed'
+                WHEN EXISTS (
+                    SELECT 1 FROM deployment_provisioning_actions
+                    WHERE deployment_id = $1 AND domain_migration_id IS NULL
+                      AND status = 'pending'
+                ) THEN 'provisioning'
+                ELSE 'ready'
+            END,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(deployment_id)
+    .execute(&app_state.db_pool)
+    .await?;
+
+    app_state
+        .cache_invalidator
+        .invalidate_deployment(deployment_id)
+        .await;
+
+    Ok(())
+}
+
+/// Moves a migration that is still in progress between provisioning, failed
+/// and verifying as its creations finish. The deletions it queues once it is
+/// over don't change its status.
+pub(crate) async fn refresh_domain_migration_status(
+    conn: &mut PgConnection,
+    domain_migration_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE deployment_domain_migrations
+        SET status = CASE
+                WHEN EXISTS (
+                    SELECT 1 FROM deployment_provisioning_actions
+                    WHERE domain_migration_id = $1 AND action LIKE 'create_%' AND status = 'failed'
+                ) THEN 'failed'
+                WHEN EXISTS (
+                    SELECT 1 FROM deployment_provisioning_actions
+                    WHERE domain_migration_id = $1 AND action LIKE 'create_%' AND status = 'pending'
+                ) THEN 'provisioning'
+                ELSE 'verifying'
+            END,
+            updated_at = NOW()
+        WHERE id = $1 AND status IN ('provisioning', 'verifying', 'failed')
+        "#,
+    )
+    .bind(domain_migration_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Runs the provisioning actions that are due, across all deployments or for
+/// one. Returns how many actions were attempted.
+pub struct DispatchProvisioningActionsCommand {
+    deployment_id: Option<i64>,
+}
+
+impl DispatchProvisioningActionsCommand {
+    pub fn new() -> Self {
+        Self {
+            deployment_id: None,
+        }
+    }
+
+    pub fn for_deployment(deployment_id: i64) -> Self {
+        Self {
+            deployment_id: Some(deployment_id),
+        }
+    }
+
+    async fn claim(
+        &self,
+        app_state: &AppState,
+    ) -> Result<Vec<DeploymentProvisioningAction>, AppError> {
+        // Creations for deployments deleted before they ran are never
+        // attempted. Deletions still are, since they clean up after it.
+        sqlx::query(
+            r#"
+            UPDATE deployment_provisioning_actions a
+            SET status = 'cancelled', completed_at = NOW(), updated_at = NOW()
+            FROM deployments d
+            WHERE d.id = a.deployment_id AND d.deleted_at IS NOT NULL AND a.status = 'pending'
+              AND a.action LIKE 'create_%'
+            "#,
+        )
+        .execute(&app_state.db_pool)
+        .await?;
+
+        let rows = sqlx::query(&format!(
+            r#"
+            UPDATE deployment_provisioning_actions
+            SET attempts = attempts + 1,
+                next_attempt_at = NOW() + $3 * INTERVAL '1 millisecond',
+                updated_at = NOW()
+            WHERE id IN (
+                SELECT id FROM deployment_provisioning_actions
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                  AND ($1::BIGINT IS NULL OR deployment_id = $1)
+                ORDER BY next_attempt_at
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING {}
+            "#,
+            PROVISIONING_ACTION_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .bind(DISPATCH_BATCH_SIZE)
+        .bind(CLAIM_LEASE.as_millis() as i64)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        rows.iter().map(provisioning_action_from_row).collect()
+    }
+
+    /// Hands claimed actions that weren't attempted back straight away
+    /// instead of waiting out the lease.
+    async fn release(app_state: &AppState, action_ids: &[i64]) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE deployment_provisioning_actions
+            SET attempts = attempts - 1, next_attempt_at = NOW(), updated_at = NOW()
+            WHERE id = ANY($1) AND status = 'pending'
+            "#,
+        )
+        .bind(action_ids)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl Default for DispatchProvisioningActionsCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for DispatchProvisioningActionsCommand {
+    type Output = usize;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let actions = self.claim(app_state).await?;
+        let mut deployment_ids: Vec<i64> = Vec::new();
+        let mut domain_migration_ids: Vec<i64> = Vec::new();
+        let mut attempted = 0;
+
+        for (index, action) in actions.iter().enumerate() {
+            if app_state.background_tasks.is_shutting_down() {
+                let remaining: Vec<i64> = actions[index..].iter().map(|a| a.id).collect();
+                Self::release(app_state, &remaining).await?;
+                break;
+            }
+
+            match perform_action(app_state, &action.payload).await {
+                Ok(resource) => {
+                    if let Some(orphan) = record_success(app_state, action, resource)
+                        .await?
+                        .and_then(ProvisionedResource::into_external_resource)
+                        && let Err(e) =
+                            EnqueueJobCommand::new(DeleteExternalResourceJob::new(orphan))
+                                .execute(app_state)
+                                .await
+                    {
+                        tracing::warn!(
+                            deployment_id = action.deployment_id,
+                            "Failed to queue removal of resource of provisioning action {} that is no longer needed: {}",
+                            action.id,
+                            e
+                        );
+                    }
+                }
+                Err(e) => record_failure(app_state, action, &e).await?,
+            }
+
+            attempted += 1;
+            match action.domain_migration_id {
+                Some(id) if !domain_migration_ids.contains(&id) => domain_migration_ids.push(id),
+                None if !deployment_ids.contains(&action.deployment_id) => {
+                    deployment_ids.push(action.deployment_id)
+                }
+                _ => {}
+            }
+        }
+
+        for deployment_id in deployment_ids {
+            refresh_provisioning_status(app_state, deployment_id).await?;
+        }
+
+        if !domain_migration_ids.is_empty() {
+            let mut conn = app_state.db_pool.acquire().await?;
+            for domain_migration_id in domain_migration_ids {
+                refresh_domain_migration_status(&mut conn, domain_migration_id).await?;
+            }
+        }
+
+        Ok(attempted)
+    }
+}
+
+/// Puts a deployment's failed actions back in the queue, including those of
+/// a domain migration in progress, and dispatches them straight away.
+pub struct RetryDeploymentProvisioningCommand {
+    deployment_id: i64,
+}
+
+impl RetryDeploymentProvisioningCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for RetryDeploymentProvisioningCommand {
+    type Output = DeploymentProvisioning;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let exists: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM deployments WHERE id = $1 AND deleted_at IS NULL")
+                .bind(self.deployment_id)
+                .fetch_optional(&app_state.db_pool)
+                .await?;
+        if exists.is_none() {
+            return Err(AppError::NotFound("Deployment not found".to_string()));
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE deployment_provisioning_actions
+            SET status = 'pending', attempts = 0, next_attempt_at = NOW(), completed_at = NULL,
+                updated_at = NOW()
+            WHERE deployment_id = $1 AND status = 'failed'
+            "#,
+        )
+        .bind(self.deployment_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        let domain_migration_id: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM deployment_domain_migrations WHERE deployment_id = $1 AND status = 'failed'",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+        if let Some(domain_migration_id) = domain_migration_id {
+            let mut conn = app_state.db_pool.acquire().await?;
+            refresh_domain_migration_status(&mut conn, domain_migration_id).await?;
+        }
+
+        refresh_provisioning_status(app_state, self.deployment_id).await?;
+        spawn_provisioning_dispatch(app_state, Some(self.deployment_id));
+
+        GetDeploymentProvisioningQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await
+    }
+}
+
+/// Dispatches due actions in the background, for one deployment or all.
+pub fn spawn_provisioning_dispatch(app_state: &AppState, deployment_id: Option<i64>) {
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let command = match deployment_id {
+            Some(deployment_id) => {
+                DispatchProvisioningActionsCommand::for_deployment(deployment_id)
+            }
+            None => DispatchProvisioningActionsCommand::new(),
+        };
+
+        if let Err(e) = command.execute(&background_state).await {
+            tracing::error!("Failed to dispatch provisioning actions: {}", e);
+        }
+    });
+}
+
+/// Polls for due actions every `PROVISIONING_DISPATCH_INTERVAL_SECS`
+/// (default 15) until shutdown. Picks up retries and anything left behind by
+/// a process that stopped mid-dispatch.
+pub fn spawn_provisioning_dispatcher(app_state: &AppState) {
+    let interval = std::env::var("PROVISIONING_DISPATCH_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DISPATCH_INTERVAL);
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = background_state.background_tasks.shutdown_requested() => break,
+            }
+
+            if let Err(e) = DispatchProvisioningActionsCommand::new()
+                .execute(&background_state)
+                .await
+            {
+                tracing::error!("Failed to dispatch provisioning actions: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{
+        commands::DeleteDeploymentCommand,
+        models::{Deployment, DeploymentMode},
+        services::{CloudflareCall, EmailDomainCall, FakeCloudflare, FakeEmailDomains},
+        state::TestAppStateBuilder,
+    };
+
+    const DEPLOYMENT_ID: i64 = 42;
+    const FRONTEND_HOST: &str = "accounts.example.com";
+    const BACKEND_HOST: &str = "frontend-api.example.com";
+    const MAIL_FROM_HOST: &str = "mail.example.com";
+
+    struct Harness {
+        app_state: AppState,
+        cloudflare: Arc<FakeCloudflare>,
+        email_domains: Arc<FakeEmailDomains>,
+    }
+
+    fn harness() -> Harness {
+        let cloudflare = Arc::new(FakeCloudflare::new());
+        let email_domains = Arc::new(FakeEmailDomains::new());
+        let app_state = TestAppStateBuilder::new()
+            .with_cloudflare(cloudflare.clone())
+            .with_email_domains(email_domains.clone())
+            .build();
+
+        Harness {
+            app_state,
+            cloudflare,
+            email_domains,
+        }
+    }
+
+    /// Runs each action a new production deployment enqueues once, as the
+    /// dispatcher would, and returns the deployment with what the ones that
+    /// succeeded wrote onto it.
+    async fn provision(app_state: &AppState) -> (Deployment, Vec<AppError>) {
+        let command = EnqueueDeploymentProvisioningCommand::new(
+            DEPLOYMENT_ID,
+            FRONTEND_HOST,
+            BACKEND_HOST,
+            MAIL_FROM_HOST,
+        );
+
+        let mut domain_verification_records = None;
+        let mut email_verification_records = None;
+        let mut errors = Vec::new();
+
+        for (_, payload) in command.actions(&app_state.domains) {
+            let resource = match perform_action(app_state, &payload).await {
+                Ok(resource) => resource,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            match updated_records(app_state, &resource, domain_verification_records.clone())
+                .unwrap()
+            {
+                Some(("domain_verification_records", records)) => {
+                    domain_verification_records = Some(records)
+                }
+                Some((_, records)) => email_verification_records = Some(records),
+                None => {}
+            }
+        }
+
+        let deployment = Deployment {
+            id: DEPLOYMENT_ID,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            maintenance_mode: false,
+            backend_host: BACKEND_HOST.to_string(),
+            frontend_host: FRONTEND_HOST.to_string(),
+            mail_from_host: MAIL_FROM_HOST.to_string(),
+            publishable_key: String::new(),
+            project_id: 7,
+            mode: DeploymentMode::Production,
+            verification_status: None,
+            provisioning_status: if errors.is_empty() {
+                ProvisioningStatus::Ready
+            } else {
+                ProvisioningStatus::Failed
+            },
+            domain_verification_records: domain_verification_records.map(|records| {
+                serde_json::from_value::<DomainVerificationRecords>(records).unwrap()
+            }),
+            email_verification_records: email_verification_records.map(|records| {
+                serde_json::from_value::<EmailVerificationRecords>(records).unwrap()
+            }),
+        };
+
+        (deployment, errors)
+    }
+
+    /// Runs the removal jobs deleting the deployment queues, as the job
+    /// worker would.
+    async fn delete(app_state: &AppState, deployment: &Deployment) {
+        for resource in DeleteDeploymentCommand::external_resources(deployment) {
+            DeleteExternalResourceJob::new(resource)
+                .run(app_state)
+                .await
+                .unwrap();
+        }
+    }
+
+    fn deleted_hostnames(cloudflare: &FakeCloudflare) -> Vec<String> {
+        cloudflare
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                CloudflareCall::DeleteCustomHostname(id) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn deleted_domains(email_domains: &FakeEmailDomains) -> Vec<i64> {
+        email_domains
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                EmailDomainCall::DeleteDomain(id) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn frontend_hostname_failure_cleans_up_what_was_created() {
+        let harness = harness();
+        harness.cloudflare.fail(
+            CloudflareCall::CreateCustomHostname {
+                hostname: FRONTEND_HOST.to_string(),
+                origin_server: harness.app_state.domains.frontend_origin.clone(),
+            },
+            500,
+        );
+
+        let (deployment, errors) = provision(&harness.app_state).await;
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], AppError::External(e) if e.status == Some(500)));
+
+        let records = deployment.domain_verification_records.clone().unwrap();
+        assert_eq!(records.frontend_hostname_id, None);
+        let postmark_domain_id = deployment
+            .email_verification_records
+            .as_ref()
+            .and_then(|records| records.postmark_domain_id)
+            .unwrap();
+
+        delete(&harness.app_state, &deployment).await;
+
+        assert_eq!(
+            deleted_hostnames(&harness.cloudflare),
+            vec![format!("cf-{}", BACKEND_HOST)]
+        );
+        assert_eq!(
+            deleted_domains(&harness.email_domains),
+            vec![postmark_domain_id]
+        );
+        assert!(harness.cloudflare.custom_hostnames().is_empty());
+        assert!(harness.email_domains.domains().is_empty());
+    }
+
+    #[tokio::test]
+    async fn backend_hostname_failure_after_frontend_success_cleans_up_frontend() {
+        let harness = harness();
+        harness.cloudflare.fail(
+            CloudflareCall::CreateCustomHostname {
+                hostname: BACKEND_HOST.to_string(),
+                origin_server: harness.app_state.domains.backend_origin.clone(),
+            },
+            429,
+        );
+
+        let (deployment, errors) = provision(&harness.app_state).await;
+        assert_eq!(errors.len(), 1);
+
+        let records = deployment.domain_verification_records.clone().unwrap();
+        assert_eq!(
+            records.frontend_hostname_id,
+            Some(format!("cf-{}", FRONTEND_HOST))
+        );
+        assert_eq!(records.backend_hostname_id, None);
+
+        delete(&harness.app_state, &deployment).await;
+
+        assert_eq!(
+            deleted_hostnames(&harness.cloudflare),
+            vec![format!("cf-{}", FRONTEND_HOST)]
+        );
+        assert_eq!(deleted_domains(&harness.email_domains).len(), 1);
+        assert!(harness.cloudflare.custom_hostnames().is_empty());
+    }
+
+    #[tokio::test]
+    async fn postmark_failure_leaves_no_domain_to_clean_up() {
+        let harness = harness();
+        harness.email_domains.fail(
+            EmailDomainCall::CreateDomain(MAIL_FROM_HOST.to_string()),
+            422,
+        );
+
+        let (deployment, errors) = provision(&harness.app_state).await;
+        assert_eq!(errors.len(), 1);
+        assert!(deployment.email_verification_records.is_none());
+
+        delete(&harness.app_state, &deployment).await;
+
+        assert_eq!(
+            deleted_hostnames(&harness.cloudflare),
+            vec![
+                format!("cf-{}", FRONTEND_HOST),
+                format!("cf-{}", BACKEND_HOST)
+            ]
+        );
+        assert!(deleted_domains(&harness.email_domains).is_empty());
+        assert_eq!(
+            harness.email_domains.calls(),
+            vec![
+                EmailDomainCall::FindDomain(MAIL_FROM_HOST.to_string()),
+                EmailDomainCall::CreateDomain(MAIL_FROM_HOST.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn removal_jobs_keep_the_resource_in_their_payload() {
+        let job = DeleteExternalResourceJob::new(ExternalResource::PostmarkDomain(31));
+        let payload = serde_json::to_value(&job).unwrap();
+        assert_eq!(
+            payload,
+            json!({ "resource": { "kind": "postmark_domain", "id": 31 } })
+        );
+
+        let job: DeleteExternalResourceJob = serde_json::from_value(payload).unwrap();
+        assert_eq!(job.resource, ExternalResource::PostmarkDomain(31));
+    }
+}
@@ -0,0 +1,107 @@
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{
+        DeploymentMode, DomainVerificationRecords, EmailVerificationRecords, VerificationStatus,
+    },
+    state::AppState,
+};
+
+use super::Command;
+
+const DEFAULT_BATCH_SIZE: i64 = 500;
+
+/// Fills in `verification_status` for deployments created before it was
+/// stored, from the records they already have; no DNS lookups are made.
+/// Only rows still without a status are touched, so it's safe to run on
+/// every start. Returns how many deployments were updated.
+pub struct MigrateDeploymentVerificationStatusCommand {
+    batch_size: i64,
+}
+
+impl MigrateDeploymentVerificationStatusCommand {
+    pub fn new() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+}
+
+impl Default for MigrateDeploymentVerificationStatusCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for MigrateDeploymentVerificationStatusCommand {
+    type Output = usize;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut last_id = 0i64;
+        let mut updated = 0;
+
+        loop {
+            if app_state.background_tasks.is_shutting_down() {
+                break;
+            }
+
+            let rows = sqlx::query(
+                r#"
+                SELECT id, mode,
+                    domain_verification_records::jsonb AS domain_verification_records,
+                    email_verification_records::jsonb AS email_verification_records
+                FROM deployments
+                WHERE verification_status IS NULL AND id > $1
+                ORDER BY id
+                LIMIT $2
+                "#,
+            )
+            .bind(last_id)
+            .bind(self.batch_size)
+            .fetch_all(&app_state.db_pool)
+            .await?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            last_id = last.get("id");
+
+            for row in rows {
+                let status = match DeploymentMode::from(row.get::<String, _>("mode")) {
+                    DeploymentMode::Staging => VerificationStatus::Verified,
+                    DeploymentMode::Production => {
+                        let domain_records: Option<DomainVerificationRecords> = row
+                            .get::<Option<serde_json::Value>, _>("domain_verification_records")
+                            .and_then(|value| serde_json::from_value(value).ok());
+                        let email_records: Option<EmailVerificationRecords> = row
+                            .get::<Option<serde_json::Value>, _>("email_verification_records")
+                            .and_then(|value| serde_json::from_value(value).ok());
+                        VerificationStatus::from_records(
+                            domain_records.as_ref(),
+                            email_records.as_ref(),
+                        )
+                    }
+                };
+
+                // A verification that ran since the batch was read has
+                // already stored a fresher status.
+                let result = sqlx::query(
+                    "UPDATE deployments SET verification_status = $2 \
+                     WHERE id = $1 AND verification_status IS NULL",
+                )
+                .bind(row.get::<i64, _>("id"))
+                .bind(status.as_str())
+                .execute(&app_state.db_pool)
+                .await?;
+                updated += result.rows_affected() as usize;
+            }
+        }
+
+        Ok(updated)
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::Utc;
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{DeploymentDisposableDomain, DisposableDomainDataset, DisposableDomainKind},
+    state::AppState,
+};
+
+use super::Command;
+
+/// Adds a domain to a deployment's disposable list, or exempts it from the
+/// upstream one. Adding a domain that's already listed switches its kind.
+pub struct AddDeploymentDisposableDomainCommand {
+    deployment_id: i64,
+    domain: String,
+    kind: DisposableDomainKind,
+}
+
+impl AddDeploymentDisposableDomainCommand {
+    pub fn new(deployment_id: i64, domain: String, kind: DisposableDomainKind) -> Self {
+        Self {
+            deployment_id,
+            domain,
+            kind,
+        }
+    }
+}
+
+impl Command for AddDeploymentDisposableDomainCommand {
+    type Output = DeploymentDisposableDomain;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let domain = self.domain.trim().trim_start_matches('@').to_lowercase();
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO deployment_disposable_email_domains (id, deployment_id, domain, kind, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (deployment_id, domain) DO UPDATE SET kind = EXCLUDED.kind
+            RETURNING id, created_at
+            "#,
+        )
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(&domain)
+        .bind(self.kind.as_str())
+        .bind(Utc::now())
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        app_state
+            .disposable_domain_service
+            .invalidate_deployment(self.deployment_id);
+
+        Ok(DeploymentDisposableDomain {
+            id: row.get("id"),
+            deployment_id: self.deployment_id,
+            domain,
+            kind: self.kind,
+            created_at: row.get("created_at"),
+        })
+    }
+}
+
+pub struct RemoveDeploymentDisposableDomainCommand {
+    deployment_id: i64,
+    domain: String,
+}
+
+impl RemoveDeploymentDisposableDomainCommand {
+    pub fn new(deployment_id: i64, domain: String) -> Self {
+        Self {
+            deployment_id,
+            domain,
+        }
+    }
+}
+
+impl Command for RemoveDeploymentDisposableDomainCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let result = sqlx::query(
+            "DELETE FROM deployment_disposable_email_domains WHERE deployment_id = $1 AND domain = $2",
+        )
+        .bind(self.deployment_id)
+        .bind(self.domain.trim().to_lowercase())
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "Domain {} is not in this deployment's disposable domain list",
+                self.domain
+            )));
+        }
+
+        app_state
+            .disposable_domain_service
+            .invalidate_deployment(self.deployment_id);
+
+        Ok(())
+    }
+}
+
+/// Fetches the upstream disposable domain list right away instead of waiting
+/// for the next scheduled refresh.
+#[derive(Default)]
+pub struct RefreshDisposableDomainsCommand;
+
+impl RefreshDisposableDomainsCommand {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Command for RefreshDisposableDomainsCommand {
+    type Output = DisposableDomainDataset;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let service = &app_state.disposable_domain_service;
+
+        if !service.refresh(true).await? {
+            return Err(AppError::BadRequest(
+                "A refresh is already in progress".to_string(),
+            ));
+        }
+
+        service.reload().await
+    }
+}
@@ -1,14 +1,44 @@
 use std::collections::HashMap;
 
-use crate::{queries::Query, error::AppError, queries::GetEmailTemplateByNameQuery, state::AppState};
+use serde_json::{Map, Value};
+use sqlx::Row;
 
-use super::Command;
+use crate::{
+    error::AppError,
+    models::EmailRetryResult,
+    queries::{GetEmailTemplateByNameQuery, Query},
+    services::EmailTransport,
+    state::AppState,
+    validators::unfilled_placeholders,
+};
 
+use super::{Command, RecordUsageEventCommand, deployment_email_sender};
+
+/// Failed emails are given up on after this many attempts.
+const MAX_EMAIL_ATTEMPTS: i32 = 5;
+
+/// How many failed emails one retry run picks up.
+const RETRY_BATCH_SIZE: i64 = 100;
+
+/// What happens when a template uses a placeholder that has no value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateVariableCheck {
+    /// The email isn't sent and the command fails.
+    Strict,
+    /// The email goes out with the placeholder left blank, and a warning is
+    /// logged.
+    Lenient,
+}
+
+/// Renders one of the deployment's templates and sends it. Every attempt is
+/// recorded in the email outbox, so failed sends can be retried and
+/// customers can see what was sent to whom.
 pub struct SendEmailCommand {
     deployment_id: i64,
     template_name: String,
     to_email: String,
     variables: HashMap<String, String>,
+    variable_check: TemplateVariableCheck,
 }
 
 impl SendEmailCommand {
@@ -23,14 +53,169 @@ impl SendEmailCommand {
             template_name,
             to_email,
             variables,
+            variable_check: TemplateVariableCheck::Lenient,
+        }
+    }
+
+    pub fn with_variable_check(mut self, variable_check: TemplateVariableCheck) -> Self {
+        self.variable_check = variable_check;
+        self
+    }
+}
+
+/// Handlebars reads `{{invitation.expires_in_days}}` as a path into nested
+/// objects, so dotted variable names are nested the same way. A name that
+/// is also the start of a longer one keeps its own value.
+fn template_context(variables: &HashMap<String, String>) -> Value {
+    fn insert(object: &mut Map<String, Value>, path: &[&str], value: &str) {
+        match path {
+            [] => {}
+            [name] => {
+                object.insert(name.to_string(), Value::String(value.to_string()));
+            }
+            [name, rest @ ..] => {
+                let entry = object
+                    .entry(name.to_string())
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(inner) = entry {
+                    insert(inner, rest, value);
+                }
+            }
+        }
+    }
+
+    let mut names: Vec<&String> = variables.keys().collect();
+    names.sort();
+
+    let mut context = Map::new();
+    for name in names {
+        let path: Vec<&str> = name.split('.').collect();
+        insert(&mut context, &path, &variables[name]);
+    }
+
+    Value::Object(context)
+}
+
+fn html_to_text(body_html: &str) -> String {
+    // Create a simple text version by stripping HTML tags (basic implementation)
+    let body_text = body_html
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("</p>", "\n\n")
+        .replace("</div>", "\n")
+        .replace("</h1>", "\n\n")
+        .replace("</h2>", "\n\n")
+        .replace("</h3>", "\n\n");
+
+    // Remove remaining HTML tags (simple regex replacement)
+    regex::Regex::new(r"<[^>]*>")
+        .unwrap()
+        .replace_all(&body_text, "")
+        .to_string()
+}
+
+/// A rendered email recorded in the outbox.
+struct OutboxEmail {
+    id: i64,
+    deployment_id: i64,
+    template_name: String,
+    from_email: String,
+    to_email: String,
+    subject: String,
+    body_html: String,
+    body_text: Option<String>,
+}
+
+impl OutboxEmail {
+    /// Sends the email and records the outcome. Once sent, the rendered
+    /// body is dropped from the outbox.
+    async fn deliver(
+        &self,
+        app_state: &AppState,
+        transport: &EmailTransport,
+    ) -> Result<String, AppError> {
+        let result = transport
+            .send_email(
+                &self.from_email,
+                &self.to_email,
+                &self.subject,
+                &self.body_html,
+                self.body_text.as_deref(),
+            )
+            .await;
+
+        match &result {
+            Ok(message_id) => {
+                tracing::info!(
+                    "Email sent successfully via {}: {} -> {} (Message ID: {})",
+                    transport.name(),
+                    self.from_email,
+                    self.to_email,
+                    message_id
+                );
+
+                sqlx::query(
+                    r#"
+                    UPDATE email_outbox
+                    SET status = 'sent', provider = $2, provider_message_id = $3,
+                        error = NULL, body_html = NULL, body_text = NULL,
+                        attempts = attempts + 1, sent_at = NOW(), updated_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(self.id)
+                .bind(transport.name())
+                .bind(message_id)
+                .execute(&app_state.db_pool)
+                .await?;
+
+                if let Err(e) =
+                    RecordUsageEventCommand::email_sent(self.deployment_id, &self.template_name)
+                        .execute(app_state)
+                        .await
+                {
+                    tracing::warn!(
+                        "Failed to record email usage for deployment {}: {}",
+                        self.deployment_id,
+                        e
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to send email via {}: from={}, to={}, error={}",
+                    transport.name(),
+                    self.from_email,
+                    self.to_email,
+                    e
+                );
+
+                sqlx::query(
+                    r#"
+                    UPDATE email_outbox
+                    SET status = 'failed', provider = $2, error = $3,
+                        attempts = attempts + 1, updated_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(self.id)
+                .bind(transport.name())
+                .bind(e.to_string())
+                .execute(&app_state.db_pool)
+                .await?;
+            }
         }
+
+        result
     }
 }
 
 impl Command for SendEmailCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let template_name = self.template_name.clone();
         let template = GetEmailTemplateByNameQuery::new(self.deployment_id, self.template_name)
             .execute(app_state)
             .await?;
@@ -43,62 +228,153 @@ impl Command for SendEmailCommand {
         .fetch_one(&app_state.db_pool)
         .await?;
 
+        let context = template_context(&self.variables);
+        let mut unfilled = unfilled_placeholders(&template.template_subject, &context);
+        for name in unfilled_placeholders(&template.template_data, &context) {
+            if !unfilled.contains(&name) {
+                unfilled.push(name);
+            }
+        }
+        if !unfilled.is_empty() {
+            match self.variable_check {
+                TemplateVariableCheck::Strict => {
+                    return Err(AppError::Internal(format!(
+                        "The {} template uses placeholders without values: {}",
+                        template_name,
+                        unfilled.join(", ")
+                    )));
+                }
+                TemplateVariableCheck::Lenient => {
+                    tracing::warn!(
+                        deployment_id = self.deployment_id,
+                        "The {} template uses placeholders without values: {}",
+                        template_name,
+                        unfilled.join(", ")
+                    );
+                }
+            }
+        }
+
         let subject = app_state
             .handlebars
-            .render_template(&template.template_subject, &self.variables)
+            .render_template(&template.template_subject, &context)
             .map_err(|e| AppError::BadRequest(format!("Failed to render subject: {}", e)))?;
 
         let body_html = app_state
             .handlebars
-            .render_template(&template.template_data, &self.variables)
+            .render_template(&template.template_data, &context)
             .map_err(|e| AppError::BadRequest(format!("Failed to render body: {}", e)))?;
 
-        // Create a simple text version by stripping HTML tags (basic implementation)
-        let body_text = body_html
-            .replace("<br>", "\n")
-            .replace("<br/>", "\n")
-            .replace("<br />", "\n")
-            .replace("</p>", "\n\n")
-            .replace("</div>", "\n")
-            .replace("</h1>", "\n\n")
-            .replace("</h2>", "\n\n")
-            .replace("</h3>", "\n\n");
-
-        // Remove remaining HTML tags (simple regex replacement)
-        let body_text = regex::Regex::new(r"<[^>]*>")
-            .unwrap()
-            .replace_all(&body_text, "")
-            .to_string();
-
-        let from_email = format!("{}@{}", template.template_from, deployment.mail_from_host);
-
-        // Send email via Postmark
-        match app_state.postmark_service.send_email(
-            &from_email,
-            &self.to_email,
-            &subject,
-            &body_html,
-            Some(&body_text),
-        ) {
-            Ok(response) => {
-                tracing::info!(
-                    "Email sent successfully via Postmark: {} -> {} (Message ID: {})",
-                    from_email,
-                    self.to_email,
-                    response.message_id
-                );
-            }
-            Err(e) => {
-                tracing::error!(
-                    "Failed to send email via Postmark: from={}, to={}, error={}",
-                    from_email,
-                    self.to_email,
-                    e
-                );
-                return Err(e);
+        let body_text = html_to_text(&body_html);
+
+        // A deployment with its own provider sends through it, from its own
+        // address if it set one.
+        let sender = deployment_email_sender(app_state, self.deployment_id).await?;
+        let from_email = sender
+            .from_address
+            .clone()
+            .unwrap_or_else(|| format!("{}@{}", template.template_from, deployment.mail_from_host));
+
+        let email = OutboxEmail {
+            id: app_state.sf.next_id()? as i64,
+            deployment_id: self.deployment_id,
+            template_name,
+            from_email,
+            to_email: self.to_email,
+            subject,
+            body_html,
+            body_text: Some(body_text),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO email_outbox (
+                id, deployment_id, template_name, to_email, from_email,
+                subject, body_html, body_text, status
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'queued')
+            "#,
+        )
+        .bind(email.id)
+        .bind(email.deployment_id)
+        .bind(&email.template_name)
+        .bind(&email.to_email)
+        .bind(&email.from_email)
+        .bind(&email.subject)
+        .bind(&email.body_html)
+        .bind(&email.body_text)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        email.deliver(app_state, &sender.transport).await?;
+
+        Ok(())
+    }
+}
+
+/// Sends the deployment's failed emails again, oldest first, through the
+/// provider it has now. Emails that have failed too often are left alone.
+pub struct RetryFailedEmailsCommand {
+    deployment_id: i64,
+}
+
+impl RetryFailedEmailsCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Command for RetryFailedEmailsCommand {
+    type Output = EmailRetryResult;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // Claimed by moving them back to queued, so two retries running at
+        // once don't send the same email twice.
+        let rows = sqlx::query(
+            r#"
+            UPDATE email_outbox SET status = 'queued', updated_at = NOW()
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE deployment_id = $1 AND status = 'failed'
+                    AND attempts < $2 AND body_html IS NOT NULL
+                ORDER BY created_at
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, template_name, to_email, from_email, subject, body_html, body_text
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(MAX_EMAIL_ATTEMPTS)
+        .bind(RETRY_BATCH_SIZE)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        let mut result = EmailRetryResult::default();
+        if rows.is_empty() {
+            return Ok(result);
+        }
+
+        let sender = deployment_email_sender(app_state, self.deployment_id).await?;
+        for row in rows {
+            let email = OutboxEmail {
+                id: row.get("id"),
+                deployment_id: self.deployment_id,
+                template_name: row.get("template_name"),
+                from_email: row.get("from_email"),
+                to_email: row.get("to_email"),
+                subject: row.get("subject"),
+                body_html: row.get("body_html"),
+                body_text: row.get("body_text"),
+            };
+
+            result.retried += 1;
+            match email.deliver(app_state, &sender.transport).await {
+                Ok(_) => result.sent += 1,
+                Err(_) => result.failed += 1,
             }
         }
 
-        Ok(())
+        Ok(result)
     }
 }
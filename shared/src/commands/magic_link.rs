@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{ConsumedMagicLink, EmailLinkSettings, IssuedMagicLink},
+    services::{hash_magic_link_token, magic_link_url},
+    state::AppState,
+    validators::RedirectUrlPolicy,
+};
+
+use super::{Command, SendEmailCommand, check_redirect_urls};
+
+/// The deployment settings magic links follow.
+struct MagicLinkTarget {
+    frontend_host: String,
+    allowed_redirect_origins: Vec<String>,
+    settings: EmailLinkSettings,
+}
+
+impl MagicLinkTarget {
+    async fn load(app_state: &AppState, deployment_id: i64) -> Result<Self, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT d.frontend_host, u.allowed_redirect_origins, a.magic_link
+            FROM deployments d
+            JOIN deployment_auth_settings a ON a.deployment_id = d.id
+            JOIN deployment_ui_settings u ON u.deployment_id = d.id
+            WHERE d.id = $1 AND d.deleted_at IS NULL
+            "#,
+        )
+        .bind(deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        Ok(Self {
+            frontend_host: row.get("frontend_host"),
+            allowed_redirect_origins: row.get("allowed_redirect_origins"),
+            settings: row
+                .get::<Option<serde_json::Value>, _>("magic_link")
+                .and_then(|settings| serde_json::from_value(settings).ok())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Fails with `redirect_url_not_allowed` unless the redirect is on the
+    /// deployment's frontend or one of its allowed redirect origins.
+    fn check_redirect(&self, redirect_url: Option<&str>) -> Result<(), AppError> {
+        let Some(redirect_url) = redirect_url else {
+            return Ok(());
+        };
+        check_redirect_urls(
+            RedirectUrlPolicy::new(&self.frontend_host, &self.allowed_redirect_origins)
+                .validate([("redirect_url", redirect_url)]),
+        )
+    }
+}
+
+/// Why a stored link can't be used, for a link that isn't usable.
+fn unusable_link_error(row: &PgRow) -> AppError {
+    if row.get::<Option<DateTime<Utc>>, _>("consumed_at").is_some() {
+        return AppError::coded(
+            ErrorCode::MagicLinkAlreadyUsed,
+            "This link has already been used. Request a new one to sign in.",
+        );
+    }
+
+    let reason = if row
+        .get::<Option<DateTime<Utc>>, _>("invalidated_at")
+        .is_some()
+    {
+        "superseded"
+    } else {
+        "expired"
+    };
+    AppError::coded(
+        ErrorCode::MagicLinkExpired,
+        "This link has expired. Request a new one to sign in.",
+    )
+    .with_details(json!({ "reason": reason }))
+}
+
+fn invalid_link() -> AppError {
+    AppError::coded(ErrorCode::InvalidMagicLink, "This link is invalid")
+}
+
+/// Emails a single-use sign-in link to an address. The link expires after
+/// the deployment's `magic_link.expiry_minutes` and, with
+/// `magic_link.invalidate_previous`, replaces the address's earlier unused
+/// links. A redirect has to be on the deployment's frontend or one of its
+/// allowed redirect origins.
+pub struct CreateMagicLinkCommand {
+    deployment_id: i64,
+    email_address: String,
+    redirect_url: Option<String>,
+}
+
+impl CreateMagicLinkCommand {
+    pub fn new(deployment_id: i64, email_address: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            email_address: email_address.into(),
+            redirect_url: None,
+        }
+    }
+
+    pub fn with_redirect_url(mut self, redirect_url: Option<String>) -> Self {
+        self.redirect_url = redirect_url;
+        self
+    }
+}
+
+impl Command for CreateMagicLinkCommand {
+    type Output = IssuedMagicLink;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let target = MagicLinkTarget::load(app_state, self.deployment_id).await?;
+        if !target.settings.enabled {
+            return Err(AppError::BadRequest(
+                "Magic links are disabled for this deployment".to_string(),
+            ));
+        }
+        target.check_redirect(self.redirect_url.as_deref())?;
+
+        let link_id = app_state.sf.next_id()? as i64;
+        let token = app_state.magic_link_token_signer.mint(self.deployment_id);
+        let expires_at = Utc::now() + Duration::minutes(target.settings.expiry_minutes);
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        if target.settings.invalidate_previous {
+            sqlx::query(
+                r#"
+                UPDATE magic_links SET invalidated_at = NOW()
+                WHERE deployment_id = $1
+                    AND LOWER(email_address) = LOWER($2)
+                    AND consumed_at IS NULL
+                    AND invalidated_at IS NULL
+                "#,
+            )
+            .bind(self.deployment_id)
+            .bind(&self.email_address)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO magic_links
+                (id, deployment_id, email_address, token_hash, redirect_url, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(link_id)
+        .bind(self.deployment_id)
+        .bind(&self.email_address)
+        .bind(hash_magic_link_token(&token))
+        .bind(&self.redirect_url)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let mut variables = HashMap::new();
+        variables.insert("app_name".to_string(), "Your App".to_string());
+        variables.insert(
+            "app_logo".to_string(),
+            "https://via.placeholder.com/150".to_string(),
+        );
+        variables.insert(
+            "action_url".to_string(),
+            magic_link_url(&target.frontend_host, &token),
+        );
+        variables.insert(
+            "link.expires_in_minutes".to_string(),
+            target.settings.expiry_minutes.to_string(),
+        );
+
+        SendEmailCommand::new(
+            self.deployment_id,
+            "magic_link_template".to_string(),
+            self.email_address.clone(),
+            variables,
+        )
+        .execute(app_state)
+        .await?;
+
+        Ok(IssuedMagicLink {
+            id: link_id,
+            email_address: self.email_address,
+            expires_at,
+        })
+    }
+}
+
+/// Uses up a magic link. Fails with `invalid_magic_link` for tokens this
+/// deployment didn't issue, `magic_link_expired` for expired or replaced
+/// links, and `magic_link_already_used` for links that have been used, even
+/// when two requests race for the same link. A stored redirect that the
+/// deployment no longer allows fails the link without using it up.
+pub struct ConsumeMagicLinkCommand {
+    deployment_id: i64,
+    token: String,
+}
+
+impl ConsumeMagicLinkCommand {
+    pub fn new(deployment_id: i64, token: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            token: token.into(),
+        }
+    }
+
+    async fn find_link(&self, app_state: &AppState) -> Result<PgRow, AppError> {
+        sqlx::query(
+            r#"
+            SELECT id, email_address, redirect_url, expires_at, consumed_at, invalidated_at,
+                expires_at > NOW() AS live
+            FROM magic_links
+            WHERE deployment_id = $1 AND token_hash = $2
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(hash_magic_link_token(&self.token))
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(invalid_link)
+    }
+}
+
+impl Command for ConsumeMagicLinkCommand {
+    type Output = ConsumedMagicLink;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if !app_state
+            .magic_link_token_signer
+            .verify(self.deployment_id, &self.token)
+        {
+            return Err(invalid_link());
+        }
+
+        let link = self.find_link(app_state).await?;
+        let usable = link.get::<bool, _>("live")
+            && link
+                .get::<Option<DateTime<Utc>>, _>("consumed_at")
+                .is_none()
+            && link
+                .get::<Option<DateTime<Utc>>, _>("invalidated_at")
+                .is_none();
+        if !usable {
+            return Err(unusable_link_error(&link));
+        }
+
+        let link_id: i64 = link.get("id");
+        let email_address: String = link.get("email_address");
+        let redirect_url: Option<String> = link.get("redirect_url");
+
+        MagicLinkTarget::load(app_state, self.deployment_id)
+            .await?
+            .check_redirect(redirect_url.as_deref())?;
+
+        // Only one request gets to use the link; the others find it used.
+        let consumed = sqlx::query(
+            r#"
+            UPDATE magic_links SET consumed_at = NOW()
+            WHERE id = $1
+                AND consumed_at IS NULL
+                AND invalidated_at IS NULL
+                AND expires_at > NOW()
+            "#,
+        )
+        .bind(link_id)
+        .execute(&app_state.db_pool)
+        .await?
+        .rows_affected();
+        if consumed == 0 {
+            return Err(unusable_link_error(&self.find_link(app_state).await?));
+        }
+
+        let user_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT e.user_id
+            FROM user_email_addresses e
+            JOIN users u ON u.id = e.user_id
+            WHERE e.deployment_id = $1
+                AND LOWER(e.email_address) = LOWER($2)
+                AND u.deleted_at IS NULL
+            LIMIT 1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&email_address)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        Ok(ConsumedMagicLink {
+            id: link_id,
+            email_address,
+            redirect_url,
+            user_id,
+        })
+    }
+}
@@ -3,49 +3,167 @@ use crate::{error::AppError, state::AppState};
 pub trait Command {
     type Output;
 
-    fn execute(self, app_state: &AppState) -> impl std::future::Future<Output = Result<Self::Output, AppError>> + Send;
+    fn run(self, app_state: &AppState) -> impl std::future::Future<Output = Result<Self::Output, AppError>> + Send;
+
+    /// Runs the command, recording its execution count, errors and latency.
+    fn execute(self, app_state: &AppState) -> impl std::future::Future<Output = Result<Self::Output, AppError>> + Send
+    where
+        Self: Sized,
+    {
+        PRIMARY_ONLY.scope((), crate::metrics::observe_command::<Self, _>(self.run(app_state)))
+    }
+}
+
+tokio::task_local! {
+    /// Set while a command runs, so the queries it calls read from the primary
+    /// and see the command's own writes.
+    static PRIMARY_ONLY: ();
+}
+
+pub(crate) fn in_command() -> bool {
+    PRIMARY_ONLY.try_with(|_| ()).is_ok()
 }
 
+pub mod audit_log;
+pub mod auth_event;
+pub mod background_job;
 pub mod create_organization;
 pub mod create_workspace;
+pub mod custom_hostname_reconciliation;
+pub mod custom_hostname_status;
 mod delete_organization;
+mod delete_workspace;
 pub mod deployment;
+mod deployment_api_key;
+pub mod deployment_asset;
+pub mod deployment_clone;
+pub mod deployment_config;
+pub mod deployment_data_export;
+pub mod deployment_domain_migration;
+pub mod deployment_email_provider;
+pub mod deployment_log_export;
 pub mod deployment_email_template;
+pub mod deployment_feature_flags;
+pub mod deployment_invitation;
+pub mod deployment_mail_settings;
+pub mod deployment_promotion;
+pub mod deployment_provisioning;
+pub mod deployment_verification_status;
+pub mod disposable_domain;
 pub mod email;
+pub mod magic_link;
+mod organization_audit_event;
+mod organization_invitation;
 mod organization_member;
 mod organization_role;
+pub mod password_reset;
 pub mod project;
+pub mod project_collaborator;
+pub mod project_transfer;
+pub mod rate_limit;
 pub mod s3;
+mod scim;
+pub mod secret_encryption;
+pub mod sign_in_lockout;
+mod sso_connection;
+pub mod storage_upload;
+pub mod token_revocation;
 mod update_organization;
+mod update_workspace;
+pub mod usage;
 pub mod user;
+pub mod user_deletion;
+pub mod user_email_normalization;
+pub mod user_erasure;
 pub mod user_identifiers;
+pub mod user_web3_wallet;
+pub mod verification_code;
+pub mod waitlist;
+mod workspace_member;
+mod workspace_role;
 
 // AI-related commands
+pub mod ai_agent_session;
 pub mod ai_agents;
+pub mod ai_budget;
+pub mod ai_workflow_run;
+pub mod ai_workflow_trigger;
 pub mod ai_workflows;
+pub mod ai_tool_import;
 pub mod ai_tools;
 pub mod ai_knowledge_base;
+pub mod ai_vector_data;
 
 
 
+pub use audit_log::*;
+pub use auth_event::*;
+pub use background_job::*;
 pub use create_organization::*;
 pub use create_workspace::*;
+pub use custom_hostname_reconciliation::*;
+pub use custom_hostname_status::*;
 pub use delete_organization::*;
+pub use delete_workspace::*;
 pub use deployment::*;
+pub use deployment_api_key::*;
+pub use deployment_asset::*;
+pub use deployment_clone::*;
+pub use deployment_config::*;
+pub use deployment_data_export::*;
+pub use deployment_domain_migration::*;
+pub use deployment_email_provider::*;
+pub use deployment_log_export::*;
 pub use deployment_email_template::*;
+pub use deployment_feature_flags::*;
+pub use deployment_invitation::*;
+pub use deployment_mail_settings::*;
+pub use deployment_promotion::*;
+pub use deployment_provisioning::*;
+pub use deployment_verification_status::*;
+pub use disposable_domain::*;
 pub use email::*;
+pub use magic_link::*;
+pub use organization_audit_event::*;
+pub use organization_invitation::*;
 pub use organization_member::*;
 pub use organization_role::*;
+pub use password_reset::*;
 pub use project::*;
+pub use project_collaborator::*;
+pub use project_transfer::*;
+pub use rate_limit::*;
 pub use s3::*;
+pub use scim::*;
+pub use secret_encryption::*;
+pub use sign_in_lockout::*;
+pub use sso_connection::*;
+pub use storage_upload::*;
+pub use token_revocation::*;
 pub use update_organization::*;
+pub use update_workspace::*;
+pub use usage::*;
 pub use user::*;
+pub use user_deletion::*;
+pub(crate) use user_email_normalization::*;
+pub use user_erasure::*;
 pub use user_identifiers::*;
+pub use user_web3_wallet::*;
+pub use verification_code::*;
+pub use waitlist::*;
+pub use workspace_member::*;
+pub use workspace_role::*;
 
 // AI-related exports
+pub use ai_agent_session::*;
 pub use ai_agents::*;
+pub use ai_budget::*;
+pub use ai_workflow_run::*;
+pub use ai_workflow_trigger::*;
 pub use ai_workflows::*;
+pub use ai_tool_import::*;
 pub use ai_tools::*;
 pub use ai_knowledge_base::*;
+pub use ai_vector_data::*;
 
 
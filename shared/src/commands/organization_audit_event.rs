@@ -0,0 +1,213 @@
+use chrono::Utc;
+use serde_json::{Map, Value, json};
+use sqlx::{PgConnection, Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{OrganizationAuditEvent, OrganizationAuditEventType},
+    state::AppState,
+};
+
+use super::Command;
+
+/// Larger payloads lose their before/after snippets and keep only which
+/// fields changed.
+pub const MAX_ORGANIZATION_AUDIT_PAYLOAD_BYTES: usize = 4096;
+
+/// Settings that organization admins can't read, so their changes are named
+/// but not shown.
+const HIDDEN_SETTINGS: &[&str] = &["private_metadata"];
+
+/// The payload for a settings change: which settings changed, and their
+/// values before and after. `before` and `after` are objects with the same
+/// keys; settings that didn't change are left out, and `None` means none did.
+pub(crate) fn settings_change_payload(before: Value, after: Value) -> Option<Value> {
+    let (Value::Object(before), Value::Object(mut after)) = (before, after) else {
+        return None;
+    };
+
+    let mut changed_fields = Vec::new();
+    let mut before_snippet = Map::new();
+    let mut after_snippet = Map::new();
+    for (field, old) in before {
+        let new = after.remove(&field).unwrap_or(Value::Null);
+        if old == new {
+            continue;
+        }
+        if !HIDDEN_SETTINGS.contains(&field.as_str()) {
+            before_snippet.insert(field.clone(), old);
+            after_snippet.insert(field.clone(), new);
+        }
+        changed_fields.push(field);
+    }
+    if changed_fields.is_empty() {
+        return None;
+    }
+
+    Some(json!({
+        "changed_fields": changed_fields,
+        "before": before_snippet,
+        "after": after_snippet,
+    }))
+}
+
+/// The settings of an organization or workspace row, as settings change
+/// payloads compare them.
+pub(crate) fn settings_snapshot(row: &PgRow) -> Value {
+    json!({
+        "name": row.get::<String, _>("name"),
+        "description": row.get::<Option<String>, _>("description"),
+        "image_url": row.get::<Option<String>, _>("image_url"),
+        "public_metadata": row.get::<Option<Value>, _>("public_metadata"),
+        "private_metadata": row.get::<Option<Value>, _>("private_metadata"),
+    })
+}
+
+fn cap_payload(mut payload: Value) -> Value {
+    let size = |payload: &Value| serde_json::to_vec(payload).map_or(0, |bytes| bytes.len());
+    if size(&payload) <= MAX_ORGANIZATION_AUDIT_PAYLOAD_BYTES {
+        return payload;
+    }
+
+    if let Value::Object(fields) = &mut payload {
+        fields.remove("before");
+        fields.remove("after");
+        fields.insert("truncated".to_string(), Value::Bool(true));
+    }
+    if size(&payload) <= MAX_ORGANIZATION_AUDIT_PAYLOAD_BYTES {
+        payload
+    } else {
+        json!({ "truncated": true })
+    }
+}
+
+/// Adds an event to an organization's own audit stream, which its admins can
+/// list. Payloads over `MAX_ORGANIZATION_AUDIT_PAYLOAD_BYTES` are cut down.
+pub struct RecordOrganizationAuditEventCommand {
+    deployment_id: i64,
+    organization_id: i64,
+    workspace_id: Option<i64>,
+    actor: Option<String>,
+    event_type: OrganizationAuditEventType,
+    payload: Value,
+}
+
+impl RecordOrganizationAuditEventCommand {
+    pub fn new(
+        deployment_id: i64,
+        organization_id: i64,
+        event_type: OrganizationAuditEventType,
+    ) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            workspace_id: None,
+            actor: None,
+            event_type,
+            payload: json!({}),
+        }
+    }
+
+    pub fn with_workspace_id(mut self, workspace_id: i64) -> Self {
+        self.workspace_id = Some(workspace_id);
+        self
+    }
+
+    pub fn with_actor(mut self, actor: Option<String>) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Writes the event on an existing connection so it commits or rolls
+    /// back together with the change it describes.
+    pub(crate) async fn execute_with(
+        self,
+        id: i64,
+        conn: &mut PgConnection,
+    ) -> Result<OrganizationAuditEvent, AppError> {
+        let created_at = Utc::now();
+        let payload = cap_payload(self.payload);
+
+        sqlx::query(
+            r#"
+            INSERT INTO organization_audit_events
+                (id, created_at, deployment_id, organization_id, workspace_id, actor, event_type, payload)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(id)
+        .bind(created_at)
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .bind(self.workspace_id)
+        .bind(&self.actor)
+        .bind(self.event_type.as_str())
+        .bind(&payload)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(OrganizationAuditEvent {
+            id,
+            created_at,
+            organization_id: self.organization_id,
+            workspace_id: self.workspace_id,
+            actor: self.actor,
+            event_type: self.event_type.as_str().to_string(),
+            payload,
+        })
+    }
+}
+
+impl Command for RecordOrganizationAuditEventCommand {
+    type Output = OrganizationAuditEvent;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let id = app_state.sf.next_id()? as i64;
+        let mut conn = app_state.db_pool.acquire().await?;
+        self.execute_with(id, &mut conn).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_changes_keep_only_changed_visible_fields() {
+        let payload = settings_change_payload(
+            json!({ "name": "Acme", "description": "", "private_metadata": { "plan": "free" } }),
+            json!({ "name": "Acme Inc", "description": "", "private_metadata": { "plan": "pro" } }),
+        );
+
+        assert_eq!(
+            payload,
+            Some(json!({
+                "changed_fields": ["name", "private_metadata"],
+                "before": { "name": "Acme" },
+                "after": { "name": "Acme Inc" },
+            }))
+        );
+        assert_eq!(
+            settings_change_payload(json!({ "name": "Acme" }), json!({ "name": "Acme" })),
+            None
+        );
+    }
+
+    #[test]
+    fn oversized_payloads_drop_their_snippets() {
+        let long = "x".repeat(MAX_ORGANIZATION_AUDIT_PAYLOAD_BYTES);
+        let payload =
+            settings_change_payload(json!({ "description": "" }), json!({ "description": long }))
+                .map(cap_payload);
+
+        assert_eq!(
+            payload,
+            Some(json!({ "changed_fields": ["description"], "truncated": true }))
+        );
+    }
+}
@@ -0,0 +1,686 @@
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    commands::{AddOrganizationMemberCommand, Command, CreateUserCommand, RecordAuditLogCommand},
+    dto::json::{AcceptOrganizationInvitationRequest, CreateUserRequest},
+    error::{AppError, ErrorCode},
+    models::{OrganizationInvitation, OrganizationMemberDetails},
+    queries::{ORGANIZATION_INVITATION_COLUMNS, organization_invitation_from_row},
+    services::{InvitationClaims, organization_invitation_url},
+    state::AppState,
+};
+
+use super::{
+    SendEmailCommand,
+    organization_member::{ensure_member_capacity, ensure_roles_belong, lock_organization},
+    waitlist::{DEFAULT_INVITATION_EXPIRY_DAYS, frontend_host, template_variables},
+};
+
+async fn fetch_invitation(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    organization_id: i64,
+    invitation_id: i64,
+) -> Result<OrganizationInvitation, AppError> {
+    sqlx::query(&format!(
+        r#"
+        SELECT {} FROM organization_invitations
+        WHERE id = $1 AND organization_id = $2 AND deployment_id = $3
+        FOR UPDATE
+        "#,
+        ORGANIZATION_INVITATION_COLUMNS
+    ))
+    .bind(invitation_id)
+    .bind(organization_id)
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| organization_invitation_from_row(&row))
+    .ok_or_else(|| AppError::NotFound("Invitation not found".to_string()))
+}
+
+fn already_accepted(invitation: &OrganizationInvitation) -> AppError {
+    AppError::coded(
+        ErrorCode::InvitationAlreadyAccepted,
+        "The invitation has already been accepted",
+    )
+    .with_details(json!({
+        "user_id": invitation.accepted_user_id.map(|id| id.to_string()),
+    }))
+}
+
+/// Why the invitation can't be accepted, if it can't.
+fn unusable(invitation: &OrganizationInvitation) -> Option<AppError> {
+    if invitation.accepted_at.is_some() {
+        return Some(already_accepted(invitation));
+    }
+    if invitation.revoked_at.is_some() {
+        return Some(AppError::BadRequest(
+            "The invitation has been revoked".to_string(),
+        ));
+    }
+    if invitation.expiry <= Utc::now() {
+        return Some(AppError::BadRequest(
+            "The invitation has expired".to_string(),
+        ));
+    }
+    None
+}
+
+/// The user who has verified `email_address` in this deployment, if any.
+async fn verified_user_id(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    email_address: &str,
+) -> Result<Option<i64>, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT user_id, verified FROM user_email_addresses
+        WHERE deployment_id = $1 AND lower(email_address) = lower($2)
+        ORDER BY verified DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(email_address)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match row {
+        Some(row) if row.get::<bool, _>("verified") => Ok(Some(row.get("user_id"))),
+        // Signing up would clash with the address, and attaching the user
+        // would trust an address they haven't proven they own.
+        Some(_) => Err(AppError::BadRequest(
+            "The invited email address belongs to a user who hasn't verified it yet".to_string(),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Signs the invitation and emails it with the organization_invite_template.
+async fn send_invitation(
+    app_state: &AppState,
+    conn: &mut PgConnection,
+    invitation: &OrganizationInvitation,
+    expires_in_days: i64,
+) -> Result<(), AppError> {
+    let frontend_host = frontend_host(app_state, invitation.deployment_id).await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT
+            o.name AS organization_name,
+            NULLIF(TRIM(concat_ws(' ', u.first_name, u.last_name)), '') AS inviter_name,
+            u.username AS inviter_username
+        FROM organizations o
+        LEFT JOIN organization_memberships om
+            ON om.organization_id = o.id AND om.user_id = $2
+        LEFT JOIN users u ON u.id = om.user_id
+        WHERE o.id = $1
+        "#,
+    )
+    .bind(invitation.organization_id)
+    .bind(invitation.inviter_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let token = app_state.invitation_token_signer.sign(&InvitationClaims {
+        invitation_id: invitation.id,
+        deployment_id: invitation.deployment_id,
+        email_address: invitation.email_address.clone(),
+        expires_at: invitation.expiry,
+        organization_id: Some(invitation.organization_id),
+    })?;
+
+    let mut variables = template_variables(
+        organization_invitation_url(&frontend_host, &token),
+        expires_in_days,
+    );
+    variables.insert(
+        "organization_name".to_string(),
+        row.get("organization_name"),
+    );
+    if let Some(inviter_name) = row
+        .get::<Option<String>, _>("inviter_name")
+        .or_else(|| row.get("inviter_username"))
+    {
+        variables.insert("inviter_name".to_string(), inviter_name);
+    }
+
+    SendEmailCommand::new(
+        invitation.deployment_id,
+        "organization_invite_template".to_string(),
+        invitation.email_address.clone(),
+        variables,
+    )
+    .execute(app_state)
+    .await?;
+
+    Ok(())
+}
+
+/// Invites someone into an organization with a role, and emails them a signed
+/// link to accept it. An address can only have one pending invitation per
+/// organization; resend that one instead.
+pub struct CreateOrganizationInvitationCommand {
+    deployment_id: i64,
+    organization_id: i64,
+    email_address: String,
+    role_id: Option<i64>,
+    inviter_id: Option<i64>,
+    expiry_days: i64,
+    initiated_by: Option<String>,
+}
+
+impl CreateOrganizationInvitationCommand {
+    pub fn new(
+        deployment_id: i64,
+        organization_id: i64,
+        email_address: String,
+        role_id: Option<i64>,
+    ) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            email_address,
+            role_id,
+            inviter_id: None,
+            expiry_days: DEFAULT_INVITATION_EXPIRY_DAYS,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_inviter_id(mut self, inviter_id: Option<i64>) -> Self {
+        self.inviter_id = inviter_id;
+        self
+    }
+
+    pub fn with_expiry_days(mut self, expiry_days: Option<i64>) -> Self {
+        self.expiry_days = expiry_days.unwrap_or(DEFAULT_INVITATION_EXPIRY_DAYS);
+        self
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for CreateOrganizationInvitationCommand {
+    type Output = OrganizationInvitation;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let invitation_id = app_state.sf.next_id()? as i64;
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let email_address = self.email_address.trim().to_string();
+        let expiry = Utc::now() + Duration::days(self.expiry_days);
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let policy = lock_organization(&mut tx, self.deployment_id, self.organization_id).await?;
+
+        let already_member: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM organization_memberships om
+                JOIN user_email_addresses e ON e.user_id = om.user_id
+                WHERE om.organization_id = $1
+                    AND lower(e.email_address) = lower($2)
+                    AND e.verified
+            )
+            "#,
+        )
+        .bind(self.organization_id)
+        .bind(&email_address)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if already_member {
+            return Err(AppError::BadRequest(
+                "A user with this email address is already a member of this organization"
+                    .to_string(),
+            ));
+        }
+
+        let pending: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM organization_invitations
+            WHERE organization_id = $1
+                AND lower(email_address) = lower($2)
+                AND accepted_at IS NULL AND revoked_at IS NULL AND expiry > NOW()
+            LIMIT 1
+            "#,
+        )
+        .bind(self.organization_id)
+        .bind(&email_address)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(pending) = pending {
+            return Err(AppError::coded(
+                ErrorCode::AlreadyExists,
+                "This email address already has a pending invitation to the organization",
+            )
+            .with_details(json!({ "invitation_id": pending.to_string() })));
+        }
+
+        ensure_member_capacity(&mut tx, self.organization_id, &policy).await?;
+
+        let role_id = self.role_id.or(policy.member_role_id);
+        if let Some(role_id) = role_id {
+            ensure_roles_belong(
+                &mut tx,
+                self.deployment_id,
+                self.organization_id,
+                &[role_id],
+            )
+            .await?;
+        }
+
+        if let Some(inviter_id) = self.inviter_id {
+            let inviter_is_member: bool = sqlx::query_scalar(
+                "SELECT EXISTS(SELECT 1 FROM organization_memberships WHERE organization_id = $1 AND user_id = $2)",
+            )
+            .bind(self.organization_id)
+            .bind(inviter_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if !inviter_is_member {
+                return Err(AppError::BadRequest(
+                    "The inviter isn't a member of this organization".to_string(),
+                ));
+            }
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO organization_invitations (
+                id, created_at, updated_at, deployment_id, organization_id,
+                email_address, organization_role_id, inviter_id, expiry
+            )
+            VALUES ($1, NOW(), NOW(), $2, $3, $4, $5, $6, $7)
+            RETURNING {}
+            "#,
+            ORGANIZATION_INVITATION_COLUMNS
+        ))
+        .bind(invitation_id)
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .bind(&email_address)
+        .bind(role_id)
+        .bind(self.inviter_id)
+        .bind(expiry)
+        .fetch_one(&mut *tx)
+        .await?;
+        let invitation = organization_invitation_from_row(&row);
+
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "organization.invitation_created",
+            "organization_invitation",
+            invitation_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({
+            "organization_id": self.organization_id.to_string(),
+            "email_address": email_address,
+            "role_id": role_id.map(|id| id.to_string()),
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        send_invitation(app_state, &mut tx, &invitation, self.expiry_days).await?;
+
+        tx.commit().await?;
+
+        Ok(invitation)
+    }
+}
+
+/// Emails a pending or expired invitation again with a new link and expiry.
+/// Links sent before keep working until their own expiry.
+pub struct ResendOrganizationInvitationCommand {
+    deployment_id: i64,
+    organization_id: i64,
+    invitation_id: i64,
+    expiry_days: i64,
+    initiated_by: Option<String>,
+}
+
+impl ResendOrganizationInvitationCommand {
+    pub fn new(deployment_id: i64, organization_id: i64, invitation_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            invitation_id,
+            expiry_days: DEFAULT_INVITATION_EXPIRY_DAYS,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_expiry_days(mut self, expiry_days: Option<i64>) -> Self {
+        self.expiry_days = expiry_days.unwrap_or(DEFAULT_INVITATION_EXPIRY_DAYS);
+        self
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for ResendOrganizationInvitationCommand {
+    type Output = OrganizationInvitation;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let expiry = Utc::now() + Duration::days(self.expiry_days);
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let policy = lock_organization(&mut tx, self.deployment_id, self.organization_id).await?;
+
+        let invitation = fetch_invitation(
+            &mut tx,
+            self.deployment_id,
+            self.organization_id,
+            self.invitation_id,
+        )
+        .await?;
+
+        if invitation.accepted_at.is_some() {
+            return Err(already_accepted(&invitation));
+        }
+        if invitation.revoked_at.is_some() {
+            return Err(AppError::BadRequest(
+                "The invitation has been revoked".to_string(),
+            ));
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE organization_invitations
+            SET expiry = $2, updated_at = NOW()
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            ORGANIZATION_INVITATION_COLUMNS
+        ))
+        .bind(self.invitation_id)
+        .bind(expiry)
+        .fetch_one(&mut *tx)
+        .await?;
+        let invitation = organization_invitation_from_row(&row);
+
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "organization.invitation_resent",
+            "organization_invitation",
+            self.invitation_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({
+            "organization_id": self.organization_id.to_string(),
+            "email_address": invitation.email_address,
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        send_invitation(app_state, &mut tx, &invitation, self.expiry_days).await?;
+
+        tx.commit().await?;
+
+        Ok(invitation)
+    }
+}
+
+/// Stops an invitation that hasn't been accepted from being used. Revoking
+/// one twice returns it unchanged.
+pub struct RevokeOrganizationInvitationCommand {
+    deployment_id: i64,
+    organization_id: i64,
+    invitation_id: i64,
+    initiated_by: Option<String>,
+}
+
+impl RevokeOrganizationInvitationCommand {
+    pub fn new(deployment_id: i64, organization_id: i64, invitation_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            invitation_id,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for RevokeOrganizationInvitationCommand {
+    type Output = OrganizationInvitation;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let policy = lock_organization(&mut tx, self.deployment_id, self.organization_id).await?;
+
+        let invitation = fetch_invitation(
+            &mut tx,
+            self.deployment_id,
+            self.organization_id,
+            self.invitation_id,
+        )
+        .await?;
+
+        if invitation.accepted_at.is_some() {
+            return Err(already_accepted(&invitation));
+        }
+        if invitation.revoked_at.is_some() {
+            return Ok(invitation);
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE organization_invitations
+            SET revoked_at = NOW(), updated_at = NOW()
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            ORGANIZATION_INVITATION_COLUMNS
+        ))
+        .bind(self.invitation_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "organization.invitation_revoked",
+            "organization_invitation",
+            self.invitation_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({
+            "organization_id": self.organization_id.to_string(),
+            "email_address": invitation.email_address,
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(organization_invitation_from_row(&row))
+    }
+}
+
+/// Adds the invitee to the organization with the invited role. A user who has
+/// verified the invited address is attached as they are; otherwise one is
+/// signed up with that address and the request's profile fields first.
+///
+/// The member limit is checked again here, as seats can fill up while an
+/// invitation is pending.
+pub struct AcceptOrganizationInvitationCommand {
+    deployment_id: i64,
+    request: AcceptOrganizationInvitationRequest,
+}
+
+impl AcceptOrganizationInvitationCommand {
+    pub fn new(deployment_id: i64, request: AcceptOrganizationInvitationRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+
+    /// Fails early when the organization is full, so no user is signed up
+    /// for a seat that isn't there. The seat is only taken when the member is
+    /// added, which checks again under the organization's lock.
+    async fn ensure_seat(
+        &self,
+        app_state: &AppState,
+        organization_id: i64,
+    ) -> Result<(), AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+        let policy = lock_organization(&mut tx, self.deployment_id, organization_id).await?;
+        ensure_member_capacity(&mut tx, organization_id, &policy).await?;
+        tx.rollback().await?;
+        Ok(())
+    }
+
+    async fn join(
+        self,
+        app_state: &AppState,
+        invitation: &OrganizationInvitation,
+        user_id: Option<i64>,
+    ) -> Result<OrganizationMemberDetails, AppError> {
+        let user_id = match user_id {
+            Some(user_id) => user_id,
+            None => {
+                let request = self.request;
+                CreateUserCommand::new(
+                    self.deployment_id,
+                    CreateUserRequest {
+                        first_name: request.first_name.unwrap_or_default(),
+                        last_name: request.last_name.unwrap_or_default(),
+                        email_address: Some(invitation.email_address.clone()),
+                        phone_number: request.phone_number,
+                        username: request.username,
+                        password: request.password,
+                    },
+                )
+                .execute(app_state)
+                .await?
+                .id
+            }
+        };
+
+        AddOrganizationMemberCommand::new(
+            self.deployment_id,
+            invitation.organization_id,
+            user_id,
+            invitation.role_id.into_iter().collect(),
+        )
+        .execute(app_state)
+        .await
+    }
+}
+
+impl Command for AcceptOrganizationInvitationCommand {
+    type Output = OrganizationMemberDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let claims = app_state
+            .invitation_token_signer
+            .verify(&self.request.token)?;
+        let organization_id = claims
+            .organization_id
+            .filter(|_| claims.deployment_id == self.deployment_id)
+            .ok_or_else(|| AppError::BadRequest("The invitation is invalid".to_string()))?;
+
+        let mut conn = app_state.db_pool.acquire().await?;
+        let invitation = fetch_invitation(
+            &mut conn,
+            self.deployment_id,
+            organization_id,
+            claims.invitation_id,
+        )
+        .await?;
+        if let Some(error) = unusable(&invitation) {
+            return Err(error);
+        }
+
+        let user_id =
+            verified_user_id(&mut conn, self.deployment_id, &invitation.email_address).await?;
+        drop(conn);
+
+        self.ensure_seat(app_state, organization_id).await?;
+
+        let claimed = sqlx::query(
+            r#"
+            UPDATE organization_invitations
+            SET accepted_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expiry > NOW()
+            "#,
+        )
+        .bind(invitation.id)
+        .execute(&app_state.db_pool)
+        .await?
+        .rows_affected();
+
+        if claimed == 0 {
+            let mut conn = app_state.db_pool.acquire().await?;
+            let invitation = fetch_invitation(
+                &mut conn,
+                self.deployment_id,
+                organization_id,
+                invitation.id,
+            )
+            .await?;
+            return Err(unusable(&invitation).unwrap_or_else(|| {
+                AppError::Internal("The invitation could not be claimed".to_string())
+            }));
+        }
+
+        let member = match self.join(app_state, &invitation, user_id).await {
+            Ok(member) => member,
+            Err(e) => {
+                // Let the invitee try the link again once the problem is fixed.
+                sqlx::query(
+                    r#"
+                    UPDATE organization_invitations
+                    SET accepted_at = NULL, updated_at = NOW()
+                    WHERE id = $1 AND accepted_user_id IS NULL
+                    "#,
+                )
+                .bind(invitation.id)
+                .execute(&app_state.db_pool)
+                .await?;
+                return Err(e);
+            }
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE organization_invitations
+            SET accepted_user_id = $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(invitation.id)
+        .bind(member.user_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(member)
+    }
+}
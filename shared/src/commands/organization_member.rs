@@ -1,14 +1,204 @@
 use crate::{
-    commands::Command, error::AppError, models::OrganizationMemberDetails, state::AppState,
+    commands::{Command, RecordAuditLogCommand, RecordOrganizationAuditEventCommand},
+    error::{AppError, ErrorCode},
+    models::{OrganizationAuditEventType, OrganizationMemberDetails},
+    queries::{fetch_organization_member, invalidate_user_authorization_context},
+    state::AppState,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, Row};
 
+/// The organization's deployment settings that govern membership changes.
+pub(super) struct MembershipPolicy {
+    pub(super) project_id: i64,
+    pub(super) max_allowed_org_members: Option<i64>,
+    pub(super) creator_role_id: Option<i64>,
+    pub(super) member_role_id: Option<i64>,
+}
+
+/// Locks the organization row for the rest of the transaction, so concurrent
+/// changes can't both slip under the member limit or both remove one of the
+/// last two admins.
+pub(super) async fn lock_organization(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    organization_id: i64,
+) -> Result<MembershipPolicy, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            d.project_id,
+            s.max_allowed_org_members,
+            s.default_org_creator_role_id,
+            s.default_org_member_role_id
+        FROM organizations o
+        JOIN deployments d ON d.id = o.deployment_id
+        LEFT JOIN deployment_b2b_settings s
+            ON s.deployment_id = o.deployment_id AND s.deleted_at IS NULL
+        WHERE o.id = $1 AND o.deployment_id = $2 AND o.deleted_at IS NULL AND d.deleted_at IS NULL
+        FOR UPDATE OF o
+        "#,
+    )
+    .bind(organization_id)
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Organization not found".to_string()))?;
+
+    let role_id = |column: &str| row.get::<Option<i64>, _>(column).filter(|id| *id > 0);
+
+    Ok(MembershipPolicy {
+        project_id: row.get("project_id"),
+        max_allowed_org_members: row
+            .get::<Option<i64>, _>("max_allowed_org_members")
+            .filter(|max| *max > 0),
+        creator_role_id: role_id("default_org_creator_role_id"),
+        member_role_id: role_id("default_org_member_role_id"),
+    })
+}
+
+/// Accepts deployment-wide roles and roles defined on this organization;
+/// anything else belongs to another deployment or doesn't exist.
+pub(super) async fn ensure_roles_belong(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    organization_id: i64,
+    role_ids: &[i64],
+) -> Result<(), AppError> {
+    let found: Vec<i64> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM organization_roles
+        WHERE id = ANY($1)
+            AND (organization_id = $3 OR (deployment_id = $2 AND organization_id IS NULL))
+        "#,
+    )
+    .bind(role_ids)
+    .bind(deployment_id)
+    .bind(organization_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    if let Some(missing) = role_ids.iter().find(|id| !found.contains(id)) {
+        return Err(AppError::BadRequest(format!(
+            "Role {} doesn't belong to this deployment",
+            missing
+        )));
+    }
+
+    Ok(())
+}
+
+/// Refuses to add anyone to an organization that already has as many members
+/// as the deployment allows.
+pub(super) async fn ensure_member_capacity(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    policy: &MembershipPolicy,
+) -> Result<(), AppError> {
+    let Some(max_allowed_org_members) = policy.max_allowed_org_members else {
+        return Ok(());
+    };
+
+    let member_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM organization_memberships WHERE organization_id = $1",
+    )
+    .bind(organization_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if member_count >= max_allowed_org_members {
+        return Err(AppError::coded(
+            ErrorCode::OrganizationMemberLimitReached,
+            format!(
+                "Organizations in this deployment can't have more than {} members",
+                max_allowed_org_members
+            ),
+        )
+        .with_details(json!({
+            "max_allowed_org_members": max_allowed_org_members,
+        })));
+    }
+
+    Ok(())
+}
+
+/// Refuses to take the creator role away from the only member holding it,
+/// which would leave the organization without an admin.
+async fn ensure_other_admin(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    membership_id: i64,
+    creator_role_id: i64,
+) -> Result<(), AppError> {
+    let other_admins: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM organization_membership_roles
+        WHERE organization_id = $1
+            AND organization_role_id = $2
+            AND organization_membership_id <> $3
+        "#,
+    )
+    .bind(organization_id)
+    .bind(creator_role_id)
+    .bind(membership_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if other_admins == 0 {
+        return Err(AppError::coded(
+            ErrorCode::LastOrganizationAdmin,
+            "This member is the organization's only admin. Give another member the role first.",
+        )
+        .with_details(json!({
+            "membership_id": membership_id.to_string(),
+            "role_id": creator_role_id.to_string(),
+        })));
+    }
+
+    Ok(())
+}
+
+async fn assign_roles(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    membership_id: i64,
+    role_ids: &[i64],
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO organization_membership_roles (organization_membership_id, organization_role_id, organization_id)
+        SELECT $1, role_id, $2 FROM UNNEST($3::BIGINT[]) AS role_id
+        "#,
+    )
+    .bind(membership_id)
+    .bind(organization_id)
+    .bind(role_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub(crate) fn dedup_role_ids(mut role_ids: Vec<i64>) -> Vec<i64> {
+    role_ids.sort_unstable();
+    role_ids.dedup();
+    role_ids
+}
+
+pub(crate) fn role_ids_metadata(role_ids: &[i64]) -> Vec<String> {
+    role_ids.iter().map(ToString::to_string).collect()
+}
+
+/// Adds a user straight to an organization, without an invitation. Without
+/// roles the member gets the deployment's default member role.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddOrganizationMemberCommand {
     pub deployment_id: i64,
     pub organization_id: i64,
     pub user_id: i64,
     pub role_ids: Vec<i64>,
+    pub initiated_by: Option<String>,
 }
 
 impl AddOrganizationMemberCommand {
@@ -18,179 +208,156 @@ impl AddOrganizationMemberCommand {
             organization_id,
             user_id,
             role_ids,
+            initiated_by: None,
         }
     }
-}
 
-impl Command for AddOrganizationMemberCommand {
-    type Output = OrganizationMemberDetails;
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // Check if user exists
-        let user_exists = sqlx::query!("SELECT id FROM users WHERE id = $1", self.user_id)
-            .fetch_optional(&app_state.db_pool)
-            .await?;
+    /// Adds the member on `conn`, which the caller commits. The user's cached
+    /// authorization context is left for the caller to invalidate once it has.
+    pub(crate) async fn execute_with(
+        self,
+        app_state: &AppState,
+        conn: &mut PgConnection,
+    ) -> Result<OrganizationMemberDetails, AppError> {
+        let membership_id = app_state.sf.next_id()? as i64;
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let audit_event_id = app_state.sf.next_id()? as i64;
 
-        if user_exists.is_none() {
-            return Err(AppError::NotFound("User not found".to_string()));
-        }
+        let policy = lock_organization(conn, self.deployment_id, self.organization_id).await?;
 
-        // Check if organization exists
-        let org_exists = sqlx::query!(
-            "SELECT id FROM organizations WHERE deployment_id = $1 AND id = $2",
-            self.deployment_id,
-            self.organization_id
+        let user_exists: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM users WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL
+            )
+            "#,
         )
-        .fetch_optional(&app_state.db_pool)
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_one(&mut *conn)
         .await?;
 
-        if org_exists.is_none() {
-            return Err(AppError::NotFound("Organization not found".to_string()));
+        if !user_exists {
+            return Err(AppError::NotFound("User not found".to_string()));
         }
 
-        // Check if user is already a member
-        let existing_membership = sqlx::query!(
-            "SELECT id FROM organization_memberships WHERE organization_id = $1 AND user_id = $2",
-            self.organization_id,
-            self.user_id
+        let already_member: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM organization_memberships WHERE organization_id = $1 AND user_id = $2)",
         )
-        .fetch_optional(&app_state.db_pool)
+        .bind(self.organization_id)
+        .bind(self.user_id)
+        .fetch_one(&mut *conn)
         .await?;
 
-        if existing_membership.is_some() {
+        if already_member {
             return Err(AppError::BadRequest(
                 "User is already a member of this organization".to_string(),
             ));
         }
 
-        // Create membership
-        let membership = sqlx::query!(
+        ensure_member_capacity(conn, self.organization_id, &policy).await?;
+
+        let role_ids = match dedup_role_ids(self.role_ids) {
+            role_ids if role_ids.is_empty() => policy.member_role_id.into_iter().collect(),
+            role_ids => role_ids,
+        };
+        ensure_roles_belong(conn, self.deployment_id, self.organization_id, &role_ids).await?;
+
+        sqlx::query(
             r#"
             INSERT INTO organization_memberships (id, organization_id, user_id, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, created_at, updated_at
+            VALUES ($1, $2, $3, NOW(), NOW())
             "#,
-            app_state.sf.next_id()? as i64,
-            self.organization_id,
-            self.user_id,
-            chrono::Utc::now(),
-            chrono::Utc::now()
         )
-        .fetch_one(&app_state.db_pool)
+        .bind(membership_id)
+        .bind(self.organization_id)
+        .bind(self.user_id)
+        .execute(&mut *conn)
         .await?;
 
-        // Add role associations
-        for role_id in &self.role_ids {
-            sqlx::query!(
-                r#"
-                INSERT INTO organization_membership_roles (organization_membership_id, organization_role_id, organization_id)
-                VALUES ($1, $2, $3)
-                "#,
-                membership.id,
-                role_id,
-                self.organization_id
-            )
-            .execute(&app_state.db_pool)
+        assign_roles(conn, self.organization_id, membership_id, &role_ids).await?;
+
+        sqlx::query("UPDATE organizations SET member_count = member_count + 1 WHERE id = $1")
+            .bind(self.organization_id)
+            .execute(&mut *conn)
             .await?;
-        }
 
-        // Update organization member count
-        sqlx::query!(
-            "UPDATE organizations SET member_count = member_count + 1 WHERE id = $1",
-            self.organization_id
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "organization.member_added",
+            "organization_membership",
+            membership_id,
         )
-        .execute(&app_state.db_pool)
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by.clone())
+        .with_metadata(json!({
+            "organization_id": self.organization_id.to_string(),
+            "user_id": self.user_id.to_string(),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_log_id, conn)
         .await?;
 
-        // Fetch and return the complete member details
-        let member_details = sqlx::query!(
-            r#"
-            SELECT
-                om.id, om.created_at, om.updated_at,
-                om.organization_id, om.user_id,
-                u.first_name, u.last_name, u.username,
-                u.created_at as user_created_at,
-                e.email_address as "primary_email_address?",
-                p.phone_number as "primary_phone_number?"
-            FROM organization_memberships om
-            JOIN users u ON om.user_id = u.id
-            LEFT JOIN user_email_addresses e ON u.primary_email_address_id = e.id
-            LEFT JOIN user_phone_numbers p ON u.primary_phone_number_id = p.id
-            WHERE om.id = $1
-            "#,
-            membership.id
+        RecordOrganizationAuditEventCommand::new(
+            self.deployment_id,
+            self.organization_id,
+            OrganizationAuditEventType::MemberAdded,
         )
-        .fetch_one(&app_state.db_pool)
+        .with_actor(self.initiated_by)
+        .with_payload(json!({
+            "membership_id": membership_id.to_string(),
+            "user_id": self.user_id.to_string(),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_event_id, conn)
         .await?;
 
-        Ok(OrganizationMemberDetails {
-            id: member_details.id,
-            created_at: member_details.created_at,
-            updated_at: member_details.updated_at,
-            organization_id: member_details.organization_id,
-            user_id: member_details.user_id,
-            roles: {
-                // Get organization roles for this member via membership roles junction table
-                let role_rows = sqlx::query!(
-                    r#"
-                    SELECT org_role.id, org_role.created_at, org_role.updated_at, org_role.name, org_role.permissions
-                    FROM organization_membership_roles omr
-                    JOIN organization_roles org_role ON omr.organization_role_id = org_role.id
-                    JOIN organization_memberships om ON omr.organization_membership_id = om.id
-                    WHERE om.organization_id = $1 AND om.user_id = $2
-                    "#,
-                    member_details.organization_id,
-                    member_details.user_id
-                )
-                .fetch_all(&app_state.db_pool)
-                .await
-                .unwrap_or_default();
-
-                role_rows
-                    .into_iter()
-                    .map(|role_row| crate::models::OrganizationRole {
-                        id: role_row.id,
-                        created_at: role_row.created_at,
-                        updated_at: role_row.updated_at,
-                        name: role_row.name,
-                        permissions: role_row
-                            .permissions
-                            .iter()
-                            .enumerate()
-                            .map(|(i, permission)| crate::models::OrganizationPermission {
-                                id: (role_row.id * 1000 + i as i64), // Generate unique ID
-                                created_at: role_row.created_at,
-                                updated_at: role_row.updated_at,
-                                org_role_id: role_row.id,
-                                permission: permission.clone(),
-                            })
-                            .collect(),
-                    })
-                    .collect()
-            },
-            first_name: member_details.first_name,
-            last_name: member_details.last_name,
-            username: if member_details.username.is_empty() {
-                None
-            } else {
-                Some(member_details.username)
-            },
-            primary_email_address: member_details.primary_email_address,
-            primary_phone_number: member_details.primary_phone_number,
-            user_created_at: member_details.user_created_at,
-        })
+        let member = fetch_organization_member(
+            conn,
+            self.deployment_id,
+            self.organization_id,
+            membership_id,
+        )
+        .await?
+        .ok_or_else(|| AppError::Internal("Organization membership disappeared".to_string()))?;
+
+        Ok(member)
+    }
+}
+
+impl Command for AddOrganizationMemberCommand {
+    type Output = OrganizationMemberDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let deployment_id = self.deployment_id;
+
+        let mut tx = app_state.db_pool.begin().await?;
+        let member = self.execute_with(app_state, &mut tx).await?;
+        tx.commit().await?;
+
+        invalidate_user_authorization_context(app_state, deployment_id, member.user_id).await;
+
+        Ok(member)
     }
 }
 
+/// Replaces a member's roles. Every role has to belong to the organization's
+/// deployment, and the last admin can't give up the creator role.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct UpdateOrganizationMemberCommand {
+pub struct UpdateOrganizationMemberRoleCommand {
     pub deployment_id: i64,
     pub organization_id: i64,
     pub membership_id: i64,
     pub role_ids: Vec<i64>,
+    pub initiated_by: Option<String>,
 }
 
-impl UpdateOrganizationMemberCommand {
+impl UpdateOrganizationMemberRoleCommand {
     pub fn new(
         deployment_id: i64,
         organization_id: i64,
@@ -202,61 +369,125 @@ impl UpdateOrganizationMemberCommand {
             organization_id,
             membership_id,
             role_ids,
+            initiated_by: None,
         }
     }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
 }
 
-impl Command for UpdateOrganizationMemberCommand {
-    type Output = ();
+impl Command for UpdateOrganizationMemberRoleCommand {
+    type Output = OrganizationMemberDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let audit_event_id = app_state.sf.next_id()? as i64;
+        let role_ids = dedup_role_ids(self.role_ids);
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let policy = lock_organization(&mut tx, self.deployment_id, self.organization_id).await?;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // Check if membership exists
-        let membership_exists = sqlx::query!(
-            "SELECT id FROM organization_memberships WHERE id = $1 AND organization_id = $2",
+        let current = fetch_organization_member(
+            &mut tx,
+            self.deployment_id,
+            self.organization_id,
             self.membership_id,
-            self.organization_id
         )
-        .fetch_optional(&app_state.db_pool)
-        .await?;
+        .await?
+        .ok_or_else(|| AppError::NotFound("Organization membership not found".to_string()))?;
 
-        if membership_exists.is_none() {
-            return Err(AppError::NotFound(
-                "Organization membership not found".to_string(),
-            ));
+        ensure_roles_belong(&mut tx, self.deployment_id, self.organization_id, &role_ids).await?;
+
+        if let Some(creator_role_id) = policy.creator_role_id
+            && current.roles.iter().any(|role| role.id == creator_role_id)
+            && !role_ids.contains(&creator_role_id)
+        {
+            ensure_other_admin(
+                &mut tx,
+                self.organization_id,
+                self.membership_id,
+                creator_role_id,
+            )
+            .await?;
         }
 
-        // Remove existing role associations
-        sqlx::query!(
+        sqlx::query(
             "DELETE FROM organization_membership_roles WHERE organization_membership_id = $1",
-            self.membership_id
         )
-        .execute(&app_state.db_pool)
+        .bind(self.membership_id)
+        .execute(&mut *tx)
         .await?;
 
-        // Add new role associations
-        for role_id in &self.role_ids {
-            sqlx::query!(
-                r#"
-                INSERT INTO organization_membership_roles (organization_membership_id, organization_role_id, organization_id)
-                VALUES ($1, $2, $3)
-                "#,
-                self.membership_id,
-                role_id,
-                self.organization_id
-            )
-            .execute(&app_state.db_pool)
+        assign_roles(&mut tx, self.organization_id, self.membership_id, &role_ids).await?;
+
+        sqlx::query("UPDATE organization_memberships SET updated_at = NOW() WHERE id = $1")
+            .bind(self.membership_id)
+            .execute(&mut *tx)
             .await?;
-        }
 
-        Ok(())
+        let previous_role_ids: Vec<i64> = current.roles.iter().map(|role| role.id).collect();
+
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "organization.member_role_updated",
+            "organization_membership",
+            self.membership_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by.clone())
+        .with_metadata(json!({
+            "organization_id": self.organization_id.to_string(),
+            "user_id": current.user_id.to_string(),
+            "previous_role_ids": role_ids_metadata(&previous_role_ids),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        RecordOrganizationAuditEventCommand::new(
+            self.deployment_id,
+            self.organization_id,
+            OrganizationAuditEventType::MemberRoleChanged,
+        )
+        .with_actor(self.initiated_by)
+        .with_payload(json!({
+            "membership_id": self.membership_id.to_string(),
+            "user_id": current.user_id.to_string(),
+            "previous_role_ids": role_ids_metadata(&previous_role_ids),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_event_id, &mut tx)
+        .await?;
+
+        let member = fetch_organization_member(
+            &mut tx,
+            self.deployment_id,
+            self.organization_id,
+            self.membership_id,
+        )
+        .await?
+        .ok_or_else(|| AppError::Internal("Organization membership disappeared".to_string()))?;
+
+        tx.commit().await?;
+
+        invalidate_user_authorization_context(app_state, self.deployment_id, member.user_id).await;
+
+        Ok(member)
     }
 }
 
+/// Removes a member and returns the membership as it was. The organization's
+/// only admin can't be removed.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RemoveOrganizationMemberCommand {
     pub deployment_id: i64,
     pub organization_id: i64,
     pub membership_id: i64,
+    pub initiated_by: Option<String>,
 }
 
 impl RemoveOrganizationMemberCommand {
@@ -265,45 +496,103 @@ impl RemoveOrganizationMemberCommand {
             deployment_id,
             organization_id,
             membership_id,
+            initiated_by: None,
         }
     }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
 }
 
 impl Command for RemoveOrganizationMemberCommand {
-    type Output = ();
+    type Output = OrganizationMemberDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let audit_event_id = app_state.sf.next_id()? as i64;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // Check if membership exists
-        let membership_exists = sqlx::query!(
-            "SELECT id FROM organization_memberships WHERE id = $1 AND organization_id = $2",
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let policy = lock_organization(&mut tx, self.deployment_id, self.organization_id).await?;
+
+        let member = fetch_organization_member(
+            &mut tx,
+            self.deployment_id,
+            self.organization_id,
             self.membership_id,
-            self.organization_id
         )
-        .fetch_optional(&app_state.db_pool)
-        .await?;
+        .await?
+        .ok_or_else(|| AppError::NotFound("Organization membership not found".to_string()))?;
 
-        if membership_exists.is_none() {
-            return Err(AppError::NotFound(
-                "Organization membership not found".to_string(),
-            ));
+        if let Some(creator_role_id) = policy.creator_role_id
+            && member.roles.iter().any(|role| role.id == creator_role_id)
+        {
+            ensure_other_admin(
+                &mut tx,
+                self.organization_id,
+                self.membership_id,
+                creator_role_id,
+            )
+            .await?;
         }
 
-        // Delete membership (this should cascade to role associations)
-        sqlx::query!(
-            "DELETE FROM organization_memberships WHERE id = $1",
-            self.membership_id
+        sqlx::query(
+            "DELETE FROM organization_membership_roles WHERE organization_membership_id = $1",
+        )
+        .bind(self.membership_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM organization_memberships WHERE id = $1")
+            .bind(self.membership_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "UPDATE organizations SET member_count = GREATEST(member_count - 1, 0) WHERE id = $1",
+        )
+        .bind(self.organization_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let role_ids: Vec<i64> = member.roles.iter().map(|role| role.id).collect();
+
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "organization.member_removed",
+            "organization_membership",
+            self.membership_id,
         )
-        .execute(&app_state.db_pool)
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by.clone())
+        .with_metadata(json!({
+            "organization_id": self.organization_id.to_string(),
+            "user_id": member.user_id.to_string(),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_log_id, &mut tx)
         .await?;
 
-        // Update organization member count
-        sqlx::query!(
-            "UPDATE organizations SET member_count = member_count - 1 WHERE id = $1",
-            self.organization_id
+        RecordOrganizationAuditEventCommand::new(
+            self.deployment_id,
+            self.organization_id,
+            OrganizationAuditEventType::MemberRemoved,
         )
-        .execute(&app_state.db_pool)
+        .with_actor(self.initiated_by)
+        .with_payload(json!({
+            "membership_id": self.membership_id.to_string(),
+            "user_id": member.user_id.to_string(),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_event_id, &mut tx)
         .await?;
 
-        Ok(())
+        tx.commit().await?;
+
+        invalidate_user_authorization_context(app_state, self.deployment_id, member.user_id).await;
+
+        Ok(member)
     }
 }
@@ -1,260 +1,629 @@
+use std::collections::BTreeMap;
+
 use crate::{
-    error::AppError, state::AppState,
-    commands::Command, models::OrganizationRole,
+    commands::{Command, deployment_email_sender},
+    error::{AppError, ErrorCode},
+    models::{DeploymentOrganizationRole, ORGANIZATION_PERMISSIONS, OrganizationRoleUpdate},
+    queries::{
+        ORGANIZATION_ROLE_COLUMNS, RolePermissionChange,
+        invalidate_deployment_authorization_contexts, invalidate_users_authorization_contexts,
+        organization_role_from_row,
+    },
+    state::AppState,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
+use sqlx::{PgConnection, Row};
+
+/// Rejects permissions missing from `catalog` and drops duplicates, keeping
+/// the first occurrence of each.
+pub(crate) fn normalize_permissions(
+    permissions: Vec<String>,
+    catalog: &[&str],
+) -> Result<Vec<String>, AppError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(permissions.len());
+
+    for permission in permissions {
+        if !catalog.contains(&permission.as_str()) {
+            return Err(AppError::Validation(format!(
+                "'{}' isn't a known permission",
+                permission
+            )));
+        }
+        if !normalized.contains(&permission) {
+            normalized.push(permission);
+        }
+    }
+
+    Ok(normalized)
+}
+
+pub(crate) fn normalize_role_name(name: String) -> Result<String, AppError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::Validation("Role name can't be empty".to_string()));
+    }
+    Ok(name)
+}
+
+struct RoleSettings {
+    custom_roles_enabled: bool,
+    default_creator_role_id: Option<i64>,
+    default_member_role_id: Option<i64>,
+}
+
+async fn load_settings(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<RoleSettings, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT custom_org_role_enabled, default_org_creator_role_id, default_org_member_role_id
+        FROM deployment_b2b_settings
+        WHERE deployment_id = $1 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(match row {
+        Some(row) => RoleSettings {
+            custom_roles_enabled: row.get("custom_org_role_enabled"),
+            default_creator_role_id: row.get("default_org_creator_role_id"),
+            default_member_role_id: row.get("default_org_member_role_id"),
+        },
+        None => RoleSettings {
+            custom_roles_enabled: false,
+            default_creator_role_id: None,
+            default_member_role_id: None,
+        },
+    })
+}
+
+async fn ensure_custom_roles_enabled(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<(), AppError> {
+    if !load_settings(conn, deployment_id)
+        .await?
+        .custom_roles_enabled
+    {
+        return Err(AppError::BadRequest(
+            "Custom organization roles are disabled for this deployment".to_string(),
+        ));
+    }
+    Ok(())
+}
 
+async fn ensure_unique_name(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    organization_id: Option<i64>,
+    name: &str,
+    exclude_role_id: Option<i64>,
+) -> Result<(), AppError> {
+    let taken: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM organization_roles
+            WHERE deployment_id = $1
+                AND organization_id IS NOT DISTINCT FROM $2
+                AND lower(name) = lower($3)
+                AND id IS DISTINCT FROM $4
+        )
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(organization_id)
+    .bind(name)
+    .bind(exclude_role_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if taken {
+        return Err(AppError::BadRequest(
+            "Role with this name already exists".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Creates an organization role. Without an organization the role is shared
+/// by every organization in the deployment.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateOrganizationRoleCommand {
     pub deployment_id: i64,
-    pub organization_id: i64,
+    pub organization_id: Option<i64>,
     pub name: String,
     pub permissions: Vec<String>,
 }
 
 impl CreateOrganizationRoleCommand {
-    pub fn new(
-        deployment_id: i64,
-        organization_id: i64,
-        name: String,
-        permissions: Vec<String>,
-    ) -> Self {
+    pub fn new(deployment_id: i64, name: String, permissions: Vec<String>) -> Self {
         Self {
             deployment_id,
-            organization_id,
+            organization_id: None,
             name,
             permissions,
         }
     }
+
+    pub fn with_organization_id(mut self, organization_id: i64) -> Self {
+        self.organization_id = Some(organization_id);
+        self
+    }
 }
 
 impl Command for CreateOrganizationRoleCommand {
-    type Output = OrganizationRole;
+    type Output = DeploymentOrganizationRole;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // Check if organization exists
-        let org_exists = sqlx::query!(
-            "SELECT id FROM organizations WHERE deployment_id = $1 AND id = $2",
-            self.deployment_id,
-            self.organization_id
-        )
-        .fetch_optional(&app_state.db_pool)
-        .await?;
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let name = normalize_role_name(self.name)?;
+        let permissions = normalize_permissions(self.permissions, ORGANIZATION_PERMISSIONS)?;
 
-        if org_exists.is_none() {
-            return Err(AppError::NotFound("Organization not found".to_string()));
+        let mut conn = app_state.db_pool.acquire().await?;
+
+        ensure_custom_roles_enabled(&mut conn, self.deployment_id).await?;
+
+        if let Some(organization_id) = self.organization_id {
+            let exists: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM organizations WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL)",
+            )
+            .bind(self.deployment_id)
+            .bind(organization_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            if !exists {
+                return Err(AppError::NotFound("Organization not found".to_string()));
+            }
         }
 
-        // Check if role name already exists in this organization
-        let existing_role = sqlx::query!(
-            "SELECT id FROM organization_roles WHERE organization_id = $1 AND name = $2",
+        ensure_unique_name(
+            &mut conn,
+            self.deployment_id,
             self.organization_id,
-            self.name
+            &name,
+            None,
         )
-        .fetch_optional(&app_state.db_pool)
         .await?;
 
-        if existing_role.is_some() {
-            return Err(AppError::BadRequest(
-                "Role with this name already exists".to_string(),
-            ));
-        }
-
-        // Create role with permissions stored as array
-        let role = sqlx::query!(
+        let row = sqlx::query(&format!(
             r#"
-            INSERT INTO organization_roles (id, organization_id, name, permissions, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            RETURNING id, created_at, updated_at, permissions
+            INSERT INTO organization_roles
+                (id, deployment_id, organization_id, name, permissions, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, NOW(), NOW())
+            RETURNING {}
             "#,
-            app_state.sf.next_id()? as i64,
-            self.organization_id,
-            self.name,
-            &self.permissions,
-            chrono::Utc::now(),
-            chrono::Utc::now()
-        )
-        .fetch_one(&app_state.db_pool)
+            ORGANIZATION_ROLE_COLUMNS
+        ))
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .bind(name)
+        .bind(&permissions)
+        .fetch_one(&mut *conn)
         .await?;
 
-        // Convert permissions back to objects
-        let permission_objects: Vec<crate::models::OrganizationPermission> = role
-            .permissions
-            .into_iter()
-            .enumerate()
-            .map(
-                |(i, permission)| crate::models::OrganizationPermission {
-                    id: i as i64, // Use index as ID for now
-                    created_at: role.created_at,
-                    updated_at: role.updated_at,
-                    org_role_id: role.id,
-                    permission,
-                },
-            )
-            .collect();
-
-        Ok(OrganizationRole {
-            id: role.id,
-            created_at: role.created_at,
-            updated_at: role.updated_at,
-            name: self.name,
-            permissions: permission_objects,
-        })
+        Ok(organization_role_from_row(&row))
     }
 }
 
+/// Updates an organization role. Members holding it have their cached
+/// authorization contexts dropped, and the result says whose permissions
+/// changed. With `notify`, the admins of each organization with affected
+/// members are emailed a summary of the change.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateOrganizationRoleCommand {
     pub deployment_id: i64,
-    pub organization_id: i64,
+    pub organization_id: Option<i64>,
     pub role_id: i64,
     pub name: Option<String>,
     pub permissions: Option<Vec<String>>,
+    pub notify: bool,
 }
 
 impl UpdateOrganizationRoleCommand {
     pub fn new(
         deployment_id: i64,
-        organization_id: i64,
         role_id: i64,
         name: Option<String>,
         permissions: Option<Vec<String>>,
     ) -> Self {
         Self {
             deployment_id,
-            organization_id,
+            organization_id: None,
             role_id,
             name,
             permissions,
+            notify: false,
         }
     }
+
+    pub fn with_organization_id(mut self, organization_id: i64) -> Self {
+        self.organization_id = Some(organization_id);
+        self
+    }
+
+    pub fn with_notify(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
 }
 
 impl Command for UpdateOrganizationRoleCommand {
-    type Output = OrganizationRole;
+    type Output = OrganizationRoleUpdate;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self.name.is_none() && self.permissions.is_none() {
+            return Err(AppError::BadRequest("No fields to update".to_string()));
+        }
+
+        let name = self.name.map(normalize_role_name).transpose()?;
+        let permissions = self
+            .permissions
+            .map(|permissions| normalize_permissions(permissions, ORGANIZATION_PERMISSIONS))
+            .transpose()?;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        ensure_custom_roles_enabled(&mut tx, self.deployment_id).await?;
+
+        let current_permissions: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT permissions FROM organization_roles
+            WHERE id = $1 AND deployment_id = $2 AND organization_id IS NOT DISTINCT FROM $3
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.role_id)
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Organization role not found".to_string()))?;
+
+        if let Some(name) = &name {
+            ensure_unique_name(
+                &mut tx,
+                self.deployment_id,
+                self.organization_id,
+                name,
+                Some(self.role_id),
+            )
+            .await?;
+        }
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // Check if role exists
-        let role_exists = sqlx::query!(
-            "SELECT id FROM organization_roles WHERE id = $1 AND organization_id = $2",
+        let change = RolePermissionChange::load(
+            &mut tx,
+            self.deployment_id,
             self.role_id,
-            self.organization_id
+            &current_permissions,
+            permissions.as_deref().unwrap_or(&current_permissions),
         )
-        .fetch_optional(&app_state.db_pool)
         .await?;
 
-        if role_exists.is_none() {
-            return Err(AppError::NotFound(
-                "Organization role not found".to_string(),
-            ));
-        }
-
-        // Build update query dynamically
-        let mut query_parts = Vec::new();
-        let mut param_count = 2; // role_id is $1, updated_at will be the last param
+        let mut query_builder =
+            sqlx::QueryBuilder::new("UPDATE organization_roles SET updated_at = NOW()");
 
-        if self.name.is_some() {
-            query_parts.push(format!("name = ${}", param_count));
-            param_count += 1;
-        }
-        if self.permissions.is_some() {
-            query_parts.push(format!("permissions = ${}", param_count));
-            param_count += 1;
+        if let Some(name) = name {
+            query_builder.push(", name = ");
+            query_builder.push_bind(name);
         }
 
-        if query_parts.is_empty() {
-            return Err(AppError::BadRequest("No fields to update".to_string()));
+        if let Some(permissions) = permissions {
+            query_builder.push(", permissions = ");
+            query_builder.push_bind(permissions);
         }
 
-        query_parts.push(format!("updated_at = ${}", param_count));
+        query_builder.push(" WHERE id = ");
+        query_builder.push_bind(self.role_id);
+        query_builder.push(" RETURNING ");
+        query_builder.push(ORGANIZATION_ROLE_COLUMNS);
 
-        let query_str = format!(
-            "UPDATE organization_roles SET {} WHERE id = $1 RETURNING id, created_at, updated_at, name, permissions",
-            query_parts.join(", ")
-        );
+        let row = query_builder.build().fetch_one(&mut *tx).await?;
 
-        let mut query = sqlx::query(&query_str).bind(self.role_id);
+        tx.commit().await?;
 
-        if let Some(name) = &self.name {
-            query = query.bind(name);
-        }
-        if let Some(permissions) = &self.permissions {
-            query = query.bind(permissions);
+        // Holders' contexts name the role, so they go stale even when their
+        // permissions don't change.
+        invalidate_users_authorization_contexts(
+            app_state,
+            self.deployment_id,
+            &change.holder_user_ids,
+        )
+        .await;
+
+        let role = organization_role_from_row(&row);
+
+        if self.notify && !change.affected_members.is_empty() {
+            // The role is already saved, so a failed notification is only
+            // logged.
+            if let Err(e) = notify_organization_admins(app_state, &role, &change).await {
+                tracing::warn!(
+                    deployment_id = self.deployment_id,
+                    role_id = self.role_id,
+                    "Failed to notify organization admins of a role change: {}",
+                    e
+                );
+            }
         }
 
-        query = query.bind(chrono::Utc::now());
-
-        let role = query.fetch_one(&app_state.db_pool).await?;
+        Ok(OrganizationRoleUpdate {
+            affected_member_count: change.affected_member_count(),
+            affected_members: change.listed_affected_members(),
+            role,
+        })
+    }
+}
 
-        // Get permissions from database
-        let permissions_vec: Vec<String> = role.get("permissions");
+/// Emails the admins of every organization with members affected by a role
+/// change, saying how many of their members it reached. Sending happens in
+/// the background.
+async fn notify_organization_admins(
+    app_state: &AppState,
+    role: &DeploymentOrganizationRole,
+    change: &RolePermissionChange,
+) -> Result<(), AppError> {
+    let mut affected_by_organization: BTreeMap<i64, usize> = BTreeMap::new();
+    for member in &change.affected_members {
+        *affected_by_organization
+            .entry(member.organization_id)
+            .or_default() += 1;
+    }
+    let organization_ids: Vec<i64> = affected_by_organization.keys().copied().collect();
+
+    // Holding the deployment's creator role is what makes a member an admin.
+    let admins = sqlx::query(
+        r#"
+        SELECT DISTINCT o.id AS organization_id, o.name AS organization_name, e.email_address
+        FROM organization_membership_roles omr
+        JOIN organization_memberships m ON m.id = omr.organization_membership_id
+        JOIN organizations o ON o.id = m.organization_id
+        JOIN users u ON u.id = m.user_id
+        JOIN user_email_addresses e ON e.id = u.primary_email_address_id
+        JOIN deployment_b2b_settings s
+            ON s.deployment_id = o.deployment_id AND s.deleted_at IS NULL
+        WHERE o.deployment_id = $1
+            AND o.id = ANY($2)
+            AND omr.organization_role_id = s.default_org_creator_role_id
+            AND o.deleted_at IS NULL
+            AND m.deleted_at IS NULL
+            AND u.deleted_at IS NULL
+        "#,
+    )
+    .bind(role.deployment_id)
+    .bind(&organization_ids)
+    .fetch_all(&app_state.db_pool)
+    .await?;
+
+    if admins.is_empty() {
+        return Ok(());
+    }
 
-        let permission_objects = permissions_vec
-            .into_iter()
-            .enumerate()
-            .map(
-                |(i, permission)| crate::models::OrganizationPermission {
-                    id: i as i64,
-                    created_at: role.get("created_at"),
-                    updated_at: role.get("updated_at"),
-                    org_role_id: self.role_id,
-                    permission,
-                },
+    let mail_from_host: String =
+        sqlx::query_scalar("SELECT mail_from_host FROM deployments WHERE id = $1")
+            .bind(role.deployment_id)
+            .fetch_one(&app_state.db_pool)
+            .await?;
+    let sender = deployment_email_sender(app_state, role.deployment_id).await?;
+    let from = sender
+        .from_address
+        .unwrap_or_else(|| format!("notifications@{}", mail_from_host));
+
+    let describe = |permissions: &[String]| {
+        if permissions.is_empty() {
+            "none".to_string()
+        } else {
+            handlebars::html_escape(&permissions.join(", "))
+        }
+    };
+    let role_name = handlebars::html_escape(&role.name);
+    let added = describe(&change.added_permissions);
+    let removed = describe(&change.removed_permissions);
+
+    let messages: Vec<(String, String, String)> = admins
+        .iter()
+        .map(|admin| {
+            let organization_id: i64 = admin.get("organization_id");
+            let organization_name: String = admin.get("organization_name");
+            let affected = affected_by_organization[&organization_id];
+            (
+                admin.get("email_address"),
+                format!("The {} role's permissions changed", role.name),
+                format!(
+                    "<p>The permissions of the <strong>{}</strong> role were changed. This affects {} member{} of {}.</p><p>Added: {}</p><p>Removed: {}</p>",
+                    role_name,
+                    affected,
+                    if affected == 1 { "" } else { "s" },
+                    handlebars::html_escape(&organization_name),
+                    added,
+                    removed,
+                ),
             )
-            .collect();
-
-        Ok(OrganizationRole {
-            id: role.get("id"),
-            created_at: role.get("created_at"),
-            updated_at: role.get("updated_at"),
-            name: role.get("name"),
-            permissions: permission_objects,
         })
-    }
+        .collect();
+
+    let transport = sender.transport;
+    app_state.background_tasks.spawn(async move {
+        for (to, subject, body) in messages {
+            if let Err(e) = transport
+                .send_email(&from, &to, &subject, &body, None)
+                .await
+            {
+                tracing::error!("Failed to send role change email to {}: {}", to, e);
+            }
+        }
+    });
+
+    Ok(())
 }
 
+/// Deletes an organization role. Default roles are never deleted, and a role
+/// that members still hold needs `reassign_to` naming the role they move to.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteOrganizationRoleCommand {
     pub deployment_id: i64,
-    pub organization_id: i64,
+    pub organization_id: Option<i64>,
     pub role_id: i64,
+    pub reassign_to: Option<i64>,
 }
 
 impl DeleteOrganizationRoleCommand {
-    pub fn new(deployment_id: i64, organization_id: i64, role_id: i64) -> Self {
+    pub fn new(deployment_id: i64, role_id: i64) -> Self {
         Self {
             deployment_id,
-            organization_id,
+            organization_id: None,
             role_id,
+            reassign_to: None,
         }
     }
+
+    pub fn with_organization_id(mut self, organization_id: i64) -> Self {
+        self.organization_id = Some(organization_id);
+        self
+    }
+
+    pub fn with_reassign_to(mut self, reassign_to: Option<i64>) -> Self {
+        self.reassign_to = reassign_to;
+        self
+    }
 }
 
 impl Command for DeleteOrganizationRoleCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // Check if role exists
-        let role_exists = sqlx::query!(
-            "SELECT id FROM organization_roles WHERE id = $1 AND organization_id = $2",
-            self.role_id,
-            self.organization_id
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let locked = sqlx::query(
+            r#"
+            SELECT id FROM organization_roles
+            WHERE id = $1 AND deployment_id = $2 AND organization_id IS NOT DISTINCT FROM $3
+            FOR UPDATE
+            "#,
         )
-        .fetch_optional(&app_state.db_pool)
+        .bind(self.role_id)
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .fetch_optional(&mut *tx)
         .await?;
 
-        if role_exists.is_none() {
+        if locked.is_none() {
             return Err(AppError::NotFound(
                 "Organization role not found".to_string(),
             ));
         }
 
-        // Delete role (this should cascade to permissions and role assignments)
-        sqlx::query!("DELETE FROM organization_roles WHERE id = $1", self.role_id)
-            .execute(&app_state.db_pool)
+        let settings = load_settings(&mut tx, self.deployment_id).await?;
+        let default_for = if settings.default_creator_role_id == Some(self.role_id) {
+            Some("creator")
+        } else if settings.default_member_role_id == Some(self.role_id) {
+            Some("member")
+        } else {
+            None
+        };
+
+        if let Some(default_for) = default_for {
+            return Err(AppError::coded(
+                ErrorCode::RoleInUse,
+                "This role is a default organization role and can't be deleted.",
+            )
+            .with_details(serde_json::json!({
+                "role_id": self.role_id.to_string(),
+                "default_for": default_for,
+            })));
+        }
+
+        let member_count: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(DISTINCT organization_membership_id)
+            FROM organization_membership_roles
+            WHERE organization_role_id = $1
+            "#,
+        )
+        .bind(self.role_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if member_count > 0 {
+            let Some(reassign_to) = self.reassign_to else {
+                return Err(AppError::coded(
+                    ErrorCode::RoleInUse,
+                    "This role is still assigned to members. Pass a role to reassign them to.",
+                )
+                .with_details(serde_json::json!({
+                    "role_id": self.role_id.to_string(),
+                    "member_count": member_count,
+                })));
+            };
+
+            if reassign_to == self.role_id {
+                return Err(AppError::BadRequest(
+                    "Can't reassign members to the role being deleted".to_string(),
+                ));
+            }
+
+            // A deployment-wide role can only hand its members to another
+            // deployment-wide role, since they span organizations.
+            let target_exists: bool = sqlx::query_scalar(
+                r#"
+                SELECT EXISTS (
+                    SELECT 1 FROM organization_roles
+                    WHERE id = $1 AND deployment_id = $2
+                        AND (organization_id IS NULL OR organization_id = $3)
+                )
+                "#,
+            )
+            .bind(reassign_to)
+            .bind(self.deployment_id)
+            .bind(self.organization_id)
+            .fetch_one(&mut *tx)
             .await?;
 
+            if !target_exists {
+                return Err(AppError::BadRequest(format!(
+                    "Role {} can't take over this role's members",
+                    reassign_to
+                )));
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO organization_membership_roles
+                    (organization_membership_id, organization_role_id, organization_id)
+                SELECT omr.organization_membership_id, $2, omr.organization_id
+                FROM organization_membership_roles omr
+                WHERE omr.organization_role_id = $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM organization_membership_roles existing
+                        WHERE existing.organization_membership_id = omr.organization_membership_id
+                            AND existing.organization_role_id = $2
+                    )
+                "#,
+            )
+            .bind(self.role_id)
+            .bind(reassign_to)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                "DELETE FROM organization_membership_roles WHERE organization_role_id = $1",
+            )
+            .bind(self.role_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query("DELETE FROM organization_roles WHERE id = $1")
+            .bind(self.role_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        invalidate_deployment_authorization_contexts(app_state, self.deployment_id).await;
+
         Ok(())
     }
 }
@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{PasswordResetMethod, PasswordResetResult},
+    queries::ValidatePasswordQuery,
+    services::{SessionRepository, SessionRevocation},
+    state::AppState,
+    utils::security::PasswordHasher,
+};
+
+use super::{Command, RecordAuditLogCommand, SendEmailCommand};
+
+const RESET_TOKEN_TTL_MINUTES: i64 = 60;
+
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn generate_reset_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+struct ResetTarget {
+    project_id: i64,
+    frontend_host: String,
+    email_address: Option<String>,
+}
+
+async fn load_reset_target(
+    app_state: &AppState,
+    deployment_id: i64,
+    user_id: i64,
+) -> Result<ResetTarget, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT d.project_id, d.frontend_host, e.email_address
+        FROM users u
+        JOIN deployments d ON d.id = u.deployment_id
+        LEFT JOIN user_email_addresses e ON e.id = u.primary_email_address_id
+        WHERE u.deployment_id = $1 AND u.id = $2 AND u.deleted_at IS NULL AND d.deleted_at IS NULL
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(user_id)
+    .fetch_optional(&app_state.db_pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    Ok(ResetTarget {
+        project_id: row.get("project_id"),
+        frontend_host: row.get("frontend_host"),
+        email_address: row.get("email_address"),
+    })
+}
+
+/// Resets a user's password on behalf of an admin. With a password, it's
+/// checked against the deployment's policy, set right away and every session
+/// is signed out. Without one, a one-time reset link is emailed to the user's
+/// primary address instead.
+pub struct ResetUserPasswordCommand {
+    deployment_id: i64,
+    user_id: i64,
+    password: Option<String>,
+    require_password_change: Option<bool>,
+    initiated_by: Option<String>,
+}
+
+impl ResetUserPasswordCommand {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            password: None,
+            require_password_change: None,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_password(mut self, password: Option<String>) -> Self {
+        self.password = password;
+        self
+    }
+
+    /// Defaults to requiring a change when an admin picked the password, and
+    /// to not requiring one for emailed links.
+    pub fn with_require_password_change(mut self, require_password_change: Option<bool>) -> Self {
+        self.require_password_change = require_password_change;
+        self
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+
+    async fn set_password(
+        self,
+        app_state: &AppState,
+        target: ResetTarget,
+        password: String,
+    ) -> Result<PasswordResetResult, AppError> {
+        ValidatePasswordQuery::new(self.deployment_id, &password)
+            .ensure_valid(app_state)
+            .await?;
+
+        let hashed_password = PasswordHasher::hash_password(&password)?;
+        let require_password_change = self.require_password_change.unwrap_or(true);
+        let audit_log_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET password = $1, require_password_change = $2, updated_at = NOW()
+            WHERE deployment_id = $3 AND id = $4
+            "#,
+        )
+        .bind(hashed_password)
+        .bind(require_password_change)
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        // Links sent before the reset shouldn't be able to undo it.
+        sqlx::query(
+            "UPDATE user_password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+        )
+        .bind(self.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        RecordAuditLogCommand::new(
+            target.project_id,
+            "user.password_reset",
+            "user",
+            self.user_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({
+            "method": PasswordResetMethod::Password.as_str(),
+            "require_password_change": require_password_change,
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let revoked_sessions = SessionRepository::new(&app_state.db_pool, &app_state.redis)
+            .revoke(self.deployment_id, self.user_id, SessionRevocation::All)
+            .await?;
+
+        Ok(PasswordResetResult {
+            user_id: self.user_id,
+            method: PasswordResetMethod::Password,
+            require_password_change,
+            revoked_sessions,
+            email_address: None,
+            expires_at: None,
+        })
+    }
+
+    async fn send_reset_link(
+        self,
+        app_state: &AppState,
+        target: ResetTarget,
+    ) -> Result<PasswordResetResult, AppError> {
+        let email_address = target.email_address.ok_or_else(|| {
+            AppError::BadRequest(
+                "User has no primary email address to send a reset link to".to_string(),
+            )
+        })?;
+
+        let token = generate_reset_token();
+        let expires_at = Utc::now() + Duration::minutes(RESET_TOKEN_TTL_MINUTES);
+        let token_id = app_state.sf.next_id()? as i64;
+        let audit_log_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        // Only the newest link works.
+        sqlx::query(
+            "UPDATE user_password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+        )
+        .bind(self.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_password_reset_tokens
+                (id, deployment_id, user_id, token_hash, expires_at, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(token_id)
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .bind(hash_reset_token(&token))
+        .bind(expires_at)
+        .bind(&self.initiated_by)
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(require_password_change) = self.require_password_change {
+            sqlx::query(
+                "UPDATE users SET require_password_change = $1, updated_at = NOW() WHERE id = $2",
+            )
+            .bind(require_password_change)
+            .bind(self.user_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        RecordAuditLogCommand::new(
+            target.project_id,
+            "user.password_reset_requested",
+            "user",
+            self.user_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({
+            "method": PasswordResetMethod::Email.as_str(),
+            "expires_at": expires_at,
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let mut variables = HashMap::new();
+        variables.insert("app_name".to_string(), "Your App".to_string());
+        variables.insert(
+            "app_logo".to_string(),
+            "https://via.placeholder.com/150".to_string(),
+        );
+        variables.insert(
+            "action_url".to_string(),
+            format!(
+                "https://{}/reset-password?token={}",
+                target.frontend_host, token
+            ),
+        );
+        variables.insert(
+            "code.expires_in_minutes".to_string(),
+            RESET_TOKEN_TTL_MINUTES.to_string(),
+        );
+
+        SendEmailCommand::new(
+            self.deployment_id,
+            "reset_password_code_template".to_string(),
+            email_address.clone(),
+            variables,
+        )
+        .execute(app_state)
+        .await?;
+
+        Ok(PasswordResetResult {
+            user_id: self.user_id,
+            method: PasswordResetMethod::Email,
+            require_password_change: self.require_password_change.unwrap_or(false),
+            revoked_sessions: 0,
+            email_address: Some(email_address),
+            expires_at: Some(expires_at),
+        })
+    }
+}
+
+impl Command for ResetUserPasswordCommand {
+    type Output = PasswordResetResult;
+
+    async fn run(mut self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let target = load_reset_target(app_state, self.deployment_id, self.user_id).await?;
+
+        match self.password.take() {
+            Some(password) => self.set_password(app_state, target, password).await,
+            None => self.send_reset_link(app_state, target).await,
+        }
+    }
+}
+
+/// Redeems an emailed reset link: sets the new password, clears any pending
+/// `require_password_change` and signs the user out everywhere.
+pub struct CompletePasswordResetCommand {
+    deployment_id: i64,
+    token: String,
+    password: String,
+}
+
+impl CompletePasswordResetCommand {
+    pub fn new(deployment_id: i64, token: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            token: token.into(),
+            password: password.into(),
+        }
+    }
+}
+
+impl Command for CompletePasswordResetCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        ValidatePasswordQuery::new(self.deployment_id, &self.password)
+            .ensure_valid(app_state)
+            .await?;
+
+        let hashed_password = PasswordHasher::hash_password(&self.password)?;
+        let audit_log_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT t.id, t.user_id, d.project_id
+            FROM user_password_reset_tokens t
+            JOIN deployments d ON d.id = t.deployment_id
+            WHERE t.token_hash = $1 AND t.deployment_id = $2
+                AND t.used_at IS NULL AND t.expires_at > NOW()
+            FOR UPDATE OF t
+            "#,
+        )
+        .bind(hash_reset_token(&self.token))
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            AppError::BadRequest("The reset link is invalid or has expired".to_string())
+        })?;
+
+        let token_id: i64 = row.get("id");
+        let user_id: i64 = row.get("user_id");
+        let project_id: i64 = row.get("project_id");
+
+        sqlx::query("UPDATE user_password_reset_tokens SET used_at = NOW() WHERE id = $1")
+            .bind(token_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET password = $1, require_password_change = false, updated_at = NOW()
+            WHERE deployment_id = $2 AND id = $3
+            "#,
+        )
+        .bind(hashed_password)
+        .bind(self.deployment_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        RecordAuditLogCommand::new(project_id, "user.password_reset_completed", "user", user_id)
+            .with_deployment_id(self.deployment_id)
+            .with_metadata(json!({ "method": PasswordResetMethod::Email.as_str() }))
+            .execute_with(audit_log_id, &mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        SessionRepository::new(&app_state.db_pool, &app_state.redis)
+            .revoke(self.deployment_id, user_id, SessionRevocation::All)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Sets or clears the flag that makes a user change their password at their
+/// next sign-in.
+pub struct SetRequirePasswordChangeCommand {
+    deployment_id: i64,
+    user_id: i64,
+    require_password_change: bool,
+    initiated_by: Option<String>,
+}
+
+impl SetRequirePasswordChangeCommand {
+    pub fn new(deployment_id: i64, user_id: i64, require_password_change: bool) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            require_password_change,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for SetRequirePasswordChangeCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let target = load_reset_target(app_state, self.deployment_id, self.user_id).await?;
+        let audit_log_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        sqlx::query(
+            "UPDATE users SET require_password_change = $1, updated_at = NOW() WHERE deployment_id = $2 AND id = $3",
+        )
+        .bind(self.require_password_change)
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        RecordAuditLogCommand::new(
+            target.project_id,
+            "user.require_password_change_updated",
+            "user",
+            self.user_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({ "require_password_change": self.require_password_change }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
@@ -1,29 +1,58 @@
 use crate::{
-    error::AppError,
+    error::{AppError, ErrorCode},
     models::{
         AuthFactorsEnabled, DarkModeSettings, Deployment, DeploymentAuthSettings,
         DeploymentB2bSettings, DeploymentB2bSettingsWithRoles, DeploymentEmailTemplate,
         DeploymentKeyPair, DeploymentMode, DeploymentOrganizationRole, DeploymentRestrictions,
         DeploymentSmsTemplate, DeploymentUISettings, DeploymentWorkspaceRole, EmailSettings,
         FirstFactor, IndividualAuthSettings, LightModeSettings, OauthCredentials, PasswordSettings,
-        PhoneSettings, ProjectWithDeployments, SecondFactorPolicy, SocialConnectionProvider,
-        UsernameSettings, VerificationPolicy,
+        PhoneSettings, ProjectWithDeployments, ProvisioningStatus, RestrictionEntry,
+        SecondFactorPolicy, SocialConnectionProvider, UsernameSettings, VerificationPolicy,
+        VerificationStatus,
     },
+    services::{ImageOutputFormat, KEY_PAIR_PRIVATE_KEY, https_url, process_image},
     state::AppState,
     utils::name::generate_random_name,
     validators::ProjectValidator,
 };
 use base64::{Engine, prelude::BASE64_STANDARD};
-use redis::AsyncCommands;
+use sqlx::PgConnection;
 use std::str::FromStr;
 
-use super::{Command, UploadToCdnCommand};
+use super::{
+    Command, DeleteDeploymentVectorDataCommand, DeleteExternalResourceJob,
+    EnqueueDeploymentProvisioningCommand, EnqueueJobCommand, ExternalResource, UploadToCdnCommand,
+    abort_active_domain_migration, cancel_pending_mail_from_host, spawn_provisioning_dispatch,
+    verify_pending_mail_from_host,
+};
+
+/// Saves the alternate first factors of a deployment's new auth settings,
+/// which the settings insert leaves at their default.
+async fn store_alternate_first_factors(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    auth_settings: &DeploymentAuthSettings,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE deployment_auth_settings SET alternate_first_factors = $2 WHERE deployment_id = $1",
+    )
+    .bind(deployment_id)
+    .bind(
+        serde_json::to_value(&auth_settings.alternate_first_factors)
+            .map_err(|e| AppError::Serialization(e.to_string()))?,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
 
 pub struct CreateProjectWithStagingDeploymentCommand {
     name: String,
     logo: Vec<u8>,
     has_logo: bool,
     auth_methods: Vec<String>,
+    owner_account_id: Option<i64>,
 }
 
 impl CreateProjectWithStagingDeploymentCommand {
@@ -34,9 +63,17 @@ impl CreateProjectWithStagingDeploymentCommand {
             logo,
             has_logo,
             auth_methods,
+            owner_account_id: None,
         }
     }
 
+    /// The console account that owns the project and is added as its owner
+    /// collaborator.
+    pub fn with_owner(mut self, account_id: i64) -> Self {
+        self.owner_account_id = Some(account_id);
+        self
+    }
+
     fn create_b2b_settings(&self, deployment_id: i64) -> DeploymentB2bSettingsWithRoles {
         DeploymentB2bSettingsWithRoles {
             settings: DeploymentB2bSettings {
@@ -126,6 +163,7 @@ impl CreateProjectWithStagingDeploymentCommand {
             phone_number: phone_settings,
             username: username_settings,
             first_factor,
+            alternate_first_factors,
             first_name: first_name_settings,
             last_name: last_name_settings,
             password: password_settings,
@@ -141,12 +179,7 @@ impl CreateProjectWithStagingDeploymentCommand {
         deployment_id: i64,
         frontend_host: String,
     ) -> DeploymentUISettings {
-        // Ensure frontend_host has https:// protocol
-        let frontend_url = if frontend_host.starts_with("https://") {
-            frontend_host
-        } else {
-            format!("https://{}", frontend_host)
-        };
+        let frontend_url = https_url(&frontend_host);
 
         DeploymentUISettings {
             deployment_id,
@@ -201,24 +234,31 @@ impl CreateProjectWithStagingDeploymentCommand {
 impl Command for CreateProjectWithStagingDeploymentCommand {
     type Output = ProjectWithDeployments;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let validator = ProjectValidator::new();
         validator.validate_project_name(&self.name)?;
         validator.validate_auth_methods(&self.auth_methods)?;
+        let logo = if self.has_logo {
+            Some(process_image(&self.logo, ImageOutputFormat::Png)?)
+        } else {
+            None
+        };
         let mut tx = app_state.db_pool.begin().await?;
         let project_id = app_state.sf.next_id()? as i64;
-        let image_url: String;
 
-        if self.has_logo {
-            image_url = UploadToCdnCommand::new(
-                format!("projects/{}/logo.png", project_id),
-                self.logo.clone(),
-            )
-            .execute(app_state)
-            .await?;
-        } else {
-            image_url = "".to_string();
-        }
+        let image_url = match logo {
+            Some(logo) => {
+                let (logo_path, thumbnail_path) =
+                    logo.file_paths(&format!("projects/{}", project_id), "logo");
+                UploadToCdnCommand::new(thumbnail_path, logo.thumbnail.bytes)
+                    .execute(app_state)
+                    .await?;
+                UploadToCdnCommand::new(logo_path, logo.image.bytes)
+                    .execute(app_state)
+                    .await?
+            }
+            None => "".to_string(),
+        };
 
         let project_row = sqlx::query!(
             r#"
@@ -235,18 +275,35 @@ impl Command for CreateProjectWithStagingDeploymentCommand {
         .fetch_one(&mut *tx)
         .await?;
 
+        if let Some(account_id) = self.owner_account_id {
+            sqlx::query("UPDATE projects SET owner_id = $1 WHERE id = $2")
+                .bind(account_id)
+                .bind(project_row.id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                r#"
+                INSERT INTO project_collaborators (id, project_id, account_id, role)
+                VALUES ($1, $2, $3, 'owner')
+                "#,
+            )
+            .bind(app_state.sf.next_id()? as i64)
+            .bind(project_row.id)
+            .bind(account_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
         let random_name = generate_random_name();
-        let count: i64 = app_state
-            .redis_client
-            .get_multiplexed_tokio_connection()
-            .await?
-            .incr(format!("project_count:{}", random_name), 1)
+        let count = app_state
+            .redis
+            .incr_counter(&format!("project_count:{}", random_name), 1, None)
             .await?;
 
         let hostname = format!("{}-{}", random_name, count);
 
-        let backend_host = format!("{}.backend-api.services", hostname);
-        let frontend_host = format!("{}.wacht.tech", hostname);
+        let backend_host = app_state.domains.staging_backend_host(&hostname);
+        let frontend_host = app_state.domains.staging_frontend_host(&hostname);
         let mut publishable_key = String::from("pk_test_");
 
         let base64_backend_host = BASE64_STANDARD.encode(format!("https://{}", backend_host));
@@ -277,13 +334,20 @@ impl Command for CreateProjectWithStagingDeploymentCommand {
             frontend_host,
             publishable_key,
             false,
-            "staging.wacht.services",
+            app_state.domains.staging_mail_from_host.as_str(),
             chrono::Utc::now(),
             chrono::Utc::now(),
         )
         .fetch_one(&mut *tx)
         .await?;
 
+        // Staging deployments have no records of their own to verify.
+        sqlx::query("UPDATE deployments SET verification_status = $2 WHERE id = $1")
+            .bind(deployment_row.id)
+            .bind(VerificationStatus::Verified.as_str())
+            .execute(&mut *tx)
+            .await?;
+
         let auth_settings = self.create_auth_settings(deployment_row.id);
 
         sqlx::query!(
@@ -367,9 +431,9 @@ impl Command for CreateProjectWithStagingDeploymentCommand {
         )
         .execute(&mut *tx)
         .await?;
+        store_alternate_first_factors(&mut tx, deployment_row.id, &auth_settings).await?;
 
-        let ui_settings =
-            self.create_ui_settings(deployment_row.id, format!("{}.wacht.tech", hostname));
+        let ui_settings = self.create_ui_settings(deployment_row.id, frontend_host.clone());
 
         let staging_ui_settings_query = format!(
             r#"
@@ -420,7 +484,7 @@ impl Command for CreateProjectWithStagingDeploymentCommand {
             .bind(ui_settings.use_initials_for_organization_profile_image)
             .bind(&ui_settings.default_user_profile_image_url)
             .bind(&ui_settings.default_organization_profile_image_url)
-            .bind(format!("https://{}.wacht.tech/waitlist", hostname))
+            .bind(format!("{}/waitlist", https_url(&frontend_host)))
             .bind(chrono::Utc::now())
             .bind(chrono::Utc::now())
             .execute(&mut *tx)
@@ -458,8 +522,8 @@ impl Command for CreateProjectWithStagingDeploymentCommand {
             serde_json::to_value(&restrictions.country_restrictions)
                 .map_err(|e| AppError::Serialization(e.to_string()))?,
             &restrictions.banned_keywords,
-            &restrictions.allowlisted_resources,
-            &restrictions.blocklisted_resources,
+            &RestrictionEntry::to_stored_list(&restrictions.allowlisted_resources),
+            &RestrictionEntry::to_stored_list(&restrictions.blocklisted_resources),
             restrictions.sign_up_mode.to_string(),
             chrono::Utc::now(),
             chrono::Utc::now(),
@@ -538,7 +602,9 @@ impl Command for CreateProjectWithStagingDeploymentCommand {
             app_state.sf.next_id()? as i64,
             deployment_row.id,
             key_pair.public_key,
-            key_pair.private_key,
+            app_state
+                .credential_cipher
+                .seal(&key_pair.private_key, KEY_PAIR_PRIVATE_KEY)?,
             chrono::Utc::now(),
             chrono::Utc::now(),
         )
@@ -797,6 +863,7 @@ impl Command for CreateProjectWithStagingDeploymentCommand {
             mode: DeploymentMode::from(deployment_row.mode),
             mail_from_host: deployment_row.mail_from_host,
             verification_status: Some(crate::models::VerificationStatus::Verified),
+            provisioning_status: ProvisioningStatus::Ready,
             domain_verification_records: None,
             email_verification_records: None,
         };
@@ -827,6 +894,16 @@ impl CreateProductionDeploymentCommand {
         }
     }
 
+    /// The frontend, backend and mail-from hosts a production deployment gets
+    /// on its custom domain.
+    pub(crate) fn production_hosts(custom_domain: &str) -> (String, String, String) {
+        (
+            format!("accounts.{}", custom_domain),
+            format!("frontend.{}", custom_domain),
+            format!("wcmail.{}", custom_domain),
+        )
+    }
+
     fn create_b2b_settings(&self, deployment_id: i64) -> DeploymentB2bSettingsWithRoles {
         DeploymentB2bSettingsWithRoles {
             settings: DeploymentB2bSettings {
@@ -916,6 +993,7 @@ impl CreateProductionDeploymentCommand {
             phone_number: phone_settings,
             username: username_settings,
             first_factor,
+            alternate_first_factors,
             first_name: first_name_settings,
             last_name: last_name_settings,
             password: password_settings,
@@ -932,12 +1010,7 @@ impl CreateProductionDeploymentCommand {
         frontend_host: String,
         app_name: String,
     ) -> DeploymentUISettings {
-        // Ensure frontend_host has https:// protocol
-        let frontend_url = if frontend_host.starts_with("https://") {
-            frontend_host
-        } else {
-            format!("https://{}", frontend_host)
-        };
+        let frontend_url = https_url(&frontend_host);
 
         DeploymentUISettings {
             deployment_id,
@@ -987,181 +1060,12 @@ impl CreateProductionDeploymentCommand {
             ..Default::default()
         }
     }
-
-    async fn cleanup_deployment_on_failure(
-        &self,
-        app_state: &AppState,
-        deployment_id: i64,
-    ) -> Result<(), AppError> {
-        tracing::warn!(
-            "Cleaning up deployment {} due to external service failure",
-            deployment_id
-        );
-        let mut tx = app_state.db_pool.begin().await?;
-
-        let now = chrono::Utc::now();
-        sqlx::query!(
-            "UPDATE deployments SET deleted_at = $1, updated_at = $1 WHERE id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "UPDATE deployment_auth_settings SET deleted_at = $1, updated_at = $1 WHERE deployment_id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "UPDATE deployment_ui_settings SET deleted_at = $1, updated_at = $1 WHERE deployment_id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "UPDATE deployment_b2b_settings SET deleted_at = $1, updated_at = $1 WHERE deployment_id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "UPDATE deployment_restrictions SET deleted_at = $1, updated_at = $1 WHERE deployment_id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "UPDATE deployment_email_templates SET deleted_at = $1, updated_at = $1 WHERE deployment_id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "UPDATE deployment_sms_templates SET deleted_at = $1, updated_at = $1 WHERE deployment_id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "UPDATE deployment_social_connections SET deleted_at = $1, updated_at = $1 WHERE deployment_id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "UPDATE deployment_key_pairs SET deleted_at = $1, updated_at = $1 WHERE deployment_id = $2",
-            now,
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "DELETE FROM workspace_roles WHERE deployment_id = $1",
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        sqlx::query!(
-            "DELETE FROM organization_roles WHERE deployment_id = $1",
-            deployment_id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        tx.commit().await?;
-
-        tracing::info!(
-            "Successfully cleaned up deployment {} and related records",
-            deployment_id
-        );
-        Ok(())
-    }
-
-    async fn cleanup_external_resources_on_failure(
-        &self,
-        app_state: &AppState,
-        frontend_hostname: &str,
-        backend_hostname: &str,
-        domain: &str,
-        cleanup_frontend: bool,
-        cleanup_backend: bool,
-        cleanup_postmark: bool,
-        postmark_domain_id: Option<i64>,
-    ) {
-        tracing::warn!("Cleaning up external resources for domain: {}", domain);
-
-        if cleanup_frontend {
-            if let Err(e) = app_state
-                .cloudflare_service
-                .delete_custom_hostname(frontend_hostname)
-            {
-                tracing::error!(
-                    "Failed to cleanup frontend hostname {}: {}",
-                    frontend_hostname,
-                    e
-                );
-            } else {
-                tracing::info!(
-                    "Successfully cleaned up frontend hostname: {}",
-                    frontend_hostname
-                );
-            }
-        }
-
-        if cleanup_backend {
-            if let Err(e) = app_state
-                .cloudflare_service
-                .delete_custom_hostname(backend_hostname)
-            {
-                tracing::error!(
-                    "Failed to cleanup backend hostname {}: {}",
-                    backend_hostname,
-                    e
-                );
-            } else {
-                tracing::info!(
-                    "Successfully cleaned up backend hostname: {}",
-                    backend_hostname
-                );
-            }
-        }
-
-        if cleanup_postmark {
-            if let Some(domain_id) = postmark_domain_id {
-                if let Err(e) = app_state.postmark_service.delete_domain(domain_id) {
-                    tracing::error!("Failed to cleanup Postmark domain {}: {}", domain_id, e);
-                } else {
-                    tracing::info!("Successfully cleaned up Postmark domain: {}", domain_id);
-                }
-            } else {
-                tracing::info!("No Postmark domain to cleanup for: {}", domain);
-            }
-        }
-    }
 }
 
 impl Command for CreateProductionDeploymentCommand {
     type Output = Deployment;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let validator = ProjectValidator::new();
         validator.validate_domain_format(&self.custom_domain)?;
         validator.validate_auth_methods(&self.auth_methods)?;
@@ -1186,9 +1090,11 @@ impl Command for CreateProductionDeploymentCommand {
         .await?;
 
         if existing_production.is_some() {
-            return Err(AppError::BadRequest(
-                "A production deployment already exists for this project".to_string(),
-            ));
+            return Err(AppError::coded(
+                ErrorCode::ProductionDeploymentExists,
+                "A production deployment already exists for this project",
+            )
+            .with_details(serde_json::json!({ "project_id": self.project_id.to_string() })));
         }
 
         let existing_domain = sqlx::query!(
@@ -1201,19 +1107,29 @@ impl Command for CreateProductionDeploymentCommand {
         .await?;
 
         if let Some(existing) = existing_domain {
-            return Err(AppError::BadRequest(format!(
-                "Domain '{}' is already in use by another deployment (ID: {})",
-                self.custom_domain, existing.id
-            )));
+            return Err(AppError::coded(
+                ErrorCode::DomainInUse,
+                format!(
+                    "Domain '{}' is already in use by another deployment",
+                    self.custom_domain
+                ),
+            )
+            .with_details(serde_json::json!({
+                "domain": self.custom_domain,
+                "deployment_id": existing.id.to_string(),
+            })));
         }
 
-        let backend_host = format!("frontend.{}", self.custom_domain);
-        let frontend_host = format!("accounts.{}", self.custom_domain);
-        let mail_from_host = format!("wcmail.{}", self.custom_domain);
+        let (frontend_host, backend_host, mail_from_host) =
+            Self::production_hosts(&self.custom_domain);
 
         let domain_verification_records = app_state
             .cloudflare_service
-            .generate_domain_verification_records(&frontend_host, &backend_host);
+            .generate_domain_verification_records(
+                &frontend_host,
+                &backend_host,
+                &app_state.domains,
+            );
 
         let empty_email_verification_records = crate::models::EmailVerificationRecords::default();
 
@@ -1335,6 +1251,7 @@ impl Command for CreateProductionDeploymentCommand {
         )
         .execute(&mut *tx)
         .await?;
+        store_alternate_first_factors(&mut tx, deployment_row.id, &auth_settings).await?;
 
         let ui_settings_query = format!(
             r#"
@@ -1574,8 +1491,8 @@ impl Command for CreateProductionDeploymentCommand {
             serde_json::to_value(&restrictions.country_restrictions)
                 .map_err(|e| AppError::Serialization(e.to_string()))?,
             &restrictions.banned_keywords,
-            &restrictions.allowlisted_resources,
-            &restrictions.blocklisted_resources,
+            &RestrictionEntry::to_stored_list(&restrictions.allowlisted_resources),
+            &RestrictionEntry::to_stored_list(&restrictions.blocklisted_resources),
             restrictions.sign_up_mode.to_string(),
             chrono::Utc::now(),
             chrono::Utc::now(),
@@ -1675,7 +1592,9 @@ impl Command for CreateProductionDeploymentCommand {
             app_state.sf.next_id()? as i64,
             deployment_row.id,
             key_pair.public_key,
-            key_pair.private_key,
+            app_state
+                .credential_cipher
+                .seal(&key_pair.private_key, KEY_PAIR_PRIVATE_KEY)?,
             chrono::Utc::now(),
             chrono::Utc::now(),
         )
@@ -1734,131 +1653,32 @@ impl Command for CreateProductionDeploymentCommand {
             }
         }
 
-        let postmark_domain = app_state.postmark_service.create_domain(&mail_from_host)?;
-        let postmark_domain_id = postmark_domain.id;
-        let email_verification_records = app_state
-            .postmark_service
-            .generate_email_verification_records(&postmark_domain);
-
-        sqlx::query!(
-            r#"
-            UPDATE deployments
-            SET email_verification_records = $1, updated_at = $2
-            WHERE id = $3
-            "#,
-            serde_json::to_value(&email_verification_records)
-                .map_err(|e| AppError::Serialization(e.to_string()))?,
-            chrono::Utc::now(),
-            deployment_row.id
-        )
-        .execute(&mut *tx)
-        .await?;
-
-        let frontend_hostname = format!("accounts.{}", self.custom_domain);
-        let backend_hostname = format!("frontend.{}", self.custom_domain);
-
-        let mut created_frontend_hostname = false;
-        let mut created_backend_hostname = false;
-        let created_postmark_domain = true;
-
-        let frontend_hostname_result = app_state
-            .cloudflare_service
-            .create_custom_hostname(&frontend_hostname, "accounts.wacht.services");
-
-        let frontend_hostname_id = match frontend_hostname_result {
-            Ok(custom_hostname) => {
-                created_frontend_hostname = true;
-                tracing::info!(
-                    "Successfully created frontend custom hostname: {}",
-                    frontend_hostname
-                );
-                Some(custom_hostname.id)
-            }
-            Err(e) => {
-                tracing::error!("Failed to create frontend custom hostname: {}", e);
-
-                let _ = self
-                    .cleanup_deployment_on_failure(app_state, deployment_row.id)
-                    .await;
-                return Err(AppError::External(format!(
-                    "Failed to create frontend custom hostname: {}. Deployment has been cleaned up.",
-                    e
-                )));
-            }
-        };
-
-        // Create backend custom hostname
-        let backend_hostname_result = app_state
-            .cloudflare_service
-            .create_custom_hostname(&backend_hostname, "frontend.wacht.services");
-
-        let backend_hostname_id = match backend_hostname_result {
-            Ok(custom_hostname) => {
-                created_backend_hostname = true;
-                tracing::info!(
-                    "Successfully created backend custom hostname: {}",
-                    backend_hostname
-                );
-                Some(custom_hostname.id)
-            }
-            Err(e) => {
-                tracing::error!("Failed to create backend custom hostname: {}", e);
-                self.cleanup_external_resources_on_failure(
-                    app_state,
-                    &frontend_hostname,
-                    &backend_hostname,
-                    &self.custom_domain,
-                    created_frontend_hostname,
-                    false,
-                    created_postmark_domain,
-                    Some(postmark_domain_id),
-                )
-                .await;
-                let _ = self
-                    .cleanup_deployment_on_failure(app_state, deployment_row.id)
-                    .await;
-                return Err(AppError::External(format!(
-                    "Failed to create backend custom hostname: {}. Resources have been cleaned up.",
-                    e
-                )));
-            }
-        };
-
-        tracing::info!(
-            "Postmark domain created successfully for: {}",
-            self.custom_domain
-        );
-
-        let mut updated_domain_verification_records = domain_verification_records;
-        updated_domain_verification_records.frontend_hostname_id = frontend_hostname_id;
-        updated_domain_verification_records.backend_hostname_id = backend_hostname_id;
-        sqlx::query!(
-            r#"
-            UPDATE deployments
-            SET domain_verification_records = $1, updated_at = $2
-            WHERE id = $3
-            "#,
-            serde_json::to_value(&updated_domain_verification_records)
-                .map_err(|e| AppError::Serialization(e.to_string()))?,
-            chrono::Utc::now(),
-            deployment_row.id
+        // Cloudflare and Postmark are called by the provisioning dispatcher once
+        // this commits, so a crash can't leave resources nobody knows about.
+        EnqueueDeploymentProvisioningCommand::new(
+            deployment_row.id,
+            frontend_host.clone(),
+            backend_host.clone(),
+            mail_from_host,
         )
-        .execute(&mut *tx)
+        .execute_with(app_state, &mut tx)
         .await?;
 
         tx.commit().await?;
 
+        spawn_provisioning_dispatch(app_state, Some(deployment_row.id));
+
         tracing::info!(
-            "Successfully created production deployment for domain: {} with hostnames: {}, {}",
+            "Created production deployment for domain: {}, provisioning hostnames: {}, {}",
             self.custom_domain,
-            frontend_hostname,
-            backend_hostname
+            frontend_host,
+            backend_host
         );
 
         Ok(Deployment {
             id: deployment_row.id,
             created_at: deployment_row.created_at,
-            updated_at: chrono::Utc::now(),
+            updated_at: deployment_row.updated_at,
             maintenance_mode: deployment_row.maintenance_mode,
             backend_host: deployment_row.backend_host,
             frontend_host: deployment_row.frontend_host,
@@ -1867,8 +1687,9 @@ impl Command for CreateProductionDeploymentCommand {
             mode: DeploymentMode::from(deployment_row.mode),
             mail_from_host: deployment_row.mail_from_host,
             verification_status: Some(crate::models::VerificationStatus::Pending),
-            domain_verification_records: Some(updated_domain_verification_records),
-            email_verification_records: Some(email_verification_records),
+            provisioning_status: ProvisioningStatus::Provisioning,
+            domain_verification_records: Some(domain_verification_records),
+            email_verification_records: Some(empty_email_verification_records),
         })
     }
 }
@@ -1886,7 +1707,13 @@ impl VerifyDeploymentDnsRecordsCommand {
 impl Command for VerifyDeploymentDnsRecordsCommand {
     type Output = Deployment;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // A new mail-from host that verifies takes over before the
+        // deployment's current records are read below.
+        if let Err(e) = verify_pending_mail_from_host(app_state, self.deployment_id).await {
+            tracing::warn!("Failed to verify pending mail-from host: {}", e);
+        }
+
         // Get current deployment with DNS records
         let deployment_row = sqlx::query!(
             r#"
@@ -1923,6 +1750,7 @@ impl Command for VerifyDeploymentDnsRecordsCommand {
                     .generate_domain_verification_records(
                         &deployment_row.frontend_host,
                         &deployment_row.backend_host,
+                        &app_state.domains,
                     )
             });
 
@@ -1932,29 +1760,39 @@ impl Command for VerifyDeploymentDnsRecordsCommand {
             .unwrap_or_default();
 
         // Verify domain records using DNS verification service with Cloudflare integration
-        app_state
+        let domain_timing = app_state
             .dns_verification_service
             .verify_domain_records(
                 &mut domain_verification_records,
-                &app_state.cloudflare_service,
+                app_state.cloudflare_service.as_ref(),
             )
+            .await
             .map_err(|e| {
                 tracing::warn!("Failed to verify domain records: {}", e);
                 e
             })
-            .unwrap_or(());
+            .unwrap_or_default();
 
         // Verify email records using DNS verification service
-        app_state
+        let email_timing = app_state
             .dns_verification_service
             .verify_email_records(&mut email_verification_records)
+            .await
             .map_err(|e| {
                 tracing::warn!("Failed to verify email records: {}", e);
                 e
             })
-            .unwrap_or(());
+            .unwrap_or_default();
 
-        tracing::info!("DNS verification completed for domain: {}", domain);
+        tracing::info!(
+            "DNS verification completed for domain {}: {} domain records in {}ms, {} email records in {}ms, {} timed out",
+            domain,
+            domain_timing.records,
+            domain_timing.total_ms,
+            email_timing.records,
+            email_timing.total_ms,
+            domain_timing.timed_out + email_timing.timed_out
+        );
 
         // Determine verification status based on record verification
         let domain_verified = app_state
@@ -1966,43 +1804,52 @@ impl Command for VerifyDeploymentDnsRecordsCommand {
             .dns_verification_service
             .are_email_records_verified(&email_verification_records);
 
-        let verification_status = if domain_verified && email_verified {
-            "verified"
-        } else {
-            "in_progress"
-        };
+        let verification_status = VerificationStatus::from_records(
+            Some(&domain_verification_records),
+            Some(&email_verification_records),
+        );
 
-        // Update the deployment with verified records (status update commented out until DB migration)
-        sqlx::query!(
+        sqlx::query(
             r#"
             UPDATE deployments
             SET domain_verification_records = $1,
                 email_verification_records = $2,
-                updated_at = $3
-            WHERE id = $4
+                verification_status = $3,
+                updated_at = $4
+            WHERE id = $5
             "#,
+        )
+        .bind(
             serde_json::to_value(&domain_verification_records)
                 .map_err(|e| AppError::Serialization(e.to_string()))?,
+        )
+        .bind(
             serde_json::to_value(&email_verification_records)
                 .map_err(|e| AppError::Serialization(e.to_string()))?,
-            chrono::Utc::now(),
-            self.deployment_id
         )
+        .bind(verification_status.as_str())
+        .bind(chrono::Utc::now())
+        .bind(self.deployment_id)
         .execute(&app_state.db_pool)
         .await?;
 
-        let final_verification_status = match verification_status {
-            "verified" => crate::models::VerificationStatus::Verified,
-            "in_progress" => crate::models::VerificationStatus::InProgress,
-            _ => crate::models::VerificationStatus::Pending,
-        };
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
+        let provisioning_status: String =
+            sqlx::query_scalar("SELECT provisioning_status FROM deployments WHERE id = $1")
+                .bind(self.deployment_id)
+                .fetch_one(&app_state.db_pool)
+                .await?;
 
         tracing::info!(
             "DNS verification completed for deployment {}: domain_verified={}, email_verified={}, status={}",
             self.deployment_id,
             domain_verified,
             email_verified,
-            verification_status
+            verification_status.as_str()
         );
 
         Ok(Deployment {
@@ -2016,7 +1863,8 @@ impl Command for VerifyDeploymentDnsRecordsCommand {
             project_id: deployment_row.project_id,
             mode: DeploymentMode::from(deployment_row.mode),
             mail_from_host: deployment_row.mail_from_host,
-            verification_status: Some(final_verification_status),
+            verification_status: Some(verification_status),
+            provisioning_status: ProvisioningStatus::from(provisioning_status),
             domain_verification_records: Some(domain_verification_records),
             email_verification_records: Some(email_verification_records),
         })
@@ -2037,7 +1885,25 @@ impl DeleteProjectCommand {
 impl Command for DeleteProjectCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // Done first, while the knowledge bases that say where the vectors
+        // are still exist, and outside the transaction, which would be held
+        // open for as long as Qdrant takes
+        let deployment_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT id FROM deployments WHERE project_id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+        for deployment_id in deployment_ids {
+            if let Err(e) = DeleteDeploymentVectorDataCommand::new(deployment_id)
+                .execute(app_state)
+                .await
+            {
+                tracing::warn!("Failed to cleanup vector data: {}", e);
+            }
+        }
+
         let mut tx = app_state.db_pool.begin().await?;
 
         let deployments = sqlx::query!(
@@ -2132,6 +1998,13 @@ impl Command for DeleteProjectCommand {
 
         tx.commit().await?;
 
+        for deployment in &deployments {
+            app_state
+                .cache_invalidator
+                .invalidate_deployment(deployment.id)
+                .await;
+        }
+
         Ok(())
     }
 }
@@ -2149,78 +2022,24 @@ impl DeleteDeploymentCommand {
         }
     }
 
-    async fn cleanup_external_resources(
-        &self,
-        app_state: &AppState,
-        deployment: &Deployment,
-    ) -> Result<(), AppError> {
-        tracing::info!(
-            "Cleaning up external resources for deployment {}",
-            self.deployment_id
-        );
-
-        // Only cleanup external resources for production deployments
-        if deployment.mode == DeploymentMode::Production {
-            if let Some(domain_records) = &deployment.domain_verification_records {
-                if let Some(frontend_hostname_id) = &domain_records.frontend_hostname_id {
-                    if let Err(e) = app_state
-                        .cloudflare_service
-                        .delete_custom_hostname(frontend_hostname_id)
-                    {
-                        tracing::warn!(
-                            "Failed to cleanup frontend hostname {}: {}",
-                            frontend_hostname_id,
-                            e
-                        );
-                    } else {
-                        tracing::info!(
-                            "Successfully cleaned up frontend hostname: {}",
-                            frontend_hostname_id
-                        );
-                    }
-                }
-
-                if let Some(backend_hostname_id) = &domain_records.backend_hostname_id {
-                    if let Err(e) = app_state
-                        .cloudflare_service
-                        .delete_custom_hostname(backend_hostname_id)
-                    {
-                        tracing::warn!(
-                            "Failed to cleanup backend hostname {}: {}",
-                            backend_hostname_id,
-                            e
-                        );
-                    } else {
-                        tracing::info!(
-                            "Successfully cleaned up backend hostname: {}",
-                            backend_hostname_id
-                        );
-                    }
-                }
-            }
-
-            if let Some(email_records) = &deployment.email_verification_records {
-                if let Some(postmark_domain_id) = email_records.postmark_domain_id {
-                    if let Err(e) = app_state.postmark_service.delete_domain(postmark_domain_id) {
-                        tracing::warn!(
-                            "Failed to cleanup Postmark domain {}: {}",
-                            postmark_domain_id,
-                            e
-                        );
-                    } else {
-                        tracing::info!(
-                            "Successfully cleaned up Postmark domain: {}",
-                            postmark_domain_id
-                        );
-                    }
-                }
-            }
+    /// The Cloudflare hostnames and Postmark domain to remove with the
+    /// deployment. Only production deployments have any.
+    pub(crate) fn external_resources(deployment: &Deployment) -> Vec<ExternalResource> {
+        if deployment.mode != DeploymentMode::Production {
+            return Vec::new();
         }
 
-        Ok(())
+        ExternalResource::recorded(
+            deployment.domain_verification_records.as_ref(),
+            deployment.email_verification_records.as_ref(),
+        )
     }
 
-    async fn cleanup_database_records(&self, app_state: &AppState) -> Result<(), AppError> {
+    async fn cleanup_database_records(
+        &self,
+        app_state: &AppState,
+        deployment: &Deployment,
+    ) -> Result<(), AppError> {
         tracing::info!(
             "Soft deleting database records for deployment {}",
             self.deployment_id
@@ -2239,8 +2058,42 @@ impl DeleteDeploymentCommand {
         .execute(&mut *tx)
         .await?;
 
+        // Customer credentials aren't kept for a deployment that's gone.
+        sqlx::query("DELETE FROM deployment_email_providers WHERE deployment_id = $1")
+            .bind(self.deployment_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Removed by the job worker, which keeps retrying while Cloudflare or
+        // Postmark can't be reached.
+        for resource in Self::external_resources(deployment) {
+            tracing::info!(
+                "Queueing removal of {} for deployment {}",
+                resource,
+                self.deployment_id
+            );
+            EnqueueJobCommand::new(DeleteExternalResourceJob::new(resource))
+                .execute_with(app_state, &mut tx)
+                .await?;
+        }
+
+        // The sending domain of a mail-from change in progress goes too, as
+        // does whatever a domain migration in progress has created.
+        cancel_pending_mail_from_host(app_state, &mut tx, self.deployment_id).await?;
+        let aborted_migration =
+            abort_active_domain_migration(app_state, &mut tx, self.deployment_id).await?;
+
         tx.commit().await?;
 
+        if aborted_migration.is_some() {
+            spawn_provisioning_dispatch(app_state, Some(self.deployment_id));
+        }
+
+        app_state
+            .cache_invalidator
+            .invalidate_deployment(self.deployment_id)
+            .await;
+
         tracing::info!(
             "Successfully cleaned up all database records for deployment {}",
             self.deployment_id
@@ -2252,7 +2105,7 @@ impl DeleteDeploymentCommand {
 impl Command for DeleteDeploymentCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         tracing::info!("Starting deletion of deployment {}", self.deployment_id);
 
         // First, verify the deployment exists and belongs to the project
@@ -2287,13 +2140,14 @@ impl Command for DeleteDeploymentCommand {
         .await?;
 
         if deployment_count.count.unwrap_or(0) <= 1 {
-            return Err(AppError::BadRequest(
-                "Cannot delete the last deployment in a project. Delete the project instead."
-                    .to_string(),
-            ));
+            return Err(AppError::coded(
+                ErrorCode::LastDeploymentCannotBeDeleted,
+                "Cannot delete the last deployment in a project. Delete the project instead.",
+            )
+            .with_details(serde_json::json!({ "project_id": self.project_id.to_string() })));
         }
 
-        // Convert to Deployment model for external cleanup
+        // Convert to Deployment model for the external resources it recorded
         let deployment_model = Deployment {
             id: deployment_row.id,
             created_at: deployment_row.created_at,
@@ -2306,6 +2160,7 @@ impl Command for DeleteDeploymentCommand {
             mode: DeploymentMode::from(deployment_row.mode),
             mail_from_host: deployment_row.mail_from_host,
             verification_status: None,
+            provisioning_status: ProvisioningStatus::default(),
             domain_verification_records: deployment_row
                 .domain_verification_records
                 .and_then(|data| serde_json::from_value(data).ok()),
@@ -2314,14 +2169,15 @@ impl Command for DeleteDeploymentCommand {
                 .and_then(|data| serde_json::from_value(data).ok()),
         };
 
-        if let Err(e) = self
-            .cleanup_external_resources(app_state, &deployment_model)
+        if let Err(e) = DeleteDeploymentVectorDataCommand::new(self.deployment_id)
+            .execute(app_state)
             .await
         {
-            tracing::warn!("Failed to cleanup external resources: {}", e);
+            tracing::warn!("Failed to cleanup vector data: {}", e);
         }
 
-        self.cleanup_database_records(app_state).await?;
+        self.cleanup_database_records(app_state, &deployment_model)
+            .await?;
 
         tracing::info!(
             "Successfully soft deleted deployment {}",
@@ -0,0 +1,551 @@
+//! Console accounts working on a project and the role each one has.
+//!
+//! Changes are serialized per project by locking the project row, which keeps
+//! the last-owner checks honest when two changes race. Each command can be
+//! told which account is acting; when it is, that account's own role decides
+//! what it may change, and it is recorded as the actor of the audit entry.
+
+use serde_json::json;
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{ProjectCollaborator, ProjectCollaboratorStatus, ProjectRole},
+    state::AppState,
+};
+
+use super::{Command, RecordAuditLogCommand};
+
+pub(crate) const COLLABORATOR_COLUMNS: &str = r#"
+    c.id, c.project_id, c.account_id, COALESCE(a.email, c.invited_email) AS email, a.name,
+    c.role, c.created_at, c.updated_at
+"#;
+
+pub(crate) fn collaborator_from_row(row: &sqlx::postgres::PgRow) -> ProjectCollaborator {
+    let account_id: Option<i64> = row.get("account_id");
+    ProjectCollaborator {
+        id: row.get("id"),
+        project_id: row.get("project_id"),
+        account_id,
+        email: row.get("email"),
+        name: row.get("name"),
+        role: ProjectRole::from(row.get::<String, _>("role")),
+        status: if account_id.is_some() {
+            ProjectCollaboratorStatus::Active
+        } else {
+            ProjectCollaboratorStatus::Invited
+        },
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn actor(acting_account_id: Option<i64>) -> Option<String> {
+    acting_account_id.map(|id| format!("console_account:{}", id))
+}
+
+pub fn insufficient_project_role(required: ProjectRole, role: Option<ProjectRole>) -> AppError {
+    AppError::coded(
+        ErrorCode::InsufficientProjectRole,
+        format!("This needs the {} role in the project", required.as_str()),
+    )
+    .with_details(json!({
+        "required_role": required,
+        "role": role,
+    }))
+}
+
+/// Locks the project and returns its name.
+async fn lock_project(conn: &mut PgConnection, project_id: i64) -> Result<String, AppError> {
+    sqlx::query_scalar("SELECT name FROM projects WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+        .bind(project_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Project with id {} not found", project_id)))
+}
+
+/// Managing collaborators takes an admin, and giving or taking away the owner
+/// role takes an owner. Callers acting for no account aren't limited.
+async fn ensure_can_manage(
+    conn: &mut PgConnection,
+    project_id: i64,
+    acting_account_id: Option<i64>,
+    roles: &[ProjectRole],
+) -> Result<(), AppError> {
+    let Some(account_id) = acting_account_id else {
+        return Ok(());
+    };
+
+    let role: Option<String> = sqlx::query_scalar(
+        "SELECT role FROM project_collaborators WHERE project_id = $1 AND account_id = $2",
+    )
+    .bind(project_id)
+    .bind(account_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let role = role.map(ProjectRole::from);
+
+    let required = if roles.contains(&ProjectRole::Owner) {
+        ProjectRole::Owner
+    } else {
+        ProjectRole::Admin
+    };
+    if role.is_some_and(|role| role.allows(required)) {
+        Ok(())
+    } else {
+        Err(insufficient_project_role(required, role))
+    }
+}
+
+/// Refuses to leave the project without an owner who has an account.
+async fn ensure_other_owner(
+    conn: &mut PgConnection,
+    project_id: i64,
+    collaborator_id: i64,
+) -> Result<(), AppError> {
+    let other_owners: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM project_collaborators
+        WHERE project_id = $1 AND role = 'owner' AND account_id IS NOT NULL AND id <> $2
+        "#,
+    )
+    .bind(project_id)
+    .bind(collaborator_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if other_owners == 0 {
+        return Err(AppError::coded(
+            ErrorCode::LastProjectOwner,
+            "This collaborator is the project's only owner. Make someone else an owner first.",
+        )
+        .with_details(json!({ "collaborator_id": collaborator_id.to_string() })));
+    }
+
+    Ok(())
+}
+
+async fn lock_collaborator(
+    conn: &mut PgConnection,
+    project_id: i64,
+    collaborator_id: i64,
+) -> Result<ProjectCollaborator, AppError> {
+    let row = sqlx::query(&format!(
+        r#"
+        SELECT {}
+        FROM project_collaborators c
+        LEFT JOIN console_accounts a ON a.id = c.account_id
+        WHERE c.id = $1 AND c.project_id = $2
+        FOR UPDATE OF c
+        "#,
+        COLLABORATOR_COLUMNS
+    ))
+    .bind(collaborator_id)
+    .bind(project_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Collaborator not found".to_string()))?;
+
+    Ok(collaborator_from_row(&row))
+}
+
+/// Gives the console account with `email` a role in the project. When no
+/// account uses the address yet, the collaborator is left invited and an
+/// invitation is emailed; the invitation turns into a membership through
+/// [`AcceptProjectInvitationsCommand`] once the account exists.
+pub struct AddProjectCollaboratorCommand {
+    project_id: i64,
+    email: String,
+    role: ProjectRole,
+    acting_account_id: Option<i64>,
+}
+
+impl AddProjectCollaboratorCommand {
+    pub fn new(project_id: i64, email: impl Into<String>, role: ProjectRole) -> Self {
+        Self {
+            project_id,
+            email: email.into(),
+            role,
+            acting_account_id: None,
+        }
+    }
+
+    pub fn with_acting_account(mut self, account_id: Option<i64>) -> Self {
+        self.acting_account_id = account_id;
+        self
+    }
+}
+
+impl Command for AddProjectCollaboratorCommand {
+    type Output = ProjectCollaborator;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let email = self.email.trim().to_lowercase();
+        let collaborator_id = app_state.sf.next_id()? as i64;
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let project_name = lock_project(&mut tx, self.project_id).await?;
+        ensure_can_manage(
+            &mut tx,
+            self.project_id,
+            self.acting_account_id,
+            &[self.role],
+        )
+        .await?;
+
+        let account_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM console_accounts
+            WHERE LOWER(email) = $1 AND deleted_at IS NULL
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+        )
+        .bind(&email)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO project_collaborators (id, project_id, account_id, invited_email, role)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(collaborator_id)
+        .bind(self.project_id)
+        .bind(account_id)
+        .bind(account_id.is_none().then_some(&email))
+        .bind(self.role.as_str())
+        .execute(&mut *tx)
+        .await?;
+        if inserted.rows_affected() == 0 {
+            return Err(AppError::coded(
+                ErrorCode::AlreadyExists,
+                format!("{} is already a collaborator on this project", email),
+            ));
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM project_collaborators c
+            LEFT JOIN console_accounts a ON a.id = c.account_id
+            WHERE c.id = $1
+            "#,
+            COLLABORATOR_COLUMNS
+        ))
+        .bind(collaborator_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let collaborator = collaborator_from_row(&row);
+
+        let action = match collaborator.status {
+            ProjectCollaboratorStatus::Active => "project.collaborator_added",
+            ProjectCollaboratorStatus::Invited => "project.collaborator_invited",
+        };
+        RecordAuditLogCommand::new(
+            self.project_id,
+            action,
+            "project_collaborator",
+            collaborator_id,
+        )
+        .with_actor(actor(self.acting_account_id))
+        .with_metadata(json!({
+            "email": email,
+            "account_id": account_id.map(|id| id.to_string()),
+            "role": self.role,
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        if collaborator.status == ProjectCollaboratorStatus::Invited {
+            send_invitation(app_state, &project_name, &collaborator);
+        }
+
+        Ok(collaborator)
+    }
+}
+
+/// Emails an invited collaborator. The invitation is already saved, so a
+/// delivery problem is only logged.
+fn send_invitation(app_state: &AppState, project_name: &str, collaborator: &ProjectCollaborator) {
+    let Ok(from) = std::env::var("CONSOLE_NOTIFICATION_FROM_EMAIL") else {
+        tracing::warn!(
+            "CONSOLE_NOTIFICATION_FROM_EMAIL is not set, skipping collaborator invitation"
+        );
+        return;
+    };
+
+    let to = collaborator.email.clone();
+    let subject = format!("You've been invited to {}", project_name);
+    let body = format!(
+        "<p>You've been invited to collaborate on <strong>{}</strong> as {}.</p><p>Sign up for the console with this email address to accept.</p>",
+        handlebars::html_escape(project_name),
+        collaborator.role.as_str().replace('_', " ")
+    );
+
+    let postmark_service = app_state.postmark_service.clone();
+    app_state.background_tasks.spawn(async move {
+        if let Err(e) = postmark_service
+            .send_email(&from, &to, &subject, &body, None)
+            .await
+        {
+            tracing::error!("Failed to send collaborator invitation to {}: {}", to, e);
+        }
+    });
+}
+
+pub struct UpdateProjectCollaboratorRoleCommand {
+    project_id: i64,
+    collaborator_id: i64,
+    role: ProjectRole,
+    acting_account_id: Option<i64>,
+}
+
+impl UpdateProjectCollaboratorRoleCommand {
+    pub fn new(project_id: i64, collaborator_id: i64, role: ProjectRole) -> Self {
+        Self {
+            project_id,
+            collaborator_id,
+            role,
+            acting_account_id: None,
+        }
+    }
+
+    pub fn with_acting_account(mut self, account_id: Option<i64>) -> Self {
+        self.acting_account_id = account_id;
+        self
+    }
+}
+
+impl Command for UpdateProjectCollaboratorRoleCommand {
+    type Output = ProjectCollaborator;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        lock_project(&mut tx, self.project_id).await?;
+        let mut collaborator =
+            lock_collaborator(&mut tx, self.project_id, self.collaborator_id).await?;
+        let previous_role = collaborator.role;
+        if previous_role == self.role {
+            return Ok(collaborator);
+        }
+
+        ensure_can_manage(
+            &mut tx,
+            self.project_id,
+            self.acting_account_id,
+            &[previous_role, self.role],
+        )
+        .await?;
+        if previous_role == ProjectRole::Owner && collaborator.account_id.is_some() {
+            ensure_other_owner(&mut tx, self.project_id, self.collaborator_id).await?;
+        }
+
+        collaborator.updated_at = sqlx::query_scalar(
+            r#"
+            UPDATE project_collaborators
+            SET role = $2, updated_at = NOW()
+            WHERE id = $1
+            RETURNING updated_at
+            "#,
+        )
+        .bind(self.collaborator_id)
+        .bind(self.role.as_str())
+        .fetch_one(&mut *tx)
+        .await?;
+        collaborator.role = self.role;
+
+        RecordAuditLogCommand::new(
+            self.project_id,
+            "project.collaborator_role_changed",
+            "project_collaborator",
+            self.collaborator_id,
+        )
+        .with_actor(actor(self.acting_account_id))
+        .with_metadata(json!({
+            "email": collaborator.email,
+            "previous_role": previous_role,
+            "role": self.role,
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(collaborator)
+    }
+}
+
+/// Removes a collaborator or withdraws an invitation. Returns what was
+/// removed.
+pub struct RemoveProjectCollaboratorCommand {
+    project_id: i64,
+    collaborator_id: i64,
+    acting_account_id: Option<i64>,
+}
+
+impl RemoveProjectCollaboratorCommand {
+    pub fn new(project_id: i64, collaborator_id: i64) -> Self {
+        Self {
+            project_id,
+            collaborator_id,
+            acting_account_id: None,
+        }
+    }
+
+    pub fn with_acting_account(mut self, account_id: Option<i64>) -> Self {
+        self.acting_account_id = account_id;
+        self
+    }
+}
+
+impl Command for RemoveProjectCollaboratorCommand {
+    type Output = ProjectCollaborator;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        lock_project(&mut tx, self.project_id).await?;
+        let collaborator =
+            lock_collaborator(&mut tx, self.project_id, self.collaborator_id).await?;
+
+        ensure_can_manage(
+            &mut tx,
+            self.project_id,
+            self.acting_account_id,
+            &[collaborator.role],
+        )
+        .await?;
+        if collaborator.role == ProjectRole::Owner && collaborator.account_id.is_some() {
+            ensure_other_owner(&mut tx, self.project_id, self.collaborator_id).await?;
+        }
+
+        sqlx::query("DELETE FROM project_collaborators WHERE id = $1")
+            .bind(self.collaborator_id)
+            .execute(&mut *tx)
+            .await?;
+
+        RecordAuditLogCommand::new(
+            self.project_id,
+            "project.collaborator_removed",
+            "project_collaborator",
+            self.collaborator_id,
+        )
+        .with_actor(actor(self.acting_account_id))
+        .with_metadata(json!({
+            "email": collaborator.email,
+            "account_id": collaborator.account_id.map(|id| id.to_string()),
+            "role": collaborator.role,
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(collaborator)
+    }
+}
+
+/// Turns the invitations sent to a console account's email address into
+/// memberships. Meant to run once the account has been created; returns the
+/// projects' new collaborator entries.
+pub struct AcceptProjectInvitationsCommand {
+    account_id: i64,
+}
+
+impl AcceptProjectInvitationsCommand {
+    pub fn new(account_id: i64) -> Self {
+        Self { account_id }
+    }
+}
+
+impl Command for AcceptProjectInvitationsCommand {
+    type Output = Vec<ProjectCollaborator>;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let email: String = sqlx::query_scalar(
+            "SELECT email FROM console_accounts WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.account_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Account with id {} not found", self.account_id))
+        })?;
+
+        // An invitation to a project the account already works on has nothing
+        // left to grant.
+        sqlx::query(
+            r#"
+            DELETE FROM project_collaborators i
+            WHERE i.account_id IS NULL AND LOWER(i.invited_email) = LOWER($2)
+                AND EXISTS (
+                    SELECT 1 FROM project_collaborators m
+                    WHERE m.project_id = i.project_id AND m.account_id = $1
+                )
+            "#,
+        )
+        .bind(self.account_id)
+        .bind(&email)
+        .execute(&mut *tx)
+        .await?;
+
+        let accepted: Vec<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE project_collaborators
+            SET account_id = $1, invited_email = NULL, updated_at = NOW()
+            WHERE account_id IS NULL AND LOWER(invited_email) = LOWER($2)
+            RETURNING id
+            "#,
+        )
+        .bind(self.account_id)
+        .bind(&email)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM project_collaborators c
+            LEFT JOIN console_accounts a ON a.id = c.account_id
+            WHERE c.id = ANY($1)
+            "#,
+            COLLABORATOR_COLUMNS
+        ))
+        .bind(&accepted)
+        .fetch_all(&mut *tx)
+        .await?;
+        let collaborators: Vec<ProjectCollaborator> =
+            rows.iter().map(collaborator_from_row).collect();
+
+        for collaborator in &collaborators {
+            RecordAuditLogCommand::new(
+                collaborator.project_id,
+                "project.collaborator_joined",
+                "project_collaborator",
+                collaborator.id,
+            )
+            .with_actor(actor(Some(self.account_id)))
+            .with_metadata(json!({
+                "email": collaborator.email,
+                "role": collaborator.role,
+            }))
+            .execute_with(app_state.sf.next_id()? as i64, &mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(collaborators)
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::Utc;
+use sqlx::Row;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{ConsoleAccount, ProjectTransfer},
+    state::AppState,
+};
+
+use super::{Command, RecordAuditLogCommand};
+
+/// Moves a project, and with it every deployment under it, to another console
+/// account. Deployments, users and templates hang off the project, so only the
+/// project's owner changes.
+pub struct TransferProjectCommand {
+    pub project_id: i64,
+    pub target_owner_id: i64,
+    pub initiated_by: Option<String>,
+    pub notify: bool,
+}
+
+impl TransferProjectCommand {
+    pub fn new(project_id: i64, target_owner_id: i64) -> Self {
+        Self {
+            project_id,
+            target_owner_id,
+            initiated_by: None,
+            notify: false,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+
+    pub fn with_notify(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+}
+
+impl Command for TransferProjectCommand {
+    type Output = ProjectTransfer;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let collaborator_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let project = sqlx::query(
+            "SELECT name, owner_id FROM projects WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        )
+        .bind(self.project_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Project with id {} not found", self.project_id))
+        })?;
+
+        let project_name: String = project.get("name");
+        let previous_owner_id: Option<i64> = project.get("owner_id");
+
+        if previous_owner_id == Some(self.target_owner_id) {
+            return Err(AppError::BadRequest(
+                "The project is already owned by this account".to_string(),
+            ));
+        }
+
+        // Locking the target serializes concurrent transfers into the same account,
+        // so two of them can't both pass the project limit check.
+        let target = sqlx::query(
+            r#"
+            SELECT id, created_at, updated_at, kind, name, email, max_projects
+            FROM console_accounts
+            WHERE id = $1 AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.target_owner_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| console_account_from_row(&row))
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Account with id {} not found",
+                self.target_owner_id
+            ))
+        })?;
+
+        if let Some(max_projects) = target.max_projects {
+            let owned: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM projects WHERE owner_id = $1 AND deleted_at IS NULL",
+            )
+            .bind(target.id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if owned >= i64::from(max_projects) {
+                return Err(AppError::coded(
+                    ErrorCode::ProjectLimitReached,
+                    format!(
+                        "Account {} already owns the maximum of {} projects",
+                        target.id, max_projects
+                    ),
+                )
+                .with_details(serde_json::json!({
+                    "account_id": target.id.to_string(),
+                    "max_projects": max_projects,
+                })));
+            }
+        }
+
+        let transferred_at = Utc::now();
+
+        sqlx::query("UPDATE projects SET owner_id = $1, updated_at = $2 WHERE id = $3")
+            .bind(target.id)
+            .bind(transferred_at)
+            .bind(self.project_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // The new owner account becomes an owner of the project, and the
+        // previous one loses its access along with the project.
+        if let Some(previous_owner_id) = previous_owner_id {
+            sqlx::query(
+                "DELETE FROM project_collaborators WHERE project_id = $1 AND account_id = $2",
+            )
+            .bind(self.project_id)
+            .bind(previous_owner_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO project_collaborators (id, project_id, account_id, role)
+            VALUES ($1, $2, $3, 'owner')
+            ON CONFLICT (project_id, account_id) WHERE account_id IS NOT NULL
+            DO UPDATE SET role = 'owner', updated_at = NOW()
+            "#,
+        )
+        .bind(collaborator_id)
+        .bind(self.project_id)
+        .bind(target.id)
+        .execute(&mut *tx)
+        .await?;
+
+        RecordAuditLogCommand::new(
+            self.project_id,
+            "project.transferred",
+            "project",
+            self.project_id,
+        )
+        .with_actor(self.initiated_by.clone())
+        .with_metadata(serde_json::json!({
+            "previous_owner_id": previous_owner_id.map(|id| id.to_string()),
+            "new_owner_id": target.id.to_string(),
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        let previous_owner = match previous_owner_id {
+            Some(id) => sqlx::query(
+                "SELECT id, created_at, updated_at, kind, name, email, max_projects FROM console_accounts WHERE id = $1",
+            )
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|row| console_account_from_row(&row)),
+            None => None,
+        };
+
+        tx.commit().await?;
+
+        if self.notify {
+            notify_transfer(app_state, &project_name, previous_owner, target);
+        }
+
+        Ok(ProjectTransfer {
+            project_id: self.project_id,
+            previous_owner_id,
+            new_owner_id: self.target_owner_id,
+            transferred_at,
+        })
+    }
+}
+
+fn console_account_from_row(row: &sqlx::postgres::PgRow) -> ConsoleAccount {
+    ConsoleAccount {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        kind: row.get("kind"),
+        name: row.get("name"),
+        email: row.get("email"),
+        max_projects: row.get("max_projects"),
+    }
+}
+
+/// Emails both accounts after the transfer has committed. Delivery problems are
+/// logged rather than surfaced, since the transfer itself already happened.
+fn notify_transfer(
+    app_state: &AppState,
+    project_name: &str,
+    previous_owner: Option<ConsoleAccount>,
+    new_owner: ConsoleAccount,
+) {
+    let Ok(from) = std::env::var("CONSOLE_NOTIFICATION_FROM_EMAIL") else {
+        tracing::warn!("CONSOLE_NOTIFICATION_FROM_EMAIL is not set, skipping transfer emails");
+        return;
+    };
+
+    let project = handlebars::html_escape(project_name);
+    let new_owner_name = handlebars::html_escape(&new_owner.name);
+
+    let mut messages = vec![(
+        new_owner.email.clone(),
+        format!("{} now owns {}", new_owner.name, project_name),
+        format!(
+            "<p>The project <strong>{}</strong> has been transferred to {}.</p>",
+            project, new_owner_name
+        ),
+    )];
+    if let Some(previous_owner) = previous_owner {
+        messages.push((
+            previous_owner.email,
+            format!("{} was transferred", project_name),
+            format!(
+                "<p>The project <strong>{}</strong> has been transferred to {} and is no longer accessible from {}.</p>",
+                project,
+                new_owner_name,
+                handlebars::html_escape(&previous_owner.name)
+            ),
+        ));
+    }
+
+    let postmark_service = app_state.postmark_service.clone();
+    app_state.background_tasks.spawn(async move {
+        for (to, subject, body) in messages {
+            if let Err(e) = postmark_service
+                .send_email(&from, &to, &subject, &body, None)
+                .await
+            {
+                tracing::error!("Failed to send project transfer email to {}: {}", to, e);
+            }
+        }
+    });
+}
@@ -0,0 +1,51 @@
+use crate::{
+    error::AppError,
+    models::{EndpointClass, RateLimitDecision},
+    queries::{GetDeploymentRateLimitsQuery, Query},
+    state::AppState,
+};
+
+use super::Command;
+
+/// Takes one token from the deployment's bucket for the given endpoint class.
+pub struct CheckRateLimitCommand {
+    deployment_id: i64,
+    endpoint_class: EndpointClass,
+}
+
+impl CheckRateLimitCommand {
+    pub fn new(deployment_id: i64, endpoint_class: EndpointClass) -> Self {
+        Self {
+            deployment_id,
+            endpoint_class,
+        }
+    }
+}
+
+impl Command for CheckRateLimitCommand {
+    type Output = RateLimitDecision;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let service = &app_state.rate_limit_service;
+        if !service.is_enabled() {
+            return Ok(RateLimitDecision::allow_unlimited());
+        }
+
+        let overrides = match service.cached_overrides(self.deployment_id) {
+            Some(overrides) => overrides,
+            None => {
+                let overrides = GetDeploymentRateLimitsQuery::new(self.deployment_id)
+                    .execute(app_state)
+                    .await?;
+                service.cache_overrides(self.deployment_id, overrides.clone());
+                overrides
+            }
+        };
+
+        let limit = service.resolve_limit(self.endpoint_class, &overrides);
+
+        service
+            .take_token(self.deployment_id, self.endpoint_class, limit)
+            .await
+    }
+}
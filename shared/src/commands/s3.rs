@@ -1,5 +1,4 @@
-use crate::{error::AppError, state::AppState};
-use aws_sdk_s3::primitives::{ByteStream, SdkBody};
+use crate::{error::AppError, models::StorageClass, services::ObjectBody, state::AppState};
 use serde_json::json;
 
 use super::Command;
@@ -18,27 +17,52 @@ impl UploadToCdnCommand {
 impl Command for UploadToCdnCommand {
     type Output = String;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        app_state
-            .s3_client
-            .put_object()
-            .bucket(std::env::var("R2_CDN_BUCKET").expect("R2_CDN_BUCKET must be set"))
-            .key(&self.file_path)
-            .body(ByteStream::new(SdkBody::from(self.body)))
-            .send()
-            .await
-            .map_err(|e| AppError::S3(e.to_string()))?;
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let bucket = app_state.storage.bucket(StorageClass::Cdn);
+        bucket
+            .put_object(&self.file_path, ObjectBody::Bytes(self.body), None)
+            .await?;
 
         let _ = ureq::post("https://api.cloudflare.com/client/v4/zones/90930ab39928937ca4d0c4aba3b03126/purge_cache")
             .header("Content-Type", "application/json")
             .header("Authorization", format!("Bearer {}", std::env::var("CLOUDFLARE_API_KEY").expect("CLOUDFLARE_API_KEY must be set")))
             .send_json(json!({
                 "files": [
-                    format!("https://cdn.wacht.services/{}", self.file_path)
+                    bucket.public_url(&self.file_path)
                 ]
             }));
 
-        Ok(format!("https://cdn.wacht.services/{}", self.file_path))
+        Ok(bucket.public_url(&self.file_path))
+    }
+}
+
+/// Deletes the objects under a prefix of the CDN bucket, except the ones
+/// still in use.
+pub struct PruneCdnPrefixCommand {
+    pub prefix: String,
+    pub keep: Vec<String>,
+}
+
+impl PruneCdnPrefixCommand {
+    pub fn new(prefix: String, keep: Vec<String>) -> Self {
+        Self { prefix, keep }
+    }
+}
+
+impl Command for PruneCdnPrefixCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let bucket = app_state.storage.bucket(StorageClass::Cdn);
+
+        for key in bucket.list_keys(&self.prefix).await? {
+            if self.keep.contains(&key) {
+                continue;
+            }
+            bucket.delete_object(&key).await?;
+        }
+
+        Ok(())
     }
 }
 
@@ -56,28 +80,15 @@ impl UploadToKnowledgeBaseBucketCommand {
 impl Command for UploadToKnowledgeBaseBucketCommand {
     type Output = String;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let bucket_name = std::env::var("R2_KNOWLEDGE_BASE_BUCKET").unwrap_or_else(|_| {
-            // Fallback to CDN bucket if knowledge base bucket is not configured
-            std::env::var("R2_CDN_BUCKET")
-                .expect("Either R2_KNOWLEDGE_BASE_BUCKET or R2_CDN_BUCKET must be set")
-        });
-
-        app_state
-            .s3_client
-            .put_object()
-            .bucket(&bucket_name)
-            .key(&self.file_path)
-            .body(ByteStream::new(SdkBody::from(self.body)))
-            .send()
-            .await
-            .map_err(|e| AppError::S3(e.to_string()))?;
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let bucket = app_state.storage.bucket(StorageClass::KnowledgeBase);
+
+        bucket
+            .put_object(&self.file_path, ObjectBody::Bytes(self.body), None)
+            .await?;
 
         // For knowledge base documents, we don't need CDN cache purging
         // as they are not served through the CDN
-        let base_url = std::env::var("R2_KNOWLEDGE_BASE_BASE_URL")
-            .unwrap_or_else(|_| format!("https://{}.r2.cloudflarestorage.com", bucket_name));
-
-        Ok(format!("{}/{}", base_url, self.file_path))
+        Ok(bucket.public_url(&self.file_path))
     }
 }
@@ -0,0 +1,909 @@
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+
+use crate::{
+    commands::{
+        AddOrganizationMemberCommand, Command, CreateOrganizationCommand, CreateUserCommand,
+        DeleteOrganizationCommand, DeleteUserCommand, RemoveOrganizationMemberCommand,
+        RevokeUserSessionsCommand, UpdateOrganizationCommand, UpdateUserCommand,
+    },
+    dto::json::{
+        CreateUserRequest, ScimGroupRequest, ScimPatchOperation, ScimPatchRequest, ScimUserRequest,
+        UpdateUserRequest,
+    },
+    error::{AppError, ErrorCode},
+    models::{CreatedScimToken, ScimFilter, ScimGroup, ScimToken, ScimUser},
+    queries::{
+        fetch_scim_group, fetch_scim_user, invalidate_user_authorization_context,
+        scim_token_from_row,
+    },
+    state::AppState,
+};
+
+/// Recorded as `initiated_by` on audit logs for changes made over SCIM.
+const SCIM_ACTOR: &str = "scim";
+
+fn hash_scim_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn generate_scim_token() -> String {
+    format!("scim_{}", hex::encode(rand::random::<[u8; 32]>()))
+}
+
+fn already_exists(field: &str, message: &str) -> AppError {
+    AppError::coded(ErrorCode::AlreadyExists, message).with_details(json!({ "field": field }))
+}
+
+fn parse_resource_id(id: &str) -> Result<i64, AppError> {
+    id.parse()
+        .map_err(|_| AppError::BadRequest(format!("'{}' isn't a valid resource id", id)))
+}
+
+pub struct CreateScimTokenCommand {
+    deployment_id: i64,
+    name: String,
+    created_by: Option<String>,
+}
+
+impl CreateScimTokenCommand {
+    pub fn new(deployment_id: i64, name: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            name: name.into(),
+            created_by: None,
+        }
+    }
+
+    pub fn with_created_by(mut self, created_by: Option<String>) -> Self {
+        self.created_by = created_by;
+        self
+    }
+}
+
+impl Command for CreateScimTokenCommand {
+    type Output = CreatedScimToken;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let secret = generate_scim_token();
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO scim_tokens (id, deployment_id, name, token_prefix, token_hash, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, created_at, deployment_id, name, token_prefix, created_by,
+                last_used_at, revoked_at
+            "#,
+        )
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(self.name.trim())
+        .bind(&secret[..12])
+        .bind(hash_scim_token(&secret))
+        .bind(self.created_by)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        Ok(CreatedScimToken {
+            token: scim_token_from_row(&row),
+            secret,
+        })
+    }
+}
+
+pub struct RevokeScimTokenCommand {
+    deployment_id: i64,
+    token_id: i64,
+}
+
+impl RevokeScimTokenCommand {
+    pub fn new(deployment_id: i64, token_id: i64) -> Self {
+        Self {
+            deployment_id,
+            token_id,
+        }
+    }
+}
+
+impl Command for RevokeScimTokenCommand {
+    type Output = ScimToken;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            UPDATE scim_tokens SET revoked_at = COALESCE(revoked_at, NOW())
+            WHERE deployment_id = $1 AND id = $2
+            RETURNING id, created_at, deployment_id, name, token_prefix, created_by,
+                last_used_at, revoked_at
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.token_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("SCIM token not found".to_string()))?;
+
+        Ok(scim_token_from_row(&row))
+    }
+}
+
+/// Checks a bearer token against the deployment's live SCIM tokens and
+/// records its use. Returns the token's id.
+pub struct AuthenticateScimTokenCommand {
+    deployment_id: i64,
+    token: String,
+}
+
+impl AuthenticateScimTokenCommand {
+    pub fn new(deployment_id: i64, token: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            token: token.into(),
+        }
+    }
+}
+
+impl Command for AuthenticateScimTokenCommand {
+    type Output = i64;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        sqlx::query_scalar(
+            r#"
+            UPDATE scim_tokens SET last_used_at = NOW()
+            WHERE token_hash = $1 AND deployment_id = $2 AND revoked_at IS NULL
+            RETURNING id
+            "#,
+        )
+        .bind(hash_scim_token(&self.token))
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or(AppError::Unauthorized)
+    }
+}
+
+/// Usernames and email addresses share one namespace, since either can be a
+/// SCIM `userName`.
+async fn ensure_user_name_free(
+    app_state: &AppState,
+    deployment_id: i64,
+    field: &str,
+    user_name: &str,
+    exclude_user_id: Option<i64>,
+) -> Result<(), AppError> {
+    let taken: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM users u
+            WHERE u.deployment_id = $1
+                AND u.id IS DISTINCT FROM $3
+                AND NOT u.identifiers_released
+                AND (
+                    lower(u.username) = lower($2)
+                    OR EXISTS (
+                        SELECT 1 FROM user_email_addresses e
+                        WHERE e.user_id = u.id AND lower(e.email_address) = lower($2)
+                    )
+                )
+        )
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(user_name)
+    .bind(exclude_user_id)
+    .fetch_one(&app_state.db_pool)
+    .await?;
+
+    if taken {
+        return Err(already_exists(
+            field,
+            &format!("A user with this {} already exists", field),
+        ));
+    }
+    Ok(())
+}
+
+/// Creates a user from a SCIM `POST /Users`. A `userName` that looks like an
+/// email address becomes the user's primary email; anything else becomes
+/// their username.
+pub struct ProvisionScimUserCommand {
+    deployment_id: i64,
+    request: ScimUserRequest,
+}
+
+impl ProvisionScimUserCommand {
+    pub fn new(deployment_id: i64, request: ScimUserRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+}
+
+impl Command for ProvisionScimUserCommand {
+    type Output = ScimUser;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let user_name = self.request.user_name.trim().to_string();
+        if user_name.is_empty() {
+            return Err(AppError::Validation("userName is required".to_string()));
+        }
+
+        ensure_user_name_free(app_state, self.deployment_id, "userName", &user_name, None).await?;
+
+        let (username, email_address) = if user_name.contains('@') {
+            (None, Some(user_name))
+        } else {
+            let email = self
+                .request
+                .emails
+                .iter()
+                .find(|email| email.primary)
+                .or_else(|| self.request.emails.first())
+                .map(|email| email.value.trim().to_string());
+            (Some(user_name), email)
+        };
+
+        if let Some(email) = &email_address
+            && username.is_some()
+        {
+            ensure_user_name_free(app_state, self.deployment_id, "emails", email, None).await?;
+        }
+
+        let user = CreateUserCommand::new(
+            self.deployment_id,
+            CreateUserRequest {
+                first_name: self.request.name.given_name.unwrap_or_default(),
+                last_name: self.request.name.family_name.unwrap_or_default(),
+                email_address,
+                phone_number: None,
+                username,
+                password: self.request.password,
+            },
+        )
+        .execute(app_state)
+        .await?;
+
+        sqlx::query("UPDATE users SET scim_external_id = $1, disabled = $2 WHERE id = $3")
+            .bind(self.request.external_id)
+            .bind(!self.request.active.unwrap_or(true))
+            .bind(user.id)
+            .execute(&app_state.db_pool)
+            .await?;
+
+        let mut conn = app_state.db_pool.acquire().await?;
+        fetch_scim_user(&mut conn, self.deployment_id, user.id).await
+    }
+}
+
+/// SCIM booleans arrive as `true` or, from some providers, `"True"`.
+fn scim_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(value) => Some(*value),
+        Value::String(value) => value.to_ascii_lowercase().parse().ok(),
+        _ => None,
+    }
+}
+
+fn scim_string(value: &Value) -> Option<String> {
+    value.as_str().map(|value| value.trim().to_string())
+}
+
+#[derive(Default)]
+struct ScimUserChanges {
+    first_name: Option<String>,
+    last_name: Option<String>,
+    user_name: Option<String>,
+    active: Option<bool>,
+    external_id: Option<Option<String>>,
+}
+
+impl ScimUserChanges {
+    fn apply(&mut self, op: &str, path: &str, value: Option<&Value>) -> Result<(), AppError> {
+        let removing = op == "remove";
+
+        match path.to_ascii_lowercase().as_str() {
+            "active" => {
+                if let Some(value) = value {
+                    self.active = Some(scim_bool(value).ok_or_else(|| {
+                        AppError::BadRequest("active must be a boolean".to_string())
+                    })?);
+                }
+            }
+            "username" => self.user_name = value.and_then(scim_string),
+            "externalid" => {
+                self.external_id = Some(if removing {
+                    None
+                } else {
+                    value.and_then(scim_string)
+                })
+            }
+            "name.givenname" => {
+                self.first_name = Some(if removing {
+                    String::new()
+                } else {
+                    value.and_then(scim_string).unwrap_or_default()
+                })
+            }
+            "name.familyname" => {
+                self.last_name = Some(if removing {
+                    String::new()
+                } else {
+                    value.and_then(scim_string).unwrap_or_default()
+                })
+            }
+            "name" => {
+                if let Some(Value::Object(name)) = value {
+                    for (key, value) in name {
+                        self.apply(op, &format!("name.{}", key), Some(value))?;
+                    }
+                }
+            }
+            // Attributes we don't store, like `title` or `emails[...]`, are
+            // ignored rather than failing the whole request.
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn apply_operation(&mut self, operation: &ScimPatchOperation) -> Result<(), AppError> {
+        let op = operation.op.to_ascii_lowercase();
+        if !matches!(op.as_str(), "add" | "replace" | "remove") {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported patch op: {}",
+                operation.op
+            )));
+        }
+
+        match (&operation.path, &operation.value) {
+            (Some(path), value) => self.apply(&op, path, value.as_ref()),
+            (None, Some(Value::Object(attributes))) => {
+                for (path, value) in attributes {
+                    self.apply(&op, path, Some(value))?;
+                }
+                Ok(())
+            }
+            (None, _) => Err(AppError::BadRequest(
+                "A patch operation without a path needs an object value".to_string(),
+            )),
+        }
+    }
+}
+
+pub struct PatchScimUserCommand {
+    deployment_id: i64,
+    user_id: i64,
+    request: ScimPatchRequest,
+}
+
+impl PatchScimUserCommand {
+    pub fn new(deployment_id: i64, user_id: i64, request: ScimPatchRequest) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            request,
+        }
+    }
+}
+
+impl Command for PatchScimUserCommand {
+    type Output = ScimUser;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+        fetch_scim_user(&mut conn, self.deployment_id, self.user_id).await?;
+
+        let mut changes = ScimUserChanges::default();
+        for operation in &self.request.operations {
+            changes.apply_operation(operation)?;
+        }
+
+        // Email-shaped userNames live on the user's email addresses, which
+        // SCIM doesn't manage, so only plain usernames are updated.
+        let username = changes
+            .user_name
+            .filter(|user_name| !user_name.is_empty() && !user_name.contains('@'));
+
+        if let Some(username) = &username {
+            ensure_user_name_free(
+                app_state,
+                self.deployment_id,
+                "userName",
+                username,
+                Some(self.user_id),
+            )
+            .await?;
+        }
+
+        if changes.first_name.is_some() || changes.last_name.is_some() || username.is_some() {
+            UpdateUserCommand::new(
+                self.deployment_id,
+                self.user_id,
+                UpdateUserRequest {
+                    first_name: changes.first_name,
+                    last_name: changes.last_name,
+                    username,
+                    public_metadata: None,
+                    private_metadata: None,
+                },
+            )
+            .execute(app_state)
+            .await?;
+        }
+
+        if let Some(external_id) = changes.external_id {
+            sqlx::query("UPDATE users SET scim_external_id = $1, updated_at = NOW() WHERE id = $2")
+                .bind(external_id)
+                .bind(self.user_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        if let Some(active) = changes.active {
+            sqlx::query("UPDATE users SET disabled = $1, updated_at = NOW() WHERE id = $2")
+                .bind(!active)
+                .bind(self.user_id)
+                .execute(&mut *conn)
+                .await?;
+
+            if !active {
+                RevokeUserSessionsCommand::new(self.deployment_id, self.user_id)
+                    .execute(app_state)
+                    .await?;
+            }
+        }
+
+        fetch_scim_user(&mut conn, self.deployment_id, self.user_id).await
+    }
+}
+
+/// Deletes a user for a SCIM `DELETE /Users/{id}`, ending their sessions
+/// first and keeping organization and workspace member counts in step.
+pub struct DeleteScimUserCommand {
+    deployment_id: i64,
+    user_id: i64,
+}
+
+impl DeleteScimUserCommand {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+        }
+    }
+}
+
+impl Command for DeleteScimUserCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        DeleteUserCommand::new(self.deployment_id, self.user_id)
+            .with_initiated_by(Some(SCIM_ACTOR.to_string()))
+            .execute(app_state)
+            .await?;
+
+        Ok(())
+    }
+}
+
+async fn ensure_group_name_free(
+    app_state: &AppState,
+    deployment_id: i64,
+    display_name: &str,
+    exclude_organization_id: Option<i64>,
+) -> Result<(), AppError> {
+    let taken: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM organizations
+            WHERE deployment_id = $1 AND lower(name) = lower($2) AND id IS DISTINCT FROM $3
+                AND deleted_at IS NULL
+        )
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(display_name)
+    .bind(exclude_organization_id)
+    .fetch_one(&app_state.db_pool)
+    .await?;
+
+    if taken {
+        return Err(already_exists(
+            "displayName",
+            "A group with this displayName already exists",
+        ));
+    }
+    Ok(())
+}
+
+async fn current_member_ids(
+    app_state: &AppState,
+    organization_id: i64,
+) -> Result<Vec<i64>, AppError> {
+    Ok(sqlx::query_scalar(
+        "SELECT user_id FROM organization_memberships WHERE organization_id = $1",
+    )
+    .bind(organization_id)
+    .fetch_all(&app_state.db_pool)
+    .await?)
+}
+
+async fn add_group_members(
+    app_state: &AppState,
+    deployment_id: i64,
+    organization_id: i64,
+    user_ids: &[i64],
+) -> Result<(), AppError> {
+    let existing = current_member_ids(app_state, organization_id).await?;
+
+    for user_id in user_ids {
+        if existing.contains(user_id) {
+            continue;
+        }
+        AddOrganizationMemberCommand::new(deployment_id, organization_id, *user_id, vec![])
+            .with_initiated_by(Some(SCIM_ACTOR.to_string()))
+            .execute(app_state)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn remove_group_members(
+    app_state: &AppState,
+    deployment_id: i64,
+    organization_id: i64,
+    user_ids: &[i64],
+) -> Result<(), AppError> {
+    let rows = sqlx::query(
+        "SELECT id FROM organization_memberships WHERE organization_id = $1 AND user_id = ANY($2)",
+    )
+    .bind(organization_id)
+    .bind(user_ids)
+    .fetch_all(&app_state.db_pool)
+    .await?;
+
+    for row in rows {
+        RemoveOrganizationMemberCommand::new(deployment_id, organization_id, row.get("id"))
+            .with_initiated_by(Some(SCIM_ACTOR.to_string()))
+            .execute(app_state)
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn member_ids(value: Option<&Value>) -> Result<Vec<i64>, AppError> {
+    let members = match value {
+        Some(Value::Array(members)) => members.as_slice(),
+        Some(member @ Value::Object(_)) => std::slice::from_ref(member),
+        None | Some(Value::Null) => &[],
+        Some(_) => {
+            return Err(AppError::BadRequest(
+                "members must be a list of { \"value\": ... } objects".to_string(),
+            ));
+        }
+    };
+
+    members
+        .iter()
+        .map(|member| {
+            member
+                .get("value")
+                .and_then(Value::as_str)
+                .ok_or_else(|| AppError::BadRequest("Each member needs a value".to_string()))
+                .and_then(parse_resource_id)
+        })
+        .collect()
+}
+
+/// Creates an organization from a SCIM `POST /Groups` and adds the listed
+/// users with the deployment's default member role, in one transaction, so a
+/// failed request leaves nothing behind to conflict with its retry.
+pub struct ProvisionScimGroupCommand {
+    deployment_id: i64,
+    request: ScimGroupRequest,
+}
+
+impl ProvisionScimGroupCommand {
+    pub fn new(deployment_id: i64, request: ScimGroupRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+}
+
+impl Command for ProvisionScimGroupCommand {
+    type Output = ScimGroup;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let display_name = self.request.display_name.trim().to_string();
+        if display_name.is_empty() {
+            return Err(AppError::Validation("displayName is required".to_string()));
+        }
+
+        let mut user_ids = self
+            .request
+            .members
+            .iter()
+            .map(|member| parse_resource_id(&member.value))
+            .collect::<Result<Vec<_>, _>>()?;
+        user_ids.sort_unstable();
+        user_ids.dedup();
+
+        ensure_group_name_free(app_state, self.deployment_id, &display_name, None).await?;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let organization = CreateOrganizationCommand::new(
+            self.deployment_id,
+            display_name,
+            None,
+            None,
+            None,
+            None,
+        )
+        .execute_with(app_state, &mut tx)
+        .await?;
+
+        sqlx::query("UPDATE organizations SET scim_external_id = $1 WHERE id = $2")
+            .bind(self.request.external_id)
+            .bind(organization.id)
+            .execute(&mut *tx)
+            .await?;
+
+        for user_id in &user_ids {
+            AddOrganizationMemberCommand::new(
+                self.deployment_id,
+                organization.id,
+                *user_id,
+                vec![],
+            )
+            .with_initiated_by(Some(SCIM_ACTOR.to_string()))
+            .execute_with(app_state, &mut tx)
+            .await?;
+        }
+
+        let group = fetch_scim_group(&mut tx, self.deployment_id, organization.id).await?;
+
+        tx.commit().await?;
+
+        for user_id in user_ids {
+            invalidate_user_authorization_context(app_state, self.deployment_id, user_id).await;
+        }
+
+        Ok(group)
+    }
+}
+
+/// Applies a SCIM `PatchOp` to a group: renames, `externalId` changes and
+/// member additions, removals and full replacements.
+pub struct PatchScimGroupCommand {
+    deployment_id: i64,
+    organization_id: i64,
+    request: ScimPatchRequest,
+}
+
+impl PatchScimGroupCommand {
+    pub fn new(deployment_id: i64, organization_id: i64, request: ScimPatchRequest) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            request,
+        }
+    }
+
+    async fn rename(&self, app_state: &AppState, value: Option<&Value>) -> Result<(), AppError> {
+        let Some(display_name) = value.and_then(scim_string).filter(|name| !name.is_empty()) else {
+            return Err(AppError::BadRequest(
+                "displayName must be a non-empty string".to_string(),
+            ));
+        };
+
+        ensure_group_name_free(
+            app_state,
+            self.deployment_id,
+            &display_name,
+            Some(self.organization_id),
+        )
+        .await?;
+
+        UpdateOrganizationCommand::new(
+            self.deployment_id,
+            self.organization_id,
+            Some(display_name),
+            None,
+            None,
+            None,
+            None,
+        )
+        .with_initiated_by(Some(SCIM_ACTOR.to_string()))
+        .execute(app_state)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_external_id(
+        &self,
+        app_state: &AppState,
+        external_id: Option<String>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE organizations SET scim_external_id = $1, updated_at = NOW() WHERE id = $2",
+        )
+        .bind(external_id)
+        .bind(self.organization_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn apply(
+        &self,
+        app_state: &AppState,
+        op: &str,
+        path: &str,
+        value: Option<&Value>,
+    ) -> Result<(), AppError> {
+        let lowered = path.to_ascii_lowercase();
+
+        if lowered == "displayname" {
+            if op != "remove" {
+                self.rename(app_state, value).await?;
+            }
+        } else if lowered == "externalid" {
+            let external_id = if op == "remove" {
+                None
+            } else {
+                value.and_then(scim_string)
+            };
+            self.set_external_id(app_state, external_id).await?;
+        } else if lowered == "members" {
+            let user_ids = member_ids(value)?;
+            match op {
+                "add" => {
+                    add_group_members(
+                        app_state,
+                        self.deployment_id,
+                        self.organization_id,
+                        &user_ids,
+                    )
+                    .await?
+                }
+                "remove" if value.is_none() => {
+                    let all = current_member_ids(app_state, self.organization_id).await?;
+                    remove_group_members(app_state, self.deployment_id, self.organization_id, &all)
+                        .await?
+                }
+                "remove" => {
+                    remove_group_members(
+                        app_state,
+                        self.deployment_id,
+                        self.organization_id,
+                        &user_ids,
+                    )
+                    .await?
+                }
+                _ => {
+                    let existing = current_member_ids(app_state, self.organization_id).await?;
+                    let removed: Vec<i64> = existing
+                        .into_iter()
+                        .filter(|user_id| !user_ids.contains(user_id))
+                        .collect();
+                    remove_group_members(
+                        app_state,
+                        self.deployment_id,
+                        self.organization_id,
+                        &removed,
+                    )
+                    .await?;
+                    add_group_members(
+                        app_state,
+                        self.deployment_id,
+                        self.organization_id,
+                        &user_ids,
+                    )
+                    .await?;
+                }
+            }
+        } else if lowered.starts_with("members[") && lowered.ends_with(']') {
+            // `members[value eq "123"]`, which Azure AD sends for removals.
+            let filter = ScimFilter::parse(&path["members[".len()..path.len() - 1])?;
+            if filter.attribute != "value" {
+                return Err(AppError::BadRequest(format!("Unsupported path: {}", path)));
+            }
+            let user_ids = [parse_resource_id(&filter.value)?];
+            if op == "remove" {
+                remove_group_members(
+                    app_state,
+                    self.deployment_id,
+                    self.organization_id,
+                    &user_ids,
+                )
+                .await?;
+            } else {
+                add_group_members(
+                    app_state,
+                    self.deployment_id,
+                    self.organization_id,
+                    &user_ids,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Command for PatchScimGroupCommand {
+    type Output = ScimGroup;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+        fetch_scim_group(&mut conn, self.deployment_id, self.organization_id).await?;
+
+        for operation in &self.request.operations {
+            let op = operation.op.to_ascii_lowercase();
+            if !matches!(op.as_str(), "add" | "replace" | "remove") {
+                return Err(AppError::BadRequest(format!(
+                    "Unsupported patch op: {}",
+                    operation.op
+                )));
+            }
+
+            match (&operation.path, &operation.value) {
+                (Some(path), value) => self.apply(app_state, &op, path, value.as_ref()).await?,
+                (None, Some(Value::Object(attributes))) => {
+                    for (path, value) in attributes {
+                        self.apply(app_state, &op, path, Some(value)).await?;
+                    }
+                }
+                (None, _) => {
+                    return Err(AppError::BadRequest(
+                        "A patch operation without a path needs an object value".to_string(),
+                    ));
+                }
+            }
+        }
+
+        fetch_scim_group(&mut conn, self.deployment_id, self.organization_id).await
+    }
+}
+
+pub struct DeleteScimGroupCommand {
+    deployment_id: i64,
+    organization_id: i64,
+}
+
+impl DeleteScimGroupCommand {
+    pub fn new(deployment_id: i64, organization_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+        }
+    }
+}
+
+impl Command for DeleteScimGroupCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // The identity provider is authoritative for groups it provisioned,
+        // so the deployment's allow_org_deletion setting doesn't apply.
+        DeleteOrganizationCommand::new(self.deployment_id, self.organization_id)
+            .with_admin_override(true)
+            .execute(app_state)
+            .await
+            .map(|_| ())
+            .map_err(|e| match e {
+                AppError::NotFound(_) => AppError::NotFound("Group not found".to_string()),
+                other => other,
+            })
+    }
+}
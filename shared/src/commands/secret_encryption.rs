@@ -0,0 +1,219 @@
+use serde_json::Value;
+use sqlx::{Postgres, Row};
+
+use crate::{
+    error::AppError,
+    services::{
+        JWT_TEMPLATE_SIGNING_KEY, JsonSecret, KEY_PAIR_PRIVATE_KEY,
+        SOCIAL_CONNECTION_CLIENT_SECRET, SSO_CONNECTION_SP_PRIVATE_KEY,
+    },
+    state::AppState,
+};
+
+use super::{Command, credentials_context, log_export_credentials_context};
+
+const DEFAULT_BATCH_SIZE: i64 = 500;
+
+/// Seals secrets still stored in plaintext and reseals those sealed with a
+/// retired key, so a key can be dropped from `CREDENTIALS_PREVIOUS_KEYS` once
+/// this has run. Rows are walked in batches, each updated in its own
+/// transaction, and a row changed since it was read is left for the next run.
+/// Safe to run on every start; returns how many values were rewritten.
+pub struct MigrateEncryptSecretsCommand {
+    batch_size: i64,
+}
+
+impl MigrateEncryptSecretsCommand {
+    pub fn new() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+}
+
+impl Default for MigrateEncryptSecretsCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for MigrateEncryptSecretsCommand {
+    type Output = usize;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let cipher = &app_state.credential_cipher;
+
+        let mut resealed = reseal_column(
+            app_state,
+            "deployment_key_pairs",
+            "private_key",
+            self.batch_size,
+            |_, stored: &String| {
+                cipher
+                    .needs_resealing(stored)
+                    .then(|| cipher.reseal(stored, KEY_PAIR_PRIVATE_KEY))
+                    .transpose()
+            },
+        )
+        .await?;
+
+        resealed += reseal_column(
+            app_state,
+            "deployment_sso_connections",
+            "sp_private_key",
+            self.batch_size,
+            |_, stored: &String| {
+                cipher
+                    .needs_resealing(stored)
+                    .then(|| cipher.reseal(stored, SSO_CONNECTION_SP_PRIVATE_KEY))
+                    .transpose()
+            },
+        )
+        .await?;
+
+        for secret in [&SOCIAL_CONNECTION_CLIENT_SECRET, &JWT_TEMPLATE_SIGNING_KEY] {
+            resealed += reseal_json_column(app_state, secret, self.batch_size).await?;
+        }
+
+        // Provider and log export credentials have always been sealed; they
+        // only need a new key after a rotation.
+        resealed += reseal_column(
+            app_state,
+            "deployment_email_providers",
+            "encrypted_credentials",
+            self.batch_size,
+            |deployment_id, stored: &String| {
+                let context = credentials_context(deployment_id.ok_or_else(no_deployment)?);
+                cipher
+                    .needs_resealing(stored)
+                    .then(|| cipher.reseal(stored, &context))
+                    .transpose()
+            },
+        )
+        .await?;
+
+        resealed += reseal_column(
+            app_state,
+            "deployment_log_exports",
+            "encrypted_credentials",
+            self.batch_size,
+            |deployment_id, stored: &String| {
+                let context =
+                    log_export_credentials_context(deployment_id.ok_or_else(no_deployment)?);
+                cipher
+                    .needs_resealing(stored)
+                    .then(|| cipher.reseal(stored, &context))
+                    .transpose()
+            },
+        )
+        .await?;
+
+        Ok(resealed)
+    }
+}
+
+async fn reseal_json_column(
+    app_state: &AppState,
+    secret: &JsonSecret,
+    batch_size: i64,
+) -> Result<usize, AppError> {
+    let cipher = &app_state.credential_cipher;
+
+    reseal_column(
+        app_state,
+        secret.table,
+        secret.column,
+        batch_size,
+        |_, stored: &Value| {
+            secret
+                .needs_resealing(cipher, stored)
+                .then(|| secret.reseal(cipher, stored.clone()))
+                .transpose()
+        },
+    )
+    .await
+}
+
+fn no_deployment() -> AppError {
+    AppError::Internal("the row belongs to no deployment".to_string())
+}
+
+/// Rewrites one column batch by batch. `reseal` gets the row's deployment id,
+/// which some tables leave empty, and stored value, and returns the new value
+/// when the row needs one.
+async fn reseal_column<T, F>(
+    app_state: &AppState,
+    table: &str,
+    column: &str,
+    batch_size: i64,
+    reseal: F,
+) -> Result<usize, AppError>
+where
+    T: for<'q> sqlx::Encode<'q, Postgres>
+        + for<'r> sqlx::Decode<'r, Postgres>
+        + sqlx::Type<Postgres>
+        + Send
+        + Sync,
+    F: Fn(Option<i64>, &T) -> Result<Option<T>, AppError>,
+{
+    let select = format!(
+        "SELECT id, deployment_id, {column} FROM {table} WHERE id > $1 AND {column} IS NOT NULL ORDER BY id LIMIT $2"
+    );
+    let update = format!("UPDATE {table} SET {column} = $1 WHERE id = $2 AND {column} = $3");
+
+    let mut last_id = 0i64;
+    let mut resealed = 0;
+
+    loop {
+        if app_state.background_tasks.is_shutting_down() {
+            break;
+        }
+
+        let rows = sqlx::query(&select)
+            .bind(last_id)
+            .bind(batch_size)
+            .fetch_all(&app_state.db_pool)
+            .await?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        last_id = last.get("id");
+
+        let mut tx = app_state.db_pool.begin().await?;
+        for row in &rows {
+            let id: i64 = row.get("id");
+            let stored: T = row.try_get(column)?;
+            let deployment_id: Option<i64> = row.try_get("deployment_id")?;
+
+            // One unreadable row shouldn't hold up the rest of the table.
+            let value = match reseal(deployment_id, &stored) {
+                Ok(Some(value)) => value,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!("Couldn't reseal {}.{} of row {}: {}", table, column, id, e);
+                    continue;
+                }
+            };
+
+            let result = sqlx::query(&update)
+                .bind(value)
+                .bind(id)
+                .bind(stored)
+                .execute(&mut *tx)
+                .await?;
+            resealed += result.rows_affected() as usize;
+        }
+        tx.commit().await?;
+
+        if (rows.len() as i64) < batch_size {
+            break;
+        }
+    }
+
+    Ok(resealed)
+}
@@ -0,0 +1,134 @@
+use std::net::IpAddr;
+
+use crate::{
+    error::AppError,
+    models::SignInLockoutStatus,
+    queries::{GetDeploymentLockoutPolicyQuery, Query},
+    state::AppState,
+};
+
+use super::Command;
+
+/// Counts a failed sign-in. The returned status is locked when this attempt
+/// used up the last one allowed.
+pub struct RecordFailedSignInCommand {
+    deployment_id: i64,
+    identifier: String,
+    ip: IpAddr,
+}
+
+impl RecordFailedSignInCommand {
+    pub fn new(deployment_id: i64, identifier: impl Into<String>, ip: IpAddr) -> Self {
+        Self {
+            deployment_id,
+            identifier: identifier.into(),
+            ip,
+        }
+    }
+}
+
+impl Command for RecordFailedSignInCommand {
+    type Output = SignInLockoutStatus;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let policy = GetDeploymentLockoutPolicyQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+
+        app_state
+            .sign_in_lockout_service
+            .record_failure(self.deployment_id, &self.identifier, self.ip, &policy)
+            .await
+    }
+}
+
+/// Resets the failed attempt count after a successful sign-in.
+pub struct RecordSuccessfulSignInCommand {
+    deployment_id: i64,
+    identifier: String,
+    ip: IpAddr,
+}
+
+impl RecordSuccessfulSignInCommand {
+    pub fn new(deployment_id: i64, identifier: impl Into<String>, ip: IpAddr) -> Self {
+        Self {
+            deployment_id,
+            identifier: identifier.into(),
+            ip,
+        }
+    }
+}
+
+impl Command for RecordSuccessfulSignInCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        app_state
+            .sign_in_lockout_service
+            .record_success(self.deployment_id, &self.identifier, self.ip)
+            .await
+    }
+}
+
+/// Lifts lockouts on every identifier a user can sign in with, from any IP.
+pub struct UnlockUserCommand {
+    deployment_id: i64,
+    user_id: i64,
+}
+
+impl UnlockUserCommand {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+        }
+    }
+}
+
+impl Command for UnlockUserCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE deployment_id = $1 AND id = $2)",
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+        if !exists {
+            return Err(AppError::NotFound(format!(
+                "User {} not found",
+                self.user_id
+            )));
+        }
+
+        let identifiers: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT e.email_address FROM user_email_addresses e
+            JOIN users u ON u.id = e.user_id
+            WHERE u.deployment_id = $1 AND u.id = $2
+            UNION
+            SELECT p.phone_number FROM user_phone_numbers p
+            JOIN users u ON u.id = p.user_id
+            WHERE u.deployment_id = $1 AND u.id = $2
+            UNION
+            SELECT u.username FROM users u
+            WHERE u.deployment_id = $1 AND u.id = $2 AND u.username IS NOT NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        for identifier in identifiers {
+            app_state
+                .sign_in_lockout_service
+                .unlock(self.deployment_id, &identifier)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
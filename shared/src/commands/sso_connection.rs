@@ -0,0 +1,307 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+
+use crate::{
+    commands::Command,
+    dto::json::{CreateSsoConnectionRequest, UpdateSsoConnectionRequest},
+    error::AppError,
+    models::{DeploymentSsoConnection, IdpMetadata},
+    queries::{GetSsoConnectionQuery, Query, SSO_CONNECTION_COLUMNS, sso_connection_from_row},
+    services::SSO_CONNECTION_SP_PRIVATE_KEY,
+    state::AppState,
+    utils::x509::{certificate_validity, decode_certificate},
+};
+
+/// The identity provider settings a connection ends up with once explicit
+/// fields have been layered over whatever the metadata document declares.
+struct ResolvedIdp {
+    entity_id: String,
+    sso_url: Option<String>,
+    certificate: String,
+    certificate_expires_at: DateTime<Utc>,
+}
+
+fn resolve_idp(
+    metadata_xml: Option<&str>,
+    entity_id: Option<String>,
+    sso_url: Option<String>,
+    certificate: Option<String>,
+) -> Result<ResolvedIdp, AppError> {
+    let metadata = metadata_xml.map(IdpMetadata::parse).unwrap_or_default();
+
+    let entity_id = entity_id.or(metadata.entity_id).ok_or_else(|| {
+        AppError::Validation("The IdP metadata doesn't declare an entityID".to_string())
+    })?;
+    let certificate = certificate.or(metadata.certificate).ok_or_else(|| {
+        AppError::Validation("The IdP metadata doesn't include a signing certificate".to_string())
+    })?;
+
+    let validity = certificate_validity(&certificate)
+        .map_err(|e| AppError::Validation(format!("Invalid IdP certificate: {}", e)))?;
+    if validity.not_after <= Utc::now() {
+        return Err(AppError::Validation(format!(
+            "The IdP certificate expired on {}",
+            validity.not_after.format("%Y-%m-%d")
+        )));
+    }
+
+    Ok(ResolvedIdp {
+        entity_id,
+        sso_url: sso_url.or(metadata.sso_url),
+        certificate: certificate_pem(&certificate)?,
+        certificate_expires_at: validity.not_after,
+    })
+}
+
+/// Stores certificates as PEM whether they arrived as PEM or as the bare
+/// base64 body SAML metadata uses.
+fn certificate_pem(certificate: &str) -> Result<String, AppError> {
+    let der = decode_certificate(certificate).map_err(AppError::Validation)?;
+    let body = STANDARD.encode(der);
+    let lines: Vec<&str> = body
+        .as_bytes()
+        .chunks(64)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+
+    Ok(format!(
+        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+        lines.join("\n")
+    ))
+}
+
+fn normalize_email_domains(domains: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for domain in domains {
+        let domain = domain.trim().trim_start_matches('@').to_lowercase();
+        if !domain.is_empty() && !normalized.contains(&domain) {
+            normalized.push(domain);
+        }
+    }
+    normalized
+}
+
+/// Empty strings clear optional settings.
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// A self-signed key pair the deployment signs its authentication requests
+/// with. Returns the certificate and the private key, both as PEM.
+fn generate_sp_key_pair(connection_id: i64) -> Result<(String, String), AppError> {
+    let key_pair = rcgen::KeyPair::generate().map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut params = rcgen::CertificateParams::new(Vec::<String>::new())
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    params.distinguished_name.push(
+        rcgen::DnType::CommonName,
+        format!("wacht-sso-{}", connection_id),
+    );
+    let certificate = params
+        .self_signed(&key_pair)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok((certificate.pem(), key_pair.serialize_pem()))
+}
+
+pub struct CreateSsoConnectionCommand {
+    deployment_id: i64,
+    request: CreateSsoConnectionRequest,
+}
+
+impl CreateSsoConnectionCommand {
+    pub fn new(deployment_id: i64, request: CreateSsoConnectionRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+}
+
+impl Command for CreateSsoConnectionCommand {
+    type Output = DeploymentSsoConnection;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let request = self.request;
+        let metadata_xml = non_empty(request.idp_metadata_xml);
+        let idp = resolve_idp(
+            metadata_xml.as_deref(),
+            non_empty(request.idp_entity_id),
+            non_empty(request.idp_sso_url),
+            non_empty(request.idp_certificate),
+        )?;
+
+        let id = app_state.sf.next_id()? as i64;
+        let (sp_certificate, sp_private_key) = generate_sp_key_pair(id)?;
+        let sp_private_key = app_state
+            .credential_cipher
+            .seal(&sp_private_key, SSO_CONNECTION_SP_PRIVATE_KEY)?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO deployment_sso_connections (
+                id, deployment_id, name, enabled, idp_metadata_url, idp_metadata_xml,
+                idp_entity_id, idp_sso_url, idp_certificate, certificate_expires_at,
+                attribute_mapping, allowed_email_domains, sp_certificate, sp_private_key
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            RETURNING {}
+            "#,
+            SSO_CONNECTION_COLUMNS
+        ))
+        .bind(id)
+        .bind(self.deployment_id)
+        .bind(request.name.trim())
+        .bind(request.enabled.unwrap_or(true))
+        .bind(non_empty(request.idp_metadata_url))
+        .bind(metadata_xml)
+        .bind(idp.entity_id)
+        .bind(idp.sso_url)
+        .bind(idp.certificate)
+        .bind(idp.certificate_expires_at)
+        .bind(Json(request.attribute_mapping.unwrap_or_default()))
+        .bind(normalize_email_domains(request.allowed_email_domains))
+        .bind(sp_certificate)
+        .bind(sp_private_key)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        sso_connection_from_row(&row, &app_state.credential_cipher)
+    }
+}
+
+pub struct UpdateSsoConnectionCommand {
+    deployment_id: i64,
+    connection_id: i64,
+    updates: UpdateSsoConnectionRequest,
+}
+
+impl UpdateSsoConnectionCommand {
+    pub fn new(
+        deployment_id: i64,
+        connection_id: i64,
+        updates: UpdateSsoConnectionRequest,
+    ) -> Self {
+        Self {
+            deployment_id,
+            connection_id,
+            updates,
+        }
+    }
+}
+
+impl Command for UpdateSsoConnectionCommand {
+    type Output = DeploymentSsoConnection;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let current = GetSsoConnectionQuery::new(self.deployment_id, self.connection_id)
+            .execute(app_state)
+            .await?;
+        let updates = self.updates;
+
+        let new_metadata_xml = non_empty(updates.idp_metadata_xml.clone());
+        let metadata_changed = new_metadata_xml.is_some();
+        let metadata_xml = match updates.idp_metadata_xml {
+            Some(_) => new_metadata_xml,
+            None => current.idp_metadata_xml,
+        };
+
+        // A new metadata document replaces whatever it declares unless the
+        // same request overrides those fields too.
+        let keep = |update: Option<String>, current: Option<String>| match update {
+            Some(value) => non_empty(Some(value)),
+            None if metadata_changed => None,
+            None => current,
+        };
+        let idp = resolve_idp(
+            metadata_xml.as_deref(),
+            keep(updates.idp_entity_id, Some(current.idp_entity_id)),
+            keep(updates.idp_sso_url, current.idp_sso_url),
+            keep(updates.idp_certificate, Some(current.idp_certificate)),
+        )?;
+
+        let idp_metadata_url = match updates.idp_metadata_url {
+            Some(url) => non_empty(Some(url)),
+            None => current.idp_metadata_url,
+        };
+        let allowed_email_domains = updates
+            .allowed_email_domains
+            .map(normalize_email_domains)
+            .unwrap_or(current.allowed_email_domains);
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE deployment_sso_connections
+            SET name = $3, enabled = $4, idp_metadata_url = $5, idp_metadata_xml = $6,
+                idp_entity_id = $7, idp_sso_url = $8, idp_certificate = $9,
+                certificate_expires_at = $10, attribute_mapping = $11,
+                allowed_email_domains = $12, updated_at = NOW()
+            WHERE deployment_id = $1 AND id = $2
+            RETURNING {}
+            "#,
+            SSO_CONNECTION_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .bind(self.connection_id)
+        .bind(
+            updates
+                .name
+                .map(|name| name.trim().to_string())
+                .unwrap_or(current.name),
+        )
+        .bind(updates.enabled.unwrap_or(current.enabled))
+        .bind(idp_metadata_url)
+        .bind(metadata_xml)
+        .bind(idp.entity_id)
+        .bind(idp.sso_url)
+        .bind(idp.certificate)
+        .bind(idp.certificate_expires_at)
+        .bind(Json(
+            updates
+                .attribute_mapping
+                .unwrap_or(current.attribute_mapping),
+        ))
+        .bind(allowed_email_domains)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("SSO connection not found".to_string()))?;
+
+        sso_connection_from_row(&row, &app_state.credential_cipher)
+    }
+}
+
+pub struct DeleteSsoConnectionCommand {
+    deployment_id: i64,
+    connection_id: i64,
+}
+
+impl DeleteSsoConnectionCommand {
+    pub fn new(deployment_id: i64, connection_id: i64) -> Self {
+        Self {
+            deployment_id,
+            connection_id,
+        }
+    }
+}
+
+impl Command for DeleteSsoConnectionCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let result = sqlx::query(
+            "DELETE FROM deployment_sso_connections WHERE deployment_id = $1 AND id = $2",
+        )
+        .bind(self.deployment_id)
+        .bind(self.connection_id)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("SSO connection not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
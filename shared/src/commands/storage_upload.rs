@@ -0,0 +1,353 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    dto::json::CreateSignedUploadRequest,
+    error::AppError,
+    models::{SignedUpload, StorageClass, StorageUpload},
+    services::{ObjectStorage, UploadConstraints},
+    state::AppState,
+};
+
+use super::Command;
+
+/// How long a signed upload URL can be used.
+const SIGNED_UPLOAD_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// How long an expired, unconfirmed upload is kept before it is cleaned up,
+/// so a client that started just before expiry can still confirm.
+const UNCONFIRMED_UPLOAD_GRACE: chrono::Duration = chrono::Duration::hours(1);
+
+const DEFAULT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const CLEANUP_BATCH_SIZE: i64 = 500;
+
+fn upload_from_row(app_state: &AppState, row: &PgRow) -> Result<StorageUpload, AppError> {
+    let storage_class: StorageClass = row
+        .get::<String, _>("storage_class")
+        .parse()
+        .map_err(AppError::Internal)?;
+    let object_key: String = row.get("object_key");
+
+    Ok(StorageUpload {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        deployment_id: row.get("deployment_id"),
+        storage_class,
+        url: app_state
+            .storage
+            .bucket(storage_class)
+            .public_url(&object_key),
+        object_key,
+        content_type: row.get("content_type"),
+        content_length: row.get("content_length"),
+        checksum_sha256: row.get("checksum_sha256"),
+        expires_at: row.get("expires_at"),
+        confirmed_at: row.get("confirmed_at"),
+    })
+}
+
+/// Keeps letters, digits, `.`, `_` and `-` of a client's file name, so it is
+/// safe in a key and a URL.
+fn sanitize_file_name(file_name: &str) -> String {
+    let sanitized: String = file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let sanitized = sanitized.trim_matches(|c| c == '.' || c == '-');
+    if sanitized.is_empty() {
+        "file".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// Signs a PUT the console can send a file to storage with, instead of
+/// passing it through the API. The size, type and checksum of the file are
+/// part of the signature, so storage rejects anything else.
+pub struct CreateSignedUploadUrlCommand {
+    deployment_id: i64,
+    request: CreateSignedUploadRequest,
+}
+
+impl CreateSignedUploadUrlCommand {
+    pub fn new(deployment_id: i64, request: CreateSignedUploadRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+}
+
+impl Command for CreateSignedUploadUrlCommand {
+    type Output = SignedUpload;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let exists: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM deployments WHERE id = $1 AND deleted_at IS NULL")
+                .bind(self.deployment_id)
+                .fetch_optional(&app_state.db_pool)
+                .await?;
+        if exists.is_none() {
+            return Err(AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.deployment_id
+            )));
+        }
+
+        let upload_id = app_state.sf.next_id()? as i64;
+        let object_key = format!(
+            "deployments/{}/{}/{}-{}",
+            self.deployment_id,
+            self.request.key_prefix,
+            upload_id,
+            sanitize_file_name(&self.request.file_name)
+        );
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(SIGNED_UPLOAD_TTL).expect("TTL fits a chrono duration");
+
+        let bucket = app_state.storage.bucket(self.request.storage_class);
+        let presigned = bucket
+            .presign_put(
+                &object_key,
+                &UploadConstraints {
+                    content_type: self.request.content_type.clone(),
+                    content_length: self.request.content_length,
+                    checksum_sha256: self.request.checksum_sha256.clone(),
+                },
+                SIGNED_UPLOAD_TTL,
+            )
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO storage_uploads (
+                id, deployment_id, storage_class, object_key, content_type,
+                content_length, checksum_sha256, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(upload_id)
+        .bind(self.deployment_id)
+        .bind(self.request.storage_class.as_str())
+        .bind(&object_key)
+        .bind(&self.request.content_type)
+        .bind(self.request.content_length)
+        .bind(&self.request.checksum_sha256)
+        .bind(expires_at)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(SignedUpload {
+            upload_id,
+            method: presigned.method,
+            upload_url: presigned.url,
+            headers: presigned.headers,
+            url: bucket.public_url(&object_key),
+            object_key,
+            expires_at,
+        })
+    }
+}
+
+/// Checks a signed upload landed as it was signed for: the object exists and
+/// has the expected size, type and SHA-256. Only a confirmed upload should be
+/// referenced from other rows. An object that doesn't match is deleted.
+pub struct ConfirmUploadCommand {
+    deployment_id: i64,
+    upload_id: i64,
+}
+
+impl ConfirmUploadCommand {
+    pub fn new(deployment_id: i64, upload_id: i64) -> Self {
+        Self {
+            deployment_id,
+            upload_id,
+        }
+    }
+
+    /// The object's SHA-256, from its metadata when storage kept the checksum
+    /// it was uploaded with, otherwise by reading it.
+    async fn checksum(
+        bucket: &dyn ObjectStorage,
+        key: &str,
+        stored: Option<String>,
+    ) -> Result<String, AppError> {
+        match stored {
+            Some(stored) => Ok(stored),
+            None => bucket.sha256(key).await,
+        }
+    }
+}
+
+impl Command for ConfirmUploadCommand {
+    type Output = StorageUpload;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query("SELECT * FROM storage_uploads WHERE id = $1 AND deployment_id = $2")
+            .bind(self.upload_id)
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("Upload with id {} not found", self.upload_id))
+            })?;
+        let upload = upload_from_row(app_state, &row)?;
+
+        if upload.confirmed_at.is_some() {
+            return Ok(upload);
+        }
+        if upload.expires_at + UNCONFIRMED_UPLOAD_GRACE < Utc::now() {
+            return Err(AppError::BadRequest(
+                "The upload has expired, request a new upload URL".to_string(),
+            ));
+        }
+
+        let bucket = app_state.storage.bucket(upload.storage_class);
+        let Some(head) = bucket.head_object(&upload.object_key).await? else {
+            return Err(AppError::BadRequest(
+                "The file hasn't been uploaded yet".to_string(),
+            ));
+        };
+
+        let mut mismatches = Vec::new();
+        if head.content_length != Some(upload.content_length) {
+            mismatches.push("size");
+        }
+        if head.content_type.as_deref() != Some(upload.content_type.as_str()) {
+            mismatches.push("content type");
+        }
+        if mismatches.is_empty() {
+            let checksum = Self::checksum(bucket, &upload.object_key, head.checksum_sha256).await?;
+            if checksum != upload.checksum_sha256 {
+                mismatches.push("checksum");
+            }
+        }
+        if !mismatches.is_empty() {
+            bucket.delete_object(&upload.object_key).await?;
+            return Err(AppError::BadRequest(format!(
+                "The uploaded file doesn't match the upload's {} and was deleted",
+                mismatches.join(", ")
+            )));
+        }
+
+        let row = sqlx::query(
+            r#"
+            UPDATE storage_uploads
+            SET confirmed_at = NOW(), updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(upload.id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        upload_from_row(app_state, &row)
+    }
+}
+
+/// Deletes uploads that were never confirmed, and whatever was uploaded for
+/// them. Returns how many were removed.
+pub struct CleanupUnconfirmedUploadsCommand;
+
+impl CleanupUnconfirmedUploadsCommand {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for CleanupUnconfirmedUploadsCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for CleanupUnconfirmedUploadsCommand {
+    type Output = u64;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, storage_class, object_key
+            FROM storage_uploads
+            WHERE confirmed_at IS NULL AND expires_at < $1
+            ORDER BY expires_at
+            LIMIT $2
+            "#,
+        )
+        .bind(Utc::now() - UNCONFIRMED_UPLOAD_GRACE)
+        .bind(CLEANUP_BATCH_SIZE)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        let mut removed = 0;
+        for row in rows {
+            let id: i64 = row.get("id");
+            let object_key: String = row.get("object_key");
+            let storage_class: StorageClass = match row.get::<String, _>("storage_class").parse() {
+                Ok(storage_class) => storage_class,
+                Err(e) => {
+                    tracing::warn!(upload_id = id, "Skipping upload cleanup: {}", e);
+                    continue;
+                }
+            };
+
+            // Deleting a key that was never uploaded succeeds too.
+            let bucket = app_state.storage.bucket(storage_class);
+            if let Err(e) = bucket.delete_object(&object_key).await {
+                tracing::warn!(upload_id = id, "Failed to delete unconfirmed upload: {}", e);
+                continue;
+            }
+
+            removed +=
+                sqlx::query("DELETE FROM storage_uploads WHERE id = $1 AND confirmed_at IS NULL")
+                    .bind(id)
+                    .execute(&app_state.db_pool)
+                    .await?
+                    .rows_affected();
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Runs [`CleanupUnconfirmedUploadsCommand`] every
+/// `UPLOAD_CLEANUP_INTERVAL_SECS` (default an hour) until shutdown.
+pub fn spawn_upload_cleanup(app_state: &AppState) {
+    let interval = std::env::var("UPLOAD_CLEANUP_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CLEANUP_INTERVAL);
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = background_state.background_tasks.shutdown_requested() => break,
+            }
+
+            match CleanupUnconfirmedUploadsCommand::new()
+                .execute(&background_state)
+                .await
+            {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!("Removed {} unconfirmed uploads", removed),
+                Err(e) => tracing::error!("Failed to clean up unconfirmed uploads: {}", e),
+            }
+        }
+    });
+}
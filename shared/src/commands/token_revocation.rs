@@ -0,0 +1,195 @@
+//! Revoking session tokens before they expire.
+//!
+//! Session tokens are verified without a database round trip, so revocations
+//! live in Redis where the verifying services can check them cheaply. A
+//! revocation only has to outlast the tokens it covers: a single token's is
+//! kept until that token expires, and a "not before" timestamp for a user or
+//! the whole deployment is kept for one token lifetime, after which every
+//! token issued before it has expired anyway.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde_json::json;
+
+use crate::{
+    error::AppError,
+    models::{RevokedToken, RevokedTokensBefore},
+    state::AppState,
+};
+
+use super::{Command, RecordAuditLogCommand};
+
+pub(crate) fn revoked_token_key(deployment_id: i64, jti: &str) -> String {
+    format!("revoked_token:{}:{}", deployment_id, jti)
+}
+
+pub(crate) fn user_tokens_not_before_key(deployment_id: i64, user_id: i64) -> String {
+    format!("tokens_not_before:{}:{}", deployment_id, user_id)
+}
+
+pub(crate) fn deployment_tokens_not_before_key(deployment_id: i64) -> String {
+    format!("tokens_not_before:{}", deployment_id)
+}
+
+/// How long the deployment's session tokens live.
+async fn session_token_lifetime(app_state: &AppState, deployment_id: i64) -> Result<i64, AppError> {
+    let lifetime: Option<i64> = sqlx::query_scalar(
+        "SELECT session_token_lifetime FROM deployment_auth_settings WHERE deployment_id = $1",
+    )
+    .bind(deployment_id)
+    .fetch_optional(&app_state.db_pool)
+    .await?;
+
+    lifetime.ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))
+}
+
+async fn revoke_tokens_before(
+    app_state: &AppState,
+    key: &str,
+    not_before: DateTime<Utc>,
+    ttl: Duration,
+) -> Result<(), AppError> {
+    let mut connection = app_state.redis.connection().await?;
+    connection
+        .set_ex::<_, _, ()>(key, not_before.timestamp(), ttl.as_secs().max(1))
+        .await?;
+    Ok(())
+}
+
+/// Revokes one session token by its `jti` until it expires.
+pub struct RevokeTokenCommand {
+    deployment_id: i64,
+    jti: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl RevokeTokenCommand {
+    pub fn new(deployment_id: i64, jti: impl Into<String>, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            deployment_id,
+            jti: jti.into(),
+            expires_at,
+        }
+    }
+}
+
+impl Command for RevokeTokenCommand {
+    type Output = RevokedToken;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // A token that has already expired is rejected anyway.
+        let remaining = (self.expires_at - Utc::now()).num_seconds();
+        if remaining > 0 {
+            let mut connection = app_state.redis.connection().await?;
+            connection
+                .set_ex::<_, _, ()>(
+                    revoked_token_key(self.deployment_id, &self.jti),
+                    1,
+                    remaining as u64,
+                )
+                .await?;
+        }
+
+        Ok(RevokedToken {
+            jti: self.jti,
+            revoked_until: self.expires_at,
+        })
+    }
+}
+
+/// Revokes every session token issued to a user until now. Signing a user
+/// out everywhere does this too.
+pub struct RevokeUserTokensCommand {
+    deployment_id: i64,
+    user_id: i64,
+}
+
+impl RevokeUserTokensCommand {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+        }
+    }
+}
+
+impl Command for RevokeUserTokensCommand {
+    type Output = RevokedTokensBefore;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let lifetime = session_token_lifetime(app_state, self.deployment_id).await?;
+        let not_before = Utc::now();
+
+        revoke_tokens_before(
+            app_state,
+            &user_tokens_not_before_key(self.deployment_id, self.user_id),
+            not_before,
+            Duration::from_secs(lifetime.max(1) as u64),
+        )
+        .await?;
+
+        Ok(RevokedTokensBefore { not_before })
+    }
+}
+
+/// The panic button: revokes every session token the deployment has issued
+/// until now. Sessions themselves survive, so users get new tokens the next
+/// time their session refreshes.
+pub struct RevokeDeploymentTokensCommand {
+    deployment_id: i64,
+    initiated_by: Option<String>,
+}
+
+impl RevokeDeploymentTokensCommand {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for RevokeDeploymentTokensCommand {
+    type Output = RevokedTokensBefore;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let lifetime = session_token_lifetime(app_state, self.deployment_id).await?;
+        let project_id: i64 =
+            sqlx::query_scalar("SELECT project_id FROM deployments WHERE id = $1")
+                .bind(self.deployment_id)
+                .fetch_one(&app_state.db_pool)
+                .await?;
+        let not_before = Utc::now();
+
+        revoke_tokens_before(
+            app_state,
+            &deployment_tokens_not_before_key(self.deployment_id),
+            not_before,
+            Duration::from_secs(lifetime.max(1) as u64),
+        )
+        .await?;
+
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let mut conn = app_state.db_pool.acquire().await?;
+        RecordAuditLogCommand::new(
+            project_id,
+            "deployment.tokens_revoked",
+            "deployment",
+            self.deployment_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by)
+        .with_metadata(json!({ "not_before": not_before }))
+        .execute_with(audit_log_id, &mut conn)
+        .await?;
+
+        Ok(RevokedTokensBefore { not_before })
+    }
+}
@@ -1,7 +1,39 @@
-use crate::{commands::Command, error::AppError, models::Organization, state::AppState};
+use crate::{
+    commands::{
+        Command, RecordOrganizationAuditEventCommand, settings_change_payload, settings_snapshot,
+    },
+    error::AppError,
+    models::{Organization, OrganizationAuditEventType},
+    state::AppState,
+    utils::{
+        metadata::{MAX_METADATA_BYTES, MetadataMergeMode, merge_metadata, metadata_size},
+        validation::{ValidationError, validation_failed},
+    },
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::Row;
+use sqlx::{PgConnection, Row, postgres::PgRow};
+
+/// Locks the organization and reads its settings before they change.
+async fn lock_organization_settings(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    organization_id: i64,
+) -> Result<PgRow, AppError> {
+    sqlx::query(
+        r#"
+        SELECT name, description, image_url, public_metadata, private_metadata
+        FROM organizations
+        WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(organization_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Organization not found".to_string()))
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateOrganizationCommand {
@@ -12,6 +44,7 @@ pub struct UpdateOrganizationCommand {
     pub image_url: Option<String>,
     pub public_metadata: Option<Value>,
     pub private_metadata: Option<Value>,
+    pub initiated_by: Option<String>,
 }
 
 impl UpdateOrganizationCommand {
@@ -32,14 +65,20 @@ impl UpdateOrganizationCommand {
             image_url,
             public_metadata,
             private_metadata,
+            initiated_by: None,
         }
     }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
 }
 
 impl Command for UpdateOrganizationCommand {
     type Output = Organization;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut query_parts = Vec::new();
         let mut param_count = 3; // deployment_id and organization_id are $1 and $2
 
@@ -74,7 +113,7 @@ impl Command for UpdateOrganizationCommand {
             r#"
             UPDATE organizations
             SET {}
-            WHERE deployment_id = $1 AND id = $2
+            WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL
             RETURNING
                 id, created_at, updated_at, deployment_id,
                 name, description, image_url, member_count,
@@ -105,7 +144,157 @@ impl Command for UpdateOrganizationCommand {
 
         query = query.bind(chrono::Utc::now());
 
-        let organization = query.fetch_one(&app_state.db_pool).await?;
+        let audit_event_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let before =
+            lock_organization_settings(&mut tx, self.deployment_id, self.organization_id).await?;
+        let organization = query.fetch_one(&mut *tx).await?;
+
+        if let Some(payload) =
+            settings_change_payload(settings_snapshot(&before), settings_snapshot(&organization))
+        {
+            RecordOrganizationAuditEventCommand::new(
+                self.deployment_id,
+                self.organization_id,
+                OrganizationAuditEventType::SettingsUpdated,
+            )
+            .with_actor(self.initiated_by)
+            .with_payload(payload)
+            .execute_with(audit_event_id, &mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Organization {
+            id: organization.get("id"),
+            created_at: organization.get("created_at"),
+            updated_at: organization.get("updated_at"),
+            name: organization.get("name"),
+            description: organization.get("description"),
+            image_url: organization.get("image_url"),
+            member_count: organization.get("member_count"),
+            public_metadata: organization.get("public_metadata"),
+            private_metadata: organization.get("private_metadata"),
+        })
+    }
+}
+
+/// Changes only an organization's metadata, merging the given objects into
+/// the stored ones according to `mode`.
+pub struct UpdateOrganizationMetadataCommand {
+    deployment_id: i64,
+    organization_id: i64,
+    public_metadata: Option<Value>,
+    private_metadata: Option<Value>,
+    mode: MetadataMergeMode,
+    initiated_by: Option<String>,
+}
+
+impl UpdateOrganizationMetadataCommand {
+    pub fn new(deployment_id: i64, organization_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            public_metadata: None,
+            private_metadata: None,
+            mode: MetadataMergeMode::default(),
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_public_metadata(mut self, public_metadata: Option<Value>) -> Self {
+        self.public_metadata = public_metadata;
+        self
+    }
+
+    pub fn with_private_metadata(mut self, private_metadata: Option<Value>) -> Self {
+        self.private_metadata = private_metadata;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: MetadataMergeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for UpdateOrganizationMetadataCommand {
+    type Output = Organization;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_event_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let current =
+            lock_organization_settings(&mut tx, self.deployment_id, self.organization_id).await?;
+
+        let mut errors = Vec::new();
+        let mut merged = |field: &str, update: Option<Value>| {
+            let current = current
+                .get::<Option<Value>, _>(field)
+                .unwrap_or_else(|| Value::Object(Default::default()));
+            let Some(update) = update else {
+                return current;
+            };
+            let value = merge_metadata(current, update, self.mode);
+            // Each update is within the limit, but merging can still push
+            // the stored object over it.
+            if metadata_size(&value) > MAX_METADATA_BYTES {
+                errors.push(ValidationError::rule(
+                    field,
+                    "too_large",
+                    &format!("{} cannot exceed {} bytes", field, MAX_METADATA_BYTES),
+                ));
+            }
+            value
+        };
+        let public_metadata = merged("public_metadata", self.public_metadata);
+        let private_metadata = merged("private_metadata", self.private_metadata);
+        if !errors.is_empty() {
+            return Err(validation_failed(errors));
+        }
+
+        let organization = sqlx::query(
+            r#"
+            UPDATE organizations
+            SET public_metadata = $3, private_metadata = $4, updated_at = NOW()
+            WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL
+            RETURNING
+                id, created_at, updated_at, deployment_id,
+                name, description, image_url, member_count,
+                public_metadata, private_metadata
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .bind(&public_metadata)
+        .bind(&private_metadata)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if let Some(payload) = settings_change_payload(
+            settings_snapshot(&current),
+            settings_snapshot(&organization),
+        ) {
+            RecordOrganizationAuditEventCommand::new(
+                self.deployment_id,
+                self.organization_id,
+                OrganizationAuditEventType::SettingsUpdated,
+            )
+            .with_actor(self.initiated_by)
+            .with_payload(payload)
+            .execute_with(audit_event_id, &mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
 
         Ok(Organization {
             id: organization.get("id"),
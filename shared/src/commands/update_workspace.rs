@@ -0,0 +1,161 @@
+use crate::{
+    commands::{
+        Command, RecordOrganizationAuditEventCommand, settings_change_payload, settings_snapshot,
+    },
+    error::AppError,
+    models::{OrganizationAuditEventType, Workspace},
+    state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::Row;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateWorkspaceCommand {
+    pub deployment_id: i64,
+    pub workspace_id: i64,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub public_metadata: Option<Value>,
+    pub private_metadata: Option<Value>,
+    pub initiated_by: Option<String>,
+}
+
+impl UpdateWorkspaceCommand {
+    pub fn new(
+        deployment_id: i64,
+        workspace_id: i64,
+        name: Option<String>,
+        description: Option<String>,
+        image_url: Option<String>,
+        public_metadata: Option<Value>,
+        private_metadata: Option<Value>,
+    ) -> Self {
+        Self {
+            deployment_id,
+            workspace_id,
+            name,
+            description,
+            image_url,
+            public_metadata,
+            private_metadata,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for UpdateWorkspaceCommand {
+    type Output = Workspace;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self.name.is_none()
+            && self.description.is_none()
+            && self.image_url.is_none()
+            && self.public_metadata.is_none()
+            && self.private_metadata.is_none()
+        {
+            return Err(AppError::BadRequest("No fields to update".to_string()));
+        }
+
+        let audit_event_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let before = sqlx::query(
+            r#"
+            SELECT organization_id, name, description, image_url, public_metadata, private_metadata
+            FROM workspaces
+            WHERE deployment_id = $1 AND id = $2
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.workspace_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Workspace not found".to_string()))?;
+
+        let mut query_builder = sqlx::QueryBuilder::new("UPDATE workspaces SET updated_at = NOW()");
+
+        if let Some(name) = self.name {
+            query_builder.push(", name = ");
+            query_builder.push_bind(name);
+        }
+
+        if let Some(description) = self.description {
+            query_builder.push(", description = ");
+            query_builder.push_bind(description);
+        }
+
+        if let Some(image_url) = self.image_url {
+            query_builder.push(", image_url = ");
+            query_builder.push_bind(image_url);
+        }
+
+        if let Some(public_metadata) = self.public_metadata {
+            query_builder.push(", public_metadata = ");
+            query_builder.push_bind(public_metadata);
+        }
+
+        if let Some(private_metadata) = self.private_metadata {
+            query_builder.push(", private_metadata = ");
+            query_builder.push_bind(private_metadata);
+        }
+
+        query_builder.push(" WHERE deployment_id = ");
+        query_builder.push_bind(self.deployment_id);
+        query_builder.push(" AND id = ");
+        query_builder.push_bind(self.workspace_id);
+        query_builder.push(
+            r#"
+            RETURNING
+                id, created_at, updated_at, name, description, image_url, member_count,
+                public_metadata, private_metadata
+            "#,
+        );
+
+        let workspace = query_builder
+            .build()
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Workspace not found".to_string()))?;
+
+        if let Some(payload) =
+            settings_change_payload(settings_snapshot(&before), settings_snapshot(&workspace))
+        {
+            RecordOrganizationAuditEventCommand::new(
+                self.deployment_id,
+                before.get("organization_id"),
+                OrganizationAuditEventType::WorkspaceSettingsUpdated,
+            )
+            .with_workspace_id(self.workspace_id)
+            .with_actor(self.initiated_by)
+            .with_payload(payload)
+            .execute_with(audit_event_id, &mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Workspace {
+            id: workspace.get("id"),
+            created_at: workspace.get("created_at"),
+            updated_at: workspace.get("updated_at"),
+            name: workspace.get("name"),
+            description: workspace
+                .get::<Option<String>, _>("description")
+                .unwrap_or_default(),
+            image_url: workspace
+                .get::<Option<String>, _>("image_url")
+                .unwrap_or_default(),
+            member_count: workspace.get("member_count"),
+            public_metadata: workspace.get("public_metadata"),
+            private_metadata: workspace.get("private_metadata"),
+        })
+    }
+}
@@ -0,0 +1,405 @@
+//! Usage metering for billing: monthly active users and emails sent.
+//!
+//! Events go to the ClickHouse `usage_events` table while their period is
+//! open, and the figures for an open period are computed from there. At the
+//! end of each month [`CloseBillingPeriodCommand`] freezes every deployment's
+//! numbers into `deployment_usage_periods` and publishes one
+//! [`BillingPeriodClosedEvent`] per deployment to a Redis stream the billing
+//! system reads. An event for a period that is already closed is kept as an
+//! adjustment and reported with the deployment's next billing event.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{BillingPeriod, BillingPeriodClosedEvent, UsageAdjustmentTotal, UsageMetric},
+    services::UsageEventRow,
+    state::AppState,
+};
+
+use super::Command;
+
+/// The Redis stream billing events are appended to.
+pub const BILLING_EVENTS_STREAM: &str = "billing:usage_period_closed";
+
+/// Roughly how many events the stream keeps; the billing system is expected
+/// to read them well before they are trimmed.
+const BILLING_EVENTS_STREAM_LENGTH: usize = 100_000;
+
+const CLOSE_BATCH_SIZE: usize = 500;
+const PUBLISH_BATCH_SIZE: i64 = 100;
+
+/// How long after a month ends it is closed, so events buffered in its last
+/// moments have reached ClickHouse.
+const CLOSE_GRACE_PERIOD: chrono::Duration = chrono::Duration::hours(1);
+
+const DEFAULT_CLOSE_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Counts one billable event towards the deployment's usage.
+pub struct RecordUsageEventCommand {
+    deployment_id: i64,
+    metric: UsageMetric,
+    user_id: Option<i64>,
+    template_name: Option<String>,
+    occurred_at: DateTime<Utc>,
+}
+
+impl RecordUsageEventCommand {
+    /// A sign-in by `user_id`, who counts once per period.
+    pub fn active_user(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            metric: UsageMetric::ActiveUser,
+            user_id: Some(user_id),
+            template_name: None,
+            occurred_at: Utc::now(),
+        }
+    }
+
+    pub fn email_sent(deployment_id: i64, template_name: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            metric: UsageMetric::EmailSent,
+            user_id: None,
+            template_name: Some(template_name.into()),
+            occurred_at: Utc::now(),
+        }
+    }
+
+    /// For events reported some time after they happened. Defaults to now.
+    pub fn with_occurred_at(mut self, occurred_at: DateTime<Utc>) -> Self {
+        self.occurred_at = occurred_at;
+        self
+    }
+
+    async fn record_adjustment(
+        self,
+        app_state: &AppState,
+        period: BillingPeriod,
+    ) -> Result<(), AppError> {
+        if let (UsageMetric::ActiveUser, Some(user_id)) = (self.metric, self.user_id)
+            && app_state
+                .clickhouse_service
+                .is_active_user(
+                    self.deployment_id,
+                    user_id,
+                    period.starts_at(),
+                    period.ends_at(),
+                )
+                .await?
+        {
+            return Ok(());
+        }
+
+        // A user already adjusted into the period hits the unique index.
+        sqlx::query(
+            r#"
+            INSERT INTO deployment_usage_adjustments (
+                id, deployment_id, period_start, metric, user_id, template_name
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(period.start)
+        .bind(self.metric.as_str())
+        .bind(self.user_id)
+        .bind(&self.template_name)
+        .execute(&app_state.db_pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl Command for RecordUsageEventCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let period = BillingPeriod::containing(self.occurred_at);
+
+        // The current period is never closed, so only late events pay for
+        // the lookup.
+        if period < BillingPeriod::containing(Utc::now()) {
+            let closed: bool = sqlx::query_scalar(
+                r#"
+                SELECT EXISTS (
+                    SELECT 1 FROM deployment_usage_periods
+                    WHERE deployment_id = $1 AND period_start = $2
+                )
+                "#,
+            )
+            .bind(self.deployment_id)
+            .bind(period.start)
+            .fetch_one(&app_state.db_pool)
+            .await?;
+            if closed {
+                return self.record_adjustment(app_state, period).await;
+            }
+        }
+
+        app_state.usage_event_buffer.push(UsageEventRow {
+            deployment_id: self.deployment_id,
+            metric: self.metric.as_str().to_string(),
+            user_id: self.user_id,
+            template_name: self.template_name,
+            timestamp: self.occurred_at.timestamp_millis(),
+        });
+
+        Ok(())
+    }
+}
+
+/// Freezes the usage of every deployment that existed during `period`, then
+/// publishes the billing events that haven't gone out yet. Deployments whose
+/// period is already closed are skipped, so running it again is harmless.
+/// Returns how many deployments were closed.
+pub struct CloseBillingPeriodCommand {
+    period: BillingPeriod,
+}
+
+impl CloseBillingPeriodCommand {
+    pub fn new(period: BillingPeriod) -> Self {
+        Self { period }
+    }
+}
+
+impl Command for CloseBillingPeriodCommand {
+    type Output = usize;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self.period >= BillingPeriod::containing(Utc::now()) {
+            return Err(AppError::BadRequest(
+                "Only past billing periods can be closed".to_string(),
+            ));
+        }
+
+        let deployment_ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT d.id FROM deployments d
+            WHERE d.created_at < $2
+                AND (d.deleted_at IS NULL OR d.deleted_at >= $1)
+                AND NOT EXISTS (
+                    SELECT 1 FROM deployment_usage_periods p
+                    WHERE p.deployment_id = d.id AND p.period_start = $3
+                )
+            ORDER BY d.id
+            "#,
+        )
+        .bind(self.period.starts_at())
+        .bind(self.period.ends_at())
+        .bind(self.period.start)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        let mut closed = 0;
+        if !deployment_ids.is_empty() {
+            let mut totals = app_state
+                .clickhouse_service
+                .get_usage(None, self.period.starts_at(), self.period.ends_at())
+                .await?;
+
+            for batch in deployment_ids.chunks(CLOSE_BATCH_SIZE) {
+                let mut ids = Vec::with_capacity(batch.len());
+                let mut active_users = Vec::with_capacity(batch.len());
+                let mut emails_sent = Vec::with_capacity(batch.len());
+                let mut emails_by_template = Vec::with_capacity(batch.len());
+                for deployment_id in batch {
+                    let usage = totals.remove(deployment_id).unwrap_or_default();
+                    let by_template: BTreeMap<String, i64> =
+                        usage.emails_by_template.into_iter().collect();
+                    ids.push(app_state.sf.next_id()? as i64);
+                    active_users.push(usage.active_users);
+                    emails_sent.push(by_template.values().sum::<i64>());
+                    emails_by_template.push(serde_json::to_value(&by_template)?);
+                }
+
+                let result = sqlx::query(
+                    r#"
+                    INSERT INTO deployment_usage_periods (
+                        id, deployment_id, period_start, active_users, emails_sent,
+                        emails_by_template
+                    )
+                    SELECT id, deployment_id, $3, active_users, emails_sent, emails_by_template
+                    FROM UNNEST($1::bigint[], $2::bigint[], $4::bigint[], $5::bigint[], $6::jsonb[])
+                        AS t(id, deployment_id, active_users, emails_sent, emails_by_template)
+                    ON CONFLICT (deployment_id, period_start) DO NOTHING
+                    "#,
+                )
+                .bind(&ids)
+                .bind(batch)
+                .bind(self.period.start)
+                .bind(&active_users)
+                .bind(&emails_sent)
+                .bind(&emails_by_template)
+                .execute(&app_state.db_pool)
+                .await?;
+                closed += result.rows_affected() as usize;
+            }
+        }
+
+        let published = publish_billing_events(app_state).await?;
+        if closed > 0 || published > 0 {
+            tracing::info!(
+                "Closed billing period {} for {} deployments and published {} billing events",
+                self.period.start,
+                closed,
+                published
+            );
+        }
+
+        Ok(closed)
+    }
+}
+
+/// Publishes closed periods whose event hasn't gone out, each with the
+/// deployment's unreported adjustments. An event can be published twice if
+/// the process dies between appending it and recording that it was
+/// appended; the billing system dedupes on `event_id`.
+async fn publish_billing_events(app_state: &AppState) -> Result<usize, AppError> {
+    let mut connection = app_state.redis.connection().await?;
+    let mut published = 0;
+
+    loop {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id, p.deployment_id, d.project_id, p.period_start, p.active_users,
+                p.emails_sent, p.emails_by_template, p.closed_at
+            FROM deployment_usage_periods p
+            JOIN deployments d ON d.id = p.deployment_id
+            WHERE p.event_published_at IS NULL
+            ORDER BY p.closed_at, p.id
+            LIMIT $1
+            FOR UPDATE OF p SKIP LOCKED
+            "#,
+        )
+        .bind(PUBLISH_BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in rows {
+            let event_id: i64 = row.get("id");
+            let deployment_id: i64 = row.get("deployment_id");
+            let period = BillingPeriod::from_date(row.get("period_start"));
+
+            let adjustments = sqlx::query(
+                r#"
+                UPDATE deployment_usage_adjustments
+                SET reported_at = NOW()
+                WHERE deployment_id = $1 AND period_start < $2 AND reported_at IS NULL
+                RETURNING period_start, metric, quantity
+                "#,
+            )
+            .bind(deployment_id)
+            .bind(period.start)
+            .fetch_all(&mut *tx)
+            .await?;
+            let mut adjustment_totals: HashMap<(NaiveDate, String), i64> = HashMap::new();
+            for adjustment in adjustments {
+                *adjustment_totals
+                    .entry((adjustment.get("period_start"), adjustment.get("metric")))
+                    .or_default() += adjustment.get::<i64, _>("quantity");
+            }
+            let mut adjustments: Vec<UsageAdjustmentTotal> = adjustment_totals
+                .into_iter()
+                .map(|((period_start, metric), quantity)| UsageAdjustmentTotal {
+                    period_start,
+                    metric: UsageMetric::from(metric),
+                    quantity,
+                })
+                .collect();
+            adjustments
+                .sort_by_key(|adjustment| (adjustment.period_start, adjustment.metric.as_str()));
+
+            let event = BillingPeriodClosedEvent {
+                event_id,
+                deployment_id,
+                project_id: row.get("project_id"),
+                period_start: period.start,
+                period_end: period.end(),
+                active_users: row.get("active_users"),
+                emails_sent: row.get("emails_sent"),
+                emails_by_template: serde_json::from_value(row.get("emails_by_template"))
+                    .unwrap_or_default(),
+                adjustments,
+                closed_at: row.get("closed_at"),
+            };
+
+            redis::cmd("XADD")
+                .arg(BILLING_EVENTS_STREAM)
+                .arg("MAXLEN")
+                .arg("~")
+                .arg(BILLING_EVENTS_STREAM_LENGTH)
+                .arg("*")
+                .arg("event_id")
+                .arg(event_id)
+                .arg("payload")
+                .arg(serde_json::to_string(&event)?)
+                .query_async::<String>(&mut connection)
+                .await?;
+
+            sqlx::query(
+                "UPDATE deployment_usage_periods SET event_published_at = NOW() WHERE id = $1",
+            )
+            .bind(event_id)
+            .execute(&mut *tx)
+            .await?;
+            published += 1;
+        }
+
+        tx.commit().await?;
+    }
+
+    Ok(published)
+}
+
+/// Closes the previous month once its grace period has passed, checking
+/// every `BILLING_CLOSE_CHECK_INTERVAL_SECS` (default fifteen minutes) until
+/// shutdown. Each check also retries billing events that failed to publish.
+pub fn spawn_billing_period_close(app_state: &AppState) {
+    let interval = std::env::var("BILLING_CLOSE_CHECK_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CLOSE_CHECK_INTERVAL);
+    let background_state = app_state.clone();
+
+    app_state.background_tasks.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = background_state.background_tasks.shutdown_requested() => break,
+            }
+
+            let now = Utc::now();
+            let current = BillingPeriod::containing(now);
+            if now - current.starts_at() < CLOSE_GRACE_PERIOD {
+                continue;
+            }
+
+            if let Err(e) = CloseBillingPeriodCommand::new(current.previous())
+                .execute(&background_state)
+                .await
+            {
+                tracing::error!(
+                    "Failed to close billing period {}: {}",
+                    current.previous().start,
+                    e
+                );
+            }
+        }
+    });
+}
@@ -5,16 +5,25 @@ use std::collections::HashMap;
 use crate::{
     dto::json::{CreateUserRequest, InviteUserRequest, UpdateUserRequest},
     error::AppError,
-    models::{DeploymentInvitation, UserDetails, UserWithIdentifiers},
-    queries::{GetDeploymentAuthSettingsQuery, Query},
+    models::{DeploymentInvitation, RevokedUserSessions, UserDetails, UserWithIdentifiers},
+    queries::{
+        CheckCompromisedPasswordQuery, CheckPhoneNumberAgainstRestrictionsQuery,
+        CheckUsernameAvailabilityQuery, GetDeploymentAuthSettingsQuery, Query,
+        ValidatePasswordQuery, map_username_conflict,
+    },
+    services::{InvitationClaims, SessionRepository, SessionRevocation, invitation_url},
     state::AppState,
     utils::{
+        email_normalization::normalize_email,
         security::{PasswordHasher, TotpGenerator},
-        validation::UserValidator,
+        validation::{UserValidator, validation_failed},
     },
 };
 
-use super::{Command, SendEmailCommand};
+use super::{
+    Command, RevokeUserTokensCommand, SendEmailCommand, TemplateVariableCheck, email_address_taken,
+    strips_subaddresses, waitlist::frontend_host,
+};
 
 pub struct CreateUserCommand {
     deployment_id: i64,
@@ -33,7 +42,7 @@ impl CreateUserCommand {
 impl Command for CreateUserCommand {
     type Output = UserWithIdentifiers;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let now = Utc::now();
         let user_id = app_state.sf.next_id()? as i64;
 
@@ -41,9 +50,13 @@ impl Command for CreateUserCommand {
             .execute(app_state)
             .await?;
 
-        let mut tx = app_state.db_pool.begin().await?;
+        if let Some(phone) = &self.request.phone_number {
+            CheckPhoneNumberAgainstRestrictionsQuery::new(self.deployment_id, phone)
+                .ensure_allowed(app_state)
+                .await?;
+        }
 
-        UserValidator::validate_user_creation(
+        let mut errors = UserValidator::validate_user_creation(
             &self.request.first_name,
             &self.request.last_name,
             &self.request.email_address,
@@ -52,13 +65,67 @@ impl Command for CreateUserCommand {
             &self.request.password,
             &auth_settings,
         )
-        .map_err(|errors| {
-            let error_messages: Vec<String> = errors
-                .into_iter()
-                .map(|e| format!("{}: {}", e.field, e.message))
-                .collect();
-            AppError::BadRequest(format!("Validation failed: {}", error_messages.join(", ")))
-        })?;
+        .err()
+        .unwrap_or_default();
+
+        // Stored normalized, and only once it passes the deployment's rules
+        // and no one else has it.
+        let username = match self.request.username.as_deref().map(str::trim) {
+            Some(username)
+                if !username.is_empty() && !errors.iter().any(|e| e.field == "username") =>
+            {
+                let (username, username_errors) =
+                    CheckUsernameAvailabilityQuery::new(self.deployment_id, username)
+                        .check(app_state, &auth_settings.username)
+                        .await?;
+                errors.extend(username_errors);
+                Some(username)
+            }
+            _ => None,
+        };
+
+        let normalized_email = match &self.request.email_address {
+            Some(email) => Some(normalize_email(
+                email,
+                strips_subaddresses(&app_state.db_pool, self.deployment_id).await?,
+            )),
+            None => None,
+        };
+        if let Some(normalized_email) = &normalized_email
+            && !errors.iter().any(|e| e.field == "email_address")
+        {
+            errors.extend(
+                email_address_taken(
+                    &app_state.db_pool,
+                    self.deployment_id,
+                    "email_address",
+                    normalized_email,
+                    None,
+                )
+                .await?,
+            );
+        }
+
+        if let Some(password) = &self.request.password
+            && auth_settings.password.enabled
+            && !errors.iter().any(|e| e.field == "password")
+        {
+            errors.extend(
+                CheckCompromisedPasswordQuery::new(
+                    self.deployment_id,
+                    password,
+                    &auth_settings.password,
+                )
+                .execute(app_state)
+                .await?,
+            );
+        }
+
+        if !errors.is_empty() {
+            return Err(validation_failed(errors));
+        }
+
+        let mut tx = app_state.db_pool.begin().await?;
 
         let hashed_password = if let Some(password) = &self.request.password {
             Some(PasswordHasher::hash_password(password)?)
@@ -82,7 +149,7 @@ impl Command for CreateUserCommand {
             now,
             self.request.first_name,
             self.request.last_name,
-            self.request.username,
+            username,
             hashed_password,
             "v1",
             false,
@@ -94,7 +161,8 @@ impl Command for CreateUserCommand {
             &Vec::<String>::new()
         )
         .execute(&mut *tx)
-        .await?;
+        .await
+        .map_err(map_username_conflict)?;
 
         let mut primary_email_address = None;
         let mut primary_phone_number = None;
@@ -102,25 +170,22 @@ impl Command for CreateUserCommand {
         if let Some(email) = &self.request.email_address {
             let email_id = app_state.sf.next_id()? as i64;
 
-            sqlx::query!(
+            sqlx::query(
                 r#"
             INSERT INTO user_email_addresses (
                 id, created_at, updated_at, deployment_id, user_id,
-                email_address, is_primary, verified, verified_at, verification_strategy
+                email_address, normalized_email_address, is_primary, verified, verified_at,
+                verification_strategy
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            VALUES ($1, $2, $2, $3, $4, $5, $6, true, true, $2, 'otp')
             "#,
-                email_id,
-                now,
-                now,
-                self.deployment_id,
-                user_id,
-                email,
-                true,
-                true,
-                now,
-                "otp"
             )
+            .bind(email_id)
+            .bind(now)
+            .bind(self.deployment_id)
+            .bind(user_id)
+            .bind(email)
+            .bind(&normalized_email)
             .execute(&mut *tx)
             .await?;
 
@@ -176,9 +241,10 @@ impl Command for CreateUserCommand {
             updated_at: now,
             first_name: self.request.first_name,
             last_name: self.request.last_name,
-            username: self.request.username,
+            username,
             primary_email_address,
             primary_phone_number,
+            primary_web3_wallet: None,
         };
 
         tx.commit().await?;
@@ -204,7 +270,8 @@ impl InviteUserCommand {
 impl Command for InviteUserCommand {
     type Output = DeploymentInvitation;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let frontend_host = frontend_host(app_state, self.deployment_id).await?;
         let now = Utc::now();
         let expiry_days = self.request.expiry_days.unwrap_or(7);
         let expiry = now + Duration::days(expiry_days);
@@ -230,6 +297,14 @@ impl Command for InviteUserCommand {
         .execute(&app_state.db_pool)
         .await?;
 
+        let token = app_state.invitation_token_signer.sign(&InvitationClaims {
+            invitation_id,
+            deployment_id: self.deployment_id,
+            email_address: self.request.email_address.clone(),
+            expires_at: expiry,
+            organization_id: None,
+        })?;
+
         let mut variables = HashMap::new();
         variables.insert("app_name".to_string(), "Your App".to_string());
         variables.insert(
@@ -238,6 +313,10 @@ impl Command for InviteUserCommand {
         );
         variables.insert("first_name".to_string(), self.request.first_name.clone());
         variables.insert("last_name".to_string(), self.request.last_name.clone());
+        variables.insert(
+            "action_url".to_string(),
+            invitation_url(&frontend_host, &token),
+        );
         variables.insert(
             "invitation.expires_in_days".to_string(),
             expiry_days.to_string(),
@@ -249,6 +328,7 @@ impl Command for InviteUserCommand {
             self.request.email_address.clone(),
             variables,
         )
+        .with_variable_check(TemplateVariableCheck::Strict)
         .execute(app_state)
         .await?;
 
@@ -261,345 +341,215 @@ impl Command for InviteUserCommand {
             last_name: self.request.last_name,
             email_address: self.request.email_address,
             expiry,
+            accepted_at: None,
+            revoked_at: None,
         };
 
         Ok(invitation)
     }
 }
 
-pub struct ApproveWaitlistUserCommand {
+pub struct UpdateUserCommand {
     deployment_id: i64,
-    waitlist_user_id: i64,
+    user_id: i64,
+    request: UpdateUserRequest,
 }
 
-impl ApproveWaitlistUserCommand {
-    pub fn new(deployment_id: i64, waitlist_user_id: i64) -> Self {
+impl UpdateUserCommand {
+    pub fn new(deployment_id: i64, user_id: i64, request: UpdateUserRequest) -> Self {
         Self {
             deployment_id,
-            waitlist_user_id,
+            user_id,
+            request,
         }
     }
 }
 
-impl Command for ApproveWaitlistUserCommand {
-    type Output = DeploymentInvitation;
+impl Command for UpdateUserCommand {
+    type Output = UserDetails;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let now = Utc::now();
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut query_builder = sqlx::QueryBuilder::new("UPDATE users SET updated_at = NOW()");
 
-        let mut tx = app_state.db_pool.begin().await?;
+        if let Some(first_name) = self.request.first_name {
+            query_builder.push(", first_name = ");
+            query_builder.push_bind(first_name);
+        }
 
-        let waitlist_user = sqlx::query!(
-            r#"
-            SELECT id, created_at, updated_at, deployment_id,
-                   first_name, last_name, email_address
-            FROM deployment_waitlist_users
-            WHERE id = $1 AND deployment_id = $2
-            "#,
-            self.waitlist_user_id,
-            self.deployment_id
-        )
-        .fetch_one(&mut *tx)
-        .await
-        .map_err(|_| AppError::NotFound("Waitlist user not found".to_string()))?;
+        if let Some(last_name) = self.request.last_name {
+            query_builder.push(", last_name = ");
+            query_builder.push_bind(last_name);
+        }
 
-        let invitation_id = app_state.sf.next_id()? as i64;
-        let expiry = now + Duration::days(7);
+        if let Some(username) = self.request.username {
+            let auth_settings = GetDeploymentAuthSettingsQuery::new(self.deployment_id)
+                .execute(app_state)
+                .await?;
+            let username = CheckUsernameAvailabilityQuery::new(self.deployment_id, username)
+                .excluding_user(self.user_id)
+                .ensure_available(app_state, &auth_settings.username)
+                .await?;
+            query_builder.push(", username = ");
+            query_builder.push_bind(username);
+        }
 
-        let first_name = waitlist_user.first_name.unwrap_or_default();
-        let last_name = waitlist_user.last_name.unwrap_or_default();
-        let email_address = waitlist_user.email_address.unwrap_or_default();
+        if let Some(public_metadata) = self.request.public_metadata {
+            query_builder.push(", public_metadata = ");
+            query_builder.push_bind(public_metadata);
+        }
 
-        sqlx::query!(
+        if let Some(private_metadata) = self.request.private_metadata {
+            query_builder.push(", private_metadata = ");
+            query_builder.push_bind(private_metadata);
+        }
+
+        query_builder.push(" WHERE deployment_id = ");
+        query_builder.push_bind(self.deployment_id);
+        query_builder.push(" AND id = ");
+        query_builder.push_bind(self.user_id);
+
+        query_builder
+            .build()
+            .execute(&app_state.db_pool)
+            .await
+            .map_err(map_username_conflict)?;
+
+        use crate::queries::{GetUserDetailsQuery, Query};
+        let user_details = GetUserDetailsQuery::new(self.deployment_id, self.user_id)
+            .execute(app_state)
+            .await?;
+
+        Ok(user_details)
+    }
+}
+
+/// Sets a new password for a user after checking it against the
+/// deployment's password policy. Every rule the password breaks is reported.
+/// Changing the password also satisfies a pending `require_password_change`.
+pub struct ChangeUserPasswordCommand {
+    deployment_id: i64,
+    user_id: i64,
+    password: String,
+}
+
+impl ChangeUserPasswordCommand {
+    pub fn new(deployment_id: i64, user_id: i64, password: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            password: password.into(),
+        }
+    }
+}
+
+impl Command for ChangeUserPasswordCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        ValidatePasswordQuery::new(self.deployment_id, &self.password)
+            .ensure_valid(app_state)
+            .await?;
+
+        let hashed_password = PasswordHasher::hash_password(&self.password)?;
+
+        let result = sqlx::query(
             r#"
-            INSERT INTO deployment_invitations (
-                id, created_at, updated_at, deployment_id,
-                first_name, last_name, email_address, expiry
-            )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            UPDATE users
+            SET password = $1, require_password_change = false, updated_at = NOW()
+            WHERE deployment_id = $2 AND id = $3
             "#,
-            invitation_id,
-            now,
-            now,
-            self.deployment_id,
-            first_name,
-            last_name,
-            email_address,
-            expiry
         )
-        .execute(&mut *tx)
+        .bind(hashed_password)
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .execute(&app_state.db_pool)
         .await?;
 
-        let mut variables = HashMap::new();
-        variables.insert("app_name".to_string(), "Your App".to_string());
-        variables.insert(
-            "app_logo".to_string(),
-            "https://via.placeholder.com/150".to_string(),
-        );
-        variables.insert("first_name".to_string(), first_name.clone());
-        variables.insert("last_name".to_string(), last_name.clone());
-        variables.insert("invitation.expires_in_days".to_string(), "7".to_string());
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
 
-        SendEmailCommand::new(
-            self.deployment_id,
-            "waitlist_invite_template".to_string(),
-            email_address.clone(),
-            variables,
-        )
-        .execute(app_state)
-        .await?;
+        Ok(())
+    }
+}
 
-        sqlx::query!(
-            "DELETE FROM deployment_waitlist_users WHERE id = $1",
-            self.waitlist_user_id
-        )
-        .execute(&mut *tx)
-        .await?;
+pub struct RevokeUserSessionCommand {
+    deployment_id: i64,
+    user_id: i64,
+    session_id: i64,
+}
 
-        tx.commit().await?;
+impl RevokeUserSessionCommand {
+    pub fn new(deployment_id: i64, user_id: i64, session_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            session_id,
+        }
+    }
+}
 
-        let invitation = DeploymentInvitation {
-            id: invitation_id,
-            created_at: now,
-            updated_at: now,
-            deployment_id: self.deployment_id,
-            first_name: first_name.clone(),
-            last_name: last_name.clone(),
-            email_address: email_address.clone(),
-            expiry,
-        };
+impl Command for RevokeUserSessionCommand {
+    type Output = ();
 
-        Ok(invitation)
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        SessionRepository::new(&app_state.db_pool, &app_state.redis)
+            .revoke(
+                self.deployment_id,
+                self.user_id,
+                SessionRevocation::One(self.session_id),
+            )
+            .await?;
+
+        Ok(())
     }
 }
 
-pub struct UpdateUserCommand {
+/// Signs a user out everywhere, revoking the session tokens issued to them,
+/// or everywhere but the caller's own sign-in with `with_current_session_id`.
+pub struct RevokeUserSessionsCommand {
     deployment_id: i64,
     user_id: i64,
-    request: UpdateUserRequest,
+    current_session_id: Option<i64>,
 }
 
-impl UpdateUserCommand {
-    pub fn new(deployment_id: i64, user_id: i64, request: UpdateUserRequest) -> Self {
+impl RevokeUserSessionsCommand {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
         Self {
             deployment_id,
             user_id,
-            request,
+            current_session_id: None,
         }
     }
+
+    pub fn with_current_session_id(mut self, current_session_id: i64) -> Self {
+        self.current_session_id = Some(current_session_id);
+        self
+    }
 }
 
-impl Command for UpdateUserCommand {
-    type Output = UserDetails;
+impl Command for RevokeUserSessionsCommand {
+    type Output = RevokedUserSessions;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        // Update the user with provided fields using compile-time verified queries
-        match (
-            &self.request.first_name,
-            &self.request.last_name,
-            &self.request.username,
-            &self.request.public_metadata,
-            &self.request.private_metadata,
-        ) {
-            (
-                Some(first_name),
-                Some(last_name),
-                Some(username),
-                Some(public_metadata),
-                Some(private_metadata),
-            ) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), first_name = $1, last_name = $2, username = $3,
-                        public_metadata = $4, private_metadata = $5
-                    WHERE deployment_id = $6 AND id = $7
-                    "#,
-                    first_name,
-                    last_name,
-                    username,
-                    public_metadata,
-                    private_metadata,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (Some(first_name), Some(last_name), Some(username), None, None) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), first_name = $1, last_name = $2, username = $3
-                    WHERE deployment_id = $4 AND id = $5
-                    "#,
-                    first_name,
-                    last_name,
-                    username,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (Some(first_name), Some(last_name), None, None, None) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), first_name = $1, last_name = $2
-                    WHERE deployment_id = $3 AND id = $4
-                    "#,
-                    first_name,
-                    last_name,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (Some(first_name), None, None, None, None) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), first_name = $1
-                    WHERE deployment_id = $2 AND id = $3
-                    "#,
-                    first_name,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (None, Some(last_name), None, None, None) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), last_name = $1
-                    WHERE deployment_id = $2 AND id = $3
-                    "#,
-                    last_name,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (None, None, Some(username), None, None) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), username = $1
-                    WHERE deployment_id = $2 AND id = $3
-                    "#,
-                    username,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (None, None, None, Some(public_metadata), None) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), public_metadata = $1
-                    WHERE deployment_id = $2 AND id = $3
-                    "#,
-                    public_metadata,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (None, None, None, None, Some(private_metadata)) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), private_metadata = $1
-                    WHERE deployment_id = $2 AND id = $3
-                    "#,
-                    private_metadata,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (None, None, None, Some(public_metadata), Some(private_metadata)) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), public_metadata = $1, private_metadata = $2
-                    WHERE deployment_id = $3 AND id = $4
-                    "#,
-                    public_metadata,
-                    private_metadata,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (
-                Some(first_name),
-                Some(last_name),
-                None,
-                Some(public_metadata),
-                Some(private_metadata),
-            ) => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW(), first_name = $1, last_name = $2,
-                        public_metadata = $3, private_metadata = $4
-                    WHERE deployment_id = $5 AND id = $6
-                    "#,
-                    first_name,
-                    last_name,
-                    public_metadata,
-                    private_metadata,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            (None, None, None, None, None) => {
-                // Just update the timestamp
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW()
-                    WHERE deployment_id = $1 AND id = $2
-                    "#,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-            _ => {
-                sqlx::query!(
-                    r#"
-                    UPDATE users
-                    SET updated_at = NOW()
-                    WHERE deployment_id = $1 AND id = $2
-                    "#,
-                    self.deployment_id,
-                    self.user_id
-                )
-                .execute(&app_state.db_pool)
-                .await?;
-            }
-        }
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let revocation = match self.current_session_id {
+            Some(current_session_id) => SessionRevocation::AllExcept(current_session_id),
+            None => SessionRevocation::All,
+        };
 
-        use crate::queries::{GetUserDetailsQuery, Query};
-        let user_details = GetUserDetailsQuery::new(self.deployment_id, self.user_id)
-            .execute(app_state)
+        let revoked_count = SessionRepository::new(&app_state.db_pool, &app_state.redis)
+            .revoke(self.deployment_id, self.user_id, revocation)
             .await?;
 
-        Ok(user_details)
+        // Tokens already minted from the revoked sessions would otherwise
+        // stay valid until they expire. Keeping the current session keeps
+        // its tokens too.
+        if self.current_session_id.is_none() {
+            RevokeUserTokensCommand::new(self.deployment_id, self.user_id)
+                .execute(app_state)
+                .await?;
+        }
+
+        Ok(RevokedUserSessions { revoked_count })
     }
 }
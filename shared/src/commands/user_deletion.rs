@@ -0,0 +1,383 @@
+//! Deleting a user only hides them. They stay restorable for the
+//! deployment's retention window, and a job queued for the end of the window
+//! purges them along with their identifiers and social connections.
+//!
+//! A deleted user keeps their username and email addresses until the purge,
+//! unless the deployment releases them immediately, in which case restoring
+//! checks nobody has taken them since.
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    error::AppError,
+    models::UserDetails,
+    queries::{
+        GetDeploymentUserDeletionPolicyQuery, GetUserDetailsQuery, Query, map_username_conflict,
+    },
+    state::AppState,
+    utils::validation::{ValidationError, validation_failed},
+};
+
+use super::{Command, EnqueueJobCommand, Job, RecordAuditLogCommand, RevokeUserSessionsCommand};
+
+/// Moves the user's organization and workspace memberships in or out of the
+/// member counts, which only count users that aren't deleted.
+async fn adjust_member_counts(
+    conn: &mut PgConnection,
+    user_id: i64,
+    delta: i32,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE organizations SET member_count = GREATEST(member_count + $2, 0)
+        WHERE id IN (SELECT organization_id FROM organization_memberships WHERE user_id = $1)
+        "#,
+    )
+    .bind(user_id)
+    .bind(delta)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE workspaces SET member_count = GREATEST(member_count + $2, 0)
+        WHERE id IN (SELECT workspace_id FROM workspace_memberships WHERE user_id = $1)
+        "#,
+    )
+    .bind(user_id)
+    .bind(delta)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Soft-deletes a user: they disappear from lists and lookups, their
+/// sessions end, and they're purged once the deployment's retention window
+/// runs out.
+pub struct DeleteUserCommand {
+    deployment_id: i64,
+    user_id: i64,
+    initiated_by: Option<String>,
+}
+
+impl DeleteUserCommand {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for DeleteUserCommand {
+    type Output = UserDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let policy = GetDeploymentUserDeletionPolicyQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+        let deleted_at = Utc::now();
+        let purge_after = deleted_at + Duration::days(policy.retention_days);
+        let audit_log_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let project_id: i64 = sqlx::query_scalar(
+            r#"
+            SELECT d.project_id
+            FROM users u
+            JOIN deployments d ON d.id = u.deployment_id
+            WHERE u.deployment_id = $1 AND u.id = $2 AND u.deleted_at IS NULL
+            FOR UPDATE OF u
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET deleted_at = $3, purge_after = $4, identifiers_released = $5, updated_at = $3
+            WHERE deployment_id = $1 AND id = $2
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .bind(deleted_at)
+        .bind(purge_after)
+        .bind(policy.release_identifiers_immediately)
+        .execute(&mut *tx)
+        .await?;
+
+        adjust_member_counts(&mut tx, self.user_id, -1).await?;
+
+        EnqueueJobCommand::new(PurgeDeletedUserJob {
+            deployment_id: self.deployment_id,
+            user_id: self.user_id,
+        })
+        .with_run_at(purge_after)
+        .execute_with(app_state, &mut tx)
+        .await?;
+
+        RecordAuditLogCommand::new(project_id, "user.deleted", "user", self.user_id)
+            .with_deployment_id(self.deployment_id)
+            .with_actor(self.initiated_by)
+            .with_metadata(json!({
+                "purge_after": purge_after,
+                "identifiers_released": policy.release_identifiers_immediately,
+            }))
+            .execute_with(audit_log_id, &mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        // After the commit, so a sign-in racing the deletion can't leave a
+        // session behind.
+        RevokeUserSessionsCommand::new(self.deployment_id, self.user_id)
+            .execute(app_state)
+            .await?;
+
+        GetUserDetailsQuery::new(self.deployment_id, self.user_id)
+            .execute(app_state)
+            .await
+    }
+}
+
+/// Undoes a deletion while the user is still within the retention window.
+/// Fails with field errors for any username, email address or wallet someone
+/// else has taken since the deletion released it.
+pub struct RestoreUserCommand {
+    deployment_id: i64,
+    user_id: i64,
+    initiated_by: Option<String>,
+}
+
+impl RestoreUserCommand {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+
+    async fn taken_identifiers(
+        &self,
+        conn: &mut PgConnection,
+    ) -> Result<Vec<ValidationError>, AppError> {
+        let mut errors = Vec::new();
+
+        let username_taken: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM users me
+                JOIN users other
+                    ON other.deployment_id = me.deployment_id
+                    AND LOWER(other.username) = LOWER(me.username)
+                    AND other.id <> me.id
+                    AND NOT other.identifiers_released
+                WHERE me.id = $1
+            )
+            "#,
+        )
+        .bind(self.user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        if username_taken {
+            errors.push(ValidationError::rule(
+                "username",
+                "taken",
+                "Another user has taken this user's username since it was deleted",
+            ));
+        }
+
+        let taken_emails: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT mine.email_address
+            FROM user_email_addresses mine
+            JOIN user_email_addresses other
+                ON other.deployment_id = mine.deployment_id
+                AND other.normalized_email_address = mine.normalized_email_address
+                AND other.user_id <> mine.user_id
+            JOIN users u ON u.id = other.user_id AND NOT u.identifiers_released
+            WHERE mine.user_id = $1
+            ORDER BY mine.email_address
+            "#,
+        )
+        .bind(self.user_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        for email in taken_emails {
+            errors.push(ValidationError::rule(
+                "email_address",
+                "taken",
+                &format!(
+                    "Another user has taken {} since this user was deleted",
+                    email
+                ),
+            ));
+        }
+
+        let taken_wallets: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT mine.address
+            FROM user_web3_wallets mine
+            JOIN user_web3_wallets other
+                ON other.deployment_id = mine.deployment_id
+                AND other.address = mine.address
+                AND other.user_id <> mine.user_id
+            JOIN users u ON u.id = other.user_id AND NOT u.identifiers_released
+            WHERE mine.user_id = $1
+            ORDER BY mine.address
+            "#,
+        )
+        .bind(self.user_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        for address in taken_wallets {
+            errors.push(ValidationError::rule(
+                "web3_wallet",
+                "taken",
+                &format!(
+                    "Another user has taken {} since this user was deleted",
+                    address
+                ),
+            ));
+        }
+
+        Ok(errors)
+    }
+}
+
+impl Command for RestoreUserCommand {
+    type Output = UserDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT d.project_id, u.purge_after
+            FROM users u
+            JOIN deployments d ON d.id = u.deployment_id
+            WHERE u.deployment_id = $1 AND u.id = $2 AND u.deleted_at IS NOT NULL
+            FOR UPDATE OF u
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deleted user not found".to_string()))?;
+        let project_id: i64 = row.get("project_id");
+        let purge_after: Option<chrono::DateTime<Utc>> = row.get("purge_after");
+
+        if purge_after.is_some_and(|purge_after| purge_after <= Utc::now()) {
+            return Err(AppError::BadRequest(
+                "The user's retention window has ended and they can no longer be restored"
+                    .to_string(),
+            ));
+        }
+
+        let errors = self.taken_identifiers(&mut tx).await?;
+        if !errors.is_empty() {
+            return Err(validation_failed(errors));
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET deleted_at = NULL, purge_after = NULL, identifiers_released = false,
+                updated_at = NOW()
+            WHERE deployment_id = $1 AND id = $2
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_username_conflict)?;
+
+        adjust_member_counts(&mut tx, self.user_id, 1).await?;
+
+        RecordAuditLogCommand::new(project_id, "user.restored", "user", self.user_id)
+            .with_deployment_id(self.deployment_id)
+            .with_actor(self.initiated_by)
+            .execute_with(audit_log_id, &mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        GetUserDetailsQuery::new(self.deployment_id, self.user_id)
+            .execute(app_state)
+            .await
+    }
+}
+
+/// Hard-deletes a soft-deleted user once their retention window is over. A
+/// user restored in the meantime, or deleted again with a later purge date,
+/// is left alone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PurgeDeletedUserJob {
+    deployment_id: i64,
+    user_id: i64,
+}
+
+impl Job for PurgeDeletedUserJob {
+    const JOB_TYPE: &'static str = "purge_deleted_user";
+
+    async fn run(self, app_state: &AppState) -> Result<(), AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let due: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM users
+            WHERE deployment_id = $1 AND id = $2
+                AND deleted_at IS NOT NULL AND purge_after <= NOW()
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if due.is_none() {
+            return Ok(());
+        }
+
+        // Memberships, identifiers and the rest of the user's rows cascade.
+        sqlx::query("DELETE FROM users WHERE deployment_id = $1 AND id = $2")
+            .bind(self.deployment_id)
+            .bind(self.user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        tracing::info!(
+            deployment_id = self.deployment_id,
+            user_id = self.user_id,
+            "Purged deleted user"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! Keeps `user_email_addresses.normalized_email_address` in step with the
+//! deployment's `block_subaddresses` setting.
+//!
+//! Every write of an address stores its normalized form next to it, and
+//! duplicates are looked for on that column. Turning `block_subaddresses` on
+//! or off changes what the normalized form is, so it queues a job that
+//! rewrites the column for the deployment's existing addresses.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, Row};
+
+use crate::{
+    commands::Job,
+    error::AppError,
+    state::AppState,
+    utils::{email_normalization::normalize_email, validation::ValidationError},
+};
+
+const NORMALIZE_BATCH_SIZE: usize = 1000;
+
+/// Whether the deployment strips subaddress tags when normalizing addresses.
+pub(crate) async fn strips_subaddresses<'e>(
+    executor: impl PgExecutor<'e>,
+    deployment_id: i64,
+) -> Result<bool, AppError> {
+    let block_subaddresses: Option<bool> = sqlx::query_scalar(
+        "SELECT block_subaddresses FROM deployment_restrictions WHERE deployment_id = $1",
+    )
+    .bind(deployment_id)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(block_subaddresses.unwrap_or(false))
+}
+
+/// The error for `field` when another address on the deployment, other than
+/// `excluding_email_id`, normalizes to `normalized_email`.
+pub(crate) async fn email_address_taken<'e>(
+    executor: impl PgExecutor<'e>,
+    deployment_id: i64,
+    field: &str,
+    normalized_email: &str,
+    excluding_email_id: Option<i64>,
+) -> Result<Option<ValidationError>, AppError> {
+    let taken: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM user_email_addresses e
+            JOIN users u ON u.id = e.user_id
+            WHERE e.deployment_id = $1
+                AND e.normalized_email_address = $2
+                AND e.id IS DISTINCT FROM $3
+                AND NOT u.identifiers_released
+        )
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(normalized_email)
+    .bind(excluding_email_id)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(taken.then(|| {
+        ValidationError::rule(field, "taken", "An account already uses this email address")
+    }))
+}
+
+/// Recomputes the normalized form of every address on a deployment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct NormalizeEmailAddressesJob {
+    deployment_id: i64,
+}
+
+impl NormalizeEmailAddressesJob {
+    pub(crate) fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Job for NormalizeEmailAddressesJob {
+    const JOB_TYPE: &'static str = "normalize_email_addresses";
+
+    async fn run(self, app_state: &AppState) -> Result<(), AppError> {
+        let strip_subaddress = strips_subaddresses(&app_state.db_pool, self.deployment_id).await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, email_address, normalized_email_address
+            FROM user_email_addresses
+            WHERE deployment_id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        let changed: Vec<(i64, String)> = rows
+            .iter()
+            .filter_map(|row| {
+                let normalized = normalize_email(row.get("email_address"), strip_subaddress);
+                let stored: Option<String> = row.get("normalized_email_address");
+                (stored.as_deref() != Some(normalized.as_str()))
+                    .then(|| (row.get("id"), normalized))
+            })
+            .collect();
+
+        for batch in changed.chunks(NORMALIZE_BATCH_SIZE) {
+            let (ids, normalized): (Vec<i64>, Vec<String>) = batch.iter().cloned().unzip();
+            sqlx::query(
+                r#"
+                UPDATE user_email_addresses e
+                SET normalized_email_address = n.normalized
+                FROM UNNEST($1::BIGINT[], $2::TEXT[]) AS n(id, normalized)
+                WHERE e.id = n.id
+                "#,
+            )
+            .bind(&ids)
+            .bind(&normalized)
+            .execute(&app_state.db_pool)
+            .await?;
+        }
+
+        tracing::info!(
+            deployment_id = self.deployment_id,
+            updated = changed.len(),
+            "Normalized email addresses"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,686 @@
+//! Erasure of a user's personal data on request.
+//!
+//! Both modes also scrub the user out of rows they don't own: invitations and
+//! waitlist entries sent to their addresses, audit log entries about them,
+//! AI agent transcripts and the analytics events recorded in ClickHouse.
+
+use std::collections::BTreeSet;
+
+use chrono::Utc;
+use serde_json::json;
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    error::AppError,
+    models::{ErasureAction, UserErasureMode, UserErasureReport},
+    services::{SessionRepository, SessionRevocation},
+    state::AppState,
+};
+
+use super::{Command, RecordAuditLogCommand};
+
+const ERASED_FIRST_NAME: &str = "Erased";
+const ERASED_LAST_NAME: &str = "User";
+const ERASED_TEXT: &str = "[erased]";
+
+/// Metadata keys that hold personal data in audit log entries and agent
+/// session metadata.
+const PERSONAL_METADATA_KEYS: [&str; 7] = [
+    "email_address",
+    "email",
+    "phone_number",
+    "first_name",
+    "last_name",
+    "username",
+    "name",
+];
+
+/// Erases a user for a GDPR request. Deleting removes the user and
+/// everything they own; anonymizing keeps the rows but overwrites names,
+/// addresses, phone numbers and usernames with placeholders derived from row
+/// ids, which can't be traced back to the original values. Either way the
+/// user's sessions end, and the report lists every table looked at.
+pub struct EraseUserCommand {
+    deployment_id: i64,
+    user_id: i64,
+    mode: UserErasureMode,
+    initiated_by: Option<String>,
+}
+
+impl EraseUserCommand {
+    pub fn new(deployment_id: i64, user_id: i64, mode: UserErasureMode) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            mode,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for EraseUserCommand {
+    type Output = UserErasureReport;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let subject = sqlx::query(
+            r#"
+            SELECT d.project_id, u.username
+            FROM users u
+            JOIN deployments d ON d.id = u.deployment_id
+            WHERE u.deployment_id = $1 AND u.id = $2
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+        let project_id: i64 = subject.get("project_id");
+        let username: Option<String> = subject.get("username");
+
+        let addresses: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT email_address FROM user_email_addresses WHERE user_id = $1
+            UNION
+            SELECT phone_number FROM user_phone_numbers WHERE user_id = $1
+            "#,
+        )
+        .bind(self.user_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+        let addresses: Vec<String> = addresses
+            .into_iter()
+            .filter(|address| !address.is_empty())
+            .collect();
+        let lowered: Vec<String> = addresses
+            .iter()
+            .chain(username.iter())
+            .map(|identifier| identifier.to_lowercase())
+            .collect();
+
+        let mut report = UserErasureReport {
+            user_id: self.user_id,
+            deployment_id: self.deployment_id,
+            mode: self.mode,
+            erased_at: Utc::now(),
+            tables: Vec::new(),
+        };
+
+        // Ending the sessions first also drops them from the cache, which the
+        // database changes below wouldn't.
+        SessionRepository::new(&app_state.db_pool, &app_state.redis)
+            .revoke(self.deployment_id, self.user_id, SessionRevocation::All)
+            .await?;
+
+        // Queued before anything else changes, so an unreachable ClickHouse
+        // fails the erasure while it can still be retried.
+        let events = app_state
+            .clickhouse_service
+            .delete_user_events(self.deployment_id, self.user_id)
+            .await?;
+        report.record("user_events", ErasureAction::Queued, events);
+
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let locked =
+            sqlx::query("SELECT id FROM users WHERE deployment_id = $1 AND id = $2 FOR UPDATE")
+                .bind(self.deployment_id)
+                .bind(self.user_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if locked.is_none() {
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
+
+        self.scrub_references(&mut tx, &lowered, &mut report)
+            .await?;
+        self.scrub_transcripts(&mut tx, &addresses, &mut report)
+            .await?;
+
+        match self.mode {
+            UserErasureMode::Delete => self.delete_user(&mut tx, &mut report).await?,
+            UserErasureMode::Anonymize => self.anonymize_user(&mut tx, &mut report).await?,
+        }
+
+        RecordAuditLogCommand::new(project_id, "user.erased", "user", self.user_id)
+            .with_deployment_id(self.deployment_id)
+            .with_actor(self.initiated_by.clone())
+            .with_metadata(json!({
+                "mode": self.mode.as_str(),
+                "tables": report.tables,
+            }))
+            .execute_with(audit_log_id, &mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(report)
+    }
+}
+
+/// Marks the transaction as erasing user data, which is the one case where
+/// agent session messages may be rewritten.
+async fn set_erasing_user_data(conn: &mut PgConnection, erasing: bool) -> Result<(), AppError> {
+    sqlx::query("SELECT set_config('wacht.erasing_user_data', $1, true)")
+        .bind(if erasing { "on" } else { "off" })
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+impl EraseUserCommand {
+    fn is_delete(&self) -> bool {
+        self.mode == UserErasureMode::Delete
+    }
+
+    /// Rows that belong to someone else but name the user, found by user id
+    /// or by one of the user's lowercased identifiers.
+    async fn scrub_references(
+        &self,
+        conn: &mut PgConnection,
+        identifiers: &[String],
+        report: &mut UserErasureReport,
+    ) -> Result<(), AppError> {
+        let invitations = sqlx::query(
+            r#"
+            UPDATE deployment_invitations
+            SET email_address = 'erased-' || id || '@erased.invalid',
+                first_name = $5, last_name = $6,
+                accepted_user_id = CASE WHEN $3 THEN NULL ELSE accepted_user_id END,
+                updated_at = NOW()
+            WHERE deployment_id = $1
+                AND (accepted_user_id = $2 OR lower(email_address) = ANY($4))
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .bind(self.is_delete())
+        .bind(identifiers)
+        .bind(ERASED_FIRST_NAME)
+        .bind(ERASED_LAST_NAME)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record(
+            "deployment_invitations",
+            ErasureAction::Scrubbed,
+            invitations,
+        );
+
+        // Invitations the user sent keep the invitee's address; only the
+        // link to the inviter goes, and only when the inviter is deleted.
+        let organization_invitations = sqlx::query(
+            r#"
+            UPDATE organization_invitations
+            SET email_address = CASE
+                    WHEN accepted_user_id = $2 OR lower(email_address) = ANY($4)
+                    THEN 'erased-' || id || '@erased.invalid'
+                    ELSE email_address
+                END,
+                accepted_user_id = CASE
+                    WHEN $3 AND accepted_user_id = $2 THEN NULL ELSE accepted_user_id
+                END,
+                inviter_id = CASE WHEN $3 AND inviter_id = $2 THEN NULL ELSE inviter_id END,
+                updated_at = NOW()
+            WHERE deployment_id = $1
+                AND (
+                    accepted_user_id = $2
+                    OR lower(email_address) = ANY($4)
+                    OR ($3 AND inviter_id = $2)
+                )
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .bind(self.is_delete())
+        .bind(identifiers)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record(
+            "organization_invitations",
+            ErasureAction::Scrubbed,
+            organization_invitations,
+        );
+
+        let waitlist = sqlx::query(
+            r#"
+            UPDATE deployment_waitlist_users
+            SET email_address = 'erased-' || id || '@erased.invalid',
+                first_name = $3, last_name = $4, metadata = '{}'::jsonb,
+                updated_at = NOW()
+            WHERE deployment_id = $1 AND lower(email_address) = ANY($2)
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(identifiers)
+        .bind(ERASED_FIRST_NAME)
+        .bind(ERASED_LAST_NAME)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record(
+            "deployment_waitlist_users",
+            ErasureAction::Scrubbed,
+            waitlist,
+        );
+
+        let reset_tokens = sqlx::query("DELETE FROM user_password_reset_tokens WHERE user_id = $1")
+            .bind(self.user_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        report.record(
+            "user_password_reset_tokens",
+            ErasureAction::Deleted,
+            reset_tokens,
+        );
+
+        // The entries themselves stay for the audit trail, keyed by user id.
+        let audit_logs = sqlx::query(
+            r#"
+            UPDATE console_audit_logs
+            SET metadata = CASE
+                    WHEN resource_type = 'user' AND resource_id = $2
+                    THEN metadata - $4::text[]
+                    ELSE metadata
+                END,
+                actor = CASE WHEN lower(actor) = ANY($3) THEN $5 ELSE actor END
+            WHERE deployment_id = $1
+                AND (
+                    (resource_type = 'user' AND resource_id = $2)
+                    OR lower(actor) = ANY($3)
+                )
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .bind(identifiers)
+        .bind(&PERSONAL_METADATA_KEYS[..])
+        .bind(ERASED_TEXT)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record("console_audit_logs", ErasureAction::Scrubbed, audit_logs);
+
+        Ok(())
+    }
+
+    /// Agent sessions opened for the user, by the `user_id` in their metadata,
+    /// lose their whole transcript. Anywhere else in the deployment's
+    /// transcripts, the user's email addresses and phone numbers are blanked.
+    async fn scrub_transcripts(
+        &self,
+        conn: &mut PgConnection,
+        addresses: &[String],
+        report: &mut UserErasureReport,
+    ) -> Result<(), AppError> {
+        let mut metadata_keys: Vec<&str> = PERSONAL_METADATA_KEYS.to_vec();
+        if self.is_delete() {
+            metadata_keys.push("user_id");
+        }
+
+        let sessions: Vec<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE ai_agent_sessions s
+            SET metadata = s.metadata - $3::text[], updated_at = NOW()
+            FROM ai_agents a
+            WHERE a.id = s.agent_id AND a.deployment_id = $1
+                AND s.metadata->>'user_id' = $2::text
+            RETURNING s.id
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .bind(&metadata_keys)
+        .fetch_all(&mut *conn)
+        .await?;
+        report.record(
+            "ai_agent_sessions",
+            ErasureAction::Scrubbed,
+            sessions.len() as u64,
+        );
+
+        // Messages are append-only; the trigger lets this transaction rewrite
+        // their content and nothing else, until the flag is cleared below.
+        set_erasing_user_data(&mut *conn, true).await?;
+
+        let mut messages: BTreeSet<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE ai_agent_session_messages
+            SET content = $2, tool_calls = NULL
+            WHERE session_id = ANY($1)
+            RETURNING id
+            "#,
+        )
+        .bind(&sessions)
+        .bind(ERASED_TEXT)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .collect();
+
+        for address in addresses {
+            let mentioned: Vec<i64> = sqlx::query_scalar(
+                r#"
+                UPDATE ai_agent_session_messages m
+                SET content = replace(m.content, $2, $3)
+                FROM ai_agent_sessions s
+                JOIN ai_agents a ON a.id = s.agent_id
+                WHERE m.session_id = s.id AND a.deployment_id = $1
+                    AND strpos(m.content, $2) > 0
+                RETURNING m.id
+                "#,
+            )
+            .bind(self.deployment_id)
+            .bind(address)
+            .bind(ERASED_TEXT)
+            .fetch_all(&mut *conn)
+            .await?;
+            messages.extend(mentioned);
+        }
+        set_erasing_user_data(&mut *conn, false).await?;
+        report.record(
+            "ai_agent_session_messages",
+            ErasureAction::Scrubbed,
+            messages.len() as u64,
+        );
+
+        Ok(())
+    }
+
+    async fn delete_user(
+        &self,
+        conn: &mut PgConnection,
+        report: &mut UserErasureReport,
+    ) -> Result<(), AppError> {
+        let organizations = sqlx::query(
+            r#"
+            UPDATE organizations SET member_count = GREATEST(member_count - 1, 0)
+            WHERE id IN (SELECT organization_id FROM organization_memberships WHERE user_id = $1)
+            "#,
+        )
+        .bind(self.user_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record("organizations", ErasureAction::Recounted, organizations);
+
+        let workspaces = sqlx::query(
+            r#"
+            UPDATE workspaces SET member_count = GREATEST(member_count - 1, 0)
+            WHERE id IN (SELECT workspace_id FROM workspace_memberships WHERE user_id = $1)
+            "#,
+        )
+        .bind(self.user_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record("workspaces", ErasureAction::Recounted, workspaces);
+
+        // Clear the user's pointers into the rows deleted below.
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET primary_email_address_id = NULL, primary_phone_number_id = NULL,
+                active_organization_membership_id = NULL,
+                active_workspace_membership_id = NULL
+            WHERE id = $1
+            "#,
+        )
+        .bind(self.user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        // Children before parents, so every row is counted where it lived
+        // rather than disappearing through a cascade.
+        let deletions: [(&str, &str); 10] = [
+            (
+                "organization_membership_roles",
+                r#"
+                DELETE FROM organization_membership_roles
+                WHERE organization_membership_id IN (
+                    SELECT id FROM organization_memberships WHERE user_id = $1
+                )
+                "#,
+            ),
+            (
+                "organization_memberships",
+                "DELETE FROM organization_memberships WHERE user_id = $1",
+            ),
+            (
+                "workspace_membership_roles",
+                r#"
+                DELETE FROM workspace_membership_roles
+                WHERE workspace_membership_id IN (
+                    SELECT id FROM workspace_memberships WHERE user_id = $1
+                )
+                "#,
+            ),
+            (
+                "workspace_memberships",
+                "DELETE FROM workspace_memberships WHERE user_id = $1",
+            ),
+            (
+                "social_connections",
+                "DELETE FROM social_connections WHERE user_id = $1",
+            ),
+            ("signins", "DELETE FROM signins WHERE user_id = $1"),
+            (
+                "user_email_addresses",
+                "DELETE FROM user_email_addresses WHERE user_id = $1",
+            ),
+            (
+                "user_phone_numbers",
+                "DELETE FROM user_phone_numbers WHERE user_id = $1",
+            ),
+            (
+                "user_web3_wallets",
+                "DELETE FROM user_web3_wallets WHERE user_id = $1",
+            ),
+            ("users", "DELETE FROM users WHERE id = $1"),
+        ];
+
+        for (table, statement) in deletions {
+            let rows = sqlx::query(statement)
+                .bind(self.user_id)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected();
+            report.record(table, ErasureAction::Deleted, rows);
+        }
+
+        Ok(())
+    }
+
+    /// Memberships and sign-ins stay so counts and history add up; social
+    /// connections go, since their OAuth tokens are of no use to anyone after.
+    async fn anonymize_user(
+        &self,
+        conn: &mut PgConnection,
+        report: &mut UserErasureReport,
+    ) -> Result<(), AppError> {
+        let users = sqlx::query(
+            r#"
+            UPDATE users
+            SET first_name = $2, last_name = $3,
+                username = CASE WHEN username IS NULL THEN NULL ELSE 'erased_' || id END,
+                password = NULL, backup_codes = '{}',
+                public_metadata = '{}'::jsonb, private_metadata = '{}'::jsonb,
+                disabled = true, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(self.user_id)
+        .bind(ERASED_FIRST_NAME)
+        .bind(ERASED_LAST_NAME)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record("users", ErasureAction::Anonymized, users);
+
+        let emails = sqlx::query(
+            r#"
+            UPDATE user_email_addresses
+            SET email_address = 'erased-' || id || '@erased.invalid',
+                normalized_email_address = 'erased-' || id || '@erased.invalid',
+                updated_at = NOW()
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(self.user_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record("user_email_addresses", ErasureAction::Anonymized, emails);
+
+        let phones = sqlx::query(
+            r#"
+            UPDATE user_phone_numbers
+            SET phone_number = 'erased-' || id, updated_at = NOW()
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(self.user_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record("user_phone_numbers", ErasureAction::Anonymized, phones);
+
+        // A wallet address can't be anonymized and still be an address.
+        let wallets = sqlx::query("DELETE FROM user_web3_wallets WHERE user_id = $1")
+            .bind(self.user_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        report.record("user_web3_wallets", ErasureAction::Deleted, wallets);
+
+        let signins = sqlx::query(
+            r#"
+            UPDATE signins
+            SET ip_address = NULL, user_agent = NULL, browser = NULL, device = NULL,
+                updated_at = NOW()
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(self.user_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.record("signins", ErasureAction::Anonymized, signins);
+
+        let social_connections = sqlx::query("DELETE FROM social_connections WHERE user_id = $1")
+            .bind(self.user_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        report.record(
+            "social_connections",
+            ErasureAction::Deleted,
+            social_connections,
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use super::*;
+
+    /// Needs a Postgres in `TEST_DATABASE_URL`; run it with `--ignored`.
+    /// Everything is created in a scratch schema inside a transaction that is
+    /// rolled back.
+    #[tokio::test]
+    #[ignore = "needs a Postgres in TEST_DATABASE_URL"]
+    async fn transcripts_are_scrubbed_despite_being_append_only() {
+        let database_url =
+            std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+        let mut conn = PgConnection::connect(&database_url).await.unwrap();
+        let mut tx = conn.begin().await.unwrap();
+
+        sqlx::raw_sql(
+            r#"
+            CREATE SCHEMA user_erasure_test;
+            SET LOCAL search_path TO user_erasure_test;
+            CREATE TABLE ai_agents (id BIGINT PRIMARY KEY, deployment_id BIGINT NOT NULL);
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        sqlx::raw_sql(include_str!(
+            "../../migrations/20261016000200_ai_agent_sessions.sql"
+        ))
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        sqlx::raw_sql(include_str!(
+            "../../migrations/20261016005100_ai_agent_session_message_erasure.sql"
+        ))
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        sqlx::raw_sql(
+            r#"
+            INSERT INTO ai_agents (id, deployment_id) VALUES (1, 10);
+            INSERT INTO ai_agent_sessions (id, agent_id, metadata) VALUES
+                (2, 1, '{"user_id": "5", "email": "jane@example.com"}'),
+                (3, 1, '{"user_id": "6"}');
+            INSERT INTO ai_agent_session_messages
+                (id, session_id, sequence, role, content, tool_calls) VALUES
+                (20, 2, 1, 'user', 'Hi, this is Jane', '[{"name": "lookup"}]'),
+                (30, 3, 1, 'user', 'Please write to jane@example.com', NULL),
+                (31, 3, 2, 'assistant', 'Done', NULL);
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+
+        let command = EraseUserCommand::new(10, 5, UserErasureMode::Delete);
+        let mut report = UserErasureReport {
+            user_id: 5,
+            deployment_id: 10,
+            mode: UserErasureMode::Delete,
+            erased_at: Utc::now(),
+            tables: Vec::new(),
+        };
+        command
+            .scrub_transcripts(&mut tx, &["jane@example.com".to_string()], &mut report)
+            .await
+            .unwrap();
+
+        let contents: Vec<(String, Option<serde_json::Value>)> =
+            sqlx::query_as("SELECT content, tool_calls FROM ai_agent_session_messages ORDER BY id")
+                .fetch_all(&mut *tx)
+                .await
+                .unwrap();
+        assert_eq!(
+            contents,
+            vec![
+                ("[erased]".to_string(), None),
+                ("Please write to [erased]".to_string(), None),
+                ("Done".to_string(), None),
+            ]
+        );
+        assert_eq!(report.tables[1].rows, 2);
+
+        // Outside of erasure the messages are as append-only as before.
+        let rewrite =
+            sqlx::query("UPDATE ai_agent_session_messages SET content = 'x' WHERE id = 31")
+                .execute(&mut *tx)
+                .await;
+        assert!(rewrite.is_err());
+
+        tx.rollback().await.unwrap();
+    }
+}
@@ -1,12 +1,15 @@
 use chrono::Utc;
 
 use crate::{
-    error::AppError, state::AppState,
-        dto::json::{AddEmailRequest, AddPhoneRequest, UpdateEmailRequest, UpdatePhoneRequest},
-        models::{UserEmailAddress, UserPhoneNumber, VerificationStrategy},
+    dto::json::{AddEmailRequest, AddPhoneRequest, UpdateEmailRequest, UpdatePhoneRequest},
+    error::AppError,
+    models::{UserEmailAddress, UserPhoneNumber, VerificationStrategy},
+    queries::CheckPhoneNumberAgainstRestrictionsQuery,
+    state::AppState,
+    utils::{email_normalization::normalize_email, validation::validation_failed},
 };
 
-use super::Command;
+use super::{Command, email_address_taken, strips_subaddresses};
 
 pub struct AddUserEmailCommand {
     deployment_id: i64,
@@ -27,12 +30,28 @@ impl AddUserEmailCommand {
 impl Command for AddUserEmailCommand {
     type Output = UserEmailAddress;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let now = Utc::now();
         let email_id = app_state.sf.next_id()? as i64;
         let verified = self.request.verified.unwrap_or(false);
         let is_primary = self.request.is_primary.unwrap_or(false);
 
+        let normalized_email = normalize_email(
+            &self.request.email,
+            strips_subaddresses(&app_state.db_pool, self.deployment_id).await?,
+        );
+        if let Some(error) = email_address_taken(
+            &app_state.db_pool,
+            self.deployment_id,
+            "email",
+            &normalized_email,
+            None,
+        )
+        .await?
+        {
+            return Err(validation_failed(vec![error]));
+        }
+
         if is_primary {
             sqlx::query!(
                 "UPDATE user_email_addresses SET is_primary = false WHERE user_id = $1",
@@ -42,25 +61,24 @@ impl Command for AddUserEmailCommand {
             .await?;
         }
 
-        sqlx::query!(
+        sqlx::query(
             r#"
             INSERT INTO user_email_addresses (
                 id, created_at, updated_at, deployment_id, user_id,
-                email_address, is_primary, verified, verified_at, verification_strategy
+                email_address, normalized_email_address, is_primary, verified, verified_at,
+                verification_strategy
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            VALUES ($1, $2, $2, $3, $4, $5, $6, $7, $8, $2, 'otp')
             "#,
-            email_id,
-            now,
-            now,
-            self.deployment_id,
-            self.user_id,
-            self.request.email,
-            is_primary,
-            verified,
-            if verified { now } else { now },
-            "otp"
         )
+        .bind(email_id)
+        .bind(now)
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .bind(&self.request.email)
+        .bind(&normalized_email)
+        .bind(is_primary)
+        .bind(verified)
         .execute(&app_state.db_pool)
         .await?;
 
@@ -115,7 +133,29 @@ impl UpdateUserEmailCommand {
 impl Command for UpdateUserEmailCommand {
     type Output = UserEmailAddress;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let normalized_email = match &self.request.email {
+            Some(email) => {
+                let normalized_email = normalize_email(
+                    email,
+                    strips_subaddresses(&app_state.db_pool, self.deployment_id).await?,
+                );
+                if let Some(error) = email_address_taken(
+                    &app_state.db_pool,
+                    self.deployment_id,
+                    "email",
+                    &normalized_email,
+                    Some(self.email_id),
+                )
+                .await?
+                {
+                    return Err(validation_failed(vec![error]));
+                }
+                Some(normalized_email)
+            }
+            None => None,
+        };
+
         if let Some(is_primary) = self.request.is_primary {
             if is_primary {
                 sqlx::query!(
@@ -254,6 +294,17 @@ impl Command for UpdateUserEmailCommand {
             }
         }
 
+        if let Some(normalized_email) = normalized_email {
+            sqlx::query(
+                "UPDATE user_email_addresses SET normalized_email_address = $1 WHERE id = $2 AND user_id = $3",
+            )
+            .bind(normalized_email)
+            .bind(self.email_id)
+            .bind(self.user_id)
+            .execute(&app_state.db_pool)
+            .await?;
+        }
+
         let row = sqlx::query!(
             r#"
             SELECT id, created_at, updated_at, deployment_id, user_id,
@@ -298,7 +349,7 @@ impl DeleteUserEmailCommand {
 impl Command for DeleteUserEmailCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         sqlx::query!(
             "DELETE FROM user_email_addresses WHERE id = $1 AND user_id = $2",
             self.email_id,
@@ -331,7 +382,14 @@ impl AddUserPhoneCommand {
 impl Command for AddUserPhoneCommand {
     type Output = UserPhoneNumber;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        CheckPhoneNumberAgainstRestrictionsQuery::new(
+            self.deployment_id,
+            &self.request.phone_number,
+        )
+        .ensure_allowed(app_state)
+        .await?;
+
         let now = Utc::now();
         let phone_id = app_state.sf.next_id()? as i64;
         let verified = self.request.verified.unwrap_or(false);
@@ -399,7 +457,7 @@ impl UpdateUserPhoneCommand {
 impl Command for UpdateUserPhoneCommand {
     type Output = UserPhoneNumber;
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         // Handle primary phone logic first
         if let Some(is_primary) = self.request.is_primary {
             if is_primary {
@@ -580,7 +638,7 @@ impl DeleteUserPhoneCommand {
 impl Command for DeleteUserPhoneCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         sqlx::query!(
             "DELETE FROM user_phone_numbers WHERE id = $1 AND user_id = $2",
             self.phone_id,
@@ -611,7 +669,7 @@ impl DeleteUserSocialConnectionCommand {
 impl Command for DeleteUserSocialConnectionCommand {
     type Output = ();
 
-    async fn execute(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
         sqlx::query!(
             "DELETE FROM social_connections WHERE id = $1 AND user_id = $2",
             self.connection_id,
@@ -0,0 +1,297 @@
+use chrono::Utc;
+use sqlx::{PgExecutor, Row};
+
+use crate::{
+    dto::json::{AddWeb3WalletRequest, VerifyWeb3SignatureRequest, Web3ChallengeRequest},
+    error::AppError,
+    models::{UserWeb3Wallet, VerifiedWeb3Wallet, Web3SignInChallenge},
+    state::AppState,
+    utils::{
+        validation::{ValidationError, validation_failed},
+        wallet_address::normalize_wallet_address,
+    },
+};
+
+use super::Command;
+
+/// Ethereum mainnet, for requests that don't name a chain.
+pub const DEFAULT_WEB3_CHAIN_ID: i64 = 1;
+
+/// Fails unless the deployment has web3 wallets enabled, and otherwise
+/// returns its frontend host, which sign-in messages are scoped to.
+async fn web3_wallet_domain<'e>(
+    executor: impl PgExecutor<'e>,
+    deployment_id: i64,
+) -> Result<String, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            d.frontend_host,
+            COALESCE((a.auth_factors_enabled->>'web3_wallet')::BOOLEAN, FALSE) AS enabled
+        FROM deployments d
+        JOIN deployment_auth_settings a ON a.deployment_id = d.id
+        WHERE d.id = $1
+        "#,
+    )
+    .bind(deployment_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+    if !row.get::<bool, _>("enabled") {
+        return Err(AppError::BadRequest(
+            "Web3 wallets are disabled for this deployment".to_string(),
+        ));
+    }
+
+    Ok(row.get("frontend_host"))
+}
+
+fn normalized_address(field: &str, address: &str) -> Result<String, AppError> {
+    normalize_wallet_address(address).ok_or_else(|| {
+        validation_failed(vec![ValidationError::rule(
+            field,
+            "invalid_wallet_address",
+            "This is not a valid Ethereum address",
+        )])
+    })
+}
+
+/// Whether another user already has the wallet. Users whose identifiers
+/// were released on deletion don't count.
+async fn wallet_address_taken<'e>(
+    executor: impl PgExecutor<'e>,
+    deployment_id: i64,
+    field: &str,
+    address: &str,
+) -> Result<Option<ValidationError>, AppError> {
+    let taken: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM user_web3_wallets w
+            JOIN users u ON u.id = w.user_id
+            WHERE w.deployment_id = $1
+                AND w.address = $2
+                AND NOT u.identifiers_released
+        )
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(address)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(taken.then(|| ValidationError::rule(field, "taken", "An account already uses this wallet")))
+}
+
+pub struct AddUserWeb3WalletCommand {
+    deployment_id: i64,
+    user_id: i64,
+    request: AddWeb3WalletRequest,
+}
+
+impl AddUserWeb3WalletCommand {
+    pub fn new(deployment_id: i64, user_id: i64, request: AddWeb3WalletRequest) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            request,
+        }
+    }
+}
+
+impl Command for AddUserWeb3WalletCommand {
+    type Output = UserWeb3Wallet;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        web3_wallet_domain(&app_state.db_pool, self.deployment_id).await?;
+
+        let now = Utc::now();
+        let wallet_id = app_state.sf.next_id()? as i64;
+        let address = normalized_address("address", &self.request.address)?;
+        let chain_id = self.request.chain_id.unwrap_or(DEFAULT_WEB3_CHAIN_ID);
+        let verified = self.request.verified.unwrap_or(false);
+        let is_primary = self.request.is_primary.unwrap_or(false);
+        let verified_at = verified.then_some(now);
+
+        if let Some(error) =
+            wallet_address_taken(&app_state.db_pool, self.deployment_id, "address", &address)
+                .await?
+        {
+            return Err(validation_failed(vec![error]));
+        }
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        if is_primary {
+            sqlx::query("UPDATE user_web3_wallets SET is_primary = false WHERE user_id = $1")
+                .bind(self.user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_web3_wallets (
+                id, created_at, updated_at, deployment_id, user_id,
+                address, chain_id, verified, verified_at, is_primary
+            )
+            VALUES ($1, $2, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(wallet_id)
+        .bind(now)
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .bind(&address)
+        .bind(chain_id)
+        .bind(verified)
+        .bind(verified_at)
+        .bind(is_primary)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UserWeb3Wallet {
+            id: wallet_id,
+            created_at: now,
+            updated_at: now,
+            deployment_id: self.deployment_id,
+            user_id: self.user_id,
+            address,
+            chain_id,
+            verified,
+            verified_at,
+            is_primary,
+        })
+    }
+}
+
+/// Removes a wallet from a user. This works with web3 wallets disabled, so
+/// a deployment can still clean up after turning them off.
+pub struct DeleteUserWeb3WalletCommand {
+    user_id: i64,
+    wallet_id: i64,
+}
+
+impl DeleteUserWeb3WalletCommand {
+    pub fn new(user_id: i64, wallet_id: i64) -> Self {
+        Self { user_id, wallet_id }
+    }
+}
+
+impl Command for DeleteUserWeb3WalletCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        sqlx::query("DELETE FROM user_web3_wallets WHERE id = $1 AND user_id = $2")
+            .bind(self.wallet_id)
+            .bind(self.user_id)
+            .execute(&app_state.db_pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Issues a Sign-In with Ethereum message for a wallet to sign, scoped to
+/// the deployment's frontend host.
+pub struct IssueWeb3ChallengeCommand {
+    deployment_id: i64,
+    request: Web3ChallengeRequest,
+}
+
+impl IssueWeb3ChallengeCommand {
+    pub fn new(deployment_id: i64, request: Web3ChallengeRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+}
+
+impl Command for IssueWeb3ChallengeCommand {
+    type Output = Web3SignInChallenge;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let domain = web3_wallet_domain(&app_state.db_pool, self.deployment_id).await?;
+        let address = normalized_address("address", &self.request.address)?;
+
+        app_state
+            .web3_wallet_service
+            .issue_challenge(
+                self.deployment_id,
+                &domain,
+                &address,
+                self.request.chain_id.unwrap_or(DEFAULT_WEB3_CHAIN_ID),
+            )
+            .await
+    }
+}
+
+/// Checks a signed challenge. When the wallet belongs to a user it's marked
+/// verified on the chain it signed on, and the user is returned so the
+/// caller can sign them in.
+pub struct VerifyWeb3SignatureCommand {
+    deployment_id: i64,
+    request: VerifyWeb3SignatureRequest,
+}
+
+impl VerifyWeb3SignatureCommand {
+    pub fn new(deployment_id: i64, request: VerifyWeb3SignatureRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+}
+
+impl Command for VerifyWeb3SignatureCommand {
+    type Output = VerifiedWeb3Wallet;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let domain = web3_wallet_domain(&app_state.db_pool, self.deployment_id).await?;
+        let address = normalized_address("address", &self.request.address)?;
+        let chain_id = self.request.chain_id.unwrap_or(DEFAULT_WEB3_CHAIN_ID);
+
+        app_state
+            .web3_wallet_service
+            .verify(
+                self.deployment_id,
+                &domain,
+                &address,
+                chain_id,
+                &self.request.nonce,
+                &self.request.signature,
+            )
+            .await?;
+
+        let user_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE user_web3_wallets w
+            SET verified = true,
+                verified_at = COALESCE(w.verified_at, NOW()),
+                chain_id = $3,
+                updated_at = NOW()
+            FROM users u
+            WHERE u.id = w.user_id
+                AND w.deployment_id = $1
+                AND w.address = $2
+                AND u.deleted_at IS NULL
+            RETURNING w.user_id
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&address)
+        .bind(chain_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        Ok(VerifiedWeb3Wallet {
+            address,
+            chain_id,
+            user_id,
+        })
+    }
+}
@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    error::AppError,
+    queries::{GetDeploymentOtpSettingsQuery, Query},
+    state::AppState,
+};
+
+use super::{Command, SendEmailCommand};
+
+/// Emails a one-time code to an address, following the deployment's OTP
+/// settings, and returns when the code expires. SMS senders issue codes
+/// through `app_state.otp_service` the same way and render the SMS template
+/// with `IssuedOtp::template_variables`.
+pub struct SendEmailVerificationCodeCommand {
+    deployment_id: i64,
+    email_address: String,
+}
+
+impl SendEmailVerificationCodeCommand {
+    pub fn new(deployment_id: i64, email_address: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            email_address: email_address.into(),
+        }
+    }
+}
+
+impl Command for SendEmailVerificationCodeCommand {
+    type Output = DateTime<Utc>;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let settings = GetDeploymentOtpSettingsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+        let issued = app_state
+            .otp_service
+            .issue(self.deployment_id, &self.email_address, &settings)
+            .await?;
+
+        let mut variables = issued.template_variables();
+        variables.insert("app_name".to_string(), "Your App".to_string());
+        variables.insert(
+            "app_logo".to_string(),
+            "https://via.placeholder.com/150".to_string(),
+        );
+
+        SendEmailCommand::new(
+            self.deployment_id,
+            "verification_code_template".to_string(),
+            self.email_address,
+            variables,
+        )
+        .execute(app_state)
+        .await?;
+
+        Ok(issued.expires_at)
+    }
+}
+
+/// Checks a one-time code sent to an email address or phone number. Wrong
+/// codes fail with `invalid_otp` and the attempts left.
+pub struct VerifyOtpCommand {
+    deployment_id: i64,
+    identifier: String,
+    code: String,
+}
+
+impl VerifyOtpCommand {
+    pub fn new(deployment_id: i64, identifier: impl Into<String>, code: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            identifier: identifier.into(),
+            code: code.into(),
+        }
+    }
+}
+
+impl Command for VerifyOtpCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let settings = GetDeploymentOtpSettingsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+
+        app_state
+            .otp_service
+            .verify(self.deployment_id, &self.identifier, &self.code, &settings)
+            .await
+    }
+}
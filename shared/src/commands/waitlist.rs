@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::Row;
+
+use crate::{
+    dto::json::JoinWaitlistRequest,
+    error::AppError,
+    models::{
+        BulkWaitlistApproval, DeploymentInvitation, DeploymentRestrictionsSignUpMode,
+        DeploymentWaitlistUser, WaitlistApproval, WaitlistApprovalFailure, WaitlistEntryStatus,
+    },
+    queries::{WAITLIST_ENTRY_COLUMNS, waitlist_entry_from_row},
+    services::{InvitationClaims, invitation_url},
+    state::AppState,
+};
+
+use super::{Command, SendEmailCommand};
+
+pub(super) const DEFAULT_INVITATION_EXPIRY_DAYS: i64 = 7;
+
+/// The most entries one bulk approval takes.
+pub const MAX_BULK_WAITLIST_APPROVALS: usize = 500;
+
+pub(super) fn template_variables(
+    action_url: String,
+    expires_in_days: i64,
+) -> HashMap<String, String> {
+    let mut variables = HashMap::new();
+    variables.insert("app_name".to_string(), "Your App".to_string());
+    variables.insert(
+        "app_logo".to_string(),
+        "https://via.placeholder.com/150".to_string(),
+    );
+    variables.insert("action_url".to_string(), action_url);
+    variables.insert(
+        "invitation.expires_in_days".to_string(),
+        expires_in_days.to_string(),
+    );
+    variables
+}
+
+pub(super) async fn frontend_host(
+    app_state: &AppState,
+    deployment_id: i64,
+) -> Result<String, AppError> {
+    sqlx::query_scalar("SELECT frontend_host FROM deployments WHERE id = $1 AND deleted_at IS NULL")
+        .bind(deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))
+}
+
+/// Adds someone to a deployment's waitlist and sends them the
+/// waitlist_signup_template email. Joining again with the same address
+/// returns the existing entry without sending another email. If the email
+/// can't be sent the entry is dropped again, so joining can be retried.
+pub struct JoinWaitlistCommand {
+    deployment_id: i64,
+    request: JoinWaitlistRequest,
+}
+
+impl JoinWaitlistCommand {
+    pub fn new(deployment_id: i64, request: JoinWaitlistRequest) -> Self {
+        Self {
+            deployment_id,
+            request,
+        }
+    }
+}
+
+impl Command for JoinWaitlistCommand {
+    type Output = DeploymentWaitlistUser;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let sign_up_mode: Option<String> = sqlx::query_scalar(
+            "SELECT sign_up_mode FROM deployment_restrictions WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        if !matches!(
+            sign_up_mode.and_then(|mode| DeploymentRestrictionsSignUpMode::from_str(&mode).ok()),
+            Some(DeploymentRestrictionsSignUpMode::Waitlist)
+        ) {
+            return Err(AppError::BadRequest(
+                "This deployment doesn't have a waitlist".to_string(),
+            ));
+        }
+
+        let email_address = self.request.email_address.trim().to_lowercase();
+        let frontend_host = frontend_host(app_state, self.deployment_id).await?;
+        let id = app_state.sf.next_id()? as i64;
+        let now = Utc::now();
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO deployment_waitlist_users (
+                id, created_at, updated_at, deployment_id,
+                email_address, first_name, last_name, status, metadata
+            )
+            VALUES ($1, $2, $2, $3, $4, $5, $6, 'pending', $7)
+            ON CONFLICT (deployment_id, lower(email_address)) DO NOTHING
+            RETURNING {}
+            "#,
+            WAITLIST_ENTRY_COLUMNS
+        ))
+        .bind(id)
+        .bind(now)
+        .bind(self.deployment_id)
+        .bind(&email_address)
+        .bind(self.request.first_name.unwrap_or_default())
+        .bind(self.request.last_name.unwrap_or_default())
+        .bind(self.request.metadata.unwrap_or_else(|| json!({})))
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        let Some(row) = row else {
+            let existing = sqlx::query(&format!(
+                "SELECT {} FROM deployment_waitlist_users WHERE deployment_id = $1 AND lower(email_address) = $2",
+                WAITLIST_ENTRY_COLUMNS
+            ))
+            .bind(self.deployment_id)
+            .bind(&email_address)
+            .fetch_one(&app_state.db_pool)
+            .await?;
+            return Ok(waitlist_entry_from_row(&existing));
+        };
+
+        if let Err(e) = SendEmailCommand::new(
+            self.deployment_id,
+            "waitlist_signup_template".to_string(),
+            email_address,
+            template_variables(
+                format!("https://{}", frontend_host),
+                DEFAULT_INVITATION_EXPIRY_DAYS,
+            ),
+        )
+        .execute(app_state)
+        .await
+        {
+            sqlx::query(
+                "DELETE FROM deployment_waitlist_users WHERE id = $1 AND status = 'pending'",
+            )
+            .bind(id)
+            .execute(&app_state.db_pool)
+            .await?;
+            return Err(e);
+        }
+
+        Ok(waitlist_entry_from_row(&row))
+    }
+}
+
+/// Approves a pending entry: creates an invitation, marks the entry approved
+/// and emails a signed invitation link through waitlist_invite_template. The
+/// link lets the invitee sign up even though the deployment's sign-up mode
+/// turns everyone else away. The email goes out after the approval is
+/// committed; if it can't be sent the invitation is revoked and the entry
+/// goes back to pending, so it can be approved again.
+pub struct ApproveWaitlistEntryCommand {
+    deployment_id: i64,
+    entry_id: i64,
+    expiry_days: i64,
+}
+
+impl ApproveWaitlistEntryCommand {
+    pub fn new(deployment_id: i64, entry_id: i64) -> Self {
+        Self {
+            deployment_id,
+            entry_id,
+            expiry_days: DEFAULT_INVITATION_EXPIRY_DAYS,
+        }
+    }
+
+    pub fn with_expiry_days(mut self, expiry_days: Option<i64>) -> Self {
+        self.expiry_days = expiry_days.unwrap_or(DEFAULT_INVITATION_EXPIRY_DAYS);
+        self
+    }
+
+    async fn approve(
+        &self,
+        app_state: &AppState,
+        frontend_host: &str,
+    ) -> Result<WaitlistApproval, AppError> {
+        let now = Utc::now();
+        let expiry = now + Duration::days(self.expiry_days);
+        let invitation_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let entry = sqlx::query(&format!(
+            "SELECT {} FROM deployment_waitlist_users WHERE id = $1 AND deployment_id = $2 FOR UPDATE",
+            WAITLIST_ENTRY_COLUMNS
+        ))
+        .bind(self.entry_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| waitlist_entry_from_row(&row))
+        .ok_or_else(|| AppError::NotFound("Waitlist entry not found".to_string()))?;
+
+        if entry.status != WaitlistEntryStatus::Pending {
+            return Err(AppError::BadRequest(format!(
+                "Waitlist entry is already {}",
+                entry.status.as_str()
+            )));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO deployment_invitations (
+                id, created_at, updated_at, deployment_id,
+                first_name, last_name, email_address, expiry
+            )
+            VALUES ($1, $2, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(invitation_id)
+        .bind(now)
+        .bind(self.deployment_id)
+        .bind(&entry.first_name)
+        .bind(&entry.last_name)
+        .bind(&entry.email_address)
+        .bind(expiry)
+        .execute(&mut *tx)
+        .await?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE deployment_waitlist_users
+            SET status = 'approved', invitation_id = $1, reviewed_at = $2, updated_at = $2
+            WHERE id = $3
+            RETURNING {}
+            "#,
+            WAITLIST_ENTRY_COLUMNS
+        ))
+        .bind(invitation_id)
+        .bind(now)
+        .bind(self.entry_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let token = app_state.invitation_token_signer.sign(&InvitationClaims {
+            invitation_id,
+            deployment_id: self.deployment_id,
+            email_address: entry.email_address.clone(),
+            expires_at: expiry,
+            organization_id: None,
+        })?;
+
+        tx.commit().await?;
+
+        if let Err(e) = SendEmailCommand::new(
+            self.deployment_id,
+            "waitlist_invite_template".to_string(),
+            entry.email_address.clone(),
+            template_variables(invitation_url(frontend_host, &token), self.expiry_days),
+        )
+        .execute(app_state)
+        .await
+        {
+            self.undo_approval(app_state, invitation_id).await?;
+            return Err(e);
+        }
+
+        Ok(WaitlistApproval {
+            entry: waitlist_entry_from_row(&row),
+            invitation: DeploymentInvitation {
+                id: invitation_id,
+                created_at: now,
+                updated_at: now,
+                deployment_id: self.deployment_id,
+                first_name: entry.first_name,
+                last_name: entry.last_name,
+                email_address: entry.email_address,
+                expiry,
+                accepted_at: None,
+                revoked_at: None,
+            },
+        })
+    }
+
+    /// Puts the entry back to pending and revokes the invitation made for it.
+    async fn undo_approval(
+        &self,
+        app_state: &AppState,
+        invitation_id: i64,
+    ) -> Result<(), AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        sqlx::query(
+            "UPDATE deployment_invitations SET revoked_at = NOW(), updated_at = NOW() WHERE id = $1",
+        )
+        .bind(invitation_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE deployment_waitlist_users
+            SET status = 'pending', invitation_id = NULL, reviewed_at = NULL, updated_at = NOW()
+            WHERE id = $1 AND invitation_id = $2
+            "#,
+        )
+        .bind(self.entry_id)
+        .bind(invitation_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+impl Command for ApproveWaitlistEntryCommand {
+    type Output = WaitlistApproval;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let frontend_host = frontend_host(app_state, self.deployment_id).await?;
+        self.approve(app_state, &frontend_host).await
+    }
+}
+
+/// Approves entries one by one so a bad entry or a failed email doesn't hold
+/// up the rest of the batch. Each failure is reported with its reason.
+pub struct BulkApproveWaitlistEntriesCommand {
+    deployment_id: i64,
+    entry_ids: Vec<i64>,
+    expiry_days: Option<i64>,
+}
+
+impl BulkApproveWaitlistEntriesCommand {
+    pub fn new(deployment_id: i64, entry_ids: Vec<i64>) -> Self {
+        Self {
+            deployment_id,
+            entry_ids,
+            expiry_days: None,
+        }
+    }
+
+    pub fn with_expiry_days(mut self, expiry_days: Option<i64>) -> Self {
+        self.expiry_days = expiry_days;
+        self
+    }
+}
+
+impl Command for BulkApproveWaitlistEntriesCommand {
+    type Output = BulkWaitlistApproval;
+
+    async fn run(mut self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self.entry_ids.len() > MAX_BULK_WAITLIST_APPROVALS {
+            return Err(AppError::BadRequest(format!(
+                "At most {} entries can be approved at once",
+                MAX_BULK_WAITLIST_APPROVALS
+            )));
+        }
+
+        let frontend_host = frontend_host(app_state, self.deployment_id).await?;
+
+        self.entry_ids.sort_unstable();
+        self.entry_ids.dedup();
+
+        let mut result = BulkWaitlistApproval::default();
+
+        for entry_id in self.entry_ids {
+            let approval = ApproveWaitlistEntryCommand::new(self.deployment_id, entry_id)
+                .with_expiry_days(self.expiry_days)
+                .approve(app_state, &frontend_host)
+                .await;
+
+            match approval {
+                Ok(approval) => result.approved.push(approval.entry),
+                Err(e) => {
+                    tracing::warn!(
+                        deployment_id = self.deployment_id,
+                        entry_id,
+                        "Failed to approve waitlist entry: {}",
+                        e
+                    );
+                    result.failed.push(WaitlistApprovalFailure {
+                        id: entry_id,
+                        reason: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+pub struct RejectWaitlistEntryCommand {
+    deployment_id: i64,
+    entry_id: i64,
+}
+
+impl RejectWaitlistEntryCommand {
+    pub fn new(deployment_id: i64, entry_id: i64) -> Self {
+        Self {
+            deployment_id,
+            entry_id,
+        }
+    }
+}
+
+impl Command for RejectWaitlistEntryCommand {
+    type Output = DeploymentWaitlistUser;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE deployment_waitlist_users
+            SET status = 'rejected', reviewed_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND deployment_id = $2 AND status = 'pending'
+            RETURNING {}
+            "#,
+            WAITLIST_ENTRY_COLUMNS
+        ))
+        .bind(self.entry_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        if let Some(row) = row {
+            return Ok(waitlist_entry_from_row(&row));
+        }
+
+        let status: Option<String> = sqlx::query(
+            "SELECT status FROM deployment_waitlist_users WHERE id = $1 AND deployment_id = $2",
+        )
+        .bind(self.entry_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .map(|row| row.get("status"));
+
+        match status {
+            Some(status) => Err(AppError::BadRequest(format!(
+                "Waitlist entry is already {}",
+                status
+            ))),
+            None => Err(AppError::NotFound("Waitlist entry not found".to_string())),
+        }
+    }
+}
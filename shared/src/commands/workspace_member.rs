@@ -0,0 +1,564 @@
+use crate::{
+    commands::{Command, RecordAuditLogCommand, RecordOrganizationAuditEventCommand},
+    error::{AppError, ErrorCode},
+    models::{OrganizationAuditEventType, WorkspaceMemberDetails},
+    queries::{fetch_workspace_member, invalidate_user_authorization_context},
+    state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, Row};
+
+use super::organization_member::{dedup_role_ids, role_ids_metadata};
+
+struct WorkspaceMembershipPolicy {
+    project_id: i64,
+    organization_id: i64,
+    max_allowed_workspace_members: Option<i64>,
+    creator_role_id: Option<i64>,
+    member_role_id: Option<i64>,
+}
+
+/// Locks the workspace row for the rest of the transaction; see
+/// `lock_organization` for why.
+async fn lock_workspace(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    workspace_id: i64,
+) -> Result<WorkspaceMembershipPolicy, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            w.organization_id,
+            d.project_id,
+            s.max_allowed_workspace_members,
+            s.default_workspace_creator_role_id,
+            s.default_workspace_member_role_id
+        FROM workspaces w
+        JOIN deployments d ON d.id = w.deployment_id
+        LEFT JOIN deployment_b2b_settings s
+            ON s.deployment_id = w.deployment_id AND s.deleted_at IS NULL
+        WHERE w.id = $1 AND w.deployment_id = $2 AND w.deleted_at IS NULL AND d.deleted_at IS NULL
+        FOR UPDATE OF w
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Workspace not found".to_string()))?;
+
+    let role_id = |column: &str| row.get::<Option<i64>, _>(column).filter(|id| *id > 0);
+
+    Ok(WorkspaceMembershipPolicy {
+        project_id: row.get("project_id"),
+        organization_id: row.get("organization_id"),
+        max_allowed_workspace_members: row
+            .get::<Option<i64>, _>("max_allowed_workspace_members")
+            .filter(|max| *max > 0),
+        creator_role_id: role_id("default_workspace_creator_role_id"),
+        member_role_id: role_id("default_workspace_member_role_id"),
+    })
+}
+
+async fn ensure_roles_belong(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    workspace_id: i64,
+    role_ids: &[i64],
+) -> Result<(), AppError> {
+    let found: Vec<i64> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM workspace_roles
+        WHERE id = ANY($1)
+            AND (
+                workspace_id = $3
+                OR (deployment_id = $2 AND workspace_id IS NULL AND organization_id IS NULL)
+            )
+        "#,
+    )
+    .bind(role_ids)
+    .bind(deployment_id)
+    .bind(workspace_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    if let Some(missing) = role_ids.iter().find(|id| !found.contains(id)) {
+        return Err(AppError::BadRequest(format!(
+            "Role {} doesn't belong to this deployment",
+            missing
+        )));
+    }
+
+    Ok(())
+}
+
+async fn ensure_other_admin(
+    conn: &mut PgConnection,
+    workspace_id: i64,
+    membership_id: i64,
+    creator_role_id: i64,
+) -> Result<(), AppError> {
+    let other_admins: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM workspace_membership_roles
+        WHERE workspace_id = $1
+            AND workspace_role_id = $2
+            AND workspace_membership_id <> $3
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(creator_role_id)
+    .bind(membership_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if other_admins == 0 {
+        return Err(AppError::coded(
+            ErrorCode::LastWorkspaceAdmin,
+            "This member is the workspace's only admin. Give another member the role first.",
+        )
+        .with_details(json!({
+            "membership_id": membership_id.to_string(),
+            "role_id": creator_role_id.to_string(),
+        })));
+    }
+
+    Ok(())
+}
+
+async fn assign_roles(
+    conn: &mut PgConnection,
+    workspace_id: i64,
+    membership_id: i64,
+    role_ids: &[i64],
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO workspace_membership_roles (workspace_membership_id, workspace_role_id, workspace_id)
+        SELECT $1, role_id, $2 FROM UNNEST($3::BIGINT[]) AS role_id
+        "#,
+    )
+    .bind(membership_id)
+    .bind(workspace_id)
+    .bind(role_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Adds a member of the workspace's organization to the workspace. Without
+/// roles the member gets the deployment's default workspace member role.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddWorkspaceMemberCommand {
+    pub deployment_id: i64,
+    pub workspace_id: i64,
+    pub user_id: i64,
+    pub role_ids: Vec<i64>,
+    pub initiated_by: Option<String>,
+}
+
+impl AddWorkspaceMemberCommand {
+    pub fn new(deployment_id: i64, workspace_id: i64, user_id: i64, role_ids: Vec<i64>) -> Self {
+        Self {
+            deployment_id,
+            workspace_id,
+            user_id,
+            role_ids,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for AddWorkspaceMemberCommand {
+    type Output = WorkspaceMemberDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let membership_id = app_state.sf.next_id()? as i64;
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let audit_event_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let policy = lock_workspace(&mut tx, self.deployment_id, self.workspace_id).await?;
+
+        let organization_member: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM organization_memberships WHERE organization_id = $1 AND user_id = $2)",
+        )
+        .bind(policy.organization_id)
+        .bind(self.user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !organization_member {
+            return Err(AppError::BadRequest(
+                "User must be a member of the workspace's organization".to_string(),
+            ));
+        }
+
+        let already_member: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM workspace_memberships WHERE workspace_id = $1 AND user_id = $2)",
+        )
+        .bind(self.workspace_id)
+        .bind(self.user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if already_member {
+            return Err(AppError::BadRequest(
+                "User is already a member of this workspace".to_string(),
+            ));
+        }
+
+        if let Some(max_allowed_workspace_members) = policy.max_allowed_workspace_members {
+            let member_count: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM workspace_memberships WHERE workspace_id = $1",
+            )
+            .bind(self.workspace_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if member_count >= max_allowed_workspace_members {
+                return Err(AppError::coded(
+                    ErrorCode::WorkspaceMemberLimitReached,
+                    format!(
+                        "Workspaces in this deployment can't have more than {} members",
+                        max_allowed_workspace_members
+                    ),
+                )
+                .with_details(json!({
+                    "max_allowed_workspace_members": max_allowed_workspace_members,
+                })));
+            }
+        }
+
+        let role_ids = match dedup_role_ids(self.role_ids) {
+            role_ids if role_ids.is_empty() => policy.member_role_id.into_iter().collect(),
+            role_ids => role_ids,
+        };
+        ensure_roles_belong(&mut tx, self.deployment_id, self.workspace_id, &role_ids).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO workspace_memberships (id, workspace_id, user_id, created_at, updated_at)
+            VALUES ($1, $2, $3, NOW(), NOW())
+            "#,
+        )
+        .bind(membership_id)
+        .bind(self.workspace_id)
+        .bind(self.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        assign_roles(&mut tx, self.workspace_id, membership_id, &role_ids).await?;
+
+        sqlx::query("UPDATE workspaces SET member_count = member_count + 1 WHERE id = $1")
+            .bind(self.workspace_id)
+            .execute(&mut *tx)
+            .await?;
+
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "workspace.member_added",
+            "workspace_membership",
+            membership_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by.clone())
+        .with_metadata(json!({
+            "workspace_id": self.workspace_id.to_string(),
+            "user_id": self.user_id.to_string(),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        RecordOrganizationAuditEventCommand::new(
+            self.deployment_id,
+            policy.organization_id,
+            OrganizationAuditEventType::WorkspaceMemberAdded,
+        )
+        .with_workspace_id(self.workspace_id)
+        .with_actor(self.initiated_by)
+        .with_payload(json!({
+            "membership_id": membership_id.to_string(),
+            "user_id": self.user_id.to_string(),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_event_id, &mut tx)
+        .await?;
+
+        let member = fetch_workspace_member(
+            &mut tx,
+            self.deployment_id,
+            self.workspace_id,
+            membership_id,
+        )
+        .await?
+        .ok_or_else(|| AppError::Internal("Workspace membership disappeared".to_string()))?;
+
+        tx.commit().await?;
+
+        invalidate_user_authorization_context(app_state, self.deployment_id, member.user_id).await;
+
+        Ok(member)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateWorkspaceMemberRoleCommand {
+    pub deployment_id: i64,
+    pub workspace_id: i64,
+    pub membership_id: i64,
+    pub role_ids: Vec<i64>,
+    pub initiated_by: Option<String>,
+}
+
+impl UpdateWorkspaceMemberRoleCommand {
+    pub fn new(
+        deployment_id: i64,
+        workspace_id: i64,
+        membership_id: i64,
+        role_ids: Vec<i64>,
+    ) -> Self {
+        Self {
+            deployment_id,
+            workspace_id,
+            membership_id,
+            role_ids,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for UpdateWorkspaceMemberRoleCommand {
+    type Output = WorkspaceMemberDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let audit_event_id = app_state.sf.next_id()? as i64;
+        let role_ids = dedup_role_ids(self.role_ids);
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let policy = lock_workspace(&mut tx, self.deployment_id, self.workspace_id).await?;
+
+        let current = fetch_workspace_member(
+            &mut tx,
+            self.deployment_id,
+            self.workspace_id,
+            self.membership_id,
+        )
+        .await?
+        .ok_or_else(|| AppError::NotFound("Workspace membership not found".to_string()))?;
+
+        ensure_roles_belong(&mut tx, self.deployment_id, self.workspace_id, &role_ids).await?;
+
+        if let Some(creator_role_id) = policy.creator_role_id
+            && current.roles.iter().any(|role| role.id == creator_role_id)
+            && !role_ids.contains(&creator_role_id)
+        {
+            ensure_other_admin(
+                &mut tx,
+                self.workspace_id,
+                self.membership_id,
+                creator_role_id,
+            )
+            .await?;
+        }
+
+        sqlx::query("DELETE FROM workspace_membership_roles WHERE workspace_membership_id = $1")
+            .bind(self.membership_id)
+            .execute(&mut *tx)
+            .await?;
+
+        assign_roles(&mut tx, self.workspace_id, self.membership_id, &role_ids).await?;
+
+        sqlx::query("UPDATE workspace_memberships SET updated_at = NOW() WHERE id = $1")
+            .bind(self.membership_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let previous_role_ids: Vec<i64> = current.roles.iter().map(|role| role.id).collect();
+
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "workspace.member_role_updated",
+            "workspace_membership",
+            self.membership_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by.clone())
+        .with_metadata(json!({
+            "workspace_id": self.workspace_id.to_string(),
+            "user_id": current.user_id.to_string(),
+            "previous_role_ids": role_ids_metadata(&previous_role_ids),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        RecordOrganizationAuditEventCommand::new(
+            self.deployment_id,
+            policy.organization_id,
+            OrganizationAuditEventType::WorkspaceMemberRoleChanged,
+        )
+        .with_workspace_id(self.workspace_id)
+        .with_actor(self.initiated_by)
+        .with_payload(json!({
+            "membership_id": self.membership_id.to_string(),
+            "user_id": current.user_id.to_string(),
+            "previous_role_ids": role_ids_metadata(&previous_role_ids),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_event_id, &mut tx)
+        .await?;
+
+        let member = fetch_workspace_member(
+            &mut tx,
+            self.deployment_id,
+            self.workspace_id,
+            self.membership_id,
+        )
+        .await?
+        .ok_or_else(|| AppError::Internal("Workspace membership disappeared".to_string()))?;
+
+        tx.commit().await?;
+
+        invalidate_user_authorization_context(app_state, self.deployment_id, member.user_id).await;
+
+        Ok(member)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoveWorkspaceMemberCommand {
+    pub deployment_id: i64,
+    pub workspace_id: i64,
+    pub membership_id: i64,
+    pub initiated_by: Option<String>,
+}
+
+impl RemoveWorkspaceMemberCommand {
+    pub fn new(deployment_id: i64, workspace_id: i64, membership_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workspace_id,
+            membership_id,
+            initiated_by: None,
+        }
+    }
+
+    pub fn with_initiated_by(mut self, initiated_by: Option<String>) -> Self {
+        self.initiated_by = initiated_by;
+        self
+    }
+}
+
+impl Command for RemoveWorkspaceMemberCommand {
+    type Output = WorkspaceMemberDetails;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let audit_log_id = app_state.sf.next_id()? as i64;
+        let audit_event_id = app_state.sf.next_id()? as i64;
+
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let policy = lock_workspace(&mut tx, self.deployment_id, self.workspace_id).await?;
+
+        let member = fetch_workspace_member(
+            &mut tx,
+            self.deployment_id,
+            self.workspace_id,
+            self.membership_id,
+        )
+        .await?
+        .ok_or_else(|| AppError::NotFound("Workspace membership not found".to_string()))?;
+
+        if let Some(creator_role_id) = policy.creator_role_id
+            && member.roles.iter().any(|role| role.id == creator_role_id)
+        {
+            ensure_other_admin(
+                &mut tx,
+                self.workspace_id,
+                self.membership_id,
+                creator_role_id,
+            )
+            .await?;
+        }
+
+        sqlx::query(
+            "UPDATE users SET active_workspace_membership_id = NULL WHERE active_workspace_membership_id = $1",
+        )
+        .bind(self.membership_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM workspace_membership_roles WHERE workspace_membership_id = $1")
+            .bind(self.membership_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM workspace_memberships WHERE id = $1")
+            .bind(self.membership_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "UPDATE workspaces SET member_count = GREATEST(member_count - 1, 0) WHERE id = $1",
+        )
+        .bind(self.workspace_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let role_ids: Vec<i64> = member.roles.iter().map(|role| role.id).collect();
+
+        RecordAuditLogCommand::new(
+            policy.project_id,
+            "workspace.member_removed",
+            "workspace_membership",
+            self.membership_id,
+        )
+        .with_deployment_id(self.deployment_id)
+        .with_actor(self.initiated_by.clone())
+        .with_metadata(json!({
+            "workspace_id": self.workspace_id.to_string(),
+            "user_id": member.user_id.to_string(),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_log_id, &mut tx)
+        .await?;
+
+        RecordOrganizationAuditEventCommand::new(
+            self.deployment_id,
+            policy.organization_id,
+            OrganizationAuditEventType::WorkspaceMemberRemoved,
+        )
+        .with_workspace_id(self.workspace_id)
+        .with_actor(self.initiated_by)
+        .with_payload(json!({
+            "membership_id": self.membership_id.to_string(),
+            "user_id": member.user_id.to_string(),
+            "role_ids": role_ids_metadata(&role_ids),
+        }))
+        .execute_with(audit_event_id, &mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        invalidate_user_authorization_context(app_state, self.deployment_id, member.user_id).await;
+
+        Ok(member)
+    }
+}
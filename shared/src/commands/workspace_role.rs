@@ -0,0 +1,446 @@
+use crate::{
+    commands::{Command, normalize_permissions, normalize_role_name},
+    error::{AppError, ErrorCode},
+    models::{DeploymentWorkspaceRole, WORKSPACE_PERMISSIONS},
+    queries::{
+        WORKSPACE_ROLE_COLUMNS, invalidate_deployment_authorization_contexts,
+        workspace_role_from_row,
+    },
+    state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+
+struct RoleSettings {
+    custom_roles_enabled: bool,
+    default_creator_role_id: Option<i64>,
+    default_member_role_id: Option<i64>,
+}
+
+async fn load_settings(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<RoleSettings, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            custom_workspace_role_enabled,
+            default_workspace_creator_role_id,
+            default_workspace_member_role_id
+        FROM deployment_b2b_settings
+        WHERE deployment_id = $1 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(match row {
+        Some(row) => RoleSettings {
+            custom_roles_enabled: row.get("custom_workspace_role_enabled"),
+            default_creator_role_id: row.get("default_workspace_creator_role_id"),
+            default_member_role_id: row.get("default_workspace_member_role_id"),
+        },
+        None => RoleSettings {
+            custom_roles_enabled: false,
+            default_creator_role_id: None,
+            default_member_role_id: None,
+        },
+    })
+}
+
+async fn ensure_custom_roles_enabled(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+) -> Result<(), AppError> {
+    if !load_settings(conn, deployment_id)
+        .await?
+        .custom_roles_enabled
+    {
+        return Err(AppError::BadRequest(
+            "Custom workspace roles are disabled for this deployment".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn ensure_unique_name(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    workspace_id: Option<i64>,
+    name: &str,
+    exclude_role_id: Option<i64>,
+) -> Result<(), AppError> {
+    let taken: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM workspace_roles
+            WHERE deployment_id = $1
+                AND workspace_id IS NOT DISTINCT FROM $2
+                AND lower(name) = lower($3)
+                AND id IS DISTINCT FROM $4
+        )
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(workspace_id)
+    .bind(name)
+    .bind(exclude_role_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if taken {
+        return Err(AppError::BadRequest(
+            "Role with this name already exists".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Creates a workspace role, either for one workspace or shared by every
+/// workspace in the deployment.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateWorkspaceRoleCommand {
+    pub deployment_id: i64,
+    pub workspace_id: Option<i64>,
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+impl CreateWorkspaceRoleCommand {
+    pub fn new(deployment_id: i64, name: String, permissions: Vec<String>) -> Self {
+        Self {
+            deployment_id,
+            workspace_id: None,
+            name,
+            permissions,
+        }
+    }
+
+    pub fn with_workspace_id(mut self, workspace_id: i64) -> Self {
+        self.workspace_id = Some(workspace_id);
+        self
+    }
+}
+
+impl Command for CreateWorkspaceRoleCommand {
+    type Output = DeploymentWorkspaceRole;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let name = normalize_role_name(self.name)?;
+        let permissions = normalize_permissions(self.permissions, WORKSPACE_PERMISSIONS)?;
+
+        let mut conn = app_state.db_pool.acquire().await?;
+
+        ensure_custom_roles_enabled(&mut conn, self.deployment_id).await?;
+
+        let organization_id: Option<i64> = match self.workspace_id {
+            Some(workspace_id) => Some(
+                sqlx::query_scalar(
+                    "SELECT organization_id FROM workspaces WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL",
+                )
+                .bind(self.deployment_id)
+                .bind(workspace_id)
+                .fetch_optional(&mut *conn)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Workspace not found".to_string()))?,
+            ),
+            None => None,
+        };
+
+        ensure_unique_name(
+            &mut conn,
+            self.deployment_id,
+            self.workspace_id,
+            &name,
+            None,
+        )
+        .await?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO workspace_roles
+                (id, deployment_id, organization_id, workspace_id, name, permissions, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, NOW(), NOW())
+            RETURNING {}
+            "#,
+            WORKSPACE_ROLE_COLUMNS
+        ))
+        .bind(app_state.sf.next_id()? as i64)
+        .bind(self.deployment_id)
+        .bind(organization_id)
+        .bind(self.workspace_id)
+        .bind(name)
+        .bind(&permissions)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(workspace_role_from_row(&row))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateWorkspaceRoleCommand {
+    pub deployment_id: i64,
+    pub workspace_id: Option<i64>,
+    pub role_id: i64,
+    pub name: Option<String>,
+    pub permissions: Option<Vec<String>>,
+}
+
+impl UpdateWorkspaceRoleCommand {
+    pub fn new(
+        deployment_id: i64,
+        role_id: i64,
+        name: Option<String>,
+        permissions: Option<Vec<String>>,
+    ) -> Self {
+        Self {
+            deployment_id,
+            workspace_id: None,
+            role_id,
+            name,
+            permissions,
+        }
+    }
+
+    pub fn with_workspace_id(mut self, workspace_id: i64) -> Self {
+        self.workspace_id = Some(workspace_id);
+        self
+    }
+}
+
+impl Command for UpdateWorkspaceRoleCommand {
+    type Output = DeploymentWorkspaceRole;
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self.name.is_none() && self.permissions.is_none() {
+            return Err(AppError::BadRequest("No fields to update".to_string()));
+        }
+
+        let name = self.name.map(normalize_role_name).transpose()?;
+        let permissions = self
+            .permissions
+            .map(|permissions| normalize_permissions(permissions, WORKSPACE_PERMISSIONS))
+            .transpose()?;
+
+        let mut conn = app_state.db_pool.acquire().await?;
+
+        ensure_custom_roles_enabled(&mut conn, self.deployment_id).await?;
+
+        let exists: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM workspace_roles
+                WHERE id = $1 AND deployment_id = $2 AND workspace_id IS NOT DISTINCT FROM $3
+            )
+            "#,
+        )
+        .bind(self.role_id)
+        .bind(self.deployment_id)
+        .bind(self.workspace_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if !exists {
+            return Err(AppError::NotFound("Workspace role not found".to_string()));
+        }
+
+        if let Some(name) = &name {
+            ensure_unique_name(
+                &mut conn,
+                self.deployment_id,
+                self.workspace_id,
+                name,
+                Some(self.role_id),
+            )
+            .await?;
+        }
+
+        let mut query_builder =
+            sqlx::QueryBuilder::new("UPDATE workspace_roles SET updated_at = NOW()");
+
+        if let Some(name) = name {
+            query_builder.push(", name = ");
+            query_builder.push_bind(name);
+        }
+
+        if let Some(permissions) = permissions {
+            query_builder.push(", permissions = ");
+            query_builder.push_bind(permissions);
+        }
+
+        query_builder.push(" WHERE id = ");
+        query_builder.push_bind(self.role_id);
+        query_builder.push(" RETURNING ");
+        query_builder.push(WORKSPACE_ROLE_COLUMNS);
+
+        let row = query_builder.build().fetch_one(&mut *conn).await?;
+
+        invalidate_deployment_authorization_contexts(app_state, self.deployment_id).await;
+
+        Ok(workspace_role_from_row(&row))
+    }
+}
+
+/// The workspace counterpart of `DeleteOrganizationRoleCommand`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteWorkspaceRoleCommand {
+    pub deployment_id: i64,
+    pub workspace_id: Option<i64>,
+    pub role_id: i64,
+    pub reassign_to: Option<i64>,
+}
+
+impl DeleteWorkspaceRoleCommand {
+    pub fn new(deployment_id: i64, role_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workspace_id: None,
+            role_id,
+            reassign_to: None,
+        }
+    }
+
+    pub fn with_workspace_id(mut self, workspace_id: i64) -> Self {
+        self.workspace_id = Some(workspace_id);
+        self
+    }
+
+    pub fn with_reassign_to(mut self, reassign_to: Option<i64>) -> Self {
+        self.reassign_to = reassign_to;
+        self
+    }
+}
+
+impl Command for DeleteWorkspaceRoleCommand {
+    type Output = ();
+
+    async fn run(self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut tx = app_state.db_pool.begin().await?;
+
+        let locked = sqlx::query(
+            r#"
+            SELECT id FROM workspace_roles
+            WHERE id = $1 AND deployment_id = $2 AND workspace_id IS NOT DISTINCT FROM $3
+            FOR UPDATE
+            "#,
+        )
+        .bind(self.role_id)
+        .bind(self.deployment_id)
+        .bind(self.workspace_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if locked.is_none() {
+            return Err(AppError::NotFound("Workspace role not found".to_string()));
+        }
+
+        let settings = load_settings(&mut tx, self.deployment_id).await?;
+        let default_for = if settings.default_creator_role_id == Some(self.role_id) {
+            Some("creator")
+        } else if settings.default_member_role_id == Some(self.role_id) {
+            Some("member")
+        } else {
+            None
+        };
+
+        if let Some(default_for) = default_for {
+            return Err(AppError::coded(
+                ErrorCode::RoleInUse,
+                "This role is a default workspace role and can't be deleted.",
+            )
+            .with_details(serde_json::json!({
+                "role_id": self.role_id.to_string(),
+                "default_for": default_for,
+            })));
+        }
+
+        let member_count: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(DISTINCT workspace_membership_id)
+            FROM workspace_membership_roles
+            WHERE workspace_role_id = $1
+            "#,
+        )
+        .bind(self.role_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if member_count > 0 {
+            let Some(reassign_to) = self.reassign_to else {
+                return Err(AppError::coded(
+                    ErrorCode::RoleInUse,
+                    "This role is still assigned to members. Pass a role to reassign them to.",
+                )
+                .with_details(serde_json::json!({
+                    "role_id": self.role_id.to_string(),
+                    "member_count": member_count,
+                })));
+            };
+
+            if reassign_to == self.role_id {
+                return Err(AppError::BadRequest(
+                    "Can't reassign members to the role being deleted".to_string(),
+                ));
+            }
+
+            let target_exists: bool = sqlx::query_scalar(
+                r#"
+                SELECT EXISTS (
+                    SELECT 1 FROM workspace_roles
+                    WHERE id = $1 AND deployment_id = $2
+                        AND (workspace_id IS NULL OR workspace_id = $3)
+                )
+                "#,
+            )
+            .bind(reassign_to)
+            .bind(self.deployment_id)
+            .bind(self.workspace_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if !target_exists {
+                return Err(AppError::BadRequest(format!(
+                    "Role {} can't take over this role's members",
+                    reassign_to
+                )));
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO workspace_membership_roles
+                    (workspace_membership_id, workspace_role_id, workspace_id)
+                SELECT wmr.workspace_membership_id, $2, wmr.workspace_id
+                FROM workspace_membership_roles wmr
+                WHERE wmr.workspace_role_id = $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM workspace_membership_roles existing
+                        WHERE existing.workspace_membership_id = wmr.workspace_membership_id
+                            AND existing.workspace_role_id = $2
+                    )
+                "#,
+            )
+            .bind(self.role_id)
+            .bind(reassign_to)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query("DELETE FROM workspace_membership_roles WHERE workspace_role_id = $1")
+                .bind(self.role_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM workspace_roles WHERE id = $1")
+            .bind(self.role_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        invalidate_deployment_authorization_contexts(app_state, self.deployment_id).await;
+
+        Ok(())
+    }
+}
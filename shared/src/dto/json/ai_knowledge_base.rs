@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{models::AiKnowledgeBaseWithDetails, services::qdrant::SearchResult};
+use crate::{
+    models::{ChunkBoundary, ChunkingConfig, ChunkingStrategy},
+    services::qdrant::SearchResult,
+};
 
 // Knowledge Base CRUD Models
 #[derive(Debug, Deserialize)]
@@ -8,6 +11,9 @@ pub struct CreateKnowledgeBaseRequest {
     pub name: String,
     pub description: Option<String>,
     pub configuration: Option<serde_json::Value>,
+    /// One of the platform's configured embedding providers.
+    pub embedding_provider: Option<String>,
+    pub chunking: Option<ChunkingConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -15,6 +21,15 @@ pub struct UpdateKnowledgeBaseRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub configuration: Option<serde_json::Value>,
+    /// One of the platform's configured embedding providers.
+    pub embedding_provider: Option<String>,
+    pub chunking: Option<ChunkingConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateEmbeddingProviderRequest {
+    /// `None` goes back to the platform default.
+    pub provider: Option<String>,
 }
 
 // Document Upload Models
@@ -25,18 +40,34 @@ pub struct UploadUrlRequest {
     pub url: String,
 }
 
-// Knowledge Base Response Models
-#[derive(Debug, Serialize)]
-pub struct KnowledgeBaseResponse {
-    pub data: Vec<AiKnowledgeBaseWithDetails>,
-    pub has_more: bool,
+#[derive(Debug, Deserialize)]
+pub struct IngestUrlRequest {
+    pub url: String,
+    #[serde(default)]
+    pub crawl: bool,
+    pub max_pages: Option<i32>,
+    pub max_depth: Option<i32>,
 }
 
-// Document Query Models
+/// Without a `strategy`, `chunk_size` and `chunk_overlap` are in characters.
 #[derive(Debug, Deserialize)]
-pub struct GetDocumentsQuery {
-    pub limit: Option<usize>,
-    pub offset: Option<usize>,
+pub struct RechunkDocumentRequest {
+    #[serde(default)]
+    pub strategy: ChunkingStrategy,
+    pub chunk_size: i64,
+    pub chunk_overlap: i64,
+    #[serde(default)]
+    pub boundary: ChunkBoundary,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DryRunChunkingRequest {
+    /// The sample document's text.
+    pub content: String,
+    /// Defaults to plain text.
+    pub file_type: Option<String>,
+    /// Defaults to the knowledge base's settings.
+    pub chunking: Option<ChunkingConfig>,
 }
 
 // Search Models
@@ -47,6 +78,22 @@ pub struct SearchKnowledgeBaseQuery {
     pub knowledge_base_id: Option<i64>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SearchKnowledgeBaseRequest {
+    pub query: String,
+    pub top_k: Option<u64>,
+    pub score_threshold: Option<f32>,
+    #[serde(default)]
+    pub filters: KnowledgeBaseSearchFilters,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct KnowledgeBaseSearchFilters {
+    #[serde(default, with = "crate::utils::serde::i64_as_string_option")]
+    pub document_id: Option<i64>,
+    pub source_url: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SearchKnowledgeBaseResponse {
     pub results: Vec<KnowledgeBaseSearchResult>,
@@ -77,11 +124,11 @@ impl From<SearchResult> for KnowledgeBaseSearchResult {
                 .get("document_id")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string()),
-            knowledge_base_id: result
-                .metadata
-                .get("knowledge_base_id")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string()),
+            knowledge_base_id: result.metadata.get("knowledge_base_id").and_then(|v| {
+                v.as_i64()
+                    .map(|id| id.to_string())
+                    .or_else(|| v.as_str().map(|s| s.to_string()))
+            }),
             title: result
                 .metadata
                 .get("title")
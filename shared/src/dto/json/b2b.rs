@@ -1,5 +1,7 @@
 use serde::Deserialize;
 
+use crate::utils::metadata::MetadataMergeMode;
+
 // Organization models
 #[derive(Debug, Deserialize)]
 pub struct CreateOrganizationRequest {
@@ -17,6 +19,7 @@ pub struct UpdateOrganizationRequest {
     pub image_url: Option<String>,
     pub public_metadata: Option<serde_json::Value>,
     pub private_metadata: Option<serde_json::Value>,
+    pub initiated_by: Option<String>,
 }
 
 // Workspace models
@@ -27,6 +30,7 @@ pub struct CreateWorkspaceRequest {
     pub image_url: Option<String>,
     pub public_metadata: Option<serde_json::Value>,
     pub private_metadata: Option<serde_json::Value>,
+    pub initiated_by: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,18 +40,76 @@ pub struct UpdateWorkspaceRequest {
     pub image_url: Option<String>,
     pub public_metadata: Option<serde_json::Value>,
     pub private_metadata: Option<serde_json::Value>,
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOrganizationMetadataRequest {
+    pub public_metadata: Option<serde_json::Value>,
+    pub private_metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    pub mode: MetadataMergeMode,
+    pub initiated_by: Option<String>,
 }
 
 // Organization member models
 #[derive(Debug, Deserialize)]
 pub struct AddOrganizationMemberRequest {
     pub user_id: i64,
+    #[serde(default)]
     pub role_ids: Vec<i64>,
+    pub initiated_by: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateOrganizationMemberRequest {
     pub role_ids: Vec<i64>,
+    pub initiated_by: Option<String>,
+}
+
+// Organization invitation models
+#[derive(Debug, Deserialize)]
+pub struct CreateOrganizationInvitationRequest {
+    pub email_address: String,
+    /// Defaults to the deployment's member role.
+    pub role_id: Option<i64>,
+    /// The member sending the invitation, named in the email.
+    pub inviter_id: Option<i64>,
+    pub expiry_days: Option<i64>,
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResendOrganizationInvitationRequest {
+    pub expiry_days: Option<i64>,
+    pub initiated_by: Option<String>,
+}
+
+/// The sign-up fields are only used when no user has verified the invited
+/// email address yet.
+#[derive(Debug, Deserialize)]
+pub struct AcceptOrganizationInvitationRequest {
+    pub token: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub phone_number: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+// Workspace member models
+#[derive(Debug, Deserialize)]
+pub struct AddWorkspaceMemberRequest {
+    pub user_id: i64,
+    #[serde(default)]
+    pub role_ids: Vec<i64>,
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWorkspaceMemberRequest {
+    pub role_ids: Vec<i64>,
+    pub initiated_by: Option<String>,
 }
 
 // Organization role models
@@ -61,4 +123,24 @@ pub struct CreateOrganizationRoleRequest {
 pub struct UpdateOrganizationRoleRequest {
     pub name: Option<String>,
     pub permissions: Option<Vec<String>>,
+    /// Emails the admins of organizations whose members are affected.
+    pub notify: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulatePermissionChangeRequest {
+    pub permissions: Vec<String>,
+}
+
+// Workspace role models
+#[derive(Debug, Deserialize)]
+pub struct CreateWorkspaceRoleRequest {
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWorkspaceRoleRequest {
+    pub name: Option<String>,
+    pub permissions: Option<Vec<String>>,
 }
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::models::{AiToolConfiguration, WorkflowConfiguration, WorkflowDefinition};
+use crate::models::{
+    AiToolConfiguration, AuthorizationConfiguration, HttpParameter, StorageClass,
+    ToolExecutionPolicy, WorkflowConfiguration, WorkflowDefinition,
+};
 
 // AI Agent models
 #[derive(Debug, Deserialize)]
@@ -19,6 +22,43 @@ pub struct UpdateAgentRequest {
     pub configuration: Option<serde_json::Value>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateAgentSessionRequest {
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppendAgentSessionMessageRequest {
+    pub role: String,
+    pub content: String,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+    /// In millionths of a US dollar.
+    pub spend_micros: Option<i64>,
+    pub tool_calls: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAiBudgetRequest {
+    pub monthly_token_limit: Option<i64>,
+    pub monthly_spend_limit_micros: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttachAgentKnowledgeBaseRequest {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub knowledge_base_id: i64,
+    pub top_k: Option<i32>,
+    pub score_threshold: Option<f32>,
+    pub include_citations: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetrieveAgentContextRequest {
+    pub query: String,
+    pub limit: Option<i64>,
+}
+
 // AI Tool models
 #[derive(Debug, Deserialize)]
 pub struct CreateToolRequest {
@@ -36,6 +76,27 @@ pub struct UpdateToolRequest {
     pub configuration: Option<AiToolConfiguration>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ExecuteToolRequest {
+    #[serde(default)]
+    pub inputs: HashMap<String, serde_json::Value>,
+}
+
+/// Exactly one of `spec_url` and `spec` is given.
+#[derive(Debug, Deserialize)]
+pub struct ImportOpenApiToolsRequest {
+    pub spec_url: Option<String>,
+    /// The OpenAPI document as JSON or YAML.
+    pub spec: Option<String>,
+    /// Imports only these operations. All of them when absent.
+    pub operation_ids: Option<Vec<String>>,
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub headers: Vec<HttpParameter>,
+    pub authorization: Option<AuthorizationConfiguration>,
+    pub execution_policy: Option<ToolExecutionPolicy>,
+}
+
 // AI Workflow models
 #[derive(Debug, Deserialize)]
 pub struct CreateWorkflowRequest {
@@ -53,13 +114,41 @@ pub struct UpdateWorkflowRequest {
     pub workflow_definition: Option<WorkflowDefinition>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PublishWorkflowRequest {
+    pub published_by: Option<String>,
+    pub change_note: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ExecuteWorkflowRequest {
     pub trigger_data: Option<serde_json::Value>,
     pub variables: Option<HashMap<String, serde_json::Value>>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateWorkflowTriggerRequest {
+    /// `schedule` or `webhook`.
+    pub trigger_type: String,
+    /// Required for schedules.
+    pub cron_expression: Option<String>,
+    /// An IANA time zone. Schedules default to UTC.
+    pub timezone: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UploadResult {
     pub url: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSignedUploadRequest {
+    pub storage_class: StorageClass,
+    /// Folder under the deployment's own, such as `documents/2026`.
+    pub key_prefix: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub content_length: i64,
+    /// Base64 SHA-256 of the file.
+    pub checksum_sha256: String,
+}
@@ -1,10 +1,13 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 use crate::models::{
     CountryRestrictions, CustomSigningKey, DarkModeSettings, DeploymentRestrictionsSignUpMode,
-    LightModeSettings, MultiSessionSupport, OauthCredentials, SecondFactorPolicy,
-    SocialConnectionProvider,
+    DisposableDomainKind, EmailProviderCredentials, FirstFactor, JwtTemplateUser,
+    LightModeSettings, LogExportCredentials, LogExportFormat, LogExportSchedule,
+    MultiSessionSupport, OauthCredentials, RestrictionEntry, SecondFactorPolicy,
+    SocialConnectionProvider, SsoAttributeMapping,
 };
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -31,6 +34,8 @@ pub struct PartialUsernameSettings {
     pub required: Option<bool>,
     pub min_length: Option<u8>,
     pub max_length: Option<u8>,
+    /// Replaces the deployment's whole list.
+    pub reserved_names: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -41,6 +46,7 @@ pub struct PartialPasswordSettings {
     pub require_uppercase: Option<bool>,
     pub require_number: Option<bool>,
     pub require_special: Option<bool>,
+    pub disallow_compromised: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -55,6 +61,8 @@ pub struct PartialNameSettings {
 pub struct PartialEmailLinkSettings {
     pub enabled: Option<bool>,
     pub require_same_device: Option<bool>,
+    pub expiry_minutes: Option<i64>,
+    pub invalidate_previous: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -124,12 +132,40 @@ pub struct DeploymentAuthSettingsUpdates {
     pub name: Option<PartialNameSettings>,
     pub authentication_factors: Option<PartialAuthenticationFactorSettings>,
     pub second_factor_policy: Option<SecondFactorPolicy>,
+    /// Replaces the whole list. Each factor must be usable with the settings
+    /// as updated and can't repeat the primary first factor.
+    pub alternate_first_factors: Option<Vec<FirstFactor>>,
     pub backup_code: Option<PartialIndividualAuthSettings>,
     pub web3_wallet: Option<PartialIndividualAuthSettings>,
     pub multi_session_support: Option<MultiSessionSupport>,
     pub session_token_lifetime: Option<i64>,
     pub session_validity_period: Option<i64>,
     pub session_inactive_timeout: Option<i64>,
+    pub lockout_policy: Option<PartialLockoutPolicy>,
+    pub user_deletion_policy: Option<PartialUserDeletionPolicy>,
+    pub otp: Option<PartialOtpSettings>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct PartialLockoutPolicy {
+    pub enabled: Option<bool>,
+    pub max_failed_attempts: Option<i64>,
+    pub lockout_duration: Option<i64>,
+    pub counter_window: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct PartialUserDeletionPolicy {
+    pub retention_days: Option<i64>,
+    pub release_identifiers_immediately: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct PartialOtpSettings {
+    pub code_length: Option<u8>,
+    pub expiry_minutes: Option<i64>,
+    pub max_attempts: Option<i64>,
+    pub resend_cooldown_seconds: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -149,8 +185,8 @@ pub struct DeploymentRestrictionsUpdates {
     pub block_voip_numbers: Option<bool>,
     pub country_restrictions: Option<CountryRestrictions>,
     pub banned_keywords: Option<Vec<String>>,
-    pub allowlisted_resources: Option<Vec<String>>,
-    pub blocklisted_resources: Option<Vec<String>>,
+    pub allowlisted_resources: Option<Vec<RestrictionEntry>>,
+    pub blocklisted_resources: Option<Vec<RestrictionEntry>>,
     pub sign_up_mode: Option<DeploymentRestrictionsSignUpMode>,
     pub multi_session_support: Option<MultiSessionSupport>,
     pub session_token_lifetime: Option<i64>,
@@ -176,6 +212,20 @@ pub struct PartialDeploymentJwtTemplate {
     pub template: Option<Value>,
 }
 
+/// Who a template is rendered for: an existing user, or a made-up one.
+#[derive(Debug, Deserialize)]
+pub struct RenderJwtTemplateRequest {
+    #[serde(default, with = "crate::utils::serde::i64_as_string_option")]
+    pub user_id: Option<i64>,
+    pub user: Option<JwtTemplateUser>,
+}
+
+/// Flags to set by name. A null value clears the deployment's override.
+#[derive(Debug, Deserialize)]
+pub struct UpdateDeploymentFeatureFlagsRequest {
+    pub flags: Map<String, Value>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeploymentB2bSettingsUpdates {
     pub organizations_enabled: Option<bool>,
@@ -186,6 +236,7 @@ pub struct DeploymentB2bSettingsUpdates {
     pub max_allowed_workspace_members: Option<i64>,
     pub allow_org_deletion: Option<bool>,
     pub allow_workspace_deletion: Option<bool>,
+    pub prevent_last_workspace_deletion: Option<bool>,
     pub custom_org_role_enabled: Option<bool>,
     pub custom_workspace_role_enabled: Option<bool>,
     #[serde(
@@ -240,4 +291,126 @@ pub struct DeploymentDisplaySettingsUpdates {
     pub after_signin_redirect_url: Option<String>,
     pub user_profile_url: Option<String>,
     pub after_create_organization_redirect_url: Option<String>,
+    /// Replaces the whole list when given.
+    pub allowed_redirect_origins: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportDeploymentConfigRequest {
+    /// Kept as raw JSON so bundles from other schema versions reach the
+    /// importer, which reports what doesn't match.
+    pub bundle: Value,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisposableDomainRequest {
+    pub domain: String,
+    pub kind: DisposableDomainKind,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSsoConnectionRequest {
+    pub name: String,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    pub idp_metadata_url: Option<String>,
+    pub idp_metadata_xml: Option<String>,
+    pub idp_entity_id: Option<String>,
+    pub idp_sso_url: Option<String>,
+    pub idp_certificate: Option<String>,
+    pub attribute_mapping: Option<SsoAttributeMapping>,
+    #[serde(default)]
+    pub allowed_email_domains: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct UpdateSsoConnectionRequest {
+    pub name: Option<String>,
+    pub enabled: Option<bool>,
+    pub idp_metadata_url: Option<String>,
+    pub idp_metadata_xml: Option<String>,
+    pub idp_entity_id: Option<String>,
+    pub idp_sso_url: Option<String>,
+    pub idp_certificate: Option<String>,
+    pub attribute_mapping: Option<SsoAttributeMapping>,
+    pub allowed_email_domains: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDeploymentApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetEmailTemplateRequest {
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetAllEmailTemplatesRequest {
+    /// Has to be true. Guards against resetting every template by accident.
+    #[serde(default)]
+    pub confirm: bool,
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetEmailProviderRequest {
+    /// `provider` picks the variant: `postmark`, `ses` or `smtp`.
+    #[serde(flatten)]
+    pub credentials: EmailProviderCredentials,
+    pub from_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendTestEmailRequest {
+    pub to_address: String,
+}
+
+/// Replaces the deployment's maintenance settings.
+#[derive(Debug, Deserialize)]
+pub struct UpdateDeploymentMaintenanceRequest {
+    pub maintenance_mode: bool,
+    /// Shown to end users during maintenance and ahead of a scheduled window.
+    pub message: Option<String>,
+    /// Starts a scheduled window; end-user traffic is refused from then on.
+    pub starts_at: Option<DateTime<Utc>>,
+    /// Ends the scheduled window, and is the estimated end shown to users.
+    pub ends_at: Option<DateTime<Utc>>,
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetLogExportRequest {
+    pub bucket: String,
+    pub region: String,
+    /// Objects are written under this path in the bucket.
+    pub prefix: Option<String>,
+    /// `ndjson` unless given.
+    pub format: Option<LogExportFormat>,
+    /// `daily` unless given.
+    pub schedule: Option<LogExportSchedule>,
+    /// `type` picks how the platform signs in: `access_key` or `assume_role`.
+    pub credentials: LogExportCredentials,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeTokenRequest {
+    /// The token's `jti` claim.
+    pub jti: String,
+    /// The token's `exp` claim. The revocation is kept until then.
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeDeploymentTokensRequest {
+    /// Has to be true. Guards against revoking every token by accident.
+    #[serde(default)]
+    pub confirm: bool,
+    pub initiated_by: Option<String>,
 }
@@ -3,6 +3,7 @@ pub mod b2b;
 pub mod deployment;
 pub mod deployment_settings;
 pub mod project;
+pub mod scim;
 pub mod user;
 
 pub use ai_knowledge_base::*;
@@ -10,4 +11,5 @@ pub use b2b::*;
 pub use deployment::*;
 pub use deployment_settings::*;
 pub use project::*;
+pub use scim::*;
 pub use user::*;
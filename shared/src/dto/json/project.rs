@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::models::ProjectRole;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateProjectRequest {
     pub name: String,
@@ -11,4 +13,48 @@ pub struct CreateProjectRequest {
 pub struct CreateProductionDeploymentRequest {
     pub custom_domain: String,
     pub auth_methods: Vec<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeDeploymentDomainRequest {
+    pub custom_domain: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDeploymentMailSettingsRequest {
+    /// The label in front of the custom domain, such as `mail`.
+    pub mail_from_subdomain: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferProjectRequest {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub target_owner_id: i64,
+    pub initiated_by: Option<String>,
+    #[serde(default)]
+    pub notify: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddProjectCollaboratorRequest {
+    pub email: String,
+    pub role: ProjectRole,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProjectCollaboratorRoleRequest {
+    pub role: ProjectRole,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CloneDeploymentRequest {
+    #[serde(default)]
+    pub include_secrets: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PromoteDeploymentRequest {
+    pub custom_domain: String,
+    #[serde(default)]
+    pub copy_oauth_credentials: bool,
+}
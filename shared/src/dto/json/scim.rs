@@ -0,0 +1,48 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::models::{ScimEmail, ScimMember, ScimName};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateScimTokenRequest {
+    pub name: String,
+    pub initiated_by: Option<String>,
+}
+
+/// Body of `POST /Users`. Attributes we don't store are ignored.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUserRequest {
+    pub user_name: String,
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub name: ScimName,
+    #[serde(default)]
+    pub emails: Vec<ScimEmail>,
+    pub active: Option<bool>,
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroupRequest {
+    pub display_name: String,
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub members: Vec<ScimMember>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScimPatchOperation {
+    pub op: String,
+    pub path: Option<String>,
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScimPatchRequest {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
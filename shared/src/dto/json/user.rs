@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::models::UserErasureMode;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateUserRequest {
     pub first_name: String,
@@ -19,6 +21,18 @@ pub struct InviteUserRequest {
     pub expiry_days: Option<i64>,
 }
 
+/// The invitee's details. The email address comes from the invitation, and
+/// names left out are taken from it too.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub phone_number: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateUserRequest {
     pub first_name: Option<String>,
@@ -28,6 +42,31 @@ pub struct UpdateUserRequest {
     pub private_metadata: Option<Value>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateUserPasswordRequest {
+    pub password: String,
+}
+
+/// Without a password, a reset link is emailed to the user instead.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetUserPasswordRequest {
+    pub password: Option<String>,
+    pub require_password_change: Option<bool>,
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdatePasswordRequirementRequest {
+    pub require_password_change: bool,
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EraseUserRequest {
+    pub mode: UserErasureMode,
+    pub initiated_by: Option<String>,
+}
+
 // Email management requests
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddEmailRequest {
@@ -57,3 +96,57 @@ pub struct UpdatePhoneRequest {
     pub verified: Option<bool>,
     pub is_primary: Option<bool>,
 }
+
+// Web3 wallet requests
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddWeb3WalletRequest {
+    pub address: String,
+    /// Defaults to Ethereum mainnet.
+    pub chain_id: Option<i64>,
+    pub verified: Option<bool>,
+    pub is_primary: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Web3ChallengeRequest {
+    pub address: String,
+    /// Defaults to Ethereum mainnet.
+    pub chain_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyWeb3SignatureRequest {
+    pub address: String,
+    pub chain_id: Option<i64>,
+    pub nonce: String,
+    pub signature: String,
+}
+
+// Magic link requests
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateMagicLinkRequest {
+    pub email_address: String,
+    /// Where to send the user once the link is used. Has to be on the
+    /// deployment's frontend or one of its allowed redirect origins.
+    pub redirect_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsumeMagicLinkRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinWaitlistRequest {
+    pub email_address: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub metadata: Option<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkApproveWaitlistRequest {
+    #[serde(with = "crate::utils::serde::i64_vec_as_string")]
+    pub ids: Vec<i64>,
+    pub expiry_days: Option<i64>,
+}
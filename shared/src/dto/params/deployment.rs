@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 pub enum DeploymentNameParams {
     #[serde(rename = "organization-invite-template")]
     OrganizationInviteTemplate,
@@ -25,3 +25,36 @@ pub enum DeploymentNameParams {
     #[serde(rename = "workspace-invite-template")]
     WorkspaceInviteTemplate,
 }
+
+impl DeploymentNameParams {
+    pub const ALL: [Self; 11] = [
+        Self::OrganizationInviteTemplate,
+        Self::VerificationCodeTemplate,
+        Self::ResetPasswordCodeTemplate,
+        Self::PrimaryEmailChangeTemplate,
+        Self::PasswordChangeTemplate,
+        Self::PasswordRemoveTemplate,
+        Self::SignInFromNewDeviceTemplate,
+        Self::MagicLinkTemplate,
+        Self::WaitlistSignupTemplate,
+        Self::WaitlistInviteTemplate,
+        Self::WorkspaceInviteTemplate,
+    ];
+
+    /// The `deployment_email_templates` column the template is stored in.
+    pub fn column_name(self) -> &'static str {
+        match self {
+            Self::OrganizationInviteTemplate => "organization_invite_template",
+            Self::VerificationCodeTemplate => "verification_code_template",
+            Self::ResetPasswordCodeTemplate => "reset_password_code_template",
+            Self::PrimaryEmailChangeTemplate => "primary_email_change_template",
+            Self::PasswordChangeTemplate => "password_change_template",
+            Self::PasswordRemoveTemplate => "password_remove_template",
+            Self::SignInFromNewDeviceTemplate => "sign_in_from_new_device_template",
+            Self::MagicLinkTemplate => "magic_link_template",
+            Self::WaitlistSignupTemplate => "waitlist_signup_template",
+            Self::WaitlistInviteTemplate => "waitlist_invite_template",
+            Self::WorkspaceInviteTemplate => "workspace_invite_template",
+        }
+    }
+}
@@ -1,171 +1,227 @@
-use std::{
-    fmt::{self, Display},
-    str::FromStr,
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::models::{
+    BackgroundJobStatus, EmailOutboxStatus, OrganizationAuditEventType, VerificationStatus,
+    WaitlistEntryStatus,
 };
 
-use serde::Deserialize;
+#[derive(Debug, Deserialize)]
+pub struct WaitlistQueryParams {
+    #[serde(
+        default,
+        deserialize_with = "crate::utils::serde::enum_from_str::from_str_option"
+    )]
+    pub status: Option<WaitlistEntryStatus>,
+}
 
-use super::SortOrder;
+#[derive(Debug, Default, Deserialize)]
+pub struct OrganizationInvitationListQueryParams {
+    /// Only list invitations that can still be accepted.
+    pub pending: Option<bool>,
+}
 
-#[derive(Debug, PartialEq, Deserialize)]
-pub enum ActiveUserListSortKey {
-    CreatedAt,
-    Username,
-    Email,
-    PhoneNumber,
+#[derive(Debug, Default, Deserialize)]
+pub struct OrganizationMemberListQueryParams {
+    pub role_id: Option<i64>,
+    pub search: Option<String>,
 }
 
-impl Default for ActiveUserListSortKey {
-    fn default() -> Self {
-        Self::CreatedAt
-    }
+#[derive(Debug, Default, Deserialize)]
+pub struct WorkspaceMemberListQueryParams {
+    pub role_id: Option<i64>,
+    pub search: Option<String>,
 }
 
-impl FromStr for ActiveUserListSortKey {
-    type Err = String;
+#[derive(Debug, Deserialize)]
+pub struct OrganizationAuditEventListQueryParams {
+    /// The organization admin the events are listed for.
+    pub user_id: i64,
+    pub actor: Option<String>,
+    pub event_type: Option<OrganizationAuditEventType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "created_at" => Ok(ActiveUserListSortKey::CreatedAt),
-            "username" => Ok(ActiveUserListSortKey::Username),
-            "email" => Ok(ActiveUserListSortKey::Email),
-            "phone_number" => Ok(ActiveUserListSortKey::PhoneNumber),
-            _ => Err("Invalid sort key".to_string()),
-        }
-    }
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteOrganizationQueryParams {
+    /// Returns what would be deleted without deleting anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Deletes even with `allow_org_deletion` off. API keys need
+    /// `settings:write` for it.
+    #[serde(default)]
+    pub admin_override: bool,
+    pub initiated_by: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-pub enum InvitationsWaitlistSortKey {
-    CreatedAt,
-    Email,
+#[derive(Debug, Default, Deserialize)]
+pub struct RemoveMemberQueryParams {
+    pub initiated_by: Option<String>,
 }
 
-impl Default for InvitationsWaitlistSortKey {
-    fn default() -> Self {
-        Self::CreatedAt
-    }
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteWorkspaceQueryParams {
+    pub initiated_by: Option<String>,
 }
 
-impl FromStr for InvitationsWaitlistSortKey {
-    type Err = String;
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListQueryParams {
+    pub filter: Option<String>,
+    pub start_index: Option<i64>,
+    pub count: Option<i64>,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "created_at" => Ok(InvitationsWaitlistSortKey::CreatedAt),
-            "email" => Ok(InvitationsWaitlistSortKey::Email),
-            _ => Err("Invalid sort key".to_string()),
-        }
-    }
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteRoleQueryParams {
+    pub reassign_to_role_id: Option<i64>,
 }
 
-impl Display for ActiveUserListSortKey {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::CreatedAt => write!(f, "created_at"),
-            Self::Username => write!(f, "username"),
-            Self::Email => write!(f, "email"),
-            Self::PhoneNumber => write!(f, "phone_number"),
-        }
-    }
+#[derive(Debug, Deserialize)]
+pub struct ClientConfigQueryParams {
+    pub publishable_key: String,
 }
 
-impl Display for InvitationsWaitlistSortKey {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::CreatedAt => write!(f, "created_at"),
-            Self::Email => write!(f, "email"),
-        }
-    }
+#[derive(Debug, Deserialize)]
+pub struct UserLookupQueryParams {
+    pub identifier: String,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct ActiveUserListQueryParams {
-    pub offset: Option<i64>,
-    #[serde(
-        default,
-        deserialize_with = "crate::utils::serde::enum_from_str::from_str_option"
-    )]
-    pub sort_key: Option<ActiveUserListSortKey>,
-    #[serde(
-        default,
-        deserialize_with = "crate::utils::serde::enum_from_str::from_str_option"
-    )]
-    pub sort_order: Option<SortOrder>,
-    pub limit: Option<usize>,
+pub struct UsernameAvailabilityQueryParams {
+    pub username: String,
 }
 
-impl Default for ActiveUserListQueryParams {
-    fn default() -> Self {
-        Self {
-            offset: Some(0),
-            sort_key: Some(ActiveUserListSortKey::CreatedAt),
-            sort_order: Some(SortOrder::Desc),
-            limit: Some(10),
-        }
-    }
+/// `current_session_id` is the caller's own sign-in.
+#[derive(Debug, Default, Deserialize)]
+pub struct UserSessionsQueryParams {
+    pub current_session_id: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportDeploymentConfigQueryParams {
+    pub include_secrets: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DataExportQueryParams {
+    pub initiated_by: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RevealSecretsQueryParams {
+    pub reveal: Option<bool>,
+    pub initiated_by: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct InvitationsWaitlistQueryParams {
-    pub offset: Option<i64>,
+pub struct EmailOutboxQueryParams {
     #[serde(
         default,
         deserialize_with = "crate::utils::serde::enum_from_str::from_str_option"
     )]
-    pub sort_key: Option<InvitationsWaitlistSortKey>,
+    pub status: Option<EmailOutboxStatus>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateEmailTemplateQueryParams {
+    pub allow_warnings: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FeatureFlagDeploymentsQueryParams {
+    /// Read as the flag's type, e.g. `true` for a boolean flag.
+    pub value: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ProjectListQueryParams {
+    /// Only deployments with this status, e.g. `in_progress`.
+    pub verification_status: Option<VerificationStatus>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReconcileCustomHostnamesQueryParams {
+    /// Deletes the orphans found instead of only listing them.
+    #[serde(default)]
+    pub delete: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReconcileQdrantCollectionsQueryParams {
+    /// Deletes the orphaned collections found instead of only listing them.
+    #[serde(default)]
+    pub delete: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BackgroundJobQueryParams {
     #[serde(
         default,
         deserialize_with = "crate::utils::serde::enum_from_str::from_str_option"
     )]
-    pub sort_order: Option<SortOrder>,
-    pub limit: Option<usize>,
+    pub status: Option<BackgroundJobStatus>,
+    /// Only jobs of this type, e.g. `delete_external_resource`.
+    pub job_type: Option<String>,
+}
+
+/// The claims of the session token being checked.
+#[derive(Debug, Deserialize)]
+pub struct TokenRevocationStatusQueryParams {
+    pub jti: String,
+    /// The `sub` claim.
+    pub user_id: i64,
+    /// The `iat` claim, in seconds since the epoch.
+    pub issued_at: i64,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct OrganizationListQueryParams {
-    pub offset: Option<i64>,
-    pub sort_key: Option<String>,
-    pub sort_order: Option<String>,
-    pub limit: Option<i32>,
+pub struct GlobalSearchQueryParams {
+    /// A project name, hostname, publishable key or custom hostname id.
+    pub q: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RequestLogQueryParams {
+    /// A status class such as `4xx`.
+    pub status: Option<String>,
+    /// Matches route templates starting with this, e.g. `/deployments/{deployment_id}/users`.
+    pub path: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
 }
 
-impl Default for OrganizationListQueryParams {
-    fn default() -> Self {
-        Self {
-            offset: Some(0),
-            sort_key: Some("created_at".to_string()),
-            sort_order: Some("desc".to_string()),
-            limit: Some(10),
-        }
-    }
+#[derive(Debug, Default, Deserialize)]
+pub struct UsageQueryParams {
+    /// The billing month as `2026-09`; the current one when left out.
+    pub month: Option<String>,
 }
 
 // AI-related query parameters
 #[derive(Debug, Deserialize)]
 pub struct GetAgentsQuery {
-    pub limit: Option<usize>,
-    pub offset: Option<usize>,
     pub search: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GetToolsQuery {
-    pub limit: Option<usize>,
-    pub offset: Option<usize>,
     pub search: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GetWorkflowsQuery {
-    pub limit: Option<usize>,
-    pub offset: Option<usize>,
     pub search: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct GetWorkflowRunsQuery {
+    pub status: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GetKnowledgeBasesQuery {
-    pub limit: Option<usize>,
-    pub offset: Option<usize>,
     pub search: Option<String>,
 }
@@ -1,5 +1,7 @@
 pub mod deployment;
+pub mod pagination;
 pub mod sort;
 
 pub use deployment::*;
+pub use pagination::*;
 pub use sort::*;
@@ -0,0 +1,145 @@
+//! Query parameters shared by list endpoints.
+//!
+//! Handlers extract these next to their own filter parameters rather than
+//! flattening them into one struct, so every list endpoint pages, sorts and
+//! filters by date the same way.
+
+use std::marker::PhantomData;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::SortOrder;
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 20;
+pub const MAX_PAGE_LIMIT: i64 = 100;
+
+/// `limit` with either `offset` or the `cursor` from the previous page.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Pagination {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+impl Pagination {
+    /// The page size in effect. Limits above [`MAX_PAGE_LIMIT`] are lowered to
+    /// it rather than rejected.
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.cursor
+            .as_deref()
+            .and_then(decode_cursor)
+            .or(self.offset)
+            .unwrap_or(0)
+            .max(0)
+    }
+
+    /// One more row than the page holds, to tell whether another page follows.
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit() + 1
+    }
+
+    /// The cursor of the page after this one.
+    pub fn next_cursor(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("offset:{}", self.offset() + self.limit()))
+    }
+}
+
+pub(crate) fn decode_cursor(cursor: &str) -> Option<i64> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    decoded
+        .strip_prefix("offset:")?
+        .parse()
+        .ok()
+        .filter(|offset| *offset >= 0)
+}
+
+/// The columns an endpoint can be sorted by. The first one is the default.
+pub trait SortColumns {
+    const COLUMNS: &'static [&'static str];
+}
+
+/// `sort_key` and `sort_order`, with `sort_key` checked against the columns
+/// of `C`.
+#[derive(Debug, Deserialize)]
+#[serde(bound = "")]
+pub struct SortParams<C: SortColumns> {
+    pub sort_key: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "crate::utils::serde::enum_from_str::from_str_option"
+    )]
+    pub sort_order: Option<SortOrder>,
+    #[serde(skip)]
+    columns: PhantomData<C>,
+}
+
+impl<C: SortColumns> SortParams<C> {
+    pub fn key(&self) -> &'static str {
+        self.sort_key
+            .as_deref()
+            .and_then(|key| C::COLUMNS.iter().find(|column| **column == key))
+            .copied()
+            .unwrap_or(C::COLUMNS[0])
+    }
+
+    /// Newest or largest first unless asked otherwise.
+    pub fn order(&self) -> SortOrder {
+        self.sort_order.unwrap_or(SortOrder::Desc)
+    }
+}
+
+impl<C: SortColumns> Default for SortParams<C> {
+    fn default() -> Self {
+        Self {
+            sort_key: None,
+            sort_order: None,
+            columns: PhantomData,
+        }
+    }
+}
+
+/// `from` and `to` as RFC 3339 timestamps. `started_after` and
+/// `started_before` are still accepted for the endpoints that used them.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DateRangeParams {
+    #[serde(
+        default,
+        alias = "started_after",
+        deserialize_with = "crate::utils::serde::rfc3339_option::deserialize"
+    )]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        alias = "started_before",
+        deserialize_with = "crate::utils::serde::rfc3339_option::deserialize"
+    )]
+    pub to: Option<DateTime<Utc>>,
+}
+
+pub struct UserSortColumns;
+
+impl SortColumns for UserSortColumns {
+    const COLUMNS: &'static [&'static str] = &["created_at", "username", "email", "phone_number"];
+}
+
+/// Deployment invitations and waitlist entries.
+pub struct InvitationSortColumns;
+
+impl SortColumns for InvitationSortColumns {
+    const COLUMNS: &'static [&'static str] = &["created_at", "email"];
+}
+
+/// Organizations and workspaces.
+pub struct OrganizationSortColumns;
+
+impl SortColumns for OrganizationSortColumns {
+    const COLUMNS: &'static [&'static str] = &["created_at", "updated_at", "name", "member_count"];
+}
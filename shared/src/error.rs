@@ -1,11 +1,106 @@
+use serde::Serialize;
+use serde_json::Value;
 use thiserror::Error;
 
+/// Stable, machine-readable identifier for an error. Clients branch on these, so
+/// existing codes must never be renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InternalError,
+    NotFound,
+    Unauthorized,
+    BadRequest,
+    ValidationFailed,
+    InvalidJson,
+    UnsupportedMediaType,
+    RateLimited,
+    ExternalServiceError,
+    DomainInUse,
+    ProductionDeploymentExists,
+    LastDeploymentCannotBeDeleted,
+    ProjectLimitReached,
+    VoipNumberNotAllowed,
+    LockedOut,
+    OrganizationMemberLimitReached,
+    LastOrganizationAdmin,
+    WorkspaceMemberLimitReached,
+    LastWorkspaceAdmin,
+    LastWorkspaceCannotBeDeleted,
+    RoleInUse,
+    AlreadyExists,
+    InsufficientScope,
+    DomainMigrationInProgress,
+    InvalidEmailTemplate,
+    EmailProviderVerificationFailed,
+    SecretDecryptionFailed,
+    InvalidJwtTemplate,
+    InvitationAlreadyAccepted,
+    ExportInProgress,
+    RedirectUrlNotAllowed,
+    InsufficientProjectRole,
+    LastProjectOwner,
+    LastSignInMethod,
+    DeploymentDeleted,
+    DeploymentInMaintenance,
+    BudgetExceeded,
+    EmbeddingModelMismatch,
+    RedisUnavailable,
+    PlatformOperatorRequired,
+    OrganizationAdminRequired,
+    InvalidOtp,
+    OtpExpired,
+    OtpResendCooldown,
+    Web3ChallengeExpired,
+    InvalidWeb3Signature,
+    InvalidMagicLink,
+    MagicLinkExpired,
+    MagicLinkAlreadyUsed,
+    LogExportDestinationFailed,
+}
+
+/// A failed call to a third-party service, with which service it was and
+/// the HTTP status it answered with, when those are known.
+#[derive(Error, Debug)]
+#[error("{message}")]
+pub struct ExternalError {
+    pub provider: Option<&'static str>,
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl ExternalError {
+    pub fn new(provider: &'static str, status: Option<u16>, message: impl Into<String>) -> Self {
+        Self {
+            provider: Some(provider),
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<String> for ExternalError {
+    fn from(message: String) -> Self {
+        Self {
+            provider: None,
+            status: None,
+            message,
+        }
+    }
+}
+
+impl From<&str> for ExternalError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
-    #[error("Sonyflake error: {0}")]
-    Sonyflake(#[from] sonyflake::Error),
+    #[error("ID generator error: {0}")]
+    IdGenerator(#[from] crate::services::IdGeneratorError),
     #[error("Resource not found")]
     NotFound(String),
     #[error("Unauthorized access")]
@@ -21,7 +116,53 @@ pub enum AppError {
     #[error("S3 error: {0}")]
     S3(String),
     #[error("External service error: {0}")]
-    External(String),
+    External(ExternalError),
+    /// A client error specific enough that callers need to tell it apart from
+    /// other bad requests.
+    #[error("{message}")]
+    Coded {
+        code: ErrorCode,
+        message: String,
+        details: Option<Value>,
+    },
+}
+
+impl AppError {
+    pub fn coded(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::Coded {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(self, details: Value) -> Self {
+        match self {
+            AppError::Coded { code, message, .. } => AppError::Coded {
+                code,
+                message,
+                details: Some(details),
+            },
+            other => other,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::Database(_)
+            | AppError::IdGenerator(_)
+            | AppError::Internal(_)
+            | AppError::Serialization(_)
+            | AppError::S3(_) => ErrorCode::InternalError,
+            AppError::NotFound(_) => ErrorCode::NotFound,
+            AppError::Unauthorized => ErrorCode::Unauthorized,
+            AppError::BadRequest(_) => ErrorCode::BadRequest,
+            AppError::Validation(_) => ErrorCode::ValidationFailed,
+            AppError::External(e) if e.provider == Some("redis") => ErrorCode::RedisUnavailable,
+            AppError::External(_) => ErrorCode::ExternalServiceError,
+            AppError::Coded { code, .. } => *code,
+        }
+    }
 }
 
 impl From<serde_json::Error> for AppError {
@@ -30,8 +171,18 @@ impl From<serde_json::Error> for AppError {
     }
 }
 
+/// Failures to reach Redis are external errors, so callers and clients can
+/// tell them apart from a command Redis rejected.
 impl From<redis::RedisError> for AppError {
     fn from(error: redis::RedisError) -> Self {
+        if error.is_connection_refusal()
+            || error.is_connection_dropped()
+            || error.is_timeout()
+            || error.is_io_error()
+        {
+            crate::metrics::METRICS.redis_connection_errors.inc();
+            return AppError::External(ExternalError::new("redis", None, error.to_string()));
+        }
         AppError::Internal(error.to_string())
     }
 }
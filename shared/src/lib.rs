@@ -1,6 +1,7 @@
 pub mod commands;
 pub mod dto;
 pub mod error;
+pub mod metrics;
 pub mod models;
 pub mod ports;
 pub mod queries;
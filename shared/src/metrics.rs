@@ -0,0 +1,295 @@
+//! Prometheus metrics for the whole process.
+//!
+//! Every [`Command`](crate::commands::Command) and
+//! [`Query`](crate::queries::Query) is instrumented through the traits'
+//! `execute` methods, so implementations don't record anything themselves.
+
+use std::future::Future;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use sqlx::PgPool;
+
+use crate::error::AppError;
+
+pub struct Metrics {
+    registry: Registry,
+    pub command_executions: IntCounterVec,
+    pub command_errors: IntCounterVec,
+    pub command_duration: HistogramVec,
+    pub query_executions: IntCounterVec,
+    pub query_errors: IntCounterVec,
+    pub query_duration: HistogramVec,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub http_requests_in_flight: IntGauge,
+    pub db_pool_connections: IntGaugeVec,
+    pub redis_connection_errors: IntCounter,
+    pub deployment_settings_cache: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels)?;
+            registry.register(Box::new(counter.clone()))?;
+            Ok::<_, prometheus::Error>(counter)
+        };
+        let histogram = |name: &str, help: &str, labels: &[&str]| {
+            let histogram = HistogramVec::new(HistogramOpts::new(name, help), labels)?;
+            registry.register(Box::new(histogram.clone()))?;
+            Ok::<_, prometheus::Error>(histogram)
+        };
+
+        let http_requests_in_flight = IntGauge::new(
+            "http_requests_in_flight",
+            "HTTP requests currently being served",
+        )?;
+        registry.register(Box::new(http_requests_in_flight.clone()))?;
+
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "db_pool_connections",
+                "Postgres pool connections by state: idle, in_use or max",
+            ),
+            &["state"],
+        )?;
+        registry.register(Box::new(db_pool_connections.clone()))?;
+
+        let redis_connection_errors = IntCounter::new(
+            "redis_connection_errors_total",
+            "Redis errors caused by a failed or dropped connection",
+        )?;
+        registry.register(Box::new(redis_connection_errors.clone()))?;
+
+        Ok(Self {
+            command_executions: counter(
+                "command_executions_total",
+                "Commands executed",
+                &["command"],
+            )?,
+            command_errors: counter(
+                "command_errors_total",
+                "Commands that returned an error",
+                &["command"],
+            )?,
+            command_duration: histogram(
+                "command_duration_seconds",
+                "Command execution time",
+                &["command"],
+            )?,
+            query_executions: counter("query_executions_total", "Queries executed", &["query"])?,
+            query_errors: counter(
+                "query_errors_total",
+                "Queries that returned an error",
+                &["query"],
+            )?,
+            query_duration: histogram(
+                "query_duration_seconds",
+                "Query execution time",
+                &["query"],
+            )?,
+            http_requests: counter(
+                "http_requests_total",
+                "HTTP requests by route template and status",
+                &["method", "route", "status"],
+            )?,
+            http_request_duration: histogram(
+                "http_request_duration_seconds",
+                "HTTP request latency by route template",
+                &["method", "route"],
+            )?,
+            deployment_settings_cache: counter(
+                "deployment_settings_cache_total",
+                "Deployment settings cache lookups by result: hit, miss, tombstone, stale or error",
+                &["result"],
+            )?,
+            http_requests_in_flight,
+            db_pool_connections,
+            redis_connection_errors,
+            registry,
+        })
+    }
+
+    /// The registry in the Prometheus text exposition format.
+    pub fn render(&self) -> Result<String, AppError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| AppError::Internal(format!("Failed to encode metrics: {}", e)))?;
+
+        String::from_utf8(buffer)
+            .map_err(|e| AppError::Internal(format!("Failed to encode metrics: {}", e)))
+    }
+
+    /// Pool gauges are sampled when scraped rather than tracked on every
+    /// checkout.
+    pub fn observe_db_pool(&self, pool: &PgPool) {
+        let size = pool.size() as i64;
+        let idle = pool.num_idle() as i64;
+
+        self.db_pool_connections
+            .with_label_values(&["idle"])
+            .set(idle);
+        self.db_pool_connections
+            .with_label_values(&["in_use"])
+            .set(size - idle);
+        self.db_pool_connections
+            .with_label_values(&["max"])
+            .set(pool.options().get_max_connections() as i64);
+    }
+
+    pub fn observe_http_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+pub static METRICS: LazyLock<Metrics> =
+    LazyLock::new(|| Metrics::new().expect("Failed to register metrics"));
+
+/// The type name without its module path, e.g. `CreateUserCommand`.
+pub fn operation_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let path = name.split('<').next().unwrap_or(name);
+    let start = path.rfind("::").map(|i| i + 2).unwrap_or(0);
+    &name[start..]
+}
+
+async fn observe<T>(
+    executions: &IntCounterVec,
+    errors: &IntCounterVec,
+    duration: &HistogramVec,
+    name: &str,
+    execution: impl Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    let started_at = Instant::now();
+    let result = execution.await;
+
+    executions.with_label_values(&[name]).inc();
+    duration
+        .with_label_values(&[name])
+        .observe(started_at.elapsed().as_secs_f64());
+    if result.is_err() {
+        errors.with_label_values(&[name]).inc();
+    }
+
+    result
+}
+
+pub async fn observe_command<C: ?Sized, T>(
+    execution: impl Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    let metrics = &*METRICS;
+    observe(
+        &metrics.command_executions,
+        &metrics.command_errors,
+        &metrics.command_duration,
+        operation_name::<C>(),
+        execution,
+    )
+    .await
+}
+
+pub async fn observe_query<Q: ?Sized, T>(
+    execution: impl Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    let metrics = &*METRICS;
+    observe(
+        &metrics.query_executions,
+        &metrics.query_errors,
+        &metrics.query_duration,
+        operation_name::<Q>(),
+        execution,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct RecordMetricsTestCommand;
+    struct FailingMetricsTestCommand;
+    struct RecordMetricsTestQuery;
+
+    #[test]
+    fn operation_name_strips_module_path() {
+        assert_eq!(
+            operation_name::<RecordMetricsTestCommand>(),
+            "RecordMetricsTestCommand"
+        );
+        assert_eq!(
+            operation_name::<Vec<String>>(),
+            "Vec<alloc::string::String>"
+        );
+    }
+
+    #[tokio::test]
+    async fn executing_a_command_counts_it() {
+        let name = "RecordMetricsTestCommand";
+        let executions = METRICS.command_executions.with_label_values(&[name]);
+        let errors = METRICS.command_errors.with_label_values(&[name]);
+        let durations = METRICS.command_duration.with_label_values(&[name]);
+        let (before, errors_before) = (executions.get(), errors.get());
+
+        let result = observe_command::<RecordMetricsTestCommand, _>(async { Ok(7) }).await;
+
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(executions.get(), before + 1);
+        assert_eq!(errors.get(), errors_before);
+        assert!(durations.get_sample_count() >= 1);
+    }
+
+    #[tokio::test]
+    async fn failing_command_counts_an_error() {
+        let name = "FailingMetricsTestCommand";
+        let executions = METRICS.command_executions.with_label_values(&[name]);
+        let errors = METRICS.command_errors.with_label_values(&[name]);
+        let (before, errors_before) = (executions.get(), errors.get());
+
+        let result = observe_command::<FailingMetricsTestCommand, ()>(async {
+            Err(AppError::BadRequest("nope".to_string()))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(executions.get(), before + 1);
+        assert_eq!(errors.get(), errors_before + 1);
+    }
+
+    #[tokio::test]
+    async fn queries_are_counted_apart_from_commands() {
+        let name = "RecordMetricsTestQuery";
+        let before = METRICS.query_executions.with_label_values(&[name]).get();
+
+        observe_query::<RecordMetricsTestQuery, _>(async { Ok(()) })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            METRICS.query_executions.with_label_values(&[name]).get(),
+            before + 1
+        );
+        assert_eq!(
+            METRICS.command_executions.with_label_values(&[name]).get(),
+            0
+        );
+        assert!(
+            METRICS
+                .render()
+                .unwrap()
+                .contains("query_executions_total{query=\"RecordMetricsTestQuery\"}")
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::KnowledgeBaseChunkMatch;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AiAgent {
     #[serde(with = "crate::utils::serde::i64_as_string")]
@@ -29,3 +31,37 @@ pub struct AiAgentWithDetails {
     pub workflows_count: i64,
     pub knowledge_bases_count: i64,
 }
+
+/// A knowledge base attached to an agent, with how the agent searches it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiAgentKnowledgeBase {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub agent_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub knowledge_base_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub top_k: i32,
+    pub score_threshold: Option<f32>,
+    /// Whether matches carry their document, title and position, so the
+    /// agent can cite them.
+    pub include_citations: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentContextChunk {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub knowledge_base_id: i64,
+    #[serde(flatten)]
+    pub chunk: KnowledgeBaseChunkMatch,
+}
+
+/// Matches from all of an agent's knowledge bases, best first.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentContext {
+    pub results: Vec<AgentContextChunk>,
+    pub embedding_latency_ms: u64,
+    pub search_latency_ms: u64,
+}
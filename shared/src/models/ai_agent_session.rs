@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiAgentSession {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub agent_id: i64,
+    pub status: String,
+    pub metadata: serde_json::Value,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiAgentSessionWithStats {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub agent_id: i64,
+    pub status: String,
+    pub metadata: serde_json::Value,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub message_count: i64,
+    pub total_tokens: i64,
+    pub duration_seconds: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiAgentSessionMessage {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub session_id: i64,
+    pub sequence: i32,
+    pub role: String,
+    pub content: String,
+    pub prompt_tokens: i32,
+    pub completion_tokens: i32,
+    pub tool_calls: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiAgentSessionTranscript {
+    pub session: AiAgentSessionWithStats,
+    pub messages: Vec<AiAgentSessionMessage>,
+}
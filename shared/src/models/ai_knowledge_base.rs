@@ -12,6 +12,13 @@ pub struct AiKnowledgeBase {
     #[serde(with = "crate::utils::serde::i64_as_string")]
     pub deployment_id: i64,
     pub configuration: serde_json::Value,
+    /// The provider new vectors are made with when re-embedding. `None`
+    /// follows the deployment.
+    pub embedding_provider: Option<String>,
+    /// The model the stored vectors were made with.
+    pub embedding_model: Option<String>,
+    /// How documents added from now on are chunked.
+    pub chunking: ChunkingConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -25,10 +32,89 @@ pub struct AiKnowledgeBaseWithDetails {
     #[serde(with = "crate::utils::serde::i64_as_string")]
     pub deployment_id: i64,
     pub configuration: serde_json::Value,
+    /// The provider new vectors are made with when re-embedding. `None`
+    /// follows the deployment.
+    pub embedding_provider: Option<String>,
+    /// The model the stored vectors were made with.
+    pub embedding_model: Option<String>,
+    /// How documents added from now on are chunked.
+    pub chunking: ChunkingConfig,
     pub documents_count: i64,
     pub total_size: i64,
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkingStrategy {
+    /// Windows of `chunk_size` characters.
+    #[default]
+    Characters,
+    /// Windows of `chunk_size` tokens, counted the way the knowledge base's
+    /// embedding model counts them.
+    Tokens,
+    /// Token windows that never span two markdown sections, each one
+    /// carrying the headings of its section. Documents without headings are
+    /// split as with `tokens`.
+    Markdown,
+}
+
+impl ChunkingStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkingStrategy::Characters => "characters",
+            ChunkingStrategy::Tokens => "tokens",
+            ChunkingStrategy::Markdown => "markdown",
+        }
+    }
+}
+
+impl From<String> for ChunkingStrategy {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "tokens" => ChunkingStrategy::Tokens,
+            "markdown" => ChunkingStrategy::Markdown,
+            _ => ChunkingStrategy::Characters,
+        }
+    }
+}
+
+/// Where a chunk that has run out of room ends.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkBoundary {
+    /// Wherever the size runs out, even mid-word.
+    #[default]
+    None,
+    /// At the last sentence end that fits, else the last word end.
+    Sentence,
+    /// At the last paragraph end that fits, else as with `sentence`.
+    Paragraph,
+}
+
+/// How a knowledge base's documents are split into chunks.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChunkingConfig {
+    #[serde(default)]
+    pub strategy: ChunkingStrategy,
+    /// In characters for `characters`, in tokens otherwise.
+    pub chunk_size: usize,
+    /// How much of the end of each chunk the next one repeats, in the same
+    /// unit as `chunk_size`.
+    pub chunk_overlap: usize,
+    #[serde(default)]
+    pub boundary: ChunkBoundary,
+}
+
+impl ChunkingConfig {
+    /// The config as stored on a knowledge base. Knowledge bases from before
+    /// configs were stored have none, and were chunked with the default.
+    pub fn from_stored(value: Option<serde_json::Value>) -> Self {
+        value
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AiKnowledgeBaseDocument {
     #[serde(with = "crate::utils::serde::i64_as_string")]
@@ -46,4 +132,204 @@ pub struct AiKnowledgeBaseDocument {
     pub processing_metadata: Option<serde_json::Value>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KnowledgeBaseDocumentStatus {
+    /// Stored, with its chunks not written yet.
+    Pending,
+    Ready,
+    /// Text extraction or embedding failed; see `ingestion_error`.
+    Failed,
+}
+
+impl From<String> for KnowledgeBaseDocumentStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "pending" => KnowledgeBaseDocumentStatus::Pending,
+            "failed" => KnowledgeBaseDocumentStatus::Failed,
+            _ => KnowledgeBaseDocumentStatus::Ready,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KnowledgeBaseDocumentSource {
+    Upload,
+    /// Fetched once from a URL.
+    Url,
+    /// Found by crawling a site; re-crawls update it in place.
+    Crawl,
+}
+
+impl From<String> for KnowledgeBaseDocumentSource {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "url" => KnowledgeBaseDocumentSource::Url,
+            "crawl" => KnowledgeBaseDocumentSource::Crawl,
+            _ => KnowledgeBaseDocumentSource::Upload,
+        }
+    }
+}
+
+/// A knowledge base document as listed, with how it was ingested.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KnowledgeBaseDocumentSummary {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub title: String,
+    pub file_name: String,
+    pub file_size: i64,
+    pub file_type: String,
+    pub source: KnowledgeBaseDocumentSource,
+    pub source_url: Option<String>,
+    pub ingestion_status: KnowledgeBaseDocumentStatus,
+    pub ingestion_error: Option<String>,
+    /// Unknown for documents ingested before chunks were counted.
+    pub chunk_count: Option<i32>,
+    pub chunk_size: Option<i32>,
+    pub chunk_overlap: Option<i32>,
+    pub chunking_strategy: ChunkingStrategy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KnowledgeBaseDocumentRechunk {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub document_id: i64,
+    pub chunk_count: i64,
+    pub chunk_size: i64,
+    pub chunk_overlap: i64,
+    pub chunking_strategy: ChunkingStrategy,
+    /// Chunks from the previous ingestion that were deleted.
+    pub chunks_replaced: u64,
+}
+
+/// A chunk of a sample document, as it would be stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChunkPreview {
+    pub chunk_index: usize,
+    pub content: String,
+    pub char_start: usize,
+    pub char_end: usize,
+    pub token_count: usize,
+    pub heading_path: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChunkingDryRun {
+    pub chunking: ChunkingConfig,
+    pub chunks: Vec<ChunkPreview>,
+    /// Tokens across all chunks, overlaps included.
+    pub total_tokens: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiKnowledgeBaseCrawl {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub knowledge_base_id: i64,
+    pub root_url: String,
+    pub status: String,
+    pub crawl: bool,
+    pub max_pages: i32,
+    pub max_depth: i32,
+    pub pages_ingested: i32,
+    pub pages_skipped: i32,
+    pub pages_failed: i32,
+    pub error: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KnowledgeBaseChunkMatch {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub chunk_id: i64,
+    pub content: String,
+    pub score: f32,
+    pub document_id: Option<String>,
+    pub source_url: Option<String>,
+    pub title: Option<String>,
+    pub chunk_index: Option<i64>,
+    pub char_start: Option<i64>,
+    pub char_end: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KnowledgeBaseSearchResults {
+    pub results: Vec<KnowledgeBaseChunkMatch>,
+    pub embedding_latency_ms: u64,
+    pub search_latency_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiKnowledgeBaseReembedJob {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub knowledge_base_id: i64,
+    pub source_collection: String,
+    pub target_collection: String,
+    pub status: String,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub cursor: Option<i64>,
+    pub processed_chunks: i64,
+    pub total_chunks: i64,
+    pub error: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub embedding_provider: Option<String>,
+    pub embedding_model: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct KnowledgeBaseCollections {
+    pub active: String,
+    /// Target collection of an unfinished re-embedding job, which must see the same
+    /// deletions as the active collection until it is flipped in.
+    pub pending: Option<String>,
+}
+
+impl KnowledgeBaseCollections {
+    pub fn all(&self) -> Vec<String> {
+        let mut collections = vec![self.active.clone()];
+        collections.extend(self.pending.clone());
+        collections
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmbeddingProviderInfo {
+    pub name: String,
+    pub model: String,
+    /// The provider used when the primary one is down.
+    pub fallback: Option<String>,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentEmbeddingProviders {
+    pub providers: Vec<EmbeddingProviderInfo>,
+    /// The deployment's choice; `None` uses the platform default.
+    pub selected: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrphanedQdrantCollection {
+    pub name: String,
+    pub deleted: bool,
+    /// Why deleting it failed, when it was asked for.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QdrantCollectionReconciliation {
+    /// How many knowledge base collections Qdrant has.
+    pub checked: usize,
+    pub orphans: Vec<OrphanedQdrantCollection>,
+}
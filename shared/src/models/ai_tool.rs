@@ -44,12 +44,35 @@ pub enum AiToolConfiguration {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ApiToolConfiguration {
+    /// May contain `{name}` placeholders, filled from `path_parameters`.
     pub endpoint: String,
     pub method: HttpMethod,
+    #[serde(default)]
+    pub path_parameters: Vec<HttpParameter>,
     pub headers: Vec<HttpParameter>,
     pub query_parameters: Vec<HttpParameter>,
     pub body_parameters: Vec<HttpParameter>,
     pub authorization: Option<AuthorizationConfiguration>,
+    #[serde(default)]
+    pub execution_policy: ToolExecutionPolicy,
+    /// JSON Schema of the inputs the tool takes, offered to the model
+    /// choosing its arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<serde_json::Value>,
+}
+
+/// Limits applied to every outbound call an API tool makes. Private networks and
+/// cloud metadata endpoints are always refused unless a CIDR in `allowed_hosts`
+/// explicitly opens a private range; the metadata endpoint can never be allowed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ToolExecutionPolicy {
+    pub timeout_ms: u64,
+    pub max_response_bytes: u64,
+    /// Hostnames (`api.example.com`, `*.example.com`), IPs or CIDRs. When empty, any
+    /// public host may be called.
+    pub allowed_hosts: Vec<String>,
+    pub denied_hosts: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -97,6 +120,37 @@ pub enum HttpMethod {
     PATCH,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenApiOperationOutcome {
+    Created,
+    Updated,
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OpenApiOperationReport {
+    pub operation_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub outcome: OpenApiOperationOutcome,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub tool_id: Option<i64>,
+    /// Why the operation was skipped.
+    pub reason: Option<String>,
+}
+
+/// What importing an OpenAPI document did with each of its operations.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OpenApiImportReport {
+    /// The document's `info.title`, which identifies it on re-import.
+    pub source: String,
+    pub created: u32,
+    pub updated: u32,
+    pub skipped: u32,
+    pub operations: Vec<OpenApiOperationReport>,
+}
+
 impl From<String> for AiToolType {
     fn from(tool_type: String) -> Self {
         match tool_type.as_str() {
@@ -156,10 +210,13 @@ impl Default for AiToolConfiguration {
         Self::Api(ApiToolConfiguration {
             endpoint: "".to_string(),
             method: HttpMethod::GET,
+            path_parameters: Vec::new(),
             headers: Vec::new(),
             query_parameters: Vec::new(),
             body_parameters: Vec::new(),
             authorization: None,
+            execution_policy: ToolExecutionPolicy::default(),
+            input_schema: None,
         })
     }
 }
@@ -169,10 +226,13 @@ impl Default for ApiToolConfiguration {
         Self {
             endpoint: "".to_string(),
             method: HttpMethod::GET,
+            path_parameters: Vec::new(),
             headers: Vec::new(),
             query_parameters: Vec::new(),
             body_parameters: Vec::new(),
             authorization: None,
+            execution_policy: ToolExecutionPolicy::default(),
+            input_schema: None,
         }
     }
 }
@@ -185,3 +245,14 @@ impl Default for KnowledgeBaseToolConfiguration {
         }
     }
 }
+
+impl Default for ToolExecutionPolicy {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 10_000,
+            max_response_bytes: 1024 * 1024,
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiToolInvocation {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub tool_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub workflow_run_id: Option<i64>,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub agent_session_id: Option<i64>,
+    pub status: String,
+    pub duration_ms: i64,
+    pub response_status: Option<i32>,
+    pub output: Option<serde_json::Value>,
+    pub error: Option<ToolExecutionError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Error)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum ToolExecutionError {
+    #[error("Invalid tool endpoint: {message}")]
+    InvalidEndpoint { message: String },
+    #[error("Host {host} is not allowed: {reason}")]
+    HostNotAllowed { host: String, reason: String },
+    #[error("Could not resolve host {host}")]
+    DnsResolutionFailed { host: String },
+    #[error("Tool did not respond within {timeout_ms}ms")]
+    Timeout { timeout_ms: u64 },
+    #[error("Tool response exceeded {limit_bytes} bytes")]
+    ResponseTooLarge { limit_bytes: u64 },
+    #[error("Missing required input: {name}")]
+    MissingInput { name: String },
+    #[error("Tool request failed: {message}")]
+    RequestFailed { message: String },
+}
@@ -0,0 +1,72 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Monthly limits on model usage, for an agent or a whole deployment. A
+/// limit left out isn't enforced.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AiBudget {
+    pub monthly_token_limit: Option<i64>,
+    /// In millionths of a US dollar.
+    pub monthly_spend_limit_micros: Option<i64>,
+}
+
+/// Whose budget a limit or warning is about.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AiBudgetScope {
+    Agent,
+    Deployment,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AiBudgetMetric {
+    Tokens,
+    Spend,
+}
+
+/// Published to [`crate::commands::AI_BUDGET_EVENTS_STREAM`] when usage for
+/// the month crosses the warning threshold of a limit.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiBudgetWarningEvent {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    /// Set when the agent's own budget is the one running out.
+    #[serde(default, with = "crate::utils::serde::i64_as_string_option")]
+    pub agent_id: Option<i64>,
+    pub scope: AiBudgetScope,
+    pub metric: AiBudgetMetric,
+    pub used: i64,
+    pub limit: i64,
+    pub period_start: NaiveDate,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// One agent's model usage in a month.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiAgentUsage {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub agent_id: i64,
+    /// None once the agent has been deleted.
+    pub agent_name: Option<String>,
+    pub invocations: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+    pub spend_micros: i64,
+    pub budget: AiBudget,
+}
+
+/// A deployment's model usage in a month, overall and per agent.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiUsageReport {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub period_start: NaiveDate,
+    /// The first day of the next month, when usage starts from zero again.
+    pub period_end: NaiveDate,
+    pub total_tokens: i64,
+    pub spend_micros: i64,
+    pub budget: AiBudget,
+    pub agents: Vec<AiAgentUsage>,
+}
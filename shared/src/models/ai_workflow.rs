@@ -14,6 +14,8 @@ pub struct AiWorkflow {
     pub deployment_id: i64,
     pub configuration: WorkflowConfiguration,
     pub workflow_definition: WorkflowDefinition,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub published_version_id: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,10 +30,28 @@ pub struct AiWorkflowWithDetails {
     pub deployment_id: i64,
     pub configuration: WorkflowConfiguration,
     pub workflow_definition: WorkflowDefinition,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub published_version_id: Option<i64>,
     pub agents_count: i64,
     pub last_execution_at: Option<DateTime<Utc>>,
 }
 
+/// An immutable snapshot of a workflow's draft, created on publish.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiWorkflowVersion {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub workflow_id: i64,
+    pub version_number: i32,
+    pub configuration: WorkflowConfiguration,
+    pub workflow_definition: WorkflowDefinition,
+    pub published_by: Option<String>,
+    pub change_note: Option<String>,
+    pub is_published: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WorkflowConfiguration {
     pub timeout_seconds: Option<u32>,
@@ -169,6 +189,7 @@ pub struct ActionNodeConfig {
     pub api_config: Option<ApiActionConfig>,
     pub knowledge_base_config: Option<KnowledgeBaseActionConfig>,
     pub trigger_workflow_config: Option<TriggerWorkflowActionConfig>,
+    pub agent_call_config: Option<AgentCallActionConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -176,6 +197,7 @@ pub enum ActionType {
     ApiCall,
     KnowledgeBaseSearch,
     TriggerWorkflow,
+    AgentCall,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -205,6 +227,18 @@ pub struct TriggerWorkflowActionConfig {
     pub timeout_seconds: Option<u32>,
 }
 
+/// Sends one message to an agent in a new session of its own, so the
+/// exchange shows up in the agent's transcripts and counts against its budget.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentCallActionConfig {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub agent_id: i64,
+    pub message: String,
+    pub system_prompt: Option<String>,
+    /// Overrides the model named in the agent's configuration.
+    pub model: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConditionNodeConfig {
     pub condition_type: ConditionEvaluationType,
@@ -242,6 +276,8 @@ pub struct WorkflowExecution {
     pub id: i64,
     #[serde(with = "crate::utils::serde::i64_as_string")]
     pub workflow_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub workflow_version_id: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub status: ExecutionStatus,
@@ -253,7 +289,7 @@ pub struct WorkflowExecution {
     pub error_message: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ExecutionStatus {
     Pending,
     Running,
@@ -261,6 +297,8 @@ pub enum ExecutionStatus {
     Failed,
     Cancelled,
     Timeout,
+    /// Stopped between steps by a shutdown; picked up again on the next start.
+    Interrupted,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -268,6 +306,9 @@ pub struct ExecutionContext {
     pub variables: HashMap<String, serde_json::Value>,
     pub node_executions: Vec<NodeExecution>,
     pub current_node: Option<String>,
+    /// Nodes still queued when the run was interrupted.
+    #[serde(default)]
+    pub pending_nodes: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -315,17 +356,33 @@ impl From<String> for ExecutionStatus {
             "failed" => ExecutionStatus::Failed,
             "cancelled" => ExecutionStatus::Cancelled,
             "timeout" => ExecutionStatus::Timeout,
+            "interrupted" => ExecutionStatus::Interrupted,
             _ => ExecutionStatus::Pending,
         }
     }
 }
 
+impl From<ExecutionStatus> for String {
+    fn from(status: ExecutionStatus) -> Self {
+        match status {
+            ExecutionStatus::Pending => "pending".to_string(),
+            ExecutionStatus::Running => "running".to_string(),
+            ExecutionStatus::Completed => "completed".to_string(),
+            ExecutionStatus::Failed => "failed".to_string(),
+            ExecutionStatus::Cancelled => "cancelled".to_string(),
+            ExecutionStatus::Timeout => "timeout".to_string(),
+            ExecutionStatus::Interrupted => "interrupted".to_string(),
+        }
+    }
+}
+
 impl Default for ExecutionContext {
     fn default() -> Self {
         Self {
             variables: HashMap::new(),
             node_executions: Vec::new(),
             current_node: None,
+            pending_nodes: Vec::new(),
         }
     }
 }
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowTriggerType {
+    Schedule,
+    Webhook,
+}
+
+impl WorkflowTriggerType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkflowTriggerType::Schedule => "schedule",
+            WorkflowTriggerType::Webhook => "webhook",
+        }
+    }
+}
+
+impl From<String> for WorkflowTriggerType {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "schedule" => WorkflowTriggerType::Schedule,
+            _ => WorkflowTriggerType::Webhook,
+        }
+    }
+}
+
+/// Something that starts runs of a workflow without anyone asking: a cron
+/// schedule, or requests to the trigger's webhook URL.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AiWorkflowTrigger {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub workflow_id: i64,
+    pub trigger_type: WorkflowTriggerType,
+    /// Schedules only.
+    pub cron_expression: Option<String>,
+    /// The IANA time zone the schedule is read in, such as `Europe/Berlin`.
+    pub timezone: Option<String>,
+    pub last_fired_at: Option<DateTime<Utc>>,
+    /// Schedules only. `None` once the schedule has no more matching times.
+    pub next_fire_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub last_run_id: Option<i64>,
+    /// Why the last firing didn't start a run.
+    pub last_error: Option<String>,
+}
+
+/// A newly created trigger. A webhook trigger's URL holds its secret and is
+/// only ever shown here.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreatedAiWorkflowTrigger {
+    #[serde(flatten)]
+    pub trigger: AiWorkflowTrigger,
+    pub webhook_url: Option<String>,
+}
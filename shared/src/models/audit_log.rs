@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditLog {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub project_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub deployment_id: Option<i64>,
+    pub actor: Option<String>,
+    pub action: String,
+    pub resource_type: String,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub resource_id: i64,
+    pub metadata: serde_json::Value,
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthEventType {
+    SignUp,
+    SignIn,
+    SignOut,
+    PasswordReset,
+    InvitationSent,
+    OrganizationCreated,
+    WorkspaceCreated,
+}
+
+impl AuthEventType {
+    /// Value stored in the `event_type` column of `user_events`. The analytics
+    /// queries predate this enum and match on `signup` / `signin`.
+    pub fn as_event_type(&self) -> &'static str {
+        match self {
+            AuthEventType::SignUp => "signup",
+            AuthEventType::SignIn => "signin",
+            AuthEventType::SignOut => "signout",
+            AuthEventType::PasswordReset => "password_reset",
+            AuthEventType::InvitationSent => "invitation_sent",
+            AuthEventType::OrganizationCreated => "organization_created",
+            AuthEventType::WorkspaceCreated => "workspace_created",
+        }
+    }
+}
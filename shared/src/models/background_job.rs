@@ -0,0 +1,60 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundJobStatus {
+    /// Waiting for its `run_at`, either for the first time or to retry.
+    Queued,
+    /// Claimed by a worker. One that stays running past its `run_at` was
+    /// left behind by a process that stopped, and is picked up again.
+    Running,
+    Succeeded,
+    /// Failed on every attempt, and waits for a retry from an operator.
+    Dead,
+}
+
+impl BackgroundJobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackgroundJobStatus::Queued => "queued",
+            BackgroundJobStatus::Running => "running",
+            BackgroundJobStatus::Succeeded => "succeeded",
+            BackgroundJobStatus::Dead => "dead",
+        }
+    }
+}
+
+impl FromStr for BackgroundJobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(BackgroundJobStatus::Queued),
+            "running" => Ok(BackgroundJobStatus::Running),
+            "succeeded" => Ok(BackgroundJobStatus::Succeeded),
+            "dead" => Ok(BackgroundJobStatus::Dead),
+            _ => Err(format!("Invalid background job status: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundJob {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub job_type: String,
+    pub payload: serde_json::Value,
+    pub status: BackgroundJobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// When the job is next due, or for a running job, when its claim runs
+    /// out.
+    pub run_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    DarkModeSettings, DeploymentMode, DeploymentRestrictionsSignUpMode, EmailSettings, FirstFactor,
+    IndividualAuthSettings, LightModeSettings, MaintenanceBanner, OtpSettings, PhoneSettings,
+    SecondFactorPolicy, SocialConnectionProvider, UsernameSettings,
+};
+
+/// The password rules a sign-up form has to enforce.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ClientPasswordPolicy {
+    pub enabled: bool,
+    pub min_length: Option<u8>,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_number: bool,
+    pub require_special: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClientUiSettings {
+    pub app_name: String,
+    pub logo_image_url: String,
+    pub favicon_image_url: String,
+    pub tos_page_url: String,
+    pub privacy_policy_url: String,
+    pub sign_in_page_url: String,
+    pub sign_up_page_url: String,
+    pub signup_terms_statement: String,
+    pub signup_terms_statement_shown: bool,
+    pub light_mode_settings: LightModeSettings,
+    pub dark_mode_settings: DarkModeSettings,
+}
+
+/// What a frontend SDK needs to render sign-in and sign-up for a
+/// deployment. Nothing in it is secret: it is served to anyone holding the
+/// publishable key.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClientConfig {
+    pub mode: DeploymentMode,
+    pub frontend_host: String,
+    /// Enabled first factors, such as `email_password` or `passkey`.
+    pub first_factors: Vec<String>,
+    /// The first factor the sign-in UI leads with.
+    pub first_factor: Option<FirstFactor>,
+    /// Other first factors the sign-in UI offers, in order.
+    pub alternate_first_factors: Vec<FirstFactor>,
+    pub second_factor_policy: SecondFactorPolicy,
+    pub email_address: EmailSettings,
+    pub phone_number: PhoneSettings,
+    pub username: UsernameSettings,
+    pub first_name: IndividualAuthSettings,
+    pub last_name: IndividualAuthSettings,
+    pub password_policy: ClientPasswordPolicy,
+    pub otp: OtpSettings,
+    pub social_providers: Vec<SocialConnectionProvider>,
+    pub sign_up_mode: DeploymentRestrictionsSignUpMode,
+    pub ui: ClientUiSettings,
+    /// Maintenance that is on or scheduled, for SDKs to warn users about.
+    pub maintenance: Option<MaintenanceBanner>,
+    /// When any of the settings above last changed.
+    pub updated_at: DateTime<Utc>,
+}
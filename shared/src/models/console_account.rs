@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConsoleAccount {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub kind: String,
+    pub name: String,
+    pub email: String,
+    pub max_projects: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectTransfer {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub project_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub previous_owner_id: Option<i64>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub new_owner_id: i64,
+    pub transferred_at: DateTime<Utc>,
+}
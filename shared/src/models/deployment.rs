@@ -2,8 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use super::{
-    DeploymentAuthSettings, DeploymentB2bSettingsWithRoles, DeploymentRestrictions,
-    DeploymentUISettings,
+    DeploymentAuthSettings, DeploymentB2bSettingsWithRoles, DeploymentFeatureFlags,
+    DeploymentMaintenance, DeploymentRestrictions, DeploymentUISettings, TokenRevocationCheck,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -15,6 +15,112 @@ pub enum VerificationStatus {
     Failed,
 }
 
+impl From<String> for VerificationStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "in_progress" => VerificationStatus::InProgress,
+            "verified" => VerificationStatus::Verified,
+            "failed" => VerificationStatus::Failed,
+            _ => VerificationStatus::Pending,
+        }
+    }
+}
+
+impl VerificationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerificationStatus::Pending => "pending",
+            VerificationStatus::InProgress => "in_progress",
+            VerificationStatus::Verified => "verified",
+            VerificationStatus::Failed => "failed",
+        }
+    }
+
+    /// The status of a production deployment with these records: failed once
+    /// Cloudflare gives up on a certificate, verified when every record is,
+    /// in progress once any record has been checked, pending before that.
+    /// Staging deployments have no records to verify and are always verified.
+    pub fn from_records(
+        domain_verification_records: Option<&DomainVerificationRecords>,
+        email_verification_records: Option<&EmailVerificationRecords>,
+    ) -> Self {
+        let Some(domain_records) = domain_verification_records else {
+            return VerificationStatus::Pending;
+        };
+        let default_email_records = EmailVerificationRecords::default();
+        let email_records = email_verification_records.unwrap_or(&default_email_records);
+
+        if domain_records.ssl_status == Some(SslStatus::Failed) {
+            return VerificationStatus::Failed;
+        }
+        if domain_records.is_verified() && email_records.is_verified() {
+            return VerificationStatus::Verified;
+        }
+
+        let checked = domain_records
+            .cloudflare_verification
+            .iter()
+            .chain(&domain_records.custom_hostname_verification)
+            .chain(&email_records.dkim_records)
+            .chain(&email_records.return_path_records)
+            .any(|record| record.status.is_some() || record.verification_attempted_at.is_some());
+        if checked {
+            VerificationStatus::InProgress
+        } else {
+            VerificationStatus::Pending
+        }
+    }
+}
+
+/// Whether the external resources of a deployment (custom hostnames, the
+/// sending domain) have been created. Only production deployments have any.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvisioningStatus {
+    Provisioning,
+    #[default]
+    Ready,
+    Failed,
+}
+
+impl From<String> for ProvisioningStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "provisioning" => ProvisioningStatus::Provisioning,
+            "failed" => ProvisioningStatus::Failed,
+            _ => ProvisioningStatus::Ready,
+        }
+    }
+}
+
+impl ProvisioningStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProvisioningStatus::Provisioning => "provisioning",
+            ProvisioningStatus::Ready => "ready",
+            ProvisioningStatus::Failed => "failed",
+        }
+    }
+}
+
+/// What the last check of a DNS record found. `Missing` and `WrongValue`
+/// need a DNS change; `TimedOut` and `LookupFailed` only need another try.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsRecordStatus {
+    Verified,
+    /// The record is in place but Cloudflare hasn't confirmed the hostname
+    /// yet.
+    Pending,
+    /// The name doesn't exist (NXDOMAIN) or has no record of this type.
+    Missing,
+    /// Records exist, but none has the expected value.
+    WrongValue,
+    TimedOut,
+    /// The resolver answered with an error such as SERVFAIL.
+    LookupFailed,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DnsRecord {
     pub name: String,
@@ -23,6 +129,58 @@ pub struct DnsRecord {
     pub verified: bool,
     pub verification_attempted_at: Option<DateTime<Utc>>,
     pub last_verified_at: Option<DateTime<Utc>>,
+    /// `None` until the record is first checked.
+    #[serde(default)]
+    pub status: Option<DnsRecordStatus>,
+}
+
+/// How far Cloudflare has got with the TLS certificate of a custom hostname.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SslStatus {
+    /// Cloudflare hasn't yet validated that the hostname points at us.
+    PendingValidation,
+    /// Validated, and the certificate is being issued or deployed.
+    Issuing,
+    Active,
+    /// Issuance timed out or the certificate was lost; see the error.
+    Failed,
+}
+
+impl SslStatus {
+    /// Maps the `ssl.status` of a Cloudflare custom hostname, which has many
+    /// more states than the console shows.
+    pub fn from_cloudflare(status: &str) -> Self {
+        match status {
+            "active" => SslStatus::Active,
+            "initializing" | "pending_validation" => SslStatus::PendingValidation,
+            "expired" | "deleted" | "inactive" | "deactivating" | "pending_deletion" => {
+                SslStatus::Failed
+            }
+            status if status.ends_with("_timed_out") => SslStatus::Failed,
+            _ => SslStatus::Issuing,
+        }
+    }
+
+    /// Orders statuses from furthest behind to done, so the status of a
+    /// deployment is that of its least advanced hostname.
+    fn rank(self) -> u8 {
+        match self {
+            SslStatus::Failed => 0,
+            SslStatus::PendingValidation => 1,
+            SslStatus::Issuing => 2,
+            SslStatus::Active => 3,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HostnameSslStatus {
+    pub status: SslStatus,
+    /// The status as Cloudflare reported it.
+    pub cloudflare_status: String,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -31,6 +189,31 @@ pub struct DomainVerificationRecords {
     pub custom_hostname_verification: Vec<DnsRecord>,
     pub frontend_hostname_id: Option<String>,
     pub backend_hostname_id: Option<String>,
+    #[serde(default)]
+    pub frontend_ssl: Option<HostnameSslStatus>,
+    #[serde(default)]
+    pub backend_ssl: Option<HostnameSslStatus>,
+    /// The certificate status of the deployment as a whole: that of its least
+    /// advanced hostname, or `None` until the hostnames are first checked.
+    #[serde(default)]
+    pub ssl_status: Option<SslStatus>,
+}
+
+impl DomainVerificationRecords {
+    pub fn is_verified(&self) -> bool {
+        self.cloudflare_verification
+            .iter()
+            .chain(&self.custom_hostname_verification)
+            .all(|record| record.verified)
+    }
+
+    pub fn update_ssl_status(&mut self) {
+        self.ssl_status = [&self.frontend_ssl, &self.backend_ssl]
+            .into_iter()
+            .flatten()
+            .map(|ssl| ssl.status)
+            .min_by_key(|status| status.rank());
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -40,6 +223,44 @@ pub struct EmailVerificationRecords {
     pub postmark_domain_id: Option<i64>,
 }
 
+impl EmailVerificationRecords {
+    pub fn is_verified(&self) -> bool {
+        self.dkim_records
+            .iter()
+            .chain(&self.return_path_records)
+            .all(|record| record.verified)
+    }
+
+    /// Keeps what was last checked of the records that are the same in
+    /// `previous`, so records regenerated after a DKIM rotation only start
+    /// over for the key that changed.
+    pub fn with_checks_from(mut self, previous: &EmailVerificationRecords) -> Self {
+        let previous_records: Vec<&DnsRecord> = previous
+            .dkim_records
+            .iter()
+            .chain(&previous.return_path_records)
+            .collect();
+
+        for record in self
+            .dkim_records
+            .iter_mut()
+            .chain(self.return_path_records.iter_mut())
+        {
+            if let Some(checked) = previous_records
+                .iter()
+                .find(|checked| checked.name == record.name && checked.value == record.value)
+            {
+                record.verified = record.verified || checked.verified;
+                record.verification_attempted_at = checked.verification_attempted_at;
+                record.last_verified_at = checked.last_verified_at;
+                record.status = checked.status;
+            }
+        }
+
+        self
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum DeploymentMode {
@@ -72,6 +293,8 @@ pub struct Deployment {
     pub project_id: i64,
     pub mode: DeploymentMode,
     pub verification_status: Option<VerificationStatus>,
+    #[serde(default)]
+    pub provisioning_status: ProvisioningStatus,
     pub domain_verification_records: Option<DomainVerificationRecords>,
     pub email_verification_records: Option<EmailVerificationRecords>,
 }
@@ -89,10 +312,21 @@ pub struct DeploymentWithSettings {
     pub publishable_key: String,
     pub mode: DeploymentMode,
     pub verification_status: Option<VerificationStatus>,
+    #[serde(default)]
+    pub provisioning_status: ProvisioningStatus,
     pub auth_settings: Option<DeploymentAuthSettings>,
     pub ui_settings: Option<DeploymentUISettings>,
     pub b2b_settings: Option<DeploymentB2bSettingsWithRoles>,
     pub restrictions: Option<DeploymentRestrictions>,
     pub domain_verification_records: Option<DomainVerificationRecords>,
     pub email_verification_records: Option<EmailVerificationRecords>,
+    /// Every feature flag with the value this deployment gets.
+    #[serde(default)]
+    pub feature_flags: DeploymentFeatureFlags,
+    #[serde(default)]
+    pub maintenance: DeploymentMaintenance,
+    /// Where services verifying the deployment's session tokens check for
+    /// revoked ones.
+    #[serde(default)]
+    pub token_revocation: TokenRevocationCheck,
 }
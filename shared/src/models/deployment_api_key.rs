@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Every scope an API key can be granted. A `:write` scope also grants the
+/// matching `:read` scope. `secrets:read` stands on its own: settings scopes
+/// only ever see stored secrets masked.
+pub const API_KEY_SCOPES: &[&str] = &[
+    "users:read",
+    "users:write",
+    "orgs:read",
+    "orgs:write",
+    "settings:read",
+    "settings:write",
+    "ai:read",
+    "ai:write",
+    SECRETS_READ_SCOPE,
+];
+
+/// Needed on top of the route's scope to reveal stored secrets in full.
+pub const SECRETS_READ_SCOPE: &str = "secrets:read";
+
+/// A server-side credential for a deployment's API. Only a hash of the
+/// secret is stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentApiKey {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub name: String,
+    /// The first characters of the secret, so customers can tell keys apart.
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_by: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl DeploymentApiKey {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| {
+            granted == scope
+                || scope.strip_suffix(":read").is_some_and(|resource| {
+                    granted
+                        .strip_suffix(":write")
+                        .is_some_and(|granted| granted == resource)
+                })
+        })
+    }
+}
+
+/// Returned once, when the key is created. The secret can't be read back.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreatedDeploymentApiKey {
+    #[serde(flatten)]
+    pub key: DeploymentApiKey,
+    pub secret: String,
+}
@@ -169,6 +169,9 @@ pub struct PasswordSettings {
     pub require_uppercase: Option<bool>,
     pub require_number: Option<bool>,
     pub require_special: Option<bool>,
+    /// Rejects passwords that show up in known data breaches.
+    #[serde(default)]
+    pub disallow_compromised: Option<bool>,
 }
 
 impl Default for PasswordSettings {
@@ -180,6 +183,7 @@ impl Default for PasswordSettings {
             require_uppercase: Some(true),
             require_number: Some(true),
             require_special: Some(true),
+            disallow_compromised: Some(true),
         }
     }
 }
@@ -261,6 +265,10 @@ pub struct UsernameSettings {
     pub required: bool,
     pub min_length: Option<u8>,
     pub max_length: Option<u8>,
+    /// Names no user may take, on top of the platform's own list and the
+    /// deployment's app name. Compared case-insensitively.
+    #[serde(default)]
+    pub reserved_names: Vec<String>,
 }
 
 impl Default for UsernameSettings {
@@ -270,14 +278,20 @@ impl Default for UsernameSettings {
             required: false,
             min_length: Some(3),
             max_length: Some(30),
+            reserved_names: Vec::new(),
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct EmailLinkSettings {
     pub enabled: bool,
     pub require_same_device: bool,
+    /// How long an emailed sign-in link works for.
+    pub expiry_minutes: i64,
+    /// Whether requesting a new link stops the earlier unused ones working.
+    pub invalidate_previous: bool,
 }
 
 impl Default for EmailLinkSettings {
@@ -285,6 +299,8 @@ impl Default for EmailLinkSettings {
         Self {
             enabled: true,
             require_same_device: false,
+            expiry_minutes: 15,
+            invalidate_previous: true,
         }
     }
 }
@@ -321,6 +337,73 @@ impl Default for MultiSessionSupport {
     }
 }
 
+/// Brute-force protection for sign-ins. Durations are in seconds, like the
+/// session settings.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockoutPolicy {
+    pub enabled: bool,
+    /// Failed attempts allowed within `counter_window` before a lockout.
+    pub max_failed_attempts: i64,
+    pub lockout_duration: i64,
+    /// How long failed attempts are counted for, starting at the first one.
+    pub counter_window: i64,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_failed_attempts: 5,
+            lockout_duration: 15 * 60,
+            counter_window: 15 * 60,
+        }
+    }
+}
+
+/// How the one-time codes sent to email addresses and phone numbers work.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OtpSettings {
+    /// Digits in a code, from 4 to 10.
+    pub code_length: u8,
+    pub expiry_minutes: i64,
+    /// Wrong codes allowed before the code stops working and a new one has
+    /// to be sent.
+    pub max_attempts: i64,
+    /// How long after sending a code to an identifier another can be sent.
+    pub resend_cooldown_seconds: i64,
+}
+
+impl Default for OtpSettings {
+    fn default() -> Self {
+        Self {
+            code_length: 6,
+            expiry_minutes: 10,
+            max_attempts: 5,
+            resend_cooldown_seconds: 30,
+        }
+    }
+}
+
+/// What happens to a deleted user. They're kept, hidden, for
+/// `retention_days` so the deletion can be undone, and purged after.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UserDeletionPolicy {
+    pub retention_days: i64,
+    /// Frees a deleted user's username and email addresses for other users
+    /// straight away rather than at the purge. Restoring the user then fails
+    /// if someone has taken one of them since.
+    pub release_identifiers_immediately: bool,
+}
+
+impl Default for UserDeletionPolicy {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+            release_identifiers_immediately: false,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DeploymentAuthSettings {
     #[serde(with = "crate::utils::serde::i64_as_string")]
@@ -339,10 +422,19 @@ pub struct DeploymentAuthSettings {
     pub verification_policy: VerificationPolicy,
     pub second_factor_policy: SecondFactorPolicy,
     pub first_factor: FirstFactor,
+    /// Other first factors the sign-in UI offers, in the order it offers them.
+    #[serde(default)]
+    pub alternate_first_factors: Vec<FirstFactor>,
     pub multi_session_support: MultiSessionSupport,
     pub session_token_lifetime: i64,
     pub session_validity_period: i64,
     pub session_inactive_timeout: i64,
+    #[serde(default)]
+    pub lockout_policy: LockoutPolicy,
+    #[serde(default)]
+    pub user_deletion_policy: UserDeletionPolicy,
+    #[serde(default)]
+    pub otp: OtpSettings,
     pub deployment_id: i64,
 }
 
@@ -364,15 +456,39 @@ impl Default for DeploymentAuthSettings {
             session_token_lifetime: 30 * 60,
             session_validity_period: 30 * 24 * 60 * 60,
             session_inactive_timeout: 7 * 60 * 60 * 24,
+            lockout_policy: LockoutPolicy::default(),
+            user_deletion_policy: UserDeletionPolicy::default(),
+            otp: OtpSettings::default(),
             auth_factors_enabled: AuthFactorsEnabled::default(),
             verification_policy: VerificationPolicy::default(),
             second_factor_policy: SecondFactorPolicy::Optional,
             first_factor: FirstFactor::EmailPassword,
+            alternate_first_factors: Vec::new(),
             deployment_id: 0,
         }
     }
 }
 
+impl DeploymentAuthSettings {
+    /// Whether users can sign in with `factor`: both the identifier it uses
+    /// and the factor itself are enabled, and so are passwords if it needs
+    /// one.
+    pub fn first_factor_available(&self, factor: &FirstFactor) -> bool {
+        let factors = &self.auth_factors_enabled;
+        match factor {
+            FirstFactor::EmailPassword => {
+                self.email_address.enabled && factors.email_password && self.password.enabled
+            }
+            FirstFactor::UsernamePassword => {
+                self.username.enabled && factors.username_password && self.password.enabled
+            }
+            FirstFactor::EmailOtp => self.email_address.enabled && factors.email_otp,
+            FirstFactor::EmailMagicLink => self.email_address.enabled && factors.email_magic_link,
+            FirstFactor::PhoneOtp => self.phone_number.enabled && factors.phone_otp,
+        }
+    }
+}
+
 impl AuthFactorsEnabled {
     pub fn with_email(mut self, enabled: bool) -> Self {
         self.email_password = enabled;
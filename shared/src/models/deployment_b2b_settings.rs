@@ -19,6 +19,9 @@ pub struct DeploymentB2bSettings {
     pub max_allowed_workspace_members: i64,
     pub allow_org_deletion: bool,
     pub allow_workspace_deletion: bool,
+    /// Keeps an organization's last workspace from being deleted.
+    #[serde(default)]
+    pub prevent_last_workspace_deletion: bool,
     pub custom_org_role_enabled: bool,
     pub limit_org_creation_per_user: bool,
     pub limit_workspace_creation_per_org: bool,
@@ -45,6 +48,7 @@ impl Default for DeploymentB2bSettings {
             max_allowed_workspace_members: 100,
             allow_org_deletion: false,
             allow_workspace_deletion: false,
+            prevent_last_workspace_deletion: false,
             limit_org_creation_per_user: false,
             limit_workspace_creation_per_org: false,
             org_creation_per_user_count: 0,
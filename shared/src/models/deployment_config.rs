@@ -0,0 +1,290 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::Deployment;
+
+/// Bumped whenever a bundle field is added, removed or changes meaning.
+pub const DEPLOYMENT_CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// A per-deployment settings table that is exported as a single object.
+pub(crate) struct ConfigSection {
+    pub name: &'static str,
+    pub table: &'static str,
+    pub fields: &'static [&'static str],
+    pub frontend_url_fields: &'static [&'static str],
+}
+
+pub(crate) const AUTH_SETTINGS_FIELDS: &[&str] = &[
+    "email_address",
+    "phone_number",
+    "username",
+    "first_factor",
+    "first_name",
+    "last_name",
+    "password",
+    "auth_factors_enabled",
+    "verification_policy",
+    "second_factor_policy",
+    "passkey",
+    "magic_link",
+    "multi_session_support",
+    "session_token_lifetime",
+    "session_validity_period",
+    "session_inactive_timeout",
+    "lockout_policy",
+    "user_deletion_policy",
+];
+
+pub(crate) const RESTRICTIONS_FIELDS: &[&str] = &[
+    "allowlist_enabled",
+    "blocklist_enabled",
+    "block_subaddresses",
+    "block_disposable_emails",
+    "block_voip_numbers",
+    "country_restrictions",
+    "banned_keywords",
+    "allowlisted_resources",
+    "blocklisted_resources",
+    "sign_up_mode",
+];
+
+pub(crate) const SMS_TEMPLATE_FIELDS: &[&str] = &[
+    "reset_password_code_template",
+    "verification_code_template",
+    "password_change_template",
+    "password_remove_template",
+];
+
+pub(crate) const EMAIL_TEMPLATE_FIELDS: &[&str] = &[
+    "organization_invite_template",
+    "verification_code_template",
+    "reset_password_code_template",
+    "primary_email_change_template",
+    "password_change_template",
+    "password_remove_template",
+    "sign_in_from_new_device_template",
+    "magic_link_template",
+    "waitlist_signup_template",
+    "waitlist_invite_template",
+    "workspace_invite_template",
+];
+
+/// UI settings that don't depend on where the deployment is hosted.
+pub(crate) const UI_SETTINGS_FIELDS: &[&str] = &[
+    "app_name",
+    "tos_page_url",
+    "favicon_image_url",
+    "logo_image_url",
+    "privacy_policy_url",
+    "signup_terms_statement",
+    "signup_terms_statement_shown",
+    "light_mode_settings",
+    "dark_mode_settings",
+    "use_initials_for_user_profile_image",
+    "use_initials_for_organization_profile_image",
+    "default_user_profile_image_url",
+    "default_organization_profile_image_url",
+];
+
+/// UI settings that usually point at the deployment's own frontend host and
+/// need rewriting when configuration moves to another deployment.
+pub(crate) const UI_SETTINGS_FRONTEND_URL_FIELDS: &[&str] = &[
+    "sign_in_page_url",
+    "sign_up_page_url",
+    "after_sign_out_one_page_url",
+    "after_sign_out_all_page_url",
+    "after_logo_click_url",
+    "organization_profile_url",
+    "create_organization_url",
+    "user_profile_url",
+    "after_signup_redirect_url",
+    "after_signin_redirect_url",
+    "after_create_organization_redirect_url",
+    "waitlist_page_url",
+];
+
+/// B2B settings without the default role ids, which only mean something inside
+/// the deployment they were created for.
+pub(crate) const B2B_SETTINGS_FIELDS: &[&str] = &[
+    "organizations_enabled",
+    "workspaces_enabled",
+    "ip_allowlist_per_org_enabled",
+    "max_allowed_org_members",
+    "max_allowed_workspace_members",
+    "allow_org_deletion",
+    "allow_workspace_deletion",
+    "prevent_last_workspace_deletion",
+    "custom_org_role_enabled",
+    "custom_workspace_role_enabled",
+    "limit_org_creation_per_user",
+    "limit_workspace_creation_per_org",
+    "org_creation_per_user_count",
+    "workspaces_per_org_count",
+    "allow_users_to_create_orgs",
+    "max_orgs_per_user",
+];
+
+pub(crate) const JWT_TEMPLATE_FIELDS: &[&str] = &[
+    "name",
+    "token_lifetime",
+    "allowed_clock_skew",
+    "custom_signing_key",
+    "template",
+];
+
+pub(crate) const SOCIAL_CONNECTION_FIELDS: &[&str] = &["provider", "enabled", "credentials"];
+
+pub(crate) const CONFIG_SECTIONS: [ConfigSection; 6] = [
+    ConfigSection {
+        name: "auth_settings",
+        table: "deployment_auth_settings",
+        fields: AUTH_SETTINGS_FIELDS,
+        frontend_url_fields: &[],
+    },
+    ConfigSection {
+        name: "ui_settings",
+        table: "deployment_ui_settings",
+        fields: UI_SETTINGS_FIELDS,
+        frontend_url_fields: UI_SETTINGS_FRONTEND_URL_FIELDS,
+    },
+    ConfigSection {
+        name: "b2b_settings",
+        table: "deployment_b2b_settings",
+        fields: B2B_SETTINGS_FIELDS,
+        frontend_url_fields: &[],
+    },
+    ConfigSection {
+        name: "restrictions",
+        table: "deployment_restrictions",
+        fields: RESTRICTIONS_FIELDS,
+        frontend_url_fields: &[],
+    },
+    ConfigSection {
+        name: "email_templates",
+        table: "deployment_email_templates",
+        fields: EMAIL_TEMPLATE_FIELDS,
+        frontend_url_fields: &[],
+    },
+    ConfigSection {
+        name: "sms_templates",
+        table: "deployment_sms_templates",
+        fields: SMS_TEMPLATE_FIELDS,
+        frontend_url_fields: &[],
+    },
+];
+
+impl ConfigSection {
+    pub fn all_fields(&self) -> Vec<&'static str> {
+        self.fields
+            .iter()
+            .chain(self.frontend_url_fields)
+            .copied()
+            .collect()
+    }
+}
+
+/// A deployment's configuration in a form that can be imported into another
+/// deployment, possibly on another platform environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeploymentConfigBundle {
+    pub schema_version: u32,
+    pub exported_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub source_deployment_id: i64,
+    pub source_frontend_host: String,
+    pub secrets_included: bool,
+    pub auth_settings: Option<Map<String, Value>>,
+    pub ui_settings: Option<Map<String, Value>>,
+    pub b2b_settings: Option<Map<String, Value>>,
+    pub restrictions: Option<Map<String, Value>>,
+    pub email_templates: Option<Map<String, Value>>,
+    pub sms_templates: Option<Map<String, Value>>,
+    /// Matched against the target's templates by name.
+    pub jwt_templates: Vec<Map<String, Value>>,
+    /// Matched against the target's connections by provider.
+    pub social_connections: Vec<Map<String, Value>>,
+}
+
+impl DeploymentConfigBundle {
+    pub(crate) fn section(&self, name: &str) -> Option<&Map<String, Value>> {
+        match name {
+            "auth_settings" => self.auth_settings.as_ref(),
+            "ui_settings" => self.ui_settings.as_ref(),
+            "b2b_settings" => self.b2b_settings.as_ref(),
+            "restrictions" => self.restrictions.as_ref(),
+            "email_templates" => self.email_templates.as_ref(),
+            "sms_templates" => self.sms_templates.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn section_mut(&mut self, name: &str) -> Option<&mut Option<Map<String, Value>>> {
+        match name {
+            "auth_settings" => Some(&mut self.auth_settings),
+            "ui_settings" => Some(&mut self.ui_settings),
+            "b2b_settings" => Some(&mut self.b2b_settings),
+            "restrictions" => Some(&mut self.restrictions),
+            "email_templates" => Some(&mut self.email_templates),
+            "sms_templates" => Some(&mut self.sms_templates),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// One difference between a bundle and the deployment it's imported into.
+/// `key` is the field name for settings sections, the template name for JWT
+/// templates and the provider for social connections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigChange {
+    pub section: String,
+    pub key: String,
+    pub action: ConfigChangeAction,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentConfigImportResult {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub changes: Vec<ConfigChange>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PromotionSectionStatus {
+    /// The section was copied and changed something on the new deployment.
+    Copied,
+    /// The new deployment's defaults already matched.
+    Unchanged,
+    /// The staging deployment has nothing to copy in this section.
+    Skipped,
+    Failed,
+}
+
+/// What happened to one configuration section during a promotion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromotionSectionReport {
+    pub section: String,
+    pub status: PromotionSectionStatus,
+    pub changes: usize,
+    pub note: Option<String>,
+}
+
+/// The production deployment created from a staging deployment and how each
+/// section of the staging configuration was carried over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentPromotion {
+    pub deployment: Deployment,
+    pub sections: Vec<PromotionSectionReport>,
+}
@@ -1,6 +1,44 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::{OrganizationPermission, OrganizationRole, WorkspacePermission, WorkspaceRole};
+
+/// Every permission an organization role can grant. Role commands reject
+/// anything else, so a typo can't silently create a permission nobody checks.
+pub const ORGANIZATION_PERMISSIONS: &[&str] = &[
+    "organization:admin",
+    "organization:member",
+    "org:read",
+    "org:update",
+    "org:delete",
+    "org:members:read",
+    "org:members:invite",
+    "org:members:remove",
+    "org:members:update_role",
+    "org:roles:read",
+    "org:roles:manage",
+    "org:workspaces:read",
+    "org:workspaces:create",
+    "org:workspaces:delete",
+    "org:billing:read",
+    "org:billing:manage",
+];
+
+/// Every permission a workspace role can grant.
+pub const WORKSPACE_PERMISSIONS: &[&str] = &[
+    "workspace:admin",
+    "workspace:member",
+    "workspace:read",
+    "workspace:update",
+    "workspace:delete",
+    "workspace:members:read",
+    "workspace:members:invite",
+    "workspace:members:remove",
+    "workspace:members:update_role",
+    "workspace:roles:read",
+    "workspace:roles:manage",
+];
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DeploymentWorkspaceRole {
     #[serde(with = "crate::utils::serde::i64_as_string")]
@@ -83,3 +121,105 @@ impl DeploymentOrganizationRole {
         }
     }
 }
+
+impl From<DeploymentOrganizationRole> for OrganizationRole {
+    fn from(role: DeploymentOrganizationRole) -> Self {
+        Self {
+            id: role.id,
+            created_at: role.created_at,
+            updated_at: role.updated_at,
+            permissions: role
+                .permissions
+                .into_iter()
+                .enumerate()
+                .map(|(i, permission)| OrganizationPermission {
+                    id: i as i64,
+                    created_at: role.created_at,
+                    updated_at: role.updated_at,
+                    org_role_id: role.id,
+                    permission,
+                })
+                .collect(),
+            name: role.name,
+        }
+    }
+}
+
+impl From<DeploymentWorkspaceRole> for WorkspaceRole {
+    fn from(role: DeploymentWorkspaceRole) -> Self {
+        Self {
+            id: role.id,
+            created_at: role.created_at,
+            updated_at: role.updated_at,
+            permissions: role
+                .permissions
+                .into_iter()
+                .enumerate()
+                .map(|(i, permission)| WorkspacePermission {
+                    id: i as i64,
+                    created_at: role.created_at,
+                    updated_at: role.updated_at,
+                    workspace_role_id: role.id,
+                    permission,
+                })
+                .collect(),
+            name: role.name,
+        }
+    }
+}
+
+/// A role with how many memberships hold it, for the console's role list.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrganizationRoleUsage {
+    #[serde(flatten)]
+    pub role: DeploymentOrganizationRole,
+    pub member_count: i64,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceRoleUsage {
+    #[serde(flatten)]
+    pub role: DeploymentWorkspaceRole,
+    pub member_count: i64,
+    pub is_default: bool,
+}
+
+/// How a change to a role's permissions reaches one membership holding it.
+/// A member only gains or loses what none of their other roles in the
+/// organization already grant.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RolePermissionImpact {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub membership_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub organization_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub user_id: i64,
+    pub email_address: Option<String>,
+    pub gained_permissions: Vec<String>,
+    pub lost_permissions: Vec<String>,
+}
+
+/// Which members a change to a role's permissions would reach.
+/// `affected_members` lists at most `MAX_LISTED_AFFECTED_MEMBERS` of them,
+/// while `affected_member_count` counts them all.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RolePermissionChangeReport {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub role_id: i64,
+    pub added_permissions: Vec<String>,
+    pub removed_permissions: Vec<String>,
+    pub affected_member_count: i64,
+    pub affected_members: Vec<RolePermissionImpact>,
+}
+
+/// An updated organization role and the members whose permissions changed
+/// with it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrganizationRoleUpdate {
+    #[serde(flatten)]
+    pub role: DeploymentOrganizationRole,
+    pub affected_member_count: i64,
+    pub affected_members: Vec<RolePermissionImpact>,
+}
@@ -0,0 +1,66 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataExportStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    /// The archive was deleted after its retention ran out.
+    Expired,
+}
+
+impl DataExportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataExportStatus::Pending => "pending",
+            DataExportStatus::Running => "running",
+            DataExportStatus::Completed => "completed",
+            DataExportStatus::Failed => "failed",
+            DataExportStatus::Expired => "expired",
+        }
+    }
+}
+
+impl FromStr for DataExportStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DataExportStatus::Pending),
+            "running" => Ok(DataExportStatus::Running),
+            "completed" => Ok(DataExportStatus::Completed),
+            "failed" => Ok(DataExportStatus::Failed),
+            "expired" => Ok(DataExportStatus::Expired),
+            _ => Err(format!("Invalid export status: {}", s)),
+        }
+    }
+}
+
+/// An archive of a deployment's end-user data: one newline-delimited JSON
+/// file per kind of record, zipped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentDataExport {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub status: DataExportStatus,
+    pub requested_by: Option<String>,
+    pub size_bytes: Option<i64>,
+    /// Records written to each file, by file name.
+    pub record_counts: Option<BTreeMap<String, i64>>,
+    pub error: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When the archive is deleted.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// A signed link to the archive, set on completed exports.
+    pub download_url: Option<String>,
+    pub download_url_expires_at: Option<DateTime<Utc>>,
+}
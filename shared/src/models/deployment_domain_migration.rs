@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{DeploymentProvisioningAction, DomainVerificationRecords, EmailVerificationRecords};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DomainMigrationStatus {
+    /// The new hostnames and sending domain are still being created.
+    Provisioning,
+    /// Everything exists and is waiting on the customer's DNS.
+    Verifying,
+    /// The deployment now serves from the new domain.
+    Completed,
+    Aborted,
+    /// An action ran out of attempts. Aborting cleans up what was created.
+    Failed,
+}
+
+impl DomainMigrationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DomainMigrationStatus::Provisioning => "provisioning",
+            DomainMigrationStatus::Verifying => "verifying",
+            DomainMigrationStatus::Completed => "completed",
+            DomainMigrationStatus::Aborted => "aborted",
+            DomainMigrationStatus::Failed => "failed",
+        }
+    }
+
+    /// Whether the migration still holds the deployment's one migration slot.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            DomainMigrationStatus::Provisioning
+                | DomainMigrationStatus::Verifying
+                | DomainMigrationStatus::Failed
+        )
+    }
+}
+
+impl From<String> for DomainMigrationStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "verifying" => DomainMigrationStatus::Verifying,
+            "completed" => DomainMigrationStatus::Completed,
+            "aborted" => DomainMigrationStatus::Aborted,
+            "failed" => DomainMigrationStatus::Failed,
+            _ => DomainMigrationStatus::Provisioning,
+        }
+    }
+}
+
+/// A production deployment moving to another custom domain. The `old_*`
+/// fields are what the deployment had when the migration started.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentDomainMigration {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub status: DomainMigrationStatus,
+    pub old_frontend_host: String,
+    pub old_backend_host: String,
+    pub old_mail_from_host: String,
+    pub old_publishable_key: String,
+    pub old_domain_verification_records: Option<DomainVerificationRecords>,
+    pub old_email_verification_records: Option<EmailVerificationRecords>,
+    pub new_frontend_host: String,
+    pub new_backend_host: String,
+    pub new_mail_from_host: String,
+    pub new_domain_verification_records: Option<DomainVerificationRecords>,
+    pub new_email_verification_records: Option<EmailVerificationRecords>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub actions: Vec<DeploymentProvisioningAction>,
+}
@@ -0,0 +1,129 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailProviderType {
+    Postmark,
+    Ses,
+    Smtp,
+}
+
+impl EmailProviderType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailProviderType::Postmark => "postmark",
+            EmailProviderType::Ses => "ses",
+            EmailProviderType::Smtp => "smtp",
+        }
+    }
+}
+
+impl From<String> for EmailProviderType {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "ses" => EmailProviderType::Ses,
+            "smtp" => EmailProviderType::Smtp,
+            _ => EmailProviderType::Postmark,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct PostmarkCredentials {
+    pub server_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct SesCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub region: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// TLS from the first byte, usually on port 465.
+    Tls,
+    /// A plain connection upgraded with STARTTLS, usually on port 587.
+    #[default]
+    StartTls,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct SmtpCredentials {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+}
+
+/// Everything needed to send through a customer's own provider. Stored
+/// encrypted, and never returned by the API.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum EmailProviderCredentials {
+    Postmark(PostmarkCredentials),
+    Ses(SesCredentials),
+    Smtp(SmtpCredentials),
+}
+
+impl EmailProviderCredentials {
+    pub fn provider(&self) -> EmailProviderType {
+        match self {
+            EmailProviderCredentials::Postmark(_) => EmailProviderType::Postmark,
+            EmailProviderCredentials::Ses(_) => EmailProviderType::Ses,
+            EmailProviderCredentials::Smtp(_) => EmailProviderType::Smtp,
+        }
+    }
+
+    /// The parts that are safe to show, so the console can tell which
+    /// account or server is configured.
+    pub fn settings(&self) -> Value {
+        match self {
+            EmailProviderCredentials::Postmark(_) => json!({}),
+            EmailProviderCredentials::Ses(ses) => json!({
+                "access_key_id": ses.access_key_id,
+                "region": ses.region,
+            }),
+            EmailProviderCredentials::Smtp(smtp) => json!({
+                "host": smtp.host,
+                "port": smtp.port,
+                "username": smtp.username,
+                "security": smtp.security,
+            }),
+        }
+    }
+}
+
+/// A deployment's own email provider. Emails go through the platform's
+/// provider when a deployment has none.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentEmailProvider {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub provider: EmailProviderType,
+    /// Sent as the From address of every email instead of the template's
+    /// sender at the deployment's mail host.
+    pub from_address: Option<String>,
+    pub settings: Value,
+    pub verified_at: DateTime<Utc>,
+}
+
+/// What a test email was sent through.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TestEmailResult {
+    /// `None` when the deployment uses the platform's provider.
+    pub provider: Option<EmailProviderType>,
+    pub from_address: String,
+    pub to_address: String,
+    pub message_id: String,
+}
@@ -47,7 +47,7 @@ impl Default for DeploymentEmailTemplate {
             <div style="background-color: #ffffff; border-radius: 0px; padding: 32px 32px 48px 32px; margin: 0; text-align: left; box-shadow: 0px 1px 3px rgba(0, 0, 0, 0.1);">
                 <h1 style="color: #000000; text-align: left; margin-top: 0; margin-bottom: 16px; font-weight: 500; font-size: 28px; line-height: 36px;">You're Invited to Join {{app_name}}</h1>
                 <p style="text-align: left; font-size: 16px; color: #000000; font-weight: normal; line-height: 26px;">
-                    You have been invited to join {{app_name}}. Click the button below to accept the invitation.
+                    {{#if inviter_name}}{{escapeURIs inviter_name}} has invited you to join {{organization_name}} on {{app_name}}.{{else}}You have been invited to join {{organization_name}} on {{app_name}}.{{/if}} Click the button below to accept the invitation.
                 </p>
                  <p style="margin-top: 16px; text-align: left; font-size: 16px; color: #000000; font-weight: normal; line-height: 26px;">
                     This invitation will expire in {{invitation.expires_in_days}} days.
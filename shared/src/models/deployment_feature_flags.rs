@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::DeploymentMode;
+
+/// The type of a flag's values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureFlagKind {
+    Boolean,
+    String,
+    Number,
+}
+
+impl FeatureFlagKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeatureFlagKind::Boolean => "boolean",
+            FeatureFlagKind::String => "string",
+            FeatureFlagKind::Number => "number",
+        }
+    }
+
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self {
+            FeatureFlagKind::Boolean => value.is_boolean(),
+            FeatureFlagKind::String => value.is_string(),
+            FeatureFlagKind::Number => value.is_number(),
+        }
+    }
+
+    /// Reads a value from text, such as a query string.
+    pub fn parse(&self, text: &str) -> Option<Value> {
+        match self {
+            FeatureFlagKind::Boolean => text.parse::<bool>().ok().map(Value::Bool),
+            FeatureFlagKind::String => Some(Value::String(text.to_string())),
+            FeatureFlagKind::Number => text
+                .parse::<i64>()
+                .map(|number| json!(number))
+                .or_else(|_| text.parse::<f64>().map(|number| json!(number)))
+                .ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(untagged)]
+pub enum FeatureFlagDefault {
+    Boolean(bool),
+    String(&'static str),
+    Number(i64),
+}
+
+/// A flag the platform knows about, with the value deployments get unless
+/// they override it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FeatureFlagDefinition {
+    pub name: &'static str,
+    pub kind: FeatureFlagKind,
+    pub default: FeatureFlagDefault,
+    pub description: &'static str,
+}
+
+impl FeatureFlagDefinition {
+    pub fn default_value(&self) -> Value {
+        json!(self.default)
+    }
+}
+
+/// The flags frontends can be given. Names not listed here are rejected on
+/// write.
+pub const FEATURE_FLAGS: &[FeatureFlagDefinition] = &[
+    FeatureFlagDefinition {
+        name: "redesigned_sign_in",
+        kind: FeatureFlagKind::Boolean,
+        default: FeatureFlagDefault::Boolean(false),
+        description: "Show the redesigned sign-in and sign-up screens",
+    },
+    FeatureFlagDefinition {
+        name: "passkey_autofill",
+        kind: FeatureFlagKind::Boolean,
+        default: FeatureFlagDefault::Boolean(false),
+        description: "Offer saved passkeys in the autofill of the identifier field",
+    },
+    FeatureFlagDefinition {
+        name: "social_buttons_layout",
+        kind: FeatureFlagKind::String,
+        default: FeatureFlagDefault::String("auto"),
+        description: "How social sign-in buttons are laid out: auto, icons or full",
+    },
+    FeatureFlagDefinition {
+        name: "otp_resend_cooldown_seconds",
+        kind: FeatureFlagKind::Number,
+        default: FeatureFlagDefault::Number(30),
+        description: "How long the UI waits before a code can be sent again",
+    },
+];
+
+pub fn feature_flag(name: &str) -> Option<&'static FeatureFlagDefinition> {
+    FEATURE_FLAGS.iter().find(|flag| flag.name == name)
+}
+
+/// Flag values by name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DeploymentFeatureFlags(pub BTreeMap<String, Value>);
+
+impl DeploymentFeatureFlags {
+    /// Reads stored overrides. Anything that isn't an object of flags reads
+    /// as no overrides.
+    pub fn from_stored(value: Value) -> Self {
+        serde_json::from_value(value).unwrap_or_default()
+    }
+
+    pub fn defaults() -> Self {
+        Self(
+            FEATURE_FLAGS
+                .iter()
+                .map(|flag| (flag.name.to_string(), flag.default_value()))
+                .collect(),
+        )
+    }
+
+    /// What a deployment gets: its overrides on top of the platform defaults.
+    /// Overrides of flags this version doesn't know are passed through, as a
+    /// newer version may have set them, while a known flag holding a value of
+    /// the wrong type keeps its default.
+    pub fn effective(overrides: &Self) -> Self {
+        let mut flags = Self::defaults();
+        for (name, value) in &overrides.0 {
+            match feature_flag(name) {
+                Some(flag) if !flag.kind.accepts(value) => {}
+                _ => {
+                    flags.0.insert(name.clone(), value.clone());
+                }
+            }
+        }
+        flags
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+}
+
+/// A deployment's flags as the console shows them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentFeatureFlagSettings {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    /// Every flag with the value the deployment gets.
+    pub flags: DeploymentFeatureFlags,
+    /// Only the flags set for this deployment.
+    pub overrides: DeploymentFeatureFlags,
+}
+
+/// A deployment a flag has a given value for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentFlagValue {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub project_id: i64,
+    pub mode: DeploymentMode,
+    pub frontend_host: String,
+    pub value: Value,
+    /// Whether the value is set for the deployment rather than the default.
+    pub overridden: bool,
+}
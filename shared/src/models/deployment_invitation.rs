@@ -11,4 +11,8 @@ pub struct DeploymentInvitation {
     pub last_name: String,
     pub email_address: String,
     pub expiry: DateTime<Utc>,
+    #[serde(default)]
+    pub accepted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
 }
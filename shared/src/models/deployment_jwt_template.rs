@@ -2,6 +2,9 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::UserDetails;
+use crate::{utils::secret_mask::mask_secret, validators::FieldViolation};
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CustomSigningKey {
     pub enabled: bool,
@@ -21,4 +24,89 @@ pub struct DeploymentJwtTemplate {
     pub deployment_id: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Whether a custom signing key is stored.
+    #[serde(default)]
+    pub has_secret: bool,
+}
+
+impl DeploymentJwtTemplate {
+    /// Sets `has_secret` from the signing key, for templates built from a row.
+    pub fn with_secret_flag(mut self) -> Self {
+        self.has_secret = self
+            .custom_signing_key
+            .as_ref()
+            .is_some_and(|key| !key.key.is_empty());
+        self
+    }
+
+    /// Replaces the custom signing key with its masked form.
+    pub fn redact(mut self) -> Self {
+        if let Some(key) = &mut self.custom_signing_key {
+            key.key = mask_secret(&key.key);
+        }
+        self
+    }
+}
+
+/// The user a template is rendered for, loaded from the deployment or made up
+/// in the request. Its fields are what `{{user.*}}` expressions read.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtTemplateUser {
+    pub id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub username: Option<String>,
+    pub primary_email_address: Option<String>,
+    pub primary_phone_number: Option<String>,
+    pub public_metadata: Value,
+    pub private_metadata: Value,
+    pub created_at: Option<DateTime<Utc>>,
+    /// The active organization, read by `{{org.*}}` expressions.
+    pub organization: Option<JwtTemplateOrganization>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtTemplateOrganization {
+    pub id: String,
+    pub name: String,
+    /// The names of the user's roles in it.
+    pub roles: Vec<String>,
+    pub public_metadata: Value,
+}
+
+impl From<UserDetails> for JwtTemplateUser {
+    fn from(user: UserDetails) -> Self {
+        Self {
+            id: user.id.to_string(),
+            first_name: user.first_name,
+            last_name: user.last_name,
+            username: user.username,
+            primary_email_address: user.primary_email_address,
+            primary_phone_number: user.primary_phone_number,
+            public_metadata: user.public_metadata,
+            private_metadata: user.private_metadata,
+            created_at: Some(user.created_at),
+            organization: None,
+        }
+    }
+}
+
+/// A token signed from a template, with its header and claims decoded.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RenderedJwtTemplate {
+    pub header: Value,
+    pub claims: Value,
+    pub token: String,
+}
+
+/// A stored template that doesn't pass validation, such as one saved before
+/// templates were checked.
+#[derive(Debug, Serialize, Clone)]
+pub struct FlaggedJwtTemplate {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub name: String,
+    pub errors: Vec<FieldViolation>,
 }
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// How exported logs are written: one JSON object per line, or CSV with a
+/// header row for tools that can't read JSON.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogExportFormat {
+    #[default]
+    Ndjson,
+    Csv,
+}
+
+impl LogExportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogExportFormat::Ndjson => "ndjson",
+            LogExportFormat::Csv => "csv",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            LogExportFormat::Ndjson => "application/x-ndjson",
+            LogExportFormat::Csv => "text/csv",
+        }
+    }
+}
+
+impl From<String> for LogExportFormat {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "csv" => LogExportFormat::Csv,
+            _ => LogExportFormat::Ndjson,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogExportSchedule {
+    Hourly,
+    #[default]
+    Daily,
+}
+
+impl LogExportSchedule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogExportSchedule::Hourly => "hourly",
+            LogExportSchedule::Daily => "daily",
+        }
+    }
+
+    /// The time between successful runs.
+    pub fn interval(&self) -> Duration {
+        match self {
+            LogExportSchedule::Hourly => Duration::hours(1),
+            LogExportSchedule::Daily => Duration::days(1),
+        }
+    }
+}
+
+impl From<String> for LogExportSchedule {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "hourly" => LogExportSchedule::Hourly,
+            _ => LogExportSchedule::Daily,
+        }
+    }
+}
+
+/// How the platform signs in to a customer's bucket. Stored encrypted, and
+/// never returned by the API.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogExportCredentials {
+    /// An IAM user's access key.
+    AccessKey {
+        access_key_id: String,
+        secret_access_key: String,
+    },
+    /// A role in the customer's account that the platform assumes. Its trust
+    /// policy has to ask for the deployment's [`LogExportExternalId`].
+    AssumeRole { role_arn: String },
+}
+
+impl LogExportCredentials {
+    /// The parts that are safe to show.
+    pub fn settings(&self) -> Value {
+        match self {
+            LogExportCredentials::AccessKey { access_key_id, .. } => json!({
+                "type": "access_key",
+                "access_key_id": access_key_id,
+            }),
+            LogExportCredentials::AssumeRole { role_arn } => json!({
+                "type": "assume_role",
+                "role_arn": role_arn,
+            }),
+        }
+    }
+}
+
+/// The external ID the platform passes when assuming the deployment's role.
+/// The platform generates it, one per deployment, and the role's trust
+/// policy has to require it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogExportExternalId {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub external_id: String,
+}
+
+/// Where and how often a deployment's audit and request logs are shipped to
+/// a bucket of its own. Each run picks up where the last successful one
+/// stopped, recorded by the `*_exported_through` watermarks.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentLogExport {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub bucket: String,
+    pub region: String,
+    pub prefix: String,
+    pub format: LogExportFormat,
+    pub schedule: LogExportSchedule,
+    pub credentials: Value,
+    pub audit_logs_exported_through: Option<DateTime<Utc>>,
+    pub request_logs_exported_through: Option<DateTime<Utc>>,
+    pub next_run_at: DateTime<Utc>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Failed runs since the last successful one. Failed runs are retried
+    /// sooner than the schedule.
+    pub consecutive_failures: i32,
+}
+
+/// A probe object was written to the bucket and deleted again.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogExportDestinationTest {
+    pub bucket: String,
+    pub probe_key: String,
+    pub tested_at: DateTime<Utc>,
+}
+
+/// What one export run shipped.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LogExportRun {
+    pub audit_logs_exported: i64,
+    pub request_logs_exported: i64,
+    /// The keys of the objects written.
+    pub objects: Vec<String>,
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+use super::EmailVerificationRecords;
+
+/// The host a production deployment sends email from, and while it is
+/// changing, the one it is moving to. Emails keep going out from
+/// `mail_from_host` until the pending host's records verify.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentMailSettings {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub mail_from_host: String,
+    pub email_verification_records: Option<EmailVerificationRecords>,
+    pub pending_mail_from_host: Option<String>,
+    pub pending_email_verification_records: Option<EmailVerificationRecords>,
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A deployment's maintenance settings. End-user traffic is refused while
+/// `maintenance_mode` is on, or during the scheduled window from `starts_at`
+/// until `ends_at`. A window without an end lasts until it's cleared.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct DeploymentMaintenance {
+    pub maintenance_mode: bool,
+    /// Shown to end users while maintenance is on, and ahead of it.
+    pub message: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    /// When maintenance is expected to end.
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+impl DeploymentMaintenance {
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.maintenance_mode
+            || self.starts_at.is_some_and(|starts_at| {
+                starts_at <= now && self.ends_at.is_none_or(|ends_at| now < ends_at)
+            })
+    }
+
+    /// When maintenance is expected to end, unless that time has passed.
+    pub fn estimated_end_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.ends_at.filter(|ends_at| *ends_at > now)
+    }
+
+    /// What SDKs show during maintenance and ahead of a scheduled window, or
+    /// `None` when there's neither.
+    pub fn banner_at(&self, now: DateTime<Utc>) -> Option<MaintenanceBanner> {
+        let active = self.is_active_at(now);
+        let upcoming = self.starts_at.is_some_and(|starts_at| starts_at > now);
+        if !active && !upcoming {
+            return None;
+        }
+
+        Some(MaintenanceBanner {
+            active,
+            message: self.message.clone(),
+            starts_at: self.starts_at,
+            estimated_end_at: self.estimated_end_at(now),
+        })
+    }
+}
+
+/// Maintenance that is on or scheduled, as published to frontend SDKs.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MaintenanceBanner {
+    /// Whether end-user requests are being refused right now.
+    pub active: bool,
+    pub message: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub estimated_end_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn a_scheduled_window_is_announced_then_enforced_until_it_ends() {
+        let now = Utc::now();
+        let maintenance = DeploymentMaintenance {
+            maintenance_mode: false,
+            message: Some("Upgrading the database".to_string()),
+            starts_at: Some(now + Duration::hours(1)),
+            ends_at: Some(now + Duration::hours(2)),
+        };
+
+        let upcoming = maintenance.banner_at(now).unwrap();
+        assert!(!upcoming.active);
+        assert!(!maintenance.is_active_at(now));
+
+        let during = now + Duration::minutes(90);
+        assert!(maintenance.is_active_at(during));
+        assert_eq!(
+            maintenance.banner_at(during).unwrap().estimated_end_at,
+            maintenance.ends_at
+        );
+
+        assert!(!maintenance.is_active_at(now + Duration::hours(2)));
+        assert_eq!(maintenance.banner_at(now + Duration::hours(3)), None);
+    }
+
+    #[test]
+    fn maintenance_mode_stays_on_past_its_estimated_end() {
+        let now = Utc::now();
+        let maintenance = DeploymentMaintenance {
+            maintenance_mode: true,
+            ends_at: Some(now - Duration::minutes(5)),
+            ..Default::default()
+        };
+
+        assert!(maintenance.is_active_at(now));
+        assert_eq!(maintenance.banner_at(now).unwrap().estimated_end_at, None);
+    }
+}
@@ -0,0 +1,160 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::ProvisioningStatus;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvisioningActionKind {
+    CreateCfHostname,
+    CreatePostmarkDomain,
+    DeleteCfHostname,
+    DeletePostmarkDomain,
+}
+
+impl ProvisioningActionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProvisioningActionKind::CreateCfHostname => "create_cf_hostname",
+            ProvisioningActionKind::CreatePostmarkDomain => "create_postmark_domain",
+            ProvisioningActionKind::DeleteCfHostname => "delete_cf_hostname",
+            ProvisioningActionKind::DeletePostmarkDomain => "delete_postmark_domain",
+        }
+    }
+
+    /// Deletions clean up after a domain migration, so unlike creations they
+    /// still run once the deployment is gone.
+    pub fn is_delete(&self) -> bool {
+        matches!(
+            self,
+            ProvisioningActionKind::DeleteCfHostname | ProvisioningActionKind::DeletePostmarkDomain
+        )
+    }
+}
+
+impl From<String> for ProvisioningActionKind {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "create_postmark_domain" => ProvisioningActionKind::CreatePostmarkDomain,
+            "delete_cf_hostname" => ProvisioningActionKind::DeleteCfHostname,
+            "delete_postmark_domain" => ProvisioningActionKind::DeletePostmarkDomain,
+            _ => ProvisioningActionKind::CreateCfHostname,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvisioningActionStatus {
+    Pending,
+    Completed,
+    /// Out of attempts. Retrying the deployment's provisioning puts it back to
+    /// pending.
+    Failed,
+    /// The deployment was deleted, or its domain migration aborted, before
+    /// the action ran.
+    Cancelled,
+}
+
+impl ProvisioningActionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProvisioningActionStatus::Pending => "pending",
+            ProvisioningActionStatus::Completed => "completed",
+            ProvisioningActionStatus::Failed => "failed",
+            ProvisioningActionStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl From<String> for ProvisioningActionStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "completed" => ProvisioningActionStatus::Completed,
+            "failed" => ProvisioningActionStatus::Failed,
+            "cancelled" => ProvisioningActionStatus::Cancelled,
+            _ => ProvisioningActionStatus::Pending,
+        }
+    }
+}
+
+/// Which of the deployment's hosts a custom hostname is for.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HostnameRole {
+    Frontend,
+    Backend,
+}
+
+/// What an action creates. Stored as the action's `payload`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ProvisioningActionPayload {
+    CustomHostname {
+        hostname: String,
+        origin: String,
+        role: HostnameRole,
+    },
+    PostmarkDomain {
+        domain: String,
+    },
+    CustomHostnameId {
+        hostname_id: String,
+    },
+    PostmarkDomainId {
+        postmark_domain_id: i64,
+    },
+}
+
+/// An external side effect of creating a deployment or moving it to another
+/// domain, written in the same transaction as that change and carried out
+/// afterwards by the provisioning dispatcher.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentProvisioningAction {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    /// Set for actions of a domain migration, which report to it rather
+    /// than to the deployment.
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub domain_migration_id: Option<i64>,
+    pub action: ProvisioningActionKind,
+    pub payload: ProvisioningActionPayload,
+    pub status: ProvisioningActionStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub result: Option<serde_json::Value>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentProvisioning {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub status: ProvisioningStatus,
+    pub actions: Vec<DeploymentProvisioningAction>,
+}
+
+/// A custom hostname in the Cloudflare zone that no live deployment or
+/// domain migration refers to.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrphanedCustomHostname {
+    pub id: String,
+    pub hostname: String,
+    pub status: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub deleted: bool,
+    /// Why deleting it failed, when it was asked for.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CustomHostnameReconciliation {
+    /// How many custom hostnames the zone has.
+    pub checked: usize,
+    pub orphans: Vec<OrphanedCustomHostname>,
+}
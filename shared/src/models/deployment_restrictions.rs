@@ -1,9 +1,16 @@
-use std::str::FromStr;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::error::AppError;
+use crate::{
+    error::AppError,
+    utils::email_normalization::{normalize_email, normalize_email_domain},
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct DeploymentRestrictions {
@@ -19,17 +26,49 @@ pub struct DeploymentRestrictions {
     pub block_voip_numbers: bool,
     pub country_restrictions: CountryRestrictions,
     pub banned_keywords: Vec<String>,
-    pub allowlisted_resources: Vec<String>,
-    pub blocklisted_resources: Vec<String>,
+    pub allowlisted_resources: Vec<RestrictionEntry>,
+    pub blocklisted_resources: Vec<RestrictionEntry>,
     pub sign_up_mode: DeploymentRestrictionsSignUpMode,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CountryRestrictions {
     pub enabled: bool,
+    /// Whether `country_codes` lists the countries to block or the only
+    /// countries to let in. Older settings predate it and are blocklists.
+    #[serde(default)]
+    pub mode: CountryRestrictionMode,
     pub country_codes: Vec<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CountryRestrictionMode {
+    #[default]
+    Block,
+    Allow,
+}
+
+impl CountryRestrictions {
+    /// Whether sign-ups from a country are let in. Country codes are ISO
+    /// 3166-1 alpha-2 and compared case-insensitively.
+    pub fn permits(&self, country_code: &str) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        let listed = self
+            .country_codes
+            .iter()
+            .any(|code| code.eq_ignore_ascii_case(country_code));
+
+        match self.mode {
+            CountryRestrictionMode::Block => !listed,
+            CountryRestrictionMode::Allow => listed,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub enum DeploymentRestrictionsSignUpMode {
     #[serde(rename = "public")]
@@ -66,3 +105,458 @@ impl ToString for DeploymentRestrictionsSignUpMode {
         }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestrictionEntryType {
+    Email,
+    EmailDomain,
+    Ip,
+    IpCidr,
+    PhonePrefix,
+}
+
+impl RestrictionEntryType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RestrictionEntryType::Email => "email",
+            RestrictionEntryType::EmailDomain => "email_domain",
+            RestrictionEntryType::Ip => "ip",
+            RestrictionEntryType::IpCidr => "ip_cidr",
+            RestrictionEntryType::PhonePrefix => "phone_prefix",
+        }
+    }
+}
+
+impl FromStr for RestrictionEntryType {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "email" => Ok(RestrictionEntryType::Email),
+            "email_domain" => Ok(RestrictionEntryType::EmailDomain),
+            "ip" => Ok(RestrictionEntryType::Ip),
+            "ip_cidr" => Ok(RestrictionEntryType::IpCidr),
+            "phone_prefix" => Ok(RestrictionEntryType::PhonePrefix),
+            _ => Err(AppError::Serialization(format!(
+                "Invalid restriction entry type: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// One allowlist or blocklist rule. Entries are stored as `type:value` strings;
+/// plain strings written before entries were typed are read back as email or
+/// email domain matches.
+///
+/// Requests may send either `{"type": "ip_cidr", "value": "10.0.0.0/8"}` or a
+/// plain string, which is treated like a stored one.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "RawRestrictionEntry")]
+pub struct RestrictionEntry {
+    #[serde(rename = "type")]
+    pub entry_type: RestrictionEntryType,
+    pub value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawRestrictionEntry {
+    Plain(String),
+    Typed {
+        #[serde(rename = "type")]
+        entry_type: RestrictionEntryType,
+        value: String,
+    },
+}
+
+impl TryFrom<RawRestrictionEntry> for RestrictionEntry {
+    type Error = String;
+
+    fn try_from(raw: RawRestrictionEntry) -> Result<Self, Self::Error> {
+        match raw {
+            RawRestrictionEntry::Plain(value) => {
+                let entry = RestrictionEntry::from_stored(&value);
+                RestrictionEntry::new(entry.entry_type, &entry.value)
+            }
+            RawRestrictionEntry::Typed { entry_type, value } => {
+                RestrictionEntry::new(entry_type, &value)
+            }
+        }
+    }
+}
+
+impl RestrictionEntry {
+    /// Validates the value for its type and normalizes it, so that matching
+    /// doesn't have to care about case or how an address was written.
+    pub fn new(entry_type: RestrictionEntryType, value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let value = match entry_type {
+            RestrictionEntryType::Email => {
+                let (local, domain) = value
+                    .split_once('@')
+                    .ok_or_else(|| format!("`{}` is not an email address", value))?;
+                if local.is_empty() || domain.is_empty() || domain.contains('@') {
+                    return Err(format!("`{}` is not an email address", value));
+                }
+                value.to_lowercase()
+            }
+            RestrictionEntryType::EmailDomain => {
+                let domain = value.trim_start_matches('@');
+                if domain.is_empty() || domain.contains('@') || domain.contains(char::is_whitespace)
+                {
+                    return Err(format!("`{}` is not an email domain", value));
+                }
+                domain.to_lowercase()
+            }
+            RestrictionEntryType::Ip => value
+                .parse::<IpAddr>()
+                .map_err(|_| format!("`{}` is not an IP address", value))?
+                .to_string(),
+            RestrictionEntryType::IpCidr => value.parse::<IpCidr>()?.to_string(),
+            RestrictionEntryType::PhonePrefix => {
+                let digits = value.strip_prefix('+').unwrap_or(value);
+                if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(format!(
+                        "`{}` is not a phone prefix, expected digits with an optional leading +",
+                        value
+                    ));
+                }
+                value.to_string()
+            }
+        };
+
+        Ok(Self { entry_type, value })
+    }
+
+    /// Reads an entry from its stored form. Never fails: anything without a
+    /// known type prefix is a legacy entry, which is an email if it contains an
+    /// `@` and an email domain otherwise.
+    pub fn from_stored(stored: &str) -> Self {
+        if let Some((prefix, value)) = stored.split_once(':')
+            && let Ok(entry_type) = RestrictionEntryType::from_str(prefix)
+        {
+            return Self {
+                entry_type,
+                value: value.to_string(),
+            };
+        }
+
+        let entry_type = if stored.contains('@') {
+            RestrictionEntryType::Email
+        } else {
+            RestrictionEntryType::EmailDomain
+        };
+
+        Self {
+            entry_type,
+            value: stored.trim().to_lowercase(),
+        }
+    }
+
+    pub fn to_stored(&self) -> String {
+        format!("{}:{}", self.entry_type.as_str(), self.value)
+    }
+
+    pub fn to_stored_list(entries: &[RestrictionEntry]) -> Vec<String> {
+        entries.iter().map(RestrictionEntry::to_stored).collect()
+    }
+
+    pub fn from_stored_list(stored: &[String]) -> Vec<RestrictionEntry> {
+        stored
+            .iter()
+            .map(|entry| RestrictionEntry::from_stored(entry))
+            .collect()
+    }
+
+    pub fn matches(&self, candidate: &RestrictionCandidate) -> bool {
+        match self.entry_type {
+            RestrictionEntryType::Email => candidate
+                .email
+                .as_deref()
+                .is_some_and(|email| email.eq_ignore_ascii_case(&self.value)),
+            RestrictionEntryType::EmailDomain => candidate
+                .email
+                .as_deref()
+                .and_then(|email| email.rsplit_once('@'))
+                .is_some_and(|(_, domain)| domain.eq_ignore_ascii_case(&self.value)),
+            RestrictionEntryType::Ip => candidate.ip.is_some_and(|ip| {
+                self.value
+                    .parse::<IpAddr>()
+                    .is_ok_and(|value| canonical_ip(value) == canonical_ip(ip))
+            }),
+            RestrictionEntryType::IpCidr => candidate.ip.is_some_and(|ip| {
+                self.value
+                    .parse::<IpCidr>()
+                    .is_ok_and(|cidr| cidr.contains(ip))
+            }),
+            RestrictionEntryType::PhonePrefix => candidate.phone.as_deref().is_some_and(|phone| {
+                let phone: String = phone
+                    .chars()
+                    .filter(|c| c.is_ascii_digit() || *c == '+')
+                    .collect();
+                let prefix = self.value.trim_start_matches('+');
+                phone.trim_start_matches('+').starts_with(prefix)
+            }),
+        }
+    }
+
+    /// Like `matches`, but also compares email addresses the way
+    /// `normalize_email` reads them, so `j.doe@googlemail.com` matches a rule
+    /// for `jdoe@gmail.com`.
+    pub fn matches_normalized(
+        &self,
+        candidate: &RestrictionCandidate,
+        strip_subaddress: bool,
+    ) -> bool {
+        if self.matches(candidate) {
+            return true;
+        }
+
+        let Some(email) = candidate.email.as_deref() else {
+            return false;
+        };
+        let email = normalize_email(email, strip_subaddress);
+
+        match self.entry_type {
+            RestrictionEntryType::Email => normalize_email(&self.value, strip_subaddress) == email,
+            RestrictionEntryType::EmailDomain => email
+                .rsplit_once('@')
+                .is_some_and(|(_, domain)| domain == normalize_email_domain(&self.value)),
+            _ => false,
+        }
+    }
+}
+
+/// An IP range in CIDR notation. The network address is stored with the host
+/// bits cleared, so `10.1.2.3/8` and `10.0.0.0/8` are the same range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = canonical_ip(ip);
+        let same_family = matches!(
+            (self.network, ip),
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_))
+        );
+        same_family && mask(ip, self.prefix_len) == self.network
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = s
+            .split_once('/')
+            .ok_or_else(|| format!("`{}` is not in CIDR notation, expected address/prefix", s))?;
+        let address = address
+            .parse::<IpAddr>()
+            .map_err(|_| format!("`{}` is not an IP address", address))?;
+        let max_prefix_len = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = prefix_len
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix_len| *prefix_len <= max_prefix_len)
+            .ok_or_else(|| {
+                format!(
+                    "`{}` has an invalid prefix length, expected 0 to {}",
+                    s, max_prefix_len
+                )
+            })?;
+
+        Ok(Self {
+            network: mask(address, prefix_len),
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+fn mask(ip: IpAddr, prefix_len: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(prefix_len))
+                .unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask))
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(prefix_len))
+                .unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+        }
+    }
+}
+
+/// Treats IPv4-mapped IPv6 addresses, as reported by dual-stack listeners, as
+/// the IPv4 address they carry.
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// What a sign-up is checked against.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestrictionCandidate {
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub ip: Option<IpAddr>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestrictionDecisionReason {
+    Blocklisted,
+    Allowlisted,
+    NotAllowlisted,
+    CountryRestricted,
+    NoRestrictions,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RestrictionDecision {
+    pub allowed: bool,
+    pub reason: RestrictionDecisionReason,
+    pub matched_rule: Option<RestrictionEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<CountryRestrictionDecision>,
+}
+
+/// The outcome of checking a sign-up's country, along with the signals it was
+/// resolved from so support can see why a user was turned away.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CountryRestrictionDecision {
+    pub allowed: bool,
+    /// Countries sharing the phone number's calling code, narrowed to the IP
+    /// country when it's one of them.
+    pub phone_countries: Vec<String>,
+    pub ip_country: Option<String>,
+    /// The country that got the sign-up denied.
+    pub denied_country: Option<String>,
+    /// Set when the phone number and the IP point at different countries.
+    pub conflicting_signals: bool,
+}
+
+/// Decides whether a sign-up is let in by the deployment's country
+/// restrictions, given the countries resolved from its phone number and IP.
+///
+/// Each signal is judged on its own and the stricter result wins, so a
+/// blocked country on either side denies the sign-up. A calling code shared
+/// by several countries only counts against the sign-up when every one of
+/// them is denied, as there's no telling which the number belongs to. With
+/// neither signal resolved the sign-up is let through.
+pub struct CountryRestrictionEvaluator<'a> {
+    restrictions: &'a CountryRestrictions,
+}
+
+impl<'a> CountryRestrictionEvaluator<'a> {
+    pub fn new(restrictions: &'a CountryRestrictions) -> Self {
+        Self { restrictions }
+    }
+
+    pub fn evaluate(
+        &self,
+        phone_countries: &[&str],
+        ip_country: Option<&str>,
+    ) -> CountryRestrictionDecision {
+        let phone_countries: Vec<&str> = match ip_country {
+            Some(ip_country)
+                if phone_countries
+                    .iter()
+                    .any(|country| country.eq_ignore_ascii_case(ip_country)) =>
+            {
+                vec![ip_country]
+            }
+            _ => phone_countries.to_vec(),
+        };
+
+        let conflicting_signals = ip_country.is_some()
+            && !phone_countries.is_empty()
+            && !phone_countries
+                .iter()
+                .any(|country| Some(*country) == ip_country);
+
+        let ip_denied = ip_country.filter(|country| !self.restrictions.permits(country));
+        let phone_denied = if phone_countries
+            .iter()
+            .all(|country| !self.restrictions.permits(country))
+        {
+            phone_countries.first().copied()
+        } else {
+            None
+        };
+
+        let denied_country = ip_denied.or(phone_denied).map(str::to_uppercase);
+
+        CountryRestrictionDecision {
+            allowed: denied_country.is_none(),
+            phone_countries: phone_countries.iter().map(|c| c.to_string()).collect(),
+            ip_country: ip_country.map(str::to_uppercase),
+            denied_country,
+            conflicting_signals,
+        }
+    }
+}
+
+impl DeploymentRestrictions {
+    /// Checks a sign-up against the blocklist and then the allowlist. A
+    /// blocklist match always wins; with the allowlist enabled, a candidate has
+    /// to match at least one of its entries. Email rules match however the
+    /// address is written, and with `block_subaddresses` whatever tag it has.
+    pub fn evaluate(&self, candidate: &RestrictionCandidate) -> RestrictionDecision {
+        if self.blocklist_enabled
+            && let Some(rule) = self
+                .blocklisted_resources
+                .iter()
+                .find(|entry| entry.matches_normalized(candidate, self.block_subaddresses))
+        {
+            return RestrictionDecision {
+                allowed: false,
+                reason: RestrictionDecisionReason::Blocklisted,
+                matched_rule: Some(rule.clone()),
+                country: None,
+            };
+        }
+
+        if self.allowlist_enabled {
+            let rule = self
+                .allowlisted_resources
+                .iter()
+                .find(|entry| entry.matches_normalized(candidate, self.block_subaddresses));
+            return RestrictionDecision {
+                allowed: rule.is_some(),
+                reason: if rule.is_some() {
+                    RestrictionDecisionReason::Allowlisted
+                } else {
+                    RestrictionDecisionReason::NotAllowlisted
+                },
+                matched_rule: rule.cloned(),
+                country: None,
+            };
+        }
+
+        RestrictionDecision {
+            allowed: true,
+            reason: RestrictionDecisionReason::NoRestrictions,
+            matched_rule: None,
+            country: None,
+        }
+    }
+}
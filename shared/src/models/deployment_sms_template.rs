@@ -20,7 +20,7 @@ impl Default for DeploymentSmsTemplate {
             deployment_id: 0,
             reset_password_code_template: "Your {{app_name}} password reset code is: {{code}}"
                 .to_string(),
-            verification_code_template: "Your {{app_name}} verification code is: {{code}}"
+            verification_code_template: "Your {{app_name}} verification code is: {{code}}. It expires in {{code.expires_in_minutes}} minutes."
                 .to_string(),
             password_change_template: "Your {{app_name}} password has been changed".to_string(),
             password_remove_template: "Your {{app_name}} password has been removed".to_string(),
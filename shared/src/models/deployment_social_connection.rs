@@ -4,6 +4,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgTypeInfo;
 
+use crate::utils::secret_mask::mask_secret;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum SocialConnectionProvider {
@@ -85,4 +87,28 @@ pub struct DeploymentSocialConnection {
     pub provider: Option<SocialConnectionProvider>,
     pub enabled: bool,
     pub credentials: Option<OauthCredentials>,
+    /// Whether a client secret is stored. Responses mask the secret itself
+    /// unless it was explicitly revealed.
+    #[serde(default)]
+    pub has_secret: bool,
+}
+
+impl DeploymentSocialConnection {
+    /// Sets `has_secret` from the credentials, for connections built from a
+    /// row.
+    pub fn with_secret_flag(mut self) -> Self {
+        self.has_secret = self
+            .credentials
+            .as_ref()
+            .is_some_and(|credentials| !credentials.client_secret.is_empty());
+        self
+    }
+
+    /// Replaces the client secret with its masked form.
+    pub fn redact(mut self) -> Self {
+        if let Some(credentials) = &mut self.credentials {
+            credentials.client_secret = mask_secret(&credentials.client_secret);
+        }
+        self
+    }
 }
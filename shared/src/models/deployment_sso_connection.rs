@@ -0,0 +1,143 @@
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// How close to expiry an IdP signing certificate has to be before the
+/// console starts warning about it.
+pub const CERTIFICATE_EXPIRY_WARNING_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CertificateStatus {
+    Valid,
+    ExpiringSoon,
+    Expired,
+}
+
+impl CertificateStatus {
+    pub fn at(expires_at: DateTime<Utc>, now: DateTime<Utc>) -> Self {
+        if expires_at <= now {
+            CertificateStatus::Expired
+        } else if expires_at <= now + Duration::days(CERTIFICATE_EXPIRY_WARNING_DAYS) {
+            CertificateStatus::ExpiringSoon
+        } else {
+            CertificateStatus::Valid
+        }
+    }
+}
+
+/// Names of the assertion attributes that carry the user's profile.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SsoAttributeMapping {
+    pub email: String,
+    #[serde(default)]
+    pub first_name: Option<String>,
+    #[serde(default)]
+    pub last_name: Option<String>,
+}
+
+impl Default for SsoAttributeMapping {
+    fn default() -> Self {
+        Self {
+            email: "email".to_string(),
+            first_name: Some("first_name".to_string()),
+            last_name: Some("last_name".to_string()),
+        }
+    }
+}
+
+/// A SAML identity provider users of the deployment can sign in with.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentSsoConnection {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub name: String,
+    pub enabled: bool,
+    pub idp_metadata_url: Option<String>,
+    pub idp_metadata_xml: Option<String>,
+    pub idp_entity_id: String,
+    pub idp_sso_url: Option<String>,
+    pub idp_certificate: String,
+    pub certificate_expires_at: DateTime<Utc>,
+    pub certificate_status: CertificateStatus,
+    pub attribute_mapping: SsoAttributeMapping,
+    pub allowed_email_domains: Vec<String>,
+    pub sp_certificate: String,
+    #[serde(skip_serializing, default)]
+    pub sp_private_key: String,
+}
+
+/// The parts of an IdP's SAML metadata document a connection needs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdpMetadata {
+    pub entity_id: Option<String>,
+    pub sso_url: Option<String>,
+    pub certificate: Option<String>,
+}
+
+impl IdpMetadata {
+    /// Reads the entity id, the first single sign-on endpoint (preferring the
+    /// HTTP-Redirect binding) and the first signing certificate.
+    pub fn parse(xml: &str) -> Self {
+        let entity_id =
+            Regex::new(r#"<(?:\w+:)?EntityDescriptor\b[^>]*\bentityID\s*=\s*"([^"]+)""#)
+                .ok()
+                .and_then(|re| re.captures(xml))
+                .map(|captures| captures[1].trim().to_string());
+
+        let sso_services: Vec<(String, String)> =
+            Regex::new(r#"<(?:\w+:)?SingleSignOnService\b([^>]*)>"#)
+                .map(|re| {
+                    re.captures_iter(xml)
+                        .filter_map(|captures| {
+                            let attributes = captures.get(1)?.as_str();
+                            Some((
+                                xml_attribute(attributes, "Binding").unwrap_or_default(),
+                                xml_attribute(attributes, "Location")?,
+                            ))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+        let sso_url = sso_services
+            .iter()
+            .find(|(binding, _)| binding.ends_with("HTTP-Redirect"))
+            .or_else(|| sso_services.first())
+            .map(|(_, location)| location.clone());
+
+        let certificate_re = Regex::new(r#"<(?:\w+:)?X509Certificate>([^<]+)</"#).ok();
+        let certificate =
+            Regex::new(r#"(?s)<(?:\w+:)?KeyDescriptor\b([^>]*)>(.*?)</(?:\w+:)?KeyDescriptor>"#)
+                .ok()
+                .zip(certificate_re)
+                .and_then(|(descriptor_re, certificate_re)| {
+                    descriptor_re
+                        .captures_iter(xml)
+                        .filter(|captures| {
+                            xml_attribute(&captures[1], "use").as_deref() != Some("encryption")
+                        })
+                        .find_map(|captures| {
+                            certificate_re.captures(&captures[2]).map(|certificate| {
+                                certificate[1].split_whitespace().collect::<String>()
+                            })
+                        })
+                });
+
+        Self {
+            entity_id,
+            sso_url,
+            certificate,
+        }
+    }
+}
+
+fn xml_attribute(attributes: &str, name: &str) -> Option<String> {
+    Regex::new(&format!(r#"\b{}\s*=\s*"([^"]*)""#, name))
+        .ok()?
+        .captures(attributes)
+        .map(|captures| captures[1].to_string())
+}
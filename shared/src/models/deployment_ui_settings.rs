@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -66,6 +68,10 @@ pub struct DeploymentUISettings {
     pub after_signin_redirect_url: String,
     pub user_profile_url: String,
     pub after_create_organization_redirect_url: String,
+    /// Origins other than the frontend host that redirect settings may point
+    /// at, such as `https://app.example.com`.
+    #[serde(default)]
+    pub allowed_redirect_origins: Vec<String>,
 }
 
 impl Default for DeploymentUISettings {
@@ -100,6 +106,62 @@ impl Default for DeploymentUISettings {
             after_signin_redirect_url: "".to_string(),
             user_profile_url: "".to_string(),
             after_create_organization_redirect_url: "".to_string(),
+            allowed_redirect_origins: Vec::new(),
+        }
+    }
+}
+
+/// An image a deployment can upload for its UI, stored in the matching
+/// `deployment_ui_settings` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeploymentAssetKind {
+    Favicon,
+    Logo,
+    UserProfile,
+    OrgProfile,
+}
+
+impl DeploymentAssetKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeploymentAssetKind::Favicon => "favicon",
+            DeploymentAssetKind::Logo => "logo",
+            DeploymentAssetKind::UserProfile => "user-profile",
+            DeploymentAssetKind::OrgProfile => "org-profile",
+        }
+    }
+
+    pub fn column(&self) -> &'static str {
+        match self {
+            DeploymentAssetKind::Favicon => "favicon_image_url",
+            DeploymentAssetKind::Logo => "logo_image_url",
+            DeploymentAssetKind::UserProfile => "default_user_profile_image_url",
+            DeploymentAssetKind::OrgProfile => "default_organization_profile_image_url",
+        }
+    }
+
+    /// The largest width and height accepted, if the kind has one.
+    pub fn max_dimensions(&self) -> Option<(u32, u32)> {
+        match self {
+            DeploymentAssetKind::Favicon => Some((512, 512)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for DeploymentAssetKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "favicon" => Ok(DeploymentAssetKind::Favicon),
+            "logo" => Ok(DeploymentAssetKind::Logo),
+            "user-profile" => Ok(DeploymentAssetKind::UserProfile),
+            "org-profile" => Ok(DeploymentAssetKind::OrgProfile),
+            _ => Err(format!(
+                "Invalid asset kind: {}. Allowed kinds: favicon, logo, user-profile, org-profile",
+                s
+            )),
         }
     }
 }
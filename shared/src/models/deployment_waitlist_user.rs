@@ -1,7 +1,44 @@
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{DeploymentInvitation, DeploymentRestrictionsSignUpMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitlistEntryStatus {
+    #[default]
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl WaitlistEntryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WaitlistEntryStatus::Pending => "pending",
+            WaitlistEntryStatus::Approved => "approved",
+            WaitlistEntryStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl FromStr for WaitlistEntryStatus {
+    type Err = String;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(WaitlistEntryStatus::Pending),
+            "approved" => Ok(WaitlistEntryStatus::Approved),
+            "rejected" => Ok(WaitlistEntryStatus::Rejected),
+            _ => Err(format!("Invalid waitlist status: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DeploymentWaitlistUser {
     #[serde(with = "crate::utils::serde::i64_as_string")]
     pub id: i64,
@@ -12,4 +49,39 @@ pub struct DeploymentWaitlistUser {
     pub email_address: String,
     pub first_name: String,
     pub last_name: String,
+    pub status: WaitlistEntryStatus,
+    pub metadata: Value,
+    /// The invitation sent when the entry was approved.
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub invitation_id: Option<i64>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaitlistApproval {
+    pub entry: DeploymentWaitlistUser,
+    pub invitation: DeploymentInvitation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaitlistApprovalFailure {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BulkWaitlistApproval {
+    pub approved: Vec<DeploymentWaitlistUser>,
+    pub failed: Vec<WaitlistApprovalFailure>,
+}
+
+/// Whether someone may sign up under the deployment's current sign-up mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignUpEligibility {
+    pub allowed: bool,
+    pub sign_up_mode: DeploymentRestrictionsSignUpMode,
+    /// Set when a valid invitation is what let the sign-up through.
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub invitation_id: Option<i64>,
 }
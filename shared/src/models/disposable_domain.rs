@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DisposableDomainKind {
+    /// Treated as disposable even though the upstream list doesn't have it.
+    Blocked,
+    /// Never treated as disposable, even if the upstream list has it.
+    Allowed,
+}
+
+impl DisposableDomainKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DisposableDomainKind::Blocked => "blocked",
+            DisposableDomainKind::Allowed => "allowed",
+        }
+    }
+}
+
+impl From<String> for DisposableDomainKind {
+    fn from(kind: String) -> Self {
+        match kind.as_str() {
+            "allowed" => DisposableDomainKind::Allowed,
+            _ => DisposableDomainKind::Blocked,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentDisposableDomain {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub domain: String,
+    pub kind: DisposableDomainKind,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DisposableDomainDataset {
+    pub domain_count: usize,
+    pub refreshed_at: Option<DateTime<Utc>>,
+}
+
+/// The disposable domain list as a deployment sees it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DisposableDomainSummary {
+    pub block_disposable_emails: bool,
+    pub upstream: DisposableDomainDataset,
+    /// Upstream domains plus the deployment's additions, minus its exceptions.
+    pub effective_domain_count: usize,
+    pub blocked: Vec<DeploymentDisposableDomain>,
+    pub allowed: Vec<DeploymentDisposableDomain>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DisposableDomainSource {
+    Upstream,
+    Deployment,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DisposableEmailCheck {
+    pub domain: String,
+    pub disposable: bool,
+    /// Whether the sign-up should be rejected, which also depends on the
+    /// deployment having `block_disposable_emails` turned on.
+    pub blocked: bool,
+    /// The list entry that decided the result. For subdomains this is the
+    /// parent domain that matched.
+    pub matched_domain: Option<String>,
+    pub source: Option<DisposableDomainSource>,
+}
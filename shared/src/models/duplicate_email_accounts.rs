@@ -0,0 +1,21 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Accounts on a deployment whose email addresses deliver to the same
+/// mailbox, left for an operator to reconcile.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateEmailAccounts {
+    pub normalized_email_address: String,
+    /// Oldest first.
+    pub accounts: Vec<DuplicateEmailAccount>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateEmailAccount {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub user_id: i64,
+    pub email_address: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailOutboxStatus {
+    /// Recorded and being handed to the provider.
+    Queued,
+    Sent,
+    /// The provider refused the message or couldn't be reached.
+    Failed,
+    /// Accepted by the provider but returned by the recipient's server.
+    Bounced,
+}
+
+impl EmailOutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailOutboxStatus::Queued => "queued",
+            EmailOutboxStatus::Sent => "sent",
+            EmailOutboxStatus::Failed => "failed",
+            EmailOutboxStatus::Bounced => "bounced",
+        }
+    }
+}
+
+impl FromStr for EmailOutboxStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(EmailOutboxStatus::Queued),
+            "sent" => Ok(EmailOutboxStatus::Sent),
+            "failed" => Ok(EmailOutboxStatus::Failed),
+            "bounced" => Ok(EmailOutboxStatus::Bounced),
+            _ => Err(format!("Invalid email outbox status: {}", s)),
+        }
+    }
+}
+
+/// One email a deployment sent, or tried to. The rendered message is only
+/// kept until it is sent, so links in it don't outlive their use.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailOutboxEntry {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub template_name: String,
+    pub to_email: String,
+    pub subject: String,
+    pub status: EmailOutboxStatus,
+    /// The transport used for the last attempt, such as Postmark or SMTP.
+    pub provider: Option<String>,
+    pub provider_message_id: Option<String>,
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmailRetryResult {
+    pub retried: u64,
+    pub sent: u64,
+    pub failed: u64,
+}
@@ -0,0 +1,9 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GeoIpDatabaseInfo {
+    pub loaded: bool,
+    pub database_type: Option<String>,
+    pub built_at: Option<DateTime<Utc>>,
+}
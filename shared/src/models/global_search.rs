@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// What a global search result is.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GlobalSearchResultKind {
+    Project,
+    Deployment,
+}
+
+/// Which field of the project or deployment matched the search term.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GlobalSearchField {
+    ProjectName,
+    FrontendHost,
+    BackendHost,
+    MailFromHost,
+    PublishableKey,
+    FrontendHostnameId,
+    BackendHostnameId,
+}
+
+impl GlobalSearchField {
+    pub fn kind(&self) -> GlobalSearchResultKind {
+        match self {
+            GlobalSearchField::ProjectName => GlobalSearchResultKind::Project,
+            _ => GlobalSearchResultKind::Deployment,
+        }
+    }
+}
+
+impl From<String> for GlobalSearchField {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "frontend_host" => GlobalSearchField::FrontendHost,
+            "backend_host" => GlobalSearchField::BackendHost,
+            "mail_from_host" => GlobalSearchField::MailFromHost,
+            "publishable_key" => GlobalSearchField::PublishableKey,
+            "frontend_hostname_id" => GlobalSearchField::FrontendHostnameId,
+            "backend_hostname_id" => GlobalSearchField::BackendHostnameId,
+            _ => GlobalSearchField::ProjectName,
+        }
+    }
+}
+
+/// A project or deployment found by an operator search, with the project it
+/// belongs to. Deleted records are included and flagged.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GlobalSearchResult {
+    pub kind: GlobalSearchResultKind,
+    pub matched_field: GlobalSearchField,
+    pub matched_value: String,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub project_id: i64,
+    pub project_name: String,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub deployment_id: Option<i64>,
+    /// The project or deployment itself was deleted, or the project the
+    /// deployment belongs to was.
+    pub deleted: bool,
+    /// The console API path of the owning project.
+    pub project_url: String,
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DependencyHealth {
+    pub name: String,
+    /// A critical dependency being down makes the instance unready; any other
+    /// only degrades it.
+    pub critical: bool,
+    pub status: DependencyStatus,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessStatus {
+    Ready,
+    /// Serving traffic, but an optional dependency is down.
+    Degraded,
+    Unavailable,
+}
+
+impl ReadinessStatus {
+    pub fn is_ready(self) -> bool {
+        !matches!(self, ReadinessStatus::Unavailable)
+    }
+}
+
+/// Migration versions, so a rolling deploy can be checked pod by pod.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseSchema {
+    /// The newest migration applied to the database.
+    pub applied: Option<i64>,
+    /// The newest migration this build ships with.
+    pub expected: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReadinessReport {
+    pub status: ReadinessStatus,
+    pub checked_at: DateTime<Utc>,
+    pub dependencies: Vec<DependencyHealth>,
+    pub schema: Option<DatabaseSchema>,
+}
+
+impl ReadinessReport {
+    pub fn from_dependencies(
+        dependencies: Vec<DependencyHealth>,
+        schema: Option<DatabaseSchema>,
+    ) -> Self {
+        let down = |critical: bool| {
+            dependencies
+                .iter()
+                .any(|d| d.critical == critical && d.status == DependencyStatus::Down)
+        };
+
+        let status = if down(true) {
+            ReadinessStatus::Unavailable
+        } else if down(false) {
+            ReadinessStatus::Degraded
+        } else {
+            ReadinessStatus::Ready
+        };
+
+        Self {
+            status,
+            checked_at: Utc::now(),
+            dependencies,
+            schema,
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A magic link that has been emailed. The token itself only goes out in the
+/// email.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IssuedMagicLink {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub email_address: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A magic link that has just been used.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConsumedMagicLink {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub email_address: String,
+    /// Where to send the user next. Checked against the deployment's allowed
+    /// redirect origins when the link is used.
+    pub redirect_url: Option<String>,
+    /// The user with this email address, if there is one.
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub user_id: Option<i64>,
+}
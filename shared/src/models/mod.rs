@@ -1,31 +1,71 @@
+mod audit_log;
+mod auth_event;
+mod background_job;
+mod client_config;
+mod console_account;
 mod deployment;
+mod deployment_api_key;
 mod deployment_auth_settings;
 mod deployment_b2b_settings;
+mod deployment_config;
 mod deployment_custom_roles;
+mod deployment_data_export;
+mod deployment_domain_migration;
+mod deployment_email_provider;
 mod deployment_email_template;
+mod deployment_feature_flags;
 mod deployment_invitation;
 mod deployment_jwt_template;
 mod deployment_keypair;
+mod deployment_log_export;
+mod deployment_maintenance;
+mod deployment_mail_settings;
 mod deployment_org_settings;
+mod deployment_provisioning;
 mod deployment_restrictions;
 mod deployment_sms_template;
 mod deployment_social_connection;
+mod deployment_sso_connection;
 mod deployment_ui_settings;
 mod deployment_waitlist_user;
+mod disposable_domain;
+mod duplicate_email_accounts;
+mod email_outbox;
+mod geoip;
+mod global_search;
+mod health;
+mod magic_link;
 mod organization;
+mod organization_audit_event;
 mod organization_details;
+mod organization_invitation;
 mod organization_membership;
 mod organization_permission;
 mod organization_role;
+mod password_reset;
+mod phone_intelligence;
 mod project;
+mod project_collaborator;
+mod rate_limit;
+mod request_log;
+mod scim;
 mod session;
 mod sign_in;
 mod sign_in_attempt;
+mod sign_in_lockout;
 mod sign_up_attempt;
 mod social_connection;
+mod storage_upload;
+mod token_revocation;
+mod usage;
 mod user;
+mod user_authorization;
 mod user_details;
+mod user_erasure;
+mod user_lookup;
 mod user_phone_number;
+mod user_web3_wallet;
+mod username_availability;
 mod workspace;
 mod workspace_details;
 mod workspace_membership;
@@ -34,39 +74,87 @@ mod workspace_role;
 
 // AI-related models
 mod ai_agent;
+mod ai_agent_session;
+mod ai_usage;
 mod ai_workflow;
+mod ai_workflow_trigger;
 mod ai_tool;
+mod ai_tool_invocation;
 mod ai_knowledge_base;
 
+pub use audit_log::*;
+pub use auth_event::*;
+pub use background_job::*;
+pub use client_config::*;
+pub use console_account::*;
 pub use deployment::*;
+pub use deployment_api_key::*;
 pub use deployment_auth_settings::*;
 pub use deployment_b2b_settings::*;
+pub use deployment_config::*;
 pub use deployment_custom_roles::*;
+pub use deployment_data_export::*;
+pub use deployment_domain_migration::*;
+pub use deployment_email_provider::*;
 pub use deployment_email_template::*;
+pub use deployment_feature_flags::*;
 pub use deployment_invitation::*;
 pub use deployment_jwt_template::*;
 pub use deployment_keypair::*;
+pub use deployment_log_export::*;
+pub use deployment_maintenance::*;
+pub use deployment_mail_settings::*;
+pub use deployment_provisioning::*;
 pub use deployment_restrictions::*;
 pub use deployment_sms_template::*;
 pub use deployment_social_connection::*;
+pub use deployment_sso_connection::*;
 pub use deployment_ui_settings::*;
 pub use deployment_waitlist_user::*;
+pub use disposable_domain::*;
+pub use duplicate_email_accounts::*;
+pub use email_outbox::*;
+pub use geoip::*;
+pub use global_search::*;
+pub use health::*;
+pub use magic_link::*;
 pub use organization::*;
+pub use organization_audit_event::*;
 pub use organization_details::*;
+pub use organization_invitation::*;
 pub use organization_permission::*;
 pub use organization_role::*;
+pub use password_reset::*;
+pub use phone_intelligence::*;
 pub use project::*;
+pub use project_collaborator::*;
+pub use rate_limit::*;
+pub use request_log::*;
+pub use scim::*;
 pub use session::*;
+pub use sign_in_lockout::*;
 pub use social_connection::*;
+pub use storage_upload::*;
+pub use token_revocation::*;
+pub use usage::*;
 pub use user::*;
+pub use user_authorization::*;
 pub use user_details::*;
+pub use user_erasure::*;
+pub use user_lookup::*;
 pub use user_phone_number::*;
+pub use user_web3_wallet::*;
+pub use username_availability::*;
 pub use workspace::*;
 
 // AI-related exports
 pub use ai_agent::*;
+pub use ai_agent_session::*;
+pub use ai_usage::*;
 pub use ai_workflow::*;
+pub use ai_workflow_trigger::*;
 pub use ai_tool::*;
+pub use ai_tool_invocation::*;
 pub use ai_knowledge_base::*;
 pub use workspace_details::*;
 pub use workspace_permission::*;
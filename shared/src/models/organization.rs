@@ -15,3 +15,20 @@ pub struct Organization {
     pub public_metadata: Value,
     pub private_metadata: Value,
 }
+
+/// What deleting an organization touched, or would touch for a dry run.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrganizationDeletion {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub organization_id: i64,
+    pub dry_run: bool,
+    pub workspaces: i64,
+    pub organization_memberships: i64,
+    pub workspace_memberships: i64,
+    /// Roles defined on the organization or one of its workspaces.
+    pub custom_roles: i64,
+    pub pending_invitations: i64,
+    /// Active sessions of users whose active organization or workspace was in
+    /// it. They stay signed in but lose its claims.
+    pub sessions: i64,
+}
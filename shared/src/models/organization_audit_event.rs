@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What happened in an organization. The names are part of the API, so
+/// existing ones must never be renamed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum OrganizationAuditEventType {
+    #[serde(rename = "organization.member_added")]
+    MemberAdded,
+    #[serde(rename = "organization.member_removed")]
+    MemberRemoved,
+    #[serde(rename = "organization.member_role_changed")]
+    MemberRoleChanged,
+    #[serde(rename = "organization.settings_updated")]
+    SettingsUpdated,
+    #[serde(rename = "workspace.created")]
+    WorkspaceCreated,
+    #[serde(rename = "workspace.settings_updated")]
+    WorkspaceSettingsUpdated,
+    #[serde(rename = "workspace.deleted")]
+    WorkspaceDeleted,
+    #[serde(rename = "workspace.member_added")]
+    WorkspaceMemberAdded,
+    #[serde(rename = "workspace.member_removed")]
+    WorkspaceMemberRemoved,
+    #[serde(rename = "workspace.member_role_changed")]
+    WorkspaceMemberRoleChanged,
+}
+
+impl OrganizationAuditEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MemberAdded => "organization.member_added",
+            Self::MemberRemoved => "organization.member_removed",
+            Self::MemberRoleChanged => "organization.member_role_changed",
+            Self::SettingsUpdated => "organization.settings_updated",
+            Self::WorkspaceCreated => "workspace.created",
+            Self::WorkspaceSettingsUpdated => "workspace.settings_updated",
+            Self::WorkspaceDeleted => "workspace.deleted",
+            Self::WorkspaceMemberAdded => "workspace.member_added",
+            Self::WorkspaceMemberRemoved => "workspace.member_removed",
+            Self::WorkspaceMemberRoleChanged => "workspace.member_role_changed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrganizationAuditEvent {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub organization_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub workspace_id: Option<i64>,
+    pub actor: Option<String>,
+    /// Kept as stored, so events of a type added later still list.
+    pub event_type: String,
+    pub payload: serde_json::Value,
+}
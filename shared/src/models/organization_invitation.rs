@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrganizationInvitation {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub organization_id: i64,
+    pub email_address: String,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub role_id: Option<i64>,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub inviter_id: Option<i64>,
+    pub expiry: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub accepted_user_id: Option<i64>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl OrganizationInvitation {
+    pub fn is_pending(&self) -> bool {
+        self.accepted_at.is_none() && self.revoked_at.is_none() && self.expiry > Utc::now()
+    }
+}
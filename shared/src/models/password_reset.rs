@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordResetMethod {
+    /// An admin chose the new password.
+    Password,
+    /// A one-time reset link was emailed to the user.
+    Email,
+}
+
+impl PasswordResetMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PasswordResetMethod::Password => "password",
+            PasswordResetMethod::Email => "email",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasswordResetResult {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub user_id: i64,
+    pub method: PasswordResetMethod,
+    pub require_password_change: bool,
+    /// Sign-ins ended by the reset. Always zero for emailed links, since the
+    /// password only changes once the link is used.
+    pub revoked_sessions: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneLineType {
+    Mobile,
+    Landline,
+    Voip,
+    TollFree,
+    Unknown,
+}
+
+impl PhoneLineType {
+    /// Maps the line types reported by Twilio Lookup compatible providers.
+    pub fn from_provider(line_type: &str) -> Self {
+        match line_type {
+            "mobile" => PhoneLineType::Mobile,
+            "landline" | "fixedLine" => PhoneLineType::Landline,
+            "fixedVoip" | "nonFixedVoip" | "voip" => PhoneLineType::Voip,
+            "tollFree" => PhoneLineType::TollFree,
+            _ => PhoneLineType::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhoneLookup {
+    pub phone_number: String,
+    pub line_type: PhoneLineType,
+    pub carrier_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct PhoneIntelligenceMetrics {
+    pub provider_requests: u64,
+    pub provider_errors: u64,
+    pub cache_hits: u64,
+    pub voip_rejections: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhoneNumberCheck {
+    pub phone_number: String,
+    /// `None` when the deployment doesn't block VOIP numbers or the lookup
+    /// failed, in which case the number is let through.
+    pub line_type: Option<PhoneLineType>,
+    pub blocked: bool,
+}
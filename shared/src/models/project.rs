@@ -1,7 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::Deployment;
+use super::{AuditLog, Deployment, DeploymentMode, VerificationStatus};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectWithDeployments {
@@ -23,3 +23,44 @@ pub struct Project {
     pub name: String,
     pub image_url: String,
 }
+
+/// Usage figures for one deployment of a project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentSummary {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub mode: DeploymentMode,
+    pub frontend_host: String,
+    pub user_count: i64,
+    pub organization_count: i64,
+    /// Users who signed in during the last 30 days. `None` when analytics
+    /// couldn't be reached.
+    pub monthly_active_users: Option<i64>,
+    pub verification_status: Option<VerificationStatus>,
+    pub maintenance_mode: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeploymentCountsByMode {
+    pub production: i64,
+    pub staging: i64,
+}
+
+/// The console home page overview of every project a console account works
+/// on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountDashboard {
+    pub project_count: i64,
+    pub deployment_counts: DeploymentCountsByMode,
+    /// Deployments whose DNS is still pending or being checked.
+    pub pending_verification_count: i64,
+    pub failed_verification_count: i64,
+    pub maintenance_mode_count: i64,
+    /// Active users of every deployment this billing period. `None` when
+    /// usage couldn't be read.
+    pub monthly_active_users: Option<i64>,
+    /// The five newest audit log entries across the projects, newest first.
+    pub recent_audit_logs: Vec<AuditLog>,
+    /// When the figures were read. They may be up to half a minute old.
+    pub generated_at: DateTime<Utc>,
+}
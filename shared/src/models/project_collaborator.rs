@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What a collaborator may do in a project, from most to least access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectRole {
+    /// Can see everything.
+    ReadOnly,
+    /// Can change deployment settings and data.
+    Developer,
+    /// Can also manage collaborators and production deployments.
+    Admin,
+    /// Can also delete or transfer the project and manage owners.
+    Owner,
+}
+
+impl ProjectRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectRole::Owner => "owner",
+            ProjectRole::Admin => "admin",
+            ProjectRole::Developer => "developer",
+            ProjectRole::ReadOnly => "read_only",
+        }
+    }
+
+    /// Whether this role grants at least what `required` does.
+    pub fn allows(&self, required: ProjectRole) -> bool {
+        *self >= required
+    }
+}
+
+impl From<String> for ProjectRole {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "owner" => ProjectRole::Owner,
+            "admin" => ProjectRole::Admin,
+            "developer" => ProjectRole::Developer,
+            _ => ProjectRole::ReadOnly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectCollaboratorStatus {
+    Active,
+    /// Invited by email; the address has no console account yet.
+    Invited,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectCollaborator {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub project_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub account_id: Option<i64>,
+    pub email: String,
+    pub name: Option<String>,
+    pub role: ProjectRole,
+    pub status: ProjectCollaboratorStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Groups endpoints by how much load a single request puts on shared resources.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointClass {
+    Read,
+    Write,
+    Expensive,
+}
+
+impl EndpointClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndpointClass::Read => "read",
+            EndpointClass::Write => "write",
+            EndpointClass::Expensive => "expensive",
+        }
+    }
+}
+
+impl From<String> for EndpointClass {
+    fn from(class: String) -> Self {
+        match class.as_str() {
+            "write" => EndpointClass::Write,
+            "expensive" => EndpointClass::Expensive,
+            _ => EndpointClass::Read,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub requests_per_minute: u32,
+    pub burst: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeploymentRateLimit {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub endpoint_class: EndpointClass,
+    pub limit: RateLimit,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    pub retry_after_secs: u64,
+}
+
+impl RateLimitDecision {
+    pub fn allow_unlimited() -> Self {
+        Self {
+            allowed: true,
+            limit: 0,
+            remaining: 0,
+            retry_after_secs: 0,
+        }
+    }
+}
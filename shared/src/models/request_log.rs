@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RequestLog {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub api_key_id: Option<i64>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub latency_ms: u32,
+    pub error: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RequestLogPage {
+    pub data: Vec<RequestLog>,
+    pub has_more: bool,
+    /// Pass as `cursor` to fetch the next, older page.
+    pub next_cursor: Option<String>,
+}
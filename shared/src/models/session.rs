@@ -21,3 +21,25 @@ pub struct Session {
     pub updated_at: DateTime<Utc>,
     pub active_signin_id: Option<i64>,
 }
+
+/// A sign-in on one device, as shown to deployment admins.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserSession {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub session_id: i64,
+    pub user_agent: Option<String>,
+    pub browser: Option<String>,
+    pub device: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_active_at: DateTime<Utc>,
+    /// The sign-in the caller named as its own.
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RevokedUserSessions {
+    pub revoked_count: usize,
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SignInLockoutStatus {
+    pub locked: bool,
+    /// Seconds until the lockout ends, set while locked.
+    pub retry_after_seconds: Option<i64>,
+    /// Failed attempts left before a lockout, unset when the policy is off.
+    pub remaining_attempts: Option<i64>,
+}
+
+impl SignInLockoutStatus {
+    pub fn unrestricted() -> Self {
+        Self {
+            locked: false,
+            retry_after_seconds: None,
+            remaining_attempts: None,
+        }
+    }
+}
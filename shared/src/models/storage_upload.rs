@@ -0,0 +1,86 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where an upload is stored. Each class has its own bucket, which may be in
+/// its own region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageClass {
+    /// Public files served from the CDN, such as logos.
+    Cdn,
+    /// Knowledge base documents, which aren't served publicly.
+    KnowledgeBase,
+}
+
+impl StorageClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageClass::Cdn => "cdn",
+            StorageClass::KnowledgeBase => "knowledge_base",
+        }
+    }
+
+    /// The largest file a signed upload of this class accepts.
+    pub fn max_upload_size(&self) -> i64 {
+        match self {
+            StorageClass::Cdn => 10 * 1024 * 1024,
+            StorageClass::KnowledgeBase => 100 * 1024 * 1024,
+        }
+    }
+
+    pub fn accepts_content_type(&self, content_type: &str) -> bool {
+        match self {
+            StorageClass::Cdn => content_type.starts_with("image/"),
+            StorageClass::KnowledgeBase => !content_type.is_empty(),
+        }
+    }
+}
+
+impl FromStr for StorageClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cdn" => Ok(StorageClass::Cdn),
+            "knowledge_base" => Ok(StorageClass::KnowledgeBase),
+            _ => Err(format!("Invalid storage class: {}", s)),
+        }
+    }
+}
+
+/// A file a client was given a signed URL to upload. Nothing should point at
+/// the file until the upload is confirmed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageUpload {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub storage_class: StorageClass,
+    pub object_key: String,
+    pub url: String,
+    pub content_type: String,
+    pub content_length: i64,
+    /// Base64 SHA-256 of the file, as S3 takes it.
+    pub checksum_sha256: String,
+    pub expires_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+/// How to upload a file straight to storage. The request must be sent with
+/// every header listed, as they are part of the signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedUpload {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub upload_id: i64,
+    pub method: String,
+    pub upload_url: String,
+    pub headers: BTreeMap<String, String>,
+    /// Where the file can be read once it is uploaded and confirmed.
+    pub url: String,
+    pub object_key: String,
+    pub expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Why a session token is no longer accepted.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenRevocationReason {
+    /// The token itself was revoked by its `jti`.
+    Token,
+    /// Every token issued to the user before a point in time was revoked.
+    User,
+    /// Every token the deployment issued before a point in time was revoked.
+    Deployment,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenRevocationStatus {
+    pub revoked: bool,
+    pub reason: Option<TokenRevocationReason>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevokedToken {
+    pub jti: String,
+    /// The revocation is forgotten after this, when the token expires anyway.
+    pub revoked_until: DateTime<Utc>,
+}
+
+/// Tokens issued before `not_before` are no longer accepted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevokedTokensBefore {
+    pub not_before: DateTime<Utc>,
+}
+
+/// Where services that verify session tokens ask whether one was revoked,
+/// passing its `jti`, `sub` and `iat` claims as `jti`, `user_id` and
+/// `issued_at`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TokenRevocationCheck {
+    pub method: String,
+    pub path: String,
+}
+
+impl TokenRevocationCheck {
+    pub fn for_deployment(deployment_id: i64) -> Self {
+        Self {
+            method: "GET".to_string(),
+            path: format!("/deployments/{}/tokens/revocation-status", deployment_id),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What a usage event counts towards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageMetric {
+    /// A user who signed in; each user counts once per period.
+    ActiveUser,
+    EmailSent,
+}
+
+impl UsageMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageMetric::ActiveUser => "active_user",
+            UsageMetric::EmailSent => "email_sent",
+        }
+    }
+}
+
+impl From<String> for UsageMetric {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "active_user" => UsageMetric::ActiveUser,
+            _ => UsageMetric::EmailSent,
+        }
+    }
+}
+
+/// A calendar month in UTC, the unit usage is billed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BillingPeriod {
+    pub start: NaiveDate,
+}
+
+impl BillingPeriod {
+    pub fn containing(at: DateTime<Utc>) -> Self {
+        let date = at.date_naive();
+        Self {
+            start: NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap_or(date),
+        }
+    }
+
+    /// The period starting on the first of the month `date` falls in.
+    pub fn from_date(date: NaiveDate) -> Self {
+        Self {
+            start: date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// Parses a month written as `2026-09`.
+    pub fn parse(month: &str) -> Option<Self> {
+        NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+            .ok()
+            .map(Self::from_date)
+    }
+
+    /// The first day of the next period, which this one runs up to.
+    pub fn end(&self) -> NaiveDate {
+        self.start
+            .checked_add_months(Months::new(1))
+            .unwrap_or(self.start)
+    }
+
+    pub fn previous(&self) -> Self {
+        Self {
+            start: self
+                .start
+                .checked_sub_months(Months::new(1))
+                .unwrap_or(self.start),
+        }
+    }
+
+    pub fn starts_at(&self) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&self.start.and_hms_opt(0, 0, 0).unwrap_or_default())
+    }
+
+    pub fn ends_at(&self) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&self.end().and_hms_opt(0, 0, 0).unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsagePeriodStatus {
+    /// Still running; the figures are computed live and keep changing.
+    Open,
+    /// Frozen by `CloseBillingPeriodCommand`. Later events are adjustments.
+    Closed,
+}
+
+/// Events that arrived for a period after it was closed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageAdjustments {
+    pub active_users: i64,
+    pub emails_sent: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentUsage {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub status: UsagePeriodStatus,
+    pub active_users: i64,
+    pub emails_sent: i64,
+    /// Emails sent per template name.
+    pub emails_by_template: BTreeMap<String, i64>,
+    pub adjustments: UsageAdjustments,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+/// Adjustments to an earlier period reported along with a later close.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageAdjustmentTotal {
+    pub period_start: NaiveDate,
+    pub metric: UsageMetric,
+    pub quantity: i64,
+}
+
+/// Published to the billing stream once per deployment and closed period.
+/// Delivery is at least once; `event_id` stays the same across redeliveries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BillingPeriodClosedEvent {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub event_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub project_id: i64,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub active_users: i64,
+    pub emails_sent: i64,
+    pub emails_by_template: BTreeMap<String, i64>,
+    /// Late events for earlier periods not reported in a previous event.
+    pub adjustments: Vec<UsageAdjustmentTotal>,
+    pub closed_at: DateTime<Utc>,
+}
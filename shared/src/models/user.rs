@@ -157,4 +157,5 @@ pub struct UserWithIdentifiers {
     pub username: Option<String>,
     pub primary_email_address: Option<String>,
     pub primary_phone_number: Option<String>,
+    pub primary_web3_wallet: Option<String>,
 }
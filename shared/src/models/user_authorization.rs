@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuthorizationRole {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrganizationAuthorization {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub membership_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub organization_id: i64,
+    pub organization_name: String,
+    pub roles: Vec<AuthorizationRole>,
+    /// Every permission the roles grant, sorted and without duplicates.
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceAuthorization {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub membership_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub workspace_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub organization_id: i64,
+    pub workspace_name: String,
+    pub roles: Vec<AuthorizationRole>,
+    /// Every permission the roles grant, sorted and without duplicates.
+    pub permissions: Vec<String>,
+}
+
+/// The deployment's b2b settings that change what members can do.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AuthorizationFlags {
+    pub organizations_enabled: bool,
+    pub workspaces_enabled: bool,
+    pub custom_org_role_enabled: bool,
+    pub custom_workspace_role_enabled: bool,
+}
+
+/// Everything needed to decide what a user may do in their organizations and
+/// workspaces, in one response.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserAuthorizationContext {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub user_id: i64,
+    pub flags: AuthorizationFlags,
+    pub organizations: Vec<OrganizationAuthorization>,
+    pub workspaces: Vec<WorkspaceAuthorization>,
+    /// When the newest membership, role or setting in this context last
+    /// changed. A removed membership can make it go back, so consumers
+    /// should refetch when it differs rather than only when it grows.
+    pub updated_at: DateTime<Utc>,
+}
@@ -2,7 +2,10 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use super::{SecondFactorPolicy, SchemaVersion, SocialConnection, UserEmailAddress, UserPhoneNumber};
+use super::{
+    SecondFactorPolicy, SchemaVersion, SocialConnection, UserEmailAddress, UserPhoneNumber,
+    UserWeb3Wallet,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserDetails {
@@ -25,14 +28,24 @@ pub struct UserDetails {
     // Primary identifiers
     pub primary_email_address: Option<String>,
     pub primary_phone_number: Option<String>,
+    pub primary_web3_wallet: Option<String>,
     
     // All identifiers
     pub email_addresses: Vec<UserEmailAddress>,
     pub phone_numbers: Vec<UserPhoneNumber>,
+    pub web3_wallets: Vec<UserWeb3Wallet>,
     pub social_connections: Vec<SocialConnection>,
     
     // Authentication
     pub has_password: bool,
     pub has_otp: bool,
     pub has_backup_codes: bool,
+    /// The user has to pick a new password the next time they sign in.
+    pub require_password_change: bool,
+
+    // Deletion
+    /// Set while the user is deleted but can still be restored.
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// When a deleted user is permanently removed.
+    pub purge_after: Option<DateTime<Utc>>,
 }
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserErasureMode {
+    /// The user and everything they own is deleted.
+    Delete,
+    /// The user row stays, so foreign keys and aggregate counts still hold,
+    /// but every identifying value is overwritten with a placeholder.
+    Anonymize,
+}
+
+impl UserErasureMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserErasureMode::Delete => "delete",
+            UserErasureMode::Anonymize => "anonymize",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErasureAction {
+    Deleted,
+    Anonymized,
+    /// Identifying values were removed from rows that aren't the user's own.
+    Scrubbed,
+    /// Counters kept in step with the deletion, such as member counts.
+    Recounted,
+    /// The deletion was handed to a store that carries it out later.
+    Queued,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ErasedTable {
+    pub table: String,
+    pub action: ErasureAction,
+    pub rows: u64,
+}
+
+/// What an erasure changed, table by table, for handing to auditors. Every
+/// table the erasure looks at is listed, including those it found nothing in.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserErasureReport {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub user_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub mode: UserErasureMode,
+    pub erased_at: DateTime<Utc>,
+    pub tables: Vec<ErasedTable>,
+}
+
+impl UserErasureReport {
+    pub(crate) fn record(&mut self, table: &str, action: ErasureAction, rows: u64) {
+        self.tables.push(ErasedTable {
+            table: table.to_string(),
+            action,
+            rows,
+        });
+    }
+}
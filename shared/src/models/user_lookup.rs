@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::UserDetails;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserIdentifierKind {
+    EmailAddress,
+    PhoneNumber,
+    Username,
+}
+
+impl From<String> for UserIdentifierKind {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "phone_number" => UserIdentifierKind::PhoneNumber,
+            "username" => UserIdentifierKind::Username,
+            _ => UserIdentifierKind::EmailAddress,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserAccountState {
+    Active,
+    /// Disabled by an admin, or anonymized by an erasure request.
+    Disabled,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserOrganizationMembership {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub organization_id: i64,
+    pub organization_name: String,
+    pub roles: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The user an identifier belongs to, with every identifier they have and
+/// the organizations they're in.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserLookup {
+    pub matched_on: UserIdentifierKind,
+    pub state: UserAccountState,
+    pub user: UserDetails,
+    pub organization_memberships: Vec<UserOrganizationMembership>,
+}
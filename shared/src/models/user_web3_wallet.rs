@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserWeb3Wallet {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deployment_id: i64,
+    pub user_id: i64,
+    /// EIP-55 checksummed.
+    pub address: String,
+    /// The EVM chain the wallet was added or last signed in on.
+    pub chain_id: i64,
+    pub verified: bool,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub is_primary: bool,
+}
+
+/// A sign-in message for a wallet to sign, in the Sign-In with Ethereum
+/// (EIP-4361) format.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Web3SignInChallenge {
+    pub address: String,
+    pub chain_id: i64,
+    pub nonce: String,
+    pub message: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A wallet that has proven it signed a challenge.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VerifiedWeb3Wallet {
+    pub address: String,
+    pub chain_id: i64,
+    /// The user the wallet belongs to, if anyone has added it.
+    #[serde(with = "crate::utils::serde::i64_as_string_option")]
+    pub user_id: Option<i64>,
+}
@@ -0,0 +1,15 @@
+use serde::Serialize;
+
+use crate::validators::FieldViolation;
+
+/// Whether a username can be taken on a deployment, for sign-up forms to
+/// check as the user types.
+#[derive(Debug, Serialize, Clone)]
+pub struct UsernameAvailability {
+    /// The username as it would be stored, after normalization.
+    pub username: String,
+    pub available: bool,
+    /// Every rule the username breaks, including `taken` when another user
+    /// already has it.
+    pub errors: Vec<FieldViolation>,
+}
@@ -1,6 +1,16 @@
+use std::{collections::HashMap, time::Instant};
+
 use sqlx::Row;
 
-use crate::{error::AppError, models::AiAgentWithDetails, queries::Query, state::AppState};
+use crate::{
+    error::AppError,
+    models::{AgentContext, AgentContextChunk, AiAgentKnowledgeBase, AiAgentWithDetails},
+    queries::{
+        GetKnowledgeBaseCollectionsQuery, GetKnowledgeBaseEmbedderQuery, Query, chunk_match,
+    },
+    services::qdrant::QdrantService,
+    state::AppState,
+};
 
 pub struct GetAiAgentsQuery {
     pub deployment_id: i64,
@@ -42,7 +52,7 @@ impl GetAiAgentsQuery {
 impl Query for GetAiAgentsQuery {
     type Output = Vec<AiAgentWithDetails>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let base_query = r#"
             SELECT
                 a.id, a.created_at, a.updated_at, a.name, a.description,
@@ -125,7 +135,7 @@ impl GetAiAgentByIdQuery {
 impl Query for GetAiAgentByIdQuery {
     type Output = AiAgentWithDetails;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let agent = sqlx::query!(
             r#"
             SELECT
@@ -173,3 +183,186 @@ impl Query for GetAiAgentByIdQuery {
         })
     }
 }
+
+pub struct ListAgentKnowledgeBasesQuery {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+}
+
+impl ListAgentKnowledgeBasesQuery {
+    pub fn new(deployment_id: i64, agent_id: i64) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+        }
+    }
+}
+
+impl Query for ListAgentKnowledgeBasesQuery {
+    type Output = Vec<AiAgentKnowledgeBase>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                akb.agent_id, akb.knowledge_base_id, kb.name, kb.description,
+                akb.top_k, akb.score_threshold, akb.include_citations,
+                akb.created_at, akb.updated_at
+            FROM ai_agent_knowledge_bases akb
+            JOIN ai_agents a ON a.id = akb.agent_id
+            JOIN ai_knowledge_bases kb
+                ON kb.id = akb.knowledge_base_id AND kb.deployment_id = a.deployment_id
+            WHERE a.id = $1 AND a.deployment_id = $2
+            ORDER BY akb.created_at, akb.knowledge_base_id
+            "#,
+        )
+        .bind(self.agent_id)
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AiAgentKnowledgeBase {
+                agent_id: row.get("agent_id"),
+                knowledge_base_id: row.get("knowledge_base_id"),
+                name: row.get("name"),
+                description: row.get("description"),
+                top_k: row.get("top_k"),
+                score_threshold: row.get("score_threshold"),
+                include_citations: row.get("include_citations"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+            })
+            .collect())
+    }
+}
+
+/// Searches every knowledge base attached to an agent, each with its own
+/// settings, and merges the matches by score. This is the context an agent
+/// run puts in its prompt.
+pub struct RetrieveAgentContextQuery {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+    pub query: String,
+    pub limit: Option<usize>,
+}
+
+impl RetrieveAgentContextQuery {
+    pub fn new(deployment_id: i64, agent_id: i64, query: String) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            query,
+            limit: None,
+        }
+    }
+
+    /// Keeps only the best `limit` matches across all knowledge bases.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl Query for RetrieveAgentContextQuery {
+    type Output = AgentContext;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self.query.trim().is_empty() {
+            return Err(AppError::Validation("Search query is required".to_string()));
+        }
+
+        let attachments = ListAgentKnowledgeBasesQuery::new(self.deployment_id, self.agent_id)
+            .execute(app_state)
+            .await?;
+        if attachments.is_empty() {
+            return Ok(AgentContext {
+                results: Vec::new(),
+                embedding_latency_ms: 0,
+                search_latency_ms: 0,
+            });
+        }
+
+        let mut embedders = Vec::with_capacity(attachments.len());
+        for attachment in &attachments {
+            embedders.push(
+                GetKnowledgeBaseEmbedderQuery::new(attachment.knowledge_base_id)
+                    .execute(app_state)
+                    .await?,
+            );
+        }
+
+        // The query is embedded once per model the knowledge bases use
+        let embedding_started = Instant::now();
+        let mut query_embeddings: HashMap<String, Vec<f32>> = HashMap::new();
+        for embedder in &embedders {
+            if !query_embeddings.contains_key(embedder.model()) {
+                let query_embedding = embedder.embed_one(self.query.clone()).await?;
+                query_embeddings.insert(embedder.model().to_string(), query_embedding);
+            }
+        }
+        let embedding_latency_ms = embedding_started.elapsed().as_millis() as u64;
+
+        let search_started = Instant::now();
+        let searches = attachments
+            .iter()
+            .zip(&embedders)
+            .map(|(attachment, embedder)| {
+                let query_embedding = query_embeddings[embedder.model()].clone();
+                async move {
+                    let collections =
+                        GetKnowledgeBaseCollectionsQuery::new(attachment.knowledge_base_id)
+                            .execute(app_state)
+                            .await?;
+                    let results = QdrantService::search_with_filters(
+                        &collections.active,
+                        query_embedding,
+                        attachment.top_k as u64,
+                        attachment.knowledge_base_id,
+                        attachment.score_threshold,
+                        HashMap::new(),
+                    )
+                    .await?;
+
+                    Ok::<_, AppError>(
+                        results
+                            .into_iter()
+                            .map(|result| {
+                                let mut chunk = chunk_match(result);
+                                if !attachment.include_citations {
+                                    chunk.document_id = None;
+                                    chunk.source_url = None;
+                                    chunk.title = None;
+                                    chunk.chunk_index = None;
+                                    chunk.char_start = None;
+                                    chunk.char_end = None;
+                                }
+                                AgentContextChunk {
+                                    knowledge_base_id: attachment.knowledge_base_id,
+                                    chunk,
+                                }
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                }
+            });
+        let mut results: Vec<AgentContextChunk> = futures::future::try_join_all(searches)
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
+        let search_latency_ms = search_started.elapsed().as_millis() as u64;
+
+        results.sort_by(|a, b| b.chunk.score.total_cmp(&a.chunk.score));
+        if let Some(limit) = self.limit {
+            results.truncate(limit);
+        }
+
+        Ok(AgentContext {
+            results,
+            embedding_latency_ms,
+            search_latency_ms,
+        })
+    }
+}
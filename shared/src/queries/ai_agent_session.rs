@@ -0,0 +1,182 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{AiAgentSessionMessage, AiAgentSessionTranscript, AiAgentSessionWithStats},
+    queries::Query,
+    state::AppState,
+};
+
+const SESSION_WITH_STATS_SELECT: &str = r#"
+    SELECT
+        s.id, s.created_at, s.updated_at, s.agent_id, s.status, s.metadata, s.closed_at,
+        COALESCE(m.message_count, 0) AS message_count,
+        COALESCE(m.total_tokens, 0) AS total_tokens,
+        EXTRACT(EPOCH FROM (COALESCE(s.closed_at, m.last_message_at, s.created_at) - s.created_at))::bigint AS duration_seconds
+    FROM ai_agent_sessions s
+    JOIN ai_agents a ON a.id = s.agent_id
+    LEFT JOIN LATERAL (
+        SELECT
+            COUNT(*) AS message_count,
+            SUM(prompt_tokens + completion_tokens)::bigint AS total_tokens,
+            MAX(created_at) AS last_message_at
+        FROM ai_agent_session_messages
+        WHERE session_id = s.id
+    ) m ON TRUE
+"#;
+
+fn session_with_stats_from_row(row: &PgRow) -> AiAgentSessionWithStats {
+    AiAgentSessionWithStats {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        agent_id: row.get("agent_id"),
+        status: row.get("status"),
+        metadata: row.get("metadata"),
+        closed_at: row.get("closed_at"),
+        message_count: row.get("message_count"),
+        total_tokens: row.get("total_tokens"),
+        duration_seconds: row.get::<Option<i64>, _>("duration_seconds").unwrap_or(0),
+    }
+}
+
+pub struct ListAgentSessionsQuery {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+    pub offset: u32,
+    pub limit: u32,
+    pub started_after: Option<DateTime<Utc>>,
+    pub started_before: Option<DateTime<Utc>>,
+}
+
+impl ListAgentSessionsQuery {
+    pub fn new(deployment_id: i64, agent_id: i64) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            offset: 0,
+            limit: 50,
+            started_after: None,
+            started_before: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: Option<u32>) -> Self {
+        if let Some(limit) = limit {
+            self.limit = limit;
+        }
+        self
+    }
+
+    pub fn with_offset(mut self, offset: Option<u32>) -> Self {
+        if let Some(offset) = offset {
+            self.offset = offset;
+        }
+        self
+    }
+
+    pub fn with_started_after(mut self, started_after: Option<DateTime<Utc>>) -> Self {
+        self.started_after = started_after;
+        self
+    }
+
+    pub fn with_started_before(mut self, started_before: Option<DateTime<Utc>>) -> Self {
+        self.started_before = started_before;
+        self
+    }
+}
+
+impl Query for ListAgentSessionsQuery {
+    type Output = Vec<AiAgentSessionWithStats>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
+            r#"{}
+            WHERE s.agent_id = $1 AND a.deployment_id = $2
+                AND ($3::timestamptz IS NULL OR s.created_at >= $3)
+                AND ($4::timestamptz IS NULL OR s.created_at < $4)
+            ORDER BY s.created_at DESC
+            LIMIT $5 OFFSET $6
+            "#,
+            SESSION_WITH_STATS_SELECT
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(self.agent_id)
+            .bind(self.deployment_id)
+            .bind(self.started_after)
+            .bind(self.started_before)
+            .bind(self.limit as i64)
+            .bind(self.offset as i64)
+            .fetch_all(&app_state.db_pool)
+            .await?;
+
+        Ok(rows.iter().map(session_with_stats_from_row).collect())
+    }
+}
+
+pub struct GetAgentSessionTranscriptQuery {
+    pub deployment_id: i64,
+    pub agent_id: i64,
+    pub session_id: i64,
+}
+
+impl GetAgentSessionTranscriptQuery {
+    pub fn new(deployment_id: i64, agent_id: i64, session_id: i64) -> Self {
+        Self {
+            deployment_id,
+            agent_id,
+            session_id,
+        }
+    }
+}
+
+impl Query for GetAgentSessionTranscriptQuery {
+    type Output = AiAgentSessionTranscript;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
+            "{} WHERE s.id = $1 AND s.agent_id = $2 AND a.deployment_id = $3",
+            SESSION_WITH_STATS_SELECT
+        );
+
+        let session = sqlx::query(&query)
+            .bind(self.session_id)
+            .bind(self.agent_id)
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?
+            .map(|row| session_with_stats_from_row(&row))
+            .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+        let messages = sqlx::query(
+            r#"
+            SELECT
+                id, created_at, session_id, sequence, role, content,
+                prompt_tokens, completion_tokens, tool_calls
+            FROM ai_agent_session_messages
+            WHERE session_id = $1
+            ORDER BY sequence ASC
+            "#,
+        )
+        .bind(self.session_id)
+        .fetch_all(&app_state.db_pool)
+        .await?
+        .into_iter()
+        .map(|row| AiAgentSessionMessage {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            session_id: row.get("session_id"),
+            sequence: row.get("sequence"),
+            role: row.get("role"),
+            content: row.get("content"),
+            prompt_tokens: row.get("prompt_tokens"),
+            completion_tokens: row.get("completion_tokens"),
+            tool_calls: row.get("tool_calls"),
+        })
+        .collect();
+
+        Ok(AiAgentSessionTranscript { session, messages })
+    }
+}
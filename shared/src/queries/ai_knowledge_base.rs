@@ -1,9 +1,21 @@
+use std::{collections::HashMap, time::Instant};
+
+use serde_json::json;
 use sqlx::Row;
 
 use crate::{
-    error::AppError,
-    models::{AiKnowledgeBaseDocument, AiKnowledgeBaseWithDetails},
+    error::{AppError, ErrorCode},
+    models::{
+        AiKnowledgeBaseCrawl, AiKnowledgeBaseReembedJob, AiKnowledgeBaseWithDetails, ChunkPreview,
+        ChunkingConfig, ChunkingDryRun, DeploymentEmbeddingProviders, EmbeddingProviderInfo,
+        KnowledgeBaseChunkMatch, KnowledgeBaseCollections, KnowledgeBaseDocumentSummary,
+        KnowledgeBaseSearchResults,
+    },
     queries::Query,
+    services::{
+        Embedder,
+        qdrant::{QdrantService, SearchResult},
+    },
     state::AppState,
 };
 
@@ -33,11 +45,12 @@ impl GetAiKnowledgeBasesQuery {
 impl Query for GetAiKnowledgeBasesQuery {
     type Output = Vec<AiKnowledgeBaseWithDetails>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let base_query = r#"
             SELECT
                 kb.id, kb.created_at, kb.updated_at, kb.name, kb.description,
-                kb.configuration, kb.deployment_id,
+                kb.configuration, kb.deployment_id, kb.embedding_provider, kb.embedding_model,
+                kb.chunking_config,
                 COALESCE(d.documents_count, 0) as documents_count,
                 COALESCE(d.total_size, 0) as total_size
             FROM ai_knowledge_bases kb
@@ -78,6 +91,9 @@ impl Query for GetAiKnowledgeBasesQuery {
                 description: row.get("description"),
                 configuration: row.get("configuration"),
                 deployment_id: row.get("deployment_id"),
+                embedding_provider: row.get("embedding_provider"),
+                embedding_model: row.get("embedding_model"),
+                chunking: ChunkingConfig::from_stored(row.get("chunking_config")),
                 documents_count: row.get::<Option<i64>, _>("documents_count").unwrap_or(0),
                 total_size: row.get("total_size"),
             })
@@ -102,12 +118,13 @@ impl GetAiKnowledgeBaseByIdQuery {
 impl Query for GetAiKnowledgeBaseByIdQuery {
     type Output = AiKnowledgeBaseWithDetails;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let knowledge_base = sqlx::query(
             r#"
             SELECT
                 kb.id, kb.created_at, kb.updated_at, kb.name, kb.description,
-                kb.configuration, kb.deployment_id,
+                kb.configuration, kb.deployment_id, kb.embedding_provider, kb.embedding_model,
+                kb.chunking_config,
                 COALESCE(d.documents_count, 0) as documents_count,
                 COALESCE(d.total_size, 0) as total_size
             FROM ai_knowledge_bases kb
@@ -133,6 +150,9 @@ impl Query for GetAiKnowledgeBaseByIdQuery {
             description: knowledge_base.get("description"),
             configuration: knowledge_base.get("configuration"),
             deployment_id: knowledge_base.get("deployment_id"),
+            embedding_provider: knowledge_base.get("embedding_provider"),
+            embedding_model: knowledge_base.get("embedding_model"),
+            chunking: ChunkingConfig::from_stored(knowledge_base.get("chunking_config")),
             documents_count: knowledge_base
                 .get::<Option<i64>, _>("documents_count")
                 .unwrap_or(0),
@@ -141,13 +161,15 @@ impl Query for GetAiKnowledgeBaseByIdQuery {
     }
 }
 
-pub struct GetKnowledgeBaseDocumentsQuery {
+/// A page of a knowledge base's documents, newest first, with where each
+/// came from and how it was chunked.
+pub struct ListKnowledgeBaseDocumentsQuery {
     pub knowledge_base_id: i64,
     pub limit: usize,
     pub offset: usize,
 }
 
-impl GetKnowledgeBaseDocumentsQuery {
+impl ListKnowledgeBaseDocumentsQuery {
     pub fn new(knowledge_base_id: i64, limit: usize, offset: usize) -> Self {
         Self {
             knowledge_base_id,
@@ -157,44 +179,553 @@ impl GetKnowledgeBaseDocumentsQuery {
     }
 }
 
-impl Query for GetKnowledgeBaseDocumentsQuery {
-    type Output = Vec<AiKnowledgeBaseDocument>;
+impl Query for ListKnowledgeBaseDocumentsQuery {
+    type Output = Vec<KnowledgeBaseDocumentSummary>;
+
+    const PREFERS_READ_REPLICA: bool = true;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let documents = sqlx::query!(
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
             r#"
             SELECT
-                id, created_at, updated_at, title, description, file_name,
-                file_size, file_type, file_url, knowledge_base_id,
-                processing_metadata
+                id, created_at, updated_at, title, file_name, file_size, file_type,
+                CASE
+                    WHEN processing_metadata->>'crawl_id' IS NOT NULL THEN 'crawl'
+                    WHEN processing_metadata->>'fetched_from' IS NOT NULL THEN 'url'
+                    ELSE 'upload'
+                END AS source,
+                COALESCE(
+                    processing_metadata->>'source_url',
+                    processing_metadata->>'fetched_from'
+                ) AS source_url,
+                ingestion_status, ingestion_error, chunk_count, chunk_size, chunk_overlap,
+                COALESCE(chunking_strategy, 'characters') AS chunking_strategy
             FROM ai_knowledge_base_documents
             WHERE knowledge_base_id = $1
             ORDER BY created_at DESC
             LIMIT $2 OFFSET $3
             "#,
-            self.knowledge_base_id,
-            self.limit as i64,
-            self.offset as i64
         )
-        .fetch_all(&app_state.db_pool)
-        .await
-        .map_err(|e| AppError::Database(e))?;
+        .bind(self.knowledge_base_id)
+        .bind(self.limit as i64)
+        .bind(self.offset as i64)
+        .fetch_all(self.pool(app_state))
+        .await?;
 
-        Ok(documents
+        Ok(rows
             .into_iter()
-            .map(|row| AiKnowledgeBaseDocument {
-                id: row.id,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-                title: row.title,
-                description: row.description,
-                file_name: row.file_name,
-                file_size: row.file_size,
-                file_type: row.file_type,
-                file_url: row.file_url,
-                knowledge_base_id: row.knowledge_base_id,
-                processing_metadata: row.processing_metadata,
+            .map(|row| KnowledgeBaseDocumentSummary {
+                id: row.get("id"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                title: row.get("title"),
+                file_name: row.get("file_name"),
+                file_size: row.get("file_size"),
+                file_type: row.get("file_type"),
+                source: row.get::<String, _>("source").into(),
+                source_url: row.get("source_url"),
+                ingestion_status: row.get::<String, _>("ingestion_status").into(),
+                ingestion_error: row.get("ingestion_error"),
+                chunk_count: row.get("chunk_count"),
+                chunk_size: row.get("chunk_size"),
+                chunk_overlap: row.get("chunk_overlap"),
+                chunking_strategy: row.get::<String, _>("chunking_strategy").into(),
             })
             .collect())
     }
 }
+
+pub struct GetKnowledgeBaseCrawlQuery {
+    pub knowledge_base_id: i64,
+    pub crawl_id: i64,
+}
+
+impl GetKnowledgeBaseCrawlQuery {
+    pub fn new(knowledge_base_id: i64, crawl_id: i64) -> Self {
+        Self {
+            knowledge_base_id,
+            crawl_id,
+        }
+    }
+}
+
+impl Query for GetKnowledgeBaseCrawlQuery {
+    type Output = AiKnowledgeBaseCrawl;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                id, created_at, updated_at, knowledge_base_id, root_url, status, crawl,
+                max_pages, max_depth, pages_ingested, pages_skipped, pages_failed,
+                error, completed_at
+            FROM ai_knowledge_base_crawls
+            WHERE id = $1 AND knowledge_base_id = $2
+            "#,
+        )
+        .bind(self.crawl_id)
+        .bind(self.knowledge_base_id)
+        .fetch_optional(&app_state.db_pool)
+        .await
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Crawl not found".to_string()))?;
+
+        Ok(AiKnowledgeBaseCrawl {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            knowledge_base_id: row.get("knowledge_base_id"),
+            root_url: row.get("root_url"),
+            status: row.get("status"),
+            crawl: row.get("crawl"),
+            max_pages: row.get("max_pages"),
+            max_depth: row.get("max_depth"),
+            pages_ingested: row.get("pages_ingested"),
+            pages_skipped: row.get("pages_skipped"),
+            pages_failed: row.get("pages_failed"),
+            error: row.get("error"),
+            completed_at: row.get("completed_at"),
+        })
+    }
+}
+
+pub(crate) fn chunk_match(result: SearchResult) -> KnowledgeBaseChunkMatch {
+    let string_field = |key: &str| {
+        result
+            .metadata
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    let integer_field = |key: &str| result.metadata.get(key).and_then(|v| v.as_i64());
+
+    KnowledgeBaseChunkMatch {
+        chunk_id: result.id,
+        score: result.score,
+        document_id: string_field("document_id"),
+        source_url: string_field("source_url"),
+        title: string_field("title"),
+        chunk_index: integer_field("chunk_index"),
+        char_start: integer_field("char_start"),
+        char_end: integer_field("char_end"),
+        content: result.content,
+    }
+}
+
+pub struct SearchKnowledgeBaseQuery {
+    pub knowledge_base_id: i64,
+    pub query: String,
+    pub top_k: u64,
+    pub score_threshold: Option<f32>,
+    pub document_id: Option<i64>,
+    pub source_url: Option<String>,
+}
+
+impl SearchKnowledgeBaseQuery {
+    pub fn new(knowledge_base_id: i64, query: String) -> Self {
+        Self {
+            knowledge_base_id,
+            query,
+            top_k: 10,
+            score_threshold: None,
+            document_id: None,
+            source_url: None,
+        }
+    }
+
+    pub fn with_top_k(mut self, top_k: u64) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    pub fn with_score_threshold(mut self, score_threshold: f32) -> Self {
+        self.score_threshold = Some(score_threshold);
+        self
+    }
+
+    pub fn with_document_id(mut self, document_id: i64) -> Self {
+        self.document_id = Some(document_id);
+        self
+    }
+
+    pub fn with_source_url(mut self, source_url: String) -> Self {
+        self.source_url = Some(source_url);
+        self
+    }
+}
+
+impl Query for SearchKnowledgeBaseQuery {
+    type Output = KnowledgeBaseSearchResults;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if self.query.trim().is_empty() {
+            return Err(AppError::Validation("Search query is required".to_string()));
+        }
+
+        if !(1..=100).contains(&self.top_k) {
+            return Err(AppError::Validation(
+                "top_k must be between 1 and 100".to_string(),
+            ));
+        }
+
+        if let Some(score_threshold) = self.score_threshold
+            && !(0.0..=1.0).contains(&score_threshold)
+        {
+            return Err(AppError::Validation(
+                "score_threshold must be between 0 and 1".to_string(),
+            ));
+        }
+
+        let mut metadata_filters = HashMap::new();
+        if let Some(document_id) = self.document_id {
+            metadata_filters.insert("document_id".to_string(), json!(document_id.to_string()));
+        }
+        if let Some(source_url) = &self.source_url {
+            metadata_filters.insert("source_url".to_string(), json!(source_url));
+        }
+
+        let embedder = GetKnowledgeBaseEmbedderQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?;
+
+        let embedding_started = Instant::now();
+        let query_embedding = embedder.embed_one(self.query.clone()).await?;
+        let embedding_latency_ms = embedding_started.elapsed().as_millis() as u64;
+
+        let collections = GetKnowledgeBaseCollectionsQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?;
+
+        let search_started = Instant::now();
+        let results = QdrantService::search_with_filters(
+            &collections.active,
+            query_embedding,
+            self.top_k,
+            self.knowledge_base_id,
+            self.score_threshold,
+            metadata_filters,
+        )
+        .await?;
+        let search_latency_ms = search_started.elapsed().as_millis() as u64;
+
+        let results = results.into_iter().map(chunk_match).collect();
+
+        Ok(KnowledgeBaseSearchResults {
+            results,
+            embedding_latency_ms,
+            search_latency_ms,
+        })
+    }
+}
+
+/// The embedder a knowledge base's vectors, and the queries searching them,
+/// have to come from: a provider serving the model the vectors were made
+/// with. The knowledge base's own provider is preferred when it serves that
+/// model; when no configured provider does, the knowledge base can't be
+/// searched or written to until it is re-embedded.
+pub struct GetKnowledgeBaseEmbedderQuery {
+    pub knowledge_base_id: i64,
+}
+
+impl GetKnowledgeBaseEmbedderQuery {
+    pub fn new(knowledge_base_id: i64) -> Self {
+        Self { knowledge_base_id }
+    }
+}
+
+impl Query for GetKnowledgeBaseEmbedderQuery {
+    type Output = Embedder;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COALESCE(kb.embedding_provider, d.ai_embedding_provider) AS embedding_provider,
+                kb.embedding_model
+            FROM ai_knowledge_bases kb
+            JOIN deployments d ON d.id = kb.deployment_id
+            WHERE kb.id = $1
+            "#,
+        )
+        .bind(self.knowledge_base_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Knowledge base not found".to_string()))?;
+
+        let embedding_provider: Option<String> = row.get("embedding_provider");
+        let Some(embedding_model) = row.get::<Option<String>, _>("embedding_model") else {
+            return Ok(app_state.embedding_service.default_embedder().clone());
+        };
+
+        app_state
+            .embedding_service
+            .embedder_for_model(&embedding_model, embedding_provider.as_deref())
+            .cloned()
+            .ok_or_else(|| {
+                AppError::coded(
+                    ErrorCode::EmbeddingModelMismatch,
+                    format!(
+                        "The knowledge base's vectors were made with '{}', which no configured embedding provider serves; re-embed it first",
+                        embedding_model
+                    ),
+                )
+                .with_details(json!({ "embedding_model": embedding_model }))
+            })
+    }
+}
+
+/// How a knowledge base chunks the documents added to it.
+pub struct GetKnowledgeBaseChunkingQuery {
+    pub knowledge_base_id: i64,
+}
+
+impl GetKnowledgeBaseChunkingQuery {
+    pub fn new(knowledge_base_id: i64) -> Self {
+        Self { knowledge_base_id }
+    }
+}
+
+impl Query for GetKnowledgeBaseChunkingQuery {
+    type Output = ChunkingConfig;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let chunking_config: Option<serde_json::Value> =
+            sqlx::query_scalar("SELECT chunking_config FROM ai_knowledge_bases WHERE id = $1")
+                .bind(self.knowledge_base_id)
+                .fetch_optional(&app_state.db_pool)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Knowledge base not found".to_string()))?;
+
+        Ok(ChunkingConfig::from_stored(chunking_config))
+    }
+}
+
+/// Largest sample document a chunking dry run takes.
+pub const MAX_DRY_RUN_CONTENT_BYTES: usize = 512 * 1024;
+
+/// Splits a sample document the way the knowledge base would, or with the
+/// settings given, so they can be tried out. Nothing is stored or embedded.
+pub struct DryRunChunkingQuery {
+    pub deployment_id: i64,
+    pub knowledge_base_id: i64,
+    pub content: String,
+    pub file_type: String,
+    pub chunking: Option<ChunkingConfig>,
+}
+
+impl DryRunChunkingQuery {
+    pub fn new(deployment_id: i64, knowledge_base_id: i64, content: String) -> Self {
+        Self {
+            deployment_id,
+            knowledge_base_id,
+            content,
+            file_type: "text/plain".to_string(),
+            chunking: None,
+        }
+    }
+
+    pub fn with_file_type(mut self, file_type: String) -> Self {
+        self.file_type = file_type;
+        self
+    }
+
+    /// Tries these settings instead of the knowledge base's.
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = Some(chunking);
+        self
+    }
+}
+
+impl Query for DryRunChunkingQuery {
+    type Output = ChunkingDryRun;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let knowledge_base =
+            GetAiKnowledgeBaseByIdQuery::new(self.deployment_id, self.knowledge_base_id)
+                .execute(app_state)
+                .await
+                .map_err(|_| AppError::NotFound("Knowledge base not found".to_string()))?;
+        let chunking = self.chunking.unwrap_or(knowledge_base.chunking);
+        chunking.validate()?;
+
+        // Tokens are counted the way the knowledge base's model counts them
+        let tokenizer = GetKnowledgeBaseEmbedderQuery::new(self.knowledge_base_id)
+            .execute(app_state)
+            .await?
+            .tokenizer();
+
+        let text_processing_service = &app_state.text_processing_service;
+        let text = text_processing_service.extract_text_for_chunking(
+            self.content.as_bytes(),
+            &self.file_type,
+            &chunking,
+        )?;
+        let chunks: Vec<ChunkPreview> = text_processing_service
+            .chunk_text(&text, &chunking, tokenizer)?
+            .into_iter()
+            .map(|chunk| ChunkPreview {
+                chunk_index: chunk.chunk_index,
+                content: chunk.content,
+                char_start: chunk.start_offset,
+                char_end: chunk.end_offset,
+                token_count: chunk.token_count,
+                heading_path: chunk.heading_path,
+            })
+            .collect();
+
+        Ok(ChunkingDryRun {
+            chunking,
+            total_tokens: chunks.iter().map(|chunk| chunk.token_count).sum(),
+            chunks,
+        })
+    }
+}
+
+pub struct GetKnowledgeBaseCollectionsQuery {
+    pub knowledge_base_id: i64,
+}
+
+impl GetKnowledgeBaseCollectionsQuery {
+    pub fn new(knowledge_base_id: i64) -> Self {
+        Self { knowledge_base_id }
+    }
+}
+
+impl Query for GetKnowledgeBaseCollectionsQuery {
+    type Output = KnowledgeBaseCollections;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                kb.active_collection,
+                (
+                    SELECT target_collection
+                    FROM ai_knowledge_base_reembed_jobs
+                    WHERE knowledge_base_id = kb.id AND status <> 'completed'
+                    ORDER BY created_at DESC
+                    LIMIT 1
+                ) AS pending_collection
+            FROM ai_knowledge_bases kb
+            WHERE kb.id = $1
+            "#,
+        )
+        .bind(self.knowledge_base_id)
+        .fetch_optional(&app_state.db_pool)
+        .await
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Knowledge base not found".to_string()))?;
+
+        Ok(KnowledgeBaseCollections {
+            active: row
+                .get::<Option<String>, _>("active_collection")
+                .unwrap_or_else(QdrantService::default_collection),
+            pending: row.get("pending_collection"),
+        })
+    }
+}
+
+pub struct GetReembedProgressQuery {
+    pub knowledge_base_id: i64,
+    pub job_id: i64,
+}
+
+impl GetReembedProgressQuery {
+    pub fn new(knowledge_base_id: i64, job_id: i64) -> Self {
+        Self {
+            knowledge_base_id,
+            job_id,
+        }
+    }
+}
+
+impl Query for GetReembedProgressQuery {
+    type Output = AiKnowledgeBaseReembedJob;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                id, created_at, updated_at, knowledge_base_id, source_collection,
+                target_collection, status, cursor, processed_chunks, total_chunks,
+                error, completed_at, embedding_provider, embedding_model
+            FROM ai_knowledge_base_reembed_jobs
+            WHERE id = $1 AND knowledge_base_id = $2
+            "#,
+        )
+        .bind(self.job_id)
+        .bind(self.knowledge_base_id)
+        .fetch_optional(&app_state.db_pool)
+        .await
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Re-embedding job not found".to_string()))?;
+
+        Ok(AiKnowledgeBaseReembedJob {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            knowledge_base_id: row.get("knowledge_base_id"),
+            source_collection: row.get("source_collection"),
+            target_collection: row.get("target_collection"),
+            status: row.get("status"),
+            cursor: row.get("cursor"),
+            processed_chunks: row.get("processed_chunks"),
+            total_chunks: row.get("total_chunks"),
+            error: row.get("error"),
+            completed_at: row.get("completed_at"),
+            embedding_provider: row.get("embedding_provider"),
+            embedding_model: row.get("embedding_model"),
+        })
+    }
+}
+
+/// The embedding providers the platform is configured with, and which one
+/// the deployment uses for new knowledge bases.
+pub struct GetDeploymentEmbeddingProvidersQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentEmbeddingProvidersQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+pub(crate) fn deployment_embedding_providers(
+    app_state: &AppState,
+    selected: Option<String>,
+) -> DeploymentEmbeddingProviders {
+    let default = app_state.embedding_service.default_embedder().name();
+    let providers = app_state
+        .embedding_service
+        .embedders()
+        .map(|embedder| EmbeddingProviderInfo {
+            name: embedder.name().to_string(),
+            model: embedder.model().to_string(),
+            fallback: embedder.fallback().map(str::to_string),
+            is_default: embedder.name() == default,
+        })
+        .collect();
+
+    DeploymentEmbeddingProviders {
+        providers,
+        selected,
+    }
+}
+
+impl Query for GetDeploymentEmbeddingProvidersQuery {
+    type Output = DeploymentEmbeddingProviders;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let selected: Option<Option<String>> = sqlx::query_scalar(
+            "SELECT ai_embedding_provider FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+        let selected =
+            selected.ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        Ok(deployment_embedding_providers(app_state, selected))
+    }
+}
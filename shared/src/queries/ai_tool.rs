@@ -43,7 +43,7 @@ impl GetAiToolsQuery {
 impl Query for GetAiToolsQuery {
     type Output = Vec<AiToolWithDetails>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut query = r#"
             SELECT
                 t.id, t.created_at, t.updated_at, t.name, t.description,
@@ -134,7 +134,7 @@ impl GetAiToolByIdQuery {
 impl Query for GetAiToolByIdQuery {
     type Output = AiToolWithDetails;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let tool = sqlx::query!(
             r#"
             SELECT
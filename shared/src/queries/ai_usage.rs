@@ -0,0 +1,120 @@
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{AiAgentUsage, AiBudget, AiUsageReport, BillingPeriod},
+    state::AppState,
+};
+
+use super::Query;
+
+/// A deployment's model usage in one month, per agent, from the hourly
+/// rollups. Agents with no usage yet are listed with zeroes so their
+/// budgets show up too.
+pub struct GetAgentUsageQuery {
+    deployment_id: i64,
+    period: BillingPeriod,
+}
+
+impl GetAgentUsageQuery {
+    pub fn new(deployment_id: i64, period: BillingPeriod) -> Self {
+        Self {
+            deployment_id,
+            period,
+        }
+    }
+}
+
+impl Query for GetAgentUsageQuery {
+    type Output = AiUsageReport;
+
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let pool = self.pool(app_state);
+
+        let deployment = sqlx::query(
+            r#"
+            SELECT ai_monthly_token_limit, ai_monthly_spend_limit_micros
+            FROM deployments
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        let rows = sqlx::query(
+            r#"
+            WITH usage AS (
+                SELECT
+                    agent_id,
+                    SUM(invocations)::BIGINT AS invocations,
+                    SUM(prompt_tokens)::BIGINT AS prompt_tokens,
+                    SUM(completion_tokens)::BIGINT AS completion_tokens,
+                    SUM(spend_micros)::BIGINT AS spend_micros
+                FROM ai_agent_usage_hourly
+                WHERE deployment_id = $1 AND hour >= $2 AND hour < $3
+                GROUP BY agent_id
+            ),
+            agents AS (
+                SELECT id, name, monthly_token_limit, monthly_spend_limit_micros
+                FROM ai_agents
+                WHERE deployment_id = $1
+            )
+            SELECT
+                COALESCE(a.id, u.agent_id) AS agent_id,
+                a.name,
+                a.monthly_token_limit,
+                a.monthly_spend_limit_micros,
+                COALESCE(u.invocations, 0) AS invocations,
+                COALESCE(u.prompt_tokens, 0) AS prompt_tokens,
+                COALESCE(u.completion_tokens, 0) AS completion_tokens,
+                COALESCE(u.spend_micros, 0) AS spend_micros
+            FROM agents a
+            FULL JOIN usage u ON u.agent_id = a.id
+            ORDER BY COALESCE(u.prompt_tokens + u.completion_tokens, 0) DESC, 1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.period.starts_at())
+        .bind(self.period.ends_at())
+        .fetch_all(pool)
+        .await?;
+
+        let agents: Vec<AiAgentUsage> = rows
+            .into_iter()
+            .map(|row| {
+                let prompt_tokens: i64 = row.get("prompt_tokens");
+                let completion_tokens: i64 = row.get("completion_tokens");
+                AiAgentUsage {
+                    agent_id: row.get("agent_id"),
+                    agent_name: row.get("name"),
+                    invocations: row.get("invocations"),
+                    prompt_tokens,
+                    completion_tokens,
+                    total_tokens: prompt_tokens + completion_tokens,
+                    spend_micros: row.get("spend_micros"),
+                    budget: AiBudget {
+                        monthly_token_limit: row.get("monthly_token_limit"),
+                        monthly_spend_limit_micros: row.get("monthly_spend_limit_micros"),
+                    },
+                }
+            })
+            .collect();
+
+        Ok(AiUsageReport {
+            deployment_id: self.deployment_id,
+            period_start: self.period.start,
+            period_end: self.period.end(),
+            total_tokens: agents.iter().map(|agent| agent.total_tokens).sum(),
+            spend_micros: agents.iter().map(|agent| agent.spend_micros).sum(),
+            budget: AiBudget {
+                monthly_token_limit: deployment.get("ai_monthly_token_limit"),
+                monthly_spend_limit_micros: deployment.get("ai_monthly_spend_limit_micros"),
+            },
+            agents,
+        })
+    }
+}
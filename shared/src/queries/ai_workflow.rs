@@ -1,12 +1,52 @@
-use sqlx::Row;
+use sqlx::{Row, postgres::PgRow};
 
 use crate::{
     error::AppError,
-    models::{AiWorkflowWithDetails, WorkflowConfiguration, WorkflowDefinition},
+    models::{AiWorkflowVersion, AiWorkflowWithDetails, WorkflowConfiguration, WorkflowDefinition},
     queries::Query,
     state::AppState,
 };
 
+const WORKFLOW_WITH_DETAILS_SELECT: &str = r#"
+    SELECT
+        w.id, w.created_at, w.updated_at, w.name, w.description,
+        w.deployment_id, w.configuration, w.workflow_definition, w.published_version_id,
+        COALESCE(a.agents_count, 0) as agents_count,
+        e.last_execution_at
+    FROM ai_workflows w
+    LEFT JOIN (
+        SELECT workflow_id, COUNT(*) as agents_count
+        FROM ai_agent_workflows
+        GROUP BY workflow_id
+    ) a ON w.id = a.workflow_id
+    LEFT JOIN (
+        SELECT workflow_id, MAX(created_at) as last_execution_at
+        FROM ai_workflow_executions
+        GROUP BY workflow_id
+    ) e ON w.id = e.workflow_id
+"#;
+
+fn workflow_with_details_from_row(row: &PgRow) -> AiWorkflowWithDetails {
+    let configuration: WorkflowConfiguration =
+        serde_json::from_value(row.get("configuration")).unwrap_or_default();
+    let workflow_definition: WorkflowDefinition =
+        serde_json::from_value(row.get("workflow_definition")).unwrap_or_default();
+
+    AiWorkflowWithDetails {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        name: row.get("name"),
+        description: row.get("description"),
+        deployment_id: row.get("deployment_id"),
+        configuration,
+        workflow_definition,
+        published_version_id: row.get("published_version_id"),
+        agents_count: row.get::<Option<i64>, _>("agents_count").unwrap_or(0),
+        last_execution_at: row.get("last_execution_at"),
+    }
+}
+
 pub struct GetAiWorkflowsQuery {
     pub deployment_id: i64,
     pub limit: Option<u32>,
@@ -43,27 +83,11 @@ impl GetAiWorkflowsQuery {
 impl Query for GetAiWorkflowsQuery {
     type Output = Vec<AiWorkflowWithDetails>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let mut query = r#"
-            SELECT
-                w.id, w.created_at, w.updated_at, w.name, w.description,
-                w.deployment_id, w.configuration, w.workflow_definition,
-                COALESCE(a.agents_count, 0) as agents_count,
-                e.last_execution_at
-            FROM ai_workflows w
-            LEFT JOIN (
-                SELECT workflow_id, COUNT(*) as agents_count
-                FROM ai_agent_workflows
-                GROUP BY workflow_id
-            ) a ON w.id = a.workflow_id
-            LEFT JOIN (
-                SELECT workflow_id, MAX(created_at) as last_execution_at
-                FROM ai_workflow_executions
-                GROUP BY workflow_id
-            ) e ON w.id = e.workflow_id
-            WHERE w.deployment_id = $1
-        "#
-        .to_string();
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut query = format!(
+            "{} WHERE w.deployment_id = $1 AND w.deleted_at IS NULL",
+            WORKFLOW_WITH_DETAILS_SELECT
+        );
 
         let mut param_count = 2;
         if self.search.is_some() {
@@ -102,26 +126,8 @@ impl Query for GetAiWorkflowsQuery {
             .map_err(|e| AppError::Database(e))?;
 
         Ok(workflows
-            .into_iter()
-            .map(|row| {
-                let configuration: WorkflowConfiguration =
-                    serde_json::from_value(row.get("configuration")).unwrap_or_default();
-                let workflow_definition: WorkflowDefinition =
-                    serde_json::from_value(row.get("workflow_definition")).unwrap_or_default();
-
-                AiWorkflowWithDetails {
-                    id: row.get("id"),
-                    created_at: row.get("created_at"),
-                    updated_at: row.get("updated_at"),
-                    name: row.get("name"),
-                    description: row.get("description"),
-                    deployment_id: row.get("deployment_id"),
-                    configuration,
-                    workflow_definition,
-                    agents_count: row.get::<Option<i64>, _>("agents_count").unwrap_or(0),
-                    last_execution_at: row.get("last_execution_at"),
-                }
-            })
+            .iter()
+            .map(workflow_with_details_from_row)
             .collect())
     }
 }
@@ -143,50 +149,144 @@ impl GetAiWorkflowByIdQuery {
 impl Query for GetAiWorkflowByIdQuery {
     type Output = AiWorkflowWithDetails;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let workflow = sqlx::query!(
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
+            "{} WHERE w.id = $1 AND w.deployment_id = $2 AND w.deleted_at IS NULL",
+            WORKFLOW_WITH_DETAILS_SELECT
+        );
+
+        let workflow = sqlx::query(&query)
+            .bind(self.workflow_id)
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Workflow not found".to_string()))?;
+
+        Ok(workflow_with_details_from_row(&workflow))
+    }
+}
+
+pub(crate) const WORKFLOW_VERSION_COLUMNS: &str = r#"
+    v.id, v.created_at, v.workflow_id, v.version_number, v.configuration,
+    v.workflow_definition, v.published_by, v.change_note,
+    (w.published_version_id = v.id) AS is_published
+"#;
+
+pub(crate) fn workflow_version_from_row(row: &PgRow) -> Result<AiWorkflowVersion, AppError> {
+    let configuration = serde_json::from_value(row.get("configuration"))
+        .map_err(|e| AppError::Serialization(e.to_string()))?;
+    let workflow_definition = serde_json::from_value(row.get("workflow_definition"))
+        .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+    Ok(AiWorkflowVersion {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        workflow_id: row.get("workflow_id"),
+        version_number: row.get("version_number"),
+        configuration,
+        workflow_definition,
+        published_by: row.get("published_by"),
+        change_note: row.get("change_note"),
+        is_published: row.get::<Option<bool>, _>("is_published").unwrap_or(false),
+    })
+}
+
+pub struct ListWorkflowVersionsQuery {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+    pub offset: u32,
+    pub limit: u32,
+}
+
+impl ListWorkflowVersionsQuery {
+    pub fn new(deployment_id: i64, workflow_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            offset: 0,
+            limit: 50,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: Option<u32>) -> Self {
+        if let Some(limit) = limit {
+            self.limit = limit;
+        }
+        self
+    }
+
+    pub fn with_offset(mut self, offset: Option<u32>) -> Self {
+        if let Some(offset) = offset {
+            self.offset = offset;
+        }
+        self
+    }
+}
+
+impl Query for ListWorkflowVersionsQuery {
+    type Output = Vec<AiWorkflowVersion>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM ai_workflow_versions v
+            JOIN ai_workflows w ON w.id = v.workflow_id
+            WHERE v.workflow_id = $1 AND w.deployment_id = $2
+                AND w.deleted_at IS NULL AND v.deleted_at IS NULL
+            ORDER BY v.version_number DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            WORKFLOW_VERSION_COLUMNS
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(self.workflow_id)
+            .bind(self.deployment_id)
+            .bind(self.limit as i64)
+            .bind(self.offset as i64)
+            .fetch_all(&app_state.db_pool)
+            .await?;
+
+        rows.iter().map(workflow_version_from_row).collect()
+    }
+}
+
+pub struct GetPublishedWorkflowVersionQuery {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+}
+
+impl GetPublishedWorkflowVersionQuery {
+    pub fn new(deployment_id: i64, workflow_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+        }
+    }
+}
+
+impl Query for GetPublishedWorkflowVersionQuery {
+    type Output = AiWorkflowVersion;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
             r#"
-            SELECT
-                w.id, w.created_at, w.updated_at, w.name, w.description,
-                w.deployment_id, w.configuration, w.workflow_definition,
-                COALESCE(a.agents_count, 0) as agents_count,
-                e.last_execution_at
+            SELECT {}
             FROM ai_workflows w
-            LEFT JOIN (
-                SELECT workflow_id, COUNT(*) as agents_count
-                FROM ai_agent_workflows
-                GROUP BY workflow_id
-            ) a ON w.id = a.workflow_id
-            LEFT JOIN (
-                SELECT workflow_id, MAX(created_at) as last_execution_at
-                FROM ai_workflow_executions
-                GROUP BY workflow_id
-            ) e ON w.id = e.workflow_id
-            WHERE w.id = $1 AND w.deployment_id = $2
+            JOIN ai_workflow_versions v ON v.id = w.published_version_id
+            WHERE w.id = $1 AND w.deployment_id = $2 AND w.deleted_at IS NULL
             "#,
-            self.workflow_id,
-            self.deployment_id
-        )
-        .fetch_one(&app_state.db_pool)
-        .await
-        .map_err(|e| AppError::Database(e))?;
-
-        let configuration: WorkflowConfiguration =
-            serde_json::from_value(workflow.configuration).unwrap_or_default();
-        let workflow_definition: WorkflowDefinition =
-            serde_json::from_value(workflow.workflow_definition).unwrap_or_default();
-
-        Ok(AiWorkflowWithDetails {
-            id: workflow.id,
-            created_at: workflow.created_at,
-            updated_at: workflow.updated_at,
-            name: workflow.name,
-            description: workflow.description,
-            deployment_id: workflow.deployment_id,
-            configuration,
-            workflow_definition,
-            agents_count: workflow.agents_count.unwrap_or(0),
-            last_execution_at: workflow.last_execution_at,
-        })
+            WORKFLOW_VERSION_COLUMNS
+        );
+
+        let row = sqlx::query(&query)
+            .bind(self.workflow_id)
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?
+            .ok_or_else(|| AppError::BadRequest("Workflow has no published version".to_string()))?;
+
+        workflow_version_from_row(&row)
     }
 }
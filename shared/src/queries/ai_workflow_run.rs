@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{ExecutionStatus, WorkflowExecution},
+    queries::Query,
+    state::AppState,
+};
+
+pub(crate) const WORKFLOW_RUN_COLUMNS: &str = r#"
+    e.id, e.created_at, e.updated_at, e.workflow_id, e.workflow_version_id, e.status, e.trigger_data,
+    e.execution_context, e.output_data, e.error_message, e.started_at, e.completed_at
+"#;
+
+pub(crate) fn workflow_run_from_row(row: &PgRow) -> Result<WorkflowExecution, AppError> {
+    let execution_context = serde_json::from_value(row.get("execution_context"))
+        .map_err(|e| AppError::Serialization(e.to_string()))?;
+
+    Ok(WorkflowExecution {
+        id: row.get("id"),
+        workflow_id: row.get("workflow_id"),
+        workflow_version_id: row.get("workflow_version_id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        status: ExecutionStatus::from(row.get::<String, _>("status")),
+        trigger_data: row.get("trigger_data"),
+        execution_context,
+        output_data: row.get("output_data"),
+        started_at: row.get("started_at"),
+        completed_at: row.get("completed_at"),
+        error_message: row.get("error_message"),
+    })
+}
+
+pub struct GetWorkflowRunQuery {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+    pub run_id: i64,
+}
+
+impl GetWorkflowRunQuery {
+    pub fn new(deployment_id: i64, workflow_id: i64, run_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            run_id,
+        }
+    }
+}
+
+impl Query for GetWorkflowRunQuery {
+    type Output = WorkflowExecution;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM ai_workflow_executions e
+            JOIN ai_workflows w ON w.id = e.workflow_id
+            WHERE e.id = $1 AND e.workflow_id = $2 AND w.deployment_id = $3
+            "#,
+            WORKFLOW_RUN_COLUMNS
+        );
+
+        let row = sqlx::query(&query)
+            .bind(self.run_id)
+            .bind(self.workflow_id)
+            .bind(self.deployment_id)
+            .fetch_optional(&app_state.db_pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Workflow run not found".to_string()))?;
+
+        workflow_run_from_row(&row)
+    }
+}
+
+pub struct ListWorkflowRunsQuery {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+    pub offset: u32,
+    pub limit: u32,
+    pub status: Option<ExecutionStatus>,
+    pub started_after: Option<DateTime<Utc>>,
+    pub started_before: Option<DateTime<Utc>>,
+}
+
+impl ListWorkflowRunsQuery {
+    pub fn new(deployment_id: i64, workflow_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+            offset: 0,
+            limit: 50,
+            status: None,
+            started_after: None,
+            started_before: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: Option<u32>) -> Self {
+        if let Some(limit) = limit {
+            self.limit = limit;
+        }
+        self
+    }
+
+    pub fn with_offset(mut self, offset: Option<u32>) -> Self {
+        if let Some(offset) = offset {
+            self.offset = offset;
+        }
+        self
+    }
+
+    pub fn with_status(mut self, status: Option<ExecutionStatus>) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_started_after(mut self, started_after: Option<DateTime<Utc>>) -> Self {
+        self.started_after = started_after;
+        self
+    }
+
+    pub fn with_started_before(mut self, started_before: Option<DateTime<Utc>>) -> Self {
+        self.started_before = started_before;
+        self
+    }
+}
+
+impl Query for ListWorkflowRunsQuery {
+    type Output = Vec<WorkflowExecution>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM ai_workflow_executions e
+            JOIN ai_workflows w ON w.id = e.workflow_id
+            WHERE e.workflow_id = $1 AND w.deployment_id = $2
+                AND ($3::text IS NULL OR e.status = $3)
+                AND ($4::timestamptz IS NULL OR e.created_at >= $4)
+                AND ($5::timestamptz IS NULL OR e.created_at < $5)
+            ORDER BY e.created_at DESC
+            LIMIT $6 OFFSET $7
+            "#,
+            WORKFLOW_RUN_COLUMNS
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(self.workflow_id)
+            .bind(self.deployment_id)
+            .bind(self.status.clone().map(String::from))
+            .bind(self.started_after)
+            .bind(self.started_before)
+            .bind(self.limit as i64)
+            .bind(self.offset as i64)
+            .fetch_all(&app_state.db_pool)
+            .await?;
+
+        rows.iter().map(workflow_run_from_row).collect()
+    }
+}
@@ -0,0 +1,68 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{AiWorkflowTrigger, WorkflowTriggerType},
+    queries::Query,
+    state::AppState,
+};
+
+pub(crate) const WORKFLOW_TRIGGER_COLUMNS: &str = r#"
+    t.id, t.created_at, t.updated_at, t.deployment_id, t.workflow_id, t.trigger_type,
+    t.cron_expression, t.timezone, t.last_fired_at, t.next_fire_at, t.last_run_id, t.last_error
+"#;
+
+pub(crate) fn workflow_trigger_from_row(row: &PgRow) -> AiWorkflowTrigger {
+    AiWorkflowTrigger {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        workflow_id: row.get("workflow_id"),
+        trigger_type: WorkflowTriggerType::from(row.get::<String, _>("trigger_type")),
+        cron_expression: row.get("cron_expression"),
+        timezone: row.get("timezone"),
+        last_fired_at: row.get("last_fired_at"),
+        next_fire_at: row.get("next_fire_at"),
+        last_run_id: row.get("last_run_id"),
+        last_error: row.get("last_error"),
+    }
+}
+
+pub struct ListWorkflowTriggersQuery {
+    pub deployment_id: i64,
+    pub workflow_id: i64,
+}
+
+impl ListWorkflowTriggersQuery {
+    pub fn new(deployment_id: i64, workflow_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workflow_id,
+        }
+    }
+}
+
+impl Query for ListWorkflowTriggersQuery {
+    type Output = Vec<AiWorkflowTrigger>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM ai_workflow_triggers t
+            WHERE t.workflow_id = $1 AND t.deployment_id = $2
+            ORDER BY t.created_at
+            "#,
+            WORKFLOW_TRIGGER_COLUMNS
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(self.workflow_id)
+            .bind(self.deployment_id)
+            .fetch_all(&app_state.db_pool)
+            .await?;
+
+        Ok(rows.iter().map(workflow_trigger_from_row).collect())
+    }
+}
@@ -1,17 +1,151 @@
-use sqlx::{Row, query, query_as};
+use sqlx::{Row, postgres::PgRow, query, query_as};
 
 use crate::{
     error::AppError,
     models::{
         DeploymentOrganizationRole, DeploymentWorkspaceRole, Organization, OrganizationDetails,
-        OrganizationMemberDetails, OrganizationRole, Workspace, WorkspaceDetails,
-        WorkspaceMemberDetails, WorkspaceRole, WorkspaceWithOrganizationName,
+        OrganizationMemberDetails, OrganizationRole, OrganizationRoleUsage, Workspace,
+        WorkspaceDetails, WorkspaceMemberDetails, WorkspaceRole, WorkspaceRoleUsage,
+        WorkspaceWithOrganizationName,
     },
     state::AppState,
 };
 
 use super::Query;
 
+pub(crate) const ORGANIZATION_ROLE_COLUMNS: &str =
+    "id, created_at, updated_at, name, permissions, deployment_id, organization_id";
+
+pub(crate) const WORKSPACE_ROLE_COLUMNS: &str =
+    "id, created_at, updated_at, name, permissions, deployment_id, organization_id, workspace_id";
+
+pub(crate) fn organization_role_from_row(row: &PgRow) -> DeploymentOrganizationRole {
+    DeploymentOrganizationRole {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        name: row.get("name"),
+        permissions: row.get("permissions"),
+        deployment_id: row.get("deployment_id"),
+        organization_id: row.get("organization_id"),
+    }
+}
+
+pub(crate) fn workspace_role_from_row(row: &PgRow) -> DeploymentWorkspaceRole {
+    DeploymentWorkspaceRole {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        name: row.get("name"),
+        permissions: row.get("permissions"),
+        organization_id: row.get("organization_id"),
+        deployment_id: row.get("deployment_id"),
+        workspace_id: row.get("workspace_id"),
+    }
+}
+
+/// Lists every organization role in a deployment, including roles defined on
+/// a single organization, with how many memberships hold each one.
+pub struct ListOrganizationRolesQuery {
+    deployment_id: i64,
+}
+
+impl ListOrganizationRolesQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for ListOrganizationRolesQuery {
+    type Output = Vec<OrganizationRoleUsage>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                r.id, r.created_at, r.updated_at, r.name, r.permissions,
+                r.deployment_id, r.organization_id,
+                (
+                    SELECT COUNT(DISTINCT omr.organization_membership_id)
+                    FROM organization_membership_roles omr
+                    WHERE omr.organization_role_id = r.id
+                ) AS member_count,
+                COALESCE(
+                    r.id IN (s.default_org_creator_role_id, s.default_org_member_role_id),
+                    false
+                ) AS is_default
+            FROM organization_roles r
+            LEFT JOIN deployment_b2b_settings s
+                ON s.deployment_id = r.deployment_id AND s.deleted_at IS NULL
+            WHERE r.deployment_id = $1
+            ORDER BY r.organization_id NULLS FIRST, r.created_at, r.id
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| OrganizationRoleUsage {
+                role: organization_role_from_row(row),
+                member_count: row.get("member_count"),
+                is_default: row.get("is_default"),
+            })
+            .collect())
+    }
+}
+
+pub struct ListWorkspaceRolesQuery {
+    deployment_id: i64,
+}
+
+impl ListWorkspaceRolesQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for ListWorkspaceRolesQuery {
+    type Output = Vec<WorkspaceRoleUsage>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                r.id, r.created_at, r.updated_at, r.name, r.permissions,
+                r.deployment_id, r.organization_id, r.workspace_id,
+                (
+                    SELECT COUNT(DISTINCT wmr.workspace_membership_id)
+                    FROM workspace_membership_roles wmr
+                    WHERE wmr.workspace_role_id = r.id
+                ) AS member_count,
+                COALESCE(
+                    r.id IN (s.default_workspace_creator_role_id, s.default_workspace_member_role_id),
+                    false
+                ) AS is_default
+            FROM workspace_roles r
+            LEFT JOIN deployment_b2b_settings s
+                ON s.deployment_id = r.deployment_id AND s.deleted_at IS NULL
+            WHERE r.deployment_id = $1
+            ORDER BY r.workspace_id NULLS FIRST, r.created_at, r.id
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| WorkspaceRoleUsage {
+                role: workspace_role_from_row(row),
+                member_count: row.get("member_count"),
+                is_default: row.get("is_default"),
+            })
+            .collect())
+    }
+}
+
 pub struct GetDeploymentWorkspaceRolesQuery {
     deployment_id: i64,
 }
@@ -25,7 +159,7 @@ impl GetDeploymentWorkspaceRolesQuery {
 impl Query for GetDeploymentWorkspaceRolesQuery {
     type Output = Vec<DeploymentWorkspaceRole>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let rows = query_as!(
             DeploymentWorkspaceRole,
             r#"
@@ -52,7 +186,7 @@ impl GetDeploymentOrganizationRolesQuery {
 impl Query for GetDeploymentOrganizationRolesQuery {
     type Output = Vec<DeploymentOrganizationRole>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let rows = query_as!(
             DeploymentOrganizationRole,
             r#"SELECT * FROM organization_roles WHERE deployment_id = $1"#,
@@ -127,8 +261,9 @@ impl DeploymentOrganizationListQuery {
 
 impl Query for DeploymentOrganizationListQuery {
     type Output = Vec<Organization>;
+    const PREFERS_READ_REPLICA: bool = true;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut query_str = String::from(
             r#"
             SELECT
@@ -136,13 +271,21 @@ impl Query for DeploymentOrganizationListQuery {
                 o.name, o.image_url, o.description, o.member_count,
                 o.public_metadata, o.private_metadata
             FROM organizations o
-            WHERE o.deployment_id = $1
+            WHERE o.deployment_id = $1 AND o.deleted_at IS NULL
             "#,
         );
 
-        let sort_key = self.sort_key.as_deref().unwrap_or("created_at");
-        let sort_order = self.sort_order.as_deref().unwrap_or("desc");
-        query_str.push_str(&format!(" ORDER BY o.{} {}", sort_key, sort_order));
+        let sort_key = match self.sort_key.as_deref() {
+            Some("updated_at") => "updated_at",
+            Some("name") => "name",
+            Some("member_count") => "member_count",
+            _ => "created_at",
+        };
+        let sort_order = match self.sort_order.as_deref().map(str::to_lowercase).as_deref() {
+            Some("asc") => "ASC",
+            _ => "DESC",
+        };
+        query_str.push_str(&format!(" ORDER BY o.{} {}, o.id", sort_key, sort_order));
 
         query_str.push_str(" OFFSET $2 LIMIT $3");
 
@@ -150,7 +293,7 @@ impl Query for DeploymentOrganizationListQuery {
             .bind(self.deployment_id)
             .bind(self.offset)
             .bind(self.limit)
-            .fetch_all(&app_state.db_pool)
+            .fetch_all(self.pool(app_state))
             .await?;
 
         Ok(rows
@@ -232,8 +375,9 @@ impl DeploymentWorkspaceListQuery {
 
 impl Query for DeploymentWorkspaceListQuery {
     type Output = Vec<WorkspaceWithOrganizationName>;
+    const PREFERS_READ_REPLICA: bool = true;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let mut query_str = String::from(
             r#"
             SELECT
@@ -243,13 +387,21 @@ impl Query for DeploymentWorkspaceListQuery {
                 o.name AS organization_name
             FROM workspaces w
             LEFT JOIN organizations o ON w.organization_id = o.id
-            WHERE w.deployment_id = $1
+            WHERE w.deployment_id = $1 AND w.deleted_at IS NULL
             "#,
         );
 
-        let sort_key = self.sort_key.as_deref().unwrap_or("created_at");
-        let sort_order = self.sort_order.as_deref().unwrap_or("desc");
-        query_str.push_str(&format!(" ORDER BY o.{} {}", sort_key, sort_order));
+        let sort_key = match self.sort_key.as_deref() {
+            Some("updated_at") => "updated_at",
+            Some("name") => "name",
+            Some("member_count") => "member_count",
+            _ => "created_at",
+        };
+        let sort_order = match self.sort_order.as_deref().map(str::to_lowercase).as_deref() {
+            Some("asc") => "ASC",
+            _ => "DESC",
+        };
+        query_str.push_str(&format!(" ORDER BY w.{} {}, w.id", sort_key, sort_order));
 
         query_str.push_str(" OFFSET $2 LIMIT $3");
 
@@ -257,7 +409,7 @@ impl Query for DeploymentWorkspaceListQuery {
             .bind(self.deployment_id)
             .bind(self.offset)
             .bind(self.limit)
-            .fetch_all(&app_state.db_pool)
+            .fetch_all(self.pool(app_state))
             .await?;
 
         Ok(rows
@@ -276,6 +428,100 @@ impl Query for DeploymentWorkspaceListQuery {
     }
 }
 
+pub struct ListOrganizationWorkspacesQuery {
+    deployment_id: i64,
+    organization_id: i64,
+    offset: i64,
+    limit: i64,
+    sort_key: Option<String>,
+    sort_order: Option<String>,
+}
+
+impl ListOrganizationWorkspacesQuery {
+    pub fn new(deployment_id: i64, organization_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            offset: 0,
+            limit: 10,
+            sort_key: None,
+            sort_order: None,
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+
+    pub fn sort_key(self, sort_key: Option<String>) -> Self {
+        Self { sort_key, ..self }
+    }
+
+    pub fn sort_order(self, sort_order: Option<String>) -> Self {
+        Self { sort_order, ..self }
+    }
+}
+
+impl Query for ListOrganizationWorkspacesQuery {
+    type Output = Vec<Workspace>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut query_builder = sqlx::QueryBuilder::new(
+            r#"
+            SELECT
+                id, created_at, updated_at, name, image_url, description, member_count,
+                public_metadata, private_metadata
+            FROM workspaces
+            WHERE deployment_id = "#,
+        );
+        query_builder.push_bind(self.deployment_id);
+        query_builder.push(" AND organization_id = ");
+        query_builder.push_bind(self.organization_id);
+        query_builder.push(" AND deleted_at IS NULL");
+
+        query_builder.push(" ORDER BY ");
+        match self.sort_key.as_deref() {
+            Some("updated_at") => query_builder.push("updated_at"),
+            Some("name") => query_builder.push("name"),
+            Some("member_count") => query_builder.push("member_count"),
+            _ => query_builder.push("created_at"),
+        };
+        match self.sort_order.as_deref().map(str::to_lowercase).as_deref() {
+            Some("asc") => query_builder.push(" ASC"),
+            _ => query_builder.push(" DESC"),
+        };
+        query_builder.push(", id LIMIT ");
+        query_builder.push_bind(self.limit);
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(self.offset);
+
+        let rows = query_builder.build().fetch_all(&app_state.db_pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Workspace {
+                id: row.get("id"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                name: row.get("name"),
+                image_url: row
+                    .get::<Option<String>, _>("image_url")
+                    .unwrap_or_default(),
+                description: row
+                    .get::<Option<String>, _>("description")
+                    .unwrap_or_default(),
+                member_count: row.get("member_count"),
+                public_metadata: row.get("public_metadata"),
+                private_metadata: row.get("private_metadata"),
+            })
+            .collect())
+    }
+}
+
 pub struct GetOrganizationDetailsQuery {
     deployment_id: i64,
     organization_id: i64,
@@ -293,7 +539,18 @@ impl GetOrganizationDetailsQuery {
 impl Query for GetOrganizationDetailsQuery {
     type Output = OrganizationDetails;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM organizations WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL)",
+        )
+        .bind(self.deployment_id)
+        .bind(self.organization_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+        if !exists {
+            return Err(AppError::NotFound("Organization not found".to_string()));
+        }
+
         // Get organization basic info
         let org_row = sqlx::query!(
             r#"
@@ -311,23 +568,23 @@ impl Query for GetOrganizationDetailsQuery {
         .await?;
 
         // Get organization members with user details
-        let member_rows = sqlx::query!(
+        let member_rows = query(
             r#"
             SELECT
                 om.id, om.created_at, om.updated_at,
                 om.organization_id, om.user_id,
                 u.first_name, u.last_name, u.username,
                 u.created_at as user_created_at,
-                e.email_address as "primary_email_address?",
-                p.phone_number as "primary_phone_number?"
+                e.email_address as primary_email_address,
+                p.phone_number as primary_phone_number
             FROM organization_memberships om
-            JOIN users u ON om.user_id = u.id
+            JOIN users u ON om.user_id = u.id AND u.deleted_at IS NULL
             LEFT JOIN user_email_addresses e ON u.primary_email_address_id = e.id
             LEFT JOIN user_phone_numbers p ON u.primary_phone_number_id = p.id
             WHERE om.organization_id = $1
             "#,
-            self.organization_id
         )
+        .bind(self.organization_id)
         .fetch_all(&app_state.db_pool)
         .await?;
 
@@ -373,22 +630,20 @@ impl Query for GetOrganizationDetailsQuery {
         let members: Vec<OrganizationMemberDetails> = member_rows
             .into_iter()
             .map(|row| OrganizationMemberDetails {
-                id: row.id,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-                organization_id: row.organization_id,
-                user_id: row.user_id,
+                id: row.get("id"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                organization_id: row.get("organization_id"),
+                user_id: row.get("user_id"),
                 roles: vec![], // Simplified for now - would need async context to fetch roles
-                first_name: row.first_name,
-                last_name: row.last_name,
-                username: if row.username.is_empty() {
-                    None
-                } else {
-                    Some(row.username)
-                },
-                primary_email_address: row.primary_email_address,
-                primary_phone_number: row.primary_phone_number,
-                user_created_at: row.user_created_at,
+                first_name: row.get("first_name"),
+                last_name: row.get("last_name"),
+                username: row
+                    .get::<Option<String>, _>("username")
+                    .filter(|username| !username.is_empty()),
+                primary_email_address: row.get("primary_email_address"),
+                primary_phone_number: row.get("primary_phone_number"),
+                user_created_at: row.get("user_created_at"),
             })
             .collect();
 
@@ -457,7 +712,18 @@ impl GetWorkspaceDetailsQuery {
 impl Query for GetWorkspaceDetailsQuery {
     type Output = WorkspaceDetails;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM workspaces WHERE deployment_id = $1 AND id = $2 AND deleted_at IS NULL)",
+        )
+        .bind(self.deployment_id)
+        .bind(self.workspace_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+        if !exists {
+            return Err(AppError::NotFound("Workspace not found".to_string()));
+        }
+
         // Get workspace basic info with organization name
         let workspace_row = sqlx::query!(
             r#"
@@ -477,23 +743,23 @@ impl Query for GetWorkspaceDetailsQuery {
         .await?;
 
         // Get workspace members with user details
-        let member_rows = sqlx::query!(
+        let member_rows = query(
             r#"
             SELECT
                 wm.id, wm.created_at, wm.updated_at,
                 wm.workspace_id, wm.user_id,
                 u.first_name, u.last_name, u.username,
                 u.created_at as user_created_at,
-                e.email_address as "primary_email_address?",
-                p.phone_number as "primary_phone_number?"
+                e.email_address as primary_email_address,
+                p.phone_number as primary_phone_number
             FROM workspace_memberships wm
-            JOIN users u ON wm.user_id = u.id
+            JOIN users u ON wm.user_id = u.id AND u.deleted_at IS NULL
             LEFT JOIN user_email_addresses e ON u.primary_email_address_id = e.id
             LEFT JOIN user_phone_numbers p ON u.primary_phone_number_id = p.id
             WHERE wm.workspace_id = $1
             "#,
-            self.workspace_id
         )
+        .bind(self.workspace_id)
         .fetch_all(&app_state.db_pool)
         .await?;
 
@@ -534,22 +800,20 @@ impl Query for GetWorkspaceDetailsQuery {
         let members: Vec<WorkspaceMemberDetails> = member_rows
             .into_iter()
             .map(|row| WorkspaceMemberDetails {
-                id: row.id,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-                workspace_id: row.workspace_id,
-                user_id: row.user_id,
+                id: row.get("id"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                workspace_id: row.get("workspace_id"),
+                user_id: row.get("user_id"),
                 roles: vec![],
-                first_name: row.first_name,
-                last_name: row.last_name,
-                username: if row.username.is_empty() {
-                    None
-                } else {
-                    Some(row.username)
-                },
-                primary_email_address: row.primary_email_address,
-                primary_phone_number: row.primary_phone_number,
-                user_created_at: row.user_created_at,
+                first_name: row.get("first_name"),
+                last_name: row.get("last_name"),
+                username: row
+                    .get::<Option<String>, _>("username")
+                    .filter(|username| !username.is_empty()),
+                primary_email_address: row.get("primary_email_address"),
+                primary_phone_number: row.get("primary_phone_number"),
+                user_created_at: row.get("user_created_at"),
             })
             .collect();
 
@@ -0,0 +1,101 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{BackgroundJob, BackgroundJobStatus},
+    queries::Query,
+    state::AppState,
+};
+
+pub(crate) const BACKGROUND_JOB_COLUMNS: &str = r#"
+    id, created_at, updated_at, job_type, payload, status, attempts, max_attempts, run_at,
+    last_error, completed_at
+"#;
+
+pub(crate) fn background_job_from_row(row: &PgRow) -> Result<BackgroundJob, AppError> {
+    Ok(BackgroundJob {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        job_type: row.get("job_type"),
+        payload: row.get("payload"),
+        status: row
+            .get::<String, _>("status")
+            .parse()
+            .map_err(AppError::Internal)?,
+        attempts: row.get("attempts"),
+        max_attempts: row.get("max_attempts"),
+        run_at: row.get("run_at"),
+        last_error: row.get("last_error"),
+        completed_at: row.get("completed_at"),
+    })
+}
+
+/// Jobs across every deployment, newest first, for operators looking into
+/// failures.
+pub struct ListBackgroundJobsQuery {
+    status: Option<BackgroundJobStatus>,
+    job_type: Option<String>,
+    offset: i64,
+    limit: i64,
+}
+
+impl ListBackgroundJobsQuery {
+    pub fn new() -> Self {
+        Self {
+            status: None,
+            job_type: None,
+            offset: 0,
+            limit: 10,
+        }
+    }
+
+    pub fn status(self, status: Option<BackgroundJobStatus>) -> Self {
+        Self { status, ..self }
+    }
+
+    pub fn job_type(self, job_type: Option<String>) -> Self {
+        Self { job_type, ..self }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl Default for ListBackgroundJobsQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Query for ListBackgroundJobsQuery {
+    type Output = Vec<BackgroundJob>;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM background_jobs
+            WHERE ($1::TEXT IS NULL OR status = $1)
+              AND ($2::TEXT IS NULL OR job_type = $2)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            BACKGROUND_JOB_COLUMNS
+        ))
+        .bind(self.status.map(|status| status.as_str()))
+        .bind(self.job_type.as_deref())
+        .bind(self.limit)
+        .bind(self.offset)
+        .fetch_all(self.pool(app_state))
+        .await?;
+
+        rows.iter().map(background_job_from_row).collect()
+    }
+}
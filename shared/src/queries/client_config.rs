@@ -0,0 +1,217 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sqlx::Row;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{
+        AuthFactorsEnabled, ClientConfig, ClientPasswordPolicy, ClientUiSettings,
+        DeploymentMaintenance, DeploymentMode, PasswordSettings, SecondFactorPolicy,
+        SocialConnectionProvider,
+    },
+    queries::Query,
+    state::AppState,
+};
+
+/// Checks that a publishable key is one this platform issued: `pk_test_` or
+/// `pk_live_` followed by the base64 of the deployment's backend URL.
+fn is_well_formed_publishable_key(publishable_key: &str) -> bool {
+    let Some(encoded) = publishable_key
+        .strip_prefix("pk_test_")
+        .or_else(|| publishable_key.strip_prefix("pk_live_"))
+    else {
+        return false;
+    };
+
+    BASE64_STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .is_some_and(|url| url.starts_with("https://"))
+}
+
+fn json_or_default<T: DeserializeOwned + Default>(value: Option<Value>) -> T {
+    value
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// The public configuration of the deployment a publishable key belongs to,
+/// for frontend SDKs. Reads only the settings the SDKs use rather than the
+/// whole deployment.
+/// It is still served during maintenance, with a banner the SDKs can show;
+/// a scheduled window starting or ending counts as a change for the ETag.
+pub struct GetClientConfigQuery {
+    publishable_key: String,
+}
+
+impl GetClientConfigQuery {
+    pub fn new(publishable_key: impl Into<String>) -> Self {
+        Self {
+            publishable_key: publishable_key.into(),
+        }
+    }
+}
+
+impl Query for GetClientConfigQuery {
+    type Output = ClientConfig;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if !is_well_formed_publishable_key(&self.publishable_key) {
+            return Err(AppError::BadRequest("Invalid publishable key".to_string()));
+        }
+
+        let row = sqlx::query(
+            r#"
+            SELECT
+                d.mode, d.frontend_host, d.maintenance_mode, d.maintenance_message,
+                d.maintenance_starts_at, d.maintenance_ends_at, d.deleted_at,
+                a.email_address::jsonb AS email_address,
+                a.phone_number::jsonb AS phone_number,
+                a.username::jsonb AS username,
+                a.first_name::jsonb AS first_name,
+                a.last_name::jsonb AS last_name,
+                a.password::jsonb AS password,
+                a.auth_factors_enabled::jsonb AS auth_factors_enabled,
+                a.second_factor_policy::text AS second_factor_policy,
+                a.first_factor::text AS first_factor,
+                a.alternate_first_factors,
+                a.otp_settings,
+                u.app_name, u.logo_image_url, u.favicon_image_url, u.tos_page_url,
+                u.privacy_policy_url, u.sign_in_page_url, u.sign_up_page_url,
+                u.signup_terms_statement, u.signup_terms_statement_shown,
+                u.light_mode_settings::jsonb AS light_mode_settings,
+                u.dark_mode_settings::jsonb AS dark_mode_settings,
+                r.sign_up_mode::text AS sign_up_mode,
+                ARRAY(
+                    SELECT provider::text FROM deployment_social_connections
+                    WHERE deployment_id = d.id AND enabled AND deleted_at IS NULL
+                    ORDER BY provider
+                ) AS social_providers,
+                GREATEST(
+                    d.updated_at, a.updated_at, u.updated_at, r.updated_at,
+                    (SELECT MAX(updated_at) FROM deployment_social_connections
+                        WHERE deployment_id = d.id),
+                    CASE WHEN d.maintenance_starts_at <= NOW() THEN d.maintenance_starts_at END,
+                    CASE WHEN d.maintenance_ends_at <= NOW() THEN d.maintenance_ends_at END
+                ) AS settings_updated_at
+            FROM deployments d
+            LEFT JOIN deployment_auth_settings a ON a.deployment_id = d.id
+            LEFT JOIN deployment_ui_settings u ON u.deployment_id = d.id
+            LEFT JOIN deployment_restrictions r ON r.deployment_id = d.id
+            WHERE d.publishable_key = $1
+            ORDER BY d.deleted_at DESC NULLS FIRST
+            LIMIT 1
+            "#,
+        )
+        .bind(&self.publishable_key)
+        .fetch_optional(self.pool(app_state))
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        if row.get::<Option<DateTime<Utc>>, _>("deleted_at").is_some() {
+            return Err(AppError::coded(
+                ErrorCode::DeploymentDeleted,
+                "This deployment has been deleted",
+            ));
+        }
+        let maintenance = DeploymentMaintenance {
+            maintenance_mode: row.get("maintenance_mode"),
+            message: row.get("maintenance_message"),
+            starts_at: row.get("maintenance_starts_at"),
+            ends_at: row.get("maintenance_ends_at"),
+        };
+
+        let factors: AuthFactorsEnabled = json_or_default(row.get("auth_factors_enabled"));
+        let first_factors = [
+            ("sso", factors.sso),
+            ("email_password", factors.email_password),
+            ("username_password", factors.username_password),
+            ("email_otp", factors.email_otp),
+            ("email_magic_link", factors.email_magic_link),
+            ("phone_otp", factors.phone_otp),
+            ("web3_wallet", factors.web3_wallet),
+            ("passkey", factors.passkey),
+        ]
+        .into_iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(factor, _)| factor.to_string())
+        .collect();
+
+        let password: PasswordSettings = json_or_default(row.get("password"));
+
+        Ok(ClientConfig {
+            mode: match row.get::<String, _>("mode").as_str() {
+                "production" => DeploymentMode::Production,
+                _ => DeploymentMode::Staging,
+            },
+            frontend_host: row.get("frontend_host"),
+            first_factors,
+            first_factor: row
+                .get::<Option<String>, _>("first_factor")
+                .and_then(|factor| factor.parse().ok()),
+            alternate_first_factors: json_or_default(row.get("alternate_first_factors")),
+            second_factor_policy: row
+                .get::<Option<String>, _>("second_factor_policy")
+                .and_then(|policy| policy.parse().ok())
+                .unwrap_or(SecondFactorPolicy::Optional),
+            email_address: json_or_default(row.get("email_address")),
+            phone_number: json_or_default(row.get("phone_number")),
+            username: json_or_default(row.get("username")),
+            first_name: json_or_default(row.get("first_name")),
+            last_name: json_or_default(row.get("last_name")),
+            password_policy: ClientPasswordPolicy {
+                enabled: password.enabled,
+                min_length: password.min_length,
+                require_lowercase: password.require_lowercase.unwrap_or(false),
+                require_uppercase: password.require_uppercase.unwrap_or(false),
+                require_number: password.require_number.unwrap_or(false),
+                require_special: password.require_special.unwrap_or(false),
+            },
+            otp: json_or_default(row.get("otp_settings")),
+            social_providers: row
+                .get::<Vec<String>, _>("social_providers")
+                .iter()
+                .filter_map(|provider| provider.parse::<SocialConnectionProvider>().ok())
+                .collect(),
+            sign_up_mode: row
+                .get::<Option<String>, _>("sign_up_mode")
+                .and_then(|mode| mode.parse().ok())
+                .unwrap_or_default(),
+            ui: ClientUiSettings {
+                app_name: row.get::<Option<String>, _>("app_name").unwrap_or_default(),
+                logo_image_url: row
+                    .get::<Option<String>, _>("logo_image_url")
+                    .unwrap_or_default(),
+                favicon_image_url: row
+                    .get::<Option<String>, _>("favicon_image_url")
+                    .unwrap_or_default(),
+                tos_page_url: row
+                    .get::<Option<String>, _>("tos_page_url")
+                    .unwrap_or_default(),
+                privacy_policy_url: row
+                    .get::<Option<String>, _>("privacy_policy_url")
+                    .unwrap_or_default(),
+                sign_in_page_url: row
+                    .get::<Option<String>, _>("sign_in_page_url")
+                    .unwrap_or_default(),
+                sign_up_page_url: row
+                    .get::<Option<String>, _>("sign_up_page_url")
+                    .unwrap_or_default(),
+                signup_terms_statement: row
+                    .get::<Option<String>, _>("signup_terms_statement")
+                    .unwrap_or_default(),
+                signup_terms_statement_shown: row
+                    .get::<Option<bool>, _>("signup_terms_statement_shown")
+                    .unwrap_or(false),
+                light_mode_settings: json_or_default(row.get("light_mode_settings")),
+                dark_mode_settings: json_or_default(row.get("dark_mode_settings")),
+            },
+            maintenance: maintenance.banner_at(Utc::now()),
+            updated_at: row.get("settings_updated_at"),
+        })
+    }
+}
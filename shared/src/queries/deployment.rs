@@ -1,20 +1,33 @@
 use std::str::FromStr;
+use std::time::Instant;
 
 use crate::{
     dto::params::deployment::DeploymentNameParams,
     error::AppError,
     models::{
+        CountryRestrictionDecision, CountryRestrictionEvaluator, CountryRestrictions,
         DeploymentAuthSettings, DeploymentB2bSettings, DeploymentB2bSettingsWithRoles,
-        DeploymentJwtTemplate, DeploymentMode, DeploymentOrganizationRole, DeploymentRestrictions,
-        DeploymentRestrictionsSignUpMode, DeploymentSocialConnection, DeploymentUISettings,
-        DeploymentWithSettings, DeploymentWorkspaceRole, EmailTemplate,
+        DeploymentJwtTemplate, DeploymentMaintenance, DeploymentMode, DeploymentOrganizationRole,
+        DeploymentRestrictions, DeploymentRestrictionsSignUpMode, DeploymentSocialConnection,
+        DeploymentUISettings, DeploymentWithSettings, DeploymentWorkspaceRole, EmailTemplate,
+        FirstFactor, LockoutPolicy, OtpSettings, ProvisioningStatus, RestrictionCandidate,
+        RestrictionDecision, RestrictionDecisionReason, RestrictionEntry, TokenRevocationCheck,
+        UserDeletionPolicy, VerificationStatus,
+    },
+    services::{
+        CachedDeploymentSettings, JWT_TEMPLATE_SIGNING_KEY, SOCIAL_CONNECTION_CLIENT_SECRET,
     },
     state::AppState,
+    utils::phone_country::countries_for_phone,
+    validators::{REDIRECT_URL_FIELDS, RedirectUrlPolicy, RedirectUrlValidation},
 };
 use sqlx::{Row, query};
 
-use super::Query;
+use super::{GetDeploymentFeatureFlagsQuery, Query};
 
+/// Served from the [`DeploymentSettingsCache`](crate::services::DeploymentSettingsCache)
+/// when possible. Commands that change settings drop the cached copy through
+/// `app_state.cache_invalidator`.
 pub struct GetDeploymentWithSettingsQuery {
     deployment_id: i64,
 }
@@ -28,7 +41,49 @@ impl GetDeploymentWithSettingsQuery {
 impl Query for GetDeploymentWithSettingsQuery {
     type Output = DeploymentWithSettings;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let started_at = Instant::now();
+        let cache = &app_state.deployment_settings_cache;
+
+        match cache.get(self.deployment_id).await {
+            Some(CachedDeploymentSettings::Found(deployment)) => {
+                tracing::debug!(
+                    deployment_id = self.deployment_id,
+                    "Deployment settings cache hit in {:?}",
+                    started_at.elapsed()
+                );
+                return Ok(*deployment);
+            }
+            Some(CachedDeploymentSettings::Deleted) => {
+                return Err(AppError::NotFound("Deployment not found".to_string()));
+            }
+            None => {}
+        }
+
+        let deployment = match self.fetch_from_database(app_state).await {
+            Ok(deployment) => deployment,
+            Err(AppError::Database(sqlx::Error::RowNotFound)) => {
+                cache.put_tombstone(self.deployment_id).await;
+                return Err(AppError::NotFound("Deployment not found".to_string()));
+            }
+            Err(e) => return Err(e),
+        };
+        cache.put(self.deployment_id, &deployment).await;
+
+        tracing::debug!(
+            deployment_id = self.deployment_id,
+            "Deployment settings cache miss, loaded in {:?}",
+            started_at.elapsed()
+        );
+        Ok(deployment)
+    }
+}
+
+impl GetDeploymentWithSettingsQuery {
+    async fn fetch_from_database(
+        &self,
+        app_state: &AppState,
+    ) -> Result<DeploymentWithSettings, AppError> {
         let row = query!(
             r#"
             SELECT
@@ -184,6 +239,54 @@ impl Query for GetDeploymentWithSettingsQuery {
             }
         };
 
+        let lockout_policy = GetDeploymentLockoutPolicyQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+        let user_deletion_policy = GetDeploymentUserDeletionPolicyQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+        let alternate_first_factors =
+            GetDeploymentAlternateFirstFactorsQuery::new(self.deployment_id)
+                .execute(app_state)
+                .await?;
+        let otp = GetDeploymentOtpSettingsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+
+        let prevent_last_workspace_deletion: Option<bool> = sqlx::query_scalar(
+            "SELECT prevent_last_workspace_deletion FROM deployment_b2b_settings WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        let allowed_redirect_origins: Option<Vec<String>> = sqlx::query_scalar(
+            "SELECT allowed_redirect_origins FROM deployment_ui_settings WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        let status_row = query(
+            r#"
+            SELECT provisioning_status, verification_status, maintenance_message,
+                maintenance_starts_at, maintenance_ends_at
+            FROM deployments WHERE id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+        let provisioning_status: String = status_row.get("provisioning_status");
+        let verification_status = status_row
+            .get::<Option<String>, _>("verification_status")
+            .map(VerificationStatus::from);
+
+        let feature_flags = GetDeploymentFeatureFlagsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?
+            .flags;
+
         Ok(DeploymentWithSettings {
             id: row.id,
             created_at: row.created_at,
@@ -194,7 +297,8 @@ impl Query for GetDeploymentWithSettingsQuery {
             publishable_key: row.publishable_key,
             mail_from_host: row.mail_from_host,
             mode,
-            verification_status: None, // TODO: Add verification_status to query when database is updated
+            verification_status,
+            provisioning_status: ProvisioningStatus::from(provisioning_status),
             auth_settings: if row.auth_settings_id.is_some() {
                 Some(DeploymentAuthSettings {
                     id: row.auth_settings_id.unwrap(),
@@ -215,12 +319,16 @@ impl Query for GetDeploymentWithSettingsQuery {
                     magic_link: serde_json::from_value(row.magic_link).unwrap(),
                     second_factor_policy: FromStr::from_str(&row.second_factor_policy).unwrap(),
                     first_factor: FromStr::from_str(&row.first_factor).unwrap(),
+                    alternate_first_factors,
                     deployment_id: self.deployment_id,
                     multi_session_support: serde_json::from_value(row.multi_session_support)
                         .unwrap(),
                     session_token_lifetime: row.session_token_lifetime,
                     session_validity_period: row.session_validity_period,
                     session_inactive_timeout: row.session_inactive_timeout,
+                    lockout_policy,
+                    user_deletion_policy,
+                    otp,
                 })
             } else {
                 None
@@ -258,6 +366,7 @@ impl Query for GetDeploymentWithSettingsQuery {
                     user_profile_url: row.user_profile_url,
                     after_create_organization_redirect_url: row
                         .after_create_organization_redirect_url,
+                    allowed_redirect_origins: allowed_redirect_origins.unwrap_or_default(),
                 })
             } else {
                 None
@@ -275,8 +384,12 @@ impl Query for GetDeploymentWithSettingsQuery {
                     block_voip_numbers: row.block_voip_numbers,
                     country_restrictions: serde_json::from_value(row.country_restrictions).unwrap(),
                     banned_keywords: row.banned_keywords,
-                    allowlisted_resources: row.allowlisted_resources,
-                    blocklisted_resources: row.blocklisted_resources,
+                    allowlisted_resources: RestrictionEntry::from_stored_list(
+                        &row.allowlisted_resources,
+                    ),
+                    blocklisted_resources: RestrictionEntry::from_stored_list(
+                        &row.blocklisted_resources,
+                    ),
                     sign_up_mode: DeploymentRestrictionsSignUpMode::from_str(&row.sign_up_mode)
                         .unwrap(),
                 })
@@ -300,6 +413,8 @@ impl Query for GetDeploymentWithSettingsQuery {
                         .unwrap(),
                     allow_org_deletion: row.b2b_settings_allow_org_deletion.unwrap(),
                     allow_workspace_deletion: row.b2b_settings_allow_workspace_deletion.unwrap(),
+                    prevent_last_workspace_deletion: prevent_last_workspace_deletion
+                        .unwrap_or_default(),
                     custom_org_role_enabled: row.b2b_settings_custom_org_role_enabled.unwrap(),
                     custom_workspace_role_enabled: row
                         .b2b_settings_custom_workspace_role_enabled
@@ -385,25 +500,45 @@ impl Query for GetDeploymentWithSettingsQuery {
             email_verification_records: row
                 .email_verification_records
                 .and_then(|v| serde_json::from_value(v).ok()),
+            feature_flags,
+            maintenance: DeploymentMaintenance {
+                maintenance_mode: row.maintenance_mode,
+                message: status_row.get("maintenance_message"),
+                starts_at: status_row.get("maintenance_starts_at"),
+                ends_at: status_row.get("maintenance_ends_at"),
+            },
+            token_revocation: TokenRevocationCheck::for_deployment(self.deployment_id),
         })
     }
 }
 
+/// Client secrets are masked unless revealed.
 pub struct GetDeploymentSocialConnectionsQuery {
     deployment_id: i64,
+    reveal_secrets: bool,
 }
 
 impl GetDeploymentSocialConnectionsQuery {
     pub fn new(deployment_id: i64) -> Self {
-        Self { deployment_id }
+        Self {
+            deployment_id,
+            reveal_secrets: false,
+        }
+    }
+
+    /// Returns client secrets in full. Callers record the reveal with
+    /// `RecordSecretsRevealedCommand` first.
+    pub fn with_reveal_secrets(mut self, reveal_secrets: bool) -> Self {
+        self.reveal_secrets = reveal_secrets;
+        self
     }
 }
 
 impl Query for GetDeploymentSocialConnectionsQuery {
     type Output = Vec<DeploymentSocialConnection>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let row = query!(
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = query(
             r#"
             SELECT
                 id,
@@ -414,44 +549,75 @@ impl Query for GetDeploymentSocialConnectionsQuery {
                 enabled,
                 credentials
             FROM deployment_social_connections
-            WHERE deployment_id = $1
+            WHERE deployment_id = $1 AND deleted_at IS NULL
             "#,
-            self.deployment_id,
         )
+        .bind(self.deployment_id)
         .fetch_all(&app_state.db_pool)
         .await?;
 
-        Ok(row
-            .into_iter()
-            .map(|row| DeploymentSocialConnection {
-                id: row.id,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-                deployment_id: row.deployment_id,
-                provider: row.provider.map(|s| FromStr::from_str(&s).unwrap()),
-                enabled: row.enabled,
-                credentials: row
-                    .credentials
-                    .map(|v| serde_json::from_value(v).unwrap_or_default()),
+        let cipher = &app_state.credential_cipher;
+
+        rows.into_iter()
+            .map(|row| {
+                let connection = DeploymentSocialConnection {
+                    id: row.get("id"),
+                    created_at: row.get("created_at"),
+                    updated_at: row.get("updated_at"),
+                    deployment_id: row.get("deployment_id"),
+                    provider: row
+                        .get::<Option<String>, _>("provider")
+                        .and_then(|provider| FromStr::from_str(&provider).ok()),
+                    enabled: row.get("enabled"),
+                    credentials: match row.get::<Option<serde_json::Value>, _>("credentials") {
+                        Some(credentials) => Some(
+                            serde_json::from_value(
+                                SOCIAL_CONNECTION_CLIENT_SECRET.open(cipher, credentials)?,
+                            )
+                            .unwrap_or_default(),
+                        ),
+                        None => None,
+                    },
+                    has_secret: false,
+                }
+                .with_secret_flag();
+
+                Ok(if self.reveal_secrets {
+                    connection
+                } else {
+                    connection.redact()
+                })
             })
-            .collect())
+            .collect()
     }
 }
 
+/// Custom signing keys are masked unless revealed.
 pub struct GetDeploymentJwtTemplatesQuery {
     deployment_id: i64,
+    reveal_secrets: bool,
 }
 
 impl GetDeploymentJwtTemplatesQuery {
     pub fn new(deployment_id: i64) -> Self {
-        Self { deployment_id }
+        Self {
+            deployment_id,
+            reveal_secrets: false,
+        }
+    }
+
+    /// Returns signing keys in full. Callers record the reveal with
+    /// `RecordSecretsRevealedCommand` first.
+    pub fn with_reveal_secrets(mut self, reveal_secrets: bool) -> Self {
+        self.reveal_secrets = reveal_secrets;
+        self
     }
 }
 
 impl Query for GetDeploymentJwtTemplatesQuery {
     type Output = Vec<DeploymentJwtTemplate>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let row = query!(
             r#"
             SELECT
@@ -472,24 +638,37 @@ impl Query for GetDeploymentJwtTemplatesQuery {
         .fetch_all(&app_state.db_pool)
         .await?;
 
-        let templates = row
-            .into_iter()
-            .map(|row| DeploymentJwtTemplate {
-                id: row.id,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-                deployment_id: row.deployment_id,
-                name: row.name,
-                token_lifetime: row.token_lifetime,
-                allowed_clock_skew: row.allowed_clock_skew,
-                custom_signing_key: row
-                    .custom_signing_key
-                    .map(|v| serde_json::from_value(v).unwrap_or_default()),
-                template: row.template,
-            })
-            .collect();
+        let cipher = &app_state.credential_cipher;
+
+        row.into_iter()
+            .map(|row| {
+                let template = DeploymentJwtTemplate {
+                    id: row.id,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                    deployment_id: row.deployment_id,
+                    name: row.name,
+                    token_lifetime: row.token_lifetime,
+                    allowed_clock_skew: row.allowed_clock_skew,
+                    custom_signing_key: match row.custom_signing_key {
+                        Some(key) => Some(
+                            serde_json::from_value(JWT_TEMPLATE_SIGNING_KEY.open(cipher, key)?)
+                                .unwrap_or_default(),
+                        ),
+                        None => None,
+                    },
+                    template: row.template,
+                    has_secret: false,
+                }
+                .with_secret_flag();
 
-        Ok(templates)
+                Ok(if self.reveal_secrets {
+                    template
+                } else {
+                    template.redact()
+                })
+            })
+            .collect()
     }
 }
 
@@ -510,7 +689,7 @@ impl GetDeploymentEmailTemplateQuery {
 impl Query for GetDeploymentEmailTemplateQuery {
     type Output = EmailTemplate;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let template = match self.template_name {
             DeploymentNameParams::OrganizationInviteTemplate => {
                 let row = query!(
@@ -668,7 +847,7 @@ impl GetEmailTemplateByNameQuery {
 impl Query for GetEmailTemplateByNameQuery {
     type Output = EmailTemplate;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let query_str = format!(
             "SELECT {} FROM deployment_email_templates WHERE deployment_id = $1",
             self.template_name
@@ -701,7 +880,7 @@ impl GetDeploymentAuthSettingsQuery {
 impl Query for GetDeploymentAuthSettingsQuery {
     type Output = crate::models::DeploymentAuthSettings;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let row = sqlx::query!(
             r#"
             SELECT
@@ -736,12 +915,333 @@ impl Query for GetDeploymentAuthSettingsQuery {
             verification_policy: serde_json::from_value(row.verification_policy)?,
             second_factor_policy: serde_json::from_str(&row.second_factor_policy)?,
             first_factor: serde_json::from_str(&row.first_factor)?,
+            alternate_first_factors: GetDeploymentAlternateFirstFactorsQuery::new(
+                self.deployment_id,
+            )
+            .execute(app_state)
+            .await?,
             multi_session_support: serde_json::from_value(row.multi_session_support)?,
             session_token_lifetime: row.session_token_lifetime,
             session_validity_period: row.session_validity_period,
             session_inactive_timeout: row.session_inactive_timeout,
+            lockout_policy: GetDeploymentLockoutPolicyQuery::new(self.deployment_id)
+                .execute(app_state)
+                .await?,
+            user_deletion_policy: GetDeploymentUserDeletionPolicyQuery::new(self.deployment_id)
+                .execute(app_state)
+                .await?,
+            otp: GetDeploymentOtpSettingsQuery::new(self.deployment_id)
+                .execute(app_state)
+                .await?,
         };
 
         Ok(auth_settings)
     }
 }
+
+pub struct GetDeploymentLockoutPolicyQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentLockoutPolicyQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentLockoutPolicyQuery {
+    type Output = LockoutPolicy;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let policy: Option<serde_json::Value> = sqlx::query_scalar(
+            "SELECT lockout_policy FROM deployment_auth_settings WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        Ok(policy
+            .and_then(|policy| serde_json::from_value(policy).ok())
+            .unwrap_or_default())
+    }
+}
+
+pub struct GetDeploymentOtpSettingsQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentOtpSettingsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentOtpSettingsQuery {
+    type Output = OtpSettings;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let settings: Option<serde_json::Value> = sqlx::query_scalar(
+            "SELECT otp_settings FROM deployment_auth_settings WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        Ok(settings
+            .and_then(|settings| serde_json::from_value(settings).ok())
+            .unwrap_or_default())
+    }
+}
+
+pub struct GetDeploymentAlternateFirstFactorsQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentAlternateFirstFactorsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentAlternateFirstFactorsQuery {
+    type Output = Vec<FirstFactor>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let factors: Option<serde_json::Value> = sqlx::query_scalar(
+            "SELECT alternate_first_factors FROM deployment_auth_settings WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        Ok(factors
+            .and_then(|factors| serde_json::from_value(factors).ok())
+            .unwrap_or_default())
+    }
+}
+
+pub struct GetDeploymentUserDeletionPolicyQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentUserDeletionPolicyQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentUserDeletionPolicyQuery {
+    type Output = UserDeletionPolicy;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let policy: Option<serde_json::Value> = sqlx::query_scalar(
+            "SELECT user_deletion_policy FROM deployment_auth_settings WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        Ok(policy
+            .and_then(|policy| serde_json::from_value(policy).ok())
+            .unwrap_or_default())
+    }
+}
+
+/// The deployment's maintenance settings. Read from the primary, so turning
+/// maintenance off takes effect on the next request.
+pub struct GetDeploymentMaintenanceQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentMaintenanceQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentMaintenanceQuery {
+    type Output = DeploymentMaintenance;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = query(
+            r#"
+            SELECT maintenance_mode, maintenance_message, maintenance_starts_at,
+                maintenance_ends_at
+            FROM deployments
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.deployment_id
+            ))
+        })?;
+
+        Ok(DeploymentMaintenance {
+            maintenance_mode: row.get("maintenance_mode"),
+            message: row.get("maintenance_message"),
+            starts_at: row.get("maintenance_starts_at"),
+            ends_at: row.get("maintenance_ends_at"),
+        })
+    }
+}
+
+/// Checks a sign-up candidate against a deployment's allowlist and blocklist.
+pub struct EvaluateRestrictionsQuery {
+    deployment_id: i64,
+    candidate: RestrictionCandidate,
+}
+
+impl EvaluateRestrictionsQuery {
+    pub fn new(deployment_id: i64, candidate: RestrictionCandidate) -> Self {
+        Self {
+            deployment_id,
+            candidate,
+        }
+    }
+
+    /// Resolves the candidate's country from its phone number and IP and
+    /// checks it against the deployment's country restrictions. Conflicting
+    /// signals and denials are logged with both signals for support.
+    fn evaluate_country(
+        &self,
+        app_state: &AppState,
+        restrictions: &CountryRestrictions,
+    ) -> CountryRestrictionDecision {
+        let phone_countries = self
+            .candidate
+            .phone
+            .as_deref()
+            .map(countries_for_phone)
+            .unwrap_or_default();
+        let ip_country = self
+            .candidate
+            .ip
+            .and_then(|ip| app_state.geoip_service.country_code(ip));
+
+        let decision = CountryRestrictionEvaluator::new(restrictions)
+            .evaluate(phone_countries, ip_country.as_deref());
+
+        if decision.conflicting_signals || !decision.allowed {
+            tracing::info!(
+                deployment_id = self.deployment_id,
+                phone_countries = ?decision.phone_countries,
+                ip_country = ?decision.ip_country,
+                ip = ?self.candidate.ip,
+                allowed = decision.allowed,
+                denied_country = ?decision.denied_country,
+                conflicting_signals = decision.conflicting_signals,
+                "Country restriction decision"
+            );
+        }
+
+        decision
+    }
+}
+
+impl Query for EvaluateRestrictionsQuery {
+    type Output = RestrictionDecision;
+
+    /// Checks the blocklist and allowlist first; a sign-up they let through
+    /// is then checked against the country restrictions.
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT allowlist_enabled, blocklist_enabled, allowlisted_resources, blocklisted_resources,
+                block_subaddresses, country_restrictions
+            FROM deployment_restrictions
+            WHERE deployment_id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(DeploymentRestrictions::default().evaluate(&self.candidate));
+        };
+
+        let allowlisted_resources: Vec<String> = row.get("allowlisted_resources");
+        let blocklisted_resources: Vec<String> = row.get("blocklisted_resources");
+
+        let restrictions = DeploymentRestrictions {
+            deployment_id: self.deployment_id,
+            allowlist_enabled: row.get("allowlist_enabled"),
+            blocklist_enabled: row.get("blocklist_enabled"),
+            block_subaddresses: row.get("block_subaddresses"),
+            allowlisted_resources: RestrictionEntry::from_stored_list(&allowlisted_resources),
+            blocklisted_resources: RestrictionEntry::from_stored_list(&blocklisted_resources),
+            country_restrictions: serde_json::from_value(row.get("country_restrictions"))
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let mut decision = restrictions.evaluate(&self.candidate);
+        if !decision.allowed || !restrictions.country_restrictions.enabled {
+            return Ok(decision);
+        }
+
+        let country = self.evaluate_country(app_state, &restrictions.country_restrictions);
+        if !country.allowed {
+            decision.allowed = false;
+            decision.reason = RestrictionDecisionReason::CountryRestricted;
+            decision.matched_rule = None;
+        }
+        decision.country = Some(country);
+
+        Ok(decision)
+    }
+}
+
+/// Checks a deployment's stored redirect settings against its current
+/// policy. Settings saved before the policy existed, or before an origin was
+/// removed from the allowlist, keep working and show up here instead.
+pub struct ValidateRedirectUrlsQuery {
+    deployment_id: i64,
+}
+
+impl ValidateRedirectUrlsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for ValidateRedirectUrlsQuery {
+    type Output = RedirectUrlValidation;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = query(&format!(
+            r#"
+            SELECT deployments.frontend_host, deployment_ui_settings.allowed_redirect_origins, {}
+            FROM deployments
+            JOIN deployment_ui_settings
+                ON deployment_ui_settings.deployment_id = deployments.id
+            WHERE deployments.id = $1 AND deployments.deleted_at IS NULL
+            "#,
+            REDIRECT_URL_FIELDS.join(", ")
+        ))
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Display settings for deployment {} not found",
+                self.deployment_id
+            ))
+        })?;
+
+        let frontend_host: String = row.get("frontend_host");
+        let allowed_origins: Vec<String> = row.get("allowed_redirect_origins");
+        let urls: Vec<(&str, String)> = REDIRECT_URL_FIELDS
+            .iter()
+            .map(|&field| (field, row.get::<String, _>(field)))
+            .collect();
+
+        Ok(RedirectUrlPolicy::new(&frontend_host, &allowed_origins)
+            .validate(urls.iter().map(|(field, url)| (*field, url.as_str()))))
+    }
+}
@@ -0,0 +1,52 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{error::AppError, models::DeploymentApiKey, queries::Query, state::AppState};
+
+pub(crate) const API_KEY_COLUMNS: &str = r#"
+    id, created_at, deployment_id, name, key_prefix, scopes, created_by,
+    expires_at, last_used_at, revoked_at
+"#;
+
+pub(crate) fn deployment_api_key_from_row(row: &PgRow) -> DeploymentApiKey {
+    DeploymentApiKey {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        deployment_id: row.get("deployment_id"),
+        name: row.get("name"),
+        key_prefix: row.get("key_prefix"),
+        scopes: row.get("scopes"),
+        created_by: row.get("created_by"),
+        expires_at: row.get("expires_at"),
+        last_used_at: row.get("last_used_at"),
+        revoked_at: row.get("revoked_at"),
+    }
+}
+
+/// Lists a deployment's API keys, revoked and expired ones included, newest
+/// first.
+pub struct ListDeploymentApiKeysQuery {
+    deployment_id: i64,
+}
+
+impl ListDeploymentApiKeysQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for ListDeploymentApiKeysQuery {
+    type Output = Vec<DeploymentApiKey>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM deployment_api_keys WHERE deployment_id = $1 \
+             ORDER BY created_at DESC, id",
+            API_KEY_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(rows.iter().map(deployment_api_key_from_row).collect())
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::Utc;
+use serde_json::{Map, Value};
+use sqlx::PgConnection;
+
+use crate::{
+    error::AppError,
+    models::{
+        CONFIG_SECTIONS, DEPLOYMENT_CONFIG_SCHEMA_VERSION, DeploymentConfigBundle,
+        JWT_TEMPLATE_FIELDS, SOCIAL_CONNECTION_FIELDS,
+    },
+    services::{CredentialCipher, JWT_TEMPLATE_SIGNING_KEY, SOCIAL_CONNECTION_CLIENT_SECRET},
+    state::AppState,
+};
+
+use super::Query;
+
+pub(crate) const JWT_TEMPLATE_SECRET_FIELD: &str = "custom_signing_key";
+pub(crate) const SOCIAL_CONNECTION_SECRET_FIELD: &str = "credentials";
+
+/// Exports a deployment's configuration as a bundle that
+/// `ImportDeploymentConfigCommand` can apply to any deployment.
+pub struct ExportDeploymentConfigQuery {
+    deployment_id: i64,
+    include_secrets: bool,
+}
+
+impl ExportDeploymentConfigQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            include_secrets: false,
+        }
+    }
+
+    /// Exports JWT signing keys and social connection credentials as they are
+    /// stored. Without this they are exported as `null`.
+    pub fn with_include_secrets(mut self, include_secrets: bool) -> Self {
+        self.include_secrets = include_secrets;
+        self
+    }
+}
+
+impl Query for ExportDeploymentConfigQuery {
+    type Output = DeploymentConfigBundle;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+        read_deployment_config(
+            &mut conn,
+            &app_state.credential_cipher,
+            self.deployment_id,
+            self.include_secrets,
+        )
+        .await
+    }
+}
+
+/// Reads the configuration of a deployment on an existing connection, so the
+/// import can diff against it inside its own transaction. Secrets, when
+/// included, are decrypted.
+pub(crate) async fn read_deployment_config(
+    conn: &mut PgConnection,
+    cipher: &CredentialCipher,
+    deployment_id: i64,
+    include_secrets: bool,
+) -> Result<DeploymentConfigBundle, AppError> {
+    let frontend_host: String = sqlx::query_scalar(
+        "SELECT frontend_host FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+    )
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Deployment with id {} not found", deployment_id)))?;
+
+    let mut bundle = DeploymentConfigBundle {
+        schema_version: DEPLOYMENT_CONFIG_SCHEMA_VERSION,
+        exported_at: Utc::now(),
+        source_deployment_id: deployment_id,
+        source_frontend_host: frontend_host,
+        secrets_included: include_secrets,
+        auth_settings: None,
+        ui_settings: None,
+        b2b_settings: None,
+        restrictions: None,
+        email_templates: None,
+        sms_templates: None,
+        jwt_templates: Vec::new(),
+        social_connections: Vec::new(),
+    };
+
+    for section in &CONFIG_SECTIONS {
+        let row: Option<Value> = sqlx::query_scalar(&format!(
+            "SELECT to_jsonb(t) FROM {} t WHERE deployment_id = $1",
+            section.table
+        ))
+        .bind(deployment_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(slot) = bundle.section_mut(section.name) {
+            *slot = row.map(|row| pick_fields(row, &section.all_fields()));
+        }
+    }
+
+    let jwt_templates: Vec<Value> = sqlx::query_scalar(
+        "SELECT to_jsonb(t) FROM deployment_jwt_templates t WHERE deployment_id = $1 ORDER BY name",
+    )
+    .bind(deployment_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    bundle.jwt_templates = jwt_templates
+        .into_iter()
+        .map(|row| {
+            let mut template = pick_fields(row, JWT_TEMPLATE_FIELDS);
+            if include_secrets {
+                JWT_TEMPLATE_SIGNING_KEY.open_in(cipher, &mut template)?;
+            } else {
+                template.insert(JWT_TEMPLATE_SECRET_FIELD.to_string(), Value::Null);
+            }
+            Ok(template)
+        })
+        .collect::<Result<_, AppError>>()?;
+
+    let social_connections: Vec<Value> = sqlx::query_scalar(
+        r#"
+        SELECT to_jsonb(t) FROM deployment_social_connections t
+        WHERE deployment_id = $1 AND deleted_at IS NULL
+        ORDER BY provider
+        "#,
+    )
+    .bind(deployment_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    bundle.social_connections = social_connections
+        .into_iter()
+        .map(|row| {
+            let mut connection = pick_fields(row, SOCIAL_CONNECTION_FIELDS);
+            if include_secrets {
+                SOCIAL_CONNECTION_CLIENT_SECRET.open_in(cipher, &mut connection)?;
+            } else {
+                connection.insert(SOCIAL_CONNECTION_SECRET_FIELD.to_string(), Value::Null);
+            }
+            Ok(connection)
+        })
+        .collect::<Result<_, AppError>>()?;
+
+    Ok(bundle)
+}
+
+/// Keeps only the exported fields of a row, dropping ids, timestamps and
+/// anything else that only makes sense inside the source deployment.
+fn pick_fields(row: Value, fields: &[&str]) -> Map<String, Value> {
+    let Value::Object(mut row) = row else {
+        return Map::new();
+    };
+
+    fields
+        .iter()
+        .map(|field| (field.to_string(), row.remove(*field).unwrap_or(Value::Null)))
+        .collect()
+}
@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{DataExportStatus, DeploymentDataExport},
+    queries::Query,
+    state::AppState,
+};
+
+pub(crate) const DATA_EXPORT_COLUMNS: &str = r#"
+    id, created_at, updated_at, deployment_id, status, requested_by, object_key,
+    size_bytes, record_counts, error, completed_at, expires_at
+"#;
+
+/// How long a download link handed out for an export works.
+const DOWNLOAD_URL_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Reads an export row without a download link.
+pub(crate) fn data_export_from_row(row: &PgRow) -> Result<DeploymentDataExport, AppError> {
+    Ok(DeploymentDataExport {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        status: row
+            .get::<String, _>("status")
+            .parse()
+            .map_err(AppError::Internal)?,
+        requested_by: row.get("requested_by"),
+        size_bytes: row.get("size_bytes"),
+        record_counts: row
+            .get::<Option<serde_json::Value>, _>("record_counts")
+            .and_then(|value| serde_json::from_value(value).ok()),
+        error: row.get("error"),
+        completed_at: row.get("completed_at"),
+        expires_at: row.get("expires_at"),
+        download_url: None,
+        download_url_expires_at: None,
+    })
+}
+
+/// Signs a short-lived link to a completed export's archive. The link is
+/// signed on every read rather than stored, and never outlives the archive.
+async fn with_download_url(
+    app_state: &AppState,
+    row: &PgRow,
+) -> Result<DeploymentDataExport, AppError> {
+    let mut export = data_export_from_row(row)?;
+    if export.status != DataExportStatus::Completed {
+        return Ok(export);
+    }
+
+    let now = Utc::now();
+    let ttl = export
+        .expires_at
+        .and_then(|expires_at| (expires_at - now).to_std().ok())
+        .map_or(DOWNLOAD_URL_TTL, |left| left.min(DOWNLOAD_URL_TTL));
+    if ttl.is_zero() {
+        return Ok(export);
+    }
+
+    let object_key: String = row.get("object_key");
+    let content_disposition = format!(
+        "attachment; filename=\"deployment-{}-export-{}.zip\"",
+        export.deployment_id, export.id
+    );
+    let download_url = app_state
+        .storage
+        .exports()
+        .presign_get(&object_key, Some(&content_disposition), ttl)
+        .await?;
+
+    export.download_url = Some(download_url);
+    export.download_url_expires_at =
+        Some(now + chrono::Duration::from_std(ttl).unwrap_or_default());
+    Ok(export)
+}
+
+/// One export of the deployment, with a download link once it is complete.
+pub struct GetExportStatusQuery {
+    deployment_id: i64,
+    export_id: i64,
+}
+
+impl GetExportStatusQuery {
+    pub fn new(deployment_id: i64, export_id: i64) -> Self {
+        Self {
+            deployment_id,
+            export_id,
+        }
+    }
+}
+
+impl Query for GetExportStatusQuery {
+    type Output = DeploymentDataExport;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM deployment_data_exports WHERE id = $1 AND deployment_id = $2",
+            DATA_EXPORT_COLUMNS
+        ))
+        .bind(self.export_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Export not found".to_string()))?;
+
+        with_download_url(app_state, &row).await
+    }
+}
+
+/// The deployment's exports, newest first.
+pub struct ListExportsQuery {
+    deployment_id: i64,
+    offset: i64,
+    limit: i64,
+}
+
+impl ListExportsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            offset: 0,
+            limit: 10,
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl Query for ListExportsQuery {
+    type Output = Vec<DeploymentDataExport>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {} FROM deployment_data_exports
+            WHERE deployment_id = $1
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            DATA_EXPORT_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .bind(self.limit)
+        .bind(self.offset)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        let mut exports = Vec::with_capacity(rows.len());
+        for row in &rows {
+            exports.push(with_download_url(app_state, row).await?);
+        }
+        Ok(exports)
+    }
+}
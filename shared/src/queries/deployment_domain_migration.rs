@@ -0,0 +1,93 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{DeploymentDomainMigration, DomainMigrationStatus},
+    queries::{PROVISIONING_ACTION_COLUMNS, Query, provisioning_action_from_row},
+    state::AppState,
+};
+
+pub(crate) const DOMAIN_MIGRATION_COLUMNS: &str = r#"
+    id, created_at, updated_at, deployment_id, status,
+    old_frontend_host, old_backend_host, old_mail_from_host, old_publishable_key,
+    old_domain_verification_records, old_email_verification_records,
+    new_frontend_host, new_backend_host, new_mail_from_host,
+    new_domain_verification_records, new_email_verification_records, completed_at
+"#;
+
+/// Reads a migration row without its actions.
+pub(crate) fn domain_migration_from_row(row: &PgRow) -> DeploymentDomainMigration {
+    fn records<T: serde::de::DeserializeOwned>(row: &PgRow, column: &str) -> Option<T> {
+        row.get::<Option<serde_json::Value>, _>(column)
+            .and_then(|value| serde_json::from_value(value).ok())
+    }
+
+    DeploymentDomainMigration {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        status: DomainMigrationStatus::from(row.get::<String, _>("status")),
+        old_frontend_host: row.get("old_frontend_host"),
+        old_backend_host: row.get("old_backend_host"),
+        old_mail_from_host: row.get("old_mail_from_host"),
+        old_publishable_key: row.get("old_publishable_key"),
+        old_domain_verification_records: records(row, "old_domain_verification_records"),
+        old_email_verification_records: records(row, "old_email_verification_records"),
+        new_frontend_host: row.get("new_frontend_host"),
+        new_backend_host: row.get("new_backend_host"),
+        new_mail_from_host: row.get("new_mail_from_host"),
+        new_domain_verification_records: records(row, "new_domain_verification_records"),
+        new_email_verification_records: records(row, "new_email_verification_records"),
+        completed_at: row.get("completed_at"),
+        actions: Vec::new(),
+    }
+}
+
+/// A deployment's most recent domain migration and its actions, oldest
+/// first.
+pub struct GetDomainMigrationStatusQuery {
+    deployment_id: i64,
+}
+
+impl GetDomainMigrationStatusQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDomainMigrationStatusQuery {
+    type Output = DeploymentDomainMigration;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM deployment_domain_migrations WHERE deployment_id = $1 \
+             ORDER BY created_at DESC, id DESC LIMIT 1",
+            DOMAIN_MIGRATION_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound("No domain migration found for this deployment".to_string())
+        })?;
+
+        let mut migration = domain_migration_from_row(&row);
+
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM deployment_provisioning_actions WHERE domain_migration_id = $1 \
+             ORDER BY created_at, id",
+            PROVISIONING_ACTION_COLUMNS
+        ))
+        .bind(migration.id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        migration.actions = rows
+            .iter()
+            .map(provisioning_action_from_row)
+            .collect::<Result<_, _>>()?;
+
+        Ok(migration)
+    }
+}
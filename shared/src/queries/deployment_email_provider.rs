@@ -0,0 +1,57 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{DeploymentEmailProvider, EmailProviderType},
+    queries::Query,
+    state::AppState,
+};
+
+pub(crate) const EMAIL_PROVIDER_COLUMNS: &str = r#"
+    id, created_at, updated_at, deployment_id, provider, from_address, settings, verified_at
+"#;
+
+pub(crate) fn email_provider_from_row(row: &PgRow) -> DeploymentEmailProvider {
+    DeploymentEmailProvider {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        provider: EmailProviderType::from(row.get::<String, _>("provider")),
+        from_address: row.get("from_address"),
+        settings: row.get("settings"),
+        verified_at: row.get("verified_at"),
+    }
+}
+
+/// The deployment's own email provider, without its credentials.
+pub struct GetDeploymentEmailProviderQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentEmailProviderQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentEmailProviderQuery {
+    type Output = DeploymentEmailProvider;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM deployment_email_providers WHERE deployment_id = $1",
+            EMAIL_PROVIDER_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(
+                "This deployment sends through the platform's email provider".to_string(),
+            )
+        })?;
+
+        Ok(email_provider_from_row(&row))
+    }
+}
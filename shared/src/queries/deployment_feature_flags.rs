@@ -0,0 +1,142 @@
+use serde_json::{Value, json};
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{
+        DeploymentFeatureFlagSettings, DeploymentFeatureFlags, DeploymentFlagValue, DeploymentMode,
+        feature_flag,
+    },
+    queries::Query,
+    state::AppState,
+};
+
+/// A deployment's flags, merged with the platform defaults.
+pub struct GetDeploymentFeatureFlagsQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentFeatureFlagsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentFeatureFlagsQuery {
+    type Output = DeploymentFeatureFlagSettings;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT f.flags
+            FROM deployments d
+            LEFT JOIN deployment_feature_flags f ON f.deployment_id = d.id
+            WHERE d.id = $1 AND d.deleted_at IS NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Deployment with id {} not found",
+                self.deployment_id
+            ))
+        })?;
+
+        let overrides = row
+            .get::<Option<Value>, _>("flags")
+            .map(DeploymentFeatureFlags::from_stored)
+            .unwrap_or_default();
+
+        Ok(DeploymentFeatureFlagSettings {
+            deployment_id: self.deployment_id,
+            flags: DeploymentFeatureFlags::effective(&overrides),
+            overrides,
+        })
+    }
+}
+
+/// Deployments a flag has a given value for, whether set for them or
+/// inherited from the default, to follow a rollout.
+pub struct ListDeploymentsWithFlagQuery {
+    flag: String,
+    value: Value,
+    limit: i64,
+    offset: i64,
+}
+
+impl ListDeploymentsWithFlagQuery {
+    pub fn new(flag: impl Into<String>, value: Value) -> Self {
+        Self {
+            flag: flag.into(),
+            value,
+            limit: 50,
+            offset: 0,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: i64) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn with_offset(mut self, offset: i64) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+impl Query for ListDeploymentsWithFlagQuery {
+    type Output = Vec<DeploymentFlagValue>;
+
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let flag = feature_flag(&self.flag).ok_or_else(|| {
+            AppError::NotFound(format!("There is no feature flag named {}", self.flag))
+        })?;
+        if !flag.kind.accepts(&self.value) {
+            return Err(AppError::BadRequest(format!(
+                "{} takes {} values",
+                flag.name,
+                flag.kind.as_str()
+            )));
+        }
+        let is_default = self.value == flag.default_value();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT d.id, d.project_id, d.mode, d.frontend_host, f.flags -> $1 AS override
+            FROM deployments d
+            LEFT JOIN deployment_feature_flags f ON f.deployment_id = d.id
+            WHERE d.deleted_at IS NULL
+              AND (f.flags @> $2 OR ($3 AND (f.flags IS NULL OR NOT f.flags ? $1)))
+            ORDER BY d.id
+            LIMIT $4 OFFSET $5
+            "#,
+        )
+        .bind(flag.name)
+        .bind(json!({ flag.name: self.value }))
+        .bind(is_default)
+        .bind(self.limit)
+        .bind(self.offset)
+        .fetch_all(self.pool(app_state))
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let overridden = row.get::<Option<Value>, _>("override").is_some();
+                DeploymentFlagValue {
+                    deployment_id: row.get("id"),
+                    project_id: row.get("project_id"),
+                    mode: DeploymentMode::from(row.get::<String, _>("mode")),
+                    frontend_host: row.get("frontend_host"),
+                    value: self.value.clone(),
+                    overridden,
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,245 @@
+use chrono::Utc;
+use serde_json::{Value, json};
+use sqlx::Row;
+
+use crate::{
+    dto::json::RenderJwtTemplateRequest,
+    error::AppError,
+    models::{
+        CustomSigningKey, FlaggedJwtTemplate, JwtTemplateOrganization, JwtTemplateUser,
+        RenderedJwtTemplate,
+    },
+    queries::{GetUserDetailsQuery, Query},
+    services::{
+        JWT_TEMPLATE_SIGNING_KEY, JwtSigningKey, KEY_PAIR_PRIVATE_KEY, https_url,
+        jwt_template_context, render_jwt_claims,
+    },
+    state::AppState,
+    validators::validate_jwt_template,
+};
+
+/// Marks rendered tokens, so nothing mistakes one for a token a user signed
+/// in for.
+pub const TEST_TOKEN_CLAIM: &str = "test_token";
+
+/// Signs a token from a template for an existing user or a made-up one, so a
+/// template can be checked before anything is issued with it. The registered
+/// claims are set after the template's, which can't override them.
+pub struct RenderJwtTemplateQuery {
+    deployment_id: i64,
+    template_id: i64,
+    request: RenderJwtTemplateRequest,
+}
+
+impl RenderJwtTemplateQuery {
+    pub fn new(deployment_id: i64, template_id: i64, request: RenderJwtTemplateRequest) -> Self {
+        Self {
+            deployment_id,
+            template_id,
+            request,
+        }
+    }
+
+    async fn user(&self, app_state: &AppState) -> Result<JwtTemplateUser, AppError> {
+        if let Some(user) = &self.request.user {
+            return Ok(user.clone());
+        }
+        let user_id = self
+            .request
+            .user_id
+            .ok_or_else(|| AppError::Validation("user_id or user is required".to_string()))?;
+
+        let mut user: JwtTemplateUser = match GetUserDetailsQuery::new(self.deployment_id, user_id)
+            .execute(app_state)
+            .await
+        {
+            Ok(user) => user.into(),
+            Err(AppError::Database(sqlx::Error::RowNotFound)) => {
+                return Err(AppError::NotFound(format!(
+                    "User with id {} not found",
+                    user_id
+                )));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let organization = sqlx::query(
+            r#"
+            SELECT
+                o.id, o.name, o.public_metadata,
+                ARRAY(
+                    SELECT r.name
+                    FROM organization_membership_roles omr
+                    JOIN organization_roles r ON r.id = omr.organization_role_id
+                    WHERE omr.organization_membership_id = om.id
+                    ORDER BY r.name
+                ) AS roles
+            FROM users u
+            JOIN organization_memberships om ON om.id = u.active_organization_membership_id
+            JOIN organizations o ON o.id = om.organization_id
+            WHERE u.id = $1 AND u.deployment_id = $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+        user.organization = organization.map(|row| JwtTemplateOrganization {
+            id: row.get::<i64, _>("id").to_string(),
+            name: row.get("name"),
+            roles: row.get("roles"),
+            public_metadata: row.get("public_metadata"),
+        });
+
+        Ok(user)
+    }
+
+    /// The template's custom key when it is enabled, otherwise the
+    /// deployment's key pair.
+    async fn signing_key(
+        &self,
+        app_state: &AppState,
+        custom_signing_key: Option<Value>,
+    ) -> Result<JwtSigningKey, AppError> {
+        let cipher = &app_state.credential_cipher;
+
+        if let Some(stored) = custom_signing_key {
+            let key: CustomSigningKey =
+                serde_json::from_value(JWT_TEMPLATE_SIGNING_KEY.open(cipher, stored)?)
+                    .unwrap_or_default();
+            if key.enabled && !key.key.is_empty() {
+                return JwtSigningKey::new(&key.algorithm, &key.key).map_err(|e| match e {
+                    AppError::BadRequest(message) => AppError::BadRequest(format!(
+                        "The template's custom signing key can't be used: {}",
+                        message
+                    )),
+                    e => e,
+                });
+            }
+        }
+
+        let row = sqlx::query(
+            "SELECT id, private_key FROM deployment_key_pairs WHERE deployment_id = $1 ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::Internal("The deployment has no signing key pair".to_string()))?;
+
+        let private_key =
+            cipher.open(&row.get::<String, _>("private_key"), KEY_PAIR_PRIVATE_KEY)?;
+        // Key pairs are generated as ES256 keys.
+        let key = JwtSigningKey::new("ES256", &private_key).map_err(|e| {
+            AppError::Internal(format!("The deployment's signing key can't be used: {}", e))
+        })?;
+
+        Ok(key.with_key_id(row.get::<i64, _>("id").to_string()))
+    }
+}
+
+impl Query for RenderJwtTemplateQuery {
+    type Output = RenderedJwtTemplate;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let template = sqlx::query(
+            r#"
+            SELECT t.token_lifetime, t.allowed_clock_skew, t.custom_signing_key, t.template, d.frontend_host
+            FROM deployment_jwt_templates t
+            JOIN deployments d ON d.id = t.deployment_id
+            WHERE t.id = $1 AND t.deployment_id = $2 AND d.deleted_at IS NULL
+            "#,
+        )
+        .bind(self.template_id)
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("JWT template with id {} not found", self.template_id))
+        })?;
+
+        let user = self.user(app_state).await?;
+        let rendered = render_jwt_claims(
+            &template.get::<Value, _>("template"),
+            &jwt_template_context(&user),
+        )?;
+        let mut claims = match rendered {
+            Value::Object(claims) => claims,
+            _ => {
+                return Err(AppError::BadRequest(
+                    "The template must be a JSON object".to_string(),
+                ));
+            }
+        };
+
+        let now = Utc::now().timestamp();
+        let token_lifetime: i64 = template.get("token_lifetime");
+        let allowed_clock_skew: i64 = template.get("allowed_clock_skew");
+        claims.insert("sub".to_string(), json!(user.id));
+        claims.insert(
+            "iss".to_string(),
+            json!(https_url(&template.get::<String, _>("frontend_host"))),
+        );
+        claims.insert("iat".to_string(), json!(now));
+        claims.insert("nbf".to_string(), json!(now - allowed_clock_skew));
+        claims.insert("exp".to_string(), json!(now + token_lifetime));
+        claims.insert(TEST_TOKEN_CLAIM.to_string(), json!(true));
+        let claims = Value::Object(claims);
+
+        let key = self
+            .signing_key(app_state, template.get("custom_signing_key"))
+            .await?;
+        let token = key.sign(&claims)?;
+
+        Ok(RenderedJwtTemplate {
+            header: serde_json::to_value(key.header())?,
+            claims,
+            token,
+        })
+    }
+}
+
+/// Runs the checks templates get on save over every template of a
+/// deployment, and returns the ones that fail them.
+pub struct ValidateExistingJwtTemplatesQuery {
+    deployment_id: i64,
+}
+
+impl ValidateExistingJwtTemplatesQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for ValidateExistingJwtTemplatesQuery {
+    type Output = Vec<FlaggedJwtTemplate>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, token_lifetime, allowed_clock_skew, template
+            FROM deployment_jwt_templates
+            WHERE deployment_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let validation = validate_jwt_template(
+                    Some(&row.get::<Value, _>("template")),
+                    Some(row.get("token_lifetime")),
+                    Some(row.get("allowed_clock_skew")),
+                );
+                (!validation.is_valid()).then(|| FlaggedJwtTemplate {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    errors: validation.errors,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,64 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{DeploymentLogExport, LogExportFormat, LogExportSchedule},
+    state::AppState,
+};
+
+use super::Query;
+
+pub(crate) const LOG_EXPORT_COLUMNS: &str = r#"
+    id, created_at, updated_at, deployment_id, bucket, region, prefix, format, schedule,
+    credentials, audit_logs_exported_through, request_logs_exported_through, next_run_at,
+    last_success_at, last_error, consecutive_failures
+"#;
+
+pub(crate) fn log_export_from_row(row: &PgRow) -> DeploymentLogExport {
+    DeploymentLogExport {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        bucket: row.get("bucket"),
+        region: row.get("region"),
+        prefix: row.get("prefix"),
+        format: LogExportFormat::from(row.get::<String, _>("format")),
+        schedule: LogExportSchedule::from(row.get::<String, _>("schedule")),
+        credentials: row.get("credentials"),
+        audit_logs_exported_through: row.get("audit_logs_exported_through"),
+        request_logs_exported_through: row.get("request_logs_exported_through"),
+        next_run_at: row.get("next_run_at"),
+        last_success_at: row.get("last_success_at"),
+        last_error: row.get("last_error"),
+        consecutive_failures: row.get("consecutive_failures"),
+    }
+}
+
+/// The deployment's log export, without its credentials.
+pub struct GetDeploymentLogExportQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentLogExportQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentLogExportQuery {
+    type Output = DeploymentLogExport;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM deployment_log_exports WHERE deployment_id = $1",
+            LOG_EXPORT_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("This deployment doesn't export its logs".to_string()))?;
+
+        Ok(log_export_from_row(&row))
+    }
+}
@@ -0,0 +1,49 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{error::AppError, models::DeploymentMailSettings, queries::Query, state::AppState};
+
+pub(crate) const MAIL_SETTINGS_COLUMNS: &str = r#"
+    id, mail_from_host, email_verification_records::jsonb AS email_verification_records,
+    pending_mail_from_host, pending_email_verification_records
+"#;
+
+pub(crate) fn mail_settings_from_row(row: &PgRow) -> DeploymentMailSettings {
+    DeploymentMailSettings {
+        deployment_id: row.get("id"),
+        mail_from_host: row.get("mail_from_host"),
+        email_verification_records: row
+            .get::<Option<serde_json::Value>, _>("email_verification_records")
+            .and_then(|v| serde_json::from_value(v).ok()),
+        pending_mail_from_host: row.get("pending_mail_from_host"),
+        pending_email_verification_records: row
+            .get::<Option<serde_json::Value>, _>("pending_email_verification_records")
+            .and_then(|v| serde_json::from_value(v).ok()),
+    }
+}
+
+pub struct GetDeploymentMailSettingsQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentMailSettingsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentMailSettingsQuery {
+    type Output = DeploymentMailSettings;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+            MAIL_SETTINGS_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        Ok(mail_settings_from_row(&row))
+    }
+}
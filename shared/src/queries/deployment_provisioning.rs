@@ -0,0 +1,83 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{
+        DeploymentProvisioning, DeploymentProvisioningAction, ProvisioningActionKind,
+        ProvisioningActionPayload, ProvisioningActionStatus, ProvisioningStatus,
+    },
+    queries::Query,
+    state::AppState,
+};
+
+pub(crate) const PROVISIONING_ACTION_COLUMNS: &str = r#"
+    id, created_at, updated_at, deployment_id, domain_migration_id, action, payload, status,
+    attempts, next_attempt_at, last_error, result, completed_at
+"#;
+
+pub(crate) fn provisioning_action_from_row(
+    row: &PgRow,
+) -> Result<DeploymentProvisioningAction, AppError> {
+    let payload: serde_json::Value = row.get("payload");
+
+    Ok(DeploymentProvisioningAction {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        domain_migration_id: row.get("domain_migration_id"),
+        action: ProvisioningActionKind::from(row.get::<String, _>("action")),
+        payload: serde_json::from_value::<ProvisioningActionPayload>(payload)
+            .map_err(|e| AppError::Serialization(e.to_string()))?,
+        status: ProvisioningActionStatus::from(row.get::<String, _>("status")),
+        attempts: row.get("attempts"),
+        next_attempt_at: row.get("next_attempt_at"),
+        last_error: row.get("last_error"),
+        result: row.get("result"),
+        completed_at: row.get("completed_at"),
+    })
+}
+
+/// A deployment's provisioning status and every action behind it, oldest
+/// first. Actions of domain migrations are listed with the migration.
+pub struct GetDeploymentProvisioningQuery {
+    deployment_id: i64,
+}
+
+impl GetDeploymentProvisioningQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentProvisioningQuery {
+    type Output = DeploymentProvisioning;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let status: String = sqlx::query_scalar(
+            "SELECT provisioning_status FROM deployments WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM deployment_provisioning_actions \
+             WHERE deployment_id = $1 AND domain_migration_id IS NULL ORDER BY created_at, id",
+            PROVISIONING_ACTION_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(DeploymentProvisioning {
+            deployment_id: self.deployment_id,
+            status: ProvisioningStatus::from(status),
+            actions: rows
+                .iter()
+                .map(provisioning_action_from_row)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
@@ -0,0 +1,167 @@
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{
+        DeploymentDisposableDomain, DisposableDomainKind, DisposableDomainSummary,
+        DisposableEmailCheck,
+    },
+    queries::Query,
+    services::DeploymentDisposableDomains,
+    state::AppState,
+};
+
+pub struct GetDeploymentDisposableDomainsQuery {
+    pub deployment_id: i64,
+}
+
+impl GetDeploymentDisposableDomainsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentDisposableDomainsQuery {
+    type Output = Vec<DeploymentDisposableDomain>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, deployment_id, domain, kind, created_at
+            FROM deployment_disposable_email_domains
+            WHERE deployment_id = $1
+            ORDER BY domain
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DeploymentDisposableDomain {
+                id: row.get("id"),
+                deployment_id: row.get("deployment_id"),
+                domain: row.get("domain"),
+                kind: DisposableDomainKind::from(row.get::<String, _>("kind")),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+}
+
+async fn block_disposable_emails(
+    app_state: &AppState,
+    deployment_id: i64,
+) -> Result<bool, AppError> {
+    let enabled: Option<bool> = sqlx::query_scalar(
+        "SELECT block_disposable_emails FROM deployment_restrictions WHERE deployment_id = $1",
+    )
+    .bind(deployment_id)
+    .fetch_optional(&app_state.db_pool)
+    .await?;
+
+    Ok(enabled.unwrap_or(false))
+}
+
+fn to_overrides(
+    block_disposable_emails: bool,
+    domains: &[DeploymentDisposableDomain],
+) -> DeploymentDisposableDomains {
+    let mut overrides = DeploymentDisposableDomains {
+        block_disposable_emails,
+        ..Default::default()
+    };
+    for domain in domains {
+        match domain.kind {
+            DisposableDomainKind::Blocked => overrides.blocked.insert(domain.domain.clone()),
+            DisposableDomainKind::Allowed => overrides.allowed.insert(domain.domain.clone()),
+        };
+    }
+    overrides
+}
+
+pub struct GetDisposableDomainSummaryQuery {
+    pub deployment_id: i64,
+}
+
+impl GetDisposableDomainSummaryQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDisposableDomainSummaryQuery {
+    type Output = DisposableDomainSummary;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let service = &app_state.disposable_domain_service;
+        let block_disposable_emails =
+            block_disposable_emails(app_state, self.deployment_id).await?;
+        let domains = GetDeploymentDisposableDomainsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+
+        let overrides = to_overrides(block_disposable_emails, &domains);
+        let (blocked, allowed) = domains
+            .into_iter()
+            .partition(|domain| domain.kind == DisposableDomainKind::Blocked);
+
+        Ok(DisposableDomainSummary {
+            block_disposable_emails,
+            upstream: service.dataset(),
+            effective_domain_count: service.effective_domain_count(&overrides),
+            blocked,
+            allowed,
+        })
+    }
+}
+
+/// Checks an email's domain against the upstream disposable list merged with
+/// the deployment's own additions and exceptions. Meant for the sign-up path:
+/// apart from a cache miss on the deployment's overrides it's all in memory.
+pub struct CheckEmailAgainstRestrictionsQuery {
+    deployment_id: i64,
+    email: String,
+}
+
+impl CheckEmailAgainstRestrictionsQuery {
+    pub fn new(deployment_id: i64, email: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            email: email.into(),
+        }
+    }
+}
+
+impl Query for CheckEmailAgainstRestrictionsQuery {
+    type Output = DisposableEmailCheck;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let (_, domain) = self
+            .email
+            .rsplit_once('@')
+            .filter(|(local, domain)| !local.is_empty() && !domain.is_empty())
+            .ok_or_else(|| {
+                AppError::Validation(format!("`{}` is not an email address", self.email))
+            })?;
+
+        let service = &app_state.disposable_domain_service;
+        let overrides = match service.cached_deployment_domains(self.deployment_id) {
+            Some(overrides) => overrides,
+            None => {
+                let block_disposable_emails =
+                    block_disposable_emails(app_state, self.deployment_id).await?;
+                let domains = GetDeploymentDisposableDomainsQuery::new(self.deployment_id)
+                    .execute(app_state)
+                    .await?;
+                service.cache_deployment_domains(
+                    self.deployment_id,
+                    to_overrides(block_disposable_emails, &domains),
+                )
+            }
+        };
+
+        Ok(service.check(domain, &overrides))
+    }
+}
@@ -0,0 +1,87 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{EmailOutboxEntry, EmailOutboxStatus},
+    queries::Query,
+    state::AppState,
+};
+
+fn outbox_entry_from_row(row: &PgRow) -> Result<EmailOutboxEntry, AppError> {
+    Ok(EmailOutboxEntry {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        template_name: row.get("template_name"),
+        to_email: row.get("to_email"),
+        subject: row.get("subject"),
+        status: row
+            .get::<String, _>("status")
+            .parse()
+            .map_err(AppError::Internal)?,
+        provider: row.get("provider"),
+        provider_message_id: row.get("provider_message_id"),
+        error: row.get("error"),
+        attempts: row.get("attempts"),
+        sent_at: row.get("sent_at"),
+    })
+}
+
+/// The emails sent for a deployment, newest first.
+pub struct ListEmailOutboxQuery {
+    deployment_id: i64,
+    status: Option<EmailOutboxStatus>,
+    offset: i64,
+    limit: i64,
+}
+
+impl ListEmailOutboxQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            status: None,
+            offset: 0,
+            limit: 10,
+        }
+    }
+
+    pub fn status(self, status: Option<EmailOutboxStatus>) -> Self {
+        Self { status, ..self }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl Query for ListEmailOutboxQuery {
+    type Output = Vec<EmailOutboxEntry>;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                id, created_at, updated_at, deployment_id, template_name, to_email,
+                subject, status, provider, provider_message_id, error, attempts, sent_at
+            FROM email_outbox
+            WHERE deployment_id = $1 AND ($2::TEXT IS NULL OR status = $2)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.status.map(|status| status.as_str()))
+        .bind(self.limit)
+        .bind(self.offset)
+        .fetch_all(self.pool(app_state))
+        .await?;
+
+        rows.iter().map(outbox_entry_from_row).collect()
+    }
+}
@@ -0,0 +1,166 @@
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{GlobalSearchField, GlobalSearchResult},
+    state::AppState,
+    utils::validation::{ValidationError, validation_failed},
+};
+
+use super::Query;
+
+/// Results are capped here whatever the term; operators narrow the term
+/// rather than page.
+pub const GLOBAL_SEARCH_LIMIT: i64 = 50;
+
+/// Shorter terms would match most of the platform.
+pub const MIN_GLOBAL_SEARCH_TERM_LENGTH: usize = 3;
+
+/// Escapes the `LIKE` wildcards in a term so it only matches literally.
+fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Finds projects and deployments across the whole platform for operators:
+/// project names containing the term, hostnames and publishable keys
+/// starting with it, and Cloudflare custom hostname ids equal to it. Deleted
+/// projects and deployments are searched too, and flagged as such.
+pub struct GlobalSearchQuery {
+    term: String,
+}
+
+impl GlobalSearchQuery {
+    pub fn new(term: impl Into<String>) -> Self {
+        Self { term: term.into() }
+    }
+}
+
+impl Query for GlobalSearchQuery {
+    type Output = Vec<GlobalSearchResult>;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let term = self.term.trim();
+        if term.chars().count() < MIN_GLOBAL_SEARCH_TERM_LENGTH {
+            return Err(validation_failed(vec![ValidationError::rule(
+                "q",
+                "min_length",
+                &format!(
+                    "Search terms need at least {} characters",
+                    MIN_GLOBAL_SEARCH_TERM_LENGTH
+                ),
+            )]));
+        }
+        let lowered = term.to_lowercase();
+
+        // Each branch is capped on its own so one broad match can't make the
+        // others scan further than the final cap needs.
+        let rows = sqlx::query(
+            r#"
+            WITH matches AS (
+                (
+                    SELECT 'project_name' AS field, p.name AS value, p.id AS project_id,
+                        NULL::BIGINT AS deployment_id
+                    FROM projects p
+                    WHERE lower(p.name) LIKE '%' || $1 || '%'
+                    LIMIT $5
+                )
+                UNION ALL
+                (
+                    SELECT 'frontend_host', d.frontend_host, d.project_id, d.id
+                    FROM deployments d
+                    WHERE lower(d.frontend_host) LIKE $1 || '%'
+                    LIMIT $5
+                )
+                UNION ALL
+                (
+                    SELECT 'backend_host', d.backend_host, d.project_id, d.id
+                    FROM deployments d
+                    WHERE lower(d.backend_host) LIKE $1 || '%'
+                    LIMIT $5
+                )
+                UNION ALL
+                (
+                    SELECT 'mail_from_host', d.mail_from_host, d.project_id, d.id
+                    FROM deployments d
+                    WHERE lower(d.mail_from_host) LIKE $1 || '%'
+                    LIMIT $5
+                )
+                UNION ALL
+                (
+                    SELECT 'publishable_key', d.publishable_key, d.project_id, d.id
+                    FROM deployments d
+                    WHERE d.publishable_key LIKE $2 || '%'
+                    LIMIT $5
+                )
+                UNION ALL
+                (
+                    SELECT 'frontend_hostname_id', $3::TEXT, d.project_id, d.id
+                    FROM deployments d
+                    WHERE d.domain_verification_records::jsonb ->> 'frontend_hostname_id' = $3
+                    LIMIT $5
+                )
+                UNION ALL
+                (
+                    SELECT 'backend_hostname_id', $3::TEXT, d.project_id, d.id
+                    FROM deployments d
+                    WHERE d.domain_verification_records::jsonb ->> 'backend_hostname_id' = $3
+                    LIMIT $5
+                )
+            )
+            SELECT m.field, m.value, m.project_id, m.deployment_id, p.name AS project_name,
+                (p.deleted_at IS NOT NULL OR d.deleted_at IS NOT NULL) AS deleted
+            FROM matches m
+            JOIN projects p ON p.id = m.project_id
+            LEFT JOIN deployments d ON d.id = m.deployment_id
+            ORDER BY lower(m.value) = $4 DESC, deleted, m.value, m.project_id, m.deployment_id
+            LIMIT $5
+            "#,
+        )
+        .bind(escape_like(&lowered))
+        .bind(escape_like(term))
+        .bind(term)
+        .bind(&lowered)
+        .bind(GLOBAL_SEARCH_LIMIT)
+        .fetch_all(self.pool(app_state))
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let matched_field = GlobalSearchField::from(row.get::<String, _>("field"));
+                let project_id: i64 = row.get("project_id");
+                GlobalSearchResult {
+                    kind: matched_field.kind(),
+                    matched_field,
+                    matched_value: row.get("value"),
+                    project_id,
+                    project_name: row.get("project_name"),
+                    deployment_id: row.get("deployment_id"),
+                    deleted: row.get("deleted"),
+                    project_url: format!("/projects/{}/deployments/summary", project_id),
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_like_wildcards() {
+        assert_eq!(escape_like("pk_live_abc"), "pk\\_live\\_abc");
+        assert_eq!(escape_like("100%"), "100\\%");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+        assert_eq!(escape_like("accounts.example.com"), "accounts.example.com");
+    }
+}
@@ -1,3 +1,5 @@
+use sqlx::PgPool;
+
 use crate::{error::AppError, state::AppState};
 
 #[derive(Debug, thiserror::Error)]
@@ -23,27 +25,121 @@ impl From<QueryError> for AppError {
 pub trait Query {
     type Output;
 
-    fn execute(&self, app_state: &AppState) -> impl std::future::Future<Output = Result<Self::Output, AppError>> + Send;
+    /// Set by read-only queries that can tolerate replica lag, such as list
+    /// endpoints. They read through [`Query::pool`] instead of `db_pool`.
+    const PREFERS_READ_REPLICA: bool = false;
+
+    fn run(&self, app_state: &AppState) -> impl std::future::Future<Output = Result<Self::Output, AppError>> + Send;
+
+    /// The read replica for queries that prefer it, unless none is configured
+    /// or the query runs inside a command. Everything else gets the primary.
+    fn pool<'a>(&self, app_state: &'a AppState) -> &'a PgPool {
+        match &app_state.read_pool {
+            Some(read_pool) if Self::PREFERS_READ_REPLICA && !crate::commands::in_command() => {
+                read_pool
+            }
+            _ => &app_state.db_pool,
+        }
+    }
+
+    /// Runs the query, recording its execution count, errors and latency.
+    fn execute(&self, app_state: &AppState) -> impl std::future::Future<Output = Result<Self::Output, AppError>> + Send {
+        crate::metrics::observe_query::<Self, _>(self.run(app_state))
+    }
 }
 
 pub mod b2b;
+pub mod background_job;
+pub mod client_config;
 pub mod deployment;
+pub mod deployment_api_key;
+pub mod deployment_config;
+pub mod deployment_data_export;
+pub mod deployment_domain_migration;
+pub mod deployment_email_provider;
+pub mod deployment_log_export;
+pub mod deployment_feature_flags;
+pub mod deployment_jwt_template;
+pub mod deployment_mail_settings;
+pub mod deployment_provisioning;
+pub mod disposable_domain;
+pub mod email_outbox;
+pub mod global_search;
+pub mod organization_audit_event;
+pub mod organization_invitation;
+pub mod organization_member;
+pub mod organization_role_impact;
+pub mod password_policy;
+pub mod phone_intelligence;
 pub mod project;
+pub mod project_collaborator;
+pub mod rate_limit;
+pub mod request_log;
+pub mod scim;
+pub mod sign_in_lockout;
+pub mod sso_connection;
+pub mod token_revocation;
+pub mod usage;
 pub mod user;
+pub mod user_authorization;
+pub mod username;
+pub mod waitlist;
+pub mod workspace_member;
 
 // AI-related queries
 pub mod ai_agent;
+pub mod ai_agent_session;
+pub mod ai_usage;
 pub mod ai_knowledge_base;
 pub mod ai_tool;
 pub mod ai_workflow;
+pub mod ai_workflow_run;
+pub mod ai_workflow_trigger;
 
 pub use b2b::*;
+pub use background_job::*;
+pub use client_config::*;
 pub use deployment::*;
+pub use deployment_api_key::*;
+pub use deployment_config::*;
+pub use deployment_data_export::*;
+pub use deployment_domain_migration::*;
+pub use deployment_email_provider::*;
+pub use deployment_log_export::*;
+pub use deployment_feature_flags::*;
+pub use deployment_jwt_template::*;
+pub use deployment_mail_settings::*;
+pub use deployment_provisioning::*;
+pub use disposable_domain::*;
+pub use email_outbox::*;
+pub use global_search::*;
+pub use organization_audit_event::*;
+pub use organization_invitation::*;
+pub use organization_member::*;
+pub use organization_role_impact::*;
+pub use password_policy::*;
+pub use phone_intelligence::*;
 pub use project::*;
+pub use project_collaborator::*;
+pub use rate_limit::*;
+pub use request_log::*;
+pub use scim::*;
+pub use sign_in_lockout::*;
+pub use sso_connection::*;
+pub use token_revocation::*;
+pub use usage::*;
 pub use user::*;
+pub use user_authorization::*;
+pub use username::*;
+pub use waitlist::*;
+pub use workspace_member::*;
 
 // AI-related exports
 pub use ai_agent::*;
+pub use ai_agent_session::*;
+pub use ai_usage::*;
 pub use ai_knowledge_base::*;
 pub use ai_tool::*;
 pub use ai_workflow::*;
+pub use ai_workflow_run::*;
+pub use ai_workflow_trigger::*;
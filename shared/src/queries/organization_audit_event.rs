@@ -0,0 +1,175 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{OrganizationAuditEvent, OrganizationAuditEventType},
+    state::AppState,
+};
+
+use super::Query;
+
+/// Whether the user holds the deployment's organization creator role in the
+/// organization, which is what makes them one of its admins.
+async fn is_organization_admin(
+    pool: &sqlx::PgPool,
+    deployment_id: i64,
+    organization_id: i64,
+    user_id: i64,
+) -> Result<bool, AppError> {
+    let is_admin: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM organization_membership_roles omr
+            JOIN organization_memberships m ON m.id = omr.organization_membership_id
+            JOIN organizations o ON o.id = m.organization_id
+            JOIN users u ON u.id = m.user_id
+            JOIN deployment_b2b_settings s
+                ON s.deployment_id = o.deployment_id AND s.deleted_at IS NULL
+            WHERE o.deployment_id = $1
+                AND o.id = $2
+                AND m.user_id = $3
+                AND omr.organization_role_id = s.default_org_creator_role_id
+                AND o.deleted_at IS NULL
+                AND m.deleted_at IS NULL
+                AND u.deleted_at IS NULL
+        )
+        "#,
+    )
+    .bind(deployment_id)
+    .bind(organization_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(is_admin)
+}
+
+/// An organization's audit events, newest first, for one of its admins.
+/// Anyone else gets `organization_admin_required`, including for
+/// organizations that don't exist.
+pub struct ListOrganizationAuditEventsQuery {
+    deployment_id: i64,
+    organization_id: i64,
+    admin_user_id: i64,
+    actor: Option<String>,
+    event_type: Option<OrganizationAuditEventType>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    offset: i64,
+    limit: i64,
+}
+
+impl ListOrganizationAuditEventsQuery {
+    pub fn new(deployment_id: i64, organization_id: i64, admin_user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            admin_user_id,
+            actor: None,
+            event_type: None,
+            from: None,
+            to: None,
+            offset: 0,
+            limit: 10,
+        }
+    }
+
+    pub fn actor(self, actor: Option<String>) -> Self {
+        Self { actor, ..self }
+    }
+
+    pub fn event_type(self, event_type: Option<OrganizationAuditEventType>) -> Self {
+        Self { event_type, ..self }
+    }
+
+    /// Only events at or after this time.
+    pub fn from(self, from: Option<DateTime<Utc>>) -> Self {
+        Self { from, ..self }
+    }
+
+    /// Only events before this time.
+    pub fn to(self, to: Option<DateTime<Utc>>) -> Self {
+        Self { to, ..self }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl Query for ListOrganizationAuditEventsQuery {
+    type Output = Vec<OrganizationAuditEvent>;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let pool = self.pool(app_state);
+
+        if !is_organization_admin(
+            pool,
+            self.deployment_id,
+            self.organization_id,
+            self.admin_user_id,
+        )
+        .await?
+        {
+            return Err(AppError::coded(
+                ErrorCode::OrganizationAdminRequired,
+                "Only the organization's admins can see its audit events",
+            ));
+        }
+
+        let mut query_builder = sqlx::QueryBuilder::new(
+            r#"
+            SELECT id, created_at, organization_id, workspace_id, actor, event_type, payload
+            FROM organization_audit_events
+            WHERE organization_id =
+            "#,
+        );
+        query_builder.push_bind(self.organization_id);
+        query_builder.push(" AND deployment_id = ");
+        query_builder.push_bind(self.deployment_id);
+
+        if let Some(actor) = &self.actor {
+            query_builder.push(" AND actor = ");
+            query_builder.push_bind(actor);
+        }
+        if let Some(event_type) = self.event_type {
+            query_builder.push(" AND event_type = ");
+            query_builder.push_bind(event_type.as_str());
+        }
+        if let Some(from) = self.from {
+            query_builder.push(" AND created_at >= ");
+            query_builder.push_bind(from);
+        }
+        if let Some(to) = self.to {
+            query_builder.push(" AND created_at < ");
+            query_builder.push_bind(to);
+        }
+
+        query_builder.push(" ORDER BY created_at DESC, id DESC LIMIT ");
+        query_builder.push_bind(self.limit);
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(self.offset);
+
+        let rows = query_builder.build().fetch_all(pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| OrganizationAuditEvent {
+                id: row.get("id"),
+                created_at: row.get("created_at"),
+                organization_id: row.get("organization_id"),
+                workspace_id: row.get("workspace_id"),
+                actor: row.get("actor"),
+                event_type: row.get("event_type"),
+                payload: row.get("payload"),
+            })
+            .collect())
+    }
+}
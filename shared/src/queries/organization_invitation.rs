@@ -0,0 +1,90 @@
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{error::AppError, models::OrganizationInvitation, queries::Query, state::AppState};
+
+pub(crate) const ORGANIZATION_INVITATION_COLUMNS: &str = r#"
+    id, created_at, updated_at, deployment_id, organization_id, email_address,
+    organization_role_id, inviter_id, expiry, accepted_at, accepted_user_id, revoked_at
+"#;
+
+pub(crate) fn organization_invitation_from_row(row: &PgRow) -> OrganizationInvitation {
+    OrganizationInvitation {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        organization_id: row.get("organization_id"),
+        email_address: row.get("email_address"),
+        role_id: row.get("organization_role_id"),
+        inviter_id: row.get("inviter_id"),
+        expiry: row.get("expiry"),
+        accepted_at: row.get("accepted_at"),
+        accepted_user_id: row.get("accepted_user_id"),
+        revoked_at: row.get("revoked_at"),
+    }
+}
+
+pub struct ListOrganizationInvitationsQuery {
+    deployment_id: i64,
+    organization_id: i64,
+    pending: bool,
+    offset: i64,
+    limit: i64,
+}
+
+impl ListOrganizationInvitationsQuery {
+    pub fn new(deployment_id: i64, organization_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            pending: false,
+            offset: 0,
+            limit: 10,
+        }
+    }
+
+    /// Leaves out accepted, revoked and expired invitations.
+    pub fn pending(self, pending: bool) -> Self {
+        Self { pending, ..self }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl Query for ListOrganizationInvitationsQuery {
+    type Output = Vec<OrganizationInvitation>;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut query_builder = sqlx::QueryBuilder::new(format!(
+            "SELECT {} FROM organization_invitations WHERE organization_id = ",
+            ORGANIZATION_INVITATION_COLUMNS
+        ));
+        query_builder.push_bind(self.organization_id);
+        query_builder.push(" AND deployment_id = ");
+        query_builder.push_bind(self.deployment_id);
+
+        if self.pending {
+            query_builder
+                .push(" AND accepted_at IS NULL AND revoked_at IS NULL AND expiry > NOW()");
+        }
+
+        query_builder.push(" ORDER BY created_at DESC, id LIMIT ");
+        query_builder.push_bind(self.limit);
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(self.offset);
+
+        let rows = query_builder
+            .build()
+            .fetch_all(self.pool(app_state))
+            .await?;
+
+        Ok(rows.iter().map(organization_invitation_from_row).collect())
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use sqlx::{PgConnection, Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{OrganizationMemberDetails, OrganizationPermission, OrganizationRole},
+    queries::Query,
+    state::AppState,
+};
+
+const ORGANIZATION_MEMBER_SELECT: &str = r#"
+    SELECT
+        om.id, om.created_at, om.updated_at, om.organization_id, om.user_id,
+        u.first_name, u.last_name, u.username, u.created_at AS user_created_at,
+        e.email_address AS primary_email_address,
+        p.phone_number AS primary_phone_number
+    FROM organization_memberships om
+    JOIN organizations o ON o.id = om.organization_id AND o.deleted_at IS NULL
+    JOIN users u ON u.id = om.user_id AND u.deleted_at IS NULL
+    LEFT JOIN user_email_addresses e ON e.id = u.primary_email_address_id
+    LEFT JOIN user_phone_numbers p ON p.id = u.primary_phone_number_id
+"#;
+
+fn organization_member_from_row(row: &PgRow) -> OrganizationMemberDetails {
+    let username = row
+        .get::<Option<String>, _>("username")
+        .filter(|username| !username.is_empty());
+
+    OrganizationMemberDetails {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        organization_id: row.get("organization_id"),
+        user_id: row.get("user_id"),
+        roles: vec![],
+        first_name: row
+            .get::<Option<String>, _>("first_name")
+            .unwrap_or_default(),
+        last_name: row
+            .get::<Option<String>, _>("last_name")
+            .unwrap_or_default(),
+        username,
+        primary_email_address: row.get("primary_email_address"),
+        primary_phone_number: row.get("primary_phone_number"),
+        user_created_at: row.get("user_created_at"),
+    }
+}
+
+/// Fills in each member's roles with a single query over all of them.
+async fn attach_roles(
+    conn: &mut PgConnection,
+    members: &mut [OrganizationMemberDetails],
+) -> Result<(), AppError> {
+    let membership_ids: Vec<i64> = members.iter().map(|member| member.id).collect();
+
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            omr.organization_membership_id,
+            r.id, r.created_at, r.updated_at, r.name, r.permissions
+        FROM organization_membership_roles omr
+        JOIN organization_roles r ON r.id = omr.organization_role_id
+        WHERE omr.organization_membership_id = ANY($1)
+        ORDER BY r.name
+        "#,
+    )
+    .bind(&membership_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut roles_by_membership: HashMap<i64, Vec<OrganizationRole>> = HashMap::new();
+
+    for row in rows {
+        let id: i64 = row.get("id");
+        let created_at = row.get("created_at");
+        let updated_at = row.get("updated_at");
+        let permissions: Vec<String> = row.get("permissions");
+
+        roles_by_membership
+            .entry(row.get("organization_membership_id"))
+            .or_default()
+            .push(OrganizationRole {
+                id,
+                created_at,
+                updated_at,
+                name: row.get("name"),
+                permissions: permissions
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, permission)| OrganizationPermission {
+                        id: i as i64,
+                        created_at,
+                        updated_at,
+                        org_role_id: id,
+                        permission,
+                    })
+                    .collect(),
+            });
+    }
+
+    for member in members.iter_mut() {
+        member.roles = roles_by_membership.remove(&member.id).unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+/// Loads one membership with its user details and roles, scoped to the
+/// deployment. Commands call this on their transaction so they return what
+/// they just wrote.
+pub(crate) async fn fetch_organization_member(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    organization_id: i64,
+    membership_id: i64,
+) -> Result<Option<OrganizationMemberDetails>, AppError> {
+    let row = sqlx::query(&format!(
+        "{} WHERE om.id = $1 AND om.organization_id = $2 AND o.deployment_id = $3",
+        ORGANIZATION_MEMBER_SELECT
+    ))
+    .bind(membership_id)
+    .bind(organization_id)
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let mut members = [organization_member_from_row(&row)];
+    attach_roles(conn, &mut members).await?;
+
+    let [member] = members;
+    Ok(Some(member))
+}
+
+pub struct ListOrganizationMembersQuery {
+    deployment_id: i64,
+    organization_id: i64,
+    role_id: Option<i64>,
+    search: Option<String>,
+    offset: i64,
+    limit: i64,
+}
+
+impl ListOrganizationMembersQuery {
+    pub fn new(deployment_id: i64, organization_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+            role_id: None,
+            search: None,
+            offset: 0,
+            limit: 10,
+        }
+    }
+
+    pub fn role_id(self, role_id: Option<i64>) -> Self {
+        Self { role_id, ..self }
+    }
+
+    /// Matches against first and last name, username and primary email.
+    pub fn search(self, search: Option<String>) -> Self {
+        Self { search, ..self }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl Query for ListOrganizationMembersQuery {
+    type Output = Vec<OrganizationMemberDetails>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut query_builder = sqlx::QueryBuilder::new(ORGANIZATION_MEMBER_SELECT);
+        query_builder.push(" WHERE om.organization_id = ");
+        query_builder.push_bind(self.organization_id);
+        query_builder.push(" AND o.deployment_id = ");
+        query_builder.push_bind(self.deployment_id);
+
+        if let Some(role_id) = self.role_id {
+            query_builder.push(
+                " AND EXISTS (SELECT 1 FROM organization_membership_roles omr \
+                 WHERE omr.organization_membership_id = om.id AND omr.organization_role_id = ",
+            );
+            query_builder.push_bind(role_id);
+            query_builder.push(")");
+        }
+
+        if let Some(search) = self
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|search| !search.is_empty())
+        {
+            let pattern = format!("%{}%", search);
+            query_builder.push(" AND (u.first_name ILIKE ");
+            query_builder.push_bind(pattern.clone());
+            query_builder.push(" OR u.last_name ILIKE ");
+            query_builder.push_bind(pattern.clone());
+            query_builder.push(" OR concat_ws(' ', u.first_name, u.last_name) ILIKE ");
+            query_builder.push_bind(pattern.clone());
+            query_builder.push(" OR u.username ILIKE ");
+            query_builder.push_bind(pattern.clone());
+            query_builder.push(" OR e.email_address ILIKE ");
+            query_builder.push_bind(pattern);
+            query_builder.push(")");
+        }
+
+        query_builder.push(" ORDER BY om.created_at DESC, om.id LIMIT ");
+        query_builder.push_bind(self.limit);
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(self.offset);
+
+        let mut conn = app_state.db_pool.acquire().await?;
+
+        let rows = query_builder.build().fetch_all(&mut *conn).await?;
+        let mut members: Vec<OrganizationMemberDetails> =
+            rows.iter().map(organization_member_from_row).collect();
+
+        attach_roles(&mut conn, &mut members).await?;
+
+        Ok(members)
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeSet;
+
+use sqlx::{PgConnection, Row};
+
+use crate::{
+    commands::normalize_permissions,
+    error::AppError,
+    models::{ORGANIZATION_PERMISSIONS, RolePermissionChangeReport, RolePermissionImpact},
+    state::AppState,
+};
+
+use super::Query;
+
+/// How many affected members a report lists. The count covers all of them.
+pub const MAX_LISTED_AFFECTED_MEMBERS: usize = 100;
+
+/// What a member gains and loses when a role of theirs goes from `current`
+/// to `proposed`, given what their other roles grant.
+fn permission_delta(
+    current: &[String],
+    proposed: &[String],
+    granted_elsewhere: &BTreeSet<String>,
+) -> (Vec<String>, Vec<String>) {
+    let only_in = |from: &[String], other: &[String]| -> Vec<String> {
+        from.iter()
+            .filter(|permission| !other.contains(permission))
+            .filter(|permission| !granted_elsewhere.contains(*permission))
+            .cloned()
+            .collect()
+    };
+    (only_in(proposed, current), only_in(current, proposed))
+}
+
+/// The role's permissions, or `None` when it isn't one of the deployment's
+/// roles with this scope.
+pub(crate) async fn fetch_organization_role_permissions(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    organization_id: Option<i64>,
+    role_id: i64,
+) -> Result<Option<Vec<String>>, AppError> {
+    let permissions = sqlx::query_scalar(
+        r#"
+        SELECT permissions FROM organization_roles
+        WHERE id = $1 AND deployment_id = $2 AND organization_id IS NOT DISTINCT FROM $3
+        "#,
+    )
+    .bind(role_id)
+    .bind(deployment_id)
+    .bind(organization_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(permissions)
+}
+
+/// Everyone holding an organization role, and how changing its permissions
+/// from `current` to `proposed` changes what each of them can do.
+pub(crate) struct RolePermissionChange {
+    pub added_permissions: Vec<String>,
+    pub removed_permissions: Vec<String>,
+    /// Every user holding the role, whether or not their permissions change.
+    pub holder_user_ids: Vec<i64>,
+    pub affected_members: Vec<RolePermissionImpact>,
+}
+
+impl RolePermissionChange {
+    pub(crate) async fn load(
+        conn: &mut PgConnection,
+        deployment_id: i64,
+        role_id: i64,
+        current: &[String],
+        proposed: &[String],
+    ) -> Result<Self, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                m.id AS membership_id,
+                m.organization_id,
+                m.user_id,
+                e.email_address,
+                COALESCE((
+                    SELECT array_agg(DISTINCT permission)
+                    FROM organization_membership_roles other
+                    JOIN organization_roles r ON r.id = other.organization_role_id
+                    CROSS JOIN LATERAL unnest(r.permissions) AS permission
+                    WHERE other.organization_membership_id = m.id
+                        AND other.organization_role_id <> $2
+                ), '{}') AS other_permissions
+            FROM organization_membership_roles omr
+            JOIN organization_memberships m ON m.id = omr.organization_membership_id
+            JOIN organizations o ON o.id = m.organization_id
+            JOIN users u ON u.id = m.user_id
+            LEFT JOIN user_email_addresses e ON e.id = u.primary_email_address_id
+            WHERE o.deployment_id = $1
+                AND omr.organization_role_id = $2
+                AND o.deleted_at IS NULL
+                AND m.deleted_at IS NULL
+                AND u.deleted_at IS NULL
+            ORDER BY m.organization_id, m.user_id
+            "#,
+        )
+        .bind(deployment_id)
+        .bind(role_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let (added_permissions, removed_permissions) =
+            permission_delta(current, proposed, &BTreeSet::new());
+
+        let mut holder_user_ids = BTreeSet::new();
+        let mut affected_members = Vec::new();
+        for row in rows {
+            let user_id: i64 = row.get("user_id");
+            holder_user_ids.insert(user_id);
+
+            let granted_elsewhere: BTreeSet<String> = row
+                .get::<Vec<String>, _>("other_permissions")
+                .into_iter()
+                .collect();
+            let (gained_permissions, lost_permissions) =
+                permission_delta(current, proposed, &granted_elsewhere);
+            if gained_permissions.is_empty() && lost_permissions.is_empty() {
+                continue;
+            }
+
+            affected_members.push(RolePermissionImpact {
+                membership_id: row.get("membership_id"),
+                organization_id: row.get("organization_id"),
+                user_id,
+                email_address: row.get("email_address"),
+                gained_permissions,
+                lost_permissions,
+            });
+        }
+
+        Ok(Self {
+            added_permissions,
+            removed_permissions,
+            holder_user_ids: holder_user_ids.into_iter().collect(),
+            affected_members,
+        })
+    }
+
+    pub(crate) fn affected_member_count(&self) -> i64 {
+        self.affected_members.len() as i64
+    }
+
+    pub(crate) fn listed_affected_members(&self) -> Vec<RolePermissionImpact> {
+        self.affected_members
+            .iter()
+            .take(MAX_LISTED_AFFECTED_MEMBERS)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Reports which members would gain or lose permissions if an organization
+/// role's permissions were replaced with `permissions`, without saving
+/// anything, so the change can be reviewed first.
+pub struct SimulatePermissionChangeQuery {
+    deployment_id: i64,
+    organization_id: Option<i64>,
+    role_id: i64,
+    permissions: Vec<String>,
+}
+
+impl SimulatePermissionChangeQuery {
+    pub fn new(deployment_id: i64, role_id: i64, permissions: Vec<String>) -> Self {
+        Self {
+            deployment_id,
+            organization_id: None,
+            role_id,
+            permissions,
+        }
+    }
+
+    pub fn with_organization_id(mut self, organization_id: i64) -> Self {
+        self.organization_id = Some(organization_id);
+        self
+    }
+}
+
+impl Query for SimulatePermissionChangeQuery {
+    type Output = RolePermissionChangeReport;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let proposed = normalize_permissions(self.permissions.clone(), ORGANIZATION_PERMISSIONS)?;
+
+        let mut conn = app_state.db_pool.acquire().await?;
+
+        let current = fetch_organization_role_permissions(
+            &mut conn,
+            self.deployment_id,
+            self.organization_id,
+            self.role_id,
+        )
+        .await?
+        .ok_or_else(|| AppError::NotFound("Organization role not found".to_string()))?;
+
+        let change = RolePermissionChange::load(
+            &mut conn,
+            self.deployment_id,
+            self.role_id,
+            &current,
+            &proposed,
+        )
+        .await?;
+
+        Ok(RolePermissionChangeReport {
+            role_id: self.role_id,
+            affected_member_count: change.affected_member_count(),
+            affected_members: change.listed_affected_members(),
+            added_permissions: change.added_permissions,
+            removed_permissions: change.removed_permissions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permissions(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn members_only_gain_and_lose_what_other_roles_dont_grant() {
+        let current = permissions(&["org:read", "org:update", "org:delete"]);
+        let proposed = permissions(&["org:read", "org:members:read", "org:billing:read"]);
+
+        assert_eq!(
+            permission_delta(&current, &proposed, &BTreeSet::new()),
+            (
+                permissions(&["org:members:read", "org:billing:read"]),
+                permissions(&["org:update", "org:delete"]),
+            )
+        );
+
+        let granted_elsewhere = permissions(&["org:billing:read", "org:delete"])
+            .into_iter()
+            .collect();
+        assert_eq!(
+            permission_delta(&current, &proposed, &granted_elsewhere),
+            (
+                permissions(&["org:members:read"]),
+                permissions(&["org:update"]),
+            )
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    error::AppError,
+    models::PasswordSettings,
+    queries::{GetDeploymentAuthSettingsQuery, Query},
+    state::AppState,
+    utils::validation::{UserValidator, ValidationError, validation_failed},
+};
+
+/// Checks a new password against the deployment's whole password policy,
+/// including the breach check, and returns every rule it breaks.
+pub struct ValidatePasswordQuery {
+    deployment_id: i64,
+    password: String,
+}
+
+impl ValidatePasswordQuery {
+    pub fn new(deployment_id: i64, password: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            password: password.into(),
+        }
+    }
+
+    /// Runs the check and turns any broken rules into a `validation_failed`
+    /// error listing them.
+    pub async fn ensure_valid(&self, app_state: &AppState) -> Result<(), AppError> {
+        let errors = self.execute(app_state).await?;
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(validation_failed(errors))
+        }
+    }
+}
+
+impl Query for ValidatePasswordQuery {
+    type Output = Vec<ValidationError>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let auth_settings = GetDeploymentAuthSettingsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+
+        if !auth_settings.password.enabled {
+            return Err(AppError::BadRequest(
+                "Password authentication is disabled for this deployment".to_string(),
+            ));
+        }
+
+        let mut errors =
+            UserValidator::validate_password_policy(&self.password, &auth_settings.password);
+
+        // No point asking about breaches for a password that's already rejected.
+        if errors.is_empty() {
+            errors.extend(
+                CheckCompromisedPasswordQuery::new(
+                    self.deployment_id,
+                    &self.password,
+                    &auth_settings.password,
+                )
+                .execute(app_state)
+                .await?,
+            );
+        }
+
+        Ok(errors)
+    }
+}
+
+/// Checks a password against known breaches when the deployment has
+/// `disallow_compromised` enabled. If the breach lookup fails, the failure is
+/// logged and the password is accepted.
+pub struct CheckCompromisedPasswordQuery {
+    deployment_id: i64,
+    password: String,
+    disallow_compromised: bool,
+}
+
+impl CheckCompromisedPasswordQuery {
+    pub fn new(
+        deployment_id: i64,
+        password: impl Into<String>,
+        settings: &PasswordSettings,
+    ) -> Self {
+        Self {
+            deployment_id,
+            password: password.into(),
+            disallow_compromised: settings.disallow_compromised.unwrap_or(false),
+        }
+    }
+}
+
+impl Query for CheckCompromisedPasswordQuery {
+    type Output = Option<ValidationError>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        if !self.disallow_compromised {
+            return Ok(None);
+        }
+
+        match app_state
+            .compromised_password_service
+            .is_compromised(&self.password)
+            .await
+        {
+            Ok(true) => Ok(Some(ValidationError::rule(
+                "password",
+                "compromised",
+                "This password has appeared in a data breach, please choose a different one",
+            ))),
+            Ok(false) => Ok(None),
+            Err(e) => {
+                tracing::warn!(
+                    deployment_id = self.deployment_id,
+                    "Breached password lookup failed, allowing the password: {}",
+                    e
+                );
+                Ok(None)
+            }
+        }
+    }
+}
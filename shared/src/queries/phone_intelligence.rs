@@ -0,0 +1,88 @@
+use serde_json::json;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{PhoneLineType, PhoneNumberCheck},
+    queries::Query,
+    services::to_e164,
+    state::AppState,
+};
+
+/// Classifies a phone number when the deployment has `block_voip_numbers`
+/// enabled. Provider failures are logged and let the number through, since a
+/// lookup outage shouldn't stop users from signing up.
+pub struct CheckPhoneNumberAgainstRestrictionsQuery {
+    deployment_id: i64,
+    phone_number: String,
+}
+
+impl CheckPhoneNumberAgainstRestrictionsQuery {
+    pub fn new(deployment_id: i64, phone_number: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            phone_number: phone_number.into(),
+        }
+    }
+
+    /// Runs the check and turns a blocked number into a
+    /// `voip_number_not_allowed` error.
+    pub async fn ensure_allowed(&self, app_state: &AppState) -> Result<(), AppError> {
+        let check = self.execute(app_state).await?;
+        if !check.blocked {
+            return Ok(());
+        }
+
+        app_state.phone_intelligence_service.record_voip_rejection();
+
+        Err(AppError::coded(
+            ErrorCode::VoipNumberNotAllowed,
+            "VOIP phone numbers are not allowed in this deployment",
+        )
+        .with_details(json!({ "phone_number": check.phone_number })))
+    }
+}
+
+impl Query for CheckPhoneNumberAgainstRestrictionsQuery {
+    type Output = PhoneNumberCheck;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let phone_number = to_e164(&self.phone_number);
+
+        let block_voip_numbers: Option<bool> = sqlx::query_scalar(
+            "SELECT block_voip_numbers FROM deployment_restrictions WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        if !block_voip_numbers.unwrap_or(false) {
+            return Ok(PhoneNumberCheck {
+                phone_number,
+                line_type: None,
+                blocked: false,
+            });
+        }
+
+        let line_type = match app_state
+            .phone_intelligence_service
+            .lookup(&phone_number)
+            .await
+        {
+            Ok(lookup) => Some(lookup.line_type),
+            Err(e) => {
+                tracing::warn!(
+                    deployment_id = self.deployment_id,
+                    "Phone lookup failed, allowing the number: {}",
+                    e
+                );
+                None
+            }
+        };
+
+        Ok(PhoneNumberCheck {
+            phone_number,
+            blocked: line_type == Some(PhoneLineType::Voip),
+            line_type,
+        })
+    }
+}
@@ -1,22 +1,45 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
+use chrono::Utc;
+use redis::AsyncCommands;
 use sqlx::{Row, query};
 
 use crate::{
     error::AppError,
-    models::{Deployment, ProjectWithDeployments},
+    models::{
+        AccountDashboard, AuditLog, BillingPeriod, Deployment, DeploymentCountsByMode,
+        DeploymentMode, DeploymentSummary, ProjectWithDeployments, ProvisioningStatus,
+        VerificationStatus,
+    },
     state::AppState,
 };
 
 use super::Query;
 
+/// Projects with their deployments. Limited to the projects the console
+/// account collaborates on; an `account_id` of 0 lists every project.
 pub struct GetProjectsWithDeploymentQuery {
-    oid: i64,
+    account_id: i64,
+    verification_status: Option<VerificationStatus>,
 }
 
 impl GetProjectsWithDeploymentQuery {
-    pub fn new(oid: i64) -> Self {
-        GetProjectsWithDeploymentQuery { oid }
+    pub fn new(account_id: i64) -> Self {
+        GetProjectsWithDeploymentQuery {
+            account_id,
+            verification_status: None,
+        }
+    }
+
+    /// Only deployments with this status are included, and only projects
+    /// that have one.
+    pub fn with_verification_status(
+        mut self,
+        verification_status: Option<VerificationStatus>,
+    ) -> Self {
+        self.verification_status = verification_status;
+        self
     }
 }
 
@@ -54,7 +77,13 @@ impl GetProjectsWithDeploymentQuery {
             mail_from_host: row
                 .get::<Option<String>, _>("deployment_mail_from_host")
                 .unwrap_or_default(),
-            verification_status: None,
+            verification_status: row
+                .get::<Option<String>, _>("deployment_verification_status")
+                .map(VerificationStatus::from),
+            provisioning_status: row
+                .get::<Option<String>, _>("deployment_provisioning_status")
+                .map(ProvisioningStatus::from)
+                .unwrap_or_default(),
             domain_verification_records: row
                 .get::<Option<serde_json::Value>, _>("deployment_domain_verification_records")
                 .and_then(|v| serde_json::from_value(v).ok()),
@@ -67,7 +96,7 @@ impl GetProjectsWithDeploymentQuery {
 impl Query for GetProjectsWithDeploymentQuery {
     type Output = Vec<ProjectWithDeployments>;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let rows = query(
             r#"
             SELECT
@@ -79,13 +108,23 @@ impl Query for GetProjectsWithDeploymentQuery {
                 d.publishable_key as deployment_publishable_key,
                 d.project_id as deployment_project_id, d.mode as deployment_mode,
                 d.mail_from_host as deployment_mail_from_host,
+                d.provisioning_status as deployment_provisioning_status,
+                d.verification_status as deployment_verification_status,
                 d.domain_verification_records::jsonb as deployment_domain_verification_records,
                 d.email_verification_records::jsonb as deployment_email_verification_records
             FROM projects p
             LEFT JOIN deployments d ON p.id = d.project_id AND d.deleted_at IS NULL
+                AND ($2::text IS NULL OR d.verification_status = $2)
+            WHERE ($1 = 0 OR EXISTS (
+                    SELECT 1 FROM project_collaborators c
+                    WHERE c.project_id = p.id AND c.account_id = $1
+                ))
+                AND ($2::text IS NULL OR d.id IS NOT NULL)
             ORDER BY p.id DESC
             "#,
         )
+        .bind(self.account_id)
+        .bind(self.verification_status.as_ref().map(VerificationStatus::as_str))
         .fetch_all(&app_state.db_pool)
         .await?;
 
@@ -123,3 +162,360 @@ impl Query for GetProjectsWithDeploymentQuery {
         Ok(projects_map.values().cloned().collect())
     }
 }
+
+/// Monthly active users change slowly and take a ClickHouse scan to count,
+/// so they are cached for a while per deployment.
+const MONTHLY_ACTIVE_USERS_TTL_SECS: u64 = 15 * 60;
+
+fn monthly_active_users_key(deployment_id: i64) -> String {
+    format!("deployment:{}:monthly_active_users", deployment_id)
+}
+
+/// Usage figures for every deployment of a project, cheap enough for each
+/// console project page load. Counts come from one aggregate query; monthly
+/// active users come from the cache or, for the deployments missing there,
+/// one ClickHouse query.
+pub struct GetProjectDeploymentsSummaryQuery {
+    project_id: i64,
+}
+
+impl GetProjectDeploymentsSummaryQuery {
+    pub fn new(project_id: i64) -> Self {
+        Self { project_id }
+    }
+
+    async fn cached_monthly_active_users(
+        app_state: &AppState,
+        deployment_ids: &[i64],
+    ) -> Result<Vec<Option<i64>>, AppError> {
+        let keys: Vec<String> = deployment_ids
+            .iter()
+            .map(|&id| monthly_active_users_key(id))
+            .collect();
+        let mut connection = app_state.redis.connection().await?;
+        Ok(connection.mget(keys).await?)
+    }
+
+    async fn cache_monthly_active_users(app_state: &AppState, counts: &HashMap<i64, i64>) {
+        let result = async {
+            let mut connection = app_state.redis.connection().await?;
+            let mut pipe = redis::pipe();
+            for (&deployment_id, &count) in counts {
+                pipe.set_ex(
+                    monthly_active_users_key(deployment_id),
+                    count,
+                    MONTHLY_ACTIVE_USERS_TTL_SECS,
+                )
+                .ignore();
+            }
+            pipe.query_async::<()>(&mut connection).await?;
+            Ok::<_, AppError>(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to cache monthly active users: {}", e);
+        }
+    }
+
+    /// `None` for deployments whose figure isn't cached and couldn't be
+    /// fetched from ClickHouse.
+    async fn monthly_active_users(
+        app_state: &AppState,
+        deployment_ids: &[i64],
+    ) -> HashMap<i64, i64> {
+        let cached = Self::cached_monthly_active_users(app_state, deployment_ids)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to read cached monthly active users: {}", e);
+                vec![None; deployment_ids.len()]
+            });
+
+        let mut counts = HashMap::new();
+        let mut missing = Vec::new();
+        for (&deployment_id, count) in deployment_ids.iter().zip(cached) {
+            match count {
+                Some(count) => {
+                    counts.insert(deployment_id, count);
+                }
+                None => missing.push(deployment_id),
+            }
+        }
+        if missing.is_empty() {
+            return counts;
+        }
+
+        match app_state
+            .clickhouse_service
+            .get_monthly_active_users(&missing)
+            .await
+        {
+            Ok(rows) => {
+                let mut fetched: HashMap<i64, i64> = missing.iter().map(|&id| (id, 0)).collect();
+                fetched.extend(rows);
+                Self::cache_monthly_active_users(app_state, &fetched).await;
+                counts.extend(fetched);
+            }
+            Err(e) => tracing::warn!("Failed to count monthly active users: {}", e),
+        }
+
+        counts
+    }
+}
+
+impl Query for GetProjectDeploymentsSummaryQuery {
+    type Output = Vec<DeploymentSummary>;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let project_exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM projects WHERE id = $1 AND deleted_at IS NULL)",
+        )
+        .bind(self.project_id)
+        .fetch_one(self.pool(app_state))
+        .await?;
+        if !project_exists {
+            return Err(AppError::NotFound("Project not found".to_string()));
+        }
+
+        let rows = query(
+            r#"
+            WITH project_deployments AS (
+                SELECT id, mode, frontend_host, maintenance_mode, verification_status
+                FROM deployments
+                WHERE project_id = $1 AND deleted_at IS NULL
+            ),
+            user_counts AS (
+                SELECT deployment_id, COUNT(*) AS count
+                FROM users
+                WHERE deployment_id IN (SELECT id FROM project_deployments)
+                    AND deleted_at IS NULL
+                GROUP BY deployment_id
+            ),
+            organization_counts AS (
+                SELECT deployment_id, COUNT(*) AS count
+                FROM organizations
+                WHERE deployment_id IN (SELECT id FROM project_deployments)
+                    AND deleted_at IS NULL
+                GROUP BY deployment_id
+            )
+            SELECT
+                d.id, d.mode, d.frontend_host, d.maintenance_mode, d.verification_status,
+                COALESCE(u.count, 0) AS user_count,
+                COALESCE(o.count, 0) AS organization_count
+            FROM project_deployments d
+            LEFT JOIN user_counts u ON u.deployment_id = d.id
+            LEFT JOIN organization_counts o ON o.deployment_id = d.id
+            ORDER BY d.id
+            "#,
+        )
+        .bind(self.project_id)
+        .fetch_all(self.pool(app_state))
+        .await?;
+
+        let deployment_ids: Vec<i64> = rows.iter().map(|row| row.get("id")).collect();
+        let monthly_active_users = if deployment_ids.is_empty() {
+            HashMap::new()
+        } else {
+            Self::monthly_active_users(app_state, &deployment_ids).await
+        };
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let deployment_id: i64 = row.get("id");
+                DeploymentSummary {
+                    deployment_id,
+                    mode: DeploymentMode::from(row.get::<String, _>("mode")),
+                    frontend_host: row.get("frontend_host"),
+                    user_count: row.get("user_count"),
+                    organization_count: row.get("organization_count"),
+                    monthly_active_users: monthly_active_users.get(&deployment_id).copied(),
+                    verification_status: row
+                        .get::<Option<String>, _>("verification_status")
+                        .map(VerificationStatus::from),
+                    maintenance_mode: row.get("maintenance_mode"),
+                }
+            })
+            .collect())
+    }
+}
+
+const ACCOUNT_DASHBOARD_TTL: Duration = Duration::from_secs(30);
+const RECENT_AUDIT_LOG_LIMIT: i64 = 5;
+
+fn account_dashboard_key(account_id: i64) -> String {
+    format!("console_account:{}:dashboard", account_id)
+}
+
+/// The console home page overview for a console account, over every project
+/// it collaborates on. It is read in three aggregate queries and kept for
+/// half a minute per account, so reloading the page doesn't recount.
+pub struct GetAccountDashboardQuery {
+    account_id: i64,
+}
+
+impl GetAccountDashboardQuery {
+    pub fn new(account_id: i64) -> Self {
+        Self { account_id }
+    }
+
+    /// Active users this billing period from the usage metering tables: the
+    /// frozen period rows of deployments whose period is closed, the metered
+    /// usage events of the others.
+    async fn monthly_active_users(
+        &self,
+        app_state: &AppState,
+        deployment_ids: &[i64],
+    ) -> Result<i64, AppError> {
+        if deployment_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let period = BillingPeriod::containing(Utc::now());
+        let closed = query(
+            r#"
+            SELECT deployment_id, active_users
+            FROM deployment_usage_periods
+            WHERE deployment_id = ANY($1) AND period_start = $2
+            "#,
+        )
+        .bind(deployment_ids)
+        .bind(period.start)
+        .fetch_all(self.pool(app_state))
+        .await?;
+
+        let mut active_users = 0;
+        let mut closed_ids = Vec::with_capacity(closed.len());
+        for row in closed {
+            active_users += row.get::<i64, _>("active_users");
+            closed_ids.push(row.get::<i64, _>("deployment_id"));
+        }
+
+        let open: Vec<i64> = deployment_ids
+            .iter()
+            .copied()
+            .filter(|id| !closed_ids.contains(id))
+            .collect();
+
+        if !open.is_empty() {
+            active_users += app_state
+                .clickhouse_service
+                .get_active_users(&open, period.starts_at(), Utc::now())
+                .await?;
+        }
+
+        Ok(active_users)
+    }
+
+    async fn compute(&self, app_state: &AppState) -> Result<AccountDashboard, AppError> {
+        let counts = query(
+            r#"
+            WITH account_projects AS (
+                SELECT p.id
+                FROM projects p
+                JOIN project_collaborators c ON c.project_id = p.id
+                WHERE c.account_id = $1 AND p.deleted_at IS NULL
+            ),
+            account_deployments AS (
+                SELECT id, mode, verification_status, maintenance_mode
+                FROM deployments
+                WHERE project_id IN (SELECT id FROM account_projects) AND deleted_at IS NULL
+            )
+            SELECT
+                (SELECT COUNT(*) FROM account_projects) AS project_count,
+                COUNT(*) FILTER (WHERE mode = 'production') AS production_count,
+                COUNT(*) FILTER (WHERE mode = 'staging') AS staging_count,
+                COUNT(*) FILTER (
+                    WHERE verification_status IN ('pending', 'in_progress')
+                ) AS pending_verification_count,
+                COUNT(*) FILTER (WHERE verification_status = 'failed') AS failed_verification_count,
+                COUNT(*) FILTER (WHERE maintenance_mode) AS maintenance_mode_count,
+                COALESCE(ARRAY_AGG(id) FILTER (WHERE id IS NOT NULL), '{}') AS deployment_ids
+            FROM account_deployments
+            "#,
+        )
+        .bind(self.account_id)
+        .fetch_one(self.pool(app_state))
+        .await?;
+
+        let recent_audit_logs = query(
+            r#"
+            SELECT l.id, l.created_at, l.project_id, l.deployment_id, l.actor, l.action,
+                   l.resource_type, l.resource_id, l.metadata
+            FROM console_audit_logs l
+            JOIN project_collaborators c ON c.project_id = l.project_id
+            WHERE c.account_id = $1
+            ORDER BY l.created_at DESC, l.id DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(self.account_id)
+        .bind(RECENT_AUDIT_LOG_LIMIT)
+        .fetch_all(self.pool(app_state))
+        .await?
+        .into_iter()
+        .map(|row| AuditLog {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            project_id: row.get("project_id"),
+            deployment_id: row.get("deployment_id"),
+            actor: row.get("actor"),
+            action: row.get("action"),
+            resource_type: row.get("resource_type"),
+            resource_id: row.get("resource_id"),
+            metadata: row.get("metadata"),
+        })
+        .collect();
+
+        let deployment_ids: Vec<i64> = counts.get("deployment_ids");
+        let monthly_active_users = match self.monthly_active_users(app_state, &deployment_ids).await
+        {
+            Ok(active_users) => Some(active_users),
+            Err(e) => {
+                tracing::warn!("Failed to read usage for the account dashboard: {}", e);
+                None
+            }
+        };
+
+        Ok(AccountDashboard {
+            project_count: counts.get("project_count"),
+            deployment_counts: DeploymentCountsByMode {
+                production: counts.get("production_count"),
+                staging: counts.get("staging_count"),
+            },
+            pending_verification_count: counts.get("pending_verification_count"),
+            failed_verification_count: counts.get("failed_verification_count"),
+            maintenance_mode_count: counts.get("maintenance_mode_count"),
+            monthly_active_users,
+            recent_audit_logs,
+            generated_at: Utc::now(),
+        })
+    }
+}
+
+impl Query for GetAccountDashboardQuery {
+    type Output = AccountDashboard;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let key = account_dashboard_key(self.account_id);
+        match app_state.redis.get_json::<AccountDashboard>(&key).await {
+            Ok(Some(dashboard)) => return Ok(dashboard),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to read the cached account dashboard: {}", e),
+        }
+
+        let dashboard = self.compute(app_state).await?;
+
+        if let Err(e) = app_state
+            .redis
+            .set_json_with_ttl(&key, &dashboard, ACCOUNT_DASHBOARD_TTL)
+            .await
+        {
+            tracing::warn!("Failed to cache the account dashboard: {}", e);
+        }
+
+        Ok(dashboard)
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    commands::project_collaborator::{COLLABORATOR_COLUMNS, collaborator_from_row},
+    error::AppError,
+    models::{ProjectCollaborator, ProjectRole},
+    state::AppState,
+};
+
+use super::Query;
+
+/// Everyone with a role in the project, invitations included, oldest first.
+pub struct ListProjectCollaboratorsQuery {
+    project_id: i64,
+}
+
+impl ListProjectCollaboratorsQuery {
+    pub fn new(project_id: i64) -> Self {
+        Self { project_id }
+    }
+}
+
+impl Query for ListProjectCollaboratorsQuery {
+    type Output = Vec<ProjectCollaborator>;
+    const PREFERS_READ_REPLICA: bool = true;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let pool = self.pool(app_state);
+
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM projects WHERE id = $1 AND deleted_at IS NULL)",
+        )
+        .bind(self.project_id)
+        .fetch_one(pool)
+        .await?;
+        if !exists {
+            return Err(AppError::NotFound(format!(
+                "Project with id {} not found",
+                self.project_id
+            )));
+        }
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM project_collaborators c
+            LEFT JOIN console_accounts a ON a.id = c.account_id
+            WHERE c.project_id = $1
+            ORDER BY c.created_at, c.id
+            "#,
+            COLLABORATOR_COLUMNS
+        ))
+        .bind(self.project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.iter().map(collaborator_from_row).collect())
+    }
+}
+
+/// The role a console account has in a project, found either from the
+/// project or from one of its deployments. `None` when the account has no
+/// role there or the project doesn't exist.
+pub struct GetProjectRoleQuery {
+    project_id: Option<i64>,
+    deployment_id: Option<i64>,
+    account_id: i64,
+}
+
+impl GetProjectRoleQuery {
+    pub fn for_project(project_id: i64, account_id: i64) -> Self {
+        Self {
+            project_id: Some(project_id),
+            deployment_id: None,
+            account_id,
+        }
+    }
+
+    pub fn for_deployment(deployment_id: i64, account_id: i64) -> Self {
+        Self {
+            project_id: None,
+            deployment_id: Some(deployment_id),
+            account_id,
+        }
+    }
+}
+
+impl Query for GetProjectRoleQuery {
+    type Output = Option<ProjectRole>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let role: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT c.role
+            FROM project_collaborators c
+            JOIN projects p ON p.id = c.project_id AND p.deleted_at IS NULL
+            WHERE c.account_id = $3
+                AND c.project_id = COALESCE(
+                    $1,
+                    (SELECT d.project_id FROM deployments d WHERE d.id = $2)
+                )
+            "#,
+        )
+        .bind(self.project_id)
+        .bind(self.deployment_id)
+        .bind(self.account_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        Ok(role.map(ProjectRole::from))
+    }
+}
+
+/// Whether a console account is a platform operator, who may use the
+/// `/admin` routes that reach across every project.
+pub struct IsPlatformOperatorQuery {
+    account_id: i64,
+}
+
+impl IsPlatformOperatorQuery {
+    pub fn new(account_id: i64) -> Self {
+        Self { account_id }
+    }
+}
+
+impl Query for IsPlatformOperatorQuery {
+    type Output = bool;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let operator: Option<bool> = sqlx::query_scalar(
+            "SELECT platform_operator FROM console_accounts WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(self.account_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        Ok(operator.unwrap_or(false))
+    }
+}
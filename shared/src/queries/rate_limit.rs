@@ -0,0 +1,54 @@
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{DeploymentRateLimit, EndpointClass, RateLimit},
+    queries::Query,
+    state::AppState,
+};
+
+pub struct GetDeploymentRateLimitsQuery {
+    pub deployment_id: i64,
+}
+
+impl GetDeploymentRateLimitsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for GetDeploymentRateLimitsQuery {
+    type Output = Vec<DeploymentRateLimit>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT deployment_id, endpoint_class, requests_per_minute, burst
+            FROM deployment_rate_limits
+            WHERE deployment_id = $1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let requests_per_minute = row.get::<i32, _>("requests_per_minute") as u32;
+
+                DeploymentRateLimit {
+                    deployment_id: row.get("deployment_id"),
+                    endpoint_class: EndpointClass::from(row.get::<String, _>("endpoint_class")),
+                    limit: RateLimit {
+                        requests_per_minute,
+                        burst: row
+                            .get::<Option<i32>, _>("burst")
+                            .map(|burst| burst as u32)
+                            .unwrap_or(requests_per_minute),
+                    },
+                }
+            })
+            .collect())
+    }
+}
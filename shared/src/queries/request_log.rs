@@ -0,0 +1,147 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    error::AppError,
+    models::{RequestLog, RequestLogPage},
+    queries::Query,
+    services::{RequestLogFilter, RequestLogRow},
+    state::AppState,
+};
+
+pub const DEFAULT_REQUEST_LOG_PAGE_SIZE: u32 = 50;
+pub const MAX_REQUEST_LOG_PAGE_SIZE: u32 = 200;
+
+/// Cursors are the `(timestamp, id)` position of the last row on a page.
+fn encode_cursor(row: &RequestLogRow) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", row.timestamp, row.id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(i64, i64), AppError> {
+    let invalid = || AppError::BadRequest("Invalid cursor".to_string());
+
+    let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (timestamp, id) = decoded.split_once(':').ok_or_else(invalid)?;
+
+    Ok((
+        timestamp.parse().map_err(|_| invalid())?,
+        id.parse().map_err(|_| invalid())?,
+    ))
+}
+
+/// Accepts `4xx` as well as a bare `4`.
+pub fn parse_status_class(value: &str) -> Result<u16, AppError> {
+    let digit = value
+        .trim()
+        .strip_suffix("xx")
+        .unwrap_or(value.trim())
+        .parse::<u16>()
+        .ok()
+        .filter(|digit| (1..=5).contains(digit));
+
+    digit.ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "'{}' isn't a status class; use one of 1xx to 5xx",
+            value
+        ))
+    })
+}
+
+pub(crate) fn request_log_from_row(row: RequestLogRow) -> RequestLog {
+    RequestLog {
+        id: row.id,
+        deployment_id: row.deployment_id,
+        api_key_id: row.api_key_id,
+        method: row.method,
+        path: row.path,
+        status: row.status,
+        latency_ms: row.latency_ms,
+        error: row.error,
+        timestamp: DateTime::from_timestamp_millis(row.timestamp).unwrap_or_default(),
+    }
+}
+
+pub struct ListRequestLogsQuery {
+    deployment_id: i64,
+    status_class: Option<u16>,
+    path: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    cursor: Option<String>,
+    limit: u32,
+}
+
+impl ListRequestLogsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            status_class: None,
+            path: None,
+            from: None,
+            to: None,
+            cursor: None,
+            limit: DEFAULT_REQUEST_LOG_PAGE_SIZE,
+        }
+    }
+
+    pub fn status_class(self, status_class: Option<u16>) -> Self {
+        Self {
+            status_class,
+            ..self
+        }
+    }
+
+    pub fn path(self, path: Option<String>) -> Self {
+        Self { path, ..self }
+    }
+
+    pub fn from(self, from: Option<DateTime<Utc>>) -> Self {
+        Self { from, ..self }
+    }
+
+    pub fn to(self, to: Option<DateTime<Utc>>) -> Self {
+        Self { to, ..self }
+    }
+
+    pub fn cursor(self, cursor: Option<String>) -> Self {
+        Self { cursor, ..self }
+    }
+
+    pub fn limit(self, limit: u32) -> Self {
+        Self {
+            limit: limit.clamp(1, MAX_REQUEST_LOG_PAGE_SIZE),
+            ..self
+        }
+    }
+}
+
+impl Query for ListRequestLogsQuery {
+    type Output = RequestLogPage;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let filter = RequestLogFilter {
+            status_class: self.status_class,
+            path_prefix: self.path.clone().filter(|path| !path.is_empty()),
+            from: self.from,
+            to: self.to,
+            before: self.cursor.as_deref().map(decode_cursor).transpose()?,
+        };
+
+        // One extra row tells us whether there is another page.
+        let mut rows = app_state
+            .clickhouse_service
+            .list_request_logs(self.deployment_id, &filter, self.limit + 1)
+            .await?;
+
+        let has_more = rows.len() > self.limit as usize;
+        rows.truncate(self.limit as usize);
+        let next_cursor = has_more.then(|| rows.last().map(encode_cursor)).flatten();
+
+        Ok(RequestLogPage {
+            data: rows.into_iter().map(request_log_from_row).collect(),
+            has_more,
+            next_cursor,
+        })
+    }
+}
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+
+use sqlx::{PgConnection, Postgres, QueryBuilder, Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{
+        SCIM_GROUP_SCHEMA, SCIM_USER_SCHEMA, ScimEmail, ScimFilter, ScimGroup, ScimListResponse,
+        ScimMember, ScimMeta, ScimName, ScimToken, ScimUser,
+    },
+    queries::Query,
+    state::AppState,
+};
+
+/// Identity providers page with `count`; anything above this is clamped.
+pub const MAX_SCIM_PAGE_SIZE: i64 = 200;
+
+const SCIM_USER_SELECT: &str = r#"
+    SELECT
+        u.id, u.created_at, u.updated_at, u.first_name, u.last_name, u.username,
+        u.disabled, u.scim_external_id,
+        e.email_address AS primary_email_address,
+        COALESCE(
+            (
+                SELECT array_agg(ea.email_address ORDER BY ea.created_at)
+                FROM user_email_addresses ea
+                WHERE ea.user_id = u.id
+            ),
+            '{}'
+        ) AS email_addresses
+    FROM users u
+    LEFT JOIN user_email_addresses e ON e.id = u.primary_email_address_id
+"#;
+
+const SCIM_GROUP_SELECT: &str = r#"
+    SELECT o.id, o.created_at, o.updated_at, o.name, o.scim_external_id
+    FROM organizations o
+"#;
+
+fn scim_meta(deployment_id: i64, resource_type: &str, row: &PgRow) -> ScimMeta {
+    let id: i64 = row.get("id");
+    ScimMeta {
+        resource_type: resource_type.to_string(),
+        created: row.get("created_at"),
+        last_modified: row.get("updated_at"),
+        location: format!(
+            "/deployments/{}/scim/v2/{}s/{}",
+            deployment_id, resource_type, id
+        ),
+    }
+}
+
+fn scim_user_from_row(deployment_id: i64, row: &PgRow) -> ScimUser {
+    let id: i64 = row.get("id");
+    let first_name: Option<String> = row.get("first_name");
+    let last_name: Option<String> = row.get("last_name");
+    let username: Option<String> = row.get("username");
+    let primary_email_address: Option<String> = row.get("primary_email_address");
+    let email_addresses: Vec<String> = row.get("email_addresses");
+
+    let formatted = [first_name.as_deref(), last_name.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    ScimUser {
+        schemas: vec![SCIM_USER_SCHEMA.to_string()],
+        id: id.to_string(),
+        external_id: row.get("scim_external_id"),
+        user_name: username
+            .filter(|username| !username.is_empty())
+            .or_else(|| primary_email_address.clone())
+            .unwrap_or_else(|| id.to_string()),
+        name: ScimName {
+            formatted: Some(formatted).filter(|formatted| !formatted.is_empty()),
+            given_name: first_name.filter(|name| !name.is_empty()),
+            family_name: last_name.filter(|name| !name.is_empty()),
+        },
+        emails: email_addresses
+            .into_iter()
+            .map(|value| ScimEmail {
+                primary: primary_email_address.as_deref() == Some(value.as_str()),
+                kind: Some("work".to_string()),
+                value,
+            })
+            .collect(),
+        active: !row.get::<bool, _>("disabled"),
+        meta: scim_meta(deployment_id, "User", row),
+    }
+}
+
+fn push_user_filter(
+    query_builder: &mut QueryBuilder<'_, Postgres>,
+    deployment_id: i64,
+    filter: Option<&ScimFilter>,
+) -> Result<(), AppError> {
+    query_builder.push(" WHERE u.deleted_at IS NULL AND u.deployment_id = ");
+    query_builder.push_bind(deployment_id);
+
+    let Some(filter) = filter else {
+        return Ok(());
+    };
+
+    match filter.attribute.as_str() {
+        "username" => {
+            query_builder.push(" AND (lower(u.username) = lower(");
+            query_builder.push_bind(filter.value.clone());
+            query_builder.push(") OR lower(e.email_address) = lower(");
+            query_builder.push_bind(filter.value.clone());
+            query_builder.push("))");
+        }
+        "externalid" => {
+            query_builder.push(" AND u.scim_external_id = ");
+            query_builder.push_bind(filter.value.clone());
+        }
+        "emails" | "emails.value" => {
+            query_builder.push(
+                " AND EXISTS (SELECT 1 FROM user_email_addresses ea \
+                 WHERE ea.user_id = u.id AND lower(ea.email_address) = lower(",
+            );
+            query_builder.push_bind(filter.value.clone());
+            query_builder.push("))");
+        }
+        "id" => {
+            let id: i64 = filter.value.parse().unwrap_or(-1);
+            query_builder.push(" AND u.id = ");
+            query_builder.push_bind(id);
+        }
+        _ => {
+            return Err(AppError::BadRequest(format!(
+                "Filtering users by {} isn't supported",
+                filter.attribute
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn push_group_filter(
+    query_builder: &mut QueryBuilder<'_, Postgres>,
+    deployment_id: i64,
+    filter: Option<&ScimFilter>,
+) -> Result<(), AppError> {
+    query_builder.push(" WHERE o.deleted_at IS NULL AND o.deployment_id = ");
+    query_builder.push_bind(deployment_id);
+
+    let Some(filter) = filter else {
+        return Ok(());
+    };
+
+    match filter.attribute.as_str() {
+        "displayname" => {
+            query_builder.push(" AND lower(o.name) = lower(");
+            query_builder.push_bind(filter.value.clone());
+            query_builder.push(")");
+        }
+        "externalid" => {
+            query_builder.push(" AND o.scim_external_id = ");
+            query_builder.push_bind(filter.value.clone());
+        }
+        "id" => {
+            let id: i64 = filter.value.parse().unwrap_or(-1);
+            query_builder.push(" AND o.id = ");
+            query_builder.push_bind(id);
+        }
+        _ => {
+            return Err(AppError::BadRequest(format!(
+                "Filtering groups by {} isn't supported",
+                filter.attribute
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+async fn attach_members(conn: &mut PgConnection, groups: &mut [ScimGroup]) -> Result<(), AppError> {
+    let organization_ids: Vec<i64> = groups
+        .iter()
+        .filter_map(|group| group.id.parse().ok())
+        .collect();
+
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            om.organization_id, om.user_id,
+            NULLIF(concat_ws(' ', u.first_name, u.last_name), '') AS display
+        FROM organization_memberships om
+        JOIN users u ON u.id = om.user_id
+        WHERE om.organization_id = ANY($1)
+        ORDER BY om.created_at, om.id
+        "#,
+    )
+    .bind(&organization_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut members_by_organization: HashMap<i64, Vec<ScimMember>> = HashMap::new();
+    for row in rows {
+        members_by_organization
+            .entry(row.get("organization_id"))
+            .or_default()
+            .push(ScimMember {
+                value: row.get::<i64, _>("user_id").to_string(),
+                display: row.get("display"),
+            });
+    }
+
+    for group in groups.iter_mut() {
+        if let Ok(id) = group.id.parse::<i64>() {
+            group.members = members_by_organization.remove(&id).unwrap_or_default();
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetches a user in the SCIM shape, or `NotFound`.
+pub(crate) async fn fetch_scim_user(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    user_id: i64,
+) -> Result<ScimUser, AppError> {
+    let row = sqlx::query(&format!(
+        "{} WHERE u.deployment_id = $1 AND u.id = $2 AND u.deleted_at IS NULL",
+        SCIM_USER_SELECT
+    ))
+    .bind(deployment_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    Ok(scim_user_from_row(deployment_id, &row))
+}
+
+/// Fetches an organization as a SCIM group, or `NotFound`.
+pub(crate) async fn fetch_scim_group(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    organization_id: i64,
+) -> Result<ScimGroup, AppError> {
+    let row = sqlx::query(&format!(
+        "{} WHERE o.deployment_id = $1 AND o.id = $2 AND o.deleted_at IS NULL",
+        SCIM_GROUP_SELECT
+    ))
+    .bind(deployment_id)
+    .bind(organization_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Group not found".to_string()))?;
+
+    let mut groups = [scim_group_from_row(deployment_id, &row)];
+    attach_members(conn, &mut groups).await?;
+
+    let [group] = groups;
+    Ok(group)
+}
+
+fn scim_group_from_row(deployment_id: i64, row: &PgRow) -> ScimGroup {
+    ScimGroup {
+        schemas: vec![SCIM_GROUP_SCHEMA.to_string()],
+        id: row.get::<i64, _>("id").to_string(),
+        external_id: row.get("scim_external_id"),
+        display_name: row.get("name"),
+        members: vec![],
+        meta: scim_meta(deployment_id, "Group", row),
+    }
+}
+
+pub struct GetScimUserQuery {
+    deployment_id: i64,
+    user_id: i64,
+}
+
+impl GetScimUserQuery {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+        }
+    }
+}
+
+impl Query for GetScimUserQuery {
+    type Output = ScimUser;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+        fetch_scim_user(&mut conn, self.deployment_id, self.user_id).await
+    }
+}
+
+pub struct ListScimUsersQuery {
+    deployment_id: i64,
+    filter: Option<ScimFilter>,
+    start_index: i64,
+    count: i64,
+}
+
+impl ListScimUsersQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            filter: None,
+            start_index: 1,
+            count: 100,
+        }
+    }
+
+    pub fn filter(self, filter: Option<ScimFilter>) -> Self {
+        Self { filter, ..self }
+    }
+
+    /// SCIM indexes are 1-based.
+    pub fn start_index(self, start_index: i64) -> Self {
+        Self {
+            start_index: start_index.max(1),
+            ..self
+        }
+    }
+
+    pub fn count(self, count: i64) -> Self {
+        Self {
+            count: count.clamp(0, MAX_SCIM_PAGE_SIZE),
+            ..self
+        }
+    }
+}
+
+impl Query for ListScimUsersQuery {
+    type Output = ScimListResponse<ScimUser>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+
+        let mut count_builder = QueryBuilder::new(
+            "SELECT COUNT(*) FROM users u \
+             LEFT JOIN user_email_addresses e ON e.id = u.primary_email_address_id",
+        );
+        push_user_filter(&mut count_builder, self.deployment_id, self.filter.as_ref())?;
+        let total: i64 = count_builder
+            .build_query_scalar()
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let mut query_builder = QueryBuilder::new(SCIM_USER_SELECT);
+        push_user_filter(&mut query_builder, self.deployment_id, self.filter.as_ref())?;
+        query_builder.push(" ORDER BY u.created_at, u.id LIMIT ");
+        query_builder.push_bind(self.count);
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(self.start_index - 1);
+
+        let rows = query_builder.build().fetch_all(&mut *conn).await?;
+        let users = rows
+            .iter()
+            .map(|row| scim_user_from_row(self.deployment_id, row))
+            .collect();
+
+        Ok(ScimListResponse::new(users, total, self.start_index))
+    }
+}
+
+pub struct GetScimGroupQuery {
+    deployment_id: i64,
+    organization_id: i64,
+}
+
+impl GetScimGroupQuery {
+    pub fn new(deployment_id: i64, organization_id: i64) -> Self {
+        Self {
+            deployment_id,
+            organization_id,
+        }
+    }
+}
+
+impl Query for GetScimGroupQuery {
+    type Output = ScimGroup;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+        fetch_scim_group(&mut conn, self.deployment_id, self.organization_id).await
+    }
+}
+
+pub struct ListScimGroupsQuery {
+    deployment_id: i64,
+    filter: Option<ScimFilter>,
+    start_index: i64,
+    count: i64,
+}
+
+impl ListScimGroupsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            filter: None,
+            start_index: 1,
+            count: 100,
+        }
+    }
+
+    pub fn filter(self, filter: Option<ScimFilter>) -> Self {
+        Self { filter, ..self }
+    }
+
+    pub fn start_index(self, start_index: i64) -> Self {
+        Self {
+            start_index: start_index.max(1),
+            ..self
+        }
+    }
+
+    pub fn count(self, count: i64) -> Self {
+        Self {
+            count: count.clamp(0, MAX_SCIM_PAGE_SIZE),
+            ..self
+        }
+    }
+}
+
+impl Query for ListScimGroupsQuery {
+    type Output = ScimListResponse<ScimGroup>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut conn = app_state.db_pool.acquire().await?;
+
+        let mut count_builder = QueryBuilder::new("SELECT COUNT(*) FROM organizations o");
+        push_group_filter(&mut count_builder, self.deployment_id, self.filter.as_ref())?;
+        let total: i64 = count_builder
+            .build_query_scalar()
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let mut query_builder = QueryBuilder::new(SCIM_GROUP_SELECT);
+        push_group_filter(&mut query_builder, self.deployment_id, self.filter.as_ref())?;
+        query_builder.push(" ORDER BY o.created_at, o.id LIMIT ");
+        query_builder.push_bind(self.count);
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(self.start_index - 1);
+
+        let rows = query_builder.build().fetch_all(&mut *conn).await?;
+        let mut groups: Vec<ScimGroup> = rows
+            .iter()
+            .map(|row| scim_group_from_row(self.deployment_id, row))
+            .collect();
+
+        attach_members(&mut conn, &mut groups).await?;
+
+        Ok(ScimListResponse::new(groups, total, self.start_index))
+    }
+}
+
+pub struct ListScimTokensQuery {
+    deployment_id: i64,
+}
+
+impl ListScimTokensQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for ListScimTokensQuery {
+    type Output = Vec<ScimToken>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, created_at, deployment_id, name, token_prefix, created_by,
+                last_used_at, revoked_at
+            FROM scim_tokens
+            WHERE deployment_id = $1
+            ORDER BY created_at DESC, id
+            "#,
+        )
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        Ok(rows.iter().map(scim_token_from_row).collect())
+    }
+}
+
+pub(crate) fn scim_token_from_row(row: &PgRow) -> ScimToken {
+    ScimToken {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        deployment_id: row.get("deployment_id"),
+        name: row.get("name"),
+        token_prefix: row.get("token_prefix"),
+        created_by: row.get("created_by"),
+        last_used_at: row.get("last_used_at"),
+        revoked_at: row.get("revoked_at"),
+    }
+}
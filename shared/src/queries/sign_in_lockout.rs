@@ -0,0 +1,61 @@
+use std::net::IpAddr;
+
+use serde_json::json;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::SignInLockoutStatus,
+    queries::{GetDeploymentLockoutPolicyQuery, Query},
+    state::AppState,
+};
+
+/// Whether an identifier may attempt to sign in from an IP under the
+/// deployment's lockout policy. Meant to run before credentials are checked.
+pub struct CheckSignInAllowedQuery {
+    deployment_id: i64,
+    identifier: String,
+    ip: IpAddr,
+}
+
+impl CheckSignInAllowedQuery {
+    pub fn new(deployment_id: i64, identifier: impl Into<String>, ip: IpAddr) -> Self {
+        Self {
+            deployment_id,
+            identifier: identifier.into(),
+            ip,
+        }
+    }
+
+    /// Runs the check and turns a lockout into a `locked_out` error.
+    pub async fn ensure_allowed(&self, app_state: &AppState) -> Result<(), AppError> {
+        let status = self.execute(app_state).await?;
+        if !status.locked {
+            return Ok(());
+        }
+
+        Err(locked_out_error(&status))
+    }
+}
+
+impl Query for CheckSignInAllowedQuery {
+    type Output = SignInLockoutStatus;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let policy = GetDeploymentLockoutPolicyQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+
+        app_state
+            .sign_in_lockout_service
+            .status(self.deployment_id, &self.identifier, self.ip, &policy)
+            .await
+    }
+}
+
+pub fn locked_out_error(status: &SignInLockoutStatus) -> AppError {
+    AppError::coded(
+        ErrorCode::LockedOut,
+        "Too many failed sign-in attempts, try again later",
+    )
+    .with_details(json!({ "retry_after_seconds": status.retry_after_seconds }))
+}
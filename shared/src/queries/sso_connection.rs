@@ -0,0 +1,186 @@
+use chrono::Utc;
+use sqlx::{Row, postgres::PgRow, types::Json};
+
+use crate::{
+    error::AppError,
+    models::{CertificateStatus, DeploymentSsoConnection},
+    queries::Query,
+    services::{CredentialCipher, SSO_CONNECTION_SP_PRIVATE_KEY},
+    state::AppState,
+};
+
+pub(crate) const SSO_CONNECTION_COLUMNS: &str = r#"
+    id, created_at, updated_at, deployment_id, name, enabled, idp_metadata_url,
+    idp_metadata_xml, idp_entity_id, idp_sso_url, idp_certificate,
+    certificate_expires_at, attribute_mapping, allowed_email_domains,
+    sp_certificate, sp_private_key
+"#;
+
+/// Maps a row, opening its sealed SP private key.
+pub(crate) fn sso_connection_from_row(
+    row: &PgRow,
+    cipher: &CredentialCipher,
+) -> Result<DeploymentSsoConnection, AppError> {
+    let certificate_expires_at = row.get("certificate_expires_at");
+    let Json(attribute_mapping) = row.get("attribute_mapping");
+    let sp_private_key = cipher.open(
+        &row.get::<String, _>("sp_private_key"),
+        SSO_CONNECTION_SP_PRIVATE_KEY,
+    )?;
+
+    Ok(DeploymentSsoConnection {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        name: row.get("name"),
+        enabled: row.get("enabled"),
+        idp_metadata_url: row.get("idp_metadata_url"),
+        idp_metadata_xml: row.get("idp_metadata_xml"),
+        idp_entity_id: row.get("idp_entity_id"),
+        idp_sso_url: row.get("idp_sso_url"),
+        idp_certificate: row.get("idp_certificate"),
+        certificate_expires_at,
+        certificate_status: CertificateStatus::at(certificate_expires_at, Utc::now()),
+        attribute_mapping,
+        allowed_email_domains: row.get("allowed_email_domains"),
+        sp_certificate: row.get("sp_certificate"),
+        sp_private_key,
+    })
+}
+
+/// Lists a deployment's SSO connections, soonest certificate expiry first.
+pub struct ListSsoConnectionsQuery {
+    deployment_id: i64,
+}
+
+impl ListSsoConnectionsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self { deployment_id }
+    }
+}
+
+impl Query for ListSsoConnectionsQuery {
+    type Output = Vec<DeploymentSsoConnection>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM deployment_sso_connections WHERE deployment_id = $1 \
+             ORDER BY certificate_expires_at ASC, id ASC",
+            SSO_CONNECTION_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| sso_connection_from_row(row, &app_state.credential_cipher))
+            .collect()
+    }
+}
+
+pub struct GetSsoConnectionQuery {
+    deployment_id: i64,
+    connection_id: i64,
+}
+
+impl GetSsoConnectionQuery {
+    pub fn new(deployment_id: i64, connection_id: i64) -> Self {
+        Self {
+            deployment_id,
+            connection_id,
+        }
+    }
+}
+
+impl Query for GetSsoConnectionQuery {
+    type Output = DeploymentSsoConnection;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM deployment_sso_connections WHERE deployment_id = $1 AND id = $2",
+            SSO_CONNECTION_COLUMNS
+        ))
+        .bind(self.deployment_id)
+        .bind(self.connection_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("SSO connection not found".to_string()))?;
+
+        sso_connection_from_row(&row, &app_state.credential_cipher)
+    }
+}
+
+/// Builds the service provider metadata document an administrator uploads
+/// to their identity provider. Both the entity id and the assertion
+/// consumer service live on the deployment's backend host.
+pub struct GenerateSpMetadataQuery {
+    deployment_id: i64,
+    connection_id: i64,
+}
+
+impl GenerateSpMetadataQuery {
+    pub fn new(deployment_id: i64, connection_id: i64) -> Self {
+        Self {
+            deployment_id,
+            connection_id,
+        }
+    }
+}
+
+impl Query for GenerateSpMetadataQuery {
+    type Output = String;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let connection = GetSsoConnectionQuery::new(self.deployment_id, self.connection_id)
+            .execute(app_state)
+            .await?;
+
+        let backend_host: String =
+            sqlx::query_scalar("SELECT backend_host FROM deployments WHERE id = $1")
+                .bind(self.deployment_id)
+                .fetch_optional(&app_state.db_pool)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Deployment not found".to_string()))?;
+
+        Ok(sp_metadata_xml(&backend_host, &connection))
+    }
+}
+
+fn sp_metadata_xml(backend_host: &str, connection: &DeploymentSsoConnection) -> String {
+    let base_url = format!("https://{}/sso/saml/{}", backend_host, connection.id);
+    let certificate: String = connection
+        .sp_certificate
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<md:EntityDescriptor xmlns:md="urn:oasis:names:tc:SAML:2.0:metadata" xmlns:ds="http://www.w3.org/2000/09/xmldsig#" entityID="{entity_id}">
+  <md:SPSSODescriptor AuthnRequestsSigned="true" WantAssertionsSigned="true" protocolSupportEnumeration="urn:oasis:names:tc:SAML:2.0:protocol">
+    <md:KeyDescriptor use="signing">
+      <ds:KeyInfo>
+        <ds:X509Data>
+          <ds:X509Certificate>{certificate}</ds:X509Certificate>
+        </ds:X509Data>
+      </ds:KeyInfo>
+    </md:KeyDescriptor>
+    <md:NameIDFormat>urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress</md:NameIDFormat>
+    <md:AssertionConsumerService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" Location="{acs_url}" index="0" isDefault="true"/>
+  </md:SPSSODescriptor>
+</md:EntityDescriptor>
+"#,
+        entity_id = xml_escape(&format!("{}/metadata", base_url)),
+        acs_url = xml_escape(&format!("{}/acs", base_url)),
+        certificate = certificate,
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::{
+    error::AppError,
+    models::{BillingPeriod, DeploymentUsage, UsageAdjustments, UsageMetric, UsagePeriodStatus},
+    state::AppState,
+};
+
+use super::Query;
+
+/// A deployment's usage in one billing period: the frozen figures if the
+/// period has been closed, live ones from ClickHouse otherwise.
+pub struct GetDeploymentUsageQuery {
+    deployment_id: i64,
+    period: BillingPeriod,
+}
+
+impl GetDeploymentUsageQuery {
+    pub fn new(deployment_id: i64, period: BillingPeriod) -> Self {
+        Self {
+            deployment_id,
+            period,
+        }
+    }
+}
+
+impl Query for GetDeploymentUsageQuery {
+    type Output = DeploymentUsage;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let exists: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM deployments WHERE id = $1)")
+                .bind(self.deployment_id)
+                .fetch_one(&app_state.db_pool)
+                .await?;
+        if !exists {
+            return Err(AppError::NotFound("Deployment not found".to_string()));
+        }
+
+        let closed = sqlx::query(
+            r#"
+            SELECT active_users, emails_sent, emails_by_template, closed_at
+            FROM deployment_usage_periods
+            WHERE deployment_id = $1 AND period_start = $2
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.period.start)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        let Some(closed) = closed else {
+            let until = self.period.ends_at().min(Utc::now());
+            let usage = app_state
+                .clickhouse_service
+                .get_usage(Some(self.deployment_id), self.period.starts_at(), until)
+                .await?
+                .remove(&self.deployment_id)
+                .unwrap_or_default();
+            let emails_by_template: BTreeMap<String, i64> =
+                usage.emails_by_template.into_iter().collect();
+
+            return Ok(DeploymentUsage {
+                deployment_id: self.deployment_id,
+                period_start: self.period.start,
+                period_end: self.period.end(),
+                status: UsagePeriodStatus::Open,
+                active_users: usage.active_users,
+                emails_sent: emails_by_template.values().sum(),
+                emails_by_template,
+                adjustments: UsageAdjustments::default(),
+                closed_at: None,
+            });
+        };
+
+        let mut adjustments = UsageAdjustments::default();
+        let totals = sqlx::query(
+            r#"
+            SELECT metric, SUM(quantity)::BIGINT AS quantity
+            FROM deployment_usage_adjustments
+            WHERE deployment_id = $1 AND period_start = $2
+            GROUP BY metric
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.period.start)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+        for total in totals {
+            let quantity: i64 = total.get("quantity");
+            match UsageMetric::from(total.get::<String, _>("metric")) {
+                UsageMetric::ActiveUser => adjustments.active_users += quantity,
+                UsageMetric::EmailSent => adjustments.emails_sent += quantity,
+            }
+        }
+
+        Ok(DeploymentUsage {
+            deployment_id: self.deployment_id,
+            period_start: self.period.start,
+            period_end: self.period.end(),
+            status: UsagePeriodStatus::Closed,
+            active_users: closed.get("active_users"),
+            emails_sent: closed.get("emails_sent"),
+            emails_by_template: serde_json::from_value(closed.get("emails_by_template"))
+                .unwrap_or_default(),
+            adjustments,
+            closed_at: Some(closed.get::<DateTime<Utc>, _>("closed_at")),
+        })
+    }
+}
@@ -2,9 +2,12 @@ use super::Query;
 use crate::{
     error::AppError,
     models::{
-        DeploymentInvitation, DeploymentWaitlistUser, SocialConnection, UserDetails,
-        UserEmailAddress, UserPhoneNumber, UserWithIdentifiers,
+        DeploymentInvitation, DuplicateEmailAccount, DuplicateEmailAccounts, SocialConnection,
+        UserAccountState, UserDetails, UserEmailAddress, UserIdentifierKind, UserLookup,
+        UserOrganizationMembership, UserPhoneNumber, UserSession, UserWeb3Wallet,
+        UserWithIdentifiers,
     },
+    services::{SessionRepository, to_e164},
     state::AppState,
 };
 use sqlx::Row;
@@ -82,46 +85,11 @@ impl DeploymentInvitationQuery {
     }
 }
 
-pub struct DeploymentWaitlistQuery {
-    deployment_id: i64,
-    offset: i64,
-    sort_key: Option<String>,
-    sort_order: Option<String>,
-    limit: i32,
-}
-
-impl DeploymentWaitlistQuery {
-    pub fn new(id: i64) -> Self {
-        Self {
-            offset: 0,
-            sort_key: None,
-            sort_order: None,
-            limit: 10,
-            deployment_id: id,
-        }
-    }
-
-    pub fn offset(self, offset: i64) -> Self {
-        Self { offset, ..self }
-    }
-
-    pub fn limit(self, limit: i32) -> Self {
-        Self { limit, ..self }
-    }
-
-    pub fn sort_key(self, sort_key: Option<String>) -> Self {
-        Self { sort_key, ..self }
-    }
-
-    pub fn sort_order(self, sort_order: Option<String>) -> Self {
-        Self { sort_order, ..self }
-    }
-}
-
 impl Query for DeploymentActiveUserListQuery {
     type Output = Vec<UserWithIdentifiers>;
+    const PREFERS_READ_REPLICA: bool = true;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let sort_key = self.sort_key.as_deref().unwrap_or("created_at");
         let sort_order = self.sort_order.as_deref().unwrap_or("desc");
 
@@ -131,11 +99,13 @@ impl Query for DeploymentActiveUserListQuery {
                 u.id, u.created_at, u.updated_at,
                 u.first_name, u.last_name, u.username,
                 e.email_address as primary_email_address,
-                p.phone_number as primary_phone_number
+                p.phone_number as primary_phone_number,
+                w.address as primary_web3_wallet
             FROM users u
             LEFT JOIN user_email_addresses e ON u.primary_email_address_id = e.id
             LEFT JOIN user_phone_numbers p ON u.primary_phone_number_id = p.id
-            WHERE u.deployment_id = "#,
+            LEFT JOIN user_web3_wallets w ON w.user_id = u.id AND w.is_primary
+            WHERE u.deleted_at IS NULL AND u.deployment_id = "#,
         );
 
         query_builder.push_bind(self.deployment_id);
@@ -160,7 +130,10 @@ impl Query for DeploymentActiveUserListQuery {
         query_builder.push(" LIMIT ");
         query_builder.push_bind(self.limit);
 
-        let rows = query_builder.build().fetch_all(&app_state.db_pool).await?;
+        let rows = query_builder
+            .build()
+            .fetch_all(self.pool(app_state))
+            .await?;
 
         let users = rows
             .into_iter()
@@ -173,6 +146,7 @@ impl Query for DeploymentActiveUserListQuery {
                 username: row.get("username"),
                 primary_email_address: row.get("primary_email_address"),
                 primary_phone_number: row.get("primary_phone_number"),
+                primary_web3_wallet: row.get("primary_web3_wallet"),
             })
             .collect();
 
@@ -182,8 +156,9 @@ impl Query for DeploymentActiveUserListQuery {
 
 impl Query for DeploymentInvitationQuery {
     type Output = Vec<DeploymentInvitation>;
+    const PREFERS_READ_REPLICA: bool = true;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let sort_key = self.sort_key.as_deref().unwrap_or("created_at");
         let sort_order = self.sort_order.as_deref().unwrap_or("desc");
 
@@ -193,7 +168,7 @@ impl Query for DeploymentInvitationQuery {
                 i.id, i.created_at, i.updated_at,
                 i.first_name, i.last_name,
                 i.email_address, i.deployment_id,
-                i.expiry
+                i.expiry, i.accepted_at, i.revoked_at
             FROM deployment_invitations i
             WHERE i.deployment_id = "#,
         );
@@ -218,7 +193,10 @@ impl Query for DeploymentInvitationQuery {
         query_builder.push(" LIMIT ");
         query_builder.push_bind(self.limit);
 
-        let rows = query_builder.build().fetch_all(&app_state.db_pool).await?;
+        let rows = query_builder
+            .build()
+            .fetch_all(self.pool(app_state))
+            .await?;
 
         let invitations = rows
             .into_iter()
@@ -231,6 +209,8 @@ impl Query for DeploymentInvitationQuery {
                 deployment_id: row.get("deployment_id"),
                 email_address: row.get("email_address"),
                 expiry: row.get("expiry"),
+                accepted_at: row.get("accepted_at"),
+                revoked_at: row.get("revoked_at"),
             })
             .collect();
 
@@ -255,7 +235,7 @@ impl GetUserDetailsQuery {
 impl Query for GetUserDetailsQuery {
     type Output = UserDetails;
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
         let user_row = sqlx::query!(
             r#"
             SELECT
@@ -337,6 +317,38 @@ impl Query for GetUserDetailsQuery {
             })
             .collect();
 
+        let web3_wallets: Vec<UserWeb3Wallet> = sqlx::query(
+            r#"
+            SELECT
+                id, created_at, updated_at, deployment_id, user_id,
+                address, chain_id, verified, verified_at, is_primary
+            FROM user_web3_wallets
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(self.user_id)
+        .fetch_all(&app_state.db_pool)
+        .await?
+        .into_iter()
+        .map(|row| UserWeb3Wallet {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            deployment_id: row.get("deployment_id"),
+            user_id: row.get("user_id"),
+            address: row.get("address"),
+            chain_id: row.get("chain_id"),
+            verified: row.get("verified"),
+            verified_at: row.get("verified_at"),
+            is_primary: row.get("is_primary"),
+        })
+        .collect();
+        let primary_web3_wallet = web3_wallets
+            .iter()
+            .find(|wallet| wallet.is_primary)
+            .map(|wallet| wallet.address.clone());
+
         let social_rows = sqlx::query!(
             r#"
             SELECT
@@ -366,6 +378,14 @@ impl Query for GetUserDetailsQuery {
             })
             .collect();
 
+        let lifecycle_row = sqlx::query(
+            "SELECT require_password_change, deleted_at, purge_after FROM users WHERE id = $1",
+        )
+        .bind(self.user_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+        let require_password_change: bool = lifecycle_row.get("require_password_change");
+
         let user_details = UserDetails {
             id: user_row.id,
             created_at: user_row.created_at,
@@ -391,72 +411,275 @@ impl Query for GetUserDetailsQuery {
             private_metadata: user_row.private_metadata,
             primary_email_address: user_row.primary_email_address,
             primary_phone_number: user_row.primary_phone_number,
+            primary_web3_wallet,
             email_addresses,
             phone_numbers,
+            web3_wallets,
             social_connections,
             has_password: user_row.password.is_some()
                 && !user_row.password.unwrap_or_default().is_empty(),
             has_otp: !user_row.otp_secret.is_empty(),
             has_backup_codes: user_row.backup_codes.is_some()
                 && !user_row.backup_codes.unwrap_or_default().is_empty(),
+            require_password_change,
+            deleted_at: lifecycle_row.get("deleted_at"),
+            purge_after: lifecycle_row.get("purge_after"),
         };
 
         Ok(user_details)
     }
 }
 
-impl Query for DeploymentWaitlistQuery {
-    type Output = Vec<DeploymentWaitlistUser>;
+pub struct ListUserSessionsQuery {
+    deployment_id: i64,
+    user_id: i64,
+    current_session_id: Option<i64>,
+    offset: i64,
+    limit: i64,
+}
 
-    async fn execute(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
-        let sort_key = self.sort_key.as_deref().unwrap_or("created_at");
-        let sort_order = self.sort_order.as_deref().unwrap_or("desc");
+impl ListUserSessionsQuery {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+            current_session_id: None,
+            offset: 0,
+            limit: 10,
+        }
+    }
 
-        let mut query_builder = sqlx::QueryBuilder::new(
+    /// The caller's own sign-in, flagged as current in the results.
+    pub fn current_session_id(self, current_session_id: Option<i64>) -> Self {
+        Self {
+            current_session_id,
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl Query for ListUserSessionsQuery {
+    type Output = Vec<UserSession>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        SessionRepository::new(&app_state.db_pool, &app_state.redis)
+            .list_active(
+                self.deployment_id,
+                self.user_id,
+                self.current_session_id,
+                self.limit,
+                self.offset,
+            )
+            .await
+    }
+}
+
+/// Finds the user an email address, phone number or username belongs to.
+/// Emails and usernames match case-insensitively and phone numbers after
+/// formatting is stripped. Disabled users are returned too, flagged as such.
+pub struct FindUserByIdentifierQuery {
+    deployment_id: i64,
+    identifier: String,
+}
+
+impl FindUserByIdentifierQuery {
+    pub fn new(deployment_id: i64, identifier: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            identifier: identifier.into(),
+        }
+    }
+}
+
+/// Only identifiers made of digits and phone formatting are tried as phone
+/// numbers, so a username like "bob-1" isn't turned into "+1".
+fn phone_number_candidate(identifier: &str) -> Option<String> {
+    let looks_like_phone = identifier
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '+' | ' ' | '-' | '(' | ')' | '.'))
+        && identifier.chars().any(|c| c.is_ascii_digit());
+    looks_like_phone.then(|| to_e164(identifier))
+}
+
+impl Query for FindUserByIdentifierQuery {
+    type Output = UserLookup;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let identifier = self.identifier.trim();
+        if identifier.is_empty() {
+            return Err(AppError::BadRequest("identifier is required".to_string()));
+        }
+        let lowered = identifier.to_lowercase();
+
+        // Emails win over phone numbers, and both over usernames, in case
+        // one user's username is another's address.
+        let matched = sqlx::query(
+            r#"
+            SELECT user_id, matched_on FROM (
+                SELECT e.user_id, 'email_address' AS matched_on, 1 AS priority
+                FROM user_email_addresses e
+                JOIN users u ON u.id = e.user_id
+                WHERE u.deployment_id = $1 AND lower(e.email_address) = $2 AND u.deleted_at IS NULL
+                UNION ALL
+                SELECT p.user_id, 'phone_number', 2
+                FROM user_phone_numbers p
+                JOIN users u ON u.id = p.user_id
+                WHERE u.deployment_id = $1 AND p.phone_number = $3 AND u.deleted_at IS NULL
+                UNION ALL
+                SELECT u.id, 'username', 3
+                FROM users u
+                WHERE u.deployment_id = $1 AND lower(u.username) = $2 AND u.deleted_at IS NULL
+            ) matches
+            ORDER BY priority
+            LIMIT 1
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&lowered)
+        .bind(phone_number_candidate(identifier))
+        .fetch_optional(&app_state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+        let user_id: i64 = matched.get("user_id");
+
+        let user = GetUserDetailsQuery::new(self.deployment_id, user_id)
+            .run(app_state)
+            .await?;
+
+        let organization_memberships = sqlx::query(
             r#"
             SELECT
-                u.id, u.created_at, u.updated_at,
-                u.email_address, u.first_name, u.last_name,
-                u.deployment_id
-            FROM deployment_waitlist_users u
-            WHERE u.deployment_id = "#,
-        );
+                om.id, om.organization_id, om.created_at,
+                o.name AS organization_name,
+                ARRAY(
+                    SELECT r.name
+                    FROM organization_membership_roles omr
+                    JOIN organization_roles r ON r.id = omr.organization_role_id
+                    WHERE omr.organization_membership_id = om.id
+                    ORDER BY r.name
+                ) AS roles
+            FROM organization_memberships om
+            JOIN organizations o ON o.id = om.organization_id AND o.deleted_at IS NULL
+            WHERE om.user_id = $1 AND om.deleted_at IS NULL
+            ORDER BY om.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&app_state.db_pool)
+        .await?
+        .into_iter()
+        .map(|row| UserOrganizationMembership {
+            id: row.get("id"),
+            organization_id: row.get("organization_id"),
+            organization_name: row.get("organization_name"),
+            roles: row.get("roles"),
+            created_at: row.get("created_at"),
+        })
+        .collect();
+
+        Ok(UserLookup {
+            matched_on: UserIdentifierKind::from(matched.get::<String, _>("matched_on")),
+            state: if user.disabled {
+                UserAccountState::Disabled
+            } else {
+                UserAccountState::Active
+            },
+            user,
+            organization_memberships,
+        })
+    }
+}
 
-        query_builder.push_bind(self.deployment_id);
+/// Groups of accounts on a deployment whose email addresses normalize to the
+/// same mailbox. They're listed for review rather than merged, since only an
+/// operator can tell which account the person behind them meant to keep.
+pub struct FindDuplicateEmailAccountsQuery {
+    deployment_id: i64,
+    offset: i64,
+    limit: i64,
+}
 
-        query_builder.push(" ORDER BY ");
+impl FindDuplicateEmailAccountsQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            offset: 0,
+            limit: 10,
+        }
+    }
 
-        match sort_key {
-            "created_at" => query_builder.push("u.created_at"),
-            "email" => query_builder.push("u.email_address"),
-            _ => query_builder.push("u.created_at"),
-        };
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
 
-        match sort_order.to_lowercase().as_str() {
-            "asc" => query_builder.push(" ASC"),
-            _ => query_builder.push(" DESC"),
-        };
+    /// The number of groups, not accounts, to return.
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
 
-        query_builder.push(" OFFSET ");
-        query_builder.push_bind(self.offset);
-        query_builder.push(" LIMIT ");
-        query_builder.push_bind(self.limit);
+impl Query for FindDuplicateEmailAccountsQuery {
+    type Output = Vec<DuplicateEmailAccounts>;
+    const PREFERS_READ_REPLICA: bool = true;
 
-        let rows = query_builder.build().fetch_all(&app_state.db_pool).await?;
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let rows = sqlx::query(
+            r#"
+            WITH duplicates AS (
+                SELECT e.normalized_email_address
+                FROM user_email_addresses e
+                JOIN users u ON u.id = e.user_id AND u.deleted_at IS NULL
+                WHERE e.deployment_id = $1 AND e.normalized_email_address IS NOT NULL
+                GROUP BY e.normalized_email_address
+                HAVING COUNT(DISTINCT e.user_id) > 1
+                ORDER BY e.normalized_email_address
+                LIMIT $2 OFFSET $3
+            )
+            SELECT d.normalized_email_address, e.user_id, e.email_address,
+                u.first_name, u.last_name, u.created_at
+            FROM duplicates d
+            JOIN user_email_addresses e
+                ON e.deployment_id = $1 AND e.normalized_email_address = d.normalized_email_address
+            JOIN users u ON u.id = e.user_id AND u.deleted_at IS NULL
+            ORDER BY d.normalized_email_address, u.created_at, e.id
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.limit)
+        .bind(self.offset)
+        .fetch_all(self.pool(app_state))
+        .await?;
 
-        let waitlist_users = rows
-            .into_iter()
-            .map(|row| DeploymentWaitlistUser {
-                id: row.get("id"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
+        let mut groups: Vec<DuplicateEmailAccounts> = Vec::new();
+        for row in rows {
+            let normalized_email_address: String = row.get("normalized_email_address");
+            let account = DuplicateEmailAccount {
+                user_id: row.get("user_id"),
+                email_address: row.get("email_address"),
                 first_name: row.get("first_name"),
                 last_name: row.get("last_name"),
-                email_address: row.get("email_address"),
-                deployment_id: row.get("deployment_id"),
-            })
-            .collect();
+                created_at: row.get("created_at"),
+            };
+
+            match groups.last_mut() {
+                Some(group) if group.normalized_email_address == normalized_email_address => {
+                    group.accounts.push(account)
+                }
+                _ => groups.push(DuplicateEmailAccounts {
+                    normalized_email_address,
+                    accounts: vec![account],
+                }),
+            }
+        }
 
-        Ok(waitlist_users)
+        Ok(groups)
     }
 }
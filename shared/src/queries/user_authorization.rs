@@ -0,0 +1,379 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{
+        AuthorizationFlags, AuthorizationRole, OrganizationAuthorization, UserAuthorizationContext,
+        WorkspaceAuthorization,
+    },
+    state::AppState,
+};
+
+use super::Query;
+
+/// Membership commands drop a user's entry and role and settings changes
+/// move the deployment to a new generation, so the TTL only bounds how long
+/// a missed invalidation can last.
+const AUTHORIZATION_CONTEXT_TTL: Duration = Duration::from_secs(300);
+
+fn authorization_context_key(deployment_id: i64, user_id: i64) -> String {
+    format!("authorization_context:{}:{}", deployment_id, user_id)
+}
+
+fn authorization_generation_key(deployment_id: i64) -> String {
+    format!("authorization_context_generation:{}", deployment_id)
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedAuthorizationContext {
+    generation: i64,
+    context: UserAuthorizationContext,
+}
+
+/// Drops a user's cached authorization context after their memberships
+/// change. The change has already been committed, so a failure is only
+/// logged; the entry expires with its TTL regardless.
+pub(crate) async fn invalidate_user_authorization_context(
+    app_state: &AppState,
+    deployment_id: i64,
+    user_id: i64,
+) {
+    if let Err(e) = app_state
+        .redis
+        .delete(&[authorization_context_key(deployment_id, user_id)])
+        .await
+    {
+        tracing::warn!(
+            deployment_id,
+            user_id,
+            "Failed to invalidate a cached authorization context: {}",
+            e
+        );
+    }
+}
+
+/// Past this many users, dropping their entries one by one costs more than
+/// moving the whole deployment to a new generation.
+const MAX_PER_USER_INVALIDATIONS: usize = 1000;
+
+/// Drops the cached authorization contexts of the given users, falling back
+/// to the whole deployment when there are too many of them. Failures are
+/// only logged, as with [`invalidate_user_authorization_context`].
+pub(crate) async fn invalidate_users_authorization_contexts(
+    app_state: &AppState,
+    deployment_id: i64,
+    user_ids: &[i64],
+) {
+    if user_ids.len() > MAX_PER_USER_INVALIDATIONS {
+        invalidate_deployment_authorization_contexts(app_state, deployment_id).await;
+        return;
+    }
+
+    let keys: Vec<String> = user_ids
+        .iter()
+        .map(|user_id| authorization_context_key(deployment_id, *user_id))
+        .collect();
+    if let Err(e) = app_state.redis.delete(&keys).await {
+        tracing::warn!(
+            deployment_id,
+            users = user_ids.len(),
+            "Failed to invalidate cached authorization contexts: {}",
+            e
+        );
+    }
+}
+
+/// Drops every cached authorization context in the deployment after a change
+/// to its roles or b2b settings, by moving it to a new generation.
+pub(crate) async fn invalidate_deployment_authorization_contexts(
+    app_state: &AppState,
+    deployment_id: i64,
+) {
+    let result = async {
+        let mut connection = app_state.redis.connection().await?;
+        redis::cmd("INCR")
+            .arg(authorization_generation_key(deployment_id))
+            .query_async::<i64>(&mut connection)
+            .await?;
+        Ok::<_, AppError>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::warn!(
+            deployment_id,
+            "Failed to invalidate cached authorization contexts: {}",
+            e
+        );
+    }
+}
+
+/// A membership's roles and the permissions they add up to, gathered from one
+/// row per role.
+#[derive(Default)]
+struct MembershipGrants {
+    roles: Vec<AuthorizationRole>,
+    permissions: BTreeSet<String>,
+}
+
+impl MembershipGrants {
+    fn add_role(&mut self, row: &PgRow) {
+        if let Some(role_id) = row.get::<Option<i64>, _>("role_id") {
+            self.roles.push(AuthorizationRole {
+                id: role_id,
+                name: row.get("role_name"),
+            });
+            self.permissions.extend(
+                row.get::<Option<Vec<String>>, _>("permissions")
+                    .unwrap_or_default(),
+            );
+        }
+    }
+}
+
+/// The newest of the membership and role timestamps in `row`.
+fn row_updated_at(row: &PgRow) -> DateTime<Utc> {
+    let membership: DateTime<Utc> = row.get("membership_updated_at");
+    row.get::<Option<DateTime<Utc>>, _>("role_updated_at")
+        .map_or(membership, |role| role.max(membership))
+}
+
+/// A user's organization and workspace memberships with their roles and
+/// flattened permissions, plus the deployment settings that affect them.
+/// Results are cached in Redis until a membership, role or setting changes.
+pub struct GetUserAuthorizationContextQuery {
+    deployment_id: i64,
+    user_id: i64,
+}
+
+impl GetUserAuthorizationContextQuery {
+    pub fn new(deployment_id: i64, user_id: i64) -> Self {
+        Self {
+            deployment_id,
+            user_id,
+        }
+    }
+
+    async fn read_cached(
+        &self,
+        app_state: &AppState,
+    ) -> Result<(i64, Option<UserAuthorizationContext>), AppError> {
+        let mut connection = app_state.redis.connection().await?;
+        let (generation, cached): (Option<i64>, Option<String>) = redis::pipe()
+            .get(authorization_generation_key(self.deployment_id))
+            .get(authorization_context_key(self.deployment_id, self.user_id))
+            .query_async(&mut connection)
+            .await?;
+        let generation = generation.unwrap_or_default();
+
+        let context = cached
+            .and_then(|cached| serde_json::from_str::<CachedAuthorizationContext>(&cached).ok())
+            .filter(|cached| cached.generation == generation)
+            .map(|cached| cached.context);
+
+        Ok((generation, context))
+    }
+
+    async fn compute(&self, app_state: &AppState) -> Result<UserAuthorizationContext, AppError> {
+        let pool = self.pool(app_state);
+
+        let user = sqlx::query(
+            r#"
+            SELECT
+                u.created_at,
+                s.updated_at AS settings_updated_at,
+                COALESCE(s.organizations_enabled, false) AS organizations_enabled,
+                COALESCE(s.workspaces_enabled, false) AS workspaces_enabled,
+                COALESCE(s.custom_org_role_enabled, false) AS custom_org_role_enabled,
+                COALESCE(s.custom_workspace_role_enabled, false) AS custom_workspace_role_enabled
+            FROM users u
+            LEFT JOIN deployment_b2b_settings s
+                ON s.deployment_id = u.deployment_id AND s.deleted_at IS NULL
+            WHERE u.deployment_id = $1 AND u.id = $2 AND u.deleted_at IS NULL
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        // Nothing in the context is older than the user.
+        let mut updated_at: DateTime<Utc> = user.get("created_at");
+        if let Some(settings_updated_at) =
+            user.get::<Option<DateTime<Utc>>, _>("settings_updated_at")
+        {
+            updated_at = updated_at.max(settings_updated_at);
+        }
+
+        let organization_rows = sqlx::query(
+            r#"
+            SELECT
+                m.id AS membership_id,
+                m.organization_id,
+                o.name AS organization_name,
+                m.updated_at AS membership_updated_at,
+                r.id AS role_id,
+                r.name AS role_name,
+                r.permissions,
+                r.updated_at AS role_updated_at
+            FROM organization_memberships m
+            JOIN organizations o ON o.id = m.organization_id
+            LEFT JOIN organization_membership_roles mr ON mr.organization_membership_id = m.id
+            LEFT JOIN organization_roles r ON r.id = mr.organization_role_id AND r.deleted_at IS NULL
+            WHERE o.deployment_id = $1
+                AND m.user_id = $2
+                AND m.deleted_at IS NULL
+                AND o.deleted_at IS NULL
+            ORDER BY o.name, m.id, r.name
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut organizations: Vec<(OrganizationAuthorization, MembershipGrants)> = Vec::new();
+        for row in &organization_rows {
+            updated_at = updated_at.max(row_updated_at(row));
+            let membership_id: i64 = row.get("membership_id");
+            if organizations.last().map(|(org, _)| org.membership_id) != Some(membership_id) {
+                organizations.push((
+                    OrganizationAuthorization {
+                        membership_id,
+                        organization_id: row.get("organization_id"),
+                        organization_name: row.get("organization_name"),
+                        roles: Vec::new(),
+                        permissions: Vec::new(),
+                    },
+                    MembershipGrants::default(),
+                ));
+            }
+            if let Some((_, grants)) = organizations.last_mut() {
+                grants.add_role(row);
+            }
+        }
+
+        let workspace_rows = sqlx::query(
+            r#"
+            SELECT
+                m.id AS membership_id,
+                w.id AS workspace_id,
+                w.organization_id,
+                w.name AS workspace_name,
+                m.updated_at AS membership_updated_at,
+                r.id AS role_id,
+                r.name AS role_name,
+                r.permissions,
+                r.updated_at AS role_updated_at
+            FROM workspace_memberships m
+            JOIN workspaces w ON w.id = m.workspace_id
+            LEFT JOIN workspace_membership_roles mr ON mr.workspace_membership_id = m.id
+            LEFT JOIN workspace_roles r ON r.id = mr.workspace_role_id AND r.deleted_at IS NULL
+            WHERE w.deployment_id = $1
+                AND m.user_id = $2
+                AND m.deleted_at IS NULL
+                AND w.deleted_at IS NULL
+            ORDER BY w.name, m.id, r.name
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(self.user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut workspaces: Vec<(WorkspaceAuthorization, MembershipGrants)> = Vec::new();
+        for row in &workspace_rows {
+            updated_at = updated_at.max(row_updated_at(row));
+            let membership_id: i64 = row.get("membership_id");
+            if workspaces.last().map(|(ws, _)| ws.membership_id) != Some(membership_id) {
+                workspaces.push((
+                    WorkspaceAuthorization {
+                        membership_id,
+                        workspace_id: row.get("workspace_id"),
+                        organization_id: row.get("organization_id"),
+                        workspace_name: row.get("workspace_name"),
+                        roles: Vec::new(),
+                        permissions: Vec::new(),
+                    },
+                    MembershipGrants::default(),
+                ));
+            }
+            if let Some((_, grants)) = workspaces.last_mut() {
+                grants.add_role(row);
+            }
+        }
+
+        Ok(UserAuthorizationContext {
+            deployment_id: self.deployment_id,
+            user_id: self.user_id,
+            flags: AuthorizationFlags {
+                organizations_enabled: user.get("organizations_enabled"),
+                workspaces_enabled: user.get("workspaces_enabled"),
+                custom_org_role_enabled: user.get("custom_org_role_enabled"),
+                custom_workspace_role_enabled: user.get("custom_workspace_role_enabled"),
+            },
+            organizations: organizations
+                .into_iter()
+                .map(|(organization, grants)| OrganizationAuthorization {
+                    roles: grants.roles,
+                    permissions: grants.permissions.into_iter().collect(),
+                    ..organization
+                })
+                .collect(),
+            workspaces: workspaces
+                .into_iter()
+                .map(|(workspace, grants)| WorkspaceAuthorization {
+                    roles: grants.roles,
+                    permissions: grants.permissions.into_iter().collect(),
+                    ..workspace
+                })
+                .collect(),
+            updated_at,
+        })
+    }
+}
+
+impl Query for GetUserAuthorizationContextQuery {
+    type Output = UserAuthorizationContext;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        // The generation is read before computing, so a role change while
+        // this runs leaves the entry stale rather than current.
+        let generation = match self.read_cached(app_state).await {
+            Ok((_, Some(context))) => return Ok(context),
+            Ok((generation, None)) => Some(generation),
+            Err(e) => {
+                tracing::warn!("Failed to read a cached authorization context: {}", e);
+                None
+            }
+        };
+
+        let context = self.compute(app_state).await?;
+
+        if let Some(generation) = generation {
+            let entry = CachedAuthorizationContext {
+                generation,
+                context,
+            };
+            if let Err(e) = app_state
+                .redis
+                .set_json_with_ttl(
+                    &authorization_context_key(self.deployment_id, self.user_id),
+                    &entry,
+                    AUTHORIZATION_CONTEXT_TTL,
+                )
+                .await
+            {
+                tracing::warn!("Failed to cache an authorization context: {}", e);
+            }
+            return Ok(entry.context);
+        }
+
+        Ok(context)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{
+    error::AppError,
+    models::{UsernameAvailability, UsernameSettings},
+    queries::{GetDeploymentAuthSettingsQuery, Query},
+    state::AppState,
+    utils::validation::{UserValidator, ValidationError, normalize_username, validation_failed},
+};
+
+/// The unique index that keeps usernames case-insensitively unique within a
+/// deployment.
+pub(crate) const USERNAME_UNIQUE_INDEX: &str = "idx_users_deployment_lower_username_unique";
+
+fn username_taken() -> ValidationError {
+    ValidationError::rule("username", "taken", "This username is already taken")
+}
+
+/// Turns a write that lost the race for a username into the same field
+/// error the availability check gives.
+pub(crate) fn map_username_conflict(err: sqlx::Error) -> AppError {
+    if let sqlx::Error::Database(db_err) = &err
+        && db_err.constraint() == Some(USERNAME_UNIQUE_INDEX)
+    {
+        return validation_failed(vec![username_taken()]);
+    }
+    err.into()
+}
+
+/// Checks a username against the deployment's username rules, its reserved
+/// names and the usernames its users already have. Usernames are compared
+/// after normalization and case-insensitively.
+pub struct CheckUsernameAvailabilityQuery {
+    deployment_id: i64,
+    username: String,
+    excluding_user_id: Option<i64>,
+}
+
+impl CheckUsernameAvailabilityQuery {
+    pub fn new(deployment_id: i64, username: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            username: username.into(),
+            excluding_user_id: None,
+        }
+    }
+
+    /// Ignores the user's own username, for a user renaming themselves.
+    pub fn excluding_user(mut self, user_id: i64) -> Self {
+        self.excluding_user_id = Some(user_id);
+        self
+    }
+
+    /// Runs the check and returns the normalized username to store, or a
+    /// `validation_failed` error listing every rule it breaks.
+    pub async fn ensure_available(
+        &self,
+        app_state: &AppState,
+        settings: &UsernameSettings,
+    ) -> Result<String, AppError> {
+        let (username, errors) = self.check(app_state, settings).await?;
+        if errors.is_empty() {
+            Ok(username)
+        } else {
+            Err(validation_failed(errors))
+        }
+    }
+
+    pub(crate) async fn check(
+        &self,
+        app_state: &AppState,
+        settings: &UsernameSettings,
+    ) -> Result<(String, Vec<ValidationError>), AppError> {
+        let username = normalize_username(&self.username);
+
+        let app_name: Option<String> = sqlx::query_scalar(
+            "SELECT app_name FROM deployment_ui_settings WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+        // "Acme Corp" reserves "acmecorp".
+        let app_names: Vec<String> = app_name
+            .map(|name| name.split_whitespace().collect())
+            .into_iter()
+            .filter(|name: &String| !name.is_empty())
+            .collect();
+
+        let mut errors = UserValidator::validate_username_rules(&username, settings, &app_names);
+        if !errors.is_empty() {
+            return Ok((username, errors));
+        }
+
+        let taken: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM users
+                WHERE deployment_id = $1
+                    AND LOWER(username) = LOWER($2)
+                    AND id IS DISTINCT FROM $3
+                    AND NOT identifiers_released
+            )
+            "#,
+        )
+        .bind(self.deployment_id)
+        .bind(&username)
+        .bind(self.excluding_user_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        if taken {
+            errors.push(username_taken());
+        }
+
+        Ok((username, errors))
+    }
+}
+
+impl Query for CheckUsernameAvailabilityQuery {
+    type Output = UsernameAvailability;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let auth_settings = GetDeploymentAuthSettingsQuery::new(self.deployment_id)
+            .execute(app_state)
+            .await?;
+
+        if !auth_settings.username.enabled {
+            return Err(AppError::BadRequest(
+                "Usernames are disabled for this deployment".to_string(),
+            ));
+        }
+
+        let (username, errors) = self.check(app_state, &auth_settings.username).await?;
+
+        Ok(UsernameAvailability {
+            username,
+            available: errors.is_empty(),
+            errors: errors.into_iter().map(Into::into).collect(),
+        })
+    }
+}
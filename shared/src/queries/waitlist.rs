@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use sqlx::{Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{
+        DeploymentRestrictionsSignUpMode, DeploymentWaitlistUser, SignUpEligibility,
+        WaitlistEntryStatus,
+    },
+    queries::Query,
+    state::AppState,
+};
+
+pub(crate) const WAITLIST_ENTRY_COLUMNS: &str = r#"
+    id, created_at, updated_at, deployment_id, email_address, first_name, last_name,
+    status, metadata, invitation_id, reviewed_at
+"#;
+
+pub(crate) fn waitlist_entry_from_row(row: &PgRow) -> DeploymentWaitlistUser {
+    DeploymentWaitlistUser {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        deployment_id: row.get("deployment_id"),
+        email_address: row
+            .get::<Option<String>, _>("email_address")
+            .unwrap_or_default(),
+        first_name: row
+            .get::<Option<String>, _>("first_name")
+            .unwrap_or_default(),
+        last_name: row
+            .get::<Option<String>, _>("last_name")
+            .unwrap_or_default(),
+        status: WaitlistEntryStatus::from_str(row.get("status")).unwrap_or_default(),
+        metadata: row.get("metadata"),
+        invitation_id: row.get("invitation_id"),
+        reviewed_at: row.get("reviewed_at"),
+    }
+}
+
+pub struct ListWaitlistEntriesQuery {
+    deployment_id: i64,
+    status: Option<WaitlistEntryStatus>,
+    offset: i64,
+    limit: i64,
+    sort_key: Option<String>,
+    sort_order: Option<String>,
+}
+
+impl ListWaitlistEntriesQuery {
+    pub fn new(deployment_id: i64) -> Self {
+        Self {
+            deployment_id,
+            status: None,
+            offset: 0,
+            limit: 10,
+            sort_key: None,
+            sort_order: None,
+        }
+    }
+
+    pub fn status(self, status: Option<WaitlistEntryStatus>) -> Self {
+        Self { status, ..self }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+
+    pub fn sort_key(self, sort_key: Option<String>) -> Self {
+        Self { sort_key, ..self }
+    }
+
+    pub fn sort_order(self, sort_order: Option<String>) -> Self {
+        Self { sort_order, ..self }
+    }
+}
+
+impl Query for ListWaitlistEntriesQuery {
+    type Output = Vec<DeploymentWaitlistUser>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut query_builder = sqlx::QueryBuilder::new(format!(
+            "SELECT {} FROM deployment_waitlist_users WHERE deployment_id = ",
+            WAITLIST_ENTRY_COLUMNS
+        ));
+        query_builder.push_bind(self.deployment_id);
+
+        if let Some(status) = self.status {
+            query_builder.push(" AND status = ");
+            query_builder.push_bind(status.as_str());
+        }
+
+        query_builder.push(" ORDER BY ");
+        match self.sort_key.as_deref() {
+            Some("email") => query_builder.push("email_address"),
+            _ => query_builder.push("created_at"),
+        };
+        match self.sort_order.as_deref().map(str::to_lowercase).as_deref() {
+            Some("asc") => query_builder.push(" ASC"),
+            _ => query_builder.push(" DESC"),
+        };
+        query_builder.push(", id");
+
+        query_builder.push(" LIMIT ");
+        query_builder.push_bind(self.limit);
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(self.offset);
+
+        let rows = query_builder.build().fetch_all(&app_state.db_pool).await?;
+
+        Ok(rows.iter().map(waitlist_entry_from_row).collect())
+    }
+}
+
+/// Decides whether an email address may sign up. Public deployments let
+/// everyone through; restricted and waitlist-only ones need an invitation
+/// token issued for the same address.
+pub struct CheckSignUpAllowedQuery {
+    deployment_id: i64,
+    email_address: String,
+    invitation_token: Option<String>,
+}
+
+impl CheckSignUpAllowedQuery {
+    pub fn new(deployment_id: i64, email_address: impl Into<String>) -> Self {
+        Self {
+            deployment_id,
+            email_address: email_address.into(),
+            invitation_token: None,
+        }
+    }
+
+    pub fn with_invitation_token(mut self, invitation_token: Option<String>) -> Self {
+        self.invitation_token = invitation_token;
+        self
+    }
+}
+
+impl Query for CheckSignUpAllowedQuery {
+    type Output = SignUpEligibility;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let sign_up_mode: Option<String> = sqlx::query_scalar(
+            "SELECT sign_up_mode FROM deployment_restrictions WHERE deployment_id = $1",
+        )
+        .bind(self.deployment_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+        let sign_up_mode = sign_up_mode
+            .and_then(|mode| DeploymentRestrictionsSignUpMode::from_str(&mode).ok())
+            .unwrap_or_default();
+
+        if matches!(sign_up_mode, DeploymentRestrictionsSignUpMode::Public) {
+            return Ok(SignUpEligibility {
+                allowed: true,
+                sign_up_mode,
+                invitation_id: None,
+            });
+        }
+
+        let Some(token) = &self.invitation_token else {
+            return Ok(SignUpEligibility {
+                allowed: false,
+                sign_up_mode,
+                invitation_id: None,
+            });
+        };
+
+        let claims = app_state.invitation_token_signer.verify(token)?;
+        if claims.deployment_id != self.deployment_id
+            || !claims
+                .email_address
+                .eq_ignore_ascii_case(self.email_address.trim())
+        {
+            return Err(AppError::BadRequest(
+                "The invitation was issued for a different email address".to_string(),
+            ));
+        }
+
+        // The token outlives revocation and acceptance, so it isn't enough
+        // on its own.
+        let invitation_exists: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM deployment_invitations
+                WHERE id = $1 AND deployment_id = $2
+                  AND revoked_at IS NULL AND accepted_at IS NULL
+            )
+            "#,
+        )
+        .bind(claims.invitation_id)
+        .bind(self.deployment_id)
+        .fetch_one(&app_state.db_pool)
+        .await?;
+
+        Ok(SignUpEligibility {
+            allowed: invitation_exists,
+            sign_up_mode,
+            invitation_id: invitation_exists.then_some(claims.invitation_id),
+        })
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use sqlx::{PgConnection, Row, postgres::PgRow};
+
+use crate::{
+    error::AppError,
+    models::{WorkspaceMemberDetails, WorkspacePermission, WorkspaceRole},
+    queries::Query,
+    state::AppState,
+};
+
+const WORKSPACE_MEMBER_SELECT: &str = r#"
+    SELECT
+        wm.id, wm.created_at, wm.updated_at, wm.workspace_id, wm.user_id,
+        u.first_name, u.last_name, u.username, u.created_at AS user_created_at,
+        e.email_address AS primary_email_address,
+        p.phone_number AS primary_phone_number
+    FROM workspace_memberships wm
+    JOIN workspaces w ON w.id = wm.workspace_id AND w.deleted_at IS NULL
+    JOIN users u ON u.id = wm.user_id AND u.deleted_at IS NULL
+    LEFT JOIN user_email_addresses e ON e.id = u.primary_email_address_id
+    LEFT JOIN user_phone_numbers p ON p.id = u.primary_phone_number_id
+"#;
+
+fn workspace_member_from_row(row: &PgRow) -> WorkspaceMemberDetails {
+    let username = row
+        .get::<Option<String>, _>("username")
+        .filter(|username| !username.is_empty());
+
+    WorkspaceMemberDetails {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        workspace_id: row.get("workspace_id"),
+        user_id: row.get("user_id"),
+        roles: vec![],
+        first_name: row
+            .get::<Option<String>, _>("first_name")
+            .unwrap_or_default(),
+        last_name: row
+            .get::<Option<String>, _>("last_name")
+            .unwrap_or_default(),
+        username,
+        primary_email_address: row.get("primary_email_address"),
+        primary_phone_number: row.get("primary_phone_number"),
+        user_created_at: row.get("user_created_at"),
+    }
+}
+
+async fn attach_roles(
+    conn: &mut PgConnection,
+    members: &mut [WorkspaceMemberDetails],
+) -> Result<(), AppError> {
+    let membership_ids: Vec<i64> = members.iter().map(|member| member.id).collect();
+
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            wmr.workspace_membership_id,
+            r.id, r.created_at, r.updated_at, r.name, r.permissions
+        FROM workspace_membership_roles wmr
+        JOIN workspace_roles r ON r.id = wmr.workspace_role_id
+        WHERE wmr.workspace_membership_id = ANY($1)
+        ORDER BY r.name
+        "#,
+    )
+    .bind(&membership_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut roles_by_membership: HashMap<i64, Vec<WorkspaceRole>> = HashMap::new();
+
+    for row in rows {
+        let id: i64 = row.get("id");
+        let created_at = row.get("created_at");
+        let updated_at = row.get("updated_at");
+        let permissions: Vec<String> = row.get("permissions");
+
+        roles_by_membership
+            .entry(row.get("workspace_membership_id"))
+            .or_default()
+            .push(WorkspaceRole {
+                id,
+                created_at,
+                updated_at,
+                name: row.get("name"),
+                permissions: permissions
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, permission)| WorkspacePermission {
+                        id: i as i64,
+                        created_at,
+                        updated_at,
+                        workspace_role_id: id,
+                        permission,
+                    })
+                    .collect(),
+            });
+    }
+
+    for member in members.iter_mut() {
+        member.roles = roles_by_membership.remove(&member.id).unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+/// The workspace counterpart of `fetch_organization_member`.
+pub(crate) async fn fetch_workspace_member(
+    conn: &mut PgConnection,
+    deployment_id: i64,
+    workspace_id: i64,
+    membership_id: i64,
+) -> Result<Option<WorkspaceMemberDetails>, AppError> {
+    let row = sqlx::query(&format!(
+        "{} WHERE wm.id = $1 AND wm.workspace_id = $2 AND w.deployment_id = $3",
+        WORKSPACE_MEMBER_SELECT
+    ))
+    .bind(membership_id)
+    .bind(workspace_id)
+    .bind(deployment_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let mut members = [workspace_member_from_row(&row)];
+    attach_roles(conn, &mut members).await?;
+
+    let [member] = members;
+    Ok(Some(member))
+}
+
+pub struct ListWorkspaceMembersQuery {
+    deployment_id: i64,
+    workspace_id: i64,
+    role_id: Option<i64>,
+    search: Option<String>,
+    offset: i64,
+    limit: i64,
+}
+
+impl ListWorkspaceMembersQuery {
+    pub fn new(deployment_id: i64, workspace_id: i64) -> Self {
+        Self {
+            deployment_id,
+            workspace_id,
+            role_id: None,
+            search: None,
+            offset: 0,
+            limit: 10,
+        }
+    }
+
+    pub fn role_id(self, role_id: Option<i64>) -> Self {
+        Self { role_id, ..self }
+    }
+
+    pub fn search(self, search: Option<String>) -> Self {
+        Self { search, ..self }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self { limit, ..self }
+    }
+}
+
+impl Query for ListWorkspaceMembersQuery {
+    type Output = Vec<WorkspaceMemberDetails>;
+
+    async fn run(&self, app_state: &AppState) -> Result<Self::Output, AppError> {
+        let mut query_builder = sqlx::QueryBuilder::new(WORKSPACE_MEMBER_SELECT);
+        query_builder.push(" WHERE wm.workspace_id = ");
+        query_builder.push_bind(self.workspace_id);
+        query_builder.push(" AND w.deployment_id = ");
+        query_builder.push_bind(self.deployment_id);
+
+        if let Some(role_id) = self.role_id {
+            query_builder.push(
+                " AND EXISTS (SELECT 1 FROM workspace_membership_roles wmr \
+                 WHERE wmr.workspace_membership_id = wm.id AND wmr.workspace_role_id = ",
+            );
+            query_builder.push_bind(role_id);
+            query_builder.push(")");
+        }
+
+        if let Some(search) = self
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|search| !search.is_empty())
+        {
+            let pattern = format!("%{}%", search);
+            query_builder.push(" AND (concat_ws(' ', u.first_name, u.last_name) ILIKE ");
+            query_builder.push_bind(pattern.clone());
+            query_builder.push(" OR u.username ILIKE ");
+            query_builder.push_bind(pattern.clone());
+            query_builder.push(" OR e.email_address ILIKE ");
+            query_builder.push_bind(pattern);
+            query_builder.push(")");
+        }
+
+        query_builder.push(" ORDER BY wm.created_at DESC, wm.id LIMIT ");
+        query_builder.push_bind(self.limit);
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(self.offset);
+
+        let mut conn = app_state.db_pool.acquire().await?;
+
+        let rows = query_builder.build().fetch_all(&mut *conn).await?;
+        let mut members: Vec<WorkspaceMemberDetails> =
+            rows.iter().map(workspace_member_from_row).collect();
+
+        attach_roles(&mut conn, &mut members).await?;
+
+        Ok(members)
+    }
+}
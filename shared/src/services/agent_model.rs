@@ -0,0 +1,176 @@
+//! The chat model agents are invoked against, from any server with an
+//! OpenAI-compatible `/chat/completions` endpoint.
+//!
+//! `AGENT_MODEL_BASE_URL` and `AGENT_MODEL_API_KEY` pick the server, falling
+//! back to `OPENAI_BASE_URL` and `OPENAI_API_KEY`. `AGENT_MODEL` names the
+//! model used when an agent's configuration doesn't name one.
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    error::{AppError, ExternalError},
+    services::http_client::provider_client,
+};
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// The model's reply, with the tokens it was billed for.
+#[derive(Debug, Clone)]
+pub struct ChatCompletion {
+    pub model: String,
+    pub content: String,
+    pub prompt_tokens: i32,
+    pub completion_tokens: i32,
+}
+
+#[derive(Clone)]
+pub struct AgentModelService {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    default_model: String,
+}
+
+impl AgentModelService {
+    pub fn new(base_url: String, api_key: Option<String>, default_model: String) -> Self {
+        Self {
+            client: provider_client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            default_model,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("AGENT_MODEL_BASE_URL")
+            .or_else(|_| std::env::var("OPENAI_BASE_URL"))
+            .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        let api_key = std::env::var("AGENT_MODEL_API_KEY")
+            .or_else(|_| std::env::var("OPENAI_API_KEY"))
+            .ok();
+        let default_model =
+            std::env::var("AGENT_MODEL").unwrap_or_else(|_| DEFAULT_MODEL.to_string());
+
+        Self::new(base_url, api_key, default_model)
+    }
+
+    /// One completion of `messages`. Not retried: the provider may have
+    /// billed for a request whose response never arrived.
+    pub async fn complete(
+        &self,
+        model: Option<&str>,
+        messages: &[ChatMessage],
+    ) -> Result<ChatCompletion, AppError> {
+        let model = model.unwrap_or(&self.default_model);
+
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&json!({ "model": model, "messages": messages }));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            AppError::External(ExternalError::from(format!(
+                "Agent model is unreachable: {}",
+                e
+            )))
+        })?;
+
+        let status = response.status();
+        let body = response.text().await.map_err(|e| {
+            AppError::External(ExternalError::from(format!(
+                "Agent model response could not be read: {}",
+                e
+            )))
+        })?;
+
+        if !status.is_success() {
+            return Err(AppError::External(ExternalError::from(format!(
+                "Agent model failed with HTTP {}: {}",
+                status.as_u16(),
+                body.chars().take(500).collect::<String>()
+            ))));
+        }
+
+        let body: Value = serde_json::from_str(&body).map_err(|e| {
+            AppError::External(ExternalError::from(format!(
+                "Agent model response isn't JSON: {}",
+                e
+            )))
+        })?;
+
+        parse_completion(model, &body)
+    }
+}
+
+fn parse_completion(model: &str, body: &Value) -> Result<ChatCompletion, AppError> {
+    let content = body
+        .pointer("/choices/0/message/content")
+        .and_then(Value::as_str)
+        .ok_or_else(|| {
+            AppError::External(ExternalError::from(
+                "Agent model response has no message".to_string(),
+            ))
+        })?;
+    let tokens = |field: &str| {
+        body.pointer(&format!("/usage/{}", field))
+            .and_then(Value::as_i64)
+            .map_or(0, |tokens| tokens.clamp(0, i32::MAX as i64) as i32)
+    };
+
+    Ok(ChatCompletion {
+        model: body
+            .get("model")
+            .and_then(Value::as_str)
+            .unwrap_or(model)
+            .to_string(),
+        content: content.to_string(),
+        prompt_tokens: tokens("prompt_tokens"),
+        completion_tokens: tokens("completion_tokens"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completion_carries_the_billed_tokens() {
+        let body = json!({
+            "model": "gpt-4o-mini-2024-07-18",
+            "choices": [{ "message": { "role": "assistant", "content": "Hello" } }],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15 },
+        });
+
+        let completion = parse_completion("gpt-4o-mini", &body).unwrap();
+        assert_eq!(completion.model, "gpt-4o-mini-2024-07-18");
+        assert_eq!(completion.content, "Hello");
+        assert_eq!(completion.prompt_tokens, 12);
+        assert_eq!(completion.completion_tokens, 3);
+    }
+
+    #[test]
+    fn missing_message_is_an_error() {
+        assert!(parse_completion("gpt-4o-mini", &json!({ "choices": [] })).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use std::future::Future;
+
+use tokio::time::Instant;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+/// Work that outlives the request that started it, such as workflow runs and
+/// re-embedding jobs.
+///
+/// Tasks spawned here are waited on at shutdown. Long tasks should check
+/// [`BackgroundTasks::is_shutting_down`] between steps and save enough state
+/// to pick up where they left off.
+#[derive(Clone, Default)]
+pub struct BackgroundTasks {
+    shutdown: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl BackgroundTasks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(task);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    /// Resolves once shutdown begins, for tasks that sleep between steps.
+    pub async fn shutdown_requested(&self) {
+        self.shutdown.cancelled().await
+    }
+
+    /// Tells running tasks to wrap up. New tasks can still be spawned, but
+    /// will see the shutdown at their first check.
+    pub fn begin_shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Waits for every task to finish. Returns `false` if some were still
+    /// running at the deadline.
+    pub async fn wait_until(&self, deadline: Instant) -> bool {
+        self.tracker.close();
+        tokio::time::timeout_at(deadline, self.tracker.wait())
+            .await
+            .is_ok()
+    }
+}
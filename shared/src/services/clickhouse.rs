@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use crate::error::AppError;
 use chrono::{DateTime, Utc};
 use clickhouse::{Client, Row};
@@ -20,11 +22,88 @@ pub struct UserEvent {
     pub ip_address: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct AuthEventRow {
+    pub deployment_id: i64,
+    pub user_id: Option<i64>,
+    pub event_type: String,
+    pub user_name: Option<String>,
+    pub user_email: Option<String>,
+    pub auth_method: Option<String>,
+    /// Milliseconds since epoch, matching the `DateTime64(3)` column.
+    pub timestamp: i64,
+    pub ip_address: Option<String>,
+    pub attributes: Vec<(String, String)>,
+}
+
+/// One billable event. `user_id` is set for active users and
+/// `template_name` for sent emails.
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct UsageEventRow {
+    pub deployment_id: i64,
+    pub metric: String,
+    pub user_id: Option<i64>,
+    pub template_name: Option<String>,
+    /// Milliseconds since epoch, matching the `DateTime64(3)` column.
+    pub timestamp: i64,
+}
+
+/// Usage of one deployment over a time range.
+#[derive(Debug, Clone, Default)]
+pub struct UsageTotals {
+    pub active_users: i64,
+    pub emails_by_template: Vec<(String, i64)>,
+}
+
+/// Request logs older than this are dropped by the table's TTL.
+pub const REQUEST_LOG_RETENTION_DAYS: u32 = 30;
+
+/// One API call. Only metadata is kept: request headers and bodies are never
+/// written, and `error` holds the error code and message of non-2xx responses.
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct RequestLogRow {
+    pub id: i64,
+    pub deployment_id: i64,
+    pub api_key_id: Option<i64>,
+    pub method: String,
+    /// The route template, e.g. `/deployments/{deployment_id}/users/{user_id}`.
+    pub path: String,
+    pub status: u16,
+    pub latency_ms: u32,
+    pub error: Option<String>,
+    /// Milliseconds since epoch, matching the `DateTime64(3)` column.
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RequestLogFilter {
+    /// 2 for 2xx, 4 for 4xx and so on.
+    pub status_class: Option<u16>,
+    pub path_prefix: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Only rows strictly older than this `(timestamp, id)` position.
+    pub before: Option<(i64, i64)>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Row)]
 struct CountResult {
     count: i64,
 }
 
+#[derive(Debug, Serialize, Deserialize, Row)]
+struct DeploymentCountRow {
+    deployment_id: i64,
+    count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Row)]
+struct TemplateCountRow {
+    deployment_id: i64,
+    template_name: String,
+    count: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RecentSignup {
     pub name: Option<String>,
@@ -52,8 +131,33 @@ impl ClickHouseService {
         Ok(Self { client })
     }
 
+    pub async fn ping(&self) -> Result<(), AppError> {
+        self.client.query("SELECT 1").execute().await?;
+        Ok(())
+    }
+
     pub async fn init_tables(&self) -> Result<(), AppError> {
         self.create_user_events_table().await?;
+        self.add_user_events_attributes_column().await?;
+        self.create_request_logs_table().await?;
+        self.create_usage_events_table().await?;
+        Ok(())
+    }
+
+    async fn create_usage_events_table(&self) -> Result<(), AppError> {
+        let query = r#"
+            CREATE TABLE IF NOT EXISTS usage_events (
+                deployment_id Int64,
+                metric LowCardinality(String),
+                user_id Nullable(Int64),
+                template_name Nullable(String),
+                timestamp DateTime64(3, 'UTC')
+            ) ENGINE = MergeTree()
+            ORDER BY (deployment_id, metric, timestamp)
+            PARTITION BY toYYYYMM(timestamp)
+        "#;
+
+        self.client.query(query).execute().await?;
         Ok(())
     }
 
@@ -68,6 +172,7 @@ impl ClickHouseService {
                 auth_method Nullable(String),
                 timestamp DateTime64(3, 'UTC'),
                 ip_address Nullable(String),
+                attributes Map(String, String),
                 INDEX idx_event_type event_type TYPE bloom_filter GRANULARITY 1,
                 INDEX idx_user_id user_id TYPE bloom_filter GRANULARITY 1
             ) ENGINE = MergeTree()
@@ -79,6 +184,244 @@ impl ClickHouseService {
         Ok(())
     }
 
+    async fn add_user_events_attributes_column(&self) -> Result<(), AppError> {
+        self.client
+            .query(
+                "ALTER TABLE user_events ADD COLUMN IF NOT EXISTS attributes Map(String, String)",
+            )
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    /// Also re-applies the TTL, so a changed retention period reaches tables
+    /// created by older versions.
+    async fn create_request_logs_table(&self) -> Result<(), AppError> {
+        let query = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS request_logs (
+                id Int64,
+                deployment_id Int64,
+                api_key_id Nullable(Int64),
+                method LowCardinality(String),
+                path String,
+                status UInt16,
+                latency_ms UInt32,
+                error Nullable(String),
+                timestamp DateTime64(3, 'UTC')
+            ) ENGINE = MergeTree()
+            ORDER BY (deployment_id, timestamp, id)
+            PARTITION BY toYYYYMMDD(timestamp)
+            TTL toDateTime(timestamp) + INTERVAL {days} DAY
+        "#,
+            days = REQUEST_LOG_RETENTION_DAYS
+        );
+        self.client.query(&query).execute().await?;
+
+        self.client
+            .query(&format!(
+                "ALTER TABLE request_logs MODIFY TTL toDateTime(timestamp) + INTERVAL {} DAY",
+                REQUEST_LOG_RETENTION_DAYS
+            ))
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    pub async fn insert_auth_events(&self, events: &[AuthEventRow]) -> Result<(), AppError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("user_events")?;
+        for event in events {
+            insert.write(event).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    pub async fn insert_request_logs(&self, logs: &[RequestLogRow]) -> Result<(), AppError> {
+        if logs.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("request_logs")?;
+        for log in logs {
+            insert.write(log).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    pub async fn insert_usage_events(&self, events: &[UsageEventRow]) -> Result<(), AppError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("usage_events")?;
+        for event in events {
+            insert.write(event).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Usage in `[from, to)` for every deployment with any, or only for
+    /// `deployment_id` when given. Active users are counted exactly.
+    pub async fn get_usage(
+        &self,
+        deployment_id: Option<i64>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<HashMap<i64, UsageTotals>, AppError> {
+        let from = from.format("%Y-%m-%d %H:%M:%S").to_string();
+        let to = to.format("%Y-%m-%d %H:%M:%S").to_string();
+        let deployment_filter = if deployment_id.is_some() {
+            "AND deployment_id = ?"
+        } else {
+            ""
+        };
+
+        let active_users_query = format!(
+            "SELECT deployment_id, toInt64(uniqExact(user_id)) as count FROM usage_events WHERE metric = 'active_user' AND timestamp >= ? AND timestamp < ? AND user_id IS NOT NULL {} GROUP BY deployment_id",
+            deployment_filter
+        );
+        let mut active_users = self.client.query(&active_users_query).bind(&from).bind(&to);
+        if let Some(deployment_id) = deployment_id {
+            active_users = active_users.bind(deployment_id);
+        }
+
+        let emails_query = format!(
+            "SELECT deployment_id, ifNull(template_name, '') as template_name, toInt64(count()) as count FROM usage_events WHERE metric = 'email_sent' AND timestamp >= ? AND timestamp < ? {} GROUP BY deployment_id, template_name",
+            deployment_filter
+        );
+        let mut emails = self.client.query(&emails_query).bind(&from).bind(&to);
+        if let Some(deployment_id) = deployment_id {
+            emails = emails.bind(deployment_id);
+        }
+
+        let mut totals: HashMap<i64, UsageTotals> = HashMap::new();
+        for row in active_users.fetch_all::<DeploymentCountRow>().await? {
+            totals.entry(row.deployment_id).or_default().active_users = row.count;
+        }
+        for row in emails.fetch_all::<TemplateCountRow>().await? {
+            totals
+                .entry(row.deployment_id)
+                .or_default()
+                .emails_by_template
+                .push((row.template_name, row.count));
+        }
+
+        Ok(totals)
+    }
+
+    /// Whether the user already counts as active for the deployment in
+    /// `[from, to)`.
+    pub async fn is_active_user(
+        &self,
+        deployment_id: i64,
+        user_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let query = "SELECT toInt64(count()) as count FROM usage_events WHERE deployment_id = ? AND metric = 'active_user' AND user_id = ? AND timestamp >= ? AND timestamp < ?";
+
+        let result = self
+            .client
+            .query(query)
+            .bind(deployment_id)
+            .bind(user_id)
+            .bind(from.format("%Y-%m-%d %H:%M:%S").to_string())
+            .bind(to.format("%Y-%m-%d %H:%M:%S").to_string())
+            .fetch_one::<CountResult>()
+            .await?;
+
+        Ok(result.count > 0)
+    }
+
+    /// Newest first.
+    pub async fn list_request_logs(
+        &self,
+        deployment_id: i64,
+        filter: &RequestLogFilter,
+        limit: u32,
+    ) -> Result<Vec<RequestLogRow>, AppError> {
+        let mut conditions = vec!["deployment_id = ?"];
+        if filter.status_class.is_some() {
+            conditions.push("intDiv(status, 100) = ?");
+        }
+        if filter.path_prefix.is_some() {
+            conditions.push("startsWith(path, ?)");
+        }
+        if filter.from.is_some() {
+            conditions.push("timestamp >= ?");
+        }
+        if filter.to.is_some() {
+            conditions.push("timestamp <= ?");
+        }
+        if filter.before.is_some() {
+            conditions.push("(timestamp, id) < (fromUnixTimestamp64Milli(toInt64(?), 'UTC'), ?)");
+        }
+
+        let query = format!(
+            "SELECT ?fields FROM request_logs WHERE {} ORDER BY timestamp DESC, id DESC LIMIT ?",
+            conditions.join(" AND ")
+        );
+
+        let mut query = self.client.query(&query).bind(deployment_id);
+        if let Some(status_class) = filter.status_class {
+            query = query.bind(status_class);
+        }
+        if let Some(path_prefix) = &filter.path_prefix {
+            query = query.bind(path_prefix);
+        }
+        if let Some(from) = filter.from {
+            query = query.bind(from.format("%Y-%m-%d %H:%M:%S%.3f").to_string());
+        }
+        if let Some(to) = filter.to {
+            query = query.bind(to.format("%Y-%m-%d %H:%M:%S%.3f").to_string());
+        }
+        if let Some((timestamp, id)) = filter.before {
+            query = query.bind(timestamp).bind(id);
+        }
+
+        Ok(query.bind(limit).fetch_all::<RequestLogRow>().await?)
+    }
+
+    /// Oldest first, for exports: rows strictly after the `(timestamp, id)`
+    /// position `after`, up to and including `through` (milliseconds since
+    /// epoch).
+    pub async fn list_request_logs_after(
+        &self,
+        deployment_id: i64,
+        after: Option<(i64, i64)>,
+        through: i64,
+        limit: u32,
+    ) -> Result<Vec<RequestLogRow>, AppError> {
+        let (after_timestamp, after_id) = after.unwrap_or((i64::MIN, i64::MIN));
+
+        Ok(self
+            .client
+            .query(
+                r#"
+                SELECT ?fields FROM request_logs
+                WHERE deployment_id = ?
+                    AND (toUnixTimestamp64Milli(timestamp), id) > (?, ?)
+                    AND toUnixTimestamp64Milli(timestamp) <= ?
+                ORDER BY timestamp ASC, id ASC
+                LIMIT ?
+                "#,
+            )
+            .bind(deployment_id)
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(through)
+            .bind(limit)
+            .fetch_all::<RequestLogRow>()
+            .await?)
+    }
+
     pub async fn insert_user_event(&self, event: &UserEvent) -> Result<(), AppError> {
         let mut insert = self.client.insert("user_events")?;
         insert.write(event).await?;
@@ -86,6 +429,34 @@ impl ClickHouseService {
         Ok(())
     }
 
+    /// Queues the deletion of every event recorded for a user and returns how
+    /// many it covers. ClickHouse runs the mutation in the background, so the
+    /// events stay readable until it finishes.
+    pub async fn delete_user_events(
+        &self,
+        deployment_id: i64,
+        user_id: i64,
+    ) -> Result<u64, AppError> {
+        let result = self
+            .client
+            .query(
+                "SELECT count() as count FROM user_events WHERE deployment_id = ? AND user_id = ?",
+            )
+            .bind(deployment_id)
+            .bind(user_id)
+            .fetch_one::<CountResult>()
+            .await?;
+
+        self.client
+            .query("ALTER TABLE user_events DELETE WHERE deployment_id = ? AND user_id = ?")
+            .bind(deployment_id)
+            .bind(user_id)
+            .execute()
+            .await?;
+
+        Ok(result.count.max(0) as u64)
+    }
+
     pub async fn get_total_signups(&self, deployment_id: i64) -> Result<i64, AppError> {
         let query = "SELECT count(DISTINCT user_id) as count FROM user_events WHERE deployment_id = ? AND event_type = 'signup' AND user_id IS NOT NULL";
 
@@ -119,6 +490,49 @@ impl ClickHouseService {
         Ok(result.count)
     }
 
+    /// Distinct users who signed in during the last 30 days, for each of the
+    /// deployments in one query. Deployments without any are left out.
+    /// Active users metered in `[from, to)` across `deployment_ids`, each
+    /// deployment's users counted exactly.
+    pub async fn get_active_users(
+        &self,
+        deployment_ids: &[i64],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<i64, AppError> {
+        let query = "SELECT toInt64(count()) as count FROM (SELECT DISTINCT deployment_id, user_id FROM usage_events WHERE has(?, deployment_id) AND metric = 'active_user' AND timestamp >= ? AND timestamp < ? AND user_id IS NOT NULL)";
+
+        let result = self
+            .client
+            .query(query)
+            .bind(deployment_ids)
+            .bind(from.format("%Y-%m-%d %H:%M:%S").to_string())
+            .bind(to.format("%Y-%m-%d %H:%M:%S").to_string())
+            .fetch_one::<CountResult>()
+            .await?;
+
+        Ok(result.count)
+    }
+
+    pub async fn get_monthly_active_users(
+        &self,
+        deployment_ids: &[i64],
+    ) -> Result<Vec<(i64, i64)>, AppError> {
+        let query = "SELECT deployment_id, count(DISTINCT user_id) as count FROM user_events WHERE has(?, deployment_id) AND event_type = 'signin' AND timestamp >= now() - INTERVAL 30 DAY AND user_id IS NOT NULL GROUP BY deployment_id";
+
+        let rows = self
+            .client
+            .query(query)
+            .bind(deployment_ids)
+            .fetch_all::<DeploymentCountRow>()
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.deployment_id, row.count))
+            .collect())
+    }
+
     pub async fn get_signups(
         &self,
         deployment_id: i64,
@@ -204,6 +618,4 @@ impl ClickHouseService {
             })
             .collect())
     }
-
-
 }
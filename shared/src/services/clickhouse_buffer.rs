@@ -0,0 +1,257 @@
+use std::future::Future;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::Duration;
+
+use tokio::sync::{
+    Mutex,
+    mpsc::{self, error::TrySendError},
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::clickhouse::{AuthEventRow, ClickHouseService, RequestLogRow, UsageEventRow};
+use crate::error::AppError;
+
+#[derive(Debug, Clone)]
+pub struct ClickHouseBufferConfig {
+    pub capacity: usize,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for ClickHouseBufferConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            batch_size: 500,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ClickHouseBufferConfig {
+    /// Reads `{prefix}_BUFFER_CAPACITY`, `{prefix}_BATCH_SIZE` and
+    /// `{prefix}_FLUSH_INTERVAL_SECS`, e.g. with the prefix `AUTH_EVENT`.
+    pub fn from_env(prefix: &str) -> Self {
+        let defaults = Self::default();
+
+        let read = |key: &str| {
+            std::env::var(format!("{}_{}", prefix, key))
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+        };
+
+        Self {
+            capacity: read("BUFFER_CAPACITY")
+                .map(|v| v as usize)
+                .unwrap_or(defaults.capacity),
+            batch_size: read("BATCH_SIZE")
+                .map(|v| v as usize)
+                .unwrap_or(defaults.batch_size),
+            flush_interval: read("FLUSH_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.flush_interval),
+        }
+    }
+}
+
+/// A row type [`ClickHouseBuffer`] can batch.
+pub trait BufferedRow: Send + Sync + Sized + 'static {
+    /// Used in log lines, e.g. "auth event".
+    const KIND: &'static str;
+
+    fn deployment_id(&self) -> i64;
+
+    fn insert(
+        clickhouse_service: &ClickHouseService,
+        rows: &[Self],
+    ) -> impl Future<Output = Result<(), AppError>> + Send;
+}
+
+impl BufferedRow for AuthEventRow {
+    const KIND: &'static str = "auth event";
+
+    fn deployment_id(&self) -> i64 {
+        self.deployment_id
+    }
+
+    async fn insert(clickhouse_service: &ClickHouseService, rows: &[Self]) -> Result<(), AppError> {
+        clickhouse_service.insert_auth_events(rows).await
+    }
+}
+
+impl BufferedRow for RequestLogRow {
+    const KIND: &'static str = "request log";
+
+    fn deployment_id(&self) -> i64 {
+        self.deployment_id
+    }
+
+    async fn insert(clickhouse_service: &ClickHouseService, rows: &[Self]) -> Result<(), AppError> {
+        clickhouse_service.insert_request_logs(rows).await
+    }
+}
+
+impl BufferedRow for UsageEventRow {
+    const KIND: &'static str = "usage event";
+
+    fn deployment_id(&self) -> i64 {
+        self.deployment_id
+    }
+
+    async fn insert(clickhouse_service: &ClickHouseService, rows: &[Self]) -> Result<(), AppError> {
+        clickhouse_service.insert_usage_events(rows).await
+    }
+}
+
+/// Buffers rows in memory and writes them to ClickHouse in batches from a
+/// background task, so request handlers never wait on a ClickHouse round trip.
+pub struct ClickHouseBuffer<T> {
+    sender: mpsc::Sender<T>,
+    dropped: Arc<AtomicU64>,
+    closed: CancellationToken,
+    flusher: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl<T> Clone for ClickHouseBuffer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            dropped: self.dropped.clone(),
+            closed: self.closed.clone(),
+            flusher: self.flusher.clone(),
+        }
+    }
+}
+
+pub type AuthEventBuffer = ClickHouseBuffer<AuthEventRow>;
+pub type RequestLogBuffer = ClickHouseBuffer<RequestLogRow>;
+pub type UsageEventBuffer = ClickHouseBuffer<UsageEventRow>;
+
+impl<T: BufferedRow> ClickHouseBuffer<T> {
+    pub fn spawn(clickhouse_service: ClickHouseService, config: ClickHouseBufferConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.capacity.max(1));
+        let closed = CancellationToken::new();
+
+        let flusher = tokio::spawn(Self::run(
+            clickhouse_service,
+            receiver,
+            config.batch_size.max(1),
+            config.flush_interval,
+            closed.clone(),
+        ));
+
+        Self {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+            closed,
+            flusher: Arc::new(Mutex::new(Some(flusher))),
+        }
+    }
+
+    /// Stops accepting rows and waits until everything already queued has
+    /// been written. Rows pushed afterwards are dropped.
+    pub async fn close(&self) {
+        self.closed.cancel();
+
+        if let Some(flusher) = self.flusher.lock().await.take()
+            && let Err(e) = flusher.await
+        {
+            tracing::error!("{} flusher stopped unexpectedly: {}", T::KIND, e);
+        }
+    }
+
+    /// Queues a row without blocking. When the buffer is full the row is
+    /// dropped and counted rather than applying backpressure to the caller.
+    pub fn push(&self, row: T) -> bool {
+        match self.sender.try_send(row) {
+            Ok(()) => true,
+            Err(TrySendError::Full(row)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                tracing::warn!(
+                    deployment_id = row.deployment_id(),
+                    dropped_total = dropped,
+                    "{} buffer full, dropping row",
+                    T::KIND
+                );
+                false
+            }
+            Err(TrySendError::Closed(row)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                tracing::error!(
+                    deployment_id = row.deployment_id(),
+                    dropped_total = dropped,
+                    "{} flusher is not running, dropping row",
+                    T::KIND
+                );
+                false
+            }
+        }
+    }
+
+    pub fn dropped_rows(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    async fn run(
+        clickhouse_service: ClickHouseService,
+        mut receiver: mpsc::Receiver<T>,
+        batch_size: usize,
+        flush_interval: Duration,
+        closed: CancellationToken,
+    ) {
+        let mut batch: Vec<T> = Vec::with_capacity(batch_size);
+        let mut ticker = tokio::time::interval(flush_interval);
+
+        loop {
+            tokio::select! {
+                row = receiver.recv() => match row {
+                    Some(row) => {
+                        batch.push(row);
+                        if batch.len() >= batch_size {
+                            Self::flush(&clickhouse_service, &mut batch).await;
+                        }
+                    }
+                    None => {
+                        Self::flush(&clickhouse_service, &mut batch).await;
+                        break;
+                    }
+                },
+                _ = ticker.tick() => {
+                    Self::flush(&clickhouse_service, &mut batch).await;
+                }
+                _ = closed.cancelled() => {
+                    receiver.close();
+                    while let Some(row) = receiver.recv().await {
+                        batch.push(row);
+                        if batch.len() >= batch_size {
+                            Self::flush(&clickhouse_service, &mut batch).await;
+                        }
+                    }
+                    Self::flush(&clickhouse_service, &mut batch).await;
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn flush(clickhouse_service: &ClickHouseService, batch: &mut Vec<T>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        if let Err(e) = T::insert(clickhouse_service, batch).await {
+            tracing::error!(
+                "Failed to flush {} {} rows to ClickHouse: {}",
+                batch.len(),
+                T::KIND,
+                e
+            );
+        }
+
+        batch.clear();
+    }
+}
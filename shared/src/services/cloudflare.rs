@@ -1,6 +1,25 @@
-use crate::error::AppError;
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use crate::error::{AppError, ExternalError};
 use crate::models::{DnsRecord, DomainVerificationRecords};
+use crate::services::DomainsConfig;
+use crate::services::http_client::{provider_client, send_with_retry};
+use chrono::{DateTime, Utc};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+const API_BASE_URL: &str = "https://api.cloudflare.com/client/v4";
+
+/// The most custom hostnames Cloudflare returns per page.
+const CUSTOM_HOSTNAMES_PAGE_SIZE: usize = 50;
+
+pub type CloudflareFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, CloudflareError>> + Send + 'a>>;
 
 #[derive(Debug, Serialize)]
 pub struct CreateCustomHostnameRequest {
@@ -11,106 +30,141 @@ pub struct CreateCustomHostnameRequest {
 #[derive(Debug, Deserialize)]
 pub struct CloudflareResponse<T> {
     pub success: bool,
-    pub errors: Vec<CloudflareError>,
-    pub messages: Vec<String>,
+    pub errors: Vec<CloudflareApiError>,
+    pub messages: Vec<serde_json::Value>,
     pub result: Option<T>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct CloudflareError {
+pub struct CloudflareApiError {
     pub code: u32,
     pub message: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct CustomHostname {
     pub id: String,
     pub hostname: String,
     pub custom_origin_server: String,
     pub status: String,
     pub verification_errors: Option<Vec<String>>,
+    #[serde(default)]
+    pub ssl: Option<CustomHostnameSsl>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Clone)]
-pub struct CloudflareService {
-    api_key: String,
-    zone_id: String,
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomHostnameSsl {
+    pub status: String,
+    #[serde(default)]
+    pub validation_errors: Vec<CustomHostnameSslError>,
 }
 
-impl CloudflareService {
-    pub fn new(api_key: String, zone_id: String) -> Self {
-        Self { api_key, zone_id }
-    }
-
-    pub fn create_custom_hostname(
-        &self,
-        hostname: &str,
-        origin_server: &str,
-    ) -> Result<CustomHostname, AppError> {
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/custom_hostnames",
-            self.zone_id
-        );
-
-        let request_body = CreateCustomHostnameRequest {
-            hostname: hostname.to_string(),
-            custom_origin_server: origin_server.to_string(),
-        };
-
-        let mut response = ureq::post(&url)
-            .header("Authorization", &format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .send_json(&request_body)
-            .map_err(|e| AppError::External(format!("Cloudflare API request failed: {}", e)))?;
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomHostnameSslError {
+    pub message: String,
+}
 
-        let cloudflare_response: CloudflareResponse<CustomHostname> =
-            response.body_mut().read_json().map_err(|e| {
-                AppError::External(format!("Failed to parse Cloudflare response: {}", e))
-            })?;
+#[derive(Debug, Error)]
+pub enum CloudflareError {
+    #[error("Cloudflare API request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Cloudflare API errors ({status}): {errors}")]
+    Api { status: u16, errors: String },
+    #[error("Failed to parse Cloudflare response ({status}): {message}")]
+    InvalidResponse { status: u16, message: String },
+    #[error("Cloudflare API returned success but no result")]
+    MissingResult,
+}
 
-        if !cloudflare_response.success {
-            let error_messages: Vec<String> = cloudflare_response
-                .errors
-                .iter()
-                .map(|e| format!("{}: {}", e.code, e.message))
-                .collect();
-            return Err(AppError::External(format!(
-                "Cloudflare API errors: {}",
-                error_messages.join(", ")
-            )));
+impl CloudflareError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            CloudflareError::Request(e) => e.status().map(|status| status.as_u16()),
+            CloudflareError::Api { status, .. }
+            | CloudflareError::InvalidResponse { status, .. } => Some(*status),
+            CloudflareError::MissingResult => None,
         }
-
-        cloudflare_response.result.ok_or_else(|| {
-            AppError::External("Cloudflare API returned success but no result".to_string())
-        })
     }
+}
 
-    pub fn delete_custom_hostname(&self, hostname_id: &str) -> Result<(), AppError> {
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/custom_hostnames/{}",
-            self.zone_id, hostname_id
-        );
-
-        let response = ureq::delete(&url)
-            .header("Authorization", &format!("Bearer {}", self.api_key))
-            .call()
-            .map_err(|e| AppError::External(format!("Cloudflare API request failed: {}", e)))?;
+impl From<CloudflareError> for AppError {
+    fn from(error: CloudflareError) -> Self {
+        AppError::External(ExternalError::new(
+            "cloudflare",
+            error.status(),
+            error.to_string(),
+        ))
+    }
+}
 
-        if response.status() != 200 {
-            return Err(AppError::External(format!(
-                "Cloudflare API error ({})",
-                response.status()
-            )));
-        }
+/// The Cloudflare for SaaS calls commands make, so they can run against
+/// [`FakeCloudflare`] in tests.
+pub trait CloudflareApi: Send + Sync {
+    /// Not retried: Cloudflare takes no idempotency key here, so callers look
+    /// the hostname up with [`Self::find_custom_hostname`] first.
+    fn create_custom_hostname<'a>(
+        &'a self,
+        hostname: &'a str,
+        origin_server: &'a str,
+    ) -> CloudflareFuture<'a, CustomHostname>;
+
+    /// Looks up a custom hostname by name, so a create that may already have
+    /// succeeded can be retried without making a duplicate.
+    fn find_custom_hostname<'a>(
+        &'a self,
+        hostname: &'a str,
+    ) -> CloudflareFuture<'a, Option<CustomHostname>>;
+
+    /// Fetches a custom hostname with its certificate status. `None` if
+    /// Cloudflare no longer has it.
+    fn get_custom_hostname<'a>(
+        &'a self,
+        hostname_id: &'a str,
+    ) -> CloudflareFuture<'a, Option<CustomHostname>>;
+
+    /// Every custom hostname in the zone.
+    fn list_custom_hostnames(&self) -> CloudflareFuture<'_, Vec<CustomHostname>>;
+
+    fn delete_custom_hostname<'a>(&'a self, hostname_id: &'a str) -> CloudflareFuture<'a, ()>;
+
+    /// Whether the zone has a CNAME record for the domain.
+    fn check_domain_verification_status<'a>(
+        &'a self,
+        domain: &'a str,
+    ) -> CloudflareFuture<'a, bool>;
+
+    /// Whether Cloudflare considers the custom hostname active.
+    fn check_custom_hostname_status<'a>(&'a self, hostname: &'a str) -> CloudflareFuture<'a, bool> {
+        Box::pin(async move {
+            let Some(custom_hostname) = self.find_custom_hostname(hostname).await? else {
+                tracing::info!("Custom hostname {} not found in Cloudflare", hostname);
+                return Ok(false);
+            };
 
-        Ok(())
+            // The listing can lag behind; the hostname itself has the current
+            // status.
+            let Some(custom_hostname) = self.get_custom_hostname(&custom_hostname.id).await? else {
+                return Ok(false);
+            };
+            tracing::info!(
+                "Custom hostname {} status: {}",
+                hostname,
+                custom_hostname.status
+            );
+
+            Ok(custom_hostname.status == "active")
+        })
     }
 
-    /// Generate DNS records required for custom hostname verification
-    pub fn generate_domain_verification_records(
+    /// The CNAME records a customer adds for the deployment's custom
+    /// hostnames.
+    fn generate_domain_verification_records(
         &self,
         frontend_hostname: &str,
         backend_hostname: &str,
+        domains: &DomainsConfig,
     ) -> DomainVerificationRecords {
         let mut records = DomainVerificationRecords::default();
 
@@ -118,202 +172,423 @@ impl CloudflareService {
         records.custom_hostname_verification.push(DnsRecord {
             name: frontend_hostname.to_string(),
             record_type: "CNAME".to_string(),
-            value: "accounts.wacht.services".to_string(),
+            value: domains.frontend_origin.clone(),
 
             verified: false,
             verification_attempted_at: None,
             last_verified_at: None,
+            status: None,
         });
 
         records.custom_hostname_verification.push(DnsRecord {
             name: backend_hostname.to_string(),
             record_type: "CNAME".to_string(),
-            value: "frontend.wacht.services".to_string(),
+            value: domains.backend_origin.clone(),
 
             verified: false,
             verification_attempted_at: None,
             last_verified_at: None,
+            status: None,
         });
 
         records
     }
+}
 
-    pub fn check_domain_verification_status(&self, domain: &str) -> Result<bool, AppError> {
-        // For domain verification, we check if the specific CNAME record exists and points to the correct value
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/dns_records?name={}&type=CNAME",
-            self.zone_id, domain
-        );
+#[derive(Clone)]
+pub struct CloudflareService {
+    client: reqwest::Client,
+    api_key: String,
+    zone_id: String,
+}
+
+impl CloudflareService {
+    pub fn new(api_key: String, zone_id: String) -> Self {
+        Self {
+            client: provider_client(),
+            api_key,
+            zone_id,
+        }
+    }
 
-        let mut response = ureq::get(&url)
-            .header("Authorization", &format!("Bearer {}", self.api_key))
-            .call()
-            .map_err(|e| AppError::External(format!("Cloudflare API error: {}", e)))?;
+    fn zone_url(&self, path: &str) -> String {
+        format!("{}/zones/{}/{}", API_BASE_URL, self.zone_id, path)
+    }
 
-        let cloudflare_response: CloudflareResponse<Vec<serde_json::Value>> = response
-            .body_mut()
-            .read_json()
-            .map_err(|e| AppError::External(format!("Failed to parse Cloudflare response: {}", e)))?;
+    /// Sends an authenticated request and unwraps Cloudflare's response
+    /// envelope.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<Option<T>, CloudflareError> {
+        let request = request.bearer_auth(&self.api_key).build()?;
+        let response = send_with_retry(&self.client, request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        let cloudflare_response: CloudflareResponse<T> =
+            serde_json::from_str(&body).map_err(|e| CloudflareError::InvalidResponse {
+                status: status.as_u16(),
+                message: e.to_string(),
+            })?;
 
-        if !cloudflare_response.success {
-            let error_messages: Vec<String> = cloudflare_response
+        if !cloudflare_response.success || !status.is_success() {
+            let errors: Vec<String> = cloudflare_response
                 .errors
                 .iter()
                 .map(|e| format!("{}: {}", e.code, e.message))
                 .collect();
-            return Err(AppError::External(format!(
-                "Cloudflare API errors: {}",
-                error_messages.join(", ")
-            )));
+            return Err(CloudflareError::Api {
+                status: status.as_u16(),
+                errors: errors.join(", "),
+            });
+        }
+
+        Ok(cloudflare_response.result)
+    }
+
+    pub async fn create_custom_hostname(
+        &self,
+        hostname: &str,
+        origin_server: &str,
+    ) -> Result<CustomHostname, CloudflareError> {
+        let request_body = CreateCustomHostnameRequest {
+            hostname: hostname.to_string(),
+            custom_origin_server: origin_server.to_string(),
+        };
+
+        self.send(
+            self.client
+                .post(self.zone_url("custom_hostnames"))
+                .json(&request_body),
+        )
+        .await?
+        .ok_or(CloudflareError::MissingResult)
+    }
+
+    pub async fn find_custom_hostname(
+        &self,
+        hostname: &str,
+    ) -> Result<Option<CustomHostname>, CloudflareError> {
+        let custom_hostnames: Option<Vec<CustomHostname>> = self
+            .send(
+                self.client
+                    .get(self.zone_url("custom_hostnames"))
+                    .query(&[("hostname", hostname)]),
+            )
+            .await?;
+
+        Ok(custom_hostnames
+            .unwrap_or_default()
+            .into_iter()
+            .find(|custom_hostname| custom_hostname.hostname == hostname))
+    }
+
+    pub async fn get_custom_hostname(
+        &self,
+        hostname_id: &str,
+    ) -> Result<Option<CustomHostname>, CloudflareError> {
+        let request = self
+            .client
+            .get(self.zone_url(&format!("custom_hostnames/{}", hostname_id)));
+
+        match self.send(request).await {
+            Err(CloudflareError::Api { status, .. }) if status == StatusCode::NOT_FOUND => Ok(None),
+            result => result,
         }
+    }
 
-        // Check if we found any CNAME record for this domain
-        // If Cloudflare has the record, it means it's properly configured
-        if let Some(records) = cloudflare_response.result {
-            for record in &records {
-                if let Some(name) = record.get("name").and_then(|n| n.as_str()) {
-                    if name == domain {
-                        // If the record exists in Cloudflare's zone, it's verified
-                        if let Some(content) = record.get("content").and_then(|c| c.as_str()) {
-                            tracing::info!("Domain verification found for {}: CNAME -> {}", domain, content);
-                        } else {
-                            tracing::info!("Domain verification found for {}: record exists", domain);
-                        }
-                        return Ok(true);
-                    }
-                }
+    pub async fn list_custom_hostnames(&self) -> Result<Vec<CustomHostname>, CloudflareError> {
+        let mut custom_hostnames = Vec::new();
+
+        for page in 1.. {
+            let batch: Vec<CustomHostname> = self
+                .send(self.client.get(self.zone_url("custom_hostnames")).query(&[
+                    ("page", page.to_string()),
+                    ("per_page", CUSTOM_HOSTNAMES_PAGE_SIZE.to_string()),
+                ]))
+                .await?
+                .unwrap_or_default();
+
+            let last_page = batch.len() < CUSTOM_HOSTNAMES_PAGE_SIZE;
+            custom_hostnames.extend(batch);
+            if last_page {
+                break;
             }
         }
 
-        tracing::info!("Domain verification not found for {}", domain);
-        Ok(false)
+        Ok(custom_hostnames)
     }
 
-    pub fn check_custom_hostname_status(&self, hostname: &str) -> Result<bool, AppError> {
-        tracing::info!("Checking custom hostname status for: {}", hostname);
+    pub async fn delete_custom_hostname(&self, hostname_id: &str) -> Result<(), CloudflareError> {
+        self.send::<serde_json::Value>(
+            self.client
+                .delete(self.zone_url(&format!("custom_hostnames/{}", hostname_id))),
+        )
+        .await?;
 
-        // First, get all custom hostnames to find the ID for this hostname
-        let list_url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/custom_hostnames?hostname={}",
-            self.zone_id, hostname
-        );
+        Ok(())
+    }
 
-        tracing::info!("Making Cloudflare API call to: {}", list_url);
+    pub fn generate_domain_verification_records(
+        &self,
+        frontend_hostname: &str,
+        backend_hostname: &str,
+        domains: &DomainsConfig,
+    ) -> DomainVerificationRecords {
+        CloudflareApi::generate_domain_verification_records(
+            self,
+            frontend_hostname,
+            backend_hostname,
+            domains,
+        )
+    }
 
-        let mut list_response = ureq::get(&list_url)
-            .header("Authorization", &format!("Bearer {}", self.api_key))
-            .call()
-            .map_err(|e| {
-                tracing::error!("Cloudflare API call failed: {}", e);
-                AppError::External(format!("Cloudflare API error: {}", e))
-            })?;
+    pub async fn check_domain_verification_status(
+        &self,
+        domain: &str,
+    ) -> Result<bool, CloudflareError> {
+        let records: Option<Vec<serde_json::Value>> = self
+            .send(
+                self.client
+                    .get(self.zone_url("dns_records"))
+                    .query(&[("name", domain), ("type", "CNAME")]),
+            )
+            .await?;
+
+        let found = records
+            .unwrap_or_default()
+            .iter()
+            .any(|record| record.get("name").and_then(|name| name.as_str()) == Some(domain));
+        tracing::info!("Domain verification for {} found: {}", domain, found);
+
+        Ok(found)
+    }
+}
 
-        let list_cloudflare_response: CloudflareResponse<Vec<CustomHostname>> = list_response
-            .body_mut()
-            .read_json()
-            .map_err(|e| {
-                tracing::error!("Failed to parse Cloudflare response: {}", e);
-                AppError::External(format!("Failed to parse Cloudflare response: {}", e))
-            })?;
+impl CloudflareApi for CloudflareService {
+    fn create_custom_hostname<'a>(
+        &'a self,
+        hostname: &'a str,
+        origin_server: &'a str,
+    ) -> CloudflareFuture<'a, CustomHostname> {
+        Box::pin(CloudflareService::create_custom_hostname(
+            self,
+            hostname,
+            origin_server,
+        ))
+    }
 
-        tracing::info!("Cloudflare API response success: {}", list_cloudflare_response.success);
+    fn find_custom_hostname<'a>(
+        &'a self,
+        hostname: &'a str,
+    ) -> CloudflareFuture<'a, Option<CustomHostname>> {
+        Box::pin(CloudflareService::find_custom_hostname(self, hostname))
+    }
 
-        if !list_cloudflare_response.success {
-            let error_messages: Vec<String> = list_cloudflare_response
-                .errors
-                .iter()
-                .map(|e| format!("{}: {}", e.code, e.message))
-                .collect();
-            tracing::error!("Cloudflare API errors: {}", error_messages.join(", "));
-            return Err(AppError::External(format!(
-                "Cloudflare API errors: {}",
-                error_messages.join(", ")
-            )));
-        }
+    fn get_custom_hostname<'a>(
+        &'a self,
+        hostname_id: &'a str,
+    ) -> CloudflareFuture<'a, Option<CustomHostname>> {
+        Box::pin(CloudflareService::get_custom_hostname(self, hostname_id))
+    }
 
-        // Find the custom hostname ID
-        let hostname_id = if let Some(hostnames) = list_cloudflare_response.result {
-            tracing::info!("Found {} custom hostnames", hostnames.len());
-            for (i, h) in hostnames.iter().enumerate() {
-                tracing::info!("Custom hostname {}: {} (id: {})", i, h.hostname, h.id);
-            }
+    fn list_custom_hostnames(&self) -> CloudflareFuture<'_, Vec<CustomHostname>> {
+        Box::pin(CloudflareService::list_custom_hostnames(self))
+    }
 
-            hostnames
-                .iter()
-                .find(|h| h.hostname == hostname)
-                .map(|h| h.id.clone())
-        } else {
-            tracing::warn!("No custom hostnames returned from Cloudflare");
-            None
-        };
+    fn delete_custom_hostname<'a>(&'a self, hostname_id: &'a str) -> CloudflareFuture<'a, ()> {
+        Box::pin(CloudflareService::delete_custom_hostname(self, hostname_id))
+    }
 
-        let hostname_id = match hostname_id {
-            Some(id) => {
-                tracing::info!("Found custom hostname ID for {}: {}", hostname, id);
-                id
-            }
-            None => {
-                tracing::warn!("Custom hostname {} not found in Cloudflare", hostname);
-                return Ok(false);
+    fn check_domain_verification_status<'a>(
+        &'a self,
+        domain: &'a str,
+    ) -> CloudflareFuture<'a, bool> {
+        Box::pin(CloudflareService::check_domain_verification_status(
+            self, domain,
+        ))
+    }
+}
+
+/// A call made to a [`FakeCloudflare`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloudflareCall {
+    CreateCustomHostname {
+        hostname: String,
+        origin_server: String,
+    },
+    FindCustomHostname(String),
+    GetCustomHostname(String),
+    ListCustomHostnames,
+    DeleteCustomHostname(String),
+    CheckDomainVerificationStatus(String),
+}
+
+#[derive(Default)]
+struct FakeCloudflareState {
+    custom_hostnames: Vec<CustomHostname>,
+    dns_records: HashSet<String>,
+    failures: Vec<(CloudflareCall, u16)>,
+    calls: Vec<CloudflareCall>,
+}
+
+/// Keeps custom hostnames in memory and records every call, for tests.
+/// Calls can be scripted to fail with [`FakeCloudflare::fail`].
+#[derive(Default)]
+pub struct FakeCloudflare {
+    state: Mutex<FakeCloudflareState>,
+}
+
+impl FakeCloudflare {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `call` fail with a Cloudflare API error of the given status,
+    /// every time it is made from now on.
+    pub fn fail(&self, call: CloudflareCall, status: u16) {
+        self.lock().failures.push((call, status));
+    }
+
+    /// Adds a CNAME for `name` to the zone.
+    pub fn add_dns_record(&self, name: &str) {
+        self.lock().dns_records.insert(name.to_string());
+    }
+
+    pub fn set_custom_hostname_status(&self, hostname: &str, status: &str) {
+        for custom_hostname in &mut self.lock().custom_hostnames {
+            if custom_hostname.hostname == hostname {
+                custom_hostname.status = status.to_string();
             }
+        }
+    }
+
+    pub fn custom_hostnames(&self) -> Vec<CustomHostname> {
+        self.lock().custom_hostnames.clone()
+    }
+
+    /// Every call made so far, in order, including those that failed.
+    pub fn calls(&self) -> Vec<CloudflareCall> {
+        self.lock().calls.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FakeCloudflareState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn call<T>(
+        &self,
+        call: CloudflareCall,
+        respond: impl FnOnce(&mut FakeCloudflareState) -> T,
+    ) -> Result<T, CloudflareError> {
+        let mut state = self.lock();
+        state.calls.push(call.clone());
+
+        if let Some((_, status)) = state.failures.iter().find(|(failing, _)| *failing == call) {
+            return Err(CloudflareError::Api {
+                status: *status,
+                errors: format!("scripted failure of {:?}", call),
+            });
+        }
+
+        Ok(respond(&mut state))
+    }
+}
+
+impl CloudflareApi for FakeCloudflare {
+    fn create_custom_hostname<'a>(
+        &'a self,
+        hostname: &'a str,
+        origin_server: &'a str,
+    ) -> CloudflareFuture<'a, CustomHostname> {
+        let call = CloudflareCall::CreateCustomHostname {
+            hostname: hostname.to_string(),
+            origin_server: origin_server.to_string(),
         };
+        let result = self.call(call, |state| {
+            let custom_hostname = CustomHostname {
+                id: format!("cf-{}", hostname),
+                hostname: hostname.to_string(),
+                custom_origin_server: origin_server.to_string(),
+                status: "pending".to_string(),
+                verification_errors: None,
+                ssl: None,
+                created_at: Some(Utc::now()),
+            };
+            state.custom_hostnames.push(custom_hostname.clone());
+            custom_hostname
+        });
+
+        Box::pin(async move { result })
+    }
 
-        // Now get the detailed status using the hostname ID
-        let detail_url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/custom_hostnames/{}",
-            self.zone_id, hostname_id
+    fn find_custom_hostname<'a>(
+        &'a self,
+        hostname: &'a str,
+    ) -> CloudflareFuture<'a, Option<CustomHostname>> {
+        let result = self.call(
+            CloudflareCall::FindCustomHostname(hostname.to_string()),
+            |state| {
+                state
+                    .custom_hostnames
+                    .iter()
+                    .find(|custom_hostname| custom_hostname.hostname == hostname)
+                    .cloned()
+            },
         );
 
-        tracing::info!("Getting detailed status from: {}", detail_url);
+        Box::pin(async move { result })
+    }
 
-        let mut detail_response = ureq::get(&detail_url)
-            .header("Authorization", &format!("Bearer {}", self.api_key))
-            .call()
-            .map_err(|e| {
-                tracing::error!("Cloudflare detail API call failed: {}", e);
-                AppError::External(format!("Cloudflare API error: {}", e))
-            })?;
+    fn get_custom_hostname<'a>(
+        &'a self,
+        hostname_id: &'a str,
+    ) -> CloudflareFuture<'a, Option<CustomHostname>> {
+        let result = self.call(
+            CloudflareCall::GetCustomHostname(hostname_id.to_string()),
+            |state| {
+                state
+                    .custom_hostnames
+                    .iter()
+                    .find(|custom_hostname| custom_hostname.id == hostname_id)
+                    .cloned()
+            },
+        );
 
-        let detail_cloudflare_response: CloudflareResponse<serde_json::Value> = detail_response
-            .body_mut()
-            .read_json()
-            .map_err(|e| {
-                tracing::error!("Failed to parse Cloudflare detail response: {}", e);
-                AppError::External(format!("Failed to parse Cloudflare response: {}", e))
-            })?;
+        Box::pin(async move { result })
+    }
 
-        tracing::info!("Cloudflare detail API response success: {}", detail_cloudflare_response.success);
+    fn list_custom_hostnames(&self) -> CloudflareFuture<'_, Vec<CustomHostname>> {
+        let result = self.call(CloudflareCall::ListCustomHostnames, |state| {
+            state.custom_hostnames.clone()
+        });
 
-        if !detail_cloudflare_response.success {
-            let error_messages: Vec<String> = detail_cloudflare_response
-                .errors
-                .iter()
-                .map(|e| format!("{}: {}", e.code, e.message))
-                .collect();
-            tracing::error!("Cloudflare detail API errors: {}", error_messages.join(", "));
-            return Err(AppError::External(format!(
-                "Cloudflare API errors: {}",
-                error_messages.join(", ")
-            )));
-        }
+        Box::pin(async move { result })
+    }
 
-        // Check the status field
-        if let Some(result) = detail_cloudflare_response.result {
-            tracing::info!("Custom hostname detail result: {}", serde_json::to_string_pretty(&result).unwrap_or_default());
-
-            if let Some(status) = result.get("status").and_then(|s| s.as_str()) {
-                tracing::info!("Custom hostname {} status: {}", hostname, status);
-                let is_active = status == "active";
-                tracing::info!("Is hostname {} active? {}", hostname, is_active);
-                return Ok(is_active);
-            } else {
-                tracing::warn!("No status field found in custom hostname response");
-            }
-        } else {
-            tracing::warn!("No result field in custom hostname response");
-        }
+    fn delete_custom_hostname<'a>(&'a self, hostname_id: &'a str) -> CloudflareFuture<'a, ()> {
+        let result = self.call(
+            CloudflareCall::DeleteCustomHostname(hostname_id.to_string()),
+            |state| {
+                state
+                    .custom_hostnames
+                    .retain(|custom_hostname| custom_hostname.id != hostname_id)
+            },
+        );
+
+        Box::pin(async move { result })
+    }
+
+    fn check_domain_verification_status<'a>(
+        &'a self,
+        domain: &'a str,
+    ) -> CloudflareFuture<'a, bool> {
+        let result = self.call(
+            CloudflareCall::CheckDomainVerificationStatus(domain.to_string()),
+            |state| state.dns_records.contains(domain),
+        );
 
-        tracing::warn!("Custom hostname check failed for {}", hostname);
-        Ok(false)
+        Box::pin(async move { result })
     }
 }
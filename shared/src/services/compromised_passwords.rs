@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use sha1::{Digest, Sha1};
+
+use crate::error::AppError;
+
+pub type BreachCountFuture<'a> = Pin<Box<dyn Future<Output = Result<u64, AppError>> + Send + 'a>>;
+
+/// Reports how often a password appears in known breaches. Providers are
+/// handed the uppercase hex SHA-1 of the password, never the password itself.
+pub trait BreachedPasswordProvider: Send + Sync {
+    fn breach_count<'a>(&'a self, sha1_hash: &'a str) -> BreachCountFuture<'a>;
+}
+
+#[derive(Debug, Clone)]
+pub struct PwnedPasswordsConfig {
+    pub base_url: String,
+}
+
+impl Default for PwnedPasswordsConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://api.pwnedpasswords.com".to_string(),
+        }
+    }
+}
+
+impl PwnedPasswordsConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            base_url: std::env::var("PWNED_PASSWORDS_URL").unwrap_or(defaults.base_url),
+        }
+    }
+}
+
+/// Uses the HaveIBeenPwned range API. Only the first five characters of the
+/// hash are sent; the matching suffix is looked for in the response.
+pub struct PwnedPasswordsProvider {
+    client: reqwest::Client,
+    config: PwnedPasswordsConfig,
+}
+
+impl PwnedPasswordsProvider {
+    pub fn new(config: PwnedPasswordsConfig) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(3))
+                .build()
+                .unwrap_or_default(),
+            config,
+        }
+    }
+}
+
+impl BreachedPasswordProvider for PwnedPasswordsProvider {
+    fn breach_count<'a>(&'a self, sha1_hash: &'a str) -> BreachCountFuture<'a> {
+        Box::pin(async move {
+            let (prefix, suffix) = sha1_hash.split_at(5);
+
+            let body = self
+                .client
+                .get(format!(
+                    "{}/range/{}",
+                    self.config.base_url.trim_end_matches('/'),
+                    prefix
+                ))
+                // Pads the response with zero-count entries so its size
+                // doesn't give away which suffixes matched.
+                .header("Add-Padding", "true")
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| AppError::External(e.to_string().into()))?
+                .text()
+                .await
+                .map_err(|e| AppError::External(e.to_string().into()))?;
+
+            Ok(count_in_range_response(&body, suffix))
+        })
+    }
+}
+
+/// Finds `suffix` in a range API response, which has one `SUFFIX:COUNT`
+/// line per hash sharing the requested prefix.
+fn count_in_range_response(body: &str, suffix: &str) -> u64 {
+    body.lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Treats a fixed set of passwords as breached, for tests and for
+/// environments that shouldn't call out to HaveIBeenPwned.
+#[derive(Default)]
+pub struct StubBreachedPasswords {
+    hashes: HashSet<String>,
+}
+
+impl StubBreachedPasswords {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_password(mut self, password: &str) -> Self {
+        self.hashes.insert(sha1_hex(password));
+        self
+    }
+}
+
+impl BreachedPasswordProvider for StubBreachedPasswords {
+    fn breach_count<'a>(&'a self, sha1_hash: &'a str) -> BreachCountFuture<'a> {
+        Box::pin(async move { Ok(u64::from(self.hashes.contains(sha1_hash))) })
+    }
+}
+
+#[derive(Clone)]
+pub struct CompromisedPasswordService {
+    provider: Arc<dyn BreachedPasswordProvider>,
+}
+
+impl CompromisedPasswordService {
+    pub fn new(provider: Arc<dyn BreachedPasswordProvider>) -> Self {
+        Self { provider }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(Arc::new(PwnedPasswordsProvider::new(
+            PwnedPasswordsConfig::from_env(),
+        )))
+    }
+
+    pub async fn is_compromised(&self, password: &str) -> Result<bool, AppError> {
+        let count = self.provider.breach_count(&sha1_hex(password)).await?;
+        Ok(count > 0)
+    }
+}
+
+pub fn sha1_hex(password: &str) -> String {
+    hex::encode_upper(Sha1::digest(password.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_match_the_range_api_format() {
+        assert_eq!(
+            sha1_hex("password"),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+    }
+
+    #[test]
+    fn finds_the_suffix_in_a_range_response() {
+        let body = "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n\
+                    1E4C9B93F3F0682250B6CF8331B7EE68FD8:9659365\r\n\
+                    1E4C9B93F3F0682250B6CF8331B7EE68FD9:0\r\n";
+
+        assert_eq!(
+            count_in_range_response(body, "1e4c9b93f3f0682250b6cf8331b7ee68fd8"),
+            9659365
+        );
+        assert_eq!(
+            count_in_range_response(body, "1E4C9B93F3F0682250B6CF8331B7EE68FD9"),
+            0
+        );
+        assert_eq!(
+            count_in_range_response(body, "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn stub_only_flags_the_configured_passwords() {
+        let service = CompromisedPasswordService::new(Arc::new(
+            StubBreachedPasswords::new().with_password("hunter2"),
+        ));
+
+        assert!(service.is_compromised("hunter2").await.unwrap());
+        assert!(
+            !service
+                .is_compromised("correct horse battery staple")
+                .await
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashMap, sync::Arc};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::error::{AppError, ErrorCode};
+
+const PREFIX: &str = "enc:v1:";
+const DEFAULT_KEY_ID: &str = "default";
+
+/// Encrypts secrets before they are stored, with AES-256-GCM. Ciphertexts are
+/// `enc:v1:<key id>:base64(nonce || sealed)`, so a value says which key
+/// sealed it and keys can be rotated without a flag day: new values use the
+/// current key, older ones stay readable while their key is still
+/// configured, and `MigrateEncryptSecretsCommand` reseals them.
+///
+/// Each ciphertext is bound to a context, such as the column it lives in, so
+/// it can't be moved somewhere it would be read as something else.
+#[derive(Clone)]
+pub struct CredentialCipher {
+    keys: HashMap<String, Arc<LessSafeKey>>,
+    current_key_id: String,
+    rng: SystemRandom,
+}
+
+impl CredentialCipher {
+    pub fn new(key_id: impl Into<String>, key: &[u8; 32]) -> Self {
+        let key_id = key_id.into();
+        Self {
+            keys: HashMap::from([(key_id.clone(), less_safe_key(key))]),
+            current_key_id: key_id,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Adds a retired key, used only to read values sealed before a rotation.
+    pub fn with_previous_key(mut self, key_id: impl Into<String>, key: &[u8; 32]) -> Self {
+        let key_id = key_id.into();
+        if key_id != self.current_key_id {
+            self.keys.insert(key_id, less_safe_key(key));
+        }
+        self
+    }
+
+    /// Reads `CREDENTIALS_ENCRYPTION_KEY` (32 bytes in base64) and its id from
+    /// `CREDENTIALS_ENCRYPTION_KEY_ID`, which defaults to `default`. Retired
+    /// keys go in `CREDENTIALS_PREVIOUS_KEYS` as comma-separated
+    /// `<id>:<base64 key>` pairs. There is no fallback for a missing current
+    /// key: secrets sealed with a key that isn't stored would be lost, so the
+    /// service refuses to start instead.
+    pub fn from_env() -> Self {
+        let key_id = std::env::var("CREDENTIALS_ENCRYPTION_KEY_ID")
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| DEFAULT_KEY_ID.to_string());
+
+        let key = std::env::var("CREDENTIALS_ENCRYPTION_KEY")
+            .ok()
+            .filter(|key| !key.is_empty())
+            .and_then(|key| decode_key("CREDENTIALS_ENCRYPTION_KEY", &key))
+            .expect("CREDENTIALS_ENCRYPTION_KEY must be set to a base64-encoded 32-byte key");
+
+        let mut cipher = Self::new(key_id, &key);
+
+        let previous = std::env::var("CREDENTIALS_PREVIOUS_KEYS").unwrap_or_default();
+        for entry in previous.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((id, key)) = entry.split_once(':') else {
+                tracing::error!("CREDENTIALS_PREVIOUS_KEYS entries must be <id>:<base64 key>");
+                continue;
+            };
+            if let Some(key) = decode_key("CREDENTIALS_PREVIOUS_KEYS", key) {
+                cipher = cipher.with_previous_key(id.trim(), &key);
+            }
+        }
+
+        cipher
+    }
+
+    pub fn current_key_id(&self) -> &str {
+        &self.current_key_id
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8], context: &[u8]) -> Result<String, AppError> {
+        let key = &self.keys[&self.current_key_id];
+
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| AppError::Internal("Failed to generate a nonce".to_string()))?;
+
+        let mut sealed = plaintext.to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(context),
+            &mut sealed,
+        )
+        .map_err(|_| AppError::Internal("Failed to encrypt a secret".to_string()))?;
+
+        let mut output = nonce.to_vec();
+        output.extend_from_slice(&sealed);
+        Ok(format!(
+            "{}{}:{}",
+            PREFIX,
+            self.current_key_id,
+            STANDARD.encode(output)
+        ))
+    }
+
+    pub fn decrypt(&self, ciphertext: &str, context: &[u8]) -> Result<Vec<u8>, AppError> {
+        let (key_id, encoded) = ciphertext
+            .strip_prefix(PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+            .ok_or_else(|| undecryptable(None, "the value isn't an encrypted secret"))?;
+        let key = self.keys.get(key_id).ok_or_else(|| {
+            undecryptable(Some(key_id), "the key it was sealed with isn't configured")
+        })?;
+        let unreadable = || {
+            undecryptable(
+                Some(key_id),
+                "it was tampered with or sealed with another key",
+            )
+        };
+
+        let mut bytes = STANDARD.decode(encoded).map_err(|_| unreadable())?;
+        if bytes.len() < NONCE_LEN {
+            return Err(unreadable());
+        }
+        let mut sealed = bytes.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&bytes).map_err(|_| unreadable())?;
+
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(context), &mut sealed)
+            .map_err(|_| unreadable())?;
+
+        Ok(plaintext.to_vec())
+    }
+
+    /// Encrypts a text secret for a text or JSON column.
+    pub fn seal(&self, secret: &str, context: &str) -> Result<String, AppError> {
+        self.encrypt(secret.as_bytes(), context.as_bytes())
+    }
+
+    /// Decrypts a value written by [`seal`](Self::seal). Values stored before
+    /// encryption was introduced are returned as they are until the
+    /// migration has sealed them.
+    pub fn open(&self, stored: &str, context: &str) -> Result<String, AppError> {
+        if !Self::is_sealed(stored) {
+            return Ok(stored.to_string());
+        }
+        String::from_utf8(self.decrypt(stored, context.as_bytes())?)
+            .map_err(|_| undecryptable(Self::key_id_of(stored), "it isn't valid UTF-8"))
+    }
+
+    /// Seals a stored value again with the current key, whether it was
+    /// plaintext or sealed with a retired one.
+    pub fn reseal(&self, stored: &str, context: &str) -> Result<String, AppError> {
+        self.seal(&self.open(stored, context)?, context)
+    }
+
+    pub fn is_sealed(value: &str) -> bool {
+        value.starts_with(PREFIX)
+    }
+
+    pub fn key_id_of(value: &str) -> Option<&str> {
+        value
+            .strip_prefix(PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+            .map(|(key_id, _)| key_id)
+    }
+
+    /// Whether a stored value is plaintext or sealed with a retired key.
+    pub fn needs_resealing(&self, value: &str) -> bool {
+        Self::key_id_of(value) != Some(self.current_key_id.as_str())
+    }
+}
+
+fn less_safe_key(key: &[u8; 32]) -> Arc<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, key).expect("AES-256 keys are 32 bytes");
+    Arc::new(LessSafeKey::new(key))
+}
+
+fn decode_key(variable: &str, encoded: &str) -> Option<[u8; 32]> {
+    match STANDARD.decode(encoded.trim()) {
+        Ok(bytes) => match <[u8; 32]>::try_from(bytes) {
+            Ok(key) => Some(key),
+            Err(_) => {
+                tracing::error!("{} keys must decode to 32 bytes", variable);
+                None
+            }
+        },
+        Err(e) => {
+            tracing::error!("{} holds a key that is not valid base64: {}", variable, e);
+            None
+        }
+    }
+}
+
+fn undecryptable(key_id: Option<&str>, reason: &str) -> AppError {
+    AppError::coded(
+        ErrorCode::SecretDecryptionFailed,
+        format!("A stored secret can't be decrypted: {}", reason),
+    )
+    .with_details(serde_json::json!({ "key_id": key_id }))
+}
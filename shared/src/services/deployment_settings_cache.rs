@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::AppError, metrics::METRICS, models::DeploymentWithSettings, services::RedisPool,
+};
+
+/// Bump whenever `DeploymentWithSettings` changes shape. Entries written by
+/// another version are ignored and replaced from the database.
+const CACHE_VERSION: u32 = 6;
+
+/// Settings changes are announced here with the deployment id as the
+/// message, for services that keep their own copy.
+pub const DEPLOYMENT_SETTINGS_INVALIDATION_CHANNEL: &str = "deployment_settings:invalidated";
+
+fn cache_key(deployment_id: i64) -> String {
+    format!("deployment_settings:{}", deployment_id)
+}
+
+#[derive(Debug, Clone)]
+pub struct DeploymentSettingsCacheConfig {
+    pub ttl: Duration,
+    /// Missing and deleted deployments are remembered for less time than
+    /// live ones.
+    pub tombstone_ttl: Duration,
+}
+
+impl Default for DeploymentSettingsCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(60),
+            tombstone_ttl: Duration::from_secs(10),
+        }
+    }
+}
+
+impl DeploymentSettingsCacheConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let seconds = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs)
+        };
+
+        Self {
+            ttl: seconds("DEPLOYMENT_SETTINGS_CACHE_TTL_SECS").unwrap_or(defaults.ttl),
+            tombstone_ttl: seconds("DEPLOYMENT_SETTINGS_CACHE_TOMBSTONE_TTL_SECS")
+                .unwrap_or(defaults.tombstone_ttl),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry<D> {
+    version: u32,
+    /// `None` is a tombstone: the deployment doesn't exist or was deleted.
+    deployment: Option<D>,
+}
+
+pub enum CachedDeploymentSettings {
+    Found(Box<DeploymentWithSettings>),
+    Deleted,
+}
+
+/// Read-through cache for [`DeploymentWithSettings`], which every
+/// authenticated request downstream needs.
+///
+/// Redis being unavailable is never an error here: reads count as a miss and
+/// writes are skipped, so callers always fall back to the database.
+#[derive(Clone)]
+pub struct DeploymentSettingsCache {
+    redis: RedisPool,
+    config: DeploymentSettingsCacheConfig,
+}
+
+impl DeploymentSettingsCache {
+    pub fn new(redis: RedisPool, config: DeploymentSettingsCacheConfig) -> Self {
+        Self { redis, config }
+    }
+
+    pub async fn get(&self, deployment_id: i64) -> Option<CachedDeploymentSettings> {
+        let counter = &METRICS.deployment_settings_cache;
+
+        let cached = match self.read(deployment_id).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!("Failed to read cached deployment settings: {}", e);
+                counter.with_label_values(&["error"]).inc();
+                return None;
+            }
+        };
+
+        let Some(cached) = cached else {
+            counter.with_label_values(&["miss"]).inc();
+            return None;
+        };
+
+        match serde_json::from_str::<CacheEntry<DeploymentWithSettings>>(&cached) {
+            Ok(entry) if entry.version == CACHE_VERSION => match entry.deployment {
+                Some(deployment) => {
+                    counter.with_label_values(&["hit"]).inc();
+                    Some(CachedDeploymentSettings::Found(Box::new(deployment)))
+                }
+                None => {
+                    counter.with_label_values(&["tombstone"]).inc();
+                    Some(CachedDeploymentSettings::Deleted)
+                }
+            },
+            _ => {
+                counter.with_label_values(&["stale"]).inc();
+                None
+            }
+        }
+    }
+
+    async fn read(&self, deployment_id: i64) -> Result<Option<String>, AppError> {
+        self.redis.get(&cache_key(deployment_id)).await
+    }
+
+    pub async fn put(&self, deployment_id: i64, deployment: &DeploymentWithSettings) {
+        self.write(deployment_id, Some(deployment), self.config.ttl)
+            .await;
+    }
+
+    pub async fn put_tombstone(&self, deployment_id: i64) {
+        self.write(deployment_id, None, self.config.tombstone_ttl)
+            .await;
+    }
+
+    async fn write(
+        &self,
+        deployment_id: i64,
+        deployment: Option<&DeploymentWithSettings>,
+        ttl: Duration,
+    ) {
+        let entry = CacheEntry {
+            version: CACHE_VERSION,
+            deployment,
+        };
+
+        if let Err(e) = self
+            .redis
+            .set_json_with_ttl(&cache_key(deployment_id), &entry, ttl)
+            .await
+        {
+            tracing::warn!("Failed to cache deployment settings: {}", e);
+        }
+    }
+
+    pub fn invalidator(&self) -> CacheInvalidator {
+        CacheInvalidator {
+            redis: self.redis.clone(),
+        }
+    }
+}
+
+/// Drops cached deployment settings after a change and tells other services
+/// to do the same.
+#[derive(Clone)]
+pub struct CacheInvalidator {
+    redis: RedisPool,
+}
+
+impl CacheInvalidator {
+    /// The change has already been committed by the time this runs, so a
+    /// failure is only logged; the entry expires with its TTL regardless.
+    pub async fn invalidate_deployment(&self, deployment_id: i64) {
+        let result = async {
+            let mut connection = self.redis.connection().await?;
+            redis::pipe()
+                .del(cache_key(deployment_id))
+                .ignore()
+                .publish(DEPLOYMENT_SETTINGS_INVALIDATION_CHANNEL, deployment_id)
+                .ignore()
+                .query_async::<()>(&mut connection)
+                .await?;
+            Ok::<_, AppError>(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!(
+                deployment_id,
+                "Failed to invalidate cached deployment settings: {}",
+                e
+            );
+        }
+    }
+}
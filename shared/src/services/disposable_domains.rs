@@ -0,0 +1,333 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    error::AppError,
+    models::{DisposableDomainDataset, DisposableDomainSource, DisposableEmailCheck},
+};
+
+const DEPLOYMENT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// Arbitrary, only has to be unique among the advisory locks the platform takes.
+const REFRESH_LOCK_KEY: i64 = 0x6469_7370_6f73_6162;
+
+type DeploymentCache = HashMap<i64, (Instant, Arc<DeploymentDisposableDomains>)>;
+
+#[derive(Debug, Clone)]
+pub struct DisposableDomainConfig {
+    pub upstream_url: String,
+    /// How old the stored upstream copy may get before it's fetched again.
+    pub refresh_interval: Duration,
+    /// How often each instance reloads the stored copy into memory.
+    pub reload_interval: Duration,
+}
+
+impl Default for DisposableDomainConfig {
+    fn default() -> Self {
+        Self {
+            upstream_url: "https://raw.githubusercontent.com/disposable-email-domains/disposable-email-domains/main/disposable_email_blocklist.conf".to_string(),
+            refresh_interval: Duration::from_secs(24 * 60 * 60),
+            reload_interval: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+impl DisposableDomainConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let read = |key: &str| std::env::var(key).ok().and_then(|v| v.parse::<u64>().ok());
+
+        Self {
+            upstream_url: std::env::var("DISPOSABLE_DOMAINS_URL").unwrap_or(defaults.upstream_url),
+            refresh_interval: read("DISPOSABLE_DOMAINS_REFRESH_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.refresh_interval),
+            reload_interval: read("DISPOSABLE_DOMAINS_RELOAD_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.reload_interval),
+        }
+    }
+}
+
+/// A deployment's own additions to and exceptions from the upstream list.
+#[derive(Debug, Clone, Default)]
+pub struct DeploymentDisposableDomains {
+    pub block_disposable_emails: bool,
+    pub blocked: HashSet<String>,
+    pub allowed: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct Snapshot {
+    domains: HashSet<String>,
+    refreshed_at: Option<DateTime<Utc>>,
+}
+
+/// Keeps the upstream disposable domain list in memory so sign-up checks are
+/// hash lookups. A background task reloads it from Postgres periodically, and
+/// fetches a fresh upstream copy into Postgres once the stored one is older
+/// than the refresh interval. Deployment overrides are cached briefly, the same
+/// way rate limit overrides are.
+#[derive(Clone)]
+pub struct DisposableDomainService {
+    db_pool: PgPool,
+    http_client: reqwest::Client,
+    config: DisposableDomainConfig,
+    snapshot: Arc<RwLock<Arc<Snapshot>>>,
+    deployments: Arc<RwLock<DeploymentCache>>,
+}
+
+impl DisposableDomainService {
+    pub fn spawn(db_pool: PgPool, config: DisposableDomainConfig) -> Self {
+        let service = Self {
+            db_pool,
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .unwrap_or_default(),
+            config,
+            snapshot: Arc::new(RwLock::new(Arc::new(Snapshot::default()))),
+            deployments: Arc::new(RwLock::new(HashMap::new())),
+        };
+
+        tokio::spawn(service.clone().run());
+
+        service
+    }
+
+    async fn run(self) {
+        let mut interval =
+            tokio::time::interval(self.config.reload_interval.max(Duration::from_secs(1)));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.refresh(false).await {
+                tracing::warn!("Failed to refresh disposable email domains: {}", e);
+            }
+            if let Err(e) = self.reload().await {
+                tracing::warn!("Failed to reload disposable email domains: {}", e);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot
+            .read()
+            .map(|snapshot| snapshot.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn dataset(&self) -> DisposableDomainDataset {
+        let snapshot = self.snapshot();
+        DisposableDomainDataset {
+            domain_count: snapshot.domains.len(),
+            refreshed_at: snapshot.refreshed_at,
+        }
+    }
+
+    pub fn contains(&self, domain: &str) -> bool {
+        self.snapshot().domains.contains(domain)
+    }
+
+    /// Loads the stored upstream copy into memory.
+    pub async fn reload(&self) -> Result<DisposableDomainDataset, AppError> {
+        let domains: Vec<String> =
+            sqlx::query_scalar("SELECT domain FROM disposable_email_domains")
+                .fetch_all(&self.db_pool)
+                .await?;
+        let refreshed_at: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT MAX(refreshed_at) FROM disposable_email_domains")
+                .fetch_one(&self.db_pool)
+                .await?;
+
+        let snapshot = Arc::new(Snapshot {
+            domains: domains.into_iter().collect(),
+            refreshed_at,
+        });
+        if let Ok(mut current) = self.snapshot.write() {
+            *current = snapshot;
+        }
+
+        Ok(self.dataset())
+    }
+
+    /// Replaces the stored upstream copy with a freshly fetched one. Returns
+    /// false without fetching when another instance holds the refresh lock or,
+    /// unless `force` is set, when the stored copy is still fresh.
+    pub async fn refresh(&self, force: bool) -> Result<bool, AppError> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_xact_lock($1)")
+            .bind(REFRESH_LOCK_KEY)
+            .fetch_one(&mut *tx)
+            .await?;
+        if !locked {
+            return Ok(false);
+        }
+
+        if !force {
+            let refreshed_at: Option<DateTime<Utc>> =
+                sqlx::query_scalar("SELECT MAX(refreshed_at) FROM disposable_email_domains")
+                    .fetch_one(&mut *tx)
+                    .await?;
+            let max_age = chrono::Duration::from_std(self.config.refresh_interval)
+                .unwrap_or(chrono::Duration::MAX);
+            if refreshed_at.is_some_and(|refreshed_at| Utc::now() - refreshed_at < max_age) {
+                return Ok(false);
+            }
+        }
+
+        let body = self
+            .http_client
+            .get(&self.config.upstream_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::External(e.to_string().into()))?
+            .text()
+            .await
+            .map_err(|e| AppError::External(e.to_string().into()))?;
+
+        let domains = parse_domain_list(&body);
+        // An empty response is far more likely a broken upstream than a list
+        // that really shrank to nothing, so the stored copy is kept.
+        if domains.is_empty() {
+            return Err(AppError::External(
+                "The upstream disposable domain list is empty"
+                    .to_string()
+                    .into(),
+            ));
+        }
+
+        sqlx::query("DELETE FROM disposable_email_domains")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO disposable_email_domains (domain, refreshed_at) SELECT UNNEST($1::text[]), $2",
+        )
+        .bind(&domains)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        tracing::info!("Refreshed {} disposable email domains", domains.len());
+
+        Ok(true)
+    }
+
+    pub fn cached_deployment_domains(
+        &self,
+        deployment_id: i64,
+    ) -> Option<Arc<DeploymentDisposableDomains>> {
+        let deployments = self.deployments.read().ok()?;
+        deployments
+            .get(&deployment_id)
+            .filter(|(cached_at, _)| cached_at.elapsed() < DEPLOYMENT_CACHE_TTL)
+            .map(|(_, domains)| domains.clone())
+    }
+
+    pub fn cache_deployment_domains(
+        &self,
+        deployment_id: i64,
+        domains: DeploymentDisposableDomains,
+    ) -> Arc<DeploymentDisposableDomains> {
+        let domains = Arc::new(domains);
+        if let Ok(mut deployments) = self.deployments.write() {
+            deployments.retain(|_, (cached_at, _)| cached_at.elapsed() < DEPLOYMENT_CACHE_TTL);
+            deployments.insert(deployment_id, (Instant::now(), domains.clone()));
+        }
+        domains
+    }
+
+    /// Drops this instance's cached overrides for a deployment. Other
+    /// instances pick the change up when their cache entry expires.
+    pub fn invalidate_deployment(&self, deployment_id: i64) {
+        if let Ok(mut deployments) = self.deployments.write() {
+            deployments.remove(&deployment_id);
+        }
+    }
+
+    /// Checks a domain and each of its parent domains, most specific first,
+    /// so `mx.mailinator.com` is caught by a `mailinator.com` entry and a
+    /// deployment can allow one subdomain of a blocked provider.
+    pub fn check(
+        &self,
+        domain: &str,
+        deployment: &DeploymentDisposableDomains,
+    ) -> DisposableEmailCheck {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+        let snapshot = self.snapshot();
+
+        let (disposable, matched_domain, source) = domain_and_parents(&domain)
+            .find_map(|candidate| {
+                if deployment.allowed.contains(candidate) {
+                    Some((false, candidate, DisposableDomainSource::Deployment))
+                } else if deployment.blocked.contains(candidate) {
+                    Some((true, candidate, DisposableDomainSource::Deployment))
+                } else if snapshot.domains.contains(candidate) {
+                    Some((true, candidate, DisposableDomainSource::Upstream))
+                } else {
+                    None
+                }
+            })
+            .map(|(disposable, matched, source)| {
+                (disposable, Some(matched.to_string()), Some(source))
+            })
+            .unwrap_or((false, None, None));
+
+        DisposableEmailCheck {
+            blocked: disposable && deployment.block_disposable_emails,
+            domain,
+            disposable,
+            matched_domain,
+            source,
+        }
+    }
+
+    /// The size of the upstream list once a deployment's additions and
+    /// exceptions are applied.
+    pub fn effective_domain_count(&self, deployment: &DeploymentDisposableDomains) -> usize {
+        let snapshot = self.snapshot();
+        let added = deployment
+            .blocked
+            .iter()
+            .filter(|domain| !snapshot.domains.contains(*domain))
+            .count();
+        let removed = deployment
+            .allowed
+            .iter()
+            .filter(|domain| snapshot.domains.contains(*domain))
+            .count();
+
+        snapshot.domains.len() + added - removed
+    }
+}
+
+/// Parses a newline separated domain list, skipping blank lines and comments.
+fn parse_domain_list(body: &str) -> Vec<String> {
+    let domains: HashSet<String> = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with("//"))
+        .map(str::to_lowercase)
+        .collect();
+
+    domains.into_iter().collect()
+}
+
+/// Yields `a.b.example.com`, `b.example.com` and `example.com`, stopping
+/// before the bare top-level domain.
+fn domain_and_parents(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(domain), |current| {
+        current.split_once('.').map(|(_, parent)| parent)
+    })
+    .take_while(|candidate| candidate.contains('.'))
+}
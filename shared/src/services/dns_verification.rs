@@ -1,70 +1,203 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
 use crate::error::AppError;
-use crate::models::{DnsRecord, DomainVerificationRecords, EmailVerificationRecords};
+use crate::models::{
+    DnsRecord, DnsRecordStatus, DomainVerificationRecords, EmailVerificationRecords,
+};
+use crate::services::cloudflare::{CloudflareApi, CloudflareError};
 use chrono::Utc;
-use serde::Deserialize;
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+
+/// How long one lookup, DNS or Cloudflare, may take before the record is
+/// marked timed out.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Records checked at the same time.
+const MAX_CONCURRENT_LOOKUPS: usize = 8;
+
+pub type DnsVerificationFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<DnsVerificationTiming, AppError>> + Send + 'a>>;
+
+/// Checks the records customers add for their domains and writes each
+/// record's outcome back onto it. A trait so commands can run against
+/// [`FakeDnsVerifier`] in tests.
+pub trait DnsVerifier: Send + Sync {
+    fn verify_domain_records<'a>(
+        &'a self,
+        records: &'a mut DomainVerificationRecords,
+        cloudflare_service: &'a dyn CloudflareApi,
+    ) -> DnsVerificationFuture<'a>;
+
+    /// Records verified already aren't looked up again.
+    fn verify_email_records<'a>(
+        &'a self,
+        records: &'a mut EmailVerificationRecords,
+    ) -> DnsVerificationFuture<'a>;
+
+    fn are_domain_records_verified(&self, records: &DomainVerificationRecords) -> bool {
+        records.is_verified()
+    }
+
+    fn are_email_records_verified(&self, records: &EmailVerificationRecords) -> bool {
+        records.is_verified()
+    }
+}
+
+/// DNS-over-HTTPS response codes.
+const RCODE_NOERROR: u32 = 0;
+const RCODE_NXDOMAIN: u32 = 3;
 
 #[derive(Clone)]
-pub struct DnsVerificationService {}
+pub struct DnsVerificationService {
+    client: reqwest::Client,
+}
 
 #[derive(Debug, Deserialize)]
 struct DnsResponse {
+    #[serde(rename = "Status")]
+    status: u32,
     #[serde(rename = "Answer")]
     answer: Option<Vec<DnsAnswer>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct DnsAnswer {
-    name: String,
-    #[serde(rename = "type")]
-    record_type: u16,
-    #[serde(rename = "TTL")]
-    ttl: u32,
     data: String,
 }
 
+/// How long a verification took, for logging.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DnsVerificationTiming {
+    /// Records checked.
+    pub records: usize,
+    pub timed_out: usize,
+    pub total_ms: u64,
+    pub slowest_ms: u64,
+}
+
+impl DnsVerificationTiming {
+    fn from_checks(started_at: Instant, checks: &[(DnsRecordStatus, Duration)]) -> Self {
+        Self {
+            records: checks.len(),
+            timed_out: checks
+                .iter()
+                .filter(|(status, _)| *status == DnsRecordStatus::TimedOut)
+                .count(),
+            total_ms: started_at.elapsed().as_millis() as u64,
+            slowest_ms: checks
+                .iter()
+                .map(|(_, elapsed)| elapsed.as_millis() as u64)
+                .max()
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// Which Cloudflare checks a record goes through before falling back to a
+/// plain DNS lookup.
+#[derive(Clone, Copy)]
+enum RecordKind {
+    /// The custom hostname status, then the zone's DNS records.
+    Cloudflare,
+    /// The custom hostname status only.
+    CustomHostname,
+    /// DNS only.
+    Email,
+}
+
+fn record_type_number(record_type: &str) -> Option<u16> {
+    match record_type {
+        "A" => Some(1),
+        "CNAME" => Some(5),
+        "MX" => Some(15),
+        "TXT" => Some(16),
+        _ => None,
+    }
+}
+
+fn apply_status(
+    record: &mut DnsRecord,
+    status: DnsRecordStatus,
+    checked_at: chrono::DateTime<Utc>,
+) {
+    record.verification_attempted_at = Some(checked_at);
+    record.verified = status == DnsRecordStatus::Verified;
+    if record.verified {
+        record.last_verified_at = Some(checked_at);
+    }
+    record.status = Some(status);
+}
+
+impl Default for DnsVerificationService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DnsVerificationService {
     pub fn new() -> Self {
-        Self {}
-    }
-
-    pub fn verify_dns_record(&self, record: &DnsRecord) -> Result<bool, AppError> {
-        let record_type_num = match record.record_type.as_str() {
-            "A" => 1,
-            "CNAME" => 5,
-            "TXT" => 16,
-            "MX" => 15,
-            _ => {
-                return Err(AppError::BadRequest(format!(
-                    "Unsupported DNS record type: {}",
-                    record.record_type
-                )));
-            }
-        };
-
-        let url = format!(
-            "https://dns.google/resolve?name={}&type={}",
-            record.name, record_type_num
-        );
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(LOOKUP_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
 
-        let mut response = ureq::get(&url)
-            .header("Accept", "application/dns-json")
-            .call()
-            .map_err(|e| AppError::External(format!("DNS query failed: {}", e)))?;
+    /// Looks the record up through Google's DNS-over-HTTPS resolver.
+    pub async fn verify_dns_record(&self, record: &DnsRecord) -> DnsRecordStatus {
+        let Some(record_type) = record_type_number(&record.record_type) else {
+            tracing::warn!("Unsupported DNS record type: {}", record.record_type);
+            return DnsRecordStatus::LookupFailed;
+        };
 
-        let dns_response: DnsResponse = response
-            .body_mut()
-            .read_json()
-            .map_err(|e| AppError::External(format!("Failed to parse DNS response: {}", e)))?;
+        let lookup = async {
+            self.client
+                .get("https://dns.google/resolve")
+                .query(&[
+                    ("name", record.name.as_str()),
+                    ("type", &record_type.to_string()),
+                ])
+                .header("Accept", "application/dns-json")
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<DnsResponse>()
+                .await
+        };
 
-        if let Some(answers) = dns_response.answer {
-            for answer in answers {
-                if self.matches_expected_value(&answer.data, &record.value, &record.record_type) {
-                    return Ok(true);
-                }
+        let response = match tokio::time::timeout(LOOKUP_TIMEOUT, lookup).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) if e.is_timeout() => return DnsRecordStatus::TimedOut,
+            Ok(Err(e)) => {
+                tracing::warn!("DNS lookup for {} failed: {}", record.name, e);
+                return DnsRecordStatus::LookupFailed;
             }
+            Err(_) => return DnsRecordStatus::TimedOut,
+        };
+
+        match response.status {
+            RCODE_NOERROR => {}
+            RCODE_NXDOMAIN => return DnsRecordStatus::Missing,
+            _ => return DnsRecordStatus::LookupFailed,
         }
 
-        Ok(false)
+        let answers = response.answer.unwrap_or_default();
+        if answers.is_empty() {
+            return DnsRecordStatus::Missing;
+        }
+        if answers.iter().any(|answer| {
+            self.matches_expected_value(&answer.data, &record.value, &record.record_type)
+        }) {
+            DnsRecordStatus::Verified
+        } else {
+            DnsRecordStatus::WrongValue
+        }
     }
 
     fn matches_expected_value(&self, actual: &str, expected: &str, record_type: &str) -> bool {
@@ -78,9 +211,7 @@ impl DnsVerificationService {
                 let actual_unquoted = actual.trim_matches('"');
                 actual_unquoted == expected
             }
-            "A" => {
-                actual == expected
-            }
+            "A" => actual == expected,
             "MX" => {
                 if let Some(hostname) = actual.split_whitespace().nth(1) {
                     let hostname_normalized = hostname.trim_end_matches('.');
@@ -94,144 +225,256 @@ impl DnsVerificationService {
         }
     }
 
-    pub fn verify_domain_records(
+    /// Runs a Cloudflare check under the lookup timeout. `None` if it failed
+    /// or took too long, in which case the caller falls back.
+    async fn cloudflare_check(
         &self,
-        records: &mut DomainVerificationRecords,
-        cloudflare_service: &crate::services::cloudflare::CloudflareService,
-    ) -> Result<(), AppError> {
-        // For Cloudflare verification records, first try custom hostname API, then DNS records
-        for record in &mut records.cloudflare_verification {
-            tracing::info!("Starting verification for Cloudflare record: {}", record.name);
-            record.verification_attempted_at = Some(Utc::now());
-
-            // First try checking as a custom hostname (for domains like accounts.wacht.dev)
-            tracing::info!("Attempting custom hostname check for: {}", record.name);
-            match cloudflare_service.check_custom_hostname_status(&record.name) {
-                Ok(verified) => {
-                    record.verified = verified;
-                    if verified {
-                        record.last_verified_at = Some(Utc::now());
-                    }
-                    tracing::info!("Cloudflare custom hostname verification for {}: {} ✅", record.name, verified);
-                }
-                Err(e) => {
-                    tracing::info!("Custom hostname check failed for {}, trying DNS records: {}", record.name, e);
-
-                    // Fallback to checking DNS records
-                    match cloudflare_service.check_domain_verification_status(&record.name) {
-                        Ok(verified) => {
-                            record.verified = verified;
-                            if verified {
-                                record.last_verified_at = Some(Utc::now());
-                            }
-                            tracing::info!("Cloudflare DNS verification for {}: {} ✅", record.name, verified);
-                        }
-                        Err(dns_e) => {
-                            tracing::warn!("Cloudflare DNS check failed for {}, trying manual DNS: {}", record.name, dns_e);
-                            // Final fallback to manual DNS lookup
-                            match self.verify_dns_record(record) {
-                                Ok(verified) => {
-                                    record.verified = verified;
-                                    if verified {
-                                        record.last_verified_at = Some(Utc::now());
-                                    }
-                                    tracing::info!("Manual DNS verification for {}: {} ✅", record.name, verified);
-                                }
-                                Err(manual_e) => {
-                                    tracing::warn!("Manual DNS fallback also failed for {}: {} ❌", record.name, manual_e);
-                                    record.verified = false;
-                                }
-                            }
-                        }
-                    }
-                }
+        name: &str,
+        check: impl Future<Output = Result<bool, CloudflareError>>,
+    ) -> Option<bool> {
+        match tokio::time::timeout(LOOKUP_TIMEOUT, check).await {
+            Ok(Ok(verified)) => Some(verified),
+            Ok(Err(e)) => {
+                tracing::info!("Cloudflare check for {} failed: {}", name, e);
+                None
+            }
+            Err(_) => {
+                tracing::info!("Cloudflare check for {} timed out", name);
+                None
             }
-
-            tracing::info!("Final verification result for {}: {}", record.name, record.verified);
         }
+    }
 
-        // For custom hostname verification, use Cloudflare API to check hostname status
-        for record in &mut records.custom_hostname_verification {
-            record.verification_attempted_at = Some(Utc::now());
-
-            // Use Cloudflare API to check custom hostname status
-            match cloudflare_service.check_custom_hostname_status(&record.name) {
-                Ok(verified) => {
-                    record.verified = verified;
-                    if verified {
-                        record.last_verified_at = Some(Utc::now());
-                    }
-                    tracing::info!("Cloudflare custom hostname verification for {}: {}", record.name, verified);
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to check Cloudflare custom hostname for {}: {}", record.name, e);
-                    // Fallback to DNS lookup if Cloudflare API fails
-                    match self.verify_dns_record(record) {
-                        Ok(verified) => {
-                            record.verified = verified;
-                            if verified {
-                                record.last_verified_at = Some(Utc::now());
-                            }
-                        }
-                        Err(dns_e) => {
-                            tracing::warn!("DNS fallback also failed for {}: {}", record.name, dns_e);
-                            record.verified = false;
-                        }
-                    }
-                }
+    async fn check_record(
+        &self,
+        record: &DnsRecord,
+        kind: RecordKind,
+        cloudflare_service: Option<&dyn CloudflareApi>,
+    ) -> DnsRecordStatus {
+        let mut confirmed = None;
+
+        if let (RecordKind::Cloudflare | RecordKind::CustomHostname, Some(cloudflare)) =
+            (kind, cloudflare_service)
+        {
+            confirmed = self
+                .cloudflare_check(
+                    &record.name,
+                    cloudflare.check_custom_hostname_status(&record.name),
+                )
+                .await;
+
+            if confirmed.is_none() && matches!(kind, RecordKind::Cloudflare) {
+                confirmed = self
+                    .cloudflare_check(
+                        &record.name,
+                        cloudflare.check_domain_verification_status(&record.name),
+                    )
+                    .await;
             }
         }
 
-        Ok(())
+        match confirmed {
+            Some(true) => DnsRecordStatus::Verified,
+            // Cloudflare hasn't confirmed it, but the lookup tells the user
+            // whether that's down to their DNS.
+            Some(false) => match self.verify_dns_record(record).await {
+                DnsRecordStatus::Verified => DnsRecordStatus::Pending,
+                status => status,
+            },
+            None => self.verify_dns_record(record).await,
+        }
     }
 
-    pub fn verify_email_records(
+    async fn timed_check(
         &self,
-        records: &mut EmailVerificationRecords,
-    ) -> Result<(), AppError> {
-        for record in &mut records.dkim_records {
-            if !record.verified {
-                record.verification_attempted_at = Some(chrono::Utc::now());
-                if let Ok(verified) = self.verify_dns_record(record) {
-                    record.verified = verified;
-                    if verified {
-                        record.last_verified_at = Some(chrono::Utc::now());
-                    }
-                }
-            }
-        }
+        record: &DnsRecord,
+        kind: RecordKind,
+        cloudflare_service: Option<&dyn CloudflareApi>,
+    ) -> (DnsRecordStatus, Duration) {
+        let started_at = Instant::now();
+        let status = self.check_record(record, kind, cloudflare_service).await;
+        (status, started_at.elapsed())
+    }
 
-        for record in &mut records.return_path_records {
-            if !record.verified {
-                record.verification_attempted_at = Some(chrono::Utc::now());
-                if let Ok(verified) = self.verify_dns_record(record) {
-                    record.verified = verified;
-                    if verified {
-                        record.last_verified_at = Some(chrono::Utc::now());
-                    }
-                }
-            }
+    /// Checks the records concurrently and writes each one's outcome back.
+    async fn check_records(
+        &self,
+        records: Vec<(&mut DnsRecord, RecordKind)>,
+        cloudflare_service: Option<&dyn CloudflareApi>,
+    ) -> DnsVerificationTiming {
+        let started_at = Instant::now();
+
+        // Collected first: a stream that maps with a closure here trips up
+        // the compiler's proof that the calling command's future is `Send`.
+        let pending: Vec<_> = records
+            .iter()
+            .map(|(record, kind)| self.timed_check(record, *kind, cloudflare_service))
+            .collect();
+        let checks: Vec<(DnsRecordStatus, Duration)> = stream::iter(pending)
+            .buffered(MAX_CONCURRENT_LOOKUPS)
+            .collect()
+            .await;
+
+        let checked_at = Utc::now();
+        for ((record, _), (status, _)) in records.into_iter().zip(&checks) {
+            apply_status(record, *status, checked_at);
+            tracing::info!("DNS verification for {}: {:?}", record.name, status);
         }
 
+        DnsVerificationTiming::from_checks(started_at, &checks)
+    }
 
+    pub async fn verify_domain_records(
+        &self,
+        records: &mut DomainVerificationRecords,
+        cloudflare_service: &dyn CloudflareApi,
+    ) -> Result<DnsVerificationTiming, AppError> {
+        let records: Vec<(&mut DnsRecord, RecordKind)> = records
+            .cloudflare_verification
+            .iter_mut()
+            .map(|record| (record, RecordKind::Cloudflare))
+            .chain(
+                records
+                    .custom_hostname_verification
+                    .iter_mut()
+                    .map(|record| (record, RecordKind::CustomHostname)),
+            )
+            .collect();
 
-        Ok(())
+        Ok(self.check_records(records, Some(cloudflare_service)).await)
     }
 
-    pub fn are_domain_records_verified(&self, records: &DomainVerificationRecords) -> bool {
-        let cloudflare_verified = records.cloudflare_verification.iter().all(|r| r.verified);
-        let hostname_verified = records
-            .custom_hostname_verification
-            .iter()
-            .all(|r| r.verified);
+    pub async fn verify_email_records(
+        &self,
+        records: &mut EmailVerificationRecords,
+    ) -> Result<DnsVerificationTiming, AppError> {
+        let records: Vec<(&mut DnsRecord, RecordKind)> = records
+            .dkim_records
+            .iter_mut()
+            .chain(records.return_path_records.iter_mut())
+            .filter(|record| !record.verified)
+            .map(|record| (record, RecordKind::Email))
+            .collect();
 
-        cloudflare_verified && hostname_verified
+        Ok(self.check_records(records, None).await)
     }
+}
+
+impl DnsVerifier for DnsVerificationService {
+    fn verify_domain_records<'a>(
+        &'a self,
+        records: &'a mut DomainVerificationRecords,
+        cloudflare_service: &'a dyn CloudflareApi,
+    ) -> DnsVerificationFuture<'a> {
+        Box::pin(DnsVerificationService::verify_domain_records(
+            self,
+            records,
+            cloudflare_service,
+        ))
+    }
+
+    fn verify_email_records<'a>(
+        &'a self,
+        records: &'a mut EmailVerificationRecords,
+    ) -> DnsVerificationFuture<'a> {
+        Box::pin(DnsVerificationService::verify_email_records(self, records))
+    }
+}
+
+#[derive(Default)]
+struct FakeDnsVerifierState {
+    statuses: HashMap<String, DnsRecordStatus>,
+    failing: bool,
+    checked: Vec<String>,
+}
 
-    pub fn are_email_records_verified(&self, records: &EmailVerificationRecords) -> bool {
-        let dkim_verified = records.dkim_records.iter().all(|r| r.verified);
-        let return_path_verified = records.return_path_records.iter().all(|r| r.verified);
+/// Answers lookups from statuses set per record name, for tests. Records
+/// without one are `Missing`.
+#[derive(Default)]
+pub struct FakeDnsVerifier {
+    state: Mutex<FakeDnsVerifierState>,
+}
+
+impl FakeDnsVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_status(&self, name: &str, status: DnsRecordStatus) {
+        self.lock().statuses.insert(name.to_string(), status);
+    }
+
+    /// Makes every verification from now on fail outright, as if the
+    /// resolver couldn't be reached.
+    pub fn fail(&self) {
+        self.lock().failing = true;
+    }
+
+    /// The names of the records looked up so far, in order.
+    pub fn checked(&self) -> Vec<String> {
+        self.lock().checked.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FakeDnsVerifierState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn check<'a>(
+        &self,
+        records: impl Iterator<Item = &'a mut DnsRecord>,
+    ) -> Result<DnsVerificationTiming, AppError> {
+        let mut state = self.lock();
+        if state.failing {
+            return Err(AppError::Internal(
+                "scripted DNS verification failure".to_string(),
+            ));
+        }
+
+        let checked_at = Utc::now();
+        let mut timing = DnsVerificationTiming::default();
+        for record in records {
+            let status = state
+                .statuses
+                .get(&record.name)
+                .copied()
+                .unwrap_or(DnsRecordStatus::Missing);
+            state.checked.push(record.name.clone());
+            apply_status(record, status, checked_at);
+            timing.records += 1;
+        }
+
+        Ok(timing)
+    }
+}
+
+impl DnsVerifier for FakeDnsVerifier {
+    fn verify_domain_records<'a>(
+        &'a self,
+        records: &'a mut DomainVerificationRecords,
+        _cloudflare_service: &'a dyn CloudflareApi,
+    ) -> DnsVerificationFuture<'a> {
+        let result = self.check(
+            records
+                .cloudflare_verification
+                .iter_mut()
+                .chain(records.custom_hostname_verification.iter_mut()),
+        );
+
+        Box::pin(async move { result })
+    }
+
+    fn verify_email_records<'a>(
+        &'a self,
+        records: &'a mut EmailVerificationRecords,
+    ) -> DnsVerificationFuture<'a> {
+        let result = self.check(
+            records
+                .dkim_records
+                .iter_mut()
+                .chain(records.return_path_records.iter_mut())
+                .filter(|record| !record.verified),
+        );
 
-        dkim_verified && return_path_verified
+        Box::pin(async move { result })
     }
 }
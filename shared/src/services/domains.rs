@@ -0,0 +1,159 @@
+/// The platform's own domains: where staging deployments are hosted and
+/// what production custom hostnames point at.
+///
+/// Only read when a deployment is created. Its hosts and verification
+/// records are stored on the deployment, so changing these later leaves
+/// existing deployments as they are.
+#[derive(Debug, Clone)]
+pub struct DomainsConfig {
+    /// Staging frontends are served from `{name}.{staging_frontend_base}`.
+    pub staging_frontend_base: String,
+    /// Staging backends are served from `{name}.{staging_backend_base}`.
+    pub staging_backend_base: String,
+    /// Mail from every staging deployment is sent from this host.
+    pub staging_mail_from_host: String,
+    /// The Cloudflare origin a production frontend hostname is a CNAME to.
+    pub frontend_origin: String,
+    /// The Cloudflare origin a production backend hostname is a CNAME to.
+    pub backend_origin: String,
+}
+
+impl Default for DomainsConfig {
+    fn default() -> Self {
+        Self {
+            staging_frontend_base: "wacht.tech".to_string(),
+            staging_backend_base: "backend-api.services".to_string(),
+            staging_mail_from_host: "staging.wacht.services".to_string(),
+            frontend_origin: "accounts.wacht.services".to_string(),
+            backend_origin: "frontend.wacht.services".to_string(),
+        }
+    }
+}
+
+impl DomainsConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let domain = |key: &str, default: String| {
+            std::env::var(key)
+                .ok()
+                .map(|v| v.trim().trim_matches('.').to_lowercase())
+                .filter(|v| !v.is_empty())
+                .unwrap_or(default)
+        };
+
+        Self {
+            staging_frontend_base: domain(
+                "STAGING_FRONTEND_BASE_DOMAIN",
+                defaults.staging_frontend_base,
+            ),
+            staging_backend_base: domain(
+                "STAGING_BACKEND_BASE_DOMAIN",
+                defaults.staging_backend_base,
+            ),
+            staging_mail_from_host: domain(
+                "STAGING_MAIL_FROM_HOST",
+                defaults.staging_mail_from_host,
+            ),
+            frontend_origin: domain("CLOUDFLARE_FRONTEND_ORIGIN", defaults.frontend_origin),
+            backend_origin: domain("CLOUDFLARE_BACKEND_ORIGIN", defaults.backend_origin),
+        }
+    }
+
+    pub fn staging_frontend_host(&self, name: &str) -> String {
+        format!("{}.{}", name, self.staging_frontend_base)
+    }
+
+    pub fn staging_backend_host(&self, name: &str) -> String {
+        format!("{}.{}", name, self.staging_backend_base)
+    }
+}
+
+/// `https://{host}`, leaving hosts that already carry the scheme alone.
+pub fn https_url(host: &str) -> String {
+    if host.starts_with("https://") {
+        host.to_string()
+    } else {
+        format!("https://{}", host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::CloudflareService;
+
+    fn custom() -> DomainsConfig {
+        DomainsConfig {
+            staging_frontend_base: "auth.example.dev".to_string(),
+            staging_backend_base: "api.example.dev".to_string(),
+            staging_mail_from_host: "mail.example.dev".to_string(),
+            frontend_origin: "accounts-origin.example.dev".to_string(),
+            backend_origin: "api-origin.example.dev".to_string(),
+        }
+    }
+
+    #[test]
+    fn staging_hosts_use_the_configured_bases() {
+        let domains = custom();
+
+        assert_eq!(
+            domains.staging_frontend_host("quiet-river-3"),
+            "quiet-river-3.auth.example.dev"
+        );
+        assert_eq!(
+            domains.staging_backend_host("quiet-river-3"),
+            "quiet-river-3.api.example.dev"
+        );
+        assert_eq!(
+            https_url(&domains.staging_frontend_host("quiet-river-3")),
+            "https://quiet-river-3.auth.example.dev"
+        );
+    }
+
+    #[test]
+    fn defaults_match_the_hosted_platform() {
+        let domains = DomainsConfig::default();
+
+        assert_eq!(domains.staging_frontend_host("a-1"), "a-1.wacht.tech");
+        assert_eq!(
+            domains.staging_backend_host("a-1"),
+            "a-1.backend-api.services"
+        );
+    }
+
+    #[test]
+    fn https_url_keeps_an_existing_scheme() {
+        assert_eq!(
+            https_url("accounts.example.com"),
+            "https://accounts.example.com"
+        );
+        assert_eq!(
+            https_url("https://accounts.example.com"),
+            "https://accounts.example.com"
+        );
+    }
+
+    #[test]
+    fn verification_records_point_at_the_configured_origins() {
+        let cloudflare = CloudflareService::new(String::new(), String::new());
+        let records = cloudflare.generate_domain_verification_records(
+            "accounts.customer.com",
+            "frontend.customer.com",
+            &custom(),
+        );
+
+        let targets: Vec<(&str, &str)> = records
+            .custom_hostname_verification
+            .iter()
+            .map(|r| (r.name.as_str(), r.value.as_str()))
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                ("accounts.customer.com", "accounts-origin.example.dev"),
+                ("frontend.customer.com", "api-origin.example.dev"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    error::AppError,
+    models::{EmailProviderCredentials, SmtpCredentials},
+};
+
+use super::{PostmarkService, SesService, SmtpService};
+
+/// Where an email is sent from: the platform's Postmark server, or a
+/// deployment's own provider.
+#[derive(Clone)]
+pub enum EmailTransport {
+    Postmark(PostmarkService),
+    Ses(SesService),
+    Smtp(SmtpService, SmtpCredentials),
+}
+
+impl EmailTransport {
+    pub fn from_credentials(credentials: EmailProviderCredentials, smtp: &SmtpService) -> Self {
+        match credentials {
+            EmailProviderCredentials::Postmark(postmark) => {
+                EmailTransport::Postmark(PostmarkService::for_server(postmark.server_token))
+            }
+            EmailProviderCredentials::Ses(ses) => EmailTransport::Ses(SesService::new(ses)),
+            EmailProviderCredentials::Smtp(credentials) => {
+                EmailTransport::Smtp(smtp.clone(), credentials)
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailTransport::Postmark(_) => "Postmark",
+            EmailTransport::Ses(_) => "Amazon SES",
+            EmailTransport::Smtp(..) => "SMTP",
+        }
+    }
+
+    /// Makes an authenticated call that sends nothing, to check the
+    /// credentials work.
+    pub async fn verify(&self) -> Result<(), AppError> {
+        match self {
+            EmailTransport::Postmark(postmark) => Ok(postmark.verify_server_token().await?),
+            EmailTransport::Ses(ses) => ses.verify_credentials().await,
+            EmailTransport::Smtp(smtp, credentials) => smtp.verify_credentials(credentials).await,
+        }
+    }
+
+    /// Sends one email and returns the provider's message id.
+    pub async fn send_email(
+        &self,
+        from: &str,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        text_body: Option<&str>,
+    ) -> Result<String, AppError> {
+        match self {
+            EmailTransport::Postmark(postmark) => Ok(postmark
+                .send_email(from, to, subject, html_body, text_body)
+                .await?
+                .message_id),
+            EmailTransport::Ses(ses) => {
+                ses.send_email(from, to, subject, html_body, text_body)
+                    .await
+            }
+            EmailTransport::Smtp(smtp, credentials) => {
+                smtp.send_email(credentials, from, to, subject, html_body, text_body)
+                    .await
+            }
+        }
+    }
+}
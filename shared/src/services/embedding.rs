@@ -1,51 +1,682 @@
-use crate::error::AppError;
-use llm::builder::{LLMBackend, LLMBuilder};
+//! Text embeddings, from providers the platform operator configures through
+//! the environment. Deployments and knowledge bases pick one of them by name.
+//!
+//! | Provider | Configured by                                                  |
+//! |----------|----------------------------------------------------------------|
+//! | `gemini` | `GEMINI_API_KEY`, `GEMINI_EMBEDDING_MODEL`, `GEMINI_EMBEDDING_DIMENSIONS` |
+//! | `openai` | `OPENAI_API_KEY`, `OPENAI_BASE_URL`, `OPENAI_EMBEDDING_MODEL`, `OPENAI_EMBEDDING_DIMENSIONS` |
+//! | `local`  | `LOCAL_EMBEDDING_URL`, `LOCAL_EMBEDDING_MODEL`, `LOCAL_EMBEDDING_DIMENSIONS`, `LOCAL_EMBEDDING_API_KEY`, `LOCAL_EMBEDDING_BATCH_SIZE` |
+//!
+//! `local` is any server with an OpenAI-compatible `/embeddings` endpoint.
+//! The `_DIMENSIONS` variables give the length of the model's vectors, which
+//! vector collections are sized by. They only have to be set for models this
+//! module doesn't know.
+//! `EMBEDDING_PROVIDER` names the default (`gemini` unless set), and
+//! `EMBEDDING_FALLBACK_PROVIDER` a provider to use when another one serving
+//! the same model is down.
 
-#[derive(Debug, Clone)]
-pub struct EmbeddingService {
+use std::{collections::BTreeMap, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use futures::{StreamExt, TryStreamExt};
+use qdrant_client::qdrant::Distance;
+use reqwest::{Client, StatusCode, header::HeaderMap};
+use serde_json::{Value, json};
+use tokio::sync::Semaphore;
+
+use crate::{
+    error::{AppError, ExternalError},
+    services::{http_client::provider_client, text_processing::Tokenizer},
+};
+
+const DEFAULT_GEMINI_MODEL: &str = "text-embedding-004";
+const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_OPENAI_MODEL: &str = "text-embedding-3-small";
+
+/// Vector lengths of the models the providers serve by default, and of
+/// their common alternatives.
+const KNOWN_DIMENSIONS: [(&str, u64); 6] = [
+    ("text-embedding-004", 768),
+    ("embedding-001", 768),
+    ("gemini-embedding-001", 3072),
+    ("text-embedding-3-small", 1536),
+    ("text-embedding-3-large", 3072),
+    ("text-embedding-ada-002", 1536),
+];
+
+/// Gemini's `batchEmbedContents` takes at most 100 texts.
+const GEMINI_MAX_BATCH_SIZE: usize = 100;
+const OPENAI_MAX_BATCH_SIZE: usize = 2048;
+
+/// Batches one provider embeds at the same time, across all callers.
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Attempts per batch while the provider answers 429.
+const RATE_LIMITED_ATTEMPTS: u32 = 5;
+/// Attempts per batch while the provider is unreachable or failing.
+const UNAVAILABLE_ATTEMPTS: u32 = 2;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// A longer `Retry-After` is cut down to this.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+pub type EmbeddingFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<Vec<f32>>, EmbeddingError>> + Send + 'a>>;
+
+#[derive(Debug)]
+pub enum EmbeddingError {
+    /// A 429, with the delay the provider asked for when it gave one.
+    RateLimited { retry_after: Option<Duration> },
+    /// The provider couldn't be reached, or failed on its side.
+    Unavailable(String),
+    /// The provider refused the request; sending it again won't help.
+    Rejected(String),
+}
+
+impl EmbeddingError {
+    fn from_response(status: StatusCode, headers: &HeaderMap, body: &str) -> Self {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = headers
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            return EmbeddingError::RateLimited { retry_after };
+        }
+
+        let message = format!(
+            "HTTP {}: {}",
+            status.as_u16(),
+            body.chars().take(500).collect::<String>()
+        );
+        if status.is_server_error() {
+            EmbeddingError::Unavailable(message)
+        } else {
+            EmbeddingError::Rejected(message)
+        }
+    }
+
+    fn into_app_error(self, provider: &str) -> AppError {
+        let message = match self {
+            EmbeddingError::RateLimited { .. } => {
+                format!(
+                    "Embedding provider '{}' is rate limiting requests",
+                    provider
+                )
+            }
+            EmbeddingError::Unavailable(reason) | EmbeddingError::Rejected(reason) => {
+                format!("Embedding provider '{}' failed: {}", provider, reason)
+            }
+        };
+        AppError::External(ExternalError::from(message))
+    }
+}
+
+/// One embedding API. Different models' vectors can't be compared, so the
+/// model is part of what a knowledge base records about its vectors.
+pub trait EmbeddingProvider: Send + Sync {
+    fn model(&self) -> &str;
+
+    /// Length of the model's vectors.
+    fn dimensions(&self) -> u64;
+
+    /// How the model's vectors are compared. The models offered are all
+    /// trained for cosine similarity.
+    fn distance(&self) -> Distance {
+        Distance::Cosine
+    }
+
+    /// How the model splits text into tokens, which chunks are sized by.
+    fn tokenizer(&self) -> Tokenizer {
+        Tokenizer::for_model(self.model())
+    }
+
+    /// Most texts one request can carry.
+    fn max_batch_size(&self) -> usize;
+
+    /// One vector per text, in order. Never called with more than
+    /// [`max_batch_size`](Self::max_batch_size) texts.
+    fn embed<'a>(&'a self, texts: &'a [String]) -> EmbeddingFuture<'a>;
+}
+
+/// The length of `model`'s vectors, from `variable` if it is set.
+fn model_dimensions(model: &str, variable: &str) -> Result<u64, AppError> {
+    if let Ok(value) = std::env::var(variable) {
+        return value
+            .parse()
+            .ok()
+            .filter(|dimensions| *dimensions > 0)
+            .ok_or_else(|| AppError::Internal(format!("{} must be a positive number", variable)));
+    }
+
+    KNOWN_DIMENSIONS
+        .iter()
+        .find(|(known, _)| *known == model)
+        .map(|(_, dimensions)| *dimensions)
+        .ok_or_else(|| {
+            AppError::Internal(format!(
+                "{} must be set, as the length of '{}' vectors isn't known",
+                variable, model
+            ))
+        })
+}
+
+pub struct GeminiEmbeddingProvider {
+    client: Client,
     api_key: String,
     model: String,
+    dimensions: u64,
 }
 
-impl EmbeddingService {
-    pub fn new() -> Result<Self, AppError> {
-        let api_key = std::env::var("GEMINI_API_KEY").map_err(|_| {
-            AppError::Internal("GEMINI_API_KEY environment variable not set".to_string())
-        })?;
+impl GeminiEmbeddingProvider {
+    pub fn new(api_key: String, model: String, dimensions: u64) -> Self {
+        Self {
+            client: provider_client(),
+            api_key,
+            model,
+            dimensions,
+        }
+    }
+}
+
+impl EmbeddingProvider for GeminiEmbeddingProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimensions(&self) -> u64 {
+        self.dimensions
+    }
+
+    fn max_batch_size(&self) -> usize {
+        GEMINI_MAX_BATCH_SIZE
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> EmbeddingFuture<'a> {
+        Box::pin(async move {
+            let model = format!("models/{}", self.model);
+            let requests: Vec<Value> = texts
+                .iter()
+                .map(|text| json!({ "model": model, "content": { "parts": [{ "text": text }] } }))
+                .collect();
+
+            let response = self
+                .client
+                .post(format!(
+                    "https://generativelanguage.googleapis.com/v1beta/{}:batchEmbedContents",
+                    model
+                ))
+                .header("x-goog-api-key", &self.api_key)
+                .json(&json!({ "requests": requests }))
+                .send()
+                .await
+                .map_err(|e| EmbeddingError::Unavailable(e.to_string()))?;
+
+            let body = read_response(response).await?;
+            let embeddings = body
+                .get("embeddings")
+                .and_then(Value::as_array)
+                .ok_or_else(|| {
+                    EmbeddingError::Rejected("The response has no embeddings".to_string())
+                })?;
+
+            embeddings
+                .iter()
+                .map(|embedding| vector(embedding.get("values")))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|vectors| expect_count(vectors, texts.len()))
+        })
+    }
+}
+
+/// Any `/embeddings` endpoint that speaks the OpenAI API, hosted or a model
+/// server on the operator's own network.
+pub struct OpenAiCompatibleEmbeddingProvider {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    dimensions: u64,
+    max_batch_size: usize,
+}
+
+impl OpenAiCompatibleEmbeddingProvider {
+    pub fn new(base_url: String, api_key: Option<String>, model: String, dimensions: u64) -> Self {
+        Self {
+            client: provider_client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model,
+            dimensions,
+            max_batch_size: OPENAI_MAX_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self
+    }
+}
+
+impl EmbeddingProvider for OpenAiCompatibleEmbeddingProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimensions(&self) -> u64 {
+        self.dimensions
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> EmbeddingFuture<'a> {
+        Box::pin(async move {
+            let mut request = self
+                .client
+                .post(format!("{}/embeddings", self.base_url))
+                .json(&json!({ "model": self.model, "input": texts }));
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+
+            let response = request
+                .send()
+                .await
+                .map_err(|e| EmbeddingError::Unavailable(e.to_string()))?;
+
+            let body = read_response(response).await?;
+            let mut data: Vec<&Value> = body
+                .get("data")
+                .and_then(Value::as_array)
+                .ok_or_else(|| EmbeddingError::Rejected("The response has no data".to_string()))?
+                .iter()
+                .collect();
+            // The order of `data` isn't promised, `index` is
+            data.sort_by_key(|item| item.get("index").and_then(Value::as_u64).unwrap_or(0));
+
+            data.into_iter()
+                .map(|item| vector(item.get("embedding")))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|vectors| expect_count(vectors, texts.len()))
+        })
+    }
+}
+
+async fn read_response(response: reqwest::Response) -> Result<Value, EmbeddingError> {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| EmbeddingError::Unavailable(e.to_string()))?;
 
-        let model = std::env::var("GEMINI_EMBEDDING_MODEL")
-            .unwrap_or_else(|_| "text-embedding-004".to_string());
+    if !status.is_success() {
+        return Err(EmbeddingError::from_response(status, &headers, &body));
+    }
+
+    serde_json::from_str(&body)
+        .map_err(|e| EmbeddingError::Rejected(format!("The response isn't JSON: {}", e)))
+}
+
+fn vector(values: Option<&Value>) -> Result<Vec<f32>, EmbeddingError> {
+    values
+        .and_then(Value::as_array)
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_f64().map(|value| value as f32))
+                .collect()
+        })
+        .ok_or_else(|| EmbeddingError::Rejected("The response has a malformed vector".to_string()))
+}
+
+fn expect_count(vectors: Vec<Vec<f32>>, expected: usize) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+    if vectors.len() != expected {
+        return Err(EmbeddingError::Rejected(format!(
+            "Asked for {} vectors, got {}",
+            expected,
+            vectors.len()
+        )));
+    }
+    Ok(vectors)
+}
+
+/// Vectors of another length wouldn't fit the collections sized for the
+/// model, so a misconfigured length is caught here rather than there.
+fn expect_dimensions(
+    vectors: Vec<Vec<f32>>,
+    dimensions: u64,
+) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+    match vectors
+        .iter()
+        .find(|vector| vector.len() as u64 != dimensions)
+    {
+        Some(vector) => Err(EmbeddingError::Rejected(format!(
+            "Expected {}-dimensional vectors, got {}",
+            dimensions,
+            vector.len()
+        ))),
+        None => Ok(vectors),
+    }
+}
+
+/// Embeds with a provider until it stops answering 429 or being down, up to
+/// a few attempts.
+async fn embed_with_retries(
+    provider: &dyn EmbeddingProvider,
+    texts: &[String],
+) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        let delay = match provider.embed(texts).await {
+            Err(EmbeddingError::RateLimited { retry_after }) if attempt < RATE_LIMITED_ATTEMPTS => {
+                retry_after.unwrap_or(backoff)
+            }
+            Err(EmbeddingError::Unavailable(_)) if attempt < UNAVAILABLE_ATTEMPTS => backoff,
+            result => {
+                return result
+                    .and_then(|vectors| expect_dimensions(vectors, provider.dimensions()));
+            }
+        };
+
+        tokio::time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// A configured provider, with its fallback and its share of concurrency.
+#[derive(Clone)]
+pub struct Embedder {
+    name: String,
+    primary: Arc<dyn EmbeddingProvider>,
+    fallback: Option<(String, Arc<dyn EmbeddingProvider>)>,
+    permits: Arc<Semaphore>,
+    max_concurrency: usize,
+}
 
-        Ok(Self { api_key, model })
+impl Embedder {
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    pub async fn generate_embeddings(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, AppError> {
+    pub fn model(&self) -> &str {
+        self.primary.model()
+    }
+
+    pub fn dimensions(&self) -> u64 {
+        self.primary.dimensions()
+    }
+
+    pub fn distance(&self) -> Distance {
+        self.primary.distance()
+    }
+
+    pub fn tokenizer(&self) -> Tokenizer {
+        self.primary.tokenizer()
+    }
+
+    pub fn fallback(&self) -> Option<&str> {
+        self.fallback.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    /// One vector per text, in order. The texts are split into batches the
+    /// provider (and its fallback) accept, and the batches embedded
+    /// concurrently.
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, AppError> {
         if texts.is_empty() {
             return Ok(vec![]);
         }
 
-        // Build LLM for each request (since we can't store it due to Clone issues)
-        let llm = LLMBuilder::new()
-            .backend(LLMBackend::Google)
-            .api_key(&self.api_key)
-            .model(&self.model)
-            .build()
-            .map_err(|e| AppError::Internal(format!("Failed to initialize Gemini LLM: {}", e)))?;
+        let batch_size = match &self.fallback {
+            Some((_, fallback)) => self.primary.max_batch_size().min(fallback.max_batch_size()),
+            None => self.primary.max_batch_size(),
+        };
 
-        // Use the llm crate to generate embeddings
-        let embeddings = llm
-            .embed(texts)
-            .await
-            .map_err(|e| AppError::Internal(format!("Failed to generate embeddings: {}", e)))?;
+        // Owned batches, so the stream's futures don't borrow from it, which
+        // would keep them from being `Send`
+        let batches: Vec<Vec<String>> = texts
+            .chunks(batch_size.max(1))
+            .map(<[String]>::to_vec)
+            .collect();
+        let batches: Vec<Vec<Vec<f32>>> = futures::stream::iter(batches)
+            .map(|batch| self.embed_batch(batch))
+            .buffered(self.max_concurrency)
+            .try_collect()
+            .await?;
 
-        Ok(embeddings)
+        Ok(batches.into_iter().flatten().collect())
     }
 
-    pub async fn generate_embedding(&self, text: String) -> Result<Vec<f32>, AppError> {
-        let embeddings = self.generate_embeddings(vec![text]).await?;
-        embeddings
+    pub async fn embed_one(&self, text: String) -> Result<Vec<f32>, AppError> {
+        self.embed(vec![text])
+            .await?
             .into_iter()
             .next()
             .ok_or_else(|| AppError::Internal("No embedding returned".to_string()))
     }
+
+    async fn embed_batch(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, AppError> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        match embed_with_retries(self.primary.as_ref(), &texts).await {
+            Ok(vectors) => Ok(vectors),
+            Err(EmbeddingError::Rejected(reason)) => {
+                Err(EmbeddingError::Rejected(reason).into_app_error(&self.name))
+            }
+            Err(e) => {
+                let Some((fallback_name, fallback)) = &self.fallback else {
+                    return Err(e.into_app_error(&self.name));
+                };
+
+                tracing::warn!(
+                    "Embedding provider '{}' failed ({:?}), falling back to '{}'",
+                    self.name,
+                    e,
+                    fallback_name
+                );
+                embed_with_retries(fallback.as_ref(), &texts)
+                    .await
+                    .map_err(|e| e.into_app_error(fallback_name))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EmbeddingService {
+    embedders: Arc<BTreeMap<String, Embedder>>,
+    default: String,
+}
+
+impl EmbeddingService {
+    pub fn new() -> Result<Self, AppError> {
+        let mut providers: Vec<(String, Arc<dyn EmbeddingProvider>)> = Vec::new();
+
+        if let Ok(api_key) = std::env::var("GEMINI_API_KEY") {
+            let model = std::env::var("GEMINI_EMBEDDING_MODEL")
+                .unwrap_or_else(|_| DEFAULT_GEMINI_MODEL.to_string());
+            let dimensions = model_dimensions(&model, "GEMINI_EMBEDDING_DIMENSIONS")?;
+            providers.push((
+                "gemini".to_string(),
+                Arc::new(GeminiEmbeddingProvider::new(api_key, model, dimensions)),
+            ));
+        }
+
+        if let Ok(api_key) = std::env::var("OPENAI_API_KEY") {
+            let base_url = std::env::var("OPENAI_BASE_URL")
+                .unwrap_or_else(|_| DEFAULT_OPENAI_BASE_URL.to_string());
+            let model = std::env::var("OPENAI_EMBEDDING_MODEL")
+                .unwrap_or_else(|_| DEFAULT_OPENAI_MODEL.to_string());
+            let dimensions = model_dimensions(&model, "OPENAI_EMBEDDING_DIMENSIONS")?;
+            providers.push((
+                "openai".to_string(),
+                Arc::new(OpenAiCompatibleEmbeddingProvider::new(
+                    base_url,
+                    Some(api_key),
+                    model,
+                    dimensions,
+                )),
+            ));
+        }
+
+        if let Ok(base_url) = std::env::var("LOCAL_EMBEDDING_URL") {
+            let model = std::env::var("LOCAL_EMBEDDING_MODEL").map_err(|_| {
+                AppError::Internal(
+                    "LOCAL_EMBEDDING_MODEL must be set along with LOCAL_EMBEDDING_URL".to_string(),
+                )
+            })?;
+            let dimensions = model_dimensions(&model, "LOCAL_EMBEDDING_DIMENSIONS")?;
+            let mut provider = OpenAiCompatibleEmbeddingProvider::new(
+                base_url,
+                std::env::var("LOCAL_EMBEDDING_API_KEY").ok(),
+                model,
+                dimensions,
+            );
+            if let Some(batch_size) = std::env::var("LOCAL_EMBEDDING_BATCH_SIZE")
+                .ok()
+                .and_then(|value| value.parse().ok())
+            {
+                provider = provider.with_max_batch_size(batch_size);
+            }
+            providers.push(("local".to_string(), Arc::new(provider)));
+        }
+
+        let default = std::env::var("EMBEDDING_PROVIDER").unwrap_or_else(|_| "gemini".to_string());
+        let fallback = std::env::var("EMBEDDING_FALLBACK_PROVIDER").ok();
+        let max_concurrency = std::env::var("EMBEDDING_MAX_CONCURRENCY")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MAX_CONCURRENCY);
+
+        Self::from_providers(providers, &default, fallback.as_deref(), max_concurrency)
+    }
+
+    /// A service with only the Gemini provider.
+    pub fn with_api_key(api_key: String, model: String) -> Self {
+        let dimensions = KNOWN_DIMENSIONS
+            .iter()
+            .find(|(known, _)| *known == model)
+            .map_or(768, |(_, dimensions)| *dimensions);
+        let provider: Arc<dyn EmbeddingProvider> =
+            Arc::new(GeminiEmbeddingProvider::new(api_key, model, dimensions));
+        Self::from_providers(
+            vec![("gemini".to_string(), provider)],
+            "gemini",
+            None,
+            DEFAULT_MAX_CONCURRENCY,
+        )
+        .expect("the only provider is the default")
+    }
+
+    /// The fallback backs every other provider serving the same model, since
+    /// only then are its vectors interchangeable with theirs.
+    pub fn from_providers(
+        providers: Vec<(String, Arc<dyn EmbeddingProvider>)>,
+        default: &str,
+        fallback: Option<&str>,
+        max_concurrency: usize,
+    ) -> Result<Self, AppError> {
+        let max_concurrency = max_concurrency.max(1);
+        let fallback = match fallback {
+            Some(name) => Some(
+                providers
+                    .iter()
+                    .find(|(provider_name, _)| provider_name == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        AppError::Internal(format!(
+                            "The fallback embedding provider '{}' is not configured",
+                            name
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+
+        let embedders: BTreeMap<String, Embedder> = providers
+            .into_iter()
+            .map(|(name, primary)| {
+                let fallback = fallback.clone().filter(|(fallback_name, fallback)| {
+                    *fallback_name != name && fallback.model() == primary.model()
+                });
+                let embedder = Embedder {
+                    name: name.clone(),
+                    primary,
+                    fallback,
+                    permits: Arc::new(Semaphore::new(max_concurrency)),
+                    max_concurrency,
+                };
+                (name, embedder)
+            })
+            .collect();
+
+        if !embedders.contains_key(default) {
+            return Err(AppError::Internal(format!(
+                "The default embedding provider '{}' is not configured",
+                default
+            )));
+        }
+        if let Some((fallback_name, _)) = &fallback
+            && !embedders
+                .values()
+                .any(|embedder| embedder.fallback() == Some(fallback_name.as_str()))
+        {
+            return Err(AppError::Internal(format!(
+                "The fallback embedding provider '{}' serves a model no other provider does",
+                fallback_name
+            )));
+        }
+
+        Ok(Self {
+            embedders: Arc::new(embedders),
+            default: default.to_string(),
+        })
+    }
+
+    pub fn default_embedder(&self) -> &Embedder {
+        &self.embedders[&self.default]
+    }
+
+    pub fn embedder(&self, name: &str) -> Option<&Embedder> {
+        self.embedders.get(name)
+    }
+
+    /// The named provider, or the default when there is no name. Errors if
+    /// the name isn't a configured provider.
+    pub fn select(&self, name: Option<&str>) -> Result<&Embedder, AppError> {
+        match name {
+            Some(name) => self.embedder(name).ok_or_else(|| {
+                AppError::BadRequest(format!("Embedding provider '{}' is not configured", name))
+            }),
+            None => Ok(self.default_embedder()),
+        }
+    }
+
+    /// A provider serving `model`, `preferred` if it does.
+    pub fn embedder_for_model(&self, model: &str, preferred: Option<&str>) -> Option<&Embedder> {
+        preferred
+            .and_then(|name| self.embedder(name))
+            .filter(|embedder| embedder.model() == model)
+            .or_else(|| {
+                self.embedders
+                    .get(&self.default)
+                    .filter(|embedder| embedder.model() == model)
+            })
+            .or_else(|| {
+                self.embedders
+                    .values()
+                    .find(|embedder| embedder.model() == model)
+            })
+    }
+
+    pub fn embedders(&self) -> impl Iterator<Item = &Embedder> {
+        self.embedders.values()
+    }
 }
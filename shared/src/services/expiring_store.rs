@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use redis::AsyncCommands;
+
+use crate::{
+    error::AppError,
+    services::{RedisConnection, RedisPool},
+    utils::clock::Clock,
+};
+
+pub type ExpiringStoreFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, AppError>> + Send + 'a>>;
+
+/// Integer values that expire after a TTL. Backs lockout counters, one-time
+/// codes and wallet challenges.
+pub trait ExpiringStore: Send + Sync {
+    /// Increments a counter, creating it with `ttl` when it doesn't exist.
+    /// Later increments keep the original expiry.
+    fn increment<'a>(&'a self, key: &'a str, ttl: Duration) -> ExpiringStoreFuture<'a, i64>;
+
+    fn set<'a>(&'a self, key: &'a str, value: i64, ttl: Duration) -> ExpiringStoreFuture<'a, ()>;
+
+    fn get<'a>(&'a self, key: &'a str) -> ExpiringStoreFuture<'a, Option<i64>>;
+
+    /// Deletes `key` only if it holds `value`, in one step, and returns
+    /// whether it did.
+    fn delete_if_equal<'a>(&'a self, key: &'a str, value: i64) -> ExpiringStoreFuture<'a, bool>;
+
+    fn delete<'a>(&'a self, keys: &'a [String]) -> ExpiringStoreFuture<'a, ()>;
+
+    fn delete_prefix<'a>(&'a self, prefix: &'a str) -> ExpiringStoreFuture<'a, ()>;
+}
+
+pub struct RedisExpiringStore {
+    redis: RedisPool,
+}
+
+impl RedisExpiringStore {
+    pub fn new(redis: RedisPool) -> Self {
+        Self { redis }
+    }
+
+    async fn connection(&self) -> Result<RedisConnection, AppError> {
+        self.redis.connection().await
+    }
+}
+
+impl ExpiringStore for RedisExpiringStore {
+    fn increment<'a>(&'a self, key: &'a str, ttl: Duration) -> ExpiringStoreFuture<'a, i64> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let (count,): (i64,) = redis::pipe()
+                .atomic()
+                .cmd("SET")
+                .arg(key)
+                .arg(0)
+                .arg("EX")
+                .arg(ttl.num_seconds().max(1))
+                .arg("NX")
+                .ignore()
+                .incr(key, 1)
+                .query_async(&mut connection)
+                .await?;
+            Ok(count)
+        })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: i64, ttl: Duration) -> ExpiringStoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let _: () = connection
+                .set_ex(key, value, ttl.num_seconds().max(1) as u64)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> ExpiringStoreFuture<'a, Option<i64>> {
+        Box::pin(self.redis.get(key))
+    }
+
+    fn delete_if_equal<'a>(&'a self, key: &'a str, value: i64) -> ExpiringStoreFuture<'a, bool> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let deleted: i64 = redis::Script::new(
+                r#"
+                if redis.call('GET', KEYS[1]) == ARGV[1] then
+                    return redis.call('DEL', KEYS[1])
+                end
+                return 0
+                "#,
+            )
+            .key(key)
+            .arg(value.to_string())
+            .invoke_async(&mut connection)
+            .await?;
+            Ok(deleted == 1)
+        })
+    }
+
+    fn delete<'a>(&'a self, keys: &'a [String]) -> ExpiringStoreFuture<'a, ()> {
+        Box::pin(self.redis.delete(keys))
+    }
+
+    fn delete_prefix<'a>(&'a self, prefix: &'a str) -> ExpiringStoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let mut cursor: u64 = 0;
+            loop {
+                let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                    .arg(cursor)
+                    .arg("MATCH")
+                    .arg(format!("{}*", prefix))
+                    .arg("COUNT")
+                    .arg(100)
+                    .query_async(&mut connection)
+                    .await?;
+                if !keys.is_empty() {
+                    let _: () = connection.del(keys).await?;
+                }
+                if next == 0 {
+                    return Ok(());
+                }
+                cursor = next;
+            }
+        })
+    }
+}
+
+/// Keeps values in process memory, expiring them by the given clock. For
+/// tests and single-instance setups.
+pub struct MemoryExpiringStore {
+    clock: Arc<dyn Clock>,
+    entries: Mutex<HashMap<String, (i64, DateTime<Utc>)>>,
+}
+
+impl MemoryExpiringStore {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn with_entries<T>(
+        &self,
+        f: impl FnOnce(&mut HashMap<String, (i64, DateTime<Utc>)>, DateTime<Utc>) -> T,
+    ) -> Result<T, AppError> {
+        let now = self.clock.now();
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| AppError::Internal("Expiring store lock poisoned".to_string()))?;
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+        Ok(f(&mut entries, now))
+    }
+}
+
+#[cfg(test)]
+impl MemoryExpiringStore {
+    /// A store expiring by `clock`, along with the clock, the way the
+    /// services built on it take them.
+    pub(crate) fn with_fake_clock(
+        clock: &Arc<crate::utils::clock::FakeClock>,
+    ) -> (Arc<dyn ExpiringStore>, Arc<dyn Clock>) {
+        let clock: Arc<dyn Clock> = clock.clone();
+        (Arc::new(Self::new(clock.clone())), clock)
+    }
+}
+
+impl ExpiringStore for MemoryExpiringStore {
+    fn increment<'a>(&'a self, key: &'a str, ttl: Duration) -> ExpiringStoreFuture<'a, i64> {
+        Box::pin(async move {
+            self.with_entries(|entries, now| {
+                let entry = entries.entry(key.to_string()).or_insert((0, now + ttl));
+                entry.0 += 1;
+                entry.0
+            })
+        })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: i64, ttl: Duration) -> ExpiringStoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.with_entries(|entries, now| {
+                entries.insert(key.to_string(), (value, now + ttl));
+            })
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> ExpiringStoreFuture<'a, Option<i64>> {
+        Box::pin(async move {
+            self.with_entries(|entries, _| entries.get(key).map(|(value, _)| *value))
+        })
+    }
+
+    fn delete_if_equal<'a>(&'a self, key: &'a str, value: i64) -> ExpiringStoreFuture<'a, bool> {
+        Box::pin(async move {
+            self.with_entries(|entries, _| {
+                let matches = entries.get(key).is_some_and(|(stored, _)| *stored == value);
+                if matches {
+                    entries.remove(key);
+                }
+                matches
+            })
+        })
+    }
+
+    fn delete<'a>(&'a self, keys: &'a [String]) -> ExpiringStoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.with_entries(|entries, _| {
+                for key in keys {
+                    entries.remove(key);
+                }
+            })
+        })
+    }
+
+    fn delete_prefix<'a>(&'a self, prefix: &'a str) -> ExpiringStoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.with_entries(|entries, _| entries.retain(|key, _| !key.starts_with(prefix)))
+        })
+    }
+}
@@ -0,0 +1,199 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use maxminddb::{Reader, geoip2};
+
+use crate::{error::AppError, models::GeoIpDatabaseInfo};
+
+/// Resolves the country an IP address is registered to.
+pub trait GeoIpProvider: Send + Sync {
+    /// The ISO 3166-1 alpha-2 code, or `None` when the address isn't known.
+    fn country_code(&self, ip: IpAddr) -> Option<String>;
+
+    /// Picks up a newer copy of the provider's data, if it has any.
+    fn reload(&self) -> Result<GeoIpDatabaseInfo, AppError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoIpConfig {
+    /// A MaxMind DB file with country data, such as GeoLite2-Country.mmdb.
+    pub database_path: Option<PathBuf>,
+    /// How often the file is checked for changes.
+    pub reload_interval: Duration,
+}
+
+impl Default for GeoIpConfig {
+    fn default() -> Self {
+        Self {
+            database_path: None,
+            reload_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl GeoIpConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            database_path: std::env::var("GEOIP_DATABASE_PATH").ok().map(PathBuf::from),
+            reload_interval: std::env::var("GEOIP_RELOAD_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.reload_interval),
+        }
+    }
+}
+
+struct LoadedDatabase {
+    reader: Reader<Vec<u8>>,
+    modified_at: Option<SystemTime>,
+}
+
+/// Reads a MaxMind DB file into memory. Lookups keep using the loaded copy
+/// while a reload reads the new one, so replacing the file on disk is enough
+/// to update it.
+pub struct MaxMindGeoIp {
+    path: PathBuf,
+    database: RwLock<Option<Arc<LoadedDatabase>>>,
+}
+
+impl MaxMindGeoIp {
+    /// Lookups find nothing until the first `reload`.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            database: RwLock::new(None),
+        }
+    }
+
+    fn loaded(&self) -> Option<Arc<LoadedDatabase>> {
+        self.database.read().ok()?.clone()
+    }
+
+    fn modified_at(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path).ok()?.modified().ok()
+    }
+
+    /// Reloads the file only when it changed since it was last read.
+    fn reload_if_changed(&self) -> Result<(), AppError> {
+        let loaded_at = self.loaded().and_then(|database| database.modified_at);
+        if loaded_at.is_some() && loaded_at == self.modified_at() {
+            return Ok(());
+        }
+
+        self.reload().map(|_| ())
+    }
+}
+
+impl GeoIpProvider for MaxMindGeoIp {
+    fn country_code(&self, ip: IpAddr) -> Option<String> {
+        let database = self.loaded()?;
+        let record: geoip2::Country = database.reader.lookup(ip).ok()?;
+
+        record
+            .country
+            .or(record.registered_country)
+            .and_then(|country| country.iso_code)
+            .map(str::to_string)
+    }
+
+    fn reload(&self) -> Result<GeoIpDatabaseInfo, AppError> {
+        let modified_at = self.modified_at();
+        let reader = Reader::open_readfile(&self.path).map_err(|e| {
+            AppError::Internal(format!(
+                "Failed to read GeoIP database {}: {}",
+                self.path.display(),
+                e
+            ))
+        })?;
+
+        let info = database_info(&reader);
+        if let Ok(mut database) = self.database.write() {
+            *database = Some(Arc::new(LoadedDatabase {
+                reader,
+                modified_at,
+            }));
+        }
+
+        tracing::info!("Loaded GeoIP database {}", self.path.display());
+
+        Ok(info)
+    }
+}
+
+fn database_info(reader: &Reader<Vec<u8>>) -> GeoIpDatabaseInfo {
+    GeoIpDatabaseInfo {
+        loaded: true,
+        database_type: Some(reader.metadata.database_type.clone()),
+        built_at: chrono::DateTime::from_timestamp(reader.metadata.build_epoch as i64, 0),
+    }
+}
+
+/// Used when no database is configured. Every address is unknown, so country
+/// restrictions fall back to the phone number alone.
+pub struct NoopGeoIp;
+
+impl GeoIpProvider for NoopGeoIp {
+    fn country_code(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+
+    fn reload(&self) -> Result<GeoIpDatabaseInfo, AppError> {
+        Ok(GeoIpDatabaseInfo::default())
+    }
+}
+
+#[derive(Clone)]
+pub struct GeoIpService {
+    provider: Arc<dyn GeoIpProvider>,
+}
+
+impl GeoIpService {
+    pub fn new(provider: Arc<dyn GeoIpProvider>) -> Self {
+        Self { provider }
+    }
+
+    /// Loads the configured database and starts a task that reloads it when
+    /// the file changes. A missing or unreadable database is logged rather
+    /// than failing startup.
+    pub fn spawn(config: GeoIpConfig) -> Self {
+        let Some(path) = config.database_path else {
+            tracing::warn!("GEOIP_DATABASE_PATH is not set, IP addresses won't be geolocated");
+            return Self::new(Arc::new(NoopGeoIp));
+        };
+
+        let geoip = Arc::new(MaxMindGeoIp::new(path));
+        if let Err(e) = geoip.reload() {
+            tracing::warn!("{}", e);
+        }
+
+        let watched = geoip.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(config.reload_interval.max(Duration::from_secs(1)));
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                if let Err(e) = watched.reload_if_changed() {
+                    tracing::warn!("{}", e);
+                }
+            }
+        });
+
+        Self::new(geoip)
+    }
+
+    pub fn country_code(&self, ip: IpAddr) -> Option<String> {
+        self.provider.country_code(ip)
+    }
+
+    pub fn reload(&self) -> Result<GeoIpDatabaseInfo, AppError> {
+        self.provider.reload()
+    }
+}
@@ -0,0 +1,199 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+
+use super::{
+    ClickHouseService, QdrantService, RedisPool, applied_schema_version, expected_schema_version,
+};
+use crate::models::{DatabaseSchema, DependencyHealth, DependencyStatus, ReadinessReport};
+
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// How long a readiness report is reused before dependencies are checked
+    /// again.
+    pub cache_ttl: Duration,
+    pub critical_timeout: Duration,
+    pub optional_timeout: Duration,
+    pub check_qdrant: bool,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl: Duration::from_secs(2),
+            critical_timeout: Duration::from_secs(1),
+            optional_timeout: Duration::from_millis(500),
+            check_qdrant: true,
+        }
+    }
+}
+
+impl HealthConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let millis = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_millis)
+        };
+
+        Self {
+            cache_ttl: millis("HEALTH_CACHE_TTL_MS").unwrap_or(defaults.cache_ttl),
+            critical_timeout: millis("HEALTH_CRITICAL_TIMEOUT_MS")
+                .unwrap_or(defaults.critical_timeout),
+            optional_timeout: millis("HEALTH_OPTIONAL_TIMEOUT_MS")
+                .unwrap_or(defaults.optional_timeout),
+            check_qdrant: std::env::var("HEALTH_CHECK_QDRANT")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(defaults.check_qdrant),
+        }
+    }
+}
+
+/// Checks the dependencies an instance needs to serve traffic.
+///
+/// Postgres and Redis are critical. ClickHouse and Qdrant only back analytics
+/// and AI features, so losing them degrades the instance without taking it
+/// out of rotation.
+#[derive(Clone)]
+pub struct HealthService {
+    db_pool: PgPool,
+    redis: RedisPool,
+    clickhouse_service: ClickHouseService,
+    config: HealthConfig,
+    cached: Arc<Mutex<Option<(Instant, ReadinessReport)>>>,
+}
+
+impl HealthService {
+    pub fn new(
+        db_pool: PgPool,
+        redis: RedisPool,
+        clickhouse_service: ClickHouseService,
+        config: HealthConfig,
+    ) -> Self {
+        Self {
+            db_pool,
+            redis,
+            clickhouse_service,
+            config,
+            cached: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the cached report while it is fresh. Concurrent callers wait
+    /// on the same check instead of each starting their own, so a burst of
+    /// probes costs one round of dependency checks.
+    pub async fn readiness(&self) -> ReadinessReport {
+        let mut cached = self.cached.lock().await;
+        if let Some((checked_at, report)) = cached.as_ref()
+            && checked_at.elapsed() < self.config.cache_ttl
+        {
+            return report.clone();
+        }
+
+        let report = self.check_dependencies().await;
+        *cached = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    async fn check_dependencies(&self) -> ReadinessReport {
+        let critical = self.config.critical_timeout;
+        let optional = self.config.optional_timeout;
+
+        let (postgres, redis, clickhouse, qdrant, schema) = tokio::join!(
+            check("postgres", true, critical, self.ping_postgres()),
+            check("redis", true, critical, self.ping_redis()),
+            check("clickhouse", false, optional, self.ping_clickhouse()),
+            async {
+                if self.config.check_qdrant {
+                    Some(check("qdrant", false, optional, ping_qdrant()).await)
+                } else {
+                    None
+                }
+            },
+            self.schema_version(),
+        );
+
+        let mut dependencies = vec![postgres, redis, clickhouse];
+        dependencies.extend(qdrant);
+
+        ReadinessReport::from_dependencies(dependencies, schema)
+    }
+
+    /// Read on every check rather than at startup, so a pod reports
+    /// migrations applied by another pod after it started.
+    async fn schema_version(&self) -> Option<DatabaseSchema> {
+        let applied = tokio::time::timeout(
+            self.config.critical_timeout,
+            applied_schema_version(&self.db_pool),
+        )
+        .await
+        .ok()?
+        .ok()?;
+
+        Some(DatabaseSchema {
+            applied,
+            expected: expected_schema_version(),
+        })
+    }
+
+    async fn ping_postgres(&self) -> Result<(), String> {
+        sqlx::query("SELECT 1")
+            .execute(&self.db_pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Goes through the connection requests use, so a broken one is
+    /// noticed and replaced.
+    async fn ping_redis(&self) -> Result<(), String> {
+        self.redis.ping().await.map_err(|e| e.to_string())
+    }
+
+    async fn ping_clickhouse(&self) -> Result<(), String> {
+        self.clickhouse_service
+            .ping()
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+async fn ping_qdrant() -> Result<(), String> {
+    let client = QdrantService::connect().await.map_err(|e| e.to_string())?;
+    client
+        .health_check()
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check(
+    name: &str,
+    critical: bool,
+    timeout: Duration,
+    ping: impl Future<Output = Result<(), String>>,
+) -> DependencyHealth {
+    let started_at = Instant::now();
+    let result = match tokio::time::timeout(timeout, ping).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}ms", timeout.as_millis())),
+    };
+
+    DependencyHealth {
+        name: name.to_string(),
+        critical,
+        status: if result.is_ok() {
+            DependencyStatus::Up
+        } else {
+            DependencyStatus::Down
+        },
+        latency_ms: started_at.elapsed().as_millis() as u64,
+        error: result.err(),
+    }
+}
@@ -0,0 +1,81 @@
+//! The HTTP client and retry policy shared by the provider integrations.
+
+use std::time::Duration;
+
+use reqwest::{Client, Method, Request, Response, StatusCode};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+const MAX_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// A POST carrying this header can be replayed without creating a duplicate.
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+pub(crate) fn provider_client() -> Client {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Whether sending the request twice has the same effect as sending it once.
+fn is_idempotent(request: &Request) -> bool {
+    match *request.method() {
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS => true,
+        Method::POST => request.headers().contains_key(IDEMPOTENCY_KEY_HEADER),
+        _ => false,
+    }
+}
+
+fn is_retryable(result: &Result<Response, reqwest::Error>) -> bool {
+    match result {
+        Ok(response) => {
+            response.status().is_server_error()
+                || response.status() == StatusCode::TOO_MANY_REQUESTS
+        }
+        Err(e) => e.is_connect() || e.is_timeout(),
+    }
+}
+
+/// Sends the request, retrying with backoff on connection failures, timeouts,
+/// 429s and 5xxs. Only idempotent requests are retried; a POST without an
+/// idempotency key is sent once, since the provider may have acted on it
+/// even when the response never arrived.
+pub(crate) async fn send_with_retry(
+    client: &Client,
+    request: Request,
+) -> Result<Response, reqwest::Error> {
+    let idempotent = is_idempotent(&request);
+    let mut request = request;
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        let retry = if idempotent && attempt < MAX_ATTEMPTS {
+            request.try_clone()
+        } else {
+            None
+        };
+
+        let result = client.execute(request).await;
+        let Some(next) = retry.filter(|_| is_retryable(&result)) else {
+            return result;
+        };
+
+        tracing::debug!(
+            "Retrying {} {} (attempt {} of {})",
+            next.method(),
+            next.url().path(),
+            attempt + 1,
+            MAX_ATTEMPTS
+        );
+        tokio::time::sleep(backoff).await;
+
+        request = next;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
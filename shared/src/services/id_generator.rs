@@ -0,0 +1,746 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeZone, Utc};
+use redis::AsyncCommands;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    error::AppError,
+    services::{RedisConnection, RedisPool},
+    utils::clock::{Clock, SystemClock},
+};
+
+// Same layout as the Sonyflake ids issued before, so old and new ids sort
+// together: 39 bits of 10ms ticks, 8 bits of sequence, 16 bits of worker id.
+const BITS_TIME: u32 = 39;
+const BITS_SEQUENCE: u32 = 8;
+const BITS_WORKER: u32 = 16;
+const SEQUENCE_MASK: u16 = (1 << BITS_SEQUENCE) - 1;
+const TICK_MS: i64 = 10;
+
+/// How many worker ids are tried at startup before giving up.
+const MAX_CLAIM_ATTEMPTS: u32 = 256;
+
+fn epoch() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdGeneratorError {
+    #[error("clock moved backwards by {behind_ms}ms")]
+    ClockMovedBackwards { behind_ms: u64 },
+    #[error("lease on worker id {0} has expired")]
+    LeaseExpired(u16),
+    #[error("id time space exhausted")]
+    OverTimeLimit,
+}
+
+#[derive(Debug, Clone)]
+pub struct IdGeneratorConfig {
+    /// A worker id assigned by deployment config. When unset, one is leased
+    /// from Redis.
+    pub worker_id: Option<u16>,
+    pub lease_ttl: Duration,
+    /// How long `next_id` may block for the clock to catch up after it steps
+    /// backwards. Longer regressions are errors.
+    pub max_clock_wait: Duration,
+}
+
+impl Default for IdGeneratorConfig {
+    fn default() -> Self {
+        Self {
+            worker_id: None,
+            lease_ttl: Duration::from_secs(30),
+            max_clock_wait: Duration::from_secs(1),
+        }
+    }
+}
+
+impl IdGeneratorConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            worker_id: std::env::var("SNOWFLAKE_WORKER_ID")
+                .ok()
+                .and_then(|v| v.parse::<u16>().ok()),
+            lease_ttl: std::env::var("SNOWFLAKE_LEASE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.lease_ttl),
+            max_clock_wait: std::env::var("SNOWFLAKE_MAX_CLOCK_WAIT_MS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.max_clock_wait),
+        }
+    }
+}
+
+pub type WorkerLeaseFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, AppError>> + Send + 'a>>;
+
+/// Where worker ids are leased from, along with the last tick each worker id
+/// issued ids in.
+pub trait WorkerLeaseStore: Send + Sync {
+    /// Takes `worker_id` for `holder` unless another holder has it.
+    fn claim<'a>(
+        &'a self,
+        worker_id: u16,
+        holder: &'a str,
+        ttl: Duration,
+    ) -> WorkerLeaseFuture<'a, bool>;
+
+    /// Extends the lease. Returns `false` if `holder` no longer has it.
+    fn renew<'a>(
+        &'a self,
+        worker_id: u16,
+        holder: &'a str,
+        ttl: Duration,
+    ) -> WorkerLeaseFuture<'a, bool>;
+
+    fn release<'a>(&'a self, worker_id: u16, holder: &'a str) -> WorkerLeaseFuture<'a, ()>;
+
+    fn high_water<'a>(&'a self, worker_id: u16) -> WorkerLeaseFuture<'a, Option<i64>>;
+
+    fn set_high_water<'a>(&'a self, worker_id: u16, tick: i64) -> WorkerLeaseFuture<'a, ()>;
+}
+
+fn lease_key(worker_id: u16) -> String {
+    format!("snowflake_worker:{}", worker_id)
+}
+
+fn high_water_key(worker_id: u16) -> String {
+    format!("snowflake_worker_high_water:{}", worker_id)
+}
+
+/// Kept for a week so a restarted instance still finds it.
+const HIGH_WATER_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+pub struct RedisWorkerLeaseStore {
+    redis: RedisPool,
+}
+
+impl RedisWorkerLeaseStore {
+    pub fn new(redis: RedisPool) -> Self {
+        Self { redis }
+    }
+
+    async fn connection(&self) -> Result<RedisConnection, AppError> {
+        self.redis.connection().await
+    }
+}
+
+impl WorkerLeaseStore for RedisWorkerLeaseStore {
+    fn claim<'a>(
+        &'a self,
+        worker_id: u16,
+        holder: &'a str,
+        ttl: Duration,
+    ) -> WorkerLeaseFuture<'a, bool> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let claimed: Option<String> = redis::cmd("SET")
+                .arg(lease_key(worker_id))
+                .arg(holder)
+                .arg("NX")
+                .arg("PX")
+                .arg(ttl.as_millis() as u64)
+                .query_async(&mut connection)
+                .await?;
+            Ok(claimed.is_some())
+        })
+    }
+
+    fn renew<'a>(
+        &'a self,
+        worker_id: u16,
+        holder: &'a str,
+        ttl: Duration,
+    ) -> WorkerLeaseFuture<'a, bool> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let renewed: i64 = redis::Script::new(
+                r#"
+                if redis.call('GET', KEYS[1]) == ARGV[1] then
+                    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+                end
+                return 0
+                "#,
+            )
+            .key(lease_key(worker_id))
+            .arg(holder)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut connection)
+            .await?;
+            Ok(renewed == 1)
+        })
+    }
+
+    fn release<'a>(&'a self, worker_id: u16, holder: &'a str) -> WorkerLeaseFuture<'a, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let _: i64 = redis::Script::new(
+                r#"
+                if redis.call('GET', KEYS[1]) == ARGV[1] then
+                    return redis.call('DEL', KEYS[1])
+                end
+                return 0
+                "#,
+            )
+            .key(lease_key(worker_id))
+            .arg(holder)
+            .invoke_async(&mut connection)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn high_water<'a>(&'a self, worker_id: u16) -> WorkerLeaseFuture<'a, Option<i64>> {
+        Box::pin(async move { self.redis.get(&high_water_key(worker_id)).await })
+    }
+
+    fn set_high_water<'a>(&'a self, worker_id: u16, tick: i64) -> WorkerLeaseFuture<'a, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let _: () = connection
+                .set_ex(high_water_key(worker_id), tick, HIGH_WATER_TTL_SECS)
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+#[derive(Default)]
+struct MemoryLeases {
+    leases: HashMap<u16, (String, DateTime<Utc>)>,
+    high_water: HashMap<u16, i64>,
+}
+
+/// Keeps leases in process memory, expiring them by the given clock. For
+/// tests.
+pub struct MemoryWorkerLeaseStore {
+    clock: Arc<dyn Clock>,
+    inner: Mutex<MemoryLeases>,
+}
+
+impl MemoryWorkerLeaseStore {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            inner: Mutex::new(MemoryLeases::default()),
+        }
+    }
+
+    fn with_leases<T>(
+        &self,
+        f: impl FnOnce(&mut MemoryLeases, DateTime<Utc>) -> T,
+    ) -> Result<T, AppError> {
+        let now = self.clock.now();
+        let mut inner = self
+            .inner
+            .lock()
+            .map_err(|_| AppError::Internal("Worker lease store lock poisoned".to_string()))?;
+        inner.leases.retain(|_, (_, expires_at)| *expires_at > now);
+        Ok(f(&mut inner, now))
+    }
+}
+
+fn expires_at(now: DateTime<Utc>, ttl: Duration) -> DateTime<Utc> {
+    now + chrono::Duration::milliseconds(ttl.as_millis() as i64)
+}
+
+impl WorkerLeaseStore for MemoryWorkerLeaseStore {
+    fn claim<'a>(
+        &'a self,
+        worker_id: u16,
+        holder: &'a str,
+        ttl: Duration,
+    ) -> WorkerLeaseFuture<'a, bool> {
+        Box::pin(async move {
+            self.with_leases(|inner, now| {
+                if inner.leases.contains_key(&worker_id) {
+                    return false;
+                }
+                inner
+                    .leases
+                    .insert(worker_id, (holder.to_string(), expires_at(now, ttl)));
+                true
+            })
+        })
+    }
+
+    fn renew<'a>(
+        &'a self,
+        worker_id: u16,
+        holder: &'a str,
+        ttl: Duration,
+    ) -> WorkerLeaseFuture<'a, bool> {
+        Box::pin(async move {
+            self.with_leases(|inner, now| match inner.leases.get_mut(&worker_id) {
+                Some((current, expires)) if current == holder => {
+                    *expires = expires_at(now, ttl);
+                    true
+                }
+                _ => false,
+            })
+        })
+    }
+
+    fn release<'a>(&'a self, worker_id: u16, holder: &'a str) -> WorkerLeaseFuture<'a, ()> {
+        Box::pin(async move {
+            self.with_leases(|inner, _| {
+                if inner
+                    .leases
+                    .get(&worker_id)
+                    .is_some_and(|(current, _)| current == holder)
+                {
+                    inner.leases.remove(&worker_id);
+                }
+            })
+        })
+    }
+
+    fn high_water<'a>(&'a self, worker_id: u16) -> WorkerLeaseFuture<'a, Option<i64>> {
+        Box::pin(
+            async move { self.with_leases(|inner, _| inner.high_water.get(&worker_id).copied()) },
+        )
+    }
+
+    fn set_high_water<'a>(&'a self, worker_id: u16, tick: i64) -> WorkerLeaseFuture<'a, ()> {
+        Box::pin(async move {
+            self.with_leases(|inner, _| {
+                inner.high_water.insert(worker_id, tick);
+            })
+        })
+    }
+}
+
+/// Tries worker ids from `start` upwards until one is free. Returns it along
+/// with the last tick its previous holder issued ids in.
+async fn claim_worker_id(
+    store: &dyn WorkerLeaseStore,
+    holder: &str,
+    ttl: Duration,
+    start: u16,
+) -> Result<(u16, Option<i64>), AppError> {
+    for attempt in 0..MAX_CLAIM_ATTEMPTS {
+        let worker_id = start.wrapping_add(attempt as u16);
+        if store.claim(worker_id, holder, ttl).await? {
+            return Ok((worker_id, store.high_water(worker_id).await?));
+        }
+    }
+
+    Err(AppError::Internal(format!(
+        "No free snowflake worker id after {} attempts",
+        MAX_CLAIM_ATTEMPTS
+    )))
+}
+
+struct GeneratorState {
+    worker_id: u16,
+    tick: i64,
+    sequence: u16,
+    /// Ids stop being issued once a lease runs out without being renewed, as
+    /// another instance may have taken the worker id by then.
+    leased_until: Option<Instant>,
+}
+
+impl GeneratorState {
+    /// Starts on `worker_id` after the last tick its previous holder used,
+    /// so none of that holder's ids can be issued again even if this
+    /// instance's clock is behind.
+    fn adopt(&mut self, worker_id: u16, high_water: Option<i64>) {
+        self.worker_id = worker_id;
+        self.tick = self.tick.max(high_water.unwrap_or(0));
+        self.sequence = SEQUENCE_MASK;
+    }
+}
+
+struct Lease {
+    store: Arc<dyn WorkerLeaseStore>,
+    holder: String,
+    ttl: Duration,
+    heartbeat: CancellationToken,
+}
+
+struct Inner {
+    clock: Arc<dyn Clock>,
+    max_clock_wait: Duration,
+    state: Mutex<GeneratorState>,
+    lease: Option<Lease>,
+}
+
+/// Snowflake id generator. Each instance needs a worker id no other running
+/// instance has: either configured with `SNOWFLAKE_WORKER_ID` or leased from
+/// Redis at startup and renewed in the background.
+#[derive(Clone)]
+pub struct IdGenerator {
+    inner: Arc<Inner>,
+}
+
+impl IdGenerator {
+    fn new(
+        worker_id: u16,
+        high_water: Option<i64>,
+        lease: Option<Lease>,
+        config: &IdGeneratorConfig,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let mut state = GeneratorState {
+            worker_id,
+            tick: 0,
+            sequence: 0,
+            leased_until: lease.as_ref().map(|lease| Instant::now() + lease.ttl),
+        };
+        state.adopt(worker_id, high_water);
+
+        Self {
+            inner: Arc::new(Inner {
+                clock,
+                max_clock_wait: config.max_clock_wait,
+                state: Mutex::new(state),
+                lease,
+            }),
+        }
+    }
+
+    pub fn with_worker_id(
+        worker_id: u16,
+        config: &IdGeneratorConfig,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self::new(worker_id, None, None, config, clock)
+    }
+
+    pub async fn leased(
+        store: Arc<dyn WorkerLeaseStore>,
+        config: &IdGeneratorConfig,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, AppError> {
+        Self::lease_from(store, config, clock, rand::random()).await
+    }
+
+    async fn lease_from(
+        store: Arc<dyn WorkerLeaseStore>,
+        config: &IdGeneratorConfig,
+        clock: Arc<dyn Clock>,
+        start: u16,
+    ) -> Result<Self, AppError> {
+        let holder = hex::encode(rand::random::<[u8; 16]>());
+        let (worker_id, high_water) =
+            claim_worker_id(store.as_ref(), &holder, config.lease_ttl, start).await?;
+
+        let lease = Lease {
+            store,
+            holder,
+            ttl: config.lease_ttl,
+            heartbeat: CancellationToken::new(),
+        };
+        let generator = Self::new(worker_id, high_water, Some(lease), config, clock);
+        tokio::spawn(generator.clone().heartbeat());
+
+        Ok(generator)
+    }
+
+    pub async fn from_env(redis: RedisPool) -> Result<Self, AppError> {
+        let config = IdGeneratorConfig::from_env();
+        let clock = Arc::new(SystemClock);
+
+        match config.worker_id {
+            Some(worker_id) => Ok(Self::with_worker_id(worker_id, &config, clock)),
+            None => {
+                let store = Arc::new(RedisWorkerLeaseStore::new(redis));
+                Self::leased(store, &config, clock).await
+            }
+        }
+    }
+
+    pub fn worker_id(&self) -> u16 {
+        self.state().worker_id
+    }
+
+    fn state(&self) -> MutexGuard<'_, GeneratorState> {
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn next_id(&self) -> Result<u64, IdGeneratorError> {
+        let mut state = self.state();
+
+        if let Some(leased_until) = state.leased_until
+            && Instant::now() >= leased_until
+        {
+            return Err(IdGeneratorError::LeaseExpired(state.worker_id));
+        }
+
+        let now = self.wait_for_tick(state.tick, self.inner.max_clock_wait)?;
+        if now > state.tick {
+            state.tick = now;
+            state.sequence = 0;
+        } else {
+            state.sequence = (state.sequence + 1) & SEQUENCE_MASK;
+            if state.sequence == 0 {
+                // Every sequence number in this tick is taken.
+                let one_tick = Duration::from_millis(TICK_MS as u64);
+                state.tick =
+                    self.wait_for_tick(state.tick + 1, self.inner.max_clock_wait.max(one_tick))?;
+            }
+        }
+
+        if state.tick >= 1 << BITS_TIME {
+            return Err(IdGeneratorError::OverTimeLimit);
+        }
+
+        Ok((state.tick as u64) << (BITS_SEQUENCE + BITS_WORKER)
+            | (state.sequence as u64) << BITS_WORKER
+            | state.worker_id as u64)
+    }
+
+    fn current_tick(&self) -> i64 {
+        (self.inner.clock.now() - epoch()).num_milliseconds() / TICK_MS
+    }
+
+    /// Returns the current tick once it has reached `tick`, sleeping up to
+    /// `max_wait` for that.
+    fn wait_for_tick(&self, tick: i64, max_wait: Duration) -> Result<i64, IdGeneratorError> {
+        let now = self.current_tick();
+        if now >= tick {
+            return Ok(now);
+        }
+
+        let behind = Duration::from_millis(((tick - now) * TICK_MS) as u64);
+        if behind <= max_wait {
+            std::thread::sleep(behind);
+            let now = self.current_tick();
+            if now >= tick {
+                return Ok(now);
+            }
+        }
+
+        Err(IdGeneratorError::ClockMovedBackwards {
+            behind_ms: behind.as_millis() as u64,
+        })
+    }
+
+    async fn heartbeat(self) {
+        let Some(lease) = &self.inner.lease else {
+            return;
+        };
+
+        loop {
+            tokio::select! {
+                _ = lease.heartbeat.cancelled() => return,
+                _ = tokio::time::sleep(lease.ttl / 3) => {}
+            }
+
+            if let Err(e) = self.renew(lease).await {
+                tracing::warn!("Failed to renew snowflake worker lease: {}", e);
+            }
+        }
+    }
+
+    async fn renew(&self, lease: &Lease) -> Result<(), AppError> {
+        let started_at = Instant::now();
+        let (worker_id, tick) = {
+            let state = self.state();
+            (state.worker_id, state.tick)
+        };
+
+        let held = lease
+            .store
+            .renew(worker_id, &lease.holder, lease.ttl)
+            .await?
+            || lease
+                .store
+                .claim(worker_id, &lease.holder, lease.ttl)
+                .await?;
+
+        if held {
+            lease.store.set_high_water(worker_id, tick).await?;
+            self.state().leased_until = Some(started_at + lease.ttl);
+            return Ok(());
+        }
+
+        tracing::error!(
+            worker_id,
+            "Snowflake worker id was taken by another instance, leasing a new one"
+        );
+        self.state().leased_until = Some(started_at);
+
+        let (worker_id, high_water) = claim_worker_id(
+            lease.store.as_ref(),
+            &lease.holder,
+            lease.ttl,
+            rand::random(),
+        )
+        .await?;
+        let mut state = self.state();
+        state.adopt(worker_id, high_water);
+        state.leased_until = Some(Instant::now() + lease.ttl);
+        tracing::info!(worker_id, "Leased snowflake worker id");
+
+        Ok(())
+    }
+
+    /// Stops issuing ids and hands the worker id back, recording how far it
+    /// got so the next holder starts after it.
+    pub async fn release(&self) {
+        let Some(lease) = &self.inner.lease else {
+            return;
+        };
+        lease.heartbeat.cancel();
+
+        let (worker_id, tick) = {
+            let mut state = self.state();
+            state.leased_until = Some(Instant::now());
+            (state.worker_id, state.tick)
+        };
+
+        let result = async {
+            lease.store.set_high_water(worker_id, tick).await?;
+            lease.store.release(worker_id, &lease.holder).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!(worker_id, "Failed to release snowflake worker id: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::FakeClock;
+
+    /// Well after the id epoch.
+    fn fake_clock() -> Arc<FakeClock> {
+        FakeClock::at(DateTime::from_timestamp(1_800_000_000, 0).unwrap())
+    }
+
+    fn config() -> IdGeneratorConfig {
+        IdGeneratorConfig {
+            max_clock_wait: Duration::ZERO,
+            ..IdGeneratorConfig::default()
+        }
+    }
+
+    fn worker_id_of(id: u64) -> u16 {
+        (id & ((1 << BITS_WORKER) - 1)) as u16
+    }
+
+    #[tokio::test]
+    async fn contending_instances_lease_different_worker_ids() {
+        let clock = fake_clock();
+        let store = Arc::new(MemoryWorkerLeaseStore::new(clock.clone()));
+
+        let first = IdGenerator::lease_from(store.clone(), &config(), clock.clone(), 7)
+            .await
+            .unwrap();
+        let second = IdGenerator::lease_from(store.clone(), &config(), clock.clone(), 7)
+            .await
+            .unwrap();
+
+        assert_eq!(first.worker_id(), 7);
+        assert_eq!(second.worker_id(), 8);
+
+        let (a, b) = (first.next_id().unwrap(), second.next_id().unwrap());
+        assert_ne!(a, b);
+        assert_eq!(worker_id_of(a), 7);
+        assert_eq!(worker_id_of(b), 8);
+    }
+
+    #[tokio::test]
+    async fn released_worker_id_can_be_leased_again() {
+        let clock = fake_clock();
+        let store = Arc::new(MemoryWorkerLeaseStore::new(clock.clone()));
+
+        let first = IdGenerator::lease_from(store.clone(), &config(), clock.clone(), 7)
+            .await
+            .unwrap();
+        first.release().await;
+        assert!(matches!(
+            first.next_id(),
+            Err(IdGeneratorError::LeaseExpired(7))
+        ));
+
+        let second = IdGenerator::lease_from(store, &config(), clock, 7)
+            .await
+            .unwrap();
+        assert_eq!(second.worker_id(), 7);
+    }
+
+    #[tokio::test]
+    async fn expired_lease_can_be_taken_by_another_instance() {
+        let clock = fake_clock();
+        let store = Arc::new(MemoryWorkerLeaseStore::new(clock.clone()));
+
+        let first = IdGenerator::lease_from(store.clone(), &config(), clock.clone(), 7)
+            .await
+            .unwrap();
+        clock.advance_millis(config().lease_ttl.as_millis() as i64);
+
+        let second = IdGenerator::lease_from(store, &config(), clock, 7)
+            .await
+            .unwrap();
+        assert_eq!(first.worker_id(), second.worker_id());
+    }
+
+    #[tokio::test]
+    async fn new_holder_never_reissues_the_previous_holders_ticks() {
+        let clock = fake_clock();
+        let store = Arc::new(MemoryWorkerLeaseStore::new(clock.clone()));
+
+        let first = IdGenerator::lease_from(store.clone(), &config(), clock.clone(), 7)
+            .await
+            .unwrap();
+        let last = first.next_id().unwrap();
+        first.release().await;
+
+        // The next holder's clock is behind the previous holder's.
+        clock.advance_millis(-5_000);
+        let second = IdGenerator::lease_from(store, &config(), clock.clone(), 7)
+            .await
+            .unwrap();
+        assert!(matches!(
+            second.next_id(),
+            Err(IdGeneratorError::ClockMovedBackwards { .. })
+        ));
+
+        clock.advance_millis(5_000 + TICK_MS);
+        assert!(second.next_id().unwrap() > last);
+    }
+
+    #[test]
+    fn ids_increase_within_a_tick() {
+        let clock = fake_clock();
+        let generator = IdGenerator::with_worker_id(3, &config(), clock);
+
+        let ids: Vec<u64> = (0..100).map(|_| generator.next_id().unwrap()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ids.iter().all(|id| worker_id_of(*id) == 3));
+    }
+
+    #[test]
+    fn clock_regression_is_an_error_not_a_reused_id() {
+        let clock = fake_clock();
+        let generator = IdGenerator::with_worker_id(3, &config(), clock.clone());
+
+        let before = generator.next_id().unwrap();
+        clock.advance_millis(-2_000);
+        assert!(matches!(
+            generator.next_id(),
+            Err(IdGeneratorError::ClockMovedBackwards { behind_ms: 2_000 })
+        ));
+
+        clock.advance_millis(2_000);
+        assert!(generator.next_id().unwrap() > before);
+    }
+}
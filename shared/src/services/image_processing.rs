@@ -0,0 +1,170 @@
+//! Normalizes uploaded images before they are put on the CDN: the upload is
+//! decoded, turned upright, scaled down and re-encoded, which also drops its
+//! EXIF and other metadata.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageDecoder, ImageError, ImageReader, Limits, imageops::FilterType};
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+
+/// Largest upload accepted for processing.
+pub const MAX_IMAGE_INPUT_SIZE: usize = 10 * 1024 * 1024;
+
+/// Uploads wider or taller than this are rejected before decoding.
+const MAX_IMAGE_INPUT_DIMENSION: u32 = 8192;
+
+/// Longest edge of the stored image.
+pub const MAX_IMAGE_EDGE: u32 = 1024;
+
+/// Longest edge of the thumbnail stored next to it.
+pub const THUMBNAIL_EDGE: u32 = 128;
+
+/// The format processed images are stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageOutputFormat {
+    Png,
+    Webp,
+}
+
+impl ImageOutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageOutputFormat::Png => "png",
+            ImageOutputFormat::Webp => "webp",
+        }
+    }
+
+    fn image_format(&self) -> image::ImageFormat {
+        match self {
+            ImageOutputFormat::Png => image::ImageFormat::Png,
+            ImageOutputFormat::Webp => image::ImageFormat::WebP,
+        }
+    }
+}
+
+/// One encoded variant of a processed image.
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+    pub bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub format: ImageOutputFormat,
+}
+
+impl EncodedImage {
+    /// `<name>-<hash>.<extension>`, named after the content so a changed
+    /// image gets a new URL and the CDN can cache each one for good.
+    pub fn file_name(&self, name: &str) -> String {
+        let hash = hex::encode(Sha256::digest(&self.bytes));
+        format!("{}-{}.{}", name, &hash[..16], self.format.extension())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessedImage {
+    /// Size of the upload, before it was scaled down.
+    pub original_width: u32,
+    pub original_height: u32,
+    pub image: EncodedImage,
+    pub thumbnail: EncodedImage,
+}
+
+impl ProcessedImage {
+    /// Path of the image and of its thumbnail under `directory`.
+    pub fn file_paths(&self, directory: &str, name: &str) -> (String, String) {
+        (
+            format!("{}/{}", directory, self.image.file_name(name)),
+            format!(
+                "{}/{}",
+                directory,
+                self.thumbnail
+                    .file_name(&format!("{}-{}", name, THUMBNAIL_EDGE))
+            ),
+        )
+    }
+}
+
+pub fn process_image(data: &[u8], output: ImageOutputFormat) -> Result<ProcessedImage, AppError> {
+    if data.is_empty() {
+        return Err(AppError::BadRequest("No image data provided".to_string()));
+    }
+    if data.len() > MAX_IMAGE_INPUT_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "The image is too large: {} bytes, more than the {} allowed",
+            data.len(),
+            MAX_IMAGE_INPUT_SIZE
+        )));
+    }
+
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| AppError::BadRequest(format!("The image can't be read: {}", e)))?;
+    if reader.format().is_none() {
+        return Err(AppError::BadRequest(
+            "Unsupported image format. Supported formats: JPEG, PNG, GIF, WEBP, ICO".to_string(),
+        ));
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_INPUT_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_INPUT_DIMENSION);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(decode_error)?;
+    let orientation = decoder.orientation().map_err(decode_error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+    image.apply_orientation(orientation);
+
+    let (original_width, original_height) = (image.width(), image.height());
+
+    Ok(ProcessedImage {
+        original_width,
+        original_height,
+        image: encode(&fit_within(&image, MAX_IMAGE_EDGE), output)?,
+        thumbnail: encode(&fit_within(&image, THUMBNAIL_EDGE), output)?,
+    })
+}
+
+/// Scales the image down, keeping its aspect ratio, until its longest edge is
+/// at most `edge`. Smaller images are left as they are.
+fn fit_within(image: &DynamicImage, edge: u32) -> DynamicImage {
+    if image.width() <= edge && image.height() <= edge {
+        return image.clone();
+    }
+    image.resize(edge, edge, FilterType::Lanczos3)
+}
+
+fn encode(image: &DynamicImage, format: ImageOutputFormat) -> Result<EncodedImage, AppError> {
+    // The WebP encoder only takes 8-bit RGB(A).
+    let image = if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(image.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    };
+
+    let mut bytes = Cursor::new(Vec::new());
+    image
+        .write_to(&mut bytes, format.image_format())
+        .map_err(|e| AppError::Internal(format!("Failed to encode the image: {}", e)))?;
+
+    Ok(EncodedImage {
+        bytes: bytes.into_inner(),
+        width: image.width(),
+        height: image.height(),
+        format,
+    })
+}
+
+fn decode_error(error: ImageError) -> AppError {
+    match error {
+        ImageError::Unsupported(e) => {
+            AppError::BadRequest(format!("Unsupported image format: {}", e))
+        }
+        ImageError::Limits(_) => AppError::BadRequest(format!(
+            "The image is too large: at most {}x{} pixels are allowed",
+            MAX_IMAGE_INPUT_DIMENSION, MAX_IMAGE_INPUT_DIMENSION
+        )),
+        e => AppError::BadRequest(format!("The image is corrupt: {}", e)),
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::error::AppError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What an invitation link vouches for. The frontend API checks these when
+/// the invitee signs up, which is how an approved waitlist entry gets past a
+/// restricted or waitlist-only sign-up mode.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvitationClaims {
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub invitation_id: i64,
+    #[serde(with = "crate::utils::serde::i64_as_string")]
+    pub deployment_id: i64,
+    pub email_address: String,
+    pub expires_at: DateTime<Utc>,
+    /// Set on invitations into an organization, which can't be used to
+    /// accept a deployment invitation or the other way around.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::serde::i64_as_string_option"
+    )]
+    pub organization_id: Option<i64>,
+}
+
+/// The link an invitation email sends the invitee to, where the frontend
+/// accepts the invitation with the token.
+pub fn invitation_url(frontend_host: &str, token: &str) -> String {
+    format!(
+        "https://{}/sign-up?invitation_token={}",
+        frontend_host, token
+    )
+}
+
+/// Where an organization invitation email sends the invitee, to sign in or
+/// sign up and then join the organization.
+pub fn organization_invitation_url(frontend_host: &str, token: &str) -> String {
+    format!(
+        "https://{}/accept-invitation?invitation_token={}",
+        frontend_host, token
+    )
+}
+
+/// Signs invitation claims with HMAC-SHA256. Tokens are
+/// `base64url(claims).base64url(signature)`, so they need no storage and can
+/// be checked by any service sharing the secret.
+#[derive(Clone)]
+pub struct InvitationTokenSigner {
+    secret: Arc<Vec<u8>>,
+}
+
+impl InvitationTokenSigner {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: Arc::new(secret.into()),
+        }
+    }
+
+    /// Reads `INVITATION_TOKEN_SECRET`, which every replica has to share for
+    /// a link signed by one to verify on another.
+    pub fn from_env() -> Self {
+        let secret = std::env::var("INVITATION_TOKEN_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .expect("INVITATION_TOKEN_SECRET must be set");
+        Self::new(secret)
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+
+    pub fn sign(&self, claims: &InvitationClaims) -> Result<String, AppError> {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        Ok(format!("{}.{}", payload, signature))
+    }
+
+    /// Checks the signature and expiry and returns the claims.
+    pub fn verify(&self, token: &str) -> Result<InvitationClaims, AppError> {
+        let invalid = || AppError::BadRequest("The invitation is invalid".to_string());
+
+        let (payload, signature) = token.split_once('.').ok_or_else(invalid)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| invalid())?;
+
+        let claims: InvitationClaims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|payload| serde_json::from_slice(&payload).ok())
+            .ok_or_else(invalid)?;
+
+        if claims.expires_at <= Utc::now() {
+            return Err(AppError::BadRequest(
+                "The invitation has expired".to_string(),
+            ));
+        }
+
+        Ok(claims)
+    }
+}
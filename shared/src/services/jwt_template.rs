@@ -0,0 +1,297 @@
+use std::str::FromStr;
+
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::JwtTemplateUser,
+};
+
+/// What `{{user.…}}` expressions can read.
+pub const JWT_USER_ATTRIBUTES: &[&str] = &[
+    "id",
+    "first_name",
+    "last_name",
+    "full_name",
+    "username",
+    "primary_email_address",
+    "primary_phone_number",
+    "created_at",
+    "public_metadata",
+    "private_metadata",
+];
+
+/// What `{{org.…}}` expressions can read, from the user's active
+/// organization.
+pub const JWT_ORG_ATTRIBUTES: &[&str] = &["id", "name", "roles", "public_metadata"];
+
+/// Attributes whose nested keys can be read, as in
+/// `{{user.public_metadata.plan}}`. A key that isn't set renders as null.
+const METADATA_ATTRIBUTES: &[&str] = &["public_metadata", "private_metadata"];
+
+/// A piece of a template string: literal text, or the path inside `{{ }}`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClaimPart<'a> {
+    Text(&'a str),
+    Expression(&'a str),
+}
+
+/// Splits a template string into text and expressions. Fails on a `{{` that
+/// isn't closed.
+pub fn parse_claim(text: &str) -> Result<Vec<ClaimPart<'_>>, String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            parts.push(ClaimPart::Text(&rest[..start]));
+        }
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "An expression isn't closed with }}".to_string())?;
+        parts.push(ClaimPart::Expression(after[..end].trim()));
+        rest = &after[end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(ClaimPart::Text(rest));
+    }
+    Ok(parts)
+}
+
+/// Checks an expression against the claim grammar:
+///
+/// - `user.<attribute>`, one of [`JWT_USER_ATTRIBUTES`]
+/// - `org.<attribute>`, one of [`JWT_ORG_ATTRIBUTES`], null without an active
+///   organization
+/// - `metadata.<key>`, short for `user.public_metadata.<key>`
+///
+/// Metadata attributes take further `.<key>` segments. Segments are letters,
+/// digits, `_` and `-`.
+pub fn check_claim_expression(expression: &str) -> Result<(), String> {
+    let segments: Vec<&str> = expression.split('.').collect();
+    let valid_segment = |segment: &&str| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+    if !segments.iter().all(valid_segment) {
+        return Err(format!("{{{{{}}}}} isn't a valid path", expression));
+    }
+
+    let (attributes, attribute, keys) = match segments.as_slice() {
+        ["metadata", _, ..] => return Ok(()),
+        ["user", attribute, keys @ ..] => (JWT_USER_ATTRIBUTES, *attribute, keys),
+        ["org", attribute, keys @ ..] => (JWT_ORG_ATTRIBUTES, *attribute, keys),
+        _ => {
+            return Err(format!(
+                "{{{{{}}}}} must read user.*, org.* or metadata.*",
+                expression
+            ));
+        }
+    };
+    if !attributes.contains(&attribute) {
+        return Err(format!("Unknown expression {{{{{}}}}}", expression));
+    }
+    if !keys.is_empty() && !METADATA_ATTRIBUTES.contains(&attribute) {
+        return Err(format!(
+            "{{{{{}}}}} reads into {}, which has no nested keys",
+            expression, attribute
+        ));
+    }
+
+    Ok(())
+}
+
+/// What expressions are evaluated against.
+pub fn jwt_template_context(user: &JwtTemplateUser) -> Value {
+    let metadata = |value: &Value| match value {
+        Value::Null => json!({}),
+        value => value.clone(),
+    };
+    let public_metadata = metadata(&user.public_metadata);
+
+    json!({
+        "user": {
+            "id": user.id,
+            "first_name": user.first_name,
+            "last_name": user.last_name,
+            "full_name": format!("{} {}", user.first_name, user.last_name).trim(),
+            "username": user.username,
+            "primary_email_address": user.primary_email_address,
+            "primary_phone_number": user.primary_phone_number,
+            "created_at": user.created_at.map(|created_at| created_at.timestamp()),
+            "public_metadata": public_metadata,
+            "private_metadata": metadata(&user.private_metadata),
+        },
+        "org": user.organization.as_ref().map(|org| json!({
+            "id": org.id,
+            "name": org.name,
+            "roles": org.roles,
+            "public_metadata": metadata(&org.public_metadata),
+        })),
+        "metadata": public_metadata,
+    })
+}
+
+/// Evaluates the expressions of a template against a context. A string that
+/// is a single expression takes the value's JSON type, so
+/// `"{{user.public_metadata.roles}}"` stays an array; expressions inside
+/// longer strings are interpolated as text.
+pub fn render_jwt_claims(template: &Value, context: &Value) -> Result<Value, AppError> {
+    render_value(template, context, "")
+}
+
+fn render_value(value: &Value, context: &Value, path: &str) -> Result<Value, AppError> {
+    match value {
+        Value::String(text) => render_string(text, context, path),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| render_value(item, context, &format!("{}[{}]", path, i)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Value::Object(claims) => claims
+            .iter()
+            .map(|(name, claim)| {
+                Ok((
+                    name.clone(),
+                    render_value(claim, context, &claim_path(path, name))?,
+                ))
+            })
+            .collect::<Result<Map<_, _>, AppError>>()
+            .map(Value::Object),
+        value => Ok(value.clone()),
+    }
+}
+
+/// `parent.name`, or `name` at the top level.
+pub fn claim_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", parent, name)
+    }
+}
+
+fn render_string(text: &str, context: &Value, path: &str) -> Result<Value, AppError> {
+    let parts = parse_claim(text).map_err(|message| {
+        invalid_template(format!("{} in claim {}", message, path), path, text)
+    })?;
+
+    let expressions: Vec<&str> = parts
+        .iter()
+        .filter_map(|part| match part {
+            ClaimPart::Expression(expression) => Some(*expression),
+            ClaimPart::Text(_) => None,
+        })
+        .collect();
+    let only_whitespace_around = parts.iter().all(|part| match part {
+        ClaimPart::Text(text) => text.trim().is_empty(),
+        ClaimPart::Expression(_) => true,
+    });
+    if let [expression] = expressions.as_slice()
+        && only_whitespace_around
+    {
+        return resolve(expression, context, path);
+    }
+
+    let mut rendered = String::new();
+    for part in parts {
+        match part {
+            ClaimPart::Text(text) => rendered.push_str(text),
+            ClaimPart::Expression(expression) => match resolve(expression, context, path)? {
+                Value::Null => {}
+                Value::String(value) => rendered.push_str(&value),
+                value => rendered.push_str(&value.to_string()),
+            },
+        }
+    }
+
+    Ok(Value::String(rendered))
+}
+
+fn resolve(expression: &str, context: &Value, path: &str) -> Result<Value, AppError> {
+    check_claim_expression(expression).map_err(|message| {
+        invalid_template(format!("{} in claim {}", message, path), path, expression)
+    })?;
+
+    let mut value = context;
+    for segment in expression.split('.') {
+        match value.get(segment) {
+            Some(nested) => value = nested,
+            None => return Ok(Value::Null),
+        }
+    }
+
+    Ok(value.clone())
+}
+
+fn invalid_template(message: String, path: &str, expression: &str) -> AppError {
+    AppError::coded(ErrorCode::InvalidJwtTemplate, message).with_details(json!({
+        "path": path,
+        "expression": expression,
+    }))
+}
+
+/// A key tokens can be signed with, parsed from its stored form. HMAC keys are
+/// the secret itself; RSA and EC keys are PKCS#8 PEM.
+pub struct JwtSigningKey {
+    algorithm: Algorithm,
+    key: EncodingKey,
+    key_id: Option<String>,
+}
+
+impl JwtSigningKey {
+    pub fn new(algorithm: &str, key: &str) -> Result<Self, AppError> {
+        let algorithm = Algorithm::from_str(algorithm).map_err(|_| {
+            AppError::BadRequest(format!("{} isn't a supported signing algorithm", algorithm))
+        })?;
+
+        let key = match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                Ok(EncodingKey::from_secret(key.as_bytes()))
+            }
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 => {
+                EncodingKey::from_rsa_pem(key.as_bytes())
+            }
+            Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(key.as_bytes()),
+            _ => {
+                return Err(AppError::BadRequest(format!(
+                    "{:?} isn't a supported signing algorithm",
+                    algorithm
+                )));
+            }
+        }
+        .map_err(|e| {
+            AppError::BadRequest(format!(
+                "The signing key isn't a valid {:?} key: {}",
+                algorithm, e
+            ))
+        })?;
+
+        Ok(Self {
+            algorithm,
+            key,
+            key_id: None,
+        })
+    }
+
+    pub fn with_key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    pub fn header(&self) -> Header {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.key_id.clone();
+        header
+    }
+
+    /// Signs the claims into a compact JWT.
+    pub fn sign(&self, claims: &Value) -> Result<String, AppError> {
+        jsonwebtoken::encode(&self.header(), claims, &self.key)
+            .map_err(|e| AppError::Internal(format!("Failed to sign a token: {}", e)))
+    }
+}
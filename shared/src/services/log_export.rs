@@ -0,0 +1,250 @@
+use aws_config::{Region, sts::AssumeRoleProvider};
+use aws_sdk_s3::{Client as S3Client, config::Credentials};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    error::AppError,
+    models::{LogExportCredentials, LogExportFormat},
+};
+
+use super::StorageBucket;
+
+/// The kinds of log a deployment can export, each under its own path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportedLog {
+    AuditLogs,
+    RequestLogs,
+}
+
+impl ExportedLog {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportedLog::AuditLogs => "audit-logs",
+            ExportedLog::RequestLogs => "request-logs",
+        }
+    }
+
+    /// The CSV columns, in the order they are written.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            ExportedLog::AuditLogs => &[
+                "id",
+                "created_at",
+                "project_id",
+                "deployment_id",
+                "actor",
+                "action",
+                "resource_type",
+                "resource_id",
+                "metadata",
+            ],
+            ExportedLog::RequestLogs => &[
+                "id",
+                "deployment_id",
+                "api_key_id",
+                "method",
+                "path",
+                "status",
+                "latency_ms",
+                "error",
+                "timestamp",
+            ],
+        }
+    }
+}
+
+/// Joins a customer's prefix and a key without doubled or leading slashes.
+fn prefixed_key(prefix: &str, key: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}/{}", prefix, key)
+    }
+}
+
+/// Where a batch of exported entries is written, partitioned by the day of
+/// its first entry so SIEM tools can load a day at a time. The timestamps
+/// in the name keep batches in order and never collide.
+pub fn log_export_key(
+    prefix: &str,
+    log: ExportedLog,
+    format: LogExportFormat,
+    first: DateTime<Utc>,
+    last: DateTime<Utc>,
+) -> String {
+    prefixed_key(
+        prefix,
+        &format!(
+            "{}/{}/{}-{}-{}.{}",
+            log.as_str(),
+            first.format("%Y/%m/%d"),
+            log.as_str(),
+            first.timestamp_millis(),
+            last.timestamp_millis(),
+            format.as_str()
+        ),
+    )
+}
+
+/// Where the destination test writes its probe object.
+pub fn log_export_probe_key(prefix: &str) -> String {
+    prefixed_key(
+        prefix,
+        &format!("_write_test/{}", hex::encode(rand::random::<[u8; 8]>())),
+    )
+}
+
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// Writes entries as one JSON object per line, or as CSV with a header row
+/// of `log`'s columns.
+pub fn encode_log_export<T: Serialize>(
+    log: ExportedLog,
+    format: LogExportFormat,
+    entries: &[T],
+) -> Result<Vec<u8>, AppError> {
+    let mut output = String::new();
+
+    match format {
+        LogExportFormat::Ndjson => {
+            for entry in entries {
+                output.push_str(&serde_json::to_string(entry)?);
+                output.push('\n');
+            }
+        }
+        LogExportFormat::Csv => {
+            output.push_str(&log.columns().join(","));
+            output.push('\n');
+            for entry in entries {
+                let entry = serde_json::to_value(entry)?;
+                let row: Vec<String> = log
+                    .columns()
+                    .iter()
+                    .map(|column| csv_field(entry.get(column).unwrap_or(&Value::Null)))
+                    .collect();
+                output.push_str(&row.join(","));
+                output.push('\n');
+            }
+        }
+    }
+
+    Ok(output.into_bytes())
+}
+
+/// A client for a customer's bucket, signed in with their access key or
+/// through the role they let the platform assume. Assuming a role starts
+/// from the platform's own AWS credentials, so it always passes the
+/// deployment's own `external_id`.
+pub async fn connect_log_export_bucket(
+    bucket: &str,
+    region: &str,
+    credentials: &LogExportCredentials,
+    external_id: &str,
+) -> StorageBucket {
+    let region = Region::new(region.to_string());
+    let loader = aws_config::from_env().region(region.clone());
+
+    let loader = match credentials {
+        LogExportCredentials::AccessKey {
+            access_key_id,
+            secret_access_key,
+        } => loader.credentials_provider(Credentials::new(
+            access_key_id,
+            secret_access_key,
+            None,
+            None,
+            "log-export",
+        )),
+        LogExportCredentials::AssumeRole { role_arn } => {
+            let provider = AssumeRoleProvider::builder(role_arn)
+                .session_name("log-export")
+                .external_id(external_id)
+                .region(region.clone())
+                .build()
+                .await;
+            loader.credentials_provider(provider)
+        }
+    };
+
+    StorageBucket {
+        client: S3Client::new(&loader.load().await),
+        name: bucket.to_string(),
+        public_base_url: format!("https://{}.s3.{}.amazonaws.com", bucket, region),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keys_are_partitioned_by_day_under_the_prefix() {
+        let first = DateTime::from_timestamp_millis(1_760_000_000_000).unwrap();
+        let last = DateTime::from_timestamp_millis(1_760_000_500_000).unwrap();
+
+        assert_eq!(
+            log_export_key(
+                "/siem/wacht/",
+                ExportedLog::AuditLogs,
+                LogExportFormat::Ndjson,
+                first,
+                last
+            ),
+            "siem/wacht/audit-logs/2025/10/09/audit-logs-1760000000000-1760000500000.ndjson"
+        );
+        assert!(
+            log_export_key(
+                "",
+                ExportedLog::RequestLogs,
+                LogExportFormat::Csv,
+                first,
+                last
+            )
+            .starts_with("request-logs/2025/10/09/")
+        );
+    }
+
+    #[test]
+    fn csv_rows_follow_the_columns_and_quote_where_needed() {
+        let entries = [json!({
+            "id": "1",
+            "created_at": "2025-10-09T08:53:20Z",
+            "project_id": "2",
+            "deployment_id": null,
+            "actor": "Jane \"JD\" Doe",
+            "action": "user.created",
+            "resource_type": "user",
+            "resource_id": "3",
+            "metadata": {"a": 1},
+        })];
+
+        let csv =
+            encode_log_export(ExportedLog::AuditLogs, LogExportFormat::Csv, &entries).unwrap();
+
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id,created_at,project_id,deployment_id,actor,action,resource_type,resource_id,metadata\n\
+             1,2025-10-09T08:53:20Z,2,,\"Jane \"\"JD\"\" Doe\",user.created,user,3,\"{\"\"a\"\":1}\"\n"
+        );
+
+        let ndjson =
+            encode_log_export(ExportedLog::AuditLogs, LogExportFormat::Ndjson, &entries).unwrap();
+        assert_eq!(String::from_utf8(ndjson).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Where a magic link email sends the user, to finish signing in with the
+/// token.
+pub fn magic_link_url(frontend_host: &str, token: &str) -> String {
+    format!(
+        "https://{}/sign-in/magic-link?token={}",
+        frontend_host, token
+    )
+}
+
+/// The form a magic link token is stored in.
+pub fn hash_magic_link_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Mints magic link tokens as `random.signature`, where the signature covers
+/// the deployment and the random part. Forged or mistyped tokens, and tokens
+/// minted for another deployment, are turned away without a database lookup;
+/// whether a token is still usable is up to the stored link.
+#[derive(Clone)]
+pub struct MagicLinkTokenSigner {
+    secret: Arc<Vec<u8>>,
+}
+
+impl MagicLinkTokenSigner {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: Arc::new(secret.into()),
+        }
+    }
+
+    /// Reads `MAGIC_LINK_TOKEN_SECRET`, which every replica has to share for
+    /// a link minted by one to be accepted by another.
+    pub fn from_env() -> Self {
+        let secret = std::env::var("MAGIC_LINK_TOKEN_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .expect("MAGIC_LINK_TOKEN_SECRET must be set");
+        Self::new(secret)
+    }
+
+    fn mac(&self, deployment_id: i64, random: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}.{}", deployment_id, random).as_bytes());
+        mac
+    }
+
+    pub fn mint(&self, deployment_id: i64) -> String {
+        let random = URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+        let signature =
+            URL_SAFE_NO_PAD.encode(self.mac(deployment_id, &random).finalize().into_bytes());
+        format!("{}.{}", random, signature)
+    }
+
+    /// Whether this signer minted `token` for the deployment.
+    pub fn verify(&self, deployment_id: i64, token: &str) -> bool {
+        let Some((random, signature)) = token.split_once('.') else {
+            return false;
+        };
+        let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+            return false;
+        };
+        self.mac(deployment_id, random)
+            .verify_slice(&signature)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_only_verify_for_their_deployment_and_signer() {
+        let signer = MagicLinkTokenSigner::new("secret");
+        let token = signer.mint(1);
+
+        assert!(signer.verify(1, &token));
+        assert!(!signer.verify(2, &token));
+        assert!(!MagicLinkTokenSigner::new("other").verify(1, &token));
+        assert_ne!(signer.mint(1), token);
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let signer = MagicLinkTokenSigner::new("secret");
+        let token = signer.mint(1);
+        let (random, signature) = token.split_once('.').unwrap();
+
+        assert!(!signer.verify(1, &format!("{}x.{}", random, signature)));
+        assert!(!signer.verify(1, random));
+        assert!(!signer.verify(1, &format!("{}.not-base64!", random)));
+    }
+}
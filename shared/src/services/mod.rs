@@ -1,15 +1,80 @@
+pub mod agent_model;
+pub mod background_tasks;
 pub mod clickhouse;
+pub mod clickhouse_buffer;
 pub mod cloudflare;
+pub mod compromised_passwords;
+pub mod credential_cipher;
+pub mod deployment_settings_cache;
+pub mod disposable_domains;
+pub mod domains;
 pub mod dns_verification;
+pub mod email_transport;
 pub mod embedding;
+pub mod expiring_store;
+pub mod geoip;
+pub mod health;
+pub mod http_client;
+pub mod id_generator;
+pub mod image_processing;
+pub mod invitation_token;
+pub mod magic_link;
+pub mod jwt_template;
+pub mod log_export;
+pub mod openapi;
+pub mod otp;
+pub mod phone_intelligence;
 pub mod postmark;
 pub mod qdrant;
+pub mod rate_limit;
+pub mod redis_pool;
+pub mod schema;
+pub mod ses;
+pub mod session_repository;
+pub mod sign_in_lockout;
+pub mod smtp;
+pub mod storage;
+pub mod stored_secrets;
 pub mod text_processing;
+pub mod tool_execution;
+pub mod web3_wallet;
 
+pub use agent_model::*;
+pub use background_tasks::*;
 pub use clickhouse::*;
+pub use clickhouse_buffer::*;
 pub use cloudflare::*;
+pub use compromised_passwords::*;
+pub use credential_cipher::*;
+pub use deployment_settings_cache::*;
+pub use disposable_domains::*;
+pub use domains::*;
 pub use dns_verification::*;
+pub use email_transport::*;
 pub use embedding::*;
+pub use expiring_store::*;
+pub use geoip::*;
+pub use health::*;
+pub use id_generator::*;
+pub use image_processing::*;
+pub use invitation_token::*;
+pub use magic_link::*;
+pub use jwt_template::*;
+pub use log_export::*;
+pub use openapi::*;
+pub use otp::*;
+pub use phone_intelligence::*;
 pub use postmark::*;
 pub use qdrant::*;
+pub use rate_limit::*;
+pub use redis_pool::*;
+pub use schema::*;
+pub use ses::*;
+pub use session_repository::*;
+pub use sign_in_lockout::*;
+pub use smtp::*;
+pub use storage::*;
+pub use stored_secrets::*;
 pub use text_processing::*;
+pub use tool_execution::*;
+pub use web3_wallet::*;
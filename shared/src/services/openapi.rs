@@ -0,0 +1,412 @@
+use serde_json::{Map, Value, json};
+
+use crate::{
+    error::AppError,
+    models::{HttpMethod, HttpParameter, ParameterValueType},
+};
+
+/// How many `$ref`s deep a schema is followed before it is treated as
+/// recursive.
+const MAX_REF_DEPTH: usize = 16;
+
+const OPERATION_METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Header parameters the OpenAPI specification says to ignore; they are
+/// described elsewhere in the document, or set by the tool's own config.
+const IGNORED_HEADERS: [&str; 3] = ["accept", "content-type", "authorization"];
+
+/// An OpenAPI 3.x document, as JSON or YAML.
+pub struct OpenApiDocument {
+    root: Value,
+}
+
+/// One operation of the document: a method on a path.
+pub struct OpenApiOperation {
+    pub operation_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub summary: Option<String>,
+    /// The API tool the operation maps to, or why it can't be imported.
+    pub tool: Result<OpenApiToolDefinition, String>,
+}
+
+/// The parts of an API tool an operation defines. Every parameter is taken
+/// from the chat under its own name.
+pub struct OpenApiToolDefinition {
+    pub method: HttpMethod,
+    pub path_parameters: Vec<HttpParameter>,
+    pub query_parameters: Vec<HttpParameter>,
+    pub headers: Vec<HttpParameter>,
+    pub body_parameters: Vec<HttpParameter>,
+    /// A JSON Schema object with one property per parameter.
+    pub input_schema: Value,
+}
+
+/// The inputs an operation takes, collected into one JSON Schema object.
+#[derive(Default)]
+struct InputSchema {
+    properties: Map<String, Value>,
+    required: Vec<Value>,
+}
+
+impl InputSchema {
+    fn add(&mut self, name: &str, schema: Value, required: bool) -> Result<(), String> {
+        if self.properties.contains_key(name) {
+            return Err(format!("The input '{}' is defined more than once", name));
+        }
+
+        self.properties.insert(name.to_string(), schema);
+        if required {
+            self.required.push(Value::String(name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn into_schema(self) -> Value {
+        let mut schema = json!({ "type": "object", "properties": self.properties });
+        if !self.required.is_empty() {
+            schema["required"] = Value::Array(self.required);
+        }
+        schema
+    }
+}
+
+fn chat_parameter(name: &str, required: bool, description: Option<&str>) -> HttpParameter {
+    HttpParameter {
+        name: name.to_string(),
+        value_type: ParameterValueType::FromChat {
+            lookup_key: name.to_string(),
+        },
+        required,
+        description: description.map(str::to_string),
+    }
+}
+
+fn is_json_media_type(media_type: &str) -> bool {
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+/// Folds an `allOf` of object schemas into a single object schema.
+fn merge_all_of(schema: Value) -> Result<Value, String> {
+    let Some(parts) = schema.get("allOf").and_then(Value::as_array) else {
+        return Ok(schema);
+    };
+
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for part in parts {
+        let part = merge_all_of(part.clone())?;
+        if part.get("oneOf").is_some() || part.get("anyOf").is_some() {
+            return Err("oneOf and anyOf request bodies are not supported".to_string());
+        }
+        if let Some(part_properties) = part.get("properties").and_then(Value::as_object) {
+            properties.extend(part_properties.clone());
+        }
+        if let Some(part_required) = part.get("required").and_then(Value::as_array) {
+            required.extend(part_required.iter().cloned());
+        }
+    }
+
+    Ok(json!({ "type": "object", "properties": properties, "required": required }))
+}
+
+impl OpenApiDocument {
+    pub fn parse(source: &str) -> Result<Self, AppError> {
+        let root: Value = match serde_json::from_str(source) {
+            Ok(root) => root,
+            Err(_) => serde_yaml::from_str(source).map_err(|e| {
+                AppError::BadRequest(format!(
+                    "The OpenAPI document is neither JSON nor YAML: {}",
+                    e
+                ))
+            })?,
+        };
+
+        let version = root
+            .get("openapi")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if !version.starts_with("3.") {
+            return Err(AppError::BadRequest(
+                "Only OpenAPI 3.x documents can be imported".to_string(),
+            ));
+        }
+        if !root.get("paths").is_some_and(Value::is_object) {
+            return Err(AppError::BadRequest(
+                "The OpenAPI document has no paths".to_string(),
+            ));
+        }
+
+        Ok(Self { root })
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.root
+            .pointer("/info/title")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|title| !title.is_empty())
+    }
+
+    /// The first server's URL. It may be relative to where the document
+    /// was fetched from.
+    pub fn server_url(&self) -> Option<&str> {
+        self.root.pointer("/servers/0/url").and_then(Value::as_str)
+    }
+
+    pub fn operations(&self) -> Vec<OpenApiOperation> {
+        let mut operations = Vec::new();
+        let Some(paths) = self.root.get("paths").and_then(Value::as_object) else {
+            return operations;
+        };
+
+        for (path, item) in paths {
+            let item = match self.resolve(item) {
+                Ok(item) => item,
+                Err(reason) => {
+                    operations.push(OpenApiOperation {
+                        operation_id: None,
+                        method: "*".to_string(),
+                        path: path.clone(),
+                        summary: None,
+                        tool: Err(reason),
+                    });
+                    continue;
+                }
+            };
+            let shared_parameters = item
+                .get("parameters")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+
+            for method in OPERATION_METHODS {
+                let Some(operation) = item.get(method) else {
+                    continue;
+                };
+
+                operations.push(OpenApiOperation {
+                    operation_id: operation
+                        .get("operationId")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    method: method.to_uppercase(),
+                    path: path.clone(),
+                    summary: operation
+                        .get("summary")
+                        .or_else(|| operation.get("description"))
+                        .and_then(Value::as_str)
+                        .map(|summary| summary.trim().to_string())
+                        .filter(|summary| !summary.is_empty()),
+                    tool: self.tool_definition(method, operation, shared_parameters),
+                });
+            }
+        }
+
+        operations
+    }
+
+    fn tool_definition(
+        &self,
+        method: &str,
+        operation: &Value,
+        shared_parameters: &[Value],
+    ) -> Result<OpenApiToolDefinition, String> {
+        let method = match method {
+            "get" => HttpMethod::GET,
+            "post" => HttpMethod::POST,
+            "put" => HttpMethod::PUT,
+            "delete" => HttpMethod::DELETE,
+            "patch" => HttpMethod::PATCH,
+            other => {
+                return Err(format!(
+                    "{} operations are not supported",
+                    other.to_uppercase()
+                ));
+            }
+        };
+
+        let mut definition = OpenApiToolDefinition {
+            method,
+            path_parameters: Vec::new(),
+            query_parameters: Vec::new(),
+            headers: Vec::new(),
+            body_parameters: Vec::new(),
+            input_schema: Value::Null,
+        };
+        let mut inputs = InputSchema::default();
+
+        // An operation's own parameters override the path's ones with the
+        // same name and location
+        let own_parameters = operation
+            .get("parameters")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut parameters: Vec<&Value> = Vec::new();
+        for parameter in own_parameters.iter().chain(shared_parameters) {
+            let parameter = self.resolve(parameter)?;
+            let key = (parameter.get("name"), parameter.get("in"));
+            if !parameters
+                .iter()
+                .any(|seen| (seen.get("name"), seen.get("in")) == key)
+            {
+                parameters.push(parameter);
+            }
+        }
+
+        for parameter in parameters {
+            let name = parameter
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or("A parameter has no name")?;
+            let location = parameter
+                .get("in")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let description = parameter.get("description").and_then(Value::as_str);
+            let required = location == "path"
+                || parameter
+                    .get("required")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+
+            let target = match location {
+                "path" => &mut definition.path_parameters,
+                "query" => &mut definition.query_parameters,
+                "header" if IGNORED_HEADERS.contains(&name.to_lowercase().as_str()) => continue,
+                "header" => &mut definition.headers,
+                other => return Err(format!("'{}' parameters are not supported", other)),
+            };
+            target.push(chat_parameter(name, required, description));
+
+            let mut schema = match parameter.get("schema") {
+                Some(schema) => self.inline_schema(schema, 0)?,
+                None => json!({ "type": "string" }),
+            };
+            if let (Some(description), Some(object)) = (description, schema.as_object_mut()) {
+                object
+                    .entry("description")
+                    .or_insert_with(|| Value::String(description.to_string()));
+            }
+            inputs.add(name, schema, required)?;
+        }
+
+        if let Some(body) = operation.get("requestBody") {
+            let body = self.resolve(body)?;
+            let content = body
+                .get("content")
+                .and_then(Value::as_object)
+                .ok_or("The request body has no content")?;
+            let Some(media) = content
+                .iter()
+                .find(|(media_type, _)| is_json_media_type(media_type))
+                .map(|(_, media)| media)
+            else {
+                let media_types: Vec<&str> = content.keys().map(String::as_str).collect();
+                return Err(format!(
+                    "{} request bodies are not supported",
+                    media_types.join(", ")
+                ));
+            };
+
+            let schema = match media.get("schema") {
+                Some(schema) => merge_all_of(self.inline_schema(schema, 0)?)?,
+                None => json!({}),
+            };
+            if schema.get("oneOf").is_some() || schema.get("anyOf").is_some() {
+                return Err("oneOf and anyOf request bodies are not supported".to_string());
+            }
+            if schema
+                .get("type")
+                .is_some_and(|schema_type| schema_type != "object")
+            {
+                return Err("Only JSON object request bodies are supported".to_string());
+            }
+
+            let body_required = body
+                .get("required")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            let required_properties = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+
+            if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                for (name, property) in properties {
+                    let required =
+                        body_required && required_properties.contains(&Value::String(name.clone()));
+                    let description = property.get("description").and_then(Value::as_str);
+                    definition
+                        .body_parameters
+                        .push(chat_parameter(name, required, description));
+                    inputs.add(name, property.clone(), required)?;
+                }
+            }
+        }
+
+        definition.input_schema = inputs.into_schema();
+        Ok(definition)
+    }
+
+    /// Follows `value` through any `$ref`s to what it refers to.
+    fn resolve<'a>(&'a self, mut value: &'a Value) -> Result<&'a Value, String> {
+        for _ in 0..MAX_REF_DEPTH {
+            let Some(reference) = value.get("$ref").and_then(Value::as_str) else {
+                return Ok(value);
+            };
+            value = self.lookup(reference)?;
+        }
+
+        Err("A reference refers back to itself".to_string())
+    }
+
+    fn lookup(&self, reference: &str) -> Result<&Value, String> {
+        let Some(pointer) = reference.strip_prefix('#') else {
+            return Err(format!(
+                "External reference '{}' is not supported",
+                reference
+            ));
+        };
+
+        self.root
+            .pointer(pointer)
+            .ok_or_else(|| format!("Reference '{}' could not be resolved", reference))
+    }
+
+    /// Copies a schema with every `$ref` in it replaced by what it refers to,
+    /// so it stands on its own outside the document.
+    fn inline_schema(&self, schema: &Value, depth: usize) -> Result<Value, String> {
+        if depth > MAX_REF_DEPTH {
+            return Err(format!(
+                "A schema is recursive or nests more than {} references",
+                MAX_REF_DEPTH
+            ));
+        }
+
+        match schema {
+            Value::Object(object) => {
+                if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                    return self.inline_schema(self.lookup(reference)?, depth + 1);
+                }
+
+                let mut inlined = Map::with_capacity(object.len());
+                for (key, value) in object {
+                    inlined.insert(key.clone(), self.inline_schema(value, depth)?);
+                }
+                Ok(Value::Object(inlined))
+            }
+            Value::Array(items) => items
+                .iter()
+                .map(|item| self.inline_schema(item, depth))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            other => Ok(other.clone()),
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::OtpSettings,
+    services::{ExpiringStore, RedisExpiringStore, RedisPool},
+    utils::clock::{Clock, SystemClock},
+};
+
+/// A code that has just been issued, for sending to the identifier it was
+/// issued for.
+#[derive(Debug, Clone)]
+pub struct IssuedOtp {
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+    pub expires_in_minutes: i64,
+}
+
+impl IssuedOtp {
+    /// The variables email and SMS verification templates are rendered with,
+    /// so `{{code.expires_in_minutes}}` always matches the deployment's
+    /// setting.
+    pub fn template_variables(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("code".to_string(), self.code.clone()),
+            (
+                "code.expires_in_minutes".to_string(),
+                self.expires_in_minutes.to_string(),
+            ),
+        ])
+    }
+}
+
+fn generate_code(length: u8) -> String {
+    (0..length)
+        .map(|_| char::from(b'0' + rand::random_range(0..10u8)))
+        .collect()
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Issues and checks the one-time codes sent to email addresses and phone
+/// numbers, following a deployment's `OtpSettings`. Codes, wrong attempts
+/// and resend cooldowns are kept per deployment and identifier. Codes are
+/// stored as an HMAC keyed with a server secret, so reading Redis isn't
+/// enough to recover them, and identifiers are hashed into the keys.
+#[derive(Clone)]
+pub struct OtpService {
+    store: Arc<dyn ExpiringStore>,
+    clock: Arc<dyn Clock>,
+    secret: Arc<Vec<u8>>,
+}
+
+impl OtpService {
+    pub fn new(
+        store: Arc<dyn ExpiringStore>,
+        clock: Arc<dyn Clock>,
+        secret: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self {
+            store,
+            clock,
+            secret: Arc::new(secret.into()),
+        }
+    }
+
+    /// Keeps codes in Redis, keyed with `OTP_CODE_SECRET`, which every
+    /// replica has to share for a code issued by one to be checked by
+    /// another.
+    pub fn from_env(redis: RedisPool) -> Self {
+        let secret = std::env::var("OTP_CODE_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .expect("OTP_CODE_SECRET must be set");
+        Self::new(
+            Arc::new(RedisExpiringStore::new(redis)),
+            Arc::new(SystemClock),
+            secret,
+        )
+    }
+
+    fn normalize(identifier: &str) -> String {
+        identifier.trim().to_lowercase()
+    }
+
+    fn key(kind: &str, deployment_id: i64, identifier: &str) -> String {
+        let hash = hex::encode(Sha256::digest(Self::normalize(identifier).as_bytes()));
+        format!("otp_{}:{}:{}", kind, deployment_id, &hash[..32])
+    }
+
+    /// The first 64 bits of an HMAC of the code, scoped to the deployment
+    /// and identifier it was sent to.
+    fn code_digest(&self, deployment_id: i64, identifier: &str, code: &str) -> i64 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(
+            format!(
+                "{}:{}:{}",
+                deployment_id,
+                Self::normalize(identifier),
+                code.trim()
+            )
+            .as_bytes(),
+        );
+        let hash = mac.finalize().into_bytes();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hash[..8]);
+        i64::from_be_bytes(bytes)
+    }
+
+    /// Issues a new code for an identifier, replacing any earlier one and
+    /// resetting its wrong attempts. Fails with `otp_resend_cooldown` while
+    /// the previous send is still within the resend cooldown.
+    pub async fn issue(
+        &self,
+        deployment_id: i64,
+        identifier: &str,
+        settings: &OtpSettings,
+    ) -> Result<IssuedOtp, AppError> {
+        let now = self.clock.now();
+        let cooldown_key = Self::key("cooldown", deployment_id, identifier);
+        if let Some(available_at) = self.store.get(&cooldown_key).await? {
+            let retry_after_seconds = available_at - now.timestamp();
+            if retry_after_seconds > 0 {
+                return Err(AppError::coded(
+                    ErrorCode::OtpResendCooldown,
+                    "A code was sent recently. Wait before sending another.",
+                )
+                .with_details(json!({ "retry_after_seconds": retry_after_seconds })));
+            }
+        }
+
+        let code = generate_code(settings.code_length);
+        let expiry = Duration::minutes(settings.expiry_minutes);
+        self.store
+            .set(
+                &Self::key("code", deployment_id, identifier),
+                self.code_digest(deployment_id, identifier, &code),
+                expiry,
+            )
+            .await?;
+        self.store
+            .delete(&[Self::key("attempts", deployment_id, identifier)])
+            .await?;
+
+        if settings.resend_cooldown_seconds > 0 {
+            let cooldown = Duration::seconds(settings.resend_cooldown_seconds);
+            self.store
+                .set(&cooldown_key, (now + cooldown).timestamp(), cooldown)
+                .await?;
+        }
+
+        Ok(IssuedOtp {
+            code,
+            expires_at: now + expiry,
+            expires_in_minutes: settings.expiry_minutes,
+        })
+    }
+
+    /// Checks a code and uses it up when it's right. Using it up is atomic,
+    /// so of two requests with the right code only one gets through. A wrong
+    /// code fails with `invalid_otp` and the attempts left; the last allowed
+    /// wrong attempt also drops the code, so later attempts fail with
+    /// `otp_expired` until a new one is sent.
+    pub async fn verify(
+        &self,
+        deployment_id: i64,
+        identifier: &str,
+        code: &str,
+        settings: &OtpSettings,
+    ) -> Result<(), AppError> {
+        let code_key = Self::key("code", deployment_id, identifier);
+        let attempts_key = Self::key("attempts", deployment_id, identifier);
+
+        let digest = self.code_digest(deployment_id, identifier, code);
+        if self.store.delete_if_equal(&code_key, digest).await? {
+            self.store.delete(&[attempts_key]).await?;
+            return Ok(());
+        }
+
+        if self.store.get(&code_key).await?.is_none() {
+            return Err(AppError::coded(
+                ErrorCode::OtpExpired,
+                "The code has expired or is no longer valid. Send a new one.",
+            ));
+        }
+
+        let attempts = self
+            .store
+            .increment(&attempts_key, Duration::minutes(settings.expiry_minutes))
+            .await?;
+        let remaining_attempts = (settings.max_attempts - attempts).max(0);
+        if remaining_attempts == 0 {
+            self.store.delete(&[code_key, attempts_key]).await?;
+        }
+
+        Err(
+            AppError::coded(ErrorCode::InvalidOtp, "The code is incorrect")
+                .with_details(json!({ "remaining_attempts": remaining_attempts })),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::MemoryExpiringStore;
+    use crate::utils::clock::FakeClock;
+
+    fn service(clock: &Arc<FakeClock>) -> OtpService {
+        let (store, clock) = MemoryExpiringStore::with_fake_clock(clock);
+        OtpService::new(store, clock, "test")
+    }
+
+    fn settings() -> OtpSettings {
+        OtpSettings {
+            code_length: 8,
+            expiry_minutes: 5,
+            max_attempts: 3,
+            resend_cooldown_seconds: 60,
+        }
+    }
+
+    fn wrong_code(code: &str) -> String {
+        code.chars()
+            .map(|c| if c == '0' { '1' } else { '0' })
+            .collect()
+    }
+
+    fn error_code_and_details(error: AppError) -> (ErrorCode, Option<serde_json::Value>) {
+        match error {
+            AppError::Coded { code, details, .. } => (code, details),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn codes_follow_the_settings_and_work_once() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        let issued = service
+            .issue(1, "user@example.com", &settings())
+            .await
+            .unwrap();
+        assert_eq!(issued.code.len(), 8);
+        assert!(issued.code.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(issued.template_variables()["code.expires_in_minutes"], "5");
+
+        service
+            .verify(1, " User@Example.com", &issued.code, &settings())
+            .await
+            .unwrap();
+        let (code, _) = error_code_and_details(
+            service
+                .verify(1, "user@example.com", &issued.code, &settings())
+                .await
+                .unwrap_err(),
+        );
+        assert_eq!(code, ErrorCode::OtpExpired);
+    }
+
+    #[tokio::test]
+    async fn codes_expire() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        let issued = service
+            .issue(1, "user@example.com", &settings())
+            .await
+            .unwrap();
+        clock.advance(5 * 60);
+
+        let (code, _) = error_code_and_details(
+            service
+                .verify(1, "user@example.com", &issued.code, &settings())
+                .await
+                .unwrap_err(),
+        );
+        assert_eq!(code, ErrorCode::OtpExpired);
+    }
+
+    #[tokio::test]
+    async fn running_out_of_attempts_invalidates_the_code() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        let issued = service
+            .issue(1, "user@example.com", &settings())
+            .await
+            .unwrap();
+        let wrong = wrong_code(&issued.code);
+
+        for remaining in [2, 1, 0] {
+            let (code, details) = error_code_and_details(
+                service
+                    .verify(1, "user@example.com", &wrong, &settings())
+                    .await
+                    .unwrap_err(),
+            );
+            assert_eq!(code, ErrorCode::InvalidOtp);
+            assert_eq!(details, Some(json!({ "remaining_attempts": remaining })));
+        }
+
+        let (code, _) = error_code_and_details(
+            service
+                .verify(1, "user@example.com", &issued.code, &settings())
+                .await
+                .unwrap_err(),
+        );
+        assert_eq!(code, ErrorCode::OtpExpired);
+    }
+
+    #[tokio::test]
+    async fn resends_wait_for_the_cooldown_and_replace_the_code() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        let first = service
+            .issue(1, "user@example.com", &settings())
+            .await
+            .unwrap();
+        service
+            .verify(1, "user@example.com", &wrong_code(&first.code), &settings())
+            .await
+            .unwrap_err();
+
+        clock.advance(20);
+        let (code, details) = error_code_and_details(
+            service
+                .issue(1, "user@example.com", &settings())
+                .await
+                .unwrap_err(),
+        );
+        assert_eq!(code, ErrorCode::OtpResendCooldown);
+        assert_eq!(details, Some(json!({ "retry_after_seconds": 40 })));
+
+        // Other identifiers have their own cooldown.
+        service
+            .issue(1, "other@example.com", &settings())
+            .await
+            .unwrap();
+
+        clock.advance(40);
+        let second = service
+            .issue(1, "user@example.com", &settings())
+            .await
+            .unwrap();
+
+        // The wrong attempt against the first code doesn't count against
+        // the new one.
+        for remaining in [2, 1] {
+            let (_, details) = error_code_and_details(
+                service
+                    .verify(
+                        1,
+                        "user@example.com",
+                        &wrong_code(&second.code),
+                        &settings(),
+                    )
+                    .await
+                    .unwrap_err(),
+            );
+            assert_eq!(details, Some(json!({ "remaining_attempts": remaining })));
+        }
+        service
+            .verify(1, "user@example.com", &second.code, &settings())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{
+    error::AppError,
+    models::{PhoneIntelligenceMetrics, PhoneLineType, PhoneLookup},
+    services::RedisPool,
+};
+
+const LOOKUP_CACHE_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+pub type PhoneLookupFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<PhoneLookup, AppError>> + Send + 'a>>;
+
+/// Classifies phone numbers. Implementations only talk to the provider;
+/// caching and metrics are handled by `PhoneIntelligenceService`.
+pub trait PhoneIntelligenceProvider: Send + Sync {
+    /// `phone_number` is always in E.164 format.
+    fn lookup<'a>(&'a self, phone_number: &'a str) -> PhoneLookupFuture<'a>;
+}
+
+#[derive(Debug, Clone)]
+pub struct PhoneLookupConfig {
+    pub base_url: String,
+    pub account_sid: String,
+    pub auth_token: String,
+}
+
+impl PhoneLookupConfig {
+    /// Returns `None` when no provider credentials are configured.
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            base_url: std::env::var("PHONE_LOOKUP_URL")
+                .unwrap_or_else(|_| "https://lookups.twilio.com/v2/PhoneNumbers".to_string()),
+            account_sid: std::env::var("PHONE_LOOKUP_ACCOUNT_SID").ok()?,
+            auth_token: std::env::var("PHONE_LOOKUP_AUTH_TOKEN").ok()?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    line_type_intelligence: Option<LineTypeIntelligence>,
+}
+
+#[derive(Deserialize)]
+struct LineTypeIntelligence {
+    #[serde(rename = "type")]
+    line_type: Option<String>,
+    carrier_name: Option<String>,
+}
+
+/// Talks to a Twilio Lookup v2 compatible API.
+pub struct LookupApiProvider {
+    client: reqwest::Client,
+    config: PhoneLookupConfig,
+}
+
+impl LookupApiProvider {
+    pub fn new(config: PhoneLookupConfig) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            config,
+        }
+    }
+}
+
+impl PhoneIntelligenceProvider for LookupApiProvider {
+    fn lookup<'a>(&'a self, phone_number: &'a str) -> PhoneLookupFuture<'a> {
+        Box::pin(async move {
+            let response: LookupResponse = self
+                .client
+                .get(format!(
+                    "{}/{}",
+                    self.config.base_url.trim_end_matches('/'),
+                    phone_number
+                ))
+                .query(&[("Fields", "line_type_intelligence")])
+                .basic_auth(&self.config.account_sid, Some(&self.config.auth_token))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| AppError::External(e.to_string().into()))?
+                .json()
+                .await
+                .map_err(|e| AppError::External(e.to_string().into()))?;
+
+            let intelligence = response.line_type_intelligence;
+
+            Ok(PhoneLookup {
+                phone_number: phone_number.to_string(),
+                line_type: intelligence
+                    .as_ref()
+                    .and_then(|i| i.line_type.as_deref())
+                    .map(PhoneLineType::from_provider)
+                    .unwrap_or(PhoneLineType::Unknown),
+                carrier_name: intelligence.and_then(|i| i.carrier_name),
+            })
+        })
+    }
+}
+
+/// Answers from a fixed table, for tests and for environments without a
+/// provider. Numbers it doesn't know are reported as `unknown`.
+#[derive(Default)]
+pub struct StubPhoneIntelligence {
+    line_types: HashMap<String, PhoneLineType>,
+}
+
+impl StubPhoneIntelligence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_line_type(mut self, phone_number: &str, line_type: PhoneLineType) -> Self {
+        self.line_types.insert(phone_number.to_string(), line_type);
+        self
+    }
+}
+
+impl PhoneIntelligenceProvider for StubPhoneIntelligence {
+    fn lookup<'a>(&'a self, phone_number: &'a str) -> PhoneLookupFuture<'a> {
+        Box::pin(async move {
+            Ok(PhoneLookup {
+                phone_number: phone_number.to_string(),
+                line_type: self
+                    .line_types
+                    .get(phone_number)
+                    .copied()
+                    .unwrap_or(PhoneLineType::Unknown),
+                carrier_name: None,
+            })
+        })
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    provider_requests: AtomicU64,
+    provider_errors: AtomicU64,
+    cache_hits: AtomicU64,
+    voip_rejections: AtomicU64,
+}
+
+/// Looks up phone numbers through a provider, caching results in Redis by
+/// E.164 number so each number is paid for at most once a month.
+#[derive(Clone)]
+pub struct PhoneIntelligenceService {
+    provider: Arc<dyn PhoneIntelligenceProvider>,
+    redis: RedisPool,
+    counters: Arc<Counters>,
+}
+
+impl PhoneIntelligenceService {
+    pub fn new(redis: RedisPool, provider: Arc<dyn PhoneIntelligenceProvider>) -> Self {
+        Self {
+            provider,
+            redis,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// Uses the lookup API when credentials are configured and the stub
+    /// otherwise, which lets every number through.
+    pub fn from_env(redis: RedisPool) -> Self {
+        let provider: Arc<dyn PhoneIntelligenceProvider> = match PhoneLookupConfig::from_env() {
+            Some(config) => Arc::new(LookupApiProvider::new(config)),
+            None => {
+                tracing::warn!(
+                    "PHONE_LOOKUP_ACCOUNT_SID or PHONE_LOOKUP_AUTH_TOKEN is not set, VOIP numbers won't be detected"
+                );
+                Arc::new(StubPhoneIntelligence::new())
+            }
+        };
+
+        Self::new(redis, provider)
+    }
+
+    pub fn metrics(&self) -> PhoneIntelligenceMetrics {
+        PhoneIntelligenceMetrics {
+            provider_requests: self.counters.provider_requests.load(Ordering::Relaxed),
+            provider_errors: self.counters.provider_errors.load(Ordering::Relaxed),
+            cache_hits: self.counters.cache_hits.load(Ordering::Relaxed),
+            voip_rejections: self.counters.voip_rejections.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn record_voip_rejection(&self) {
+        self.counters
+            .voip_rejections
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub async fn lookup(&self, phone_number: &str) -> Result<PhoneLookup, AppError> {
+        let phone_number = to_e164(phone_number);
+        let key = format!("phone_lookup:{}", phone_number);
+
+        match self.redis.get_json::<PhoneLookup>(&key).await {
+            Ok(Some(lookup)) => {
+                self.counters.cache_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(lookup);
+            }
+            // A lookup cached in an older shape is looked up again.
+            Ok(None) | Err(AppError::Serialization(_)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let requests = self
+            .counters
+            .provider_requests
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        let lookup = match self.provider.lookup(&phone_number).await {
+            Ok(lookup) => lookup,
+            Err(e) => {
+                self.counters
+                    .provider_errors
+                    .fetch_add(1, Ordering::Relaxed);
+                return Err(e);
+            }
+        };
+        tracing::debug!(
+            provider_requests_total = requests,
+            "Phone lookup provider called"
+        );
+
+        self.redis
+            .set_json_with_ttl(&key, &lookup, LOOKUP_CACHE_TTL)
+            .await?;
+
+        Ok(lookup)
+    }
+}
+
+/// Strips formatting so the same number always maps to the same cache key.
+pub fn to_e164(phone_number: &str) -> String {
+    let digits: String = phone_number.chars().filter(char::is_ascii_digit).collect();
+    format!("+{}", digits)
+}
@@ -1,17 +1,101 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
 use crate::{
-    error::AppError,
+    error::{AppError, ExternalError},
     models::{DnsRecord, EmailVerificationRecords},
+    services::http_client::{provider_client, send_with_retry},
 };
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub type EmailDomainFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, PostmarkError>> + Send + 'a>>;
+
+/// The sending domains deployments get on the platform's Postmark account,
+/// behind a trait so commands can run against [`FakeEmailDomains`] in tests.
+pub trait EmailDomainApi: Send + Sync {
+    /// Not retried; callers look the domain up with [`Self::find_domain`]
+    /// first.
+    fn create_domain<'a>(&'a self, domain_name: &'a str) -> EmailDomainFuture<'a, PostmarkDomain>;
+
+    /// Finds a domain by name, so a create that may already have succeeded
+    /// can be retried without making a duplicate.
+    fn find_domain<'a>(
+        &'a self,
+        domain_name: &'a str,
+    ) -> EmailDomainFuture<'a, Option<PostmarkDomain>>;
+
+    fn delete_domain(&self, domain_id: i64) -> EmailDomainFuture<'_, ()>;
+
+    /// Starts replacing the domain's DKIM key. The new key comes back as the
+    /// pending one, and Postmark keeps signing with the current key until
+    /// the new one's record verifies.
+    fn rotate_dkim(&self, domain_id: i64) -> EmailDomainFuture<'_, PostmarkDomain>;
+
+    fn generate_email_verification_records(
+        &self,
+        domain: &PostmarkDomain,
+    ) -> EmailVerificationRecords {
+        let mut records = EmailVerificationRecords {
+            postmark_domain_id: Some(domain.id),
+            ..Default::default()
+        };
+
+        if !domain.dkim_pending_host.is_empty() && !domain.dkim_pending_text_value.is_empty() {
+            records.dkim_records.push(DnsRecord {
+                name: domain.dkim_pending_host.clone(),
+                record_type: "TXT".to_string(),
+                value: domain.dkim_pending_text_value.clone(),
+                verified: false,
+                verification_attempted_at: None,
+                last_verified_at: None,
+                status: None,
+            });
+        }
+
+        if !domain.dkim_host.is_empty() && !domain.dkim_text_value.is_empty() {
+            records.dkim_records.push(DnsRecord {
+                name: domain.dkim_host.clone(),
+                record_type: "TXT".to_string(),
+                value: domain.dkim_text_value.clone(),
+                verified: domain.dkim_verified,
+                verification_attempted_at: None,
+                last_verified_at: None,
+                status: None,
+            });
+        }
+
+        if !domain.return_path_domain.is_empty()
+            && !domain.return_path_domain_cname_value.is_empty()
+        {
+            records.return_path_records.push(DnsRecord {
+                name: domain.return_path_domain.clone(),
+                record_type: "CNAME".to_string(),
+                value: domain.return_path_domain_cname_value.clone(),
+                verified: domain.return_path_domain_verified,
+                verification_attempted_at: None,
+                last_verified_at: None,
+                status: None,
+            });
+        }
+
+        records
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct PostmarkService {
+    client: reqwest::Client,
     account_token: String,
     server_token: String,
     base_url: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostmarkDomain {
     #[serde(rename = "ID")]
     pub id: i64,
@@ -51,6 +135,24 @@ pub struct PostmarkDomain {
     pub return_path_domain_cname_value: String,
 }
 
+const DOMAIN_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Deserialize)]
+struct DomainSummary {
+    #[serde(rename = "ID")]
+    id: i64,
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DomainList {
+    #[serde(rename = "TotalCount")]
+    total_count: usize,
+    #[serde(rename = "Domains")]
+    domains: Vec<DomainSummary>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct CreateDomainRequest {
     #[serde(rename = "Name")]
@@ -89,45 +191,123 @@ pub struct SendEmailResponse {
     pub message: String,
 }
 
+/// The error body Postmark answers failed requests with.
+#[derive(Debug, Deserialize)]
+struct PostmarkApiError {
+    #[serde(rename = "ErrorCode")]
+    error_code: i64,
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+#[derive(Debug, Error)]
+pub enum PostmarkError {
+    #[error("Postmark API request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Postmark API error ({status}, code {error_code}): {message}")]
+    Api {
+        status: u16,
+        error_code: i64,
+        message: String,
+    },
+    #[error("Failed to parse Postmark response ({status}): {message}")]
+    InvalidResponse { status: u16, message: String },
+}
+
+impl PostmarkError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            PostmarkError::Request(e) => e.status().map(|status| status.as_u16()),
+            PostmarkError::Api { status, .. } | PostmarkError::InvalidResponse { status, .. } => {
+                Some(*status)
+            }
+        }
+    }
+}
+
+impl From<PostmarkError> for AppError {
+    fn from(error: PostmarkError) -> Self {
+        AppError::External(ExternalError::new(
+            "postmark",
+            error.status(),
+            error.to_string(),
+        ))
+    }
+}
+
 impl PostmarkService {
     pub fn new(account_token: String, server_token: String) -> Self {
         Self {
+            client: provider_client(),
             account_token,
             server_token,
             base_url: "https://api.postmarkapp.com".to_string(),
         }
     }
 
-    pub fn create_domain(&self, domain_name: &str) -> Result<PostmarkDomain, AppError> {
-        let return_path_domain = format!("rp.{}", domain_name);
-
-        let request = CreateDomainRequest {
-            name: domain_name.to_string(),
-            return_path_domain,
-        };
+    /// A service that can only send, for a deployment using its own Postmark
+    /// server.
+    pub fn for_server(server_token: String) -> Self {
+        Self::new(String::new(), server_token)
+    }
 
-        let mut response = ureq::post(&format!("{}/domains", self.base_url))
+    fn account_request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.base_url, path))
             .header("Accept", "application/json")
-            .header("Content-Type", "application/json")
             .header("X-Postmark-Account-Token", &self.account_token)
-            .send_json(&request)
-            .map_err(|e| AppError::External(format!("Failed to create Postmark domain: {}", e)))?;
-
-        if response.status() != 200 {
-            let error_text = response
-                .body_mut()
-                .read_to_string()
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::External(format!(
-                "Postmark API error: {}",
-                error_text
-            )));
+    }
+
+    fn server_request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.base_url, path))
+            .header("Accept", "application/json")
+            .header("X-Postmark-Server-Token", &self.server_token)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, PostmarkError> {
+        let response = send_with_retry(&self.client, request.build()?).await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            let (error_code, message) = match serde_json::from_str::<PostmarkApiError>(&body) {
+                Ok(error) => (error.error_code, error.message),
+                Err(_) => (0, body),
+            };
+            return Err(PostmarkError::Api {
+                status: status.as_u16(),
+                error_code,
+                message,
+            });
         }
 
-        let domain: PostmarkDomain = response
-            .body_mut()
-            .read_json()
-            .map_err(|e| AppError::External(format!("Failed to parse Postmark response: {}", e)))?;
+        serde_json::from_str(&body).map_err(|e| PostmarkError::InvalidResponse {
+            status: status.as_u16(),
+            message: e.to_string(),
+        })
+    }
+
+    /// Checks the server token by reading the server it belongs to.
+    pub async fn verify_server_token(&self) -> Result<(), PostmarkError> {
+        self.send::<serde_json::Value>(self.server_request(Method::GET, "/server"))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn create_domain(&self, domain_name: &str) -> Result<PostmarkDomain, PostmarkError> {
+        let request = CreateDomainRequest {
+            name: domain_name.to_string(),
+            return_path_domain: format!("rp.{}", domain_name),
+        };
+
+        let domain: PostmarkDomain = self
+            .send(
+                self.account_request(Method::POST, "/domains")
+                    .json(&request),
+            )
+            .await?;
 
         tracing::info!(
             "Successfully created Postmark domain: {} (ID: {})",
@@ -137,98 +317,78 @@ impl PostmarkService {
         Ok(domain)
     }
 
-    pub fn get_domain(&self, domain_id: i64) -> Result<PostmarkDomain, AppError> {
-        let mut response = ureq::get(&format!("{}/domains/{}", self.base_url, domain_id))
-            .header("Accept", "application/json")
-            .header("X-Postmark-Account-Token", &self.account_token)
-            .call()
-            .map_err(|e| AppError::External(format!("Failed to get Postmark domain: {}", e)))?;
-
-        if response.status() != 200 {
-            let error_text = response
-                .body_mut()
-                .read_to_string()
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::External(format!(
-                "Postmark API error: {}",
-                error_text
-            )));
+    pub async fn find_domain(
+        &self,
+        domain_name: &str,
+    ) -> Result<Option<PostmarkDomain>, PostmarkError> {
+        let mut offset = 0;
+
+        loop {
+            let page: DomainList = self
+                .send(
+                    self.account_request(Method::GET, "/domains")
+                        .query(&[("count", DOMAIN_PAGE_SIZE), ("offset", offset)]),
+                )
+                .await?;
+
+            if let Some(domain) = page
+                .domains
+                .iter()
+                .find(|domain| domain.name.eq_ignore_ascii_case(domain_name))
+            {
+                return self.get_domain(domain.id).await.map(Some);
+            }
+
+            offset += page.domains.len();
+            if page.domains.is_empty() || offset >= page.total_count {
+                return Ok(None);
+            }
         }
-
-        let domain: PostmarkDomain = response
-            .body_mut()
-            .read_json()
-            .map_err(|e| AppError::External(format!("Failed to parse Postmark response: {}", e)))?;
-
-        Ok(domain)
     }
 
-    pub fn verify_dkim(&self, domain_id: i64) -> Result<PostmarkDomain, AppError> {
-        let mut response = ureq::put(&format!(
-            "{}/domains/{}/verifyDkim",
-            self.base_url, domain_id
-        ))
-        .header("Accept", "application/json")
-        .header("X-Postmark-Account-Token", &self.account_token)
-        .send("")
-        .map_err(|e| AppError::External(format!("Failed to verify DKIM: {}", e)))?;
-
-        if response.status() != 200 {
-            let error_text = response
-                .body_mut()
-                .read_to_string()
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::External(format!(
-                "Postmark DKIM verification error: {}",
-                error_text
-            )));
-        }
-
-        let domain: PostmarkDomain = response
-            .body_mut()
-            .read_json()
-            .map_err(|e| AppError::External(format!("Failed to parse Postmark response: {}", e)))?;
+    pub async fn get_domain(&self, domain_id: i64) -> Result<PostmarkDomain, PostmarkError> {
+        self.send(self.account_request(Method::GET, &format!("/domains/{}", domain_id)))
+            .await
+    }
 
-        Ok(domain)
+    pub async fn verify_dkim(&self, domain_id: i64) -> Result<PostmarkDomain, PostmarkError> {
+        self.send(self.account_request(Method::PUT, &format!("/domains/{}/verifyDkim", domain_id)))
+            .await
     }
 
-    pub fn verify_return_path(&self, domain_id: i64) -> Result<PostmarkDomain, AppError> {
-        let mut response = ureq::put(&format!(
-            "{}/domains/{}/verifyReturnPath",
-            self.base_url, domain_id
+    pub async fn verify_return_path(
+        &self,
+        domain_id: i64,
+    ) -> Result<PostmarkDomain, PostmarkError> {
+        self.send(self.account_request(
+            Method::PUT,
+            &format!("/domains/{}/verifyReturnPath", domain_id),
         ))
-        .header("Accept", "application/json")
-        .header("X-Postmark-Account-Token", &self.account_token)
-        .send("")
-        .map_err(|e| AppError::External(format!("Failed to verify Return-Path: {}", e)))?;
-
-        if response.status() != 200 {
-            let error_text = response
-                .body_mut()
-                .read_to_string()
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::External(format!(
-                "Postmark Return-Path verification error: {}",
-                error_text
-            )));
-        }
+        .await
+    }
 
-        let domain: PostmarkDomain = response
-            .body_mut()
-            .read_json()
-            .map_err(|e| AppError::External(format!("Failed to parse Postmark response: {}", e)))?;
+    pub async fn rotate_dkim(&self, domain_id: i64) -> Result<PostmarkDomain, PostmarkError> {
+        let domain: PostmarkDomain = self
+            .send(self.account_request(Method::POST, &format!("/domains/{}/rotatedkim", domain_id)))
+            .await?;
 
+        tracing::info!(
+            "Started DKIM key rotation for Postmark domain: {} (ID: {})",
+            domain.name,
+            domain.id
+        );
         Ok(domain)
     }
 
-    pub fn send_email(
+    /// Sent once: a retried send could deliver the email twice.
+    pub async fn send_email(
         &self,
         from: &str,
         to: &str,
         subject: &str,
         html_body: &str,
         text_body: Option<&str>,
-    ) -> Result<SendEmailResponse, AppError> {
+    ) -> Result<SendEmailResponse, PostmarkError> {
         let request = SendEmailRequest {
             from: from.to_string(),
             to: to.to_string(),
@@ -238,57 +398,27 @@ impl PostmarkService {
             message_stream: Some("outbound".to_string()),
         };
 
-        let mut response = ureq::post(&format!("{}/email", self.base_url))
-            .header("Accept", "application/json")
-            .header("Content-Type", "application/json")
-            .header("X-Postmark-Server-Token", &self.server_token)
-            .send_json(&request)
-            .map_err(|e| AppError::External(format!("Failed to send email via Postmark: {}", e)))?;
-
-        if response.status() != 200 {
-            let error_text = response
-                .body_mut()
-                .read_to_string()
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::External(format!(
-                "Postmark email sending error: {}",
-                error_text
-            )));
-        }
-
-        let email_response: SendEmailResponse = response
-            .body_mut()
-            .read_json()
-            .map_err(|e| AppError::External(format!("Failed to parse Postmark response: {}", e)))?;
+        let email_response: SendEmailResponse = self
+            .send(self.server_request(Method::POST, "/email").json(&request))
+            .await?;
 
         if email_response.error_code != 0 {
-            return Err(AppError::External(format!(
-                "Postmark email error: {}",
-                email_response.message
-            )));
+            return Err(PostmarkError::Api {
+                status: 200,
+                error_code: email_response.error_code.into(),
+                message: email_response.message,
+            });
         }
 
         tracing::info!("Successfully sent email via Postmark: {} -> {}", from, to);
         Ok(email_response)
     }
 
-    pub fn delete_domain(&self, domain_id: i64) -> Result<(), AppError> {
-        let mut response = ureq::delete(&format!("{}/domains/{}", self.base_url, domain_id))
-            .header("Accept", "application/json")
-            .header("X-Postmark-Account-Token", &self.account_token)
-            .call()
-            .map_err(|e| AppError::External(format!("Failed to delete Postmark domain: {}", e)))?;
-
-        if response.status() != 200 {
-            let error_text = response
-                .body_mut()
-                .read_to_string()
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::External(format!(
-                "Postmark domain deletion error: {}",
-                error_text
-            )));
-        }
+    pub async fn delete_domain(&self, domain_id: i64) -> Result<(), PostmarkError> {
+        self.send::<serde_json::Value>(
+            self.account_request(Method::DELETE, &format!("/domains/{}", domain_id)),
+        )
+        .await?;
 
         tracing::info!("Successfully deleted Postmark domain: {}", domain_id);
         Ok(())
@@ -298,52 +428,185 @@ impl PostmarkService {
         &self,
         domain: &PostmarkDomain,
     ) -> EmailVerificationRecords {
-        let mut records = EmailVerificationRecords::default();
+        EmailDomainApi::generate_email_verification_records(self, domain)
+    }
 
-        records.postmark_domain_id = Some(domain.id);
+    pub fn are_records_verified(&self, records: &EmailVerificationRecords) -> bool {
+        let dkim_verified = records.dkim_records.iter().all(|r| r.verified);
+        let return_path_verified = records.return_path_records.iter().all(|r| r.verified);
 
-        if !domain.dkim_pending_host.is_empty() && !domain.dkim_pending_text_value.is_empty() {
-            records.dkim_records.push(DnsRecord {
-                name: domain.dkim_pending_host.clone(),
-                record_type: "TXT".to_string(),
-                value: domain.dkim_pending_text_value.clone(),
-                verified: false,
-                verification_attempted_at: None,
-                last_verified_at: None,
-            });
-        }
+        dkim_verified && return_path_verified
+    }
+}
 
-        if !domain.dkim_host.is_empty() && !domain.dkim_text_value.is_empty() {
-            records.dkim_records.push(DnsRecord {
-                name: domain.dkim_host.clone(),
-                record_type: "TXT".to_string(),
-                value: domain.dkim_text_value.clone(),
-                verified: domain.dkim_verified,
-                verification_attempted_at: None,
-                last_verified_at: None,
-            });
-        }
+impl EmailDomainApi for PostmarkService {
+    fn create_domain<'a>(&'a self, domain_name: &'a str) -> EmailDomainFuture<'a, PostmarkDomain> {
+        Box::pin(PostmarkService::create_domain(self, domain_name))
+    }
 
-        if !domain.return_path_domain.is_empty()
-            && !domain.return_path_domain_cname_value.is_empty()
-        {
-            records.return_path_records.push(DnsRecord {
-                name: domain.return_path_domain.clone(),
-                record_type: "CNAME".to_string(),
-                value: domain.return_path_domain_cname_value.clone(),
-                verified: domain.return_path_domain_verified,
-                verification_attempted_at: None,
-                last_verified_at: None,
+    fn find_domain<'a>(
+        &'a self,
+        domain_name: &'a str,
+    ) -> EmailDomainFuture<'a, Option<PostmarkDomain>> {
+        Box::pin(PostmarkService::find_domain(self, domain_name))
+    }
+
+    fn delete_domain(&self, domain_id: i64) -> EmailDomainFuture<'_, ()> {
+        Box::pin(PostmarkService::delete_domain(self, domain_id))
+    }
+
+    fn rotate_dkim(&self, domain_id: i64) -> EmailDomainFuture<'_, PostmarkDomain> {
+        Box::pin(PostmarkService::rotate_dkim(self, domain_id))
+    }
+}
+
+/// A call made to a [`FakeEmailDomains`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailDomainCall {
+    CreateDomain(String),
+    FindDomain(String),
+    DeleteDomain(i64),
+    RotateDkim(i64),
+}
+
+#[derive(Default)]
+struct FakeEmailDomainsState {
+    domains: Vec<PostmarkDomain>,
+    failures: Vec<(EmailDomainCall, u16)>,
+    calls: Vec<EmailDomainCall>,
+}
+
+/// Keeps sending domains in memory and records every call, for tests. Calls
+/// can be scripted to fail with [`FakeEmailDomains::fail`].
+#[derive(Default)]
+pub struct FakeEmailDomains {
+    state: Mutex<FakeEmailDomainsState>,
+}
+
+impl FakeEmailDomains {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `call` fail with a Postmark API error of the given status,
+    /// every time it is made from now on.
+    pub fn fail(&self, call: EmailDomainCall, status: u16) {
+        self.lock().failures.push((call, status));
+    }
+
+    pub fn domains(&self) -> Vec<PostmarkDomain> {
+        self.lock().domains.clone()
+    }
+
+    /// Every call made so far, in order, including those that failed.
+    pub fn calls(&self) -> Vec<EmailDomainCall> {
+        self.lock().calls.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FakeEmailDomainsState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn call<T>(
+        &self,
+        call: EmailDomainCall,
+        respond: impl FnOnce(&mut FakeEmailDomainsState) -> T,
+    ) -> Result<T, PostmarkError> {
+        let mut state = self.lock();
+        state.calls.push(call.clone());
+
+        if let Some((_, status)) = state.failures.iter().find(|(failing, _)| *failing == call) {
+            return Err(PostmarkError::Api {
+                status: *status,
+                error_code: 0,
+                message: format!("scripted failure of {:?}", call),
             });
         }
 
-        records
+        Ok(respond(&mut state))
     }
+}
 
-    pub fn are_records_verified(&self, records: &EmailVerificationRecords) -> bool {
-        let dkim_verified = records.dkim_records.iter().all(|r| r.verified);
-        let return_path_verified = records.return_path_records.iter().all(|r| r.verified);
+impl EmailDomainApi for FakeEmailDomains {
+    fn create_domain<'a>(&'a self, domain_name: &'a str) -> EmailDomainFuture<'a, PostmarkDomain> {
+        let result = self.call(
+            EmailDomainCall::CreateDomain(domain_name.to_string()),
+            |state| {
+                let domain = PostmarkDomain {
+                    id: state.calls.len() as i64,
+                    name: domain_name.to_string(),
+                    spf_verified: false,
+                    spf_host: String::new(),
+                    spf_text_value: String::new(),
+                    dkim_verified: false,
+                    weak_dkim: false,
+                    dkim_host: String::new(),
+                    dkim_text_value: String::new(),
+                    dkim_pending_host: format!("pm._domainkey.{}", domain_name),
+                    dkim_pending_text_value: "k=rsa;p=fake".to_string(),
+                    dkim_revoked_host: String::new(),
+                    dkim_revoked_text_value: String::new(),
+                    safe_to_remove_revoked_key: false,
+                    dkim_update_status: "Pending".to_string(),
+                    return_path_domain: format!("rp.{}", domain_name),
+                    return_path_domain_verified: false,
+                    return_path_domain_cname_value: "pm.mtasv.net".to_string(),
+                };
+                state.domains.push(domain.clone());
+                domain
+            },
+        );
 
-        dkim_verified && return_path_verified
+        Box::pin(async move { result })
+    }
+
+    fn find_domain<'a>(
+        &'a self,
+        domain_name: &'a str,
+    ) -> EmailDomainFuture<'a, Option<PostmarkDomain>> {
+        let result = self.call(
+            EmailDomainCall::FindDomain(domain_name.to_string()),
+            |state| {
+                state
+                    .domains
+                    .iter()
+                    .find(|domain| domain.name.eq_ignore_ascii_case(domain_name))
+                    .cloned()
+            },
+        );
+
+        Box::pin(async move { result })
+    }
+
+    fn delete_domain(&self, domain_id: i64) -> EmailDomainFuture<'_, ()> {
+        let result = self.call(EmailDomainCall::DeleteDomain(domain_id), |state| {
+            state.domains.retain(|domain| domain.id != domain_id)
+        });
+
+        Box::pin(async move { result })
+    }
+
+    fn rotate_dkim(&self, domain_id: i64) -> EmailDomainFuture<'_, PostmarkDomain> {
+        let result = self.call(EmailDomainCall::RotateDkim(domain_id), |state| {
+            let rotation = state.calls.len();
+            state
+                .domains
+                .iter_mut()
+                .find(|domain| domain.id == domain_id)
+                .map(|domain| {
+                    domain.dkim_pending_host = format!("{}pm._domainkey.{}", rotation, domain.name);
+                    domain.dkim_pending_text_value = format!("k=rsa;p=fake{}", rotation);
+                    domain.dkim_update_status = "Pending".to_string();
+                    domain.clone()
+                })
+        });
+
+        Box::pin(async move {
+            result?.ok_or_else(|| PostmarkError::Api {
+                status: 404,
+                error_code: 510,
+                message: format!("Domain {} not found", domain_id),
+            })
+        })
     }
 }
@@ -1,8 +1,9 @@
 use crate::error::AppError;
 use qdrant_client::qdrant::{
-    Condition, CreateCollectionBuilder, CreateFieldIndexCollectionBuilder, DeletePointsBuilder,
-    Distance, FieldType, Filter, HnswConfigDiff, PointStruct, SearchPointsBuilder,
-    UpsertPointsBuilder, VectorParamsBuilder,
+    Condition, CountPointsBuilder, CreateCollectionBuilder, CreateFieldIndexCollectionBuilder,
+    DeletePointsBuilder, Distance, FieldType, Filter, HnswConfigDiff, PointId, PointStruct,
+    ScrollPointsBuilder, SearchPointsBuilder, UpsertPointsBuilder, VectorParamsBuilder,
+    point_id::PointIdOptions,
 };
 use qdrant_client::{Payload, Qdrant};
 use serde_json::Value;
@@ -10,6 +11,14 @@ use std::collections::HashMap;
 
 pub struct QdrantService;
 
+/// Payload fields every knowledge base collection indexes, for the filters
+/// searches and deletions use.
+const PAYLOAD_INDEXES: [(&str, FieldType); 3] = [
+    ("knowledge_base_id", FieldType::Integer),
+    ("document_id", FieldType::Keyword),
+    ("source", FieldType::Keyword),
+];
+
 #[derive(Debug, Clone)]
 pub struct DocumentChunk {
     pub id: i64,
@@ -18,6 +27,13 @@ pub struct DocumentChunk {
     pub embedding: Vec<f32>,
 }
 
+#[derive(Debug, Clone)]
+pub struct StoredChunk {
+    pub id: i64,
+    pub content: String,
+    pub metadata: HashMap<String, Value>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub id: i64,
@@ -46,6 +62,49 @@ impl QdrantService {
         std::env::var("QDRANT_DEFAULT_COLLECTION").unwrap_or_else(|_| "knowledge_base".to_string())
     }
 
+    /// The collection a knowledge base's vectors go in. Knowledge bases from
+    /// before collections were per knowledge base are still in the default
+    /// collection, until they are re-embedded.
+    pub fn knowledge_base_collection(deployment_id: i64, knowledge_base_id: i64) -> String {
+        format!("kb_{}_{}", deployment_id, knowledge_base_id)
+    }
+
+    /// The collection a re-embedding job writes to before it replaces the
+    /// knowledge base's active one.
+    pub fn reembed_collection(deployment_id: i64, knowledge_base_id: i64, job_id: i64) -> String {
+        format!("kb_{}_{}_{}", deployment_id, knowledge_base_id, job_id)
+    }
+
+    /// Whether the collection holds a single knowledge base's vectors. Also
+    /// true of the `{default}_kb_{id}` collections used before collections
+    /// were named after deployments.
+    pub fn is_knowledge_base_collection(collection_name: &str) -> bool {
+        let numbered = |rest: &str| {
+            rest.split('_')
+                .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+        };
+
+        let legacy_prefix = format!("{}_kb_", Self::default_collection());
+        collection_name
+            .strip_prefix(&legacy_prefix)
+            .or_else(|| collection_name.strip_prefix("kb_"))
+            .is_some_and(numbered)
+    }
+
+    pub async fn list_collections() -> Result<Vec<String>, AppError> {
+        let client = Self::connect().await?;
+        let collections = client
+            .list_collections()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to list collections: {}", e)))?;
+
+        Ok(collections
+            .collections
+            .into_iter()
+            .map(|collection| collection.name)
+            .collect())
+    }
+
     /// Initialize Qdrant collection and indexes - call this on application startup
     pub async fn initialize() -> Result<(), AppError> {
         let client = Self::connect().await?;
@@ -89,33 +148,7 @@ impl QdrantService {
             println!("Qdrant collection already exists: {}", collection_name);
         }
 
-        // Always try to create the index (it's safe to call even if it exists)
-        println!("Creating/verifying integer index on knowledge_base_id field...");
-        match client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                &collection_name,
-                "knowledge_base_id",
-                FieldType::Integer,
-            ))
-            .await
-        {
-            Ok(_) => {
-                println!("✅ Successfully created/verified integer index on knowledge_base_id")
-            }
-            Err(e) => {
-                let error_msg = e.to_string();
-                if error_msg.contains("already exists")
-                    || error_msg.contains("Index already exists")
-                {
-                    println!("✅ Integer index on knowledge_base_id already exists");
-                } else {
-                    return Err(AppError::Internal(format!(
-                        "Failed to create knowledge_base_id index: {}",
-                        e
-                    )));
-                }
-            }
-        }
+        Self::ensure_payload_indexes(&client, &collection_name).await?;
 
         println!(
             "🎉 Qdrant initialization completed successfully for collection: {}",
@@ -148,7 +181,111 @@ impl QdrantService {
         Ok(())
     }
 
+    /// Ensure a dedicated collection exists, creating it with the same tenant-friendly
+    /// settings as the default collection but with the embedding model's vector size
+    /// and distance. Safe to call again after a run that failed half way: whatever
+    /// exists is kept and whatever is missing is created.
+    pub async fn ensure_collection(
+        collection_name: &str,
+        vector_size: u64,
+        distance: Distance,
+    ) -> Result<(), AppError> {
+        if collection_name == Self::default_collection() {
+            return Self::ensure_default_collection().await;
+        }
+
+        let client = Self::connect().await?;
+
+        let collection_exists = client
+            .collection_exists(collection_name)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to check collection: {}", e)))?;
+
+        if !collection_exists {
+            let created = client
+                .create_collection(
+                    CreateCollectionBuilder::new(collection_name)
+                        .vectors_config(VectorParamsBuilder::new(vector_size, distance))
+                        .hnsw_config(HnswConfigDiff {
+                            payload_m: Some(16),
+                            m: Some(0),
+                            ..Default::default()
+                        }),
+                )
+                .await;
+            // Another ingestion into the knowledge base may have created it
+            // since it was checked for
+            if let Err(e) = created
+                && !e.to_string().contains("already exists")
+            {
+                return Err(AppError::Internal(format!(
+                    "Failed to create collection: {}",
+                    e
+                )));
+            }
+        }
+
+        Self::ensure_payload_indexes(&client, collection_name).await
+    }
+
+    /// Creates whichever of the payload indexes the collection doesn't have.
+    async fn ensure_payload_indexes(
+        client: &Qdrant,
+        collection_name: &str,
+    ) -> Result<(), AppError> {
+        let info = client
+            .collection_info(collection_name)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to get collection info: {}", e)))?;
+        let payload_schema = info
+            .result
+            .map(|info| info.payload_schema)
+            .unwrap_or_default();
+
+        for (field, field_type) in PAYLOAD_INDEXES {
+            if payload_schema.contains_key(field) {
+                continue;
+            }
+
+            client
+                .create_field_index(CreateFieldIndexCollectionBuilder::new(
+                    collection_name,
+                    field,
+                    field_type,
+                ))
+                .await
+                .map_err(|e| {
+                    AppError::Internal(format!("Failed to create {} index: {}", field, e))
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the collection. One that doesn't exist counts as deleted.
+    pub async fn delete_collection(collection_name: &str) -> Result<(), AppError> {
+        let client = Self::connect().await?;
+        let collection_exists = client
+            .collection_exists(collection_name)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to check collection: {}", e)))?;
+        if !collection_exists {
+            return Ok(());
+        }
+
+        client
+            .delete_collection(collection_name)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to delete collection: {}", e)))?;
+
+        println!("Deleted Qdrant collection: {}", collection_name);
+        Ok(())
+    }
+
+    /// Writes the chunks to a collection, which [`Self::ensure_collection`]
+    /// has to have created.
     pub async fn upsert_documents(
+        collection_name: &str,
         chunks: Vec<DocumentChunk>,
         knowledge_base_id: i64,
     ) -> Result<(), AppError> {
@@ -156,10 +293,6 @@ impl QdrantService {
             return Ok(());
         }
 
-        Self::ensure_default_collection().await?;
-
-        let collection_name = Self::default_collection();
-
         let points: Vec<PointStruct> = chunks
             .into_iter()
             .map(|chunk| {
@@ -169,15 +302,17 @@ impl QdrantService {
                     serde_json::Value::String(chunk.content),
                 );
 
+                for (key, value) in chunk.metadata {
+                    payload_map.insert(key, value);
+                }
+
+                // Inserted last so chunk metadata can't shadow the integer tenant key
+                // that every filter matches on
                 payload_map.insert(
                     "knowledge_base_id".to_string(),
                     serde_json::Value::Number(knowledge_base_id.into()),
                 );
 
-                for (key, value) in chunk.metadata {
-                    payload_map.insert(key, value);
-                }
-
                 let payload: Payload = serde_json::Value::Object(payload_map).try_into().unwrap();
 
                 PointStruct::new(chunk.id as u64, chunk.embedding, payload)
@@ -187,7 +322,7 @@ impl QdrantService {
         let client = Self::connect().await?;
         let points_count = points.len();
         client
-            .upsert_points(UpsertPointsBuilder::new(&collection_name, points))
+            .upsert_points(UpsertPointsBuilder::new(collection_name, points))
             .await
             .map_err(|e| AppError::Internal(format!("Failed to upsert points: {}", e)))?;
 
@@ -199,18 +334,43 @@ impl QdrantService {
     }
 
     pub async fn search_similar(
+        collection_name: &str,
         query_embedding: Vec<f32>,
         limit: u64,
         knowledge_base_id: i64,
     ) -> Result<Vec<SearchResult>, AppError> {
-        let collection_name = Self::default_collection();
+        Self::search_with_filters(
+            collection_name,
+            query_embedding,
+            limit,
+            knowledge_base_id,
+            None,
+            HashMap::new(),
+        )
+        .await
+    }
 
+    /// Searches a knowledge base, optionally dropping matches below `score_threshold`
+    /// and restricting results to points whose payload matches every metadata filter.
+    pub async fn search_with_filters(
+        collection_name: &str,
+        query_embedding: Vec<f32>,
+        limit: u64,
+        knowledge_base_id: i64,
+        score_threshold: Option<f32>,
+        metadata_filters: HashMap<String, Value>,
+    ) -> Result<Vec<SearchResult>, AppError> {
         let mut search_builder =
-            SearchPointsBuilder::new(&collection_name, query_embedding, limit).with_payload(true);
+            SearchPointsBuilder::new(collection_name, query_embedding, limit).with_payload(true);
 
-        let conditions = vec![Condition::matches("knowledge_base_id", knowledge_base_id)];
+        if let Some(score_threshold) = score_threshold {
+            search_builder = search_builder.score_threshold(score_threshold);
+        }
 
-        let filter = Filter::all(conditions);
+        let filter = Filter::all(Self::metadata_conditions(
+            knowledge_base_id,
+            metadata_filters,
+        ));
         search_builder = search_builder.filter(filter);
 
         let client = Self::connect().await?;
@@ -270,15 +430,195 @@ impl QdrantService {
         Ok(results)
     }
 
+    /// Page through the stored chunks of a knowledge base in point id order. Returns the
+    /// chunks and the offset to pass in for the next page, if any.
+    pub async fn scroll_chunks(
+        collection_name: &str,
+        knowledge_base_id: i64,
+        offset: Option<i64>,
+        limit: u32,
+    ) -> Result<(Vec<StoredChunk>, Option<i64>), AppError> {
+        let mut scroll_builder = ScrollPointsBuilder::new(collection_name)
+            .filter(Filter::all(Self::metadata_conditions(
+                knowledge_base_id,
+                HashMap::new(),
+            )))
+            .limit(limit)
+            .with_payload(true)
+            .with_vectors(false);
+
+        if let Some(offset) = offset {
+            scroll_builder = scroll_builder.offset(offset as u64);
+        }
+
+        let client = Self::connect().await?;
+        let response = client
+            .scroll(scroll_builder)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to scroll points: {}", e)))?;
+
+        let chunks = response
+            .result
+            .into_iter()
+            .map(|point| {
+                let mut content = String::new();
+                let mut metadata = HashMap::new();
+                for (key, value) in point.payload {
+                    if key == "content" {
+                        content = value.as_str().map(|s| s.to_string()).unwrap_or_default();
+                    } else {
+                        metadata.insert(key, value.into_json());
+                    }
+                }
+
+                StoredChunk {
+                    id: point.id.and_then(Self::point_id_to_i64).unwrap_or(0),
+                    content,
+                    metadata,
+                }
+            })
+            .collect();
+
+        let next_offset = response.next_page_offset.and_then(Self::point_id_to_i64);
+
+        Ok((chunks, next_offset))
+    }
+
+    pub async fn count_chunks(
+        collection_name: &str,
+        knowledge_base_id: i64,
+    ) -> Result<u64, AppError> {
+        let client = Self::connect().await?;
+        let response = client
+            .count(
+                CountPointsBuilder::new(collection_name)
+                    .filter(Filter::all(Self::metadata_conditions(
+                        knowledge_base_id,
+                        HashMap::new(),
+                    )))
+                    .exact(true),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to count points: {}", e)))?;
+
+        Ok(response.result.map(|r| r.count).unwrap_or(0))
+    }
+
+    fn point_id_to_i64(point_id: PointId) -> Option<i64> {
+        match point_id.point_id_options {
+            Some(PointIdOptions::Num(num)) => Some(num as i64),
+            Some(PointIdOptions::Uuid(uuid)) => uuid.parse::<i64>().ok(),
+            None => None,
+        }
+    }
+
     pub async fn delete_by_metadata(
+        collection_name: &str,
         knowledge_base_id: i64,
         additional_filters: HashMap<String, Value>,
     ) -> Result<(), AppError> {
-        let collection_name = Self::default_collection();
+        let filter = Filter::all(Self::metadata_conditions(
+            knowledge_base_id,
+            additional_filters,
+        ));
+
+        let client = Self::connect().await?;
+        client
+            .delete_points(DeletePointsBuilder::new(collection_name).points(filter))
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to delete points: {}", e)))?;
+
+        println!(
+            "Deleted points from knowledge base {} in collection '{}'",
+            knowledge_base_id, collection_name
+        );
+        Ok(())
+    }
+
+    /// Deletes a document's chunks and returns how many there were. Chunks
+    /// tagged with `keep_revision` are left alone, so a document can be
+    /// re-chunked by writing the new chunks before deleting the old ones.
+    /// Fails if any of the chunks are still there afterwards.
+    pub async fn delete_document_chunks(
+        collection_name: &str,
+        knowledge_base_id: i64,
+        document_id: i64,
+        keep_revision: Option<i64>,
+    ) -> Result<u64, AppError> {
+        let mut filter = Filter::all(Self::metadata_conditions(
+            knowledge_base_id,
+            HashMap::from([(
+                "document_id".to_string(),
+                Value::String(document_id.to_string()),
+            )]),
+        ));
+        if let Some(revision) = keep_revision {
+            filter.must_not = vec![Condition::matches("revision", revision)];
+        }
+
+        let client = Self::connect().await?;
+        let count = |filter: Filter| {
+            client.count(
+                CountPointsBuilder::new(collection_name)
+                    .filter(filter)
+                    .exact(true),
+            )
+        };
 
-        let mut conditions = vec![Condition::matches("knowledge_base_id", knowledge_base_id)];
+        let before = count(filter.clone())
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to count points: {}", e)))?
+            .result
+            .map(|r| r.count)
+            .unwrap_or(0);
+        if before == 0 {
+            return Ok(0);
+        }
+
+        client
+            .delete_points(
+                DeletePointsBuilder::new(collection_name)
+                    .points(filter.clone())
+                    .wait(true),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to delete points: {}", e)))?;
+
+        let remaining = count(filter)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to count points: {}", e)))?
+            .result
+            .map(|r| r.count)
+            .unwrap_or(0);
+        if remaining > 0 {
+            return Err(AppError::Internal(format!(
+                "{} of {} chunks of document {} are still in collection '{}'",
+                remaining, before, document_id, collection_name
+            )));
+        }
+
+        Ok(before)
+    }
+
+    /// Matches a knowledge base's points. Points written before the tenant key
+    /// was stored as an integer hold it as a string, so both are matched;
+    /// otherwise re-embedding would find none of them and then delete them
+    /// along with the old collection.
+    fn knowledge_base_condition(knowledge_base_id: i64) -> Condition {
+        Filter::any([
+            Condition::matches("knowledge_base_id", knowledge_base_id),
+            Condition::matches("knowledge_base_id", knowledge_base_id.to_string()),
+        ])
+        .into()
+    }
+
+    fn metadata_conditions(
+        knowledge_base_id: i64,
+        filters: HashMap<String, Value>,
+    ) -> Vec<Condition> {
+        let mut conditions = vec![Self::knowledge_base_condition(knowledge_base_id)];
 
-        for (key, value) in additional_filters {
+        for (key, value) in filters {
             let condition = match value {
                 Value::String(s) => Condition::matches(key, s),
                 Value::Number(n) => {
@@ -294,28 +634,51 @@ impl QdrantService {
             conditions.push(condition);
         }
 
-        let filter = Filter::all(conditions);
+        conditions
+    }
 
-        let client = Self::connect().await?;
-        client
-            .delete_points(DeletePointsBuilder::new(&collection_name).points(filter))
-            .await
-            .map_err(|e| AppError::Internal(format!("Failed to delete points: {}", e)))?;
+    pub async fn delete_knowledge_base(
+        collection_name: &str,
+        knowledge_base_id: i64,
+    ) -> Result<(), AppError> {
+        Self::delete_by_metadata(collection_name, knowledge_base_id, HashMap::new()).await?;
 
         println!(
-            "Deleted points from knowledge base {} in collection '{}'",
-            knowledge_base_id, collection_name
+            "Deleted all documents for knowledge base {}",
+            knowledge_base_id
         );
         Ok(())
     }
+}
 
-    pub async fn delete_knowledge_base(knowledge_base_id: i64) -> Result<(), AppError> {
-        Self::delete_by_metadata(knowledge_base_id, HashMap::new()).await?;
+#[cfg(test)]
+mod tests {
+    use qdrant_client::qdrant::{condition::ConditionOneOf, r#match::MatchValue};
 
-        println!(
-            "Deleted all documents for knowledge base {}",
-            knowledge_base_id
+    use super::*;
+
+    #[test]
+    fn knowledge_base_ids_stored_as_strings_still_match() {
+        let conditions = QdrantService::metadata_conditions(42, HashMap::new());
+
+        let Some(ConditionOneOf::Filter(filter)) = &conditions[0].condition_one_of else {
+            panic!("expected a nested filter");
+        };
+        let matched: Vec<MatchValue> = filter
+            .should
+            .iter()
+            .filter_map(|condition| match &condition.condition_one_of {
+                Some(ConditionOneOf::Field(field)) => field.r#match.clone()?.match_value,
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            matched,
+            vec![
+                MatchValue::Integer(42),
+                MatchValue::Keyword("42".to_string())
+            ]
         );
-        Ok(())
     }
 }
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use redis::Script;
+
+use crate::{
+    error::AppError,
+    models::{DeploymentRateLimit, EndpointClass, RateLimit, RateLimitDecision},
+    services::RedisPool,
+};
+
+const OVERRIDE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+type OverrideCache = HashMap<i64, (Instant, Vec<DeploymentRateLimit>)>;
+
+// Refills the bucket for the time elapsed since the last request, then takes one
+// token if there is one. Returns {allowed, retry_after_ms, remaining}.
+static TOKEN_BUCKET_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+        local capacity = tonumber(ARGV[1])
+        local refill_per_ms = tonumber(ARGV[2])
+        local now = tonumber(ARGV[3])
+
+        local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+        local tokens = tonumber(state[1]) or capacity
+        local ts = tonumber(state[2]) or now
+
+        tokens = math.min(capacity, tokens + math.max(0, now - ts) * refill_per_ms)
+
+        local allowed = 0
+        local retry_after_ms = 0
+        if tokens >= 1 then
+            tokens = tokens - 1
+            allowed = 1
+        else
+            retry_after_ms = math.ceil((1 - tokens) / refill_per_ms)
+        end
+
+        redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+        redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / refill_per_ms) + 1000)
+
+        return {allowed, retry_after_ms, math.floor(tokens)}
+        "#,
+    )
+});
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub read: RateLimit,
+    pub write: RateLimit,
+    pub expensive: RateLimit,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            read: RateLimit {
+                requests_per_minute: 600,
+                burst: 100,
+            },
+            write: RateLimit {
+                requests_per_minute: 120,
+                burst: 30,
+            },
+            expensive: RateLimit {
+                requests_per_minute: 30,
+                burst: 10,
+            },
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let read = |key: &str| std::env::var(key).ok().and_then(|v| v.parse::<u32>().ok());
+        let limit = |class: &str, default: RateLimit| RateLimit {
+            requests_per_minute: read(&format!("RATE_LIMIT_{}_PER_MINUTE", class))
+                .unwrap_or(default.requests_per_minute),
+            burst: read(&format!("RATE_LIMIT_{}_BURST", class)).unwrap_or(default.burst),
+        };
+
+        Self {
+            enabled: std::env::var("RATE_LIMIT_ENABLED")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(defaults.enabled),
+            read: limit("READ", defaults.read),
+            write: limit("WRITE", defaults.write),
+            expensive: limit("EXPENSIVE", defaults.expensive),
+        }
+    }
+
+    pub fn default_for(&self, class: EndpointClass) -> RateLimit {
+        match class {
+            EndpointClass::Read => self.read,
+            EndpointClass::Write => self.write,
+            EndpointClass::Expensive => self.expensive,
+        }
+    }
+}
+
+/// Redis-backed token buckets keyed by deployment and endpoint class. Deployment
+/// overrides are cached in memory briefly so the limiter doesn't add a database
+/// query to every request.
+#[derive(Clone)]
+pub struct RateLimitService {
+    redis: RedisPool,
+    config: RateLimitConfig,
+    overrides: Arc<RwLock<OverrideCache>>,
+}
+
+impl RateLimitService {
+    pub fn new(redis: RedisPool, config: RateLimitConfig) -> Self {
+        Self {
+            redis,
+            config,
+            overrides: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn cached_overrides(&self, deployment_id: i64) -> Option<Vec<DeploymentRateLimit>> {
+        let overrides = self.overrides.read().ok()?;
+        overrides
+            .get(&deployment_id)
+            .filter(|(cached_at, _)| cached_at.elapsed() < OVERRIDE_CACHE_TTL)
+            .map(|(_, limits)| limits.clone())
+    }
+
+    pub fn cache_overrides(&self, deployment_id: i64, limits: Vec<DeploymentRateLimit>) {
+        if let Ok(mut overrides) = self.overrides.write() {
+            overrides.retain(|_, (cached_at, _)| cached_at.elapsed() < OVERRIDE_CACHE_TTL);
+            overrides.insert(deployment_id, (Instant::now(), limits));
+        }
+    }
+
+    pub fn resolve_limit(
+        &self,
+        class: EndpointClass,
+        overrides: &[DeploymentRateLimit],
+    ) -> RateLimit {
+        overrides
+            .iter()
+            .find(|o| o.endpoint_class == class)
+            .map(|o| o.limit)
+            .unwrap_or_else(|| self.config.default_for(class))
+    }
+
+    pub async fn take_token(
+        &self,
+        deployment_id: i64,
+        class: EndpointClass,
+        limit: RateLimit,
+    ) -> Result<RateLimitDecision, AppError> {
+        let mut connection = self.redis.connection().await?;
+
+        let capacity = limit.burst.max(1);
+        let refill_per_ms = limit.requests_per_minute.max(1) as f64 / 60_000.0;
+        let now_ms = chrono::Utc::now().timestamp_millis();
+
+        let (allowed, retry_after_ms, remaining): (i64, i64, i64) = TOKEN_BUCKET_SCRIPT
+            .key(format!("rate_limit:{}:{}", deployment_id, class.as_str()))
+            .arg(capacity)
+            .arg(refill_per_ms)
+            .arg(now_ms)
+            .invoke_async(&mut connection)
+            .await?;
+
+        let allowed = allowed == 1;
+
+        Ok(RateLimitDecision {
+            allowed,
+            limit: limit.requests_per_minute,
+            remaining: remaining.max(0) as u32,
+            retry_after_secs: if allowed {
+                0
+            } else {
+                (retry_after_ms.max(0) as u64).div_ceil(1000).max(1)
+            },
+        })
+    }
+}
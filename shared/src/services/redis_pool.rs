@@ -0,0 +1,280 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use redis::{
+    AsyncCommands, Client as RedisClient, Cmd, FromRedisValue, Pipeline, RedisError, RedisFuture,
+    Value,
+    aio::{ConnectionLike, MultiplexedConnection},
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::error::AppError;
+
+#[derive(Debug, Clone)]
+pub struct RedisConfig {
+    pub connect_timeout: Duration,
+    /// How long a command waits for its reply before failing, so a slow
+    /// Redis fails requests instead of holding them.
+    pub response_timeout: Duration,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(2),
+            response_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RedisConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let millis = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_millis)
+        };
+
+        Self {
+            connect_timeout: millis("REDIS_CONNECT_TIMEOUT_MS").unwrap_or(defaults.connect_timeout),
+            response_timeout: millis("REDIS_RESPONSE_TIMEOUT_MS")
+                .unwrap_or(defaults.response_timeout),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SharedConnection {
+    /// Bumped on every reconnect, so a failure on an old connection doesn't
+    /// drop its replacement.
+    generation: u64,
+    connection: Option<MultiplexedConnection>,
+}
+
+/// The one Redis connection an instance shares between every request and
+/// background task. Commands are multiplexed over it, so handing it out is
+/// a clone rather than a handshake.
+///
+/// The connection is made on first use. One that breaks is dropped, and the
+/// next caller reconnects.
+#[derive(Clone)]
+pub struct RedisPool {
+    client: RedisClient,
+    config: RedisConfig,
+    shared: Arc<Mutex<SharedConnection>>,
+    /// Held while connecting, so callers arriving at the same time wait for
+    /// one connection instead of each opening their own.
+    connecting: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl RedisPool {
+    pub fn new(client: RedisClient, config: RedisConfig) -> Self {
+        Self {
+            client,
+            config,
+            shared: Arc::new(Mutex::new(SharedConnection::default())),
+            connecting: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    fn current(&self) -> Option<RedisConnection> {
+        let shared = self.shared.lock().ok()?;
+        shared.connection.clone().map(|connection| RedisConnection {
+            connection,
+            generation: shared.generation,
+            pool: self.clone(),
+        })
+    }
+
+    /// A handle on the shared connection, for pipelines, scripts and other
+    /// commands the helpers below don't cover.
+    pub async fn connection(&self) -> Result<RedisConnection, AppError> {
+        if let Some(connection) = self.current() {
+            return Ok(connection);
+        }
+
+        let _connecting = self.connecting.lock().await;
+        if let Some(connection) = self.current() {
+            return Ok(connection);
+        }
+
+        let config = redis::AsyncConnectionConfig::new()
+            .set_connection_timeout(self.config.connect_timeout)
+            .set_response_timeout(self.config.response_timeout);
+        let connection = self
+            .client
+            .get_multiplexed_async_connection_with_config(&config)
+            .await?;
+
+        let mut shared = self
+            .shared
+            .lock()
+            .map_err(|_| AppError::Internal("Redis connection lock poisoned".to_string()))?;
+        shared.generation += 1;
+        shared.connection = Some(connection.clone());
+
+        Ok(RedisConnection {
+            connection,
+            generation: shared.generation,
+            pool: self.clone(),
+        })
+    }
+
+    fn discard(&self, generation: u64) {
+        if let Ok(mut shared) = self.shared.lock()
+            && shared.generation == generation
+            && shared.connection.take().is_some()
+        {
+            tracing::warn!("Redis connection lost, reconnecting on next use");
+        }
+    }
+
+    /// Increments a counter, setting it to expire after `ttl` when given.
+    pub async fn incr_counter(
+        &self,
+        key: &str,
+        by: i64,
+        ttl: Option<Duration>,
+    ) -> Result<i64, AppError> {
+        let mut connection = self.connection().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic().incr(key, by);
+        if let Some(ttl) = ttl {
+            pipe.expire(key, ttl.as_secs().max(1) as i64).ignore();
+        }
+
+        let (count,): (i64,) = pipe.query_async(&mut connection).await?;
+        Ok(count)
+    }
+
+    /// Reads a value written by [`RedisPool::set_json_with_ttl`]. A value
+    /// that no longer parses is a serialization error, which callers using
+    /// Redis as a cache can treat as a miss.
+    pub async fn get_json<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, AppError> {
+        let mut connection = self.connection().await?;
+        let value: Option<String> = connection.get(key).await?;
+
+        value
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    pub async fn set_json_with_ttl<T: Serialize + ?Sized>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+    ) -> Result<(), AppError> {
+        let value = serde_json::to_string(value)?;
+        let mut connection = self.connection().await?;
+        connection
+            .set_ex::<_, _, ()>(key, value, ttl.as_secs().max(1))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get<T: FromRedisValue>(&self, key: &str) -> Result<T, AppError> {
+        let mut connection = self.connection().await?;
+        Ok(connection.get(key).await?)
+    }
+
+    pub async fn delete(&self, keys: &[String]) -> Result<(), AppError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let mut connection = self.connection().await?;
+        connection.del::<_, ()>(keys).await?;
+        Ok(())
+    }
+
+    /// Answers the readiness check.
+    pub async fn ping(&self) -> Result<(), AppError> {
+        let mut connection = self.connection().await?;
+        redis::cmd("PING")
+            .query_async::<String>(&mut connection)
+            .await?;
+        Ok(())
+    }
+}
+
+/// A clone of the shared connection. Usable anywhere a Redis connection is,
+/// and drops the shared connection when a command finds it broken.
+#[derive(Clone)]
+pub struct RedisConnection {
+    connection: MultiplexedConnection,
+    generation: u64,
+    pool: RedisPool,
+}
+
+impl RedisConnection {
+    fn check<T>(&self, result: Result<T, RedisError>) -> Result<T, RedisError> {
+        if let Err(e) = &result
+            && is_broken(e)
+        {
+            self.pool.discard(self.generation);
+        }
+        result
+    }
+}
+
+/// Errors after which the connection can't be used again. Timeouts count,
+/// as a reply that arrives late would be read as the next command's.
+fn is_broken(error: &RedisError) -> bool {
+    error.is_unrecoverable_error() || error.is_connection_dropped() || error.is_timeout()
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let result = self.connection.req_packed_command(cmd).await;
+            self.check(result)
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let result = self
+                .connection
+                .req_packed_commands(cmd, offset, count)
+                .await;
+            self.check(result)
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.connection.get_db()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+
+    #[tokio::test]
+    async fn an_unreachable_redis_is_a_distinct_external_error() {
+        let pool = RedisPool::new(
+            RedisClient::open("redis://127.0.0.1:1/").unwrap(),
+            RedisConfig {
+                connect_timeout: Duration::from_millis(200),
+                response_timeout: Duration::from_millis(200),
+            },
+        );
+        let error = pool.ping().await.unwrap_err();
+
+        assert!(matches!(
+            &error,
+            AppError::External(e) if e.provider == Some("redis")
+        ));
+        assert_eq!(error.code(), ErrorCode::RedisUnavailable);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+
+use sqlx::{PgPool, migrate::Migrator};
+
+use crate::{error::AppError, models::DatabaseSchema};
+
+#[derive(Debug, Clone, Default)]
+pub struct SchemaConfig {
+    /// Apply pending migrations at startup instead of only checking for them.
+    pub run_migrations: bool,
+}
+
+impl SchemaConfig {
+    pub fn from_env() -> Self {
+        Self {
+            run_migrations: std::env::var("RUN_MIGRATIONS")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// The migrations compiled into this build.
+///
+/// Migrations applied by a newer build are tolerated, so an old pod can
+/// still start in the middle of a rolling deploy.
+fn migrator() -> Migrator {
+    let mut migrator = sqlx::migrate!("./migrations");
+    migrator.set_ignore_missing(true);
+    migrator
+}
+
+/// The newest migration this build knows about.
+pub fn expected_schema_version() -> i64 {
+    migrator()
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .map(|m| m.version)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The newest migration applied to the database, or `None` if sqlx has never
+/// migrated it.
+pub async fn applied_schema_version(pool: &PgPool) -> Result<Option<i64>, AppError> {
+    if !migrations_table_exists(pool).await? {
+        return Ok(None);
+    }
+
+    Ok(
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = true")
+            .fetch_one(pool)
+            .await?,
+    )
+}
+
+/// Runs before anything else touches the database. With `RUN_MIGRATIONS`
+/// set, pending migrations are applied (sqlx holds an advisory lock while
+/// doing so, so pods starting together don't race). Otherwise startup fails
+/// if any migration in this build hasn't been applied, rather than failing
+/// later on the first query that needs it.
+pub async fn ensure_schema(
+    pool: &PgPool,
+    config: &SchemaConfig,
+) -> Result<DatabaseSchema, AppError> {
+    let migrator = migrator();
+
+    if config.run_migrations {
+        migrator
+            .run(pool)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to run migrations: {}", e)))?;
+    } else {
+        let applied: HashSet<i64> = if migrations_table_exists(pool).await? {
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = true")
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        let missing: Vec<String> = migrator
+            .iter()
+            .filter(|m| m.migration_type.is_up_migration() && !applied.contains(&m.version))
+            .map(|m| format!("{} ({})", m.version, m.description))
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(AppError::Internal(format!(
+                "Database is missing {} migration(s): {}. Apply them or start with RUN_MIGRATIONS=true",
+                missing.len(),
+                missing.join(", ")
+            )));
+        }
+    }
+
+    Ok(DatabaseSchema {
+        applied: applied_schema_version(pool).await?,
+        expected: expected_schema_version(),
+    })
+}
+
+async fn migrations_table_exists(pool: &PgPool) -> Result<bool, AppError> {
+    Ok(
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(pool)
+            .await?,
+    )
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{AppError, ExternalError},
+    models::SesCredentials,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SERVICE: &str = "ses";
+
+#[derive(Debug, Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageId")]
+    message_id: String,
+}
+
+/// Sends through a customer's Amazon SES account with the SES v2 API. There's
+/// no SES SDK in the tree, so requests are signed with SigV4 here.
+#[derive(Clone)]
+pub struct SesService {
+    client: reqwest::Client,
+    credentials: SesCredentials,
+}
+
+impl SesService {
+    pub fn new(credentials: SesCredentials) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            credentials,
+        }
+    }
+
+    fn host(&self) -> String {
+        format!("email.{}.amazonaws.com", self.credentials.region)
+    }
+
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, AppError> {
+        let host = self.host();
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let signed_headers = "content-type;host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\ncontent-type:application/json\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!(
+            "{}/{}/{}/aws4_request",
+            date, self.credentials.region, SERVICE
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut key = hmac(
+            format!("AWS4{}", self.credentials.secret_access_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [self.credentials.region.as_str(), SERVICE, "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+
+        let response = self
+            .client
+            .request(method, format!("https://{}{}", host, path))
+            .header("Content-Type", "application/json")
+            .header("X-Amz-Content-Sha256", payload_hash)
+            .header("X-Amz-Date", amz_date)
+            .header(
+                "Authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.credentials.access_key_id, scope, signed_headers, signature
+                ),
+            )
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| AppError::External(format!("Failed to reach Amazon SES: {}", e).into()))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(AppError::External(ExternalError::new(
+                "ses",
+                Some(status.as_u16()),
+                format!("Amazon SES API error ({}): {}", status, error_text),
+            )));
+        }
+
+        Ok(response)
+    }
+
+    /// Checks the credentials by reading the account they belong to.
+    pub async fn verify_credentials(&self) -> Result<(), AppError> {
+        self.request(reqwest::Method::GET, "/v2/email/account", Vec::new())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn send_email(
+        &self,
+        from: &str,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        text_body: Option<&str>,
+    ) -> Result<String, AppError> {
+        let mut body = json!({
+            "Html": { "Data": html_body, "Charset": "UTF-8" },
+        });
+        if let Some(text_body) = text_body {
+            body["Text"] = json!({ "Data": text_body, "Charset": "UTF-8" });
+        }
+
+        let request = json!({
+            "FromEmailAddress": from,
+            "Destination": { "ToAddresses": [to] },
+            "Content": {
+                "Simple": {
+                    "Subject": { "Data": subject, "Charset": "UTF-8" },
+                    "Body": body,
+                },
+            },
+        });
+
+        let response: SendEmailResponse = self
+            .request(
+                reqwest::Method::POST,
+                "/v2/email/outbound-emails",
+                serde_json::to_vec(&request)?,
+            )
+            .await?
+            .json()
+            .await
+            .map_err(|e| {
+                AppError::External(format!("Failed to parse SES response: {}", e).into())
+            })?;
+
+        Ok(response.message_id)
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
@@ -0,0 +1,176 @@
+//! User sign-ins, as the backend sees them.
+//!
+//! The frontend API caches session state in Redis and reads it on every
+//! request that presents a session token. Both sides build the key with
+//! [`SessionRepository::cache_key`], which is the contract between them: a
+//! revocation here drops that key, so the frontend API's next lookup misses
+//! and reads the revoked state from Postgres.
+
+use sqlx::{PgPool, Row};
+
+use crate::{error::AppError, models::UserSession, services::RedisPool};
+
+/// Prefix of the Redis keys session state is cached under, followed by the
+/// id of the `sessions` row.
+pub const SESSION_CACHE_KEY_PREFIX: &str = "session:";
+
+/// Which of a user's active sign-ins a revocation applies to.
+pub enum SessionRevocation {
+    One(i64),
+    All,
+    /// Every sign-in but the given one, which is the caller's own.
+    AllExcept(i64),
+}
+
+/// Reads and revokes user sign-ins. Every change to the `signins` rows also
+/// drops the affected sessions from the cache.
+pub struct SessionRepository<'a> {
+    db_pool: &'a PgPool,
+    redis: &'a RedisPool,
+}
+
+impl<'a> SessionRepository<'a> {
+    pub fn new(db_pool: &'a PgPool, redis: &'a RedisPool) -> Self {
+        Self { db_pool, redis }
+    }
+
+    pub fn cache_key(session_id: i64) -> String {
+        format!("{}{}", SESSION_CACHE_KEY_PREFIX, session_id)
+    }
+
+    /// Active sign-ins, most recently active first. `current_signin_id` is the
+    /// caller's own sign-in, which is flagged as current.
+    pub async fn list_active(
+        &self,
+        deployment_id: i64,
+        user_id: i64,
+        current_signin_id: Option<i64>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UserSession>, AppError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                s.id, s.session_id, s.user_agent, s.browser, s.device,
+                s.ip_address::text AS ip_address, s.created_at,
+                COALESCE(s.last_active_at, s.updated_at) AS last_active_at,
+                COALESCE(s.id = $5, false) AS current
+            FROM signins s
+            JOIN users u ON u.id = s.user_id
+            WHERE u.deployment_id = $1 AND s.user_id = $2 AND NOT s.expired
+            ORDER BY last_active_at DESC, s.id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+        )
+        .bind(deployment_id)
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .bind(current_signin_id)
+        .fetch_all(self.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| UserSession {
+                id: row.get("id"),
+                session_id: row.get("session_id"),
+                user_agent: row.get("user_agent"),
+                browser: row.get("browser"),
+                device: row.get("device"),
+                ip_address: row.get("ip_address"),
+                created_at: row.get("created_at"),
+                last_active_at: row.get("last_active_at"),
+                current: row.get("current"),
+            })
+            .collect())
+    }
+
+    /// Expires the matching sign-ins, detaches them from their sessions and
+    /// drops the affected sessions from the cache. Returns the number of
+    /// sign-ins revoked.
+    pub async fn revoke(
+        &self,
+        deployment_id: i64,
+        user_id: i64,
+        revocation: SessionRevocation,
+    ) -> Result<usize, AppError> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let (signin_id, kept_signin_id) = match revocation {
+            SessionRevocation::One(signin_id) => (Some(signin_id), None),
+            SessionRevocation::All => (None, None),
+            SessionRevocation::AllExcept(kept_signin_id) => (None, Some(kept_signin_id)),
+        };
+
+        let rows = sqlx::query(
+            r#"
+            UPDATE signins
+            SET expired = true, expired_at = NOW(), updated_at = NOW()
+            FROM users u
+            WHERE u.id = signins.user_id
+                AND u.deployment_id = $1 AND signins.user_id = $2 AND NOT signins.expired
+                AND ($3::bigint IS NULL OR signins.id = $3)
+                AND ($4::bigint IS NULL OR signins.id <> $4)
+            RETURNING signins.id, signins.session_id
+            "#,
+        )
+        .bind(deployment_id)
+        .bind(user_id)
+        .bind(signin_id)
+        .bind(kept_signin_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if let Some(signin_id) = signin_id
+            && rows.is_empty()
+        {
+            return Err(AppError::NotFound(format!(
+                "Session {} not found or already revoked",
+                signin_id
+            )));
+        }
+
+        let signin_ids: Vec<i64> = rows.iter().map(|row| row.get("id")).collect();
+        let mut session_ids: Vec<i64> = rows.iter().map(|row| row.get("session_id")).collect();
+        session_ids.sort_unstable();
+        session_ids.dedup();
+
+        sqlx::query(
+            "UPDATE sessions SET active_signin_id = NULL, updated_at = NOW() WHERE active_signin_id = ANY($1)",
+        )
+        .bind(&signin_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let keys: Vec<String> = session_ids.into_iter().map(Self::cache_key).collect();
+        self.redis.delete(&keys).await?;
+
+        Ok(signin_ids.len())
+    }
+
+    /// Drops the cached state of a user's active sessions without signing
+    /// them out, so the frontend API reloads it, and whatever changed about
+    /// the user, on the next request. Returns the number of sessions.
+    pub async fn refresh(&self, deployment_id: i64, user_id: i64) -> Result<usize, AppError> {
+        let session_ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT s.session_id
+            FROM signins s
+            JOIN users u ON u.id = s.user_id
+            WHERE u.deployment_id = $1 AND s.user_id = $2 AND NOT s.expired
+            "#,
+        )
+        .bind(deployment_id)
+        .bind(user_id)
+        .fetch_all(self.db_pool)
+        .await?;
+
+        let keys: Vec<String> = session_ids.iter().copied().map(Self::cache_key).collect();
+        self.redis.delete(&keys).await?;
+
+        Ok(session_ids.len())
+    }
+}
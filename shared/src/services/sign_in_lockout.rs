@@ -0,0 +1,341 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use chrono::Duration;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::AppError,
+    models::{LockoutPolicy, SignInLockoutStatus},
+    services::{ExpiringStore, RedisExpiringStore, RedisPool},
+    utils::clock::{Clock, SystemClock},
+};
+
+/// Counts failed sign-ins per deployment, identifier and IP, and locks the
+/// combination out once a deployment's `LockoutPolicy` threshold is hit.
+/// Identifiers are hashed into the keys so no emails or phone numbers end up
+/// in Redis.
+#[derive(Clone)]
+pub struct SignInLockoutService {
+    store: Arc<dyn ExpiringStore>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SignInLockoutService {
+    pub fn new(store: Arc<dyn ExpiringStore>, clock: Arc<dyn Clock>) -> Self {
+        Self { store, clock }
+    }
+
+    pub fn from_redis(redis: RedisPool) -> Self {
+        Self::new(
+            Arc::new(RedisExpiringStore::new(redis)),
+            Arc::new(SystemClock),
+        )
+    }
+
+    fn identifier_prefix(kind: &str, deployment_id: i64, identifier: &str) -> String {
+        let identifier = identifier.trim().to_lowercase();
+        let hash = hex::encode(Sha256::digest(identifier.as_bytes()));
+        format!("signin_{}:{}:{}:", kind, deployment_id, &hash[..32])
+    }
+
+    fn failures_key(deployment_id: i64, identifier: &str, ip: IpAddr) -> String {
+        format!(
+            "{}{}",
+            Self::identifier_prefix("failures", deployment_id, identifier),
+            ip
+        )
+    }
+
+    fn lockout_key(deployment_id: i64, identifier: &str, ip: IpAddr) -> String {
+        format!(
+            "{}{}",
+            Self::identifier_prefix("lockout", deployment_id, identifier),
+            ip
+        )
+    }
+
+    fn locked_status(&self, locked_until: i64) -> Option<SignInLockoutStatus> {
+        let remaining = locked_until - self.clock.now().timestamp();
+        (remaining > 0).then_some(SignInLockoutStatus {
+            locked: true,
+            retry_after_seconds: Some(remaining),
+            remaining_attempts: Some(0),
+        })
+    }
+
+    pub async fn status(
+        &self,
+        deployment_id: i64,
+        identifier: &str,
+        ip: IpAddr,
+        policy: &LockoutPolicy,
+    ) -> Result<SignInLockoutStatus, AppError> {
+        if !policy.enabled {
+            return Ok(SignInLockoutStatus::unrestricted());
+        }
+
+        let lockout_key = Self::lockout_key(deployment_id, identifier, ip);
+        if let Some(status) = self
+            .store
+            .get(&lockout_key)
+            .await?
+            .and_then(|locked_until| self.locked_status(locked_until))
+        {
+            return Ok(status);
+        }
+
+        let failures_key = Self::failures_key(deployment_id, identifier, ip);
+        let failures = self.store.get(&failures_key).await?.unwrap_or(0);
+
+        Ok(SignInLockoutStatus {
+            locked: false,
+            retry_after_seconds: None,
+            remaining_attempts: Some((policy.max_failed_attempts - failures).max(0)),
+        })
+    }
+
+    /// Counts a failed attempt and starts a lockout when it's the last one
+    /// allowed. Attempts made while locked out don't extend the lockout.
+    pub async fn record_failure(
+        &self,
+        deployment_id: i64,
+        identifier: &str,
+        ip: IpAddr,
+        policy: &LockoutPolicy,
+    ) -> Result<SignInLockoutStatus, AppError> {
+        let status = self.status(deployment_id, identifier, ip, policy).await?;
+        if !policy.enabled || status.locked {
+            return Ok(status);
+        }
+
+        let failures_key = Self::failures_key(deployment_id, identifier, ip);
+        let failures = self
+            .store
+            .increment(&failures_key, Duration::seconds(policy.counter_window))
+            .await?;
+
+        if failures < policy.max_failed_attempts {
+            return Ok(SignInLockoutStatus {
+                locked: false,
+                retry_after_seconds: None,
+                remaining_attempts: Some(policy.max_failed_attempts - failures),
+            });
+        }
+
+        let lockout_duration = Duration::seconds(policy.lockout_duration);
+        let locked_until = (self.clock.now() + lockout_duration).timestamp();
+        self.store
+            .set(
+                &Self::lockout_key(deployment_id, identifier, ip),
+                locked_until,
+                lockout_duration,
+            )
+            .await?;
+        self.store.delete(&[failures_key]).await?;
+
+        Ok(SignInLockoutStatus {
+            locked: true,
+            retry_after_seconds: Some(policy.lockout_duration),
+            remaining_attempts: Some(0),
+        })
+    }
+
+    pub async fn record_success(
+        &self,
+        deployment_id: i64,
+        identifier: &str,
+        ip: IpAddr,
+    ) -> Result<(), AppError> {
+        self.store
+            .delete(&[
+                Self::failures_key(deployment_id, identifier, ip),
+                Self::lockout_key(deployment_id, identifier, ip),
+            ])
+            .await
+    }
+
+    /// Clears failed attempts and lockouts for an identifier from every IP.
+    pub async fn unlock(&self, deployment_id: i64, identifier: &str) -> Result<(), AppError> {
+        for kind in ["failures", "lockout"] {
+            self.store
+                .delete_prefix(&Self::identifier_prefix(kind, deployment_id, identifier))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::MemoryExpiringStore;
+    use crate::utils::clock::FakeClock;
+
+    fn service(clock: &Arc<FakeClock>) -> SignInLockoutService {
+        let (store, clock) = MemoryExpiringStore::with_fake_clock(clock);
+        SignInLockoutService::new(store, clock)
+    }
+
+    fn policy() -> LockoutPolicy {
+        LockoutPolicy {
+            enabled: true,
+            max_failed_attempts: 3,
+            lockout_duration: 600,
+            counter_window: 300,
+        }
+    }
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 7));
+    const OTHER_IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 8));
+
+    async fn fail(service: &SignInLockoutService, times: usize) -> SignInLockoutStatus {
+        let mut status = SignInLockoutStatus::unrestricted();
+        for _ in 0..times {
+            status = service
+                .record_failure(1, "user@example.com", IP, &policy())
+                .await
+                .unwrap();
+        }
+        status
+    }
+
+    #[tokio::test]
+    async fn locks_out_after_max_failed_attempts() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        let status = fail(&service, 2).await;
+        assert!(!status.locked);
+        assert_eq!(status.remaining_attempts, Some(1));
+
+        let status = fail(&service, 1).await;
+        assert!(status.locked);
+        assert_eq!(status.retry_after_seconds, Some(600));
+
+        clock.advance(250);
+        let status = service
+            .status(1, "User@Example.com ", IP, &policy())
+            .await
+            .unwrap();
+        assert!(status.locked);
+        assert_eq!(status.retry_after_seconds, Some(350));
+    }
+
+    #[tokio::test]
+    async fn lockout_expires_after_its_duration() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        fail(&service, 3).await;
+        clock.advance(600);
+
+        let status = service
+            .status(1, "user@example.com", IP, &policy())
+            .await
+            .unwrap();
+        assert!(!status.locked);
+        assert_eq!(status.remaining_attempts, Some(3));
+    }
+
+    #[tokio::test]
+    async fn failures_outside_the_counter_window_are_forgotten() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        fail(&service, 2).await;
+        clock.advance(301);
+
+        let status = fail(&service, 1).await;
+        assert!(!status.locked);
+        assert_eq!(status.remaining_attempts, Some(2));
+    }
+
+    #[tokio::test]
+    async fn failures_while_locked_do_not_extend_the_lockout() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        fail(&service, 3).await;
+        clock.advance(100);
+
+        let status = fail(&service, 1).await;
+        assert!(status.locked);
+        assert_eq!(status.retry_after_seconds, Some(500));
+    }
+
+    #[tokio::test]
+    async fn successful_sign_in_clears_the_counter() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        fail(&service, 2).await;
+        service
+            .record_success(1, "user@example.com", IP)
+            .await
+            .unwrap();
+
+        let status = fail(&service, 2).await;
+        assert!(!status.locked);
+        assert_eq!(status.remaining_attempts, Some(1));
+    }
+
+    #[tokio::test]
+    async fn counters_are_scoped_to_deployment_and_ip() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        fail(&service, 3).await;
+
+        for (deployment_id, ip) in [(1, OTHER_IP), (2, IP)] {
+            let status = service
+                .status(deployment_id, "user@example.com", ip, &policy())
+                .await
+                .unwrap();
+            assert!(!status.locked);
+        }
+    }
+
+    #[tokio::test]
+    async fn unlock_clears_lockouts_from_every_ip() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+
+        fail(&service, 3).await;
+        for _ in 0..3 {
+            service
+                .record_failure(1, "user@example.com", OTHER_IP, &policy())
+                .await
+                .unwrap();
+        }
+
+        service.unlock(1, "user@example.com").await.unwrap();
+
+        for ip in [IP, OTHER_IP] {
+            let status = service
+                .status(1, "user@example.com", ip, &policy())
+                .await
+                .unwrap();
+            assert!(!status.locked);
+            assert_eq!(status.remaining_attempts, Some(3));
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_policy_never_locks() {
+        let clock = FakeClock::new();
+        let service = service(&clock);
+        let policy = LockoutPolicy {
+            enabled: false,
+            ..policy()
+        };
+
+        for _ in 0..10 {
+            let status = service
+                .record_failure(1, "user@example.com", IP, &policy)
+                .await
+                .unwrap();
+            assert_eq!(status, SignInLockoutStatus::unrestricted());
+        }
+    }
+}
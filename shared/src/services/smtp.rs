@@ -0,0 +1,356 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Utc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout,
+};
+use tokio_native_tls::{TlsConnector, native_tls};
+
+use crate::{
+    error::{AppError, ExternalError},
+    models::{SmtpCredentials, SmtpSecurity},
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+/// Most servers drop idle clients after a few minutes; a connection is
+/// retired well before that.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_IDLE_PER_SERVER: usize = 4;
+const HELO_NAME: &str = "wacht.services";
+
+trait SmtpStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> SmtpStream for T {}
+
+struct SmtpConnection {
+    stream: BufReader<Box<dyn SmtpStream>>,
+    idle_since: Instant,
+}
+
+impl SmtpConnection {
+    /// Connects, upgrades to TLS and authenticates. Certificates are always
+    /// verified, and the password is never sent over a plain connection.
+    async fn open(credentials: &SmtpCredentials) -> Result<Self, AppError> {
+        let tcp = timeout(
+            CONNECT_TIMEOUT,
+            TcpStream::connect((credentials.host.as_str(), credentials.port)),
+        )
+        .await
+        .map_err(|_| smtp_error(format!("Timed out connecting to {}", credentials.host)))?
+        .map_err(|e| smtp_error(format!("Failed to connect to {}: {}", credentials.host, e)))?;
+
+        let stream: Box<dyn SmtpStream> = match credentials.security {
+            SmtpSecurity::Tls => Box::new(tls(&credentials.host, tcp).await?),
+            SmtpSecurity::StartTls => Box::new(tcp),
+        };
+        let mut connection = Self {
+            stream: BufReader::new(stream),
+            idle_since: Instant::now(),
+        };
+
+        connection.expect_reply("greeting", &[220]).await?;
+        connection.ehlo().await?;
+
+        if credentials.security == SmtpSecurity::StartTls {
+            connection.command("STARTTLS", &[220]).await?;
+            // Nothing can be buffered past the 220, so the plain stream can be
+            // taken back out of the reader.
+            let plain = connection.stream.into_inner();
+            connection.stream = BufReader::new(Box::new(tls(&credentials.host, plain).await?));
+            connection.ehlo().await?;
+        }
+
+        let auth = STANDARD.encode(format!(
+            "\0{}\0{}",
+            credentials.username, credentials.password
+        ));
+        connection
+            .send_line(&format!("AUTH PLAIN {}", auth))
+            .await?;
+        connection.expect_reply("AUTH", &[235]).await?;
+
+        Ok(connection)
+    }
+
+    async fn ehlo(&mut self) -> Result<(), AppError> {
+        self.command(&format!("EHLO {}", HELO_NAME), &[250]).await?;
+        Ok(())
+    }
+
+    async fn send_line(&mut self, line: &str) -> Result<(), AppError> {
+        let write = async {
+            self.stream.write_all(line.as_bytes()).await?;
+            self.stream.write_all(b"\r\n").await?;
+            self.stream.flush().await
+        };
+        timeout(COMMAND_TIMEOUT, write)
+            .await
+            .map_err(|_| smtp_error("Timed out writing to the SMTP server"))?
+            .map_err(|e| smtp_error(format!("Failed to write to the SMTP server: {}", e)))
+    }
+
+    /// Reads a reply, joining the lines of a multiline one, and checks its
+    /// code. `what` names the command in errors; commands carrying secrets
+    /// aren't echoed.
+    async fn expect_reply(&mut self, what: &str, expected: &[u16]) -> Result<String, AppError> {
+        let mut message = String::new();
+
+        loop {
+            let mut line = String::new();
+            let read = timeout(COMMAND_TIMEOUT, self.stream.read_line(&mut line))
+                .await
+                .map_err(|_| smtp_error("Timed out waiting for the SMTP server"))?
+                .map_err(|e| smtp_error(format!("Failed to read from the SMTP server: {}", e)))?;
+            if read == 0 {
+                return Err(smtp_error("The SMTP server closed the connection"));
+            }
+
+            let line = line.trim_end();
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse::<u16>().ok())
+                .ok_or_else(|| smtp_error(format!("Unexpected SMTP reply: {}", line)))?;
+            if !message.is_empty() {
+                message.push(' ');
+            }
+            message.push_str(line.get(4..).unwrap_or_default());
+
+            if line.as_bytes().get(3) != Some(&b'-') {
+                if !expected.contains(&code) {
+                    return Err(smtp_error(format!(
+                        "The SMTP server rejected {}: {} {}",
+                        what, code, message
+                    )));
+                }
+                return Ok(message);
+            }
+        }
+    }
+
+    async fn command(&mut self, command: &str, expected: &[u16]) -> Result<String, AppError> {
+        self.send_line(command).await?;
+        let verb = command.split_whitespace().next().unwrap_or(command);
+        self.expect_reply(verb, expected).await
+    }
+
+    async fn send(&mut self, message: &OutgoingMessage<'_>) -> Result<(), AppError> {
+        self.command(
+            &format!("MAIL FROM:<{}>", envelope_address(message.from)),
+            &[250],
+        )
+        .await?;
+        self.command(
+            &format!("RCPT TO:<{}>", envelope_address(message.to)),
+            &[250, 251],
+        )
+        .await?;
+        self.command("DATA", &[354]).await?;
+
+        let mut data = String::new();
+        for line in message.render().lines() {
+            // Dot-stuffing, so no line of the message ends the DATA section.
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push('.');
+        self.send_line(&data).await?;
+        self.expect_reply("the message", &[250]).await?;
+
+        Ok(())
+    }
+}
+
+/// Sends through customers' own SMTP servers, keeping a few authenticated
+/// connections per server open between emails.
+#[derive(Clone, Default)]
+pub struct SmtpService {
+    idle: Arc<Mutex<HashMap<SmtpCredentials, Vec<SmtpConnection>>>>,
+}
+
+impl SmtpService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects and authenticates, so bad settings are caught when they are
+    /// saved rather than when the first email goes out.
+    pub async fn verify_credentials(&self, credentials: &SmtpCredentials) -> Result<(), AppError> {
+        let mut connection = SmtpConnection::open(credentials).await?;
+        connection.command("NOOP", &[250]).await?;
+        let _ = connection.command("QUIT", &[221]).await;
+        Ok(())
+    }
+
+    /// Sends one email and returns its Message-ID.
+    pub async fn send_email(
+        &self,
+        credentials: &SmtpCredentials,
+        from: &str,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        text_body: Option<&str>,
+    ) -> Result<String, AppError> {
+        let message = OutgoingMessage::new(from, to, subject, html_body, text_body);
+
+        // The server may have dropped a pooled connection; a reset shows
+        // whether it's still usable before anything is sent on it.
+        let mut pooled = self.checkout(credentials);
+        if let Some(connection) = &mut pooled
+            && connection.command("RSET", &[250]).await.is_err()
+        {
+            pooled = None;
+        }
+        let mut connection = match pooled {
+            Some(connection) => connection,
+            None => SmtpConnection::open(credentials).await?,
+        };
+
+        connection.send(&message).await?;
+        self.checkin(credentials, connection);
+
+        Ok(message.message_id)
+    }
+
+    fn checkout(&self, credentials: &SmtpCredentials) -> Option<SmtpConnection> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.retain(|_, connections| {
+            connections.retain(|connection| connection.idle_since.elapsed() < IDLE_TIMEOUT);
+            !connections.is_empty()
+        });
+        idle.get_mut(credentials)
+            .and_then(|connections| connections.pop())
+    }
+
+    fn checkin(&self, credentials: &SmtpCredentials, mut connection: SmtpConnection) {
+        connection.idle_since = Instant::now();
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        let connections = idle.entry(credentials.clone()).or_default();
+        if connections.len() < MAX_IDLE_PER_SERVER {
+            connections.push(connection);
+        }
+    }
+}
+
+struct OutgoingMessage<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: Option<&'a str>,
+    message_id: String,
+}
+
+impl<'a> OutgoingMessage<'a> {
+    fn new(
+        from: &'a str,
+        to: &'a str,
+        subject: &'a str,
+        html_body: &'a str,
+        text_body: Option<&'a str>,
+    ) -> Self {
+        let domain = envelope_address(from)
+            .rsplit_once('@')
+            .map_or("localhost", |(_, domain)| domain);
+        let message_id = format!("<{}@{}>", hex::encode(rand::random::<[u8; 16]>()), domain);
+
+        Self {
+            from,
+            to,
+            subject,
+            html_body,
+            text_body,
+            message_id,
+        }
+    }
+
+    /// A multipart/alternative message with base64 parts, which keeps every
+    /// line short and ASCII whatever the bodies contain.
+    fn render(&self) -> String {
+        let boundary = format!("=_{}", hex::encode(rand::random::<[u8; 12]>()));
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: {}\r\nMIME-Version: 1.0\r\n",
+            header_value(self.from),
+            header_value(self.to),
+            encode_header(self.subject),
+            Utc::now().to_rfc2822(),
+            self.message_id,
+        );
+        message.push_str(&format!(
+            "Content-Type: multipart/alternative; boundary=\"{}\"\r\n\r\n",
+            boundary
+        ));
+
+        let parts = self
+            .text_body
+            .map(|text| ("text/plain", text))
+            .into_iter()
+            .chain([("text/html", self.html_body)]);
+        for (content_type, body) in parts {
+            message.push_str(&format!(
+                "--{}\r\nContent-Type: {}; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n",
+                boundary, content_type
+            ));
+            let encoded = STANDARD.encode(body);
+            for chunk in encoded.as_bytes().chunks(76) {
+                message.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+                message.push_str("\r\n");
+            }
+        }
+        message.push_str(&format!("--{}--\r\n", boundary));
+
+        message
+    }
+}
+
+async fn tls<S>(host: &str, stream: S) -> Result<tokio_native_tls::TlsStream<S>, AppError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let connector = native_tls::TlsConnector::new()
+        .map_err(|e| smtp_error(format!("Failed to set up TLS: {}", e)))?;
+    timeout(
+        CONNECT_TIMEOUT,
+        TlsConnector::from(connector).connect(host, stream),
+    )
+    .await
+    .map_err(|_| smtp_error(format!("Timed out negotiating TLS with {}", host)))?
+    .map_err(|e| smtp_error(format!("TLS with {} failed: {}", host, e)))
+}
+
+/// The bare address of `Name <address>` or `address`.
+fn envelope_address(address: &str) -> &str {
+    match (address.rfind('<'), address.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &address[start + 1..end],
+        _ => address.trim(),
+    }
+}
+
+/// Drops line breaks, so a value can't add headers of its own.
+fn header_value(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+fn encode_header(value: &str) -> String {
+    let value = header_value(value);
+    if value.is_ascii() {
+        value
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+fn smtp_error(message: impl Into<String>) -> AppError {
+    AppError::External(ExternalError::new("smtp", None, message))
+}
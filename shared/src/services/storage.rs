@@ -0,0 +1,623 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use aws_config::Region;
+use aws_sdk_s3::{
+    Client as S3Client,
+    config::Credentials,
+    presigning::PresigningConfig,
+    primitives::{ByteStream, SdkBody},
+    types::ChecksumMode,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sha2::{Digest, Sha256};
+
+use crate::{error::AppError, models::StorageClass};
+
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, AppError>> + Send + 'a>>;
+
+/// What is stored: bytes already in memory, or a file streamed from disk.
+pub enum ObjectBody {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+/// What a signed upload is restricted to. Storage rejects a PUT that doesn't
+/// match.
+#[derive(Debug, Clone)]
+pub struct UploadConstraints {
+    pub content_type: String,
+    pub content_length: i64,
+    /// Base64 encoded.
+    pub checksum_sha256: String,
+}
+
+/// A request signed for a client to send straight to storage.
+#[derive(Debug, Clone)]
+pub struct PresignedRequest {
+    pub method: String,
+    pub url: String,
+    /// Headers the request has to be sent with.
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ObjectHead {
+    pub content_length: Option<i64>,
+    pub content_type: Option<String>,
+    /// Base64 encoded, when storage kept the checksum it was uploaded with.
+    pub checksum_sha256: Option<String>,
+}
+
+/// One bucket, behind a trait so commands can run against
+/// [`MemoryObjectStorage`] in tests.
+pub trait ObjectStorage: Send + Sync {
+    fn public_url(&self, key: &str) -> String;
+
+    fn put_object<'a>(
+        &'a self,
+        key: &'a str,
+        body: ObjectBody,
+        content_type: Option<&'a str>,
+    ) -> StorageFuture<'a, ()>;
+
+    fn presign_put<'a>(
+        &'a self,
+        key: &'a str,
+        constraints: &'a UploadConstraints,
+        expires_in: Duration,
+    ) -> StorageFuture<'a, PresignedRequest>;
+
+    /// A download link, served with `content_disposition` when given.
+    fn presign_get<'a>(
+        &'a self,
+        key: &'a str,
+        content_disposition: Option<&'a str>,
+        expires_in: Duration,
+    ) -> StorageFuture<'a, String>;
+
+    fn get_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Vec<u8>>;
+
+    /// `None` if there is no object under the key.
+    fn head_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<ObjectHead>>;
+
+    /// Reads the object through and returns its SHA-256, base64 encoded.
+    fn sha256<'a>(&'a self, key: &'a str) -> StorageFuture<'a, String>;
+
+    /// Deleting a key that doesn't exist succeeds.
+    fn delete_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()>;
+
+    fn list_keys<'a>(&'a self, prefix: &'a str) -> StorageFuture<'a, Vec<String>>;
+}
+
+fn s3_error(error: impl std::fmt::Display) -> AppError {
+    AppError::S3(error.to_string())
+}
+
+fn presigning_config(expires_in: Duration) -> Result<PresigningConfig, AppError> {
+    PresigningConfig::expires_in(expires_in)
+        .map_err(|e| AppError::Internal(format!("Invalid presigned URL lifetime: {}", e)))
+}
+
+/// Where one class of files is stored. Every setting but the bucket falls
+/// back to the shared `R2_*` variables.
+#[derive(Debug, Clone)]
+pub struct StorageBucketConfig {
+    pub endpoint_url: String,
+    pub region: String,
+    pub bucket: String,
+    /// Files are read from `{public_base_url}/{key}`.
+    pub public_base_url: String,
+}
+
+impl StorageBucketConfig {
+    /// Reads `{prefix}_ENDPOINT_URL`, `{prefix}_REGION` and `{prefix}_BUCKET`.
+    fn from_env(prefix: &str, default_bucket: Option<String>, public_base_url: String) -> Self {
+        let read = |key: &str| {
+            std::env::var(format!("{}_{}", prefix, key))
+                .ok()
+                .filter(|v| !v.is_empty())
+        };
+
+        Self {
+            endpoint_url: read("ENDPOINT_URL").unwrap_or_else(|| {
+                std::env::var("R2_ENDPOINT_URL").expect("R2_ENDPOINT_URL must be set")
+            }),
+            region: read("REGION").unwrap_or_else(|| "auto".to_string()),
+            bucket: read("BUCKET")
+                .or(default_bucket)
+                .unwrap_or_else(|| panic!("{}_BUCKET must be set", prefix)),
+            public_base_url: public_base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+/// A bucket and the client for its region.
+#[derive(Clone)]
+pub struct StorageBucket {
+    pub client: S3Client,
+    pub name: String,
+    pub public_base_url: String,
+}
+
+impl StorageBucket {
+    async fn connect(config: StorageBucketConfig) -> Self {
+        let access_key_id =
+            std::env::var("R2_ACCESS_KEY_ID").expect("R2_ACCESS_KEY_ID must be set");
+        let secret_access_key =
+            std::env::var("R2_SECRET_ACCESS_KEY").expect("R2_SECRET_ACCESS_KEY must be set");
+
+        let client = S3Client::new(
+            &aws_config::from_env()
+                .endpoint_url(config.endpoint_url)
+                .credentials_provider(Credentials::new(
+                    access_key_id,
+                    secret_access_key,
+                    None,
+                    None,
+                    "R2",
+                ))
+                .region(Region::new(config.region))
+                .load()
+                .await,
+        );
+
+        Self {
+            client,
+            name: config.bucket,
+            public_base_url: config.public_base_url,
+        }
+    }
+}
+
+impl ObjectStorage for StorageBucket {
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_base_url, key)
+    }
+
+    fn put_object<'a>(
+        &'a self,
+        key: &'a str,
+        body: ObjectBody,
+        content_type: Option<&'a str>,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let body = match body {
+                ObjectBody::Bytes(bytes) => ByteStream::new(SdkBody::from(bytes)),
+                ObjectBody::File(path) => ByteStream::from_path(path).await.map_err(s3_error)?,
+            };
+
+            self.client
+                .put_object()
+                .bucket(&self.name)
+                .key(key)
+                .set_content_type(content_type.map(str::to_string))
+                .body(body)
+                .send()
+                .await
+                .map_err(s3_error)?;
+
+            Ok(())
+        })
+    }
+
+    fn presign_put<'a>(
+        &'a self,
+        key: &'a str,
+        constraints: &'a UploadConstraints,
+        expires_in: Duration,
+    ) -> StorageFuture<'a, PresignedRequest> {
+        Box::pin(async move {
+            let presigned = self
+                .client
+                .put_object()
+                .bucket(&self.name)
+                .key(key)
+                .content_type(&constraints.content_type)
+                .content_length(constraints.content_length)
+                .checksum_sha256(&constraints.checksum_sha256)
+                .presigned(presigning_config(expires_in)?)
+                .await
+                .map_err(s3_error)?;
+
+            Ok(PresignedRequest {
+                method: presigned.method().to_string(),
+                url: presigned.uri().to_string(),
+                headers: presigned
+                    .headers()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            })
+        })
+    }
+
+    fn presign_get<'a>(
+        &'a self,
+        key: &'a str,
+        content_disposition: Option<&'a str>,
+        expires_in: Duration,
+    ) -> StorageFuture<'a, String> {
+        Box::pin(async move {
+            let presigned = self
+                .client
+                .get_object()
+                .bucket(&self.name)
+                .key(key)
+                .set_response_content_disposition(content_disposition.map(str::to_string))
+                .presigned(presigning_config(expires_in)?)
+                .await
+                .map_err(s3_error)?;
+
+            Ok(presigned.uri().to_string())
+        })
+    }
+
+    fn get_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            let body = self
+                .client
+                .get_object()
+                .bucket(&self.name)
+                .key(key)
+                .send()
+                .await
+                .map_err(s3_error)?
+                .body
+                .collect()
+                .await
+                .map_err(s3_error)?;
+
+            Ok(body.into_bytes().to_vec())
+        })
+    }
+
+    fn head_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<ObjectHead>> {
+        Box::pin(async move {
+            match self
+                .client
+                .head_object()
+                .bucket(&self.name)
+                .key(key)
+                .checksum_mode(ChecksumMode::Enabled)
+                .send()
+                .await
+            {
+                Ok(head) => Ok(Some(ObjectHead {
+                    content_length: head.content_length(),
+                    content_type: head.content_type().map(str::to_string),
+                    checksum_sha256: head.checksum_sha256().map(str::to_string),
+                })),
+                Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+                Err(e) => Err(s3_error(e)),
+            }
+        })
+    }
+
+    fn sha256<'a>(&'a self, key: &'a str) -> StorageFuture<'a, String> {
+        Box::pin(async move {
+            let mut body = self
+                .client
+                .get_object()
+                .bucket(&self.name)
+                .key(key)
+                .send()
+                .await
+                .map_err(s3_error)?
+                .body;
+            let mut hasher = Sha256::new();
+            while let Some(chunk) = body.next().await {
+                hasher.update(chunk.map_err(s3_error)?);
+            }
+
+            Ok(STANDARD.encode(hasher.finalize()))
+        })
+    }
+
+    fn delete_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.client
+                .delete_object()
+                .bucket(&self.name)
+                .key(key)
+                .send()
+                .await
+                .map_err(s3_error)?;
+
+            Ok(())
+        })
+    }
+
+    fn list_keys<'a>(&'a self, prefix: &'a str) -> StorageFuture<'a, Vec<String>> {
+        Box::pin(async move {
+            let objects = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.name)
+                .prefix(prefix)
+                .send()
+                .await
+                .map_err(s3_error)?;
+
+            Ok(objects
+                .contents()
+                .iter()
+                .filter_map(|object| object.key().map(str::to_string))
+                .collect())
+        })
+    }
+}
+
+/// The buckets files are stored in, by class.
+#[derive(Clone)]
+pub struct StorageService {
+    cdn: Arc<dyn ObjectStorage>,
+    knowledge_base: Arc<dyn ObjectStorage>,
+    exports: Arc<dyn ObjectStorage>,
+}
+
+impl StorageService {
+    pub fn new(
+        cdn: Arc<dyn ObjectStorage>,
+        knowledge_base: Arc<dyn ObjectStorage>,
+        exports: Arc<dyn ObjectStorage>,
+    ) -> Self {
+        Self {
+            cdn,
+            knowledge_base,
+            exports,
+        }
+    }
+
+    /// The CDN bucket is configured with `R2_CDN_*` and served from
+    /// `CDN_BASE_URL`. Knowledge base documents use `R2_KNOWLEDGE_BASE_*`
+    /// and share the CDN bucket when they have none of their own. Data
+    /// exports use `R2_EXPORTS_*` when `R2_EXPORTS_BUCKET` is set, and the
+    /// knowledge base bucket otherwise, as neither is public.
+    pub async fn from_env() -> Self {
+        let cdn = StorageBucketConfig::from_env(
+            "R2_CDN",
+            None,
+            std::env::var("CDN_BASE_URL")
+                .unwrap_or_else(|_| "https://cdn.wacht.services".to_string()),
+        );
+
+        let knowledge_base_bucket = std::env::var("R2_KNOWLEDGE_BASE_BUCKET")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| cdn.bucket.clone());
+        let knowledge_base = StorageBucketConfig::from_env(
+            "R2_KNOWLEDGE_BASE",
+            Some(knowledge_base_bucket.clone()),
+            std::env::var("R2_KNOWLEDGE_BASE_BASE_URL").unwrap_or_else(|_| {
+                format!("https://{}.r2.cloudflarestorage.com", knowledge_base_bucket)
+            }),
+        );
+
+        // Exports are only handed out through signed links.
+        let exports = match std::env::var("R2_EXPORTS_BUCKET") {
+            Ok(bucket) if !bucket.is_empty() => StorageBucketConfig::from_env(
+                "R2_EXPORTS",
+                None,
+                knowledge_base.public_base_url.clone(),
+            ),
+            _ => knowledge_base.clone(),
+        };
+
+        Self::new(
+            Arc::new(StorageBucket::connect(cdn).await),
+            Arc::new(StorageBucket::connect(knowledge_base).await),
+            Arc::new(StorageBucket::connect(exports).await),
+        )
+    }
+
+    /// The private bucket deployment data exports are kept in.
+    pub fn exports(&self) -> &dyn ObjectStorage {
+        self.exports.as_ref()
+    }
+
+    pub fn bucket(&self, class: StorageClass) -> &dyn ObjectStorage {
+        match class {
+            StorageClass::Cdn => self.cdn.as_ref(),
+            StorageClass::KnowledgeBase => self.knowledge_base.as_ref(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct StoredObject {
+    body: Vec<u8>,
+    content_type: Option<String>,
+}
+
+#[derive(Default)]
+struct MemoryObjectStorageState {
+    objects: HashMap<String, StoredObject>,
+    failing_keys: HashSet<String>,
+}
+
+/// Keeps objects in process memory, for tests. Every operation on a key
+/// passed to [`MemoryObjectStorage::fail`] fails.
+#[derive(Default)]
+pub struct MemoryObjectStorage {
+    state: Mutex<MemoryObjectStorageState>,
+}
+
+impl MemoryObjectStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores an object as a client holding a signed upload would.
+    pub fn insert(&self, key: &str, body: Vec<u8>, content_type: Option<&str>) {
+        self.lock().objects.insert(
+            key.to_string(),
+            StoredObject {
+                body,
+                content_type: content_type.map(str::to_string),
+            },
+        );
+    }
+
+    pub fn fail(&self, key: &str) {
+        self.lock().failing_keys.insert(key.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.lock()
+            .objects
+            .get(key)
+            .map(|object| object.body.clone())
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.lock().objects.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryObjectStorageState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn with_key<T>(
+        &self,
+        key: &str,
+        operation: impl FnOnce(&mut MemoryObjectStorageState) -> Result<T, AppError>,
+    ) -> Result<T, AppError> {
+        let mut state = self.lock();
+        if state.failing_keys.contains(key) {
+            return Err(AppError::S3(format!("scripted failure for {}", key)));
+        }
+        operation(&mut state)
+    }
+}
+
+impl ObjectStorage for MemoryObjectStorage {
+    fn public_url(&self, key: &str) -> String {
+        format!("memory://{}", key)
+    }
+
+    fn put_object<'a>(
+        &'a self,
+        key: &'a str,
+        body: ObjectBody,
+        content_type: Option<&'a str>,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let body = match body {
+                ObjectBody::Bytes(bytes) => bytes,
+                ObjectBody::File(path) => std::fs::read(path).map_err(s3_error)?,
+            };
+            self.with_key(key, |state| {
+                state.objects.insert(
+                    key.to_string(),
+                    StoredObject {
+                        body,
+                        content_type: content_type.map(str::to_string),
+                    },
+                );
+                Ok(())
+            })
+        })
+    }
+
+    fn presign_put<'a>(
+        &'a self,
+        key: &'a str,
+        constraints: &'a UploadConstraints,
+        _expires_in: Duration,
+    ) -> StorageFuture<'a, PresignedRequest> {
+        let result = self.with_key(key, |_| {
+            Ok(PresignedRequest {
+                method: "PUT".to_string(),
+                url: self.public_url(key),
+                headers: BTreeMap::from([
+                    ("content-type".to_string(), constraints.content_type.clone()),
+                    (
+                        "content-length".to_string(),
+                        constraints.content_length.to_string(),
+                    ),
+                    (
+                        "x-amz-checksum-sha256".to_string(),
+                        constraints.checksum_sha256.clone(),
+                    ),
+                ]),
+            })
+        });
+
+        Box::pin(async move { result })
+    }
+
+    fn presign_get<'a>(
+        &'a self,
+        key: &'a str,
+        _content_disposition: Option<&'a str>,
+        _expires_in: Duration,
+    ) -> StorageFuture<'a, String> {
+        let result = self.with_key(key, |_| Ok(self.public_url(key)));
+
+        Box::pin(async move { result })
+    }
+
+    fn get_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Vec<u8>> {
+        let result = self.with_key(key, |state| {
+            state
+                .objects
+                .get(key)
+                .map(|object| object.body.clone())
+                .ok_or_else(|| AppError::S3(format!("No object under {}", key)))
+        });
+
+        Box::pin(async move { result })
+    }
+
+    fn head_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<ObjectHead>> {
+        let result = self.with_key(key, |state| {
+            Ok(state.objects.get(key).map(|object| ObjectHead {
+                content_length: Some(object.body.len() as i64),
+                content_type: object.content_type.clone(),
+                checksum_sha256: None,
+            }))
+        });
+
+        Box::pin(async move { result })
+    }
+
+    fn sha256<'a>(&'a self, key: &'a str) -> StorageFuture<'a, String> {
+        let result = self.with_key(key, |state| {
+            let object = state
+                .objects
+                .get(key)
+                .ok_or_else(|| AppError::S3(format!("No object under {}", key)))?;
+            Ok(STANDARD.encode(Sha256::digest(&object.body)))
+        });
+
+        Box::pin(async move { result })
+    }
+
+    fn delete_object<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()> {
+        let result = self.with_key(key, |state| {
+            state.objects.remove(key);
+            Ok(())
+        });
+
+        Box::pin(async move { result })
+    }
+
+    fn list_keys<'a>(&'a self, prefix: &'a str) -> StorageFuture<'a, Vec<String>> {
+        let mut keys: Vec<String> = self
+            .lock()
+            .objects
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+
+        Box::pin(async move { Ok(keys) })
+    }
+}
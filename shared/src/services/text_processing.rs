@@ -1,6 +1,23 @@
-use crate::error::AppError;
+use std::ops::Range;
+
+use crate::{
+    error::AppError,
+    models::{ChunkBoundary, ChunkingConfig, ChunkingStrategy},
+};
 use pulldown_cmark::{Parser, html};
 
+/// Chunk size, in characters, documents are split with unless re-chunked
+/// with other settings.
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+pub const DEFAULT_CHUNK_OVERLAP: usize = 200;
+pub const MIN_CHUNK_SIZE: usize = 100;
+pub const MAX_CHUNK_SIZE: usize = 8000;
+
+/// Chunk size bounds, in tokens, for the token-aware strategies.
+pub const MIN_CHUNK_TOKENS: usize = 32;
+/// The smallest input limit of the embedding models offered.
+pub const MAX_CHUNK_TOKENS: usize = 2048;
+
 #[derive(Debug, Clone)]
 pub struct TextProcessingService;
 
@@ -8,6 +25,134 @@ pub struct TextProcessingService;
 pub struct TextChunk {
     pub content: String,
     pub chunk_index: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+    pub token_count: usize,
+    /// The markdown headings the chunk sits under, outermost first.
+    pub heading_path: Vec<String>,
+}
+
+impl ChunkingStrategy {
+    /// The smallest and largest chunk size the strategy takes.
+    pub fn size_limits(&self) -> (usize, usize) {
+        match self {
+            ChunkingStrategy::Characters => (MIN_CHUNK_SIZE, MAX_CHUNK_SIZE),
+            ChunkingStrategy::Tokens | ChunkingStrategy::Markdown => {
+                (MIN_CHUNK_TOKENS, MAX_CHUNK_TOKENS)
+            }
+        }
+    }
+}
+
+/// What documents were always split with before strategies could be chosen.
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ChunkingStrategy::Characters,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            chunk_overlap: DEFAULT_CHUNK_OVERLAP,
+            boundary: ChunkBoundary::None,
+        }
+    }
+}
+
+impl ChunkingConfig {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let (min, max) = self.strategy.size_limits();
+        if !(min..=max).contains(&self.chunk_size) {
+            return Err(AppError::Validation(format!(
+                "chunk_size must be between {} and {} for the {} strategy",
+                min,
+                max,
+                self.strategy.as_str()
+            )));
+        }
+        if self.chunk_overlap >= self.chunk_size {
+            return Err(AppError::Validation(
+                "chunk_overlap must be smaller than chunk_size".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Approximates how an embedding model's tokenizer splits text. The
+/// vocabularies themselves don't ship with the platform, so text is split
+/// where the tokenizer would pre-split it, with words longer than its
+/// vocabulary typically merges broken into several tokens. Good enough to
+/// size chunks by, not to bill by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// The byte-pair encoding of OpenAI's embedding models, `cl100k_base`.
+    Cl100k,
+    /// The SentencePiece vocabulary of Google's models.
+    SentencePiece,
+}
+
+impl Tokenizer {
+    pub fn for_model(model: &str) -> Self {
+        if model.starts_with("text-embedding-3") || model == "text-embedding-ada-002" {
+            Tokenizer::Cl100k
+        } else {
+            Tokenizer::SentencePiece
+        }
+    }
+
+    /// Offsets, in `chars`, at which each token starts.
+    pub fn token_starts(&self, chars: &[char]) -> Vec<usize> {
+        // Longest run of letters, and of digits, one token is assumed to cover
+        let (letters, digits) = match self {
+            Tokenizer::Cl100k => (6, 3),
+            Tokenizer::SentencePiece => (5, 1),
+        };
+        let run = |from: usize, max: usize, matches: fn(&char) -> bool| {
+            chars[from..]
+                .iter()
+                .take(max)
+                .take_while(|c| matches(c))
+                .count()
+        };
+
+        let mut starts = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            starts.push(i);
+
+            // A single space is part of the word after it
+            let word = if chars[i] == ' ' && chars.get(i + 1).is_some_and(|c| !c.is_whitespace()) {
+                i + 1
+            } else {
+                i
+            };
+
+            let c = chars[word];
+            i = word
+                + if c.is_whitespace() {
+                    run(word, usize::MAX, |c| c.is_whitespace())
+                } else if c.is_alphabetic() {
+                    run(word, letters, |c| c.is_alphabetic())
+                } else if c.is_numeric() {
+                    run(word, digits, |c| c.is_numeric())
+                } else {
+                    1
+                };
+        }
+
+        starts
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.token_starts(&text.chars().collect::<Vec<_>>()).len()
+    }
+}
+
+fn normalized_file_type(file_type: &str) -> String {
+    if file_type.contains("/") {
+        file_type.split('/').last().unwrap_or(file_type)
+    } else {
+        file_type
+    }
+    .to_lowercase()
 }
 
 impl TextProcessingService {
@@ -20,14 +165,7 @@ impl TextProcessingService {
         file_content: &[u8],
         file_type: &str,
     ) -> Result<String, AppError> {
-        let normalized_type = if file_type.contains("/") {
-            file_type.split('/').last().unwrap_or(file_type)
-        } else {
-            file_type
-        }
-        .to_lowercase();
-
-        match normalized_type.as_str() {
+        match normalized_file_type(file_type).as_str() {
             "pdf" | "application/pdf" => self.extract_text_from_pdf(file_content),
             "txt" | "text" | "plain" | "text/plain" => self.extract_text_from_txt(file_content),
             "md" | "markdown" | "text/markdown" => self.extract_text_from_markdown(file_content),
@@ -101,6 +239,34 @@ impl TextProcessingService {
         Ok(text)
     }
 
+    /// Extracts the readable body of a web page, dropping scripts, styles and
+    /// page chrome such as navigation, headers and footers.
+    pub fn extract_readable_text_from_html(&self, html: &str) -> Result<String, AppError> {
+        let mut content = regex::Regex::new(r"(?s)<!--.*?-->")
+            .unwrap()
+            .replace_all(html, "")
+            .to_string();
+
+        for tag in [
+            "script", "style", "noscript", "nav", "header", "footer", "aside", "svg", "form",
+        ] {
+            content = regex::Regex::new(&format!(r"(?is)<{tag}\b[^>]*>.*?</{tag}\s*>"))
+                .unwrap()
+                .replace_all(&content, " ")
+                .to_string();
+        }
+
+        let text = self.extract_text_from_html(content.as_bytes())?;
+
+        Ok(text
+            .replace("&nbsp;", " ")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&"))
+    }
+
     fn extract_text_from_json(&self, content: &[u8]) -> Result<String, AppError> {
         let json_content = String::from_utf8(content.to_vec())
             .map_err(|e| AppError::Internal(format!("Failed to parse JSON file: {}", e)))?;
@@ -136,27 +302,99 @@ impl TextProcessingService {
         }
     }
 
+    /// Extracts a document's text the way `config` chunks it best: with its
+    /// line breaks kept when chunks end at sentences or paragraphs, and with
+    /// its headings kept as markdown when chunks follow sections.
+    pub fn extract_text_for_chunking(
+        &self,
+        file_content: &[u8],
+        file_type: &str,
+        config: &ChunkingConfig,
+    ) -> Result<String, AppError> {
+        let text = match normalized_file_type(file_type).as_str() {
+            "md" | "markdown" if config.strategy == ChunkingStrategy::Markdown => {
+                self.extract_text_from_txt(file_content)?
+            }
+            "html" | "htm" if config.strategy == ChunkingStrategy::Markdown => {
+                let html = String::from_utf8_lossy(file_content);
+                self.extract_text_from_html(html_headings_to_markdown(&html).as_bytes())?
+            }
+            _ => self.extract_text_from_file(file_content, file_type)?,
+        };
+
+        Ok(self.clean_text_for(&text, config))
+    }
+
+    /// [`Self::extract_readable_text_from_html`], cleaned for `config`.
+    pub fn extract_readable_text_for_chunking(
+        &self,
+        html: &str,
+        config: &ChunkingConfig,
+    ) -> Result<String, AppError> {
+        let text = if config.strategy == ChunkingStrategy::Markdown {
+            self.extract_readable_text_from_html(&html_headings_to_markdown(html))?
+        } else {
+            self.extract_readable_text_from_html(html)?
+        };
+
+        Ok(self.clean_text_for(&text, config))
+    }
+
+    /// Plain character windows don't care about line breaks, and are cleaned
+    /// as they always were so their chunks come out as before.
+    fn clean_text_for(&self, text: &str, config: &ChunkingConfig) -> String {
+        if config.strategy == ChunkingStrategy::Characters && config.boundary == ChunkBoundary::None
+        {
+            self.clean_text(text)
+        } else {
+            self.clean_text_keeping_lines(text)
+        }
+    }
+
+    /// Splits text into chunks as `config` says, counting tokens with
+    /// `tokenizer`. Each chunk starts `chunk_overlap` before the previous one
+    /// ended.
     pub fn chunk_text(
         &self,
         text: &str,
-        chunk_size: usize,
-        _overlap: usize,
+        config: &ChunkingConfig,
+        tokenizer: Tokenizer,
     ) -> Result<Vec<TextChunk>, AppError> {
-        let mut chunks = Vec::new();
-        let chars: Vec<char> = text.chars().collect();
+        config.validate()?;
 
-        for (index, chunk_chars) in chars.chunks(chunk_size).enumerate() {
-            let chunk_text = chunk_chars.iter().collect::<String>();
+        let chars: Vec<char> = text.chars().collect();
+        let mut chunks = Vec::new();
 
-            chunks.push(TextChunk {
-                content: chunk_text.clone(),
-                chunk_index: index,
-            });
+        if config.strategy == ChunkingStrategy::Markdown {
+            for (range, heading_path) in markdown_sections(&chars) {
+                split_range(&chars, range, config, tokenizer, &heading_path, &mut chunks);
+            }
+        } else {
+            split_range(&chars, 0..chars.len(), config, tokenizer, &[], &mut chunks);
         }
 
         Ok(chunks)
     }
 
+    /// Like [`Self::clean_text`], but keeps line breaks, with runs of blank
+    /// lines collapsed into one.
+    pub fn clean_text_keeping_lines(&self, text: &str) -> String {
+        let spaces = regex::Regex::new(r"[^\S\n]+").unwrap();
+        let blank_lines = regex::Regex::new(r"\n{3,}").unwrap();
+
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let lines: Vec<String> = text
+            .lines()
+            .map(|line| spaces.replace_all(line.trim(), " ").to_string())
+            .collect();
+
+        blank_lines
+            .replace_all(lines.join("\n").trim(), "\n\n")
+            .chars()
+            .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+            .collect()
+    }
+
     pub fn clean_text(&self, text: &str) -> String {
         // Remove excessive whitespace and normalize line endings
         let cleaned = regex::Regex::new(r"\s+")
@@ -171,3 +409,273 @@ impl TextProcessingService {
             .collect()
     }
 }
+
+/// Rewrites HTML headings as markdown ones, so markdown chunking can find
+/// them once the tags are gone.
+fn html_headings_to_markdown(html: &str) -> String {
+    regex::Regex::new(r"(?is)<h([1-6])\b[^>]*>(.*?)</h[1-6]\s*>")
+        .unwrap()
+        .replace_all(html, |captures: &regex::Captures| {
+            let level: usize = captures[1].parse().unwrap_or(1);
+            let title = regex::Regex::new(r"<[^>]*>")
+                .unwrap()
+                .replace_all(&captures[2], "")
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("\n\n{} {}\n\n", "#".repeat(level), title)
+        })
+        .to_string()
+}
+
+/// Splits markdown into sections, one per heading plus whatever comes
+/// before the first, each with the headings it sits under. Headings inside
+/// fenced code blocks don't count.
+fn markdown_sections(chars: &[char]) -> Vec<(Range<usize>, Vec<String>)> {
+    let mut sections = Vec::new();
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut section_start = 0;
+    let mut in_fence = false;
+
+    let mut line_start = 0;
+    while line_start < chars.len() {
+        let line_end = chars[line_start..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(chars.len(), |i| line_start + i);
+        let line: String = chars[line_start..line_end].iter().collect();
+        let line = line.trim_start();
+
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            let level = line.chars().take_while(|c| *c == '#').count();
+            let title = line[level..].trim();
+            if (1..=6).contains(&level) && (line[level..].starts_with(' ') || line.len() == level) {
+                sections.push((
+                    section_start..line_start,
+                    headings.iter().map(|(_, title)| title.clone()).collect(),
+                ));
+                headings.retain(|(outer, _)| *outer < level);
+                headings.push((level, title.trim_end_matches('#').trim().to_string()));
+                section_start = line_start;
+            }
+        }
+
+        line_start = line_end + 1;
+    }
+    sections.push((
+        section_start..chars.len(),
+        headings.into_iter().map(|(_, title)| title).collect(),
+    ));
+
+    sections.retain(|(range, _)| chars[range.clone()].iter().any(|c| !c.is_whitespace()));
+    sections
+}
+
+/// Splits `range` of the text into windows of `config.chunk_size` units,
+/// characters or tokens, pulled back to the boundary `config` prefers.
+fn split_range(
+    chars: &[char],
+    range: Range<usize>,
+    config: &ChunkingConfig,
+    tokenizer: Tokenizer,
+    heading_path: &[String],
+    chunks: &mut Vec<TextChunk>,
+) {
+    // Where each unit starts
+    let starts: Vec<usize> = match config.strategy {
+        ChunkingStrategy::Characters => range.clone().collect(),
+        ChunkingStrategy::Tokens | ChunkingStrategy::Markdown => tokenizer
+            .token_starts(&chars[range.clone()])
+            .into_iter()
+            .map(|offset| range.start + offset)
+            .collect(),
+    };
+    let offset_of = |unit: usize| starts.get(unit).copied().unwrap_or(range.end);
+
+    let mut start = 0;
+    while start < starts.len() {
+        let mut end = (start + config.chunk_size).min(starts.len());
+
+        if end < starts.len() {
+            // Never pulled back to less than half a chunk
+            let earliest = offset_of(start + config.chunk_size / 2);
+            if let Some(cut) = boundary_before(chars, earliest, offset_of(end), config.boundary) {
+                end = starts
+                    .partition_point(|offset| *offset < cut)
+                    .max(start + 1);
+            }
+        }
+
+        let (from, to) = (offset_of(start), offset_of(end));
+        let leading = chars[from..to]
+            .iter()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        let trailing = chars[from + leading..to]
+            .iter()
+            .rev()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        let (from, to) = (from + leading, to - trailing);
+
+        if from < to {
+            let mut content: String = chars[from..to].iter().collect();
+            // Chunks after the first in a section don't start at its heading,
+            // so they are given it
+            if !heading_path.is_empty() && from > range.start + leading_whitespace(chars, &range) {
+                content = format!("{}\n\n{}", heading_path.join(" > "), content);
+            }
+
+            chunks.push(TextChunk {
+                token_count: tokenizer.count(&content),
+                content,
+                chunk_index: chunks.len(),
+                start_offset: from,
+                end_offset: to,
+                heading_path: heading_path.to_vec(),
+            });
+        }
+
+        if end == starts.len() {
+            break;
+        }
+        start = end.saturating_sub(config.chunk_overlap).max(start + 1);
+    }
+}
+
+fn leading_whitespace(chars: &[char], range: &Range<usize>) -> usize {
+    chars[range.clone()]
+        .iter()
+        .take_while(|c| c.is_whitespace())
+        .count()
+}
+
+/// The last position in `earliest..=latest` a chunk can end at that
+/// `boundary` prefers, falling back to the ends of smaller units.
+fn boundary_before(
+    chars: &[char],
+    earliest: usize,
+    latest: usize,
+    boundary: ChunkBoundary,
+) -> Option<usize> {
+    let paragraph_end = |p: usize| {
+        chars[p - 1] != '\n' && chars.get(p) == Some(&'\n') && chars.get(p + 1) == Some(&'\n')
+    };
+    let sentence_end = |p: usize| {
+        matches!(chars[p - 1], '.' | '!' | '?' | '\u{3002}')
+            && chars.get(p).is_none_or(|c| c.is_whitespace())
+    };
+    let word_end =
+        |p: usize| !chars[p - 1].is_whitespace() && chars.get(p).is_none_or(|c| c.is_whitespace());
+
+    let preferences: Vec<&dyn Fn(usize) -> bool> = match boundary {
+        ChunkBoundary::None => return None,
+        ChunkBoundary::Sentence => vec![&sentence_end, &word_end],
+        ChunkBoundary::Paragraph => vec![&paragraph_end, &sentence_end, &word_end],
+    };
+
+    preferences
+        .iter()
+        .find_map(|is_boundary| (earliest.max(1)..=latest).rev().find(|p| is_boundary(*p)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(strategy: ChunkingStrategy, size: usize, overlap: usize) -> ChunkingConfig {
+        ChunkingConfig {
+            strategy,
+            chunk_size: size,
+            chunk_overlap: overlap,
+            boundary: ChunkBoundary::None,
+        }
+    }
+
+    #[test]
+    fn default_config_keeps_the_old_character_windows() {
+        let text = "a".repeat(2500);
+        let chunks = TextProcessingService::new()
+            .chunk_text(&text, &ChunkingConfig::default(), Tokenizer::Cl100k)
+            .unwrap();
+
+        let windows: Vec<_> = chunks
+            .iter()
+            .map(|chunk| (chunk.start_offset, chunk.end_offset))
+            .collect();
+        assert_eq!(windows, [(0, 1000), (800, 1800), (1600, 2500)]);
+    }
+
+    #[test]
+    fn sentence_boundary_ends_chunks_after_a_full_stop() {
+        let text = format!("{}. {}", "word ".repeat(30).trim(), "more ".repeat(30));
+        let chunks = TextProcessingService::new()
+            .chunk_text(
+                &text,
+                &ChunkingConfig {
+                    boundary: ChunkBoundary::Sentence,
+                    ..config(ChunkingStrategy::Characters, 200, 0)
+                },
+                Tokenizer::Cl100k,
+            )
+            .unwrap();
+
+        assert!(chunks[0].content.ends_with("word."));
+        assert!(chunks[1].content.starts_with("more"));
+    }
+
+    #[test]
+    fn token_chunks_stay_within_the_size() {
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(100);
+        let chunks = TextProcessingService::new()
+            .chunk_text(
+                &text,
+                &config(ChunkingStrategy::Tokens, 64, 8),
+                Tokenizer::SentencePiece,
+            )
+            .unwrap();
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.token_count <= 64));
+    }
+
+    #[test]
+    fn markdown_chunks_carry_their_heading_path() {
+        let text = "Intro text.\n\n# Guide\n\nOverview.\n\n## Install\n\nRun it.\n\n```\n# not a heading\n```";
+        let chunks = TextProcessingService::new()
+            .chunk_text(
+                text,
+                &config(ChunkingStrategy::Markdown, 256, 0),
+                Tokenizer::Cl100k,
+            )
+            .unwrap();
+
+        let paths: Vec<_> = chunks
+            .iter()
+            .map(|chunk| chunk.heading_path.clone())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                vec![],
+                vec!["Guide".to_string()],
+                vec!["Guide".to_string(), "Install".to_string()],
+            ]
+        );
+        assert!(chunks[2].content.starts_with("## Install"));
+        assert!(chunks[2].content.contains("# not a heading"));
+    }
+
+    #[test]
+    fn overlap_must_be_smaller_than_the_size() {
+        let result = TextProcessingService::new().chunk_text(
+            "text",
+            &config(ChunkingStrategy::Tokens, 64, 64),
+            Tokenizer::Cl100k,
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,467 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use url::{Host, Url};
+
+use crate::models::{HttpMethod, ToolExecutionError, ToolExecutionPolicy};
+
+const MAX_TOOL_TIMEOUT_MS: u64 = 60_000;
+const DNS_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const METADATA_ADDRESSES: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::new(169, 254, 169, 254)),
+    IpAddr::V6(Ipv6Addr::new(0xfd00, 0xec2, 0, 0, 0, 0, 0, 0x254)),
+];
+
+const BLOCKED_NETWORKS: [&str; 16] = [
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "198.18.0.0/15",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    "::/128",
+    "::1/128",
+    "64:ff9b::/96",
+    "2002::/16",
+    "fc00::/7",
+    "fe80::/10",
+];
+
+#[derive(Debug, Clone)]
+pub struct ToolHttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolHttpResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: String,
+}
+
+/// A single `allowed_hosts` / `denied_hosts` entry.
+#[derive(Debug, Clone, PartialEq)]
+enum HostRule {
+    Network(IpAddr, u8),
+    Domain(String),
+    DomainSuffix(String),
+}
+
+impl HostRule {
+    fn parse(rule: &str) -> Option<Self> {
+        let rule = rule.trim().to_lowercase();
+
+        if let Some((address, prefix)) = rule.split_once('/') {
+            let address: IpAddr = address.parse().ok()?;
+            let prefix: u8 = prefix.parse().ok()?;
+            let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+            return (prefix <= max_prefix).then_some(Self::Network(address, prefix));
+        }
+
+        if let Ok(address) = rule.parse::<IpAddr>() {
+            let prefix = if address.is_ipv4() { 32 } else { 128 };
+            return Some(Self::Network(address, prefix));
+        }
+
+        if let Some(suffix) = rule.strip_prefix("*.") {
+            return Some(Self::DomainSuffix(suffix.to_string()));
+        }
+
+        (!rule.is_empty()).then_some(Self::Domain(rule))
+    }
+
+    fn matches_domain(&self, domain: &str) -> bool {
+        match self {
+            Self::Domain(expected) => domain == expected,
+            Self::DomainSuffix(suffix) => domain
+                .strip_suffix(suffix.as_str())
+                .is_some_and(|rest| rest.ends_with('.')),
+            Self::Network(..) => false,
+        }
+    }
+
+    fn matches_ip(&self, ip: IpAddr) -> bool {
+        match self {
+            Self::Network(network, prefix) => ip_in_network(ip, *network, *prefix),
+            _ => false,
+        }
+    }
+}
+
+fn ip_in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+/// Sends API tool requests under a [`ToolExecutionPolicy`]. Every target address is
+/// checked after DNS resolution and the connection is pinned to the checked
+/// addresses, so a hostname can't be rebound to an internal address mid-request.
+pub struct ToolExecutionService;
+
+impl ToolExecutionService {
+    pub fn effective_timeout(policy: &ToolExecutionPolicy) -> Duration {
+        Duration::from_millis(policy.timeout_ms.clamp(1, MAX_TOOL_TIMEOUT_MS))
+    }
+
+    pub async fn authorize_target(
+        policy: &ToolExecutionPolicy,
+        url: &Url,
+    ) -> Result<Vec<SocketAddr>, ToolExecutionError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(ToolExecutionError::InvalidEndpoint {
+                message: format!("Unsupported scheme '{}'", url.scheme()),
+            });
+        }
+
+        let host = url
+            .host()
+            .ok_or_else(|| ToolExecutionError::InvalidEndpoint {
+                message: "Endpoint has no host".to_string(),
+            })?;
+        let port =
+            url.port_or_known_default()
+                .ok_or_else(|| ToolExecutionError::InvalidEndpoint {
+                    message: "Endpoint has no port".to_string(),
+                })?;
+        let host_name = host.to_string();
+
+        let allowed: Vec<HostRule> = policy
+            .allowed_hosts
+            .iter()
+            .filter_map(|rule| HostRule::parse(rule))
+            .collect();
+        let denied: Vec<HostRule> = policy
+            .denied_hosts
+            .iter()
+            .filter_map(|rule| HostRule::parse(rule))
+            .collect();
+
+        let not_allowed = |reason: &str| ToolExecutionError::HostNotAllowed {
+            host: host_name.clone(),
+            reason: reason.to_string(),
+        };
+
+        let addresses: Vec<IpAddr> = match &host {
+            Host::Ipv4(ip) => vec![IpAddr::V4(*ip)],
+            Host::Ipv6(ip) => vec![IpAddr::V6(*ip)],
+            Host::Domain(domain) => {
+                let domain = domain.to_lowercase();
+                if denied.iter().any(|rule| rule.matches_domain(&domain)) {
+                    return Err(not_allowed("host is on the tool's deny list"));
+                }
+
+                let lookup = tokio::time::timeout(
+                    DNS_LOOKUP_TIMEOUT,
+                    tokio::net::lookup_host((domain.as_str(), port)),
+                )
+                .await;
+
+                match lookup {
+                    Ok(Ok(resolved)) => resolved.map(|addr| addr.ip()).collect(),
+                    _ => Vec::new(),
+                }
+            }
+        };
+
+        if addresses.is_empty() {
+            return Err(ToolExecutionError::DnsResolutionFailed { host: host_name });
+        }
+
+        let domain_allowed = match &host {
+            Host::Domain(domain) => {
+                let domain = domain.to_lowercase();
+                allowed.iter().any(|rule| rule.matches_domain(&domain))
+            }
+            _ => false,
+        };
+
+        let blocked: Vec<HostRule> = BLOCKED_NETWORKS
+            .iter()
+            .filter_map(|network| HostRule::parse(network))
+            .collect();
+
+        for address in &addresses {
+            let address = canonical_ip(*address);
+            let explicitly_allowed = allowed.iter().any(|rule| rule.matches_ip(address));
+
+            if METADATA_ADDRESSES.contains(&address) {
+                return Err(not_allowed("cloud metadata endpoints are never reachable"));
+            }
+
+            if denied.iter().any(|rule| rule.matches_ip(address)) {
+                return Err(not_allowed("address is on the tool's deny list"));
+            }
+
+            if !allowed.is_empty() && !domain_allowed && !explicitly_allowed {
+                return Err(not_allowed("host is not on the tool's allow list"));
+            }
+
+            if blocked.iter().any(|rule| rule.matches_ip(address)) && !explicitly_allowed {
+                return Err(not_allowed("private and link-local addresses are blocked"));
+            }
+        }
+
+        Ok(addresses
+            .into_iter()
+            .map(|address| SocketAddr::new(address, port))
+            .collect())
+    }
+
+    pub async fn send(
+        policy: &ToolExecutionPolicy,
+        request: ToolHttpRequest,
+    ) -> Result<ToolHttpResponse, ToolExecutionError> {
+        let timeout = Self::effective_timeout(policy);
+        let timeout_ms = timeout.as_millis() as u64;
+
+        tokio::time::timeout(timeout, Self::send_checked(policy, request, timeout))
+            .await
+            .map_err(|_| ToolExecutionError::Timeout { timeout_ms })?
+    }
+
+    async fn send_checked(
+        policy: &ToolExecutionPolicy,
+        request: ToolHttpRequest,
+        timeout: Duration,
+    ) -> Result<ToolHttpResponse, ToolExecutionError> {
+        let url = Url::parse(&request.url).map_err(|e| ToolExecutionError::InvalidEndpoint {
+            message: e.to_string(),
+        })?;
+
+        let addresses = Self::authorize_target(policy, &url).await?;
+
+        // Redirects are refused because the redirect target never went through the policy
+        let mut client_builder = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none());
+
+        if let Some(Host::Domain(domain)) = url.host() {
+            client_builder = client_builder.resolve_to_addrs(domain, &addresses);
+        }
+
+        let client = client_builder
+            .build()
+            .map_err(|e| ToolExecutionError::RequestFailed {
+                message: e.to_string(),
+            })?;
+
+        let method = match request.method {
+            HttpMethod::GET => reqwest::Method::GET,
+            HttpMethod::POST => reqwest::Method::POST,
+            HttpMethod::PUT => reqwest::Method::PUT,
+            HttpMethod::DELETE => reqwest::Method::DELETE,
+            HttpMethod::PATCH => reqwest::Method::PATCH,
+        };
+
+        let mut request_builder = client.request(method, url);
+        for (name, value) in &request.headers {
+            request_builder = request_builder.header(name, value);
+        }
+        if let Some(body) = &request.body {
+            request_builder = request_builder.json(body);
+        }
+
+        let timeout_ms = timeout.as_millis() as u64;
+        let map_request_error = |e: reqwest::Error| {
+            if e.is_timeout() {
+                ToolExecutionError::Timeout { timeout_ms }
+            } else {
+                ToolExecutionError::RequestFailed {
+                    message: e.to_string(),
+                }
+            }
+        };
+
+        let mut response = request_builder.send().await.map_err(map_request_error)?;
+        let status = response.status().as_u16();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        if response
+            .content_length()
+            .is_some_and(|length| length > policy.max_response_bytes)
+        {
+            return Err(ToolExecutionError::ResponseTooLarge {
+                limit_bytes: policy.max_response_bytes,
+            });
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(map_request_error)? {
+            if (body.len() + chunk.len()) as u64 > policy.max_response_bytes {
+                return Err(ToolExecutionError::ResponseTooLarge {
+                    limit_bytes: policy.max_response_bytes,
+                });
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(ToolHttpResponse {
+            status,
+            content_type,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    fn policy() -> ToolExecutionPolicy {
+        ToolExecutionPolicy::default()
+    }
+
+    #[tokio::test]
+    async fn rejects_cloud_metadata_endpoint() {
+        let url = Url::parse("http://169.254.169.254/latest/meta-data/").unwrap();
+
+        let result = ToolExecutionService::authorize_target(&policy(), &url).await;
+
+        assert!(matches!(
+            result,
+            Err(ToolExecutionError::HostNotAllowed { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn metadata_endpoint_cannot_be_allow_listed() {
+        let mut policy = policy();
+        policy.allowed_hosts = vec!["169.254.0.0/16".to_string()];
+        let url = Url::parse("http://169.254.169.254/latest/meta-data/").unwrap();
+
+        let result = ToolExecutionService::authorize_target(&policy, &url).await;
+
+        assert!(matches!(
+            result,
+            Err(ToolExecutionError::HostNotAllowed { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_private_networks_by_default() {
+        for target in [
+            "http://10.1.2.3/",
+            "http://172.20.0.1/",
+            "http://192.168.1.1/",
+            "http://127.0.0.1:8080/",
+            "http://[::ffff:10.0.0.1]/",
+            "http://198.18.0.1/",
+            "http://224.0.0.1/",
+            "http://240.0.0.1/",
+            "http://[64:ff9b::a00:1]/",
+            "http://[2002:a00:1::1]/",
+        ] {
+            let url = Url::parse(target).unwrap();
+            let result = ToolExecutionService::authorize_target(&policy(), &url).await;
+            assert!(
+                matches!(result, Err(ToolExecutionError::HostNotAllowed { .. })),
+                "{target} should be rejected"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn enforces_deny_and_allow_lists() {
+        let mut policy = policy();
+        policy.denied_hosts = vec!["8.8.8.8".to_string()];
+        let url = Url::parse("https://8.8.8.8/").unwrap();
+        assert!(
+            ToolExecutionService::authorize_target(&policy, &url)
+                .await
+                .is_err()
+        );
+
+        let mut policy = self::policy();
+        policy.allowed_hosts = vec!["1.1.1.0/24".to_string()];
+        let allowed = Url::parse("https://1.1.1.1/").unwrap();
+        let other = Url::parse("https://8.8.4.4/").unwrap();
+        assert!(
+            ToolExecutionService::authorize_target(&policy, &allowed)
+                .await
+                .is_ok()
+        );
+        assert!(
+            ToolExecutionService::authorize_target(&policy, &other)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn matches_wildcard_domains() {
+        let rule = HostRule::parse("*.example.com").unwrap();
+        assert!(rule.matches_domain("api.example.com"));
+        assert!(!rule.matches_domain("example.com"));
+        assert!(!rule.matches_domain("badexample.com"));
+    }
+
+    #[tokio::test]
+    async fn cuts_off_slow_endpoint_at_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buffer = [0u8; 1024];
+                    let _ = socket.read(&mut buffer).await;
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                });
+            }
+        });
+
+        let mut policy = policy();
+        policy.timeout_ms = 200;
+        policy.allowed_hosts = vec!["127.0.0.1/32".to_string()];
+
+        let started = std::time::Instant::now();
+        let result = ToolExecutionService::send(
+            &policy,
+            ToolHttpRequest {
+                method: HttpMethod::GET,
+                url: format!("http://{}/slow", address),
+                headers: Vec::new(),
+                body: None,
+            },
+        )
+        .await;
+
+        assert_eq!(
+            result.unwrap_err(),
+            ToolExecutionError::Timeout { timeout_ms: 200 }
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
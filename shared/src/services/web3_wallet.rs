@@ -0,0 +1,648 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, SecondsFormat, SubsecRound, Utc};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    error::{AppError, ErrorCode},
+    models::Web3SignInChallenge,
+    services::{ExpiringStore, RedisExpiringStore, RedisPool},
+    utils::{
+        clock::{Clock, SystemClock},
+        wallet_address::{keccak256, normalize_wallet_address},
+    },
+};
+
+/// How long a wallet has to sign a challenge.
+const CHALLENGE_TTL_MINUTES: i64 = 10;
+
+const SIGN_IN_STATEMENT: &str = "Sign in with your wallet.";
+
+pub type RecoverSignerFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<String>, AppError>> + Send + 'a>>;
+
+/// Recovers who signed a message. Implementations only check signatures;
+/// challenges and nonces are handled by `Web3WalletService`.
+pub trait Web3SignatureVerifier: Send + Sync {
+    /// The address whose key made `signature`, an EIP-191 `personal_sign`
+    /// signature of `message`, or `None` when the signature is malformed.
+    fn recover_signer<'a>(
+        &'a self,
+        message: &'a str,
+        signature: &'a str,
+    ) -> RecoverSignerFuture<'a>;
+}
+
+/// Recovers signers in process, the way `ecrecover` does: the message is
+/// hashed with the EIP-191 prefix, the public key is recovered from the
+/// 65-byte `r || s || v` signature, and the address is the last 20 bytes of
+/// the key's Keccak-256 hash.
+#[derive(Debug, Default)]
+pub struct LocalWeb3SignatureVerifier;
+
+impl LocalWeb3SignatureVerifier {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn recover(message: &str, signature: &str) -> Option<String> {
+        let signature = signature.trim();
+        let bytes = hex::decode(signature.strip_prefix("0x").unwrap_or(signature)).ok()?;
+        let [rs @ .., v]: [u8; 65] = bytes.try_into().ok()?;
+
+        // Wallets send v as 27/28, some libraries as 0/1.
+        let v = match v {
+            27 | 28 => v - 27,
+            0 | 1 => v,
+            _ => return None,
+        };
+        let mut recovery_id = RecoveryId::from_byte(v)?;
+        let mut signature = Signature::from_slice(&rs).ok()?;
+        // `ecrecover` takes high-s signatures too; negating s flips the parity
+        // of the recovered point.
+        if let Some(normalized) = signature.normalize_s() {
+            signature = normalized;
+            recovery_id = RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced());
+        }
+
+        let prefixed = format!("\x19Ethereum Signed Message:\n{}{}", message.len(), message);
+        let key = VerifyingKey::recover_from_prehash(
+            &keccak256(prefixed.as_bytes()),
+            &signature,
+            recovery_id,
+        )
+        .ok()?;
+
+        let public_key = key.to_encoded_point(false);
+        let hash = keccak256(&public_key.as_bytes()[1..]);
+        normalize_wallet_address(&hex::encode(&hash[12..]))
+    }
+}
+
+impl Web3SignatureVerifier for LocalWeb3SignatureVerifier {
+    fn recover_signer<'a>(
+        &'a self,
+        message: &'a str,
+        signature: &'a str,
+    ) -> RecoverSignerFuture<'a> {
+        Box::pin(async move { Ok(Self::recover(message, signature)) })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Web3VerifierConfig {
+    pub url: String,
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RecoverResponse {
+    address: String,
+}
+
+/// Posts the message and signature to a signature recovery service, which
+/// answers with the signer's address, or a 4xx status when the signature is
+/// malformed. For setups that keep recovery out of process.
+pub struct Web3VerifierApi {
+    client: reqwest::Client,
+    config: Web3VerifierConfig,
+}
+
+impl Web3VerifierApi {
+    pub fn new(config: Web3VerifierConfig) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            config,
+        }
+    }
+}
+
+impl Web3SignatureVerifier for Web3VerifierApi {
+    fn recover_signer<'a>(
+        &'a self,
+        message: &'a str,
+        signature: &'a str,
+    ) -> RecoverSignerFuture<'a> {
+        Box::pin(async move {
+            let mut request = self
+                .client
+                .post(&self.config.url)
+                .json(&json!({ "message": message, "signature": signature }));
+            if let Some(token) = &self.config.token {
+                request = request.bearer_auth(token);
+            }
+
+            let response = request
+                .send()
+                .await
+                .map_err(|e| AppError::External(e.to_string().into()))?;
+            if response.status().is_client_error() {
+                return Ok(None);
+            }
+
+            let response: RecoverResponse = response
+                .error_for_status()
+                .map_err(|e| AppError::External(e.to_string().into()))?
+                .json()
+                .await
+                .map_err(|e| AppError::External(e.to_string().into()))?;
+
+            Ok(Some(response.address))
+        })
+    }
+}
+
+/// Answers from a fixed table of signatures, for tests. Signatures it
+/// doesn't know are treated as malformed.
+#[derive(Default)]
+pub struct StubWeb3SignatureVerifier {
+    signers: HashMap<(String, String), String>,
+}
+
+impl StubWeb3SignatureVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_signature(mut self, message: &str, signature: &str, signer: &str) -> Self {
+        self.signers.insert(
+            (message.to_string(), signature.to_string()),
+            signer.to_string(),
+        );
+        self
+    }
+}
+
+impl Web3SignatureVerifier for StubWeb3SignatureVerifier {
+    fn recover_signer<'a>(
+        &'a self,
+        message: &'a str,
+        signature: &'a str,
+    ) -> RecoverSignerFuture<'a> {
+        Box::pin(async move {
+            Ok(self
+                .signers
+                .get(&(message.to_string(), signature.to_string()))
+                .cloned())
+        })
+    }
+}
+
+/// The Sign-In with Ethereum (EIP-4361) message for a challenge. Timestamps
+/// are kept to the second so the message can be rebuilt exactly from what
+/// the challenge stores.
+pub fn siwe_message(
+    domain: &str,
+    address: &str,
+    chain_id: i64,
+    nonce: &str,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+) -> String {
+    format!(
+        "{domain} wants you to sign in with your Ethereum account:\n\
+         {address}\n\
+         \n\
+         {SIGN_IN_STATEMENT}\n\
+         \n\
+         URI: https://{domain}\n\
+         Version: 1\n\
+         Chain ID: {chain_id}\n\
+         Nonce: {nonce}\n\
+         Issued At: {}\n\
+         Expiration Time: {}",
+        issued_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+    )
+}
+
+fn generate_nonce() -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    (0..20)
+        .map(|_| char::from(ALPHABET[rand::random_range(0..ALPHABET.len())]))
+        .collect()
+}
+
+fn challenge_expired() -> AppError {
+    AppError::coded(
+        ErrorCode::Web3ChallengeExpired,
+        "The sign-in message has expired or was already used. Request a new one.",
+    )
+}
+
+/// Issues sign-in challenges for wallets and checks the signed ones. A
+/// challenge is only kept as its issue time, keyed by the deployment, chain,
+/// address and nonce, and the message is rebuilt from those when the
+/// signature comes back. Each challenge can be answered once.
+#[derive(Clone)]
+pub struct Web3WalletService {
+    store: Arc<dyn ExpiringStore>,
+    verifier: Arc<dyn Web3SignatureVerifier>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Web3WalletService {
+    pub fn new(
+        store: Arc<dyn ExpiringStore>,
+        verifier: Arc<dyn Web3SignatureVerifier>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            store,
+            verifier,
+            clock,
+        }
+    }
+
+    /// Keeps challenges in Redis and recovers signers in process.
+    pub fn from_redis(redis: RedisPool) -> Self {
+        Self::new(
+            Arc::new(RedisExpiringStore::new(redis)),
+            Arc::new(LocalWeb3SignatureVerifier::new()),
+            Arc::new(SystemClock),
+        )
+    }
+
+    fn challenge_key(deployment_id: i64, chain_id: i64, address: &str, nonce: &str) -> String {
+        format!(
+            "web3_challenge:{}:{}:{}:{}",
+            deployment_id, chain_id, address, nonce
+        )
+    }
+
+    /// Issues a challenge for `address`, which must already be normalized,
+    /// to sign on `domain`.
+    pub async fn issue_challenge(
+        &self,
+        deployment_id: i64,
+        domain: &str,
+        address: &str,
+        chain_id: i64,
+    ) -> Result<Web3SignInChallenge, AppError> {
+        let nonce = generate_nonce();
+        let ttl = Duration::minutes(CHALLENGE_TTL_MINUTES);
+        let issued_at = self.clock.now().trunc_subsecs(0);
+        let expires_at = issued_at + ttl;
+
+        self.store
+            .set(
+                &Self::challenge_key(deployment_id, chain_id, address, &nonce),
+                issued_at.timestamp(),
+                ttl,
+            )
+            .await?;
+
+        Ok(Web3SignInChallenge {
+            address: address.to_string(),
+            chain_id,
+            message: siwe_message(domain, address, chain_id, &nonce, issued_at, expires_at),
+            nonce,
+            issued_at,
+            expires_at,
+        })
+    }
+
+    /// Checks that `address` signed the challenge issued with `nonce`, and
+    /// uses the challenge up whatever the outcome. Fails with
+    /// `web3_challenge_expired` when there's no such challenge and
+    /// `invalid_web3_signature` when someone else signed it.
+    pub async fn verify(
+        &self,
+        deployment_id: i64,
+        domain: &str,
+        address: &str,
+        chain_id: i64,
+        nonce: &str,
+        signature: &str,
+    ) -> Result<(), AppError> {
+        if nonce.is_empty() || nonce.len() > 64 || !nonce.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(challenge_expired());
+        }
+
+        let key = Self::challenge_key(deployment_id, chain_id, address, nonce);
+        let Some(issued_at) = self.store.get(&key).await? else {
+            return Err(challenge_expired());
+        };
+
+        // Only the first answer to a challenge is checked, even when several
+        // arrive at once.
+        let claim_key = format!("{}:claimed", key);
+        let claims = self
+            .store
+            .increment(&claim_key, Duration::minutes(CHALLENGE_TTL_MINUTES))
+            .await?;
+        if claims > 1 {
+            return Err(challenge_expired());
+        }
+        self.store.delete(&[key, claim_key]).await?;
+
+        let issued_at = DateTime::from_timestamp(issued_at, 0).ok_or_else(challenge_expired)?;
+        let message = siwe_message(
+            domain,
+            address,
+            chain_id,
+            nonce,
+            issued_at,
+            issued_at + Duration::minutes(CHALLENGE_TTL_MINUTES),
+        );
+
+        let signer = self
+            .verifier
+            .recover_signer(&message, signature)
+            .await?
+            .and_then(|signer| normalize_wallet_address(&signer));
+        if signer.as_deref() != Some(address) {
+            return Err(AppError::coded(
+                ErrorCode::InvalidWeb3Signature,
+                "The signature wasn't made by this wallet",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::MemoryExpiringStore;
+    use crate::utils::clock::FakeClock;
+
+    const ADDRESS: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+    const OTHER_ADDRESS: &str = "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359";
+
+    fn service(clock: &Arc<FakeClock>, verifier: StubWeb3SignatureVerifier) -> Web3WalletService {
+        let (store, clock) = MemoryExpiringStore::with_fake_clock(clock);
+        Web3WalletService::new(store, Arc::new(verifier), clock)
+    }
+
+    /// The account from the web3.js `accounts.sign` documentation.
+    const SIGNING_KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    const SIGNING_ADDRESS: &str = "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23";
+
+    /// `personal_sign` as a wallet does it, with v as 27/28.
+    fn personal_sign(message: &str) -> String {
+        let key = k256::ecdsa::SigningKey::from_slice(&hex::decode(SIGNING_KEY).unwrap()).unwrap();
+        let prefixed = format!("\x19Ethereum Signed Message:\n{}{}", message.len(), message);
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(&keccak256(prefixed.as_bytes()))
+            .unwrap();
+
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        format!("0x{}", hex::encode(bytes))
+    }
+
+    fn error_code(error: AppError) -> ErrorCode {
+        match error {
+            AppError::Coded { code, .. } => code,
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn challenges_are_siwe_messages() {
+        let clock = FakeClock::new();
+        let service = service(&clock, StubWeb3SignatureVerifier::new());
+
+        let challenge = service
+            .issue_challenge(1, "app.example.com", ADDRESS, 1)
+            .await
+            .unwrap();
+
+        assert_eq!(challenge.nonce.len(), 20);
+        assert_eq!(
+            challenge.message,
+            format!(
+                "app.example.com wants you to sign in with your Ethereum account:\n\
+                 {ADDRESS}\n\
+                 \n\
+                 Sign in with your wallet.\n\
+                 \n\
+                 URI: https://app.example.com\n\
+                 Version: 1\n\
+                 Chain ID: 1\n\
+                 Nonce: {}\n\
+                 Issued At: 2023-11-14T22:13:20Z\n\
+                 Expiration Time: 2023-11-14T22:23:20Z",
+                challenge.nonce
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn a_signed_challenge_verifies_once() {
+        let clock = FakeClock::new();
+        let unsigned = service(&clock, StubWeb3SignatureVerifier::new());
+        let challenge = unsigned
+            .issue_challenge(1, "app.example.com", ADDRESS, 1)
+            .await
+            .unwrap();
+
+        let service = Web3WalletService {
+            verifier: Arc::new(StubWeb3SignatureVerifier::new().with_signature(
+                &challenge.message,
+                "0xsigned",
+                &ADDRESS.to_lowercase(),
+            )),
+            ..unsigned
+        };
+        clock.advance(60);
+
+        service
+            .verify(
+                1,
+                "app.example.com",
+                ADDRESS,
+                1,
+                &challenge.nonce,
+                "0xsigned",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            error_code(
+                service
+                    .verify(
+                        1,
+                        "app.example.com",
+                        ADDRESS,
+                        1,
+                        &challenge.nonce,
+                        "0xsigned"
+                    )
+                    .await
+                    .unwrap_err()
+            ),
+            ErrorCode::Web3ChallengeExpired
+        );
+    }
+
+    #[tokio::test]
+    async fn other_signers_and_other_chains_are_rejected() {
+        let clock = FakeClock::new();
+        let unsigned = service(&clock, StubWeb3SignatureVerifier::new());
+        let challenge = unsigned
+            .issue_challenge(1, "app.example.com", ADDRESS, 1)
+            .await
+            .unwrap();
+        let service = Web3WalletService {
+            verifier: Arc::new(StubWeb3SignatureVerifier::new().with_signature(
+                &challenge.message,
+                "0xsigned",
+                OTHER_ADDRESS,
+            )),
+            ..unsigned
+        };
+
+        assert_eq!(
+            error_code(
+                service
+                    .verify(
+                        1,
+                        "app.example.com",
+                        ADDRESS,
+                        137,
+                        &challenge.nonce,
+                        "0xsigned"
+                    )
+                    .await
+                    .unwrap_err()
+            ),
+            ErrorCode::Web3ChallengeExpired
+        );
+        assert_eq!(
+            error_code(
+                service
+                    .verify(
+                        1,
+                        "app.example.com",
+                        ADDRESS,
+                        1,
+                        &challenge.nonce,
+                        "0xsigned"
+                    )
+                    .await
+                    .unwrap_err()
+            ),
+            ErrorCode::InvalidWeb3Signature
+        );
+    }
+
+    #[tokio::test]
+    async fn challenges_expire() {
+        let clock = FakeClock::new();
+        let service = service(&clock, StubWeb3SignatureVerifier::new());
+        let challenge = service
+            .issue_challenge(1, "app.example.com", ADDRESS, 1)
+            .await
+            .unwrap();
+        clock.advance(CHALLENGE_TTL_MINUTES * 60);
+
+        assert_eq!(
+            error_code(
+                service
+                    .verify(
+                        1,
+                        "app.example.com",
+                        ADDRESS,
+                        1,
+                        &challenge.nonce,
+                        "0xsigned"
+                    )
+                    .await
+                    .unwrap_err()
+            ),
+            ErrorCode::Web3ChallengeExpired
+        );
+    }
+
+    #[tokio::test]
+    async fn local_verifier_recovers_personal_sign_signers() {
+        let verifier = LocalWeb3SignatureVerifier::new();
+
+        // From the web3.js documentation, signed by SIGNING_KEY.
+        let signer = verifier
+            .recover_signer(
+                "Some data",
+                "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c",
+            )
+            .await
+            .unwrap();
+        assert_eq!(signer.as_deref(), Some(SIGNING_ADDRESS));
+
+        let signature = personal_sign("Hello, wallet");
+        let signer = verifier
+            .recover_signer("Hello, wallet", &signature)
+            .await
+            .unwrap();
+        assert_eq!(signer.as_deref(), Some(SIGNING_ADDRESS));
+
+        let signer = verifier
+            .recover_signer("Hello, someone else", &signature)
+            .await
+            .unwrap();
+        assert_ne!(signer.as_deref(), Some(SIGNING_ADDRESS));
+    }
+
+    #[tokio::test]
+    async fn local_verifier_treats_malformed_signatures_as_unsigned() {
+        let verifier = LocalWeb3SignatureVerifier::new();
+        let signature = personal_sign("Hello, wallet");
+
+        for malformed in [
+            "0xsigned".to_string(),
+            signature[..signature.len() - 2].to_string(),
+            format!("{}1d", &signature[..signature.len() - 2]),
+            format!("0x{}", "00".repeat(65)),
+        ] {
+            assert_eq!(
+                verifier
+                    .recover_signer("Hello, wallet", &malformed)
+                    .await
+                    .unwrap(),
+                None,
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn challenges_signed_by_the_wallet_verify_locally() {
+        let clock = FakeClock::new();
+        let (store, store_clock) = MemoryExpiringStore::with_fake_clock(&clock);
+        let service = Web3WalletService::new(
+            store,
+            Arc::new(LocalWeb3SignatureVerifier::new()),
+            store_clock,
+        );
+
+        let challenge = service
+            .issue_challenge(1, "app.example.com", SIGNING_ADDRESS, 1)
+            .await
+            .unwrap();
+        let signature = personal_sign(&challenge.message);
+
+        service
+            .verify(
+                1,
+                "app.example.com",
+                SIGNING_ADDRESS,
+                1,
+                &challenge.nonce,
+                &signature,
+            )
+            .await
+            .unwrap();
+    }
+}
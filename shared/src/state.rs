@@ -1,15 +1,20 @@
-use std::str::FromStr;
-
-use aws_config::Region;
-use aws_sdk_s3::Client as S3Client;
+use std::sync::Arc;
 
 use redis::Client as RedisClient;
 use sqlx::{PgPool, postgres::PgPoolOptions};
 
 use crate::{
     services::{
-        ClickHouseService, CloudflareService, DnsVerificationService, EmbeddingService,
-        PostmarkService, TextProcessingService,
+        AgentModelService, AuthEventBuffer, BackgroundTasks, CacheInvalidator,
+        ClickHouseBufferConfig, ClickHouseService, CloudflareApi, CloudflareService,
+        CompromisedPasswordService, CredentialCipher, DeploymentSettingsCache,
+        DeploymentSettingsCacheConfig, DisposableDomainConfig, DisposableDomainService,
+        DnsVerificationService, DnsVerifier, DomainsConfig, EmailDomainApi, EmbeddingService,
+        GeoIpConfig, GeoIpService, HealthConfig, HealthService, IdGenerator, InvitationTokenSigner,
+        MagicLinkTokenSigner, OtpService, PhoneIntelligenceService, PostmarkService,
+        RateLimitConfig, RateLimitService, RedisConfig, RedisPool, RequestLogBuffer, SchemaConfig,
+        SignInLockoutService, SmtpService, StorageService, TextProcessingService, UsageEventBuffer,
+        Web3WalletService, ensure_schema,
     },
     utils::handlebars_helpers,
 };
@@ -17,16 +22,46 @@ use crate::{
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: PgPool,
-    pub s3_client: S3Client,
-    pub sf: sonyflake::Sonyflake,
-    pub redis_client: RedisClient,
+    /// A Postgres read replica, from `DATABASE_READ_URL`. Queries opt in
+    /// through [`Query::pool`](crate::queries::Query::pool).
+    pub read_pool: Option<PgPool>,
+    /// Buckets for files, by storage class.
+    pub storage: StorageService,
+    pub sf: IdGenerator,
+    /// The instance's shared Redis connection.
+    pub redis: RedisPool,
     pub handlebars: handlebars::Handlebars<'static>,
-    pub cloudflare_service: CloudflareService,
+    pub cloudflare_service: Arc<dyn CloudflareApi>,
+    /// Sends the platform's own mail.
     pub postmark_service: PostmarkService,
-    pub dns_verification_service: DnsVerificationService,
+    /// Registers deployments' sending domains; Postmark in production.
+    pub email_domain_service: Arc<dyn EmailDomainApi>,
+    /// Pooled connections to deployments' own SMTP servers.
+    pub smtp_service: SmtpService,
+    pub credential_cipher: CredentialCipher,
+    pub dns_verification_service: Arc<dyn DnsVerifier>,
     pub embedding_service: EmbeddingService,
+    pub agent_model_service: AgentModelService,
     pub text_processing_service: TextProcessingService,
     pub clickhouse_service: ClickHouseService,
+    pub auth_event_buffer: AuthEventBuffer,
+    pub request_log_buffer: RequestLogBuffer,
+    pub usage_event_buffer: UsageEventBuffer,
+    pub rate_limit_service: RateLimitService,
+    pub disposable_domain_service: DisposableDomainService,
+    pub phone_intelligence_service: PhoneIntelligenceService,
+    pub geoip_service: GeoIpService,
+    pub sign_in_lockout_service: SignInLockoutService,
+    pub otp_service: OtpService,
+    pub web3_wallet_service: Web3WalletService,
+    pub compromised_password_service: CompromisedPasswordService,
+    pub invitation_token_signer: InvitationTokenSigner,
+    pub magic_link_token_signer: MagicLinkTokenSigner,
+    pub health_service: HealthService,
+    pub background_tasks: BackgroundTasks,
+    pub deployment_settings_cache: DeploymentSettingsCache,
+    pub cache_invalidator: CacheInvalidator,
+    pub domains: DomainsConfig,
 }
 
 impl AppState {
@@ -40,38 +75,49 @@ impl AppState {
 
         println!("Database connected");
 
-        let r2_endpoint_url =
-            std::env::var("R2_ENDPOINT_URL").expect("R2_ENDPOINT_URL must be set");
-        let r2_access_key_id =
-            std::env::var("R2_ACCESS_KEY_ID").expect("R2_ACCESS_KEY_ID must be set");
-        let r2_secret_access_key =
-            std::env::var("R2_SECRET_ACCESS_KEY").expect("R2_SECRET_ACCESS_KEY must be set");
-
-        let s3_client = S3Client::new(
-            &aws_config::from_env()
-                .endpoint_url(r2_endpoint_url)
-                .credentials_provider(aws_sdk_s3::config::Credentials::new(
-                    r2_access_key_id,
-                    r2_secret_access_key,
-                    None,
-                    None,
-                    "R2",
-                ))
-                .region(Region::new("auto"))
-                .load()
-                .await,
+        let schema_version = ensure_schema(&pool, &SchemaConfig::from_env())
+            .await
+            .unwrap_or_else(|e| panic!("Refusing to start: {}", e));
+        tracing::info!(
+            applied = schema_version.applied,
+            expected = schema_version.expected,
+            "Database schema checked"
         );
 
-        let sf = sonyflake::Sonyflake::builder()
-            .start_time(
-                chrono::DateTime::<chrono::Utc>::from_str("2025-01-01 00:00:00+0000").unwrap(),
-            )
-            .finalize()
-            .expect("Failed to create Sonyflake");
+        let read_pool = match std::env::var("DATABASE_READ_URL") {
+            Ok(read_url) if !read_url.is_empty() => match PgPoolOptions::new()
+                .max_connections(5)
+                .connect(&read_url)
+                .await
+            {
+                Ok(read_pool) => {
+                    println!("Read replica connected");
+                    Some(read_pool)
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to connect to read replica, using the primary: {}",
+                        e
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        let storage = StorageService::from_env().await;
 
         let redis_client =
             RedisClient::open(std::env::var("REDIS_URL").expect("REDIS_URL must be set"))
                 .expect("Failed to create Redis client");
+        let redis = RedisPool::new(redis_client, RedisConfig::from_env());
+
+        let sf = IdGenerator::from_env(redis.clone())
+            .await
+            .expect("Failed to create ID generator");
+        tracing::info!(worker_id = sf.worker_id(), "Snowflake worker id assigned");
+
+        let rate_limit_service = RateLimitService::new(redis.clone(), RateLimitConfig::from_env());
 
         let mut handlebars = handlebars::Handlebars::new();
 
@@ -81,7 +127,10 @@ impl AppState {
             std::env::var("CLOUDFLARE_API_KEY").expect("CLOUDFLARE_API_KEY must be set");
         let cloudflare_zone_id =
             std::env::var("CLOUDFLARE_ZONE_ID").expect("CLOUDFLARE_ZONE_ID must be set");
-        let cloudflare_service = CloudflareService::new(cloudflare_api_key, cloudflare_zone_id);
+        let cloudflare_service: Arc<dyn CloudflareApi> = Arc::new(CloudflareService::new(
+            cloudflare_api_key,
+            cloudflare_zone_id,
+        ));
 
         let postmark_account_token =
             std::env::var("POSTMARK_ACCOUNT_TOKEN").expect("POSTMARK_ACCOUNT_TOKEN must be set");
@@ -89,7 +138,10 @@ impl AppState {
             std::env::var("POSTMARK_SERVER_TOKEN").expect("POSTMARK_SERVER_TOKEN must be set");
         let postmark_service = PostmarkService::new(postmark_account_token, postmark_server_token);
 
-        let dns_verification_service = DnsVerificationService::new();
+        let email_domain_service: Arc<dyn EmailDomainApi> = Arc::new(postmark_service.clone());
+
+        let dns_verification_service: Arc<dyn DnsVerifier> =
+            Arc::new(DnsVerificationService::new());
 
         let text_processing_service = TextProcessingService::new();
 
@@ -108,18 +160,226 @@ impl AppState {
 
         println!("ClickHouse service initialized");
 
+        let auth_event_buffer = AuthEventBuffer::spawn(
+            clickhouse_service.clone(),
+            ClickHouseBufferConfig::from_env("AUTH_EVENT"),
+        );
+
+        let request_log_buffer = RequestLogBuffer::spawn(
+            clickhouse_service.clone(),
+            ClickHouseBufferConfig::from_env("REQUEST_LOG"),
+        );
+
+        let usage_event_buffer = UsageEventBuffer::spawn(
+            clickhouse_service.clone(),
+            ClickHouseBufferConfig::from_env("USAGE_EVENT"),
+        );
+
+        let disposable_domain_service =
+            DisposableDomainService::spawn(pool.clone(), DisposableDomainConfig::from_env());
+
+        let phone_intelligence_service = PhoneIntelligenceService::from_env(redis.clone());
+
+        let geoip_service = GeoIpService::spawn(GeoIpConfig::from_env());
+
+        let sign_in_lockout_service = SignInLockoutService::from_redis(redis.clone());
+
+        let otp_service = OtpService::from_env(redis.clone());
+
+        let web3_wallet_service = Web3WalletService::from_redis(redis.clone());
+
+        let compromised_password_service = CompromisedPasswordService::from_env();
+
+        let invitation_token_signer = InvitationTokenSigner::from_env();
+
+        let magic_link_token_signer = MagicLinkTokenSigner::from_env();
+
+        let health_service = HealthService::new(
+            pool.clone(),
+            redis.clone(),
+            clickhouse_service.clone(),
+            HealthConfig::from_env(),
+        );
+
+        let deployment_settings_cache =
+            DeploymentSettingsCache::new(redis.clone(), DeploymentSettingsCacheConfig::from_env());
+        let cache_invalidator = deployment_settings_cache.invalidator();
+
         Self {
             db_pool: pool,
-            s3_client,
+            read_pool,
+            storage,
             sf,
-            redis_client,
+            redis,
             handlebars,
             cloudflare_service,
             postmark_service,
+            email_domain_service,
+            smtp_service: SmtpService::new(),
+            credential_cipher: CredentialCipher::from_env(),
             dns_verification_service,
             embedding_service,
+            agent_model_service: AgentModelService::from_env(),
             text_processing_service,
             clickhouse_service,
+            auth_event_buffer,
+            request_log_buffer,
+            usage_event_buffer,
+            rate_limit_service,
+            disposable_domain_service,
+            phone_intelligence_service,
+            geoip_service,
+            sign_in_lockout_service,
+            otp_service,
+            web3_wallet_service,
+            compromised_password_service,
+            invitation_token_signer,
+            magic_link_token_signer,
+            health_service,
+            background_tasks: BackgroundTasks::new(),
+            deployment_settings_cache,
+            cache_invalidator,
+            domains: DomainsConfig::from_env(),
+        }
+    }
+}
+
+/// Builds an [`AppState`] whose providers are in-memory fakes, for tests of
+/// commands that call out to them. Postgres and Redis clients are created
+/// but never connected, so only code paths that skip the database can run.
+#[cfg(test)]
+pub(crate) struct TestAppStateBuilder {
+    cloudflare_service: Arc<dyn CloudflareApi>,
+    email_domain_service: Arc<dyn EmailDomainApi>,
+    dns_verification_service: Arc<dyn DnsVerifier>,
+    storage: Arc<dyn crate::services::ObjectStorage>,
+}
+
+#[cfg(test)]
+impl TestAppStateBuilder {
+    pub(crate) fn new() -> Self {
+        use crate::services::{
+            FakeCloudflare, FakeDnsVerifier, FakeEmailDomains, MemoryObjectStorage,
+        };
+
+        Self {
+            cloudflare_service: Arc::new(FakeCloudflare::new()),
+            email_domain_service: Arc::new(FakeEmailDomains::new()),
+            dns_verification_service: Arc::new(FakeDnsVerifier::new()),
+            storage: Arc::new(MemoryObjectStorage::new()),
+        }
+    }
+
+    pub(crate) fn with_cloudflare(mut self, cloudflare_service: Arc<dyn CloudflareApi>) -> Self {
+        self.cloudflare_service = cloudflare_service;
+        self
+    }
+
+    pub(crate) fn with_email_domains(
+        mut self,
+        email_domain_service: Arc<dyn EmailDomainApi>,
+    ) -> Self {
+        self.email_domain_service = email_domain_service;
+        self
+    }
+
+    /// Must be called inside a Tokio runtime, as the buffers spawn their
+    /// flush tasks.
+    pub(crate) fn build(self) -> AppState {
+        use crate::{
+            services::{
+                IdGeneratorConfig, MemoryExpiringStore, NoopGeoIp, StubBreachedPasswords,
+                StubPhoneIntelligence, StubWeb3SignatureVerifier,
+            },
+            utils::clock::{Clock, SystemClock},
+        };
+
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://test@127.0.0.1:1/test")
+            .expect("Failed to create lazy pool");
+        let redis = RedisPool::new(
+            RedisClient::open("redis://127.0.0.1:1/").expect("Failed to create Redis client"),
+            RedisConfig::default(),
+        );
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        let clickhouse_service = ClickHouseService::new("http://127.0.0.1:1", "")
+            .expect("Failed to create ClickHouse service");
+        let deployment_settings_cache =
+            DeploymentSettingsCache::new(redis.clone(), DeploymentSettingsCacheConfig::default());
+        let cache_invalidator = deployment_settings_cache.invalidator();
+
+        AppState {
+            db_pool: pool.clone(),
+            read_pool: None,
+            storage: StorageService::new(self.storage.clone(), self.storage.clone(), self.storage),
+            sf: IdGenerator::with_worker_id(1, &IdGeneratorConfig::default(), clock.clone()),
+            redis: redis.clone(),
+            handlebars: handlebars::Handlebars::new(),
+            cloudflare_service: self.cloudflare_service,
+            postmark_service: PostmarkService::new(String::new(), String::new()),
+            email_domain_service: self.email_domain_service,
+            smtp_service: SmtpService::new(),
+            credential_cipher: CredentialCipher::new("test", &[0u8; 32]),
+            dns_verification_service: self.dns_verification_service,
+            embedding_service: EmbeddingService::with_api_key(String::new(), String::new()),
+            agent_model_service: AgentModelService::new(
+                "http://127.0.0.1:1".to_string(),
+                None,
+                String::new(),
+            ),
+            text_processing_service: TextProcessingService::new(),
+            clickhouse_service: clickhouse_service.clone(),
+            auth_event_buffer: AuthEventBuffer::spawn(
+                clickhouse_service.clone(),
+                ClickHouseBufferConfig::default(),
+            ),
+            request_log_buffer: RequestLogBuffer::spawn(
+                clickhouse_service.clone(),
+                ClickHouseBufferConfig::default(),
+            ),
+            usage_event_buffer: UsageEventBuffer::spawn(
+                clickhouse_service.clone(),
+                ClickHouseBufferConfig::default(),
+            ),
+            rate_limit_service: RateLimitService::new(redis.clone(), RateLimitConfig::default()),
+            disposable_domain_service: DisposableDomainService::spawn(
+                pool.clone(),
+                DisposableDomainConfig::default(),
+            ),
+            phone_intelligence_service: PhoneIntelligenceService::new(
+                redis.clone(),
+                Arc::new(StubPhoneIntelligence::new()),
+            ),
+            geoip_service: GeoIpService::new(Arc::new(NoopGeoIp)),
+            sign_in_lockout_service: SignInLockoutService::new(
+                Arc::new(MemoryExpiringStore::new(clock.clone())),
+                clock.clone(),
+            ),
+            otp_service: OtpService::new(
+                Arc::new(MemoryExpiringStore::new(clock.clone())),
+                clock.clone(),
+                "test",
+            ),
+            web3_wallet_service: Web3WalletService::new(
+                Arc::new(MemoryExpiringStore::new(clock.clone())),
+                Arc::new(StubWeb3SignatureVerifier::new()),
+                clock,
+            ),
+            compromised_password_service: CompromisedPasswordService::new(Arc::new(
+                StubBreachedPasswords::new(),
+            )),
+            invitation_token_signer: InvitationTokenSigner::new("test"),
+            magic_link_token_signer: MagicLinkTokenSigner::new("test"),
+            health_service: HealthService::new(
+                pool,
+                redis,
+                clickhouse_service,
+                HealthConfig::default(),
+            ),
+            background_tasks: BackgroundTasks::new(),
+            deployment_settings_cache,
+            cache_invalidator,
+            domains: DomainsConfig::default(),
         }
     }
 }
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+
+/// The current time, behind a trait so time-dependent logic can be tested
+/// without sleeping.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when a test moves it.
+#[cfg(test)]
+pub(crate) struct FakeClock(std::sync::RwLock<DateTime<Utc>>);
+
+#[cfg(test)]
+impl FakeClock {
+    pub(crate) fn new() -> std::sync::Arc<Self> {
+        Self::at(DateTime::from_timestamp(1_700_000_000, 0).unwrap())
+    }
+
+    pub(crate) fn at(now: DateTime<Utc>) -> std::sync::Arc<Self> {
+        std::sync::Arc::new(Self(std::sync::RwLock::new(now)))
+    }
+
+    pub(crate) fn advance(&self, seconds: i64) {
+        self.advance_millis(seconds * 1000);
+    }
+
+    pub(crate) fn advance_millis(&self, millis: i64) {
+        *self.0.write().unwrap() += chrono::Duration::milliseconds(millis);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.read().unwrap()
+    }
+}
@@ -0,0 +1,162 @@
+//! Five-field cron expressions: minute, hour, day of month, month and day of
+//! week. Each field takes `*`, numbers, ranges (`1-5`), lists (`1,15`) and
+//! steps (`*/15`, `0-30/10`); months and days of the week also take their
+//! three-letter English names. There is no seconds field, so nothing can be
+//! scheduled more often than once a minute.
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+
+/// How far ahead a matching minute is looked for. Leap days can be four
+/// years apart, and eight years apart across a century; anything not found
+/// by then never matches, like `0 0 30 2 *`.
+const MAX_SEARCH_DAYS: i64 = 366 * 8;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u32,
+    days_of_week: u32,
+    /// Cron matches a day when either day field does, if both are
+    /// restricted. Otherwise both have to.
+    days_either: bool,
+}
+
+fn parse_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lowered = value.to_lowercase();
+    if let Some(index) = names.iter().position(|name| *name == lowered) {
+        return Ok(min + index as u32);
+    }
+
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| format!("'{}' is not between {} and {}", value, min, max))
+}
+
+/// The field as a bit mask, bit `n` meaning `n` is included.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut mask = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("'{}' is not a valid step", step))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, max, names)?,
+                parse_value(end, min, max, names)?,
+            )
+        } else {
+            let start = parse_value(range, min, max, names)?;
+            // `5/15` means every fifteenth value from 5
+            (start, if part.contains('/') { max } else { start })
+        };
+        if start > end {
+            return Err(format!("'{}' is a range that runs backwards", range));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() == 6 {
+            return Err(
+                "Schedules can run at most once a minute, so there is no seconds field".to_string(),
+            );
+        }
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(
+                "A cron expression has five fields: minute, hour, day of month, month and day of week"
+                    .to_string(),
+            );
+        };
+
+        let mut days_of_week = parse_field(day_of_week, 0, 7, &DAY_NAMES)?;
+        // Both 0 and 7 are Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, &[])?,
+            hours: parse_field(hour, 0, 23, &[])? as u32,
+            days_of_month: parse_field(day_of_month, 1, 31, &[])? as u32,
+            months: parse_field(month, 1, 12, &MONTH_NAMES)? as u32,
+            days_of_week: days_of_week as u32,
+            days_either: !day_of_month.starts_with('*') && !day_of_week.starts_with('*'),
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day_of_month = self.days_of_month & (1 << date.day()) != 0;
+        let day_of_week = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+
+        if self.days_either {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+
+    /// The first minute after `after` the schedule matches, in the same
+    /// local time `after` is given in. `None` if it never matches.
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut next =
+            after.date().and_hms_opt(after.hour(), after.minute(), 0)? + TimeDelta::minutes(1);
+        let limit = next + TimeDelta::days(MAX_SEARCH_DAYS);
+
+        while next < limit {
+            if self.months & (1 << next.month()) == 0 {
+                let (year, month) = if next.month() == 12 {
+                    (next.year() + 1, 1)
+                } else {
+                    (next.year(), next.month() + 1)
+                };
+                next = NaiveDate::from_ymd_opt(year, month, 1)?.and_time(NaiveTime::MIN);
+                continue;
+            }
+            if !self.matches_day(next.date()) {
+                next = next.date().succ_opt()?.and_time(NaiveTime::MIN);
+                continue;
+            }
+            if self.hours & (1 << next.hour()) == 0 {
+                next = next.date().and_hms_opt(next.hour(), 0, 0)? + TimeDelta::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << next.minute()) == 0 {
+                next += TimeDelta::minutes(1);
+                continue;
+            }
+            return Some(next);
+        }
+
+        None
+    }
+}
@@ -0,0 +1,169 @@
+/// How a mail provider reads the addresses it hosts.
+struct EmailProvider {
+    domains: &'static [&'static str],
+    /// The domain every other one is an alias of. Providers whose domains
+    /// hold separate mailboxes, like Outlook's, have none.
+    canonical_domain: Option<&'static str>,
+    /// Whether dots in the local part are ignored, so `j.doe` and `jdoe` are
+    /// one mailbox.
+    ignores_dots: bool,
+    subaddress_separator: char,
+}
+
+const PROVIDERS: &[EmailProvider] = &[
+    EmailProvider {
+        domains: &["gmail.com", "googlemail.com"],
+        canonical_domain: Some("gmail.com"),
+        ignores_dots: true,
+        subaddress_separator: '+',
+    },
+    EmailProvider {
+        domains: &["outlook.com", "hotmail.com", "live.com", "msn.com"],
+        canonical_domain: None,
+        ignores_dots: false,
+        subaddress_separator: '+',
+    },
+    EmailProvider {
+        domains: &["icloud.com", "me.com", "mac.com"],
+        canonical_domain: Some("icloud.com"),
+        ignores_dots: false,
+        subaddress_separator: '+',
+    },
+    EmailProvider {
+        domains: &["proton.me", "protonmail.com", "pm.me"],
+        canonical_domain: Some("proton.me"),
+        ignores_dots: false,
+        subaddress_separator: '+',
+    },
+    EmailProvider {
+        domains: &["yahoo.com"],
+        canonical_domain: None,
+        ignores_dots: false,
+        subaddress_separator: '-',
+    },
+];
+
+/// Used for domains not in the provider table. Only the separator is
+/// assumed, as dots are significant on most mail servers.
+const DEFAULT_SUBADDRESS_SEPARATOR: char = '+';
+
+/// Reduces an email address to the mailbox it delivers to, so the different
+/// ways of writing one address count as one: lowercased, with the provider's
+/// domain aliases and ignored dots folded away, and with any subaddress tag
+/// (`jane+news@`) removed when `strip_subaddress` is set. Anything that
+/// isn't an address is only trimmed and lowercased.
+pub fn normalize_email(email: &str, strip_subaddress: bool) -> String {
+    let email = email.trim().to_lowercase();
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return email;
+    };
+
+    let provider = PROVIDERS
+        .iter()
+        .find(|provider| provider.domains.contains(&domain));
+    let separator = provider
+        .map(|provider| provider.subaddress_separator)
+        .unwrap_or(DEFAULT_SUBADDRESS_SEPARATOR);
+
+    let mut local = local;
+    if strip_subaddress
+        && let Some((mailbox, _)) = local.split_once(separator)
+        && !mailbox.is_empty()
+    {
+        local = mailbox;
+    }
+
+    let local = match provider {
+        Some(provider) if provider.ignores_dots => local.replace('.', ""),
+        _ => local.to_string(),
+    };
+    let domain = provider
+        .and_then(|provider| provider.canonical_domain)
+        .unwrap_or(domain);
+
+    format!("{}@{}", local, domain)
+}
+
+/// The domain `normalize_email` would give an address on `domain`.
+pub fn normalize_email_domain(domain: &str) -> String {
+    let domain = domain.trim().to_lowercase();
+    PROVIDERS
+        .iter()
+        .find(|provider| provider.domains.contains(&domain.as_str()))
+        .and_then(|provider| provider.canonical_domain)
+        .map(str::to_string)
+        .unwrap_or(domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gmail_ignores_dots_and_its_other_domain() {
+        assert_eq!(
+            normalize_email("J.Doe@GoogleMail.com", false),
+            "jdoe@gmail.com"
+        );
+        assert_eq!(
+            normalize_email("j.doe+news@gmail.com", false),
+            "jdoe+news@gmail.com"
+        );
+        assert_eq!(
+            normalize_email("j.doe+news@gmail.com", true),
+            "jdoe@gmail.com"
+        );
+    }
+
+    #[test]
+    fn outlook_keeps_dots_and_separate_domains() {
+        assert_eq!(
+            normalize_email("J.Doe+shop@Outlook.com", true),
+            "j.doe@outlook.com"
+        );
+        assert_eq!(
+            normalize_email("j.doe@hotmail.com", true),
+            "j.doe@hotmail.com"
+        );
+        assert_eq!(
+            normalize_email("j.doe+shop@outlook.com", false),
+            "j.doe+shop@outlook.com"
+        );
+    }
+
+    #[test]
+    fn unknown_providers_only_lose_the_subaddress() {
+        assert_eq!(
+            normalize_email(" J.Doe+Tag@Example.com ", true),
+            "j.doe@example.com"
+        );
+        assert_eq!(
+            normalize_email("j.doe+tag@example.com", false),
+            "j.doe+tag@example.com"
+        );
+        // A local part that is all tag is left alone.
+        assert_eq!(
+            normalize_email("+tag@example.com", true),
+            "+tag@example.com"
+        );
+    }
+
+    #[test]
+    fn yahoo_subaddresses_use_a_hyphen() {
+        assert_eq!(
+            normalize_email("jdoe-shop@yahoo.com", true),
+            "jdoe@yahoo.com"
+        );
+        assert_eq!(
+            normalize_email("jdoe+shop@yahoo.com", true),
+            "jdoe+shop@yahoo.com"
+        );
+    }
+
+    #[test]
+    fn folds_domain_aliases() {
+        assert_eq!(normalize_email_domain("GoogleMail.com"), "gmail.com");
+        assert_eq!(normalize_email_domain("hotmail.com"), "hotmail.com");
+        assert_eq!(normalize_email_domain("example.com"), "example.com");
+    }
+}
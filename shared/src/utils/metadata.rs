@@ -0,0 +1,60 @@
+//! Limits and merge rules for the `public_metadata`/`private_metadata` objects
+//! stored on users, organizations and workspaces.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The largest a metadata object may be once serialized.
+pub const MAX_METADATA_BYTES: usize = 8 * 1024;
+
+/// How a metadata update combines with what is stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataMergeMode {
+    /// Top-level keys in the update overwrite stored ones; the rest are kept.
+    #[default]
+    Shallow,
+    /// Nested objects are merged key by key instead of being overwritten.
+    Deep,
+    /// The update becomes the stored object.
+    Replace,
+}
+
+/// Applies `update` to `current`. In the merging modes a `null` value removes
+/// the key.
+pub fn merge_metadata(current: Value, update: Value, mode: MetadataMergeMode) -> Value {
+    match (mode, current, update) {
+        (MetadataMergeMode::Replace, _, update) => update,
+        (mode, Value::Object(current), Value::Object(update)) => Value::Object(merge_objects(
+            current,
+            update,
+            mode == MetadataMergeMode::Deep,
+        )),
+        (_, _, update) => update,
+    }
+}
+
+fn merge_objects(
+    mut current: Map<String, Value>,
+    update: Map<String, Value>,
+    deep: bool,
+) -> Map<String, Value> {
+    for (key, value) in update {
+        match (value, current.remove(&key)) {
+            (Value::Null, _) => {}
+            (Value::Object(value), Some(Value::Object(existing))) if deep => {
+                current.insert(key, Value::Object(merge_objects(existing, value, deep)));
+            }
+            (value, _) => {
+                current.insert(key, value);
+            }
+        }
+    }
+    current
+}
+
+pub fn metadata_size(value: &Value) -> usize {
+    serde_json::to_vec(value)
+        .map(|bytes| bytes.len())
+        .unwrap_or(0)
+}
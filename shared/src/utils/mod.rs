@@ -1,5 +1,13 @@
+pub mod clock;
+pub mod cron;
+pub mod email_normalization;
 pub mod handlebars_helpers;
+pub mod metadata;
 pub mod name;
+pub mod phone_country;
+pub mod secret_mask;
 pub mod security;
 pub mod serde;
 pub mod validation;
+pub mod wallet_address;
+pub mod x509;
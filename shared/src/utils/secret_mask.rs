@@ -0,0 +1,56 @@
+/// What a secret is shown as in API responses.
+pub const MASK: &str = "••••";
+
+/// Secrets shorter than this are masked completely, as their last
+/// characters would give away too much of them.
+const MIN_LEN_FOR_HINT: usize = 12;
+const HINT_LEN: usize = 4;
+
+/// `••••` followed by the last four characters, so a secret can be told apart
+/// from another without being readable. An empty secret stays empty.
+pub fn mask_secret(secret: &str) -> String {
+    let chars = secret.chars().count();
+    if chars == 0 {
+        return String::new();
+    }
+    if chars < MIN_LEN_FOR_HINT {
+        return MASK.to_string();
+    }
+
+    let hint: String = secret.chars().skip(chars - HINT_LEN).collect();
+    format!("{}{}", MASK, hint)
+}
+
+/// Whether a submitted value is the mask of a secret rather than a new one.
+/// Forms send back what they were given, so this means "keep the stored
+/// secret".
+pub fn is_masked_secret(value: &str) -> bool {
+    value
+        .strip_prefix(MASK)
+        .is_some_and(|hint| hint.chars().count() <= HINT_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_secrets_keep_their_last_four_characters() {
+        assert_eq!(mask_secret("sk_live_0123456789abcdef"), "••••cdef");
+        assert!(is_masked_secret(&mask_secret("sk_live_0123456789abcdef")));
+    }
+
+    #[test]
+    fn short_and_empty_secrets_give_nothing_away() {
+        assert_eq!(mask_secret("hunter2"), "••••");
+        assert_eq!(mask_secret(""), "");
+        assert!(is_masked_secret("••••"));
+    }
+
+    #[test]
+    fn real_secrets_are_not_taken_for_masks() {
+        assert!(!is_masked_secret("GOCSPX-4f7Qk2mZ9xWcT1bY8sLh"));
+        assert!(!is_masked_secret("••••a-much-longer-secret"));
+        assert!(!is_masked_secret(""));
+    }
+}
@@ -45,6 +45,31 @@ pub mod i64_as_string_option {
     }
 }
 
+pub mod i64_vec_as_string {
+    use serde::{Deserialize, Deserializer, Serializer, ser::SerializeSeq};
+
+    pub fn serialize<S>(values: &[i64], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values {
+            seq.serialize_element(&value.to_string())?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<i64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| s.parse::<i64>().map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
 pub mod enum_from_str {
     use serde::{Deserialize, Deserializer};
     use std::{fmt::Display, str::FromStr};
@@ -71,3 +96,27 @@ pub mod enum_from_str {
         }
     }
 }
+
+/// Timestamps in query strings. chrono's own errors don't say what format was
+/// expected, which is all a caller needs to know.
+pub mod rfc3339_option {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Some(value) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        DateTime::parse_from_rfc3339(value.trim())
+            .map(|at| Some(at.with_timezone(&Utc)))
+            .map_err(|_| {
+                serde::de::Error::custom(format!(
+                    "'{}' isn't an RFC 3339 timestamp such as 2026-01-31T09:30:00Z",
+                    value
+                ))
+            })
+    }
+}
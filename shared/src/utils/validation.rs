@@ -1,23 +1,169 @@
-use crate::models::{
-    DeploymentAuthSettings, EmailSettings, PasswordSettings, PhoneSettings, UsernameSettings,
+use crate::{
+    error::{AppError, ErrorCode},
+    models::{
+        DeploymentAuthSettings, EmailSettings, FirstFactor, PasswordSettings, PhoneSettings,
+        UsernameSettings,
+    },
+    validators::FieldViolation,
 };
 use regex::Regex;
+use serde_json::json;
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Debug, Clone)]
 pub struct ValidationError {
     pub field: String,
+    /// Identifies the rule that failed, so clients can show their own message.
+    pub code: String,
     pub message: String,
 }
 
 impl ValidationError {
     pub fn new(field: &str, message: &str) -> Self {
+        Self::rule(field, "invalid", message)
+    }
+
+    pub fn rule(field: &str, code: &str, message: &str) -> Self {
         Self {
             field: field.to_string(),
+            code: code.to_string(),
             message: message.to_string(),
         }
     }
 }
 
+impl From<ValidationError> for FieldViolation {
+    fn from(error: ValidationError) -> Self {
+        FieldViolation::new(error.field, error.code, error.message)
+    }
+}
+
+/// Reports every failed rule under `details.fields`, in the same shape the
+/// console uses for invalid request bodies.
+pub fn validation_failed(errors: Vec<ValidationError>) -> AppError {
+    let fields: Vec<FieldViolation> = errors.into_iter().map(Into::into).collect();
+
+    AppError::coded(ErrorCode::ValidationFailed, "Validation failed")
+        .with_details(json!({ "fields": fields }))
+}
+
+/// The longest username any deployment can allow.
+pub const MAX_USERNAME_LENGTH: i64 = 64;
+
+/// Usernames no deployment hands out.
+pub const RESERVED_USERNAMES: &[&str] = &["admin", "root", "support", "billing"];
+
+/// The form a username is stored in: trimmed and NFC normalized, so a name
+/// typed with a precomposed accent and one typed with a combining accent are
+/// the same name. Uniqueness compares it lowercased.
+pub fn normalize_username(username: &str) -> String {
+    username.trim().nfc().collect()
+}
+
+/// Characters that take up no space or render as nothing, which would let two
+/// usernames look identical.
+fn is_invisible(c: char) -> bool {
+    c.is_whitespace()
+        || c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{034F}'
+                | '\u{061C}'
+                | '\u{115F}'..='\u{1160}'
+                | '\u{17B4}'..='\u{17B5}'
+                | '\u{180B}'..='\u{180F}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{206F}'
+                | '\u{3164}'
+                | '\u{FE00}'..='\u{FE0F}'
+                | '\u{FEFF}'
+                | '\u{FFA0}'
+                | '\u{E0000}'..='\u{E0FFF}'
+        )
+}
+
+#[derive(PartialEq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c {
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => {
+            Some(Script::Latin)
+        }
+        '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Some(Script::Greek),
+        '\u{0400}'..='\u{052F}'
+        | '\u{1C80}'..='\u{1C8F}'
+        | '\u{2DE0}'..='\u{2DFF}'
+        | '\u{A640}'..='\u{A69F}' => Some(Script::Cyrillic),
+        _ => None,
+    }
+}
+
+/// Whether a username could pass for a different one: Latin, Greek and
+/// Cyrillic letters mixed together (a Cyrillic "а" in "admin"), or
+/// full-width and mathematical letters that render like plain ones.
+fn is_confusable(username: &str) -> bool {
+    if username
+        .chars()
+        .any(|c| matches!(c, '\u{FF00}'..='\u{FFEF}' | '\u{1D400}'..='\u{1D7FF}'))
+    {
+        return true;
+    }
+
+    let mut scripts = username.chars().filter_map(script);
+    match scripts.next() {
+        Some(first) => scripts.any(|script| script != first),
+        None => false,
+    }
+}
+
+/// Checks a deployment's alternate first factors against its auth settings:
+/// each must be usable, listed once, and not the primary first factor.
+pub fn validate_alternate_first_factors(
+    settings: &DeploymentAuthSettings,
+    factors: &[FirstFactor],
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    for (index, factor) in factors.iter().enumerate() {
+        let name = factor.to_string();
+        if *factor == settings.first_factor {
+            errors.push(ValidationError::rule(
+                "alternate_first_factors",
+                "primary_first_factor",
+                &format!("{} is already the primary first factor", name),
+            ));
+        } else if factors[..index].contains(factor) {
+            errors.push(ValidationError::rule(
+                "alternate_first_factors",
+                "duplicate",
+                &format!("{} is listed more than once", name),
+            ));
+        } else if !settings.first_factor_available(factor) {
+            let prerequisite = match factor {
+                FirstFactor::EmailPassword => "email addresses, email and password and passwords",
+                FirstFactor::UsernamePassword => "usernames, username and password and passwords",
+                FirstFactor::EmailOtp => "email addresses and email OTP",
+                FirstFactor::EmailMagicLink => "email addresses and magic links",
+                FirstFactor::PhoneOtp => "phone numbers and phone OTP",
+            };
+            errors.push(ValidationError::rule(
+                "alternate_first_factors",
+                "prerequisite_disabled",
+                &format!("{} needs {} to be enabled", name, prerequisite),
+            ));
+        }
+    }
+
+    errors
+}
+
 pub struct UserValidator;
 
 impl UserValidator {
@@ -195,59 +341,99 @@ impl UserValidator {
         settings: &UsernameSettings,
         errors: &mut Vec<ValidationError>,
     ) {
-        let len = username.len();
+        errors.extend(Self::validate_username_rules(username, settings, &[]));
+    }
 
-        if let Some(min_len) = settings.min_length {
-            if len < min_len as usize {
-                errors.push(ValidationError::new(
-                    "username",
-                    &format!("Username must be at least {} characters", min_len),
-                ));
-            }
+    /// Checks a username against the deployment's username settings and
+    /// returns one error per rule it breaks. `reserved_names` adds to the
+    /// platform's reserved names and the deployment's own, such as its app
+    /// name. Whether another user has it is checked separately.
+    pub fn validate_username_rules(
+        username: &str,
+        settings: &UsernameSettings,
+        reserved_names: &[String],
+    ) -> Vec<ValidationError> {
+        let username = normalize_username(username);
+        let mut errors = Vec::new();
+        let len = username.chars().count();
+
+        if let Some(min_len) = settings.min_length
+            && len < min_len as usize
+        {
+            errors.push(ValidationError::rule(
+                "username",
+                "min_length",
+                &format!("Username must be at least {} characters", min_len),
+            ));
         }
 
-        if let Some(max_len) = settings.max_length {
-            if len > max_len as usize {
-                errors.push(ValidationError::new(
-                    "username",
-                    &format!("Username must be at most {} characters", max_len),
-                ));
-            }
+        if let Some(max_len) = settings.max_length
+            && len > max_len as usize
+        {
+            errors.push(ValidationError::rule(
+                "username",
+                "max_length",
+                &format!("Username must be at most {} characters", max_len),
+            ));
         }
 
-        // Username should only contain alphanumeric characters, underscores, and hyphens
-        if !username
+        if username.chars().any(is_invisible) {
+            errors.push(ValidationError::rule(
+                "username",
+                "invisible_characters",
+                "Username cannot contain spaces or invisible characters",
+            ));
+        } else if is_confusable(&username) {
+            errors.push(ValidationError::rule(
+                "username",
+                "confusable_characters",
+                "Username cannot mix characters that look alike from different alphabets",
+            ));
+        } else if !username
             .chars()
-            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
         {
-            errors.push(ValidationError::new(
+            errors.push(ValidationError::rule(
                 "username",
-                "Username can only contain letters, numbers, underscores, and hyphens",
+                "invalid_characters",
+                "Username can only contain letters, numbers, underscores, hyphens and periods",
             ));
         }
+
+        let lowered = username.to_lowercase();
+        let reserved = RESERVED_USERNAMES
+            .iter()
+            .map(|name| name.to_string())
+            .chain(settings.reserved_names.iter().cloned())
+            .chain(reserved_names.iter().cloned())
+            .any(|name| normalize_username(&name).to_lowercase() == lowered);
+        if reserved {
+            errors.push(ValidationError::rule(
+                "username",
+                "reserved",
+                "This username is reserved",
+            ));
+        }
+
+        errors
     }
 
     fn validate_password(
         password: &Option<String>,
         settings: &PasswordSettings,
     ) -> Result<(), Vec<ValidationError>> {
-        let mut errors = Vec::new();
-
         if !settings.enabled {
             return Ok(());
         }
 
-        match password {
-            None => {
-                errors.push(ValidationError::new("password", "Password is required"));
-            }
-            Some(p) if p.trim().is_empty() => {
-                errors.push(ValidationError::new("password", "Password is required"));
-            }
-            Some(password_str) => {
-                Self::validate_password_constraints(password_str, settings, &mut errors);
-            }
-        }
+        let errors = match password {
+            Some(p) if !p.trim().is_empty() => Self::validate_password_policy(p, settings),
+            _ => vec![ValidationError::rule(
+                "password",
+                "required",
+                "Password is required",
+            )],
+        };
 
         if errors.is_empty() {
             Ok(())
@@ -256,25 +442,31 @@ impl UserValidator {
         }
     }
 
-    fn validate_password_constraints(
+    /// Checks a password against the deployment's composition rules and
+    /// returns one error per rule it breaks. Whether the password has been
+    /// breached is checked separately, since that needs a network call.
+    pub fn validate_password_policy(
         password: &str,
         settings: &PasswordSettings,
-        errors: &mut Vec<ValidationError>,
-    ) {
-        if let Some(min_len) = settings.min_length {
-            if password.len() < min_len as usize {
-                errors.push(ValidationError::new(
-                    "password",
-                    &format!("Password must be at least {} characters", min_len),
-                ));
-            }
+    ) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+
+        if let Some(min_len) = settings.min_length
+            && password.chars().count() < min_len as usize
+        {
+            errors.push(ValidationError::rule(
+                "password",
+                "min_length",
+                &format!("Password must be at least {} characters", min_len),
+            ));
         }
 
         if settings.require_lowercase.unwrap_or(false)
             && !password.chars().any(|c| c.is_lowercase())
         {
-            errors.push(ValidationError::new(
+            errors.push(ValidationError::rule(
                 "password",
+                "require_lowercase",
                 "Password must contain at least one lowercase letter",
             ));
         }
@@ -282,29 +474,36 @@ impl UserValidator {
         if settings.require_uppercase.unwrap_or(false)
             && !password.chars().any(|c| c.is_uppercase())
         {
-            errors.push(ValidationError::new(
+            errors.push(ValidationError::rule(
                 "password",
+                "require_uppercase",
                 "Password must contain at least one uppercase letter",
             ));
         }
 
         if settings.require_number.unwrap_or(false) && !password.chars().any(|c| c.is_numeric()) {
-            errors.push(ValidationError::new(
+            errors.push(ValidationError::rule(
                 "password",
+                "require_number",
                 "Password must contain at least one number",
             ));
         }
 
+        // Any printable character that isn't a letter or digit counts, not
+        // just ASCII punctuation.
         if settings.require_special.unwrap_or(false)
             && !password
                 .chars()
-                .any(|c| "!@#$%^&*()_+-=[]{}|;:,.<>?".contains(c))
+                .any(|c| !c.is_alphanumeric() && !c.is_whitespace() && !c.is_control())
         {
-            errors.push(ValidationError::new(
+            errors.push(ValidationError::rule(
                 "password",
+                "require_special",
                 "Password must contain at least one special character",
             ));
         }
+
+        errors
     }
 
     fn is_valid_email(email: &str) -> bool {
@@ -319,3 +518,125 @@ impl UserValidator {
         phone_regex.is_match(&cleaned)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuthFactorsEnabled;
+
+    fn codes(errors: &[ValidationError]) -> Vec<&str> {
+        errors.iter().map(|e| e.code.as_str()).collect()
+    }
+
+    #[test]
+    fn lists_every_rule_a_password_breaks() {
+        let errors = UserValidator::validate_password_policy("abc", &PasswordSettings::default());
+
+        assert_eq!(
+            codes(&errors),
+            vec![
+                "min_length",
+                "require_uppercase",
+                "require_number",
+                "require_special"
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_characters_rather_than_bytes() {
+        let settings = PasswordSettings {
+            min_length: Some(8),
+            require_lowercase: Some(false),
+            require_uppercase: Some(false),
+            require_number: Some(false),
+            require_special: Some(false),
+            ..PasswordSettings::default()
+        };
+
+        let errors = UserValidator::validate_password_policy("ééééé", &settings);
+
+        assert_eq!(codes(&errors), vec!["min_length"]);
+    }
+
+    #[test]
+    fn accepts_non_ascii_symbols() {
+        let errors =
+            UserValidator::validate_password_policy("Passw0rd€", &PasswordSettings::default());
+
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn reserves_names_case_insensitively() {
+        let settings = UsernameSettings {
+            reserved_names: vec!["Staff".to_string()],
+            ..UsernameSettings::default()
+        };
+        let app_name = vec!["acme".to_string()];
+
+        for name in ["Admin", "staff", "ACME"] {
+            let errors = UserValidator::validate_username_rules(name, &settings, &app_name);
+            assert_eq!(codes(&errors), vec!["reserved"], "{name}");
+        }
+    }
+
+    #[test]
+    fn rejects_invisible_and_confusable_characters() {
+        let settings = UsernameSettings::default();
+
+        let errors = UserValidator::validate_username_rules("ja\u{200B}ne", &settings, &[]);
+        assert_eq!(codes(&errors), vec!["invisible_characters"]);
+
+        // A Cyrillic "а" among Latin letters.
+        let errors = UserValidator::validate_username_rules("j\u{0430}ne", &settings, &[]);
+        assert_eq!(codes(&errors), vec!["confusable_characters"]);
+
+        let errors = UserValidator::validate_username_rules("\u{FF4A}ane", &settings, &[]);
+        assert_eq!(codes(&errors), vec!["confusable_characters"]);
+    }
+
+    #[test]
+    fn normalizes_before_checking_length() {
+        let settings = UsernameSettings {
+            max_length: Some(4),
+            ..UsernameSettings::default()
+        };
+
+        // "josé" written with a combining accent is five code points, four
+        // once composed.
+        let errors = UserValidator::validate_username_rules("jose\u{0301}", &settings, &[]);
+
+        assert!(errors.is_empty());
+        assert_eq!(normalize_username(" jose\u{0301} "), "jos\u{00E9}");
+    }
+
+    #[test]
+    fn alternate_first_factors_need_their_prerequisites() {
+        let settings = DeploymentAuthSettings {
+            phone_number: PhoneSettings {
+                enabled: false,
+                ..PhoneSettings::default()
+            },
+            auth_factors_enabled: AuthFactorsEnabled::default()
+                .with_email(true)
+                .with_phone(true),
+            ..DeploymentAuthSettings::default()
+        };
+
+        let errors = validate_alternate_first_factors(
+            &settings,
+            &[
+                FirstFactor::EmailOtp,
+                FirstFactor::PhoneOtp,
+                FirstFactor::EmailPassword,
+                FirstFactor::EmailOtp,
+            ],
+        );
+
+        assert_eq!(
+            codes(&errors),
+            vec!["prerequisite_disabled", "primary_first_factor", "duplicate"]
+        );
+    }
+}
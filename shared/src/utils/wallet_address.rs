@@ -0,0 +1,110 @@
+//! Ethereum wallet addresses, normalized to their EIP-55 checksummed form so
+//! the same wallet always maps to the same stored address.
+
+use sha3::{Digest, Keccak256};
+
+/// Keccak-256 as Ethereum uses it, which pads differently from SHA3-256.
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// The EIP-55 checksummed form of a 40 digit hex address, without the `0x`.
+fn checksum(hex_digits: &str) -> String {
+    let lowercase = hex_digits.to_ascii_lowercase();
+    let hash = keccak256(lowercase.as_bytes());
+
+    lowercase
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = if i % 2 == 0 {
+                hash[i / 2] >> 4
+            } else {
+                hash[i / 2] & 0x0f
+            };
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Returns the EIP-55 checksummed form of an address, or `None` when it isn't
+/// one. All-lowercase and all-uppercase addresses carry no checksum and are
+/// accepted as they are; mixed-case ones have to match their checksum, which
+/// catches most typos.
+pub fn normalize_wallet_address(address: &str) -> Option<String> {
+    let address = address.trim();
+    let hex_digits = address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix("0X"))
+        .unwrap_or(address);
+    if hex_digits.len() != 40 || !hex_digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let checksummed = checksum(hex_digits);
+    let has_lowercase = hex_digits.chars().any(|c| c.is_ascii_lowercase());
+    let has_uppercase = hex_digits.chars().any(|c| c.is_ascii_uppercase());
+    if has_lowercase && has_uppercase && hex_digits != checksummed {
+        return None;
+    }
+
+    Some(format!("0x{}", checksummed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keccak256_matches_known_digests() {
+        assert_eq!(
+            hex::encode(keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(
+            hex::encode(keccak256(b"hello")),
+            "1c8aff950685c2ed4bc3174f3472287b56d9517b9c948127319a09a7a36deac8"
+        );
+    }
+
+    #[test]
+    fn addresses_normalize_to_their_eip55_checksum() {
+        for checksummed in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            assert_eq!(
+                normalize_wallet_address(checksummed).as_deref(),
+                Some(checksummed)
+            );
+            assert_eq!(
+                normalize_wallet_address(&checksummed.to_lowercase()).as_deref(),
+                Some(checksummed)
+            );
+            assert_eq!(
+                normalize_wallet_address(&format!(" 0x{} ", checksummed[2..].to_uppercase()))
+                    .as_deref(),
+                Some(checksummed)
+            );
+        }
+    }
+
+    #[test]
+    fn bad_checksums_and_malformed_addresses_are_rejected() {
+        for address in [
+            "0x5aaeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAe",
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAedd",
+            "0xgaAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "",
+        ] {
+            assert_eq!(normalize_wallet_address(address), None, "{}", address);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Just enough DER reading to pull the validity window out of an X.509
+//! certificate. Signatures and extensions are not inspected.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_EXPLICIT_VERSION: u8 = 0xa0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CertificateValidity {
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+}
+
+/// Decodes a certificate given as PEM, or as the bare base64 body found in
+/// SAML metadata `<X509Certificate>` elements.
+pub fn decode_certificate(certificate: &str) -> Result<Vec<u8>, String> {
+    let body: String = certificate
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("-----"))
+        .flat_map(|line| line.chars().filter(|c| !c.is_whitespace()))
+        .collect();
+
+    if body.is_empty() {
+        return Err("certificate is empty".to_string());
+    }
+
+    STANDARD
+        .decode(body)
+        .map_err(|_| "certificate is not valid base64".to_string())
+}
+
+pub fn certificate_validity(certificate: &str) -> Result<CertificateValidity, String> {
+    let der = decode_certificate(certificate)?;
+    let invalid = || "certificate is not a valid X.509 certificate".to_string();
+
+    let (certificate, _) = read_tlv(&der, TAG_SEQUENCE).ok_or_else(invalid)?;
+    let (mut tbs, _) = read_tlv(certificate, TAG_SEQUENCE).ok_or_else(invalid)?;
+
+    if tbs.first() == Some(&TAG_EXPLICIT_VERSION) {
+        tbs = read_tlv(tbs, TAG_EXPLICIT_VERSION).ok_or_else(invalid)?.1;
+    }
+
+    // serialNumber, signature algorithm and issuer precede the validity.
+    for _ in 0..3 {
+        tbs = skip_tlv(tbs).ok_or_else(invalid)?;
+    }
+
+    let (validity, _) = read_tlv(tbs, TAG_SEQUENCE).ok_or_else(invalid)?;
+    let (not_before, rest) = read_time(validity).ok_or_else(invalid)?;
+    let (not_after, _) = read_time(rest).ok_or_else(invalid)?;
+
+    Ok(CertificateValidity {
+        not_before,
+        not_after,
+    })
+}
+
+/// Splits off one element, returning its contents and whatever follows it.
+fn read_tlv(input: &[u8], expected_tag: u8) -> Option<(&[u8], &[u8])> {
+    let (tag, contents, rest) = read_any(input)?;
+    (tag == expected_tag).then_some((contents, rest))
+}
+
+fn skip_tlv(input: &[u8]) -> Option<&[u8]> {
+    read_any(input).map(|(_, _, rest)| rest)
+}
+
+fn read_any(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first, mut input) = input.split_first()?;
+
+    let length = if first & 0x80 == 0 {
+        first as usize
+    } else {
+        let octets = (first & 0x7f) as usize;
+        if octets == 0 || octets > 4 || input.len() < octets {
+            return None;
+        }
+        let (length_bytes, rest) = input.split_at(octets);
+        input = rest;
+        length_bytes
+            .iter()
+            .fold(0usize, |length, &byte| (length << 8) | byte as usize)
+    };
+
+    if input.len() < length {
+        return None;
+    }
+    let (contents, rest) = input.split_at(length);
+    Some((tag, contents, rest))
+}
+
+fn read_time(input: &[u8]) -> Option<(DateTime<Utc>, &[u8])> {
+    let (tag, contents, rest) = read_any(input)?;
+    let text = std::str::from_utf8(contents).ok()?.strip_suffix('Z')?;
+
+    let (year, text) = match tag {
+        TAG_UTC_TIME => {
+            let year: i32 = text.get(..2)?.parse().ok()?;
+            // RFC 5280: two-digit years of 50 and above are in the 1900s.
+            let year = if year >= 50 { 1900 + year } else { 2000 + year };
+            (year, text.get(2..)?)
+        }
+        TAG_GENERALIZED_TIME => (text.get(..4)?.parse().ok()?, text.get(4..)?),
+        _ => return None,
+    };
+
+    let field = |range: std::ops::Range<usize>| -> Option<u32> { text.get(range)?.parse().ok() };
+    let date = NaiveDate::from_ymd_opt(year, field(0..2)?, field(2..4)?)?;
+    let time = date.and_hms_opt(field(4..6)?, field(6..8)?, field(8..10)?)?;
+
+    Some((NaiveDateTime::and_utc(&time), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn certificate(not_before: (i32, u8, u8), not_after: (i32, u8, u8)) -> rcgen::Certificate {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["sp.example.com".to_string()]).unwrap();
+        params.not_before = rcgen::date_time_ymd(not_before.0, not_before.1, not_before.2);
+        params.not_after = rcgen::date_time_ymd(not_after.0, not_after.1, not_after.2);
+        params.self_signed(&key_pair).unwrap()
+    }
+
+    #[test]
+    fn reads_the_validity_a_certificate_was_issued_with() {
+        let pem = certificate((2024, 1, 15), (2049, 12, 31)).pem();
+
+        let validity = certificate_validity(&pem).unwrap();
+
+        assert_eq!(
+            validity.not_before,
+            Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            validity.not_after,
+            Utc.with_ymd_and_hms(2049, 12, 31, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn reads_generalized_times_from_2050_on() {
+        let pem = certificate((2025, 6, 1), (2060, 3, 2)).pem();
+
+        let validity = certificate_validity(&pem).unwrap();
+
+        assert_eq!(
+            validity.not_after,
+            Utc.with_ymd_and_hms(2060, 3, 2, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn accepts_the_bare_base64_body_from_saml_metadata() {
+        let certificate = certificate((2024, 1, 15), (2034, 1, 15));
+        let body = STANDARD.encode(certificate.der());
+
+        assert_eq!(
+            decode_certificate(&certificate.pem()).unwrap(),
+            certificate.der().to_vec()
+        );
+        assert_eq!(
+            certificate_validity(&body).unwrap(),
+            certificate_validity(&certificate.pem()).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_certificate() {
+        assert!(certificate_validity("").is_err());
+        assert!(certificate_validity("not base64!").is_err());
+        assert!(certificate_validity(&STANDARD.encode(b"\x30\x03\x02\x01")).is_err());
+    }
+}
@@ -0,0 +1,1649 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::Value;
+
+use super::{
+    MAX_ALLOWED_CLOCK_SKEW, MAX_ALLOWED_REDIRECT_ORIGINS, MAX_TOKEN_LIFETIME, MIN_TOKEN_LIFETIME,
+    RequestValidator, Validate, ValidationErrors, is_valid_url, normalize_redirect_origin,
+};
+use crate::commands::{
+    AGENT_SESSION_MESSAGE_ROLES, MAX_BULK_WAITLIST_APPROVALS, MAX_OPENAPI_DOCUMENT_BYTES,
+};
+use crate::dto::json::*;
+use crate::dto::query::{
+    DateRangeParams, GlobalSearchQueryParams, Pagination, SortColumns, SortParams, decode_cursor,
+};
+use crate::models::{
+    API_KEY_SCOPES, ChunkingStrategy, CustomSigningKey, EmailProviderCredentials, EmailTemplate,
+    LogExportCredentials, ORGANIZATION_PERMISSIONS, RestrictionCandidate, SsoAttributeMapping,
+    WORKSPACE_PERMISSIONS, WorkflowTriggerType, feature_flag,
+};
+use crate::queries::{MAX_DRY_RUN_CONTENT_BYTES, MIN_GLOBAL_SEARCH_TERM_LENGTH};
+use crate::utils::cron::CronSchedule;
+use crate::utils::metadata::{MAX_METADATA_BYTES, metadata_size};
+use crate::utils::validation::MAX_USERNAME_LENGTH;
+
+const AI_TOOL_TYPES: &[&str] = &["api", "knowledge_base"];
+
+const JWT_ALGORITHMS: &[&str] = &[
+    "HS256", "HS384", "HS512", "RS256", "RS384", "RS512", "ES256", "ES384",
+];
+
+fn validate_name(v: &mut RequestValidator, field: &str, value: &str) {
+    v.length(field, value, 1, 100);
+}
+
+fn validate_permissions(v: &mut RequestValidator, permissions: &[String], catalog: &[&str]) {
+    for (i, permission) in permissions.iter().enumerate() {
+        if !catalog.contains(&permission.as_str()) {
+            v.add(
+                &format!("permissions[{}]", i),
+                "unknown_permission",
+                format!("'{}' isn't a known permission", permission),
+            );
+        }
+    }
+}
+
+fn validate_api_key_scopes(v: &mut RequestValidator, scopes: &[String]) {
+    if scopes.is_empty() {
+        v.add("scopes", "required", "scopes cannot be empty");
+    }
+    for (i, scope) in scopes.iter().enumerate() {
+        if !API_KEY_SCOPES.contains(&scope.as_str()) {
+            v.add(
+                &format!("scopes[{}]", i),
+                "unknown_scope",
+                format!("'{}' isn't a known scope", scope),
+            );
+        }
+    }
+}
+
+/// Only the outer bound: the deployment's own length limits, and which
+/// characters are allowed, are checked against its username settings.
+fn validate_username(v: &mut RequestValidator, value: &str) {
+    v.length("username", value, 1, MAX_USERNAME_LENGTH as usize);
+}
+
+fn validate_metadata(v: &mut RequestValidator, field: &str, value: &Option<Value>) {
+    if let Some(value) = value
+        && !value.is_object()
+    {
+        v.add(
+            field,
+            "invalid_type",
+            format!("{} must be a JSON object", field),
+        );
+    }
+}
+
+/// The public and private metadata of users, organizations and workspaces,
+/// which are stored on the row and so kept small.
+fn validate_stored_metadata(v: &mut RequestValidator, field: &str, value: &Option<Value>) {
+    validate_metadata(v, field, value);
+    if let Some(value) = value
+        && metadata_size(value) > MAX_METADATA_BYTES
+    {
+        v.add(
+            field,
+            "too_large",
+            format!("{} cannot exceed {} bytes", field, MAX_METADATA_BYTES),
+        );
+    }
+}
+
+fn validate_b2b_profile(
+    v: &mut RequestValidator,
+    name: Option<&str>,
+    description: &Option<String>,
+    image_url: &Option<String>,
+) {
+    if let Some(name) = name {
+        validate_name(v, "name", name);
+    }
+    if let Some(description) = description {
+        v.length("description", description, 0, 1000);
+    }
+    if let Some(image_url) = image_url.as_deref().filter(|url| !url.is_empty()) {
+        v.url("image_url", image_url);
+    }
+}
+
+fn validate_jwt_timing(
+    v: &mut RequestValidator,
+    token_lifetime: Option<i64>,
+    allowed_clock_skew: Option<i64>,
+) {
+    if let Some(token_lifetime) = token_lifetime {
+        v.range(
+            "token_lifetime",
+            token_lifetime,
+            MIN_TOKEN_LIFETIME,
+            MAX_TOKEN_LIFETIME,
+        );
+    }
+    if let Some(allowed_clock_skew) = allowed_clock_skew {
+        v.range(
+            "allowed_clock_skew",
+            allowed_clock_skew,
+            0,
+            MAX_ALLOWED_CLOCK_SKEW,
+        );
+    }
+}
+
+fn validate_signing_key(v: &mut RequestValidator, key: &Option<CustomSigningKey>) {
+    let Some(key) = key else {
+        return;
+    };
+
+    v.one_of(
+        "custom_signing_key.algorithm",
+        &key.algorithm,
+        JWT_ALGORITHMS,
+    );
+    if key.enabled && key.key.trim().is_empty() {
+        v.add(
+            "custom_signing_key.key",
+            "required",
+            "custom_signing_key.key is required when the custom key is enabled",
+        );
+    }
+}
+
+fn validate_colors(v: &mut RequestValidator, prefix: &str, colors: [(&str, &Option<String>); 3]) {
+    for (name, value) in colors {
+        if let Some(value) = value {
+            v.hex_color(&format!("{}.{}", prefix, name), value);
+        }
+    }
+}
+
+fn validate_domain(v: &mut RequestValidator, field: &str, value: &str) {
+    let domain = value.trim().trim_start_matches('@');
+    v.length(field, domain, 3, 253);
+    if !domain.contains('.')
+        || domain.starts_with('.')
+        || domain.ends_with('.')
+        || domain.contains(|c: char| c.is_whitespace() || c == '@' || c == '/')
+    {
+        v.add(
+            field,
+            "invalid_format",
+            format!("{} must be a domain name like example.com", field),
+        );
+    }
+}
+
+fn validate_sso_attribute_mapping(v: &mut RequestValidator, mapping: &SsoAttributeMapping) {
+    v.length("attribute_mapping.email", &mapping.email, 1, 255);
+    if let Some(first_name) = &mapping.first_name {
+        v.length("attribute_mapping.first_name", first_name, 1, 255);
+    }
+    if let Some(last_name) = &mapping.last_name {
+        v.length("attribute_mapping.last_name", last_name, 1, 255);
+    }
+}
+
+fn validate_sso_connection(
+    v: &mut RequestValidator,
+    idp_metadata_url: &Option<String>,
+    idp_sso_url: &Option<String>,
+    allowed_email_domains: Option<&[String]>,
+) {
+    if let Some(url) = idp_metadata_url {
+        v.url("idp_metadata_url", url);
+    }
+    if let Some(url) = idp_sso_url {
+        v.url("idp_sso_url", url);
+    }
+    for (i, domain) in allowed_email_domains.unwrap_or_default().iter().enumerate() {
+        validate_domain(v, &format!("allowed_email_domains[{}]", i), domain);
+    }
+}
+
+impl Validate for CreateUserRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_name(&mut v, "first_name", &self.first_name);
+        validate_name(&mut v, "last_name", &self.last_name);
+
+        if let Some(email) = &self.email_address {
+            v.email("email_address", email);
+        }
+        if let Some(phone) = &self.phone_number {
+            v.phone("phone_number", phone);
+        }
+        if let Some(username) = &self.username {
+            validate_username(&mut v, username);
+        }
+        if let Some(password) = &self.password {
+            // The minimum comes from the deployment's password policy.
+            v.length("password", password, 1, 128);
+        }
+
+        v.finish()
+    }
+}
+
+impl Validate for InviteUserRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_name(&mut v, "first_name", &self.first_name);
+        validate_name(&mut v, "last_name", &self.last_name);
+        v.email("email_address", &self.email_address);
+
+        if let Some(expiry_days) = self.expiry_days {
+            v.range("expiry_days", expiry_days, 1, 90);
+        }
+
+        v.finish()
+    }
+}
+
+impl Validate for AcceptInvitationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if self.token.is_empty() {
+            v.add("token", "required", "token is required");
+        }
+        if let Some(first_name) = &self.first_name {
+            validate_name(&mut v, "first_name", first_name);
+        }
+        if let Some(last_name) = &self.last_name {
+            validate_name(&mut v, "last_name", last_name);
+        }
+        if let Some(phone) = &self.phone_number {
+            v.phone("phone_number", phone);
+        }
+        if let Some(username) = &self.username {
+            validate_username(&mut v, username);
+        }
+        if let Some(password) = &self.password {
+            v.length("password", password, 1, 128);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for UpdateUserRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+
+        if let Some(first_name) = &self.first_name {
+            validate_name(&mut v, "first_name", first_name);
+        }
+        if let Some(last_name) = &self.last_name {
+            validate_name(&mut v, "last_name", last_name);
+        }
+        if let Some(username) = &self.username {
+            validate_username(&mut v, username);
+        }
+        validate_stored_metadata(&mut v, "public_metadata", &self.public_metadata);
+        validate_stored_metadata(&mut v, "private_metadata", &self.private_metadata);
+
+        v.finish()
+    }
+}
+
+impl Validate for AddEmailRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        RequestValidator::new().email("email", &self.email).finish()
+    }
+}
+
+impl Validate for UpdateEmailRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(email) = &self.email {
+            v.email("email", email);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for AddPhoneRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        RequestValidator::new()
+            .phone("phone_number", &self.phone_number)
+            .finish()
+    }
+}
+
+impl Validate for AddWeb3WalletRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.wallet_address("address", &self.address);
+        if let Some(chain_id) = self.chain_id {
+            v.range("chain_id", chain_id, 1, i64::MAX);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for Web3ChallengeRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.wallet_address("address", &self.address);
+        if let Some(chain_id) = self.chain_id {
+            v.range("chain_id", chain_id, 1, i64::MAX);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for VerifyWeb3SignatureRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.wallet_address("address", &self.address);
+        if let Some(chain_id) = self.chain_id {
+            v.range("chain_id", chain_id, 1, i64::MAX);
+        }
+        v.length("nonce", &self.nonce, 1, 64)
+            .length("signature", &self.signature, 1, 1024);
+        v.finish()
+    }
+}
+
+impl Validate for CreateMagicLinkRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.email("email_address", &self.email_address);
+        if let Some(redirect_url) = &self.redirect_url {
+            v.url("redirect_url", redirect_url);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for ConsumeMagicLinkRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        RequestValidator::new()
+            .length("token", &self.token, 1, 256)
+            .finish()
+    }
+}
+
+impl Validate for UpdateUserPasswordRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        RequestValidator::new()
+            .length("password", &self.password, 1, 128)
+            .finish()
+    }
+}
+
+impl Validate for ResetUserPasswordRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(password) = &self.password {
+            v.length("password", password, 1, 128);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for UpdatePasswordRequirementRequest {}
+
+impl Validate for EraseUserRequest {}
+
+impl Validate for JoinWaitlistRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.email("email_address", &self.email_address);
+        if let Some(first_name) = &self.first_name {
+            v.length("first_name", first_name, 0, 100);
+        }
+        if let Some(last_name) = &self.last_name {
+            v.length("last_name", last_name, 0, 100);
+        }
+        validate_metadata(&mut v, "metadata", &self.metadata);
+        v.finish()
+    }
+}
+
+impl Validate for BulkApproveWaitlistRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if self.ids.is_empty() {
+            v.add("ids", "required", "ids cannot be empty");
+        } else if self.ids.len() > MAX_BULK_WAITLIST_APPROVALS {
+            v.add(
+                "ids",
+                "too_long",
+                format!(
+                    "at most {} ids can be approved at once",
+                    MAX_BULK_WAITLIST_APPROVALS
+                ),
+            );
+        }
+        if let Some(expiry_days) = self.expiry_days {
+            v.range("expiry_days", expiry_days, 1, 90);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for UpdatePhoneRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(phone_number) = &self.phone_number {
+            v.phone("phone_number", phone_number);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for DeploymentDisplaySettingsUpdates {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+
+        if let Some(app_name) = &self.app_name {
+            validate_name(&mut v, "app_name", app_name);
+        }
+        if let Some(statement) = &self.signup_terms_statement {
+            v.length("signup_terms_statement", statement, 0, 1000);
+        }
+
+        let urls = [
+            ("tos_page_url", &self.tos_page_url),
+            ("sign_in_page_url", &self.sign_in_page_url),
+            ("sign_up_page_url", &self.sign_up_page_url),
+            (
+                "after_sign_out_one_page_url",
+                &self.after_sign_out_one_page_url,
+            ),
+            (
+                "after_sign_out_all_page_url",
+                &self.after_sign_out_all_page_url,
+            ),
+            ("favicon_image_url", &self.favicon_image_url),
+            ("logo_image_url", &self.logo_image_url),
+            ("privacy_policy_url", &self.privacy_policy_url),
+            ("after_logo_click_url", &self.after_logo_click_url),
+            ("organization_profile_url", &self.organization_profile_url),
+            ("create_organization_url", &self.create_organization_url),
+            (
+                "default_user_profile_image_url",
+                &self.default_user_profile_image_url,
+            ),
+            (
+                "default_organization_profile_image_url",
+                &self.default_organization_profile_image_url,
+            ),
+            ("after_signup_redirect_url", &self.after_signup_redirect_url),
+            ("after_signin_redirect_url", &self.after_signin_redirect_url),
+            ("user_profile_url", &self.user_profile_url),
+            (
+                "after_create_organization_redirect_url",
+                &self.after_create_organization_redirect_url,
+            ),
+        ];
+        // Empty strings clear a URL, so only non-empty values are checked.
+        for (field, value) in urls {
+            if let Some(value) = value.as_deref().filter(|value| !value.is_empty()) {
+                v.url(field, value);
+            }
+        }
+
+        if let Some(origins) = &self.allowed_redirect_origins {
+            if origins.len() > MAX_ALLOWED_REDIRECT_ORIGINS {
+                v.add(
+                    "allowed_redirect_origins",
+                    "too_many",
+                    format!(
+                        "allowed_redirect_origins cannot have more than {} entries",
+                        MAX_ALLOWED_REDIRECT_ORIGINS
+                    ),
+                );
+            }
+            for (index, origin) in origins.iter().enumerate() {
+                if normalize_redirect_origin(origin).is_none() {
+                    v.add(
+                        &format!("allowed_redirect_origins[{}]", index),
+                        "invalid_origin",
+                        format!(
+                            "allowed_redirect_origins[{}] must be an origin like https://app.example.com",
+                            index
+                        ),
+                    );
+                }
+            }
+        }
+
+        if let Some(light) = &self.light_mode_settings {
+            validate_colors(
+                &mut v,
+                "light_mode_settings",
+                [
+                    ("primary_color", &light.primary_color),
+                    ("background_color", &light.background_color),
+                    ("text_color", &light.text_color),
+                ],
+            );
+        }
+        if let Some(dark) = &self.dark_mode_settings {
+            validate_colors(
+                &mut v,
+                "dark_mode_settings",
+                [
+                    ("primary_color", &dark.primary_color),
+                    ("background_color", &dark.background_color),
+                    ("text_color", &dark.text_color),
+                ],
+            );
+        }
+
+        v.finish()
+    }
+}
+
+impl Validate for NewDeploymentJwtTemplate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_name(&mut v, "name", &self.name);
+        validate_jwt_timing(
+            &mut v,
+            Some(self.token_lifetime),
+            Some(self.allowed_clock_skew),
+        );
+        validate_signing_key(&mut v, &self.custom_signing_key);
+
+        if !self.template.is_object() {
+            v.add("template", "invalid_type", "template must be a JSON object");
+        }
+
+        v.finish()
+    }
+}
+
+impl Validate for PartialDeploymentJwtTemplate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+
+        if let Some(name) = &self.name {
+            validate_name(&mut v, "name", name);
+        }
+        validate_jwt_timing(&mut v, self.token_lifetime, self.allowed_clock_skew);
+        validate_signing_key(&mut v, &self.custom_signing_key);
+        validate_metadata(&mut v, "template", &self.template);
+
+        v.finish()
+    }
+}
+
+impl Validate for RenderJwtTemplateRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+
+        match (&self.user_id, &self.user) {
+            (Some(_), Some(_)) => {
+                v.add("user", "conflict", "Send either user_id or user, not both");
+            }
+            (None, None) => {
+                v.add("user_id", "required", "user_id or user is required");
+            }
+            _ => {}
+        }
+        if let Some(user) = &self.user {
+            for (field, metadata) in [
+                ("user.public_metadata", &user.public_metadata),
+                ("user.private_metadata", &user.private_metadata),
+            ] {
+                if !metadata.is_null() && !metadata.is_object() {
+                    v.add(
+                        field,
+                        "invalid_type",
+                        format!("{} must be a JSON object", field),
+                    );
+                }
+            }
+        }
+
+        v.finish()
+    }
+}
+
+impl Validate for UpdateDeploymentFeatureFlagsRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+
+        if self.flags.is_empty() {
+            v.add("flags", "required", "flags cannot be empty");
+        }
+        for (name, value) in &self.flags {
+            let field = format!("flags.{}", name);
+            match feature_flag(name) {
+                None => {
+                    v.add(
+                        &field,
+                        "unknown_flag",
+                        format!("{} is not a feature flag", name),
+                    );
+                }
+                Some(flag) if !value.is_null() && !flag.kind.accepts(value) => {
+                    v.add(
+                        &field,
+                        "invalid_type",
+                        format!("{} takes {} values", name, flag.kind.as_str()),
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+
+        v.finish()
+    }
+}
+
+impl Validate for IngestUrlRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        // Relative paths are fine for settings but mean nothing to the crawler.
+        if self.url.starts_with('/') || !is_valid_url(&self.url) {
+            v.add("url", "invalid_url", "url must be an absolute http(s) URL");
+        }
+        v.finish()
+    }
+}
+
+fn validate_chunking(
+    v: &mut RequestValidator,
+    prefix: &str,
+    strategy: ChunkingStrategy,
+    chunk_size: i64,
+    chunk_overlap: i64,
+) {
+    let (min, max) = strategy.size_limits();
+    v.range(
+        &format!("{}chunk_size", prefix),
+        chunk_size,
+        min as i64,
+        max as i64,
+    );
+    v.range(
+        &format!("{}chunk_overlap", prefix),
+        chunk_overlap,
+        0,
+        chunk_size - 1,
+    );
+}
+
+impl Validate for RechunkDocumentRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_chunking(
+            &mut v,
+            "",
+            self.strategy,
+            self.chunk_size,
+            self.chunk_overlap,
+        );
+        v.finish()
+    }
+}
+
+impl Validate for DryRunChunkingRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if self.content.is_empty() || self.content.len() > MAX_DRY_RUN_CONTENT_BYTES {
+            v.add(
+                "content",
+                "invalid_length",
+                format!(
+                    "content must be between 1 and {} bytes",
+                    MAX_DRY_RUN_CONTENT_BYTES
+                ),
+            );
+        }
+        if let Some(chunking) = &self.chunking {
+            validate_chunking(
+                &mut v,
+                "chunking.",
+                chunking.strategy,
+                chunking.chunk_size as i64,
+                chunking.chunk_overlap as i64,
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateAgentRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        RequestValidator::new()
+            .length("name", &self.name, 1, 100)
+            .finish()
+    }
+}
+
+impl Validate for UpdateAgentRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(name) = &self.name {
+            validate_name(&mut v, "name", name);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for AppendAgentSessionMessageRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.one_of("role", &self.role, &AGENT_SESSION_MESSAGE_ROLES);
+        for (field, tokens) in [
+            ("prompt_tokens", self.prompt_tokens),
+            ("completion_tokens", self.completion_tokens),
+        ] {
+            if let Some(tokens) = tokens {
+                v.range(field, tokens.into(), 0, i32::MAX.into());
+            }
+        }
+        if let Some(spend_micros) = self.spend_micros {
+            v.range("spend_micros", spend_micros, 0, i64::MAX);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for UpdateAiBudgetRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        for (field, limit) in [
+            ("monthly_token_limit", self.monthly_token_limit),
+            (
+                "monthly_spend_limit_micros",
+                self.monthly_spend_limit_micros,
+            ),
+        ] {
+            if let Some(limit) = limit {
+                v.range(field, limit, 1, i64::MAX);
+            }
+        }
+        v.finish()
+    }
+}
+
+impl Validate for AttachAgentKnowledgeBaseRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(top_k) = self.top_k {
+            v.range("top_k", top_k.into(), 1, 100);
+        }
+        if let Some(score_threshold) = self.score_threshold
+            && !(0.0..=1.0).contains(&score_threshold)
+        {
+            v.add(
+                "score_threshold",
+                "out_of_range",
+                "score_threshold must be between 0 and 1",
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for RetrieveAgentContextRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.length("query", self.query.trim(), 1, 2000);
+        if let Some(limit) = self.limit {
+            v.range("limit", limit, 1, 100);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateToolRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        RequestValidator::new()
+            .length("name", &self.name, 1, 100)
+            .one_of("tool_type", &self.tool_type, AI_TOOL_TYPES)
+            .finish()
+    }
+}
+
+impl Validate for UpdateToolRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(name) = &self.name {
+            validate_name(&mut v, "name", name);
+        }
+        if let Some(tool_type) = &self.tool_type {
+            v.one_of("tool_type", tool_type, AI_TOOL_TYPES);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for ImportOpenApiToolsRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        match (&self.spec_url, &self.spec) {
+            (Some(spec_url), None) => {
+                if spec_url.starts_with('/') || !is_valid_url(spec_url) {
+                    v.add(
+                        "spec_url",
+                        "invalid_url",
+                        "spec_url must be an absolute http(s) URL",
+                    );
+                }
+            }
+            (None, Some(spec)) => {
+                if spec.len() > MAX_OPENAPI_DOCUMENT_BYTES {
+                    v.add(
+                        "spec",
+                        "too_large",
+                        format!("spec can be at most {} bytes", MAX_OPENAPI_DOCUMENT_BYTES),
+                    );
+                }
+            }
+            _ => {
+                v.add(
+                    "spec",
+                    "required",
+                    "Exactly one of spec_url and spec is required",
+                );
+            }
+        }
+        if let Some(base_url) = &self.base_url
+            && (base_url.starts_with('/') || !is_valid_url(base_url))
+        {
+            v.add(
+                "base_url",
+                "invalid_url",
+                "base_url must be an absolute http(s) URL",
+            );
+        }
+        if self
+            .operation_ids
+            .as_ref()
+            .is_some_and(|operation_ids| operation_ids.is_empty())
+        {
+            v.add(
+                "operation_ids",
+                "required",
+                "operation_ids must name at least one operation",
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateWorkflowTriggerRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.one_of(
+            "trigger_type",
+            &self.trigger_type,
+            &[
+                WorkflowTriggerType::Schedule.as_str(),
+                WorkflowTriggerType::Webhook.as_str(),
+            ],
+        );
+        if self.trigger_type == WorkflowTriggerType::Schedule.as_str() {
+            match &self.cron_expression {
+                Some(cron_expression) => {
+                    if let Err(message) = CronSchedule::parse(cron_expression) {
+                        v.add("cron_expression", "invalid_cron_expression", message);
+                    }
+                }
+                None => {
+                    v.add(
+                        "cron_expression",
+                        "required",
+                        "cron_expression is required for schedules",
+                    );
+                }
+            }
+        }
+        if let Some(timezone) = &self.timezone {
+            v.length("timezone", timezone, 1, 64);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateWorkflowRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        RequestValidator::new()
+            .length("name", &self.name, 1, 100)
+            .finish()
+    }
+}
+
+impl Validate for UpdateWorkflowRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(name) = &self.name {
+            validate_name(&mut v, "name", name);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateKnowledgeBaseRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.length("name", &self.name, 1, 100);
+        if let Some(embedding_provider) = &self.embedding_provider {
+            v.length("embedding_provider", embedding_provider, 1, 50);
+        }
+        if let Some(chunking) = &self.chunking {
+            validate_chunking(
+                &mut v,
+                "chunking.",
+                chunking.strategy,
+                chunking.chunk_size as i64,
+                chunking.chunk_overlap as i64,
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for UpdateKnowledgeBaseRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(name) = &self.name {
+            validate_name(&mut v, "name", name);
+        }
+        if let Some(embedding_provider) = &self.embedding_provider {
+            v.length("embedding_provider", embedding_provider, 1, 50);
+        }
+        if let Some(chunking) = &self.chunking {
+            validate_chunking(
+                &mut v,
+                "chunking.",
+                chunking.strategy,
+                chunking.chunk_size as i64,
+                chunking.chunk_overlap as i64,
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for UpdateEmbeddingProviderRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(provider) = &self.provider {
+            v.length("provider", provider, 1, 50);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateSignedUploadRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        let valid_segment = |segment: &str| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        };
+        v.length("key_prefix", &self.key_prefix, 1, 100);
+        if !self.key_prefix.split('/').all(valid_segment) {
+            v.add(
+                "key_prefix",
+                "invalid_format",
+                "key_prefix must be lowercase letters, digits, _ and -, separated by /",
+            );
+        }
+        v.length("file_name", &self.file_name, 1, 200);
+        if !self.storage_class.accepts_content_type(&self.content_type) {
+            v.add(
+                "content_type",
+                "unsupported_content_type",
+                format!(
+                    "{} files can't be uploaded to {} storage",
+                    self.content_type,
+                    self.storage_class.as_str()
+                ),
+            );
+        }
+        v.range(
+            "content_length",
+            self.content_length,
+            1,
+            self.storage_class.max_upload_size(),
+        );
+        let checksum_length = STANDARD
+            .decode(&self.checksum_sha256)
+            .map(|checksum| checksum.len())
+            .unwrap_or_default();
+        if checksum_length != 32 {
+            v.add(
+                "checksum_sha256",
+                "invalid_format",
+                "checksum_sha256 must be a base64 SHA-256 digest",
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for ImportDeploymentConfigRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if !self.bundle.is_object() {
+            v.add("bundle", "invalid_type", "bundle must be a JSON object");
+        }
+        v.finish()
+    }
+}
+
+impl Validate for DisposableDomainRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_domain(&mut v, "domain", &self.domain);
+        v.finish()
+    }
+}
+
+impl Validate for DeploymentAuthSettingsUpdates {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(min_length) = self.password.as_ref().and_then(|p| p.min_length) {
+            v.range("password.min_length", min_length as i64, 1, 128);
+        }
+        if let Some(username) = &self.username {
+            if let Some(min_length) = username.min_length {
+                v.range(
+                    "username.min_length",
+                    min_length as i64,
+                    1,
+                    MAX_USERNAME_LENGTH,
+                );
+            }
+            if let Some(max_length) = username.max_length {
+                v.range(
+                    "username.max_length",
+                    max_length as i64,
+                    1,
+                    MAX_USERNAME_LENGTH,
+                );
+            }
+            if let (Some(min_length), Some(max_length)) = (username.min_length, username.max_length)
+                && min_length > max_length
+            {
+                v.add(
+                    "username.max_length",
+                    "invalid_range",
+                    "max_length cannot be less than min_length",
+                );
+            }
+            for (i, name) in username.reserved_names.iter().flatten().enumerate() {
+                v.length(
+                    &format!("username.reserved_names[{}]", i),
+                    name,
+                    1,
+                    MAX_USERNAME_LENGTH as usize,
+                );
+            }
+        }
+        if let Some(policy) = &self.lockout_policy {
+            if let Some(max_failed_attempts) = policy.max_failed_attempts {
+                v.range(
+                    "lockout_policy.max_failed_attempts",
+                    max_failed_attempts,
+                    1,
+                    1000,
+                );
+            }
+            if let Some(lockout_duration) = policy.lockout_duration {
+                v.range(
+                    "lockout_policy.lockout_duration",
+                    lockout_duration,
+                    1,
+                    30 * 24 * 60 * 60,
+                );
+            }
+            if let Some(counter_window) = policy.counter_window {
+                v.range(
+                    "lockout_policy.counter_window",
+                    counter_window,
+                    1,
+                    30 * 24 * 60 * 60,
+                );
+            }
+        }
+        if let Some(retention_days) = self
+            .user_deletion_policy
+            .as_ref()
+            .and_then(|p| p.retention_days)
+        {
+            v.range(
+                "user_deletion_policy.retention_days",
+                retention_days,
+                1,
+                365,
+            );
+        }
+        if let Some(otp) = &self.otp {
+            if let Some(code_length) = otp.code_length {
+                v.range("otp.code_length", code_length as i64, 4, 10);
+            }
+            if let Some(expiry_minutes) = otp.expiry_minutes {
+                v.range("otp.expiry_minutes", expiry_minutes, 1, 24 * 60);
+            }
+            if let Some(max_attempts) = otp.max_attempts {
+                v.range("otp.max_attempts", max_attempts, 1, 100);
+            }
+            if let Some(resend_cooldown_seconds) = otp.resend_cooldown_seconds {
+                v.range(
+                    "otp.resend_cooldown_seconds",
+                    resend_cooldown_seconds,
+                    0,
+                    60 * 60,
+                );
+            }
+        }
+        if let Some(expiry_minutes) = self
+            .authentication_factors
+            .as_ref()
+            .and_then(|f| f.magic_link.as_ref())
+            .and_then(|m| m.expiry_minutes)
+        {
+            v.range(
+                "authentication_factors.magic_link.expiry_minutes",
+                expiry_minutes,
+                1,
+                24 * 60,
+            );
+        }
+        v.finish()
+    }
+}
+impl Validate for DeploymentB2bSettingsUpdates {}
+impl Validate for DeploymentRestrictionsUpdates {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(country_restrictions) = &self.country_restrictions {
+            for (i, code) in country_restrictions.country_codes.iter().enumerate() {
+                if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+                    v.add(
+                        &format!("country_restrictions.country_codes[{}]", i),
+                        "invalid_format",
+                        "country codes must be ISO 3166-1 alpha-2 codes like US",
+                    );
+                }
+            }
+        }
+        v.finish()
+    }
+}
+impl Validate for RestrictionCandidate {}
+impl Validate for DeploymentSocialConnectionUpsert {}
+impl Validate for EmailTemplate {}
+
+impl Validate for ResetEmailTemplateRequest {}
+
+fn validate_aws_region(v: &mut RequestValidator, field: &str, region: &str) {
+    let region_chars = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+    if region.is_empty() || !region.chars().all(region_chars) {
+        v.add(
+            field,
+            "invalid_format",
+            format!("{} must be an AWS region like us-east-1", field),
+        );
+    }
+}
+
+impl Validate for SetEmailProviderRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        match &self.credentials {
+            EmailProviderCredentials::Postmark(postmark) => {
+                v.length("server_token", postmark.server_token.trim(), 1, 200);
+            }
+            EmailProviderCredentials::Ses(ses) => {
+                v.length("access_key_id", ses.access_key_id.trim(), 16, 128);
+                v.length("secret_access_key", &ses.secret_access_key, 1, 200);
+                validate_aws_region(&mut v, "region", &ses.region);
+            }
+            EmailProviderCredentials::Smtp(smtp) => {
+                validate_domain(&mut v, "host", &smtp.host);
+                v.range("port", smtp.port.into(), 1, 65535);
+                v.length("username", &smtp.username, 1, 256);
+                v.length("password", &smtp.password, 1, 256);
+            }
+        }
+        if let Some(from_address) = &self.from_address {
+            v.email("from_address", from_address);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for SendTestEmailRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        RequestValidator::new()
+            .email("to_address", &self.to_address)
+            .finish()
+    }
+}
+
+impl Validate for UpdateDeploymentMaintenanceRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(message) = &self.message {
+            v.length("message", message.trim(), 1, 500);
+        }
+        if let (Some(starts_at), Some(ends_at)) = (self.starts_at, self.ends_at)
+            && ends_at <= starts_at
+        {
+            v.add(
+                "ends_at",
+                "invalid_range",
+                "ends_at must be after starts_at",
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for SetLogExportRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        let bucket_chars =
+            |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.';
+        if !(3..=63).contains(&self.bucket.len()) || !self.bucket.chars().all(bucket_chars) {
+            v.add(
+                "bucket",
+                "invalid_format",
+                "bucket must be an S3 bucket name: 3 to 63 lowercase letters, digits, dots or hyphens",
+            );
+        }
+        validate_aws_region(&mut v, "region", &self.region);
+        if let Some(prefix) = &self.prefix {
+            v.length("prefix", prefix, 0, 512);
+        }
+        match &self.credentials {
+            LogExportCredentials::AccessKey {
+                access_key_id,
+                secret_access_key,
+            } => {
+                v.length("credentials.access_key_id", access_key_id.trim(), 16, 128);
+                v.length("credentials.secret_access_key", secret_access_key, 1, 200);
+            }
+            LogExportCredentials::AssumeRole { role_arn } => {
+                if !role_arn.starts_with("arn:aws:iam::") || !role_arn.contains(":role/") {
+                    v.add(
+                        "credentials.role_arn",
+                        "invalid_format",
+                        "role_arn must be an IAM role ARN like arn:aws:iam::123456789012:role/name",
+                    );
+                }
+            }
+        }
+        v.finish()
+    }
+}
+
+impl Validate for RevokeTokenRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.length("jti", &self.jti, 1, 255);
+        v.finish()
+    }
+}
+
+impl Validate for RevokeDeploymentTokensRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if !self.confirm {
+            v.add(
+                "confirm",
+                "confirmation_required",
+                "Set confirm to true to revoke every session token",
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for ResetAllEmailTemplatesRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if !self.confirm {
+            v.add(
+                "confirm",
+                "confirmation_required",
+                "Set confirm to true to reset every email template",
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for AddOrganizationMemberRequest {}
+
+impl Validate for CreateOrganizationInvitationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.email("email_address", &self.email_address);
+        if let Some(expiry_days) = self.expiry_days {
+            v.range("expiry_days", expiry_days, 1, 90);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for ResendOrganizationInvitationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(expiry_days) = self.expiry_days {
+            v.range("expiry_days", expiry_days, 1, 90);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for AcceptOrganizationInvitationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if self.token.is_empty() {
+            v.add("token", "required", "token is required");
+        }
+        if let Some(first_name) = &self.first_name {
+            validate_name(&mut v, "first_name", first_name);
+        }
+        if let Some(last_name) = &self.last_name {
+            validate_name(&mut v, "last_name", last_name);
+        }
+        if let Some(phone) = &self.phone_number {
+            v.phone("phone_number", phone);
+        }
+        if let Some(username) = &self.username {
+            validate_username(&mut v, username);
+        }
+        if let Some(password) = &self.password {
+            v.length("password", password, 1, 128);
+        }
+        v.finish()
+    }
+}
+impl Validate for AddWorkspaceMemberRequest {}
+impl Validate for UpdateOrganizationMemberRequest {}
+impl Validate for UpdateWorkspaceMemberRequest {}
+
+impl Validate for CreateOrganizationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_b2b_profile(&mut v, Some(&self.name), &self.description, &self.image_url);
+        validate_stored_metadata(&mut v, "public_metadata", &self.public_metadata);
+        validate_stored_metadata(&mut v, "private_metadata", &self.private_metadata);
+        v.finish()
+    }
+}
+
+impl Validate for UpdateOrganizationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_b2b_profile(
+            &mut v,
+            self.name.as_deref(),
+            &self.description,
+            &self.image_url,
+        );
+        validate_stored_metadata(&mut v, "public_metadata", &self.public_metadata);
+        validate_stored_metadata(&mut v, "private_metadata", &self.private_metadata);
+        v.finish()
+    }
+}
+
+impl Validate for UpdateOrganizationMetadataRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if self.public_metadata.is_none() && self.private_metadata.is_none() {
+            v.add(
+                "public_metadata",
+                "required",
+                "public_metadata or private_metadata is required",
+            );
+        }
+        validate_stored_metadata(&mut v, "public_metadata", &self.public_metadata);
+        validate_stored_metadata(&mut v, "private_metadata", &self.private_metadata);
+        v.finish()
+    }
+}
+
+impl Validate for CreateWorkspaceRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_b2b_profile(&mut v, Some(&self.name), &self.description, &self.image_url);
+        validate_stored_metadata(&mut v, "public_metadata", &self.public_metadata);
+        validate_stored_metadata(&mut v, "private_metadata", &self.private_metadata);
+        v.finish()
+    }
+}
+
+impl Validate for UpdateWorkspaceRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_b2b_profile(
+            &mut v,
+            self.name.as_deref(),
+            &self.description,
+            &self.image_url,
+        );
+        validate_stored_metadata(&mut v, "public_metadata", &self.public_metadata);
+        validate_stored_metadata(&mut v, "private_metadata", &self.private_metadata);
+        v.finish()
+    }
+}
+
+impl Validate for CreateScimTokenRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_name(&mut v, "name", self.name.trim());
+        v.finish()
+    }
+}
+
+impl Validate for CreateSsoConnectionRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_name(&mut v, "name", self.name.trim());
+        validate_sso_connection(
+            &mut v,
+            &self.idp_metadata_url,
+            &self.idp_sso_url,
+            Some(&self.allowed_email_domains),
+        );
+        if let Some(mapping) = &self.attribute_mapping {
+            validate_sso_attribute_mapping(&mut v, mapping);
+        }
+        if self.idp_metadata_xml.is_none()
+            && (self.idp_entity_id.is_none() || self.idp_certificate.is_none())
+        {
+            v.add(
+                "idp_metadata_xml",
+                "required",
+                "idp_metadata_xml is required unless idp_entity_id and idp_certificate are given",
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for UpdateSsoConnectionRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(name) = &self.name {
+            validate_name(&mut v, "name", name.trim());
+        }
+        validate_sso_connection(
+            &mut v,
+            &self.idp_metadata_url,
+            &self.idp_sso_url,
+            self.allowed_email_domains.as_deref(),
+        );
+        if let Some(mapping) = &self.attribute_mapping {
+            validate_sso_attribute_mapping(&mut v, mapping);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateDeploymentApiKeyRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_name(&mut v, "name", self.name.trim());
+        validate_api_key_scopes(&mut v, &self.scopes);
+        v.finish()
+    }
+}
+
+impl Validate for CreateOrganizationRoleRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_name(&mut v, "name", self.name.trim());
+        validate_permissions(&mut v, &self.permissions, ORGANIZATION_PERMISSIONS);
+        v.finish()
+    }
+}
+
+impl Validate for UpdateOrganizationRoleRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(name) = &self.name {
+            validate_name(&mut v, "name", name.trim());
+        }
+        if let Some(permissions) = &self.permissions {
+            validate_permissions(&mut v, permissions, ORGANIZATION_PERMISSIONS);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for SimulatePermissionChangeRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_permissions(&mut v, &self.permissions, ORGANIZATION_PERMISSIONS);
+        v.finish()
+    }
+}
+
+impl Validate for CreateWorkspaceRoleRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        validate_name(&mut v, "name", self.name.trim());
+        validate_permissions(&mut v, &self.permissions, WORKSPACE_PERMISSIONS);
+        v.finish()
+    }
+}
+
+impl Validate for UpdateWorkspaceRoleRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(name) = &self.name {
+            validate_name(&mut v, "name", name.trim());
+        }
+        if let Some(permissions) = &self.permissions {
+            validate_permissions(&mut v, permissions, WORKSPACE_PERMISSIONS);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateAgentSessionRequest {}
+impl Validate for ExecuteToolRequest {}
+impl Validate for PublishWorkflowRequest {}
+impl Validate for ExecuteWorkflowRequest {}
+
+impl Validate for SearchKnowledgeBaseRequest {}
+impl Validate for UploadUrlRequest {}
+
+impl Validate for Pagination {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if self.limit.is_some_and(|limit| limit < 1) {
+            v.add("limit", "out_of_range", "limit must be at least 1");
+        }
+        if self.offset.is_some_and(|offset| offset < 0) {
+            v.add("offset", "out_of_range", "offset cannot be negative");
+        }
+        if let Some(cursor) = &self.cursor {
+            if self.offset.is_some() {
+                v.add(
+                    "cursor",
+                    "conflict",
+                    "Use either cursor or offset, not both",
+                );
+            } else if decode_cursor(cursor).is_none() {
+                v.add(
+                    "cursor",
+                    "invalid",
+                    "cursor must be a next_cursor returned by this endpoint",
+                );
+            }
+        }
+        v.finish()
+    }
+}
+
+impl<C: SortColumns> Validate for SortParams<C> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let Some(key) = &self.sort_key
+            && !C::COLUMNS.contains(&key.as_str())
+        {
+            v.add(
+                "sort_key",
+                "invalid_choice",
+                format!("sort_key must be one of {}", C::COLUMNS.join(", ")),
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for DateRangeParams {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        if let (Some(from), Some(to)) = (self.from, self.to)
+            && from > to
+        {
+            v.add("to", "invalid_range", "to cannot be before from");
+        }
+        v.finish()
+    }
+}
+
+impl Validate for GlobalSearchQueryParams {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.length("q", &self.q, MIN_GLOBAL_SEARCH_TERM_LENGTH, 200);
+        v.finish()
+    }
+}
+
+impl Validate for AddProjectCollaboratorRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.email("email", self.email.trim());
+        v.finish()
+    }
+}
+
+impl Validate for UpdateDeploymentMailSettingsRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut v = RequestValidator::new();
+        v.length(
+            "mail_from_subdomain",
+            self.mail_from_subdomain.trim(),
+            1,
+            63,
+        );
+        v.finish()
+    }
+}
+
+impl Validate for CreateProductionDeploymentRequest {}
+impl Validate for ChangeDeploymentDomainRequest {}
+impl Validate for TransferProjectRequest {}
+impl Validate for UpdateProjectCollaboratorRoleRequest {}
+impl Validate for CloneDeploymentRequest {}
+impl Validate for PromoteDeploymentRequest {}
@@ -0,0 +1,331 @@
+//! Checks an email template before it is saved, so a broken one is rejected
+//! at the console instead of failing when the email is sent.
+//!
+//! Each template type has its own set of placeholders, the variables the
+//! sending side fills in for it. Anything else would render as an empty
+//! string, so it is an error.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Serialize;
+
+use super::FieldViolation;
+use crate::{dto::params::deployment::DeploymentNameParams, models::EmailTemplate};
+
+/// Filled in for every template.
+const COMMON_PLACEHOLDERS: &[&str] = &["app_name", "app_logo", "first_name", "last_name"];
+
+/// Helpers that take placeholders as arguments, like `{{image app_logo}}`.
+const INLINE_HELPERS: &[&str] = &["image", "escapeURIs"];
+
+/// Block helpers, like `{{#if inviter_name}}...{{/if}}`.
+const BLOCK_HELPERS: &[&str] = &["if", "unless"];
+
+/// The placeholders one template type can use, and the ones its email is
+/// useless without.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TemplatePlaceholders {
+    pub allowed: &'static [&'static str],
+    pub required: &'static [&'static str],
+}
+
+impl TemplatePlaceholders {
+    pub fn allows(&self, placeholder: &str) -> bool {
+        COMMON_PLACEHOLDERS.contains(&placeholder) || self.allowed.contains(&placeholder)
+    }
+}
+
+/// The placeholder registry. Anything that renders or previews a template
+/// should take its variable names from here.
+pub fn template_placeholders(template: DeploymentNameParams) -> TemplatePlaceholders {
+    let (allowed, required): (&'static [&'static str], &'static [&'static str]) = match template {
+        DeploymentNameParams::OrganizationInviteTemplate => (
+            &[
+                "action_url",
+                "invitation.expires_in_days",
+                "inviter_name",
+                "organization_name",
+            ],
+            &["action_url"],
+        ),
+        DeploymentNameParams::VerificationCodeTemplate => {
+            (&["code", "code.expires_in_minutes"], &["code"])
+        }
+        DeploymentNameParams::ResetPasswordCodeTemplate => (
+            &["action_url", "code", "code.expires_in_minutes"],
+            &["action_url"],
+        ),
+        DeploymentNameParams::PrimaryEmailChangeTemplate
+        | DeploymentNameParams::PasswordChangeTemplate
+        | DeploymentNameParams::PasswordRemoveTemplate
+        | DeploymentNameParams::WaitlistSignupTemplate => (&[], &[]),
+        DeploymentNameParams::SignInFromNewDeviceTemplate => (&["device_info"], &[]),
+        DeploymentNameParams::MagicLinkTemplate => {
+            (&["action_url", "link.expires_in_minutes"], &["action_url"])
+        }
+        DeploymentNameParams::WaitlistInviteTemplate => (
+            &["action_url", "invitation.expires_in_days"],
+            &["action_url"],
+        ),
+        DeploymentNameParams::WorkspaceInviteTemplate => (
+            &[
+                "action_url",
+                "invitation.expires_in_days",
+                "inviter_name",
+                "workspace_name",
+            ],
+            &["action_url"],
+        ),
+    };
+
+    TemplatePlaceholders { allowed, required }
+}
+
+/// Problems found in a template. Errors always block saving; warnings only
+/// when the caller hasn't accepted them.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmailTemplateValidation {
+    pub errors: Vec<FieldViolation>,
+    pub warnings: Vec<FieldViolation>,
+}
+
+impl EmailTemplateValidation {
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+
+    fn error(&mut self, field: &str, code: &str, message: impl Into<String>) {
+        self.errors.push(FieldViolation::new(field, code, message));
+    }
+
+    fn warning(&mut self, field: &str, code: &str, message: impl Into<String>) {
+        self.warnings
+            .push(FieldViolation::new(field, code, message));
+    }
+}
+
+static MUSTACHE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{(\{?)~?\s*(.*?)\s*~?\}?\}\}").unwrap());
+static DISALLOWED_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<\s*(script|iframe|object|embed)\b").unwrap());
+static EVENT_HANDLER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)<[^>]*\son[a-z]+\s*="#).unwrap());
+static JAVASCRIPT_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)(href|src|action)\s*=\s*["']?\s*javascript:"#).unwrap());
+static FORM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<\s*form\b[^>]*>").unwrap());
+static EXTERNAL_ACTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\saction\s*=\s*["']?\s*(https?:)?//"#).unwrap());
+static INSECURE_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)(href|src)\s*=\s*["']?\s*http://"#).unwrap());
+static STYLE_BLOCK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<\s*style\b").unwrap());
+
+/// Validates the subject and body of a template of the given type.
+pub fn validate_email_template(
+    template_type: DeploymentNameParams,
+    template: &EmailTemplate,
+) -> EmailTemplateValidation {
+    let placeholders = template_placeholders(template_type);
+    let mut validation = EmailTemplateValidation::default();
+
+    let subject_used = check_placeholders(
+        &mut validation,
+        "template_subject",
+        &template.template_subject,
+        &placeholders,
+    );
+    let body_used = check_placeholders(
+        &mut validation,
+        "template_data",
+        &template.template_data,
+        &placeholders,
+    );
+
+    for required in placeholders.required {
+        if !subject_used.contains(required) && !body_used.contains(required) {
+            validation.error(
+                "template_data",
+                "missing_placeholder",
+                format!("This template has to use {{{{{}}}}}", required),
+            );
+        }
+    }
+
+    check_html(&mut validation, &template.template_data);
+
+    validation
+}
+
+/// Compiles the template and checks every placeholder and helper in it.
+/// Returns the placeholders it uses.
+fn check_placeholders<'a>(
+    validation: &mut EmailTemplateValidation,
+    field: &str,
+    source: &'a str,
+    placeholders: &TemplatePlaceholders,
+) -> Vec<&'a str> {
+    if let Err(e) = handlebars::Template::compile(source) {
+        validation.error(field, "invalid_syntax", e.to_string());
+        return Vec::new();
+    }
+
+    let mut used = Vec::new();
+
+    for captures in MUSTACHE.captures_iter(source) {
+        let raw = !captures[1].is_empty();
+        let expression = captures.get(2).map_or("", |m| m.as_str());
+
+        if expression.starts_with('!') || expression.starts_with('/') || expression == "else" {
+            continue;
+        }
+
+        let (block, expression) = match expression.strip_prefix(['#', '^']) {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, expression),
+        };
+
+        let mut words = expression.split_whitespace();
+        let Some(first) = words.next() else {
+            validation.error(field, "invalid_syntax", "Empty placeholder");
+            continue;
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        let names = if block {
+            if !BLOCK_HELPERS.contains(&first) {
+                validation.error(
+                    field,
+                    "unknown_helper",
+                    format!("{{{{#{}}}}} is not a supported block", first),
+                );
+                continue;
+            }
+            arguments
+        } else if arguments.is_empty() {
+            vec![first]
+        } else {
+            if !INLINE_HELPERS.contains(&first) {
+                validation.error(
+                    field,
+                    "unknown_helper",
+                    format!("{} is not a supported helper", first),
+                );
+                continue;
+            }
+            arguments
+        };
+
+        for name in names {
+            // String and number literals passed to helpers aren't variables.
+            if name.starts_with(['"', '\'']) || name.parse::<f64>().is_ok() {
+                continue;
+            }
+            if placeholders.allows(name) {
+                used.push(name);
+            } else {
+                validation.error(
+                    field,
+                    "unknown_placeholder",
+                    format!("{{{{{}}}}} is not available in this template", name),
+                );
+            }
+        }
+
+        if raw {
+            validation.warning(
+                field,
+                "unescaped_placeholder",
+                format!(
+                    "{{{{{{{}}}}}}} is inserted without HTML escaping",
+                    expression
+                ),
+            );
+        }
+    }
+
+    used
+}
+
+fn check_html(validation: &mut EmailTemplateValidation, html: &str) {
+    const FIELD: &str = "template_data";
+
+    if let Some(tag) = DISALLOWED_TAG.captures(html) {
+        validation.error(
+            FIELD,
+            "disallowed_tag",
+            format!(
+                "<{}> is not allowed in email templates",
+                tag[1].to_lowercase()
+            ),
+        );
+    }
+    if EVENT_HANDLER.is_match(html) {
+        validation.error(
+            FIELD,
+            "event_handler",
+            "Inline event handlers such as onclick are not allowed",
+        );
+    }
+    if JAVASCRIPT_URL.is_match(html) {
+        validation.error(FIELD, "javascript_url", "javascript: URLs are not allowed");
+    }
+
+    for form in FORM.find_iter(html) {
+        if EXTERNAL_ACTION.is_match(form.as_str()) {
+            validation.error(FIELD, "external_form", "Forms can't post to another site");
+        } else {
+            validation.warning(FIELD, "form", "Most email clients don't support forms");
+        }
+    }
+
+    if INSECURE_URL.is_match(html) {
+        validation.warning(FIELD, "insecure_url", "Links and images should use https");
+    }
+    if STYLE_BLOCK.is_match(html) {
+        validation.warning(
+            FIELD,
+            "style_block",
+            "Many email clients drop <style> blocks; prefer inline styles",
+        );
+    }
+}
+
+/// The placeholders in a template source that `context` has no value for.
+/// Conditions of `{{#if}}` and `{{#unless}}` blocks are skipped, since
+/// leaving those out is how optional parts of an email are turned off.
+pub fn unfilled_placeholders(source: &str, context: &serde_json::Value) -> Vec<String> {
+    let mut unfilled: Vec<String> = Vec::new();
+
+    for captures in MUSTACHE.captures_iter(source) {
+        let expression = captures.get(2).map_or("", |m| m.as_str());
+        if expression.starts_with(['!', '/', '#', '^']) || expression == "else" {
+            continue;
+        }
+
+        let mut words = expression.split_whitespace();
+        let Some(first) = words.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = words.collect();
+        let names = if arguments.is_empty() {
+            vec![first]
+        } else {
+            arguments
+        };
+
+        for name in names {
+            if name.starts_with(['"', '\'', '@']) || name == "this" || name.parse::<f64>().is_ok() {
+                continue;
+            }
+            let filled = name
+                .split('.')
+                .try_fold(context, |value, segment| value.get(segment))
+                .is_some();
+            if !filled && !unfilled.iter().any(|n| n == name) {
+                unfilled.push(name.to_string());
+            }
+        }
+    }
+
+    unfilled
+}
@@ -0,0 +1,147 @@
+//! Checks a JWT template before it is saved, so a broken one is rejected at
+//! the console instead of when a token is minted from it.
+//!
+//! Claims are checked against the grammar in [`check_claim_expression`].
+//! Violations name the claim, as `template.<path>`, so the console can point
+//! at it.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::FieldViolation;
+use crate::services::{ClaimPart, check_claim_expression, claim_path, parse_claim};
+
+/// Claims set for every token, which a template can't override.
+pub const RESERVED_JWT_CLAIMS: &[&str] = &["iss", "sub", "exp", "iat", "nbf", "jti"];
+
+/// Templates end up in headers and cookies, so their claims are kept small.
+pub const MAX_JWT_TEMPLATE_SIZE: usize = 4096;
+
+pub const MIN_TOKEN_LIFETIME: i64 = 30;
+pub const MAX_TOKEN_LIFETIME: i64 = 86_400;
+pub const MAX_ALLOWED_CLOCK_SKEW: i64 = 300;
+
+/// Problems found in a template. Any of them blocks saving.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JwtTemplateValidation {
+    pub errors: Vec<FieldViolation>,
+}
+
+impl JwtTemplateValidation {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, field: &str, code: &str, message: impl Into<String>) {
+        self.errors.push(FieldViolation::new(field, code, message));
+    }
+}
+
+/// Validates the parts of a template that are given, so updates check only
+/// what they change.
+pub fn validate_jwt_template(
+    template: Option<&Value>,
+    token_lifetime: Option<i64>,
+    allowed_clock_skew: Option<i64>,
+) -> JwtTemplateValidation {
+    let mut validation = JwtTemplateValidation::default();
+
+    if let Some(token_lifetime) = token_lifetime
+        && !(MIN_TOKEN_LIFETIME..=MAX_TOKEN_LIFETIME).contains(&token_lifetime)
+    {
+        validation.error(
+            "token_lifetime",
+            "out_of_range",
+            format!(
+                "token_lifetime must be between {} and {} seconds",
+                MIN_TOKEN_LIFETIME, MAX_TOKEN_LIFETIME
+            ),
+        );
+    }
+    if let Some(allowed_clock_skew) = allowed_clock_skew
+        && !(0..=MAX_ALLOWED_CLOCK_SKEW).contains(&allowed_clock_skew)
+    {
+        validation.error(
+            "allowed_clock_skew",
+            "out_of_range",
+            format!(
+                "allowed_clock_skew must be between 0 and {} seconds",
+                MAX_ALLOWED_CLOCK_SKEW
+            ),
+        );
+    }
+
+    if let Some(template) = template {
+        check_claims(&mut validation, template);
+    }
+
+    validation
+}
+
+fn check_claims(validation: &mut JwtTemplateValidation, template: &Value) {
+    let Some(claims) = template.as_object() else {
+        validation.error("template", "invalid_type", "template must be a JSON object");
+        return;
+    };
+
+    let size = template.to_string().len();
+    if size > MAX_JWT_TEMPLATE_SIZE {
+        validation.error(
+            "template",
+            "too_large",
+            format!(
+                "template is {} bytes, more than the {} allowed",
+                size, MAX_JWT_TEMPLATE_SIZE
+            ),
+        );
+    }
+
+    for (name, claim) in claims {
+        if RESERVED_JWT_CLAIMS.contains(&name.as_str()) {
+            validation.error(
+                &format!("template.{}", name),
+                "reserved_claim",
+                format!("{} is set for every token and can't be overridden", name),
+            );
+            continue;
+        }
+        check_value(validation, claim, name);
+    }
+}
+
+fn check_value(validation: &mut JwtTemplateValidation, value: &Value, path: &str) {
+    match value {
+        Value::String(text) => check_string(validation, text, path),
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                check_value(validation, item, &format!("{}[{}]", path, i));
+            }
+        }
+        Value::Object(claims) => {
+            for (name, claim) in claims {
+                check_value(validation, claim, &claim_path(path, name));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_string(validation: &mut JwtTemplateValidation, text: &str, path: &str) {
+    let field = format!("template.{}", path);
+
+    let parts = match parse_claim(text) {
+        Ok(parts) => parts,
+        Err(message) => {
+            validation.error(&field, "invalid_syntax", message);
+            return;
+        }
+    };
+
+    for part in parts {
+        if let ClaimPart::Expression(expression) = part
+            && let Err(message) = check_claim_expression(expression)
+        {
+            validation.error(&field, "unknown_expression", message);
+        }
+    }
+}
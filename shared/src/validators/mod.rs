@@ -1,3 +1,12 @@
+pub mod dto;
+pub mod email_template;
+pub mod jwt_template;
 pub mod project;
+pub mod redirect_url;
+pub mod request;
 
+pub use email_template::*;
+pub use jwt_template::*;
 pub use project::*;
+pub use redirect_url::*;
+pub use request::*;
@@ -0,0 +1,147 @@
+//! Checks the UI settings that send a browser somewhere, so console access
+//! can't be used to point sign-in or sign-up flows at a phishing page.
+//!
+//! A redirect may be a path on the deployment's frontend, a URL on its
+//! `frontend_host`, or a URL on one of its `allowed_redirect_origins`.
+//! `javascript:` and `data:` URLs are rejected whatever the allowlist says.
+
+use serde::Serialize;
+
+use super::FieldViolation;
+
+/// UI settings the frontend navigates to after an action or link click.
+pub const REDIRECT_URL_FIELDS: &[&str] = &[
+    "sign_in_page_url",
+    "sign_up_page_url",
+    "after_sign_out_one_page_url",
+    "after_sign_out_all_page_url",
+    "after_logo_click_url",
+    "organization_profile_url",
+    "create_organization_url",
+    "user_profile_url",
+    "after_signup_redirect_url",
+    "after_signin_redirect_url",
+    "after_create_organization_redirect_url",
+];
+
+pub const MAX_ALLOWED_REDIRECT_ORIGINS: usize = 50;
+
+const FORBIDDEN_SCHEMES: &[&str] = &["javascript", "data"];
+
+/// Problems found in a deployment's redirect settings.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RedirectUrlValidation {
+    pub errors: Vec<FieldViolation>,
+}
+
+impl RedirectUrlValidation {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// A message naming the fields that failed, for the top of the error.
+    pub fn summary(&self) -> String {
+        let fields = self
+            .errors
+            .iter()
+            .map(|error| error.field.as_str())
+            .collect::<Vec<_>>();
+        format!("Redirect URL not allowed for {}", fields.join(", "))
+    }
+}
+
+/// Where a deployment's redirect settings may point.
+#[derive(Debug, Clone)]
+pub struct RedirectUrlPolicy<'a> {
+    frontend_host: &'a str,
+    allowed_origins: &'a [String],
+}
+
+impl<'a> RedirectUrlPolicy<'a> {
+    pub fn new(frontend_host: &'a str, allowed_origins: &'a [String]) -> Self {
+        Self {
+            frontend_host,
+            allowed_origins,
+        }
+    }
+
+    /// Checks the given `(field, url)` pairs. Empty values clear a setting
+    /// and are always allowed.
+    pub fn validate<'v>(
+        &self,
+        urls: impl IntoIterator<Item = (&'v str, &'v str)>,
+    ) -> RedirectUrlValidation {
+        let mut validation = RedirectUrlValidation::default();
+        for (field, value) in urls {
+            if let Some(error) = self.check(field, value) {
+                validation.errors.push(error);
+            }
+        }
+        validation
+    }
+
+    fn check(&self, field: &str, value: &str) -> Option<FieldViolation> {
+        let value = value.trim();
+        if value.is_empty() || (value.starts_with('/') && !value.starts_with("//")) {
+            return None;
+        }
+
+        if has_forbidden_scheme(value) {
+            return Some(FieldViolation::new(
+                field,
+                "forbidden_scheme",
+                format!("{} cannot be a javascript: or data: URL", field),
+            ));
+        }
+
+        let allowed = url::Url::parse(value).is_ok_and(|url| {
+            matches!(url.scheme(), "http" | "https")
+                && (url
+                    .host_str()
+                    .is_some_and(|host| host.eq_ignore_ascii_case(self.frontend_host))
+                    || self
+                        .allowed_origins
+                        .iter()
+                        .any(|origin| *origin == url.origin().ascii_serialization()))
+        });
+        (!allowed).then(|| {
+            FieldViolation::new(
+                field,
+                "redirect_not_allowed",
+                format!(
+                    "{} must point at {} or one of the deployment's allowed redirect origins",
+                    field, self.frontend_host
+                ),
+            )
+        })
+    }
+}
+
+/// Browsers ignore leading control characters and whitespace and treat the
+/// scheme case-insensitively, so `" JavaScript:"` has to be caught too.
+fn has_forbidden_scheme(value: &str) -> bool {
+    let value = value.trim_start_matches(|c: char| c.is_ascii_control() || c.is_whitespace());
+    let Some((scheme, _)) = value.split_once(':') else {
+        return false;
+    };
+    let scheme = scheme
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect::<String>()
+        .to_ascii_lowercase();
+    FORBIDDEN_SCHEMES.contains(&scheme.as_str())
+}
+
+/// Turns an origin such as `https://App.example.com/` into the form redirect
+/// URLs are compared against, or `None` when it isn't a bare http(s) origin.
+pub fn normalize_redirect_origin(value: &str) -> Option<String> {
+    let url = url::Url::parse(value.trim()).ok()?;
+    let bare = matches!(url.scheme(), "http" | "https")
+        && url.host().is_some()
+        && url.username().is_empty()
+        && url.password().is_none()
+        && url.path() == "/"
+        && url.query().is_none()
+        && url.fragment().is_none();
+    bare.then(|| url.origin().ascii_serialization())
+}
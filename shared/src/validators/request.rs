@@ -0,0 +1,205 @@
+use serde::Serialize;
+
+use crate::utils::wallet_address::normalize_wallet_address;
+
+/// A single rule a request body broke.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FieldViolation {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldViolation {
+    pub fn new(
+        field: impl Into<String>,
+        code: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldViolation>,
+}
+
+/// Implemented by request bodies that have rules beyond what deserialization
+/// already enforces. Bodies with no extra rules use the default.
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
+}
+
+/// Collects every violation in a body rather than stopping at the first one.
+#[derive(Debug, Default)]
+pub struct RequestValidator {
+    errors: Vec<FieldViolation>,
+}
+
+impl RequestValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: &str, code: &str, message: impl Into<String>) -> &mut Self {
+        self.errors.push(FieldViolation::new(field, code, message));
+        self
+    }
+
+    pub fn length(&mut self, field: &str, value: &str, min: usize, max: usize) -> &mut Self {
+        let length = value.trim().chars().count();
+        if length < min {
+            if min == 1 {
+                self.add(field, "required", format!("{} cannot be empty", field));
+            } else {
+                self.add(
+                    field,
+                    "too_short",
+                    format!("{} must be at least {} characters", field, min),
+                );
+            }
+        } else if value.chars().count() > max {
+            self.add(
+                field,
+                "too_long",
+                format!("{} cannot exceed {} characters", field, max),
+            );
+        }
+        self
+    }
+
+    pub fn range(&mut self, field: &str, value: i64, min: i64, max: i64) -> &mut Self {
+        if value < min || value > max {
+            self.add(
+                field,
+                "out_of_range",
+                format!("{} must be between {} and {}", field, min, max),
+            );
+        }
+        self
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) -> &mut Self {
+        if !is_valid_email(value) {
+            self.add(
+                field,
+                "invalid_email",
+                format!("{} is not a valid email address", field),
+            );
+        }
+        self
+    }
+
+    pub fn phone(&mut self, field: &str, value: &str) -> &mut Self {
+        if !is_valid_phone(value) {
+            self.add(
+                field,
+                "invalid_phone",
+                format!("{} must be a phone number in international format", field),
+            );
+        }
+        self
+    }
+
+    /// Mixed-case Ethereum addresses have to match their EIP-55 checksum.
+    pub fn wallet_address(&mut self, field: &str, value: &str) -> &mut Self {
+        if normalize_wallet_address(value).is_none() {
+            self.add(
+                field,
+                "invalid_wallet_address",
+                format!("{} is not a valid Ethereum address", field),
+            );
+        }
+        self
+    }
+
+    /// Accepts absolute http(s) URLs and paths relative to the deployment's
+    /// frontend, such as `/sign-in`.
+    pub fn url(&mut self, field: &str, value: &str) -> &mut Self {
+        if !is_valid_url(value) {
+            self.add(
+                field,
+                "invalid_url",
+                format!("{} must be an http(s) URL or a path", field),
+            );
+        }
+        self
+    }
+
+    pub fn hex_color(&mut self, field: &str, value: &str) -> &mut Self {
+        let valid = value.strip_prefix('#').is_some_and(|hex| {
+            matches!(hex.len(), 3 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        });
+        if !valid {
+            self.add(
+                field,
+                "invalid_color",
+                format!("{} must be a hex color like #6366F1", field),
+            );
+        }
+        self
+    }
+
+    pub fn one_of(&mut self, field: &str, value: &str, allowed: &[&str]) -> &mut Self {
+        if !allowed.contains(&value) {
+            self.add(
+                field,
+                "invalid_choice",
+                format!("{} must be one of: {}", field, allowed.join(", ")),
+            );
+        }
+        self
+    }
+
+    pub fn finish(&mut self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors {
+                errors: std::mem::take(&mut self.errors),
+            })
+        }
+    }
+}
+
+pub fn is_valid_email(value: &str) -> bool {
+    let Some((local, domain)) = value.rsplit_once('@') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && local.len() <= 64
+        && value.len() <= 254
+        && !value.chars().any(char::is_whitespace)
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+pub fn is_valid_phone(value: &str) -> bool {
+    let digits = value.strip_prefix('+').unwrap_or(value);
+    let digit_count = digits.chars().filter(char::is_ascii_digit).count();
+
+    (7..=15).contains(&digit_count)
+        && digits
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '(' | ')'))
+}
+
+pub fn is_valid_url(value: &str) -> bool {
+    if value.starts_with('/') && !value.starts_with("//") {
+        return true;
+    }
+
+    url::Url::parse(value)
+        .map(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+        .unwrap_or(false)
+}